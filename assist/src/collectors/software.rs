@@ -1,5 +1,6 @@
 //! Software information collectors.
 
+use buckos_core::MachineId;
 use serde::{Deserialize, Serialize};
 use sysinfo::{System, Users};
 
@@ -35,6 +36,10 @@ pub struct OsInfo {
     pub arch: String,
     /// Hostname (may be redacted).
     pub hostname: String,
+    /// Machine ID, or an ID specific to these reports when hostnames are
+    /// redacted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub machine_id: Option<String>,
     /// System uptime in seconds.
     pub uptime: u64,
     /// Boot time as Unix timestamp.
@@ -127,6 +132,7 @@ impl SoftwareInfo {
             arch: std::env::consts::ARCH.to_string(),
             hostname: redactor
                 .redact(&System::host_name().unwrap_or_else(|| "Unknown".to_string())),
+            machine_id: MachineId::read().map(|id| redactor.machine_id(&id)),
             uptime: System::uptime(),
             boot_time: System::boot_time(),
         }
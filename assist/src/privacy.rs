@@ -1,8 +1,15 @@
 //! Privacy controls and data redaction for system diagnostics.
 
+use buckos_core::MachineId;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 
+/// Application ID the machine ID is derived from in redacted reports
+/// (65e6b02ecc17087efee1ce3e478aa4a4).
+const REPORT_APP_ID: MachineId = MachineId::from_bytes([
+    0x65, 0xe6, 0xb0, 0x2e, 0xcc, 0x17, 0x08, 0x7e, 0xfe, 0xe1, 0xce, 0x3e, 0x47, 0x8a, 0xa4, 0xa4,
+]);
+
 /// Privacy settings that control what information is collected and how it's handled.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PrivacySettings {
@@ -100,13 +107,14 @@ impl Redactor {
             .map(|h| h.to_string_lossy().to_string())
             .unwrap_or_else(|_| String::from("localhost"));
 
-        // Compile regex patterns
+        // Compile regex patterns; regex is built without Unicode support,
+        // so word boundaries must be ASCII ones
         let ip_regex = Regex::new(
-            r"\b(?:(?:25[0-5]|2[0-4][0-9]|[01]?[0-9][0-9]?)\.){3}(?:25[0-5]|2[0-4][0-9]|[01]?[0-9][0-9]?)\b"
+            r"(?-u:\b)(?:(?:25[0-5]|2[0-4][0-9]|[01]?[0-9][0-9]?)\.){3}(?:25[0-5]|2[0-4][0-9]|[01]?[0-9][0-9]?)(?-u:\b)"
         ).expect("Invalid IP regex");
 
-        let mac_regex =
-            Regex::new(r"\b(?:[0-9A-Fa-f]{2}[:-]){5}[0-9A-Fa-f]{2}\b").expect("Invalid MAC regex");
+        let mac_regex = Regex::new(r"(?-u:\b)(?:[0-9A-Fa-f]{2}[:-]){5}[0-9A-Fa-f]{2}(?-u:\b)")
+            .expect("Invalid MAC regex");
 
         Self {
             settings,
@@ -164,6 +172,17 @@ impl Redactor {
         result
     }
 
+    /// The machine ID to report. With hostnames redacted this is an ID
+    /// derived for these reports, so reports from one machine can still be
+    /// told apart from another's without revealing its machine ID.
+    pub fn machine_id(&self, id: &MachineId) -> String {
+        if self.settings.redact_hostnames {
+            id.app_specific(&REPORT_APP_ID).to_string()
+        } else {
+            id.to_string()
+        }
+    }
+
     /// Check if a category should be collected based on privacy settings.
     pub fn should_collect(&self, category: &str) -> bool {
         match category {
@@ -204,4 +223,16 @@ mod tests {
         assert!(!result.contains("00:1A:2B:3C:4D:5E"));
         assert!(result.contains("[REDACTED_MAC]"));
     }
+
+    #[test]
+    fn test_machine_id_redaction() {
+        let id = MachineId::parse("0123456789abcdef0123456789abcdef").unwrap();
+        let full = Redactor::new(PrivacySettings::full());
+        assert_eq!(full.machine_id(&id), "0123456789abcdef0123456789abcdef");
+
+        let minimal = Redactor::new(PrivacySettings::minimal());
+        let redacted = minimal.machine_id(&id);
+        assert_ne!(redacted, id.to_string());
+        assert_eq!(redacted, minimal.machine_id(&id));
+    }
 }
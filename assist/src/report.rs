@@ -214,6 +214,9 @@ impl DiagnosticReport {
             output.push_str(&format!("  Kernel: {}\n", sw.os.kernel_version));
             output.push_str(&format!("  Architecture: {}\n", sw.os.arch));
             output.push_str(&format!("  Hostname: {}\n", sw.os.hostname));
+            if let Some(machine_id) = &sw.os.machine_id {
+                output.push_str(&format!("  Machine ID: {}\n", machine_id));
+            }
             output.push_str(&format!("  Uptime: {}\n", format_uptime(sw.os.uptime)));
             output.push('\n');
 
@@ -0,0 +1,210 @@
+//! Per-service resource accounting.
+//!
+//! Usage of a running process is sampled from its cgroup when the service
+//! runs in a cgroup of its own, and from /proc otherwise. When a process
+//! exits, the usage reported by `wait4` is folded into the service's
+//! totals, so CPU time and IO keep adding up across restarts.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Root of the unified cgroup hierarchy.
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Resources used by a service.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    /// User plus system CPU time in microseconds
+    pub cpu_usec: u64,
+    /// Highest resident memory seen
+    pub peak_memory_bytes: u64,
+    /// Bytes read from storage
+    pub io_read_bytes: u64,
+    /// Bytes written to storage
+    pub io_write_bytes: u64,
+}
+
+impl ResourceUsage {
+    /// Usage of a reaped child, as reported by `wait4`.
+    pub fn from_rusage(usage: &libc::rusage) -> Self {
+        let usec = |tv: libc::timeval| tv.tv_sec as u64 * 1_000_000 + tv.tv_usec as u64;
+        Self {
+            cpu_usec: usec(usage.ru_utime) + usec(usage.ru_stime),
+            // ru_maxrss is in kilobytes, block counts in 512-byte units
+            peak_memory_bytes: usage.ru_maxrss.max(0) as u64 * 1024,
+            io_read_bytes: usage.ru_inblock.max(0) as u64 * 512,
+            io_write_bytes: usage.ru_oublock.max(0) as u64 * 512,
+        }
+    }
+
+    /// Usage so far of the running main process of `service`.
+    pub fn sample(service: &str, pid: u32) -> Option<Self> {
+        match service_cgroup(service, pid) {
+            Some(cgroup) => Self::from_cgroup(&cgroup),
+            None => Self::from_proc(pid),
+        }
+    }
+
+    /// Usage of every process in a cgroup.
+    fn from_cgroup(cgroup: &Path) -> Option<Self> {
+        let read = |file: &str| std::fs::read_to_string(cgroup.join(file)).ok();
+        let (io_read_bytes, io_write_bytes) = read("io.stat")
+            .map(|s| parse_io_stat(&s))
+            .unwrap_or_default();
+        Some(Self {
+            cpu_usec: parse_cpu_stat(&read("cpu.stat")?)?,
+            peak_memory_bytes: read("memory.peak")
+                .or_else(|| read("memory.current"))
+                .and_then(|s| s.trim().parse().ok())
+                .unwrap_or(0),
+            io_read_bytes,
+            io_write_bytes,
+        })
+    }
+
+    /// Usage of a single process and the children it has waited for.
+    fn from_proc(pid: u32) -> Option<Self> {
+        let read = |file: &str| std::fs::read_to_string(format!("/proc/{}/{}", pid, file)).ok();
+        let ticks = parse_stat_ticks(&read("stat")?)?;
+        let hertz = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as u64;
+        let (io_read_bytes, io_write_bytes) = read("io")
+            .and_then(|s| parse_proc_io(&s))
+            .unwrap_or_default();
+        Some(Self {
+            cpu_usec: ticks * 1_000_000 / hertz,
+            peak_memory_bytes: read("status")
+                .and_then(|s| parse_status_hwm(&s))
+                .unwrap_or(0),
+            io_read_bytes,
+            io_write_bytes,
+        })
+    }
+
+    /// Usage of two runs one after the other.
+    pub fn add(&self, other: &Self) -> Self {
+        Self {
+            cpu_usec: self.cpu_usec + other.cpu_usec,
+            peak_memory_bytes: self.peak_memory_bytes.max(other.peak_memory_bytes),
+            io_read_bytes: self.io_read_bytes + other.io_read_bytes,
+            io_write_bytes: self.io_write_bytes + other.io_write_bytes,
+        }
+    }
+
+    /// CPU time in seconds.
+    pub fn cpu_secs(&self) -> f64 {
+        self.cpu_usec as f64 / 1_000_000.0
+    }
+}
+
+/// Cgroup of a process, if it is one dedicated to the service.
+fn service_cgroup(service: &str, pid: u32) -> Option<PathBuf> {
+    let content = std::fs::read_to_string(format!("/proc/{}/cgroup", pid)).ok()?;
+    let path = content.lines().find_map(|l| l.strip_prefix("0::"))?;
+    let leaf = path.rsplit('/').next()?;
+    if leaf == service || leaf == format!("{}.service", service) {
+        Some(Path::new(CGROUP_ROOT).join(path.trim_start_matches('/')))
+    } else {
+        None
+    }
+}
+
+/// utime + stime + cutime + cstime, in clock ticks, from /proc/pid/stat.
+fn parse_stat_ticks(content: &str) -> Option<u64> {
+    // The command name may contain spaces, so count fields after it
+    let fields: Vec<&str> = content[content.rfind(')')? + 1..]
+        .split_whitespace()
+        .collect();
+    fields
+        .get(11..15)?
+        .iter()
+        .map(|f| f.parse::<i64>().ok().map(|t| t.max(0) as u64))
+        .sum()
+}
+
+/// VmHWM from /proc/pid/status, in bytes.
+fn parse_status_hwm(content: &str) -> Option<u64> {
+    let line = content.lines().find_map(|l| l.strip_prefix("VmHWM:"))?;
+    let kb: u64 = line.split_whitespace().next()?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+/// read_bytes and write_bytes from /proc/pid/io.
+fn parse_proc_io(content: &str) -> Option<(u64, u64)> {
+    let field = |name: &str| {
+        content
+            .lines()
+            .find_map(|l| l.strip_prefix(name))
+            .and_then(|v| v.trim().parse().ok())
+    };
+    Some((field("read_bytes:")?, field("write_bytes:")?))
+}
+
+/// usage_usec from a cgroup's cpu.stat.
+fn parse_cpu_stat(content: &str) -> Option<u64> {
+    content
+        .lines()
+        .find_map(|l| l.strip_prefix("usage_usec "))
+        .and_then(|v| v.trim().parse().ok())
+}
+
+/// rbytes and wbytes summed over every device in a cgroup's io.stat.
+fn parse_io_stat(content: &str) -> (u64, u64) {
+    let mut totals = (0, 0);
+    for field in content.split_whitespace() {
+        if let Some(v) = field.strip_prefix("rbytes=") {
+            totals.0 += v.parse::<u64>().unwrap_or(0);
+        } else if let Some(v) = field.strip_prefix("wbytes=") {
+            totals.1 += v.parse::<u64>().unwrap_or(0);
+        }
+    }
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc() {
+        let stat = "1234 (my (odd) daemon) S 1 1234 1234 0 -1 4194560 500 0 0 0 \
+                    150 50 10 5 20 0 1 0 12345 1000000 200";
+        assert_eq!(parse_stat_ticks(stat), Some(215));
+
+        let status = "Name:\tdaemon\nVmPeak:\t  20000 kB\nVmHWM:\t    5120 kB\nVmRSS:\t 4096 kB\n";
+        assert_eq!(parse_status_hwm(status), Some(5120 * 1024));
+
+        let io = "rchar: 100\nwchar: 200\nsyscr: 1\nsyscw: 2\nread_bytes: 4096\nwrite_bytes: 8192\ncancelled_write_bytes: 0\n";
+        assert_eq!(parse_proc_io(io), Some((4096, 8192)));
+    }
+
+    #[test]
+    fn test_parse_cgroup() {
+        let cpu = "usage_usec 2500000\nuser_usec 2000000\nsystem_usec 500000\n";
+        assert_eq!(parse_cpu_stat(cpu), Some(2_500_000));
+
+        let io = "8:0 rbytes=1000 wbytes=2000 rios=1 wios=2 dbytes=0 dios=0\n\
+                  259:0 rbytes=500 wbytes=0 rios=1 wios=0 dbytes=0 dios=0\n";
+        assert_eq!(parse_io_stat(io), (1500, 2000));
+    }
+
+    #[test]
+    fn test_add_accumulates_across_runs() {
+        let first = ResourceUsage {
+            cpu_usec: 1_000_000,
+            peak_memory_bytes: 4096,
+            io_read_bytes: 10,
+            io_write_bytes: 20,
+        };
+        let second = ResourceUsage {
+            cpu_usec: 500_000,
+            peak_memory_bytes: 2048,
+            io_read_bytes: 1,
+            io_write_bytes: 2,
+        };
+        let total = first.add(&second);
+        assert_eq!(total.cpu_usec, 1_500_000);
+        assert_eq!(total.peak_memory_bytes, 4096);
+        assert_eq!((total.io_read_bytes, total.io_write_bytes), (11, 22));
+        assert_eq!(total.cpu_secs(), 1.5);
+    }
+}
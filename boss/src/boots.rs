@@ -0,0 +1,233 @@
+//! Boot IDs and the index of boots the journal has entries of.
+//!
+//! Init generates a boot ID when it starts; the journal stamps it on every
+//! entry as `_BOOT_ID`. The boot index, `boots.json` in the journal
+//! directory, lists every boot with when it started, how long it took and
+//! how long each service took to start, so the logs of an earlier boot can
+//! be picked by position (`-1` is the boot before this one) and boot times
+//! compared across boots.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// File name of the boot index in the journal directory.
+pub const BOOT_INDEX_FILE: &str = "boots.json";
+
+/// Boots kept in the index; older ones are dropped as new ones are added.
+pub const MAX_BOOTS: usize = 100;
+
+/// A new boot ID: 32 hex digits, like journald's.
+pub fn generate_boot_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+/// One boot in the index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BootRecord {
+    pub boot_id: String,
+    /// When init started
+    pub started: DateTime<Utc>,
+    /// When init shut the system down, if it got that far
+    #[serde(default)]
+    pub ended: Option<DateTime<Utc>>,
+    /// Time from init starting until the enabled services were started
+    #[serde(default)]
+    pub boot_duration_ms: Option<u64>,
+    /// Start time of each service started at boot
+    #[serde(default)]
+    pub service_times_ms: BTreeMap<String, u64>,
+}
+
+impl BootRecord {
+    /// A boot starting now.
+    pub fn new(boot_id: &str) -> Self {
+        Self {
+            boot_id: boot_id.to_string(),
+            started: Utc::now(),
+            ended: None,
+            boot_duration_ms: None,
+            service_times_ms: BTreeMap::new(),
+        }
+    }
+}
+
+/// Which boot to look at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BootSpec {
+    /// Counted from the current boot backwards when zero or negative (`0`
+    /// is the current boot, `-1` the one before), from the oldest boot in
+    /// the index forwards when positive (`1` is the oldest)
+    Offset(i64),
+    /// A boot ID, or a prefix of one
+    Id(String),
+}
+
+impl std::fmt::Display for BootSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BootSpec::Offset(n) => write!(f, "{}", n),
+            BootSpec::Id(id) => write!(f, "{}", id),
+        }
+    }
+}
+
+impl std::str::FromStr for BootSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Ok(BootSpec::Offset(0));
+        }
+        if let Ok(offset) = s.parse::<i64>() {
+            // A boot ID of only digits is taken for an offset unless it is
+            // long enough not to be one
+            if s.len() < 8 || s.starts_with(['-', '+']) {
+                return Ok(BootSpec::Offset(offset));
+            }
+        }
+        let id = s.replace('-', "").to_ascii_lowercase();
+        if id.chars().all(|c| c.is_ascii_hexdigit()) {
+            Ok(BootSpec::Id(id))
+        } else {
+            Err(format!(
+                "invalid boot '{}', expected an offset or boot ID",
+                s
+            ))
+        }
+    }
+}
+
+/// Boots in the journal, oldest first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BootIndex {
+    pub boots: Vec<BootRecord>,
+}
+
+impl BootIndex {
+    /// Load the index of a journal directory, empty if there is none.
+    pub fn load(log_dir: &Path) -> Self {
+        std::fs::read_to_string(log_dir.join(BOOT_INDEX_FILE))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    /// Write the index to a journal directory, replacing it atomically.
+    pub fn save(&self, log_dir: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(log_dir)?;
+        let path = log_dir.join(BOOT_INDEX_FILE);
+        let tmp = log_dir.join(format!("{}.tmp", BOOT_INDEX_FILE));
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(tmp, path)
+    }
+
+    /// Add a boot, or update it if it is in the index already.
+    pub fn record(&mut self, boot: BootRecord) {
+        match self.boots.iter_mut().find(|b| b.boot_id == boot.boot_id) {
+            Some(known) => *known = boot,
+            None => {
+                self.boots.push(boot);
+                self.boots.sort_by_key(|b| b.started);
+                let excess = self.boots.len().saturating_sub(MAX_BOOTS);
+                self.boots.drain(..excess);
+            }
+        }
+    }
+
+    /// The boot `spec` refers to.
+    pub fn resolve(&self, spec: &BootSpec) -> Option<&BootRecord> {
+        match spec {
+            BootSpec::Offset(n) if *n <= 0 => {
+                let back = usize::try_from(n.unsigned_abs()).ok()?;
+                self.boots.iter().rev().nth(back)
+            }
+            BootSpec::Offset(n) => self.boots.get(usize::try_from(*n).ok()? - 1),
+            BootSpec::Id(id) => {
+                let mut found = self.boots.iter().filter(|b| b.boot_id.starts_with(id));
+                // A prefix must be unique
+                let boot = found.next()?;
+                found.next().is_none().then_some(boot)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boot(id: &str, minutes: i64) -> BootRecord {
+        BootRecord {
+            started: DateTime::from_timestamp(1_700_000_000 + minutes * 60, 0).unwrap(),
+            ..BootRecord::new(id)
+        }
+    }
+
+    #[test]
+    fn test_resolve_boots() {
+        let mut index = BootIndex::default();
+        index.record(boot("bb22", 10));
+        index.record(boot("aa11", 0));
+        index.record(boot("bb33", 20));
+        let mut current = boot("bb33", 20);
+        current.boot_duration_ms = Some(1200);
+        index.record(current);
+        assert_eq!(index.boots.len(), 3);
+
+        let id = |spec: &str| {
+            index
+                .resolve(&spec.parse().unwrap())
+                .map(|b| b.boot_id.as_str())
+        };
+        assert_eq!(id("0"), Some("bb33"));
+        assert_eq!(id("-1"), Some("bb22"));
+        assert_eq!(id("-2"), Some("aa11"));
+        assert_eq!(id("-3"), None);
+        assert_eq!(id("1"), Some("aa11"));
+        assert_eq!(id("+3"), Some("bb33"));
+        assert_eq!(id("aa"), Some("aa11"));
+        // Ambiguous prefix
+        assert_eq!(id("bb"), None);
+        assert_eq!(index.boots[2].boot_duration_ms, Some(1200));
+        assert!("not-a-boot".parse::<BootSpec>().is_err());
+
+        let dir = std::env::temp_dir().join(format!("boss-boots-{}", std::process::id()));
+        index.save(&dir).unwrap();
+        assert_eq!(BootIndex::load(&dir), index);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_journal_of_previous_boot() {
+        use crate::journal::{Journal, JournalEntry};
+        use crate::journal_index::JournalFilter;
+
+        let dir = std::env::temp_dir().join(format!("boss-boot-journal-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for (boot_id, message) in [("1111aaaa", "first boot"), ("2222bbbb", "second boot")] {
+            let journal = Journal::new(dir.clone());
+            journal.begin_boot(boot_id);
+            journal
+                .log(JournalEntry::new("sshd", message, "stdout"))
+                .await;
+            journal.update_boot(|boot| boot.boot_duration_ms = Some(100));
+        }
+
+        let journal = Journal::new(dir.clone());
+        let boots = journal.boots();
+        assert_eq!(boots.boots.len(), 2);
+        let previous = boots.resolve(&BootSpec::Offset(-1)).unwrap();
+        assert_eq!(previous.boot_id, "1111aaaa");
+        assert_eq!(previous.boot_duration_ms, Some(100));
+
+        let filter = JournalFilter::new().with_match("_BOOT_ID", &previous.boot_id);
+        let entries = journal.query(&filter, None).await;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].message, "first boot");
+        assert_eq!(entries[0].boot_id.as_deref(), Some("1111aaaa"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -4,8 +4,14 @@
 //! and the running init process via a Unix domain socket.
 
 use crate::error::{Error, Result};
+use crate::inhibit::{InhibitWhat, Inhibitor};
+use crate::journal_ingest::IngestStats;
+use crate::orphans::OrphanCount;
+use crate::session::Session;
+use crate::transient::TransientUnit;
 use crate::ShutdownType;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::{UnixListener, UnixStream};
@@ -35,10 +41,55 @@ pub enum ControlCommand {
     GetAllStatus,
     /// List all services
     ListServices,
-    /// Initiate system shutdown
-    Shutdown { shutdown_type: ShutdownType },
+    /// Get the state of init itself
+    GetSystemStatus,
+    /// Initiate system shutdown; refused while users are logged in or a
+    /// shutdown inhibitor is held, unless forced
+    Shutdown {
+        shutdown_type: ShutdownType,
+        #[serde(default)]
+        force: bool,
+        /// Wait this long for inhibitors to be released instead of failing
+        #[serde(default)]
+        wait_secs: Option<u64>,
+    },
+    /// Take an inhibitor lock, held until the connection is closed
+    Inhibit {
+        what: Vec<InhibitWhat>,
+        who: String,
+        why: String,
+    },
+    /// List held inhibitor locks
+    ListInhibitors,
+    /// List login sessions
+    ListSessions,
     /// Reload service definitions
     ReloadDaemon,
+    /// Move journal files written to /run while the journal directory was
+    /// read-only to the persistent journal
+    FlushJournal,
+    /// Report how many entries each service logged and how many were
+    /// dropped
+    JournalStats,
+    /// Create and start a transient unit
+    StartTransient { unit: TransientUnit },
+    /// Packages were installed or removed; check units with
+    /// RequiresPackage again
+    PackagesChanged,
+    /// A service pinged its watchdog, reported its status or reported
+    /// being busy
+    Notify {
+        name: String,
+        #[serde(default)]
+        watchdog: bool,
+        #[serde(default)]
+        status: Option<String>,
+        #[serde(default)]
+        busy: bool,
+        /// Further fields to record in the service's journal
+        #[serde(default)]
+        fields: BTreeMap<String, String>,
+    },
     /// Ping to check if init is responding
     Ping,
 }
@@ -59,6 +110,14 @@ pub enum ControlResponse {
     },
     /// List of services
     ServiceList { services: Vec<ServiceInfo> },
+    /// State of init itself
+    SystemStatus { status: SystemStatus },
+    /// Login sessions
+    SessionList { sessions: Vec<Session> },
+    /// Held inhibitor locks
+    InhibitorList { inhibitors: Vec<Inhibitor> },
+    /// Journal ingestion counters per service
+    JournalStats { stats: Vec<IngestStats> },
     /// Pong response
     Pong,
 }
@@ -72,6 +131,21 @@ pub struct ServiceInfo {
     pub description: Option<String>,
 }
 
+/// State of the init process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemStatus {
+    /// PID of init; 1 unless running as a subreaper
+    pub pid: u32,
+    /// Services known to init
+    pub services: usize,
+    /// Services running
+    pub running: usize,
+    /// Services that failed
+    pub failed: usize,
+    /// Orphaned processes adopted and reaped, by originating service
+    pub orphans: Vec<OrphanCount>,
+}
+
 /// Control socket server (runs in init process)
 pub struct ControlServer {
     socket_path: PathBuf,
@@ -220,9 +294,16 @@ impl ControlClient {
     /// Send a command and receive a response
     pub async fn send_command(&self, command: ControlCommand) -> Result<ControlResponse> {
         let mut stream = self.connect().await?;
+        Self::exchange(&mut stream, &command).await
+    }
 
+    /// Send a command on an open connection and read the response.
+    async fn exchange(
+        stream: &mut UnixStream,
+        command: &ControlCommand,
+    ) -> Result<ControlResponse> {
         // Send command
-        let json = serde_json::to_string(&command)
+        let json = serde_json::to_string(command)
             .map_err(|e| Error::Other(format!("Failed to serialize command: {}", e)))?;
 
         stream.write_all(json.as_bytes()).await?;
@@ -230,7 +311,7 @@ impl ControlClient {
         stream.flush().await?;
 
         // Read response
-        let mut reader = BufReader::new(&mut stream);
+        let mut reader = BufReader::new(stream);
         let mut line = String::new();
         reader.read_line(&mut line).await?;
 
@@ -240,6 +321,26 @@ impl ControlClient {
         Ok(response)
     }
 
+    /// Take an inhibitor lock, held until the returned lock is dropped.
+    pub async fn inhibit(
+        &self,
+        what: Vec<InhibitWhat>,
+        who: &str,
+        why: &str,
+    ) -> Result<InhibitorLock> {
+        let mut stream = self.connect().await?;
+        let command = ControlCommand::Inhibit {
+            what,
+            who: who.to_string(),
+            why: why.to_string(),
+        };
+        match Self::exchange(&mut stream, &command).await? {
+            ControlResponse::Success { .. } => Ok(InhibitorLock { _stream: stream }),
+            ControlResponse::Error { message } => Err(Error::Other(message)),
+            _ => Err(Error::Other("Unexpected response from init".to_string())),
+        }
+    }
+
     /// Convenience methods for common commands
     pub async fn start_service(&self, name: &str) -> Result<ControlResponse> {
         self.send_command(ControlCommand::StartService {
@@ -273,8 +374,68 @@ impl ControlClient {
         self.send_command(ControlCommand::ListServices).await
     }
 
-    pub async fn shutdown(&self, shutdown_type: ShutdownType) -> Result<ControlResponse> {
-        self.send_command(ControlCommand::Shutdown { shutdown_type })
+    pub async fn system_status(&self) -> Result<ControlResponse> {
+        self.send_command(ControlCommand::GetSystemStatus).await
+    }
+
+    pub async fn shutdown(
+        &self,
+        shutdown_type: ShutdownType,
+        force: bool,
+        wait_secs: Option<u64>,
+    ) -> Result<ControlResponse> {
+        self.send_command(ControlCommand::Shutdown {
+            shutdown_type,
+            force,
+            wait_secs,
+        })
+        .await
+    }
+
+    pub async fn flush_journal(&self) -> Result<ControlResponse> {
+        self.send_command(ControlCommand::FlushJournal).await
+    }
+
+    pub async fn journal_stats(&self) -> Result<ControlResponse> {
+        self.send_command(ControlCommand::JournalStats).await
+    }
+
+    pub async fn reload_daemon(&self) -> Result<ControlResponse> {
+        self.send_command(ControlCommand::ReloadDaemon).await
+    }
+
+    pub async fn packages_changed(&self) -> Result<ControlResponse> {
+        self.send_command(ControlCommand::PackagesChanged).await
+    }
+
+    pub async fn notify(
+        &self,
+        name: &str,
+        watchdog: bool,
+        status: Option<String>,
+        busy: bool,
+        fields: BTreeMap<String, String>,
+    ) -> Result<ControlResponse> {
+        self.send_command(ControlCommand::Notify {
+            name: name.to_string(),
+            watchdog,
+            status,
+            busy,
+            fields,
+        })
+        .await
+    }
+
+    pub async fn list_inhibitors(&self) -> Result<ControlResponse> {
+        self.send_command(ControlCommand::ListInhibitors).await
+    }
+
+    pub async fn list_sessions(&self) -> Result<ControlResponse> {
+        self.send_command(ControlCommand::ListSessions).await
+    }
+
+    pub async fn start_transient(&self, unit: TransientUnit) -> Result<ControlResponse> {
+        self.send_command(ControlCommand::StartTransient { unit })
             .await
     }
 
@@ -287,6 +448,13 @@ impl ControlClient {
     }
 }
 
+/// An inhibitor lock taken with [`ControlClient::inhibit`]; init releases
+/// it when this is dropped and the connection closes.
+#[derive(Debug)]
+pub struct InhibitorLock {
+    _stream: UnixStream,
+}
+
 /// Helper to determine if we should use local init or connect to running init
 pub fn should_use_control_socket() -> bool {
     // Use control socket if:
@@ -0,0 +1,317 @@
+//! Core dump capture.
+//!
+//! Init points kernel.core_pattern at `boss coredump-capture`, which the
+//! kernel runs with the core on stdin whenever a process dumps core. The
+//! helper identifies the crashed process and its service while the process
+//! still exists, then stores the core zstd-compressed next to a JSON
+//! metadata file. Cores are truncated at a size limit, and the oldest are
+//! removed when the store grows past its own limit.
+
+use crate::error::{Error, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Directory core dumps are stored in.
+pub const COREDUMP_DIR: &str = "/var/lib/buckos/coredump";
+
+const CORE_PATTERN: &str = "/proc/sys/kernel/core_pattern";
+
+/// Longest core_pattern the kernel accepts, including the terminating NUL.
+const CORE_PATTERN_MAX: usize = 128;
+
+/// Size limits on stored core dumps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoredumpLimits {
+    /// Largest core kept, uncompressed; larger cores are truncated
+    pub max_size: u64,
+    /// Most space all stored cores may use, compressed
+    pub max_use: u64,
+}
+
+impl Default for CoredumpLimits {
+    fn default() -> Self {
+        Self {
+            max_size: 2 << 30,
+            max_use: 4 << 30,
+        }
+    }
+}
+
+/// core_pattern piping cores to `helper`.
+pub fn core_pattern(helper: &Path, limits: &CoredumpLimits) -> Result<String> {
+    let pattern = format!(
+        "|{} coredump-capture --max-size {} --max-use {} %P %u %g %s %t %e",
+        helper.display(),
+        limits.max_size,
+        limits.max_use
+    );
+    // The kernel splits the pattern into arguments at spaces
+    if pattern.len() >= CORE_PATTERN_MAX || helper.to_string_lossy().contains(char::is_whitespace) {
+        return Err(Error::ConfigError(format!(
+            "cannot build a core_pattern for helper {}",
+            helper.display()
+        )));
+    }
+    Ok(pattern)
+}
+
+/// Route core dumps to `helper`.
+pub fn install(helper: &Path, limits: &CoredumpLimits) -> Result<()> {
+    std::fs::write(CORE_PATTERN, core_pattern(helper, limits)?)?;
+    Ok(())
+}
+
+/// The crashed process, as described by the kernel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashedProcess {
+    pub pid: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub signal: i32,
+    /// Time of the dump, in seconds since the epoch
+    pub timestamp: i64,
+    /// Executable name
+    pub comm: String,
+}
+
+/// A stored core dump.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoredumpInfo {
+    pub id: String,
+    pub pid: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub signal: i32,
+    pub timestamp: DateTime<Utc>,
+    pub comm: String,
+    /// Path of the executable
+    pub exe: Option<PathBuf>,
+    /// Service the process belonged to
+    pub service: Option<String>,
+    /// Uncompressed size stored
+    pub size: u64,
+    /// Compressed size on disk
+    pub stored_size: u64,
+    /// Whether the core was cut off at the size limit
+    pub truncated: bool,
+}
+
+impl CoredumpInfo {
+    /// Name of the signal that caused the dump, e.g. `SIGSEGV`.
+    pub fn signal_name(&self) -> String {
+        nix::sys::signal::Signal::try_from(self.signal)
+            .map(|s| s.as_str().to_string())
+            .unwrap_or_else(|_| self.signal.to_string())
+    }
+}
+
+/// Executable and service of a running process.
+pub fn identify(pid: u32) -> (Option<PathBuf>, Option<String>) {
+    let exe = std::fs::read_link(format!("/proc/{}/exe", pid)).ok();
+    let from_cgroup = std::fs::read_to_string(format!("/proc/{}/cgroup", pid))
+        .ok()
+        .and_then(|c| parse_cgroup_service(&c));
+    let service = from_cgroup.or_else(|| {
+        std::fs::read(format!("/proc/{}/environ", pid))
+            .ok()
+            .and_then(|e| parse_environ_service(&e))
+    });
+    (exe, service)
+}
+
+/// Service of a `<name>.service` cgroup.
+pub(crate) fn parse_cgroup_service(content: &str) -> Option<String> {
+    let path = content.lines().find_map(|l| l.strip_prefix("0::"))?;
+    let leaf = path.rsplit('/').next()?;
+    leaf.strip_suffix(".service").map(str::to_string)
+}
+
+/// BUCKOS_SERVICE from a NUL-separated environment.
+fn parse_environ_service(environ: &[u8]) -> Option<String> {
+    environ
+        .split(|&b| b == 0)
+        .find_map(|var| var.strip_prefix(b"BUCKOS_SERVICE="))
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+}
+
+/// Stored core dumps.
+pub struct CoredumpStore {
+    dir: PathBuf,
+}
+
+impl CoredumpStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn core_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.core.zst", id))
+    }
+
+    fn meta_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    /// Store the core of `process`, read from `core`.
+    pub fn capture(
+        &self,
+        process: &CrashedProcess,
+        mut core: impl Read,
+        limits: &CoredumpLimits,
+    ) -> Result<CoredumpInfo> {
+        std::fs::create_dir_all(&self.dir)?;
+        let timestamp = DateTime::from_timestamp(process.timestamp, 0).unwrap_or_else(Utc::now);
+        let id = format!("{}-{}", timestamp.format("%Y%m%d%H%M%S"), process.pid);
+        let (exe, service) = identify(process.pid);
+
+        let core_path = self.core_path(&id);
+        let mut encoder = zstd::Encoder::new(File::create(&core_path)?, 3)?;
+        let size = io::copy(&mut (&mut core).take(limits.max_size), &mut encoder)?;
+        encoder.finish()?.sync_all()?;
+        let truncated = size == limits.max_size && core.read(&mut [0u8; 1])? > 0;
+
+        let info = CoredumpInfo {
+            id: id.clone(),
+            pid: process.pid,
+            uid: process.uid,
+            gid: process.gid,
+            signal: process.signal,
+            timestamp,
+            comm: process.comm.clone(),
+            exe,
+            service,
+            size,
+            stored_size: std::fs::metadata(&core_path)?.len(),
+            truncated,
+        };
+        let tmp = self.dir.join(format!("{}.json.tmp", id));
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&info)?)?;
+        std::fs::rename(&tmp, self.meta_path(&id))?;
+
+        self.enforce(limits.max_use)?;
+        Ok(info)
+    }
+
+    /// Stored core dumps, oldest first.
+    pub fn list(&self) -> Vec<CoredumpInfo> {
+        let mut dumps: Vec<CoredumpInfo> = std::fs::read_dir(&self.dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|p| std::fs::read(p).ok())
+            .filter_map(|b| serde_json::from_slice(&b).ok())
+            .collect();
+        dumps.sort_by(|a, b| (a.timestamp, &a.id).cmp(&(b.timestamp, &b.id)));
+        dumps
+    }
+
+    /// A core dump by id, PID or executable name; the newest match wins.
+    pub fn find(&self, query: &str) -> Option<CoredumpInfo> {
+        self.list()
+            .into_iter()
+            .rev()
+            .find(|d| d.id == query || d.pid.to_string() == query || d.comm == query)
+    }
+
+    /// Decompress a core dump to `dest`.
+    pub fn extract(&self, info: &CoredumpInfo, dest: &Path) -> Result<()> {
+        let mut decoder = zstd::Decoder::new(File::open(self.core_path(&info.id))?)?;
+        io::copy(&mut decoder, &mut File::create(dest)?)?;
+        Ok(())
+    }
+
+    /// Remove core dumps, oldest first, until the store uses at most
+    /// `max_use` bytes. The newest dump is always kept. Returns how many
+    /// were removed.
+    pub fn enforce(&self, max_use: u64) -> Result<usize> {
+        let dumps = self.list();
+        let mut total: u64 = dumps.iter().map(|d| d.stored_size).sum();
+        let mut removed = 0;
+        for dump in dumps.iter().take(dumps.len().saturating_sub(1)) {
+            if total <= max_use {
+                break;
+            }
+            let _ = std::fs::remove_file(self.core_path(&dump.id));
+            std::fs::remove_file(self.meta_path(&dump.id))?;
+            total -= dump.stored_size;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crashed(pid: u32, timestamp: i64) -> CrashedProcess {
+        CrashedProcess {
+            pid,
+            uid: 0,
+            gid: 0,
+            signal: libc::SIGSEGV,
+            timestamp,
+            comm: "crasher".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_capture_and_limits() {
+        let dir = std::env::temp_dir().join(format!("boss-coredump-{}", std::process::id()));
+        let store = CoredumpStore::new(&dir);
+        let core: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+        let limits = CoredumpLimits {
+            max_size: 16 * 1024,
+            max_use: u64::MAX,
+        };
+
+        // PIDs that do not exist, so nothing is identified
+        let info = store
+            .capture(&crashed(u32::MAX - 1, 1_700_000_000), &core[..], &limits)
+            .unwrap();
+        assert_eq!(info.id, "20231114221320-4294967294");
+        assert_eq!(info.size, 16 * 1024);
+        assert!(info.truncated);
+        assert_eq!(info.signal_name(), "SIGSEGV");
+        assert_eq!(info.service, None);
+
+        let extracted = dir.join("extracted");
+        store.extract(&info, &extracted).unwrap();
+        assert_eq!(std::fs::read(&extracted).unwrap(), &core[..16 * 1024]);
+
+        store
+            .capture(&crashed(u32::MAX - 2, 1_700_000_100), &core[..100], &limits)
+            .unwrap();
+        assert_eq!(store.list().len(), 2);
+        assert_eq!(store.find("crasher").unwrap().pid, u32::MAX - 2);
+
+        // Only the newest fits
+        assert_eq!(store.enforce(0).unwrap(), 1);
+        assert_eq!(store.list().len(), 1);
+        assert!(store.find(&info.id).is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_identify_parsing() {
+        assert_eq!(
+            parse_cgroup_service("0::/system.slice/nginx.service\n"),
+            Some("nginx".to_string())
+        );
+        assert_eq!(parse_cgroup_service("0::/user.slice\n"), None);
+        assert_eq!(
+            parse_environ_service(b"PATH=/bin\0BUCKOS_SERVICE=sshd\0"),
+            Some("sshd".to_string())
+        );
+
+        let pattern = core_pattern(Path::new("/sbin/boss"), &CoredumpLimits::default()).unwrap();
+        assert!(pattern.starts_with("|/sbin/boss coredump-capture "));
+        assert!(pattern.ends_with(" %P %u %g %s %t %e"));
+    }
+}
@@ -0,0 +1,308 @@
+//! Crash handling for PID 1.
+//!
+//! If init exits, the kernel panics, so a panic in the main loop or a
+//! critical task must not take the process down. Critical tasks run under
+//! [`CrashHandler::supervise`]; when one panics or fails, a diagnostic dump
+//! (backtrace, service table and the last journal entries) is written to
+//! the crash directory, and PID 1 then drops to a rescue shell on the
+//! console instead of exiting. Without a usable shell it freezes, still
+//! reaping orphans, so the dump can be collected.
+
+use crate::error::{Error, Result};
+use crate::journal::JournalEntry;
+use crate::manager::ServiceManager;
+use crate::tr;
+use chrono::Utc;
+use std::backtrace::Backtrace;
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use tracing::error;
+
+/// Default directory for crash dumps.
+pub const CRASH_DIR: &str = "/run/buckos/crash";
+
+/// Journal entries included in a dump.
+const DUMP_JOURNAL_ENTRIES: usize = 50;
+
+/// Shells tried for the rescue shell, in order.
+const RESCUE_SHELLS: &[&str] = &["/bin/sh", "/bin/bash", "/bin/busybox"];
+
+/// A panic caught by the panic hook.
+#[derive(Debug, Clone)]
+struct PanicRecord {
+    thread: String,
+    message: String,
+    location: String,
+    backtrace: String,
+}
+
+/// Writes crash dumps and keeps PID 1 alive after a fatal failure.
+pub struct CrashHandler {
+    dir: PathBuf,
+    manager: Arc<ServiceManager>,
+    /// Most recent panic seen by the panic hook
+    last_panic: Mutex<Option<PanicRecord>>,
+}
+
+impl CrashHandler {
+    pub fn new(dir: impl Into<PathBuf>, manager: Arc<ServiceManager>) -> Arc<Self> {
+        Arc::new(Self {
+            dir: dir.into(),
+            manager,
+            last_panic: Mutex::new(None),
+        })
+    }
+
+    /// Install a panic hook recording the backtrace of every panic.
+    ///
+    /// A panic on the main thread cannot be caught by [`Self::supervise`],
+    /// so with `rescue` set it is dumped and handled in the hook itself.
+    pub fn install(self: &Arc<Self>, rescue: bool) {
+        let handler = Arc::clone(self);
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let thread = std::thread::current();
+            let record = PanicRecord {
+                thread: thread.name().unwrap_or("unnamed").to_string(),
+                message: panic_message(info.payload()),
+                location: info
+                    .location()
+                    .map(|l| l.to_string())
+                    .unwrap_or_else(|| "unknown".to_string()),
+                backtrace: Backtrace::force_capture().to_string(),
+            };
+            *handler.last_panic.lock().unwrap_or_else(|e| e.into_inner()) = Some(record.clone());
+            previous(info);
+
+            if rescue && thread.name() == Some("main") {
+                handler.write_dump(&format!("main thread panicked: {}", record.message));
+                rescue_shell();
+            }
+        }));
+    }
+
+    /// Run a critical task, writing a crash dump if it panics or fails.
+    pub async fn supervise<F>(&self, name: &str, task: F) -> Result<()>
+    where
+        F: Future<Output = Result<()>> + Send + 'static,
+    {
+        let reason = match tokio::spawn(task).await {
+            Ok(Ok(())) => return Ok(()),
+            Ok(Err(e)) => format!("critical task {} failed: {}", name, e),
+            Err(e) if e.is_panic() => {
+                format!(
+                    "critical task {} panicked: {}",
+                    name,
+                    panic_message(e.into_panic().as_ref())
+                )
+            }
+            Err(e) => format!("critical task {} was cancelled: {}", name, e),
+        };
+        error!("{}", reason);
+        self.write_dump(&reason);
+        Err(Error::Other(reason))
+    }
+
+    /// Write a crash dump, returning its path.
+    ///
+    /// Never fails: anything that cannot be collected is noted in the dump.
+    pub fn write_dump(&self, reason: &str) -> Option<PathBuf> {
+        let report = self.report(reason);
+        let path = self.dir.join(format!(
+            "crash-{}.txt",
+            Utc::now().format("%Y%m%dT%H%M%S%.3f")
+        ));
+        let written =
+            std::fs::create_dir_all(&self.dir).and_then(|_| std::fs::write(&path, &report));
+        match written {
+            Ok(()) => {
+                error!(path = %path.display(), "Wrote crash dump");
+                Some(path)
+            }
+            Err(e) => {
+                // The console is the last place left to put it
+                error!(error = %e, "Failed to write crash dump:\n{}", report);
+                None
+            }
+        }
+    }
+
+    /// Text of a crash dump.
+    fn report(&self, reason: &str) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "buckos init crash report");
+        let _ = writeln!(out, "time: {}", Utc::now().to_rfc3339());
+        let _ = writeln!(out, "pid: {}", std::process::id());
+        let _ = writeln!(out, "reason: {}", reason);
+
+        let panic = self
+            .last_panic
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let _ = writeln!(out, "\n== backtrace ==");
+        match panic {
+            Some(p) => {
+                let _ = writeln!(
+                    out,
+                    "thread '{}' panicked at {}: {}",
+                    p.thread, p.location, p.message
+                );
+                let _ = writeln!(out, "{}", p.backtrace);
+            }
+            None => {
+                let _ = writeln!(out, "{}", Backtrace::force_capture());
+            }
+        }
+
+        let _ = writeln!(out, "\n== services ==");
+        match self.manager.try_instances() {
+            Some(mut instances) => {
+                instances.sort_by(|a, b| a.name.cmp(&b.name));
+                for i in instances {
+                    let _ = writeln!(
+                        out,
+                        "{:<32} {:<12} pid={:<8} restarts={}",
+                        i.name,
+                        i.state.to_string(),
+                        i.main_pid.map_or("-".to_string(), |p| p.to_string()),
+                        i.restart_count
+                    );
+                }
+            }
+            None => {
+                let _ = writeln!(out, "(unavailable: service table is locked)");
+            }
+        }
+
+        let _ = writeln!(out, "\n== journal (last {}) ==", DUMP_JOURNAL_ENTRIES);
+        match self.manager.journal().try_recent(DUMP_JOURNAL_ENTRIES) {
+            Some(entries) => {
+                for entry in entries {
+                    let _ = writeln!(out, "{}", JournalEntry::format(&entry));
+                }
+            }
+            None => {
+                let _ = writeln!(out, "(unavailable: journal is locked)");
+            }
+        }
+        out
+    }
+
+    /// Directory crash dumps are written to.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+/// Text of a panic payload.
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "(non-string panic payload)".to_string()
+    }
+}
+
+/// Run a rescue shell on the console for as long as one can be started,
+/// then freeze. Never returns, since PID 1 exiting panics the kernel.
+pub fn rescue_shell() -> ! {
+    loop {
+        let shell = RESCUE_SHELLS.iter().find(|s| Path::new(s).exists());
+        let status = shell.and_then(|shell| {
+            let console = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open("/dev/console")
+                .ok()?;
+            let mut cmd = Command::new(shell);
+            cmd.stdin(Stdio::from(console.try_clone().ok()?))
+                .stdout(Stdio::from(console.try_clone().ok()?))
+                .stderr(Stdio::from(console))
+                .env("PS1", "rescue# ");
+            unsafe {
+                cmd.pre_exec(|| {
+                    nix::unistd::setsid().map_err(std::io::Error::other)?;
+                    // Take the console as controlling terminal, for job control
+                    libc::ioctl(0, libc::TIOCSCTTY, 1);
+                    Ok(())
+                });
+            }
+            eprintln!("{}", tr!("crash-rescue-shell", shell = shell.to_string()));
+            cmd.status().ok()
+        });
+        if status.is_none() {
+            freeze();
+        }
+    }
+}
+
+/// Reap orphans forever.
+fn freeze() -> ! {
+    eprintln!("{}", tr!("crash-freezing"));
+    loop {
+        let mut status = 0;
+        if unsafe { libc::waitpid(-1, &mut status, 0) } < 0 {
+            std::thread::sleep(std::time::Duration::from_secs(60));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_worker_panic_writes_dump() {
+        let dir = std::env::temp_dir().join(format!("boss-crash-{}", std::process::id()));
+        let manager = Arc::new(ServiceManager::new(dir.join("services")));
+        manager
+            .register_service(crate::service::ServiceDefinition::new(
+                "sshd",
+                "/usr/sbin/sshd",
+            ))
+            .await
+            .unwrap();
+        manager
+            .journal()
+            .log(JournalEntry::new("sshd", "listening on port 22", "stdout"))
+            .await;
+        let handler = CrashHandler::new(dir.join("crash"), Arc::clone(&manager));
+
+        let result = handler
+            .supervise("worker", async {
+                let v: Vec<u32> = Vec::new();
+                if v.is_empty() {
+                    panic!("injected failure");
+                }
+                Ok(())
+            })
+            .await;
+        let Err(Error::Other(reason)) = result else {
+            panic!("expected the panic to be reported");
+        };
+        assert_eq!(reason, "critical task worker panicked: injected failure");
+
+        let dumps: Vec<PathBuf> = std::fs::read_dir(handler.dir())
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        assert_eq!(dumps.len(), 1);
+        let dump = std::fs::read_to_string(&dumps[0]).unwrap();
+        assert!(dump.contains("reason: critical task worker panicked: injected failure"));
+        assert!(dump.contains("== backtrace =="));
+        assert!(dump.contains("sshd"));
+        assert!(dump.contains("listening on port 22"));
+
+        // The handler survives and keeps supervising
+        assert!(handler.supervise("ok", async { Ok(()) }).await.is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
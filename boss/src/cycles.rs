@@ -0,0 +1,205 @@
+//! Dependency cycle detection.
+//!
+//! A cycle in Requires, Wants or After would make startup recurse forever
+//! or leave the start order undefined. Cycles are found when services are
+//! loaded and broken by dropping the weakest dependency in each cycle, so
+//! the rest of the system still boots; every cycle broken is reported.
+
+use crate::service::ServiceDefinition;
+use std::collections::HashMap;
+
+/// Kind of a dependency, weakest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DependencyKind {
+    Wants,
+    After,
+    Requires,
+}
+
+impl std::fmt::Display for DependencyKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DependencyKind::Wants => write!(f, "Wants"),
+            DependencyKind::After => write!(f, "After"),
+            DependencyKind::Requires => write!(f, "Requires"),
+        }
+    }
+}
+
+/// A dependency cycle and the dependency dropped to break it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyCycle {
+    /// Services in the cycle, starting and ending with the same service
+    pub path: Vec<String>,
+    /// Service whose dependency was dropped
+    pub service: String,
+    /// The dependency that was dropped
+    pub dependency: String,
+    pub kind: DependencyKind,
+}
+
+impl std::fmt::Display for DependencyCycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (dropped {}={} from {})",
+            self.path.join(" -> "),
+            self.kind,
+            self.dependency,
+            self.service
+        )
+    }
+}
+
+/// Strongest dependency of `def` on `dep`, if any.
+fn dependency_kind(def: &ServiceDefinition, dep: &str) -> Option<DependencyKind> {
+    let has = |list: &[String]| list.iter().any(|d| d == dep);
+    if has(&def.requires) {
+        Some(DependencyKind::Requires)
+    } else if has(&def.after) {
+        Some(DependencyKind::After)
+    } else if has(&def.wants) {
+        Some(DependencyKind::Wants)
+    } else {
+        None
+    }
+}
+
+/// Dependencies of a service on other known services, sorted.
+fn dependencies<'a>(
+    def: &'a ServiceDefinition,
+    definitions: &HashMap<String, ServiceDefinition>,
+) -> Vec<&'a String> {
+    let mut deps: Vec<&String> = def
+        .requires
+        .iter()
+        .chain(&def.after)
+        .chain(&def.wants)
+        .filter(|d| definitions.contains_key(*d))
+        .collect();
+    deps.sort();
+    deps.dedup();
+    deps
+}
+
+/// Find one cycle, rotated to start at its smallest service name.
+pub fn find_cycle(definitions: &HashMap<String, ServiceDefinition>) -> Option<Vec<String>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        New,
+        Active,
+        Done,
+    }
+
+    let mut names: Vec<&String> = definitions.keys().collect();
+    names.sort();
+    let mut marks: HashMap<&str, Mark> = names.iter().map(|n| (n.as_str(), Mark::New)).collect();
+
+    for root in names {
+        if marks[root.as_str()] != Mark::New {
+            continue;
+        }
+        // Depth-first search with an explicit stack of (service, next edge)
+        let mut stack: Vec<(&String, usize)> = vec![(root, 0)];
+        marks.insert(root, Mark::Active);
+        while let Some((name, next)) = stack.last_mut() {
+            let deps = dependencies(&definitions[name.as_str()], definitions);
+            let Some(dep) = deps.get(*next).copied() else {
+                marks.insert(name, Mark::Done);
+                stack.pop();
+                continue;
+            };
+            *next += 1;
+            match marks[dep.as_str()] {
+                Mark::New => {
+                    marks.insert(dep, Mark::Active);
+                    stack.push((dep, 0));
+                }
+                Mark::Active => {
+                    let start = stack.iter().position(|(n, _)| *n == dep).unwrap();
+                    let mut cycle: Vec<String> =
+                        stack[start..].iter().map(|(n, _)| (*n).clone()).collect();
+                    let smallest = (0..cycle.len()).min_by_key(|&i| &cycle[i]).unwrap();
+                    cycle.rotate_left(smallest);
+                    cycle.push(cycle[0].clone());
+                    return Some(cycle);
+                }
+                Mark::Done => {}
+            }
+        }
+    }
+    None
+}
+
+/// Break every dependency cycle, dropping the weakest dependency of each.
+///
+/// Ties are broken by position in the cycle, which starts at the smallest
+/// service name, so the result does not depend on load order.
+pub fn break_cycles(definitions: &mut HashMap<String, ServiceDefinition>) -> Vec<DependencyCycle> {
+    let mut broken = Vec::new();
+    while let Some(path) = find_cycle(definitions) {
+        let (service, dependency, kind) = path
+            .windows(2)
+            .filter_map(|edge| {
+                let kind = dependency_kind(&definitions[&edge[0]], &edge[1])?;
+                Some((edge[0].clone(), edge[1].clone(), kind))
+            })
+            .min_by_key(|(_, _, kind)| *kind)
+            .expect("cycle has edges");
+
+        let def = definitions.get_mut(&service).unwrap();
+        def.requires.retain(|d| *d != dependency);
+        def.after.retain(|d| *d != dependency);
+        def.wants.retain(|d| *d != dependency);
+
+        broken.push(DependencyCycle {
+            path,
+            service,
+            dependency,
+            kind,
+        });
+    }
+    broken
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(name: &str, requires: &[&str], after: &[&str], wants: &[&str]) -> ServiceDefinition {
+        let list = |l: &[&str]| l.iter().map(|s| s.to_string()).collect();
+        let mut def = ServiceDefinition::new(name, "/bin/true");
+        def.requires = list(requires);
+        def.after = list(after);
+        def.wants = list(wants);
+        def
+    }
+
+    #[test]
+    fn test_break_cycles_drops_weakest_edge() {
+        let mut definitions: HashMap<String, ServiceDefinition> = [
+            service("web", &["db"], &[], &[]),
+            service("db", &[], &["network"], &[]),
+            service("network", &[], &[], &["web"]),
+            service("ntp", &["ntp"], &[], &[]),
+            service("log", &[], &[], &[]),
+        ]
+        .into_iter()
+        .map(|d| (d.name.clone(), d))
+        .collect();
+
+        let broken = break_cycles(&mut definitions);
+        assert_eq!(broken.len(), 2);
+        assert_eq!(broken[0].path, vec!["db", "network", "web", "db"]);
+        assert_eq!(
+            broken[0].to_string(),
+            "db -> network -> web -> db (dropped Wants=web from network)"
+        );
+        assert_eq!(broken[1].path, vec!["ntp", "ntp"]);
+        assert_eq!(broken[1].kind, DependencyKind::Requires);
+
+        assert!(definitions["network"].wants.is_empty());
+        assert_eq!(definitions["web"].requires, vec!["db"]);
+        assert_eq!(find_cycle(&definitions), None);
+    }
+}
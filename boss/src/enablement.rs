@@ -0,0 +1,313 @@
+//! Unit enablement records and preset policy.
+//!
+//! Enabling a service links its unit file into `<target>.wants/` under
+//! /etc/buckos/system for each target in its WantedBy, the way systemd
+//! does; a service is enabled when any such link exists. Once the record
+//! directory exists it is the only source of enablement, overriding the
+//! `enabled` flag of unit files.
+//!
+//! Preset files (`*.preset` in /etc/buckos/presets and
+//! /usr/lib/buckos/presets) decide whether a newly installed service starts
+//! out enabled. Each line is `enable <pattern>` or `disable <pattern>`;
+//! files are read in name order, with a file in /etc replacing one of the
+//! same name in /usr/lib, and the first matching line wins. A service no
+//! line matches is enabled.
+
+use crate::error::{Error, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Target a service is enabled for when it declares none.
+pub const DEFAULT_TARGET: &str = "multi-user.target";
+
+/// Preset directories, highest priority first.
+pub const PRESET_DIRS: &[&str] = &["/etc/buckos/presets", "/usr/lib/buckos/presets"];
+
+/// Enablement records of services.
+#[derive(Debug, Clone)]
+pub struct EnablementStore {
+    dir: PathBuf,
+}
+
+impl EnablementStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Whether records are kept yet.
+    pub fn exists(&self) -> bool {
+        self.dir.is_dir()
+    }
+
+    /// The `<target>.wants` directories.
+    fn wants_dirs(&self) -> Vec<PathBuf> {
+        std::fs::read_dir(&self.dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_dir() && p.extension().is_some_and(|ext| ext == "wants"))
+            .collect()
+    }
+
+    /// Targets a service is enabled for.
+    pub fn targets(&self, service: &str) -> Vec<String> {
+        let mut targets: Vec<String> = self
+            .wants_dirs()
+            .into_iter()
+            .filter(|dir| dir.join(service).symlink_metadata().is_ok())
+            .filter_map(|dir| Some(dir.file_stem()?.to_string_lossy().into_owned()))
+            .collect();
+        targets.sort();
+        targets
+    }
+
+    pub fn is_enabled(&self, service: &str) -> bool {
+        !self.targets(service).is_empty()
+    }
+
+    /// Link `unit_file` into the wants directory of each target, or of
+    /// [`DEFAULT_TARGET`] if there are none.
+    pub fn enable(&self, service: &str, wanted_by: &[String], unit_file: &Path) -> Result<()> {
+        let default = [DEFAULT_TARGET.to_string()];
+        let targets = if wanted_by.is_empty() {
+            &default[..]
+        } else {
+            wanted_by
+        };
+        for target in targets {
+            let dir = self.dir.join(format!("{}.wants", target));
+            std::fs::create_dir_all(&dir)?;
+            let link = dir.join(service);
+            if link.symlink_metadata().is_ok() {
+                std::fs::remove_file(&link)?;
+            }
+            std::os::unix::fs::symlink(unit_file, &link)?;
+        }
+        Ok(())
+    }
+
+    /// Remove every record of a service.
+    pub fn disable(&self, service: &str) -> Result<()> {
+        for dir in self.wants_dirs() {
+            let link = dir.join(service);
+            if link.symlink_metadata().is_ok() {
+                std::fs::remove_file(&link)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What a preset says to do with a service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresetAction {
+    Enable,
+    Disable,
+}
+
+impl std::fmt::Display for PresetAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PresetAction::Enable => write!(f, "enable"),
+            PresetAction::Disable => write!(f, "disable"),
+        }
+    }
+}
+
+/// Which preset actions to apply.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PresetMode {
+    /// Enable and disable
+    #[default]
+    Full,
+    /// Only enable; never disable a service
+    EnableOnly,
+    /// Only disable; never enable a service
+    DisableOnly,
+}
+
+impl PresetMode {
+    /// Whether `action` is applied in this mode.
+    pub fn allows(&self, action: PresetAction) -> bool {
+        match self {
+            PresetMode::Full => true,
+            PresetMode::EnableOnly => action == PresetAction::Enable,
+            PresetMode::DisableOnly => action == PresetAction::Disable,
+        }
+    }
+}
+
+impl std::str::FromStr for PresetMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "full" => Ok(PresetMode::Full),
+            "enable-only" => Ok(PresetMode::EnableOnly),
+            "disable-only" => Ok(PresetMode::DisableOnly),
+            _ => Err(format!(
+                "unknown preset mode '{}' (full, enable-only, disable-only)",
+                s
+            )),
+        }
+    }
+}
+
+/// Preset rules, in the order they are matched.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PresetPolicy {
+    rules: Vec<(PresetAction, String)>,
+}
+
+impl PresetPolicy {
+    /// Load the preset files of `dirs`, highest priority first.
+    pub fn load(dirs: &[PathBuf]) -> Result<Self> {
+        // By file name, keeping the first directory's file
+        let mut files: BTreeMap<String, PathBuf> = BTreeMap::new();
+        for dir in dirs {
+            for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and(|ext| ext == "preset") {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    files.entry(name).or_insert(path);
+                }
+            }
+        }
+
+        let mut policy = Self::default();
+        for path in files.values() {
+            let content = std::fs::read_to_string(path)?;
+            policy
+                .add(&content)
+                .map_err(|e| Error::ConfigError(format!("{}: {}", path.display(), e)))?;
+        }
+        Ok(policy)
+    }
+
+    /// Append the rules of a preset file.
+    pub fn add(&mut self, content: &str) -> std::result::Result<(), String> {
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            let (action, pattern) = match line.split_once(char::is_whitespace) {
+                Some(("enable", pattern)) => (PresetAction::Enable, pattern),
+                Some(("disable", pattern)) => (PresetAction::Disable, pattern),
+                _ => return Err(format!("line {}: expected enable or disable", number + 1)),
+            };
+            // Extra words, such as instance names, are not supported
+            let pattern = pattern.split_whitespace().next().unwrap_or_default();
+            self.rules.push((action, pattern.to_string()));
+        }
+        Ok(())
+    }
+
+    /// What to do with a service. Patterns may name the service with or
+    /// without a `.service` suffix.
+    pub fn action(&self, service: &str) -> PresetAction {
+        let unit = format!("{}.service", service);
+        self.rules
+            .iter()
+            .find(|(_, pattern)| glob_match(pattern, service) || glob_match(pattern, &unit))
+            .map_or(PresetAction::Enable, |(action, _)| *action)
+    }
+}
+
+/// Match a shell-style pattern with `*` and `?`.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let (p, n): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    let (mut pi, mut ni) = (0, 0);
+    // Position after the last `*`, and the name position it matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while ni < n.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == n[ni]) {
+            pi += 1;
+            ni += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi + 1, ni));
+            pi += 1;
+        } else if let Some((after, matched)) = star {
+            pi = after;
+            ni = matched + 1;
+            star = Some((after, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preset_policy() {
+        let mut policy = PresetPolicy::default();
+        policy
+            .add("# vendor defaults\nenable sshd.service\ndisable debug-*\nenable getty@*\n")
+            .unwrap();
+        policy.add("disable *\n").unwrap();
+        assert_eq!(policy.action("sshd"), PresetAction::Enable);
+        assert_eq!(policy.action("debug-shell"), PresetAction::Disable);
+        assert_eq!(policy.action("getty@tty1"), PresetAction::Enable);
+        assert_eq!(policy.action("cups"), PresetAction::Disable);
+        assert_eq!(PresetPolicy::default().action("cups"), PresetAction::Enable);
+        assert!(policy.add("start sshd\n").is_err());
+
+        assert!(glob_match("a*b?d", "axxbcd"));
+        assert!(!glob_match("a*b?d", "axxbd"));
+        assert!(PresetMode::EnableOnly.allows(PresetAction::Enable));
+        assert!(!PresetMode::EnableOnly.allows(PresetAction::Disable));
+    }
+
+    #[test]
+    fn test_enablement_records_and_preset_files() {
+        let root = std::env::temp_dir().join(format!("boss-enablement-{}", std::process::id()));
+        let store = EnablementStore::new(root.join("system"));
+        assert!(!store.exists());
+
+        let unit = root.join("services/web.toml");
+        store
+            .enable(
+                "web",
+                &["multi-user.target".into(), "graphical.target".into()],
+                &unit,
+            )
+            .unwrap();
+        store
+            .enable("db", &[], &root.join("services/db.toml"))
+            .unwrap();
+        assert!(store.exists());
+        assert_eq!(
+            store.targets("web"),
+            vec!["graphical.target", "multi-user.target"]
+        );
+        assert_eq!(
+            std::fs::read_link(root.join("system/multi-user.target.wants/web")).unwrap(),
+            unit
+        );
+        store.disable("web").unwrap();
+        assert!(!store.is_enabled("web"));
+        assert!(store.is_enabled("db"));
+
+        // /etc replaces the vendor file of the same name
+        let (etc, lib) = (root.join("etc"), root.join("lib"));
+        std::fs::create_dir_all(&etc).unwrap();
+        std::fs::create_dir_all(&lib).unwrap();
+        std::fs::write(lib.join("50-web.preset"), "enable web\n").unwrap();
+        std::fs::write(etc.join("50-web.preset"), "disable web\n").unwrap();
+        std::fs::write(lib.join("90-default.preset"), "disable *\n").unwrap();
+        let policy = PresetPolicy::load(&[etc, lib]).unwrap();
+        assert_eq!(policy.action("web"), PresetAction::Disable);
+        assert_eq!(policy.action("db"), PresetAction::Disable);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -33,6 +33,13 @@ pub enum Error {
     #[error("Service is masked: {0}")]
     ServiceMasked(String),
 
+    /// Packages the service requires are not installed
+    #[error("Service {service} is uninstallable, missing packages: {}", packages.join(", "))]
+    PackagesMissing {
+        service: String,
+        packages: Vec<String>,
+    },
+
     /// Service dependency error
     #[error("Service dependency error: {service} depends on {dependency}: {reason}")]
     DependencyError {
@@ -97,6 +104,10 @@ pub enum Error {
     #[error("Timer error for {name}: {reason}")]
     TimerError { name: String, reason: String },
 
+    /// Time server query error
+    #[error("Failed to query time server {server}: {reason}")]
+    TimeSyncError { server: String, reason: String },
+
     /// Template instantiation error
     #[error("Failed to instantiate template {template} with instance {instance}: {reason}")]
     TemplateError {
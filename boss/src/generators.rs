@@ -0,0 +1,231 @@
+//! Generators: programs that write unit files at boot.
+//!
+//! Before service definitions are loaded, every executable in the generator
+//! directories is run with the generated unit directory as its only
+//! argument, the way systemd runs its generators. A generator turns some
+//! other configuration into units: fstab entries into mount services, a
+//! kernel command line option into a debug shell, and so on. Packages extend
+//! boot by installing a generator instead of patching init.
+//!
+//! Generators run in name order. One in /etc/buckos/generators replaces a
+//! /usr/lib/buckos/generators one of the same name; an empty file or a link
+//! to /dev/null disables it. The output directory is emptied before every
+//! run, so units of a removed generator go away on the next boot or daemon
+//! reload. Units in the services directory take precedence over generated
+//! units of the same name.
+
+use crate::error::{Error, Result};
+use std::collections::BTreeMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Generator directories, highest priority first.
+pub const GENERATOR_DIRS: &[&str] = &["/etc/buckos/generators", "/usr/lib/buckos/generators"];
+
+/// Where generators write their units.
+pub const GENERATED_DIR: &str = "/run/buckos/generator";
+
+/// How long a generator may run before it is killed.
+pub const GENERATOR_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The generators of a system and where their units go.
+#[derive(Debug, Clone)]
+pub struct Generators {
+    dirs: Vec<PathBuf>,
+    output: PathBuf,
+    timeout: Duration,
+}
+
+impl Default for Generators {
+    fn default() -> Self {
+        Self::new(
+            GENERATOR_DIRS.iter().map(PathBuf::from).collect(),
+            GENERATED_DIR,
+        )
+    }
+}
+
+/// How one generator run went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratorRun {
+    /// File name of the generator
+    pub name: String,
+    /// Why it failed, if it did
+    pub error: Option<String>,
+}
+
+impl Generators {
+    /// Generators in `dirs`, highest priority first, writing to `output`.
+    pub fn new(dirs: Vec<PathBuf>, output: impl Into<PathBuf>) -> Self {
+        Self {
+            dirs,
+            output: output.into(),
+            timeout: GENERATOR_TIMEOUT,
+        }
+    }
+
+    /// Kill generators that run longer than `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Directory the generated units are in.
+    pub fn output(&self) -> &Path {
+        &self.output
+    }
+
+    /// Generators to run, by file name, skipping disabled ones.
+    pub fn find(&self) -> BTreeMap<String, PathBuf> {
+        // By file name, keeping the first directory's file
+        let mut found: BTreeMap<String, PathBuf> = BTreeMap::new();
+        for dir in &self.dirs {
+            for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                found.entry(name).or_insert_with(|| entry.path());
+            }
+        }
+        found.retain(|_, path| is_executable(path));
+        found
+    }
+
+    /// Empty the output directory and run every generator.
+    ///
+    /// A generator that fails or times out is logged and skipped; the
+    /// units it wrote before failing are kept.
+    pub async fn run(&self) -> Result<Vec<GeneratorRun>> {
+        if self.output.exists() {
+            std::fs::remove_dir_all(&self.output)?;
+        }
+        std::fs::create_dir_all(&self.output)?;
+
+        let mut runs = Vec::new();
+        for (name, path) in self.find() {
+            let error = self.run_one(&path).await.err().map(|e| e.to_string());
+            match &error {
+                None => debug!(generator = %name, "Generator finished"),
+                Some(e) => warn!(generator = %name, error = %e, "Generator failed"),
+            }
+            runs.push(GeneratorRun { name, error });
+        }
+        if !runs.is_empty() {
+            info!(count = runs.len(), dir = ?self.output, "Ran generators");
+        }
+        Ok(runs)
+    }
+
+    async fn run_one(&self, path: &Path) -> Result<()> {
+        let child = tokio::process::Command::new(path)
+            .arg(&self.output)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let output = tokio::time::timeout(self.timeout, child.wait_with_output())
+            .await
+            .map_err(|_| Error::Other(format!("timed out after {:?}", self.timeout)))??;
+        if output.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(Error::Other(match stderr.trim() {
+            "" => format!("exited with {}", output.status),
+            message => format!("exited with {}: {}", output.status, message),
+        }))
+    }
+}
+
+/// Whether `path` is a non-empty executable file, following links.
+fn is_executable(path: &Path) -> bool {
+    std::fs::metadata(path).is_ok_and(|meta| {
+        meta.is_file() && meta.len() > 0 && meta.permissions().mode() & 0o111 != 0
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_script(path: &Path, script: &str) {
+        std::fs::write(path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run_generators() {
+        let dir = std::env::temp_dir().join(format!("boss-generators-{}", std::process::id()));
+        let (etc, lib) = (dir.join("etc"), dir.join("lib"));
+        std::fs::create_dir_all(&etc).unwrap();
+        std::fs::create_dir_all(&lib).unwrap();
+
+        write_script(
+            &lib.join("debug-shell"),
+            r#"printf '[Service]\nExecStart=/bin/sh\n' > "$1/debug-shell.service""#,
+        );
+        write_script(&lib.join("fstab"), "echo lib > \"$1/fstab\"");
+        // /etc replaces and disables generators of the same name
+        std::os::unix::fs::symlink("/dev/null", etc.join("fstab")).unwrap();
+        write_script(&lib.join("broken"), "echo 'no fstab' >&2; exit 3");
+        write_script(&lib.join("slow"), "sleep 30");
+        std::fs::write(lib.join("not-executable"), "#!/bin/sh\n").unwrap();
+
+        let generators = Generators::new(vec![etc, lib], dir.join("out"))
+            .with_timeout(Duration::from_millis(500));
+        assert_eq!(
+            generators.find().into_keys().collect::<Vec<_>>(),
+            vec!["broken", "debug-shell", "slow"]
+        );
+
+        std::fs::create_dir_all(dir.join("out")).unwrap();
+        std::fs::write(dir.join("out/stale.service"), "").unwrap();
+        let runs = generators.run().await.unwrap();
+        assert_eq!(runs.len(), 3);
+        assert_eq!(
+            runs[0].error.as_deref(),
+            Some("exited with exit status: 3: no fstab")
+        );
+        assert_eq!(runs[1].error, None);
+        assert_eq!(runs[2].error.as_deref(), Some("timed out after 500ms"));
+
+        let generated: Vec<_> = std::fs::read_dir(generators.output())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(generated, vec!["debug-shell.service"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_generated_units_are_loaded() {
+        use crate::manager::ServiceManager;
+
+        let dir = std::env::temp_dir().join(format!("boss-generated-{}", std::process::id()));
+        let (lib, services) = (dir.join("lib"), dir.join("services"));
+        std::fs::create_dir_all(&lib).unwrap();
+        std::fs::create_dir_all(&services).unwrap();
+        write_script(
+            &lib.join("units"),
+            r#"for unit in debug-shell getty; do
+                printf '[Service]\nExecStart=/bin/%s\n' "$unit" > "$1/$unit.service"
+            done"#,
+        );
+        std::fs::write(
+            services.join("getty.service"),
+            "[Unit]\nDescription=Configured getty\n[Service]\nExecStart=/sbin/agetty\n",
+        )
+        .unwrap();
+
+        let manager = ServiceManager::new(services)
+            .with_generators(Generators::new(vec![lib], dir.join("out")));
+        manager.load_services().await.unwrap();
+        let mut names = manager.list_services().await;
+        names.sort();
+        assert_eq!(names, vec!["debug-shell", "getty"]);
+        let getty = manager.get_status("getty").await.unwrap();
+        assert_eq!(getty.description, "Configured getty");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
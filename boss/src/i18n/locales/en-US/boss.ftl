@@ -0,0 +1,175 @@
+# English messages of the boss command line
+#
+# Each message is looked up with tr!("<id>"). Translations copy this file
+# to <locale>/boss.ftl under the locale directory and may leave out
+# messages, which are then shown in English.
+
+## Status
+
+status-no-services-found = No services found
+
+## List
+
+list-services = Services:
+
+## List timers
+
+list-timers-no-timers-found = No timers found
+list-timers-timers-listed = { $count } timers listed.
+
+## Enable
+
+enable-enabled = Enabled { $name }
+
+## Disable
+
+disable-disabled = Disabled { $name }
+
+## Preset
+
+preset-left-unchanged = Left { $name } unchanged
+
+## Mask
+
+mask-masked = Masked { $name }
+
+## Unmask
+
+unmask-unmasked = Unmasked { $name }
+
+## Logs
+
+logs-no-logs-found-for = No logs found for { $targets }
+
+## Journal
+
+journal-files-take-up = Journal files take up { $disk_usage } on disk.
+journal-no-boots-recorded = No boots recorded
+journal-removed-entries-files-freed = Removed { $entries_removed } entries ({ $files_removed } files), freed { $reclaimed } ({ $bytes_before } -> { $bytes_after }).
+
+## Seccomp
+
+seccomp-wrote-system-call-filter = Wrote system call filter to { $path }
+
+## Coredump
+
+coredump-pid-uid-gid = PID: { $pid } (uid { $uid }, gid { $gid })
+coredump-signal = Signal: { $signal_name } ({ $signal })
+coredump-timestamp = Timestamp: { $timestamp }
+coredump-command = Command: { $comm }
+coredump-executable = Executable: { $exe }
+coredump-service = Service: { $service }
+coredump-size-compressed = Size: { $size } ({ $stored_size } compressed){ $truncated }
+coredump-truncated = truncated
+
+## Deps
+
+deps-dependencies-for = Dependencies for { $name }:
+deps-requires = Requires: { $requires }
+deps-wants = Wants: { $wants }
+deps-before = Before: { $before }
+deps-after = After: { $after }
+deps-dependency-graph = Dependency Graph:
+deps-no-dependency-cycles = No dependency cycles
+deps-dependency-cycles = Dependency cycles:
+
+## Swap
+
+swap-inactive-swap-units = Inactive swap units:
+
+## Timesync
+
+timesync-clock-not-synchronized = Clock not synchronized
+timesync-clock-synchronized-with-last = Clock synchronized with { $server }, last at { $time }
+timesync-clock-synchronized-by-an = Clock synchronized by an NTP daemon
+timesync-server-stratum = Server:  { $server } (stratum { $stratum })
+timesync-offset = Offset:  { $offset }s
+timesync-delay = Delay:   { $delay }s
+
+## Provision
+
+provision-provisioning-completed = Provisioning completed { $at }
+provision-pending = pending
+provision-provisioning-pending-runs-on = Provisioning pending, runs on the next boot
+provision-provisioning-was-never-flagged = Provisioning was never flagged
+provision-provisioning-runs-again-on = Provisioning runs again on the next boot
+
+## Analyze
+
+analyze-no-boot-timing-data = No boot timing data available
+analyze-startup-finished-in-ms = Startup finished in { $ms }ms
+analyze-ms = { $duration_ms }ms { $name }
+analyze-no-critical-chain-data = No critical chain data available
+analyze-critical-chain = Critical chain:
+analyze-total-boot-time-ms = Total boot time: { $ms }ms
+analyze-startup-limits = Startup limits: { $limits }
+analyze-no-enabled-services = No enabled services
+analyze-critical-path = { $title } (* = critical path):
+analyze-planned-startup-order = Planned startup order
+analyze-last-boot-startup-order = Last boot startup order
+analyze-boot = boot
+analyze-total = (total)
+analyze-order = order
+analyze-depth = depth
+analyze-expected = expected
+analyze-chain = chain
+analyze-queued = queued
+analyze-service = service
+
+## Instantiate
+
+instantiate-instantiated = Instantiated { $template }@{ $instance }
+
+## New
+
+new-created-service-definition = Created service definition: { $path }
+
+## Shutdown
+
+shutdown-initiated = Shutdown initiated: { $message }
+shutdown-type = Shutdown type: { $shutdown_type }
+shutdown-note-no-running-init = Note: No running init process found. Control socket not available at /run/boss/control.sock
+
+## System
+
+system-services-running-failed = Services: { $services } ({ $running } running, { $failed } failed)
+system-orphans-none = Orphans:  none
+
+## Migrate
+
+migrate-migrated = Migrated { $source } -> { $dest }
+migrate-no-service-files-found = No .service files found in { $source }
+migrate-migrated-service-files-to = Migrated { $count } service files to { $dest_dir }
+
+## Verify units
+
+verify-units-ok = { $path }: ok
+
+## Edit unit
+
+edit-unit-removed = Removed { $target }
+edit-unit-saved = Saved { $target }
+edit-unit-discarded-changes-to = Discarded changes to { $target }
+edit-unit-reloaded-units-restart-to = Reloaded units; restart { $name } to apply the changes
+edit-unit-failed-to-reload = Failed to reload: { $message }
+
+## Print status
+
+print-status-state = State: { $state }
+print-status-missing-packages = Missing packages: { $missing_packages }
+print-status-status = Status: "{ $text }"
+print-status-watchdog-timeouts = Watchdog timeouts: { $watchdog_failures }
+print-status-masked-yes = Masked: yes
+print-status-enabled-yes = Enabled: yes
+print-status-label = Label: { $label }
+print-status-uptime = Uptime: { $hours }h { $minutes }m { $seconds }s
+print-status-restarts = Restarts: { $restarts }
+print-status-memory-peak = Memory: { $memory } (peak { $peak })
+print-status-io-read-written = IO: { $read } read, { $written } written
+print-status-health = Health: { $health }
+print-status-boot-time-ms = Boot time: { $boot_ms }ms
+
+## Crash
+
+crash-rescue-shell = buckos: init crashed, starting rescue shell { $shell }
+crash-freezing = buckos: init crashed and no rescue shell is available, freezing
@@ -0,0 +1,75 @@
+//! Translated messages of the boss command line
+//!
+//! Messages are [Fluent](https://projectfluent.org) resources looked up by
+//! id through [`tr!`](crate::tr), with English built in. Translations are
+//! read from `<dir>/<locale>/boss.ftl` as described in
+//! [`buckos_core::i18n`]; messages a translation lacks are shown in English.
+
+use buckos_core::i18n::Catalog;
+
+pub use buckos_core::i18n::FluentArgs;
+
+/// The messages of boss
+static CATALOG: Catalog = Catalog::new("boss.ftl", include_str!("locales/en-US/boss.ftl"));
+
+/// Look up a message, with optional `name = value` arguments
+///
+/// ```ignore
+/// println!("{}", tr!("service-started", name = name.as_str()));
+/// ```
+#[macro_export]
+macro_rules! tr {
+    ($id:literal) => {
+        $crate::i18n::message($id, None)
+    };
+    ($id:literal, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = $crate::i18n::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::i18n::message($id, Some(&args))
+    }};
+}
+
+/// Use the locale of the environment for all further messages
+pub fn init() {
+    CATALOG.init();
+}
+
+/// Format a message in the current locale, English until [`init`] is called
+pub fn message(id: &str, args: Option<&FluentArgs>) -> String {
+    CATALOG.message(id, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+
+    /// Every message boss uses has an English text, every English text is
+    /// used, and nothing printed skips `tr!`
+    #[test]
+    fn test_messages_have_english_text() {
+        let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let mut dirs = vec![src.clone()];
+        let mut sources = Vec::new();
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(&dir).unwrap() {
+                let path: PathBuf = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path.extension().is_some_and(|e| e == "rs")
+                    && !path.ends_with("i18n/mod.rs")
+                {
+                    let name = path.strip_prefix(&src).unwrap().display().to_string();
+                    sources.push((name, std::fs::read_to_string(&path).unwrap()));
+                }
+            }
+        }
+        let problems = buckos_core::i18n::check_sources(
+            &CATALOG,
+            sources
+                .iter()
+                .map(|(name, source)| (name.as_str(), source.as_str())),
+        );
+        assert!(problems.is_empty(), "{}", problems.join("\n"));
+    }
+}
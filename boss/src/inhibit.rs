@@ -0,0 +1,190 @@
+//! Inhibitor locks.
+//!
+//! A long-running operation, such as a package transaction, takes a lock
+//! over the control socket saying what it blocks and why. The lock is held
+//! for as long as the connection stays open, so a holder that dies cannot
+//! leave one behind. A shutdown request fails while a shutdown lock is
+//! held, unless it asks to wait for the locks to go or to proceed anyway.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// What a lock blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InhibitWhat {
+    /// Power off, reboot and halt
+    Shutdown,
+    /// Suspend and hibernate, for the tools that put the machine to sleep
+    Sleep,
+}
+
+impl std::fmt::Display for InhibitWhat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InhibitWhat::Shutdown => write!(f, "shutdown"),
+            InhibitWhat::Sleep => write!(f, "sleep"),
+        }
+    }
+}
+
+impl std::str::FromStr for InhibitWhat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "shutdown" => Ok(InhibitWhat::Shutdown),
+            "sleep" => Ok(InhibitWhat::Sleep),
+            _ => Err(format!("unknown inhibitor lock '{}' (shutdown, sleep)", s)),
+        }
+    }
+}
+
+/// A held lock.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Inhibitor {
+    pub id: u64,
+    pub what: Vec<InhibitWhat>,
+    /// Program or operation holding the lock
+    pub who: String,
+    pub why: String,
+    /// Process holding the lock, if known
+    pub pid: Option<u32>,
+    pub uid: Option<u32>,
+    pub since: DateTime<Utc>,
+}
+
+impl std::fmt::Display for Inhibitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.who, self.why)
+    }
+}
+
+/// The held locks.
+#[derive(Debug, Default)]
+pub struct InhibitorRegistry {
+    locks: Mutex<Vec<Inhibitor>>,
+    next_id: Mutex<u64>,
+    released: Notify,
+}
+
+impl InhibitorRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take a lock, returning its id for [`release`](Self::release).
+    pub fn take(
+        &self,
+        what: Vec<InhibitWhat>,
+        who: String,
+        why: String,
+        pid: Option<u32>,
+        uid: Option<u32>,
+    ) -> u64 {
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap_or_else(|e| e.into_inner());
+            *next_id += 1;
+            *next_id
+        };
+        self.lock().push(Inhibitor {
+            id,
+            what,
+            who,
+            why,
+            pid,
+            uid,
+            since: Utc::now(),
+        });
+        id
+    }
+
+    pub fn release(&self, id: u64) {
+        self.lock().retain(|lock| lock.id != id);
+        self.released.notify_waiters();
+    }
+
+    /// Every held lock, oldest first.
+    pub fn list(&self) -> Vec<Inhibitor> {
+        self.lock().clone()
+    }
+
+    /// The locks blocking `what`.
+    pub fn blocking(&self, what: InhibitWhat) -> Vec<Inhibitor> {
+        self.lock()
+            .iter()
+            .filter(|lock| lock.what.contains(&what))
+            .cloned()
+            .collect()
+    }
+
+    /// Wait up to `timeout` for the locks blocking `what` to be released.
+    ///
+    /// Returns the locks still held at the end.
+    pub async fn wait(&self, what: InhibitWhat, timeout: Duration) -> Vec<Inhibitor> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            // Register before checking, so a release in between wakes us
+            let released = self.released.notified();
+            let blocking = self.blocking(what);
+            if blocking.is_empty() || tokio::time::timeout_at(deadline, released).await.is_err() {
+                return blocking;
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Inhibitor>> {
+        self.locks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_locks_block_until_released() {
+        let registry = Arc::new(InhibitorRegistry::new());
+        let update = registry.take(
+            vec![InhibitWhat::Shutdown, InhibitWhat::Sleep],
+            "buckos".to_string(),
+            "Installing packages".to_string(),
+            Some(42),
+            Some(0),
+        );
+        let backup = registry.take(
+            vec![InhibitWhat::Sleep],
+            "backup".to_string(),
+            "Copying snapshots".to_string(),
+            None,
+            None,
+        );
+        assert_eq!(registry.list().len(), 2);
+        let blocking = registry.blocking(InhibitWhat::Shutdown);
+        assert_eq!(blocking.len(), 1);
+        assert_eq!(blocking[0].to_string(), "buckos (Installing packages)");
+
+        // Times out while held
+        let held = registry
+            .wait(InhibitWhat::Shutdown, Duration::from_millis(10))
+            .await;
+        assert_eq!(held.len(), 1);
+
+        let waiter = tokio::spawn({
+            let registry = Arc::clone(&registry);
+            async move {
+                registry
+                    .wait(InhibitWhat::Shutdown, Duration::from_secs(10))
+                    .await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        registry.release(update);
+        assert!(waiter.await.unwrap().is_empty());
+        assert_eq!(registry.blocking(InhibitWhat::Sleep)[0].id, backup);
+        assert!("hibernate".parse::<InhibitWhat>().is_err());
+    }
+}
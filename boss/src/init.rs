@@ -1,11 +1,34 @@
 //! Init system core - PID 1 duties and signal handling.
 
+use crate::boots;
+use crate::control::{
+    ControlCommand, ControlResponse, ControlServer, ServiceInfo, SystemStatus,
+    DEFAULT_CONTROL_SOCKET,
+};
+use crate::coredump::{self, CoredumpLimits};
+use crate::crash::{self, CrashHandler};
 use crate::error::{Error, Result};
+use crate::generators::{Generators, GENERATED_DIR, GENERATOR_DIRS};
+use crate::inhibit::{InhibitWhat, InhibitorRegistry};
+use crate::journal_vacuum::JournalLimits;
 use crate::manager::ServiceManager;
+use crate::packages::PACKAGE_DB_DIR;
+use crate::provision::Provisioner;
+use crate::scheduler::StartupLimits;
+use crate::service::ServiceState;
+use crate::swap::{self, SwapConfig};
+use crate::syslog::{RemoteSyslogConfig, SyslogForwarder};
+use crate::volatile::{self, read_only};
+use buckos_core::compress::Compression;
+use buckos_core::machine_id::MACHINE_ID_PATH;
+use buckos_core::MachineId;
 use nix::mount::{mount, MsFlags};
 use nix::sys::reboot::{reboot, RebootMode};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::UnixStream;
 use tokio::signal::unix::{signal, SignalKind};
 use tokio::sync::broadcast;
 use tracing::{info, warn};
@@ -19,6 +42,36 @@ pub struct InitConfig {
     pub mount_filesystems: bool,
     /// Whether to enforce PID 1 requirement
     pub require_pid1: bool,
+    /// Remote syslog collector for service output
+    pub remote_syslog: Option<RemoteSyslogConfig>,
+    /// Limits on concurrent service starts during boot
+    pub startup_limits: StartupLimits,
+    /// Disk usage limits of the persistent journal
+    pub journal_limits: JournalLimits,
+    /// Compress journal entries from earlier boots into archives
+    pub journal_compression: Option<Compression>,
+    /// Directory crash dumps are written to
+    pub crash_dir: PathBuf,
+    /// Swap to activate at boot
+    pub swap: SwapConfig,
+    /// Capture core dumps of crashing processes, within these limits
+    pub coredumps: Option<CoredumpLimits>,
+    /// Socket to accept boss commands on
+    pub control_socket: Option<PathBuf>,
+    /// Factory defaults to provision missing /etc entries from
+    pub factory_etc: Option<PathBuf>,
+    /// Overlay /etc with a tmpfs so changes to it are lost at shutdown,
+    /// while /var persists
+    pub volatile: bool,
+    /// Machine ID file to give an ID at boot if it has none
+    pub machine_id: Option<PathBuf>,
+    /// Package records of the package manager, for RequiresPackage
+    pub package_db: PathBuf,
+    /// Directories of generators to run before loading units, highest
+    /// priority first; none are run when empty
+    pub generator_dirs: Vec<PathBuf>,
+    /// Provisioning run on first boot, before the enabled services start
+    pub provisioning: Option<Provisioner>,
 }
 
 impl Default for InitConfig {
@@ -27,6 +80,23 @@ impl Default for InitConfig {
             services_dir: PathBuf::from("/etc/buckos/services"),
             mount_filesystems: true,
             require_pid1: true,
+            remote_syslog: None,
+            startup_limits: StartupLimits::default(),
+            journal_limits: JournalLimits::default(),
+            journal_compression: None,
+            crash_dir: PathBuf::from(crash::CRASH_DIR),
+            swap: SwapConfig {
+                fstab: Some(PathBuf::from("/etc/fstab")),
+                zram: None,
+            },
+            coredumps: Some(CoredumpLimits::default()),
+            control_socket: Some(PathBuf::from(DEFAULT_CONTROL_SOCKET)),
+            factory_etc: Some(PathBuf::from(volatile::FACTORY_ETC_DIR)),
+            volatile: false,
+            machine_id: Some(PathBuf::from(MACHINE_ID_PATH)),
+            package_db: PathBuf::from(PACKAGE_DB_DIR),
+            generator_dirs: GENERATOR_DIRS.iter().map(PathBuf::from).collect(),
+            provisioning: Some(Provisioner::default()),
         }
     }
 }
@@ -39,6 +109,8 @@ pub struct Init {
     manager: Arc<ServiceManager>,
     /// Shutdown signal sender
     shutdown_tx: broadcast::Sender<ShutdownType>,
+    /// Inhibitor locks taken over the control socket
+    inhibitors: Arc<InhibitorRegistry>,
 }
 
 /// Type of shutdown to perform.
@@ -61,16 +133,50 @@ impl Init {
             return Err(Error::NotPid1(pid));
         }
 
-        let manager = Arc::new(ServiceManager::new(config.services_dir.clone()));
+        let mut manager = ServiceManager::new(config.services_dir.clone())
+            .with_startup_limits(config.startup_limits)
+            .with_package_db(&config.package_db);
+        if !config.generator_dirs.is_empty() {
+            manager = manager.with_generators(Generators::new(
+                config.generator_dirs.clone(),
+                GENERATED_DIR,
+            ));
+        }
+        let manager = Arc::new(manager);
+        // Outside PID 1, orphaned service processes would go to the real init
+        if let Err(e) = manager.supervisor().become_subreaper() {
+            warn!(error = %e, "Failed to become child subreaper");
+        }
         let (shutdown_tx, _) = broadcast::channel(1);
 
         Ok(Self {
             config,
             manager,
             shutdown_tx,
+            inhibitors: Arc::new(InhibitorRegistry::new()),
         })
     }
 
+    /// Run the init system under the crash handler.
+    ///
+    /// A panic or fatal error in the main loop writes a crash dump. As PID 1
+    /// this never returns on failure, since init exiting panics the kernel;
+    /// a rescue shell is started on the console instead.
+    pub async fn run_supervised(self: Arc<Self>) -> Result<()> {
+        let pid1 = std::process::id() == 1;
+        let handler = CrashHandler::new(self.config.crash_dir.clone(), self.manager());
+        handler.install(pid1);
+
+        let init = Arc::clone(&self);
+        let result = handler
+            .supervise("main loop", async move { init.run().await })
+            .await;
+        if pid1 && result.is_err() {
+            crash::rescue_shell();
+        }
+        result
+    }
+
     /// Run the init system.
     pub async fn run(&self) -> Result<()> {
         info!("Buckos init system starting");
@@ -80,11 +186,67 @@ impl Init {
             self.mount_filesystems()?;
         }
 
+        self.prepare_root();
+
+        self.activate_swap();
+
+        if let Some(limits) = &self.config.coredumps {
+            let installed = std::env::current_exe()
+                .map_err(Error::from)
+                .and_then(|exe| coredump::install(&exe, limits));
+            match installed {
+                Ok(()) => info!("Capturing core dumps"),
+                Err(e) => warn!(error = %e, "Failed to set core_pattern"),
+            }
+        }
+
+        if let Some(syslog) = &self.config.remote_syslog {
+            self.manager
+                .journal()
+                .forward_to(SyslogForwarder::spawn(syslog.clone()));
+        }
+
+        // Stamp the journal entries of this boot with an ID of its own
+        self.manager
+            .journal()
+            .begin_boot(&boots::generate_boot_id());
+        self.manager.journal().start_writer();
+        if let Some(compression) = self.config.journal_compression {
+            self.manager.journal().set_compression(compression);
+        }
+        self.manager
+            .journal()
+            .set_limits(self.config.journal_limits);
+
         // Load service definitions
         self.manager.load_services().await?;
 
+        // Provision a new system before anything depending on it starts;
+        // a failed task is retried on the next boot, and never stops this one
+        if let Some(provisioner) = &self.config.provisioning {
+            if let Err(e) = provisioner.run(&self.manager).await {
+                warn!(error = %e, "Failed to provision the system");
+            }
+        }
+
+        // Listen for socket-activated services, before any is started so
+        // the ones started at boot get their sockets
+        self.manager.start_socket_listeners().await;
+
         // Start enabled services in parallel for faster boot
         self.manager.start_enabled_services_parallel().await?;
+        let timings = self.manager.get_boot_blame().await;
+        let boot_ms = self.manager.get_total_boot_time();
+        self.manager.journal().update_boot(|boot| {
+            boot.boot_duration_ms = Some(boot_ms);
+            boot.service_times_ms = timings
+                .iter()
+                .map(|t| (t.name.clone(), t.duration_ms))
+                .collect();
+        });
+
+        // Start services when their watched paths trigger
+        self.manager.start_path_watches().await;
 
         // Run the main event loop
         self.event_loop().await?;
@@ -124,6 +286,95 @@ impl Init {
         Ok(())
     }
 
+    /// Make a read-only or volatile root usable: overlay /etc in volatile
+    /// mode, provision it from factory defaults, give the machine an ID,
+    /// and keep the journal on /run while its directory is read-only.
+    fn prepare_root(&self) {
+        let etc = Path::new("/etc");
+        if read_only(Path::new("/")) {
+            info!("Root filesystem is read-only");
+        }
+        if self.config.volatile {
+            match volatile::mount_etc_overlay(etc, Path::new(volatile::ETC_OVERLAY_DIR)) {
+                Ok(()) => info!("Mounted volatile /etc"),
+                Err(e) => warn!(error = %e, "Failed to mount volatile /etc"),
+            }
+        }
+        if let Some(factory) = &self.config.factory_etc {
+            if !read_only(etc) {
+                match volatile::provision_factory(factory, etc) {
+                    Ok(created) if !created.is_empty() => info!(
+                        entries = created.len(),
+                        "Provisioned /etc from factory defaults"
+                    ),
+                    Ok(_) => {}
+                    Err(e) => warn!(error = %e, "Failed to provision /etc from factory defaults"),
+                }
+            }
+        }
+
+        let journal = self.manager.journal();
+        let machine_id = match &self.config.machine_id {
+            Some(path) => {
+                match volatile::setup_machine_id(path, Path::new(volatile::RUNTIME_MACHINE_ID)) {
+                    Ok((id, source)) => {
+                        info!(machine_id = %id, source = ?source, "Machine ID set up");
+                        Some(id)
+                    }
+                    Err(e) => {
+                        warn!(error = %e, "Failed to set up the machine ID");
+                        None
+                    }
+                }
+            }
+            None => MachineId::read(),
+        };
+        if let Some(id) = machine_id {
+            journal.set_machine_id(&id);
+        }
+
+        if read_only(journal.log_dir()) {
+            info!(
+                dir = %journal.log_dir().display(),
+                "Journal directory is read-only, logging to {} until flushed",
+                volatile::RUNTIME_JOURNAL_DIR
+            );
+            journal.use_runtime_dir(PathBuf::from(volatile::RUNTIME_JOURNAL_DIR));
+        }
+    }
+
+    /// Activate fstab swap entries and the zram device. Failures are
+    /// logged but never stop the boot.
+    fn activate_swap(&self) {
+        if let Some(fstab) = &self.config.swap.fstab {
+            let units = std::fs::read_to_string(fstab)
+                .map(|s| swap::parse_fstab(&s))
+                .unwrap_or_default();
+            for unit in units {
+                match unit.activate() {
+                    Ok(()) => info!(unit = %unit.unit_name(), "Activated swap"),
+                    Err(e) if unit.nofail => {
+                        info!(unit = %unit.unit_name(), error = %e, "Skipped swap")
+                    }
+                    Err(e) => {
+                        warn!(unit = %unit.unit_name(), error = %e, "Failed to activate swap")
+                    }
+                }
+            }
+        }
+
+        if let Some(zram) = &self.config.swap.zram {
+            match swap::setup_zram(zram) {
+                Ok(device) => info!(
+                    device = %device.display(),
+                    algorithm = %zram.algorithm,
+                    "Activated zram swap"
+                ),
+                Err(e) => warn!(error = %e, "Failed to set up zram swap"),
+            }
+        }
+    }
+
     /// Mount a filesystem.
     fn mount_fs(&self, source: &str, target: &str, fstype: &str, flags: MsFlags) -> Result<()> {
         let target_path = std::path::Path::new(target);
@@ -150,16 +401,56 @@ impl Init {
         let mut sigint = signal(SignalKind::interrupt())?;
 
         let mut shutdown_rx = self.shutdown_tx.subscribe();
+        let timers_changed = self.manager.timers_changed();
+        let reap_requested = self.manager.supervisor().reap_requested();
+        let reboot_requested = self.manager.reboot_requested();
+        let control = self.start_control_server().await;
 
         info!("Init system ready, entering event loop");
 
         loop {
+            // Start services whose timers elapsed, act on missed watchdog
+            // deadlines, and wake for whichever comes next
+            let next_timer = self.manager.run_due_timers().await;
+            let next_watchdog = self.manager.check_watchdogs().await;
+            let next_wakeup = [next_timer, next_watchdog]
+                .into_iter()
+                .flatten()
+                .min()
+                .unwrap_or(std::time::Duration::from_secs(3600));
+
             tokio::select! {
+                // Wake when the next timer elapses or watchdog is due
+                _ = tokio::time::sleep(next_wakeup) => {}
+
+                // Recompute the next wakeup when a timer is added or a
+                // service with a watchdog starts
+                _ = timers_changed.notified() => {}
+
+                // A service that keeps missing its watchdog escalated to a
+                // reboot
+                _ = reboot_requested.notified() => {
+                    warn!("Watchdog action requested a reboot");
+                    self.shutdown(ShutdownType::Reboot).await?;
+                    break;
+                }
+
+                // Answer a boss command
+                stream = accept_control(control.as_ref()) => match stream {
+                    Ok(stream) => self.spawn_control(stream),
+                    Err(e) => warn!(error = %e, "Failed to accept control connection"),
+                },
+
                 // Handle SIGCHLD - reap zombie processes
                 _ = sigchld.recv() => {
                     self.handle_sigchld().await;
                 }
 
+                // Reap children left while a spawn was in flight
+                _ = reap_requested.notified() => {
+                    self.handle_sigchld().await;
+                }
+
                 // Handle SIGTERM - initiate shutdown
                 _ = sigterm.recv() => {
                     info!("Received SIGTERM, initiating shutdown");
@@ -187,6 +478,40 @@ impl Init {
         Ok(())
     }
 
+    /// Listen on the control socket, if one is configured.
+    async fn start_control_server(&self) -> Option<ControlServer> {
+        let mut server = ControlServer::new(self.config.control_socket.as_ref()?);
+        match server.start().await {
+            Ok(()) => Some(server),
+            Err(e) => {
+                warn!(error = %e, "Failed to start control socket");
+                None
+            }
+        }
+    }
+
+    /// Answer a control connection in the background.
+    fn spawn_control(&self, mut stream: UnixStream) {
+        let manager = self.manager();
+        let shutdown_tx = self.shutdown_tx.clone();
+        let inhibitors = Arc::clone(&self.inhibitors);
+        tokio::spawn(async move {
+            let response = match ControlServer::read_command(&mut stream).await {
+                Ok(ControlCommand::Inhibit { what, who, why }) => {
+                    hold_inhibitor(&inhibitors, stream, what, who, why).await;
+                    return;
+                }
+                Ok(command) => handle_command(&manager, &shutdown_tx, &inhibitors, command).await,
+                Err(e) => ControlResponse::Error {
+                    message: e.to_string(),
+                },
+            };
+            if let Err(e) = ControlServer::write_response(&mut stream, &response).await {
+                warn!(error = %e, "Failed to answer control command");
+            }
+        });
+    }
+
     /// Handle SIGCHLD signal - reap zombies and notify service manager.
     async fn handle_sigchld(&self) {
         let supervisor = self.manager.supervisor();
@@ -204,6 +529,26 @@ impl Init {
         // Stop all services
         self.manager.stop_all_services().await?;
 
+        // Page swapped memory back in while the swap devices still exist
+        if self.config.require_pid1 {
+            for (path, e) in swap::deactivate_all() {
+                warn!(path = %path.display(), error = %e, "Failed to deactivate swap");
+            }
+        }
+
+        self.manager
+            .journal()
+            .update_boot(|boot| boot.ended = Some(chrono::Utc::now()));
+        if let Err(e) = self.manager.journal().sync() {
+            warn!(error = %e, "Failed to sync journal");
+        }
+        // Keep the journal of this boot if the root was made writable
+        if self.manager.journal().runtime_dir().is_some() {
+            if let Err(e) = self.manager.journal().flush() {
+                warn!(error = %e, "Journal left on /run, its entries will be lost");
+            }
+        }
+
         // Sync filesystems
         unsafe {
             libc::sync();
@@ -237,12 +582,249 @@ impl Init {
     }
 }
 
+/// Accept a control connection; never completes without a server.
+async fn accept_control(server: Option<&ControlServer>) -> Result<UnixStream> {
+    match server {
+        Some(server) => server.accept().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Hold an inhibitor lock until the client closes the connection.
+async fn hold_inhibitor(
+    inhibitors: &InhibitorRegistry,
+    mut stream: UnixStream,
+    what: Vec<InhibitWhat>,
+    who: String,
+    why: String,
+) {
+    let cred = stream.peer_cred().ok();
+    let pid = cred.and_then(|c| c.pid()).map(|pid| pid as u32);
+    let id = inhibitors.take(what, who, why, pid, cred.map(|c| c.uid()));
+    let response = ControlResponse::Success {
+        message: format!("Inhibitor lock {} taken", id),
+    };
+    if ControlServer::write_response(&mut stream, &response)
+        .await
+        .is_ok()
+    {
+        // Nothing more is sent; any read ending means the holder is gone
+        let mut buf = [0u8; 64];
+        while matches!(stream.read(&mut buf).await, Ok(n) if n > 0) {}
+    }
+    inhibitors.release(id);
+}
+
+/// Carry out a control command.
+async fn handle_command(
+    manager: &ServiceManager,
+    shutdown_tx: &broadcast::Sender<ShutdownType>,
+    inhibitors: &InhibitorRegistry,
+    command: ControlCommand,
+) -> ControlResponse {
+    let reply = |result: Result<()>, message: String| match result {
+        Ok(()) => ControlResponse::Success { message },
+        Err(e) => ControlResponse::Error {
+            message: e.to_string(),
+        },
+    };
+    match command {
+        ControlCommand::StartService { name } => reply(
+            manager.start_service(&name).await,
+            format!("Started {}", name),
+        ),
+        ControlCommand::StopService { name } => reply(
+            manager.stop_service(&name).await,
+            format!("Stopped {}", name),
+        ),
+        ControlCommand::RestartService { name } => reply(
+            manager.restart_service(&name).await,
+            format!("Restarted {}", name),
+        ),
+        ControlCommand::ReloadService { name } => reply(
+            manager.reload_service(&name).await,
+            format!("Reloaded {}", name),
+        ),
+        ControlCommand::EnableService { name } => reply(
+            manager.enable_service(&name).await,
+            format!("Enabled {}", name),
+        ),
+        ControlCommand::DisableService { name } => reply(
+            manager.disable_service(&name).await,
+            format!("Disabled {}", name),
+        ),
+        ControlCommand::GetServiceStatus { name } => match manager.get_status(&name).await {
+            Ok(status) => ControlResponse::ServiceStatus {
+                name: status.name,
+                state: status.state.to_string(),
+                pid: status.main_pid,
+                uptime_secs: status.uptime_secs,
+            },
+            Err(e) => ControlResponse::Error {
+                message: e.to_string(),
+            },
+        },
+        ControlCommand::GetAllStatus | ControlCommand::ListServices => {
+            let services = manager
+                .get_all_status()
+                .await
+                .into_iter()
+                .map(|status| ServiceInfo {
+                    name: status.name,
+                    state: status.state.to_string(),
+                    enabled: status.enabled,
+                    description: Some(status.description).filter(|d| !d.is_empty()),
+                })
+                .collect();
+            ControlResponse::ServiceList { services }
+        }
+        ControlCommand::GetSystemStatus => {
+            let statuses = manager.get_all_status().await;
+            let count = |state: ServiceState| statuses.iter().filter(|s| s.state == state).count();
+            ControlResponse::SystemStatus {
+                status: SystemStatus {
+                    pid: std::process::id(),
+                    services: statuses.len(),
+                    running: count(ServiceState::Running),
+                    failed: count(ServiceState::Failed),
+                    orphans: manager.supervisor().orphan_counts().await,
+                },
+            }
+        }
+        ControlCommand::StartTransient { unit } => match manager.start_transient(&unit).await {
+            Ok(name) => ControlResponse::Success {
+                message: format!("Running as unit: {}.service", name),
+            },
+            Err(e) => ControlResponse::Error {
+                message: e.to_string(),
+            },
+        },
+        ControlCommand::Shutdown {
+            shutdown_type,
+            force,
+            wait_secs,
+        } => {
+            if !force {
+                let timeout = Duration::from_secs(wait_secs.unwrap_or(0));
+                let held = inhibitors.wait(InhibitWhat::Shutdown, timeout).await;
+                if !held.is_empty() {
+                    let held: Vec<String> = held.iter().map(|lock| lock.to_string()).collect();
+                    return ControlResponse::Error {
+                        message: format!(
+                            "shutdown is inhibited by {}; use --force to shut down anyway",
+                            held.join(", ")
+                        ),
+                    };
+                }
+            }
+            let sessions = manager.sessions().await;
+            if !force && !sessions.is_empty() {
+                let who: Vec<String> = sessions
+                    .iter()
+                    .map(|s| match &s.tty {
+                        Some(tty) => format!("{} on {}", s.user, tty),
+                        None => s.user.clone(),
+                    })
+                    .collect();
+                return ControlResponse::Error {
+                    message: format!(
+                        "users are logged in ({}); use --force to shut down anyway",
+                        who.join(", ")
+                    ),
+                };
+            }
+            reply(
+                shutdown_tx
+                    .send(shutdown_type)
+                    .map(|_| ())
+                    .map_err(|_| Error::SignalError("Failed to send shutdown signal".to_string())),
+                format!("{:?}", shutdown_type),
+            )
+        }
+        ControlCommand::ListSessions => ControlResponse::SessionList {
+            sessions: manager.sessions().await,
+        },
+        ControlCommand::ListInhibitors => ControlResponse::InhibitorList {
+            inhibitors: inhibitors.list(),
+        },
+        // Answered by hold_inhibitor, which keeps the connection
+        ControlCommand::Inhibit { .. } => ControlResponse::Error {
+            message: "inhibitor locks are taken on a connection of their own".to_string(),
+        },
+        ControlCommand::FlushJournal => match manager.journal().flush() {
+            Ok(files) => ControlResponse::Success {
+                message: format!("Flushed {} journal files", files),
+            },
+            Err(e) => ControlResponse::Error {
+                message: format!("Failed to flush journal: {}", e),
+            },
+        },
+        ControlCommand::JournalStats => ControlResponse::JournalStats {
+            stats: manager.journal().ingest_stats(),
+        },
+        ControlCommand::ReloadDaemon => {
+            let result = manager.load_services().await;
+            // Listen on the sockets of new socket-activated services
+            if result.is_ok() {
+                manager.start_socket_listeners().await;
+            }
+            reply(result, "Reloaded service definitions".to_string())
+        }
+        ControlCommand::PackagesChanged => {
+            let installable = manager.packages_changed().await;
+            ControlResponse::Success {
+                message: if installable.is_empty() {
+                    "No services became installable".to_string()
+                } else {
+                    format!("Services now installable: {}", installable.join(", "))
+                },
+            }
+        }
+        ControlCommand::Notify {
+            name,
+            watchdog,
+            status,
+            busy,
+            fields,
+        } => {
+            let mut result = Ok(());
+            if status.is_some() || !fields.is_empty() {
+                result = manager
+                    .record_notify(&name, status.as_deref(), &fields)
+                    .await;
+            }
+            if watchdog && result.is_ok() {
+                result = manager.watchdog_ping(&name).await;
+            }
+            if busy && result.is_ok() {
+                result = manager.record_activity(&name).await;
+            }
+            reply(result, format!("Notified {}", name))
+        }
+        ControlCommand::Ping => ControlResponse::Pong,
+    }
+}
+
 /// Create a minimal init system for testing or non-PID1 operation.
 pub fn create_test_init(services_dir: PathBuf) -> Result<Init> {
     let config = InitConfig {
         services_dir,
         mount_filesystems: false,
         require_pid1: false,
+        remote_syslog: None,
+        startup_limits: StartupLimits::default(),
+        journal_limits: JournalLimits::default(),
+        journal_compression: None,
+        crash_dir: PathBuf::from(crash::CRASH_DIR),
+        swap: SwapConfig::default(),
+        coredumps: None,
+        control_socket: None,
+        factory_etc: None,
+        volatile: false,
+        machine_id: None,
+        package_db: PathBuf::from(PACKAGE_DB_DIR),
+        generator_dirs: Vec::new(),
+        provisioning: None,
     };
     Init::new(config)
 }
@@ -1,20 +1,49 @@
 //! Journal module for structured service logging.
 //!
 //! This module provides a simple journal implementation for capturing
-//! and storing service output (stdout/stderr) with timestamps.
+//! and storing service output (stdout/stderr) with timestamps. Entries are
+//! queued and written in batches, see [`journal_ingest`](crate::journal_ingest).
 
+use crate::boots::{BootIndex, BootRecord};
+use crate::journal_index::{self, JournalFilter};
+use crate::journal_ingest::{self, IngestStats, Ring, WriterState};
+use crate::journal_vacuum::{self, JournalLimits, VacuumCriteria, VacuumReport};
+use crate::syslog::SyslogForwarder;
+use crate::volatile;
+use buckos_core::compress::Compression;
+use buckos_core::MachineId;
 use chrono::{DateTime, Utc};
+use crossbeam_channel::{Receiver, Sender};
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fs::{File, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
 use std::path::PathBuf;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use tokio::sync::Notify;
+
+/// Fields of an entry taken from the entry itself rather than its
+/// [`fields`](JournalEntry::fields).
+pub const ENTRY_FIELDS: &[&str] = &[
+    "MESSAGE",
+    "PRIORITY",
+    "_PID",
+    "UNIT",
+    "_SYSTEMD_UNIT",
+    "SYSLOG_IDENTIFIER",
+    "BOSS_STREAM",
+    "_BOOT_ID",
+    "_MACHINE_ID",
+];
 
 /// Maximum number of log entries to keep in memory per service.
 const MAX_MEMORY_ENTRIES: usize = 1000;
 
+/// Bytes written between checks of the disk usage limits.
+const LIMIT_CHECK_BYTES: u64 = 1 << 20;
+
 /// Priority level for journal entries.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
@@ -53,6 +82,25 @@ impl std::fmt::Display for Priority {
     }
 }
 
+impl std::str::FromStr for Priority {
+    type Err = String;
+
+    /// Parse a syslog priority name (`err`, `warning`, ...) or number.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "emerg" | "emergency" | "0" => Priority::Emergency,
+            "alert" | "1" => Priority::Alert,
+            "crit" | "critical" | "2" => Priority::Critical,
+            "err" | "error" | "3" => Priority::Error,
+            "warning" | "warn" | "4" => Priority::Warning,
+            "notice" | "5" => Priority::Notice,
+            "info" | "6" => Priority::Info,
+            "debug" | "7" => Priority::Debug,
+            _ => return Err(format!("unknown priority '{}'", s)),
+        })
+    }
+}
+
 /// A single journal entry.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JournalEntry {
@@ -68,6 +116,20 @@ pub struct JournalEntry {
     pub message: String,
     /// Stream: stdout or stderr
     pub stream: String,
+    /// Position in the journal, increasing with each entry logged by this
+    /// process; orders entries that share a timestamp
+    #[serde(default)]
+    pub seqnum: u64,
+    /// Boot the entry was logged in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boot_id: Option<String>,
+    /// Machine the entry was logged on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub machine_id: Option<String>,
+    /// Further fields, by journald field name (`_UID`, `ERRNO`, `STATUS`,
+    /// ...)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, String>,
 }
 
 impl JournalEntry {
@@ -84,6 +146,10 @@ impl JournalEntry {
             },
             message: message.to_string(),
             stream: stream.to_string(),
+            seqnum: 0,
+            boot_id: None,
+            machine_id: None,
+            fields: BTreeMap::new(),
         }
     }
 
@@ -99,6 +165,28 @@ impl JournalEntry {
         self
     }
 
+    /// Create a journal entry with a further field.
+    pub fn with_field(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.fields.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Value of a field, by journald field name; those in
+    /// [`ENTRY_FIELDS`] are taken from the entry itself.
+    pub fn field(&self, name: &str) -> Option<Cow<'_, str>> {
+        match name {
+            "MESSAGE" => Some(Cow::Borrowed(&self.message)),
+            "PRIORITY" => Some(Cow::Owned((self.priority as u8).to_string())),
+            "_PID" => self.pid.map(|pid| Cow::Owned(pid.to_string())),
+            "UNIT" | "_SYSTEMD_UNIT" => Some(Cow::Owned(format!("{}.service", self.service))),
+            "SYSLOG_IDENTIFIER" => Some(Cow::Borrowed(&self.service)),
+            "BOSS_STREAM" => Some(Cow::Borrowed(&self.stream)),
+            "_BOOT_ID" => self.boot_id.as_deref().map(Cow::Borrowed),
+            "_MACHINE_ID" => self.machine_id.as_deref().map(Cow::Borrowed),
+            _ => self.fields.get(name).map(|v| Cow::Borrowed(v.as_str())),
+        }
+    }
+
     /// Format the entry for display.
     pub fn format(&self) -> String {
         let pid_str = self.pid.map(|p| format!("[{}]", p)).unwrap_or_default();
@@ -138,20 +226,258 @@ impl ServiceLogs {
 /// Journal for storing and retrieving service logs.
 pub struct Journal {
     /// In-memory logs per service
-    logs: Arc<RwLock<std::collections::HashMap<String, ServiceLogs>>>,
+    logs: RwLock<HashMap<String, ServiceLogs>>,
+    /// Entries of each service waiting for the writer
+    rings: RwLock<HashMap<String, Arc<Ring>>>,
+    /// Capacity of each ring
+    ring_capacity: usize,
+    /// Entries queued and entries written, for waiting on the writer
+    queued: AtomicU64,
+    written: AtomicU64,
+    /// Wakes the writer thread
+    wake: Sender<()>,
+    /// Handed to the writer thread once it is started
+    wake_rx: Mutex<Option<Receiver<()>>>,
+    /// Whether a writer thread drains the rings
+    writer_running: AtomicBool,
+    /// Held while draining the rings
+    writer: Mutex<WriterState>,
+    /// Notified after every drained batch
+    drained: Notify,
+    /// ID of the current boot, stamped on every entry
+    boot_id: OnceLock<String>,
+    /// ID of this machine, stamped on every entry
+    machine_id: OnceLock<String>,
+    /// The current boot, as recorded in the boot index
+    boot: Mutex<Option<BootRecord>>,
     /// Directory for persistent log files
     log_dir: PathBuf,
+    /// Directory on a tmpfs written to instead while `log_dir` is on a
+    /// read-only filesystem, until flushed
+    runtime_dir: Mutex<Option<PathBuf>>,
+    /// Sequence number of the next entry
+    next_seqnum: AtomicU64,
+    /// Remote syslog collector receiving a copy of each entry
+    forwarder: OnceLock<SyslogForwarder>,
+    /// Disk usage limits of the log files
+    limits: OnceLock<JournalLimits>,
+    /// Compression of entries archived from earlier boots
+    compression: OnceLock<Compression>,
+    /// Bytes written since the limits were last checked
+    unchecked_bytes: AtomicU64,
+    /// Held while appending to or rewriting log files
+    file_lock: Mutex<()>,
 }
 
 impl Journal {
     /// Create a new journal.
     pub fn new(log_dir: PathBuf) -> Self {
+        let (wake, wake_rx) = crossbeam_channel::bounded(1);
         Self {
-            logs: Arc::new(RwLock::new(std::collections::HashMap::new())),
+            logs: RwLock::new(HashMap::new()),
+            rings: RwLock::new(HashMap::new()),
+            ring_capacity: journal_ingest::RING_CAPACITY,
+            queued: AtomicU64::new(0),
+            written: AtomicU64::new(0),
+            wake,
+            wake_rx: Mutex::new(Some(wake_rx)),
+            writer_running: AtomicBool::new(false),
+            writer: Mutex::new(WriterState::new()),
+            drained: Notify::new(),
+            boot_id: OnceLock::new(),
+            machine_id: OnceLock::new(),
+            boot: Mutex::new(None),
             log_dir,
+            runtime_dir: Mutex::new(None),
+            next_seqnum: AtomicU64::new(1),
+            forwarder: OnceLock::new(),
+            limits: OnceLock::new(),
+            compression: OnceLock::new(),
+            unchecked_bytes: AtomicU64::new(0),
+            file_lock: Mutex::new(()),
+        }
+    }
+
+    /// Queue at most `capacity` entries per service for the writer.
+    pub fn with_ring_capacity(mut self, capacity: usize) -> Self {
+        self.ring_capacity = capacity.max(1);
+        self
+    }
+
+    /// Write queued entries from a thread of their own.
+    ///
+    /// Until this is called, [`log`](Self::log) writes entries itself and
+    /// entries [`submit`](Self::submit)ted wait for the next reader or
+    /// `log`. Only the first call starts a writer.
+    pub fn start_writer(self: &Arc<Self>) {
+        let Some(wake) = self
+            .wake_rx
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+        else {
+            return;
+        };
+        match journal_ingest::spawn_writer(Arc::downgrade(self), wake) {
+            Ok(()) => self.writer_running.store(true, Ordering::Release),
+            Err(e) => tracing::warn!(error = %e, "Failed to start journal writer"),
         }
     }
 
+    /// Stamp entries with `boot_id` from now on and add the boot to the
+    /// boot index.
+    ///
+    /// Only the first boot set is used.
+    pub fn begin_boot(&self, boot_id: &str) {
+        if self.boot_id.set(boot_id.to_string()).is_err() {
+            return;
+        }
+        *self.boot.lock().unwrap_or_else(|e| e.into_inner()) = Some(BootRecord::new(boot_id));
+        self.save_boot();
+    }
+
+    /// ID of the current boot, if one was begun.
+    pub fn boot_id(&self) -> Option<&str> {
+        self.boot_id.get().map(String::as_str)
+    }
+
+    /// Stamp entries with the ID of this machine from now on.
+    ///
+    /// Only the first ID set is used.
+    pub fn set_machine_id(&self, machine_id: &MachineId) {
+        let _ = self.machine_id.set(machine_id.to_string());
+    }
+
+    /// ID of this machine, if it was set.
+    pub fn machine_id(&self) -> Option<&str> {
+        self.machine_id.get().map(String::as_str)
+    }
+
+    /// Update the current boot's record in the boot index.
+    pub fn update_boot(&self, update: impl FnOnce(&mut BootRecord)) {
+        if let Some(boot) = self.boot.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            update(boot);
+        }
+        self.save_boot();
+    }
+
+    /// Boots with entries in the journal, oldest first, the current one
+    /// included even if the index could not be written yet.
+    pub fn boots(&self) -> BootIndex {
+        let mut index = BootIndex::load(&self.log_dir);
+        if let Some(boot) = self.boot.lock().unwrap_or_else(|e| e.into_inner()).clone() {
+            index.record(boot);
+        }
+        index
+    }
+
+    /// Write the current boot to the boot index, unless the journal
+    /// directory is read-only; [`flush`](Self::flush) does once it is not.
+    fn save_boot(&self) {
+        let Some(boot) = self.boot.lock().unwrap_or_else(|e| e.into_inner()).clone() else {
+            return;
+        };
+        if self.runtime_dir().is_some() {
+            return;
+        }
+        let mut index = BootIndex::load(&self.log_dir);
+        index.record(boot);
+        if let Err(e) = index.save(&self.log_dir) {
+            tracing::warn!(error = %e, "Failed to save boot index");
+        }
+    }
+
+    /// Forward entries to a remote syslog collector.
+    ///
+    /// Only the first forwarder set is used.
+    pub fn forward_to(&self, forwarder: SyslogForwarder) {
+        let _ = self.forwarder.set(forwarder);
+    }
+
+    /// Limit the disk space used by the log files.
+    ///
+    /// Only the first limits set are used.
+    pub fn set_limits(&self, limits: JournalLimits) {
+        let _ = self.limits.set(limits);
+        self.enforce_limits();
+    }
+
+    /// Compress entries from earlier boots into an archive per service.
+    ///
+    /// Only the first compression set is used.
+    pub fn set_compression(&self, compression: Compression) {
+        let _ = self.compression.set(compression);
+        self.archive_previous_boots();
+    }
+
+    /// Move entries from before the current boot into the compressed
+    /// archives, once the log directory is written to directly.
+    fn archive_previous_boots(&self) {
+        if self.runtime_dir().is_some() {
+            return;
+        }
+        let Some(compression) = self.compression.get() else {
+            return;
+        };
+        let _guard = self.file_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let boot = journal_vacuum::current_boot_start().unwrap_or_else(Utc::now);
+        match journal_vacuum::archive(&self.log_dir, *compression, boot) {
+            Ok(0) => {}
+            Ok(entries) => tracing::info!(
+                entries,
+                compression = %compression,
+                "Archived journal entries from earlier boots"
+            ),
+            Err(e) => tracing::warn!(error = %e, "Failed to archive journal"),
+        }
+    }
+
+    /// Remove old entries from the log files, keeping the current boot's.
+    pub fn vacuum(&self, criteria: &VacuumCriteria) -> std::io::Result<VacuumReport> {
+        let _guard = self.file_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Utc::now();
+        let boot = journal_vacuum::current_boot_start().unwrap_or(now);
+        journal_vacuum::vacuum(&self.log_dir, criteria, boot, now)
+    }
+
+    /// Bytes used by the log files.
+    pub fn disk_usage(&self) -> u64 {
+        journal_vacuum::disk_usage(&self.log_dir)
+    }
+
+    /// Vacuum the log files down to the configured limits.
+    fn enforce_limits(&self) {
+        // Entries on the tmpfs are bounded by the memory it may use
+        if self.runtime_dir().is_some() {
+            return;
+        }
+        let Some(limits) = self.limits.get().filter(|l| !l.is_unlimited()) else {
+            return;
+        };
+        let usage = self.disk_usage();
+        let free = journal_vacuum::free_space(&self.log_dir).unwrap_or(u64::MAX / 2);
+        let Some(allowed) = limits.allowed(usage, free).filter(|a| usage > *a) else {
+            return;
+        };
+        let criteria = VacuumCriteria {
+            max_size: Some(allowed),
+            max_age: None,
+        };
+        match self.vacuum(&criteria) {
+            Ok(report) => tracing::info!(
+                freed = report.reclaimed(),
+                entries = report.entries_removed,
+                "Vacuumed journal to stay within its disk usage limits"
+            ),
+            Err(e) => tracing::warn!(error = %e, "Failed to vacuum journal"),
+        }
+    }
+
+    /// Directory of the persistent log files.
+    pub fn log_dir(&self) -> &std::path::Path {
+        &self.log_dir
+    }
+
     /// Ensure the log directory exists.
     pub fn ensure_dir(&self) -> std::io::Result<()> {
         if !self.log_dir.exists() {
@@ -160,36 +486,307 @@ impl Journal {
         Ok(())
     }
 
+    /// Write log files to `dir` while the persistent log directory is on a
+    /// read-only filesystem. [`flush`](Self::flush) moves them over once it
+    /// is writable.
+    pub fn use_runtime_dir(&self, dir: PathBuf) {
+        *self.runtime_dir.lock().unwrap_or_else(|e| e.into_inner()) = Some(dir);
+    }
+
+    /// The tmpfs directory log files are written to, if not yet flushed.
+    pub fn runtime_dir(&self) -> Option<PathBuf> {
+        self.runtime_dir
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Append the log files written to the runtime directory to the
+    /// persistent ones and write there from now on.
+    ///
+    /// Returns the number of files flushed; fails, leaving the runtime
+    /// directory in use, while the log directory is still read-only.
+    pub fn flush(&self) -> std::io::Result<usize> {
+        self.drain();
+        let mut runtime = self.runtime_dir.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(dir) = runtime.clone() else {
+            return Ok(0);
+        };
+        self.ensure_dir()?;
+        if volatile::read_only(&self.log_dir) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::ReadOnlyFilesystem,
+                format!("{} is read-only", self.log_dir.display()),
+            ));
+        }
+
+        let guard = self.file_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut flushed = 0;
+        for entry in std::fs::read_dir(&dir)?.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|e| e != "log") {
+                continue;
+            }
+            let mut src = File::open(&path)?;
+            let mut dest = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.log_dir.join(entry.file_name()))?;
+            std::io::copy(&mut src, &mut dest)?;
+            dest.sync_all()?;
+            std::fs::remove_file(&path)?;
+            // The persistent file's index takes in the flushed entries on
+            // the next append
+            let _ = std::fs::remove_file(journal_index::index_path(&path));
+            flushed += 1;
+        }
+        *runtime = None;
+        drop(guard);
+        drop(runtime);
+        self.save_boot();
+        self.archive_previous_boots();
+        Ok(flushed)
+    }
+
+    /// Directories log files are read from, oldest entries first.
+    fn read_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = vec![self.log_dir.clone()];
+        dirs.extend(self.runtime_dir());
+        dirs
+    }
+
     /// Add a log entry.
+    ///
+    /// Without a writer thread the entry is written before this returns.
     pub async fn log(&self, entry: JournalEntry) {
-        let service = entry.service.clone();
+        self.submit(entry);
+        if !self.writer_running.load(Ordering::Acquire) {
+            self.drain();
+        }
+    }
+
+    /// Queue an entry for the writer without blocking.
+    ///
+    /// Returns false if the entry was dropped because the service has
+    /// more entries waiting than the ring holds.
+    pub fn submit(&self, mut entry: JournalEntry) -> bool {
+        entry.seqnum = self.next_seqnum.fetch_add(1, Ordering::Relaxed);
+        if entry.boot_id.is_none() {
+            entry.boot_id = self.boot_id.get().cloned();
+        }
+        if entry.machine_id.is_none() {
+            entry.machine_id = self.machine_id.get().cloned();
+        }
+        if let Some(forwarder) = self.forwarder.get() {
+            forwarder.submit(&entry);
+        }
+        let queued = self.ring(&entry.service).push(entry);
+        if queued {
+            self.queued.fetch_add(1, Ordering::Release);
+        }
+        let _ = self.wake.try_send(());
+        queued
+    }
 
-        // Add to memory
+    /// The ring of a service, created on its first entry.
+    fn ring(&self, service: &str) -> Arc<Ring> {
+        if let Some(ring) = self
+            .rings
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(service)
         {
-            let mut logs = self.logs.write().await;
-            logs.entry(service.clone()).or_default().add(entry.clone());
+            return Arc::clone(ring);
         }
+        let mut rings = self.rings.write().unwrap_or_else(|e| e.into_inner());
+        Arc::clone(
+            rings
+                .entry(service.to_string())
+                .or_insert_with(|| Arc::new(Ring::new(self.ring_capacity))),
+        )
+    }
+
+    /// Ingestion counters of every service that logged, by name.
+    pub fn ingest_stats(&self) -> Vec<IngestStats> {
+        let rings = self.rings.read().unwrap_or_else(|e| e.into_inner());
+        let mut stats: Vec<IngestStats> = rings
+            .iter()
+            .map(|(service, ring)| ring.stats(service))
+            .collect();
+        stats.sort_by(|a, b| a.service.cmp(&b.service));
+        stats
+    }
+
+    /// Write every queued entry, returning how many were written.
+    pub(crate) fn drain(&self) -> usize {
+        let mut state = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let rings: Vec<(String, Arc<Ring>)> = self
+            .rings
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(service, ring)| (service.clone(), Arc::clone(ring)))
+            .collect();
 
-        // Write to file
-        if let Err(e) = self.write_to_file(&entry) {
-            tracing::warn!(error = %e, "Failed to write log entry to file");
+        let mut total = 0;
+        loop {
+            let mut batch = Vec::new();
+            let mut taken = 0;
+            for (service, ring) in &rings {
+                taken += ring.drain(journal_ingest::BATCH_ENTRIES, &mut batch);
+                let dropped = ring.take_unreported();
+                if dropped > 0 {
+                    tracing::warn!(service = %service, dropped, "Dropped journal entries");
+                    let mut notice = JournalEntry::new(
+                        service,
+                        &format!(
+                            "Dropped {} entries logged faster than the journal could write them",
+                            dropped
+                        ),
+                        "journal",
+                    )
+                    .with_priority(Priority::Warning);
+                    notice.seqnum = self.next_seqnum.fetch_add(1, Ordering::Relaxed);
+                    batch.push(notice);
+                }
+            }
+            if batch.is_empty() {
+                break;
+            }
+            self.write_batch(&mut state, batch);
+            self.written.fetch_add(taken as u64, Ordering::Release);
+            total += taken;
+        }
+        drop(state);
+        self.drained.notify_waiters();
+
+        if self.unchecked_bytes.load(Ordering::Relaxed) >= LIMIT_CHECK_BYTES {
+            self.unchecked_bytes.store(0, Ordering::Relaxed);
+            self.enforce_limits();
         }
+        total
     }
 
-    /// Write an entry to the service's log file.
-    fn write_to_file(&self, entry: &JournalEntry) -> std::io::Result<()> {
-        let _ = self.ensure_dir();
+    /// Append a batch of entries to the services' log files, one JSON
+    /// object per line, index them and add them to memory.
+    fn write_batch(&self, state: &mut WriterState, batch: Vec<JournalEntry>) {
+        // Lines of each service, and where each entry's line starts in them
+        let mut lines: BTreeMap<&str, (String, journal_index::Appended)> = BTreeMap::new();
+        for entry in &batch {
+            match serde_json::to_string(entry) {
+                Ok(line) => {
+                    let (out, starts) = lines.entry(&entry.service).or_default();
+                    starts.push((out.len() as u64, entry));
+                    out.push_str(&line);
+                    out.push('\n');
+                }
+                Err(e) => tracing::warn!(error = %e, "Failed to serialize log entry"),
+            }
+        }
 
-        let log_path = self.log_dir.join(format!("{}.log", entry.service));
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&log_path)?;
+        let runtime = self.runtime_dir();
+        let dir = match &runtime {
+            Some(dir) => {
+                let _ = std::fs::create_dir_all(dir);
+                dir.clone()
+            }
+            None => {
+                let _ = self.ensure_dir();
+                self.log_dir.clone()
+            }
+        };
+        let guard = self.file_lock.lock().unwrap_or_else(|e| e.into_inner());
+        for (service, (lines, mut starts)) in lines {
+            let log_path = dir.join(format!("{}.log", service));
+            let written = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&log_path)
+                .and_then(|mut file| {
+                    let offset = file.metadata()?.len();
+                    file.write_all(lines.as_bytes())?;
+                    Ok(offset)
+                });
+            let offset = match written {
+                Ok(offset) => offset,
+                Err(e) => {
+                    tracing::warn!(path = %log_path.display(), error = %e, "Failed to write log entries to file");
+                    continue;
+                }
+            };
+            for (start, _) in &mut starts {
+                *start += offset;
+            }
+            let end = offset + lines.len() as u64;
+            if let Err(e) = journal_index::append(&mut state.index, &log_path, &starts, end) {
+                tracing::warn!(path = %log_path.display(), error = %e, "Failed to index log entries");
+            }
+            self.unchecked_bytes
+                .fetch_add(lines.len() as u64, Ordering::Relaxed);
+            // Entries on the tmpfs do not outlive the boot anyway
+            if runtime.is_none() {
+                state.dirty.insert(log_path);
+            }
+        }
+        drop(guard);
+
+        let mut logs = self.logs.write().unwrap_or_else(|e| e.into_inner());
+        for entry in batch {
+            logs.entry(entry.service.clone()).or_default().add(entry);
+        }
+    }
 
-        writeln!(file, "{}", entry.format())?;
+    /// Fsync the persistent log files written since the last sync; unless
+    /// `force`, only once [`SYNC_INTERVAL`](journal_ingest::SYNC_INTERVAL)
+    /// has passed since then.
+    pub(crate) fn sync_files(&self, force: bool) -> std::io::Result<()> {
+        let mut state = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        if state.dirty.is_empty()
+            || (!force && state.last_sync.elapsed() < journal_ingest::SYNC_INTERVAL)
+        {
+            return Ok(());
+        }
+        state.last_sync = std::time::Instant::now();
+        for path in std::mem::take(&mut state.dirty) {
+            match File::open(&path) {
+                Ok(file) => file.sync_data()?,
+                // Vacuumed or cleared since
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
         Ok(())
     }
 
+    /// Write every queued entry and fsync the log files.
+    pub fn sync(&self) -> std::io::Result<()> {
+        self.drain();
+        self.sync_files(true)
+    }
+
+    /// Wait until the entries queued so far are written.
+    async fn settle(&self) {
+        let target = self.queued.load(Ordering::Acquire);
+        if !self.writer_running.load(Ordering::Acquire) {
+            self.drain();
+            return;
+        }
+        while self.written.load(Ordering::Acquire) < target {
+            let drained = self.drained.notified();
+            if self.written.load(Ordering::Acquire) >= target {
+                return;
+            }
+            let _ = self.wake.try_send(());
+            // Write them here should the writer be stuck
+            if tokio::time::timeout(journal_ingest::SYNC_INTERVAL, drained)
+                .await
+                .is_err()
+            {
+                self.drain();
+            }
+        }
+    }
+
     /// Get log entries for a service.
     pub async fn get_logs(
         &self,
@@ -197,10 +794,11 @@ impl Journal {
         limit: Option<usize>,
         follow: bool,
     ) -> Vec<JournalEntry> {
+        self.settle().await;
         if follow {
             // For follow mode, we just return current entries
             // The caller should poll for updates
-            let logs = self.logs.read().await;
+            let logs = self.logs.read().unwrap_or_else(|e| e.into_inner());
             logs.get(service)
                 .map(|l| l.get_entries(limit))
                 .unwrap_or_default()
@@ -212,52 +810,136 @@ impl Journal {
             }
 
             // Fall back to memory
-            let logs = self.logs.read().await;
+            let logs = self.logs.read().unwrap_or_else(|e| e.into_inner());
             logs.get(service)
                 .map(|l| l.get_entries(limit))
                 .unwrap_or_default()
         }
     }
 
-    /// Read entries from a service's log file.
+    /// Read entries from a service's log file and its archive.
     fn read_from_file(&self, service: &str, limit: Option<usize>) -> Vec<JournalEntry> {
-        let log_path = self.log_dir.join(format!("{}.log", service));
-        if !log_path.exists() {
-            return Vec::new();
+        let mut lines: Vec<String> = Vec::new();
+        for dir in self.read_dirs() {
+            for path in journal_vacuum::service_files(&dir, service) {
+                match journal_vacuum::read_lines(&path) {
+                    Ok(read) => lines.extend(read),
+                    Err(e) => {
+                        tracing::warn!(path = %path.display(), error = %e, "Failed to read journal file")
+                    }
+                }
+            }
         }
 
-        let file = match File::open(&log_path) {
-            Ok(f) => f,
-            Err(_) => return Vec::new(),
-        };
-
-        let reader = BufReader::new(file);
-        let lines: Vec<String> = reader.lines().map_while(Result::ok).collect();
-
         let entries: Vec<JournalEntry> = match limit {
             Some(n) => lines.iter().rev().take(n).rev(),
             None => lines.iter().rev().take(lines.len()).rev(),
         }
-        .map(|line| {
-            // Parse the line back into a JournalEntry
-            // Format: "timestamp service [pid]PRIORITY: message"
-            JournalEntry {
-                timestamp: Utc::now(), // We lose timestamp precision here
-                service: service.to_string(),
-                pid: None,
-                priority: Priority::Info,
-                message: line.clone(),
-                stream: "stdout".to_string(),
-            }
-        })
+        .map(|line| parse_line(line, service))
         .collect();
 
         entries
     }
 
+    /// Entries of a service's log files and archives matching `filter`,
+    /// looking up the lines to read in the index where it can.
+    fn read_matching(&self, service: &str, filter: &JournalFilter) -> Vec<JournalEntry> {
+        let mut entries = Vec::new();
+        for dir in self.read_dirs() {
+            for path in journal_vacuum::service_files(&dir, service) {
+                let lines = match journal_index::lookup(&path, filter) {
+                    Some((offsets, end)) => journal_index::read_at(&path, &offsets, end),
+                    None => journal_vacuum::read_lines(&path),
+                };
+                match lines {
+                    Ok(lines) => entries.extend(
+                        lines
+                            .iter()
+                            .map(|line| parse_line(line, service))
+                            .filter(|entry| filter.matches(entry)),
+                    ),
+                    Err(e) => {
+                        tracing::warn!(path = %path.display(), error = %e, "Failed to read journal file")
+                    }
+                }
+            }
+        }
+        entries
+    }
+
+    /// Entries matching `filter` across all services, oldest first, at
+    /// most the last `limit`.
+    ///
+    /// Services without log files are searched in memory.
+    pub async fn query(&self, filter: &JournalFilter, limit: Option<usize>) -> Vec<JournalEntry> {
+        self.settle().await;
+        let services: Vec<String> = match filter.services() {
+            Some(services) => services,
+            None => {
+                let mut services = self.file_services();
+                services.extend(
+                    self.logs
+                        .read()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .keys()
+                        .cloned(),
+                );
+                services.into_iter().collect()
+            }
+        };
+
+        let mut entries = Vec::new();
+        for service in services {
+            let has_files = self
+                .read_dirs()
+                .iter()
+                .any(|dir| !journal_vacuum::service_files(dir, &service).is_empty());
+            if has_files {
+                entries.extend(self.read_matching(&service, filter));
+            } else if let Some(logs) = self
+                .logs
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .get(&service)
+            {
+                entries.extend(logs.entries.iter().filter(|e| filter.matches(e)).cloned());
+            }
+        }
+        entries.sort_by_key(|e| (e.timestamp, e.seqnum));
+        let skip = limit.map_or(0, |n| entries.len().saturating_sub(n));
+        entries.split_off(skip)
+    }
+
+    /// Services with log files.
+    fn file_services(&self) -> std::collections::BTreeSet<String> {
+        self.read_dirs()
+            .iter()
+            .filter_map(|dir| std::fs::read_dir(dir).ok())
+            .flatten()
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let name = e.file_name().to_string_lossy().into_owned();
+                journal_vacuum::journal_service(&name).map(str::to_string)
+            })
+            .collect()
+    }
+
+    /// Read the persisted entries of every service, oldest first.
+    pub fn read_all_from_files(&self) -> Vec<JournalEntry> {
+        self.drain();
+        let mut entries: Vec<JournalEntry> = self
+            .file_services()
+            .into_iter()
+            .flat_map(|service| self.read_from_file(&service, None))
+            .collect();
+        entries.sort_by_key(|e| (e.timestamp, e.seqnum));
+        entries
+    }
+
     /// Get all log entries across all services.
     pub async fn get_all_logs(&self, limit: Option<usize>) -> Vec<JournalEntry> {
-        let logs = self.logs.read().await;
+        self.settle().await;
+        let logs = self.logs.read().unwrap_or_else(|e| e.into_inner());
         let mut all_entries: Vec<JournalEntry> = logs
             .values()
             .flat_map(|l| l.entries.iter().cloned())
@@ -272,14 +954,33 @@ impl Journal {
         }
     }
 
+    /// The most recent entries across all services, without waiting for
+    /// the journal lock; `None` if it is held. For crash handling, where the
+    /// lock may be held by the code that failed.
+    pub fn try_recent(&self, limit: usize) -> Option<Vec<JournalEntry>> {
+        let logs = self.logs.try_read().ok()?;
+        let mut entries: Vec<JournalEntry> = logs
+            .values()
+            .flat_map(|l| l.entries.iter().cloned())
+            .collect();
+        entries.sort_by_key(|e| (e.timestamp, e.seqnum));
+        let skip = entries.len().saturating_sub(limit);
+        Some(entries.split_off(skip))
+    }
+
     /// Clear logs for a service.
     pub async fn clear(&self, service: &str) {
-        let mut logs = self.logs.write().await;
+        self.settle().await;
+        let mut logs = self.logs.write().unwrap_or_else(|e| e.into_inner());
         logs.remove(service);
 
-        // Also remove the log file
-        let log_path = self.log_dir.join(format!("{}.log", service));
-        let _ = std::fs::remove_file(log_path);
+        // Also remove the log files, archives and indexes
+        for dir in self.read_dirs() {
+            for path in journal_vacuum::service_files(&dir, service) {
+                let _ = std::fs::remove_file(journal_index::index_path(&path));
+                let _ = std::fs::remove_file(path);
+            }
+        }
     }
 
     /// Get the log file path for a service.
@@ -288,6 +989,26 @@ impl Journal {
     }
 }
 
+/// Parse a line of a log file.
+fn parse_line(line: &str, service: &str) -> JournalEntry {
+    serde_json::from_str(line).unwrap_or_else(|_| {
+        // Plain-text line from an older log file:
+        // "timestamp service [pid]PRIORITY: message"
+        JournalEntry {
+            timestamp: Utc::now(), // We lose timestamp precision here
+            service: service.to_string(),
+            pid: None,
+            priority: Priority::Info,
+            message: line.to_string(),
+            stream: "stdout".to_string(),
+            seqnum: 0,
+            boot_id: None,
+            machine_id: None,
+            fields: BTreeMap::new(),
+        }
+    })
+}
+
 impl Default for Journal {
     fn default() -> Self {
         Self::new(PathBuf::from("/var/log/buckos"))
@@ -340,7 +1061,7 @@ impl OutputReader {
                 Ok(_) => {
                     let entry = JournalEntry::new(&self.service, line.trim(), &self.stream)
                         .with_pid(self.pid);
-                    self.journal.log(entry).await;
+                    self.journal.submit(entry);
                 }
                 Err(_) => break,
             }
@@ -0,0 +1,304 @@
+//! Journal export for log shippers.
+//!
+//! Writes journal entries in systemd's Journal Export Format or as JSON
+//! Lines with journald field names, so tools that already consume
+//! `journalctl -o export` or `journalctl -o json` can ingest boss logs
+//! unchanged. Every entry carries a `__CURSOR`; passing the last cursor
+//! back exports only what was logged since.
+
+use crate::journal::JournalEntry;
+use buckos_core::MachineId;
+use std::io::{self, Write};
+
+/// Output format of an export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Journal Export Format (`journalctl -o export`)
+    Export,
+    /// One JSON object per line (`journalctl -o json`)
+    Json,
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "export" => Ok(ExportFormat::Export),
+            "json" | "jsonl" => Ok(ExportFormat::Json),
+            _ => Err(format!("unknown export format '{}' (export, json)", s)),
+        }
+    }
+}
+
+/// Position in the journal.
+///
+/// Serialized like a journald cursor (`b=<boot id>;t=<usec>;i=<seqnum>`).
+/// Entries are ordered by realtime timestamp, then sequence number; the
+/// boot ID is informational.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub boot_id: String,
+    /// Realtime timestamp in microseconds since the epoch
+    pub realtime: i64,
+    pub seqnum: u64,
+}
+
+impl Cursor {
+    /// Cursor of an entry.
+    pub fn of(entry: &JournalEntry, boot_id: &str) -> Self {
+        Self {
+            boot_id: boot_id.to_string(),
+            realtime: entry.timestamp.timestamp_micros(),
+            seqnum: entry.seqnum,
+        }
+    }
+
+    /// Whether an entry was logged after this cursor.
+    pub fn precedes(&self, entry: &JournalEntry) -> bool {
+        (entry.timestamp.timestamp_micros(), entry.seqnum) > (self.realtime, self.seqnum)
+    }
+}
+
+impl std::fmt::Display for Cursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "b={};t={:x};i={:x}",
+            self.boot_id, self.realtime, self.seqnum
+        )
+    }
+}
+
+impl std::str::FromStr for Cursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid cursor '{}'", s);
+        let (mut boot_id, mut realtime, mut seqnum) = (String::new(), None, None);
+        for part in s.trim().split(';') {
+            match part.split_once('=').ok_or_else(invalid)? {
+                ("b", v) => boot_id = v.to_string(),
+                ("t", v) => realtime = i64::from_str_radix(v, 16).ok(),
+                ("i", v) => seqnum = u64::from_str_radix(v, 16).ok(),
+                // Fields of real journald cursors that boss does not use
+                _ => {}
+            }
+        }
+        Ok(Self {
+            boot_id,
+            realtime: realtime.ok_or_else(invalid)?,
+            seqnum: seqnum.ok_or_else(invalid)?,
+        })
+    }
+}
+
+/// Writes journal entries for external consumers.
+pub struct JournalExporter {
+    format: ExportFormat,
+    boot_id: String,
+    hostname: String,
+    machine_id: Option<String>,
+}
+
+impl JournalExporter {
+    /// Create an exporter for the running system.
+    pub fn new(format: ExportFormat) -> Self {
+        let read = |path: &str| {
+            std::fs::read_to_string(path)
+                .map(|s| s.trim().to_string())
+                .unwrap_or_default()
+        };
+        Self {
+            format,
+            // journald prints boot IDs without dashes
+            boot_id: read("/proc/sys/kernel/random/boot_id").replace('-', ""),
+            hostname: read("/proc/sys/kernel/hostname"),
+            machine_id: MachineId::read().map(|id| id.to_string()),
+        }
+    }
+
+    /// Override the boot ID and hostname stamped on entries.
+    pub fn with_identity(mut self, boot_id: &str, hostname: &str) -> Self {
+        self.boot_id = boot_id.to_string();
+        self.hostname = hostname.to_string();
+        self
+    }
+
+    /// Override the machine ID stamped on entries that have none.
+    pub fn with_machine_id(mut self, machine_id: Option<&str>) -> Self {
+        self.machine_id = machine_id.map(String::from);
+        self
+    }
+
+    /// Boot of an entry: the one it was stamped with, or else the running
+    /// one.
+    fn boot_id<'a>(&'a self, entry: &'a JournalEntry) -> &'a str {
+        entry.boot_id.as_deref().unwrap_or(&self.boot_id)
+    }
+
+    /// Journald fields of an entry, in output order.
+    pub fn fields(&self, entry: &JournalEntry) -> Vec<(String, String)> {
+        let boot_id = self.boot_id(entry);
+        let mut fields: Vec<(String, String)> = [
+            ("__CURSOR", Cursor::of(entry, boot_id).to_string()),
+            (
+                "__REALTIME_TIMESTAMP",
+                entry.timestamp.timestamp_micros().to_string(),
+            ),
+            ("_BOOT_ID", boot_id.to_string()),
+            ("_HOSTNAME", self.hostname.clone()),
+            ("_TRANSPORT", "stdout".to_string()),
+            ("_SYSTEMD_UNIT", format!("{}.service", entry.service)),
+            ("SYSLOG_IDENTIFIER", entry.service.clone()),
+            ("PRIORITY", (entry.priority as u8).to_string()),
+            ("BOSS_STREAM", entry.stream.clone()),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+        let machine_id = entry.machine_id.as_ref().or(self.machine_id.as_ref());
+        if let Some(machine_id) = machine_id {
+            fields.insert(3, ("_MACHINE_ID".to_string(), machine_id.clone()));
+        }
+        if let Some(pid) = entry.pid {
+            fields.push(("_PID".to_string(), pid.to_string()));
+        }
+        fields.extend(entry.fields.iter().map(|(k, v)| (k.clone(), v.clone())));
+        fields.push(("MESSAGE".to_string(), entry.message.clone()));
+        fields
+    }
+
+    /// Write the entries logged after `after`, returning the cursor of the
+    /// last one written.
+    pub fn export<'a, W: Write>(
+        &self,
+        entries: impl IntoIterator<Item = &'a JournalEntry>,
+        after: Option<&Cursor>,
+        mut out: W,
+    ) -> io::Result<Option<Cursor>> {
+        let mut last = None;
+        for entry in entries {
+            if after.is_some_and(|cursor| !cursor.precedes(entry)) {
+                continue;
+            }
+            let fields = self.fields(entry);
+            match self.format {
+                ExportFormat::Export => write_export_entry(&mut out, &fields)?,
+                ExportFormat::Json => write_json_entry(&mut out, &fields)?,
+            }
+            last = Some(Cursor::of(entry, self.boot_id(entry)));
+        }
+        out.flush()?;
+        Ok(last)
+    }
+}
+
+/// One entry in Journal Export Format: `KEY=value` lines, with values
+/// containing newlines written as binary fields, ended by a blank line.
+fn write_export_entry<W: Write>(out: &mut W, fields: &[(String, String)]) -> io::Result<()> {
+    for (key, value) in fields {
+        if value.contains('\n') {
+            out.write_all(key.as_bytes())?;
+            out.write_all(b"\n")?;
+            out.write_all(&(value.len() as u64).to_le_bytes())?;
+            out.write_all(value.as_bytes())?;
+            out.write_all(b"\n")?;
+        } else {
+            writeln!(out, "{}={}", key, value)?;
+        }
+    }
+    out.write_all(b"\n")
+}
+
+fn write_json_entry<W: Write>(out: &mut W, fields: &[(String, String)]) -> io::Result<()> {
+    let object: serde_json::Map<String, serde_json::Value> = fields
+        .iter()
+        .map(|(k, v)| (k.clone(), serde_json::Value::String(v.clone())))
+        .collect();
+    serde_json::to_writer(&mut *out, &object)?;
+    out.write_all(b"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn entry(seqnum: u64, message: &str) -> JournalEntry {
+        let mut entry = JournalEntry::new("sshd", message, "stderr")
+            .with_pid(42)
+            .with_field("_UID", "0");
+        entry.timestamp = chrono::Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        entry.seqnum = seqnum;
+        entry
+    }
+
+    fn exporter(format: ExportFormat) -> JournalExporter {
+        JournalExporter::new(format)
+            .with_identity("abc123", "host")
+            .with_machine_id(Some("0123456789abcdef0123456789abcdef"))
+    }
+
+    #[test]
+    fn test_export_format() {
+        let mut out = Vec::new();
+        let last = exporter(ExportFormat::Export)
+            .export(&[entry(1, "hello"), entry(2, "two\nlines")], None, &mut out)
+            .unwrap()
+            .unwrap();
+
+        let mut expected = b"__CURSOR=b=abc123;t=60a24181e4000;i=1\n\
+__REALTIME_TIMESTAMP=1700000000000000\n\
+_BOOT_ID=abc123\n\
+_MACHINE_ID=0123456789abcdef0123456789abcdef\n\
+_HOSTNAME=host\n\
+_TRANSPORT=stdout\n\
+_SYSTEMD_UNIT=sshd.service\n\
+SYSLOG_IDENTIFIER=sshd\n\
+PRIORITY=3\n\
+BOSS_STREAM=stderr\n\
+_PID=42\n\
+_UID=0\n\
+MESSAGE=hello\n\n"
+            .to_vec();
+        assert!(out.starts_with(&expected));
+        expected.clear();
+        expected.extend_from_slice(b"MESSAGE\n");
+        expected.extend_from_slice(&9u64.to_le_bytes());
+        expected.extend_from_slice(b"two\nlines\n\n");
+        assert!(out.ends_with(&expected));
+        assert_eq!(last.seqnum, 2);
+    }
+
+    #[test]
+    fn test_incremental_json_export() {
+        let entries = [entry(1, "a"), entry(2, "b"), entry(3, "c")];
+        let exporter = exporter(ExportFormat::Json);
+
+        let cursor: Cursor = "b=abc123;t=60a24181e4000;i=2".parse().unwrap();
+        assert_eq!(cursor.to_string(), "b=abc123;t=60a24181e4000;i=2");
+        let mut out = Vec::new();
+        let last = exporter.export(&entries, Some(&cursor), &mut out).unwrap();
+
+        let lines: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["MESSAGE"], "c");
+        assert_eq!(lines[0]["_PID"], "42");
+        assert_eq!(last.unwrap().seqnum, 3);
+
+        // Nothing new since the last cursor
+        let last = Cursor::of(&entries[2], "abc123");
+        let mut out = Vec::new();
+        assert_eq!(
+            exporter.export(&entries, Some(&last), &mut out).unwrap(),
+            None
+        );
+        assert!(out.is_empty());
+        assert!("garbage".parse::<Cursor>().is_err());
+    }
+}
@@ -0,0 +1,450 @@
+//! Field matches over journal entries and the index that answers them.
+//!
+//! A [`JournalFilter`] holds journalctl-style matches: `_PID=1234`,
+//! `UNIT=nginx.service`, `ERRNO=2`. Matches on the same field are
+//! alternatives, matches on different fields must all hold.
+//!
+//! The writer indexes the fields in [`INDEXED_FIELDS`] of every entry it
+//! appends to a plain log file, in `<service>.log.idx` next to it. The
+//! index starts with the inode of the log file it covers and is a list of
+//! `FIELD=value<TAB>offset` postings, each batch closed by `#end <offset>`,
+//! the length of the log file it covers. A log file rewritten by vacuuming
+//! or archiving has a new inode, so its index is thrown away and rebuilt;
+//! entries appended without being indexed, by a flush or a crash between
+//! the two writes, are scanned and indexed on the next append.
+
+use crate::journal::JournalEntry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// Fields the persistent journal is indexed on. The unit needs no index,
+/// every service has log files of its own.
+pub const INDEXED_FIELDS: &[&str] = &["_BOOT_ID", "_PID", "_UID", "PRIORITY", "ERRNO"];
+
+/// First word of an index file.
+const INDEX_MAGIC: &str = "boss-journal-index";
+
+/// Whether `name` is a valid journald field name: upper case letters,
+/// digits and underscores, not starting with a digit, at most 64 bytes.
+pub fn is_valid_field_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Field matches entries are selected by.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JournalFilter {
+    matches: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl JournalFilter {
+    /// A filter matching every entry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse `FIELD=value` matches.
+    pub fn parse<S: AsRef<str>>(matches: impl IntoIterator<Item = S>) -> Result<Self, String> {
+        let mut filter = Self::new();
+        for m in matches {
+            let m = m.as_ref();
+            let (field, value) = m
+                .split_once('=')
+                .ok_or_else(|| format!("invalid match '{}', expected FIELD=value", m))?;
+            if !is_valid_field_name(field) {
+                return Err(format!("invalid field name '{}'", field));
+            }
+            filter = filter.with_match(field, value);
+        }
+        Ok(filter)
+    }
+
+    /// Also require `field` to be `value`, or any other value given for it.
+    pub fn with_match(mut self, field: &str, value: &str) -> Self {
+        // The unit of an entry always has a suffix
+        let value = match field {
+            "UNIT" | "_SYSTEMD_UNIT" if !value.contains('.') => format!("{}.service", value),
+            _ => value.to_string(),
+        };
+        let field = if field == "_SYSTEMD_UNIT" {
+            "UNIT"
+        } else {
+            field
+        };
+        self.matches
+            .entry(field.to_string())
+            .or_default()
+            .insert(value);
+        self
+    }
+
+    /// Whether the filter matches every entry.
+    pub fn is_empty(&self) -> bool {
+        self.matches.is_empty()
+    }
+
+    /// Whether an entry has all the matched fields.
+    pub fn matches(&self, entry: &JournalEntry) -> bool {
+        self.matches.iter().all(|(field, values)| {
+            entry
+                .field(field)
+                .is_some_and(|value| values.contains(value.as_ref()))
+        })
+    }
+
+    /// Services the filter is limited to, `None` if it matches any.
+    pub fn services(&self) -> Option<Vec<String>> {
+        let units = self.matches.get("UNIT")?;
+        Some(
+            units
+                .iter()
+                .filter_map(|unit| unit.strip_suffix(".service"))
+                .map(str::to_string)
+                .collect(),
+        )
+    }
+
+    /// Matches on indexed fields.
+    fn indexed(&self) -> impl Iterator<Item = (&String, &BTreeSet<String>)> {
+        self.matches
+            .iter()
+            .filter(|(field, _)| INDEXED_FIELDS.contains(&field.as_str()))
+    }
+}
+
+/// Index file of a log file.
+pub fn index_path(log: &Path) -> PathBuf {
+    let mut name = log.file_name().unwrap_or_default().to_os_string();
+    name.push(".idx");
+    log.with_file_name(name)
+}
+
+/// Log file an index covers and how much of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct IndexState {
+    inode: u64,
+    end: u64,
+}
+
+/// Index file states known to the writer, so appending to an index that is
+/// up to date needs no reading.
+pub(crate) type IndexCache = HashMap<PathBuf, IndexState>;
+
+/// Entries appended to a log file, with the offsets of their lines.
+pub(crate) type Appended<'a> = Vec<(u64, &'a JournalEntry)>;
+
+/// Index entries just appended to a log file: each with the offset of its
+/// line, ending at `end`.
+pub(crate) fn append(
+    cache: &mut IndexCache,
+    log: &Path,
+    entries: &[(u64, &JournalEntry)],
+    end: u64,
+) -> io::Result<()> {
+    let Some(&(start, _)) = entries.first() else {
+        return Ok(());
+    };
+    let inode = std::fs::metadata(log)?.ino();
+    let known = cache.get(log).copied();
+    if known != Some(IndexState { inode, end: start }) {
+        // Stale or never seen; takes in the entries just written as well
+        cache.remove(log);
+        let state = catch_up(log)?;
+        cache.insert(log.to_path_buf(), state);
+        return Ok(());
+    }
+
+    let mut out = String::new();
+    for (offset, entry) in entries {
+        postings(&mut out, *offset, entry);
+    }
+    out.push_str(&format!("#end {}\n", end));
+    let written = OpenOptions::new()
+        .append(true)
+        .open(index_path(log))
+        .and_then(|mut file| file.write_all(out.as_bytes()));
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(index_path(log));
+        return Err(e);
+    }
+    cache.insert(log.to_path_buf(), IndexState { inode, end });
+    Ok(())
+}
+
+/// Postings of one entry.
+fn postings(out: &mut String, offset: u64, entry: &JournalEntry) {
+    for field in INDEXED_FIELDS {
+        if let Some(value) = entry.field(field) {
+            if !value.contains(['\t', '\n']) {
+                out.push_str(&format!("{}={}\t{}\n", field, value, offset));
+            }
+        }
+    }
+}
+
+/// Bring the index of a log file up to date, rebuilding it if it covers
+/// another file.
+fn catch_up(log: &Path) -> io::Result<IndexState> {
+    let meta = std::fs::metadata(log)?;
+    let path = index_path(log);
+    let mut from = match read_state(&path) {
+        Some(state) if state.inode == meta.ino() && state.end <= meta.len() => state.end,
+        _ => {
+            std::fs::write(&path, format!("{} {}\n", INDEX_MAGIC, meta.ino()))?;
+            0
+        }
+    };
+
+    let mut reader = BufReader::new(File::open(log)?);
+    reader.seek(SeekFrom::Start(from))?;
+    let mut out = String::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line)? as u64;
+        // Stop short of a line still being written
+        if read == 0 || !line.ends_with('\n') {
+            break;
+        }
+        if let Ok(entry) = serde_json::from_str::<JournalEntry>(&line) {
+            postings(&mut out, from, &entry);
+        }
+        from += read;
+    }
+    out.push_str(&format!("#end {}\n", from));
+    OpenOptions::new()
+        .append(true)
+        .open(&path)?
+        .write_all(out.as_bytes())?;
+    Ok(IndexState {
+        inode: meta.ino(),
+        end: from,
+    })
+}
+
+/// Inode and end of an index, from its first line and its last `#end`.
+fn read_state(path: &Path) -> Option<IndexState> {
+    let mut file = File::open(path).ok()?;
+    let mut header = String::new();
+    BufReader::new(&mut file).read_line(&mut header).ok()?;
+    let inode = header.strip_prefix(INDEX_MAGIC)?.trim().parse().ok()?;
+
+    let len = file.metadata().ok()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(4096))).ok()?;
+    let mut tail = String::new();
+    file.read_to_string(&mut tail).ok()?;
+    let end = tail
+        .lines()
+        .rev()
+        .find_map(|l| l.strip_prefix("#end "))
+        .and_then(|end| end.parse().ok())
+        .unwrap_or(0);
+    Some(IndexState { inode, end })
+}
+
+/// Offsets of the lines of a log file that may match the indexed fields of
+/// `filter`, and the length of the file the index covers; lines past it are
+/// not indexed yet. `None` if the filter has no indexed fields or the
+/// index does not cover the file.
+pub(crate) fn lookup(log: &Path, filter: &JournalFilter) -> Option<(BTreeSet<u64>, u64)> {
+    let wanted: Vec<(&String, &BTreeSet<String>)> = filter.indexed().collect();
+    if wanted.is_empty() {
+        return None;
+    }
+    let meta = std::fs::metadata(log).ok()?;
+    let reader = BufReader::new(File::open(index_path(log)).ok()?);
+    let mut lines = reader.lines().map_while(|l| l.ok());
+    let header = lines.next()?;
+    if header.strip_prefix(INDEX_MAGIC)?.trim() != meta.ino().to_string() {
+        return None;
+    }
+
+    let mut hits: Vec<BTreeSet<u64>> = vec![BTreeSet::new(); wanted.len()];
+    let mut end = 0;
+    for line in lines {
+        if let Some(covered) = line.strip_prefix("#end ") {
+            end = covered.parse().ok()?;
+            continue;
+        }
+        let Some((posting, offset)) = line.rsplit_once('\t') else {
+            continue;
+        };
+        let Some((field, value)) = posting.split_once('=') else {
+            continue;
+        };
+        for (i, (wanted_field, values)) in wanted.iter().enumerate() {
+            if *wanted_field == field && values.contains(value) {
+                if let Ok(offset) = offset.parse() {
+                    hits[i].insert(offset);
+                }
+            }
+        }
+    }
+    if end > meta.len() {
+        return None;
+    }
+    let mut offsets = hits.pop()?;
+    for other in hits {
+        offsets.retain(|o| other.contains(o));
+    }
+    // Postings of a batch whose `#end` was never written
+    offsets.retain(|o| *o < end);
+    Some((offsets, end))
+}
+
+/// Lines of a log file at the given offsets, then every line from `from`.
+pub(crate) fn read_at(log: &Path, offsets: &BTreeSet<u64>, from: u64) -> io::Result<Vec<String>> {
+    let mut reader = BufReader::new(File::open(log)?);
+    let mut lines = Vec::new();
+    for offset in offsets {
+        reader.seek(SeekFrom::Start(*offset))?;
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        lines.push(line.trim_end_matches('\n').to_string());
+    }
+    reader.seek(SeekFrom::Start(from))?;
+    lines.extend(reader.lines().collect::<io::Result<Vec<_>>>()?);
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(pid: u32, errno: Option<&str>) -> JournalEntry {
+        let entry = JournalEntry::new("web", &format!("from {}", pid), "stdout").with_pid(pid);
+        match errno {
+            Some(errno) => entry.with_field("ERRNO", errno),
+            None => entry,
+        }
+    }
+
+    fn write(log: &Path, entries: &[JournalEntry], cache: &mut IndexCache) {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log)
+            .unwrap();
+        let mut offset = file.metadata().unwrap().len();
+        let mut appended = Vec::new();
+        for entry in entries {
+            let line = serde_json::to_string(entry).unwrap() + "\n";
+            file.write_all(line.as_bytes()).unwrap();
+            appended.push((offset, entry));
+            offset += line.len() as u64;
+        }
+        append(cache, log, &appended, offset).unwrap();
+    }
+
+    fn messages(log: &Path, filter: &JournalFilter) -> Vec<String> {
+        let (offsets, end) = lookup(log, filter).unwrap();
+        read_at(log, &offsets, end)
+            .unwrap()
+            .iter()
+            .map(|l| serde_json::from_str::<JournalEntry>(l).unwrap())
+            .filter(|e| filter.matches(e))
+            .map(|e| e.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = JournalFilter::parse(["_PID=1", "_PID=2", "UNIT=web", "ERRNO=2"]).unwrap();
+        assert_eq!(filter.services(), Some(vec!["web".to_string()]));
+        assert!(filter.matches(&entry(2, Some("2"))));
+        assert!(!filter.matches(&entry(2, None)));
+        assert!(!filter.matches(&entry(3, Some("2"))));
+        assert!(!JournalFilter::parse(["UNIT=db.service"])
+            .unwrap()
+            .matches(&entry(1, None)));
+        assert!(JournalFilter::parse(["_PID"]).is_err());
+        assert!(JournalFilter::parse(["pid=1"]).is_err());
+        assert!(JournalFilter::new().matches(&entry(1, None)));
+    }
+
+    #[tokio::test]
+    async fn test_query_journal() {
+        use crate::journal::Journal;
+
+        let dir = std::env::temp_dir().join(format!("boss-journal-query-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let journal = Journal::new(dir.clone());
+        journal.log(entry(1, None)).await;
+        journal.log(entry(2, Some("13"))).await;
+        journal
+            .log(JournalEntry::new("getty", "login", "stdout").with_pid(1))
+            .await;
+        journal.log(entry(1, Some("13"))).await;
+
+        let messages = |entries: Vec<JournalEntry>| -> Vec<String> {
+            entries.into_iter().map(|e| e.message).collect()
+        };
+        let by_pid = JournalFilter::parse(["_PID=1"]).unwrap();
+        assert_eq!(
+            messages(journal.query(&by_pid, None).await),
+            vec!["from 1", "login", "from 1"]
+        );
+        assert_eq!(
+            messages(journal.query(&by_pid, Some(1)).await),
+            vec!["from 1"]
+        );
+        let errno = JournalFilter::parse(["ERRNO=13", "UNIT=web.service"]).unwrap();
+        assert_eq!(
+            messages(journal.query(&errno, None).await),
+            vec!["from 2", "from 1"]
+        );
+        assert!(index_path(&dir.join("web.log")).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_index_follows_log_file() {
+        let dir = std::env::temp_dir().join(format!("boss-journal-index-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("web.log");
+        let mut cache = IndexCache::new();
+
+        write(&log, &[entry(1, None), entry(2, Some("13"))], &mut cache);
+        write(&log, &[entry(1, Some("13"))], &mut cache);
+        let by_pid = JournalFilter::parse(["_PID=1"]).unwrap();
+        assert_eq!(messages(&log, &by_pid), vec!["from 1", "from 1"]);
+        let both = JournalFilter::parse(["_PID=1", "ERRNO=13"]).unwrap();
+        assert_eq!(messages(&log, &both), vec!["from 1"]);
+        // Not indexed
+        assert_eq!(
+            lookup(&log, &JournalFilter::parse(["UNIT=web"]).unwrap()),
+            None
+        );
+
+        // Appended behind the index's back, as by a flush
+        let line = serde_json::to_string(&entry(1, None)).unwrap() + "\n";
+        OpenOptions::new()
+            .append(true)
+            .open(&log)
+            .unwrap()
+            .write_all(line.as_bytes())
+            .unwrap();
+        assert_eq!(messages(&log, &by_pid).len(), 3);
+        write(&log, &[entry(2, None)], &mut cache);
+        let (_, end) = lookup(&log, &by_pid).unwrap();
+        assert_eq!(end, std::fs::metadata(&log).unwrap().len());
+        assert_eq!(messages(&log, &by_pid).len(), 3);
+
+        // Replaced, as by vacuuming: the index no longer applies
+        let tmp = dir.join("web.log.tmp");
+        std::fs::copy(&log, &tmp).unwrap();
+        std::fs::rename(&tmp, &log).unwrap();
+        assert_eq!(lookup(&log, &by_pid), None);
+        write(&log, &[entry(1, None)], &mut cache);
+        assert_eq!(messages(&log, &by_pid).len(), 4);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
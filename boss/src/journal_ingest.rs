@@ -0,0 +1,239 @@
+//! Journal ingestion: per-service ring buffers drained by a single writer.
+//!
+//! Output readers of supervised processes hand their lines to
+//! [`Journal::submit`], which pushes them onto the service's bounded ring
+//! buffer without taking a lock or touching the disk, so a slow disk never
+//! stalls a process writing to its stdout. One writer thread drains the
+//! rings in batches, appending each service's entries to its log file with
+//! a single write, and fsyncs the persistent log files at most once per
+//! [`SYNC_INTERVAL`].
+//!
+//! A ring that is full drops the entry instead of waiting for the writer.
+//! Dropped entries are counted per service; once the writer catches up it
+//! records how many were lost in the service's journal.
+
+use crate::journal::{Journal, JournalEntry};
+use crate::journal_index::IndexCache;
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Weak;
+use std::time::{Duration, Instant};
+
+/// Entries a service may have waiting for the writer before new ones are
+/// dropped.
+pub const RING_CAPACITY: usize = 16384;
+
+/// Entries taken from one ring per pass, so a chatty service cannot keep
+/// the writer from the others.
+pub const BATCH_ENTRIES: usize = 2048;
+
+/// Longest time written entries of the persistent journal go without an
+/// fsync.
+pub const SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Ingestion counters of one service.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IngestStats {
+    /// Service name
+    pub service: String,
+    /// Entries queued for the writer
+    pub received: u64,
+    /// Entries dropped because the ring was full
+    pub dropped: u64,
+    /// Entries waiting for the writer
+    pub queued: usize,
+}
+
+/// Bounded queue of one service's entries.
+pub(crate) struct Ring {
+    tx: Sender<JournalEntry>,
+    rx: Receiver<JournalEntry>,
+    received: AtomicU64,
+    dropped: AtomicU64,
+    /// Dropped entries not yet recorded in the journal
+    unreported: AtomicU64,
+}
+
+impl Ring {
+    pub(crate) fn new(capacity: usize) -> Self {
+        let (tx, rx) = crossbeam_channel::bounded(capacity);
+        Self {
+            tx,
+            rx,
+            received: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            unreported: AtomicU64::new(0),
+        }
+    }
+
+    /// Queue an entry, dropping it if the ring is full.
+    pub(crate) fn push(&self, entry: JournalEntry) -> bool {
+        match self.tx.try_send(entry) {
+            Ok(()) => {
+                self.received.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Err(_) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                self.unreported.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+
+    /// Move up to `max` queued entries to `out`, returning how many.
+    pub(crate) fn drain(&self, max: usize, out: &mut Vec<JournalEntry>) -> usize {
+        let before = out.len();
+        out.extend(self.rx.try_iter().take(max));
+        out.len() - before
+    }
+
+    /// Dropped entries since the last call.
+    pub(crate) fn take_unreported(&self) -> u64 {
+        self.unreported.swap(0, Ordering::Relaxed)
+    }
+
+    pub(crate) fn stats(&self, service: &str) -> IngestStats {
+        IngestStats {
+            service: service.to_string(),
+            received: self.received.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            queued: self.rx.len(),
+        }
+    }
+}
+
+/// State of whoever is writing entries, the writer thread or a caller
+/// draining the rings itself.
+pub(crate) struct WriterState {
+    /// Persistent log files written since the last fsync
+    pub(crate) dirty: HashSet<PathBuf>,
+    pub(crate) last_sync: Instant,
+    /// Indexes of the log files written to
+    pub(crate) index: IndexCache,
+}
+
+impl WriterState {
+    pub(crate) fn new() -> Self {
+        Self {
+            dirty: HashSet::new(),
+            last_sync: Instant::now(),
+            index: IndexCache::new(),
+        }
+    }
+}
+
+/// Start the writer thread of a journal. It drains the rings when woken and
+/// fsyncs on every [`SYNC_INTERVAL`], and exits once the journal is dropped.
+pub(crate) fn spawn_writer(journal: Weak<Journal>, wake: Receiver<()>) -> std::io::Result<()> {
+    std::thread::Builder::new()
+        .name("journal-writer".to_string())
+        .spawn(move || loop {
+            if let Err(RecvTimeoutError::Disconnected) = wake.recv_timeout(SYNC_INTERVAL) {
+                return;
+            }
+            let Some(journal) = journal.upgrade() else {
+                return;
+            };
+            journal.drain();
+            if let Err(e) = journal.sync_files(false) {
+                tracing::warn!(error = %e, "Failed to sync journal files");
+            }
+        })
+        .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("boss-ingest-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_full_ring_drops_and_reports() {
+        let dir = scratch("drops");
+        let journal = Journal::new(dir.clone()).with_ring_capacity(4);
+        for i in 0..10 {
+            journal.submit(JournalEntry::new(
+                "chatty",
+                &format!("line {}", i),
+                "stdout",
+            ));
+        }
+        assert_eq!(
+            journal.ingest_stats(),
+            vec![IngestStats {
+                service: "chatty".to_string(),
+                received: 4,
+                dropped: 6,
+                queued: 4,
+            }]
+        );
+
+        let messages: Vec<String> = journal
+            .get_logs("chatty", None, false)
+            .await
+            .into_iter()
+            .map(|e| e.message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "line 0",
+                "line 1",
+                "line 2",
+                "line 3",
+                "Dropped 6 entries logged faster than the journal could write them",
+            ]
+        );
+        assert_eq!(journal.ingest_stats()[0].queued, 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_writer_keeps_order_across_services() {
+        let dir = scratch("writer");
+        let journal = Arc::new(Journal::new(dir.clone()));
+        journal.start_writer();
+
+        let producers: Vec<_> = ["a", "b", "c", "d"]
+            .into_iter()
+            .map(|service| {
+                let journal = Arc::clone(&journal);
+                std::thread::spawn(move || {
+                    for i in 0..RING_CAPACITY {
+                        // Never blocks; retry the rare entry the writer had
+                        // no room for yet
+                        while !journal.submit(JournalEntry::new(service, &i.to_string(), "stdout"))
+                        {
+                            std::thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+        for producer in producers {
+            producer.join().unwrap();
+        }
+
+        for service in ["a", "b", "c", "d"] {
+            let entries = journal.get_logs(service, None, false).await;
+            let expected = (0..RING_CAPACITY).map(|i| i.to_string());
+            assert!(entries
+                .iter()
+                .filter(|e| e.stream == "stdout")
+                .map(|e| e.message.clone())
+                .eq(expected));
+        }
+        journal.sync().unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,483 @@
+//! Journal disk usage limits and vacuuming.
+//!
+//! The persistent journal is one JSON Lines file per service. Vacuuming
+//! drops the oldest entries across all files until the journal is under a
+//! size limit, or every entry older than a given age, but never touches
+//! entries logged since the current boot. The same limits, in the style of
+//! journald's SystemMaxUse= and SystemKeepFree=, are enforced as the
+//! journal grows.
+//!
+//! Entries from earlier boots can be moved into a compressed archive per
+//! service, `<service>.log.zst` and the like. Archives are read and
+//! vacuumed like the plain files, whatever algorithm wrote them.
+
+use crate::journal::JournalEntry;
+use buckos_core::compress::{self, Algorithm, Compression};
+use chrono::{DateTime, Utc};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Limits on the disk space used by the persistent journal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JournalLimits {
+    /// Most space the journal may use (SystemMaxUse=)
+    pub max_use: Option<u64>,
+    /// Space the journal must leave free on its filesystem (SystemKeepFree=)
+    pub keep_free: Option<u64>,
+}
+
+impl JournalLimits {
+    /// Space the journal may use, given what it uses now and what is free
+    /// on its filesystem; `None` if unlimited.
+    pub fn allowed(&self, usage: u64, free: u64) -> Option<u64> {
+        let keep_free = self
+            .keep_free
+            .map(|keep| (usage + free).saturating_sub(keep));
+        match (self.max_use, keep_free) {
+            (Some(max), Some(keep)) => Some(max.min(keep)),
+            (max, keep) => max.or(keep),
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.max_use.is_none() && self.keep_free.is_none()
+    }
+}
+
+/// What to remove in a vacuum.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VacuumCriteria {
+    /// Remove the oldest entries until the journal is at most this size
+    pub max_size: Option<u64>,
+    /// Remove entries older than this
+    pub max_age: Option<Duration>,
+}
+
+/// Outcome of a vacuum.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VacuumReport {
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub entries_removed: usize,
+    /// Log files removed because none of their entries were kept
+    pub files_removed: usize,
+}
+
+impl VacuumReport {
+    /// Space freed by the vacuum.
+    pub fn reclaimed(&self) -> u64 {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
+/// A line of a journal file.
+struct Line {
+    file: usize,
+    timestamp: Option<DateTime<Utc>>,
+    text: String,
+    keep: bool,
+}
+
+impl Line {
+    /// Bytes on disk, including the newline.
+    fn size(&self) -> u64 {
+        self.text.len() as u64 + 1
+    }
+}
+
+/// Service a journal file belongs to: `<service>.log`, or an archive
+/// `<service>.log.<suffix>` compressed with a supported algorithm.
+pub fn journal_service(file_name: &str) -> Option<&str> {
+    if let Some(service) = file_name.strip_suffix(".log") {
+        return Some(service);
+    }
+    let (base, suffix) = file_name.rsplit_once('.')?;
+    Algorithm::from_suffix(suffix)?;
+    base.strip_suffix(".log")
+}
+
+/// Algorithm of an archive, `None` for a plain log file.
+fn archive_algorithm(path: &Path) -> Option<Algorithm> {
+    Algorithm::from_suffix(path.extension()?.to_str()?)
+}
+
+/// Journal files in a log directory.
+fn journal_files(log_dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(log_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .filter(|e| journal_service(&e.file_name().to_string_lossy()).is_some())
+        .map(|e| e.path())
+        .collect();
+    files.sort();
+    files
+}
+
+/// Files holding a service's entries in `dir`, oldest entries first: its
+/// archives, then its plain log file.
+pub fn service_files(dir: &Path, service: &str) -> Vec<PathBuf> {
+    Algorithm::ALL
+        .into_iter()
+        .filter(|a| *a != Algorithm::None)
+        .chain([Algorithm::None])
+        .map(|a| dir.join(Compression::new(a).file_name(&format!("{}.log", service))))
+        .filter(|p| p.exists())
+        .collect()
+}
+
+/// Lines of a journal file, decompressing archives.
+pub fn read_lines(path: &Path) -> io::Result<Vec<String>> {
+    BufReader::new(compress::open(path)?).lines().collect()
+}
+
+/// Replace a journal file atomically, so a crash leaves either version.
+fn write_lines<'a>(
+    path: &Path,
+    lines: impl IntoIterator<Item = &'a str>,
+    compression: Option<Compression>,
+) -> io::Result<()> {
+    let mut data = Vec::new();
+    for line in lines {
+        writeln!(data, "{}", line)?;
+    }
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!("{}.tmp", name));
+    match compression {
+        Some(compression) => compression.write(&tmp, &data)?,
+        None => std::fs::write(&tmp, &data)?,
+    }
+    File::open(&tmp)?.sync_all()?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Bytes used by the journal files in a log directory.
+pub fn disk_usage(log_dir: &Path) -> u64 {
+    journal_files(log_dir)
+        .iter()
+        .filter_map(|p| std::fs::metadata(p).ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Remove old entries from the journal files in `log_dir`.
+///
+/// Entries at or after `keep_since` (the start of the current boot) are
+/// always kept, so the result may still be over `max_size`. Lines that
+/// cannot be parsed count as older than any entry.
+pub fn vacuum(
+    log_dir: &Path,
+    criteria: &VacuumCriteria,
+    keep_since: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> io::Result<VacuumReport> {
+    let files = journal_files(log_dir);
+    let mut lines = Vec::new();
+    for (index, path) in files.iter().enumerate() {
+        for text in read_lines(path)? {
+            let timestamp = serde_json::from_str::<JournalEntry>(&text)
+                .ok()
+                .map(|e| e.timestamp);
+            lines.push(Line {
+                file: index,
+                timestamp,
+                text,
+                keep: true,
+            });
+        }
+    }
+
+    let mut report = VacuumReport {
+        bytes_before: disk_usage(log_dir),
+        ..Default::default()
+    };
+    let mut size: u64 = lines.iter().map(Line::size).sum();
+
+    // Oldest first; only entries from before this boot may go
+    let mut candidates: Vec<usize> = (0..lines.len())
+        .filter(|&i| lines[i].timestamp.is_none_or(|t| t < keep_since))
+        .collect();
+    candidates.sort_by_key(|&i| lines[i].timestamp);
+
+    let cutoff = criteria
+        .max_age
+        .and_then(|age| chrono::Duration::from_std(age).ok())
+        .map(|age| now - age);
+    for &i in &candidates {
+        let too_old = match (cutoff, lines[i].timestamp) {
+            (Some(cutoff), Some(t)) => t < cutoff,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        let too_big = criteria.max_size.is_some_and(|max| size > max);
+        if !too_old && !too_big {
+            continue;
+        }
+        lines[i].keep = false;
+        size -= lines[i].size();
+        report.entries_removed += 1;
+    }
+
+    if report.entries_removed > 0 {
+        for (index, path) in files.iter().enumerate() {
+            let file_lines = || lines.iter().filter(|l| l.file == index);
+            if file_lines().all(|l| l.keep) {
+                continue;
+            }
+            if !file_lines().any(|l| l.keep) {
+                std::fs::remove_file(path)?;
+                report.files_removed += 1;
+                continue;
+            }
+            write_lines(
+                path,
+                file_lines().filter(|l| l.keep).map(|l| l.text.as_str()),
+                archive_algorithm(path).map(Compression::new),
+            )?;
+        }
+    }
+
+    report.bytes_after = disk_usage(log_dir);
+    Ok(report)
+}
+
+/// Move entries from before `keep_since` (the start of the current boot)
+/// out of the plain log files into a compressed archive per service.
+///
+/// A service's existing archive is rewritten with `compression` together
+/// with the new entries, whatever it was compressed with before. Lines
+/// that cannot be parsed count as older than any entry. Returns the number
+/// of entries archived.
+pub fn archive(
+    log_dir: &Path,
+    compression: Compression,
+    keep_since: DateTime<Utc>,
+) -> io::Result<usize> {
+    if compression.algorithm == Algorithm::None {
+        return Ok(0);
+    }
+    let mut archived = 0;
+    for path in journal_files(log_dir) {
+        if archive_algorithm(&path).is_some() {
+            continue;
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let Some(service) = name.strip_suffix(".log") else {
+            continue;
+        };
+        let (old, current): (Vec<String>, Vec<String>) =
+            read_lines(&path)?.into_iter().partition(|text| {
+                serde_json::from_str::<JournalEntry>(text)
+                    .map_or(true, |e| e.timestamp < keep_since)
+            });
+        if old.is_empty() {
+            continue;
+        }
+
+        let existing: Vec<PathBuf> = service_files(log_dir, service)
+            .into_iter()
+            .filter(|p| p != &path)
+            .collect();
+        let mut lines = Vec::new();
+        for file in &existing {
+            lines.extend(read_lines(file)?);
+        }
+        lines.extend(old.iter().cloned());
+
+        // The archive is complete before entries leave the plain file, so a
+        // crash in between duplicates entries rather than losing them
+        let target = log_dir.join(compression.file_name(&name));
+        write_lines(&target, lines.iter().map(String::as_str), Some(compression))?;
+        for file in existing.iter().filter(|f| **f != target) {
+            std::fs::remove_file(file)?;
+        }
+        if current.is_empty() {
+            std::fs::remove_file(&path)?;
+        } else {
+            write_lines(&path, current.iter().map(String::as_str), None)?;
+        }
+        archived += old.len();
+    }
+    Ok(archived)
+}
+
+/// Start of the current boot, from the kernel's boot time.
+pub fn current_boot_start() -> Option<DateTime<Utc>> {
+    let stat = std::fs::read_to_string("/proc/stat").ok()?;
+    let btime = stat
+        .lines()
+        .find_map(|l| l.strip_prefix("btime "))?
+        .trim()
+        .parse()
+        .ok()?;
+    DateTime::from_timestamp(btime, 0)
+}
+
+/// Free space on the filesystem holding `path`.
+pub fn free_space(path: &Path) -> Option<u64> {
+    let stat = nix::sys::statvfs::statvfs(path).ok()?;
+    Some(stat.blocks_available() as u64 * stat.fragment_size() as u64)
+}
+
+/// Parse a size such as `512M`, `2G` or a plain byte count.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", s))?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(format!("unknown size unit in '{}' (K, M, G, T)", s)),
+    };
+    Ok(number * multiplier)
+}
+
+/// Parse an age such as `30min`, `12h`, `2weeks` or `6months`.
+pub fn parse_age(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number.parse().map_err(|_| format!("invalid age '{}'", s))?;
+    let secs: u64 = match unit.trim() {
+        "" | "s" | "sec" | "second" | "seconds" => 1,
+        "m" | "min" | "minute" | "minutes" => 60,
+        "h" | "hour" | "hours" => 3600,
+        "d" | "day" | "days" => 86400,
+        "w" | "week" | "weeks" => 7 * 86400,
+        "month" | "months" => 30 * 86400,
+        "y" | "year" | "years" => 365 * 86400,
+        _ => return Err(format!("unknown age unit in '{}'", s)),
+    };
+    Ok(Duration::from_secs(number * secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_journal(dir: &Path, service: &str, ages_hours: &[i64], now: DateTime<Utc>) {
+        let mut file = File::create(dir.join(format!("{}.log", service))).unwrap();
+        for hours in ages_hours {
+            let mut entry = JournalEntry::new(service, "message", "stdout");
+            entry.timestamp = now - chrono::Duration::hours(*hours);
+            writeln!(file, "{}", serde_json::to_string(&entry).unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_vacuum_keeps_current_boot() {
+        let dir = std::env::temp_dir().join(format!("boss-vacuum-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let now = Utc::now();
+        let boot = now - chrono::Duration::hours(2);
+        write_journal(&dir, "old", &[72, 48], now);
+        write_journal(&dir, "web", &[50, 20, 1], now);
+
+        // By age: everything from over a day ago
+        let report = vacuum(
+            &dir,
+            &VacuumCriteria {
+                max_age: Some(Duration::from_secs(86400)),
+                ..Default::default()
+            },
+            boot,
+            now,
+        )
+        .unwrap();
+        assert_eq!(report.entries_removed, 3);
+        assert_eq!(report.files_removed, 1);
+        assert!(report.reclaimed() > 0);
+
+        // By size: even a zero limit keeps this boot's entry
+        let report = vacuum(
+            &dir,
+            &VacuumCriteria {
+                max_size: Some(0),
+                ..Default::default()
+            },
+            boot,
+            now,
+        )
+        .unwrap();
+        assert_eq!(report.entries_removed, 1);
+        let left = std::fs::read_to_string(dir.join("web.log")).unwrap();
+        assert_eq!(left.lines().count(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_archive_and_vacuum_compressed() {
+        let dir = std::env::temp_dir().join(format!("boss-archive-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let now = Utc::now();
+        let boot = now - chrono::Duration::hours(2);
+        write_journal(&dir, "web", &[72, 48, 1], now);
+        // A gzip archive left by an earlier setting
+        let mut legacy = String::new();
+        let mut entry = JournalEntry::new("web", "legacy", "stdout");
+        entry.timestamp = now - chrono::Duration::hours(96);
+        legacy.push_str(&serde_json::to_string(&entry).unwrap());
+        legacy.push('\n');
+        Compression::new(Algorithm::Gzip)
+            .write(&dir.join("web.log.gz"), legacy.as_bytes())
+            .unwrap();
+        assert_eq!(journal_service("web.log.gz"), Some("web"));
+        assert_eq!(journal_service("web.log.tmp"), None);
+
+        let zstd = Compression::new(Algorithm::Zstd).with_level(19);
+        assert_eq!(archive(&dir, zstd, boot).unwrap(), 2);
+        assert!(!dir.join("web.log.gz").exists());
+        assert_eq!(read_lines(&dir.join("web.log.zst")).unwrap().len(), 3);
+        assert_eq!(read_lines(&dir.join("web.log")).unwrap().len(), 1);
+        assert_eq!(
+            service_files(&dir, "web"),
+            vec![dir.join("web.log.zst"), dir.join("web.log")]
+        );
+        // Nothing left to archive
+        assert_eq!(archive(&dir, zstd, boot).unwrap(), 0);
+
+        // Vacuuming reaches into the archive and keeps it compressed
+        let report = vacuum(
+            &dir,
+            &VacuumCriteria {
+                max_age: Some(Duration::from_secs(60 * 3600)),
+                ..Default::default()
+            },
+            boot,
+            now,
+        )
+        .unwrap();
+        assert_eq!(report.entries_removed, 2);
+        let left = read_lines(&dir.join("web.log.zst")).unwrap();
+        assert_eq!(left.len(), 1);
+        assert!(left[0].contains("message"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_limits_and_parsing() {
+        let limits = JournalLimits {
+            max_use: Some(1000),
+            keep_free: Some(500),
+        };
+        assert_eq!(limits.allowed(400, 800), Some(700));
+        assert_eq!(limits.allowed(400, 5000), Some(1000));
+        assert_eq!(JournalLimits::default().allowed(400, 0), None);
+
+        assert_eq!(parse_size("512M"), Ok(512 << 20));
+        assert_eq!(parse_size("100"), Ok(100));
+        assert!(parse_size("5X").is_err());
+        assert_eq!(parse_age("2weeks"), Ok(Duration::from_secs(14 * 86400)));
+        assert_eq!(parse_age("30min"), Ok(Duration::from_secs(1800)));
+    }
+}
@@ -12,8 +12,11 @@
 //! - Health checks and watchdog support
 //! - Socket activation
 //! - Timer services
+//! - Clock synchronization (SNTP)
 //! - Resource limits
 //! - Service templates
+//! - Generators creating units at boot
+//! - First-boot provisioning
 //! - Structured logging (journal)
 //! - Boot time analysis
 //!
@@ -41,27 +44,87 @@
 //! }
 //! ```
 
+pub mod accounting;
+pub mod boots;
 pub mod control;
+pub mod coredump;
+pub mod crash;
+pub mod cycles;
+pub mod enablement;
 pub mod error;
+pub mod generators;
+pub mod i18n;
+pub mod inhibit;
 pub mod init;
 pub mod journal;
+pub mod journal_export;
+pub mod journal_index;
+pub mod journal_ingest;
+pub mod journal_vacuum;
 pub mod loaders;
+pub mod lsm;
 pub mod manager;
+pub mod netns;
+pub mod orphans;
+pub mod packages;
+pub mod path_unit;
 pub mod process;
+pub mod provision;
+pub mod scheduler;
+pub mod seccomp;
 pub mod service;
+pub mod session;
+pub mod socket_unit;
+pub mod swap;
+pub mod syslog;
+pub mod timer;
+pub mod timesync;
+pub mod transient;
+pub mod volatile;
 
 // Re-export main types
+pub use accounting::ResourceUsage;
+pub use boots::{BootIndex, BootRecord, BootSpec};
 pub use control::{
-    ControlClient, ControlCommand, ControlResponse, ControlServer, ServiceInfo,
-    DEFAULT_CONTROL_SOCKET,
+    ControlClient, ControlCommand, ControlResponse, ControlServer, InhibitorLock, ServiceInfo,
+    SystemStatus, DEFAULT_CONTROL_SOCKET,
 };
+pub use coredump::{CoredumpInfo, CoredumpLimits, CoredumpStore, CrashedProcess};
+pub use crash::CrashHandler;
+pub use cycles::{DependencyCycle, DependencyKind};
+pub use enablement::{EnablementStore, PresetAction, PresetMode, PresetPolicy};
 pub use error::{Error, Result};
+pub use generators::{GeneratorRun, Generators, GENERATED_DIR, GENERATOR_DIRS};
+pub use inhibit::{InhibitWhat, Inhibitor, InhibitorRegistry};
 pub use init::{create_test_init, Init, InitConfig, ShutdownType};
 pub use journal::{Journal, JournalEntry, Priority};
-pub use loaders::{LoaderRegistry, ServiceLoader, SystemdLoader, TomlLoader};
+pub use journal_export::{Cursor, ExportFormat, JournalExporter};
+pub use journal_index::JournalFilter;
+pub use journal_ingest::IngestStats;
+pub use journal_vacuum::{JournalLimits, VacuumCriteria, VacuumReport};
+pub use loaders::{
+    Diagnostic, LoaderRegistry, ServiceLoader, Severity, SystemdLoader, TomlLoader, VerifyReport,
+};
+pub use lsm::{ExecLabel, Lsm};
 pub use manager::{BootTiming, DependencyNode, ServiceManager};
+pub use netns::{NetworkHelper, PrivateNetwork};
+pub use orphans::{OrphanCount, OrphanTracker};
+pub use packages::{PackageDb, PACKAGE_DB_DIR};
+pub use path_unit::{PathCondition, PathWatcher, TriggerLimit};
 pub use process::{ExitStatus, ProcessSupervisor};
+pub use provision::{ProvisionState, ProvisionTask, Provisioner, TaskStatus};
+pub use scheduler::{BootHistory, ScheduleDecision, StartupLimits, StartupPlan};
+pub use seccomp::{SeccompFilter, SyscallGroup, SyscallLearner, SyscallStore};
 pub use service::{
-    HealthCheck, HealthStatus, ResourceLimits, RestartPolicy, ServiceDefinition, ServiceInstance,
-    ServiceState, ServiceStatus, ServiceType, SocketConfig, TimerConfig, WatchdogConfig,
+    HealthCheck, HealthStatus, NetworkConfig, PathConfig, PublishedPort, ResourceLimits,
+    RestartPolicy, SeccompConfig, SecurityConfig, ServiceDefinition, ServiceInstance, ServiceState,
+    ServiceStatus, ServiceType, SocketConfig, TimerConfig, TtyConfig, WatchdogAction,
+    WatchdogConfig,
 };
+pub use session::{Session, SessionSource, SessionStore};
+pub use socket_unit::ServiceSockets;
+pub use swap::{ActiveSwap, SwapConfig, SwapUnit, ZramConfig};
+pub use syslog::{RemoteSyslogConfig, SyslogForwarder, SyslogTransport};
+pub use timer::{CalendarSpec, TimerInfo, TimerSchedule};
+pub use timesync::{Adjustment, ClockSync, Sample, SntpClient, TIME_SYNC};
+pub use transient::TransientUnit;
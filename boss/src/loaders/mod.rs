@@ -6,6 +6,20 @@
 //! - TOML (native buckos format)
 //! - systemd unit files (.service)
 //!
+//! Loaders can also validate a file without loading it into the manager;
+//! see [`verify`].
+//!
+//! # Drop-ins
+//!
+//! Settings of a unit can be overridden without editing its file, which a
+//! package update would overwrite. Drop-in files in a `<unit file>.d/`
+//! directory are applied over the unit in file name order, so
+//! `50-limits.conf` overrides `10-env.conf`. Drop-in directories are looked
+//! up in /etc/buckos/system first and then next to the unit file; a drop-in
+//! there replaces a drop-in of the same name next to the unit file, which
+//! is where packages ship theirs. systemd units take `*.conf` drop-ins, TOML
+//! units `*.toml` ones whose tables are merged into the unit.
+//!
 //! # Migration Support
 //!
 //! The systemd loader also provides utilities to convert systemd unit files
@@ -13,10 +27,12 @@
 
 pub mod systemd;
 pub mod toml;
+pub mod verify;
 
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::service::ServiceDefinition;
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 /// Trait for service configuration loaders.
 ///
@@ -30,6 +46,68 @@ pub trait ServiceLoader: Send + Sync {
 
     /// Get a description of the loader for logging purposes.
     fn name(&self) -> &'static str;
+
+    /// Validate a service file, collecting every problem found.
+    ///
+    /// The default implementation reports a load failure as a single error
+    /// and otherwise checks the loaded definition.
+    fn verify(&self, path: &Path) -> VerifyReport {
+        let mut report = VerifyReport::new(path);
+        match self.load(path) {
+            Ok(def) => {
+                report.definition = Some(def);
+                report.check_definition();
+            }
+            Err(e) => report
+                .diagnostics
+                .push(Diagnostic::error(None, e.to_string())),
+        }
+        report
+    }
+
+    /// Extension of drop-in files of this format, if it takes drop-ins.
+    fn dropin_extension(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Load a service definition with drop-in files applied in order.
+    fn load_with_dropins(&self, path: &Path, dropins: &[PathBuf]) -> Result<ServiceDefinition> {
+        if dropins.is_empty() {
+            return self.load(path);
+        }
+        Err(Error::ConfigError(format!(
+            "{} units do not support drop-ins",
+            self.name()
+        )))
+    }
+
+    /// Validate the drop-in `dropin`, and the unit with `dropins`
+    /// (including it) applied.
+    fn verify_dropin(&self, path: &Path, dropins: &[PathBuf], dropin: &Path) -> VerifyReport {
+        let mut report = VerifyReport::new(dropin);
+        match self.load_with_dropins(path, dropins) {
+            Ok(def) => {
+                report.definition = Some(def);
+                report.check_definition();
+            }
+            Err(e) => report
+                .diagnostics
+                .push(Diagnostic::error(None, e.to_string())),
+        }
+        report
+    }
+}
+
+/// Read a unit or drop-in file.
+pub(crate) fn read_file(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path)
+        .map_err(|e| Error::ConfigError(format!("Failed to read {}: {}", path.display(), e)))
+}
+
+/// Drop-in directory of a unit file in `dir`, e.g. `nginx.service.d`.
+pub fn dropin_dir(dir: &Path, unit_file: &Path) -> PathBuf {
+    let name = unit_file.file_name().unwrap_or_default().to_string_lossy();
+    dir.join(format!("{}.d", name))
 }
 
 /// Registry of service loaders.
@@ -83,6 +161,70 @@ impl LoaderRegistry {
         loader.load(path)
     }
 
+    /// Validate a service file with the loader for its extension.
+    pub fn verify(&self, path: &Path) -> Result<VerifyReport> {
+        let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+
+        let loader = self.find_loader(ext).ok_or_else(|| {
+            crate::error::Error::ConfigError(format!("No loader found for extension: {}", ext))
+        })?;
+
+        Ok(loader.verify(path))
+    }
+
+    /// Drop-in files of a unit file, in the order they apply.
+    ///
+    /// `dirs` are searched for the unit's drop-in directory, highest
+    /// priority first; a drop-in replaces those of the same name in later
+    /// directories.
+    pub fn find_dropins(&self, path: &Path, dirs: &[PathBuf]) -> Vec<PathBuf> {
+        let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+        let Some(dropin_ext) = self.find_loader(ext).and_then(|l| l.dropin_extension()) else {
+            return Vec::new();
+        };
+
+        // By file name, keeping the first directory's file
+        let mut found: BTreeMap<String, PathBuf> = BTreeMap::new();
+        for dir in dirs {
+            let entries = std::fs::read_dir(dropin_dir(dir, path));
+            for entry in entries.into_iter().flatten().flatten() {
+                let dropin = entry.path();
+                if dropin.extension().is_some_and(|e| e == dropin_ext) {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    found.entry(name).or_insert(dropin);
+                }
+            }
+        }
+        found.into_values().collect()
+    }
+
+    /// Load a service definition with drop-in files applied in order.
+    pub fn load_with_dropins(&self, path: &Path, dropins: &[PathBuf]) -> Result<ServiceDefinition> {
+        self.loader_for(path)?.load_with_dropins(path, dropins)
+    }
+
+    /// Validate a drop-in of the unit `path`; see
+    /// [`ServiceLoader::verify_dropin`].
+    pub fn verify_dropin(
+        &self,
+        path: &Path,
+        dropins: &[PathBuf],
+        dropin: &Path,
+    ) -> Result<VerifyReport> {
+        Ok(self.loader_for(path)?.verify_dropin(path, dropins, dropin))
+    }
+
+    /// Extension of the drop-ins of the unit `path`, if it takes any.
+    pub fn dropin_extension(&self, path: &Path) -> Result<Option<&'static str>> {
+        Ok(self.loader_for(path)?.dropin_extension())
+    }
+
+    fn loader_for(&self, path: &Path) -> Result<&dyn ServiceLoader> {
+        let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+        self.find_loader(ext)
+            .ok_or_else(|| Error::ConfigError(format!("No loader found for extension: {}", ext)))
+    }
+
     /// Get all supported file extensions.
     pub fn supported_extensions(&self) -> Vec<&'static str> {
         let mut exts = Vec::new();
@@ -101,3 +243,4 @@ impl LoaderRegistry {
 // Re-export main types
 pub use systemd::SystemdLoader;
 pub use toml::TomlLoader;
+pub use verify::{Diagnostic, Severity, VerifyReport};
@@ -8,6 +8,7 @@
 //! ## [Unit] Section
 //! - Description
 //! - Requires, Wants, Before, After
+//! - RequiresPackage (buckos extension)
 //!
 //! ## [Service] Section
 //! - Type (simple, forking, oneshot, notify, idle)
@@ -17,18 +18,44 @@
 //! - Environment, EnvironmentFile
 //! - Restart, RestartSec
 //! - TimeoutStartSec, TimeoutStopSec
-//! - StandardOutput, StandardError
-//! - WatchdogSec
+//! - StandardInput, StandardOutput, StandardError
+//! - TTYPath, TTYReset, TTYVHangup
+//! - PrivateNetwork, PrivateNetworkNAT, PublishPort (buckos extensions)
+//! - SystemCallFilter, SystemCallLearning (buckos extension)
+//! - AppArmorProfile, SELinuxContext
+//! - WatchdogSec, WatchdogAction, WatchdogRecoveryCommand (buckos extensions)
 //! - MemoryLimit, CPUQuota, LimitNOFILE, LimitNPROC
 //!
 //! ## [Install] Section
 //! - WantedBy, RequiredBy (used to determine if enabled)
+//!
+//! ## [Socket] Section
+//! - ListenStream, ListenDatagram, ListenSequentialPacket
+//! - Accept, Backlog, SocketMode, SocketUser, SocketGroup
+//! - ExitIdleTimeSec (buckos extension)
+//!
+//! ## [Path] Section
+//! - PathExists, PathChanged, DirectoryNotEmpty
+//! - MakeDirectory
+//! - TriggerLimitIntervalSec, TriggerLimitBurst
+//!
+//! # Drop-ins
+//!
+//! `*.conf` drop-ins use the same sections and keys. A list directive in a
+//! drop-in (Wants, Environment, PublishPort, ...) adds to the unit's value,
+//! any other directive replaces it, and an empty assignment such as
+//! `ExecStart=` clears it, so the next line can set it anew.
 
+use super::verify::{Diagnostic, VerifyReport};
 use crate::error::{Error, Result};
+use crate::packages;
+use crate::seccomp;
 use crate::service::{
-    HealthCheck, ResourceLimits, RestartPolicy, ServiceDefinition, ServiceType, SocketConfig,
-    TimerConfig, WatchdogConfig,
+    HealthCheck, NetworkConfig, PathConfig, PublishedPort, ResourceLimits, RestartPolicy,
+    SeccompConfig, SecurityConfig, ServiceDefinition, ServiceType, SocketConfig, TimerConfig,
+    TtyConfig, WatchdogAction, WatchdogConfig,
 };
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
@@ -51,6 +78,44 @@ impl super::ServiceLoader for SystemdLoader {
     fn name(&self) -> &'static str {
         "systemd"
     }
+
+    fn verify(&self, path: &Path) -> VerifyReport {
+        match std::fs::read_to_string(path) {
+            Ok(content) => verify_unit_file(&content, path),
+            Err(e) => {
+                let mut report = VerifyReport::new(path);
+                report
+                    .diagnostics
+                    .push(Diagnostic::error(None, format!("Failed to read: {}", e)));
+                report
+            }
+        }
+    }
+
+    fn dropin_extension(&self) -> Option<&'static str> {
+        Some("conf")
+    }
+
+    fn load_with_dropins(&self, path: &Path, dropins: &[PathBuf]) -> Result<ServiceDefinition> {
+        let mut sections = parse_sections(&super::read_file(path)?);
+        for dropin in dropins {
+            apply_dropin(&mut sections, &super::read_file(dropin)?);
+        }
+        parse_unit(sections, path)
+    }
+
+    fn verify_dropin(&self, path: &Path, dropins: &[PathBuf], dropin: &Path) -> VerifyReport {
+        match std::fs::read_to_string(dropin) {
+            Ok(content) => verify_dropin_file(&content, dropin, path, dropins),
+            Err(e) => {
+                let mut report = VerifyReport::new(dropin);
+                report
+                    .diagnostics
+                    .push(Diagnostic::error(None, format!("Failed to read: {}", e)));
+                report
+            }
+        }
+    }
 }
 
 impl SystemdLoader {
@@ -123,6 +188,40 @@ impl SystemdLoader {
 
         Ok(migrated)
     }
+
+    /// Validate and load a unit given as `Key=Value` properties, each
+    /// placed in the [Unit], [Service] or [Timer] section that reads it.
+    ///
+    /// Used for transient units, which have no file.
+    pub fn from_properties(name: &str, properties: &[(String, String)]) -> VerifyReport {
+        let path = PathBuf::from(format!("{}.service", name));
+        let mut unknown = Vec::new();
+        let mut sections: Vec<(&str, String)> = ["Unit", "Service", "Timer"]
+            .iter()
+            .map(|s| (*s, format!("[{}]\n", s)))
+            .collect();
+        for (key, value) in properties {
+            match sections
+                .iter_mut()
+                .find(|(section, _)| directive_kind(section, key).is_some())
+            {
+                Some((_, content)) => content.push_str(&format!("{}={}\n", key, value)),
+                None => unknown.push(Diagnostic::error(
+                    None,
+                    format!("unknown property {}=", key),
+                )),
+            }
+        }
+
+        let content: String = sections.into_iter().map(|(_, content)| content).collect();
+        let mut report = verify_unit_file(&content, &path);
+        // Lines of the generated file mean nothing to the caller
+        for diagnostic in &mut report.diagnostics {
+            diagnostic.line = None;
+        }
+        report.diagnostics.extend(unknown);
+        report
+    }
 }
 
 impl Default for SystemdLoader {
@@ -139,66 +238,65 @@ struct UnitSections {
     install: HashMap<String, String>,
     timer: HashMap<String, String>,
     socket: HashMap<String, String>,
+    path: HashMap<String, String>,
 }
 
-/// Parse a systemd unit file content into sections.
-fn parse_sections(content: &str) -> UnitSections {
-    let mut sections = UnitSections::default();
-    let mut current_section = String::new();
+impl UnitSections {
+    /// Directives of a section the loader reads.
+    fn section_mut(&mut self, name: &str) -> Option<&mut HashMap<String, String>> {
+        match name {
+            "Unit" => Some(&mut self.unit),
+            "Service" => Some(&mut self.service),
+            "Install" => Some(&mut self.install),
+            "Timer" => Some(&mut self.timer),
+            "Socket" => Some(&mut self.socket),
+            "Path" => Some(&mut self.path),
+            _ => None,
+        }
+    }
+}
 
-    for line in content.lines() {
+/// Assignments of a unit file as (section, key, value).
+fn assignments(content: &str) -> impl Iterator<Item = (String, &str, &str)> {
+    let mut current_section = String::new();
+    content.lines().filter_map(move |line| {
         let line = line.trim();
 
         // Skip empty lines and comments
         if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
-            continue;
+            return None;
         }
 
         // Check for section header
         if line.starts_with('[') && line.ends_with(']') {
             current_section = line[1..line.len() - 1].to_string();
-            continue;
+            return None;
         }
 
         // Parse key=value
-        if let Some((key, value)) = line.split_once('=') {
-            let key = key.trim().to_string();
-            let value = value.trim().to_string();
-
-            match current_section.as_str() {
-                "Unit" => {
-                    // Handle list values (append with space separator)
-                    if let Some(existing) = sections.unit.get_mut(&key) {
-                        existing.push(' ');
-                        existing.push_str(&value);
-                    } else {
-                        sections.unit.insert(key, value);
-                    }
-                }
-                "Service" => {
-                    // Handle list values
-                    if let Some(existing) = sections.service.get_mut(&key) {
-                        existing.push(' ');
-                        existing.push_str(&value);
-                    } else {
-                        sections.service.insert(key, value);
-                    }
-                }
-                "Install" => {
-                    if let Some(existing) = sections.install.get_mut(&key) {
-                        existing.push(' ');
-                        existing.push_str(&value);
-                    } else {
-                        sections.install.insert(key, value);
-                    }
-                }
-                "Timer" => {
-                    sections.timer.insert(key, value);
-                }
-                "Socket" => {
-                    sections.socket.insert(key, value);
-                }
-                _ => {}
+        let (key, value) = line.split_once('=')?;
+        Some((current_section.clone(), key.trim(), value.trim()))
+    })
+}
+
+/// Parse a systemd unit file content into sections.
+fn parse_sections(content: &str) -> UnitSections {
+    let mut sections = UnitSections::default();
+
+    for (section, key, value) in assignments(content) {
+        // Timer and Socket keep the last value, the rest are lists joined
+        // with a space
+        let join = !matches!(section.as_str(), "Timer" | "Socket");
+        let Some(directives) = sections.section_mut(&section) else {
+            continue;
+        };
+        match directives.get_mut(key) {
+            Some(existing) if join => {
+                existing.push(' ');
+                existing.push_str(value);
+            }
+            _ => {
+                directives.insert(key.to_string(), value.to_string());
             }
         }
     }
@@ -206,10 +304,38 @@ fn parse_sections(content: &str) -> UnitSections {
     sections
 }
 
+/// Apply a drop-in file over the sections of a unit.
+///
+/// A list directive is extended, any other one replaced, and an empty
+/// assignment (`Wants=`) clears what was set before it.
+fn apply_dropin(sections: &mut UnitSections, content: &str) {
+    for (section, key, value) in assignments(content) {
+        let append = directive_kind(&section, key).is_some_and(DirectiveKind::is_list);
+        let Some(directives) = sections.section_mut(&section) else {
+            continue;
+        };
+        match directives.get_mut(key) {
+            _ if value.is_empty() => {
+                directives.remove(key);
+            }
+            Some(existing) if append => {
+                existing.push(' ');
+                existing.push_str(value);
+            }
+            _ => {
+                directives.insert(key.to_string(), value.to_string());
+            }
+        }
+    }
+}
+
 /// Parse a systemd unit file into a ServiceDefinition.
 fn parse_unit_file(content: &str, path: &Path) -> Result<ServiceDefinition> {
-    let sections = parse_sections(content);
+    parse_unit(parse_sections(content), path)
+}
 
+/// Build the ServiceDefinition of a unit from its sections.
+fn parse_unit(sections: UnitSections, path: &Path) -> Result<ServiceDefinition> {
     // Extract service name from filename
     let name = path
         .file_stem()
@@ -272,6 +398,7 @@ fn parse_unit_file(content: &str, path: &Path) -> Result<ServiceDefinition> {
     let wants = parse_list(sections.unit.get("Wants"));
     let before = parse_list(sections.unit.get("Before"));
     let after = parse_list(sections.unit.get("After"));
+    let requires_package = parse_list(sections.unit.get("RequiresPackage"));
 
     // Parse restart policy
     let restart = sections
@@ -302,31 +429,110 @@ fn parse_unit_file(content: &str, path: &Path) -> Result<ServiceDefinition> {
     // Check if enabled (based on WantedBy/RequiredBy)
     let enabled =
         sections.install.contains_key("WantedBy") || sections.install.contains_key("RequiredBy");
+    let mut wanted_by = parse_list(sections.install.get("WantedBy"));
+    wanted_by.extend(parse_list(sections.install.get("RequiredBy")));
+
+    // Standard input/output/error
+    let standard_input = sections
+        .service
+        .get("StandardInput")
+        .map(|s| normalize_stdin(s))
+        .unwrap_or_else(|| "null".to_string());
+
+    // Output follows a terminal or socket on standard input by default
+    let default_output = if standard_input == "null" {
+        "journal"
+    } else {
+        "inherit"
+    };
 
-    // Standard output/error
     let standard_output = sections
         .service
         .get("StandardOutput")
         .map(|s| normalize_stdio(s))
-        .unwrap_or_else(|| "journal".to_string());
+        .unwrap_or_else(|| default_output.to_string());
 
     let standard_error = sections
         .service
         .get("StandardError")
         .map(|s| normalize_stdio(s))
-        .unwrap_or_else(|| "journal".to_string());
+        .unwrap_or_else(|| default_output.to_string());
+
+    let is_true = |key: &str| {
+        sections
+            .service
+            .get(key)
+            .map(|s| s.to_lowercase() == "true" || s == "yes")
+            .unwrap_or(false)
+    };
+    let mut tty = TtyConfig {
+        reset: is_true("TTYReset"),
+        vhangup: is_true("TTYVHangup"),
+        ..TtyConfig::default()
+    };
+    if let Some(path) = sections.service.get("TTYPath") {
+        tty.path = PathBuf::from(path);
+    }
+
+    let network = NetworkConfig {
+        private_network: is_true("PrivateNetwork"),
+        nat: is_true("PrivateNetworkNAT"),
+        published_ports: sections
+            .service
+            .get("PublishPort")
+            .map(|ports| {
+                ports
+                    .split_whitespace()
+                    .filter_map(|p| p.parse().ok())
+                    .collect()
+            })
+            .unwrap_or_default(),
+    };
+
+    let seccomp = SeccompConfig {
+        system_call_filter: sections
+            .service
+            .get("SystemCallFilter")
+            .map(|f| f.split_whitespace().map(str::to_string).collect())
+            .unwrap_or_default(),
+        learn: is_true("SystemCallLearning"),
+    };
+
+    let security = SecurityConfig {
+        apparmor_profile: sections.service.get("AppArmorProfile").cloned(),
+        selinux_context: sections.service.get("SELinuxContext").cloned(),
+    };
 
     // Parse resource limits
     let resource_limits = parse_resource_limits(&sections.service);
 
-    // Parse watchdog config
+    // Parse watchdog config. WatchdogAction= lists the action on the first
+    // timeout, then the ones further consecutive timeouts escalate to.
     let watchdog = sections
         .service
         .get("WatchdogSec")
         .and_then(|s| parse_duration(s))
-        .map(|timeout| WatchdogConfig {
-            timeout,
-            action: "restart".to_string(),
+        .map(|timeout| {
+            let mut actions: Vec<WatchdogAction> = sections
+                .service
+                .get("WatchdogAction")
+                .map(|a| {
+                    a.split_whitespace()
+                        .filter_map(|a| a.parse().ok())
+                        .collect()
+                })
+                .unwrap_or_default();
+            let action = if actions.is_empty() {
+                WatchdogAction::default()
+            } else {
+                actions.remove(0)
+            };
+            WatchdogConfig {
+                timeout,
+                action,
+                escalate: actions,
+                recovery_command: sections.service.get("WatchdogRecoveryCommand").cloned(),
+            }
         });
 
     // Parse health check (from systemd notify or custom)
@@ -339,6 +545,13 @@ fn parse_unit_file(content: &str, path: &Path) -> Result<ServiceDefinition> {
         None
     };
 
+    // Parse path activation configuration
+    let path_config = if !sections.path.is_empty() {
+        Some(parse_path_config(&sections.path))
+    } else {
+        None
+    };
+
     // Parse socket configuration
     let sockets = if !sections.socket.is_empty() {
         parse_socket_config(&sections.socket)
@@ -346,6 +559,11 @@ fn parse_unit_file(content: &str, path: &Path) -> Result<ServiceDefinition> {
         Vec::new()
     };
 
+    let exit_idle_time = sections
+        .socket
+        .get("ExitIdleTimeSec")
+        .and_then(|s| parse_duration(s));
+
     // Check if template
     let template = name.contains('@');
 
@@ -364,22 +582,375 @@ fn parse_unit_file(content: &str, path: &Path) -> Result<ServiceDefinition> {
         wants,
         before,
         after,
+        requires_package,
         restart,
         restart_sec,
         timeout_start_sec,
         timeout_stop_sec,
         enabled,
+        wanted_by,
         health_check,
         resource_limits,
         sockets,
+        exit_idle_time,
         timer,
+        path: path_config,
         watchdog,
         template,
+        standard_input,
         standard_output,
         standard_error,
+        tty,
+        network,
+        seccomp,
+        security,
+    })
+}
+
+/// Sections understood by the loader.
+const SECTIONS: &[&str] = &["Unit", "Service", "Install", "Timer", "Socket", "Path"];
+
+/// How the loader interprets the value of a directive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DirectiveKind {
+    Text,
+    List,
+    Environment,
+    Type,
+    Restart,
+    Duration,
+    Size,
+    Percent,
+    Count,
+    Bool,
+    Mode,
+    Stdin,
+    Stdio,
+    Ports,
+    Syscalls,
+    Packages,
+    WatchdogActions,
+}
+
+impl DirectiveKind {
+    /// Whether repeated assignments add to the value rather than replace it.
+    fn is_list(self) -> bool {
+        use DirectiveKind::*;
+        matches!(self, List | Environment | Ports | Syscalls | Packages)
+    }
+}
+
+/// Kind of a directive the loader understands, or None if it is ignored.
+fn directive_kind(section: &str, key: &str) -> Option<DirectiveKind> {
+    use DirectiveKind::*;
+    Some(match (section, key) {
+        ("Unit", "Description" | "Documentation") => Text,
+        ("Unit", "Requires" | "Wants" | "Before" | "After") => List,
+        ("Unit", "RequiresPackage") => Packages,
+        ("Service", "Type") => Type,
+        (
+            "Service",
+            "ExecStart" | "ExecStop" | "ExecReload" | "WorkingDirectory" | "User" | "Group",
+        ) => Text,
+        ("Service", "Environment") => Environment,
+        ("Service", "Restart") => Restart,
+        (
+            "Service",
+            "RestartSec" | "TimeoutStartSec" | "TimeoutStopSec" | "WatchdogSec" | "LimitCPU",
+        ) => Duration,
+        (
+            "Service",
+            "MemoryLimit" | "MemoryMax" | "MemoryHigh" | "LimitFSIZE" | "LimitCORE" | "LimitSTACK",
+        ) => Size,
+        ("Service", "CPUQuota") => Percent,
+        ("Service", "LimitNOFILE" | "LimitNPROC") => Count,
+        ("Service", "StandardInput") => Stdin,
+        ("Service", "StandardOutput" | "StandardError") => Stdio,
+        ("Service", "TTYPath") => Text,
+        ("Service", "TTYReset" | "TTYVHangup") => Bool,
+        ("Service", "PrivateNetwork" | "PrivateNetworkNAT") => Bool,
+        ("Service", "PublishPort") => Ports,
+        ("Service", "SystemCallFilter") => Syscalls,
+        ("Service", "SystemCallLearning") => Bool,
+        ("Service", "AppArmorProfile" | "SELinuxContext") => Text,
+        ("Service", "WatchdogAction") => WatchdogActions,
+        ("Service", "WatchdogRecoveryCommand") => Text,
+        ("Install", "WantedBy" | "RequiredBy") => List,
+        ("Timer", "OnCalendar") => Text,
+        (
+            "Timer",
+            "OnBootSec" | "OnUnitActiveSec" | "OnUnitInactiveSec" | "AccuracySec"
+            | "RandomizedDelaySec",
+        ) => Duration,
+        ("Timer", "Persistent") => Bool,
+        (
+            "Socket",
+            "ListenStream"
+            | "ListenDatagram"
+            | "ListenSequentialPacket"
+            | "SocketUser"
+            | "SocketGroup",
+        ) => Text,
+        ("Socket", "Accept") => Bool,
+        ("Socket", "Backlog") => Count,
+        ("Socket", "ExitIdleTimeSec") => Duration,
+        ("Socket", "SocketMode") => Mode,
+        ("Path", "PathExists" | "PathChanged" | "DirectoryNotEmpty") => List,
+        ("Path", "MakeDirectory") => Bool,
+        ("Path", "TriggerLimitIntervalSec") => Duration,
+        ("Path", "TriggerLimitBurst") => Count,
+        _ => return None,
     })
 }
 
+/// Check a directive's value the way the loader will read it.
+fn check_value(kind: DirectiveKind, key: &str, value: &str, line: usize) -> Option<Diagnostic> {
+    let line = Some(line);
+    let invalid = |what: &str| {
+        Some(Diagnostic::error(
+            line,
+            format!("{}={}: {}", key, value, what),
+        ))
+    };
+    match kind {
+        DirectiveKind::Text | DirectiveKind::List => None,
+        DirectiveKind::Environment => value
+            .split_whitespace()
+            .map(|env| env.trim_matches('"').trim_matches('\''))
+            .find(|env| !env.contains('='))
+            .and_then(|env| invalid(&format!("'{}' is not a VAR=value assignment", env))),
+        DirectiveKind::Type => match value.to_lowercase().as_str() {
+            "simple" | "forking" | "oneshot" | "notify" | "idle" => None,
+            _ => invalid("unknown service type"),
+        },
+        DirectiveKind::Restart => match value.to_lowercase().as_str() {
+            "no" | "on-success" | "on-failure" | "on-abnormal" | "always" => None,
+            _ => invalid("unsupported restart policy"),
+        },
+        DirectiveKind::Duration => parse_duration(value)
+            .is_none()
+            .then(|| invalid("invalid duration"))
+            .flatten(),
+        DirectiveKind::Size => parse_memory_size(value)
+            .is_none()
+            .then(|| invalid("invalid size"))
+            .flatten(),
+        DirectiveKind::Percent => value
+            .strip_suffix('%')
+            .and_then(|p| p.trim().parse::<u32>().ok())
+            .is_none()
+            .then(|| invalid("expected a percentage"))
+            .flatten(),
+        DirectiveKind::Count => value
+            .parse::<u64>()
+            .is_err()
+            .then(|| invalid("expected a number"))
+            .flatten(),
+        DirectiveKind::Bool => match value.to_lowercase().as_str() {
+            "true" | "yes" | "false" | "no" => None,
+            _ => invalid("expected yes or no"),
+        },
+        DirectiveKind::Mode => u32::from_str_radix(value, 8)
+            .is_err()
+            .then(|| invalid("expected an octal mode"))
+            .flatten(),
+        DirectiveKind::Stdin => match normalize_stdin(value).as_str() {
+            "null" if !value.eq_ignore_ascii_case("null") => Some(Diagnostic::warning(
+                line,
+                format!("{}={} is not supported, input is /dev/null", key, value),
+            )),
+            _ => None,
+        },
+        DirectiveKind::Stdio => match normalize_stdio(value).as_str() {
+            "journal"
+                if !matches!(
+                    value.to_lowercase().as_str(),
+                    "journal" | "syslog" | "kmsg" | "journal+console"
+                ) =>
+            {
+                Some(Diagnostic::warning(
+                    line,
+                    format!(
+                        "{}={} is not supported, output goes to the journal",
+                        key, value
+                    ),
+                ))
+            }
+            _ => None,
+        },
+        DirectiveKind::Packages => parse_list(Some(&value.to_string()))
+            .iter()
+            .find(|p| packages::parse_package(p).is_none())
+            .and_then(|p| invalid(&format!("'{}' is not a category/name package", p))),
+        DirectiveKind::WatchdogActions => value
+            .split_whitespace()
+            .find_map(|a| a.parse::<WatchdogAction>().err())
+            .and_then(|e| invalid(&e)),
+        DirectiveKind::Ports => value
+            .split_whitespace()
+            .find_map(|p| p.parse::<PublishedPort>().err())
+            .and_then(|e| invalid(&e)),
+        DirectiveKind::Syscalls => {
+            let entries: Vec<String> = value.split_whitespace().map(str::to_string).collect();
+            if value.starts_with('~') {
+                invalid("deny lists are not supported")
+            } else {
+                let unknown = seccomp::unknown(&entries);
+                (!unknown.is_empty()).then(|| {
+                    Diagnostic::warning(
+                        line,
+                        format!(
+                            "{}: unknown system calls or groups, ignored: {}",
+                            key,
+                            unknown.join(" ")
+                        ),
+                    )
+                })
+            }
+        }
+    }
+}
+
+/// Validate a systemd unit file, reporting problems with line numbers.
+fn verify_unit_file(content: &str, path: &Path) -> VerifyReport {
+    let mut report = VerifyReport::new(path);
+    check_lines(content, &mut report, false);
+
+    match parse_unit_file(content, path) {
+        Ok(def) => {
+            report.definition = Some(def);
+            report.check_definition();
+        }
+        Err(e) => report
+            .diagnostics
+            .push(Diagnostic::error(None, e.to_string())),
+    }
+    report
+}
+
+/// Validate a drop-in file, then the unit `path` with `dropins` applied.
+fn verify_dropin_file(
+    content: &str,
+    dropin: &Path,
+    path: &Path,
+    dropins: &[PathBuf],
+) -> VerifyReport {
+    let mut report = VerifyReport::new(dropin);
+    check_lines(content, &mut report, true);
+
+    match super::ServiceLoader::load_with_dropins(&SystemdLoader, path, dropins) {
+        Ok(def) => {
+            report.definition = Some(def);
+            report.check_definition();
+        }
+        Err(e) => report
+            .diagnostics
+            .push(Diagnostic::error(None, e.to_string())),
+    }
+    report
+}
+
+/// Check the assignments of a unit or drop-in file line by line.
+fn check_lines(content: &str, report: &mut VerifyReport, dropin: bool) {
+    let mut section: Option<String> = None;
+    let mut seen: HashMap<(String, String), usize> = HashMap::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            let name = &line[1..line.len() - 1];
+            if !SECTIONS.contains(&name) {
+                report.diagnostics.push(Diagnostic::warning(
+                    Some(number),
+                    format!("unknown section [{}], ignored", name),
+                ));
+            }
+            section = Some(name.to_string());
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            report.diagnostics.push(Diagnostic::error(
+                Some(number),
+                format!("expected Key=Value, found '{}'", line),
+            ));
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+        let Some(section) = section.as_deref() else {
+            report.diagnostics.push(Diagnostic::error(
+                Some(number),
+                format!("{}= is outside of any section", key),
+            ));
+            continue;
+        };
+        if !SECTIONS.contains(&section) {
+            continue;
+        }
+        if value.ends_with('\\') {
+            report.diagnostics.push(Diagnostic::error(
+                Some(number),
+                format!("{}=: line continuations are not supported", key),
+            ));
+        }
+
+        let Some(kind) = directive_kind(section, key) else {
+            if !key.starts_with("X-") {
+                report.diagnostics.push(Diagnostic::warning(
+                    Some(number),
+                    format!("unknown key {}= in [{}], ignored", key, section),
+                ));
+            }
+            continue;
+        };
+
+        // An empty assignment in a drop-in clears the setting
+        if dropin && value.is_empty() {
+            seen.remove(&(section.to_string(), key.to_string()));
+            continue;
+        }
+
+        match seen.entry((section.to_string(), key.to_string())) {
+            Entry::Occupied(first) => {
+                let quiet = if dropin {
+                    kind.is_list()
+                } else {
+                    matches!(kind, DirectiveKind::List | DirectiveKind::Environment)
+                };
+                if !quiet {
+                    let effect = if dropin || matches!(section, "Timer" | "Socket") {
+                        "the earlier value is ignored"
+                    } else {
+                        "the values are joined"
+                    };
+                    report.diagnostics.push(Diagnostic::warning(
+                        Some(number),
+                        format!(
+                            "{}= is already set on line {}, {}",
+                            key,
+                            first.get(),
+                            effect
+                        ),
+                    ));
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(number);
+            }
+        }
+        report.key_lines.entry(key.to_string()).or_insert(number);
+
+        if let Some(diagnostic) = check_value(kind, key, value, number) {
+            report.diagnostics.push(diagnostic);
+        }
+    }
+}
+
 /// Parse a service type string to ServiceType enum.
 fn parse_service_type(s: &str) -> ServiceType {
     match s.to_lowercase().as_str() {
@@ -405,7 +976,7 @@ fn parse_restart_policy(s: &str) -> RestartPolicy {
 }
 
 /// Parse a duration string (supports "30s", "5min", "1h", etc.)
-fn parse_duration(s: &str) -> Option<Duration> {
+pub(crate) fn parse_duration(s: &str) -> Option<Duration> {
     let s = s.trim();
 
     // Try to parse as plain number (seconds)
@@ -449,10 +1020,19 @@ fn parse_list(s: Option<&String>) -> Vec<String> {
     .unwrap_or_default()
 }
 
+/// Normalize standard input type to buckos format.
+fn normalize_stdin(s: &str) -> String {
+    match s.to_lowercase().as_str() {
+        s @ ("tty" | "tty-force" | "tty-fail" | "socket") => s.to_string(),
+        _ => "null".to_string(),
+    }
+}
+
 /// Normalize standard I/O type to buckos format.
 fn normalize_stdio(s: &str) -> String {
     match s.to_lowercase().as_str() {
-        "inherit" | "tty" => "inherit".to_string(),
+        "inherit" => "inherit".to_string(),
+        "tty" => "tty".to_string(),
         "null" | "none" => "null".to_string(),
         "journal" | "syslog" | "kmsg" | "journal+console" => "journal".to_string(),
         s if s.starts_with("file:") => s.to_string(),
@@ -602,6 +1182,10 @@ fn parse_timer_config(timer: &HashMap<String, String>) -> TimerConfig {
         .get("AccuracySec")
         .and_then(|s| parse_duration(s))
         .unwrap_or(Duration::from_secs(60));
+    let randomized_delay = timer
+        .get("RandomizedDelaySec")
+        .and_then(|s| parse_duration(s))
+        .unwrap_or(Duration::ZERO);
 
     TimerConfig {
         on_calendar,
@@ -610,6 +1194,35 @@ fn parse_timer_config(timer: &HashMap<String, String>) -> TimerConfig {
         on_unit_inactive,
         persistent,
         accuracy,
+        randomized_delay,
+    }
+}
+
+/// Parse path activation configuration from [Path] section.
+fn parse_path_config(path: &HashMap<String, String>) -> PathConfig {
+    let paths = |key: &str| -> Vec<PathBuf> {
+        path.get(key)
+            .map(|s| s.split_whitespace().map(PathBuf::from).collect())
+            .unwrap_or_default()
+    };
+    let defaults = PathConfig::default();
+
+    PathConfig {
+        path_exists: paths("PathExists"),
+        path_changed: paths("PathChanged"),
+        directory_not_empty: paths("DirectoryNotEmpty"),
+        make_directory: path
+            .get("MakeDirectory")
+            .map(|s| s.to_lowercase() == "true" || s == "yes")
+            .unwrap_or(false),
+        trigger_limit_interval: path
+            .get("TriggerLimitIntervalSec")
+            .and_then(|s| parse_duration(s))
+            .unwrap_or(defaults.trigger_limit_interval),
+        trigger_limit_burst: path
+            .get("TriggerLimitBurst")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(defaults.trigger_limit_burst),
     }
 }
 
@@ -774,6 +1387,338 @@ WantedBy=multi-user.target
         assert_eq!(watchdog.timeout, Duration::from_secs(30));
     }
 
+    #[test]
+    fn test_dropins() {
+        use crate::loaders::LoaderRegistry;
+
+        let dir = std::env::temp_dir().join(format!("boss-dropins-{}", std::process::id()));
+        let (services, system) = (dir.join("services"), dir.join("system"));
+        let unit = services.join("web.service");
+        std::fs::create_dir_all(services.join("web.service.d")).unwrap();
+        std::fs::create_dir_all(system.join("web.service.d")).unwrap();
+        std::fs::write(
+            &unit,
+            "[Unit]\nWants=a\n[Service]\nExecStart=/bin/false\nRestart=no\n\
+             Environment=A=1\n[Install]\nWantedBy=multi-user.target\n",
+        )
+        .unwrap();
+        // Shipped next to the unit, replaced by the one in the system dir
+        std::fs::write(
+            services.join("web.service.d/10-vendor.conf"),
+            "[Service]\nRestart=always\n",
+        )
+        .unwrap();
+        std::fs::write(
+            services.join("web.service.d/20-wants.conf"),
+            "[Unit]\nWants=b\n[Service]\nExecStart=\nExecStart=/bin/true --fast\n",
+        )
+        .unwrap();
+        std::fs::write(
+            system.join("web.service.d/10-vendor.conf"),
+            "[Service]\nRestart=on-failure\nEnvironment=B=2\n",
+        )
+        .unwrap();
+        std::fs::write(
+            system.join("web.service.d/30-install.conf"),
+            "[Install]\nWantedBy=\n",
+        )
+        .unwrap();
+
+        let registry = LoaderRegistry::new();
+        let dropins = registry.find_dropins(&unit, &[system.clone(), services.clone()]);
+        assert_eq!(
+            dropins,
+            vec![
+                system.join("web.service.d/10-vendor.conf"),
+                services.join("web.service.d/20-wants.conf"),
+                system.join("web.service.d/30-install.conf"),
+            ]
+        );
+        let def = registry.load_with_dropins(&unit, &dropins).unwrap();
+        assert_eq!(def.wants, vec!["a", "b"]);
+        assert_eq!(def.exec_start, "/bin/true --fast");
+        assert_eq!(def.restart, RestartPolicy::OnFailure);
+        assert_eq!(def.environment.get("A").map(String::as_str), Some("1"));
+        assert_eq!(def.environment.get("B").map(String::as_str), Some("2"));
+        assert!(!def.enabled);
+
+        let bad = system.join("web.service.d/40-bad.conf");
+        std::fs::write(
+            &bad,
+            "[Service]\nRestartSec=soon\nRestart=no\nRestart=always\n",
+        )
+        .unwrap();
+        let report = registry
+            .verify_dropin(&unit, std::slice::from_ref(&bad), &bad)
+            .unwrap();
+        let found: Vec<(Option<usize>, &str)> = report
+            .diagnostics
+            .iter()
+            .map(|d| (d.line, d.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Some(2), "RestartSec=soon: invalid duration"),
+                (
+                    Some(4),
+                    "Restart= is already set on line 3, the earlier value is ignored"
+                ),
+            ]
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_parse_requires_package() {
+        let content = r#"
+[Unit]
+RequiresPackage=www-servers/nginx dev-libs/openssl
+
+[Service]
+ExecStart=/usr/sbin/nginx
+"#;
+        let def = parse_unit_file(content, Path::new("nginx.service")).unwrap();
+        assert_eq!(
+            def.requires_package,
+            vec!["www-servers/nginx", "dev-libs/openssl"]
+        );
+
+        let report = verify_unit_file(
+            "[Unit]\nRequiresPackage=nginx\n[Service]\nExecStart=/bin/true\n",
+            Path::new("bad.service"),
+        );
+        assert!(report
+            .diagnostics
+            .iter()
+            .any(|d| d.message.contains("'nginx' is not a category/name package")));
+    }
+
+    #[test]
+    fn test_parse_watchdog_actions() {
+        let content = r#"
+[Service]
+ExecStart=/usr/sbin/nginx
+WatchdogSec=30
+WatchdogAction=restart kill reboot
+"#;
+        let def = parse_unit_file(content, Path::new("nginx.service")).unwrap();
+        let watchdog = def.watchdog.unwrap();
+        assert_eq!(watchdog.timeout, std::time::Duration::from_secs(30));
+        let actions: Vec<WatchdogAction> = (1..=4).map(|n| watchdog.action_for(n)).collect();
+        assert_eq!(
+            actions,
+            vec![
+                WatchdogAction::Restart,
+                WatchdogAction::RestartForce,
+                WatchdogAction::Reboot,
+                WatchdogAction::Reboot
+            ]
+        );
+
+        let report = verify_unit_file(
+            "[Service]\nExecStart=/bin/true\nWatchdogSec=5\nWatchdogAction=restart panic\n",
+            Path::new("bad.service"),
+        );
+        assert!(report
+            .diagnostics
+            .iter()
+            .any(|d| d.message.contains("unknown watchdog action 'panic'")));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_watchdog_timeouts_escalate() {
+        use crate::manager::ServiceManager;
+
+        let dir = std::env::temp_dir().join(format!("boss-watchdog-{}", std::process::id()));
+        let services = dir.join("services");
+        std::fs::create_dir_all(&services).unwrap();
+        let recovered = dir.join("recovered");
+        std::fs::write(
+            services.join("web.service"),
+            format!(
+                "[Service]\nExecStart=/bin/sleep 30\nWatchdogSec=100ms\n\
+                 WatchdogAction=run none\n\
+                 WatchdogRecoveryCommand=echo $BOSS_SERVICE $BOSS_WATCHDOG_FAILURES > {}\n",
+                recovered.display()
+            ),
+        )
+        .unwrap();
+
+        let manager = ServiceManager::new(services);
+        manager.load_services().await.unwrap();
+        manager.start_service("web").await.unwrap();
+        manager.set_status_text("web", "serving").await.unwrap();
+        assert!(manager.check_watchdogs().await.is_some());
+        assert_eq!(
+            manager.get_status("web").await.unwrap().watchdog_failures,
+            0
+        );
+
+        tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+        manager.check_watchdogs().await;
+        for _ in 0..50 {
+            if recovered.exists() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(std::fs::read_to_string(&recovered).unwrap(), "web 1\n");
+
+        // The second timeout escalates to only recording it
+        tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+        manager.check_watchdogs().await;
+        let status = manager.get_status("web").await.unwrap();
+        assert_eq!(status.watchdog_failures, 2);
+        let logs = manager.get_logs("web", None).await;
+        assert!(logs
+            .iter()
+            .any(|e| e.message.contains("(2 consecutive), action: none")
+                && e.message.contains("last status: serving")));
+
+        manager.watchdog_ping("web").await.unwrap();
+        assert_eq!(
+            manager.get_status("web").await.unwrap().watchdog_failures,
+            0
+        );
+        manager.stop_service("web").await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_parse_tty_unit() {
+        let content = r#"
+[Service]
+ExecStart=/sbin/agetty --noclear %i linux
+StandardInput=tty
+TTYPath=/dev/%i
+TTYReset=yes
+TTYVHangup=yes
+"#;
+
+        let def = parse_unit_file(content, Path::new("getty@.service")).unwrap();
+        assert_eq!(def.standard_input, "tty");
+        assert_eq!(def.standard_output, "inherit");
+        assert!(def.tty.reset && def.tty.vhangup);
+
+        let def = def.instantiate("tty1");
+        assert_eq!(def.tty.path, PathBuf::from("/dev/tty1"));
+        assert!(def.stdin_is_tty());
+    }
+
+    #[test]
+    fn test_parse_path_unit() {
+        let content = r#"
+[Service]
+ExecStart=/usr/bin/process-spool
+
+[Path]
+DirectoryNotEmpty=/var/spool/jobs
+PathChanged=/etc/jobs.conf
+PathChanged=/etc/jobs.d/extra.conf
+MakeDirectory=yes
+TriggerLimitBurst=10
+"#;
+
+        let def = parse_unit_file(content, Path::new("jobs.service")).unwrap();
+        let path = def.path.unwrap();
+        assert_eq!(
+            path.directory_not_empty,
+            vec![PathBuf::from("/var/spool/jobs")]
+        );
+        assert_eq!(
+            path.path_changed,
+            vec![
+                PathBuf::from("/etc/jobs.conf"),
+                PathBuf::from("/etc/jobs.d/extra.conf")
+            ]
+        );
+        assert!(path.path_exists.is_empty());
+        assert!(path.make_directory);
+        assert_eq!(path.trigger_limit_burst, 10);
+        assert_eq!(path.trigger_limit_interval, Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_parse_private_network() {
+        let content = r#"
+[Service]
+ExecStart=/usr/bin/untrusted
+PrivateNetwork=yes
+PrivateNetworkNAT=yes
+PublishPort=8080:80
+PublishPort=5353/udp
+"#;
+
+        let def = parse_unit_file(content, Path::new("untrusted.service")).unwrap();
+        assert!(def.network.private_network);
+        assert!(def.network.nat);
+        let ports: Vec<String> = def
+            .network
+            .published_ports
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        assert_eq!(ports, vec!["8080:80/tcp", "5353:5353/udp"]);
+
+        let report = verify_unit_file(
+            "[Service]\nExecStart=/bin/true\nPublishPort=80:99999\n",
+            Path::new("bad.service"),
+        );
+        assert!(report
+            .diagnostics
+            .iter()
+            .any(|d| d.message.contains("invalid port '99999'")));
+    }
+
+    #[test]
+    fn test_verify_unit_file() {
+        let content = r#"
+[Unit]
+Description=Broken
+After=db
+
+[Service]
+Type=oneshot
+ExecStart=/bin/sh -c true
+Restart=always
+RestartSec=soon
+Nice=5
+Type=simple
+
+[Bogus]
+Anything=goes
+"#;
+        let report = verify_unit_file(content, Path::new("broken.service"));
+        let found: Vec<(Option<usize>, &str)> = report
+            .diagnostics
+            .iter()
+            .map(|d| (d.line, d.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Some(10), "RestartSec=soon: invalid duration"),
+                (Some(11), "unknown key Nice= in [Service], ignored"),
+                (
+                    Some(12),
+                    "Type= is already set on line 7, the values are joined"
+                ),
+                (Some(14), "unknown section [Bogus], ignored"),
+            ]
+        );
+        assert!(report.has_errors());
+
+        // The joined Type is read as simple, so only a single Type=oneshot
+        // conflicts with Restart=always
+        let report = verify_unit_file(
+            "[Service]\nType=oneshot\nExecStart=/bin/sh\nRestart=always\n",
+            Path::new("job.service"),
+        );
+        assert_eq!(report.diagnostics.len(), 1);
+        assert_eq!(report.diagnostics[0].line, Some(4));
+    }
+
     #[test]
     fn test_parse_duration() {
         assert_eq!(parse_duration("30"), Some(Duration::from_secs(30)));
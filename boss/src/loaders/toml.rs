@@ -1,10 +1,15 @@
 //! TOML service loader.
 //!
 //! This is the native configuration format for buckos services.
+//!
+//! Drop-ins are TOML files whose tables are merged into the unit's: a key
+//! set in a drop-in replaces the unit's value, arrays included, and keys it
+//! leaves out keep theirs.
 
 use crate::error::{Error, Result};
 use crate::service::ServiceDefinition;
-use std::path::Path;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
 
 /// Loader for TOML service configuration files.
 pub struct TomlLoader;
@@ -28,6 +33,39 @@ impl super::ServiceLoader for TomlLoader {
     fn name(&self) -> &'static str {
         "TOML"
     }
+
+    fn dropin_extension(&self) -> Option<&'static str> {
+        Some("toml")
+    }
+
+    fn load_with_dropins(&self, path: &Path, dropins: &[PathBuf]) -> Result<ServiceDefinition> {
+        let mut unit = parse_table(path)?;
+        for dropin in dropins {
+            merge(&mut unit, parse_table(dropin)?);
+        }
+        ServiceDefinition::deserialize(unit).map_err(|e| {
+            Error::ConfigError(format!("Failed to parse TOML {}: {}", path.display(), e))
+        })
+    }
+}
+
+/// Read a TOML file as a table.
+fn parse_table(path: &Path) -> Result<toml::Table> {
+    super::read_file(path)?
+        .parse()
+        .map_err(|e| Error::ConfigError(format!("Failed to parse TOML {}: {}", path.display(), e)))
+}
+
+/// Merge `over` into `base`, table by table.
+fn merge(base: &mut toml::Table, over: toml::Table) {
+    for (key, value) in over {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(over)) => merge(base, over),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
 }
 
 impl TomlLoader {
@@ -42,3 +80,30 @@ impl Default for TomlLoader {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loaders::ServiceLoader;
+
+    #[test]
+    fn test_dropins() {
+        let dir = std::env::temp_dir().join(format!("boss-toml-dropins-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let unit = dir.join("web.toml");
+        std::fs::write(
+            &unit,
+            "name = \"web\"\ndescription = \"Web\"\nexec_start = \"/usr/bin/web\"\nwants = [\"a\"]\n\
+             [environment]\nA = \"1\"\n",
+        )
+        .unwrap();
+        let dropin = dir.join("override.toml");
+        std::fs::write(&dropin, "wants = [\"b\"]\n[environment]\nB = \"2\"\n").unwrap();
+
+        let def = TomlLoader.load_with_dropins(&unit, &[dropin]).unwrap();
+        assert_eq!(def.exec_start, "/usr/bin/web");
+        assert_eq!(def.wants, vec!["b"]);
+        assert_eq!(def.environment.len(), 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
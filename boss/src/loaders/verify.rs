@@ -0,0 +1,385 @@
+//! Service definition validation.
+//!
+//! Loaders report problems in a service file as structured diagnostics
+//! instead of failing on the first one (or silently falling back to a
+//! default), so unit authors can check a file before installing it.
+//! Checks that need the whole service set, such as dependency cycles, are
+//! run separately against the other loaded definitions.
+
+use crate::process::SERVICE_PATH;
+use crate::service::{RestartPolicy, ServiceDefinition, ServiceType};
+use crate::timer::CalendarSpec;
+use std::collections::{HashMap, HashSet};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The file loads, but probably not the way the author intended
+    Warning,
+    /// The file does not load or the service cannot start
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A single problem found in a service file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// 1-based line number, if the format tracks lines
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(line: Option<usize>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            line,
+            message: message.into(),
+        }
+    }
+
+    pub fn warning(line: Option<usize>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+/// Result of validating one service file.
+#[derive(Debug, Clone)]
+pub struct VerifyReport {
+    pub path: PathBuf,
+    /// The loaded definition, if the file loads at all
+    pub definition: Option<ServiceDefinition>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Line of the first assignment of each key
+    pub(crate) key_lines: HashMap<String, usize>,
+}
+
+impl VerifyReport {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            definition: None,
+            diagnostics: Vec::new(),
+            key_lines: HashMap::new(),
+        }
+    }
+
+    /// Whether any diagnostic is an error.
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|d| d.severity == Severity::Error)
+    }
+
+    /// Check the loaded definition for problems independent of the format.
+    pub fn check_definition(&mut self) {
+        let Some(def) = self.definition.take() else {
+            return;
+        };
+        let line = |key: &str| self.key_lines.get(key).copied();
+        let mut found = Vec::new();
+
+        for (key, command) in [
+            ("ExecStart", Some(&def.exec_start)),
+            ("ExecStop", def.exec_stop.as_ref()),
+            ("ExecReload", def.exec_reload.as_ref()),
+        ] {
+            if let Some(message) = command.and_then(|c| check_command(c)) {
+                found.push(Diagnostic::error(
+                    line(key),
+                    format!("{}: {}", key, message),
+                ));
+            }
+        }
+
+        if def.service_type == ServiceType::Oneshot
+            && matches!(
+                def.restart,
+                RestartPolicy::Always | RestartPolicy::OnSuccess
+            )
+        {
+            found.push(Diagnostic::error(
+                line("Restart"),
+                "Restart=always and Restart=on-success are not allowed for Type=oneshot",
+            ));
+        }
+
+        for (key, deps) in [
+            ("Requires", &def.requires),
+            ("Wants", &def.wants),
+            ("Before", &def.before),
+            ("After", &def.after),
+        ] {
+            if deps.contains(&def.name) {
+                found.push(Diagnostic::error(
+                    line(key),
+                    format!("{} refers to the service itself", key),
+                ));
+            }
+        }
+        for unit in def.before.iter().filter(|u| def.after.contains(u)) {
+            found.push(Diagnostic::error(
+                line("Before"),
+                format!("ordered both before and after {}", unit),
+            ));
+        }
+
+        if let Some(limits) = &def.resource_limits {
+            if let (Some(soft), Some(hard)) = (limits.memory_soft, limits.memory_hard) {
+                if soft > hard {
+                    found.push(Diagnostic::warning(
+                        line("MemoryHigh"),
+                        "MemoryHigh is above the hard memory limit and has no effect",
+                    ));
+                }
+            }
+        }
+
+        if let Some(timer) = &def.timer {
+            if timer.on_calendar.is_none()
+                && timer.on_boot.is_none()
+                && timer.on_unit_active.is_none()
+                && timer.on_unit_inactive.is_none()
+            {
+                found.push(Diagnostic::warning(
+                    None,
+                    "timer has no OnCalendar, OnBootSec, OnUnitActiveSec or OnUnitInactiveSec and never fires",
+                ));
+            }
+            if let Some(Err(e)) = timer.on_calendar.as_deref().map(str::parse::<CalendarSpec>) {
+                found.push(Diagnostic::error(
+                    line("OnCalendar"),
+                    format!("OnCalendar: {}", e),
+                ));
+            }
+        }
+
+        if let Some(path) = &def.path {
+            if path.path_exists.is_empty()
+                && path.path_changed.is_empty()
+                && path.directory_not_empty.is_empty()
+            {
+                found.push(Diagnostic::warning(
+                    None,
+                    "path unit has no PathExists, PathChanged or DirectoryNotEmpty and never triggers",
+                ));
+            }
+        }
+
+        let network = &def.network;
+        if !network.private_network && (network.nat || !network.published_ports.is_empty()) {
+            let key = if network.nat {
+                "PrivateNetworkNAT"
+            } else {
+                "PublishPort"
+            };
+            found.push(Diagnostic::warning(
+                line(key),
+                format!("{} has no effect without PrivateNetwork=yes", key),
+            ));
+        }
+
+        self.diagnostics.extend(found);
+        self.definition = Some(def);
+    }
+
+    /// Check for dependency cycles through the other known services.
+    pub fn check_dependencies(&mut self, services: &HashMap<String, ServiceDefinition>) {
+        let Some(def) = &self.definition else {
+            return;
+        };
+        if let Some(cycle) = find_cycle(def, services) {
+            let key = if def.requires.contains(&cycle[1]) {
+                "Requires"
+            } else if def.after.contains(&cycle[1]) {
+                "After"
+            } else {
+                "Wants"
+            };
+            self.diagnostics.push(Diagnostic::error(
+                self.key_lines.get(key).copied(),
+                format!("dependency cycle: {}", cycle.join(" -> ")),
+            ));
+        }
+    }
+}
+
+impl std::fmt::Display for VerifyReport {
+    /// One `path:line: severity: message` line per diagnostic.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut diagnostics: Vec<&Diagnostic> = self.diagnostics.iter().collect();
+        diagnostics.sort_by_key(|d| d.line);
+        for d in diagnostics {
+            match d.line {
+                Some(line) => writeln!(
+                    f,
+                    "{}:{}: {}: {}",
+                    self.path.display(),
+                    line,
+                    d.severity,
+                    d.message
+                )?,
+                None => writeln!(f, "{}: {}: {}", self.path.display(), d.severity, d.message)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check that the program of a command line exists and is executable.
+fn check_command(command: &str) -> Option<String> {
+    let Some(program) = command.split_whitespace().next() else {
+        return Some("empty command".to_string());
+    };
+    if let Some(prefix) = program.chars().next().filter(|c| "-@:+!".contains(*c)) {
+        return Some(format!("command prefix '{}' is not supported", prefix));
+    }
+
+    let candidates: Vec<PathBuf> = if program.contains('/') {
+        vec![PathBuf::from(program)]
+    } else {
+        SERVICE_PATH
+            .split(':')
+            .map(|dir| Path::new(dir).join(program))
+            .collect()
+    };
+    let mut found = false;
+    for candidate in &candidates {
+        if let Ok(meta) = std::fs::metadata(candidate) {
+            if meta.is_file() && meta.permissions().mode() & 0o111 != 0 {
+                return None;
+            }
+            found = true;
+        }
+    }
+    if found {
+        Some(format!("{} is not executable", program))
+    } else if program.contains('/') {
+        Some(format!("{} does not exist", program))
+    } else {
+        Some(format!("{} not found in {}", program, SERVICE_PATH))
+    }
+}
+
+/// Find a dependency cycle through `def`, following Requires, After and
+/// Wants like the manager does.
+///
+/// Returns the path from the service back to itself, e.g. `[a, b, a]`.
+pub fn find_cycle(
+    def: &ServiceDefinition,
+    services: &HashMap<String, ServiceDefinition>,
+) -> Option<Vec<String>> {
+    let deps = |name: &str| -> Vec<String> {
+        let d = if name == def.name {
+            Some(def)
+        } else {
+            services.get(name)
+        };
+        let mut deps: Vec<String> = d
+            .map(|d| {
+                d.requires
+                    .iter()
+                    .chain(&d.after)
+                    .chain(&d.wants)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        deps.sort();
+        deps.dedup();
+        deps
+    };
+
+    let mut path = vec![def.name.clone()];
+    let mut visited = HashSet::new();
+    let mut stack = vec![deps(&def.name)];
+    while let Some(next) = stack.last_mut() {
+        let Some(name) = next.pop() else {
+            stack.pop();
+            path.pop();
+            continue;
+        };
+        if name == def.name {
+            path.push(name);
+            return Some(path);
+        }
+        if !visited.insert(name.clone()) {
+            continue;
+        }
+        stack.push(deps(&name));
+        path.push(name);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(name: &str, requires: &[&str], after: &[&str]) -> ServiceDefinition {
+        let mut def = ServiceDefinition::new(name, "/bin/sh");
+        def.requires = requires.iter().map(|s| s.to_string()).collect();
+        def.after = after.iter().map(|s| s.to_string()).collect();
+        def
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        let services: HashMap<String, ServiceDefinition> = [
+            service("b", &["c"], &[]),
+            service("c", &[], &["a", "d"]),
+            service("d", &[], &[]),
+        ]
+        .into_iter()
+        .map(|d| (d.name.clone(), d))
+        .collect();
+
+        let a = service("a", &["b"], &["network"]);
+        assert_eq!(find_cycle(&a, &services).unwrap(), vec!["a", "b", "c", "a"]);
+        assert_eq!(find_cycle(&service("a", &["d"], &[]), &services), None);
+    }
+
+    #[test]
+    fn test_check_definition() {
+        let mut def = service("web", &["web"], &["db"]);
+        def.exec_start = "/nonexistent/webd --port 80".to_string();
+        def.service_type = ServiceType::Oneshot;
+        def.restart = RestartPolicy::Always;
+        def.before = vec!["db".to_string()];
+
+        let mut report = VerifyReport::new(Path::new("web.toml"));
+        report.definition = Some(def);
+        report.check_definition();
+        let messages: Vec<&str> = report
+            .diagnostics
+            .iter()
+            .map(|d| d.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "ExecStart: /nonexistent/webd does not exist",
+                "Restart=always and Restart=on-success are not allowed for Type=oneshot",
+                "Requires refers to the service itself",
+                "ordered both before and after db",
+            ]
+        );
+        assert!(report.has_errors());
+        assert_eq!(check_command("sh -c true"), None);
+    }
+}
@@ -0,0 +1,242 @@
+//! Mandatory access control labels.
+//!
+//! A service can name an AppArmor profile (`AppArmorProfile=`) or an
+//! SELinux context (`SELinuxContext=`) to run under. The label is requested
+//! for the next exec just before the service's program runs, so the kernel
+//! moves the process into it as the program starts. A service whose label
+//! cannot be applied, because its security module is not active or the
+//! profile is not loaded, fails to start saying so, unless the label is
+//! prefixed with `-`, in which case it runs unconfined.
+
+use crate::error::{Error, Result};
+use crate::service::SecurityConfig;
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use tracing::debug;
+
+/// A Linux security module that labels processes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lsm {
+    AppArmor,
+    SELinux,
+}
+
+impl std::fmt::Display for Lsm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Lsm::AppArmor => write!(f, "AppArmor"),
+            Lsm::SELinux => write!(f, "SELinux"),
+        }
+    }
+}
+
+/// The labelling security modules the kernel has active.
+pub fn active() -> Vec<Lsm> {
+    match std::fs::read_to_string("/sys/kernel/security/lsm") {
+        Ok(list) => parse_lsm_list(&list),
+        // securityfs is not mounted; look for the modules' own filesystems
+        Err(_) => {
+            let mut active = Vec::new();
+            if std::fs::read_to_string("/sys/module/apparmor/parameters/enabled")
+                .is_ok_and(|enabled| enabled.trim() == "Y")
+            {
+                active.push(Lsm::AppArmor);
+            }
+            if Path::new("/sys/fs/selinux/enforce").exists() {
+                active.push(Lsm::SELinux);
+            }
+            active
+        }
+    }
+}
+
+/// Labelling modules in the comma separated list of
+/// /sys/kernel/security/lsm.
+fn parse_lsm_list(list: &str) -> Vec<Lsm> {
+    list.trim()
+        .split(',')
+        .filter_map(|name| match name {
+            "apparmor" => Some(Lsm::AppArmor),
+            "selinux" => Some(Lsm::SELinux),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Names of the loaded AppArmor profiles, from lines such as
+/// `/usr/sbin/nginx (enforce)`.
+fn parse_profiles(content: &str) -> Vec<&str> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            Some(line.rsplit_once(" (").map_or(line, |(name, _)| name))
+                .filter(|name| !name.is_empty())
+        })
+        .collect()
+}
+
+/// Split the `-` prefix off a label, returning whether it is optional.
+fn optional(label: &str) -> (&str, bool) {
+    match label.strip_prefix('-') {
+        Some(label) => (label, true),
+        None => (label, false),
+    }
+}
+
+/// A label to request for the next exec.
+#[derive(Debug, Clone)]
+pub struct ExecLabel {
+    attr: CString,
+    value: CString,
+}
+
+impl ExecLabel {
+    fn new(attr: &Path, value: &str) -> Result<Self> {
+        let invalid = |_| Error::ConfigError(format!("invalid security label '{}'", value));
+        Ok(Self {
+            attr: CString::new(attr.as_os_str().as_bytes()).map_err(invalid)?,
+            value: CString::new(value).map_err(invalid)?,
+        })
+    }
+
+    /// Request the label for the next exec of the calling process.
+    ///
+    /// Only makes system calls, so it may run between fork and exec.
+    pub fn apply(&self) -> std::io::Result<()> {
+        unsafe {
+            let fd = libc::open(self.attr.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+            if fd < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let bytes = self.value.as_bytes();
+            let written = libc::write(fd, bytes.as_ptr() as *const libc::c_void, bytes.len());
+            let error = std::io::Error::last_os_error();
+            libc::close(fd);
+            if written < 0 {
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The labels to apply for a service, checking that each can be.
+pub fn exec_labels(config: &SecurityConfig) -> Result<Vec<ExecLabel>> {
+    let active = active();
+    let mut labels = Vec::new();
+
+    if let Some(profile) = &config.apparmor_profile {
+        let (profile, optional) = optional(profile);
+        let loaded = std::fs::read_to_string("/sys/kernel/security/apparmor/profiles")
+            .is_ok_and(|profiles| parse_profiles(&profiles).contains(&profile));
+        let problem = if !active.contains(&Lsm::AppArmor) {
+            Some("AppArmor is not active")
+        } else if !loaded {
+            Some("the profile is not loaded")
+        } else {
+            None
+        };
+        match problem {
+            None => {
+                // Kernels with LSM stacking have a directory per module
+                let attr = Path::new("/proc/self/attr/apparmor/exec");
+                let attr = if attr.exists() {
+                    attr
+                } else {
+                    Path::new("/proc/self/attr/exec")
+                };
+                labels.push(ExecLabel::new(attr, &format!("exec {}", profile))?);
+            }
+            Some(problem) if optional => {
+                debug!(profile, problem, "Skipping AppArmor profile");
+            }
+            Some(problem) => {
+                return Err(Error::ConfigError(format!(
+                    "cannot use AppArmor profile '{}': {}",
+                    profile, problem
+                )));
+            }
+        }
+    }
+
+    if let Some(context) = &config.selinux_context {
+        let (context, optional) = optional(context);
+        let problem = if !active.contains(&Lsm::SELinux) {
+            Some("SELinux is not active".to_string())
+        } else {
+            // The kernel checks a context written to the context file
+            std::fs::write("/sys/fs/selinux/context", context)
+                .err()
+                .map(|e| format!("the context is not valid in the loaded policy ({})", e))
+        };
+        match problem {
+            None => labels.push(ExecLabel::new(Path::new("/proc/self/attr/exec"), context)?),
+            Some(problem) if optional => {
+                debug!(context, problem, "Skipping SELinux context");
+            }
+            Some(problem) => {
+                return Err(Error::ConfigError(format!(
+                    "cannot use SELinux context '{}': {}",
+                    context, problem
+                )));
+            }
+        }
+    }
+
+    Ok(labels)
+}
+
+/// The label a process runs under, if a labelling module is active.
+pub fn process_label(pid: u32) -> Option<String> {
+    ["attr/apparmor/current", "attr/current"]
+        .iter()
+        .find_map(|attr| std::fs::read_to_string(format!("/proc/{}/{}", pid, attr)).ok())
+        .map(|label| label.trim_end_matches(['\0', '\n']).to_string())
+        .filter(|label| !label.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lsm_and_profiles() {
+        assert_eq!(
+            parse_lsm_list("lockdown,capability,landlock,yama,apparmor,bpf\n"),
+            vec![Lsm::AppArmor]
+        );
+        assert_eq!(parse_lsm_list("capability,selinux"), vec![Lsm::SELinux]);
+        assert!(parse_lsm_list("capability,yama").is_empty());
+
+        let profiles =
+            "/usr/sbin/nginx (enforce)\nbuckos-web (complain)\n\nlsb_release (enforce)\n";
+        assert_eq!(
+            parse_profiles(profiles),
+            vec!["/usr/sbin/nginx", "buckos-web", "lsb_release"]
+        );
+    }
+
+    #[test]
+    fn test_optional_labels() {
+        assert_eq!(optional("-buckos-web"), ("buckos-web", true));
+        assert_eq!(
+            optional("system_u:system_r:httpd_t:s0"),
+            ("system_u:system_r:httpd_t:s0", false)
+        );
+
+        // Missing optional labels are skipped, required ones fail the start
+        let config = |profile: &str| SecurityConfig {
+            apparmor_profile: Some(profile.to_string()),
+            selinux_context: None,
+        };
+        assert!(exec_labels(&config("-buckos-no-such-profile"))
+            .unwrap()
+            .is_empty());
+        let err = exec_labels(&config("buckos-no-such-profile"))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("cannot use AppArmor profile 'buckos-no-such-profile'"));
+    }
+}
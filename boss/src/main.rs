@@ -3,12 +3,24 @@
 //! This is the main entry point for the buckos init system.
 //! It can run as PID 1 or as a service management tool.
 
+use buckos_boss::tr;
+use buckos_boss::volatile;
 use buckos_boss::{
-    create_test_init, ControlClient, ControlResponse, Init, InitConfig, ServiceDefinition,
-    ServiceStatus, ShutdownType, SystemdLoader,
+    coredump, create_test_init, journal_vacuum, loaders, seccomp, session, swap, timesync,
+    BootHistory, BootSpec, ClockSync, ControlClient, ControlResponse, CoredumpLimits,
+    CoredumpStore, CrashedProcess, Cursor, ExportFormat, InhibitWhat, Init, InitConfig,
+    JournalExporter, JournalFilter, JournalLimits, LoaderRegistry, PresetAction, PresetMode,
+    Priority, Provisioner, RemoteSyslogConfig, ServiceDefinition, ServiceManager, ServiceStatus,
+    Session, SessionSource, SessionStore, ShutdownType, SntpClient, StartupLimits, SwapConfig,
+    SystemdLoader, TransientUnit, VacuumCriteria, ZramConfig, DEFAULT_CONTROL_SOCKET,
+    GENERATOR_DIRS, PACKAGE_DB_DIR,
 };
+use buckos_core::compress::Compression;
+use buckos_core::machine_id::MACHINE_ID_PATH;
 use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tracing::{error, info};
 use tracing_subscriber::EnvFilter;
 
@@ -32,6 +44,53 @@ struct Cli {
     #[arg(long)]
     no_mount: bool,
 
+    /// Forward service output to a remote syslog collector
+    /// (udp://host[:port], tcp://host[:port] or tls://host[:port])
+    #[arg(long)]
+    syslog_target: Option<String>,
+
+    /// Least severe priority forwarded to the remote collector
+    #[arg(long, default_value = "info")]
+    syslog_priority: Priority,
+
+    /// Maximum service starts running at once during boot
+    #[arg(long)]
+    max_parallel_starts: Option<usize>,
+
+    /// Maximum service starts running at once at the same dependency depth
+    #[arg(long)]
+    max_starts_per_depth: Option<usize>,
+
+    /// Most disk space the persistent journal may use (e.g. 512M)
+    #[arg(long, value_parser = journal_vacuum::parse_size)]
+    journal_max_use: Option<u64>,
+
+    /// Disk space the persistent journal must leave free (e.g. 1G)
+    #[arg(long, value_parser = journal_vacuum::parse_size)]
+    journal_keep_free: Option<u64>,
+
+    /// Compress journal entries from earlier boots into per-service
+    /// archives (e.g. zstd, zstd:19, xz:9)
+    #[arg(long)]
+    journal_compress: Option<Compression>,
+
+    /// Don't activate swap from /etc/fstab
+    #[arg(long)]
+    no_swap: bool,
+
+    /// Set up zram swap sized as this fraction of memory (e.g. 0.5)
+    #[arg(long)]
+    zram: Option<f64>,
+
+    /// Compression algorithm of the zram swap
+    #[arg(long, default_value = "zstd")]
+    zram_algorithm: String,
+
+    /// Keep changes to /etc in memory only, losing them at shutdown;
+    /// /var stays persistent
+    #[arg(long)]
+    volatile: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -65,6 +124,25 @@ enum Commands {
         name: String,
     },
 
+    /// Run a command as a transient unit of the running init
+    Run {
+        /// Unit name (default: run-<id>)
+        #[arg(long)]
+        unit: Option<String>,
+        /// Unit property as Key=Value, e.g. MemoryMax=512M (repeatable)
+        #[arg(short, long = "property")]
+        properties: Vec<String>,
+        /// Start the command on this calendar schedule instead of now
+        #[arg(long)]
+        on_calendar: Option<String>,
+        /// Start the command after this delay instead of now, e.g. 5min
+        #[arg(long)]
+        on_active: Option<String>,
+        /// Program and arguments
+        #[arg(required = true, trailing_var_arg = true)]
+        command: Vec<String>,
+    },
+
     /// Show service status
     Status {
         /// Service name (optional, shows all if not specified)
@@ -74,6 +152,9 @@ enum Commands {
     /// List all services
     List,
 
+    /// List timers and when they next elapse
+    ListTimers,
+
     /// Enable a service for auto-start
     Enable {
         /// Service name
@@ -86,6 +167,22 @@ enum Commands {
         name: String,
     },
 
+    /// Enable or disable a service as the preset files say
+    Preset {
+        /// Service name
+        name: String,
+        /// Which actions to apply: full, enable-only or disable-only
+        #[arg(long, default_value = "full")]
+        preset_mode: PresetMode,
+    },
+
+    /// Apply the preset files to every service
+    PresetAll {
+        /// Which actions to apply: full, enable-only or disable-only
+        #[arg(long, default_value = "full")]
+        preset_mode: PresetMode,
+    },
+
     /// Mask a service to prevent it from starting
     Mask {
         /// Service name
@@ -100,8 +197,14 @@ enum Commands {
 
     /// Show service logs
     Logs {
-        /// Service name
-        name: String,
+        /// Service names and FIELD=VALUE matches (_PID=1234, ERRNO=2,
+        /// UNIT=nginx.service); entries must match every field, and any of
+        /// the values given for one
+        targets: Vec<String>,
+        /// Only entries of this boot: 0 for the current one, -1 for the
+        /// one before, 1 for the oldest, or a boot ID
+        #[arg(short, long, allow_hyphen_values = true)]
+        boot: Option<BootSpec>,
         /// Number of lines to show
         #[arg(short = 'n', long, default_value = "100")]
         lines: usize,
@@ -110,15 +213,90 @@ enum Commands {
         follow: bool,
     },
 
+    /// Export the journal for log shippers
+    ExportJournal {
+        /// Output format: export (Journal Export Format) or json
+        #[arg(short, long, default_value = "export")]
+        output: ExportFormat,
+        /// Only export entries after this cursor
+        #[arg(long)]
+        after_cursor: Option<String>,
+        /// Read the starting cursor from this file and store the last
+        /// exported cursor in it
+        #[arg(long)]
+        cursor_file: Option<PathBuf>,
+    },
+
+    /// Manage the persistent journal
+    Journal {
+        #[command(subcommand)]
+        action: JournalCommands,
+    },
+
+    /// List and inspect captured core dumps
+    Coredump {
+        #[command(subcommand)]
+        action: CoredumpCommands,
+    },
+
+    /// Learn and generate system call filters
+    Seccomp {
+        #[command(subcommand)]
+        action: SeccompCommands,
+    },
+
+    /// Store a core dump piped in by the kernel (set up as core_pattern)
+    #[command(hide = true)]
+    CoredumpCapture {
+        #[arg(long)]
+        max_size: u64,
+        #[arg(long)]
+        max_use: u64,
+        pid: u32,
+        uid: u32,
+        gid: u32,
+        signal: i32,
+        timestamp: i64,
+        /// Executable name, which may contain spaces
+        #[arg(num_args = 1..)]
+        comm: Vec<String>,
+    },
+
     /// Show service dependency graph
     Deps {
         /// Service name (optional, shows all if not specified)
         name: Option<String>,
+        /// Also show dependency cycles and the dependency dropped to
+        /// break each
+        #[arg(long)]
+        with_cycles: bool,
+    },
+
+    /// Show resource usage of every service
+    Top {
+        /// Sort order: cpu or memory
+        #[arg(short, long, default_value = "cpu")]
+        sort: String,
+    },
+
+    /// Show active swap and the swap units in /etc/fstab
+    Swap,
+
+    /// Show or reset first-boot provisioning
+    Provision {
+        #[command(subcommand)]
+        action: ProvisionCommands,
+    },
+
+    /// Synchronize the clock, or show whether it is synchronized
+    Timesync {
+        #[command(subcommand)]
+        action: TimesyncCommands,
     },
 
     /// Analyze boot performance
     Analyze {
-        /// Analysis type: blame, critical-chain, or time
+        /// Analysis type: blame, critical-chain, time, schedule, or boots
         #[arg(default_value = "blame")]
         analysis_type: String,
     },
@@ -147,8 +325,66 @@ enum Commands {
         /// Shutdown type: poweroff, reboot, or halt
         #[arg(default_value = "poweroff")]
         shutdown_type: String,
+        /// Shut down even if users are logged in or an inhibitor lock is
+        /// held
+        #[arg(long)]
+        force: bool,
+        /// Wait up to this many seconds for inhibitor locks to be released
+        #[arg(long, value_name = "SECS")]
+        wait: Option<u64>,
     },
 
+    /// Run a command while holding an inhibitor lock
+    Inhibit {
+        /// What to block: shutdown, sleep (comma separated)
+        #[arg(long, value_delimiter = ',', default_value = "shutdown")]
+        what: Vec<InhibitWhat>,
+        /// Name of the program or operation holding the lock
+        #[arg(long)]
+        who: Option<String>,
+        /// Why the lock is held
+        #[arg(long, default_value = "Unknown reason")]
+        why: String,
+        /// Program and arguments
+        #[arg(required = true, trailing_var_arg = true)]
+        command: Vec<String>,
+    },
+
+    /// List held inhibitor locks
+    Inhibitors,
+
+    /// Ping a service's watchdog, record its status or report it busy, on
+    /// behalf of the service
+    Notify {
+        /// Service name
+        name: String,
+        /// Reset the service's watchdog
+        #[arg(long)]
+        watchdog: bool,
+        /// Status text shown by `boss status` and logged on watchdog
+        /// timeouts
+        #[arg(long)]
+        status: Option<String>,
+        /// Keep a socket-activated service with ExitIdleTimeSec running, as
+        /// if a connection had arrived
+        #[arg(long)]
+        busy: bool,
+        /// Fields to record in the service's journal, as sd_notify sends
+        /// them (STATUS=..., ERRNO=2, ...)
+        #[arg(value_name = "FIELD=VALUE")]
+        fields: Vec<String>,
+    },
+
+    /// Show init status and orphaned processes it adopted
+    System,
+
+    /// List logged in users
+    Sessions,
+
+    /// Record a login session opened or closed (run by pam_exec)
+    #[command(hide = true)]
+    PamSession,
+
     /// Migrate systemd unit files to buckos TOML format
     Migrate {
         /// Source path (file or directory with .service files)
@@ -166,6 +402,141 @@ enum Commands {
         /// Path to systemd .service file
         path: PathBuf,
     },
+
+    /// Check service files for errors before installing them
+    Verify {
+        /// Service files, or names of services in the services directory
+        #[arg(required = true)]
+        units: Vec<String>,
+    },
+
+    /// Override settings of a service in a drop-in opened in $EDITOR,
+    /// leaving the unit file a package installed untouched
+    Edit {
+        /// Service name
+        name: String,
+        /// Name of the drop-in file, without extension
+        #[arg(long, default_value = "override")]
+        drop_in: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum JournalCommands {
+    /// Show the disk space used by the journal
+    DiskUsage,
+
+    /// Remove old entries, keeping those from the current boot
+    Vacuum {
+        /// Shrink the journal to at most this size (e.g. 100M)
+        #[arg(long, value_parser = journal_vacuum::parse_size)]
+        size: Option<u64>,
+        /// Remove entries older than this (e.g. 2weeks)
+        #[arg(long, value_parser = journal_vacuum::parse_age)]
+        time: Option<std::time::Duration>,
+    },
+
+    /// Move entries logged to /run while the journal directory was
+    /// read-only to the persistent journal
+    Flush,
+
+    /// Show how many entries each service logged and how many were dropped
+    /// because it logged faster than the journal could write
+    Stats,
+
+    /// List the boots the journal has entries of
+    ListBoots,
+}
+
+#[derive(Subcommand)]
+enum ProvisionCommands {
+    /// Show the status of each provisioning task
+    Status,
+
+    /// Run provisioning tasks again on the next boot
+    Reset {
+        /// Tasks to run again (default: all)
+        tasks: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TimesyncCommands {
+    /// Show whether the clock is synchronized
+    Status,
+
+    /// Ask a time server for the time and show the clock offset, without
+    /// changing the clock
+    Query {
+        /// Time server (default: pool.ntp.org servers)
+        server: Option<String>,
+    },
+
+    /// Keep the clock synchronized with SNTP, as a service ordered before
+    /// time-sync
+    Run {
+        /// Time server, asked in the order given (repeatable, default:
+        /// pool.ntp.org servers)
+        #[arg(long = "server")]
+        servers: Vec<String>,
+        /// Step the clock on the first sync when it is off by more than
+        /// this; smaller offsets, and every later one, are slewed
+        #[arg(long, value_name = "MS", default_value = "128")]
+        step_threshold: u64,
+        /// Shortest polling interval
+        #[arg(long, value_name = "SECS", default_value = "32")]
+        min_poll: u64,
+        /// Longest polling interval
+        #[arg(long, value_name = "SECS", default_value = "2048")]
+        max_poll: u64,
+    },
+
+    /// Wait until the clock is synchronized, by the SNTP client or an NTP
+    /// daemon
+    Wait {
+        /// Give up after this many seconds
+        #[arg(long, value_name = "SECS")]
+        timeout: Option<u64>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CoredumpCommands {
+    /// List captured core dumps
+    List,
+
+    /// Show details of a core dump
+    Info {
+        /// Core dump id, PID or executable name
+        id: String,
+    },
+
+    /// Debug a core dump with gdb
+    Gdb {
+        /// Core dump id, PID or executable name
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SeccompCommands {
+    /// List the system call groups filters can name
+    Groups,
+
+    /// Show the system calls learned for a service
+    Show {
+        /// Service name
+        name: String,
+    },
+
+    /// Print a SystemCallFilter allowing what a service was seen to use
+    Generate {
+        /// Service name
+        name: String,
+        /// Write the filter into the unit file, ending learning mode
+        #[arg(long)]
+        write: bool,
+    },
 }
 
 #[tokio::main]
@@ -178,6 +549,7 @@ async fn main() -> anyhow::Result<()> {
         .with_target(false)
         .init();
 
+    buckos_boss::i18n::init();
     let cli = Cli::parse();
 
     match cli.command {
@@ -229,7 +601,7 @@ async fn main() -> anyhow::Result<()> {
             } else {
                 let statuses = init.manager().get_all_status().await;
                 if statuses.is_empty() {
-                    println!("No services found");
+                    println!("{}", tr!("status-no-services-found"));
                 } else {
                     for status in statuses {
                         print_status(&status);
@@ -246,21 +618,60 @@ async fn main() -> anyhow::Result<()> {
 
             let services = init.manager().list_services().await;
             if services.is_empty() {
-                println!("No services found");
+                println!("{}", tr!("status-no-services-found"));
             } else {
-                println!("Services:");
+                println!("{}", tr!("list-services"));
                 for name in services {
                     println!("  {}", name);
                 }
             }
         }
 
+        Some(Commands::ListTimers) => {
+            let init = create_test_init(cli.services_dir)?;
+            init.manager().load_services().await?;
+
+            let timers = init.manager().list_timers().await;
+            if timers.is_empty() {
+                println!("{}", tr!("list-timers-no-timers-found"));
+            } else {
+                let now = chrono::Utc::now();
+                let time = |t: Option<chrono::DateTime<chrono::Utc>>| {
+                    t.map_or("n/a".to_string(), |t| {
+                        t.with_timezone(&chrono::Local)
+                            .format("%a %Y-%m-%d %H:%M:%S")
+                            .to_string()
+                    })
+                };
+                println!(
+                    "{:<24} {:>10} {:<24} {:>10} {:>10}  UNIT",
+                    "NEXT", "LEFT", "LAST", "ACCURACY", "RANDOM"
+                );
+                for timer in &timers {
+                    let left = timer.next_elapse.map_or("n/a".to_string(), |t| {
+                        format_secs((t - now).num_seconds().max(0) as u64)
+                    });
+                    println!(
+                        "{:<24} {:>10} {:<24} {:>10} {:>10}  {}",
+                        time(timer.next_elapse),
+                        left,
+                        time(timer.last_trigger),
+                        format_secs(timer.accuracy.as_secs()),
+                        format_secs(timer.randomized_delay.as_secs()),
+                        timer.name
+                    );
+                }
+                println!();
+                println!("{}", tr!("list-timers-timers-listed", count = timers.len()));
+            }
+        }
+
         Some(Commands::Enable { name }) => {
             // Enable a service
             let init = create_test_init(cli.services_dir)?;
             init.manager().load_services().await?;
             init.manager().enable_service(&name).await?;
-            println!("Enabled {}", name);
+            println!("{}", tr!("enable-enabled", name = name.to_string()));
         }
 
         Some(Commands::Disable { name }) => {
@@ -268,7 +679,60 @@ async fn main() -> anyhow::Result<()> {
             let init = create_test_init(cli.services_dir)?;
             init.manager().load_services().await?;
             init.manager().disable_service(&name).await?;
-            println!("Disabled {}", name);
+            println!("{}", tr!("disable-disabled", name = name.to_string()));
+        }
+
+        Some(Commands::Preset { name, preset_mode }) => {
+            let init = create_test_init(cli.services_dir)?;
+            init.manager().load_services().await?;
+            match init.manager().preset_service(&name, preset_mode).await? {
+                Some(PresetAction::Enable) => {
+                    println!("{}", tr!("enable-enabled", name = name.to_string()))
+                }
+                Some(PresetAction::Disable) => {
+                    println!("{}", tr!("disable-disabled", name = name.to_string()))
+                }
+                None => println!("{}", tr!("preset-left-unchanged", name = name.to_string())),
+            }
+        }
+
+        Some(Commands::PresetAll { preset_mode }) => {
+            let init = create_test_init(cli.services_dir)?;
+            init.manager().load_services().await?;
+            for (name, action) in init.manager().preset_all(preset_mode).await? {
+                println!("{:<8} {}", action, name);
+            }
+        }
+
+        Some(Commands::Run {
+            unit,
+            mut properties,
+            on_calendar,
+            on_active,
+            command,
+        }) => {
+            properties.extend(on_calendar.map(|spec| format!("OnCalendar={}", spec)));
+            properties.extend(on_active.map(|delay| format!("OnActiveSec={}", delay)));
+            let unit = TransientUnit {
+                name: unit,
+                command,
+                properties,
+            };
+
+            // Transient units live in the running init, so there is no
+            // local fallback
+            let client = ControlClient::with_default_path();
+            match client.start_transient(unit).await? {
+                ControlResponse::Success { message } => println!("{}", message),
+                ControlResponse::Error { message } => {
+                    error!("Failed to run: {}", message);
+                    std::process::exit(1);
+                }
+                _ => {
+                    error!("Unexpected response from init");
+                    std::process::exit(1);
+                }
+            }
         }
 
         Some(Commands::Mask { name }) => {
@@ -276,7 +740,7 @@ async fn main() -> anyhow::Result<()> {
             let init = create_test_init(cli.services_dir)?;
             init.manager().load_services().await?;
             init.manager().mask_service(&name).await?;
-            println!("Masked {}", name);
+            println!("{}", tr!("mask-masked", name = name.to_string()));
         }
 
         Some(Commands::Unmask { name }) => {
@@ -284,21 +748,40 @@ async fn main() -> anyhow::Result<()> {
             let init = create_test_init(cli.services_dir)?;
             init.manager().load_services().await?;
             init.manager().unmask_service(&name).await?;
-            println!("Unmasked {}", name);
+            println!("{}", tr!("unmask-unmasked", name = name.to_string()));
         }
 
         Some(Commands::Logs {
-            name,
+            targets,
             lines,
             follow: _,
+            boot,
         }) => {
             // Show service logs
             let init = create_test_init(cli.services_dir)?;
             init.manager().load_services().await?;
-
-            let logs = init.manager().get_logs(&name, Some(lines)).await;
+            let journal = init.manager().journal();
+
+            let (matches, names): (Vec<&String>, Vec<&String>) =
+                targets.iter().partition(|t| t.contains('='));
+            let mut filter = names.iter().fold(
+                JournalFilter::parse(matches).map_err(anyhow::Error::msg)?,
+                |filter, name| filter.with_match("UNIT", name),
+            );
+            if let Some(spec) = &boot {
+                let boots = journal.boots();
+                let Some(record) = boots.resolve(spec) else {
+                    error!("No such boot in the journal: {}", spec);
+                    std::process::exit(1);
+                };
+                filter = filter.with_match("_BOOT_ID", &record.boot_id);
+            }
+            let logs = journal.query(&filter, Some(lines)).await;
             if logs.is_empty() {
-                println!("No logs found for {}", name);
+                println!(
+                    "{}",
+                    tr!("logs-no-logs-found-for", targets = targets.join(" "))
+                );
             } else {
                 for entry in logs {
                     println!("{}", entry.format());
@@ -306,45 +789,545 @@ async fn main() -> anyhow::Result<()> {
             }
         }
 
-        Some(Commands::Deps { name }) => {
+        Some(Commands::Journal { action }) => {
+            let init = create_test_init(cli.services_dir)?;
+            let journal = init.manager().journal();
+            match action {
+                JournalCommands::DiskUsage => {
+                    println!(
+                        "{}",
+                        tr!(
+                            "journal-files-take-up",
+                            disk_usage = format_bytes(journal.disk_usage()).to_string()
+                        )
+                    );
+                }
+                JournalCommands::Flush => {
+                    let client = ControlClient::with_default_path();
+                    match client.flush_journal().await? {
+                        ControlResponse::Success { message } => println!("{}", message),
+                        ControlResponse::Error { message } => {
+                            error!("{}", message);
+                            std::process::exit(1);
+                        }
+                        _ => {
+                            error!("Unexpected response from init");
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                JournalCommands::Stats => {
+                    let client = ControlClient::with_default_path();
+                    match client.journal_stats().await? {
+                        ControlResponse::JournalStats { stats } => {
+                            println!(
+                                "{:<24} {:>12} {:>10} {:>8}",
+                                "SERVICE", "RECEIVED", "DROPPED", "QUEUED"
+                            );
+                            for s in stats {
+                                println!(
+                                    "{:<24} {:>12} {:>10} {:>8}",
+                                    s.service, s.received, s.dropped, s.queued
+                                );
+                            }
+                        }
+                        ControlResponse::Error { message } => {
+                            error!("{}", message);
+                            std::process::exit(1);
+                        }
+                        _ => {
+                            error!("Unexpected response from init");
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                JournalCommands::ListBoots => {
+                    let index = journal.boots();
+                    if index.boots.is_empty() {
+                        println!("{}", tr!("journal-no-boots-recorded"));
+                    }
+                    let count = index.boots.len() as i64;
+                    for (i, boot) in index.boots.iter().enumerate() {
+                        let ended = boot
+                            .ended
+                            .map_or("-".to_string(), |t| t.format("%F %T").to_string());
+                        let boot_time = boot
+                            .boot_duration_ms
+                            .map_or("-".to_string(), |ms| format!("{}ms", ms));
+                        println!(
+                            "{:>4} {} {} - {} {:>9}",
+                            i as i64 + 1 - count,
+                            boot.boot_id,
+                            boot.started.format("%F %T"),
+                            ended,
+                            boot_time
+                        );
+                    }
+                }
+                JournalCommands::Vacuum { size, time } => {
+                    if size.is_none() && time.is_none() {
+                        error!("Specify --size and/or --time");
+                        std::process::exit(1);
+                    }
+                    let report = journal.vacuum(&VacuumCriteria {
+                        max_size: size,
+                        max_age: time,
+                    })?;
+                    println!(
+                        "{}",
+                        tr!(
+                            "journal-removed-entries-files-freed",
+                            entries_removed = report.entries_removed.to_string(),
+                            files_removed = report.files_removed.to_string(),
+                            reclaimed = format_bytes(report.reclaimed()).to_string(),
+                            bytes_before = format_bytes(report.bytes_before).to_string(),
+                            bytes_after = format_bytes(report.bytes_after).to_string()
+                        )
+                    );
+                }
+            }
+        }
+
+        Some(Commands::Seccomp { action }) => match action {
+            SeccompCommands::Groups => {
+                for group in seccomp::GROUPS {
+                    println!("{:<14} {}", group.name, group.description);
+                    println!("    {}", group.syscalls.join(" "));
+                }
+            }
+            SeccompCommands::Show { name } => {
+                let init = create_test_init(cli.services_dir)?;
+                for syscall in init.manager().learned_syscalls(&name) {
+                    println!("{}", syscall);
+                }
+            }
+            SeccompCommands::Generate { name, write } => {
+                let init = create_test_init(cli.services_dir)?;
+                init.manager().load_services().await?;
+                let filter = init.manager().generate_syscall_filter(&name).await?;
+                if write {
+                    let path = init.manager().write_syscall_filter(&name, filter).await?;
+                    println!(
+                        "{}",
+                        tr!(
+                            "seccomp-wrote-system-call-filter",
+                            path = path.display().to_string()
+                        )
+                    );
+                } else {
+                    println!("SystemCallFilter={}", filter.join(" "));
+                }
+            }
+        },
+
+        Some(Commands::Coredump { action }) => {
+            let store = CoredumpStore::new(coredump::COREDUMP_DIR);
+            let find = |id: &str| {
+                store.find(id).unwrap_or_else(|| {
+                    error!("No core dump matches {}", id);
+                    std::process::exit(1);
+                })
+            };
+            match action {
+                CoredumpCommands::List => {
+                    println!(
+                        "{:<24} {:>7} {:<8} {:<20} {:<16} {:>10}",
+                        "ID", "PID", "SIGNAL", "SERVICE", "COMMAND", "SIZE"
+                    );
+                    for dump in store.list() {
+                        println!(
+                            "{:<24} {:>7} {:<8} {:<20} {:<16} {:>10}",
+                            dump.id,
+                            dump.pid,
+                            dump.signal_name(),
+                            dump.service.as_deref().unwrap_or("-"),
+                            dump.comm,
+                            format_bytes(dump.size)
+                        );
+                    }
+                }
+                CoredumpCommands::Info { id } => {
+                    let dump = find(&id);
+                    println!("           ID: {}", dump.id);
+                    println!(
+                        "          {}",
+                        tr!(
+                            "coredump-pid-uid-gid",
+                            pid = dump.pid.to_string(),
+                            uid = dump.uid.to_string(),
+                            gid = dump.gid.to_string()
+                        )
+                    );
+                    println!(
+                        "       {}",
+                        tr!(
+                            "coredump-signal",
+                            signal_name = dump.signal_name().to_string(),
+                            signal = dump.signal.to_string()
+                        )
+                    );
+                    println!(
+                        "    {}",
+                        tr!(
+                            "coredump-timestamp",
+                            timestamp = dump.timestamp.to_rfc3339().to_string()
+                        )
+                    );
+                    println!(
+                        "      {}",
+                        tr!("coredump-command", comm = dump.comm.to_string())
+                    );
+                    if let Some(exe) = &dump.exe {
+                        println!(
+                            "   {}",
+                            tr!("coredump-executable", exe = exe.display().to_string())
+                        );
+                    }
+                    println!(
+                        "      {}",
+                        tr!(
+                            "coredump-service",
+                            service = dump.service.as_deref().unwrap_or("-").to_string()
+                        )
+                    );
+                    println!(
+                        "         {}",
+                        tr!(
+                            "coredump-size-compressed",
+                            size = format_bytes(dump.size).to_string(),
+                            stored_size = format_bytes(dump.stored_size).to_string(),
+                            truncated = if dump.truncated {
+                                format!(", {}", tr!("coredump-truncated"))
+                            } else {
+                                String::new()
+                            }
+                        )
+                    );
+                }
+                CoredumpCommands::Gdb { id } => {
+                    let dump = find(&id);
+                    let Some(exe) = dump.exe.clone() else {
+                        error!("Executable of core dump {} is unknown", dump.id);
+                        std::process::exit(1);
+                    };
+                    let core = std::env::temp_dir().join(format!("boss-core-{}", dump.id));
+                    store.extract(&dump, &core)?;
+                    let status = std::process::Command::new("gdb")
+                        .arg(&exe)
+                        .arg(&core)
+                        .status();
+                    let _ = std::fs::remove_file(&core);
+                    if !status?.success() {
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+
+        Some(Commands::CoredumpCapture {
+            max_size,
+            max_use,
+            pid,
+            uid,
+            gid,
+            signal,
+            timestamp,
+            comm,
+        }) => {
+            let process = CrashedProcess {
+                pid,
+                uid,
+                gid,
+                signal,
+                timestamp,
+                comm: comm.join(" "),
+            };
+            let limits = CoredumpLimits { max_size, max_use };
+            CoredumpStore::new(coredump::COREDUMP_DIR).capture(
+                &process,
+                std::io::stdin().lock(),
+                &limits,
+            )?;
+        }
+
+        Some(Commands::ExportJournal {
+            output,
+            after_cursor,
+            cursor_file,
+        }) => {
+            let init = create_test_init(cli.services_dir)?;
+            let saved = cursor_file
+                .as_ref()
+                .and_then(|path| std::fs::read_to_string(path).ok());
+            let after = match after_cursor.or(saved) {
+                Some(cursor) => Some(cursor.parse::<Cursor>().map_err(anyhow::Error::msg)?),
+                None => None,
+            };
+
+            let entries = init.manager().journal().read_all_from_files();
+            let last = JournalExporter::new(output).export(
+                &entries,
+                after.as_ref(),
+                std::io::stdout().lock(),
+            )?;
+            if let (Some(path), Some(last)) = (cursor_file, last) {
+                std::fs::write(path, last.to_string())?;
+            }
+        }
+
+        Some(Commands::Deps { name, with_cycles }) => {
             // Show dependency graph
             let init = create_test_init(cli.services_dir)?;
             init.manager().load_services().await?;
 
             let graph = init.manager().get_dependency_graph().await;
 
-            if let Some(name) = name {
+            if let Some(name) = &name {
                 // Show deps for specific service
-                if let Some(node) = graph.iter().find(|n| n.name == name) {
-                    println!("Dependencies for {}:", name);
+                if let Some(node) = graph.iter().find(|n| n.name == *name) {
+                    println!("{}", tr!("deps-dependencies-for", name = name.to_string()));
                     if !node.requires.is_empty() {
-                        println!("  Requires: {}", node.requires.join(", "));
+                        println!(
+                            "  {}",
+                            tr!("deps-requires", requires = node.requires.join(", "))
+                        );
                     }
                     if !node.wants.is_empty() {
-                        println!("  Wants: {}", node.wants.join(", "));
+                        println!("  {}", tr!("deps-wants", wants = node.wants.join(", ")));
                     }
                     if !node.before.is_empty() {
-                        println!("  Before: {}", node.before.join(", "));
+                        println!("  {}", tr!("deps-before", before = node.before.join(", ")));
                     }
                     if !node.after.is_empty() {
-                        println!("  After: {}", node.after.join(", "));
+                        println!("  {}", tr!("deps-after", after = node.after.join(", ")));
                     }
                 } else {
                     error!("Service not found: {}", name);
                 }
             } else {
                 // Show all deps
-                println!("Dependency Graph:");
+                println!("{}", tr!("deps-dependency-graph"));
                 for node in graph {
                     if !node.requires.is_empty() || !node.wants.is_empty() {
                         println!("  {} -> {:?}", node.name, node.requires);
                     }
                 }
             }
+
+            if with_cycles {
+                let cycles: Vec<_> = init
+                    .manager()
+                    .dependency_cycles()
+                    .await
+                    .into_iter()
+                    .filter(|c| name.as_ref().is_none_or(|n| c.path.contains(n)))
+                    .collect();
+                if cycles.is_empty() {
+                    println!("{}", tr!("deps-no-dependency-cycles"));
+                } else {
+                    println!("{}", tr!("deps-dependency-cycles"));
+                    for cycle in cycles {
+                        println!("  {}", cycle);
+                    }
+                }
+            }
+        }
+
+        Some(Commands::Top { sort }) => {
+            // Show services by resource usage
+            let init = create_test_init(cli.services_dir)?;
+            init.manager().load_services().await?;
+
+            let mut statuses = init.manager().get_all_status().await;
+            match sort.as_str() {
+                "cpu" => statuses.sort_by_key(|s| std::cmp::Reverse(s.usage.cpu_usec)),
+                "memory" => statuses.sort_by_key(|s| std::cmp::Reverse(s.usage.peak_memory_bytes)),
+                _ => {
+                    error!("Unknown sort order: {}", sort);
+                    std::process::exit(1);
+                }
+            }
+
+            println!(
+                "{:<24} {:<10} {:>7} {:>10} {:>10} {:>10} {:>10} {:>8}",
+                "SERVICE", "STATE", "PID", "CPU", "PEAK MEM", "READ", "WRITE", "RESTARTS"
+            );
+            for status in statuses {
+                println!(
+                    "{:<24} {:<10} {:>7} {:>9.2}s {:>10} {:>10} {:>10} {:>8}",
+                    status.name,
+                    status.state.to_string(),
+                    status.main_pid.map_or("-".to_string(), |p| p.to_string()),
+                    status.usage.cpu_secs(),
+                    format_bytes(status.usage.peak_memory_bytes),
+                    format_bytes(status.usage.io_read_bytes),
+                    format_bytes(status.usage.io_write_bytes),
+                    status.restart_count
+                );
+            }
+        }
+
+        Some(Commands::Swap) => {
+            let active = swap::active_swaps();
+            println!(
+                "{:<32} {:<10} {:>10} {:>10} {:>5}",
+                "NAME", "TYPE", "SIZE", "USED", "PRIO"
+            );
+            for area in &active {
+                println!(
+                    "{:<32} {:<10} {:>10} {:>10} {:>5}",
+                    area.path.display(),
+                    area.kind,
+                    format_bytes(area.size_bytes),
+                    format_bytes(area.used_bytes),
+                    area.priority
+                );
+            }
+
+            let fstab = std::fs::read_to_string("/etc/fstab").unwrap_or_default();
+            let inactive: Vec<_> = swap::parse_fstab(&fstab)
+                .into_iter()
+                .filter(|unit| !active.iter().any(|a| a.path == unit.what))
+                .collect();
+            if !inactive.is_empty() {
+                println!("\n{}", tr!("swap-inactive-swap-units"));
+                for unit in inactive {
+                    println!("  {} ({})", unit.unit_name(), unit.what.display());
+                }
+            }
+        }
+
+        Some(Commands::Timesync { action }) => match action {
+            TimesyncCommands::Status => {
+                let clock = ClockSync::default();
+                if !clock.is_synchronized() {
+                    println!("{}", tr!("timesync-clock-not-synchronized"));
+                } else if let Some((server, at)) = clock.last_sync() {
+                    println!(
+                        "{}",
+                        tr!(
+                            "timesync-clock-synchronized-with-last",
+                            server = server.to_string(),
+                            time = at.with_timezone(&chrono::Local).format("%F %T").to_string()
+                        )
+                    );
+                } else {
+                    println!("{}", tr!("timesync-clock-synchronized-by-an"));
+                }
+            }
+            TimesyncCommands::Query { server } => {
+                let client = SntpClient::new(server.into_iter().collect());
+                let sample = tokio::task::spawn_blocking(move || client.sample()).await??;
+                println!(
+                    "{}",
+                    tr!(
+                        "timesync-server-stratum",
+                        server = sample.server.to_string(),
+                        stratum = sample.stratum.to_string()
+                    )
+                );
+                println!(
+                    "{}",
+                    tr!("timesync-offset", offset = format!("{:+.6}", sample.offset))
+                );
+                println!(
+                    "{}",
+                    tr!("timesync-delay", delay = format!("{:.6}", sample.delay))
+                );
+            }
+            TimesyncCommands::Run {
+                servers,
+                step_threshold,
+                min_poll,
+                max_poll,
+            } => {
+                let client = SntpClient::new(servers)
+                    .with_step_threshold(std::time::Duration::from_millis(step_threshold))
+                    .with_poll(
+                        std::time::Duration::from_secs(min_poll),
+                        std::time::Duration::from_secs(max_poll),
+                    );
+                info!(servers = ?client.servers(), "Synchronizing the clock");
+                tokio::task::spawn_blocking(move || client.run()).await??;
+            }
+            TimesyncCommands::Wait { timeout } => {
+                let clock = ClockSync::default();
+                let deadline =
+                    timeout.map(|t| std::time::Instant::now() + std::time::Duration::from_secs(t));
+                while !clock.is_synchronized() {
+                    if deadline.is_some_and(|d| std::time::Instant::now() >= d) {
+                        error!("Clock not synchronized");
+                        std::process::exit(1);
+                    }
+                    tokio::time::sleep(timesync::SYNC_POLL).await;
+                }
+            }
+        },
+
+        Some(Commands::Provision { action }) => {
+            let provisioner = Provisioner::default();
+            match action {
+                ProvisionCommands::Status => {
+                    let state = provisioner.state();
+                    match state.completed {
+                        Some(at) => println!(
+                            "{}",
+                            tr!(
+                                "provision-provisioning-completed",
+                                at = at.format("%F %T").to_string()
+                            )
+                        ),
+                        None if provisioner.is_first_boot() => {
+                            println!("{}", tr!("provision-provisioning-pending-runs-on"))
+                        }
+                        None => println!("{}", tr!("provision-provisioning-was-never-flagged")),
+                    }
+                    println!(
+                        "\n{:<16} {:<8} {:>8} {:<19} MESSAGE",
+                        "TASK", "STATUS", "ATTEMPTS", "FINISHED"
+                    );
+                    for task in provisioner.tasks() {
+                        match state.tasks.get(&task.name) {
+                            Some(record) => println!(
+                                "{:<16} {:<8} {:>8} {:<19} {}",
+                                task.name,
+                                record.status,
+                                record.attempts,
+                                record.finished.format("%F %T"),
+                                record.message.as_deref().unwrap_or("")
+                            ),
+                            None => {
+                                println!(
+                                    "{:<16} {:<8} {:>8} {:<19}",
+                                    task.name,
+                                    tr!("provision-pending"),
+                                    0,
+                                    "-"
+                                )
+                            }
+                        }
+                    }
+                }
+                ProvisionCommands::Reset { tasks } => {
+                    if let Some(unknown) = tasks
+                        .iter()
+                        .find(|t| !provisioner.tasks().iter().any(|task| &task.name == *t))
+                    {
+                        error!("No provisioning task named {}", unknown);
+                        std::process::exit(1);
+                    }
+                    provisioner.reset(&tasks)?;
+                    println!("{}", tr!("provision-provisioning-runs-again-on"));
+                }
+            }
         }
 
         Some(Commands::Analyze { analysis_type }) => {
             // Analyze boot performance
+            let limits = StartupLimits {
+                max_parallel: cli.max_parallel_starts,
+                max_per_depth: cli.max_starts_per_depth,
+            };
             let init = create_test_init(cli.services_dir)?;
             init.manager().load_services().await?;
 
@@ -352,24 +1335,34 @@ async fn main() -> anyhow::Result<()> {
                 "blame" => {
                     let blame = init.manager().get_boot_blame().await;
                     if blame.is_empty() {
-                        println!("No boot timing data available");
+                        println!("{}", tr!("analyze-no-boot-timing-data"));
                     } else {
                         println!(
-                            "Startup finished in {}ms",
-                            init.manager().get_total_boot_time()
+                            "{}",
+                            tr!(
+                                "analyze-startup-finished-in-ms",
+                                ms = init.manager().get_total_boot_time().to_string()
+                            )
                         );
                         println!();
                         for timing in blame {
-                            println!("{:>8}ms {}", timing.duration_ms, timing.name);
+                            println!(
+                                "{}",
+                                tr!(
+                                    "analyze-ms",
+                                    duration_ms = format!("{:>8}", timing.duration_ms),
+                                    name = timing.name.to_string()
+                                )
+                            );
                         }
                     }
                 }
                 "critical-chain" => {
                     let chain = init.manager().get_critical_chain().await;
                     if chain.is_empty() {
-                        println!("No critical chain data available");
+                        println!("{}", tr!("analyze-no-critical-chain-data"));
                     } else {
-                        println!("Critical chain:");
+                        println!("{}", tr!("analyze-critical-chain"));
                         for (i, name) in chain.iter().enumerate() {
                             let indent = "  ".repeat(i);
                             println!("{}{}", indent, name);
@@ -378,10 +1371,94 @@ async fn main() -> anyhow::Result<()> {
                 }
                 "time" => {
                     println!(
-                        "Total boot time: {}ms",
-                        init.manager().get_total_boot_time()
+                        "{}",
+                        tr!(
+                            "analyze-total-boot-time-ms",
+                            ms = init.manager().get_total_boot_time().to_string()
+                        )
                     );
                 }
+                "schedule" => {
+                    println!(
+                        "{}",
+                        tr!("analyze-startup-limits", limits = limits.to_string())
+                    );
+                    let history = BootHistory::load(&init.manager().boot_history_path());
+                    let (title, schedule) = if history.last_schedule.is_empty() {
+                        (
+                            tr!("analyze-planned-startup-order"),
+                            init.manager().startup_plan().await?.services,
+                        )
+                    } else {
+                        (
+                            tr!("analyze-last-boot-startup-order"),
+                            history.last_schedule,
+                        )
+                    };
+                    if schedule.is_empty() {
+                        println!("{}", tr!("analyze-no-enabled-services"));
+                    } else {
+                        println!("{}", tr!("analyze-critical-path", title = title));
+                        println!(
+                            "{:>5} {:>5} {:>9} {:>9} {:>9}  {}",
+                            tr!("analyze-order"),
+                            tr!("analyze-depth"),
+                            tr!("analyze-expected"),
+                            tr!("analyze-chain"),
+                            tr!("analyze-queued"),
+                            tr!("analyze-service")
+                        );
+                        for (i, s) in schedule.iter().enumerate() {
+                            let expected = s
+                                .expected_ms
+                                .map_or("-".to_string(), |ms| format!("{}ms", ms));
+                            println!(
+                                "{:>5} {:>5} {:>9} {:>7}ms {:>7}ms {}{}",
+                                if s.order > 0 { s.order } else { i + 1 },
+                                s.depth,
+                                expected,
+                                s.critical_path_ms,
+                                s.queued_ms,
+                                if s.critical { "*" } else { " " },
+                                s.name
+                            );
+                        }
+                    }
+                }
+                "boots" => {
+                    // Start times of the last few boots side by side
+                    let index = init.manager().journal().boots();
+                    let recent = &index.boots[index.boots.len().saturating_sub(5)..];
+                    if recent.is_empty() {
+                        println!("{}", tr!("journal-no-boots-recorded"));
+                    } else {
+                        let cell =
+                            |ms: Option<u64>| ms.map_or("-".to_string(), |ms| format!("{}ms", ms));
+                        let offset = recent.len() as i64 - 1;
+                        let header: Vec<String> = (0..recent.len())
+                            .map(|i| format!("{:>9}", i as i64 - offset))
+                            .collect();
+                        println!("{:<24} {}", tr!("analyze-boot"), header.join(" "));
+                        let total: Vec<String> = recent
+                            .iter()
+                            .map(|b| format!("{:>9}", cell(b.boot_duration_ms)))
+                            .collect();
+                        println!("{:<24} {}", tr!("analyze-total"), total.join(" "));
+                        let services: std::collections::BTreeSet<&String> = recent
+                            .iter()
+                            .flat_map(|b| b.service_times_ms.keys())
+                            .collect();
+                        for service in services {
+                            let times: Vec<String> = recent
+                                .iter()
+                                .map(|b| {
+                                    format!("{:>9}", cell(b.service_times_ms.get(service).copied()))
+                                })
+                                .collect();
+                            println!("{:<24} {}", service, times.join(" "));
+                        }
+                    }
+                }
                 _ => {
                     error!("Unknown analysis type: {}", analysis_type);
                     std::process::exit(1);
@@ -396,7 +1473,14 @@ async fn main() -> anyhow::Result<()> {
             init.manager()
                 .instantiate_template(&template, &instance)
                 .await?;
-            println!("Instantiated {}@{}", template, instance);
+            println!(
+                "{}",
+                tr!(
+                    "instantiate-instantiated",
+                    template = template.to_string(),
+                    instance = instance.to_string()
+                )
+            );
         }
 
         Some(Commands::New { name, exec, output }) => {
@@ -411,10 +1495,20 @@ async fn main() -> anyhow::Result<()> {
             }
 
             def.to_file(&path)?;
-            println!("Created service definition: {}", path.display());
+            println!(
+                "{}",
+                tr!(
+                    "new-created-service-definition",
+                    path = path.display().to_string()
+                )
+            );
         }
 
-        Some(Commands::Shutdown { shutdown_type }) => {
+        Some(Commands::Shutdown {
+            shutdown_type,
+            force,
+            wait,
+        }) => {
             // Request shutdown
             let shutdown_type = match shutdown_type.as_str() {
                 "poweroff" | "power-off" => ShutdownType::PowerOff,
@@ -431,9 +1525,12 @@ async fn main() -> anyhow::Result<()> {
 
             if client.is_available() {
                 // Connect to running init and send shutdown command
-                match client.shutdown(shutdown_type).await {
+                match client.shutdown(shutdown_type, force, wait).await {
                     Ok(ControlResponse::Success { message }) => {
-                        println!("Shutdown initiated: {}", message);
+                        println!(
+                            "{}",
+                            tr!("shutdown-initiated", message = message.to_string())
+                        );
                     }
                     Ok(ControlResponse::Error { message }) => {
                         error!("Shutdown failed: {}", message);
@@ -450,14 +1547,207 @@ async fn main() -> anyhow::Result<()> {
                 }
             } else {
                 // No running init process, just print info
-                println!("Shutdown type: {:?}", shutdown_type);
                 println!(
-                    "Note: No running init process found. \
-                     Control socket not available at /run/boss/control.sock"
+                    "{}",
+                    tr!(
+                        "shutdown-type",
+                        shutdown_type = format!("{:?}", shutdown_type)
+                    )
+                );
+                println!("{}", tr!("shutdown-note-no-running-init"));
+            }
+        }
+
+        Some(Commands::Inhibit {
+            what,
+            who,
+            why,
+            command,
+        }) => {
+            let who = who.unwrap_or_else(|| command.join(" "));
+            let lock = ControlClient::with_default_path()
+                .inhibit(what, &who, &why)
+                .await?;
+            let status = tokio::process::Command::new(&command[0])
+                .args(&command[1..])
+                .status()
+                .await?;
+            drop(lock);
+            std::process::exit(status.code().unwrap_or(1));
+        }
+
+        Some(Commands::Inhibitors) => {
+            let client = ControlClient::with_default_path();
+            let inhibitors = match client.list_inhibitors().await? {
+                ControlResponse::InhibitorList { inhibitors } => inhibitors,
+                _ => {
+                    error!("Unexpected response from init");
+                    std::process::exit(1);
+                }
+            };
+            println!(
+                "{:<20} {:<16} {:>7} {:<20} WHY",
+                "WHO", "WHAT", "PID", "SINCE"
+            );
+            for lock in inhibitors {
+                let what: Vec<String> = lock.what.iter().map(|w| w.to_string()).collect();
+                println!(
+                    "{:<20} {:<16} {:>7} {:<20} {}",
+                    lock.who,
+                    what.join(","),
+                    lock.pid.map_or("-".to_string(), |pid| pid.to_string()),
+                    lock.since
+                        .with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M"),
+                    lock.why
+                );
+            }
+        }
+
+        Some(Commands::Notify {
+            name,
+            watchdog,
+            mut status,
+            busy,
+            fields,
+        }) => {
+            let mut reported = BTreeMap::new();
+            for field in fields {
+                let Some((key, value)) = field.split_once('=') else {
+                    error!("Invalid field '{}', expected FIELD=VALUE", field);
+                    std::process::exit(1);
+                };
+                match key {
+                    "STATUS" => status = Some(value.to_string()),
+                    _ => {
+                        reported.insert(key.to_string(), value.to_string());
+                    }
+                }
+            }
+            let client = ControlClient::with_default_path();
+            match client
+                .notify(&name, watchdog, status, busy, reported)
+                .await?
+            {
+                ControlResponse::Success { .. } => {}
+                ControlResponse::Error { message } => {
+                    error!("Notify failed: {}", message);
+                    std::process::exit(1);
+                }
+                _ => {
+                    error!("Unexpected response from init");
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Some(Commands::System) => {
+            let client = ControlClient::with_default_path();
+            let status = match client.system_status().await? {
+                ControlResponse::SystemStatus { status } => status,
+                _ => {
+                    error!("Unexpected response from init");
+                    std::process::exit(1);
+                }
+            };
+            println!("PID:      {}", status.pid);
+            println!(
+                "{}",
+                tr!(
+                    "system-services-running-failed",
+                    services = status.services.to_string(),
+                    running = status.running.to_string(),
+                    failed = status.failed.to_string()
+                )
+            );
+            if status.orphans.is_empty() {
+                println!("{}", tr!("system-orphans-none"));
+            } else {
+                println!();
+                println!("{:<32} {:>8} {:>8}", "SERVICE", "RUNNING", "REAPED");
+                for count in status.orphans {
+                    println!(
+                        "{:<32} {:>8} {:>8}",
+                        count.service.as_deref().unwrap_or("-"),
+                        count.running,
+                        count.reaped
+                    );
+                }
+            }
+        }
+
+        Some(Commands::Sessions) => {
+            let client = ControlClient::with_default_path();
+            let sessions = if client.is_available() {
+                match client.list_sessions().await? {
+                    ControlResponse::SessionList { sessions } => sessions,
+                    ControlResponse::Error { message } => {
+                        error!("Failed to list sessions: {}", message);
+                        std::process::exit(1);
+                    }
+                    _ => {
+                        error!("Unexpected response from init");
+                        std::process::exit(1);
+                    }
+                }
+            } else {
+                // Without init, only sessions recorded through PAM are known
+                SessionStore::default().list()
+            };
+
+            println!(
+                "{:<12} {:<10} {:<20} {:<16} {:>7}",
+                "USER", "TTY", "LOGIN", "FROM", "PID"
+            );
+            for session in sessions {
+                println!(
+                    "{:<12} {:<10} {:<20} {:<16} {:>7}",
+                    session.user,
+                    session.tty.as_deref().unwrap_or("-"),
+                    session
+                        .started
+                        .with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M"),
+                    session.remote_host.as_deref().unwrap_or("-"),
+                    session.pid
                 );
             }
         }
 
+        Some(Commands::PamSession) => {
+            // pam_exec runs us as a child of the process opening the session
+            let pid = nix::unistd::getppid().as_raw() as u32;
+            let env = |key: &str| std::env::var(key).ok().filter(|v| !v.is_empty());
+            let store = SessionStore::default();
+            match env("PAM_TYPE").as_deref() {
+                Some("open_session") => {
+                    let user = env("PAM_USER").unwrap_or_default();
+                    let uid = nix::unistd::User::from_name(&user)
+                        .ok()
+                        .flatten()
+                        .map_or(u32::MAX, |u| u.uid.as_raw());
+                    store.open(&Session {
+                        user,
+                        uid,
+                        // PAM_TTY is a device, with or without /dev/, or
+                        // a placeholder such as sshd's "ssh"
+                        tty: env("PAM_TTY")
+                            .map(|tty| Path::new("/dev").join(tty))
+                            .filter(|path| path.exists())
+                            .map(|path| session::tty_name(&path)),
+                        remote_host: env("PAM_RHOST"),
+                        started: chrono::Utc::now(),
+                        pid,
+                        service: env("PAM_SERVICE").unwrap_or_default(),
+                        source: SessionSource::Pam,
+                    })?;
+                }
+                Some("close_session") => store.close(pid)?,
+                // Other stages (auth, account, password) have no session
+                _ => {}
+            }
+        }
+
         Some(Commands::Migrate {
             source,
             output,
@@ -486,7 +1776,14 @@ async fn main() -> anyhow::Result<()> {
                     }
 
                     SystemdLoader::migrate(&source, &dest)?;
-                    println!("Migrated {} -> {}", source.display(), dest.display());
+                    println!(
+                        "{}",
+                        tr!(
+                            "migrate-migrated",
+                            source = source.display().to_string(),
+                            dest = dest.display().to_string()
+                        )
+                    );
                 }
             } else if source.is_dir() {
                 // Directory migration
@@ -495,12 +1792,21 @@ async fn main() -> anyhow::Result<()> {
                 let migrated = SystemdLoader::migrate_directory(&source, &dest_dir)?;
 
                 if migrated.is_empty() {
-                    println!("No .service files found in {}", source.display());
+                    println!(
+                        "{}",
+                        tr!(
+                            "migrate-no-service-files-found",
+                            source = source.display().to_string()
+                        )
+                    );
                 } else {
                     println!(
-                        "Migrated {} service files to {}",
-                        migrated.len(),
-                        dest_dir.display()
+                        "{}",
+                        tr!(
+                            "migrate-migrated-service-files-to",
+                            count = migrated.len(),
+                            dest_dir = dest_dir.display().to_string()
+                        )
                     );
                     for path in migrated {
                         println!("  {}", path.display());
@@ -517,25 +1823,318 @@ async fn main() -> anyhow::Result<()> {
             let toml_content = SystemdLoader::convert_to_toml(&path)?;
             println!("{}", toml_content);
         }
+
+        Some(Commands::Verify { units }) => {
+            if !verify_units(&cli.services_dir, &units)? {
+                std::process::exit(1);
+            }
+        }
+
+        Some(Commands::Edit { name, drop_in }) => {
+            if !edit_unit(&cli.services_dir, &name, &drop_in).await? {
+                std::process::exit(1);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Verify service files, printing diagnostics.
+///
+/// Returns false if any file has errors.
+fn verify_units(services_dir: &Path, units: &[String]) -> anyhow::Result<bool> {
+    let registry = LoaderRegistry::new();
+    let paths: Vec<PathBuf> = units
+        .iter()
+        .map(|unit| unit_path(&registry, services_dir, unit))
+        .collect();
+
+    let mut reports = Vec::new();
+    for path in &paths {
+        if !path.exists() {
+            anyhow::bail!("No such service file: {}", path.display());
+        }
+        reports.push(registry.verify(path)?);
+    }
+
+    // Cycles are checked against the installed services, with the files
+    // being verified taking their place
+    let mut services = HashMap::new();
+    if let Ok(entries) = std::fs::read_dir(services_dir) {
+        for entry in entries.flatten() {
+            if let Ok(def) = registry.load(&entry.path()) {
+                services.insert(def.name.clone(), def);
+            }
+        }
+    }
+    for def in reports.iter().filter_map(|r| r.definition.as_ref()) {
+        services.insert(def.name.clone(), def.clone());
+    }
+
+    let mut ok = true;
+    for report in &mut reports {
+        report.check_dependencies(&services);
+        if report.diagnostics.is_empty() {
+            println!(
+                "{}",
+                tr!("verify-units-ok", path = report.path.display().to_string())
+            );
+        } else {
+            print!("{}", report);
+        }
+        ok &= !report.has_errors();
+    }
+    Ok(ok)
+}
+
+/// Path of a service file, or of the file of a service in the services
+/// directory.
+fn unit_path(registry: &LoaderRegistry, services_dir: &Path, unit: &str) -> PathBuf {
+    let path = PathBuf::from(unit);
+    if path.exists() || unit.contains('/') {
+        return path;
+    }
+    registry
+        .supported_extensions()
+        .into_iter()
+        .map(|ext| services_dir.join(format!("{}.{}", unit, ext)))
+        .find(|p| p.exists())
+        .unwrap_or(path)
+}
+
+/// Edit a drop-in of a service, saving it only once the service loads
+/// with it, then have a running init reload its units.
+///
+/// An edit that leaves only comments removes the drop-in. Returns false if
+/// the changes were discarded.
+async fn edit_unit(services_dir: &Path, name: &str, drop_in: &str) -> anyhow::Result<bool> {
+    let registry = LoaderRegistry::new();
+    let unit = unit_path(&registry, services_dir, name);
+    if !unit.exists() {
+        anyhow::bail!("No such service file: {}", unit.display());
+    }
+    let Some(ext) = registry.dropin_extension(&unit)? else {
+        anyhow::bail!("{} does not support drop-ins", unit.display());
+    };
+
+    let override_dir = ServiceManager::system_dir(services_dir);
+    let dir = loaders::dropin_dir(&override_dir, &unit);
+    let target = dir.join(format!("{}.{}", drop_in, ext));
+    // Not a drop-in until it is renamed, so nothing loads it half written
+    let scratch = dir.join(format!(".{}.{}.edit", drop_in, ext));
+    let initial = match std::fs::read_to_string(&target) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => format!(
+            "# Overrides of {}\n\
+             # Lines starting with # are ignored; leave only comments to\n\
+             # remove the drop-in.\n",
+            unit.display()
+        ),
+        Err(e) => return Err(e.into()),
+    };
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(&scratch, &initial)?;
+
+    let mut search = vec![override_dir];
+    search.extend(unit.parent().map(Path::to_path_buf));
+    loop {
+        if let Err(e) = run_editor(&scratch) {
+            let _ = std::fs::remove_file(&scratch);
+            return Err(e);
+        }
+        let content = std::fs::read_to_string(&scratch)?;
+        if content
+            .lines()
+            .all(|l| l.trim().is_empty() || l.trim_start().starts_with('#'))
+        {
+            std::fs::remove_file(&scratch)?;
+            if target.exists() {
+                std::fs::remove_file(&target)?;
+                println!(
+                    "{}",
+                    tr!("edit-unit-removed", target = target.display().to_string())
+                );
+            }
+            // Only removed if no other drop-ins are left in it
+            let _ = std::fs::remove_dir(&dir);
+            if initial == content {
+                return Ok(true);
+            }
+            break;
+        }
+
+        // Validate the unit as it will load, with the edit in place of the
+        // drop-in it is saved to
+        let mut dropins: Vec<PathBuf> = registry
+            .find_dropins(&unit, &search)
+            .into_iter()
+            .filter(|p| p.file_name() != target.file_name())
+            .collect();
+        let at = dropins.partition_point(|p| p.file_name() < target.file_name());
+        dropins.insert(at, scratch.clone());
+        let mut report = registry.verify_dropin(&unit, &dropins, &scratch)?;
+        report.path = target.clone();
+        if !report.diagnostics.is_empty() {
+            print!("{}", report);
+        }
+        if !report.has_errors() {
+            std::fs::rename(&scratch, &target)?;
+            println!(
+                "{}",
+                tr!("edit-unit-saved", target = target.display().to_string())
+            );
+            break;
+        }
+        if !confirm("Edit again?")? {
+            std::fs::remove_file(&scratch)?;
+            println!(
+                "{}",
+                tr!(
+                    "edit-unit-discarded-changes-to",
+                    target = target.display().to_string()
+                )
+            );
+            return Ok(false);
+        }
+    }
+
+    let client = ControlClient::with_default_path();
+    if client.is_available() {
+        match client.reload_daemon().await {
+            Ok(ControlResponse::Success { .. }) => {
+                println!(
+                    "{}",
+                    tr!(
+                        "edit-unit-reloaded-units-restart-to",
+                        name = name.to_string()
+                    )
+                )
+            }
+            Ok(ControlResponse::Error { message }) => eprintln!(
+                "{}",
+                tr!("edit-unit-failed-to-reload", message = message.to_string())
+            ),
+            Ok(_) => {}
+            Err(e) => eprintln!(
+                "{}",
+                tr!("edit-unit-failed-to-reload", message = e.to_string())
+            ),
+        }
     }
+    Ok(true)
+}
 
+/// Open a file in $VISUAL or $EDITOR, falling back to vi.
+fn run_editor(path: &Path) -> anyhow::Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // Through the shell, so the editor may carry arguments
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        anyhow::bail!("{} exited with {}", editor, status);
+    }
     Ok(())
 }
 
+/// Ask a yes/no question on the terminal, yes by default.
+fn confirm(question: &str) -> std::io::Result<bool> {
+    use std::io::Write;
+    print!("{} [Y/n] ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim().to_lowercase();
+    Ok(answer.is_empty() || answer.starts_with('y'))
+}
+
+/// Startup limits given on the command line.
+fn startup_limits(cli: &Cli) -> StartupLimits {
+    StartupLimits {
+        max_parallel: cli.max_parallel_starts,
+        max_per_depth: cli.max_starts_per_depth,
+    }
+}
+
 /// Run as the init system.
 async fn run_init(cli: &Cli) -> anyhow::Result<()> {
     let config = InitConfig {
         services_dir: cli.services_dir.clone(),
         mount_filesystems: !cli.no_mount,
         require_pid1: !cli.no_pid1,
+        startup_limits: startup_limits(cli),
+        journal_limits: JournalLimits {
+            max_use: cli.journal_max_use,
+            keep_free: cli.journal_keep_free,
+        },
+        journal_compression: cli.journal_compress,
+        crash_dir: PathBuf::from(buckos_boss::crash::CRASH_DIR),
+        control_socket: Some(PathBuf::from(DEFAULT_CONTROL_SOCKET)),
+        coredumps: (!cli.no_pid1).then(CoredumpLimits::default),
+        factory_etc: (!cli.no_pid1).then(|| PathBuf::from(volatile::FACTORY_ETC_DIR)),
+        volatile: cli.volatile,
+        machine_id: (!cli.no_pid1).then(|| PathBuf::from(MACHINE_ID_PATH)),
+        package_db: PathBuf::from(PACKAGE_DB_DIR),
+        generator_dirs: if cli.no_pid1 {
+            Vec::new()
+        } else {
+            GENERATOR_DIRS.iter().map(PathBuf::from).collect()
+        },
+        provisioning: (!cli.no_pid1).then(Provisioner::default),
+        swap: SwapConfig {
+            fstab: (!cli.no_swap).then(|| PathBuf::from("/etc/fstab")),
+            zram: cli.zram.map(|fraction| ZramConfig {
+                fraction,
+                algorithm: cli.zram_algorithm.clone(),
+                ..Default::default()
+            }),
+        },
+        remote_syslog: match &cli.syslog_target {
+            Some(target) => {
+                Some(RemoteSyslogConfig::parse(target)?.with_max_priority(cli.syslog_priority))
+            }
+            None => None,
+        },
     };
 
-    let init = Init::new(config)?;
-    init.run().await?;
+    let init = Arc::new(Init::new(config)?);
+    init.run_supervised().await?;
 
     Ok(())
 }
 
+/// Format a duration in seconds as e.g. `1h 5m` or `30s`.
+fn format_secs(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+        3600..=86399 => format!("{}h {}m", secs / 3600, (secs % 3600) / 60),
+        _ => format!("{}d {}h", secs / 86400, (secs % 86400) / 3600),
+    }
+}
+
+/// Format a byte count with a binary unit.
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "K", "M", "G", "T"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}B", bytes)
+    } else {
+        format!("{:.1}{}", value, UNITS[unit])
+    }
+}
+
 /// Print service status.
 fn print_status(status: &ServiceStatus) {
     let state_symbol = match status.state {
@@ -546,41 +2145,125 @@ fn print_status(status: &ServiceStatus) {
     };
 
     println!("{} {} - {}", state_symbol, status.name, status.description);
-    println!("   State: {}", status.state);
+    println!(
+        "   {}",
+        tr!("print-status-state", state = status.state.to_string())
+    );
+
+    if !status.missing_packages.is_empty() {
+        println!(
+            "   {}",
+            tr!(
+                "print-status-missing-packages",
+                missing_packages = status.missing_packages.join(", ")
+            )
+        );
+    }
+
+    if let Some(text) = &status.status_text {
+        println!("   {}", tr!("print-status-status", text = text.to_string()));
+    }
+
+    if status.watchdog_failures > 0 {
+        println!(
+            "   {}",
+            tr!(
+                "print-status-watchdog-timeouts",
+                watchdog_failures = status.watchdog_failures.to_string()
+            )
+        );
+    }
 
     if status.masked {
-        println!("   Masked: yes");
+        println!("   {}", tr!("print-status-masked-yes"));
     }
 
     if status.enabled {
-        println!("   Enabled: yes");
+        println!("   {}", tr!("print-status-enabled-yes"));
     }
 
     if let Some(pid) = status.main_pid {
         println!("   PID: {}", pid);
     }
 
+    if let Some(label) = &status.security_label {
+        println!(
+            "   {}",
+            tr!("print-status-label", label = label.to_string())
+        );
+    }
+
     if let Some(uptime) = status.uptime_secs {
         let hours = uptime / 3600;
         let minutes = (uptime % 3600) / 60;
         let seconds = uptime % 60;
-        println!("   Uptime: {}h {}m {}s", hours, minutes, seconds);
+        println!(
+            "   {}",
+            tr!(
+                "print-status-uptime",
+                hours = hours.to_string(),
+                minutes = minutes.to_string(),
+                seconds = seconds.to_string()
+            )
+        );
     }
 
     if status.restart_count > 0 {
-        println!("   Restarts: {}", status.restart_count);
+        println!(
+            "   {}",
+            tr!(
+                "print-status-restarts",
+                restarts = status.restart_count.to_string()
+            )
+        );
+    }
+
+    let usage = &status.usage;
+    if *usage != buckos_boss::ResourceUsage::default() {
+        println!("   CPU: {:.2}s", usage.cpu_secs());
+        println!(
+            "   {}",
+            tr!(
+                "print-status-memory-peak",
+                memory = status
+                    .memory_bytes
+                    .map_or("-".to_string(), format_bytes)
+                    .to_string(),
+                peak = format_bytes(usage.peak_memory_bytes).to_string()
+            )
+        );
+        println!(
+            "   {}",
+            tr!(
+                "print-status-io-read-written",
+                read = format_bytes(usage.io_read_bytes).to_string(),
+                written = format_bytes(usage.io_write_bytes).to_string()
+            )
+        );
     }
 
     // Show health status if not "none"
     if status.health_status != buckos_boss::HealthStatus::None {
-        println!("   Health: {}", status.health_status);
+        println!(
+            "   {}",
+            tr!(
+                "print-status-health",
+                health = status.health_status.to_string()
+            )
+        );
     }
 
     if let Some(boot_ms) = status.boot_duration_ms {
-        println!("   Boot time: {}ms", boot_ms);
+        println!(
+            "   {}",
+            tr!("print-status-boot-time-ms", boot_ms = boot_ms.to_string())
+        );
     }
 
     if !status.requires.is_empty() {
-        println!("   Requires: {}", status.requires.join(", "));
+        println!(
+            "   {}",
+            tr!("deps-requires", requires = status.requires.join(", "))
+        );
     }
 }
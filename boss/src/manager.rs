@@ -1,19 +1,35 @@
 //! Service manager for tracking and managing services.
 
+use crate::cycles::{break_cycles, DependencyCycle};
+use crate::enablement::{EnablementStore, PresetAction, PresetMode, PresetPolicy, PRESET_DIRS};
 use crate::error::{Error, Result};
-use crate::journal::Journal;
+use crate::generators::Generators;
+use crate::journal::{self, Journal, JournalEntry, Priority};
+use crate::journal_index;
 use crate::loaders::LoaderRegistry;
+use crate::netns::NetworkHelper;
+use crate::packages::PackageDb;
+use crate::path_unit::{PathWatcher, TriggerLimit};
 use crate::process::{ExitStatus, ProcessSupervisor};
+use crate::scheduler::{BootHistory, ScheduleDecision, StartupLimits, StartupPlan};
+use crate::seccomp::{self, SyscallLearner, SyscallStore};
 use crate::service::{
-    HealthStatus, RestartPolicy, ServiceDefinition, ServiceInstance, ServiceState, ServiceStatus,
+    HealthStatus, PathConfig, RestartPolicy, SeccompConfig, ServiceDefinition, ServiceInstance,
+    ServiceState, ServiceStatus, SocketConfig, WatchdogAction, WatchdogConfig,
 };
-use chrono::Utc;
+use crate::session::{self, Session, SessionStore};
+use crate::socket_unit::ServiceSockets;
+use crate::timer::{self, TimerInfo, TimerSchedule};
+use crate::timesync::{self, ClockSync};
+use crate::transient::TransientUnit;
+use chrono::{DateTime, Utc};
 use nix::sys::signal::Signal;
-use std::collections::{HashMap, HashSet, VecDeque};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Instant;
-use tokio::sync::RwLock;
+use tokio::sync::{Notify, RwLock};
+use tokio::task::JoinSet;
 use tracing::{debug, error, info, warn};
 
 /// Boot timing information for a service.
@@ -62,12 +78,49 @@ pub struct ServiceManager {
     boot_start: Instant,
     /// Loader registry for different config formats
     loader_registry: LoaderRegistry,
+    /// Dependency cycles broken when services were loaded
+    dependency_cycles: Arc<RwLock<Vec<DependencyCycle>>>,
+    /// Limits on concurrent starts during boot
+    startup_limits: StartupLimits,
+    /// How services were scheduled during boot
+    startup_schedule: Arc<RwLock<Vec<ScheduleDecision>>>,
+    /// Timer elapse computation for this machine
+    timer_schedule: TimerSchedule,
+    /// When each timer last started its service
+    timer_triggers: Arc<RwLock<HashMap<String, DateTime<Utc>>>>,
+    /// Networks of services with PrivateNetwork
+    network: Arc<NetworkHelper>,
+    /// Enablement records
+    enablement: EnablementStore,
+    /// Preset directories, highest priority first
+    preset_dirs: Vec<PathBuf>,
+    /// Names of transient units
+    transient: Arc<RwLock<HashSet<String>>>,
+    /// Woken when a timer is added at runtime
+    timers_changed: Arc<Notify>,
+    /// Woken when a watchdog action asks for a reboot
+    reboot_requested: Arc<Notify>,
+    /// Records of PAM login sessions
+    sessions: SessionStore,
+    /// System calls of services in learning mode
+    syscalls: Arc<SyscallLearner>,
+    /// Installed packages, for units with RequiresPackage
+    packages: PackageDb,
+    /// Generators run before units are loaded
+    generators: Option<Generators>,
+    /// Drop-in overrides made by the administrator
+    override_dir: PathBuf,
+    /// Listening sockets of socket-activated services
+    sockets: Arc<RwLock<HashMap<String, Arc<ServiceSockets>>>>,
+    /// Whether the clock is synchronized, for units after time-sync
+    clock_sync: ClockSync,
 }
 
 impl ServiceManager {
     /// Create a new service manager.
     pub fn new(services_dir: PathBuf) -> Self {
         let log_dir = services_dir.parent().unwrap_or(&services_dir).join("logs");
+        let system_dir = Self::system_dir(&services_dir);
 
         Self {
             definitions: Arc::new(RwLock::new(HashMap::new())),
@@ -78,27 +131,113 @@ impl ServiceManager {
             boot_timings: Arc::new(RwLock::new(Vec::new())),
             boot_start: Instant::now(),
             loader_registry: LoaderRegistry::new(),
+            dependency_cycles: Arc::new(RwLock::new(Vec::new())),
+            startup_limits: StartupLimits::default(),
+            startup_schedule: Arc::new(RwLock::new(Vec::new())),
+            timer_schedule: TimerSchedule::new(),
+            timer_triggers: Arc::new(RwLock::new(HashMap::new())),
+            network: Arc::new(NetworkHelper::new()),
+            enablement: EnablementStore::new(system_dir.clone()),
+            preset_dirs: PRESET_DIRS.iter().map(PathBuf::from).collect(),
+            transient: Arc::new(RwLock::new(HashSet::new())),
+            timers_changed: Arc::new(Notify::new()),
+            reboot_requested: Arc::new(Notify::new()),
+            sessions: SessionStore::default(),
+            syscalls: Arc::new(SyscallLearner::new(SyscallStore::default())),
+            packages: PackageDb::default(),
+            generators: None,
+            override_dir: system_dir,
+            sockets: Arc::new(RwLock::new(HashMap::new())),
+            clock_sync: ClockSync::default(),
         }
     }
 
+    /// Directory of enablement records and drop-in overrides for units in
+    /// `services_dir`, e.g. /etc/buckos/system.
+    pub fn system_dir(services_dir: &Path) -> PathBuf {
+        services_dir.parent().unwrap_or(services_dir).join("system")
+    }
+
+    /// Limit concurrent service starts during boot.
+    pub fn with_startup_limits(mut self, limits: StartupLimits) -> Self {
+        self.startup_limits = limits;
+        self
+    }
+
+    /// Read presets from these directories, highest priority first.
+    pub fn with_preset_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.preset_dirs = dirs;
+        self
+    }
+
+    /// Read installed packages from this package database directory.
+    pub fn with_package_db(mut self, dir: impl AsRef<Path>) -> Self {
+        self.packages = PackageDb::new(dir);
+        self
+    }
+
+    /// Run these generators before loading units, and load their units.
+    pub fn with_generators(mut self, generators: Generators) -> Self {
+        self.generators = Some(generators);
+        self
+    }
+
+    /// Tell whether the clock is synchronized with this.
+    pub fn with_clock_sync(mut self, clock_sync: ClockSync) -> Self {
+        self.clock_sync = clock_sync;
+        self
+    }
+
     /// Get a reference to the journal.
     pub fn journal(&self) -> Arc<Journal> {
         Arc::clone(&self.journal)
     }
 
-    /// Load all service definitions from the services directory.
+    /// Snapshot of the service instances without waiting for the lock;
+    /// `None` if it is held.
+    pub fn try_instances(&self) -> Option<Vec<ServiceInstance>> {
+        let instances = self.instances.try_read().ok()?;
+        Some(instances.values().cloned().collect())
+    }
+
+    /// Load all service definitions from the services directory, and the
+    /// units of the generators after running them.
     ///
     /// Supports multiple configuration formats:
     /// - `.toml` - Native buckos format
     /// - `.service` - systemd unit files
     pub async fn load_services(&self) -> Result<()> {
+        let mut configured = HashSet::new();
         if !self.services_dir.exists() {
             info!(dir = ?self.services_dir, "Services directory doesn't exist, creating");
             std::fs::create_dir_all(&self.services_dir)?;
-            return Ok(());
+        } else {
+            configured = self.load_dir(&self.services_dir, &HashSet::new()).await?;
+        }
+
+        let mut generated = HashSet::new();
+        if let Some(generators) = &self.generators {
+            match generators.run().await {
+                Ok(_) => {
+                    // Units in the services directory win over generated ones
+                    generated = self.load_dir(generators.output(), &configured).await?;
+                }
+                Err(e) => error!(error = %e, "Failed to run generators"),
+            }
         }
 
-        let entries = std::fs::read_dir(&self.services_dir)?;
+        self.break_dependency_cycles().await;
+        self.apply_enablement(&generated).await;
+        self.packages_changed().await;
+
+        Ok(())
+    }
+
+    /// Load the units of a directory, skipping those named in `skip`.
+    /// Returns the names of the units loaded.
+    async fn load_dir(&self, dir: &Path, skip: &HashSet<String>) -> Result<HashSet<String>> {
+        let entries = std::fs::read_dir(dir)?;
+        let mut loaded = HashSet::new();
 
         for entry in entries {
             let entry = entry?;
@@ -108,7 +247,13 @@ impl ServiceManager {
             let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
 
             if self.loader_registry.find_loader(ext).is_some() {
-                match self.loader_registry.load(&path) {
+                let dropins = self
+                    .loader_registry
+                    .find_dropins(&path, &[self.override_dir.clone(), dir.to_path_buf()]);
+                match self.loader_registry.load_with_dropins(&path, &dropins) {
+                    Ok(def) if skip.contains(&def.name) => {
+                        info!(service = %def.name, path = ?path, "Generated unit overridden");
+                    }
                     Ok(def) => {
                         let loader_name = self
                             .loader_registry
@@ -118,9 +263,19 @@ impl ServiceManager {
                         info!(
                             service = %def.name,
                             format = loader_name,
+                            dropins = dropins.len(),
                             "Loaded service definition"
                         );
-                        self.register_service(def).await?;
+                        loaded.insert(def.name.clone());
+                        // On a reload the new definition applies from the
+                        // service's next start
+                        let mut definitions = self.definitions.write().await;
+                        if let Some(existing) = definitions.get_mut(&def.name) {
+                            *existing = def;
+                        } else {
+                            drop(definitions);
+                            self.register_service(def).await?;
+                        }
                     }
                     Err(e) => {
                         error!(path = ?path, error = %e, "Failed to load service definition");
@@ -129,7 +284,72 @@ impl ServiceManager {
             }
         }
 
-        Ok(())
+        Ok(loaded)
+    }
+
+    /// Take enablement from the records. The first time, create them from
+    /// the `enabled` flags of the unit files; if that fails, the flags stay
+    /// authoritative.
+    ///
+    /// Generated units are made anew at every boot, so they are enabled by
+    /// their own flags and not recorded.
+    async fn apply_enablement(&self, generated: &HashSet<String>) {
+        let mut definitions = self.definitions.write().await;
+        if self.enablement.exists() {
+            for def in definitions.values_mut() {
+                let own = def.enabled && generated.contains(&def.name);
+                def.enabled = own || self.enablement.is_enabled(&def.name);
+            }
+            return;
+        }
+
+        for def in definitions
+            .values()
+            .filter(|d| d.enabled && !generated.contains(&d.name))
+        {
+            let unit_file = self.unit_file(&def.name);
+            if let Err(e) = self
+                .enablement
+                .enable(&def.name, &def.wanted_by, &unit_file)
+            {
+                debug!(error = %e, "Cannot record enablement, using unit files");
+                // Partial records would disable the rest on the next load
+                let _ = std::fs::remove_dir_all(self.enablement.dir());
+                return;
+            }
+        }
+    }
+
+    /// Unit file of a service, or where a TOML one would be.
+    fn unit_file(&self, name: &str) -> PathBuf {
+        self.supported_extensions()
+            .into_iter()
+            .map(|ext| self.services_dir.join(format!("{}.{}", name, ext)))
+            .find(|path| path.exists())
+            .unwrap_or_else(|| self.services_dir.join(format!("{}.toml", name)))
+    }
+
+    /// Break dependency cycles among the loaded services.
+    ///
+    /// Each cycle is reported in the journal and kept for
+    /// [`dependency_cycles`](Self::dependency_cycles).
+    async fn break_dependency_cycles(&self) {
+        let broken = break_cycles(&mut *self.definitions.write().await);
+        for cycle in &broken {
+            warn!(cycle = %cycle, "Broke dependency cycle");
+            self.journal
+                .log(
+                    JournalEntry::new("boss", &format!("Dependency cycle: {}", cycle), "manager")
+                        .with_priority(Priority::Warning),
+                )
+                .await;
+        }
+        self.dependency_cycles.write().await.extend(broken);
+    }
+
+    /// Dependency cycles broken when services were loaded.
+    pub async fn dependency_cycles(&self) -> Vec<DependencyCycle> {
+        self.dependency_cycles.read().await.clone()
     }
 
     /// Get supported file extensions for service configurations.
@@ -155,12 +375,73 @@ impl ServiceManager {
         Ok(())
     }
 
+    /// Create a transient unit and start it, or arm its timer.
+    ///
+    /// Returns the unit name. The unit lives in memory only, until its
+    /// service exits with no restart or timer pending.
+    pub async fn start_transient(&self, unit: &TransientUnit) -> Result<String> {
+        let def = unit.definition(timer::uptime())?;
+        let name = def.name.clone();
+        let has_timer = def.timer.is_some();
+        self.register_service(def).await?;
+        self.transient.write().await.insert(name.clone());
+        info!(service = %name, "Created transient unit");
+
+        if has_timer {
+            self.timers_changed.notify_one();
+        } else if let Err(e) = self.start_service(&name).await {
+            self.remove_transient(&name).await;
+            return Err(e);
+        }
+        Ok(name)
+    }
+
+    /// Whether a service is a transient unit.
+    pub async fn is_transient(&self, name: &str) -> bool {
+        self.transient.read().await.contains(name)
+    }
+
+    /// Remove a transient unit whose service has stopped, unless its timer
+    /// will start it again.
+    async fn collect_transient(&self, name: &str) {
+        if !self.is_transient(name).await {
+            return;
+        }
+        let pending = self
+            .list_timers()
+            .await
+            .iter()
+            .any(|t| t.name == name && t.next_elapse.is_some());
+        if !pending {
+            self.remove_transient(name).await;
+        }
+    }
+
+    async fn remove_transient(&self, name: &str) {
+        self.transient.write().await.remove(name);
+        self.definitions.write().await.remove(name);
+        self.instances.write().await.remove(name);
+        self.timer_triggers.write().await.remove(name);
+        info!(service = %name, "Removed transient unit");
+    }
+
+    /// Woken when a timer is added at runtime, so the event loop can
+    /// recompute its next wakeup.
+    pub fn timers_changed(&self) -> Arc<Notify> {
+        Arc::clone(&self.timers_changed)
+    }
+
+    /// Notified when a watchdog action asks init to reboot the system.
+    pub fn reboot_requested(&self) -> Arc<Notify> {
+        Arc::clone(&self.reboot_requested)
+    }
+
     /// Start a service by name.
     pub async fn start_service(&self, name: &str) -> Result<()> {
         let start_time = Instant::now();
 
         // Get the service definition
-        let def = self
+        let mut def = self
             .definitions
             .read()
             .await
@@ -178,6 +459,17 @@ impl ServiceManager {
             }
         }
 
+        // Without its packages the service can only fail to exec, so it is
+        // not started until the package manager reports a change
+        let missing = self.packages.missing(&def.requires_package);
+        if !missing.is_empty() {
+            self.mark_uninstallable(name, missing.clone()).await;
+            return Err(Error::PackagesMissing {
+                service: name.to_string(),
+                packages: missing,
+            });
+        }
+
         // Check if already running
         {
             let instances = self.instances.read().await;
@@ -189,8 +481,10 @@ impl ServiceManager {
             }
         }
 
-        // Start dependencies first
-        for dep in &def.requires {
+        // Start dependencies first; time-sync is an ordering point, not a
+        // service
+        let dependency = |dep: &&String| !timesync::is_time_sync(dep);
+        for dep in def.requires.iter().filter(dependency) {
             Box::pin(self.start_service(dep))
                 .await
                 .map_err(|e| Error::DependencyError {
@@ -201,7 +495,7 @@ impl ServiceManager {
         }
 
         // Start wanted services (ignore failures)
-        for dep in &def.wants {
+        for dep in def.wants.iter().filter(dependency) {
             if let Err(e) = Box::pin(self.start_service(dep)).await {
                 warn!(service = %name, dependency = %dep, error = %e, "Failed to start wanted service");
             }
@@ -212,8 +506,31 @@ impl ServiceManager {
 
         info!(service = %name, "Starting service");
 
-        // Spawn the process
-        match self.supervisor.spawn(&def, Arc::clone(&self.journal)).await {
+        // Allow the system calls learned so far, and learn the rest
+        if def.seccomp.learn {
+            let program = def.exec_start.split_whitespace().next().unwrap_or_default();
+            self.syscalls.watch(name, Path::new(program));
+            def.seccomp
+                .system_call_filter
+                .extend(self.syscalls.store().load(name));
+        }
+
+        // Spawn the process, in its own network namespace if it has one,
+        // with its sockets if it is socket-activated
+        let sockets = self.sockets.read().await.get(name).cloned();
+        let spawned = match (self.setup_network(&def).await, sockets) {
+            (Ok(()), Some(sockets)) => match sockets.fds() {
+                Ok(fds) => {
+                    self.supervisor
+                        .spawn_with_listen_fds(&def, Arc::clone(&self.journal), fds)
+                        .await
+                }
+                Err(e) => Err(e),
+            },
+            (Ok(()), None) => self.supervisor.spawn(&def, Arc::clone(&self.journal)).await,
+            (Err(e), _) => Err(e),
+        };
+        match spawned {
             Ok(pid) => {
                 let duration_ms = start_time.elapsed().as_millis() as u64;
 
@@ -222,10 +539,12 @@ impl ServiceManager {
                 if let Some(instance) = instances.get_mut(name) {
                     instance.main_pid = Some(pid);
                     instance.started_at = Some(Utc::now());
-                    instance.state = ServiceState::Running;
+                    self.transition(instance, ServiceState::Running);
                     instance.exit_code = None;
                     instance.exit_signal = None;
                     instance.failure_reason = None;
+                    instance.status_text = None;
+                    instance.last_activity = None;
                     instance.boot_duration_ms = Some(duration_ms);
 
                     // Set initial health status if health check is configured
@@ -242,6 +561,11 @@ impl ServiceManager {
                     duration_ms,
                 });
 
+                // The event loop waits for the new watchdog deadline
+                if def.watchdog.is_some() {
+                    self.timers_changed.notify_one();
+                }
+
                 info!(service = %name, pid = pid, duration_ms = duration_ms, "Service started");
                 Ok(())
             }
@@ -249,7 +573,7 @@ impl ServiceManager {
                 // Update state to failed
                 let mut instances = self.instances.write().await;
                 if let Some(instance) = instances.get_mut(name) {
-                    instance.state = ServiceState::Failed;
+                    self.transition(instance, ServiceState::Failed);
                     instance.failure_reason = Some(e.to_string());
                 }
 
@@ -262,8 +586,57 @@ impl ServiceManager {
         }
     }
 
+    /// Start a service and wait up to `timeout` for its process to exit,
+    /// failing unless it exits successfully.
+    ///
+    /// Meant for one-shot units run before the event loop reaps children,
+    /// such as first-boot provisioning tasks. A unit still running at the
+    /// timeout is stopped.
+    pub async fn run_to_completion(&self, name: &str, timeout: std::time::Duration) -> Result<()> {
+        self.start_service(name).await?;
+        let pid = self
+            .instances
+            .read()
+            .await
+            .get(name)
+            .and_then(|i| i.main_pid);
+        let Some(pid) = pid else {
+            return Ok(());
+        };
+        let Some(status) = self.supervisor.wait_exit(pid, timeout).await? else {
+            if let Err(e) = self.stop_service(name).await {
+                warn!(service = %name, error = %e, "Failed to stop service");
+            }
+            return Err(Error::ServiceStartFailed {
+                name: name.to_string(),
+                reason: format!("did not finish within {:?}", timeout),
+            });
+        };
+        let (success, code, signal) = (status.success(), status.code, status.signal);
+        self.handle_process_exit(status).await;
+        if success {
+            Ok(())
+        } else {
+            Err(Error::ServiceStartFailed {
+                name: name.to_string(),
+                reason: format!("exited with code {:?}, signal {:?}", code, signal),
+            })
+        }
+    }
+
     /// Stop a service by name.
+    ///
+    /// A stopped transient unit is removed unless its timer is pending.
     pub async fn stop_service(&self, name: &str) -> Result<()> {
+        self.stop_running(name, false).await?;
+        self.collect_transient(name).await;
+        Ok(())
+    }
+
+    /// Stop the processes of a service, keeping a transient unit.
+    ///
+    /// With `force` the main process is killed with SIGKILL right away.
+    async fn stop_running(&self, name: &str, force: bool) -> Result<()> {
         // Get the service definition
         let def = self
             .definitions
@@ -295,19 +668,28 @@ impl ServiceManager {
 
         // Stop the process
         if let Some(pid) = pid {
-            match self.supervisor.stop(pid, def.timeout_stop_sec).await {
+            let stopped = if force {
+                self.supervisor.kill(pid).await
+            } else {
+                self.supervisor.stop(pid, def.timeout_stop_sec).await
+            };
+            match stopped {
                 Ok(status) => {
                     // Update instance
                     let mut instances = self.instances.write().await;
                     if let Some(instance) = instances.get_mut(name) {
                         instance.main_pid = None;
                         instance.stopped_at = Some(Utc::now());
-                        instance.state = ServiceState::Stopped;
+                        self.transition(instance, ServiceState::Stopped);
                         instance.exit_code = status.code;
                         instance.exit_signal = status.signal;
+                        if let Some(usage) = &status.usage {
+                            instance.usage = instance.usage.add(usage);
+                        }
                     }
 
                     info!(service = %name, "Service stopped");
+                    self.teardown_network(&def).await;
                     Ok(())
                 }
                 Err(e) => {
@@ -321,13 +703,42 @@ impl ServiceManager {
         } else {
             // No PID, just mark as stopped
             self.set_state(name, ServiceState::Stopped).await?;
+            self.teardown_network(&def).await;
             Ok(())
         }
     }
 
+    /// Create the network namespace of a PrivateNetwork service.
+    async fn setup_network(&self, def: &ServiceDefinition) -> Result<()> {
+        if !def.network.private_network {
+            return Ok(());
+        }
+        let network = Arc::clone(&self.network);
+        let (name, config) = (def.name.clone(), def.network.clone());
+        tokio::task::spawn_blocking(move || network.setup(&name, &config).map(|_| ()))
+            .await
+            .map_err(|e| Error::Other(e.to_string()))?
+    }
+
+    /// Remove the network namespace of a stopped PrivateNetwork service.
+    async fn teardown_network(&self, def: &ServiceDefinition) {
+        if !def.network.private_network {
+            return;
+        }
+        let network = Arc::clone(&self.network);
+        let name = def.name.clone();
+        let _ = tokio::task::spawn_blocking(move || network.teardown(&name)).await;
+    }
+
     /// Restart a service by name.
     pub async fn restart_service(&self, name: &str) -> Result<()> {
-        self.stop_service(name).await?;
+        self.stop_running(name, false).await?;
+        self.start_service(name).await
+    }
+
+    /// Kill a service with SIGKILL and start it again.
+    pub async fn force_restart_service(&self, name: &str) -> Result<()> {
+        self.stop_running(name, true).await?;
         self.start_service(name).await
     }
 
@@ -403,16 +814,62 @@ impl ServiceManager {
             .get_mut(name)
             .ok_or_else(|| Error::ServiceNotFound(name.to_string()))?;
 
+        self.enablement
+            .enable(name, &def.wanted_by, &self.unit_file(name))?;
         def.enabled = true;
 
-        // Save to file
-        let path = self.services_dir.join(format!("{}.toml", name));
-        def.to_file(&path)?;
-
         info!(service = %name, "Service enabled");
         Ok(())
     }
 
+    /// System calls learned for a service.
+    pub fn learned_syscalls(&self, name: &str) -> BTreeSet<String> {
+        self.syscalls.store().load(name)
+    }
+
+    /// A system call filter allowing what a service's filter already
+    /// allows and what was learned for it.
+    pub async fn generate_syscall_filter(&self, name: &str) -> Result<Vec<String>> {
+        let definitions = self.definitions.read().await;
+        let def = definitions
+            .get(name)
+            .ok_or_else(|| Error::ServiceNotFound(name.to_string()))?;
+        let mut allowed: BTreeSet<String> = seccomp::expand(&def.seccomp.system_call_filter)
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        allowed.extend(self.learned_syscalls(name));
+        Ok(seccomp::generate(allowed))
+    }
+
+    /// Write a system call filter into a service's unit file, ending
+    /// learning mode, and return the file.
+    pub async fn write_syscall_filter(&self, name: &str, filter: Vec<String>) -> Result<PathBuf> {
+        let mut definitions = self.definitions.write().await;
+        let def = definitions
+            .get_mut(name)
+            .ok_or_else(|| Error::ServiceNotFound(name.to_string()))?;
+        let path = self.unit_file(name);
+        if path.extension().is_some_and(|ext| ext == "service") {
+            let content = std::fs::read_to_string(&path)?;
+            std::fs::write(&path, seccomp::set_unit_filter(&content, &filter))?;
+        } else {
+            let mut unit = ServiceDefinition::from_file(&path)?;
+            unit.seccomp = SeccompConfig {
+                system_call_filter: filter.clone(),
+                learn: false,
+            };
+            unit.to_file(&path)?;
+        }
+        def.seccomp = SeccompConfig {
+            system_call_filter: filter,
+            learn: false,
+        };
+
+        info!(service = %name, path = %path.display(), "Wrote system call filter");
+        Ok(path)
+    }
+
     /// Disable a service from auto-start.
     pub async fn disable_service(&self, name: &str) -> Result<()> {
         let mut definitions = self.definitions.write().await;
@@ -420,16 +877,66 @@ impl ServiceManager {
             .get_mut(name)
             .ok_or_else(|| Error::ServiceNotFound(name.to_string()))?;
 
+        self.enablement.disable(name)?;
         def.enabled = false;
 
-        // Save to file
-        let path = self.services_dir.join(format!("{}.toml", name));
-        def.to_file(&path)?;
-
         info!(service = %name, "Service disabled");
         Ok(())
     }
 
+    /// Enable or disable a service as the preset policy says, returning
+    /// the action taken, if `mode` allows it.
+    pub async fn preset_service(
+        &self,
+        name: &str,
+        mode: PresetMode,
+    ) -> Result<Option<PresetAction>> {
+        let policy = PresetPolicy::load(&self.preset_dirs)?;
+        self.apply_preset(&policy, name, mode).await
+    }
+
+    /// Apply the preset policy to every service except templates.
+    pub async fn preset_all(&self, mode: PresetMode) -> Result<Vec<(String, PresetAction)>> {
+        let policy = PresetPolicy::load(&self.preset_dirs)?;
+        let mut names: Vec<String> = self
+            .definitions
+            .read()
+            .await
+            .values()
+            .filter(|def| !def.is_template())
+            .map(|def| def.name.clone())
+            .collect();
+        names.sort();
+
+        let mut applied = Vec::new();
+        for name in names {
+            if let Some(action) = self.apply_preset(&policy, &name, mode).await? {
+                applied.push((name, action));
+            }
+        }
+        Ok(applied)
+    }
+
+    async fn apply_preset(
+        &self,
+        policy: &PresetPolicy,
+        name: &str,
+        mode: PresetMode,
+    ) -> Result<Option<PresetAction>> {
+        if !self.definitions.read().await.contains_key(name) {
+            return Err(Error::ServiceNotFound(name.to_string()));
+        }
+        let action = policy.action(name);
+        if !mode.allows(action) {
+            return Ok(None);
+        }
+        match action {
+            PresetAction::Enable => self.enable_service(name).await?,
+            PresetAction::Disable => self.disable_service(name).await?,
+        }
+        Ok(Some(action))
+    }
+
     /// Mask a service to prevent it from starting.
     pub async fn mask_service(&self, name: &str) -> Result<()> {
         let mut instances = self.instances.write().await;
@@ -457,6 +964,12 @@ impl ServiceManager {
     }
 
     /// Start all enabled services in parallel.
+    ///
+    /// Each service starts once its dependencies have started, within the
+    /// configured startup limits; when the limits hold services back, those
+    /// on the longest chain of expected start times go first. Start times
+    /// and the decisions made are saved for the next boot and for
+    /// analysis.
     pub async fn start_enabled_services_parallel(&self) -> Result<()> {
         // Build dependency graph and find services that can start in parallel
         let enabled: Vec<String> = self
@@ -473,35 +986,197 @@ impl ServiceManager {
         }
 
         // Topologically sort services based on dependencies
-        let sorted = self.topological_sort(&enabled).await?;
+        let mut sorted = self.topological_sort(&enabled).await?;
+
+        // Services after time-sync, and the ones waiting on them, start
+        // once the clock is synchronized rather than holding up boot
+        if !self.time_synchronized().await {
+            let waiting = self.waiting_for_time_sync(&sorted).await;
+            if !waiting.is_empty() {
+                sorted.retain(|name| !waiting.contains(name));
+                self.start_after_time_sync(waiting).await;
+            }
+        }
 
-        // Group services by dependency level for parallel execution
+        // Group services by dependency level to find each one's depth
         let levels = self.group_by_dependency_level(&sorted).await;
 
-        for level in levels {
-            // Start all services in this level in parallel
-            let handles: Vec<_> = level
-                .iter()
-                .map(|name| {
-                    let manager = self.clone_for_restart();
-                    let name = name.clone();
-                    tokio::spawn(async move {
-                        if let Err(e) = manager.start_service(&name).await {
-                            error!(service = %name, error = %e, "Failed to start service in parallel");
-                        }
-                    })
-                })
-                .collect();
+        let history_path = self.boot_history_path();
+        let mut history = BootHistory::load(&history_path);
+        let plan = StartupPlan::new(&levels, &*self.definitions.read().await, &history);
+
+        let mut pending = plan.services.clone();
+        let mut schedule = Vec::new();
+        let mut started = HashSet::new();
+        let mut ready_since: HashMap<String, Instant> = HashMap::new();
+        let mut running_by_depth: HashMap<usize, usize> = HashMap::new();
+        let mut running = JoinSet::new();
+
+        while !pending.is_empty() || !running.is_empty() {
+            for service in &pending {
+                if plan.is_ready(&service.name, &started) {
+                    ready_since
+                        .entry(service.name.clone())
+                        .or_insert_with(Instant::now);
+                }
+            }
+
+            while let Some(index) =
+                plan.next(&pending, &started, &running_by_depth, &self.startup_limits)
+            {
+                let mut decision = pending.remove(index);
+                decision.order = schedule.len() + 1;
+                decision.queued_ms = ready_since
+                    .get(&decision.name)
+                    .map_or(0, |t| t.elapsed().as_millis() as u64);
+                if decision.queued_ms > 0 {
+                    debug!(
+                        service = %decision.name,
+                        queued_ms = decision.queued_ms,
+                        "Service start was held back by startup limits"
+                    );
+                }
+                *running_by_depth.entry(decision.depth).or_default() += 1;
+
+                let manager = self.clone_for_restart();
+                let (name, depth) = (decision.name.clone(), decision.depth);
+                running.spawn(async move {
+                    if let Err(e) = manager.start_service(&name).await {
+                        error!(service = %name, error = %e, "Failed to start service in parallel");
+                    }
+                    (name, depth)
+                });
+                schedule.push(decision);
+            }
 
-            // Wait for all services in this level to start
-            for handle in handles {
-                let _ = handle.await;
+            // Wait for a start to finish before scheduling more
+            match running.join_next().await {
+                Some(Ok((name, depth))) => {
+                    *running_by_depth.entry(depth).or_default() -= 1;
+                    started.insert(name);
+                }
+                Some(Err(e)) => {
+                    error!(error = %e, "Service start task failed");
+                    // The service is unknown; stop waiting on anything
+                    pending.clear();
+                }
+                None => break,
             }
         }
 
+        history.record(&self.boot_timings.read().await);
+        history.last_schedule = schedule.clone();
+        if let Err(e) = history.save(&history_path) {
+            warn!(path = ?history_path, error = %e, "Failed to save boot history");
+        }
+        *self.startup_schedule.write().await = schedule;
+
         Ok(())
     }
 
+    /// Planned start order of the enabled services, from earlier boots.
+    pub async fn startup_plan(&self) -> Result<StartupPlan> {
+        let enabled: Vec<String> = self
+            .definitions
+            .read()
+            .await
+            .iter()
+            .filter(|(_, def)| def.enabled)
+            .map(|(name, _)| name.clone())
+            .collect();
+        let sorted = self.topological_sort(&enabled).await?;
+        let levels = self.group_by_dependency_level(&sorted).await;
+        let history = BootHistory::load(&self.boot_history_path());
+        Ok(StartupPlan::new(
+            &levels,
+            &*self.definitions.read().await,
+            &history,
+        ))
+    }
+
+    /// Schedule of this boot's service starts.
+    pub async fn startup_schedule(&self) -> Vec<ScheduleDecision> {
+        self.startup_schedule.read().await.clone()
+    }
+
+    /// File keeping start times and the schedule of earlier boots.
+    pub fn boot_history_path(&self) -> PathBuf {
+        self.services_dir
+            .parent()
+            .unwrap_or(&self.services_dir)
+            .join("boot-history.json")
+    }
+
+    /// Whether the time-sync point is reached: the clock is synchronized,
+    /// or no enabled unit synchronizes it.
+    pub async fn time_synchronized(&self) -> bool {
+        self.clock_sync.is_synchronized()
+            || !self
+                .definitions
+                .read()
+                .await
+                .values()
+                .any(|def| def.enabled && def.synchronizes_time())
+    }
+
+    /// Wait until the time-sync point is reached.
+    pub async fn wait_time_sync(&self) {
+        while !self.time_synchronized().await {
+            tokio::time::sleep(timesync::SYNC_POLL).await;
+        }
+    }
+
+    /// Services in `sorted` ordered after time-sync, or depending on one
+    /// that is, in start order.
+    async fn waiting_for_time_sync(&self, sorted: &[String]) -> Vec<String> {
+        let definitions = self.definitions.read().await;
+        let mut waiting: HashSet<&String> = HashSet::new();
+        // Wants are not in the sort order, so go round until nothing changes
+        loop {
+            let before = waiting.len();
+            for name in sorted {
+                let Some(def) = definitions.get(name) else {
+                    continue;
+                };
+                let deps = def.requires.iter().chain(&def.wants).chain(&def.after);
+                if def.after_time_sync() || deps.clone().any(|dep| waiting.contains(dep)) {
+                    waiting.insert(name);
+                }
+            }
+            if waiting.len() == before {
+                break;
+            }
+        }
+        sorted
+            .iter()
+            .filter(|name| waiting.contains(name))
+            .cloned()
+            .collect()
+    }
+
+    /// Start `services` in order once the time-sync point is reached.
+    async fn start_after_time_sync(&self, services: Vec<String>) {
+        let message = format!(
+            "Waiting for the clock to be synchronized to start {}",
+            services.join(", ")
+        );
+        info!("{}", message);
+        self.journal
+            .log(JournalEntry::new("boss", &message, "manager"))
+            .await;
+
+        let manager = self.clone_for_restart();
+        tokio::spawn(async move {
+            manager.wait_time_sync().await;
+            info!("Clock synchronized, starting the services waiting for it");
+            for name in services {
+                if let Err(e) = manager.start_service(&name).await {
+                    error!(service = %name, error = %e, "Failed to start service after time-sync");
+                }
+            }
+        });
+    }
+
     /// Topologically sort services based on dependencies.
     async fn topological_sort(&self, services: &[String]) -> Result<Vec<String>> {
         let definitions = self.definitions.read().await;
@@ -719,6 +1394,221 @@ impl ServiceManager {
             .ok_or_else(|| Error::ServiceNotFound(name.to_string()))?;
 
         instance.last_watchdog_ping = Some(Utc::now());
+        instance.watchdog_failures = 0;
+        Ok(())
+    }
+
+    /// Record the status a service reports about itself.
+    pub async fn set_status_text(&self, name: &str, text: &str) -> Result<()> {
+        let mut instances = self.instances.write().await;
+        let instance = instances
+            .get_mut(name)
+            .ok_or_else(|| Error::ServiceNotFound(name.to_string()))?;
+
+        instance.status_text = Some(text.to_string());
+        Ok(())
+    }
+
+    /// Record the status and fields a service reported about itself in its
+    /// journal, the way sd_notify messages are (`STATUS=`, `ERRNO=`, ...).
+    ///
+    /// Fields starting with an underscore are set by boss only, and fields
+    /// an entry has of itself cannot be set either.
+    pub async fn record_notify(
+        &self,
+        name: &str,
+        status: Option<&str>,
+        fields: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        if let Some(field) = fields.keys().find(|field| {
+            !journal_index::is_valid_field_name(field)
+                || field.starts_with('_')
+                || journal::ENTRY_FIELDS.contains(&field.as_str())
+        }) {
+            return Err(Error::Other(format!("field {} cannot be set", field)));
+        }
+        let pid = self
+            .instances
+            .read()
+            .await
+            .get(name)
+            .ok_or_else(|| Error::ServiceNotFound(name.to_string()))?
+            .main_pid;
+        if let Some(text) = status {
+            self.set_status_text(name, text).await?;
+        }
+
+        let message = match status {
+            Some(text) => format!("Status: {}", text),
+            None => format!(
+                "Notified {}",
+                fields
+                    .iter()
+                    .map(|(k, v)| format!("{}={}", k, v))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+        };
+        let mut entry = JournalEntry::new(name, &message, "notify");
+        entry.fields = fields.clone();
+        if let Some(text) = status {
+            entry = entry.with_field("STATUS", text);
+        }
+        if let Some(pid) = pid {
+            entry = entry.with_pid(pid);
+        }
+        self.journal.log(entry).await;
+        Ok(())
+    }
+
+    /// Act on every running service that missed its watchdog deadline.
+    ///
+    /// A service must ping within its watchdog timeout of starting, of its
+    /// last ping and of its last timeout. Each timeout is recorded in the
+    /// service's journal with the status it last reported, and the action
+    /// for the number of consecutive timeouts is carried out in the
+    /// background. Returns the time until the next deadline, if any service
+    /// has a watchdog.
+    pub async fn check_watchdogs(&self) -> Option<std::time::Duration> {
+        let watchdogs: Vec<(String, WatchdogConfig)> = self
+            .definitions
+            .read()
+            .await
+            .iter()
+            .filter_map(|(name, def)| Some((name.clone(), def.watchdog.clone()?)))
+            .collect();
+
+        let now = Utc::now();
+        let mut next: Option<std::time::Duration> = None;
+        for (name, watchdog) in watchdogs {
+            let (failures, status_text) = {
+                let mut instances = self.instances.write().await;
+                let Some(instance) = instances.get_mut(&name) else {
+                    continue;
+                };
+                if instance.state != ServiceState::Running {
+                    continue;
+                }
+                let since = [
+                    instance.started_at,
+                    instance.last_watchdog_ping,
+                    instance.last_watchdog_timeout,
+                ]
+                .into_iter()
+                .flatten()
+                .max()
+                .unwrap_or(now);
+                let timeout = chrono::Duration::from_std(watchdog.timeout).unwrap_or_default();
+                let left = (since + timeout - now).to_std().unwrap_or_default();
+                if !left.is_zero() {
+                    next = Some(next.map_or(left, |n| n.min(left)));
+                    continue;
+                }
+                instance.watchdog_failures += 1;
+                instance.last_watchdog_timeout = Some(now);
+                (instance.watchdog_failures, instance.status_text.clone())
+            };
+            next = Some(next.map_or(watchdog.timeout, |n| n.min(watchdog.timeout)));
+
+            let action = watchdog.action_for(failures);
+            let message = format!(
+                "Watchdog timeout after {:?} ({} consecutive), action: {}; last status: {}",
+                watchdog.timeout,
+                failures,
+                action,
+                status_text.as_deref().unwrap_or("none")
+            );
+            warn!(service = %name, "{}", message);
+            let priority = if failures > 1 {
+                Priority::Error
+            } else {
+                Priority::Warning
+            };
+            let mut entry = JournalEntry::new(&name, &message, "watchdog").with_priority(priority);
+            if let Some(text) = status_text {
+                entry = entry.with_field("STATUS", text);
+            }
+            self.journal.log(entry).await;
+
+            let manager = self.clone_for_restart();
+            let command = watchdog.recovery_command.clone();
+            tokio::spawn(async move {
+                manager
+                    .run_watchdog_action(&name, action, failures, command.as_deref())
+                    .await
+            });
+        }
+        next
+    }
+
+    /// Carry out the action on a watchdog timeout of a service.
+    async fn run_watchdog_action(
+        &self,
+        name: &str,
+        action: WatchdogAction,
+        failures: u32,
+        command: Option<&str>,
+    ) {
+        let result = match action {
+            WatchdogAction::None => Ok(()),
+            WatchdogAction::Restart => self.restart_service(name).await,
+            WatchdogAction::RestartForce => self.force_restart_service(name).await,
+            WatchdogAction::Reboot => {
+                self.reboot_requested.notify_one();
+                Ok(())
+            }
+            WatchdogAction::Run => self.run_recovery_command(name, failures, command).await,
+        };
+        if let Err(e) = result {
+            let message = format!("Watchdog action {} failed: {}", action, e);
+            error!(service = %name, "{}", message);
+            self.journal
+                .log(JournalEntry::new(name, &message, "watchdog").with_priority(Priority::Error))
+                .await;
+        }
+    }
+
+    /// Run the recovery command of a service that missed its watchdog.
+    ///
+    /// The command runs through the shell with BOSS_SERVICE and
+    /// BOSS_WATCHDOG_FAILURES set; its output goes to the journal.
+    async fn run_recovery_command(
+        &self,
+        name: &str,
+        failures: u32,
+        command: Option<&str>,
+    ) -> Result<()> {
+        let command = command.ok_or_else(|| {
+            Error::Other("WatchdogAction=run needs a WatchdogRecoveryCommand".to_string())
+        })?;
+        let output = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("BOSS_SERVICE", name)
+            .env("BOSS_WATCHDOG_FAILURES", failures.to_string())
+            .stdin(std::process::Stdio::null())
+            .output()
+            .await?;
+        let text = String::from_utf8_lossy(&output.stdout).to_string()
+            + &String::from_utf8_lossy(&output.stderr);
+        for line in text.lines().filter(|l| !l.trim().is_empty()) {
+            self.journal
+                .log(JournalEntry::new(name, line, "watchdog"))
+                .await;
+        }
+        if !output.status.success() {
+            return Err(Error::Other(format!(
+                "{} exited with {}",
+                command, output.status
+            )));
+        }
+        self.journal
+            .log(JournalEntry::new(
+                name,
+                &format!("Watchdog recovery command finished: {}", command),
+                "watchdog",
+            ))
+            .await;
         Ok(())
     }
 
@@ -791,6 +1681,334 @@ impl ServiceManager {
             .collect()
     }
 
+    /// Logged in users, oldest session first: supervised gettys running a
+    /// login shell, and PAM sessions on other terminals.
+    pub async fn sessions(&self) -> Vec<Session> {
+        let mut sessions: Vec<Session> = {
+            let definitions = self.definitions.read().await;
+            let instances = self.instances.read().await;
+            definitions
+                .values()
+                .filter(|def| def.stdin_is_tty())
+                .filter_map(|def| {
+                    let pid = instances.get(&def.name)?.main_pid?;
+                    session::getty_session(&def.name, &def.tty.path, pid)
+                })
+                .collect()
+        };
+        // login on a getty terminal is recorded by PAM too
+        for pam in self.sessions.list() {
+            if pam.tty.is_none() || !sessions.iter().any(|s| s.tty == pam.tty) {
+                sessions.push(pam);
+            }
+        }
+        sessions.sort_by(|a, b| (a.started, &a.tty).cmp(&(b.started, &b.tty)));
+        sessions
+    }
+
+    /// Timers and when they next elapse, soonest first.
+    pub async fn list_timers(&self) -> Vec<TimerInfo> {
+        let definitions = self.definitions.read().await;
+        let instances = self.instances.read().await;
+        let triggers = self.timer_triggers.read().await;
+        let now = Utc::now();
+
+        let mut timers: Vec<TimerInfo> = definitions
+            .values()
+            .filter_map(|def| {
+                let timer = def.timer.as_ref()?;
+                let last_trigger = triggers.get(&def.name).copied();
+                let last_inactive = instances.get(&def.name).and_then(|i| i.stopped_at);
+                Some(TimerInfo {
+                    name: def.name.clone(),
+                    next_elapse: self.timer_schedule.next_elapse(
+                        &def.name,
+                        timer,
+                        last_trigger,
+                        last_inactive,
+                        now,
+                    ),
+                    last_trigger,
+                    accuracy: timer.accuracy,
+                    randomized_delay: timer.randomized_delay,
+                })
+            })
+            .collect();
+        timers.sort_by(|a, b| {
+            (a.next_elapse.is_none(), a.next_elapse, &a.name).cmp(&(
+                b.next_elapse.is_none(),
+                b.next_elapse,
+                &b.name,
+            ))
+        });
+        timers
+    }
+
+    /// Start the services of every timer that has elapsed.
+    ///
+    /// Timers of services after time-sync, and Persistent timers, whose
+    /// missed runs are judged by the clock, are held back until the clock
+    /// is synchronized.
+    ///
+    /// Returns the time until the next timer elapses, or until the clock is
+    /// checked again, if any is pending.
+    pub async fn run_due_timers(&self) -> Option<std::time::Duration> {
+        let now = Utc::now();
+        let due: Vec<String> = self
+            .list_timers()
+            .await
+            .into_iter()
+            .filter(|t| t.next_elapse.is_some_and(|at| at <= now))
+            .map(|t| t.name)
+            .collect();
+
+        let mut held = Vec::new();
+        if !due.is_empty() && !self.time_synchronized().await {
+            let definitions = self.definitions.read().await;
+            held = due
+                .iter()
+                .filter(|name| {
+                    definitions.get(*name).is_some_and(|def| {
+                        def.after_time_sync() || def.timer.as_ref().is_some_and(|t| t.persistent)
+                    })
+                })
+                .cloned()
+                .collect();
+        }
+
+        for name in due.into_iter().filter(|name| !held.contains(name)) {
+            self.timer_triggers.write().await.insert(name.clone(), now);
+            let active = self
+                .instances
+                .read()
+                .await
+                .get(&name)
+                .is_some_and(|i| i.is_active());
+            if active {
+                debug!(service = %name, "Timer elapsed while service is still active");
+                continue;
+            }
+            info!(service = %name, "Timer elapsed, starting service");
+            if let Err(e) = self.start_service(&name).await {
+                error!(service = %name, error = %e, "Failed to start timer service");
+            }
+        }
+
+        let next = self
+            .list_timers()
+            .await
+            .into_iter()
+            .filter(|t| !held.contains(&t.name))
+            .find_map(|t| t.next_elapse)
+            .map(|next| (next - Utc::now()).to_std().unwrap_or_default());
+        if held.is_empty() {
+            next
+        } else {
+            Some(next.map_or(timesync::SYNC_POLL, |d| d.min(timesync::SYNC_POLL)))
+        }
+    }
+
+    /// Bind the sockets of every socket-activated service not listening
+    /// yet, and start each service when a connection arrives.
+    ///
+    /// Call before services are started, so services started at boot get
+    /// their sockets too.
+    pub async fn start_socket_listeners(&self) {
+        let configured: Vec<(String, Vec<SocketConfig>)> = self
+            .definitions
+            .read()
+            .await
+            .values()
+            .filter(|def| !def.sockets.is_empty())
+            .map(|def| (def.name.clone(), def.sockets.clone()))
+            .collect();
+
+        for (name, configs) in configured {
+            if self.sockets.read().await.contains_key(&name) {
+                continue;
+            }
+            let sockets = match ServiceSockets::bind(&configs) {
+                Ok(sockets) => Arc::new(sockets),
+                Err(e) => {
+                    let message = format!("Failed to listen on the sockets of {}: {}", name, e);
+                    error!(service = %name, "{}", message);
+                    self.journal
+                        .log(
+                            JournalEntry::new("boss", &message, "manager")
+                                .with_priority(Priority::Error),
+                        )
+                        .await;
+                    continue;
+                }
+            };
+            self.sockets
+                .write()
+                .await
+                .insert(name.clone(), Arc::clone(&sockets));
+            let manager = self.clone_for_restart();
+            tokio::spawn(async move {
+                if let Err(e) = manager.watch_sockets(&name, &sockets).await {
+                    error!(service = %name, error = %e, "Socket watch failed");
+                }
+            });
+        }
+    }
+
+    /// Start a socket-activated service when a connection arrives, and
+    /// stop it once it has been idle for its ExitIdleTimeSec.
+    async fn watch_sockets(&self, name: &str, sockets: &ServiceSockets) -> Result<()> {
+        info!(service = %name, "Listening for socket activation");
+        loop {
+            let (running, last_activity) = match self.instances.read().await.get(name) {
+                Some(instance) => (
+                    instance.is_active(),
+                    instance.last_activity.or(instance.started_at),
+                ),
+                None => return Ok(()),
+            };
+            let Some(def) = self.definitions.read().await.get(name).cloned() else {
+                return Ok(());
+            };
+
+            if !running {
+                // A connection made while the service was stopping is
+                // already waiting
+                if !sockets.pending() {
+                    sockets.readable().await?;
+                }
+                debug!(service = %name, "Connection on socket, starting service");
+                if let Err(e) = self.start_service(name).await {
+                    error!(service = %name, error = %e, "Failed to start socket-activated service");
+                    // The connection is still waiting; try again later
+                    tokio::time::sleep(def.restart_sec).await;
+                }
+                continue;
+            }
+
+            let Some(idle) = def.exit_idle_time else {
+                sockets.readable().await?;
+                self.record_activity(name).await?;
+                continue;
+            };
+            let since = last_activity.unwrap_or_else(Utc::now);
+            let left = (since + chrono::Duration::from_std(idle).unwrap_or_default() - Utc::now())
+                .to_std()
+                .unwrap_or_default();
+            tokio::select! {
+                ready = sockets.readable() => {
+                    ready?;
+                    self.record_activity(name).await?;
+                }
+                _ = tokio::time::sleep(left) => {
+                    if self.idle_for(name, idle).await {
+                        let message = format!(
+                            "Stopping after {:?} without connections; the next one starts it again",
+                            idle
+                        );
+                        info!(service = %name, "{}", message);
+                        self.journal
+                            .log(JournalEntry::new(name, &message, "manager"))
+                            .await;
+                        if let Err(e) = self.stop_service(name).await {
+                            error!(service = %name, error = %e, "Failed to stop idle service");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether a running service has had no activity for `idle`.
+    async fn idle_for(&self, name: &str, idle: std::time::Duration) -> bool {
+        self.instances.read().await.get(name).is_some_and(|i| {
+            let since = i.last_activity.or(i.started_at);
+            i.state == ServiceState::Running
+                && since
+                    .is_some_and(|since| (Utc::now() - since).to_std().unwrap_or_default() >= idle)
+        })
+    }
+
+    /// Record that a socket-activated service got a connection or is still
+    /// busy, restarting its idle timer.
+    pub async fn record_activity(&self, name: &str) -> Result<()> {
+        let mut instances = self.instances.write().await;
+        let instance = instances
+            .get_mut(name)
+            .ok_or_else(|| Error::ServiceNotFound(name.to_string()))?;
+
+        instance.last_activity = Some(Utc::now());
+        Ok(())
+    }
+
+    /// Start watching the paths of every path-activated service.
+    pub async fn start_path_watches(&self) {
+        let watched: Vec<(String, PathConfig)> = self
+            .definitions
+            .read()
+            .await
+            .values()
+            .filter_map(|def| Some((def.name.clone(), def.path.clone()?)))
+            .collect();
+
+        for (name, config) in watched {
+            let manager = self.clone_for_restart();
+            tokio::spawn(async move {
+                if let Err(e) = manager.watch_paths(&name, &config).await {
+                    error!(service = %name, error = %e, "Path watch failed");
+                }
+            });
+        }
+    }
+
+    /// Start a service whenever its path conditions trigger, until the
+    /// activation rate limit is hit.
+    async fn watch_paths(&self, name: &str, config: &PathConfig) -> Result<()> {
+        let mut watcher = PathWatcher::new(config)?;
+        let mut limit = TriggerLimit::new(config);
+        // How often to recheck PathExists and DirectoryNotEmpty, which
+        // trigger again once the service stops if they still hold
+        let recheck = std::time::Duration::from_secs(1);
+        info!(service = %name, "Watching paths");
+
+        let mut triggered = false;
+        loop {
+            let active = self
+                .instances
+                .read()
+                .await
+                .get(name)
+                .is_some_and(|i| i.is_active());
+            if !active && (triggered || watcher.holds()) {
+                if !limit.allow(Instant::now()) {
+                    let message = format!(
+                        "Path activation of {} hit the trigger limit of {} in {:?}, no longer watching",
+                        name, config.trigger_limit_burst, config.trigger_limit_interval
+                    );
+                    warn!(service = %name, "{}", message);
+                    self.journal
+                        .log(
+                            JournalEntry::new("boss", &message, "manager")
+                                .with_priority(Priority::Warning),
+                        )
+                        .await;
+                    return Ok(());
+                }
+                debug!(service = %name, "Path condition triggered, starting service");
+                if let Err(e) = self.start_service(name).await {
+                    error!(service = %name, error = %e, "Failed to start path-activated service");
+                }
+            }
+
+            triggered = tokio::select! {
+                result = watcher.wait() => {
+                    result?;
+                    true
+                }
+                _ = tokio::time::sleep(recheck) => false,
+            };
+        }
+    }
+
     /// List all service names.
     pub async fn list_services(&self) -> Vec<String> {
         self.definitions.read().await.keys().cloned().collect()
@@ -799,7 +2017,7 @@ impl ServiceManager {
     /// Handle a process exit.
     pub async fn handle_process_exit(&self, status: ExitStatus) {
         // Find which service this process belongs to
-        let service_name = match self.supervisor.get_service_name(status.pid).await {
+        let service_name = match status.service.clone() {
             Some(name) => name,
             None => {
                 debug!(pid = status.pid, "Unknown process exited");
@@ -829,11 +2047,14 @@ impl ServiceManager {
                 instance.stopped_at = Some(Utc::now());
                 instance.exit_code = status.code;
                 instance.exit_signal = status.signal;
+                if let Some(usage) = &status.usage {
+                    instance.usage = instance.usage.add(usage);
+                }
 
                 if status.success() {
-                    instance.state = ServiceState::Stopped;
+                    self.transition(instance, ServiceState::Stopped);
                 } else {
-                    instance.state = ServiceState::Failed;
+                    self.transition(instance, ServiceState::Failed);
                     instance.failure_reason = Some(format!(
                         "Process exited with code {:?}, signal {:?}",
                         status.code, status.signal
@@ -896,12 +2117,13 @@ impl ServiceManager {
                         error!(service = %name, error = %e, "Failed to restart service");
                     }
                 });
+                return;
             } else {
                 // Rate limited - mark as failed
                 {
                     let mut instances = self.instances.write().await;
                     if let Some(instance) = instances.get_mut(&service_name) {
-                        instance.state = ServiceState::Failed;
+                        self.transition(instance, ServiceState::Failed);
                         instance.failure_reason = Some(
                             "Service restart rate limit exceeded (5 restarts in 10 seconds)"
                                 .to_string(),
@@ -915,6 +2137,8 @@ impl ServiceManager {
                 );
             }
         }
+
+        self.collect_transient(&service_name).await;
     }
 
     /// Get the process supervisor.
@@ -922,13 +2146,35 @@ impl ServiceManager {
         Arc::clone(&self.supervisor)
     }
 
+    /// Move a service to `state`, recording the change in its journal.
+    fn transition(&self, instance: &mut ServiceInstance, state: ServiceState) {
+        if instance.state == state {
+            return;
+        }
+        let message = format!("State changed from {} to {}", instance.state, state);
+        let priority = if state == ServiceState::Failed {
+            Priority::Warning
+        } else {
+            Priority::Info
+        };
+        let mut entry = JournalEntry::new(&instance.name, &message, "state")
+            .with_priority(priority)
+            .with_field("STATE", state.to_string())
+            .with_field("PREVIOUS_STATE", instance.state.to_string());
+        if let Some(pid) = instance.main_pid {
+            entry = entry.with_pid(pid);
+        }
+        self.journal.submit(entry);
+        instance.state = state;
+    }
+
     /// Set service state.
     async fn set_state(&self, name: &str, state: ServiceState) -> Result<()> {
         let mut instances = self.instances.write().await;
         let instance = instances
             .get_mut(name)
             .ok_or_else(|| Error::ServiceNotFound(name.to_string()))?;
-        instance.state = state;
+        self.transition(instance, state);
         Ok(())
     }
 
@@ -943,7 +2189,88 @@ impl ServiceManager {
             boot_timings: Arc::clone(&self.boot_timings),
             boot_start: self.boot_start,
             loader_registry: LoaderRegistry::new(),
+            dependency_cycles: Arc::clone(&self.dependency_cycles),
+            startup_limits: self.startup_limits,
+            startup_schedule: Arc::clone(&self.startup_schedule),
+            timer_schedule: self.timer_schedule,
+            timer_triggers: Arc::clone(&self.timer_triggers),
+            network: Arc::clone(&self.network),
+            enablement: self.enablement.clone(),
+            preset_dirs: self.preset_dirs.clone(),
+            transient: Arc::clone(&self.transient),
+            timers_changed: Arc::clone(&self.timers_changed),
+            reboot_requested: Arc::clone(&self.reboot_requested),
+            sessions: self.sessions.clone(),
+            syscalls: Arc::clone(&self.syscalls),
+            packages: self.packages.clone(),
+            generators: self.generators.clone(),
+            override_dir: self.override_dir.clone(),
+            sockets: Arc::clone(&self.sockets),
+            clock_sync: self.clock_sync.clone(),
+        }
+    }
+
+    /// Mark a service uninstallable for want of `missing` packages.
+    async fn mark_uninstallable(&self, name: &str, missing: Vec<String>) {
+        let mut instances = self.instances.write().await;
+        if let Some(instance) = instances.get_mut(name) {
+            if instance.state != ServiceState::Uninstallable {
+                warn!(service = %name, packages = ?missing, "Service is missing packages");
+            }
+            self.transition(instance, ServiceState::Uninstallable);
+            instance.missing_packages = missing;
+        }
+    }
+
+    /// Check the packages of every unit with RequiresPackage again, after
+    /// packages were installed or removed.
+    ///
+    /// Units whose packages are now missing are marked uninstallable, unless
+    /// they are running. Uninstallable units whose packages are all installed
+    /// become inactive, and the enabled ones are started. Returns the units
+    /// that became installable.
+    pub async fn packages_changed(&self) -> Vec<String> {
+        let definitions: Vec<(String, Vec<String>, bool)> = self
+            .definitions
+            .read()
+            .await
+            .values()
+            .filter(|def| !def.requires_package.is_empty())
+            .map(|def| (def.name.clone(), def.requires_package.clone(), def.enabled))
+            .collect();
+
+        let mut installable = Vec::new();
+        let mut to_start = Vec::new();
+        for (name, packages, enabled) in definitions {
+            let missing = self.packages.missing(&packages);
+            let mut instances = self.instances.write().await;
+            let Some(instance) = instances.get_mut(&name) else {
+                continue;
+            };
+            if !missing.is_empty() {
+                // A running service keeps running; it fails on its next start
+                if !instance.is_active() {
+                    drop(instances);
+                    self.mark_uninstallable(&name, missing).await;
+                }
+            } else if instance.state == ServiceState::Uninstallable {
+                self.transition(instance, ServiceState::Inactive);
+                instance.missing_packages.clear();
+                info!(service = %name, "Required packages installed");
+                installable.push(name.clone());
+                if enabled {
+                    to_start.push(name);
+                }
+            }
+        }
+
+        for name in to_start {
+            if let Err(e) = self.start_service(&name).await {
+                error!(service = %name, error = %e, "Failed to start installable service");
+            }
         }
+        installable.sort();
+        installable
     }
 
     /// Start all enabled services.
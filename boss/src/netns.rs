@@ -0,0 +1,427 @@
+//! Private networking for services.
+//!
+//! A service with `PrivateNetwork=yes` runs in a network namespace of its
+//! own, pinned by a bind mount under /run/buckos/netns. The namespace is
+//! connected to the host by a veth pair on a /30 from 10.89.0.0/16: the
+//! host end gets the first address and the service's `eth0` the second,
+//! with a default route through the host. With NAT, traffic from the
+//! namespace is masqueraded out through the host, and published ports are
+//! forwarded in with DNAT. Links are configured with iproute2 and the
+//! forwarding rules with iptables, run from a thread that has joined the
+//! namespace where needed. Rules already present are not added twice, and
+//! IPv4 forwarding is put back as it was once the last network that needed
+//! it is gone.
+
+use crate::error::{Error, Result};
+use crate::service::NetworkConfig;
+use nix::mount::{mount, umount2, MntFlags, MsFlags};
+use std::collections::HashMap;
+use std::fs::File;
+use std::net::Ipv4Addr;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// Directory the namespaces are pinned in.
+pub const NETNS_DIR: &str = "/run/buckos/netns";
+
+/// /30 subnets available in 10.89.0.0/16.
+const SLOTS: u16 = 16384;
+
+/// Host-wide IPv4 forwarding switch.
+const IP_FORWARD: &str = "/proc/sys/net/ipv4/ip_forward";
+
+/// Namespace of a privately networked service.
+pub fn namespace_path(service: &str) -> PathBuf {
+    Path::new(NETNS_DIR).join(service)
+}
+
+/// An iptables rule added for a namespace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    table: &'static str,
+    chain: &'static str,
+    spec: Vec<String>,
+}
+
+impl Rule {
+    fn new(table: &'static str, chain: &'static str, spec: &str) -> Self {
+        Self {
+            table,
+            chain,
+            spec: spec.split_whitespace().map(str::to_string).collect(),
+        }
+    }
+
+    /// Check (`-C`), add (`-A`) or delete (`-D`) the rule.
+    fn apply(&self, action: &str) -> Result<()> {
+        let mut args = vec!["-w", "-t", self.table, action, self.chain];
+        args.extend(self.spec.iter().map(String::as_str));
+        run("iptables", &args)
+    }
+
+    fn exists(&self) -> bool {
+        self.apply("-C").is_ok()
+    }
+
+    /// Add the rule unless it is already there, say from before a restart.
+    fn add(&self) -> Result<()> {
+        if self.exists() {
+            return Ok(());
+        }
+        self.apply("-A")
+    }
+
+    /// Delete every copy of the rule.
+    fn remove(&self) -> Result<()> {
+        while self.exists() {
+            self.apply("-D")?;
+        }
+        Ok(())
+    }
+}
+
+/// The network of one service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrivateNetwork {
+    pub slot: u16,
+    pub namespace: PathBuf,
+    /// Host end of the veth pair
+    pub host_interface: String,
+    pub host_address: Ipv4Addr,
+    /// Address of `eth0` inside the namespace
+    pub service_address: Ipv4Addr,
+    rules: Vec<Rule>,
+}
+
+impl PrivateNetwork {
+    /// Addresses and forwarding rules of subnet `slot`.
+    fn new(service: &str, slot: u16, config: &NetworkConfig) -> Self {
+        let base = u32::from(Ipv4Addr::new(10, 89, 0, 0)) + slot as u32 * 4;
+        let host_interface = format!("vb-{}", slot);
+        let host_address = Ipv4Addr::from(base + 1);
+        let service_address = Ipv4Addr::from(base + 2);
+        let subnet = format!("{}/30", Ipv4Addr::from(base));
+
+        let mut rules = Vec::new();
+        if config.nat || !config.published_ports.is_empty() {
+            rules.push(Rule::new(
+                "filter",
+                "FORWARD",
+                &format!("-i {} -j ACCEPT", host_interface),
+            ));
+            rules.push(Rule::new(
+                "filter",
+                "FORWARD",
+                &format!("-o {} -j ACCEPT", host_interface),
+            ));
+        }
+        if config.nat {
+            rules.push(Rule::new(
+                "nat",
+                "POSTROUTING",
+                &format!("-s {} ! -o {} -j MASQUERADE", subnet, host_interface),
+            ));
+        }
+        for port in &config.published_ports {
+            let dnat = format!(
+                "-p {} -m addrtype --dst-type LOCAL --dport {} -j DNAT --to-destination {}:{}",
+                port.protocol(),
+                port.host_port,
+                service_address,
+                port.service_port
+            );
+            rules.push(Rule::new("nat", "PREROUTING", &dnat));
+            rules.push(Rule::new("nat", "OUTPUT", &dnat));
+        }
+
+        Self {
+            slot,
+            namespace: namespace_path(service),
+            host_interface,
+            host_address,
+            service_address,
+            rules,
+        }
+    }
+
+    /// Create the namespace, link it to the host and add the rules.
+    fn create(&self) -> Result<()> {
+        create_namespace(&self.namespace)?;
+
+        // Create the pair inside the namespace and hand the host end to
+        // init's own namespace
+        let host_interface = self.host_interface.clone();
+        let service_address = format!("{}/30", self.service_address);
+        let gateway = self.host_address.to_string();
+        let init_pid = std::process::id().to_string();
+        in_namespace(&self.namespace, move || {
+            run("ip", &["link", "set", "lo", "up"])?;
+            run(
+                "ip",
+                &[
+                    "link",
+                    "add",
+                    "eth0",
+                    "type",
+                    "veth",
+                    "peer",
+                    "name",
+                    &host_interface,
+                ],
+            )?;
+            run("ip", &["link", "set", &host_interface, "netns", &init_pid])?;
+            run("ip", &["addr", "add", &service_address, "dev", "eth0"])?;
+            run("ip", &["link", "set", "eth0", "up"])?;
+            run("ip", &["route", "add", "default", "via", &gateway])
+        })?;
+
+        let host_address = format!("{}/30", self.host_address);
+        run(
+            "ip",
+            &["addr", "add", &host_address, "dev", &self.host_interface],
+        )?;
+        run("ip", &["link", "set", &self.host_interface, "up"])?;
+
+        for rule in &self.rules {
+            rule.add()?;
+        }
+        Ok(())
+    }
+
+    /// Remove the rules, links and namespace, continuing past failures.
+    /// Returns the first failure.
+    fn destroy(&self) -> Result<()> {
+        let mut result = Ok(());
+        let mut keep_first = |r: Result<()>| {
+            if let Err(e) = r {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        };
+        for rule in self.rules.iter().rev() {
+            keep_first(rule.remove());
+        }
+        // Deleting one end of the pair deletes both
+        keep_first(run("ip", &["link", "del", &self.host_interface]));
+        keep_first(umount2(&self.namespace, MntFlags::MNT_DETACH).map_err(Error::from));
+        keep_first(std::fs::remove_file(&self.namespace).map_err(Error::from));
+        result
+    }
+}
+
+/// Creates and removes the networks of privately networked services.
+#[derive(Debug, Default)]
+pub struct NetworkHelper {
+    networks: Mutex<HashMap<String, PrivateNetwork>>,
+    /// `ip_forward` as found before the first forwarding network turned it
+    /// on; written back once the last one is gone
+    saved_forward: Mutex<Option<String>>,
+}
+
+impl NetworkHelper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set up the network of `service`, unless it already exists.
+    pub fn setup(&self, service: &str, config: &NetworkConfig) -> Result<PrivateNetwork> {
+        let mut networks = self.networks.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(network) = networks.get(service) {
+            return Ok(network.clone());
+        }
+        let slot = lowest_free_slot(networks.values().map(|n| n.slot))
+            .ok_or_else(|| Error::Other("no free subnet left for private networks".to_string()))?;
+
+        let network = PrivateNetwork::new(service, slot, config);
+        let created = network.create().and_then(|()| {
+            if network.rules.is_empty() {
+                Ok(())
+            } else {
+                self.enable_forwarding()
+            }
+        });
+        if let Err(e) = created {
+            // Undo whatever was set up; failures here are expected
+            let _ = network.destroy();
+            return Err(Error::Other(format!(
+                "failed to set up private network of {}: {}",
+                service, e
+            )));
+        }
+        info!(
+            service = %service,
+            address = %network.service_address,
+            interface = %network.host_interface,
+            "Created private network"
+        );
+        networks.insert(service.to_string(), network.clone());
+        Ok(network)
+    }
+
+    /// Remove the network of `service`, if it has one.
+    pub fn teardown(&self, service: &str) {
+        let mut networks = self.networks.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(network) = networks.remove(service) {
+            if let Err(e) = network.destroy() {
+                warn!(service = %service, error = %e, "Failed to remove private network");
+            }
+        }
+        if networks.values().all(|n| n.rules.is_empty()) {
+            if let Err(e) = self.restore_forwarding() {
+                warn!(error = %e, "Failed to restore IPv4 forwarding");
+            }
+        }
+    }
+
+    /// Turn on IPv4 forwarding, remembering the setting it replaces.
+    fn enable_forwarding(&self) -> Result<()> {
+        let mut saved = self.saved_forward.lock().unwrap_or_else(|e| e.into_inner());
+        if saved.is_none() {
+            let current = std::fs::read_to_string(IP_FORWARD)?;
+            if current.trim() == "1" {
+                return Ok(());
+            }
+            std::fs::write(IP_FORWARD, "1")?;
+            *saved = Some(current);
+        }
+        Ok(())
+    }
+
+    /// Put back the forwarding setting [`Self::enable_forwarding`] replaced.
+    fn restore_forwarding(&self) -> Result<()> {
+        let mut saved = self.saved_forward.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(value) = saved.take() {
+            std::fs::write(IP_FORWARD, value.trim())?;
+        }
+        Ok(())
+    }
+
+    /// The network of `service`, if it has one.
+    pub fn get(&self, service: &str) -> Option<PrivateNetwork> {
+        self.networks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(service)
+            .cloned()
+    }
+}
+
+/// Lowest subnet slot not in `used`.
+fn lowest_free_slot(used: impl Iterator<Item = u16>) -> Option<u16> {
+    let mut used: Vec<u16> = used.collect();
+    used.sort_unstable();
+    let mut slot = 0;
+    for u in used {
+        if u == slot {
+            slot += 1;
+        } else if u > slot {
+            break;
+        }
+    }
+    (slot < SLOTS).then_some(slot)
+}
+
+/// Create a network namespace pinned at `path`.
+fn create_namespace(path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    File::create(path)?;
+    let target = path.to_path_buf();
+    // Unsharing moves only the calling thread, so use a throwaway one
+    std::thread::spawn(move || -> Result<()> {
+        if unsafe { libc::unshare(libc::CLONE_NEWNET) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        mount(
+            Some("/proc/thread-self/ns/net"),
+            &target,
+            None::<&str>,
+            MsFlags::MS_BIND,
+            None::<&str>,
+        )?;
+        Ok(())
+    })
+    .join()
+    .map_err(|_| Error::Other("network namespace setup panicked".to_string()))?
+}
+
+/// Run `f` on a thread inside the namespace pinned at `path`; processes it
+/// spawns start in the namespace too.
+fn in_namespace<F>(path: &Path, f: F) -> Result<()>
+where
+    F: FnOnce() -> Result<()> + Send + 'static,
+{
+    let ns = File::open(path)?;
+    std::thread::spawn(move || {
+        if unsafe { libc::setns(ns.as_raw_fd(), libc::CLONE_NEWNET) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        f()
+    })
+    .join()
+    .map_err(|_| Error::Other("network namespace setup panicked".to_string()))?
+}
+
+/// Run a network tool, failing on a non-zero exit.
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    let output = Command::new(program).args(args).output()?;
+    if !output.status.success() {
+        return Err(Error::Other(format!(
+            "{} {}: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::PublishedPort;
+
+    #[test]
+    fn test_addresses_and_rules() {
+        let config = NetworkConfig {
+            private_network: true,
+            nat: true,
+            published_ports: vec!["8080:80".parse().unwrap()],
+        };
+        let network = PrivateNetwork::new("web", 70, &config);
+        assert_eq!(network.host_interface, "vb-70");
+        assert_eq!(network.host_address, Ipv4Addr::new(10, 89, 1, 25));
+        assert_eq!(network.service_address, Ipv4Addr::new(10, 89, 1, 26));
+        assert_eq!(network.namespace, PathBuf::from("/run/buckos/netns/web"));
+
+        let specs: Vec<String> = network.rules.iter().map(|r| r.spec.join(" ")).collect();
+        assert!(specs.contains(&"-s 10.89.1.24/30 ! -o vb-70 -j MASQUERADE".to_string()));
+        assert!(specs
+            .iter()
+            .any(|s| s.ends_with("--dport 8080 -j DNAT --to-destination 10.89.1.26:80")));
+
+        // Isolated: no forwarding at all
+        let isolated = PrivateNetwork::new("web", 0, &NetworkConfig::default());
+        assert!(isolated.rules.is_empty());
+    }
+
+    #[test]
+    fn test_slots_and_ports() {
+        assert_eq!(lowest_free_slot([].into_iter()), Some(0));
+        assert_eq!(lowest_free_slot([0, 1, 3].into_iter()), Some(2));
+        assert_eq!(lowest_free_slot(0..SLOTS), None);
+
+        let port: PublishedPort = "53/udp".parse().unwrap();
+        assert_eq!(
+            (port.host_port, port.service_port, port.udp),
+            (53, 53, true)
+        );
+        assert!("80/sctp".parse::<PublishedPort>().is_err());
+        assert!("0:80".parse::<PublishedPort>().is_err());
+    }
+}
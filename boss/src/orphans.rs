@@ -0,0 +1,176 @@
+//! Accounting of orphaned processes adopted by init.
+//!
+//! As PID 1, or as a child subreaper, boss inherits every process whose
+//! parent exits first and reaps it when it exits. Each orphan is
+//! attributed to the service whose cgroup it ran in. Many orphans from one
+//! service in a short time usually mean a daemon double-forks without a
+//! unit tracking its children; such storms are logged.
+
+use crate::coredump::parse_cgroup_service;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Orphans reaped from one service within [`STORM_WINDOW`] that are
+/// reported as a storm.
+pub const STORM_THRESHOLD: usize = 50;
+
+/// Window orphan storms are detected over.
+pub const STORM_WINDOW: Duration = Duration::from_secs(60);
+
+/// Orphans of one originating service.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrphanCount {
+    /// Service the orphans ran under, if their cgroup names one
+    pub service: Option<String>,
+    /// Orphans adopted and still running
+    pub running: usize,
+    /// Orphans reaped since boot
+    pub reaped: u64,
+}
+
+/// Reaped orphans per originating service, and recent reaps for storm
+/// detection.
+#[derive(Debug, Default)]
+pub struct OrphanTracker {
+    reaped: HashMap<Option<String>, u64>,
+    recent: HashMap<Option<String>, VecDeque<Instant>>,
+}
+
+impl OrphanTracker {
+    /// Create an empty tracker.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a reaped orphan.
+    ///
+    /// Returns the number of orphans reaped from the service within
+    /// [`STORM_WINDOW`] when that reaches [`STORM_THRESHOLD`]; the count
+    /// then starts over, so a continuing storm is reported once a window.
+    pub fn record(&mut self, service: Option<String>, now: Instant) -> Option<usize> {
+        *self.reaped.entry(service.clone()).or_default() += 1;
+
+        let recent = self.recent.entry(service).or_default();
+        while recent
+            .front()
+            .is_some_and(|t| now.duration_since(*t) > STORM_WINDOW)
+        {
+            recent.pop_front();
+        }
+        recent.push_back(now);
+        if recent.len() < STORM_THRESHOLD {
+            return None;
+        }
+        let count = recent.len();
+        recent.clear();
+        Some(count)
+    }
+
+    /// Counts per service, given the orphans still running.
+    pub fn counts(&self, running: &HashMap<Option<String>, usize>) -> Vec<OrphanCount> {
+        let services: HashSet<&Option<String>> = self.reaped.keys().chain(running.keys()).collect();
+        let mut counts: Vec<OrphanCount> = services
+            .into_iter()
+            .map(|service| OrphanCount {
+                service: service.clone(),
+                running: running.get(service).copied().unwrap_or(0),
+                reaped: self.reaped.get(service).copied().unwrap_or(0),
+            })
+            .collect();
+        counts.sort_by(|a, b| b.reaped.cmp(&a.reaped).then(a.service.cmp(&b.service)));
+        counts
+    }
+}
+
+/// Service whose cgroup a process, or the zombie it left, is in.
+pub fn origin(pid: u32) -> Option<String> {
+    std::fs::read_to_string(format!("/proc/{}/cgroup", pid))
+        .ok()
+        .and_then(|c| parse_cgroup_service(&c))
+}
+
+/// Running children of this process that it did not spawn, by service.
+pub fn running_orphans(spawned: &HashSet<u32>) -> HashMap<Option<String>, usize> {
+    let me = std::process::id();
+    let mut running = HashMap::new();
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return running;
+    };
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|n| n.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if spawned.contains(&pid) {
+            continue;
+        }
+        let Ok(status) = std::fs::read_to_string(entry.path().join("status")) else {
+            continue;
+        };
+        if parse_ppid(&status) == Some(me) {
+            *running.entry(origin(pid)).or_default() += 1;
+        }
+    }
+    running
+}
+
+/// PPid from /proc/<pid>/status.
+fn parse_ppid(status: &str) -> Option<u32> {
+    status
+        .lines()
+        .find_map(|l| l.strip_prefix("PPid:"))
+        .and_then(|v| v.trim().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storm_reported_once_per_threshold() {
+        let mut tracker = OrphanTracker::new();
+        let start = Instant::now();
+        let daemon = Some("forky".to_string());
+
+        // Spread out, no storm
+        for i in 0..STORM_THRESHOLD as u64 {
+            let at = start + STORM_WINDOW * 2 * i as u32 / STORM_THRESHOLD as u32;
+            assert_eq!(tracker.record(daemon.clone(), at), None, "reap {}", i);
+        }
+
+        let burst = start + STORM_WINDOW * 3;
+        let storms: Vec<usize> = (0..STORM_THRESHOLD * 2)
+            .filter_map(|_| tracker.record(daemon.clone(), burst))
+            .collect();
+        assert_eq!(storms, vec![STORM_THRESHOLD, STORM_THRESHOLD]);
+
+        tracker.record(None, burst);
+        let running = HashMap::from([(Some("sshd".to_string()), 2)]);
+        let counts = tracker.counts(&running);
+        assert_eq!(
+            counts[0],
+            OrphanCount {
+                service: daemon,
+                running: 0,
+                reaped: STORM_THRESHOLD as u64 * 3,
+            }
+        );
+        assert_eq!(counts.len(), 3);
+        assert!(counts
+            .iter()
+            .any(|c| c.service.as_deref() == Some("sshd") && c.running == 2 && c.reaped == 0));
+    }
+
+    #[test]
+    fn test_parse_ppid() {
+        assert_eq!(
+            parse_ppid("Name:\tsleep\nState:\tS (sleeping)\nPid:\t42\nPPid:\t1\n"),
+            Some(1)
+        );
+        assert_eq!(parse_ppid("Name:\tsleep\n"), None);
+    }
+}
@@ -0,0 +1,131 @@
+//! Packages services require to be installed.
+//!
+//! A unit names the packages it needs with `RequiresPackage=category/name`
+//! (`requires_package` in TOML). A unit whose packages are missing is not
+//! started; it is marked uninstallable instead of failing to exec its
+//! binary over and over. The package manager tells init when packages
+//! change, and the units are checked again.
+//!
+//! Installed packages are read from the plain-text records the package
+//! manager keeps, one directory per package at
+//! `<dir>/<category>/<name>-<version>/`. Init only lists directories, so it
+//! never takes the package database's lock.
+
+use std::path::{Path, PathBuf};
+
+/// Where the package manager keeps its package records
+pub const PACKAGE_DB_DIR: &str = "/var/db/buckos/pkg";
+
+/// Read-only view of the installed packages.
+#[derive(Debug, Clone)]
+pub struct PackageDb {
+    dir: PathBuf,
+}
+
+impl Default for PackageDb {
+    fn default() -> Self {
+        Self::new(PACKAGE_DB_DIR)
+    }
+}
+
+impl PackageDb {
+    /// Packages recorded under `dir`.
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Whether any version of `category/name` is installed.
+    pub fn is_installed(&self, package: &str) -> bool {
+        let Some((category, name)) = parse_package(package) else {
+            return false;
+        };
+        let Ok(entries) = std::fs::read_dir(self.dir.join(category)) else {
+            return false;
+        };
+        entries.flatten().any(|entry| {
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            // The version starts with a digit, so `foo` does not match
+            // a record of `foo-bar`
+            file_name
+                .strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix('-'))
+                .is_some_and(|version| version.starts_with(|c: char| c.is_ascii_digit()))
+                && entry.path().is_dir()
+        })
+    }
+
+    /// The packages of `packages` that are not installed.
+    pub fn missing(&self, packages: &[String]) -> Vec<String> {
+        packages
+            .iter()
+            .filter(|p| !self.is_installed(p))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Category and name of a `category/name` package.
+pub fn parse_package(package: &str) -> Option<(&str, &str)> {
+    let (category, name) = package.split_once('/')?;
+    let valid = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_+.".contains(c))
+    };
+    (valid(category) && valid(name)).then_some((category, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_installed_packages() {
+        let dir = std::env::temp_dir().join(format!("boss-packages-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("www-servers/nginx-1.25.3")).unwrap();
+        std::fs::create_dir_all(dir.join("dev-db/redis-tools-7.0.0")).unwrap();
+        let db = PackageDb::new(&dir);
+
+        assert!(db.is_installed("www-servers/nginx"));
+        assert!(!db.is_installed("dev-db/redis"));
+        assert!(!db.is_installed("nginx"));
+        assert_eq!(
+            db.missing(&["www-servers/nginx".to_string(), "dev-db/redis".to_string()]),
+            vec!["dev-db/redis".to_string()]
+        );
+        assert_eq!(parse_package("a/b/c"), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_service_missing_package_is_uninstallable() {
+        use crate::error::Error;
+        use crate::manager::ServiceManager;
+        use crate::service::{ServiceDefinition, ServiceState};
+
+        let dir =
+            std::env::temp_dir().join(format!("boss-requires-package-{}", std::process::id()));
+        let manager = ServiceManager::new(dir.join("services")).with_package_db(dir.join("pkg"));
+        let mut def = ServiceDefinition::new("nginx", "/usr/sbin/nginx");
+        def.requires_package = vec!["www-servers/nginx".to_string()];
+        manager.register_service(def).await.unwrap();
+
+        let err = manager.start_service("nginx").await.unwrap_err();
+        assert!(matches!(err, Error::PackagesMissing { .. }), "{}", err);
+        let status = manager.get_status("nginx").await.unwrap();
+        assert_eq!(status.state, ServiceState::Uninstallable);
+        assert_eq!(status.missing_packages, vec!["www-servers/nginx"]);
+        assert!(manager.packages_changed().await.is_empty());
+
+        std::fs::create_dir_all(dir.join("pkg/www-servers/nginx-1.25.3")).unwrap();
+        assert_eq!(manager.packages_changed().await, vec!["nginx"]);
+        let status = manager.get_status("nginx").await.unwrap();
+        assert_eq!(status.state, ServiceState::Inactive);
+        assert!(status.missing_packages.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,250 @@
+//! Path activation.
+//!
+//! A service with a `[Path]` section is started when a watched path
+//! appears, changes, or a watched directory gets entries. Paths are watched
+//! with inotify on the directory containing them, so a path can be watched
+//! before it exists, and activations are rate limited so a busy directory
+//! cannot start the service in a loop.
+
+use crate::error::Result;
+use crate::service::PathConfig;
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, WatchDescriptor};
+use std::collections::{HashMap, VecDeque};
+use std::ffi::OsStr;
+use std::os::fd::{AsFd, AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::io::unix::AsyncFd;
+use tracing::warn;
+
+/// A condition that starts the service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathCondition {
+    /// The path exists
+    Exists(PathBuf),
+    /// The file was written, created, moved or deleted
+    Changed(PathBuf),
+    /// The directory has entries
+    DirectoryNotEmpty(PathBuf),
+}
+
+impl PathCondition {
+    /// Every condition of a path configuration.
+    pub fn all(config: &PathConfig) -> Vec<Self> {
+        let exists = config.path_exists.iter().cloned().map(Self::Exists);
+        let changed = config.path_changed.iter().cloned().map(Self::Changed);
+        let not_empty = config
+            .directory_not_empty
+            .iter()
+            .cloned()
+            .map(Self::DirectoryNotEmpty);
+        exists.chain(changed).chain(not_empty).collect()
+    }
+
+    /// Whether the condition holds right now. Only PathExists and
+    /// DirectoryNotEmpty are states; PathChanged needs an event.
+    pub fn holds(&self) -> bool {
+        match self {
+            Self::Exists(path) => path.exists(),
+            Self::Changed(_) => false,
+            Self::DirectoryNotEmpty(dir) => {
+                std::fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_some())
+            }
+        }
+    }
+
+    /// Directory whose events can trigger the condition.
+    fn watch_dir(&self) -> Option<&Path> {
+        match self {
+            Self::Exists(path) | Self::Changed(path) => path.parent(),
+            Self::DirectoryNotEmpty(dir) => Some(dir),
+        }
+    }
+
+    /// Whether an event for `name` in the watched directory `dir`
+    /// triggers the condition.
+    fn triggered_by(&self, dir: &Path, name: Option<&OsStr>) -> bool {
+        match self {
+            Self::Exists(path) => {
+                path.parent() == Some(dir) && path.file_name() == name && self.holds()
+            }
+            Self::Changed(path) => path.parent() == Some(dir) && path.file_name() == name,
+            Self::DirectoryNotEmpty(path) => path == dir && self.holds(),
+        }
+    }
+}
+
+/// Events that can trigger a condition.
+fn watch_flags() -> AddWatchFlags {
+    AddWatchFlags::IN_CREATE
+        | AddWatchFlags::IN_MOVED_TO
+        | AddWatchFlags::IN_MOVED_FROM
+        | AddWatchFlags::IN_DELETE
+        | AddWatchFlags::IN_CLOSE_WRITE
+        | AddWatchFlags::IN_ATTRIB
+}
+
+/// Inotify instance registered with the tokio reactor.
+struct InotifyFd(Inotify);
+
+impl AsRawFd for InotifyFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_fd().as_raw_fd()
+    }
+}
+
+/// Watches the paths of one service.
+pub struct PathWatcher {
+    inotify: AsyncFd<InotifyFd>,
+    /// Directory of each watch
+    watches: HashMap<WatchDescriptor, PathBuf>,
+    conditions: Vec<PathCondition>,
+}
+
+impl PathWatcher {
+    pub fn new(config: &PathConfig) -> Result<Self> {
+        let conditions = PathCondition::all(config);
+        if config.make_directory {
+            for dir in conditions.iter().filter_map(|c| c.watch_dir()) {
+                std::fs::create_dir_all(dir)?;
+            }
+        }
+
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+        let mut watcher = Self {
+            inotify: AsyncFd::new(InotifyFd(inotify))?,
+            watches: HashMap::new(),
+            conditions,
+        };
+        watcher.add_watches();
+        Ok(watcher)
+    }
+
+    /// Watch each condition's directory, or its nearest existing ancestor
+    /// until the directory is created.
+    fn add_watches(&mut self) {
+        let dirs: Vec<PathBuf> = self
+            .conditions
+            .iter()
+            .filter_map(|c| c.watch_dir())
+            .filter_map(|dir| dir.ancestors().find(|d| d.is_dir()))
+            .map(Path::to_path_buf)
+            .collect();
+        for dir in dirs {
+            if self.watches.values().any(|d| *d == dir) {
+                continue;
+            }
+            match self.inotify.get_ref().0.add_watch(&dir, watch_flags()) {
+                Ok(wd) => {
+                    self.watches.insert(wd, dir);
+                }
+                Err(e) => warn!(path = %dir.display(), error = %e, "Failed to watch path"),
+            }
+        }
+    }
+
+    /// Whether a PathExists or DirectoryNotEmpty condition holds.
+    pub fn holds(&self) -> bool {
+        self.conditions.iter().any(|c| c.holds())
+    }
+
+    /// Wait for an event that triggers a condition.
+    pub async fn wait(&mut self) -> Result<()> {
+        loop {
+            let mut ready = self.inotify.readable().await?;
+            let events = match ready.try_io(|fd| Ok(fd.get_ref().0.read_events()?)) {
+                Ok(events) => events?,
+                Err(_would_block) => continue,
+            };
+
+            let triggered = events.iter().any(|event| {
+                self.watches.get(&event.wd).is_some_and(|dir| {
+                    self.conditions
+                        .iter()
+                        .any(|c| c.triggered_by(dir, event.name.as_deref()))
+                })
+            });
+            // A watched ancestor may now contain the directory itself
+            self.add_watches();
+            if triggered {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Rate limit on activations: at most `burst` per `interval`.
+#[derive(Debug)]
+pub struct TriggerLimit {
+    interval: Duration,
+    burst: u32,
+    recent: VecDeque<Instant>,
+}
+
+impl TriggerLimit {
+    pub fn new(config: &PathConfig) -> Self {
+        Self {
+            interval: config.trigger_limit_interval,
+            burst: config.trigger_limit_burst,
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// Record an activation at `now`, or refuse it if the limit is hit.
+    pub fn allow(&mut self, now: Instant) -> bool {
+        while self
+            .recent
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= self.interval)
+        {
+            self.recent.pop_front();
+        }
+        if self.burst > 0 && self.recent.len() >= self.burst as usize {
+            return false;
+        }
+        self.recent.push_back(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conditions() {
+        let config = PathConfig {
+            path_exists: vec![PathBuf::from("/")],
+            path_changed: vec![PathBuf::from("/etc/app/app.conf")],
+            directory_not_empty: vec![PathBuf::from("/var/spool/app")],
+            ..Default::default()
+        };
+        let conditions = PathCondition::all(&config);
+        assert_eq!(conditions.len(), 3);
+        assert!(conditions[0].holds());
+        assert!(!conditions[1].holds());
+
+        let changed = &conditions[1];
+        assert_eq!(changed.watch_dir(), Some(Path::new("/etc/app")));
+        assert!(changed.triggered_by(Path::new("/etc/app"), Some(OsStr::new("app.conf"))));
+        assert!(!changed.triggered_by(Path::new("/etc/app"), Some(OsStr::new("other.conf"))));
+        assert!(!changed.triggered_by(Path::new("/etc"), Some(OsStr::new("app.conf"))));
+    }
+
+    #[test]
+    fn test_trigger_limit() {
+        let config = PathConfig {
+            trigger_limit_interval: Duration::from_secs(2),
+            trigger_limit_burst: 3,
+            ..Default::default()
+        };
+        let mut limit = TriggerLimit::new(&config);
+        let start = Instant::now();
+        assert!(limit.allow(start));
+        assert!(limit.allow(start + Duration::from_millis(100)));
+        assert!(limit.allow(start + Duration::from_millis(200)));
+        assert!(!limit.allow(start + Duration::from_millis(300)));
+        // The first activation has left the window
+        assert!(limit.allow(start + Duration::from_millis(2000)));
+    }
+}
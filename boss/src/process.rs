@@ -1,23 +1,53 @@
 //! Process management for the init system.
 //!
 //! This module handles spawning, supervising, and reaping processes.
+//!
+//! Each process is tracked through a pidfd, so signals and waits reach
+//! exactly the process that was spawned even once its PID is reused, and
+//! exits are noticed by polling the pidfd rather than retrying `waitpid`.
+//! Every signal is blocked in the spawning thread across `fork`; the child
+//! restores default handlers before unblocking them, so a signal arriving
+//! before `exec` can never run init's handlers in the child.
 
+use crate::accounting::ResourceUsage;
 use crate::error::{Error, Result};
 use crate::journal::{Journal, JournalEntry};
-use crate::service::{ResourceLimits, ServiceDefinition};
+use crate::lsm::{self, ExecLabel};
+use crate::netns;
+use crate::orphans::{self, OrphanCount, OrphanTracker, STORM_WINDOW};
+use crate::seccomp::SeccompFilter;
+use crate::service::{ResourceLimits, ServiceDefinition, TtyConfig};
+use crate::socket_unit;
+use nix::errno::Errno;
 use nix::sys::resource::{setrlimit, Resource};
-use nix::sys::signal::{self, Signal};
-use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::sys::signal::{self, SigSet, SigmaskHow, Signal};
+use nix::sys::wait::WaitStatus;
 use nix::unistd::Pid;
-use std::collections::HashMap;
-use std::io::{BufRead, BufReader};
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::os::fd::{OwnedFd, RawFd};
+use std::os::unix::fs::OpenOptionsExt;
 use std::os::unix::io::{AsRawFd, FromRawFd};
 use std::os::unix::process::CommandExt;
 use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::io::unix::AsyncFd;
+use tokio::io::Interest;
+use tokio::sync::{Notify, RwLock};
 use tracing::{debug, error, info, warn};
 
+/// `PATH` of every service process.
+pub const SERVICE_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+/// How long a process gets to exit after SIGKILL.
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Interval between checks on processes without a pidfd.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 /// Information about a spawned process.
 #[derive(Debug)]
 pub struct ProcessInfo {
@@ -29,6 +59,10 @@ pub struct ProcessInfo {
     pub service_name: String,
     /// Whether this is the main process
     pub is_main: bool,
+    /// Controlling terminal, released when the process exits
+    pub tty: Option<TtyConfig>,
+    /// Descriptor for the process, on kernels with pidfds (5.3 and later)
+    pub pidfd: Option<OwnedFd>,
 }
 
 /// Exit status of a process.
@@ -40,6 +74,10 @@ pub struct ExitStatus {
     pub code: Option<i32>,
     /// Signal (if killed by signal)
     pub signal: Option<i32>,
+    /// Service the process belonged to, if it was supervised
+    pub service: Option<String>,
+    /// Resources used by the process and the children it waited for
+    pub usage: Option<ResourceUsage>,
 }
 
 impl ExitStatus {
@@ -53,6 +91,16 @@ impl ExitStatus {
 pub struct ProcessSupervisor {
     /// Map of PID to process info
     processes: Arc<RwLock<HashMap<u32, ProcessInfo>>>,
+    /// Held shared while a spawn is in flight, and exclusively to reap
+    /// children that are not tracked: a child that exits before it is
+    /// tracked must not be reaped as an orphan.
+    spawn_gate: RwLock<()>,
+    /// Orphans were left for later because a spawn was in flight
+    reap_pending: AtomicBool,
+    /// Notified when left-over children should be reaped
+    reap_requested: Arc<Notify>,
+    /// Orphans reaped, by the service they came from
+    orphans: std::sync::Mutex<OrphanTracker>,
 }
 
 impl ProcessSupervisor {
@@ -60,11 +108,73 @@ impl ProcessSupervisor {
     pub fn new() -> Self {
         Self {
             processes: Arc::new(RwLock::new(HashMap::new())),
+            spawn_gate: RwLock::new(()),
+            reap_pending: AtomicBool::new(false),
+            reap_requested: Arc::new(Notify::new()),
+            orphans: std::sync::Mutex::new(OrphanTracker::new()),
         }
     }
 
+    /// Adopt orphaned descendants when not running as PID 1.
+    ///
+    /// Services that double-fork are then reparented to boss rather than
+    /// the real init, so their exits are still seen and reaped.
+    pub fn become_subreaper(&self) -> Result<()> {
+        if std::process::id() == 1 {
+            return Ok(());
+        }
+        if unsafe { libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1, 0, 0, 0) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        info!("Registered as child subreaper");
+        Ok(())
+    }
+
+    /// Notified when children left unreaped by [`reap_zombies`] while a
+    /// spawn was in flight can be reaped.
+    ///
+    /// [`reap_zombies`]: Self::reap_zombies
+    pub fn reap_requested(&self) -> Arc<Notify> {
+        Arc::clone(&self.reap_requested)
+    }
+
     /// Spawn a process for a service.
     pub async fn spawn(&self, service: &ServiceDefinition, journal: Arc<Journal>) -> Result<u32> {
+        self.spawn_with_stdin(service, journal, None, Vec::new())
+            .await
+    }
+
+    /// Spawn a process for a socket-activated service, with the connection
+    /// as standard input (`standard_input = "socket"`).
+    pub async fn spawn_with_socket(
+        &self,
+        service: &ServiceDefinition,
+        journal: Arc<Journal>,
+        socket: OwnedFd,
+    ) -> Result<u32> {
+        self.spawn_with_stdin(service, journal, Some(socket), Vec::new())
+            .await
+    }
+
+    /// Spawn a process for a socket-activated service, passing it its
+    /// listening sockets from descriptor 3 on.
+    pub async fn spawn_with_listen_fds(
+        &self,
+        service: &ServiceDefinition,
+        journal: Arc<Journal>,
+        listen_fds: Vec<OwnedFd>,
+    ) -> Result<u32> {
+        self.spawn_with_stdin(service, journal, None, listen_fds)
+            .await
+    }
+
+    async fn spawn_with_stdin(
+        &self,
+        service: &ServiceDefinition,
+        journal: Arc<Journal>,
+        socket: Option<OwnedFd>,
+        listen_fds: Vec<OwnedFd>,
+    ) -> Result<u32> {
         let parts: Vec<&str> = service.exec_start.split_whitespace().collect();
         if parts.is_empty() {
             return Err(Error::ProcessSpawnFailed(
@@ -78,6 +188,12 @@ impl ProcessSupervisor {
         let mut cmd = Command::new(program);
         cmd.args(args);
 
+        // Undo init's signal handling first, so later setup steps can be
+        // interrupted like the service itself
+        unsafe {
+            cmd.pre_exec(reset_signals);
+        }
+
         // Set working directory if specified
         if let Some(ref dir) = service.working_directory {
             cmd.current_dir(dir);
@@ -87,10 +203,90 @@ impl ProcessSupervisor {
         cmd.envs(&service.environment);
 
         // Clear environment and set basic vars
-        cmd.env(
-            "PATH",
-            "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin",
-        );
+        cmd.env("PATH", SERVICE_PATH);
+        cmd.env("BUCKOS_SERVICE", &service.name);
+
+        // Standard input, and the terminal (or socket) that output set to
+        // inherit or tty goes to
+        let terminal: Option<File> = match service.standard_input.as_str() {
+            "socket" => Some(File::from(socket.ok_or_else(|| {
+                Error::ProcessSpawnFailed(format!(
+                    "{}: standard_input = \"socket\" requires socket activation",
+                    service.name
+                ))
+            })?)),
+            _ if service.stdin_is_tty()
+                || service.standard_output == "tty"
+                || service.standard_error == "tty" =>
+            {
+                Some(open_tty(&service.tty)?)
+            }
+            _ => None,
+        };
+        let stdin_terminal = service.stdin_is_tty() || service.standard_input == "socket";
+        match &terminal {
+            Some(terminal) if stdin_terminal => {
+                cmd.stdin(Stdio::from(terminal.try_clone()?));
+            }
+            _ => {
+                cmd.stdin(Stdio::null());
+            }
+        }
+
+        // Join the service's network namespace, set up by the manager
+        if service.network.private_network {
+            let netns = File::open(netns::namespace_path(&service.name))?;
+            unsafe {
+                cmd.pre_exec(move || {
+                    if libc::setns(netns.as_raw_fd(), libc::CLONE_NEWNET) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+
+        // Request the service's AppArmor or SELinux label for the exec,
+        // while still privileged
+        let labels = lsm::exec_labels(&service.security)
+            .map_err(|e| Error::ProcessSpawnFailed(format!("{}: {}", service.name, e)))?;
+        if !labels.is_empty() {
+            unsafe {
+                cmd.pre_exec(move || labels.iter().try_for_each(ExecLabel::apply));
+            }
+        }
+
+        // Pass listening sockets the way sd_listen_fds() expects them
+        let listen_pid = (!listen_fds.is_empty()).then(|| Arc::new(AtomicU32::new(0)));
+        if let Some(pid) = &listen_pid {
+            cmd.env("LISTEN_FDS", listen_fds.len().to_string());
+            let fds: Vec<RawFd> = listen_fds.iter().map(|fd| fd.as_raw_fd()).collect();
+            let pid = Arc::clone(pid);
+            unsafe {
+                cmd.pre_exec(move || {
+                    // Open until the command is dropped after the spawn
+                    let _ = &listen_fds;
+                    socket_unit::install_listen_fds(&fds, &pid)
+                });
+            }
+        }
+
+        // Create a new session for the process, taking the terminal as its
+        // controlling terminal. This runs before dropping privileges, since
+        // stealing a terminal needs CAP_SYS_ADMIN.
+        let tty_mode = service
+            .stdin_is_tty()
+            .then(|| service.standard_input.clone());
+        let tty_wait = service.timeout_start_sec;
+        unsafe {
+            cmd.pre_exec(move || {
+                nix::unistd::setsid().map_err(std::io::Error::other)?;
+                if let Some(mode) = &tty_mode {
+                    acquire_controlling_tty(mode, tty_wait)?;
+                }
+                Ok(())
+            });
+        }
 
         // Set user/group if specified
         if let Some(ref user) = service.user {
@@ -116,12 +312,22 @@ impl ProcessSupervisor {
             }
         }
 
-        // Create new session for the process
-        unsafe {
-            cmd.pre_exec(|| {
-                nix::unistd::setsid().map_err(std::io::Error::other)?;
-                Ok(())
-            });
+        // Install the system call filter last, so that only exec itself
+        // runs under it
+        if service.seccomp.enabled() {
+            let filter =
+                SeccompFilter::new(&service.seccomp.system_call_filter, service.seccomp.learn);
+            unsafe {
+                cmd.pre_exec(move || filter.install());
+            }
+        }
+
+        // With sockets, exec with LISTEN_PID set, after everything else
+        if let Some(pid) = listen_pid {
+            let exec = socket_unit::ListenExec::new(&cmd, pid)?;
+            unsafe {
+                cmd.pre_exec(move || exec.exec());
+            }
         }
 
         // Set up output handling based on configuration
@@ -145,49 +351,80 @@ impl ProcessSupervisor {
                 (None, None)
             };
 
-        cmd.stdin(Stdio::null());
+        if let Some(terminal) = &terminal {
+            let to_terminal =
+                |output: &str| output == "tty" || (stdin_terminal && output == "inherit");
+            if to_terminal(&service.standard_output) {
+                cmd.stdout(Stdio::from(terminal.try_clone()?));
+            }
+            if to_terminal(&service.standard_error) {
+                cmd.stderr(Stdio::from(terminal.try_clone()?));
+            }
+        }
 
-        // Spawn the process
-        let child = cmd
-            .spawn()
+        // Spawn the process. Waiting for a terminal blocks until exec, so
+        // spawn off the async workers.
+        let gate = self.spawn_gate.read().await;
+        let child = tokio::task::spawn_blocking(move || spawn_blocked(&mut cmd))
+            .await
+            .map_err(|e| Error::ProcessSpawnFailed(e.to_string()))?
             .map_err(|e| Error::ProcessSpawnFailed(format!("{}: {}", service.exec_start, e)))?;
+        drop(terminal);
 
         let pid = child.id();
         info!(service = %service.name, pid = pid, "Spawned process");
 
+        // The child is not reaped before it is tracked, so the PID is
+        // still its own
+        let pidfd = pidfd_open(pid)?;
+
         // Track the process
         let process_info = ProcessInfo {
             pid,
             child,
             service_name: service.name.clone(),
             is_main: true,
+            tty: service.stdin_is_tty().then(|| service.tty.clone()),
+            pidfd,
         };
 
         self.processes.write().await.insert(pid, process_info);
-
-        // Spawn tasks to read output and log to journal
-        if let Some(stdout_read) = stdout_pipe {
-            let journal = Arc::clone(&journal);
-            let service_name = service.name.clone();
-            tokio::spawn(async move {
-                let reader = BufReader::new(stdout_read);
-                for line in reader.lines().map_while(|r| r.ok()) {
-                    let entry = JournalEntry::new(&service_name, &line, "stdout").with_pid(pid);
-                    journal.log(entry).await;
-                }
-            });
+        drop(gate);
+        if self.reap_pending.swap(false, Ordering::AcqRel) {
+            self.reap_requested.notify_one();
         }
 
-        if let Some(stderr_read) = stderr_pipe {
+        // Read output on threads of their own, the reads block; queuing an
+        // entry never does, so the pipes keep draining however fast the
+        // service logs
+        let uid = service
+            .user
+            .as_deref()
+            .and_then(|user| user.parse::<u32>().ok())
+            .unwrap_or_else(|| nix::unistd::getuid().as_raw())
+            .to_string();
+        for (pipe, stream) in [(stdout_pipe, "stdout"), (stderr_pipe, "stderr")] {
+            let Some(pipe) = pipe else {
+                continue;
+            };
             let journal = Arc::clone(&journal);
             let service_name = service.name.clone();
-            tokio::spawn(async move {
-                let reader = BufReader::new(stderr_read);
-                for line in reader.lines().map_while(|r| r.ok()) {
-                    let entry = JournalEntry::new(&service_name, &line, "stderr").with_pid(pid);
-                    journal.log(entry).await;
-                }
-            });
+            let uid = uid.clone();
+            let spawned = std::thread::Builder::new()
+                .name(format!("{}-{}", service_name, stream))
+                .spawn(move || {
+                    let reader = BufReader::new(pipe);
+                    for line in reader.lines().map_while(|r| r.ok()) {
+                        journal.submit(
+                            JournalEntry::new(&service_name, &line, stream)
+                                .with_pid(pid)
+                                .with_field("_UID", uid.as_str()),
+                        );
+                    }
+                });
+            if let Err(e) = spawned {
+                warn!(service = %service.name, error = %e, "Failed to start output reader");
+            }
         }
 
         Ok(pid)
@@ -196,114 +433,213 @@ impl ProcessSupervisor {
     /// Send a signal to a process.
     pub async fn signal(&self, pid: u32, sig: Signal) -> Result<()> {
         let processes = self.processes.read().await;
-        if !processes.contains_key(&pid) {
-            return Err(Error::ProcessNotFound(pid));
-        }
+        let info = processes.get(&pid).ok_or(Error::ProcessNotFound(pid))?;
 
-        signal::kill(Pid::from_raw(pid as i32), sig)?;
+        match &info.pidfd {
+            Some(pidfd) => pidfd_send_signal(pidfd, sig)?,
+            None => signal::kill(Pid::from_raw(pid as i32), sig)?,
+        }
         debug!(pid = pid, signal = ?sig, "Sent signal to process");
         Ok(())
     }
 
     /// Stop a process gracefully.
-    pub async fn stop(&self, pid: u32, timeout: std::time::Duration) -> Result<ExitStatus> {
+    pub async fn stop(&self, pid: u32, timeout: Duration) -> Result<ExitStatus> {
         // Send SIGTERM first
         self.signal(pid, Signal::SIGTERM).await?;
 
+        // Shells and other terminal programs ignore SIGTERM but exit when
+        // their terminal hangs up
+        let has_tty = self
+            .processes
+            .read()
+            .await
+            .get(&pid)
+            .is_some_and(|p| p.tty.is_some());
+        if has_tty {
+            self.signal(pid, Signal::SIGHUP).await?;
+        }
+
         // Wait for process to exit
-        let start = std::time::Instant::now();
-        loop {
-            if let Some(status) = self.try_wait(pid).await? {
-                return Ok(status);
-            }
+        if let Some(status) = self.wait_exit(pid, timeout).await? {
+            return Ok(status);
+        }
 
-            if start.elapsed() > timeout {
-                // Process didn't exit in time, send SIGKILL
-                warn!(pid = pid, "Process didn't exit in time, sending SIGKILL");
-                self.signal(pid, Signal::SIGKILL).await?;
+        // Process didn't exit in time, send SIGKILL
+        warn!(pid = pid, "Process didn't exit in time, sending SIGKILL");
+        self.signal(pid, Signal::SIGKILL).await?;
+        if let Some(status) = self.wait_exit(pid, KILL_TIMEOUT).await? {
+            return Ok(status);
+        }
+
+        Err(Error::ServiceStopFailed {
+            name: pid.to_string(),
+            reason: "Process didn't respond to SIGKILL".to_string(),
+        })
+    }
+
+    /// Kill a process with SIGKILL, without asking it to stop first.
+    pub async fn kill(&self, pid: u32) -> Result<ExitStatus> {
+        self.signal(pid, Signal::SIGKILL).await?;
+        if let Some(status) = self.wait_exit(pid, KILL_TIMEOUT).await? {
+            return Ok(status);
+        }
+
+        Err(Error::ServiceStopFailed {
+            name: pid.to_string(),
+            reason: "Process didn't respond to SIGKILL".to_string(),
+        })
+    }
 
-                // Wait a bit more for SIGKILL
-                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    /// Wait up to `timeout` for a process to exit, and reap it.
+    ///
+    /// Returns `None` if it is still running.
+    pub async fn wait_exit(&self, pid: u32, timeout: Duration) -> Result<Option<ExitStatus>> {
+        let pidfd = match self.processes.read().await.get(&pid) {
+            Some(ProcessInfo {
+                pidfd: Some(pidfd), ..
+            }) => Some(pidfd.try_clone()?),
+            _ => None,
+        };
 
+        let Some(pidfd) = pidfd else {
+            // No pidfd to poll; check periodically instead
+            let deadline = tokio::time::Instant::now() + timeout;
+            loop {
                 if let Some(status) = self.try_wait(pid).await? {
-                    return Ok(status);
+                    return Ok(Some(status));
                 }
-
-                return Err(Error::ServiceStopFailed {
-                    name: pid.to_string(),
-                    reason: "Process didn't respond to SIGKILL".to_string(),
-                });
+                if tokio::time::Instant::now() >= deadline {
+                    return Ok(None);
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
             }
+        };
 
-            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        // A pidfd polls readable once the process has exited
+        let pidfd = AsyncFd::with_interest(pidfd, Interest::READABLE)?;
+        match tokio::time::timeout(timeout, pidfd.readable()).await {
+            Ok(ready) => {
+                // Exited stays exited
+                ready?.retain_ready();
+                self.try_wait(pid).await
+            }
+            Err(_) => Ok(None),
         }
     }
 
     /// Try to reap a specific process without blocking.
     pub async fn try_wait(&self, pid: u32) -> Result<Option<ExitStatus>> {
-        match waitpid(Pid::from_raw(pid as i32), Some(WaitPidFlag::WNOHANG)) {
-            Ok(WaitStatus::Exited(_, code)) => {
-                self.processes.write().await.remove(&pid);
-                Ok(Some(ExitStatus {
-                    pid,
-                    code: Some(code),
-                    signal: None,
-                }))
-            }
-            Ok(WaitStatus::Signaled(_, sig, _)) => {
-                self.processes.write().await.remove(&pid);
-                Ok(Some(ExitStatus {
-                    pid,
-                    code: None,
-                    signal: Some(sig as i32),
-                }))
-            }
-            Ok(WaitStatus::StillAlive) => Ok(None),
-            Ok(_) => Ok(None),
+        let waited = match self.processes.read().await.get(&pid) {
+            Some(ProcessInfo {
+                pidfd: Some(pidfd), ..
+            }) => pidfd_wait_nohang(pidfd),
+            _ => wait_nohang(pid as i32),
+        };
+        match waited {
+            Ok((WaitStatus::StillAlive, _)) => Ok(None),
+            Ok((status, usage)) => Ok(self.exited(pid, status, usage).await),
             Err(nix::Error::ECHILD) => {
                 // Process doesn't exist
-                self.processes.write().await.remove(&pid);
                 Ok(Some(ExitStatus {
                     pid,
                     code: None,
                     signal: None,
+                    service: self.remove(pid).await,
+                    usage: None,
                 }))
             }
             Err(e) => Err(e.into()),
         }
     }
 
+    /// Record the exit of a reaped process.
+    ///
+    /// Returns `None` for wait statuses other than an exit.
+    async fn exited(
+        &self,
+        pid: u32,
+        status: WaitStatus,
+        usage: Option<ResourceUsage>,
+    ) -> Option<ExitStatus> {
+        let (code, signal) = match status {
+            WaitStatus::Exited(_, code) => (Some(code), None),
+            WaitStatus::Signaled(_, sig, _) => (None, Some(sig as i32)),
+            _ => return None,
+        };
+        Some(ExitStatus {
+            pid,
+            code,
+            signal,
+            service: self.remove(pid).await,
+            usage,
+        })
+    }
+
+    /// Stop tracking a process that exited, releasing its terminal.
+    ///
+    /// Returns the service the process belonged to.
+    async fn remove(&self, pid: u32) -> Option<String> {
+        let info = self.processes.write().await.remove(&pid)?;
+        if let Some(tty) = &info.tty {
+            release_tty(tty);
+        }
+        Some(info.service_name)
+    }
+
     /// Reap any zombie processes (for PID 1 duty).
+    ///
+    /// Tracked processes are reaped through their pidfd. Other children
+    /// (orphans adopted as init or subreaper) are left while a spawn is in
+    /// flight, since one of them may be the new, not yet tracked, child;
+    /// [`reap_requested`](Self::reap_requested) fires once they can be
+    /// reaped.
     pub async fn reap_zombies(&self) -> Vec<ExitStatus> {
         let mut statuses = Vec::new();
 
         loop {
-            match waitpid(Pid::from_raw(-1), Some(WaitPidFlag::WNOHANG)) {
-                Ok(WaitStatus::Exited(pid, code)) => {
-                    let pid = pid.as_raw() as u32;
-                    self.processes.write().await.remove(&pid);
-                    debug!(pid = pid, code = code, "Reaped zombie process");
-                    statuses.push(ExitStatus {
-                        pid,
-                        code: Some(code),
-                        signal: None,
-                    });
-                }
-                Ok(WaitStatus::Signaled(pid, sig, _)) => {
-                    let pid = pid.as_raw() as u32;
-                    self.processes.write().await.remove(&pid);
-                    debug!(pid = pid, signal = ?sig, "Reaped signaled process");
-                    statuses.push(ExitStatus {
-                        pid,
-                        code: None,
-                        signal: Some(sig as i32),
-                    });
-                }
-                Ok(WaitStatus::StillAlive) | Err(nix::Error::ECHILD) => {
+            let pid = match peek_exited() {
+                Ok(Some(pid)) => pid,
+                Ok(None) | Err(nix::Error::ECHILD) => {
                     // No more zombies to reap
                     break;
                 }
-                Ok(_) => continue,
+                Err(e) => {
+                    error!(error = %e, "Error reaping zombies");
+                    break;
+                }
+            };
+
+            if self.processes.read().await.contains_key(&pid) {
+                match self.try_wait(pid).await {
+                    Ok(Some(status)) => {
+                        debug!(pid = pid, code = ?status.code, signal = ?status.signal, "Reaped process");
+                        statuses.push(status);
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        error!(error = %e, "Error reaping zombies");
+                        break;
+                    }
+                }
+                continue;
+            }
+
+            let Ok(_gate) = self.spawn_gate.try_write() else {
+                self.reap_pending.store(true, Ordering::Release);
+                break;
+            };
+            // Still a zombie, so its cgroup is still known
+            let origin = orphans::origin(pid);
+            match wait_nohang(pid as i32) {
+                Ok((WaitStatus::StillAlive, _)) | Err(nix::Error::ECHILD) => continue,
+                Ok((status, usage)) => {
+                    debug!(pid = pid, status = ?status, service = ?origin, "Reaped orphaned process");
+                    self.record_orphan(origin);
+                    if let Some(status) = self.exited(pid, status, usage).await {
+                        statuses.push(status);
+                    }
+                }
                 Err(e) => {
                     error!(error = %e, "Error reaping zombies");
                     break;
@@ -314,6 +650,31 @@ impl ProcessSupervisor {
         statuses
     }
 
+    /// Count a reaped orphan, warning when its service leaves a storm of
+    /// them.
+    fn record_orphan(&self, service: Option<String>) {
+        let storm = self
+            .orphans
+            .lock()
+            .unwrap()
+            .record(service.clone(), std::time::Instant::now());
+        if let Some(count) = storm {
+            warn!(
+                service = service.as_deref().unwrap_or("unknown"),
+                count = count,
+                window_secs = STORM_WINDOW.as_secs(),
+                "Orphan storm: a daemon is double-forking processes no unit tracks"
+            );
+        }
+    }
+
+    /// Orphans adopted and reaped, by the service they came from.
+    pub async fn orphan_counts(&self) -> Vec<OrphanCount> {
+        let spawned: HashSet<u32> = self.processes.read().await.keys().copied().collect();
+        let running = orphans::running_orphans(&spawned);
+        self.orphans.lock().unwrap().counts(&running)
+    }
+
     /// Get the service name for a PID.
     pub async fn get_service_name(&self, pid: u32) -> Option<String> {
         self.processes
@@ -326,8 +687,12 @@ impl ProcessSupervisor {
     /// Check if a process is running.
     pub async fn is_running(&self, pid: u32) -> bool {
         // Check if we're tracking it
-        if !self.processes.read().await.contains_key(&pid) {
-            return false;
+        match self.processes.read().await.get(&pid) {
+            None => return false,
+            Some(ProcessInfo {
+                pidfd: Some(pidfd), ..
+            }) => return !pidfd_exited(pidfd),
+            Some(_) => {}
         }
 
         // Check if the process actually exists
@@ -335,7 +700,7 @@ impl ProcessSupervisor {
             Ok(_) => true,
             Err(_) => {
                 // Process doesn't exist, remove it from tracking
-                self.processes.write().await.remove(&pid);
+                self.remove(pid).await;
                 false
             }
         }
@@ -353,6 +718,283 @@ impl Default for ProcessSupervisor {
     }
 }
 
+/// Open a service's terminal, hanging it up and resetting it first if
+/// configured.
+fn open_tty(config: &TtyConfig) -> Result<File> {
+    let open = || {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY | libc::O_CLOEXEC)
+            .open(&config.path)
+            .map_err(|e| Error::ProcessSpawnFailed(format!("{}: {}", config.path.display(), e)))
+    };
+
+    let mut tty = open()?;
+    if config.vhangup {
+        // The hangup also hits this descriptor, so reopen afterwards
+        vhangup_tty(&tty);
+        tty = open()?;
+    }
+    if config.reset {
+        reset_tty(&tty);
+    }
+    Ok(tty)
+}
+
+/// `waitpid(pid, WNOHANG)` that also returns the resources the reaped
+/// child used.
+fn wait_nohang(pid: i32) -> nix::Result<(WaitStatus, Option<ResourceUsage>)> {
+    let mut status = 0;
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::wait4(pid, &mut status, libc::WNOHANG, &mut rusage) };
+    match ret {
+        -1 => Err(Errno::last()),
+        0 => Ok((WaitStatus::StillAlive, None)),
+        pid => Ok((
+            WaitStatus::from_raw(Pid::from_raw(pid), status)?,
+            Some(ResourceUsage::from_rusage(&rusage)),
+        )),
+    }
+}
+
+/// `waitid(P_PIDFD, WEXITED | WNOHANG)`, returning the resources the
+/// reaped child used like [`wait_nohang`].
+fn pidfd_wait_nohang(pidfd: &OwnedFd) -> nix::Result<(WaitStatus, Option<ResourceUsage>)> {
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+    // The raw system call, unlike libc's waitid, also fills in rusage
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_waitid,
+            libc::P_PIDFD,
+            pidfd.as_raw_fd(),
+            &mut info,
+            libc::WEXITED | libc::WNOHANG,
+            &mut rusage,
+        )
+    };
+    if ret < 0 {
+        return Err(Errno::last());
+    }
+    let (pid, status) = unsafe { (info.si_pid(), info.si_status()) };
+    if pid == 0 {
+        return Ok((WaitStatus::StillAlive, None));
+    }
+    let pid = Pid::from_raw(pid);
+    let status = match info.si_code {
+        libc::CLD_EXITED => WaitStatus::Exited(pid, status),
+        code => WaitStatus::Signaled(pid, Signal::try_from(status)?, code == libc::CLD_DUMPED),
+    };
+    Ok((status, Some(ResourceUsage::from_rusage(&rusage))))
+}
+
+/// PID of a child that has exited, left unreaped.
+fn peek_exited() -> nix::Result<Option<u32>> {
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let ret = unsafe {
+        libc::waitid(
+            libc::P_ALL,
+            0,
+            &mut info,
+            libc::WEXITED | libc::WNOHANG | libc::WNOWAIT,
+        )
+    };
+    if ret < 0 {
+        return Err(Errno::last());
+    }
+    let pid = unsafe { info.si_pid() };
+    Ok((pid != 0).then_some(pid as u32))
+}
+
+/// Open a pidfd for a child that has not been reaped.
+///
+/// Returns `None` on kernels without pidfds.
+fn pidfd_open(pid: u32) -> Result<Option<OwnedFd>> {
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+    if fd < 0 {
+        let err = std::io::Error::last_os_error();
+        if err.raw_os_error() == Some(libc::ENOSYS) {
+            return Ok(None);
+        }
+        return Err(err.into());
+    }
+    Ok(Some(unsafe { OwnedFd::from_raw_fd(fd as RawFd) }))
+}
+
+/// Send a signal through a pidfd; fails with `ESRCH` once the process
+/// has exited, even if its PID was reused.
+fn pidfd_send_signal(pidfd: &OwnedFd, sig: Signal) -> nix::Result<()> {
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_pidfd_send_signal,
+            pidfd.as_raw_fd(),
+            sig as libc::c_int,
+            std::ptr::null::<libc::siginfo_t>(),
+            0,
+        )
+    };
+    if ret < 0 {
+        return Err(Errno::last());
+    }
+    Ok(())
+}
+
+/// Whether the process behind a pidfd has exited.
+fn pidfd_exited(pidfd: &OwnedFd) -> bool {
+    let mut fds = [libc::pollfd {
+        fd: pidfd.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    }];
+    unsafe { libc::poll(fds.as_mut_ptr(), 1, 0) > 0 }
+}
+
+/// Spawn with every signal blocked in this thread, so the child starts
+/// with them blocked until [`reset_signals`] has run.
+fn spawn_blocked(cmd: &mut Command) -> std::io::Result<Child> {
+    let old = SigSet::all()
+        .thread_swap_mask(SigmaskHow::SIG_SETMASK)
+        .map_err(std::io::Error::other)?;
+    let child = cmd.spawn();
+    if let Err(e) = old.thread_set_mask() {
+        error!(error = %e, "Failed to restore signal mask");
+    }
+    child
+}
+
+/// Restore default handling of every signal, then unblock them all.
+///
+/// Handlers and ignored signals are inherited across fork, and ignored
+/// ones across exec too. Runs in the child between fork and exec.
+fn reset_signals() -> std::io::Result<()> {
+    let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+    action.sa_sigaction = libc::SIG_DFL;
+    // The kernel's struct sigaction, all zero: SIG_DFL with no flags
+    let kernel_default = [0u64; 4];
+    for sig in 1..=libc::SIGRTMAX() {
+        if sig == libc::SIGKILL || sig == libc::SIGSTOP {
+            continue;
+        }
+        if unsafe { libc::sigaction(sig, &action, std::ptr::null_mut()) } != 0 {
+            // libc refuses the signals it reserves for threads, which may
+            // still have been inherited as ignored; the child has one thread
+            unsafe {
+                libc::syscall(
+                    libc::SYS_rt_sigaction,
+                    sig,
+                    kernel_default.as_ptr(),
+                    std::ptr::null_mut::<u64>(),
+                    // Size of the kernel's signal set
+                    8,
+                )
+            };
+        }
+    }
+    SigSet::empty()
+        .thread_set_mask()
+        .map_err(std::io::Error::other)
+}
+
+/// Hang up and reset a terminal after its service exits, so nothing left
+/// over from the session keeps using it.
+fn release_tty(config: &TtyConfig) {
+    if !config.vhangup && !config.reset {
+        return;
+    }
+    match OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY | libc::O_CLOEXEC | libc::O_NONBLOCK)
+        .open(&config.path)
+    {
+        Ok(tty) => {
+            if config.vhangup {
+                vhangup_tty(&tty);
+            }
+            if config.reset {
+                reset_tty(&tty);
+            }
+        }
+        Err(e) => warn!(tty = ?config.path, error = %e, "Failed to release terminal"),
+    }
+}
+
+/// Hang up every process using a terminal.
+fn vhangup_tty(tty: &File) {
+    if unsafe { libc::ioctl(tty.as_raw_fd(), libc::TIOCVHANGUP) } < 0 {
+        warn!(error = %std::io::Error::last_os_error(), "Failed to hang up terminal");
+    }
+}
+
+/// Reset a terminal to sane settings and clear it.
+fn reset_tty(tty: &File) {
+    let fd = tty.as_raw_fd();
+    unsafe {
+        let mut t: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &mut t) == 0 {
+            t.c_iflag &= !(libc::IGNBRK | libc::BRKINT | libc::ISTRIP | libc::INLCR | libc::IGNCR);
+            t.c_iflag |= libc::ICRNL | libc::IMAXBEL | libc::IUTF8;
+            t.c_oflag |= libc::ONLCR | libc::OPOST;
+            t.c_cflag |= libc::CREAD;
+            t.c_lflag = libc::ISIG
+                | libc::ICANON
+                | libc::IEXTEN
+                | libc::ECHO
+                | libc::ECHOE
+                | libc::ECHOK
+                | libc::ECHOCTL
+                | libc::ECHOKE;
+            for (index, value) in [
+                (libc::VINTR, 0o003),
+                (libc::VQUIT, 0o034),
+                (libc::VERASE, 0o177),
+                (libc::VKILL, 0o025),
+                (libc::VEOF, 0o004),
+                (libc::VSTART, 0o021),
+                (libc::VSTOP, 0o023),
+                (libc::VSUSP, 0o032),
+                (libc::VLNEXT, 0o026),
+                (libc::VWERASE, 0o027),
+                (libc::VREPRINT, 0o022),
+                (libc::VEOL, 0),
+                (libc::VEOL2, 0),
+                (libc::VTIME, 0),
+                (libc::VMIN, 1),
+            ] {
+                t.c_cc[index] = value;
+            }
+            libc::tcsetattr(fd, libc::TCSANOW, &t);
+        }
+        libc::tcflush(fd, libc::TCIOFLUSH);
+    }
+    // Full reset (RIS)
+    let _ = (&*tty).write_all(b"\x1bc");
+}
+
+/// Make standard input the controlling terminal of the (new) session.
+///
+/// `tty-force` steals it from another session, `tty-fail` fails if it is
+/// taken, and `tty` waits up to `wait` for it to be released. Runs in the
+/// child between fork and exec.
+fn acquire_controlling_tty(mode: &str, wait: std::time::Duration) -> std::io::Result<()> {
+    let force = libc::c_int::from(mode == "tty-force");
+    let deadline = std::time::Instant::now() + wait;
+    loop {
+        if unsafe { libc::ioctl(0, libc::TIOCSCTTY, force) } == 0 {
+            return Ok(());
+        }
+        let err = std::io::Error::last_os_error();
+        if mode != "tty" || err.raw_os_error() != Some(libc::EPERM) {
+            return Err(err);
+        }
+        if std::time::Instant::now() >= deadline {
+            return Err(err);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+}
+
 /// Create a pipe pair.
 fn create_pipe() -> Result<(std::fs::File, std::fs::File)> {
     let mut fds = [0i32; 2];
@@ -432,3 +1074,153 @@ fn set_resource_limits(limits: &ResourceLimits) -> std::result::Result<(), Strin
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn service(name: &str, exec: &str) -> ServiceDefinition {
+        let mut def = ServiceDefinition::new(name, exec);
+        def.standard_output = "inherit".to_string();
+        def.standard_error = "inherit".to_string();
+        def
+    }
+
+    fn journal() -> Arc<Journal> {
+        Arc::new(Journal::new(
+            std::env::temp_dir().join(format!("boss-process-{}", std::process::id())),
+        ))
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_stress_short_lived_children() {
+        const CHILDREN: usize = 2000;
+        let supervisor = Arc::new(ProcessSupervisor::new());
+        let journal = journal();
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for i in 0..CHILDREN {
+            let supervisor = Arc::clone(&supervisor);
+            let journal = Arc::clone(&journal);
+            tasks.spawn(async move {
+                let def = service(&format!("true-{}", i), "/bin/true");
+                let pid = supervisor.spawn(&def, journal).await.unwrap();
+                let status = supervisor
+                    .wait_exit(pid, Duration::from_secs(30))
+                    .await
+                    .unwrap()
+                    .expect("child did not exit");
+                (def.name, status)
+            });
+        }
+
+        let mut reaped = 0;
+        while let Some(result) = tasks.join_next().await {
+            let (name, status) = result.unwrap();
+            assert!(status.success(), "{} exited with {:?}", name, status);
+            assert_eq!(status.service.as_deref(), Some(name.as_str()));
+            reaped += 1;
+        }
+        assert_eq!(reaped, CHILDREN);
+        assert!(supervisor.get_pids().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_signals_never_reach_reaped_process() {
+        let supervisor = ProcessSupervisor::new();
+        let pid = supervisor
+            .spawn(&service("sleeper", "/bin/sleep 30"), journal())
+            .await
+            .unwrap();
+        let pidfd = supervisor.processes.read().await[&pid]
+            .pidfd
+            .as_ref()
+            .map(|fd| fd.try_clone().unwrap());
+        assert!(supervisor.is_running(pid).await);
+
+        let status = supervisor.stop(pid, Duration::from_secs(5)).await.unwrap();
+        assert_eq!(status.signal, Some(libc::SIGTERM));
+        assert!(!supervisor.is_running(pid).await);
+        assert!(matches!(
+            supervisor.signal(pid, Signal::SIGKILL).await,
+            Err(Error::ProcessNotFound(_))
+        ));
+        // Even if the PID is reused, the old pidfd only ever names the
+        // process that exited
+        if let Some(pidfd) = pidfd {
+            assert_eq!(
+                pidfd_send_signal(&pidfd, Signal::SIGKILL),
+                Err(Errno::ESRCH)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_children_start_with_default_signals() {
+        let dir = std::env::temp_dir().join(format!("boss-signals-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("check-signals");
+        std::fs::write(
+            &script,
+            "#!/bin/sh\n\
+             grep -q '^SigBlk:[[:space:]]*0*$' /proc/self/status &&\n\
+             grep -q '^SigIgn:[[:space:]]*0*$' /proc/self/status\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        // Ignored in init, as SIGPIPE is by the Rust runtime
+        unsafe { libc::signal(libc::SIGUSR2, libc::SIG_IGN) };
+        let supervisor = ProcessSupervisor::new();
+        let pid = supervisor
+            .spawn(&service("signals", script.to_str().unwrap()), journal())
+            .await
+            .unwrap();
+        let status = supervisor
+            .wait_exit(pid, Duration::from_secs(10))
+            .await
+            .unwrap()
+            .unwrap();
+        unsafe { libc::signal(libc::SIGUSR2, libc::SIG_DFL) };
+        assert!(
+            status.success(),
+            "child inherited signal state: {:?}",
+            status
+        );
+    }
+
+    #[tokio::test]
+    async fn test_orphans_wait_while_spawning() {
+        let supervisor = ProcessSupervisor::new();
+        let mut child = Command::new("/bin/true").spawn().unwrap();
+        let pid = child.id();
+        while !is_zombie(pid) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // Stands in for a spawn whose child is not tracked yet
+        let gate = supervisor.spawn_gate.read().await;
+        let statuses = supervisor.reap_zombies().await;
+        assert!(statuses.iter().all(|s| s.pid != pid));
+        assert!(supervisor.reap_pending.load(Ordering::Acquire));
+        drop(gate);
+
+        // Still ours to reap
+        assert!(child.wait().unwrap().success());
+    }
+
+    /// Whether `pid` is a child that exited, without reaping it
+    fn is_zombie(pid: u32) -> bool {
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let ret = unsafe {
+            libc::waitid(
+                libc::P_PID,
+                pid,
+                &mut info,
+                libc::WEXITED | libc::WNOHANG | libc::WNOWAIT,
+            )
+        };
+        ret == 0 && unsafe { info.si_pid() } != 0
+    }
+}
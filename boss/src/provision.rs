@@ -0,0 +1,559 @@
+//! First-boot provisioning.
+//!
+//! An image ships with the flag file `/etc/buckos/first-boot`. While it
+//! exists, init runs the provisioning tasks after loading units and before
+//! starting the enabled services: committing the machine ID, running the
+//! units that create SSH host keys, applying the buckos manifest of the
+//! machine and growing the root filesystem to fill its partition.
+//!
+//! Tasks run in order, and a task only runs once every task before it
+//! succeeded. The status of each task is kept in
+//! `/var/lib/buckos/provision.json`, so a task that failed is retried on the
+//! next boot while those that succeeded are not run again. The flag file is
+//! removed once every task succeeded or had nothing to do. With a volatile
+//! `/etc` the flag comes back on every boot, but the tasks already done are
+//! still skipped.
+
+use crate::error::{Error, Result};
+use crate::journal::{JournalEntry, Priority};
+use crate::manager::ServiceManager;
+use crate::volatile::{self, read_only};
+use buckos_core::machine_id::MACHINE_ID_PATH;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Flag file marking a system that has not finished provisioning.
+pub const FIRST_BOOT_FLAG: &str = "/etc/buckos/first-boot";
+
+/// Status of the provisioning tasks, on the persistent /var.
+pub const PROVISION_STATE_FILE: &str = "/var/lib/buckos/provision.json";
+
+/// Manifest the machine is converged to on first boot, as written by
+/// `buckos export --manifest`.
+pub const MANIFEST_FILE: &str = "/etc/buckos/manifest.toml";
+
+/// Unit creating the SSH host keys.
+pub const SSH_KEYGEN_UNIT: &str = "sshd-keygen";
+
+/// Journal identifier of provisioning entries.
+pub const PROVISION_LOG: &str = "provision";
+
+/// How long one task may run before it counts as failed.
+pub const TASK_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// What a provisioning task does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProvisionAction {
+    /// Commit a machine ID mounted over this file from /run to the file
+    /// itself, or write a new one if the machine has none
+    MachineId(PathBuf),
+    /// Run a one-shot unit to completion; skipped if there is no such unit
+    Unit(String),
+    /// Converge the installed packages to this manifest with
+    /// `buckos apply`; skipped if the file does not exist
+    ApplyManifest(PathBuf),
+    /// Grow the filesystem mounted here to the size of its device
+    ResizeFilesystem(PathBuf),
+}
+
+/// One step of provisioning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProvisionTask {
+    /// Name the status of the task is kept under
+    pub name: String,
+    pub action: ProvisionAction,
+}
+
+impl ProvisionTask {
+    pub fn new(name: impl Into<String>, action: ProvisionAction) -> Self {
+        Self {
+            name: name.into(),
+            action,
+        }
+    }
+}
+
+/// The provisioning tasks of a buckos system, in the order they run.
+pub fn default_tasks() -> Vec<ProvisionTask> {
+    vec![
+        ProvisionTask::new(
+            "machine-id",
+            ProvisionAction::MachineId(PathBuf::from(MACHINE_ID_PATH)),
+        ),
+        ProvisionTask::new(
+            "ssh-host-keys",
+            ProvisionAction::Unit(SSH_KEYGEN_UNIT.to_string()),
+        ),
+        ProvisionTask::new(
+            "apply-manifest",
+            ProvisionAction::ApplyManifest(PathBuf::from(MANIFEST_FILE)),
+        ),
+        ProvisionTask::new(
+            "resize-root",
+            ProvisionAction::ResizeFilesystem(PathBuf::from("/")),
+        ),
+    ]
+}
+
+/// How a task went the last time it ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskStatus {
+    /// The task succeeded
+    Done,
+    /// There was nothing to do
+    Skipped,
+    /// The task failed and is retried on the next boot
+    Failed,
+}
+
+impl std::fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskStatus::Done => write!(f, "done"),
+            TaskStatus::Skipped => write!(f, "skipped"),
+            TaskStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+/// Recorded status of one task.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskRecord {
+    pub status: TaskStatus,
+    /// Boots the task ran on
+    pub attempts: u32,
+    /// Why the task failed or was skipped
+    #[serde(default)]
+    pub message: Option<String>,
+    /// When the task last ran
+    pub finished: DateTime<Utc>,
+}
+
+/// Status of every task that ran, kept across boots.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvisionState {
+    pub tasks: BTreeMap<String, TaskRecord>,
+    /// When the last task succeeded, once provisioning is complete
+    #[serde(default)]
+    pub completed: Option<DateTime<Utc>>,
+}
+
+impl ProvisionState {
+    /// Load the state file, empty if there is none.
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    /// Write the state file, replacing it atomically.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    /// Whether a task succeeded or had nothing to do on an earlier boot.
+    pub fn is_finished(&self, task: &str) -> bool {
+        self.tasks
+            .get(task)
+            .is_some_and(|t| t.status != TaskStatus::Failed)
+    }
+}
+
+/// Runs the provisioning tasks on first boot.
+#[derive(Debug, Clone)]
+pub struct Provisioner {
+    flag: PathBuf,
+    state_file: PathBuf,
+    tasks: Vec<ProvisionTask>,
+    timeout: Duration,
+}
+
+impl Default for Provisioner {
+    fn default() -> Self {
+        Self::new(FIRST_BOOT_FLAG, PROVISION_STATE_FILE).with_tasks(default_tasks())
+    }
+}
+
+/// Result of running a task: `Ok(None)` when it did its work,
+/// `Ok(Some(reason))` when there was nothing to do.
+type TaskOutcome = Result<Option<String>>;
+
+impl Provisioner {
+    /// Provisioning flagged by `flag`, keeping its state in `state_file`,
+    /// without any tasks.
+    pub fn new(flag: impl Into<PathBuf>, state_file: impl Into<PathBuf>) -> Self {
+        Self {
+            flag: flag.into(),
+            state_file: state_file.into(),
+            tasks: Vec::new(),
+            timeout: TASK_TIMEOUT,
+        }
+    }
+
+    /// Run these tasks, in order.
+    pub fn with_tasks(mut self, tasks: Vec<ProvisionTask>) -> Self {
+        self.tasks = tasks;
+        self
+    }
+
+    /// Longest a single task may run.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn flag(&self) -> &Path {
+        &self.flag
+    }
+
+    pub fn tasks(&self) -> &[ProvisionTask] {
+        &self.tasks
+    }
+
+    pub fn state_file(&self) -> &Path {
+        &self.state_file
+    }
+
+    /// Whether provisioning has yet to complete.
+    pub fn is_first_boot(&self) -> bool {
+        self.flag.exists()
+    }
+
+    /// Status of the tasks so far.
+    pub fn state(&self) -> ProvisionState {
+        ProvisionState::load(&self.state_file)
+    }
+
+    /// Forget the status of `tasks`, or of every task when empty, and flag
+    /// the system so they run again on the next boot.
+    pub fn reset(&self, tasks: &[String]) -> Result<()> {
+        let mut state = self.state();
+        if tasks.is_empty() {
+            state.tasks.clear();
+        } else {
+            state.tasks.retain(|name, _| !tasks.contains(name));
+        }
+        state.completed = None;
+        state.save(&self.state_file)?;
+        if let Some(dir) = self.flag.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.flag, "")?;
+        Ok(())
+    }
+
+    /// Run the tasks not finished on an earlier boot, stopping at the first
+    /// that fails. Returns `None` if this is not a first boot.
+    ///
+    /// The state is saved after every task, so a boot that dies halfway
+    /// through does not run the finished tasks again.
+    pub async fn run(&self, manager: &ServiceManager) -> Result<Option<ProvisionState>> {
+        if !self.is_first_boot() {
+            return Ok(None);
+        }
+        info!("First boot, provisioning the system");
+        let journal = manager.journal();
+        let mut state = self.state();
+        let mut complete = true;
+        for task in &self.tasks {
+            if state.is_finished(&task.name) {
+                continue;
+            }
+            let outcome = self.run_task(task, manager).await;
+            let attempts = state.tasks.get(&task.name).map_or(0, |t| t.attempts) + 1;
+            let (status, message) = match outcome {
+                Ok(None) => (TaskStatus::Done, None),
+                Ok(Some(reason)) => (TaskStatus::Skipped, Some(reason)),
+                Err(e) => (TaskStatus::Failed, Some(e.to_string())),
+            };
+            let (text, priority) = match (&status, &message) {
+                (TaskStatus::Failed, Some(e)) => {
+                    warn!(task = %task.name, error = %e, "Provisioning task failed");
+                    (format!("{} failed: {}", task.name, e), Priority::Error)
+                }
+                (TaskStatus::Skipped, Some(reason)) => {
+                    info!(task = %task.name, reason = %reason, "Provisioning task skipped");
+                    (format!("{} skipped: {}", task.name, reason), Priority::Info)
+                }
+                _ => {
+                    info!(task = %task.name, "Provisioning task done");
+                    (format!("{} done", task.name), Priority::Info)
+                }
+            };
+            journal
+                .log(
+                    JournalEntry::new(PROVISION_LOG, &text, "provision")
+                        .with_priority(priority)
+                        .with_field("TASK", &task.name)
+                        .with_field("STATUS", status.to_string()),
+                )
+                .await;
+            state.tasks.insert(
+                task.name.clone(),
+                TaskRecord {
+                    status,
+                    attempts,
+                    message,
+                    finished: Utc::now(),
+                },
+            );
+            state.save(&self.state_file)?;
+            if status == TaskStatus::Failed {
+                complete = false;
+                break;
+            }
+        }
+
+        if complete {
+            state.completed = Some(Utc::now());
+            state.save(&self.state_file)?;
+            match std::fs::remove_file(&self.flag) {
+                Ok(()) => info!("Provisioning complete"),
+                Err(e) => {
+                    warn!(error = %e, "Provisioning complete, but failed to remove the first-boot flag")
+                }
+            }
+        } else {
+            warn!("Provisioning incomplete, retrying on the next boot");
+        }
+        Ok(Some(state))
+    }
+
+    async fn run_task(&self, task: &ProvisionTask, manager: &ServiceManager) -> TaskOutcome {
+        match &task.action {
+            ProvisionAction::MachineId(path) => commit_machine_id(path),
+            ProvisionAction::Unit(unit) => {
+                if !manager.list_services().await.contains(unit) {
+                    return Ok(Some(format!("no unit {}", unit)));
+                }
+                manager.run_to_completion(unit, self.timeout).await?;
+                Ok(None)
+            }
+            ProvisionAction::ApplyManifest(manifest) => {
+                if !manifest.exists() {
+                    return Ok(Some(format!("no manifest at {}", manifest.display())));
+                }
+                let mut command = tokio::process::Command::new("buckos");
+                command.arg("apply").arg(manifest);
+                self.run_command(command, manager).await?;
+                Ok(None)
+            }
+            ProvisionAction::ResizeFilesystem(mount_point) => {
+                let mounts = std::fs::read_to_string("/proc/self/mounts")?;
+                let Some((device, fstype)) = find_mount(&mounts, mount_point) else {
+                    return Ok(Some(format!(
+                        "nothing mounted at {}",
+                        mount_point.display()
+                    )));
+                };
+                if !Path::new(&device).exists() {
+                    return Ok(Some(format!("no device {}", device)));
+                }
+                let Some(args) = resize_command(&device, &fstype, mount_point) else {
+                    return Ok(Some(format!("cannot grow {} filesystems", fstype)));
+                };
+                let mut command = tokio::process::Command::new(&args[0]);
+                command.args(&args[1..]);
+                self.run_command(command, manager).await?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Run a command, recording its output in the journal.
+    async fn run_command(
+        &self,
+        mut command: tokio::process::Command,
+        manager: &ServiceManager,
+    ) -> Result<()> {
+        let child = command
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let output = tokio::time::timeout(self.timeout, child.wait_with_output())
+            .await
+            .map_err(|_| Error::Other(format!("timed out after {:?}", self.timeout)))??;
+
+        let journal = manager.journal();
+        for (stream, bytes) in [("stdout", &output.stdout), ("stderr", &output.stderr)] {
+            for line in String::from_utf8_lossy(bytes).lines() {
+                journal
+                    .log(JournalEntry::new(PROVISION_LOG, line, stream))
+                    .await;
+            }
+        }
+        if output.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(Error::Other(match stderr.trim().lines().last() {
+            None => format!("exited with {}", output.status),
+            Some(message) => format!("exited with {}: {}", output.status, message),
+        }))
+    }
+}
+
+/// Write the transient machine ID to `path`, or a new one if there is none.
+fn commit_machine_id(path: &Path) -> TaskOutcome {
+    if read_only(path.parent().unwrap_or(Path::new("/"))) {
+        return Ok(Some(
+            "read-only, the machine ID stays transient".to_string(),
+        ));
+    }
+    Ok(match volatile::commit_machine_id(path)? {
+        true => None,
+        false => Some("machine ID already set".to_string()),
+    })
+}
+
+/// Device and filesystem type mounted at `mount_point`, from the contents
+/// of /proc/self/mounts. The last mount wins, as it hides the earlier ones.
+fn find_mount(mounts: &str, mount_point: &Path) -> Option<(String, String)> {
+    mounts.lines().rev().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let (device, target, fstype) = (fields.next()?, fields.next()?, fields.next()?);
+        (Path::new(target) == mount_point).then(|| (device.to_string(), fstype.to_string()))
+    })
+}
+
+/// Command growing a mounted filesystem to the size of its device, if the
+/// filesystem can be grown while mounted.
+fn resize_command(device: &str, fstype: &str, mount_point: &Path) -> Option<Vec<String>> {
+    let mount_point = mount_point.display().to_string();
+    let args: &[&str] = match fstype {
+        "ext2" | "ext3" | "ext4" => &["resize2fs", device],
+        "xfs" => &["xfs_growfs", &mount_point],
+        "btrfs" => &["btrfs", "filesystem", "resize", "max", &mount_point],
+        _ => return None,
+    };
+    Some(args.iter().map(|a| a.to_string()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_resize_command() {
+        let mounts = "\
+/dev/vda2 / ext4 rw,relatime 0 0
+proc /proc proc rw 0 0
+/dev/vdb1 /srv xfs rw 0 0
+overlay / overlay rw 0 0
+";
+        assert_eq!(
+            find_mount(mounts, Path::new("/srv")),
+            Some(("/dev/vdb1".to_string(), "xfs".to_string()))
+        );
+        // The overlay hides the ext4 root
+        assert_eq!(
+            find_mount(mounts, Path::new("/")),
+            Some(("overlay".to_string(), "overlay".to_string()))
+        );
+        assert_eq!(find_mount(mounts, Path::new("/home")), None);
+
+        assert_eq!(
+            resize_command("/dev/vda2", "ext4", Path::new("/")),
+            Some(vec!["resize2fs".to_string(), "/dev/vda2".to_string()])
+        );
+        assert_eq!(
+            resize_command("/dev/vdb1", "xfs", Path::new("/srv")),
+            Some(vec!["xfs_growfs".to_string(), "/srv".to_string()])
+        );
+        assert_eq!(resize_command("overlay", "overlay", Path::new("/")), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_failed_task_retried_on_next_boot() {
+        let dir = std::env::temp_dir().join(format!("boss-provision-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let services = dir.join("services");
+        std::fs::create_dir_all(&services).unwrap();
+        // Fails the first time it runs, succeeds the next
+        let (script, marker) = (dir.join("keygen"), dir.join("keygen-ran"));
+        std::fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\ntest -e {0} && exit 0\ntouch {0}\nexit 1\n",
+                marker.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::write(
+            services.join("keygen.service"),
+            format!("[Service]\nType=oneshot\nExecStart={}\n", script.display()),
+        )
+        .unwrap();
+        let (flag, state_file) = (dir.join("first-boot"), dir.join("provision.json"));
+        std::fs::write(&flag, "").unwrap();
+        let machine_id = dir.join("machine-id");
+        let provisioner = Provisioner::new(&flag, &state_file).with_tasks(vec![
+            ProvisionTask::new("machine-id", ProvisionAction::MachineId(machine_id.clone())),
+            ProvisionTask::new("no-unit", ProvisionAction::Unit("missing".to_string())),
+            ProvisionTask::new("keygen", ProvisionAction::Unit("keygen".to_string())),
+            ProvisionTask::new(
+                "apply-manifest",
+                ProvisionAction::ApplyManifest(dir.join("manifest.toml")),
+            ),
+        ]);
+
+        let manager = ServiceManager::new(services);
+        manager.load_services().await.unwrap();
+        let state = provisioner.run(&manager).await.unwrap().unwrap();
+        let status = |state: &ProvisionState, task: &str| {
+            state.tasks.get(task).map(|t| (t.status, t.attempts))
+        };
+        assert_eq!(status(&state, "machine-id"), Some((TaskStatus::Done, 1)));
+        assert_eq!(status(&state, "no-unit"), Some((TaskStatus::Skipped, 1)));
+        assert_eq!(status(&state, "keygen"), Some((TaskStatus::Failed, 1)));
+        // Tasks after a failed one wait for it
+        assert_eq!(status(&state, "apply-manifest"), None);
+        assert_eq!(state.completed, None);
+        assert!(flag.exists());
+        assert_eq!(provisioner.state(), state);
+        let id = std::fs::read_to_string(&machine_id).unwrap();
+        assert_eq!(id.trim().len(), 32);
+
+        // The next boot retries the failed task only
+        let manager = ServiceManager::new(dir.join("services"));
+        manager.load_services().await.unwrap();
+        let state = provisioner.run(&manager).await.unwrap().unwrap();
+        assert_eq!(status(&state, "machine-id"), Some((TaskStatus::Done, 1)));
+        assert_eq!(status(&state, "keygen"), Some((TaskStatus::Done, 2)));
+        assert_eq!(
+            status(&state, "apply-manifest"),
+            Some((TaskStatus::Skipped, 1))
+        );
+        assert!(state.completed.is_some());
+        assert!(!flag.exists());
+        assert_eq!(std::fs::read_to_string(&machine_id).unwrap(), id);
+        let logs = manager.get_logs(PROVISION_LOG, None).await;
+        assert!(logs.iter().any(|e| e.message == "keygen done"));
+
+        // Provisioned systems boot without running any task
+        assert_eq!(provisioner.run(&manager).await.unwrap(), None);
+
+        provisioner.reset(&["keygen".to_string()]).unwrap();
+        assert!(provisioner.is_first_boot());
+        let state = provisioner.state();
+        assert_eq!(status(&state, "keygen"), None);
+        assert_eq!(status(&state, "machine-id"), Some((TaskStatus::Done, 1)));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
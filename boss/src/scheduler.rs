@@ -0,0 +1,330 @@
+//! Boot startup scheduling.
+//!
+//! Enabled services are started as soon as their dependencies have
+//! started, subject to optional limits on how many starts run at once
+//! overall and per dependency depth. When more services are ready than the
+//! limits allow, the ones heading the longest remaining chain of startup
+//! time (measured on earlier boots) go first, so the boot critical path is
+//! never left waiting behind services nothing depends on.
+
+use crate::error::Result;
+use crate::manager::BootTiming;
+use crate::service::ServiceDefinition;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// Weight of the newest boot in the running average of start times
+const HISTORY_WEIGHT: u64 = 4;
+
+/// Limits on concurrent service starts; `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StartupLimits {
+    /// Starts running at once
+    pub max_parallel: Option<usize>,
+    /// Starts running at once at the same dependency depth
+    pub max_per_depth: Option<usize>,
+}
+
+impl StartupLimits {
+    /// Whether another start may begin.
+    pub fn allows(&self, running: usize, running_at_depth: usize) -> bool {
+        self.max_parallel.is_none_or(|max| running < max)
+            && self.max_per_depth.is_none_or(|max| running_at_depth < max)
+    }
+}
+
+impl std::fmt::Display for StartupLimits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let limit = |l: Option<usize>| l.map_or("unlimited".to_string(), |n| n.to_string());
+        write!(
+            f,
+            "{} overall, {} per depth",
+            limit(self.max_parallel),
+            limit(self.max_per_depth)
+        )
+    }
+}
+
+/// How one service was (or will be) scheduled during boot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduleDecision {
+    pub name: String,
+    /// Dependency depth; 0 for services without dependencies
+    pub depth: usize,
+    /// Start time measured on earlier boots
+    pub expected_ms: Option<u64>,
+    /// Expected time from this start until the end of the longest chain
+    /// of services waiting on it
+    pub critical_path_ms: u64,
+    /// Whether the service is on the boot critical path
+    pub critical: bool,
+    /// Position in start order, from 1
+    pub order: usize,
+    /// Time spent ready but held back by the limits
+    pub queued_ms: u64,
+}
+
+/// Service start times from earlier boots, and the last boot's schedule.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BootHistory {
+    /// Running average start time of each service
+    pub durations_ms: HashMap<String, u64>,
+    #[serde(default)]
+    pub last_schedule: Vec<ScheduleDecision>,
+}
+
+impl BootHistory {
+    /// Load the history, or start a new one if there is none.
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Fold the start times of a boot into the averages.
+    pub fn record(&mut self, timings: &[BootTiming]) {
+        for timing in timings {
+            self.durations_ms
+                .entry(timing.name.clone())
+                .and_modify(|avg| {
+                    *avg = (*avg * (HISTORY_WEIGHT - 1) + timing.duration_ms) / HISTORY_WEIGHT
+                })
+                .or_insert(timing.duration_ms);
+        }
+    }
+}
+
+/// Startup order of a set of services.
+#[derive(Debug, Clone)]
+pub struct StartupPlan {
+    /// Services by priority: critical path length, then name
+    pub services: Vec<ScheduleDecision>,
+    /// Dependencies of each service within the set
+    dependencies: HashMap<String, Vec<String>>,
+}
+
+impl StartupPlan {
+    /// Plan the start of `levels`, services grouped by dependency depth.
+    pub fn new(
+        levels: &[Vec<String>],
+        definitions: &HashMap<String, ServiceDefinition>,
+        history: &BootHistory,
+    ) -> Self {
+        let depth: HashMap<&str, usize> = levels
+            .iter()
+            .enumerate()
+            .flat_map(|(depth, level)| level.iter().map(move |n| (n.as_str(), depth)))
+            .collect();
+
+        let mut dependencies: HashMap<String, Vec<String>> = HashMap::new();
+        let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+        for &name in depth.keys() {
+            let mut deps: Vec<String> = definitions
+                .get(name)
+                .map(|def| def.requires.iter().chain(&def.after).cloned().collect())
+                .unwrap_or_default();
+            deps.retain(|d| depth.contains_key(d.as_str()));
+            deps.sort();
+            deps.dedup();
+            for dep in &deps {
+                let dep = depth.get_key_value(dep.as_str()).unwrap().0;
+                dependents.entry(dep).or_default().push(name);
+            }
+            dependencies.insert(name.to_string(), deps);
+        }
+
+        // Longest remaining chain, computed deepest level first
+        let expected = |name: &str| history.durations_ms.get(name).copied();
+        let mut path_ms: HashMap<&str, u64> = HashMap::new();
+        for level in levels.iter().rev() {
+            for name in level {
+                let rest = dependents
+                    .get(name.as_str())
+                    .into_iter()
+                    .flatten()
+                    .map(|d| path_ms.get(d).copied().unwrap_or(0))
+                    .max()
+                    .unwrap_or(0);
+                path_ms.insert(name, expected(name).unwrap_or(0) + rest);
+            }
+        }
+
+        // Follow the heaviest chain from the heaviest root
+        let mut critical = HashSet::new();
+        let mut next = levels
+            .first()
+            .and_then(|roots| heaviest(roots.iter().map(|n| n.as_str()), &path_ms));
+        while let Some(name) = next.filter(|n| path_ms[n] > 0) {
+            critical.insert(name);
+            next = heaviest(
+                dependents.get(name).into_iter().flatten().copied(),
+                &path_ms,
+            );
+        }
+
+        let mut services: Vec<ScheduleDecision> = depth
+            .iter()
+            .map(|(&name, &depth)| ScheduleDecision {
+                name: name.to_string(),
+                depth,
+                expected_ms: expected(name),
+                critical_path_ms: path_ms[name],
+                critical: critical.contains(name),
+                order: 0,
+                queued_ms: 0,
+            })
+            .collect();
+        services.sort_by(|a, b| {
+            b.critical_path_ms
+                .cmp(&a.critical_path_ms)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+
+        Self {
+            services,
+            dependencies,
+        }
+    }
+
+    /// Whether every dependency of a service has started.
+    pub fn is_ready(&self, name: &str, started: &HashSet<String>) -> bool {
+        self.dependencies
+            .get(name)
+            .is_none_or(|deps| deps.iter().all(|d| started.contains(d)))
+    }
+
+    /// Index in `pending` of the next service to start, if the limits
+    /// allow one.
+    pub fn next(
+        &self,
+        pending: &[ScheduleDecision],
+        started: &HashSet<String>,
+        running_by_depth: &HashMap<usize, usize>,
+        limits: &StartupLimits,
+    ) -> Option<usize> {
+        let running = running_by_depth.values().sum();
+        pending.iter().position(|s| {
+            self.is_ready(&s.name, started)
+                && limits.allows(
+                    running,
+                    running_by_depth.get(&s.depth).copied().unwrap_or(0),
+                )
+        })
+    }
+}
+
+/// Service heading the longest chain, ties going to the smallest name.
+fn heaviest<'a>(
+    names: impl Iterator<Item = &'a str>,
+    path_ms: &HashMap<&str, u64>,
+) -> Option<&'a str> {
+    names.max_by(|a, b| path_ms[a].cmp(&path_ms[b]).then_with(|| b.cmp(a)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(name: &str, requires: &[&str]) -> ServiceDefinition {
+        let mut def = ServiceDefinition::new(name, "/bin/true");
+        def.requires = requires.iter().map(|s| s.to_string()).collect();
+        def
+    }
+
+    fn plan() -> StartupPlan {
+        let definitions: HashMap<String, ServiceDefinition> = [
+            service("udev", &[]),
+            service("syslog", &[]),
+            service("net", &["udev"]),
+            service("sshd", &["net"]),
+            service("cron", &["syslog"]),
+        ]
+        .into_iter()
+        .map(|d| (d.name.clone(), d))
+        .collect();
+        let mut history = BootHistory::default();
+        for (name, ms) in [("udev", 300), ("net", 900), ("sshd", 50), ("syslog", 100)] {
+            history.durations_ms.insert(name.to_string(), ms);
+        }
+        let levels = vec![
+            vec!["syslog".to_string(), "udev".to_string()],
+            vec!["cron".to_string(), "net".to_string()],
+            vec!["sshd".to_string()],
+        ];
+        StartupPlan::new(&levels, &definitions, &history)
+    }
+
+    #[test]
+    fn test_plan_prioritizes_critical_path() {
+        let plan = plan();
+        let order: Vec<(&str, u64, bool)> = plan
+            .services
+            .iter()
+            .map(|s| (s.name.as_str(), s.critical_path_ms, s.critical))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                ("udev", 1250, true),
+                ("net", 950, true),
+                ("syslog", 100, false),
+                ("sshd", 50, true),
+                ("cron", 0, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let plan = plan();
+        let limits = StartupLimits {
+            max_parallel: Some(2),
+            max_per_depth: Some(1),
+        };
+        let started = HashSet::new();
+        let mut running = HashMap::new();
+        // udev first, then nothing else at depth 0 until it finishes
+        assert_eq!(
+            plan.next(&plan.services, &started, &running, &limits),
+            Some(0)
+        );
+        running.insert(0, 1);
+        assert_eq!(plan.next(&plan.services, &started, &running, &limits), None);
+
+        let started: HashSet<String> = ["udev".to_string()].into();
+        running.clear();
+        assert_eq!(
+            plan.next(&plan.services[1..], &started, &running, &limits),
+            Some(0)
+        );
+        assert!(!StartupLimits {
+            max_parallel: Some(2),
+            max_per_depth: None
+        }
+        .allows(2, 0));
+    }
+
+    #[test]
+    fn test_history_average() {
+        let mut history = BootHistory::default();
+        let timing = |ms| BootTiming {
+            name: "net".to_string(),
+            start_time: std::time::Instant::now(),
+            end_time: None,
+            duration_ms: ms,
+        };
+        history.record(&[timing(800)]);
+        history.record(&[timing(400)]);
+        assert_eq!(history.durations_ms["net"], 700);
+    }
+}
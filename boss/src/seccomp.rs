@@ -0,0 +1,1195 @@
+//! System call filtering.
+//!
+//! A service with `SystemCallFilter=` gets a seccomp filter installed just
+//! before it execs: the listed system calls, and the `@default` group every
+//! program needs, are allowed, and any other call kills the process.
+//! Entries are system call names or `@group` names from [`GROUPS`].
+//!
+//! Writing an allowlist by hand is error prone, so a service can be run in
+//! learning mode (`SystemCallLearning=yes`) instead. Its filter allows what
+//! was learned so far and makes the kernel log every other call instead of
+//! refusing it. Those logs are audit records (type 1326) in the kernel log;
+//! [`SyscallLearner`] follows /dev/kmsg, matches each record to a service
+//! and adds the call to the service's list under /var/lib/buckos/seccomp.
+//! The kernel rate limits the records, but a learned call is no longer
+//! logged, so each run of the service's workload fills in the rest.
+//! [`generate`] turns the list into a filter, folding complete groups back
+//! into their names.
+
+use crate::coredump;
+use crate::error::Result;
+use std::collections::{BTreeSet, HashMap};
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Once};
+use tracing::{debug, warn};
+
+/// Directory learned system calls are kept in, one file per service.
+pub const SECCOMP_DIR: &str = "/var/lib/buckos/seccomp";
+
+/// Audit record type of a logged seccomp action.
+const AUDIT_SECCOMP: &str = "type=1326";
+
+/// `AUDIT_ARCH_*` of the system calls the table describes.
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xc000_003e;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xc000_00b7;
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const AUDIT_ARCH: u32 = 0;
+
+/// A named set of system calls.
+#[derive(Debug, Clone, Copy)]
+pub struct SyscallGroup {
+    /// Name, with the leading `@`
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Members; those the architecture lacks are skipped
+    pub syscalls: &'static [&'static str],
+}
+
+/// Groups for writing filters by hand.
+pub const GROUPS: &[SyscallGroup] = &[
+    SyscallGroup {
+        name: "@default",
+        description: "Calls every program makes; always allowed",
+        syscalls: &[
+            "arch_prctl",
+            "brk",
+            "clock_getres",
+            "clock_gettime",
+            "clock_nanosleep",
+            "execve",
+            "exit",
+            "exit_group",
+            "futex",
+            "get_robust_list",
+            "getpid",
+            "getrandom",
+            "getrlimit",
+            "gettid",
+            "gettimeofday",
+            "madvise",
+            "membarrier",
+            "mmap",
+            "mprotect",
+            "mremap",
+            "munmap",
+            "nanosleep",
+            "prlimit64",
+            "restart_syscall",
+            "rseq",
+            "rt_sigreturn",
+            "sched_getaffinity",
+            "sched_yield",
+            "set_robust_list",
+            "set_tid_address",
+        ],
+    },
+    SyscallGroup {
+        name: "@basic-io",
+        description: "Reading and writing open file descriptors",
+        syscalls: &[
+            "close",
+            "close_range",
+            "dup",
+            "dup2",
+            "dup3",
+            "lseek",
+            "pread64",
+            "preadv",
+            "preadv2",
+            "pwrite64",
+            "pwritev",
+            "pwritev2",
+            "read",
+            "readv",
+            "write",
+            "writev",
+        ],
+    },
+    SyscallGroup {
+        name: "@file-system",
+        description: "Opening, inspecting and changing files and directories",
+        syscalls: &[
+            "access",
+            "chdir",
+            "chmod",
+            "creat",
+            "faccessat",
+            "faccessat2",
+            "fallocate",
+            "fchdir",
+            "fchmod",
+            "fchmodat",
+            "fcntl",
+            "fdatasync",
+            "fgetxattr",
+            "flistxattr",
+            "flock",
+            "fstat",
+            "fstatfs",
+            "fsync",
+            "ftruncate",
+            "getcwd",
+            "getdents",
+            "getdents64",
+            "getxattr",
+            "inotify_add_watch",
+            "inotify_init",
+            "inotify_init1",
+            "inotify_rm_watch",
+            "lgetxattr",
+            "link",
+            "linkat",
+            "listxattr",
+            "llistxattr",
+            "lstat",
+            "mkdir",
+            "mkdirat",
+            "newfstatat",
+            "open",
+            "openat",
+            "openat2",
+            "readlink",
+            "readlinkat",
+            "rename",
+            "renameat",
+            "renameat2",
+            "rmdir",
+            "stat",
+            "statfs",
+            "statx",
+            "symlink",
+            "symlinkat",
+            "truncate",
+            "umask",
+            "unlink",
+            "unlinkat",
+            "utime",
+            "utimensat",
+            "utimes",
+        ],
+    },
+    SyscallGroup {
+        name: "@io-event",
+        description: "Waiting for file descriptors",
+        syscalls: &[
+            "epoll_create",
+            "epoll_create1",
+            "epoll_ctl",
+            "epoll_pwait",
+            "epoll_pwait2",
+            "epoll_wait",
+            "eventfd",
+            "eventfd2",
+            "poll",
+            "ppoll",
+            "pselect6",
+            "select",
+        ],
+    },
+    SyscallGroup {
+        name: "@network",
+        description: "Sockets",
+        syscalls: &[
+            "accept",
+            "accept4",
+            "bind",
+            "connect",
+            "getpeername",
+            "getsockname",
+            "getsockopt",
+            "listen",
+            "recvfrom",
+            "recvmmsg",
+            "recvmsg",
+            "sendmmsg",
+            "sendmsg",
+            "sendto",
+            "setsockopt",
+            "shutdown",
+            "socket",
+            "socketpair",
+        ],
+    },
+    SyscallGroup {
+        name: "@process",
+        description: "Creating, waiting for and signalling processes",
+        syscalls: &[
+            "clone",
+            "clone3",
+            "execveat",
+            "fork",
+            "getpgid",
+            "getpgrp",
+            "getppid",
+            "getsid",
+            "kill",
+            "pidfd_open",
+            "pidfd_send_signal",
+            "prctl",
+            "setpgid",
+            "setsid",
+            "tgkill",
+            "tkill",
+            "vfork",
+            "wait4",
+            "waitid",
+        ],
+    },
+    SyscallGroup {
+        name: "@signal",
+        description: "Handling signals",
+        syscalls: &[
+            "rt_sigaction",
+            "rt_sigpending",
+            "rt_sigprocmask",
+            "rt_sigsuspend",
+            "rt_sigtimedwait",
+            "sigaltstack",
+            "signalfd",
+            "signalfd4",
+        ],
+    },
+    SyscallGroup {
+        name: "@timer",
+        description: "Timers and alarms",
+        syscalls: &[
+            "alarm",
+            "getitimer",
+            "setitimer",
+            "timer_create",
+            "timer_delete",
+            "timer_getoverrun",
+            "timer_gettime",
+            "timer_settime",
+            "timerfd_create",
+            "timerfd_gettime",
+            "timerfd_settime",
+        ],
+    },
+    SyscallGroup {
+        name: "@ipc",
+        description: "Pipes, message queues, semaphores and shared memory",
+        syscalls: &[
+            "memfd_create",
+            "mq_getsetattr",
+            "mq_notify",
+            "mq_open",
+            "mq_timedreceive",
+            "mq_timedsend",
+            "mq_unlink",
+            "msgctl",
+            "msgget",
+            "msgrcv",
+            "msgsnd",
+            "pipe",
+            "pipe2",
+            "semctl",
+            "semget",
+            "semop",
+            "semtimedop",
+            "shmat",
+            "shmctl",
+            "shmdt",
+            "shmget",
+        ],
+    },
+    SyscallGroup {
+        name: "@credentials",
+        description: "Reading process credentials",
+        syscalls: &[
+            "capget",
+            "getegid",
+            "geteuid",
+            "getgid",
+            "getgroups",
+            "getresgid",
+            "getresuid",
+            "getuid",
+        ],
+    },
+];
+
+/// A group by name, with or without the `@`.
+pub fn group(name: &str) -> Option<&'static SyscallGroup> {
+    let name = name.strip_prefix('@').unwrap_or(name);
+    GROUPS.iter().find(|g| &g.name[1..] == name)
+}
+
+/// Number of a system call on this architecture.
+pub fn syscall_number(name: &str) -> Option<i64> {
+    table().find(|(n, _)| *n == name).map(|(_, nr)| nr)
+}
+
+/// Name of a system call number on this architecture.
+pub fn syscall_name(nr: i64) -> Option<&'static str> {
+    table().find(|(_, n)| *n == nr).map(|(name, _)| name)
+}
+
+fn table() -> impl Iterator<Item = (&'static str, i64)> {
+    SYSCALLS.iter().chain(LEGACY_SYSCALLS).copied()
+}
+
+/// Filter entries that are neither a system call nor a group.
+pub fn unknown(entries: &[String]) -> Vec<String> {
+    entries
+        .iter()
+        .filter(|e| match e.strip_prefix('@') {
+            Some(_) => group(e).is_none(),
+            None => syscall_number(e).is_none(),
+        })
+        .cloned()
+        .collect()
+}
+
+/// The system calls a filter allows, including `@default`. Unknown entries
+/// and group members this architecture lacks are left out.
+pub fn expand(entries: &[String]) -> BTreeSet<&'static str> {
+    let mut names = BTreeSet::new();
+    let default = std::iter::once("@default");
+    for entry in default.chain(entries.iter().map(String::as_str)) {
+        let members: Vec<&str> = match entry.strip_prefix('@') {
+            Some(_) => group(entry).map_or(Vec::new(), |g| g.syscalls.to_vec()),
+            None => vec![entry],
+        };
+        names.extend(
+            members
+                .into_iter()
+                .filter_map(|m| table().find(|(n, _)| *n == m).map(|(n, _)| n)),
+        );
+    }
+    names
+}
+
+/// A filter allowlist for `syscalls`, with each group whose members were
+/// all seen written by name, and `@default` left implicit.
+pub fn generate<S: AsRef<str>>(syscalls: impl IntoIterator<Item = S>) -> Vec<String> {
+    let mut remaining: BTreeSet<String> = syscalls
+        .into_iter()
+        .map(|s| s.as_ref().to_string())
+        .collect();
+    let default = expand(&[]);
+    remaining.retain(|s| !default.contains(s.as_str()));
+
+    let mut entries = Vec::new();
+    for g in GROUPS.iter().skip(1) {
+        let members: Vec<&str> = g
+            .syscalls
+            .iter()
+            .copied()
+            .filter(|s| syscall_number(s).is_some())
+            .collect();
+        if !members.is_empty() && members.iter().all(|s| remaining.contains(*s)) {
+            remaining.retain(|s| !members.contains(&s.as_str()));
+            entries.push(g.name.to_string());
+        }
+    }
+    entries.extend(remaining);
+    entries
+}
+
+/// A compiled seccomp filter.
+#[derive(Debug, Clone)]
+pub struct SeccompFilter {
+    program: Vec<libc::sock_filter>,
+}
+
+impl SeccompFilter {
+    /// Allow `entries` and `@default`. Any other call kills the process,
+    /// or is only logged when `learn` is set.
+    pub fn new(entries: &[String], learn: bool) -> Self {
+        let allowed: Vec<u32> = expand(entries)
+            .into_iter()
+            .filter_map(syscall_number)
+            .map(|nr| nr as u32)
+            .collect();
+        let default = if learn {
+            libc::SECCOMP_RET_LOG
+        } else {
+            libc::SECCOMP_RET_KILL_PROCESS
+        };
+
+        // struct seccomp_data: nr at offset 0, arch at offset 4
+        let mut program = vec![
+            bpf_stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, 4),
+            bpf_jump(
+                libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+                AUDIT_ARCH,
+                1,
+                0,
+            ),
+            bpf_stmt(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_KILL_PROCESS),
+            bpf_stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, 0),
+        ];
+        for nr in allowed {
+            program.push(bpf_jump(
+                libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+                nr,
+                0,
+                1,
+            ));
+            program.push(bpf_stmt(
+                libc::BPF_RET | libc::BPF_K,
+                libc::SECCOMP_RET_ALLOW,
+            ));
+        }
+        program.push(bpf_stmt(libc::BPF_RET | libc::BPF_K, default));
+        Self { program }
+    }
+
+    /// Number of BPF instructions.
+    pub fn len(&self) -> usize {
+        self.program.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.program.is_empty()
+    }
+
+    /// Install the filter on the calling thread, setting no_new_privs as an
+    /// unprivileged process must.
+    ///
+    /// Only makes system calls, so it may run between fork and exec.
+    pub fn install(&self) -> std::io::Result<()> {
+        let prog = libc::sock_fprog {
+            len: self.program.len() as libc::c_ushort,
+            filter: self.program.as_ptr() as *mut libc::sock_filter,
+        };
+        unsafe {
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0
+                || libc::prctl(
+                    libc::PR_SET_SECCOMP,
+                    libc::SECCOMP_MODE_FILTER,
+                    &prog as *const libc::sock_fprog,
+                ) != 0
+            {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn bpf_stmt(code: u32, k: u32) -> libc::sock_filter {
+    bpf_jump(code, k, 0, 0)
+}
+
+fn bpf_jump(code: u32, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+/// A system call logged by a seccomp filter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AuditRecord {
+    pid: u32,
+    syscall: i64,
+    exe: Option<PathBuf>,
+}
+
+/// A seccomp audit record from the kernel log, if it is one for this
+/// architecture.
+fn parse_audit(line: &str) -> Option<AuditRecord> {
+    if !line.contains(AUDIT_SECCOMP) {
+        return None;
+    }
+    let field = |key: &str| {
+        line.split_whitespace()
+            .find_map(|word| word.strip_prefix(key)?.strip_prefix('='))
+    };
+    let arch = u32::from_str_radix(field("arch")?, 16).ok()?;
+    if arch != AUDIT_ARCH {
+        return None;
+    }
+    Some(AuditRecord {
+        pid: field("pid")?.parse().ok()?,
+        syscall: field("syscall")?.parse().ok()?,
+        // Quoted, or hex encoded if it has spaces or quotes
+        exe: field("exe")
+            .and_then(|exe| exe.strip_prefix('"')?.strip_suffix('"'))
+            .map(PathBuf::from),
+    })
+}
+
+/// Learned system calls of services.
+#[derive(Debug, Clone)]
+pub struct SyscallStore {
+    dir: PathBuf,
+}
+
+impl SyscallStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn file(&self, service: &str) -> PathBuf {
+        self.dir.join(service)
+    }
+
+    /// System calls learned for a service.
+    pub fn load(&self, service: &str) -> BTreeSet<String> {
+        std::fs::read_to_string(self.file(service))
+            .map(|content| {
+                content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Add to the system calls learned for a service.
+    pub fn add<S: AsRef<str>>(
+        &self,
+        service: &str,
+        syscalls: impl IntoIterator<Item = S>,
+    ) -> Result<()> {
+        let mut learned = self.load(service);
+        learned.extend(syscalls.into_iter().map(|s| s.as_ref().to_string()));
+        std::fs::create_dir_all(&self.dir)?;
+        let content: String = learned.iter().map(|s| format!("{}\n", s)).collect();
+        std::fs::write(self.file(service), content)?;
+        Ok(())
+    }
+
+    /// Forget what was learned for a service.
+    pub fn clear(&self, service: &str) -> Result<()> {
+        match std::fs::remove_file(self.file(service)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Default for SyscallStore {
+    fn default() -> Self {
+        Self::new(SECCOMP_DIR)
+    }
+}
+
+/// Follows the kernel log for the system calls of services in learning
+/// mode.
+#[derive(Debug)]
+pub struct SyscallLearner {
+    store: SyscallStore,
+    /// Services being learned, with the program each runs
+    services: Arc<Mutex<HashMap<String, PathBuf>>>,
+    started: Once,
+}
+
+impl SyscallLearner {
+    pub fn new(store: SyscallStore) -> Self {
+        Self {
+            store,
+            services: Arc::new(Mutex::new(HashMap::new())),
+            started: Once::new(),
+        }
+    }
+
+    pub fn store(&self) -> &SyscallStore {
+        &self.store
+    }
+
+    /// Learn the system calls of a service running `program`, starting to
+    /// follow the kernel log if this is the first.
+    ///
+    /// Records are matched to services through the cgroup or environment
+    /// of their process, or by the program for a process that has already
+    /// exited.
+    pub fn watch(&self, service: &str, program: &Path) {
+        let program = std::fs::canonicalize(program).unwrap_or_else(|_| program.to_path_buf());
+        self.services
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(service.to_string(), program);
+        self.started.call_once(|| {
+            // Open the log before the service starts, so none of its
+            // records are missed
+            let kmsg = std::fs::File::open("/dev/kmsg")
+                .and_then(|mut kmsg| kmsg.seek(SeekFrom::End(0)).map(|_| kmsg));
+            let (store, services) = (self.store.clone(), Arc::clone(&self.services));
+            let spawned = kmsg.and_then(|kmsg| {
+                std::thread::Builder::new()
+                    .name("seccomp-learn".to_string())
+                    .spawn(move || {
+                        if let Err(e) = follow_kmsg(kmsg, &store, &services) {
+                            warn!(error = %e, "Stopped learning system calls");
+                        }
+                    })
+            });
+            if let Err(e) = spawned {
+                warn!(error = %e, "Failed to start learning system calls");
+            }
+        });
+    }
+}
+
+/// Record the logged system calls of watched services until the kernel
+/// log cannot be read.
+fn follow_kmsg(
+    kmsg: std::fs::File,
+    store: &SyscallStore,
+    services: &Mutex<HashMap<String, PathBuf>>,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(kmsg);
+    // What is already stored, per service
+    let mut learned: HashMap<String, BTreeSet<String>> = HashMap::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => return Ok(()),
+            Ok(_) => {}
+            // Records were overwritten before we read them
+            Err(e) if e.raw_os_error() == Some(libc::EPIPE) => continue,
+            Err(e) => return Err(e),
+        }
+        let Some(record) = parse_audit(&line) else {
+            continue;
+        };
+        let service = {
+            let services = services.lock().unwrap_or_else(|e| e.into_inner());
+            match coredump::identify(record.pid).1 {
+                Some(service) => services.contains_key(&service).then_some(service),
+                None => services
+                    .iter()
+                    .find(|(_, program)| record.exe.as_ref() == Some(*program))
+                    .map(|(service, _)| service.clone()),
+            }
+        };
+        let (Some(service), Some(name)) = (service, syscall_name(record.syscall)) else {
+            continue;
+        };
+        let known = learned
+            .entry(service.clone())
+            .or_insert_with(|| store.load(&service));
+        if known.insert(name.to_string()) {
+            debug!(service = %service, syscall = name, "Learned system call");
+            if let Err(e) = store.add(&service, [name]) {
+                warn!(service = %service, error = %e, "Failed to record system call");
+            }
+        }
+    }
+}
+
+/// Set `SystemCallFilter=` in the [Service] section of a unit file,
+/// dropping `SystemCallLearning=`.
+pub fn set_unit_filter(content: &str, entries: &[String]) -> String {
+    let directive = format!("SystemCallFilter={}", entries.join(" "));
+    let mut lines: Vec<String> = Vec::new();
+    let mut section = String::new();
+    let mut written = false;
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            if section == "Service" && !written {
+                insert_before_blank(&mut lines, directive.clone());
+                written = true;
+            }
+            section = trimmed[1..trimmed.len() - 1].to_string();
+        } else if section == "Service" {
+            let key = trimmed.split_once('=').map(|(k, _)| k.trim());
+            match key {
+                Some("SystemCallFilter") => {
+                    if !written {
+                        lines.push(directive.clone());
+                        written = true;
+                    }
+                    continue;
+                }
+                Some("SystemCallLearning") => continue,
+                _ => {}
+            }
+        }
+        lines.push(line.to_string());
+    }
+    if !written {
+        if section != "Service" {
+            lines.push(String::new());
+            lines.push("[Service]".to_string());
+        }
+        insert_before_blank(&mut lines, directive);
+    }
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+/// Push `line` before any trailing blank lines.
+fn insert_before_blank(lines: &mut Vec<String>, line: String) {
+    let at = lines
+        .iter()
+        .rposition(|l| !l.trim().is_empty())
+        .map_or(lines.len(), |i| i + 1);
+    lines.insert(at, line);
+}
+
+/// System calls of both supported architectures.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+const SYSCALLS: &[(&str, i64)] = &[
+    ("accept", libc::SYS_accept),
+    ("accept4", libc::SYS_accept4),
+    ("acct", libc::SYS_acct),
+    ("add_key", libc::SYS_add_key),
+    ("adjtimex", libc::SYS_adjtimex),
+    ("bind", libc::SYS_bind),
+    ("bpf", libc::SYS_bpf),
+    ("brk", libc::SYS_brk),
+    ("capget", libc::SYS_capget),
+    ("capset", libc::SYS_capset),
+    ("chdir", libc::SYS_chdir),
+    ("chroot", libc::SYS_chroot),
+    ("clock_adjtime", libc::SYS_clock_adjtime),
+    ("clock_getres", libc::SYS_clock_getres),
+    ("clock_gettime", libc::SYS_clock_gettime),
+    ("clock_nanosleep", libc::SYS_clock_nanosleep),
+    ("clock_settime", libc::SYS_clock_settime),
+    ("clone", libc::SYS_clone),
+    ("clone3", libc::SYS_clone3),
+    ("close", libc::SYS_close),
+    ("close_range", libc::SYS_close_range),
+    ("connect", libc::SYS_connect),
+    ("copy_file_range", libc::SYS_copy_file_range),
+    ("delete_module", libc::SYS_delete_module),
+    ("dup", libc::SYS_dup),
+    ("dup3", libc::SYS_dup3),
+    ("epoll_create1", libc::SYS_epoll_create1),
+    ("epoll_ctl", libc::SYS_epoll_ctl),
+    ("epoll_pwait", libc::SYS_epoll_pwait),
+    ("epoll_pwait2", libc::SYS_epoll_pwait2),
+    ("eventfd2", libc::SYS_eventfd2),
+    ("execve", libc::SYS_execve),
+    ("execveat", libc::SYS_execveat),
+    ("exit", libc::SYS_exit),
+    ("exit_group", libc::SYS_exit_group),
+    ("faccessat", libc::SYS_faccessat),
+    ("faccessat2", libc::SYS_faccessat2),
+    ("fallocate", libc::SYS_fallocate),
+    ("fanotify_init", libc::SYS_fanotify_init),
+    ("fanotify_mark", libc::SYS_fanotify_mark),
+    ("fchdir", libc::SYS_fchdir),
+    ("fchmod", libc::SYS_fchmod),
+    ("fchmodat", libc::SYS_fchmodat),
+    ("fchown", libc::SYS_fchown),
+    ("fchownat", libc::SYS_fchownat),
+    ("fcntl", libc::SYS_fcntl),
+    ("fdatasync", libc::SYS_fdatasync),
+    ("fgetxattr", libc::SYS_fgetxattr),
+    ("finit_module", libc::SYS_finit_module),
+    ("flistxattr", libc::SYS_flistxattr),
+    ("flock", libc::SYS_flock),
+    ("fremovexattr", libc::SYS_fremovexattr),
+    ("fsconfig", libc::SYS_fsconfig),
+    ("fsetxattr", libc::SYS_fsetxattr),
+    ("fsmount", libc::SYS_fsmount),
+    ("fsopen", libc::SYS_fsopen),
+    ("fspick", libc::SYS_fspick),
+    ("fstat", libc::SYS_fstat),
+    ("fstatfs", libc::SYS_fstatfs),
+    ("fsync", libc::SYS_fsync),
+    ("ftruncate", libc::SYS_ftruncate),
+    ("futex", libc::SYS_futex),
+    ("futex_waitv", libc::SYS_futex_waitv),
+    ("get_mempolicy", libc::SYS_get_mempolicy),
+    ("get_robust_list", libc::SYS_get_robust_list),
+    ("getcpu", libc::SYS_getcpu),
+    ("getcwd", libc::SYS_getcwd),
+    ("getdents64", libc::SYS_getdents64),
+    ("getegid", libc::SYS_getegid),
+    ("geteuid", libc::SYS_geteuid),
+    ("getgid", libc::SYS_getgid),
+    ("getgroups", libc::SYS_getgroups),
+    ("getitimer", libc::SYS_getitimer),
+    ("getpeername", libc::SYS_getpeername),
+    ("getpgid", libc::SYS_getpgid),
+    ("getpid", libc::SYS_getpid),
+    ("getppid", libc::SYS_getppid),
+    ("getpriority", libc::SYS_getpriority),
+    ("getrandom", libc::SYS_getrandom),
+    ("getresgid", libc::SYS_getresgid),
+    ("getresuid", libc::SYS_getresuid),
+    ("getrusage", libc::SYS_getrusage),
+    ("getsid", libc::SYS_getsid),
+    ("getsockname", libc::SYS_getsockname),
+    ("getsockopt", libc::SYS_getsockopt),
+    ("gettid", libc::SYS_gettid),
+    ("gettimeofday", libc::SYS_gettimeofday),
+    ("getuid", libc::SYS_getuid),
+    ("getxattr", libc::SYS_getxattr),
+    ("init_module", libc::SYS_init_module),
+    ("inotify_add_watch", libc::SYS_inotify_add_watch),
+    ("inotify_init1", libc::SYS_inotify_init1),
+    ("inotify_rm_watch", libc::SYS_inotify_rm_watch),
+    ("io_cancel", libc::SYS_io_cancel),
+    ("io_destroy", libc::SYS_io_destroy),
+    ("io_getevents", libc::SYS_io_getevents),
+    ("io_setup", libc::SYS_io_setup),
+    ("io_submit", libc::SYS_io_submit),
+    ("io_uring_enter", libc::SYS_io_uring_enter),
+    ("io_uring_register", libc::SYS_io_uring_register),
+    ("io_uring_setup", libc::SYS_io_uring_setup),
+    ("ioctl", libc::SYS_ioctl),
+    ("ioprio_get", libc::SYS_ioprio_get),
+    ("ioprio_set", libc::SYS_ioprio_set),
+    ("kcmp", libc::SYS_kcmp),
+    ("kexec_file_load", libc::SYS_kexec_file_load),
+    ("kexec_load", libc::SYS_kexec_load),
+    ("keyctl", libc::SYS_keyctl),
+    ("kill", libc::SYS_kill),
+    ("landlock_add_rule", libc::SYS_landlock_add_rule),
+    ("landlock_create_ruleset", libc::SYS_landlock_create_ruleset),
+    ("landlock_restrict_self", libc::SYS_landlock_restrict_self),
+    ("lgetxattr", libc::SYS_lgetxattr),
+    ("linkat", libc::SYS_linkat),
+    ("listen", libc::SYS_listen),
+    ("listxattr", libc::SYS_listxattr),
+    ("llistxattr", libc::SYS_llistxattr),
+    ("lookup_dcookie", libc::SYS_lookup_dcookie),
+    ("lremovexattr", libc::SYS_lremovexattr),
+    ("lseek", libc::SYS_lseek),
+    ("lsetxattr", libc::SYS_lsetxattr),
+    ("madvise", libc::SYS_madvise),
+    ("mbind", libc::SYS_mbind),
+    ("membarrier", libc::SYS_membarrier),
+    ("memfd_create", libc::SYS_memfd_create),
+    ("memfd_secret", libc::SYS_memfd_secret),
+    ("migrate_pages", libc::SYS_migrate_pages),
+    ("mincore", libc::SYS_mincore),
+    ("mkdirat", libc::SYS_mkdirat),
+    ("mknodat", libc::SYS_mknodat),
+    ("mlock", libc::SYS_mlock),
+    ("mlock2", libc::SYS_mlock2),
+    ("mlockall", libc::SYS_mlockall),
+    ("mmap", libc::SYS_mmap),
+    ("mount", libc::SYS_mount),
+    ("mount_setattr", libc::SYS_mount_setattr),
+    ("move_mount", libc::SYS_move_mount),
+    ("move_pages", libc::SYS_move_pages),
+    ("mprotect", libc::SYS_mprotect),
+    ("mq_getsetattr", libc::SYS_mq_getsetattr),
+    ("mq_notify", libc::SYS_mq_notify),
+    ("mq_open", libc::SYS_mq_open),
+    ("mq_timedreceive", libc::SYS_mq_timedreceive),
+    ("mq_timedsend", libc::SYS_mq_timedsend),
+    ("mq_unlink", libc::SYS_mq_unlink),
+    ("mremap", libc::SYS_mremap),
+    ("mseal", libc::SYS_mseal),
+    ("msgctl", libc::SYS_msgctl),
+    ("msgget", libc::SYS_msgget),
+    ("msgrcv", libc::SYS_msgrcv),
+    ("msgsnd", libc::SYS_msgsnd),
+    ("msync", libc::SYS_msync),
+    ("munlock", libc::SYS_munlock),
+    ("munlockall", libc::SYS_munlockall),
+    ("munmap", libc::SYS_munmap),
+    ("name_to_handle_at", libc::SYS_name_to_handle_at),
+    ("nanosleep", libc::SYS_nanosleep),
+    ("newfstatat", libc::SYS_newfstatat),
+    ("nfsservctl", libc::SYS_nfsservctl),
+    ("open_by_handle_at", libc::SYS_open_by_handle_at),
+    ("open_tree", libc::SYS_open_tree),
+    ("openat", libc::SYS_openat),
+    ("openat2", libc::SYS_openat2),
+    ("perf_event_open", libc::SYS_perf_event_open),
+    ("personality", libc::SYS_personality),
+    ("pidfd_getfd", libc::SYS_pidfd_getfd),
+    ("pidfd_open", libc::SYS_pidfd_open),
+    ("pidfd_send_signal", libc::SYS_pidfd_send_signal),
+    ("pipe2", libc::SYS_pipe2),
+    ("pivot_root", libc::SYS_pivot_root),
+    ("pkey_alloc", libc::SYS_pkey_alloc),
+    ("pkey_free", libc::SYS_pkey_free),
+    ("pkey_mprotect", libc::SYS_pkey_mprotect),
+    ("ppoll", libc::SYS_ppoll),
+    ("prctl", libc::SYS_prctl),
+    ("pread64", libc::SYS_pread64),
+    ("preadv", libc::SYS_preadv),
+    ("preadv2", libc::SYS_preadv2),
+    ("prlimit64", libc::SYS_prlimit64),
+    ("process_madvise", libc::SYS_process_madvise),
+    ("process_mrelease", libc::SYS_process_mrelease),
+    ("process_vm_readv", libc::SYS_process_vm_readv),
+    ("process_vm_writev", libc::SYS_process_vm_writev),
+    ("pselect6", libc::SYS_pselect6),
+    ("ptrace", libc::SYS_ptrace),
+    ("pwrite64", libc::SYS_pwrite64),
+    ("pwritev", libc::SYS_pwritev),
+    ("pwritev2", libc::SYS_pwritev2),
+    ("quotactl", libc::SYS_quotactl),
+    ("quotactl_fd", libc::SYS_quotactl_fd),
+    ("read", libc::SYS_read),
+    ("readahead", libc::SYS_readahead),
+    ("readlinkat", libc::SYS_readlinkat),
+    ("readv", libc::SYS_readv),
+    ("reboot", libc::SYS_reboot),
+    ("recvfrom", libc::SYS_recvfrom),
+    ("recvmmsg", libc::SYS_recvmmsg),
+    ("recvmsg", libc::SYS_recvmsg),
+    ("remap_file_pages", libc::SYS_remap_file_pages),
+    ("removexattr", libc::SYS_removexattr),
+    ("renameat2", libc::SYS_renameat2),
+    ("request_key", libc::SYS_request_key),
+    ("restart_syscall", libc::SYS_restart_syscall),
+    ("rseq", libc::SYS_rseq),
+    ("rt_sigaction", libc::SYS_rt_sigaction),
+    ("rt_sigpending", libc::SYS_rt_sigpending),
+    ("rt_sigprocmask", libc::SYS_rt_sigprocmask),
+    ("rt_sigqueueinfo", libc::SYS_rt_sigqueueinfo),
+    ("rt_sigreturn", libc::SYS_rt_sigreturn),
+    ("rt_sigsuspend", libc::SYS_rt_sigsuspend),
+    ("rt_sigtimedwait", libc::SYS_rt_sigtimedwait),
+    ("rt_tgsigqueueinfo", libc::SYS_rt_tgsigqueueinfo),
+    ("sched_get_priority_max", libc::SYS_sched_get_priority_max),
+    ("sched_get_priority_min", libc::SYS_sched_get_priority_min),
+    ("sched_getaffinity", libc::SYS_sched_getaffinity),
+    ("sched_getattr", libc::SYS_sched_getattr),
+    ("sched_getparam", libc::SYS_sched_getparam),
+    ("sched_getscheduler", libc::SYS_sched_getscheduler),
+    ("sched_rr_get_interval", libc::SYS_sched_rr_get_interval),
+    ("sched_setaffinity", libc::SYS_sched_setaffinity),
+    ("sched_setattr", libc::SYS_sched_setattr),
+    ("sched_setparam", libc::SYS_sched_setparam),
+    ("sched_setscheduler", libc::SYS_sched_setscheduler),
+    ("sched_yield", libc::SYS_sched_yield),
+    ("seccomp", libc::SYS_seccomp),
+    ("semctl", libc::SYS_semctl),
+    ("semget", libc::SYS_semget),
+    ("semop", libc::SYS_semop),
+    ("semtimedop", libc::SYS_semtimedop),
+    ("sendmmsg", libc::SYS_sendmmsg),
+    ("sendmsg", libc::SYS_sendmsg),
+    ("sendto", libc::SYS_sendto),
+    ("set_mempolicy", libc::SYS_set_mempolicy),
+    ("set_mempolicy_home_node", libc::SYS_set_mempolicy_home_node),
+    ("set_robust_list", libc::SYS_set_robust_list),
+    ("set_tid_address", libc::SYS_set_tid_address),
+    ("setdomainname", libc::SYS_setdomainname),
+    ("setfsgid", libc::SYS_setfsgid),
+    ("setfsuid", libc::SYS_setfsuid),
+    ("setgid", libc::SYS_setgid),
+    ("setgroups", libc::SYS_setgroups),
+    ("sethostname", libc::SYS_sethostname),
+    ("setitimer", libc::SYS_setitimer),
+    ("setns", libc::SYS_setns),
+    ("setpgid", libc::SYS_setpgid),
+    ("setpriority", libc::SYS_setpriority),
+    ("setregid", libc::SYS_setregid),
+    ("setresgid", libc::SYS_setresgid),
+    ("setresuid", libc::SYS_setresuid),
+    ("setreuid", libc::SYS_setreuid),
+    ("setsid", libc::SYS_setsid),
+    ("setsockopt", libc::SYS_setsockopt),
+    ("settimeofday", libc::SYS_settimeofday),
+    ("setuid", libc::SYS_setuid),
+    ("setxattr", libc::SYS_setxattr),
+    ("shmat", libc::SYS_shmat),
+    ("shmctl", libc::SYS_shmctl),
+    ("shmdt", libc::SYS_shmdt),
+    ("shmget", libc::SYS_shmget),
+    ("shutdown", libc::SYS_shutdown),
+    ("sigaltstack", libc::SYS_sigaltstack),
+    ("signalfd4", libc::SYS_signalfd4),
+    ("socket", libc::SYS_socket),
+    ("socketpair", libc::SYS_socketpair),
+    ("splice", libc::SYS_splice),
+    ("statfs", libc::SYS_statfs),
+    ("statx", libc::SYS_statx),
+    ("swapoff", libc::SYS_swapoff),
+    ("swapon", libc::SYS_swapon),
+    ("symlinkat", libc::SYS_symlinkat),
+    ("sync", libc::SYS_sync),
+    ("syncfs", libc::SYS_syncfs),
+    ("sysinfo", libc::SYS_sysinfo),
+    ("syslog", libc::SYS_syslog),
+    ("tee", libc::SYS_tee),
+    ("tgkill", libc::SYS_tgkill),
+    ("timer_create", libc::SYS_timer_create),
+    ("timer_delete", libc::SYS_timer_delete),
+    ("timer_getoverrun", libc::SYS_timer_getoverrun),
+    ("timer_gettime", libc::SYS_timer_gettime),
+    ("timer_settime", libc::SYS_timer_settime),
+    ("timerfd_create", libc::SYS_timerfd_create),
+    ("timerfd_gettime", libc::SYS_timerfd_gettime),
+    ("timerfd_settime", libc::SYS_timerfd_settime),
+    ("times", libc::SYS_times),
+    ("tkill", libc::SYS_tkill),
+    ("truncate", libc::SYS_truncate),
+    ("umask", libc::SYS_umask),
+    ("umount2", libc::SYS_umount2),
+    ("uname", libc::SYS_uname),
+    ("unlinkat", libc::SYS_unlinkat),
+    ("unshare", libc::SYS_unshare),
+    ("userfaultfd", libc::SYS_userfaultfd),
+    ("utimensat", libc::SYS_utimensat),
+    ("vhangup", libc::SYS_vhangup),
+    ("vmsplice", libc::SYS_vmsplice),
+    ("wait4", libc::SYS_wait4),
+    ("waitid", libc::SYS_waitid),
+    ("write", libc::SYS_write),
+    ("writev", libc::SYS_writev),
+];
+
+/// System calls only x86_64 has, replaced by the `*at` and newer calls
+/// elsewhere.
+#[cfg(target_arch = "x86_64")]
+const LEGACY_SYSCALLS: &[(&str, i64)] = &[
+    ("_sysctl", libc::SYS__sysctl),
+    ("access", libc::SYS_access),
+    ("afs_syscall", libc::SYS_afs_syscall),
+    ("alarm", libc::SYS_alarm),
+    ("arch_prctl", libc::SYS_arch_prctl),
+    ("chmod", libc::SYS_chmod),
+    ("chown", libc::SYS_chown),
+    ("creat", libc::SYS_creat),
+    ("dup2", libc::SYS_dup2),
+    ("epoll_create", libc::SYS_epoll_create),
+    ("epoll_ctl_old", libc::SYS_epoll_ctl_old),
+    ("epoll_wait", libc::SYS_epoll_wait),
+    ("epoll_wait_old", libc::SYS_epoll_wait_old),
+    ("eventfd", libc::SYS_eventfd),
+    ("fadvise64", libc::SYS_fadvise64),
+    ("fchmodat2", libc::SYS_fchmodat2),
+    ("fork", libc::SYS_fork),
+    ("futimesat", libc::SYS_futimesat),
+    ("get_thread_area", libc::SYS_get_thread_area),
+    ("getdents", libc::SYS_getdents),
+    ("getpgrp", libc::SYS_getpgrp),
+    ("getpmsg", libc::SYS_getpmsg),
+    ("getrlimit", libc::SYS_getrlimit),
+    ("inotify_init", libc::SYS_inotify_init),
+    ("ioperm", libc::SYS_ioperm),
+    ("iopl", libc::SYS_iopl),
+    ("lchown", libc::SYS_lchown),
+    ("link", libc::SYS_link),
+    ("lstat", libc::SYS_lstat),
+    ("mkdir", libc::SYS_mkdir),
+    ("mknod", libc::SYS_mknod),
+    ("modify_ldt", libc::SYS_modify_ldt),
+    ("open", libc::SYS_open),
+    ("pause", libc::SYS_pause),
+    ("pipe", libc::SYS_pipe),
+    ("poll", libc::SYS_poll),
+    ("putpmsg", libc::SYS_putpmsg),
+    ("readlink", libc::SYS_readlink),
+    ("rename", libc::SYS_rename),
+    ("renameat", libc::SYS_renameat),
+    ("rmdir", libc::SYS_rmdir),
+    ("security", libc::SYS_security),
+    ("select", libc::SYS_select),
+    ("sendfile", libc::SYS_sendfile),
+    ("set_thread_area", libc::SYS_set_thread_area),
+    ("setrlimit", libc::SYS_setrlimit),
+    ("signalfd", libc::SYS_signalfd),
+    ("stat", libc::SYS_stat),
+    ("symlink", libc::SYS_symlink),
+    ("sync_file_range", libc::SYS_sync_file_range),
+    ("sysfs", libc::SYS_sysfs),
+    ("time", libc::SYS_time),
+    ("tuxcall", libc::SYS_tuxcall),
+    ("unlink", libc::SYS_unlink),
+    ("uselib", libc::SYS_uselib),
+    ("ustat", libc::SYS_ustat),
+    ("utime", libc::SYS_utime),
+    ("utimes", libc::SYS_utimes),
+    ("vfork", libc::SYS_vfork),
+    ("vserver", libc::SYS_vserver),
+];
+
+#[cfg(target_arch = "aarch64")]
+const LEGACY_SYSCALLS: &[(&str, i64)] = &[];
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const SYSCALLS: &[(&str, i64)] = &[];
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const LEGACY_SYSCALLS: &[(&str, i64)] = &[];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_groups_and_filters() {
+        let entries = vec!["@basic-io".to_string(), "socket".to_string()];
+        let allowed = expand(&entries);
+        assert!(allowed.contains("write"));
+        assert!(allowed.contains("socket"));
+        assert!(allowed.contains("exit_group"));
+        assert!(!allowed.contains("connect"));
+        assert_eq!(
+            unknown(&["@network".into(), "@nope".into(), "frobnicate".into()]),
+            vec!["@nope", "frobnicate"]
+        );
+        assert_eq!(
+            syscall_name(syscall_number("openat").unwrap()),
+            Some("openat")
+        );
+
+        // Arch check, syscall load, an allow pair per call and the default
+        let filter = SeccompFilter::new(&entries, false);
+        assert_eq!(filter.len(), 4 + 2 * allowed.len() + 1);
+        assert_eq!(
+            filter.program.last().unwrap().k,
+            libc::SECCOMP_RET_KILL_PROCESS
+        );
+        let learning = SeccompFilter::new(&[], true);
+        assert_eq!(learning.program.last().unwrap().k, libc::SECCOMP_RET_LOG);
+
+        let mut seen: Vec<&str> = group("@network").unwrap().syscalls.to_vec();
+        seen.extend(["read", "mmap", "ioctl"]);
+        assert_eq!(generate(seen), vec!["@network", "ioctl", "read"]);
+    }
+
+    #[test]
+    fn test_learning_records() {
+        let record = format!(
+            "5,812,93811234,-;audit: type=1326 audit(1760000000.120:77): auid=4294967295 \
+             uid=0 gid=0 ses=4294967295 pid=4242 comm=\"nginx\" exe=\"/usr/sbin/nginx\" \
+             sig=0 arch={:x} syscall=41 compat=0 ip=0x7f3a code=0x7ffc0000",
+            AUDIT_ARCH
+        );
+        assert_eq!(
+            parse_audit(&record),
+            Some(AuditRecord {
+                pid: 4242,
+                syscall: 41,
+                exe: Some(PathBuf::from("/usr/sbin/nginx")),
+            })
+        );
+        assert_eq!(parse_audit(&record.replace("type=1326", "type=1400")), None);
+        assert_eq!(parse_audit(&record.replace("arch=", "arch=4")), None);
+
+        let dir = std::env::temp_dir().join(format!("boss-seccomp-{}", std::process::id()));
+        let store = SyscallStore::new(&dir);
+        store.add("web", ["socket", "bind"]).unwrap();
+        store.add("web", ["bind", "listen"]).unwrap();
+        assert_eq!(
+            store.load("web").into_iter().collect::<Vec<_>>(),
+            vec!["bind", "listen", "socket"]
+        );
+        store.clear("web").unwrap();
+        assert!(store.load("web").is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let unit = "[Unit]\nDescription=Web\n\n[Service]\nExecStart=/bin/web\nSystemCallLearning=yes\n\n[Install]\nWantedBy=multi-user.target\n";
+        let entries = vec!["@network".to_string(), "ioctl".to_string()];
+        assert_eq!(
+            set_unit_filter(unit, &entries),
+            "[Unit]\nDescription=Web\n\n[Service]\nExecStart=/bin/web\nSystemCallFilter=@network ioctl\n\n[Install]\nWantedBy=multi-user.target\n"
+        );
+        let updated = set_unit_filter(&set_unit_filter(unit, &entries), &["read".to_string()]);
+        assert!(updated.contains("SystemCallFilter=read\n"));
+        assert!(!updated.contains("@network"));
+    }
+}
@@ -1,5 +1,7 @@
 //! Service definition types and states for the init system.
 
+use crate::accounting::ResourceUsage;
+use crate::timesync;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -148,10 +150,16 @@ pub struct TimerConfig {
     /// Whether timer is persistent (triggers missed runs on startup)
     #[serde(default)]
     pub persistent: bool,
-    /// Accuracy/randomization window
+    /// Window the elapse may slide later within, so timers on one
+    /// machine wake together
     #[serde(default = "default_timer_accuracy")]
     #[serde(with = "humantime_serde")]
     pub accuracy: Duration,
+    /// Upper bound of an extra per-machine delay, spreading the same timer
+    /// across many machines
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub randomized_delay: Duration,
 }
 
 fn default_timer_accuracy() -> Duration {
@@ -167,30 +175,290 @@ impl Default for TimerConfig {
             on_unit_inactive: None,
             persistent: false,
             accuracy: default_timer_accuracy(),
+            randomized_delay: Duration::ZERO,
         }
     }
 }
 
-/// Watchdog configuration.
+/// Path activation configuration: start the service when a watched path
+/// appears or changes.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathConfig {
+    /// Start the service while any of these paths exist
+    #[serde(default)]
+    pub path_exists: Vec<PathBuf>,
+    /// Start the service when any of these files is written and closed,
+    /// created, moved or deleted
+    #[serde(default)]
+    pub path_changed: Vec<PathBuf>,
+    /// Start the service while any of these directories has entries
+    #[serde(default)]
+    pub directory_not_empty: Vec<PathBuf>,
+    /// Create missing watched directories
+    #[serde(default)]
+    pub make_directory: bool,
+    /// Window for the activation rate limit
+    #[serde(default = "default_trigger_limit_interval")]
+    #[serde(with = "humantime_serde")]
+    pub trigger_limit_interval: Duration,
+    /// Activations allowed per window before the watch gives up
+    #[serde(default = "default_trigger_limit_burst")]
+    pub trigger_limit_burst: u32,
+}
+
+fn default_trigger_limit_interval() -> Duration {
+    Duration::from_secs(2)
+}
+
+fn default_trigger_limit_burst() -> u32 {
+    200
+}
+
+impl Default for PathConfig {
+    fn default() -> Self {
+        Self {
+            path_exists: Vec::new(),
+            path_changed: Vec::new(),
+            directory_not_empty: Vec::new(),
+            make_directory: false,
+            trigger_limit_interval: default_trigger_limit_interval(),
+            trigger_limit_burst: default_trigger_limit_burst(),
+        }
+    }
+}
+
+/// Watchdog configuration.
+///
+/// A service that misses its deadline gets `action`. If it misses the next
+/// one too without pinging in between, it gets the first of `escalate`,
+/// then the second, and so on; the last action repeats.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct WatchdogConfig {
     /// Watchdog timeout - service must ping within this interval
     #[serde(with = "humantime_serde")]
     pub timeout: Duration,
-    /// Action on timeout: restart, kill, none
-    #[serde(default = "default_watchdog_action")]
-    pub action: String,
+    /// Action on the first timeout
+    #[serde(default)]
+    pub action: WatchdogAction,
+    /// Actions on further consecutive timeouts
+    #[serde(default)]
+    pub escalate: Vec<WatchdogAction>,
+    /// Command of the `run` action
+    #[serde(default)]
+    pub recovery_command: Option<String>,
+}
+
+impl WatchdogConfig {
+    /// Action on the `failures`th consecutive timeout, counting from 1.
+    pub fn action_for(&self, failures: u32) -> WatchdogAction {
+        match failures.saturating_sub(2) as usize {
+            _ if failures <= 1 || self.escalate.is_empty() => self.action,
+            n => self.escalate[n.min(self.escalate.len() - 1)],
+        }
+    }
+}
+
+/// What to do when a service misses its watchdog deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum WatchdogAction {
+    /// Only record the timeout
+    None,
+    /// Stop the service gracefully and start it again
+    #[default]
+    Restart,
+    /// Kill the service with SIGKILL and start it again
+    #[serde(alias = "kill")]
+    RestartForce,
+    /// Reboot the system
+    Reboot,
+    /// Run the recovery command
+    Run,
 }
 
-fn default_watchdog_action() -> String {
-    "restart".to_string()
+impl std::fmt::Display for WatchdogAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WatchdogAction::None => write!(f, "none"),
+            WatchdogAction::Restart => write!(f, "restart"),
+            WatchdogAction::RestartForce => write!(f, "restart-force"),
+            WatchdogAction::Reboot => write!(f, "reboot"),
+            WatchdogAction::Run => write!(f, "run"),
+        }
+    }
+}
+
+impl std::str::FromStr for WatchdogAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(WatchdogAction::None),
+            "restart" => Ok(WatchdogAction::Restart),
+            "restart-force" | "kill" => Ok(WatchdogAction::RestartForce),
+            "reboot" => Ok(WatchdogAction::Reboot),
+            "run" => Ok(WatchdogAction::Run),
+            other => Err(format!("unknown watchdog action '{}'", other)),
+        }
+    }
+}
+
+/// Terminal of a service whose standard input or output is a TTY.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TtyConfig {
+    /// Terminal device
+    #[serde(default = "default_tty_path")]
+    pub path: PathBuf,
+    /// Reset terminal settings before and after use
+    #[serde(default)]
+    pub reset: bool,
+    /// Hang up other processes using the terminal before and after use
+    #[serde(default)]
+    pub vhangup: bool,
+}
+
+fn default_tty_path() -> PathBuf {
+    PathBuf::from("/dev/console")
+}
+
+impl Default for TtyConfig {
+    fn default() -> Self {
+        Self {
+            path: default_tty_path(),
+            reset: false,
+            vhangup: false,
+        }
+    }
+}
+
+/// Network isolation of a service.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkConfig {
+    /// Run in a network namespace of its own, connected to the host by a
+    /// veth pair
+    #[serde(default)]
+    pub private_network: bool,
+    /// Masquerade traffic from the namespace out through the host
+    #[serde(default)]
+    pub nat: bool,
+    /// Host ports forwarded into the namespace
+    #[serde(default)]
+    pub published_ports: Vec<PublishedPort>,
+}
+
+/// System call filtering of a service.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeccompConfig {
+    /// Allowed system calls and `@group`s; empty for no filter
+    #[serde(default)]
+    pub system_call_filter: Vec<String>,
+    /// Log calls outside the filter instead of refusing them, and learn
+    /// them
+    #[serde(default)]
+    pub learn: bool,
+}
+
+impl SeccompConfig {
+    /// Whether a filter is installed.
+    pub fn enabled(&self) -> bool {
+        self.learn || !self.system_call_filter.is_empty()
+    }
+}
+
+/// Mandatory access control label of a service. A label prefixed with `-`
+/// is skipped if it cannot be applied.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecurityConfig {
+    /// AppArmor profile to run under
+    #[serde(default)]
+    pub apparmor_profile: Option<String>,
+    /// SELinux context to run in
+    #[serde(default)]
+    pub selinux_context: Option<String>,
+}
+
+/// A host port forwarded to a privately networked service, written as
+/// `[host:]service[/tcp|/udp]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PublishedPort {
+    pub host_port: u16,
+    pub service_port: u16,
+    /// Whether the port is UDP rather than TCP
+    pub udp: bool,
+}
+
+impl PublishedPort {
+    pub fn protocol(&self) -> &'static str {
+        if self.udp {
+            "udp"
+        } else {
+            "tcp"
+        }
+    }
+}
+
+impl std::str::FromStr for PublishedPort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ports, udp) = match s.rsplit_once('/') {
+            Some((ports, "tcp")) => (ports, false),
+            Some((ports, "udp")) => (ports, true),
+            Some((_, protocol)) => return Err(format!("unknown protocol '{}'", protocol)),
+            None => (s, false),
+        };
+        let port = |p: &str| {
+            p.parse::<u16>()
+                .ok()
+                .filter(|&p| p != 0)
+                .ok_or_else(|| format!("invalid port '{}'", p))
+        };
+        let (host_port, service_port) = match ports.split_once(':') {
+            Some((host, service)) => (port(host)?, port(service)?),
+            None => (port(ports)?, port(ports)?),
+        };
+        Ok(Self {
+            host_port,
+            service_port,
+            udp,
+        })
+    }
+}
+
+impl TryFrom<String> for PublishedPort {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<PublishedPort> for String {
+    fn from(port: PublishedPort) -> Self {
+        port.to_string()
+    }
+}
+
+impl std::fmt::Display for PublishedPort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}/{}",
+            self.host_port,
+            self.service_port,
+            self.protocol()
+        )
+    }
 }
 
 impl Default for WatchdogConfig {
     fn default() -> Self {
         Self {
             timeout: Duration::from_secs(30),
-            action: default_watchdog_action(),
+            action: WatchdogAction::default(),
+            escalate: Vec::new(),
+            recovery_command: None,
         }
     }
 }
@@ -272,6 +540,8 @@ pub enum ServiceState {
     Failed,
     /// Service is reloading configuration
     Reloading,
+    /// Packages the service requires are not installed
+    Uninstallable,
 }
 
 impl std::fmt::Display for ServiceState {
@@ -284,6 +554,7 @@ impl std::fmt::Display for ServiceState {
             ServiceState::Stopped => write!(f, "stopped"),
             ServiceState::Failed => write!(f, "failed"),
             ServiceState::Reloading => write!(f, "reloading"),
+            ServiceState::Uninstallable => write!(f, "uninstallable"),
         }
     }
 }
@@ -325,6 +596,9 @@ pub struct ServiceDefinition {
     /// Services that must start before this one
     #[serde(default)]
     pub after: Vec<String>,
+    /// Packages that must be installed, as `category/name`
+    #[serde(default)]
+    pub requires_package: Vec<String>,
     /// Restart policy
     #[serde(default)]
     pub restart: RestartPolicy,
@@ -343,6 +617,9 @@ pub struct ServiceDefinition {
     /// Whether to enable this service by default
     #[serde(default)]
     pub enabled: bool,
+    /// Targets the service is enabled for (WantedBy, RequiredBy)
+    #[serde(default)]
+    pub wanted_by: Vec<String>,
     /// Health check configuration
     #[serde(default)]
     pub health_check: Option<HealthCheck>,
@@ -352,21 +629,50 @@ pub struct ServiceDefinition {
     /// Socket activation configuration
     #[serde(default)]
     pub sockets: Vec<SocketConfig>,
+    /// Stop the socket-activated service after it has been idle this long;
+    /// the next connection starts it again
+    #[serde(default)]
+    #[serde(with = "option_humantime_serde")]
+    pub exit_idle_time: Option<Duration>,
     /// Timer configuration for scheduled execution
     #[serde(default)]
     pub timer: Option<TimerConfig>,
+    /// Path activation configuration
+    #[serde(default)]
+    pub path: Option<PathConfig>,
     /// Watchdog configuration
     #[serde(default)]
     pub watchdog: Option<WatchdogConfig>,
     /// Whether this service is a template (name contains @)
     #[serde(default)]
     pub template: bool,
-    /// Standard output handling: inherit, null, journal, file:/path
+    /// Standard input: null, tty, tty-force, tty-fail, socket
+    #[serde(default = "default_stdin")]
+    pub standard_input: String,
+    /// Standard output handling: inherit, null, journal, tty, file:/path
+    ///
+    /// With a TTY or socket on standard input, inherit writes to it.
     #[serde(default = "default_stdout")]
     pub standard_output: String,
-    /// Standard error handling: inherit, null, journal, file:/path
+    /// Standard error handling: inherit, null, journal, tty, file:/path
     #[serde(default = "default_stderr")]
     pub standard_error: String,
+    /// Terminal used for tty standard input and output
+    #[serde(default)]
+    pub tty: TtyConfig,
+    /// Network isolation
+    #[serde(default)]
+    pub network: NetworkConfig,
+    /// System call filter
+    #[serde(default)]
+    pub seccomp: SeccompConfig,
+    /// AppArmor or SELinux label
+    #[serde(default)]
+    pub security: SecurityConfig,
+}
+
+fn default_stdin() -> String {
+    "null".to_string()
 }
 
 fn default_stdout() -> String {
@@ -428,22 +734,50 @@ impl ServiceDefinition {
             wants: Vec::new(),
             before: Vec::new(),
             after: Vec::new(),
+            requires_package: Vec::new(),
             restart: RestartPolicy::default(),
             restart_sec: default_restart_sec(),
             timeout_start_sec: default_timeout_start(),
             timeout_stop_sec: default_timeout_stop(),
             enabled: false,
+            wanted_by: Vec::new(),
             health_check: None,
             resource_limits: None,
             sockets: Vec::new(),
+            exit_idle_time: None,
             timer: None,
+            path: None,
             watchdog: None,
             template: false,
+            standard_input: default_stdin(),
             standard_output: default_stdout(),
             standard_error: default_stderr(),
+            tty: TtyConfig::default(),
+            network: NetworkConfig::default(),
+            seccomp: SeccompConfig::default(),
+            security: SecurityConfig::default(),
         }
     }
 
+    /// Whether the service waits for the clock to be synchronized.
+    pub fn after_time_sync(&self) -> bool {
+        self.after.iter().any(|unit| timesync::is_time_sync(unit))
+    }
+
+    /// Whether the service synchronizes the clock, ordered before
+    /// time-sync.
+    pub fn synchronizes_time(&self) -> bool {
+        self.before.iter().any(|unit| timesync::is_time_sync(unit))
+    }
+
+    /// Whether standard input is a controlling terminal.
+    pub fn stdin_is_tty(&self) -> bool {
+        matches!(
+            self.standard_input.as_str(),
+            "tty" | "tty-force" | "tty-fail"
+        )
+    }
+
     /// Check if this is a template service.
     pub fn is_template(&self) -> bool {
         self.template || self.name.contains('@')
@@ -462,6 +796,7 @@ impl ServiceDefinition {
         if let Some(ref mut cmd) = def.exec_reload {
             *cmd = cmd.replace("%i", instance);
         }
+        def.tty.path = PathBuf::from(def.tty.path.to_string_lossy().replace("%i", instance));
         def
     }
 
@@ -542,10 +877,29 @@ pub struct ServiceInstance {
     pub last_health_check: Option<DateTime<Utc>>,
     /// Last watchdog ping time
     pub last_watchdog_ping: Option<DateTime<Utc>>,
+    /// Watchdog timeouts since the last ping
+    #[serde(default)]
+    pub watchdog_failures: u32,
+    /// When the watchdog last timed out
+    #[serde(default)]
+    pub last_watchdog_timeout: Option<DateTime<Utc>>,
+    /// Status the service last reported about itself
+    #[serde(default)]
+    pub status_text: Option<String>,
+    /// Last connection to a socket-activated service, or report of it
+    /// being busy
+    #[serde(default)]
+    pub last_activity: Option<DateTime<Utc>>,
     /// Whether the service is masked
     pub masked: bool,
     /// Boot time for this service (for analyze)
     pub boot_duration_ms: Option<u64>,
+    /// Resources used by processes of the service that have exited
+    #[serde(default)]
+    pub usage: ResourceUsage,
+    /// Required packages found missing when last checked
+    #[serde(default)]
+    pub missing_packages: Vec<String>,
 }
 
 impl ServiceInstance {
@@ -568,11 +922,25 @@ impl ServiceInstance {
             health_failures: 0,
             last_health_check: None,
             last_watchdog_ping: None,
+            watchdog_failures: 0,
+            last_watchdog_timeout: None,
+            status_text: None,
+            last_activity: None,
             masked: false,
             boot_duration_ms: None,
+            usage: ResourceUsage::default(),
+            missing_packages: Vec::new(),
         }
     }
 
+    /// Resources used by the service across all its runs, including the
+    /// running main process.
+    pub fn total_usage(&self) -> ResourceUsage {
+        self.main_pid
+            .and_then(|pid| ResourceUsage::sample(&self.name, pid))
+            .map_or(self.usage, |live| self.usage.add(&live))
+    }
+
     /// Check if the service can restart based on rate limiting.
     ///
     /// Returns true if restart is allowed, false if rate limited.
@@ -656,6 +1024,21 @@ pub struct ServiceStatus {
     pub requires: Vec<String>,
     /// Soft dependencies (wants)
     pub wants: Vec<String>,
+    /// Resources used across all runs of the service
+    #[serde(default)]
+    pub usage: ResourceUsage,
+    /// AppArmor or SELinux label of the main process
+    #[serde(default)]
+    pub security_label: Option<String>,
+    /// Required packages that are not installed
+    #[serde(default)]
+    pub missing_packages: Vec<String>,
+    /// Status the service last reported about itself
+    #[serde(default)]
+    pub status_text: Option<String>,
+    /// Watchdog timeouts since the service last pinged
+    #[serde(default)]
+    pub watchdog_failures: u32,
 }
 
 impl ServiceStatus {
@@ -682,6 +1065,11 @@ impl ServiceStatus {
             enabled: def.enabled,
             requires: def.requires.clone(),
             wants: def.wants.clone(),
+            usage: instance.total_usage(),
+            security_label: instance.main_pid.and_then(crate::lsm::process_label),
+            missing_packages: instance.missing_packages.clone(),
+            status_text: instance.status_text.clone(),
+            watchdog_failures: instance.watchdog_failures,
         }
     }
 }
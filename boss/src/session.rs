@@ -0,0 +1,216 @@
+//! Login session tracking.
+//!
+//! Sessions come from two places. A supervised getty whose process has
+//! become login with a shell below it, or a shell itself, is a session on
+//! its terminal; the user is the shell's owner and the start time is when
+//! login took ownership of the terminal. Logins that do not go through a
+//! supervised getty, such as sshd, are recorded by a pam_exec hook running
+//! `boss pam-session`, which writes one file per session under
+//! /run/buckos/sessions. Records whose process has gone are dropped when
+//! sessions are listed, so a missed close does not linger.
+
+use crate::error::{Error, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// Directory PAM session records are kept in.
+pub const SESSIONS_DIR: &str = "/run/buckos/sessions";
+
+/// Process names of a getty still waiting for a login.
+const GETTY_NAMES: &[&str] = &["agetty", "getty", "mingetty", "fgetty", "login"];
+
+/// How a session was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SessionSource {
+    /// A supervised getty service
+    Getty,
+    /// A pam_exec hook
+    Pam,
+}
+
+/// A logged in user.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    pub user: String,
+    pub uid: u32,
+    /// Terminal, without /dev/
+    pub tty: Option<String>,
+    /// Host logged in from
+    pub remote_host: Option<String>,
+    pub started: DateTime<Utc>,
+    /// Session leader
+    pub pid: u32,
+    /// Getty service or PAM service name
+    pub service: String,
+    pub source: SessionSource,
+}
+
+/// Records of PAM sessions.
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn record(&self, pid: u32) -> PathBuf {
+        self.dir.join(format!("{}.json", pid))
+    }
+
+    /// Record a session opened by the process `session.pid`.
+    pub fn open(&self, session: &Session) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let json = serde_json::to_string(session)
+            .map_err(|e| Error::Other(format!("Failed to serialize session: {}", e)))?;
+        std::fs::write(self.record(session.pid), json)?;
+        Ok(())
+    }
+
+    /// Remove the session of `pid`, if recorded.
+    pub fn close(&self, pid: u32) -> Result<()> {
+        match std::fs::remove_file(self.record(pid)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Recorded sessions whose process is still running, removing the rest.
+    pub fn list(&self) -> Vec<Session> {
+        let mut sessions = Vec::new();
+        for entry in std::fs::read_dir(&self.dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            let session = std::fs::read_to_string(&path)
+                .ok()
+                .and_then(|json| serde_json::from_str::<Session>(&json).ok());
+            match session {
+                Some(session) if process_alive(session.pid) => sessions.push(session),
+                _ => {
+                    let _ = std::fs::remove_file(&path);
+                }
+            }
+        }
+        sessions
+    }
+}
+
+impl Default for SessionStore {
+    fn default() -> Self {
+        Self::new(SESSIONS_DIR)
+    }
+}
+
+/// The session on the terminal of a getty service, if someone has logged
+/// in there.
+pub fn getty_session(service: &str, tty: &Path, pid: u32) -> Option<Session> {
+    let (name, mut uid) = process_status(pid)?;
+    if name == "login" {
+        // login waits for the shell it forked, if authentication is done
+        let children = std::fs::read_to_string(format!("/proc/{}/task/{}/children", pid, pid));
+        let shell = children.ok()?.split_whitespace().next()?.parse().ok()?;
+        uid = process_status(shell)?.1;
+    } else if GETTY_NAMES.contains(&name.as_str()) {
+        return None;
+    }
+    // login chowns the terminal to the user as the session starts
+    let started = std::fs::metadata(tty)
+        .ok()
+        .and_then(|meta| DateTime::from_timestamp(meta.ctime(), 0))
+        .unwrap_or_else(Utc::now);
+    Some(Session {
+        user: user_name(uid),
+        uid,
+        tty: Some(tty_name(tty)),
+        remote_host: None,
+        started,
+        pid,
+        service: service.to_string(),
+        source: SessionSource::Getty,
+    })
+}
+
+fn process_status(pid: u32) -> Option<(String, u32)> {
+    parse_status(&std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?)
+}
+
+/// Process name and real uid from /proc/<pid>/status.
+fn parse_status(status: &str) -> Option<(String, u32)> {
+    let field = |key: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(key))
+            .map(str::trim)
+    };
+    let name = field("Name:")?.to_string();
+    let uid = field("Uid:")?.split_whitespace().next()?.parse().ok()?;
+    Some((name, uid))
+}
+
+/// Terminal name as `who` shows it, e.g. `tty1` or `pts/0`.
+pub fn tty_name(tty: &Path) -> String {
+    tty.strip_prefix("/dev")
+        .unwrap_or(tty)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Name of a user, or the uid if it has none.
+pub fn user_name(uid: u32) -> String {
+    nix::unistd::User::from_uid(uid.into())
+        .ok()
+        .flatten()
+        .map_or_else(|| uid.to_string(), |user| user.name)
+}
+
+fn process_alive(pid: u32) -> bool {
+    Path::new(&format!("/proc/{}", pid)).exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(pid: u32) -> Session {
+        Session {
+            user: "alice".to_string(),
+            uid: 1000,
+            tty: Some("pts/0".to_string()),
+            remote_host: Some("10.0.0.5".to_string()),
+            started: Utc::now(),
+            pid,
+            service: "sshd".to_string(),
+            source: SessionSource::Pam,
+        }
+    }
+
+    #[test]
+    fn test_store_drops_dead_sessions() {
+        let dir = std::env::temp_dir().join(format!("boss-sessions-{}", std::process::id()));
+        let store = SessionStore::new(&dir);
+        let alive = session(std::process::id());
+        store.open(&alive).unwrap();
+        store.open(&session(u32::MAX)).unwrap();
+
+        assert_eq!(store.list(), vec![alive.clone()]);
+        assert!(!dir.join(format!("{}.json", u32::MAX)).exists());
+        store.close(alive.pid).unwrap();
+        store.close(alive.pid).unwrap();
+        assert!(store.list().is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_status() {
+        let status =
+            "Name:\tbash\nUmask:\t0022\nState:\tS (sleeping)\nUid:\t1000\t1000\t1000\t1000\n";
+        assert_eq!(parse_status(status), Some(("bash".to_string(), 1000)));
+        assert_eq!(parse_status("Name:\tbash\n"), None);
+        assert_eq!(tty_name(Path::new("/dev/pts/3")), "pts/3");
+        assert_eq!(user_name(0), "root");
+    }
+}
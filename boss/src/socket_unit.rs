@@ -0,0 +1,425 @@
+//! Socket activation.
+//!
+//! Init binds the sockets of a service with a `[Socket]` section before
+//! services start, and starts the service when a connection or datagram
+//! arrives. The service gets the listening sockets the way systemd passes
+//! them, as file descriptors 3 and up with LISTEN_FDS and LISTEN_PID set,
+//! so sd_listen_fds() works unchanged. Init keeps its own copies, so a
+//! connection made while the service is down waits in the backlog until it
+//! is started again.
+//!
+//! With `ExitIdleTimeSec`, a service that got no new connection for that
+//! long is stopped, and the next connection starts it again, so a rarely
+//! used daemon only takes memory while it is used. New connections are
+//! seen as the listening socket polling readable. A service that keeps
+//! long-lived connections open reports that it is still busy with
+//! `boss notify <name> --busy`.
+//!
+//! Only `Accept=no` is supported: one service process handles every
+//! connection.
+
+use crate::error::{Error, Result};
+use crate::service::SocketConfig;
+use std::collections::HashMap;
+use std::ffi::{CString, OsStr, OsString};
+use std::net::{SocketAddr, TcpListener, UdpSocket};
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixDatagram, UnixListener};
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::task::Poll;
+use tokio::io::unix::AsyncFd;
+use tokio::io::Interest;
+
+/// File descriptor of the first passed socket, after standard input,
+/// output and error.
+pub const LISTEN_FDS_START: RawFd = 3;
+
+/// The bound sockets of one socket-activated service.
+pub struct ServiceSockets {
+    sockets: Vec<AsyncFd<OwnedFd>>,
+}
+
+impl ServiceSockets {
+    /// Bind every socket of a service, in order.
+    pub fn bind(configs: &[SocketConfig]) -> Result<Self> {
+        let sockets = configs
+            .iter()
+            .map(|config| {
+                let fd = bind_socket(config)
+                    .map_err(|e| Error::Other(format!("{}: {}", config.listen, e)))?;
+                Ok(AsyncFd::with_interest(fd, Interest::READABLE)?)
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { sockets })
+    }
+
+    /// Whether a connection or datagram is waiting on any socket.
+    pub fn pending(&self) -> bool {
+        let mut fds: Vec<libc::pollfd> = self
+            .sockets
+            .iter()
+            .map(|socket| libc::pollfd {
+                fd: socket.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            })
+            .collect();
+        let ready = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, 0) };
+        ready > 0
+    }
+
+    /// Wait until a connection or datagram arrives on any socket.
+    ///
+    /// Readiness is reported once per arrival, whether or not the service
+    /// takes the connection before this returns.
+    pub async fn readable(&self) -> Result<()> {
+        std::future::poll_fn(|cx| {
+            for socket in &self.sockets {
+                if let Poll::Ready(guard) = socket.poll_read_ready(cx) {
+                    guard?.clear_ready();
+                    return Poll::Ready(Ok(()));
+                }
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Copies of the sockets to pass to the service.
+    pub fn fds(&self) -> Result<Vec<OwnedFd>> {
+        self.sockets
+            .iter()
+            .map(|socket| Ok(socket.get_ref().try_clone()?))
+            .collect()
+    }
+}
+
+/// Bind and listen on one socket, blocking as services expect.
+fn bind_socket(config: &SocketConfig) -> std::io::Result<OwnedFd> {
+    let invalid = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidInput, message);
+    if config.accept {
+        return Err(invalid("Accept=yes is not supported"));
+    }
+    let path = Path::new(&config.listen);
+    let unix = path.is_absolute();
+    if unix {
+        prepare_socket_path(path)?;
+    }
+    let fd = match (config.socket_type.as_str(), unix) {
+        ("stream", true) => OwnedFd::from(UnixListener::bind(path)?),
+        ("stream", false) => OwnedFd::from(bind_inet(&config.listen, TcpListener::bind)?),
+        ("dgram", true) => OwnedFd::from(UnixDatagram::bind(path)?),
+        ("dgram", false) => OwnedFd::from(bind_inet(&config.listen, UdpSocket::bind)?),
+        (other, _) => {
+            return Err(invalid(&format!(
+                "{} sockets are not supported for activation",
+                other
+            )))
+        }
+    };
+    if config.socket_type == "stream" {
+        // The standard library listens with its own backlog
+        if unsafe { libc::listen(fd.as_raw_fd(), config.backlog as libc::c_int) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    if unix {
+        if let Some(mode) = config.socket_mode {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+        }
+        chown_socket(path, config)?;
+    }
+    Ok(fd)
+}
+
+/// Create the directory of a Unix socket and remove a stale socket file.
+fn prepare_socket_path(path: &Path) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Bind an IP socket; a bare port listens on every address.
+fn bind_inet<T>(
+    listen: &str,
+    bind: impl Fn(SocketAddr) -> std::io::Result<T>,
+) -> std::io::Result<T> {
+    if let Ok(port) = listen.parse::<u16>() {
+        // Both IPv6 and IPv4, unless IPv6 is disabled
+        return bind(SocketAddr::from(([0u16; 8], port)))
+            .or_else(|_| bind(SocketAddr::from(([0u8; 4], port))));
+    }
+    let addr = listen.parse::<SocketAddr>().map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "expected a path, a port or an address:port",
+        )
+    })?;
+    bind(addr)
+}
+
+/// Give a Unix socket the owner of SocketUser and SocketGroup.
+fn chown_socket(path: &Path, config: &SocketConfig) -> std::io::Result<()> {
+    use nix::unistd::{Group, User};
+    let not_found = |what: &str, name: &str| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("no such {}: {}", what, name),
+        )
+    };
+    let uid = match &config.socket_user {
+        Some(name) => Some(
+            User::from_name(name)?
+                .ok_or_else(|| not_found("user", name))?
+                .uid,
+        ),
+        None => None,
+    };
+    let gid = match &config.socket_group {
+        Some(name) => Some(
+            Group::from_name(name)?
+                .ok_or_else(|| not_found("group", name))?
+                .gid,
+        ),
+        None => None,
+    };
+    if uid.is_some() || gid.is_some() {
+        nix::unistd::chown(path, uid, gid)?;
+    }
+    Ok(())
+}
+
+/// Move passed sockets to descriptors 3 and up, in the child between fork
+/// and exec, and note the child's PID for [`ListenExec`].
+pub(crate) fn install_listen_fds(fds: &[RawFd], pid: &AtomicU32) -> std::io::Result<()> {
+    // Out of the way first, so moving one cannot overwrite another
+    let above = LISTEN_FDS_START + fds.len() as RawFd;
+    let mut moved = Vec::with_capacity(fds.len());
+    for &fd in fds {
+        let high = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, above) };
+        if high < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        moved.push(high);
+    }
+    // dup2 clears close-on-exec on the new descriptor
+    for (target, fd) in (LISTEN_FDS_START..).zip(moved) {
+        if unsafe { libc::dup2(fd, target) } < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    pid.store(std::process::id(), Ordering::Relaxed);
+    Ok(())
+}
+
+/// The exec of a socket-activated service.
+///
+/// LISTEN_PID must be the PID of the service, which is only known after
+/// fork, but the standard library execs with the environment it prepared
+/// before. So the service is exec'd here instead, as the last step between
+/// fork and exec, with the environment of the command plus LISTEN_PID.
+pub(crate) struct ListenExec {
+    program: CString,
+    argv: Vec<CString>,
+    env: Vec<CString>,
+    pid: Arc<AtomicU32>,
+}
+
+impl ListenExec {
+    /// Prepare the exec of `cmd`, whose PID [`install_listen_fds`] notes
+    /// in `pid`.
+    pub(crate) fn new(cmd: &Command, pid: Arc<AtomicU32>) -> std::io::Result<Self> {
+        let mut env: HashMap<OsString, OsString> = std::env::vars_os().collect();
+        for (key, value) in cmd.get_envs() {
+            match value {
+                Some(value) => env.insert(key.to_owned(), value.to_owned()),
+                None => env.remove(key),
+            };
+        }
+        env.remove(OsStr::new("LISTEN_PID"));
+
+        // Searched for in the service's PATH, as the standard library does
+        let program = Path::new(cmd.get_program());
+        let program = match env.get(OsStr::new("PATH")) {
+            Some(path) if program.components().count() == 1 => std::env::split_paths(path)
+                .map(|dir| dir.join(program))
+                .find(|candidate| candidate.is_file())
+                .unwrap_or_else(|| program.to_path_buf()),
+            _ => program.to_path_buf(),
+        };
+
+        let cstring = |s: &OsStr| CString::new(s.as_bytes());
+        let argv = std::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .map(cstring)
+            .collect::<std::result::Result<_, _>>()?;
+        let env = env
+            .iter()
+            .map(|(key, value)| {
+                let mut var = key.clone();
+                var.push("=");
+                var.push(value);
+                cstring(&var)
+            })
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(Self {
+            program: cstring(program.as_os_str())?,
+            argv,
+            env,
+            pid,
+        })
+    }
+
+    /// Exec the service; only returns on failure.
+    pub(crate) fn exec(&self) -> std::io::Result<()> {
+        let listen_pid = CString::new(format!("LISTEN_PID={}", self.pid.load(Ordering::Relaxed)))?;
+        let argv: Vec<*const libc::c_char> = self
+            .argv
+            .iter()
+            .map(|arg| arg.as_ptr())
+            .chain(std::iter::once(std::ptr::null()))
+            .collect();
+        let envp: Vec<*const libc::c_char> = self
+            .env
+            .iter()
+            .map(|var| var.as_ptr())
+            .chain([listen_pid.as_ptr(), std::ptr::null()])
+            .collect();
+        unsafe { libc::execve(self.program.as_ptr(), argv.as_ptr(), envp.as_ptr()) };
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bind_sockets() {
+        let dir = std::env::temp_dir().join(format!("boss-sockets-{}", std::process::id()));
+        let path = dir.join("run/echo.sock");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        // Left over from an earlier boot
+        std::fs::write(&path, "").unwrap();
+        let configs = vec![
+            SocketConfig {
+                listen: path.display().to_string(),
+                socket_mode: Some(0o600),
+                ..SocketConfig::default()
+            },
+            SocketConfig {
+                socket_type: "dgram".to_string(),
+                listen: "127.0.0.1:0".to_string(),
+                ..SocketConfig::default()
+            },
+        ];
+        let sockets = ServiceSockets::bind(&configs).unwrap();
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        assert!(!sockets.pending());
+
+        let _client = std::os::unix::net::UnixStream::connect(&path).unwrap();
+        assert!(sockets.pending());
+        tokio::time::timeout(std::time::Duration::from_secs(5), sockets.readable())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(sockets.fds().unwrap().len(), 2);
+
+        let accept = SocketConfig {
+            listen: dir.join("accept.sock").display().to_string(),
+            accept: true,
+            ..SocketConfig::default()
+        };
+        let err = ServiceSockets::bind(&[accept]).err().unwrap();
+        assert!(err.to_string().contains("Accept=yes is not supported"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_idle_service_stops_and_restarts() {
+        use crate::manager::ServiceManager;
+        use std::time::Duration;
+
+        let dir = std::env::temp_dir().join(format!("boss-socket-idle-{}", std::process::id()));
+        let services = dir.join("services");
+        std::fs::create_dir_all(&services).unwrap();
+        let (socket, seen) = (dir.join("echo.sock"), dir.join("seen"));
+        let script = dir.join("echo");
+        std::fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\necho $LISTEN_FDS $LISTEN_PID $$ > {}\nread -r line <&3\nexec sleep 30\n",
+                seen.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::write(
+            services.join("echo.service"),
+            format!(
+                "[Service]\nExecStart={}\n[Socket]\nListenDatagram={}\nExitIdleTimeSec=300ms\n",
+                script.display(),
+                socket.display()
+            ),
+        )
+        .unwrap();
+
+        let manager = ServiceManager::new(services);
+        manager.load_services().await.unwrap();
+        manager.start_socket_listeners().await;
+        let running = || async { manager.get_status("echo").await.unwrap().main_pid.is_some() };
+        let wait_for = |want: bool| async move {
+            for _ in 0..100 {
+                if running().await == want {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            panic!(
+                "service never became {}",
+                if want { "active" } else { "inactive" }
+            );
+        };
+        assert!(!running().await);
+
+        // The service reads the datagram, so it is not waiting any more once
+        // the service goes idle
+        let client = std::os::unix::net::UnixDatagram::unbound().unwrap();
+        client.send_to(b"\n", &socket).unwrap();
+        wait_for(true).await;
+        let pid = manager.get_status("echo").await.unwrap().main_pid.unwrap();
+        for _ in 0..100 {
+            if std::fs::read_to_string(&seen).is_ok_and(|s| s.ends_with('\n')) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(
+            std::fs::read_to_string(&seen).unwrap(),
+            format!("1 {} {}\n", pid, pid)
+        );
+
+        // Stopped once idle, and started again by the next datagram
+        wait_for(false).await;
+        let logs = manager.get_logs("echo", None).await;
+        assert!(logs
+            .iter()
+            .any(|e| e.message.contains("without connections")));
+        client.send_to(b"\n", &socket).unwrap();
+        wait_for(true).await;
+        manager.stop_service("echo").await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,410 @@
+//! Swap activation and zram setup.
+//!
+//! Swap devices and files listed in fstab are activated at boot with their
+//! `pri=` priorities, and deactivated at shutdown before the final sync.
+//! Small systems can also get compressed swap in RAM: the zram
+//! configurator sizes a zram device as a fraction of memory, picks its
+//! compression algorithm and writes the swap signature itself, so no
+//! zram-generator or mkswap is needed.
+
+use crate::error::{Error, Result};
+use serde::Serialize;
+use std::ffi::CString;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+// From <linux/swap.h>, which libc does not export
+const SWAP_FLAG_PREFER: i32 = 0x8000;
+const SWAP_FLAG_PRIO_MASK: i32 = 0x7fff;
+const SWAP_FLAG_DISCARD: i32 = 0x10000;
+
+/// Swap configuration of the init system.
+#[derive(Debug, Clone, Default)]
+pub struct SwapConfig {
+    /// fstab to activate swap entries from
+    pub fstab: Option<PathBuf>,
+    /// Compressed swap in RAM
+    pub zram: Option<ZramConfig>,
+}
+
+/// A zram swap device.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZramConfig {
+    /// Size as a fraction of total memory
+    pub fraction: f64,
+    /// Upper bound on the size in bytes
+    pub max_size: Option<u64>,
+    /// Compression algorithm, e.g. `zstd` or `lz4`
+    pub algorithm: String,
+    /// Swap priority, above disk swap so zram fills first
+    pub priority: i32,
+}
+
+impl Default for ZramConfig {
+    fn default() -> Self {
+        Self {
+            fraction: 0.5,
+            max_size: Some(4 << 30),
+            algorithm: "zstd".to_string(),
+            priority: 100,
+        }
+    }
+}
+
+impl ZramConfig {
+    /// Device size for a machine with `mem_total` bytes of memory, rounded
+    /// down to whole pages.
+    pub fn size_for(&self, mem_total: u64) -> u64 {
+        let size = (mem_total as f64 * self.fraction.max(0.0)) as u64;
+        let size = self.max_size.map_or(size, |max| size.min(max));
+        size - size % page_size()
+    }
+}
+
+/// A swap unit from an fstab entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwapUnit {
+    /// Device or file
+    pub what: PathBuf,
+    /// Priority from `pri=`
+    pub priority: Option<i32>,
+    /// Whether to discard freed pages (`discard`)
+    pub discard: bool,
+    /// Whether a failure to activate is expected (`nofail`)
+    pub nofail: bool,
+}
+
+impl SwapUnit {
+    /// Unit name in systemd style, e.g. `dev-sda2.swap`.
+    pub fn unit_name(&self) -> String {
+        let path = self.what.to_string_lossy();
+        let escaped: String = path
+            .trim_start_matches('/')
+            .chars()
+            .map(|c| match c {
+                '/' => "-".to_string(),
+                c if c.is_ascii_alphanumeric() || c == '_' || c == '.' => c.to_string(),
+                c => format!("\\x{:02x}", c as u32),
+            })
+            .collect();
+        format!("{}.swap", escaped)
+    }
+
+    /// Activate this swap.
+    pub fn activate(&self) -> Result<()> {
+        swapon(&self.what, self.priority, self.discard)
+    }
+}
+
+/// An active swap area, from /proc/swaps.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ActiveSwap {
+    pub path: PathBuf,
+    /// `partition` or `file`
+    pub kind: String,
+    pub size_bytes: u64,
+    pub used_bytes: u64,
+    pub priority: i32,
+}
+
+/// Swap entries of an fstab, skipping `noauto` ones.
+pub fn parse_fstab(contents: &str) -> Vec<SwapUnit> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 3 || fields[2] != "swap" {
+                return None;
+            }
+            let options: Vec<&str> = fields
+                .get(3)
+                .map(|o| o.split(',').collect())
+                .unwrap_or_default();
+            if options.contains(&"noauto") {
+                return None;
+            }
+            Some(SwapUnit {
+                what: resolve_spec(&unescape(fields[0])),
+                priority: options
+                    .iter()
+                    .find_map(|o| o.strip_prefix("pri="))
+                    .and_then(|p| p.parse().ok()),
+                discard: options
+                    .iter()
+                    .any(|o| *o == "discard" || o.starts_with("discard=")),
+                nofail: options.contains(&"nofail"),
+            })
+        })
+        .collect()
+}
+
+/// Decode the octal escapes fstab uses for whitespace, e.g. `\040`.
+fn unescape(field: &str) -> String {
+    let mut out = String::new();
+    let mut rest = field;
+    while let Some(i) = rest.find('\\') {
+        out.push_str(&rest[..i]);
+        let code = rest.get(i + 1..i + 4);
+        match code.and_then(|c| u8::from_str_radix(c, 8).ok()) {
+            Some(byte) => {
+                out.push(byte as char);
+                rest = &rest[i + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[i + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Resolve `UUID=`, `LABEL=`, `PARTUUID=` and `PARTLABEL=` specs to their
+/// /dev/disk symlinks.
+fn resolve_spec(spec: &str) -> PathBuf {
+    let by = [
+        ("UUID=", "by-uuid"),
+        ("LABEL=", "by-label"),
+        ("PARTUUID=", "by-partuuid"),
+        ("PARTLABEL=", "by-partlabel"),
+    ];
+    for (prefix, dir) in by {
+        if let Some(value) = spec.strip_prefix(prefix) {
+            return Path::new("/dev/disk").join(dir).join(value);
+        }
+    }
+    PathBuf::from(spec)
+}
+
+/// Active swap areas, from the contents of /proc/swaps.
+pub fn parse_proc_swaps(contents: &str) -> Vec<ActiveSwap> {
+    contents
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 5 {
+                return None;
+            }
+            Some(ActiveSwap {
+                path: PathBuf::from(unescape(fields[0])),
+                kind: fields[1].to_string(),
+                size_bytes: fields[2].parse::<u64>().ok()? * 1024,
+                used_bytes: fields[3].parse::<u64>().ok()? * 1024,
+                priority: fields[4].parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Currently active swap areas.
+pub fn active_swaps() -> Vec<ActiveSwap> {
+    std::fs::read_to_string("/proc/swaps")
+        .map(|s| parse_proc_swaps(&s))
+        .unwrap_or_default()
+}
+
+fn c_path(path: &Path) -> Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| Error::ConfigError(format!("invalid path {}", path.display())))
+}
+
+/// Enable a swap area.
+pub fn swapon(path: &Path, priority: Option<i32>, discard: bool) -> Result<()> {
+    let mut flags = 0;
+    if let Some(prio) = priority {
+        flags |= SWAP_FLAG_PREFER | (prio.max(0) & SWAP_FLAG_PRIO_MASK);
+    }
+    if discard {
+        flags |= SWAP_FLAG_DISCARD;
+    }
+    let path = c_path(path)?;
+    if unsafe { libc::swapon(path.as_ptr(), flags) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// Disable a swap area.
+pub fn swapoff(path: &Path) -> Result<()> {
+    let path = c_path(path)?;
+    if unsafe { libc::swapoff(path.as_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// Disable every active swap area, resetting zram devices so their memory
+/// is freed. Returns the areas that could not be disabled.
+pub fn deactivate_all() -> Vec<(PathBuf, Error)> {
+    let mut failed = Vec::new();
+    for swap in active_swaps() {
+        if let Err(e) = swapoff(&swap.path) {
+            failed.push((swap.path, e));
+            continue;
+        }
+        if let Some(name) = zram_name(&swap.path) {
+            let _ = std::fs::write(Path::new("/sys/block").join(name).join("reset"), "1");
+        }
+    }
+    failed
+}
+
+/// Name of a zram device such as `zram0`.
+fn zram_name(path: &Path) -> Option<&str> {
+    let name = path.strip_prefix("/dev").ok()?.to_str()?;
+    let id = name.strip_prefix("zram")?;
+    (!id.is_empty() && id.bytes().all(|b| b.is_ascii_digit())).then_some(name)
+}
+
+fn page_size() -> u64 {
+    nix::unistd::sysconf(nix::unistd::SysconfVar::PAGE_SIZE)
+        .ok()
+        .flatten()
+        .map_or(4096, |p| p as u64)
+}
+
+/// Total memory in bytes, from /proc/meminfo.
+pub fn mem_total() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let kb: u64 = meminfo
+        .lines()
+        .find_map(|l| l.strip_prefix("MemTotal:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+/// Swap signature for an area of `size` bytes: the first page, holding the
+/// version 1 header at offset 1024 and `SWAPSPACE2` at the end.
+pub fn swap_header(size: u64, page_size: u64, uuid: [u8; 16]) -> Vec<u8> {
+    let mut page = vec![0u8; page_size as usize];
+    let last_page = (size / page_size).saturating_sub(1) as u32;
+    page[1024..1028].copy_from_slice(&1u32.to_ne_bytes());
+    page[1028..1032].copy_from_slice(&last_page.to_ne_bytes());
+    // nr_badpages stays 0
+    page[1036..1052].copy_from_slice(&uuid);
+    let magic = b"SWAPSPACE2";
+    let at = page.len() - magic.len();
+    page[at..].copy_from_slice(magic);
+    page
+}
+
+/// Set up a zram device as swap and activate it, returning its path.
+pub fn setup_zram(config: &ZramConfig) -> Result<PathBuf> {
+    let mem = mem_total().ok_or_else(|| Error::Other("cannot read total memory".to_string()))?;
+    let size = config.size_for(mem);
+    if size == 0 {
+        return Err(Error::ConfigError("zram size is zero".to_string()));
+    }
+
+    let name = free_zram_device()?;
+    let sys = Path::new("/sys/block").join(&name);
+
+    // The algorithm must be set before the size
+    let supported = std::fs::read_to_string(sys.join("comp_algorithm")).unwrap_or_default();
+    let algorithms: Vec<&str> = supported
+        .split_whitespace()
+        .map(|a| a.trim_matches(|c| c == '[' || c == ']'))
+        .collect();
+    if !algorithms.contains(&config.algorithm.as_str()) {
+        return Err(Error::ConfigError(format!(
+            "zram does not support compression algorithm '{}' (available: {})",
+            config.algorithm,
+            algorithms.join(" ")
+        )));
+    }
+    std::fs::write(sys.join("comp_algorithm"), &config.algorithm)?;
+    std::fs::write(sys.join("disksize"), size.to_string())?;
+
+    let device = Path::new("/dev").join(&name);
+    let header = swap_header(size, page_size(), *uuid::Uuid::new_v4().as_bytes());
+    let mut dev = OpenOptions::new().write(true).open(&device)?;
+    dev.write_all(&header)?;
+    dev.sync_all()?;
+
+    swapon(&device, Some(config.priority), true)?;
+    Ok(device)
+}
+
+/// An unused zram device, adding one through zram-control if needed.
+fn free_zram_device() -> Result<String> {
+    let unused = |name: &str| {
+        std::fs::read_to_string(Path::new("/sys/block").join(name).join("disksize"))
+            .is_ok_and(|s| s.trim() == "0")
+    };
+    if unused("zram0") {
+        return Ok("zram0".to_string());
+    }
+    match std::fs::read_to_string("/sys/class/zram-control/hot_add") {
+        Ok(id) => Ok(format!("zram{}", id.trim())),
+        Err(_) if !Path::new("/sys/block/zram0").exists() => Err(Error::Other(
+            "zram is not available (is the zram module loaded?)".to_string(),
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fstab() {
+        let fstab = "\
+# <fs> <mountpoint> <type> <opts> <dump> <pass>
+UUID=1234-abcd / ext4 defaults 0 1
+/dev/sda2 none swap sw,pri=10 0 0
+UUID=5678 none swap defaults,discard,nofail 0 0
+/var/swap\\040file none swap sw 0 0
+/dev/sdb1 none swap noauto 0 0
+";
+        let units = parse_fstab(fstab);
+        assert_eq!(units.len(), 3);
+        assert_eq!(units[0].what, PathBuf::from("/dev/sda2"));
+        assert_eq!(units[0].priority, Some(10));
+        assert_eq!(units[0].unit_name(), "dev-sda2.swap");
+        assert_eq!(units[1].what, PathBuf::from("/dev/disk/by-uuid/5678"));
+        assert!(units[1].discard && units[1].nofail);
+        assert_eq!(units[2].what, PathBuf::from("/var/swap file"));
+        assert_eq!(units[2].unit_name(), "var-swap\\x20file.swap");
+    }
+
+    #[test]
+    fn test_proc_swaps_and_zram() {
+        let swaps = "\
+Filename\t\t\t\tType\t\tSize\t\tUsed\t\tPriority
+/dev/zram0                              partition\t2097148\t\t1024\t\t100
+/swapfile                               file\t\t1048572\t\t0\t\t-2
+";
+        let active = parse_proc_swaps(swaps);
+        assert_eq!(active.len(), 2);
+        assert_eq!(active[0].size_bytes, 2097148 * 1024);
+        assert_eq!(active[1].priority, -2);
+        assert_eq!(zram_name(&active[0].path), Some("zram0"));
+        assert_eq!(zram_name(&active[1].path), None);
+
+        let config = ZramConfig {
+            fraction: 0.5,
+            max_size: Some(1 << 30),
+            ..Default::default()
+        };
+        assert_eq!(config.size_for(1 << 30), 1 << 29);
+        assert_eq!(config.size_for(8 << 30), 1 << 30);
+
+        let header = swap_header(1 << 20, 4096, [7; 16]);
+        assert_eq!(&header[4086..], b"SWAPSPACE2");
+        assert_eq!(
+            u32::from_ne_bytes(header[1028..1032].try_into().unwrap()),
+            255
+        );
+    }
+}
@@ -0,0 +1,350 @@
+//! Remote syslog forwarding.
+//!
+//! Journal entries at or above a configured priority are formatted per
+//! RFC 5424 and shipped to a remote collector over UDP, TCP or TLS (TCP
+//! and TLS use octet-counting framing, RFC 6587/5425). Entries are queued
+//! in a bounded buffer while the collector is unreachable; when it fills,
+//! the oldest entries are dropped and the count is reported on reconnect.
+
+use crate::error::{Error, Result};
+use crate::journal::{JournalEntry, Priority};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+/// Facility used for service output (daemon)
+const FACILITY_DAEMON: u8 = 3;
+
+/// Longest wait between reconnection attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Transport to the remote collector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyslogTransport {
+    Udp,
+    Tcp,
+    Tls,
+}
+
+/// Remote syslog settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteSyslogConfig {
+    /// Collector host
+    pub host: String,
+    /// Collector port
+    pub port: u16,
+    pub transport: SyslogTransport,
+    /// Least severe priority forwarded; entries below it are skipped
+    pub max_priority: Priority,
+    /// Entries kept while the collector is unreachable
+    pub buffer_size: usize,
+}
+
+impl RemoteSyslogConfig {
+    /// Parse a collector URL: `udp://host[:514]`, `tcp://host[:514]` or
+    /// `tls://host[:6514]`.
+    pub fn parse(url: &str) -> Result<Self> {
+        let invalid =
+            |reason: &str| Error::ConfigError(format!("syslog target '{}': {}", url, reason));
+        let (scheme, rest) = url
+            .split_once("://")
+            .ok_or_else(|| invalid("expected udp://, tcp:// or tls://"))?;
+        let (transport, default_port) = match scheme {
+            "udp" => (SyslogTransport::Udp, 514),
+            "tcp" => (SyslogTransport::Tcp, 514),
+            "tls" => (SyslogTransport::Tls, 6514),
+            _ => return Err(invalid("expected udp://, tcp:// or tls://")),
+        };
+        let rest = rest.trim_end_matches('/');
+        let (host, port) = match rest.rsplit_once(':') {
+            Some((host, port)) if !host.ends_with(':') => {
+                (host, port.parse().map_err(|_| invalid("invalid port"))?)
+            }
+            _ => (rest, default_port),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            return Err(invalid("missing host"));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            transport,
+            max_priority: Priority::Debug,
+            buffer_size: 10_000,
+        })
+    }
+
+    /// Only forward entries at `priority` or more severe.
+    pub fn with_max_priority(mut self, priority: Priority) -> Self {
+        self.max_priority = priority;
+        self
+    }
+
+    /// Whether an entry passes the priority filter.
+    pub fn accepts(&self, entry: &JournalEntry) -> bool {
+        entry.priority as u8 <= self.max_priority as u8
+    }
+}
+
+/// Format an entry as an RFC 5424 message (without transport framing).
+pub fn format_rfc5424(entry: &JournalEntry, hostname: &str) -> String {
+    let pri = FACILITY_DAEMON * 8 + entry.priority as u8;
+    let procid = entry
+        .pid
+        .map(|p| p.to_string())
+        .unwrap_or_else(|| "-".to_string());
+    format!(
+        "<{}>1 {} {} {} {} {} [meta sequenceId=\"{}\"] {}",
+        pri,
+        entry.timestamp.format("%Y-%m-%dT%H:%M:%S%.6fZ"),
+        header_field(hostname, 255),
+        header_field(&entry.service, 48),
+        procid,
+        header_field(&entry.stream, 32),
+        // sequenceId is 1..=2147483647
+        entry.seqnum % 2_147_483_647 + 1,
+        entry.message
+    )
+}
+
+/// Header fields are printable US-ASCII without spaces, or `-` if empty.
+fn header_field(value: &str, max_len: usize) -> String {
+    let field: String = value
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(max_len)
+        .collect();
+    if field.is_empty() {
+        "-".to_string()
+    } else {
+        field
+    }
+}
+
+/// Bounded queue of formatted messages awaiting delivery.
+#[derive(Debug)]
+struct Backlog {
+    messages: Mutex<VecDeque<String>>,
+    capacity: usize,
+    dropped: AtomicU64,
+    notify: Notify,
+}
+
+impl Backlog {
+    fn push(&self, message: String) {
+        let mut messages = self.messages.lock().unwrap();
+        if messages.len() >= self.capacity {
+            messages.pop_front();
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        messages.push_back(message);
+        drop(messages);
+        self.notify.notify_one();
+    }
+
+    fn pop(&self) -> Option<String> {
+        self.messages.lock().unwrap().pop_front()
+    }
+
+    /// Put back a message that could not be delivered.
+    fn requeue(&self, message: String) {
+        let mut messages = self.messages.lock().unwrap();
+        if messages.len() >= self.capacity {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        } else {
+            messages.push_front(message);
+        }
+    }
+}
+
+/// Connection to the collector.
+enum Connection {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+    Tls(Box<tokio_native_tls::TlsStream<TcpStream>>),
+}
+
+impl Connection {
+    async fn open(config: &RemoteSyslogConfig) -> std::io::Result<Self> {
+        let addr = (config.host.as_str(), config.port);
+        match config.transport {
+            SyslogTransport::Udp => {
+                let bind = if config.host.contains(':') {
+                    "[::]:0"
+                } else {
+                    "0.0.0.0:0"
+                };
+                let socket = UdpSocket::bind(bind).await?;
+                socket.connect(addr).await?;
+                Ok(Connection::Udp(socket))
+            }
+            SyslogTransport::Tcp => Ok(Connection::Tcp(TcpStream::connect(addr).await?)),
+            SyslogTransport::Tls => {
+                let tcp = TcpStream::connect(addr).await?;
+                let connector = native_tls::TlsConnector::new().map_err(std::io::Error::other)?;
+                let stream = tokio_native_tls::TlsConnector::from(connector)
+                    .connect(&config.host, tcp)
+                    .await
+                    .map_err(std::io::Error::other)?;
+                Ok(Connection::Tls(Box::new(stream)))
+            }
+        }
+    }
+
+    async fn send(&mut self, message: &str) -> std::io::Result<()> {
+        match self {
+            Connection::Udp(socket) => socket.send(message.as_bytes()).await.map(|_| ()),
+            Connection::Tcp(stream) => stream.write_all(&octet_counted(message)).await,
+            Connection::Tls(stream) => stream.write_all(&octet_counted(message)).await,
+        }
+    }
+}
+
+fn octet_counted(message: &str) -> Vec<u8> {
+    format!("{} {}", message.len(), message).into_bytes()
+}
+
+/// Ships journal entries to a remote syslog collector.
+#[derive(Debug, Clone)]
+pub struct SyslogForwarder {
+    config: RemoteSyslogConfig,
+    hostname: String,
+    backlog: Arc<Backlog>,
+}
+
+impl SyslogForwarder {
+    /// Start forwarding in a background task.
+    pub fn spawn(config: RemoteSyslogConfig) -> Self {
+        let hostname = std::fs::read_to_string("/proc/sys/kernel/hostname")
+            .map(|h| h.trim().to_string())
+            .unwrap_or_default();
+        let forwarder = Self {
+            backlog: Arc::new(Backlog {
+                messages: Mutex::new(VecDeque::new()),
+                capacity: config.buffer_size.max(1),
+                dropped: AtomicU64::new(0),
+                notify: Notify::new(),
+            }),
+            config,
+            hostname,
+        };
+        tokio::spawn(deliver(
+            forwarder.config.clone(),
+            Arc::clone(&forwarder.backlog),
+        ));
+        forwarder
+    }
+
+    /// Queue an entry if it passes the priority filter.
+    pub fn submit(&self, entry: &JournalEntry) {
+        if self.config.accepts(entry) {
+            self.backlog.push(format_rfc5424(entry, &self.hostname));
+        }
+    }
+}
+
+/// Delivery loop: connect, drain the backlog, reconnect with backoff on
+/// errors.
+async fn deliver(config: RemoteSyslogConfig, backlog: Arc<Backlog>) {
+    let target = format!("{}:{}", config.host, config.port);
+    let mut backoff = Duration::from_secs(1);
+    loop {
+        let mut connection = match Connection::open(&config).await {
+            Ok(connection) => {
+                info!(target = %target, "Connected to remote syslog");
+                backoff = Duration::from_secs(1);
+                connection
+            }
+            Err(e) => {
+                warn!(target = %target, error = %e, "Remote syslog unreachable");
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+        };
+
+        let dropped = backlog.dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            warn!(target = %target, dropped, "Dropped syslog messages while disconnected");
+        }
+
+        loop {
+            let Some(message) = backlog.pop() else {
+                backlog.notify.notified().await;
+                continue;
+            };
+            if let Err(e) = connection.send(&message).await {
+                warn!(target = %target, error = %e, "Lost connection to remote syslog");
+                backlog.requeue(message);
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_target() {
+        let config = RemoteSyslogConfig::parse("tls://logs.example.org").unwrap();
+        assert_eq!(config.transport, SyslogTransport::Tls);
+        assert_eq!(
+            (config.host.as_str(), config.port),
+            ("logs.example.org", 6514)
+        );
+
+        let config = RemoteSyslogConfig::parse("udp://[::1]:1514").unwrap();
+        assert_eq!((config.host.as_str(), config.port), ("::1", 1514));
+
+        assert!(RemoteSyslogConfig::parse("http://example.org").is_err());
+        assert!(RemoteSyslogConfig::parse("tcp://host:port").is_err());
+    }
+
+    #[test]
+    fn test_format_and_filter() {
+        let mut entry = JournalEntry::new("my daemon", "disk full", "stderr").with_pid(7);
+        entry.timestamp = chrono::Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        entry.seqnum = 41;
+
+        assert_eq!(
+            format_rfc5424(&entry, "host1"),
+            "<27>1 2023-11-14T22:13:20.000000Z host1 mydaemon 7 stderr \
+             [meta sequenceId=\"42\"] disk full"
+        );
+        assert_eq!(octet_counted("abc"), b"3 abc");
+
+        let config = RemoteSyslogConfig::parse("udp://host")
+            .unwrap()
+            .with_max_priority(Priority::Warning);
+        assert!(config.accepts(&entry));
+        assert!(!config.accepts(&entry.clone().with_priority(Priority::Info)));
+    }
+
+    #[test]
+    fn test_backlog_drops_oldest() {
+        let backlog = Backlog {
+            messages: Mutex::new(VecDeque::new()),
+            capacity: 2,
+            dropped: AtomicU64::new(0),
+            notify: Notify::new(),
+        };
+        for m in ["a", "b", "c"] {
+            backlog.push(m.to_string());
+        }
+        assert_eq!(backlog.dropped.load(Ordering::Relaxed), 1);
+        assert_eq!(backlog.pop().as_deref(), Some("b"));
+        backlog.requeue("b".to_string());
+        assert_eq!(backlog.pop().as_deref(), Some("b"));
+        assert_eq!(backlog.pop().as_deref(), Some("c"));
+        assert_eq!(backlog.pop(), None);
+    }
+}
@@ -0,0 +1,420 @@
+//! Timer scheduling.
+//!
+//! Works out when each timer-activated service next runs. The elapse time
+//! from OnCalendar, OnBootSec, OnUnitActiveSec and OnUnitInactiveSec is
+//! delayed by up to RandomizedDelaySec, so machines running the same timer
+//! don't all hit a server at once, then slid later within AccuracySec to a
+//! point shared by every timer on the machine, so they wake together. Both
+//! are seeded from the machine ID rather than random, so a machine keeps
+//! its place in the spread from boot to boot.
+
+use crate::service::TimerConfig;
+use buckos_core::MachineId;
+use chrono::{
+    DateTime, Datelike, Duration as ChronoDuration, Local, NaiveDate, NaiveDateTime, NaiveTime,
+    TimeZone, Timelike, Utc, Weekday,
+};
+use std::time::Duration;
+
+/// Days searched for a matching calendar date; long enough for Feb 29 on
+/// a given weekday to come round.
+const SEARCH_DAYS: i64 = 366 * 28;
+
+/// A parsed OnCalendar expression, e.g. `Mon..Fri *-*-* 06:30:00`.
+///
+/// `None` fields match any value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CalendarSpec {
+    /// Days of the week; empty matches every day
+    weekdays: Vec<Weekday>,
+    year: Option<i32>,
+    month: Option<u32>,
+    day: Option<u32>,
+    hour: Option<u32>,
+    minute: Option<u32>,
+    second: Option<u32>,
+}
+
+impl std::str::FromStr for CalendarSpec {
+    type Err = String;
+
+    /// Parse `[weekdays] [[year-]month-day] [hour:minute[:second]]`, or
+    /// one of minutely, hourly, daily, weekly, monthly and yearly.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expanded = match s.trim().to_ascii_lowercase().as_str() {
+            "minutely" => "*-*-* *:*:00",
+            "hourly" => "*-*-* *:00:00",
+            "daily" => "*-*-* 00:00:00",
+            "weekly" => "Mon *-*-* 00:00:00",
+            "monthly" => "*-*-01 00:00:00",
+            "yearly" | "annually" => "*-01-01 00:00:00",
+            _ => s,
+        };
+
+        let mut spec = CalendarSpec {
+            weekdays: Vec::new(),
+            year: None,
+            month: None,
+            day: None,
+            hour: Some(0),
+            minute: Some(0),
+            second: Some(0),
+        };
+        let mut tokens = expanded.split_whitespace().peekable();
+        if tokens.peek().is_none() {
+            return Err("empty calendar expression".to_string());
+        }
+        if let Some(days) = tokens.next_if(|t| t.starts_with(|c: char| c.is_ascii_alphabetic())) {
+            spec.weekdays = parse_weekdays(days)?;
+        }
+        if let Some(date) = tokens.next_if(|t| t.contains('-')) {
+            let parts: Vec<&str> = date.split('-').collect();
+            let (year, month, day) = match parts[..] {
+                [year, month, day] => (Some(year), month, day),
+                [month, day] => (None, month, day),
+                _ => return Err(format!("invalid date '{}'", date)),
+            };
+            spec.year = year.map(|y| field(y, 1970, 9999)).transpose()?.flatten();
+            spec.month = field(month, 1, 12)?;
+            spec.day = field(day, 1, 31)?;
+        }
+        if let Some(time) = tokens.next_if(|t| t.contains(':')) {
+            let parts: Vec<&str> = time.split(':').collect();
+            let (hour, minute, second) = match parts[..] {
+                [hour, minute, second] => (hour, minute, second),
+                [hour, minute] => (hour, minute, "00"),
+                _ => return Err(format!("invalid time '{}'", time)),
+            };
+            spec.hour = field(hour, 0, 23)?;
+            spec.minute = field(minute, 0, 59)?;
+            spec.second = field(second, 0, 59)?;
+        }
+        if let Some(extra) = tokens.next() {
+            return Err(format!("unexpected '{}' in calendar expression", extra));
+        }
+        Ok(spec)
+    }
+}
+
+impl CalendarSpec {
+    /// First matching local time strictly after `after`.
+    pub fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let start = after.with_nanosecond(0)? + ChronoDuration::seconds(1);
+        for offset in 0..SEARCH_DAYS {
+            let date = start.date() + ChronoDuration::days(offset);
+            if !self.matches_date(date) {
+                continue;
+            }
+            let earliest = if offset == 0 {
+                start.time()
+            } else {
+                NaiveTime::MIN
+            };
+            if let Some(time) = self.first_time_from(earliest) {
+                return Some(date.and_time(time));
+            }
+        }
+        None
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        (self.weekdays.is_empty() || self.weekdays.contains(&date.weekday()))
+            && self.year.is_none_or(|y| y == date.year())
+            && self.month.is_none_or(|m| m == date.month())
+            && self.day.is_none_or(|d| d == date.day())
+    }
+
+    /// First matching time of day at or after `earliest`.
+    fn first_time_from(&self, earliest: NaiveTime) -> Option<NaiveTime> {
+        let values = |v: Option<u32>, max: u32| v.map_or(0..=max, |v| v..=v);
+        for hour in values(self.hour, 23).filter(|h| *h >= earliest.hour()) {
+            for minute in values(self.minute, 59) {
+                for second in values(self.second, 59) {
+                    let time = NaiveTime::from_hms_opt(hour, minute, second)?;
+                    if time >= earliest {
+                        return Some(time);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// First matching time strictly after `after`, in local time.
+    fn next_elapse(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut after = after.with_timezone(&Local).naive_local();
+        loop {
+            let next = self.next_after(after)?;
+            // Skip local times that fall in a DST gap
+            if let Some(local) = Local.from_local_datetime(&next).earliest() {
+                return Some(local.with_timezone(&Utc));
+            }
+            after = next;
+        }
+    }
+}
+
+/// Parse a calendar field: `*` or a number in `min..=max`.
+fn field<T>(s: &str, min: T, max: T) -> Result<Option<T>, String>
+where
+    T: std::str::FromStr + PartialOrd + std::fmt::Display,
+{
+    if s == "*" {
+        return Ok(None);
+    }
+    match s.parse::<T>() {
+        Ok(v) if v >= min && v <= max => Ok(Some(v)),
+        _ => Err(format!("'{}' is not * or {}..{}", s, min, max)),
+    }
+}
+
+/// Parse a weekday list such as `Mon,Wed` or `Mon..Fri`.
+fn parse_weekdays(s: &str) -> Result<Vec<Weekday>, String> {
+    let day = |name: &str| {
+        name.parse::<Weekday>()
+            .map_err(|_| format!("unknown weekday '{}'", name))
+    };
+    let mut days = Vec::new();
+    for part in s.split(',') {
+        match part.split_once("..") {
+            Some((first, last)) => {
+                let (mut d, last) = (day(first)?, day(last)?);
+                days.push(d);
+                while d != last {
+                    d = d.succ();
+                    days.push(d);
+                }
+            }
+            None => days.push(day(part)?),
+        }
+    }
+    Ok(days)
+}
+
+/// A timer as shown by `list-timers`.
+#[derive(Debug, Clone)]
+pub struct TimerInfo {
+    /// Service the timer starts
+    pub name: String,
+    pub next_elapse: Option<DateTime<Utc>>,
+    pub last_trigger: Option<DateTime<Utc>>,
+    pub accuracy: Duration,
+    pub randomized_delay: Duration,
+}
+
+/// Computes timer elapse times for this machine and boot.
+#[derive(Debug, Clone, Copy)]
+pub struct TimerSchedule {
+    /// Seed for accuracy and randomized delay, from the machine ID
+    machine_seed: u64,
+    /// Wall clock time of boot, for OnBootSec
+    boot: DateTime<Utc>,
+}
+
+impl TimerSchedule {
+    /// Schedule for the running machine.
+    pub fn new() -> Self {
+        Self {
+            machine_seed: machine_seed(),
+            boot: Utc::now() - ChronoDuration::milliseconds(uptime().as_millis() as i64),
+        }
+    }
+
+    /// When a timer next elapses.
+    ///
+    /// `last_trigger` is when it last started the service and
+    /// `last_inactive` when the service last stopped.
+    pub fn next_elapse(
+        &self,
+        name: &str,
+        config: &TimerConfig,
+        last_trigger: Option<DateTime<Utc>>,
+        last_inactive: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        let after =
+            |d: Duration, from: DateTime<Utc>| ChronoDuration::from_std(d).ok().map(|d| from + d);
+        let mut candidates = Vec::new();
+
+        if let Some(at) = config.on_boot.and_then(|d| after(d, self.boot)) {
+            if last_trigger.is_none_or(|t| t < at) {
+                candidates.push(at);
+            }
+        }
+        if let (Some(d), Some(t)) = (config.on_unit_active, last_trigger) {
+            candidates.extend(after(d, t));
+        }
+        if let (Some(d), Some(t)) = (config.on_unit_inactive, last_inactive) {
+            candidates.extend(after(d, t));
+        }
+        if let Some(spec) = config
+            .on_calendar
+            .as_deref()
+            .and_then(|s| s.parse::<CalendarSpec>().ok())
+        {
+            match last_trigger {
+                // A run was missed while the machine was off
+                Some(t) if config.persistent && spec.next_elapse(t).is_some_and(|n| n <= now) => {
+                    candidates.push(now)
+                }
+                _ => candidates.extend(spec.next_elapse(last_trigger.map_or(now, |t| t.max(now)))),
+            }
+        }
+
+        let base = candidates.into_iter().min()?;
+        Some(self.perturb(name, config, base))
+    }
+
+    /// Apply the randomized delay and accuracy window to an elapse time.
+    fn perturb(&self, name: &str, config: &TimerConfig, base: DateTime<Utc>) -> DateTime<Utc> {
+        let mut micros = base.timestamp_micros();
+
+        let delay = config.randomized_delay.as_micros() as u64;
+        if delay > 0 {
+            let seed = fnv1a(
+                fnv1a(self.machine_seed, name.as_bytes()),
+                &base.timestamp().to_le_bytes(),
+            );
+            micros += (seed % delay) as i64;
+        }
+
+        // Round up to this machine's point in the accuracy window
+        let accuracy = config.accuracy.as_micros() as i64;
+        if accuracy > 1 {
+            let phase = (self.machine_seed % accuracy as u64) as i64;
+            micros += (phase - micros).rem_euclid(accuracy);
+        }
+
+        DateTime::from_timestamp_micros(micros).unwrap_or(base)
+    }
+}
+
+impl Default for TimerSchedule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Time since boot, the clock OnBootSec counts on.
+pub fn uptime() -> Duration {
+    std::fs::read_to_string("/proc/uptime")
+        .ok()
+        .and_then(|s| s.split_whitespace().next()?.parse::<f64>().ok())
+        .map(Duration::from_secs_f64)
+        .unwrap_or_default()
+}
+
+/// Seed derived from /etc/machine-id, or the hostname if there is none.
+fn machine_seed() -> u64 {
+    let id = MachineId::read()
+        .map(|id| id.to_string())
+        .or_else(|| {
+            std::fs::read_to_string("/proc/sys/kernel/hostname")
+                .ok()
+                .map(|s| s.trim().to_string())
+        })
+        .unwrap_or_default();
+    fnv1a(FNV_OFFSET, id.as_bytes())
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// FNV-1a, which unlike the std hasher is stable across builds.
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn test_calendar_next_after() {
+        let spec: CalendarSpec = "Mon..Fri *-*-* 06:30".parse().unwrap();
+        // Friday evening -> Monday morning
+        assert_eq!(
+            spec.next_after(at("2024-03-01 07:00:00")),
+            Some(at("2024-03-04 06:30:00"))
+        );
+
+        let hourly: CalendarSpec = "hourly".parse().unwrap();
+        assert_eq!(
+            hourly.next_after(at("2024-03-01 07:00:00")),
+            Some(at("2024-03-01 08:00:00"))
+        );
+
+        let leap: CalendarSpec = "*-02-29 12:00:00".parse().unwrap();
+        assert_eq!(
+            leap.next_after(at("2024-03-01 00:00:00")),
+            Some(at("2028-02-29 12:00:00"))
+        );
+
+        assert!("*-13-01".parse::<CalendarSpec>().is_err());
+        assert!("Funday".parse::<CalendarSpec>().is_err());
+    }
+
+    fn schedule(machine_id: &str) -> TimerSchedule {
+        TimerSchedule {
+            machine_seed: fnv1a(FNV_OFFSET, machine_id.as_bytes()),
+            boot: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_randomized_delay_is_per_machine() {
+        let config = TimerConfig {
+            on_boot: Some(Duration::from_secs(600)),
+            randomized_delay: Duration::from_secs(3600),
+            accuracy: Duration::from_micros(1),
+            ..Default::default()
+        };
+        let base = DateTime::from_timestamp(1_700_000_600, 0).unwrap();
+        let now = DateTime::from_timestamp(1_700_000_010, 0).unwrap();
+
+        let a = schedule("machine-a");
+        let first = a.next_elapse("backup", &config, None, None, now).unwrap();
+        assert!(first >= base && first < base + ChronoDuration::hours(1));
+        // Same machine, same answer
+        assert_eq!(
+            a.next_elapse("backup", &config, None, None, now),
+            Some(first)
+        );
+        assert_ne!(
+            schedule("machine-b").next_elapse("backup", &config, None, None, now),
+            Some(first)
+        );
+    }
+
+    #[test]
+    fn test_accuracy_window() {
+        let config = TimerConfig {
+            on_boot: Some(Duration::from_secs(600)),
+            on_unit_active: Some(Duration::from_secs(3600)),
+            accuracy: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let s = schedule("machine-a");
+        let now = DateTime::from_timestamp(1_700_000_010, 0).unwrap();
+        let first = s.next_elapse("a", &config, None, None, now).unwrap();
+        let base = DateTime::from_timestamp(1_700_000_600, 0).unwrap();
+        assert!(first >= base && first < base + ChronoDuration::seconds(60));
+
+        // Timers on one machine share the point in the window
+        let other = s.next_elapse("b", &config, None, None, now).unwrap();
+        assert_eq!(other, first);
+
+        // After the boot trigger fires, OnUnitActiveSec takes over
+        let next = s
+            .next_elapse("a", &config, Some(first), None, first)
+            .unwrap();
+        assert!(next >= first + ChronoDuration::hours(1));
+    }
+}
@@ -0,0 +1,603 @@
+//! Clock synchronization.
+//!
+//! `time-sync` is the point in boot from which the system clock can be
+//! trusted. It is not a service: units order themselves after it with
+//! `after = ["time-sync"]` (`After=time-sync.target` in systemd units),
+//! and units that synchronize the clock declare `before = ["time-sync"]`.
+//! The point is reached once the clock is synchronized, either by the SNTP
+//! client here or by an NTP daemon such as chronyd or ntpd, which report
+//! the kernel clock synchronized. When no enabled unit synchronizes the
+//! clock, the point is reached at once, so nothing waits for a sync that
+//! never comes.
+//!
+//! The SNTP client (`boss timesync run`) asks the configured servers in
+//! turn. On the first sync an offset over the step threshold is corrected
+//! by stepping the clock; every later correction slews it, so the clock
+//! never jumps under running services.
+
+use crate::error::{Error, Result};
+use chrono::{DateTime, Utc};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// Units order themselves after this name to wait for the clock
+pub const TIME_SYNC: &str = "time-sync";
+
+/// Written by the SNTP client once it has synchronized the clock
+pub const SYNCHRONIZED_FLAG: &str = "/run/buckos/timesync/synchronized";
+
+/// Servers asked when none are configured
+pub const DEFAULT_SERVERS: &[&str] = &["0.pool.ntp.org", "1.pool.ntp.org", "2.pool.ntp.org"];
+
+/// How often units waiting for time-sync check the clock again
+pub const SYNC_POLL: Duration = Duration::from_secs(1);
+
+/// Offsets the first sync steps rather than slews, as in ntpd
+pub const DEFAULT_STEP_THRESHOLD: Duration = Duration::from_millis(128);
+
+const NTP_PORT: u16 = 123;
+
+/// Seconds from the NTP epoch, 1900, to the Unix epoch
+const NTP_UNIX_OFFSET: f64 = 2_208_988_800.0;
+
+const PACKET_LEN: usize = 48;
+
+/// First wait before asking the servers again when none answered
+const RETRY_MIN: Duration = Duration::from_secs(1);
+
+/// Whether `unit` names the time-sync point, as `time-sync` or as
+/// `time-sync.target`.
+pub fn is_time_sync(unit: &str) -> bool {
+    unit == TIME_SYNC || unit == "time-sync.target"
+}
+
+/// Whether the kernel clock is synchronized, as NTP daemons report it
+/// through adjtimex(2).
+pub fn kernel_synchronized() -> bool {
+    let mut tx: libc::timex = unsafe { std::mem::zeroed() };
+    let state = unsafe { libc::adjtimex(&mut tx) };
+    state >= 0 && state != libc::TIME_ERROR
+}
+
+/// Tells whether the system clock is synchronized.
+#[derive(Debug, Clone)]
+pub struct ClockSync {
+    /// Written by the SNTP client on each sync
+    flag: PathBuf,
+    /// Whether a kernel clock reported synchronized counts
+    kernel: bool,
+}
+
+impl ClockSync {
+    pub fn new(flag: impl Into<PathBuf>) -> Self {
+        Self {
+            flag: flag.into(),
+            kernel: true,
+        }
+    }
+
+    /// Only count syncs recorded in the flag file, ignoring the kernel
+    /// clock status.
+    pub fn without_kernel(mut self) -> Self {
+        self.kernel = false;
+        self
+    }
+
+    pub fn flag(&self) -> &Path {
+        &self.flag
+    }
+
+    /// Whether the clock is synchronized.
+    pub fn is_synchronized(&self) -> bool {
+        self.flag.exists() || (self.kernel && kernel_synchronized())
+    }
+
+    /// Server and time of the last sync by the SNTP client.
+    pub fn last_sync(&self) -> Option<(String, DateTime<Utc>)> {
+        let content = std::fs::read_to_string(&self.flag).ok()?;
+        let (server, at) = content.trim().rsplit_once(' ')?;
+        let at = DateTime::parse_from_rfc3339(at).ok()?;
+        Some((server.to_string(), at.with_timezone(&Utc)))
+    }
+
+    /// Record a sync with `server`.
+    pub fn mark(&self, server: &str) -> Result<()> {
+        if let Some(dir) = self.flag.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self.flag.with_extension("tmp");
+        std::fs::write(&tmp, format!("{} {}\n", server, Utc::now().to_rfc3339()))?;
+        std::fs::rename(&tmp, &self.flag)?;
+        Ok(())
+    }
+}
+
+impl Default for ClockSync {
+    fn default() -> Self {
+        Self::new(SYNCHRONIZED_FLAG)
+    }
+}
+
+/// The answer of a time server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub server: String,
+    /// Seconds the local clock is behind the server
+    pub offset: f64,
+    /// Round trip time in seconds, less the time the server took to answer
+    pub delay: f64,
+    pub stratum: u8,
+}
+
+/// How a clock offset is corrected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Adjustment {
+    /// Set the clock at once
+    Step,
+    /// Run the clock slightly fast or slow until the offset is gone
+    Slew,
+}
+
+impl std::fmt::Display for Adjustment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Adjustment::Step => "step",
+            Adjustment::Slew => "slew",
+        })
+    }
+}
+
+/// A minimal SNTP client (RFC 4330).
+#[derive(Debug, Clone)]
+pub struct SntpClient {
+    servers: Vec<String>,
+    step_threshold: Duration,
+    min_poll: Duration,
+    max_poll: Duration,
+    timeout: Duration,
+    clock: ClockSync,
+}
+
+impl SntpClient {
+    /// Client of these servers, or of [`DEFAULT_SERVERS`] if none are
+    /// given.
+    pub fn new(servers: Vec<String>) -> Self {
+        let servers = if servers.is_empty() {
+            DEFAULT_SERVERS.iter().map(|s| s.to_string()).collect()
+        } else {
+            servers
+        };
+        Self {
+            servers,
+            step_threshold: DEFAULT_STEP_THRESHOLD,
+            min_poll: Duration::from_secs(32),
+            max_poll: Duration::from_secs(2048),
+            timeout: Duration::from_secs(5),
+            clock: ClockSync::default(),
+        }
+    }
+
+    /// Step the clock on the first sync when it is off by more than this.
+    pub fn with_step_threshold(mut self, threshold: Duration) -> Self {
+        self.step_threshold = threshold;
+        self
+    }
+
+    /// Ask the servers at least every `max` and at most every `min`.
+    pub fn with_poll(mut self, min: Duration, max: Duration) -> Self {
+        self.min_poll = min;
+        self.max_poll = max.max(min);
+        self
+    }
+
+    /// Wait this long for a server to answer.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Record syncs with this flag file.
+    pub fn with_clock(mut self, clock: ClockSync) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn servers(&self) -> &[String] {
+        &self.servers
+    }
+
+    /// Ask the servers in turn, returning the first answer.
+    pub fn sample(&self) -> Result<Sample> {
+        let mut last_error = None;
+        for server in &self.servers {
+            match query(server, self.timeout) {
+                Ok(sample) => return Ok(sample),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| Error::ConfigError("no time servers".to_string())))
+    }
+
+    /// How to correct `offset`; only the first sync may step the clock.
+    pub fn adjustment(&self, offset: f64, first: bool) -> Adjustment {
+        if first && offset.abs() > self.step_threshold.as_secs_f64() {
+            Adjustment::Step
+        } else {
+            Adjustment::Slew
+        }
+    }
+
+    /// Correct the clock by the offset of `sample`, and record the sync.
+    pub fn apply(&self, sample: &Sample, first: bool) -> Result<Adjustment> {
+        let adjustment = self.adjustment(sample.offset, first);
+        match adjustment {
+            Adjustment::Step => step_clock(sample.offset)?,
+            Adjustment::Slew => slew_clock(sample.offset)?,
+        }
+        set_kernel_synchronized(sample.delay / 2.0)?;
+        self.clock.mark(&sample.server)?;
+        Ok(adjustment)
+    }
+
+    /// Keep the clock synchronized. Only returns if the clock cannot be
+    /// set.
+    ///
+    /// The servers are asked less often while the clock stays within the
+    /// step threshold, down to once per maximum polling interval.
+    pub fn run(&self) -> Result<()> {
+        let mut first = true;
+        let mut poll = self.min_poll;
+        let mut retry = RETRY_MIN;
+        loop {
+            let sample = match self.sample() {
+                Ok(sample) => sample,
+                Err(e) => {
+                    warn!(error = %e, retry = ?retry, "No time server answered");
+                    std::thread::sleep(retry);
+                    retry = (retry * 2).min(self.min_poll);
+                    continue;
+                }
+            };
+            let adjustment = self.apply(&sample, first)?;
+            info!(
+                server = %sample.server,
+                offset = sample.offset,
+                delay = sample.delay,
+                stratum = sample.stratum,
+                adjustment = %adjustment,
+                "Corrected the clock"
+            );
+            if first {
+                info!("Clock synchronized");
+            }
+            first = false;
+            retry = RETRY_MIN;
+
+            std::thread::sleep(poll);
+            poll = if sample.offset.abs() < self.step_threshold.as_secs_f64() {
+                (poll * 2).min(self.max_poll)
+            } else {
+                self.min_poll
+            };
+        }
+    }
+}
+
+/// Ask one server for the time. `server` is a host name or address,
+/// with an optional port.
+pub fn query(server: &str, timeout: Duration) -> Result<Sample> {
+    let error = |reason: String| Error::TimeSyncError {
+        server: server.to_string(),
+        reason,
+    };
+    let addr = match server.parse::<SocketAddr>() {
+        Ok(addr) => addr,
+        Err(_) => (server, NTP_PORT)
+            .to_socket_addrs()
+            .map_err(|e| error(e.to_string()))?
+            .next()
+            .ok_or_else(|| error("no address".to_string()))?,
+    };
+    let local = if addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket.connect(addr)?;
+
+    let packet = request(now());
+    socket.send(&packet).map_err(|e| error(e.to_string()))?;
+    let mut reply = [0u8; PACKET_LEN * 2];
+    let len = socket.recv(&mut reply).map_err(|e| error(e.to_string()))?;
+    parse_reply(server, &packet, &reply[..len], now()).map_err(error)
+}
+
+/// A client request sent at `transmit`, in seconds since the Unix epoch.
+pub fn request(transmit: f64) -> [u8; PACKET_LEN] {
+    let mut packet = [0u8; PACKET_LEN];
+    // No leap warning, version 4, client mode
+    packet[0] = (4 << 3) | 3;
+    packet[40..48].copy_from_slice(&ntp_timestamp(transmit));
+    packet
+}
+
+/// The sample in the server's `reply` to `request`, received at
+/// `received`.
+pub fn parse_reply(
+    server: &str,
+    request: &[u8; PACKET_LEN],
+    reply: &[u8],
+    received: f64,
+) -> std::result::Result<Sample, String> {
+    if reply.len() < PACKET_LEN {
+        return Err(format!("short reply of {} bytes", reply.len()));
+    }
+    let (leap, mode, stratum) = (reply[0] >> 6, reply[0] & 0x7, reply[1]);
+    if mode != 4 {
+        return Err(format!("reply in mode {}, not server mode", mode));
+    }
+    // The originate timestamp echoes our transmit timestamp
+    if reply[24..32] != request[40..48] {
+        return Err("reply does not answer the request".to_string());
+    }
+    if stratum == 0 {
+        let code = String::from_utf8_lossy(&reply[12..16]).into_owned();
+        return Err(format!(
+            "server sent kiss code {}",
+            code.trim_end_matches('\0')
+        ));
+    }
+    if leap == 3 || stratum > 15 {
+        return Err("server is not synchronized".to_string());
+    }
+    if reply[40..48].iter().all(|b| *b == 0) {
+        return Err("reply has no transmit timestamp".to_string());
+    }
+
+    let sent = unix_time(&request[40..48]);
+    let server_received = unix_time(&reply[32..40]);
+    let server_sent = unix_time(&reply[40..48]);
+    Ok(Sample {
+        server: server.to_string(),
+        offset: ((server_received - sent) + (server_sent - received)) / 2.0,
+        delay: ((received - sent) - (server_sent - server_received)).max(0.0),
+        stratum,
+    })
+}
+
+/// Seconds since the Unix epoch.
+fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+/// NTP timestamp, 32-bit seconds since 1900 and 32-bit fraction, of a
+/// Unix time.
+fn ntp_timestamp(unix: f64) -> [u8; 8] {
+    let ntp = unix + NTP_UNIX_OFFSET;
+    // Seconds wrap into the next era in 2036
+    let seconds = (ntp.floor() as u64) as u32;
+    let fraction = (ntp.fract() * 4_294_967_296.0) as u32;
+    let mut bytes = [0u8; 8];
+    bytes[..4].copy_from_slice(&seconds.to_be_bytes());
+    bytes[4..].copy_from_slice(&fraction.to_be_bytes());
+    bytes
+}
+
+/// Unix time of an NTP timestamp, taking seconds below 2^31 to be in the
+/// era starting in 2036.
+fn unix_time(bytes: &[u8]) -> f64 {
+    let seconds = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as u64;
+    let fraction = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as f64;
+    let seconds = if seconds < 1 << 31 {
+        seconds + (1 << 32)
+    } else {
+        seconds
+    };
+    seconds as f64 - NTP_UNIX_OFFSET + fraction / 4_294_967_296.0
+}
+
+/// Set the clock `offset` seconds ahead.
+fn step_clock(offset: f64) -> Result<()> {
+    let mut ts: libc::timespec = unsafe { std::mem::zeroed() };
+    if unsafe { libc::clock_gettime(libc::CLOCK_REALTIME, &mut ts) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let target = ts.tv_sec as f64 + ts.tv_nsec as f64 / 1e9 + offset;
+    ts.tv_sec = target.floor() as libc::time_t;
+    ts.tv_nsec = (target.fract() * 1e9) as libc::c_long;
+    if unsafe { libc::clock_settime(libc::CLOCK_REALTIME, &ts) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// Have the kernel run the clock slightly fast or slow until it has
+/// gained `offset` seconds.
+fn slew_clock(offset: f64) -> Result<()> {
+    let micros = (offset * 1e6).round() as i64;
+    let delta = libc::timeval {
+        tv_sec: micros.div_euclid(1_000_000) as libc::time_t,
+        tv_usec: micros.rem_euclid(1_000_000) as libc::suseconds_t,
+    };
+    if unsafe { libc::adjtime(&delta, std::ptr::null_mut()) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// Tell the kernel the clock is synchronized to within `error` seconds,
+/// which also lets it keep the hardware clock in step.
+fn set_kernel_synchronized(error: f64) -> Result<()> {
+    let mut tx: libc::timex = unsafe { std::mem::zeroed() };
+    if unsafe { libc::adjtimex(&mut tx) } < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    tx.modes = libc::ADJ_STATUS | libc::ADJ_MAXERROR | libc::ADJ_ESTERROR;
+    tx.status &= !libc::STA_UNSYNC;
+    tx.maxerror = (error * 1e6) as libc::c_long;
+    tx.esterror = tx.maxerror;
+    if unsafe { libc::adjtimex(&mut tx) } < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answer one request as a server whose clock is `offset` seconds
+    /// ahead.
+    fn answer(request: &[u8], offset: f64, stratum: u8) -> [u8; PACKET_LEN] {
+        let mut reply = [0u8; PACKET_LEN];
+        reply[0] = (4 << 3) | 4;
+        reply[1] = stratum;
+        reply[24..32].copy_from_slice(&request[40..48]);
+        reply[32..40].copy_from_slice(&ntp_timestamp(now() + offset));
+        reply[40..48].copy_from_slice(&ntp_timestamp(now() + offset));
+        reply
+    }
+
+    #[test]
+    fn test_parse_reply() {
+        let sent = 1_700_000_000.25;
+        let packet = request(sent);
+        assert!((unix_time(&packet[40..48]) - sent).abs() < 1e-6);
+        // Past the 2036 era rollover
+        assert!((unix_time(&ntp_timestamp(2_200_000_000.5)) - 2_200_000_000.5).abs() < 1e-6);
+
+        // Server 10s ahead, 40ms each way, 20ms to answer
+        let mut reply = [0u8; PACKET_LEN];
+        reply[0] = (4 << 3) | 4;
+        reply[1] = 2;
+        reply[24..32].copy_from_slice(&packet[40..48]);
+        reply[32..40].copy_from_slice(&ntp_timestamp(sent + 10.04));
+        reply[40..48].copy_from_slice(&ntp_timestamp(sent + 10.06));
+        let sample = parse_reply("ntp", &packet, &reply, sent + 0.1).unwrap();
+        assert!((sample.offset - 10.0).abs() < 1e-4);
+        assert!((sample.delay - 0.08).abs() < 1e-4);
+        assert_eq!(sample.stratum, 2);
+
+        // A reply to another request
+        let other = request(sent + 1.0);
+        assert!(parse_reply("ntp", &other, &reply, sent + 0.1).is_err());
+
+        let mut kiss = reply;
+        kiss[1] = 0;
+        kiss[12..16].copy_from_slice(b"RATE");
+        assert_eq!(
+            parse_reply("ntp", &packet, &kiss, sent + 0.1),
+            Err("server sent kiss code RATE".to_string())
+        );
+        assert!(parse_reply("ntp", &packet, &reply[..40], sent + 0.1).is_err());
+    }
+
+    #[test]
+    fn test_step_then_slew() {
+        let client = SntpClient::new(Vec::new());
+        assert_eq!(client.servers().len(), DEFAULT_SERVERS.len());
+        assert_eq!(client.adjustment(2.5, true), Adjustment::Step);
+        assert_eq!(client.adjustment(-0.2, true), Adjustment::Step);
+        assert_eq!(client.adjustment(0.05, true), Adjustment::Slew);
+        // After the first sync the clock is never stepped
+        assert_eq!(client.adjustment(2.5, false), Adjustment::Slew);
+    }
+
+    #[test]
+    fn test_query() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        let responder = std::thread::spawn(move || {
+            let mut buf = [0u8; PACKET_LEN];
+            let (_, peer) = server.recv_from(&mut buf).unwrap();
+            server.send_to(&answer(&buf, 30.0, 1), peer).unwrap();
+        });
+
+        let client = SntpClient::new(vec![addr]).with_timeout(Duration::from_secs(5));
+        let sample = client.sample().unwrap();
+        responder.join().unwrap();
+        assert!((sample.offset - 30.0).abs() < 0.5);
+        assert_eq!(sample.stratum, 1);
+        assert_eq!(client.adjustment(sample.offset, true), Adjustment::Step);
+
+        let dir = std::env::temp_dir().join(format!("boss-timesync-{}", std::process::id()));
+        let clock = ClockSync::new(dir.join("synchronized")).without_kernel();
+        assert!(!clock.is_synchronized());
+        clock.mark(&sample.server).unwrap();
+        assert!(clock.is_synchronized());
+        assert_eq!(clock.last_sync().map(|(s, _)| s), Some(sample.server));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_services_wait_for_time_sync() {
+        use crate::manager::ServiceManager;
+        use crate::service::{ServiceDefinition, ServiceState, TimerConfig};
+
+        let dir = std::env::temp_dir().join(format!("boss-time-sync-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let clock = ClockSync::new(dir.join("synchronized")).without_kernel();
+        let manager = ServiceManager::new(dir.join("services")).with_clock_sync(clock.clone());
+        let service = |name: &str, before: &[&str], after: &[&str], requires: &[&str]| {
+            let mut def = ServiceDefinition::new(name, "sleep 30");
+            def.enabled = true;
+            def.before = before.iter().map(|s| s.to_string()).collect();
+            def.after = after.iter().map(|s| s.to_string()).collect();
+            def.requires = requires.iter().map(|s| s.to_string()).collect();
+            def
+        };
+        let mut cleanup = service("cleanup", &[], &[TIME_SYNC], &[]);
+        cleanup.enabled = false;
+        cleanup.timer = Some(TimerConfig {
+            on_boot: Some(Duration::ZERO),
+            accuracy: Duration::from_micros(1),
+            ..Default::default()
+        });
+        for def in [
+            service("sntp", &[TIME_SYNC], &[], &[]),
+            service("tls", &[], &["time-sync.target"], &["time-sync.target"]),
+            service("app", &[], &[], &["tls"]),
+            service("web", &[], &[], &[]),
+            cleanup,
+        ] {
+            manager.register_service(def).await.unwrap();
+        }
+        let state = |name: &'static str| {
+            let manager = &manager;
+            async move { manager.get_status(name).await.unwrap().state }
+        };
+
+        assert!(!manager.time_synchronized().await);
+        manager.start_enabled_services_parallel().await.unwrap();
+        assert_eq!(state("sntp").await, ServiceState::Running);
+        assert_eq!(state("web").await, ServiceState::Running);
+        assert_eq!(state("tls").await, ServiceState::Inactive);
+        // Waits through its dependency
+        assert_eq!(state("app").await, ServiceState::Inactive);
+        // The elapsed timer is held back, and checked again soon
+        assert!(manager.run_due_timers().await <= Some(SYNC_POLL));
+        assert_eq!(state("cleanup").await, ServiceState::Inactive);
+
+        clock.mark("sntp").unwrap();
+        for _ in 0..50 {
+            if state("app").await == ServiceState::Running {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(state("tls").await, ServiceState::Running);
+        assert_eq!(state("app").await, ServiceState::Running);
+        manager.run_due_timers().await;
+        assert_eq!(state("cleanup").await, ServiceState::Running);
+
+        manager.stop_all_services().await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
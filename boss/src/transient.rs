@@ -0,0 +1,184 @@
+//! Transient units, created at runtime instead of from a unit file.
+//!
+//! `boss run` sends a command line and `Key=Value` properties over the
+//! control socket; init builds a service from them with the systemd loader
+//! and starts it, or arms its timer. Transient units are never written to
+//! disk: one is removed once its service exits for good, and none survive
+//! a reboot.
+
+use crate::error::{Error, Result};
+use crate::loaders::systemd::{parse_duration, SystemdLoader};
+use crate::loaders::verify::Severity;
+use crate::service::ServiceDefinition;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Short property names accepted in place of the directive.
+const ALIASES: &[(&str, &str)] = &[("Mem", "MemoryMax"), ("CPU", "CPUQuota")];
+
+/// A unit to create at runtime.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransientUnit {
+    /// Unit name; `run-<id>` if not given
+    pub name: Option<String>,
+    /// Program and arguments
+    pub command: Vec<String>,
+    /// `Key=Value` properties, as in a unit file
+    pub properties: Vec<String>,
+}
+
+impl TransientUnit {
+    pub fn new(command: Vec<String>) -> Self {
+        Self {
+            command,
+            ..Default::default()
+        }
+    }
+
+    /// The unit name, generating one if none was given.
+    pub fn unit_name(&self) -> String {
+        match &self.name {
+            Some(name) => name.strip_suffix(".service").unwrap_or(name).to_string(),
+            None => format!("run-{}", &Uuid::new_v4().simple().to_string()[..8]),
+        }
+    }
+
+    /// Build the service definition of the unit.
+    ///
+    /// `OnActiveSec=` is relative to now, so it becomes an OnBootSec at
+    /// `uptime` plus the delay.
+    pub fn definition(&self, uptime: std::time::Duration) -> Result<ServiceDefinition> {
+        let name = self.unit_name();
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.@:".contains(c))
+        {
+            return Err(Error::ConfigError(format!("invalid unit name '{}'", name)));
+        }
+        if self.command.is_empty() {
+            return Err(Error::ConfigError("no command given".to_string()));
+        }
+        // ExecStart is split on whitespace
+        if let Some(arg) = self
+            .command
+            .iter()
+            .find(|a| a.contains(char::is_whitespace))
+        {
+            return Err(Error::ConfigError(format!(
+                "argument '{}' contains whitespace, which ExecStart cannot express",
+                arg
+            )));
+        }
+
+        let mut properties = vec![
+            (
+                "Description".to_string(),
+                format!("Transient unit: {}", self.command.join(" ")),
+            ),
+            ("ExecStart".to_string(), self.command.join(" ")),
+        ];
+        for property in &self.properties {
+            let (key, value) = property.split_once('=').ok_or_else(|| {
+                Error::ConfigError(format!("expected Key=Value, found '{}'", property))
+            })?;
+            let key = ALIASES
+                .iter()
+                .find(|(alias, _)| *alias == key)
+                .map_or(key, |(_, key)| key);
+            if key == "OnActiveSec" {
+                let delay = parse_duration(value).ok_or_else(|| {
+                    Error::ConfigError(format!("OnActiveSec={}: invalid duration", value))
+                })?;
+                let at = uptime + delay;
+                properties.push(("OnBootSec".to_string(), format!("{}ms", at.as_millis())));
+                continue;
+            }
+            if key == "Description" {
+                properties.retain(|(k, _)| k != key);
+            }
+            properties.push((key.to_string(), value.to_string()));
+        }
+
+        let mut report = SystemdLoader::from_properties(&name, &properties);
+        if report.has_errors() {
+            let errors: Vec<String> = report
+                .diagnostics
+                .iter()
+                .filter(|d| d.severity == Severity::Error)
+                .map(|d| d.message.clone())
+                .collect();
+            return Err(Error::ConfigError(format!(
+                "invalid transient unit {}: {}",
+                name,
+                errors.join("; ")
+            )));
+        }
+        let mut def = report
+            .definition
+            .take()
+            .ok_or_else(|| Error::ConfigError(format!("invalid transient unit {}", name)))?;
+        def.enabled = false;
+        Ok(def)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_definition_from_properties() {
+        let unit = TransientUnit {
+            name: Some("cleanup.service".to_string()),
+            command: vec!["/bin/sh".into(), "-c".into(), "true".into()],
+            properties: vec![
+                "Mem=512M".into(),
+                "CPUQuota=50%".into(),
+                "Environment=MODE=full".into(),
+                "OnActiveSec=5min".into(),
+                "Description=Nightly cleanup".into(),
+            ],
+        };
+        let def = unit.definition(Duration::from_secs(100)).unwrap();
+        assert_eq!(def.name, "cleanup");
+        assert_eq!(def.exec_start, "/bin/sh -c true");
+        assert_eq!(def.description, "Nightly cleanup");
+        assert_eq!(
+            def.environment.get("MODE").map(String::as_str),
+            Some("full")
+        );
+        let limits = def.resource_limits.unwrap();
+        assert_eq!(limits.memory_hard, Some(512 * 1024 * 1024));
+        assert_eq!(def.timer.unwrap().on_boot, Some(Duration::from_secs(400)));
+        assert!(!def.enabled);
+    }
+
+    #[test]
+    fn test_invalid_units() {
+        let unit = |name: Option<&str>, command: &[&str], properties: &[&str]| TransientUnit {
+            name: name.map(str::to_string),
+            command: command.iter().map(|s| s.to_string()).collect(),
+            properties: properties.iter().map(|s| s.to_string()).collect(),
+        };
+        let now = Duration::ZERO;
+        assert!(unit(None, &[], &[]).definition(now).is_err());
+        assert!(unit(Some("a/b"), &["/bin/true"], &[])
+            .definition(now)
+            .is_err());
+        assert!(unit(None, &["/bin/echo", "a b"], &[])
+            .definition(now)
+            .is_err());
+        let err = unit(None, &["/bin/true"], &["Colour=blue"])
+            .definition(now)
+            .unwrap_err();
+        assert!(err.to_string().contains("unknown property Colour="));
+        assert!(unit(None, &["/bin/true"], &["MemoryMax=lots"])
+            .definition(now)
+            .is_err());
+        assert!(unit(None, &["/bin/true"], &[])
+            .unit_name()
+            .starts_with("run-"));
+    }
+}
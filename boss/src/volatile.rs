@@ -0,0 +1,304 @@
+//! Read-only root filesystems and stateless boot.
+//!
+//! When the journal directory is on a read-only filesystem, log files are
+//! written to a tmpfs under `/run` and flushed once it becomes writable.
+//! In volatile mode `/etc` is overlaid with a tmpfs, so changes to it are
+//! lost at shutdown while `/var` persists. Configuration missing from
+//! `/etc` is provisioned from the factory defaults shipped in
+//! `/usr/share/factory/etc`, the way tmpfiles' `C` lines copy them.
+//!
+//! A machine without an ID gets one at boot. On a read-only root it is
+//! written to `/run/machine-id` and bind-mounted over `/etc/machine-id`,
+//! so programs reading the file find it, until first-boot provisioning
+//! commits it to `/etc` once that is writable.
+
+use crate::error::Result;
+use buckos_core::MachineId;
+use nix::mount::{mount, umount, MsFlags};
+use nix::sys::statvfs::{statvfs, FsFlags};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+/// Directory journal files are written to while the persistent journal
+/// directory is read-only.
+pub const RUNTIME_JOURNAL_DIR: &str = "/run/buckos/journal";
+
+/// Factory defaults of `/etc`.
+pub const FACTORY_ETC_DIR: &str = "/usr/share/factory/etc";
+
+/// Directory holding the tmpfs layer of a volatile `/etc`.
+pub const ETC_OVERLAY_DIR: &str = "/run/buckos/etc-overlay";
+
+/// Where the machine ID is kept while `/etc` is read-only.
+pub const RUNTIME_MACHINE_ID: &str = "/run/machine-id";
+
+/// How the machine got its ID at boot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MachineIdSource {
+    /// The machine ID file held one already
+    Existing,
+    /// A new ID was written to the machine ID file
+    Generated,
+    /// The machine ID file is read-only, so a new ID was mounted over it
+    Transient,
+}
+
+/// Whether `path`, or the nearest existing directory above it, is on a
+/// read-only filesystem.
+pub fn read_only(path: &Path) -> bool {
+    path.ancestors()
+        .find(|p| p.exists())
+        .and_then(|p| statvfs(p).ok())
+        .is_some_and(|stat| stat.flags().contains(FsFlags::ST_RDONLY))
+}
+
+/// Overlay `etc` with a tmpfs mounted at `overlay_dir`.
+///
+/// The existing contents stay visible, but every change is written to
+/// memory and lost at shutdown.
+pub fn mount_etc_overlay(etc: &Path, overlay_dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(overlay_dir)?;
+    mount(
+        Some("tmpfs"),
+        overlay_dir,
+        Some("tmpfs"),
+        MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+        Some("mode=0755"),
+    )?;
+    let upper = overlay_dir.join("upper");
+    let work = overlay_dir.join("work");
+    std::fs::create_dir(&upper)?;
+    std::fs::create_dir(&work)?;
+    // The overlay root takes the mode of the upper directory
+    let mode = std::fs::metadata(etc)?.permissions().mode();
+    std::fs::set_permissions(&upper, std::fs::Permissions::from_mode(mode))?;
+
+    let options = format!(
+        "lowerdir={},upperdir={},workdir={}",
+        etc.display(),
+        upper.display(),
+        work.display()
+    );
+    mount(
+        Some("overlay"),
+        etc,
+        Some("overlay"),
+        MsFlags::empty(),
+        Some(options.as_str()),
+    )?;
+    Ok(())
+}
+
+/// Give the machine an ID at boot, as systemd does.
+///
+/// The ID in `path` is kept if there is one. Otherwise a new one is written
+/// to `path`, or, if it is read-only, to `runtime_path` and bind-mounted
+/// over `path`. The bind mount needs a file to mount over, so images meant
+/// to boot read-only ship an empty `/etc/machine-id`.
+pub fn setup_machine_id(path: &Path, runtime_path: &Path) -> Result<(MachineId, MachineIdSource)> {
+    if let Some(id) = MachineId::read_from(path) {
+        return Ok((id, MachineIdSource::Existing));
+    }
+    let id = MachineId::generate()?;
+    if !read_only(path) {
+        write_machine_id(path, &id)?;
+        return Ok((id, MachineIdSource::Generated));
+    }
+    write_machine_id(runtime_path, &id)?;
+    mount(
+        Some(runtime_path),
+        path,
+        None::<&str>,
+        MsFlags::MS_BIND,
+        None::<&str>,
+    )?;
+    Ok((id, MachineIdSource::Transient))
+}
+
+/// Write the machine ID mounted over `path` to `path` itself, the way
+/// systemd-machine-id-commit does, or a new one if `path` has none.
+///
+/// Returns false if `path` held a permanent ID already. The directory of
+/// `path` must be writable.
+pub fn commit_machine_id(path: &Path) -> Result<bool> {
+    let Some(id) = MachineId::read_from(path) else {
+        write_machine_id(path, &MachineId::generate()?)?;
+        return Ok(true);
+    };
+    let dir = path.parent().unwrap_or(Path::new("/"));
+    // A file bind-mounted from /run is on another device than its directory
+    if std::fs::metadata(path)?.dev() == std::fs::metadata(dir)?.dev() {
+        return Ok(false);
+    }
+    umount(path)?;
+    write_machine_id(path, &id)?;
+    Ok(true)
+}
+
+/// Replace a machine ID file atomically, read-only like systemd's.
+fn write_machine_id(path: &Path, id: &MachineId) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, id.to_file_contents())?;
+    std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o444))?;
+    std::fs::rename(tmp, path)?;
+    Ok(())
+}
+
+/// Copy the entries of `factory` missing from `target`, recursing into
+/// directories both have. Existing files are never replaced.
+///
+/// Returns the paths created, relative to `target`.
+pub fn provision_factory(factory: &Path, target: &Path) -> Result<Vec<PathBuf>> {
+    let mut created = Vec::new();
+    if factory.is_dir() {
+        provision_dir(factory, target, Path::new(""), &mut created)?;
+    }
+    Ok(created)
+}
+
+fn provision_dir(
+    factory: &Path,
+    target: &Path,
+    relative: &Path,
+    created: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let mut entries: Vec<_> =
+        std::fs::read_dir(factory.join(relative))?.collect::<std::io::Result<_>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let relative = relative.join(entry.file_name());
+        let src = entry.path();
+        let dest = target.join(&relative);
+        let meta = std::fs::symlink_metadata(&src)?;
+        let exists = std::fs::symlink_metadata(&dest).is_ok();
+
+        if meta.is_dir() {
+            if !exists {
+                std::fs::DirBuilder::new()
+                    .mode(meta.permissions().mode() & 0o7777)
+                    .create(&dest)?;
+                created.push(relative.clone());
+            }
+            if dest.is_dir() {
+                provision_dir(factory, target, &relative, created)?;
+            }
+        } else if !exists {
+            if meta.file_type().is_symlink() {
+                std::os::unix::fs::symlink(std::fs::read_link(&src)?, &dest)?;
+            } else {
+                std::fs::copy(&src, &dest)?;
+            }
+            created.push(relative);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::{Journal, JournalEntry};
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("boss-volatile-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_provision_keeps_existing_files() {
+        let dir = scratch("provision");
+        let factory = dir.join("factory");
+        let etc = dir.join("etc");
+        std::fs::create_dir_all(factory.join("buckos/services")).unwrap();
+        std::fs::write(factory.join("hostname"), "appliance\n").unwrap();
+        std::fs::write(factory.join("buckos/services/getty.toml"), "").unwrap();
+        std::os::unix::fs::symlink("../proc/self/mounts", factory.join("mtab")).unwrap();
+        std::fs::create_dir_all(etc.join("buckos")).unwrap();
+        std::fs::write(etc.join("hostname"), "kiosk-7\n").unwrap();
+
+        let created = provision_factory(&factory, &etc).unwrap();
+        assert_eq!(
+            created,
+            vec![
+                PathBuf::from("buckos/services"),
+                PathBuf::from("buckos/services/getty.toml"),
+                PathBuf::from("mtab"),
+            ]
+        );
+        assert_eq!(
+            std::fs::read_to_string(etc.join("hostname")).unwrap(),
+            "kiosk-7\n"
+        );
+        assert_eq!(
+            std::fs::read_link(etc.join("mtab")).unwrap(),
+            PathBuf::from("../proc/self/mounts")
+        );
+        assert!(provision_factory(&factory, &etc).unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_machine_id_setup() {
+        let dir = scratch("machine-id");
+        let (path, runtime) = (dir.join("machine-id"), dir.join("run/machine-id"));
+        std::fs::write(&path, "uninitialized\n").unwrap();
+
+        let (id, source) = setup_machine_id(&path, &runtime).unwrap();
+        assert_eq!(source, MachineIdSource::Generated);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), format!("{}\n", id));
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o444
+        );
+        assert_eq!(
+            setup_machine_id(&path, &runtime).unwrap(),
+            (id, MachineIdSource::Existing)
+        );
+        assert!(!runtime.exists());
+        // Nothing is mounted over it, so there is nothing to commit
+        assert!(!commit_machine_id(&path).unwrap());
+        assert_eq!(MachineId::read_from(&path), Some(id));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(commit_machine_id(&path).unwrap());
+        assert_ne!(MachineId::read_from(&path), Some(id));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_journal_flushes_runtime_logs() {
+        let dir = scratch("journal");
+        let journal = Journal::new(dir.join("persistent"));
+        journal
+            .log(JournalEntry::new("sshd", "before", "stdout"))
+            .await;
+        journal.use_runtime_dir(dir.join("runtime"));
+        journal
+            .log(JournalEntry::new("sshd", "while read-only", "stdout"))
+            .await;
+        journal
+            .log(JournalEntry::new("getty", "login", "stdout"))
+            .await;
+        assert!(dir.join("runtime/getty.log").exists());
+        assert_eq!(journal.read_all_from_files().len(), 3);
+
+        assert!(!read_only(&dir.join("persistent")));
+        assert_eq!(journal.flush().unwrap(), 2);
+        assert_eq!(journal.runtime_dir(), None);
+        let messages: Vec<String> = journal
+            .get_logs("sshd", None, false)
+            .await
+            .into_iter()
+            .map(|e| e.message)
+            .collect();
+        assert_eq!(messages, vec!["before", "while read-only"]);
+        assert!(!dir.join("runtime/sshd.log").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -64,6 +64,7 @@ enum Commands {
         flags: Vec<String>,
     },
     /// Start MCP server (Model Context Protocol for AI assistants)
+    #[cfg(feature = "mcp")]
     Mcp {
         /// MCP server configuration file
         #[clap(long)]
@@ -71,6 +72,25 @@ enum Commands {
         /// Run in user-mode (install to ~/.local, no root required)
         #[clap(long)]
         user_mode: bool,
+        /// Serve HTTP with Server-Sent Events on this address instead of stdio
+        #[clap(long)]
+        listen: Option<std::net::SocketAddr>,
+        /// File holding the bearer token HTTP clients must present
+        #[clap(long, requires = "listen")]
+        token_file: Option<PathBuf>,
+        /// TLS certificate chain (PEM)
+        #[clap(long, requires_all = ["listen", "tls_key"])]
+        tls_cert: Option<PathBuf>,
+        /// TLS private key (PKCS#8 PEM)
+        #[clap(long, requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+        /// Let HTTP clients install packages and change configuration when
+        /// there is no policy file
+        #[clap(long, requires = "listen")]
+        remote_writes: bool,
+        /// Tool authorization policy
+        #[clap(long, default_value = buckos_mcp::policy::POLICY_PATH)]
+        policy: PathBuf,
     },
 }
 
@@ -85,6 +105,36 @@ enum ConfigAction {
         /// Configuration value
         value: String,
     },
+    /// Convert between config.toml and the make.conf/package.* tree
+    Convert {
+        /// Representation to convert to: toml or tree
+        #[clap(long)]
+        to: String,
+        /// Configuration root
+        #[clap(long, default_value = "/etc/buckos")]
+        config_root: PathBuf,
+    },
+    /// Show the effective value of a variable, USE flag or package and
+    /// every setting that contributed to it
+    Explain {
+        /// Variable (CFLAGS), USE flag (wayland) or package (app-editors/vim)
+        query: String,
+        /// Configuration root
+        #[clap(long, default_value = "/etc/buckos")]
+        config_root: PathBuf,
+    },
+    /// Warn about remote configuration overridden by local settings
+    Lint {
+        /// Configuration root
+        #[clap(long, default_value = "/etc/buckos")]
+        config_root: PathBuf,
+    },
+    /// Sync the remote configuration repositories in remote.conf
+    Sync {
+        /// Configuration root
+        #[clap(long, default_value = "/etc/buckos")]
+        config_root: PathBuf,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -167,6 +217,10 @@ async fn main() -> Result<()> {
             println!("Syncing repository from {}", repo_path.display());
             pm.sync().await?;
             println!("Repository synced successfully");
+            let config_root = buckos_config::loader::get_config_root();
+            for remote in buckos_config::remote::sync_all(&config_root)? {
+                println!("Synced remote configuration {}", remote.name);
+            }
         }
         Some(Commands::Install { packages, root }) => {
             // Command-specific root overrides global root
@@ -235,9 +289,16 @@ async fn main() -> Result<()> {
         Some(Commands::Use { action, flags }) => {
             handle_use(action, flags, &repo_path).await?;
         }
+        #[cfg(feature = "mcp")]
         Some(Commands::Mcp {
             mcp_config,
             user_mode,
+            listen,
+            token_file,
+            tls_cert,
+            tls_key,
+            remote_writes,
+            policy,
         }) => {
             use buckos_mcp::{ExecutionContext, McpServer, ServerConfig};
 
@@ -254,8 +315,41 @@ async fn main() -> Result<()> {
                 context.enable_user_mode();
             }
 
-            let server = McpServer::new(pm, config, context);
-            server.serve_stdio().await?;
+            let policy = if policy.exists() {
+                if remote_writes {
+                    eprintln!(
+                        "Warning: --remote-writes is ignored; {} decides what clients may do",
+                        policy.display()
+                    );
+                }
+                buckos_mcp::Policy::load(&policy)?
+            } else {
+                buckos_mcp::Policy::builtin(remote_writes)
+            };
+
+            let server = McpServer::new(pm, config, context).with_policy(policy);
+            match listen {
+                Some(listen) => {
+                    let token = match token_file {
+                        Some(path) => std::fs::read_to_string(&path)?.trim().to_string(),
+                        None => std::env::var("BUCKOS_MCP_TOKEN").map_err(|_| {
+                            anyhow::anyhow!("--listen needs --token-file or BUCKOS_MCP_TOKEN")
+                        })?,
+                    };
+                    let tls = tls_cert
+                        .zip(tls_key)
+                        .map(|(cert, key)| buckos_mcp::TlsConfig { cert, key });
+                    server
+                        .serve_http(buckos_mcp::HttpConfig {
+                            listen,
+                            token,
+                            clients: Vec::new(),
+                            tls,
+                        })
+                        .await?;
+                }
+                None => server.serve_stdio().await?,
+            }
         }
         None => {
             println!("Buckos Package Manager");
@@ -347,11 +441,77 @@ async fn handle_config(action: ConfigAction, repo_path: &Path) -> Result<()> {
                 println!("Synced modifier config to {}", config.buck_repo.display());
             }
         }
+        ConfigAction::Convert { to, config_root } => convert_config(&to, &config_root)?,
+        ConfigAction::Explain { query, config_root } => {
+            let explanation = buckos_config::explain(&config_root, &query)?;
+            println!("{} = {}", explanation.query, explanation.value);
+            for contribution in &explanation.contributions {
+                println!(
+                    "  {:<8} {:<50} {}",
+                    contribution.layer.to_string(),
+                    contribution.location(),
+                    contribution.text
+                );
+            }
+        }
+        ConfigAction::Lint { config_root } => {
+            let mut config = buckos_config::ConfigLoader::new(&config_root)
+                .remote(false)
+                .load()?;
+            let conflicts = buckos_config::remote::merge(&mut config)?;
+            for conflict in &conflicts {
+                println!("warning: {}", conflict);
+            }
+            if conflicts.is_empty() {
+                println!("No problems found");
+            }
+        }
+        ConfigAction::Sync { config_root } => {
+            for remote in buckos_config::remote::sync_all(&config_root)? {
+                println!("Synced {} from {}", remote.name, remote.sync_uri);
+            }
+        }
     }
 
     Ok(())
 }
 
+/// Handle `buckos config convert`
+fn convert_config(to: &str, config_root: &Path) -> Result<()> {
+    use buckos_config::toml_config::CONFIG_TOML;
+    use buckos_config::{ConfigLoader, TomlConfig};
+
+    let toml_path = config_root.join(CONFIG_TOML);
+    // Remote policy stays in its repository rather than being copied in
+    let config = ConfigLoader::new(config_root).remote(false).load()?;
+    match to {
+        "toml" => {
+            if toml_path.exists() {
+                anyhow::bail!("{} already exists", toml_path.display());
+            }
+            TomlConfig::from_portage(&config).save(&toml_path)?;
+            println!("Wrote {}", toml_path.display());
+            println!("The make.conf and package.* files are now ignored and can be removed.");
+        }
+        "tree" => {
+            if !toml_path.exists() {
+                anyhow::bail!("{} does not exist", toml_path.display());
+            }
+            config.save_tree()?;
+            // The loader prefers config.toml, so move it out of the way
+            let backup = toml_path.with_extension("toml.old");
+            std::fs::rename(&toml_path, &backup)?;
+            println!(
+                "Wrote make.conf and package.* files under {}",
+                config_root.display()
+            );
+            println!("Moved {} to {}", toml_path.display(), backup.display());
+        }
+        other => anyhow::bail!("Unknown representation '{}'. Use toml or tree", other),
+    }
+    Ok(())
+}
+
 /// Handle `buckos use` subcommands
 async fn handle_use(action: Option<UseAction>, flags: Vec<String>, repo_path: &Path) -> Result<()> {
     use buckos_package::PackageId;
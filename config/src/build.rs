@@ -50,6 +50,9 @@ PORTAGE_TMPDIR="/var/tmp"
 # existing installs where bzip2 is used for backward compatibility.
 BINPKG_COMPRESS="zstd"
 
+# Flags for the BINPKG_COMPRESS compressor, such as "-19 -T0" for zstd.
+BINPKG_COMPRESS_FLAGS=""
+
 # The format used for binary packages. The default is use old "xpak" format.
 # Set to "gpkg" to use new gentoo binary package format.
 BINPKG_FORMAT="xpak"
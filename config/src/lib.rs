@@ -17,9 +17,12 @@
 //! - [`repos`]: Repository configuration (repos.conf)
 //! - [`profile`]: System profile configuration
 //! - [`sets`]: Package sets (@world, @system, custom)
+//! - [`toml_config`]: Single-file TOML alternative to the tree below
 //! - [`features`]: FEATURES configuration
 //! - [`mirrors`]: Mirror configuration
 //! - [`loader`]: Configuration loading utilities
+//! - [`remote`]: Shared configuration synced from git (remote.conf)
+//! - [`provenance`]: Which layer set a value (`buckos config explain`)
 //!
 //! # Quick Start
 //!
@@ -69,7 +72,9 @@
 //! ├── package.env/           # Per-package environment
 //! ├── env/                   # Environment file definitions
 //! ├── sets/                  # Custom package sets
-//! └── world                  # User-selected packages
+//! ├── world                  # User-selected packages
+//! ├── remote.conf            # Git repositories of shared configuration
+//! └── config.toml            # All of the above in one file (optional)
 //! ```
 
 // Core modules
@@ -88,8 +93,11 @@ pub mod mirrors;
 pub mod package_sets_parser;
 pub mod portage;
 pub mod profile;
+pub mod provenance;
+pub mod remote;
 pub mod repos;
 pub mod sets;
+pub mod toml_config;
 pub mod use_flags;
 
 // Legacy modules (kept for compatibility)
@@ -112,8 +120,11 @@ pub use mirrors::{Mirror, MirrorConfig, MirrorStrategy, ThirdpartyMirrors};
 pub use package_sets_parser::{PackageSetInfo, PackageSets};
 pub use portage::{PortageConfig, PortageConfigBuilder};
 pub use profile::{AvailableProfiles, ProfileConfig, ProfileEntry, ProfileInfo, ProfileStatus};
+pub use provenance::{explain, Contribution, Explanation, Layer};
+pub use remote::{Conflict, RemoteConfig};
 pub use repos::{RepoDefaults, ReposConfig, Repository, SyncType};
 pub use sets::{PackageSet, SetsConfig};
+pub use toml_config::TomlConfig;
 pub use use_flags::{PackageUseEntry, UseConfig, UseExpandVariable, UseFlag, UseFlagDescription};
 
 // Re-export legacy types
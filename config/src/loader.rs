@@ -3,7 +3,8 @@
 //! Provides utilities for loading and parsing configuration files
 //! from the filesystem in various formats.
 
-use crate::{ConfigError, PortageConfig, Result};
+use crate::toml_config::{TomlConfig, CONFIG_TOML};
+use crate::{ConfigError, PortageConfig, ProfileConfig, Result};
 use std::path::{Path, PathBuf};
 
 /// Configuration loader for loading system configuration
@@ -14,6 +15,8 @@ pub struct ConfigLoader {
     use_defaults: bool,
     /// Whether to validate configuration after loading
     validate: bool,
+    /// Whether to merge in synced remote configuration
+    remote: bool,
 }
 
 impl ConfigLoader {
@@ -23,6 +26,7 @@ impl ConfigLoader {
             root: root.into(),
             use_defaults: true,
             validate: true,
+            remote: true,
         }
     }
 
@@ -50,6 +54,12 @@ impl ConfigLoader {
         self
     }
 
+    /// Set whether to merge in remote configuration from remote.conf
+    pub fn remote(mut self, remote: bool) -> Self {
+        self.remote = remote;
+        self
+    }
+
     /// Load the complete configuration
     pub fn load(&self) -> Result<PortageConfig> {
         if !self.root.exists() {
@@ -60,7 +70,7 @@ impl ConfigLoader {
             }
         }
 
-        let config = PortageConfig::load(&self.root)?;
+        let config = load_root(&self.root, self.remote)?;
 
         if self.validate {
             validate_config(&config)?;
@@ -75,7 +85,7 @@ impl ConfigLoader {
 
         // Load overlay configuration
         if overlay.exists() {
-            let overlay_config = PortageConfig::load(overlay)?;
+            let overlay_config = load_root(overlay, self.remote)?;
 
             // Merge overlay into base config
             merge_configs(&mut config, &overlay_config);
@@ -118,8 +128,59 @@ impl ConfigLoader {
     }
 }
 
+/// Load the configuration under a root, from config.toml if it has one
+/// and from the make.conf and package.* tree otherwise, along with the
+/// profile make.profile points to and, if `remote` is set, the synced
+/// remote configuration
+fn load_root(root: &Path, remote: bool) -> Result<PortageConfig> {
+    let toml_path = root.join(CONFIG_TOML);
+    let mut config = if toml_path.exists() {
+        let ignored: Vec<&str> = [
+            "make.conf",
+            "package.use",
+            "package.accept_keywords",
+            "package.mask",
+        ]
+        .into_iter()
+        .filter(|name| root.join(name).exists())
+        .collect();
+        if !ignored.is_empty() {
+            tracing::warn!(
+                "{} is used; ignoring {} in the same directory",
+                toml_path.display(),
+                ignored.join(", ")
+            );
+        }
+        TomlConfig::load(&toml_path)?.into_portage(root)?
+    } else {
+        PortageConfig::load(root)?
+    };
+
+    if remote {
+        for conflict in crate::remote::merge(&mut config)? {
+            tracing::debug!("{}; the local setting wins", conflict);
+        }
+    }
+
+    if let Some(path) = profile_path(root) {
+        config.profile = ProfileConfig::load(&path)?;
+        let path = path.to_string_lossy();
+        config.profile.current = path
+            .split_once("/profiles/")
+            .map_or(&*path, |(_, name)| name)
+            .to_string();
+    }
+
+    Ok(config)
+}
+
+/// The profile directory make.profile points to, if it exists
+pub fn profile_path(root: &Path) -> Option<PathBuf> {
+    root.join("make.profile").canonicalize().ok()
+}
+
 /// Validate configuration for common issues
-fn validate_config(config: &PortageConfig) -> Result<()> {
+pub fn validate_config(config: &PortageConfig) -> Result<()> {
     // Check CHOST format
     if !config.make_conf.chost.contains('-') {
         return Err(ConfigError::Invalid(format!(
@@ -216,6 +277,11 @@ pub mod paths {
         system_config().join("make.conf")
     }
 
+    /// Single-file alternative to make.conf and the package.* files
+    pub fn config_toml() -> PathBuf {
+        system_config().join(crate::toml_config::CONFIG_TOML)
+    }
+
     /// Package database
     pub fn package_db() -> PathBuf {
         PathBuf::from("/var/db/buckos")
@@ -316,6 +382,32 @@ mod tests {
         validate_config(&config).unwrap();
     }
 
+    #[test]
+    fn test_config_toml_and_tree() {
+        let root = std::env::temp_dir().join(format!("buckos-config-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let toml = "mask = [\"dev-lang/python:2.7\"]\n\n[make]\nCFLAGS = \"-O3\"\n\n\
+                    [use]\n\"app-editors/vim\" = \"python\"\n\n\
+                    [sets]\nworld = [\"app-editors/vim\"]\ntools = [\"dev-vcs/git\", \"@world\"]\n";
+        std::fs::write(root.join("config.toml"), toml).unwrap();
+
+        let config = ConfigLoader::new(&root).load().unwrap();
+        assert_eq!(config.make_conf.cflags, "-O3");
+
+        // Writing the tree and dropping config.toml gives the same configuration
+        config.save_tree().unwrap();
+        std::fs::remove_file(root.join("config.toml")).unwrap();
+        let tree = ConfigLoader::new(&root).load().unwrap();
+        assert_eq!(
+            TomlConfig::from_portage(&tree),
+            TomlConfig::from_portage(&config)
+        );
+        assert!(tree.is_in_world("app-editors", "vim"));
+        assert_eq!(tree.sets.get("tools").unwrap().dependencies, vec!["world"]);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
     #[test]
     fn test_paths() {
         assert_eq!(paths::system_config(), PathBuf::from("/etc/buckos"));
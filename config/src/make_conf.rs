@@ -83,6 +83,8 @@ pub struct MakeConf {
     // === Binary packages ===
     /// Binary package compression
     pub binpkg_compress: String,
+    /// Flags for the binary package compressor, e.g. "-19 -T0"
+    pub binpkg_compress_flags: String,
     /// Binary package format
     pub binpkg_format: String,
 
@@ -167,6 +169,7 @@ impl Default for MakeConf {
 
             // Binary packages
             binpkg_compress: "zstd".to_string(),
+            binpkg_compress_flags: String::new(),
             binpkg_format: "gpkg".to_string(),
 
             // Config protection
@@ -5,7 +5,7 @@
 
 use crate::{
     EnvConfig, KeywordConfig, LicenseConfig, MakeConf, MaskConfig, PackageAtom, ProfileConfig,
-    ReposConfig, Result, SetsConfig, UseConfig,
+    RemoteConfig, ReposConfig, Result, SetsConfig, UseConfig,
 };
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
@@ -23,6 +23,7 @@ use std::path::{Path, PathBuf};
 /// - repos.conf -> Repository configuration
 /// - profile -> System profile
 /// - sets -> Custom package sets
+/// - remote.conf -> Git repositories of shared configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PortageConfig {
     /// Global make.conf settings
@@ -52,6 +53,10 @@ pub struct PortageConfig {
     /// Per-package environment (package.env)
     pub package_env: EnvConfig,
 
+    /// Git repositories of shared configuration (remote.conf)
+    #[serde(default)]
+    pub remotes: Vec<RemoteConfig>,
+
     /// Configuration root path
     pub config_root: PathBuf,
 }
@@ -68,6 +73,7 @@ impl Default for PortageConfig {
             package_license: LicenseConfig::default(),
             package_mask: MaskConfig::default(),
             package_env: EnvConfig::default(),
+            remotes: Vec::new(),
             config_root: PathBuf::from("/etc/buckos"),
         }
     }
@@ -136,6 +142,13 @@ impl PortageConfig {
             config.sets = SetsConfig::load_from_dir(&sets_path)?;
         }
 
+        // Load remote.conf
+        let remote_path = config_root.join("remote.conf");
+        if remote_path.exists() {
+            let content = std::fs::read_to_string(&remote_path)?;
+            config.remotes = crate::remote::parse_remote_conf(&content)?;
+        }
+
         // Load world file
         let world_path = config_root.join("world");
         if world_path.exists() {
@@ -164,6 +177,98 @@ impl PortageConfig {
         Ok(())
     }
 
+    /// Save the whole configuration as a make.conf and package.* tree
+    ///
+    /// Each package.* file is replaced. A package.* directory is refused
+    /// rather than merged into, as its other files would still be read.
+    pub fn save_tree(&self) -> Result<()> {
+        std::fs::create_dir_all(self.config_root.join("repos.conf"))?;
+        std::fs::create_dir_all(self.config_root.join("sets"))?;
+
+        for (name, content) in self.tree_files()? {
+            let path = self.config_root.join(name);
+            if path.is_dir() {
+                return Err(crate::ConfigError::Invalid(format!(
+                    "{} is a directory; move it aside to write a single file",
+                    path.display()
+                )));
+            }
+            std::fs::write(path, content)?;
+        }
+
+        Ok(())
+    }
+
+    /// The files [`save_tree`](Self::save_tree) writes, relative to the
+    /// configuration root, with their content
+    pub fn tree_files(&self) -> Result<Vec<(PathBuf, String)>> {
+        let lines = |entries: Vec<(String, String)>| {
+            entries
+                .into_iter()
+                .map(|(atom, values)| format!("{} {}\n", atom, values))
+                .collect::<String>()
+        };
+        let package_use = self
+            .package_use
+            .package
+            .iter()
+            .map(|e| (e.atom.to_string(), join(&e.flags)))
+            .collect();
+        let package_keywords = self
+            .package_keywords
+            .package
+            .iter()
+            .map(|e| (e.atom.to_string(), join(&e.keywords)))
+            .collect();
+        let package_license = self
+            .package_license
+            .package
+            .iter()
+            .map(|e| (e.atom.to_string(), e.licenses.join(" ")))
+            .collect();
+
+        let mut files = vec![
+            (
+                PathBuf::from("make.conf"),
+                toml::to_string_pretty(&self.make_conf)?,
+            ),
+            (PathBuf::from("package.use"), lines(package_use)),
+            (
+                PathBuf::from("package.accept_keywords"),
+                lines(package_keywords),
+            ),
+            (PathBuf::from("package.license"), lines(package_license)),
+            (
+                PathBuf::from("package.mask"),
+                crate::mask::format_mask_file(&self.package_mask.masked),
+            ),
+            (
+                PathBuf::from("package.unmask"),
+                crate::mask::format_mask_file(&self.package_mask.unmasked),
+            ),
+            (
+                PathBuf::from("repos.conf/buckos.conf"),
+                crate::repos::format_repos_conf(&self.repos),
+            ),
+        ];
+        if !self.remotes.is_empty() {
+            files.push((
+                PathBuf::from("remote.conf"),
+                crate::remote::format_remote_conf(&self.remotes),
+            ));
+        }
+        if let Some(world) = self.sets.get("world") {
+            files.push((PathBuf::from("world"), world.format()));
+        }
+        for set in self.sets.customized() {
+            if set.name != "world" {
+                files.push((Path::new("sets").join(&set.name), set.format()));
+            }
+        }
+
+        Ok(files)
+    }
+
     /// Get effective USE flags for a package
     pub fn effective_use(&self, category: &str, name: &str) -> std::collections::HashSet<String> {
         // Start with global flags from make.conf
@@ -187,6 +292,14 @@ impl PortageConfig {
             }
         }
 
+        // Profile use.mask and use.force win over any setting
+        for flag in &self.profile.use_config.mask {
+            flags.remove(flag);
+        }
+        for flag in &self.profile.use_config.force {
+            flags.insert(flag.clone());
+        }
+
         flags
     }
 
@@ -380,6 +493,15 @@ fn load_package_license(path: &Path) -> Result<LicenseConfig> {
     Ok(config)
 }
 
+/// Join displayable values with spaces
+fn join<T: std::fmt::Display>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 /// Read content from a path (file or directory)
 fn read_config_path(path: &Path) -> Result<String> {
     if path.is_dir() {
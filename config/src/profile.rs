@@ -264,6 +264,28 @@ impl ProfileInfo {
             }
         }
 
+        // Read package.use
+        let pkg_use = path.join("package.use");
+        if pkg_use.is_file() {
+            let content = std::fs::read_to_string(pkg_use)?;
+            for line in content.lines() {
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                if parts.len() >= 2 && !parts[0].starts_with('#') {
+                    if let Ok(atom) = parts[0].parse::<PackageAtom>() {
+                        let flags = UseConfig::parse_use_string(&parts[1..].join(" "));
+                        info.use_config.add_package_use(atom, flags);
+                    }
+                }
+            }
+        }
+
+        // Read package.mask
+        let pkg_mask = path.join("package.mask");
+        if pkg_mask.is_file() {
+            let content = std::fs::read_to_string(pkg_mask)?;
+            info.mask_config.masked = crate::mask::parse_mask_file(&content);
+        }
+
         // Read provided packages
         let packages_file = path.join("packages");
         if packages_file.exists() {
@@ -0,0 +1,583 @@
+//! Where configuration values come from
+//!
+//! [`explain`] reports the effective value of a make.conf variable, a
+//! global USE flag or a package's settings, along with every line that
+//! contributed to it, layer by layer:
+//!
+//! 1. built-in defaults
+//! 2. the profile make.profile points to, parents first
+//! 3. remote configuration from remote.conf, for packages
+//! 4. the configuration root, from config.toml or from make.conf and the
+//!    package.* files
+//! 5. the environment, for variables
+//!
+//! Variables are layered as Portage layers them. USE, FEATURES,
+//! ACCEPT_KEYWORDS and the other incremental variables add to the layers
+//! below them, with `-value` removing a value and `-*` clearing them all.
+//! Any other variable takes its value from the last layer that sets it.
+
+use crate::loader::profile_path;
+use crate::toml_config::{make_values, MaskToml, TomlConfig, CONFIG_TOML};
+use crate::{ConfigLoader, MakeConf, PackageAtom, ProfileConfig, Result};
+use std::path::{Path, PathBuf};
+
+/// Variables whose layers add up instead of replacing each other
+const INCREMENTAL: &[&str] = &[
+    "USE",
+    "FEATURES",
+    "ACCEPT_KEYWORDS",
+    "ACCEPT_LICENSE",
+    "CONFIG_PROTECT",
+    "CONFIG_PROTECT_MASK",
+    "INPUT_DEVICES",
+    "VIDEO_CARDS",
+    "L10N",
+];
+
+/// Per-package files, in the profile and the configuration root
+const PROFILE_PACKAGE_FILES: &[&str] = &["package.use", "package.use.force", "package.mask"];
+const PACKAGE_FILES: &[&str] = &[
+    "package.use",
+    "package.accept_keywords",
+    "package.license",
+    "package.mask",
+    "package.unmask",
+];
+
+/// A configuration layer, lowest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    Default,
+    Profile,
+    Remote,
+    Config,
+    Environment,
+}
+
+impl std::fmt::Display for Layer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Layer::Default => write!(f, "default"),
+            Layer::Profile => write!(f, "profile"),
+            Layer::Remote => write!(f, "remote"),
+            Layer::Config => write!(f, "config"),
+            Layer::Environment => write!(f, "env"),
+        }
+    }
+}
+
+/// A setting that contributed to a value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Contribution {
+    pub layer: Layer,
+    /// File the setting is in; none for defaults and the environment
+    pub source: Option<PathBuf>,
+    /// Line of the setting, counting from 1
+    pub line: Option<usize>,
+    /// The setting as written
+    pub text: String,
+}
+
+impl Contribution {
+    /// Where the setting is, as `file:line`
+    pub fn location(&self) -> String {
+        match (&self.source, self.line) {
+            (Some(source), Some(line)) => format!("{}:{}", source.display(), line),
+            (Some(source), None) => source.display().to_string(),
+            (None, _) if self.layer == Layer::Environment => "environment".to_string(),
+            (None, _) => "built in".to_string(),
+        }
+    }
+}
+
+/// The effective value of a query and what set it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    pub query: String,
+    pub value: String,
+    /// Contributing settings, lowest layer first
+    pub contributions: Vec<Contribution>,
+}
+
+/// Explain a query against the configuration under `root`.
+///
+/// A query with a slash is a package atom. A name of two or more capital
+/// letters, digits and underscores is a variable, and anything else is a
+/// USE flag.
+pub fn explain(root: &Path, query: &str) -> Result<Explanation> {
+    if query.contains('/') {
+        explain_package(root, query)
+    } else if query.len() > 1
+        && query
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+    {
+        explain_variable(root, query)
+    } else {
+        explain_use_flag(root, query)
+    }
+}
+
+fn explain_variable(root: &Path, var: &str) -> Result<Explanation> {
+    let layers = variable_layers(root, var)?;
+    let value = layers
+        .iter()
+        .fold(String::new(), |value, (_, set)| apply(var, &value, set));
+    Ok(Explanation {
+        query: var.to_string(),
+        value,
+        contributions: layers.into_iter().map(|(c, _)| c).collect(),
+    })
+}
+
+fn explain_use_flag(root: &Path, flag: &str) -> Result<Explanation> {
+    let mentions = |value: &str| {
+        value
+            .split_whitespace()
+            .any(|token| token.trim_start_matches('-') == flag || token == "-*")
+    };
+    let layers = variable_layers(root, "USE")?;
+    let global = layers
+        .iter()
+        .fold(String::new(), |value, (_, set)| apply("USE", &value, set));
+    let mut contributions: Vec<Contribution> = layers
+        .into_iter()
+        .filter(|(_, value)| mentions(value))
+        .map(|(c, _)| c)
+        .collect();
+
+    // use.force and use.mask win over USE, each line overriding the last
+    let mut forced = false;
+    let mut masked = false;
+    for dir in profile_dirs(root)? {
+        for (file, state) in [("use.force", &mut forced), ("use.mask", &mut masked)] {
+            for (source, line, text) in setting_lines(&dir.join(file)) {
+                if text.trim_start_matches('-') == flag {
+                    *state = !text.starts_with('-');
+                    contributions.push(Contribution {
+                        layer: Layer::Profile,
+                        source: Some(source),
+                        line: Some(line),
+                        text,
+                    });
+                }
+            }
+        }
+    }
+
+    let value = if masked {
+        "disabled (masked by the profile)"
+    } else if forced {
+        "enabled (forced by the profile)"
+    } else if global.split_whitespace().any(|f| f == flag) {
+        "enabled"
+    } else {
+        "disabled"
+    };
+    Ok(Explanation {
+        query: flag.to_string(),
+        value: value.to_string(),
+        contributions,
+    })
+}
+
+fn explain_package(root: &Path, query: &str) -> Result<Explanation> {
+    let atom: PackageAtom = query.parse()?;
+    let matches = |text: &str| {
+        text.split_whitespace()
+            .next()
+            .and_then(|first| first.parse::<PackageAtom>().ok())
+            .is_some_and(|a| a.matches_cpn(&atom.category, &atom.name))
+    };
+    let mut contributions = Vec::new();
+
+    for dir in profile_dirs(root)? {
+        for file in PROFILE_PACKAGE_FILES {
+            for (source, line, text) in setting_lines(&dir.join(file)) {
+                if matches(&text) {
+                    contributions.push(Contribution {
+                        layer: Layer::Profile,
+                        source: Some(source),
+                        line: Some(line),
+                        text,
+                    });
+                }
+            }
+        }
+    }
+
+    let mut config = ConfigLoader::new(root).validate(false).load()?;
+    for remote in &config.remotes {
+        for file in PACKAGE_FILES.iter().filter(|f| remote.includes(f)) {
+            for (source, line, text) in setting_lines(&remote.location.join(file)) {
+                if matches(&text) {
+                    contributions.push(Contribution {
+                        layer: Layer::Remote,
+                        source: Some(source),
+                        line: Some(line),
+                        text,
+                    });
+                }
+            }
+        }
+    }
+
+    let toml_path = root.join(CONFIG_TOML);
+    if toml_path.exists() {
+        let content = std::fs::read_to_string(&toml_path)?;
+        let toml: TomlConfig = toml::from_str(&content)?;
+        let tables = [
+            ("use", &toml.package_use),
+            ("keywords", &toml.keywords),
+            ("licenses", &toml.licenses),
+        ];
+        for (section, table) in tables {
+            for (key, value) in table {
+                if matches(key) {
+                    contributions.push(Contribution {
+                        layer: Layer::Config,
+                        source: Some(toml_path.clone()),
+                        line: toml_line(&content, section, key),
+                        text: format!("{}: {} {}", section, key, value),
+                    });
+                }
+            }
+        }
+        for (kind, masks) in [("mask", &toml.mask), ("unmask", &toml.unmask)] {
+            for mask in masks {
+                let (MaskToml::Atom(key) | MaskToml::Entry { atom: key, .. }) = mask;
+                if matches(key) {
+                    // Masks are in arrays before the first table
+                    let quoted = format!("\"{}\"", key);
+                    let line = content
+                        .lines()
+                        .take_while(|l| !l.trim_start().starts_with('['))
+                        .position(|l| l.contains(&quoted))
+                        .map(|i| i + 1);
+                    contributions.push(Contribution {
+                        layer: Layer::Config,
+                        source: Some(toml_path.clone()),
+                        line,
+                        text: format!("{}: {}", kind, key),
+                    });
+                }
+            }
+        }
+    } else {
+        for file in PACKAGE_FILES {
+            for (source, line, text) in setting_lines(&root.join(file)) {
+                if matches(&text) {
+                    contributions.push(Contribution {
+                        layer: Layer::Config,
+                        source: Some(source),
+                        line: Some(line),
+                        text,
+                    });
+                }
+            }
+        }
+    }
+
+    // Global USE as layered above, not only the configuration root's
+    config.make_conf.use_config.global = explain_variable(root, "USE")?
+        .value
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    let (category, name) = (&atom.category, &atom.name);
+    let sorted = |values: std::collections::HashSet<String>| {
+        let mut values: Vec<String> = values.into_iter().collect();
+        values.sort();
+        values.join(" ")
+    };
+    let value = format!(
+        "USE=\"{}\" ACCEPT_KEYWORDS=\"{}\" {}",
+        sorted(config.effective_use(category, name)),
+        sorted(config.package_keywords.effective_keywords(category, name)),
+        if config.is_masked(category, name, atom.version.as_deref()) {
+            "masked"
+        } else {
+            "not masked"
+        }
+    );
+    Ok(Explanation {
+        query: query.to_string(),
+        value,
+        contributions,
+    })
+}
+
+/// Every setting of a variable with the value it sets, lowest layer first
+fn variable_layers(root: &Path, var: &str) -> Result<Vec<(Contribution, String)>> {
+    let mut layers = Vec::new();
+    let setting = |layer, source: Option<&Path>, line, text: String| Contribution {
+        layer,
+        source: source.map(Path::to_path_buf),
+        line,
+        text,
+    };
+
+    let defaults = make_values(&MakeConf::default());
+    if let Some(value) = defaults.get(var).filter(|v| !v.is_empty()) {
+        let text = format!("{}=\"{}\"", var, value);
+        layers.push((setting(Layer::Default, None, None, text), value.clone()));
+    }
+
+    for dir in profile_dirs(root)? {
+        for (source, line, text) in setting_lines(&dir.join("make.defaults")) {
+            if let Some(value) = assignment(&text, var) {
+                let value = value.to_string();
+                layers.push((
+                    setting(Layer::Profile, Some(&source), Some(line), text),
+                    value,
+                ));
+            }
+        }
+    }
+
+    let toml_path = root.join(CONFIG_TOML);
+    let make_conf = root.join("make.conf");
+    if toml_path.exists() {
+        let content = std::fs::read_to_string(&toml_path)?;
+        let toml: TomlConfig = toml::from_str(&content)?;
+        if let Some(value) = toml.make.get(var) {
+            let line = toml_line(&content, "make", var);
+            let text = format!("{} = \"{}\"", var, value);
+            layers.push((
+                setting(Layer::Config, Some(&toml_path), line, text),
+                value.clone(),
+            ));
+        }
+    } else if make_conf.exists() {
+        // make.conf holds every variable; only those changed from the
+        // default were set by someone
+        let content = std::fs::read_to_string(&make_conf)?;
+        let make: MakeConf = toml::from_str(&content)?;
+        let value = make_values(&make).get(var).cloned();
+        if let Some(value) = value.filter(|v| defaults.get(var) != Some(v)) {
+            let (section, key) = make_conf_key(var);
+            let line = toml_line(&content, section, &key);
+            let text = format!("{}=\"{}\"", var, value);
+            layers.push((setting(Layer::Config, Some(&make_conf), line, text), value));
+        }
+    }
+
+    if let Ok(value) = std::env::var(var) {
+        let text = format!("{}=\"{}\"", var, value);
+        layers.push((setting(Layer::Environment, None, None, text), value));
+    }
+
+    Ok(layers)
+}
+
+/// The value of a variable after a layer sets it to `set`
+fn apply(var: &str, value: &str, set: &str) -> String {
+    if !INCREMENTAL.contains(&var) && !var.starts_with("CPU_FLAGS_") {
+        return set.to_string();
+    }
+    let mut values: Vec<&str> = value.split_whitespace().collect();
+    for token in set.split_whitespace() {
+        match token.strip_prefix('-') {
+            Some("*") => values.clear(),
+            Some(removed) => values.retain(|v| *v != removed),
+            None if !values.contains(&token) => values.push(token),
+            None => {}
+        }
+    }
+    values.join(" ")
+}
+
+/// The value of `VAR="value"` in a make.defaults line, if it sets `var`
+fn assignment<'a>(line: &'a str, var: &str) -> Option<&'a str> {
+    let value = line.strip_prefix(var)?.trim_start().strip_prefix('=')?;
+    Some(value.trim().trim_matches('"').trim_matches('\''))
+}
+
+/// Section and key of a variable in make.conf
+fn make_conf_key(var: &str) -> (&'static str, String) {
+    match var {
+        "USE" => ("use_config", "global".to_string()),
+        "FEATURES" => ("features", "enabled".to_string()),
+        "ACCEPT_KEYWORDS" => ("keywords", "accept_keywords".to_string()),
+        "ACCEPT_LICENSE" => ("license", "accept_license".to_string()),
+        "GENTOO_MIRRORS" => ("mirrors", "mirrors".to_string()),
+        "PORT_LOGDIR" => ("", "logdir".to_string()),
+        "PORTAGE_TMPDIR" => ("", "tmpdir".to_string()),
+        "PORTDIR" => ("", "repodir".to_string()),
+        _ if var.starts_with("CPU_FLAGS_") => ("cpu_flags", var.to_string()),
+        _ if make_values(&MakeConf::default()).contains_key(var) => ("", var.to_lowercase()),
+        _ => ("custom", var.to_string()),
+    }
+}
+
+/// Line of `key = ...` within `[section]` of a TOML document, with ""
+/// for the keys before the first table
+fn toml_line(content: &str, section: &str, key: &str) -> Option<usize> {
+    let mut current = "";
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = header.trim();
+        } else if current == section {
+            let found = line
+                .split_once('=')
+                .is_some_and(|(k, _)| k.trim().trim_matches('"') == key);
+            if found {
+                return Some(i + 1);
+            }
+        }
+    }
+    None
+}
+
+/// The profile directories, parents first
+fn profile_dirs(root: &Path) -> Result<Vec<PathBuf>> {
+    match profile_path(root) {
+        Some(path) => Ok(ProfileConfig::load(&path)?
+            .stack
+            .into_iter()
+            .map(|info| info.path)
+            .collect()),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Settings in a file, or in each file of a directory in name order, with
+/// their file and line, skipping blank lines and comments
+fn setting_lines(path: &Path) -> Vec<(PathBuf, usize, String)> {
+    let files = if path.is_dir() {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(path)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.is_file())
+            .collect();
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut lines = Vec::new();
+    for file in files {
+        let Ok(content) = std::fs::read_to_string(&file) else {
+            continue;
+        };
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with('#') {
+                lines.push((file.clone(), i + 1, line.to_string()));
+            }
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A root with a two-level profile and a config.toml
+    fn root(name: &str) -> PathBuf {
+        let base = std::env::temp_dir().join(format!("buckos-{}-{}", name, std::process::id()));
+        let profiles = base.join("profiles");
+        std::fs::create_dir_all(profiles.join("base")).unwrap();
+        std::fs::create_dir_all(profiles.join("desktop")).unwrap();
+        std::fs::write(
+            profiles.join("base/make.defaults"),
+            "USE=\"ssl ipv6 gtk\"\nCFLAGS=\"-O2\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            profiles.join("base/package.mask"),
+            "# Unmaintained\ndev-lang/python:2.7\n",
+        )
+        .unwrap();
+        std::fs::write(profiles.join("desktop/parent"), "../base\n").unwrap();
+        std::fs::write(
+            profiles.join("desktop/make.defaults"),
+            "USE=\"X wayland\"\n",
+        )
+        .unwrap();
+        std::fs::write(profiles.join("desktop/use.mask"), "wayland\n").unwrap();
+
+        let root = base.join("etc");
+        std::fs::create_dir_all(&root).unwrap();
+        std::os::unix::fs::symlink(profiles.join("desktop"), root.join("make.profile")).unwrap();
+        std::fs::write(
+            root.join("config.toml"),
+            "[make]\nUSE = \"-gtk qt6\"\nCFLAGS = \"-O3\"\n\n[use]\n\"app-editors/vim\" = \"python\"\n",
+        )
+        .unwrap();
+        root
+    }
+
+    #[test]
+    fn test_explain_variables_and_flags() {
+        let root = root("explain-vars");
+
+        let cflags = explain(&root, "CFLAGS").unwrap();
+        assert_eq!(cflags.value, "-O3");
+        let layers: Vec<Layer> = cflags.contributions.iter().map(|c| c.layer).collect();
+        assert_eq!(layers, vec![Layer::Default, Layer::Profile, Layer::Config]);
+        assert!(cflags.contributions[1]
+            .location()
+            .ends_with("profiles/base/make.defaults:2"));
+        assert!(cflags.contributions[2]
+            .location()
+            .ends_with("config.toml:3"));
+
+        let use_flags = explain(&root, "USE").unwrap();
+        assert_eq!(use_flags.value, "ssl ipv6 X wayland qt6");
+
+        let gtk = explain(&root, "gtk").unwrap();
+        assert_eq!(gtk.value, "disabled");
+        assert_eq!(gtk.contributions.len(), 2);
+        let wayland = explain(&root, "wayland").unwrap();
+        assert_eq!(wayland.value, "disabled (masked by the profile)");
+        assert!(wayland.contributions[1]
+            .location()
+            .ends_with("desktop/use.mask:1"));
+
+        std::env::set_var("BUCKOS_EXPLAIN_TEST", "1");
+        let env = explain(&root, "BUCKOS_EXPLAIN_TEST").unwrap();
+        assert_eq!(env.value, "1");
+        assert_eq!(env.contributions[0].location(), "environment");
+
+        std::fs::remove_dir_all(root.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_explain_package() {
+        let root = root("explain-package");
+
+        let vim = explain(&root, "app-editors/vim").unwrap();
+        assert!(vim.value.starts_with("USE=\"X ipv6 python qt6 ssl\""));
+        assert_eq!(vim.contributions.len(), 1);
+        assert!(vim.contributions[0].location().ends_with("config.toml:6"));
+
+        let python = explain(&root, "dev-lang/python").unwrap();
+        assert!(python.value.ends_with(" masked"));
+        assert_eq!(python.contributions[0].layer, Layer::Profile);
+        assert!(python.contributions[0]
+            .location()
+            .ends_with("base/package.mask:2"));
+
+        // The tree is used when there is no config.toml
+        std::fs::remove_file(root.join("config.toml")).unwrap();
+        std::fs::create_dir(root.join("package.use")).unwrap();
+        std::fs::write(
+            root.join("package.use/editors"),
+            "\napp-editors/vim -X lua\n",
+        )
+        .unwrap();
+        let vim = explain(&root, "app-editors/vim").unwrap();
+        assert!(vim.value.contains("lua"));
+        assert!(vim.contributions[0]
+            .location()
+            .ends_with("package.use/editors:2"));
+
+        std::fs::remove_dir_all(root.parent().unwrap()).unwrap();
+    }
+}
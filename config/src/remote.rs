@@ -0,0 +1,388 @@
+//! Configuration sourced from git
+//!
+//! remote.conf names git repositories holding shared policy, laid out
+//! like the configuration root (sets/, package.mask, package.use, ...):
+//!
+//! ```text
+//! [fleet]
+//! sync-uri = https://git.example.com/buckos-policy.git
+//! branch = stable
+//! include = sets package.mask package.use
+//! ```
+//!
+//! Each is cloned under /var/db/buckos/remote-config by `buckos sync` and
+//! merged into the configuration below the local files, so a local
+//! setting always wins: local unmasks beat remote masks, remote unmasks of
+//! locally masked packages are dropped, local package.use lines apply
+//! after remote ones, and a set defined locally hides a remote set of the
+//! same name. [`merge`] returns each such conflict, which `buckos config
+//! lint` reports.
+
+use crate::{ConfigError, PortageConfig, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Parts of the configuration a remote can provide
+pub const PARTS: &[&str] = &[
+    "sets",
+    "package.mask",
+    "package.unmask",
+    "package.use",
+    "package.accept_keywords",
+    "package.license",
+];
+
+/// A git repository of shared configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemoteConfig {
+    pub name: String,
+    pub sync_uri: String,
+    /// Branch to follow; the remote's default branch if not given
+    pub branch: Option<String>,
+    /// Local checkout
+    pub location: PathBuf,
+    /// Parts of the configuration taken from the remote
+    pub include: Vec<String>,
+}
+
+impl RemoteConfig {
+    pub fn new(name: impl Into<String>, sync_uri: impl Into<String>) -> Self {
+        let name = name.into();
+        Self {
+            location: crate::paths::package_db().join("remote-config").join(&name),
+            name,
+            sync_uri: sync_uri.into(),
+            branch: None,
+            include: PARTS.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    /// Clone the remote, or fast-forward an existing checkout
+    pub fn sync(&self) -> Result<()> {
+        let mut git = Command::new("git");
+        if self.location.join(".git").exists() {
+            git.arg("-C")
+                .arg(&self.location)
+                .args(["pull", "--ff-only"]);
+        } else {
+            if let Some(parent) = self.location.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            git.args(["clone", "--depth", "1"]);
+            if let Some(branch) = &self.branch {
+                git.args(["--branch", branch]);
+            }
+            git.arg(&self.sync_uri).arg(&self.location);
+        }
+        let output = git.output()?;
+        if !output.status.success() {
+            return Err(ConfigError::Invalid(format!(
+                "syncing remote configuration {} failed: {}",
+                self.name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check that every included part is one of [`PARTS`]
+    pub fn validate(&self) -> Result<()> {
+        match self.include.iter().find(|p| !PARTS.contains(&p.as_str())) {
+            Some(part) => Err(ConfigError::Invalid(format!(
+                "remote configuration {} includes unknown part '{}' (expected {})",
+                self.name,
+                part,
+                PARTS.join(", ")
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether the remote provides a part of the configuration
+    pub fn includes(&self, part: &str) -> bool {
+        self.include.iter().any(|p| p == part)
+    }
+}
+
+/// A remote setting overridden by a local one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// Remote the setting came from
+    pub remote: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "remote {}: {}", self.remote, self.message)
+    }
+}
+
+/// Parse remote.conf content
+pub fn parse_remote_conf(content: &str) -> Result<Vec<RemoteConfig>> {
+    let mut sections: Vec<(String, HashMap<String, String>)> = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push((name.trim().to_string(), HashMap::new()));
+        } else if let (Some((key, value)), Some((_, values))) =
+            (line.split_once('='), sections.last_mut())
+        {
+            values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+
+    let mut remotes = Vec::new();
+    for (name, values) in sections {
+        let uri = values.get("sync-uri").ok_or_else(|| {
+            ConfigError::Invalid(format!("remote configuration {} has no sync-uri", name))
+        })?;
+        let mut remote = RemoteConfig::new(&name, uri);
+        remote.branch = values.get("branch").cloned();
+        if let Some(location) = values.get("location") {
+            remote.location = PathBuf::from(location);
+        }
+        if let Some(include) = values.get("include") {
+            remote.include = include.split_whitespace().map(str::to_string).collect();
+        }
+        remote.validate()?;
+        remotes.push(remote);
+    }
+    Ok(remotes)
+}
+
+/// Format remotes as remote.conf content
+pub fn format_remote_conf(remotes: &[RemoteConfig]) -> String {
+    let mut output = String::new();
+    for remote in remotes {
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        output.push_str(&format!("[{}]\n", remote.name));
+        output.push_str(&format!("sync-uri = {}\n", remote.sync_uri));
+        if let Some(branch) = &remote.branch {
+            output.push_str(&format!("branch = {}\n", branch));
+        }
+        output.push_str(&format!("location = {}\n", remote.location.display()));
+        output.push_str(&format!("include = {}\n", remote.include.join(" ")));
+    }
+    output
+}
+
+/// Merge the synced remotes of a configuration into it, below its own
+/// settings, returning the remote settings local ones override
+pub fn merge(config: &mut PortageConfig) -> Result<Vec<Conflict>> {
+    let local = config.clone();
+    let mut conflicts = Vec::new();
+
+    for remote in &local.remotes {
+        if !remote.location.exists() {
+            tracing::warn!(
+                "Remote configuration {} has not been synced yet",
+                remote.name
+            );
+            continue;
+        }
+        let policy = PortageConfig::load(&remote.location)?;
+        let mut conflict = |message: String| {
+            conflicts.push(Conflict {
+                remote: remote.name.clone(),
+                message,
+            })
+        };
+
+        if remote.includes("package.mask") {
+            for entry in policy.package_mask.masked {
+                let (category, name) = (&entry.atom.category, &entry.atom.name);
+                if local
+                    .package_mask
+                    .unmasked
+                    .iter()
+                    .any(|e| e.atom.matches_cpn(category, name))
+                {
+                    conflict(format!("masks {}, which is unmasked locally", entry.atom));
+                }
+                config.package_mask.masked.push(entry);
+            }
+        }
+
+        if remote.includes("package.unmask") {
+            for entry in policy.package_mask.unmasked {
+                let (category, name) = (&entry.atom.category, &entry.atom.name);
+                if local
+                    .package_mask
+                    .masked
+                    .iter()
+                    .any(|e| e.atom.matches_cpn(category, name))
+                {
+                    conflict(format!(
+                        "unmasks {}, which is masked locally; keeping the mask",
+                        entry.atom
+                    ));
+                } else {
+                    config.package_mask.unmasked.push(entry);
+                }
+            }
+        }
+
+        if remote.includes("package.use") {
+            for entry in &policy.package_use.package {
+                let (category, name) = (&entry.atom.category, &entry.atom.name);
+                for flag in &entry.flags {
+                    let overridden = local
+                        .package_use
+                        .package
+                        .iter()
+                        .filter(|e| e.atom.matches_cpn(category, name))
+                        .flat_map(|e| &e.flags)
+                        .any(|f| f.name == flag.name && f.enabled != flag.enabled);
+                    if overridden {
+                        conflict(format!(
+                            "sets USE {} for {}, which is overridden locally",
+                            flag, entry.atom
+                        ));
+                    }
+                }
+            }
+            // Entries apply in order, so the local ones go last
+            let mut package = policy.package_use.package;
+            package.append(&mut config.package_use.package);
+            config.package_use.package = package;
+        }
+
+        if remote.includes("package.accept_keywords") {
+            let mut package = policy.package_keywords.package;
+            package.append(&mut config.package_keywords.package);
+            config.package_keywords.package = package;
+        }
+
+        if remote.includes("package.license") {
+            let mut package = policy.package_license.package;
+            package.append(&mut config.package_license.package);
+            config.package_license.package = package;
+        }
+
+        if remote.includes("sets") {
+            let local_sets = local.sets.customized();
+            // The world set belongs to the machine
+            for set in policy.sets.customized() {
+                if set.name == "world" {
+                    continue;
+                }
+                if local_sets.iter().any(|s| s.name == set.name) {
+                    conflict(format!(
+                        "defines set @{}, which is defined locally",
+                        set.name
+                    ));
+                } else {
+                    config.sets.sets.insert(set.name.clone(), set.clone());
+                }
+            }
+        }
+    }
+
+    Ok(conflicts)
+}
+
+/// Sync every remote of the configuration under `root`
+pub fn sync_all(root: &Path) -> Result<Vec<RemoteConfig>> {
+    let config = crate::ConfigLoader::new(root).remote(false).load()?;
+    for remote in &config.remotes {
+        remote.sync()?;
+    }
+    Ok(config.remotes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::use_flags::UseFlag;
+    use crate::MaskEntry;
+
+    #[test]
+    fn test_parse_remote_conf() {
+        let content = "# Fleet policy\n[fleet]\nsync-uri = https://git.example.com/policy.git\n\
+                       branch = stable\ninclude = sets package.mask\n\n\
+                       [team]\nsync-uri = /srv/policy.git\nlocation = /var/lib/team-policy\n";
+        let remotes = parse_remote_conf(content).unwrap();
+        assert_eq!(remotes.len(), 2);
+        assert_eq!(remotes[0].branch.as_deref(), Some("stable"));
+        assert_eq!(remotes[0].include, vec!["sets", "package.mask"]);
+        assert_eq!(
+            remotes[0].location,
+            PathBuf::from("/var/db/buckos/remote-config/fleet")
+        );
+        assert_eq!(remotes[1].include.len(), PARTS.len());
+        assert_eq!(
+            parse_remote_conf(&format_remote_conf(&remotes)).unwrap(),
+            remotes
+        );
+
+        assert!(parse_remote_conf("[x]\nbranch = main\n").is_err());
+        assert!(parse_remote_conf("[x]\nsync-uri = /x\ninclude = make.conf\n").is_err());
+    }
+
+    #[test]
+    fn test_local_settings_win() {
+        let dir = std::env::temp_dir().join(format!("buckos-remote-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sets")).unwrap();
+        std::fs::write(
+            dir.join("package.mask"),
+            "dev-lang/python:2.7\nsys-apps/systemd\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("package.unmask"), "dev-lang/rust\n").unwrap();
+        std::fs::write(dir.join("package.use"), "app-editors/vim perl lua\n").unwrap();
+        std::fs::write(dir.join("sets/server"), "app-admin/sudo\n").unwrap();
+        std::fs::write(dir.join("sets/tools"), "dev-vcs/git\n").unwrap();
+
+        let mut config = PortageConfig::default();
+        let mut remote = RemoteConfig::new("fleet", "https://git.example.com/policy.git");
+        remote.location = dir.clone();
+        config.remotes.push(remote);
+        config
+            .package_mask
+            .unmasked
+            .push(MaskEntry::new("sys-apps/systemd".parse().unwrap()));
+        config
+            .package_mask
+            .masked
+            .push(MaskEntry::new("dev-lang/rust".parse().unwrap()));
+        config.package_use.add_package_use(
+            "app-editors/vim".parse().unwrap(),
+            vec![UseFlag::disabled("perl")],
+        );
+        config.sets.create("tools", "");
+        config
+            .sets
+            .add_to_set("tools", "app-editors/vim".parse().unwrap())
+            .unwrap();
+
+        let conflicts = merge(&mut config).unwrap();
+        let messages: Vec<String> = conflicts.iter().map(|c| c.to_string()).collect();
+        assert_eq!(messages.len(), 4, "{:?}", messages);
+        assert!(messages.contains(
+            &"remote fleet: sets USE perl for app-editors/vim, which is overridden locally"
+                .to_string()
+        ));
+
+        assert!(config.is_masked("dev-lang", "python", None));
+        assert!(!config.is_masked("sys-apps", "systemd", None));
+        assert!(config.is_masked("dev-lang", "rust", None));
+        let vim = config.effective_use("app-editors", "vim");
+        assert!(vim.contains("lua") && !vim.contains("perl"));
+        assert!(config
+            .sets
+            .get("server")
+            .unwrap()
+            .contains("app-admin", "sudo"));
+        assert!(!config.sets.get("tools").unwrap().contains("dev-vcs", "git"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
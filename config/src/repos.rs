@@ -97,6 +97,8 @@ impl ReposConfig {
             auto_sync: true,
             clone_depth: Some(1),
             sync_git_verify_commit_signature: false,
+            sync_signature_threshold: 0,
+            sync_signature_keys: Vec::new(),
             masters: Vec::new(),
             aliases: Vec::new(),
             eclass_overrides: Vec::new(),
@@ -139,6 +141,11 @@ pub struct Repository {
     pub clone_depth: Option<u32>,
     /// Verify git commit signatures
     pub sync_git_verify_commit_signature: bool,
+    /// Valid signatures over the repository metadata required before it is
+    /// trusted (0 = no threshold)
+    pub sync_signature_threshold: u32,
+    /// Keys whose signatures count towards the threshold
+    pub sync_signature_keys: Vec<String>,
     /// Master repositories
     pub masters: Vec<String>,
     /// Repository aliases
@@ -160,6 +167,8 @@ impl Default for Repository {
             auto_sync: true,
             clone_depth: None,
             sync_git_verify_commit_signature: false,
+            sync_signature_threshold: 0,
+            sync_signature_keys: Vec::new(),
             masters: Vec::new(),
             aliases: Vec::new(),
             eclass_overrides: Vec::new(),
@@ -316,6 +325,65 @@ pub fn parse_repos_conf(path: &Path) -> Result<ReposConfig> {
     Ok(config)
 }
 
+/// Format a repository configuration as repos.conf content
+pub fn format_repos_conf(config: &ReposConfig) -> String {
+    let yes_no = |b: bool| if b { "yes" } else { "no" };
+    let mut output = String::from("[DEFAULT]\n");
+    if let Some(main_repo) = &config.defaults.main_repo {
+        output.push_str(&format!("main-repo = {}\n", main_repo));
+    }
+    output.push_str(&format!(
+        "auto-sync = {}\n",
+        yes_no(config.defaults.auto_sync)
+    ));
+    if let Some(sync_type) = &config.defaults.sync_type {
+        output.push_str(&format!("sync-type = {}\n", sync_type.as_str()));
+    }
+    if let Some(depth) = config.defaults.clone_depth {
+        output.push_str(&format!("clone-depth = {}\n", depth));
+    }
+
+    let mut repos: Vec<&Repository> = config.repos.values().collect();
+    repos.sort_by(|a, b| a.name.cmp(&b.name));
+    for repo in repos {
+        output.push_str(&format!("\n[{}]\n", repo.name));
+        output.push_str(&format!("location = {}\n", repo.location.display()));
+        output.push_str(&format!("sync-type = {}\n", repo.sync_type.as_str()));
+        if let Some(uri) = &repo.sync_uri {
+            output.push_str(&format!("sync-uri = {}\n", uri));
+        }
+        output.push_str(&format!("priority = {}\n", repo.priority));
+        output.push_str(&format!("auto-sync = {}\n", yes_no(repo.auto_sync)));
+        if let Some(depth) = repo.clone_depth {
+            output.push_str(&format!("clone-depth = {}\n", depth));
+        }
+        if repo.sync_git_verify_commit_signature {
+            output.push_str("sync-git-verify-commit-signature = yes\n");
+        }
+        if repo.sync_signature_threshold > 0 {
+            output.push_str(&format!(
+                "sync-signature-threshold = {}\n",
+                repo.sync_signature_threshold
+            ));
+        }
+        for (key, values) in [
+            ("sync-signature-keys", &repo.sync_signature_keys),
+            ("masters", &repo.masters),
+            ("aliases", &repo.aliases),
+            ("eclass-overrides", &repo.eclass_overrides),
+        ] {
+            if !values.is_empty() {
+                output.push_str(&format!("{} = {}\n", key, values.join(" ")));
+            }
+        }
+        if repo.force {
+            output.push_str("force = yes\n");
+        }
+    }
+
+    output
+}
+
 /// Parse repos.conf content (INI-like format)
 fn parse_repos_conf_content(content: &str, config: &mut ReposConfig) -> Result<()> {
     let mut current_section: Option<String> = None;
@@ -404,9 +472,26 @@ fn save_section(
         if let Some(v) = values.get("sync-git-verify-commit-signature") {
             repo.sync_git_verify_commit_signature = v.to_lowercase() == "yes" || v == "true";
         }
+        if let Some(v) = values.get("sync-signature-threshold") {
+            repo.sync_signature_threshold = v.parse().map_err(|_| {
+                ConfigError::Invalid(format!("invalid sync-signature-threshold: {}", v))
+            })?;
+        }
+        if let Some(v) = values.get("sync-signature-keys") {
+            repo.sync_signature_keys = v.split_whitespace().map(|s| s.to_string()).collect();
+        }
         if let Some(v) = values.get("masters") {
             repo.masters = v.split_whitespace().map(|s| s.to_string()).collect();
         }
+        if let Some(v) = values.get("aliases") {
+            repo.aliases = v.split_whitespace().map(|s| s.to_string()).collect();
+        }
+        if let Some(v) = values.get("eclass-overrides") {
+            repo.eclass_overrides = v.split_whitespace().map(|s| s.to_string()).collect();
+        }
+        if let Some(v) = values.get("force") {
+            repo.force = v.to_lowercase() == "yes" || v == "true";
+        }
 
         config.repos.insert(repo.name.clone(), repo);
     }
@@ -473,4 +558,34 @@ priority = 50
         assert_eq!(gentoo.sync_type, SyncType::Rsync);
         assert_eq!(gentoo.priority, 100);
     }
+
+    #[test]
+    fn test_signature_threshold_round_trips() {
+        let content = r#"
+[production]
+location = /var/db/repos/production
+sync-type = git
+sync-uri = https://example.org/production.git
+sync-signature-threshold = 2
+sync-signature-keys = E7620F1842B4E81F 9E1A0C37B6D2F481 0123456789ABCDEF
+"#;
+
+        let mut config = ReposConfig::new();
+        parse_repos_conf_content(content, &mut config).unwrap();
+        let repo = config.get_repo("production").unwrap();
+        assert_eq!(repo.sync_signature_threshold, 2);
+        assert_eq!(repo.sync_signature_keys.len(), 3);
+
+        let mut reparsed = ReposConfig::new();
+        parse_repos_conf_content(&format_repos_conf(&config), &mut reparsed).unwrap();
+        let repo = reparsed.get_repo("production").unwrap();
+        assert_eq!(repo.sync_signature_threshold, 2);
+        assert_eq!(repo.sync_signature_keys[1], "9E1A0C37B6D2F481");
+
+        let mut invalid = ReposConfig::new();
+        assert!(
+            parse_repos_conf_content("[x]\nsync-signature-threshold = two\n", &mut invalid)
+                .is_err()
+        );
+    }
 }
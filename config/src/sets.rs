@@ -162,6 +162,23 @@ impl SetsConfig {
         self.sets.keys().map(|s| s.as_str()).collect()
     }
 
+    /// Sets that differ from the standard ones, sorted by name
+    pub fn customized(&self) -> Vec<&PackageSet> {
+        let defaults = Self::with_defaults();
+        let mut sets: Vec<&PackageSet> = self
+            .sets
+            .iter()
+            .filter(|(name, set)| {
+                !defaults.get(name).is_some_and(|default| {
+                    default.atoms == set.atoms && default.dependencies == set.dependencies
+                })
+            })
+            .map(|(_, set)| set)
+            .collect();
+        sets.sort_by(|a, b| a.name.cmp(&b.name));
+        sets
+    }
+
     /// Load sets from a directory
     pub fn load_from_dir(dir: &Path) -> Result<Self> {
         let mut config = Self::with_defaults();
@@ -290,6 +307,10 @@ impl PackageSet {
             output.push('\n');
         }
 
+        for dependency in &self.dependencies {
+            output.push_str(&format!("@{}\n", dependency));
+        }
+
         output
     }
 
@@ -0,0 +1,611 @@
+//! Single-file TOML configuration
+//!
+//! `/etc/buckos/config.toml` is an alternative to the make.conf and
+//! package.* tree, holding the same settings in one file:
+//!
+//! ```toml
+//! main-repo = "buckos"
+//! mask = ["dev-lang/python:2.7", { atom = ">=sys-libs/glibc-2.40", reason = "Breaks the toolchain" }]
+//!
+//! [make]
+//! CFLAGS = "-O2 -march=native -pipe"
+//! USE = "X wayland -gtk"
+//! FEATURES = "ccache parallel-fetch"
+//!
+//! [use]
+//! "app-editors/vim" = "python -perl"
+//!
+//! [keywords]
+//! "dev-lang/rust" = "~amd64"
+//!
+//! [licenses]
+//! "www-client/chromium" = "google-chrome"
+//!
+//! [repos.buckos]
+//! location = "/var/db/repos/buckos"
+//! sync-type = "git"
+//! sync-uri = "https://github.com/hodgesds/buckos-packages.git"
+//! priority = 1000
+//!
+//! [sets]
+//! desktop = ["media-video/mpv", "@fonts"]
+//!
+//! [remote.fleet]
+//! sync-uri = "https://git.example.com/buckos-policy.git"
+//! include = ["sets", "package.mask"]
+//! ```
+//!
+//! When the file exists, [`ConfigLoader`](crate::ConfigLoader) reads it in
+//! place of the tree. [`TomlConfig::from_portage`] and
+//! [`TomlConfig::into_portage`] convert between the two, so a system can
+//! move from one to the other with `buckos config convert`.
+
+use crate::keywords::Keyword;
+use crate::sets::PackageSet;
+use crate::{
+    ConfigError, KeywordConfig, LicenseConfig, MakeConf, MaskEntry, PackageAtom, PortageConfig,
+    RemoteConfig, ReposConfig, Repository, Result, UseConfig,
+};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Name of the single-file configuration within a configuration root
+pub const CONFIG_TOML: &str = "config.toml";
+
+/// The whole configuration in one TOML document
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TomlConfig {
+    /// Repository used when an atom names none
+    #[serde(rename = "main-repo", skip_serializing_if = "Option::is_none")]
+    pub main_repo: Option<String>,
+    /// Masked atoms (package.mask)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub mask: Vec<MaskToml>,
+    /// Unmasked atoms (package.unmask)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unmask: Vec<MaskToml>,
+    /// make.conf variables by name, such as CFLAGS or USE
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    pub make: IndexMap<String, String>,
+    /// Per-package USE flags (package.use), atom to flags
+    #[serde(rename = "use", skip_serializing_if = "IndexMap::is_empty")]
+    pub package_use: IndexMap<String, String>,
+    /// Per-package keywords (package.accept_keywords), atom to keywords
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    pub keywords: IndexMap<String, String>,
+    /// Per-package licenses (package.license), atom to licenses
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    pub licenses: IndexMap<String, String>,
+    /// Repositories (repos.conf) by name
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    pub repos: IndexMap<String, RepoToml>,
+    /// Package sets by name; `@name` entries include another set
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    pub sets: IndexMap<String, Vec<String>>,
+    /// Git repositories of shared configuration (remote.conf) by name
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    pub remote: IndexMap<String, RemoteToml>,
+}
+
+/// A mask or unmask, either a bare atom or an atom with the reason for it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MaskToml {
+    Atom(String),
+    Entry { atom: String, reason: String },
+}
+
+/// A repos.conf section
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RepoToml {
+    pub location: PathBuf,
+    pub sync_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sync_uri: Option<String>,
+    pub priority: i32,
+    pub auto_sync: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clone_depth: Option<u32>,
+    pub sync_git_verify_commit_signature: bool,
+    pub sync_signature_threshold: u32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sync_signature_keys: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub masters: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub eclass_overrides: Vec<String>,
+    pub force: bool,
+}
+
+impl Default for RepoToml {
+    fn default() -> Self {
+        Self::from(&Repository::default())
+    }
+}
+
+impl From<&Repository> for RepoToml {
+    fn from(repo: &Repository) -> Self {
+        Self {
+            location: repo.location.clone(),
+            sync_type: repo.sync_type.as_str().to_string(),
+            sync_uri: repo.sync_uri.clone(),
+            priority: repo.priority,
+            auto_sync: repo.auto_sync,
+            clone_depth: repo.clone_depth,
+            sync_git_verify_commit_signature: repo.sync_git_verify_commit_signature,
+            sync_signature_threshold: repo.sync_signature_threshold,
+            sync_signature_keys: repo.sync_signature_keys.clone(),
+            masters: repo.masters.clone(),
+            aliases: repo.aliases.clone(),
+            eclass_overrides: repo.eclass_overrides.clone(),
+            force: repo.force,
+        }
+    }
+}
+
+/// A remote.conf section; the location and included parts default as
+/// in remote.conf
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RemoteToml {
+    pub sync_uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+}
+
+impl RemoteToml {
+    fn from_remote(remote: &RemoteConfig) -> Self {
+        let default = RemoteConfig::new(&remote.name, &remote.sync_uri);
+        Self {
+            sync_uri: remote.sync_uri.clone(),
+            branch: remote.branch.clone(),
+            location: (remote.location != default.location).then(|| remote.location.clone()),
+            include: if remote.include == default.include {
+                Vec::new()
+            } else {
+                remote.include.clone()
+            },
+        }
+    }
+
+    fn into_remote(self, name: &str) -> Result<RemoteConfig> {
+        let mut remote = RemoteConfig::new(name, self.sync_uri);
+        remote.branch = self.branch;
+        if let Some(location) = self.location {
+            remote.location = location;
+        }
+        if !self.include.is_empty() {
+            remote.include = self.include;
+        }
+        remote.validate()?;
+        Ok(remote)
+    }
+}
+
+impl RepoToml {
+    fn into_repository(self, name: &str) -> Result<Repository> {
+        Ok(Repository {
+            name: name.to_string(),
+            location: self.location,
+            sync_type: self.sync_type.parse()?,
+            sync_uri: self.sync_uri,
+            priority: self.priority,
+            auto_sync: self.auto_sync,
+            clone_depth: self.clone_depth,
+            sync_git_verify_commit_signature: self.sync_git_verify_commit_signature,
+            sync_signature_threshold: self.sync_signature_threshold,
+            sync_signature_keys: self.sync_signature_keys,
+            masters: self.masters,
+            aliases: self.aliases,
+            eclass_overrides: self.eclass_overrides,
+            force: self.force,
+        })
+    }
+}
+
+impl TomlConfig {
+    /// Load from a TOML file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    /// Save to a TOML file
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.to_toml()?)?;
+        Ok(())
+    }
+
+    /// The TOML document [`save`](Self::save) writes
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    /// Convert a loaded configuration, keeping only the make.conf
+    /// variables and package sets that differ from the defaults
+    pub fn from_portage(config: &PortageConfig) -> Self {
+        let mut toml = Self {
+            main_repo: config.repos.defaults.main_repo.clone(),
+            mask: config.package_mask.masked.iter().map(mask_toml).collect(),
+            unmask: config.package_mask.unmasked.iter().map(mask_toml).collect(),
+            make: make_vars(&config.make_conf),
+            ..Default::default()
+        };
+
+        for entry in &config.package_use.package {
+            let flags = entry.flags.iter().map(|f| f.to_string());
+            append(&mut toml.package_use, &entry.atom, flags);
+        }
+        for entry in &config.package_keywords.package {
+            let keywords = entry.keywords.iter().map(|k| k.to_string());
+            append(&mut toml.keywords, &entry.atom, keywords);
+        }
+        for entry in &config.package_license.package {
+            append(
+                &mut toml.licenses,
+                &entry.atom,
+                entry.licenses.iter().cloned(),
+            );
+        }
+
+        let mut repos: Vec<_> = config.repos.repos.values().collect();
+        repos.sort_by(|a, b| a.name.cmp(&b.name));
+        for repo in repos {
+            toml.repos.insert(repo.name.clone(), RepoToml::from(repo));
+        }
+
+        for set in config.sets.customized() {
+            toml.sets.insert(set.name.clone(), set_entries(set));
+        }
+
+        for remote in &config.remotes {
+            toml.remote
+                .insert(remote.name.clone(), RemoteToml::from_remote(remote));
+        }
+
+        toml
+    }
+
+    /// Build the configuration rooted at `config_root`
+    pub fn into_portage(self, config_root: &Path) -> Result<PortageConfig> {
+        let mut config = PortageConfig {
+            config_root: config_root.to_path_buf(),
+            ..Default::default()
+        };
+
+        for (name, value) in &self.make {
+            set_make_var(&mut config.make_conf, name, value)?;
+        }
+
+        config.package_use = UseConfig::default();
+        for (atom, flags) in &self.package_use {
+            config
+                .package_use
+                .add_package_use(atom.parse()?, UseConfig::parse_use_string(flags));
+        }
+
+        config.package_keywords = KeywordConfig::new("amd64");
+        for (atom, keywords) in &self.keywords {
+            // As in package.accept_keywords, no keywords means ~arch
+            let keywords = if keywords.trim().is_empty() {
+                vec![Keyword::testing(&config.package_keywords.arch)]
+            } else {
+                KeywordConfig::parse_keywords_string(keywords)
+            };
+            config
+                .package_keywords
+                .add_package_keywords(atom.parse()?, keywords);
+        }
+
+        config.package_license = LicenseConfig::default();
+        for (atom, licenses) in &self.licenses {
+            let licenses = licenses.split_whitespace().map(str::to_string).collect();
+            config
+                .package_license
+                .add_package_license(atom.parse()?, licenses);
+        }
+
+        config.package_mask.masked = self.mask.iter().map(mask_entry).collect::<Result<_>>()?;
+        config.package_mask.unmasked = self.unmask.iter().map(mask_entry).collect::<Result<_>>()?;
+
+        if !self.repos.is_empty() {
+            let mut repos = ReposConfig {
+                defaults: config.repos.defaults.clone(),
+                ..Default::default()
+            };
+            for (name, repo) in self.repos {
+                repos.add_repo(repo.into_repository(&name)?);
+            }
+            config.repos = repos;
+        }
+        if let Some(main_repo) = self.main_repo {
+            config.repos.set_main_repo(main_repo);
+        }
+
+        for (name, entries) in &self.sets {
+            // PackageSet::parse skips atoms it cannot parse; report them instead
+            for entry in entries.iter().filter(|e| !e.starts_with('@')) {
+                entry.parse::<PackageAtom>()?;
+            }
+            let set = PackageSet::parse(name, &entries.join("\n"))?;
+            config.sets.sets.insert(name.clone(), set);
+        }
+
+        for (name, remote) in self.remote {
+            config.remotes.push(remote.into_remote(&name)?);
+        }
+
+        Ok(config)
+    }
+}
+
+/// Add values to an atom's entry, joining repeated atoms
+fn append(
+    map: &mut IndexMap<String, String>,
+    atom: &PackageAtom,
+    values: impl Iterator<Item = String>,
+) {
+    let entry = map.entry(atom.to_string()).or_default();
+    for value in values {
+        if !entry.is_empty() {
+            entry.push(' ');
+        }
+        entry.push_str(&value);
+    }
+}
+
+fn mask_toml(entry: &MaskEntry) -> MaskToml {
+    match &entry.reason {
+        Some(reason) => MaskToml::Entry {
+            atom: entry.atom.to_string(),
+            reason: reason.clone(),
+        },
+        None => MaskToml::Atom(entry.atom.to_string()),
+    }
+}
+
+fn mask_entry(mask: &MaskToml) -> Result<MaskEntry> {
+    Ok(match mask {
+        MaskToml::Atom(atom) => MaskEntry::new(atom.parse()?),
+        MaskToml::Entry { atom, reason } => MaskEntry::new(atom.parse()?).with_reason(reason),
+    })
+}
+
+/// Atoms of a set, sorted, followed by the sets it includes
+fn set_entries(set: &PackageSet) -> Vec<String> {
+    let mut entries: Vec<String> = set.atoms.iter().map(|a| a.to_string()).collect();
+    entries.sort();
+    entries.extend(set.dependencies.iter().map(|d| format!("@{}", d)));
+    entries
+}
+
+fn sorted(values: &HashSet<String>) -> String {
+    let mut values: Vec<&str> = values.iter().map(String::as_str).collect();
+    values.sort_unstable();
+    values.join(" ")
+}
+
+fn words(value: &str) -> impl Iterator<Item = String> + '_ {
+    value.split_whitespace().map(str::to_string)
+}
+
+/// Every make.conf variable of a configuration by name
+pub(crate) fn make_values(make: &MakeConf) -> IndexMap<String, String> {
+    let path = |p: &Path| p.to_string_lossy().into_owned();
+    let mut vars: IndexMap<String, String> = [
+        ("CFLAGS", make.cflags.clone()),
+        ("CXXFLAGS", make.cxxflags.clone()),
+        ("FFLAGS", make.fflags.clone()),
+        ("FCFLAGS", make.fcflags.clone()),
+        ("LDFLAGS", make.ldflags.clone()),
+        ("RUSTFLAGS", make.rustflags.clone()),
+        ("GOFLAGS", make.goflags.clone()),
+        ("MAKEOPTS", make.makeopts.clone()),
+        ("NINJAOPTS", make.ninjaopts.clone()),
+        ("EMERGE_DEFAULT_OPTS", make.emerge_default_opts.clone()),
+        ("ARCH", make.arch.clone()),
+        ("CHOST", make.chost.clone()),
+        ("USE", sorted(&make.use_config.global)),
+        ("FEATURES", make.features_string()),
+        ("ACCEPT_KEYWORDS", sorted(&make.keywords.accept_keywords)),
+        ("ACCEPT_LICENSE", make.license.accept_license.clone()),
+        ("GENTOO_MIRRORS", make.mirrors_string()),
+        ("DISTDIR", path(&make.distdir)),
+        ("PKGDIR", path(&make.pkgdir)),
+        ("PORT_LOGDIR", path(&make.logdir)),
+        ("PORTAGE_TMPDIR", path(&make.tmpdir)),
+        ("PORTDIR", path(&make.repodir)),
+        ("BINPKG_COMPRESS", make.binpkg_compress.clone()),
+        ("BINPKG_COMPRESS_FLAGS", make.binpkg_compress_flags.clone()),
+        ("BINPKG_FORMAT", make.binpkg_format.clone()),
+        ("CONFIG_PROTECT", make.config_protect.join(" ")),
+        ("CONFIG_PROTECT_MASK", make.config_protect_mask.join(" ")),
+        ("CLEAN_DELAY", make.clean_delay.to_string()),
+        (
+            "EMERGE_WARNING_DELAY",
+            make.emerge_warning_delay.to_string(),
+        ),
+        ("COLLISION_IGNORE", make.collision_ignore.join(" ")),
+        ("UNINSTALL_IGNORE", make.uninstall_ignore.join(" ")),
+        ("INPUT_DEVICES", sorted(&make.input_devices)),
+        ("VIDEO_CARDS", sorted(&make.video_cards)),
+        ("L10N", sorted(&make.l10n)),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value))
+    .collect();
+    for (name, flags) in &make.cpu_flags {
+        vars.insert(name.clone(), sorted(flags));
+    }
+    for (name, value) in &make.custom {
+        vars.insert(name.clone(), value.clone());
+    }
+    vars
+}
+
+/// make.conf variables that differ from the defaults
+pub fn make_vars(make: &MakeConf) -> IndexMap<String, String> {
+    let defaults = make_values(&MakeConf::default());
+    make_values(make)
+        .into_iter()
+        .filter(|(name, value)| defaults.get(name) != Some(value))
+        .collect()
+}
+
+/// Set a make.conf variable by name; unknown names become custom variables
+pub fn set_make_var(make: &mut MakeConf, name: &str, value: &str) -> Result<()> {
+    let delay = || {
+        value.trim().parse().map_err(|_| {
+            ConfigError::Invalid(format!("{}: expected seconds, found '{}'", name, value))
+        })
+    };
+    match name {
+        "CFLAGS" => make.cflags = value.to_string(),
+        "CXXFLAGS" => make.cxxflags = value.to_string(),
+        "FFLAGS" => make.fflags = value.to_string(),
+        "FCFLAGS" => make.fcflags = value.to_string(),
+        "LDFLAGS" => make.ldflags = value.to_string(),
+        "RUSTFLAGS" => make.rustflags = value.to_string(),
+        "GOFLAGS" => make.goflags = value.to_string(),
+        "MAKEOPTS" => make.makeopts = value.to_string(),
+        "NINJAOPTS" => make.ninjaopts = value.to_string(),
+        "EMERGE_DEFAULT_OPTS" => make.emerge_default_opts = value.to_string(),
+        "ARCH" => make.arch = value.to_string(),
+        "CHOST" => make.chost = value.to_string(),
+        "USE" => make.set_use(value),
+        "FEATURES" => make.set_features(value),
+        "ACCEPT_KEYWORDS" => make.set_accept_keywords(value),
+        "ACCEPT_LICENSE" => make.license = LicenseConfig::new(value),
+        "GENTOO_MIRRORS" => make.set_mirrors(value),
+        "DISTDIR" => make.distdir = PathBuf::from(value),
+        "PKGDIR" => make.pkgdir = PathBuf::from(value),
+        "PORT_LOGDIR" => make.logdir = PathBuf::from(value),
+        "PORTAGE_TMPDIR" => make.tmpdir = PathBuf::from(value),
+        "PORTDIR" => make.repodir = PathBuf::from(value),
+        "BINPKG_COMPRESS" => make.binpkg_compress = value.to_string(),
+        "BINPKG_COMPRESS_FLAGS" => make.binpkg_compress_flags = value.to_string(),
+        "BINPKG_FORMAT" => make.binpkg_format = value.to_string(),
+        "CONFIG_PROTECT" => make.config_protect = words(value).collect(),
+        "CONFIG_PROTECT_MASK" => make.config_protect_mask = words(value).collect(),
+        "CLEAN_DELAY" => make.clean_delay = delay()?,
+        "EMERGE_WARNING_DELAY" => make.emerge_warning_delay = delay()?,
+        "COLLISION_IGNORE" => make.collision_ignore = words(value).collect(),
+        "UNINSTALL_IGNORE" => make.uninstall_ignore = words(value).collect(),
+        "INPUT_DEVICES" => make.input_devices = words(value).collect(),
+        "VIDEO_CARDS" => make.video_cards = words(value).collect(),
+        "L10N" => make.l10n = words(value).collect(),
+        _ => match name.strip_prefix("CPU_FLAGS_") {
+            Some(arch) => make.set_cpu_flags(arch, words(value).collect()),
+            None => make.set_custom(name, value),
+        },
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::use_flags::UseFlag;
+
+    #[test]
+    fn test_round_trip() {
+        let content = r#"
+main-repo = "overlay"
+mask = ["dev-lang/python:2.7", { atom = ">=sys-libs/glibc-2.40", reason = "Breaks the toolchain" }]
+
+[make]
+CFLAGS = "-O3 -pipe"
+USE = "X wayland -gtk"
+CLEAN_DELAY = "0"
+CPU_FLAGS_X86 = "avx2 sse4_2"
+MY_VAR = "1"
+
+[use]
+"app-editors/vim" = "python -perl"
+
+[keywords]
+"dev-lang/rust" = ""
+
+[repos.overlay]
+location = "/var/db/repos/overlay"
+sync-type = "local"
+priority = 50
+
+[sets]
+desktop = ["media-video/mpv", "@fonts"]
+"#;
+        let config = toml::from_str::<TomlConfig>(content)
+            .unwrap()
+            .into_portage(Path::new("/etc/buckos"))
+            .unwrap();
+
+        assert_eq!(config.make_conf.cflags, "-O3 -pipe");
+        assert!(config.make_conf.use_config.global.contains("wayland"));
+        assert!(!config.make_conf.use_config.global.contains("gtk"));
+        assert_eq!(config.make_conf.clean_delay, 0);
+        assert!(config.make_conf.cpu_flags["CPU_FLAGS_X86"].contains("avx2"));
+        assert_eq!(config.make_conf.custom["MY_VAR"], "1");
+        assert!(config
+            .effective_use("app-editors", "vim")
+            .contains("python"));
+        assert!(config.is_keyword_acceptable("dev-lang", "rust", &["~amd64"]));
+        assert!(config.is_masked("dev-lang", "python", Some("2.7")));
+        assert_eq!(
+            config.package_mask.masked[1].reason.as_deref(),
+            Some("Breaks the toolchain")
+        );
+        assert_eq!(config.main_repo().unwrap().name, "overlay");
+        let desktop = config.sets.get("desktop").unwrap();
+        assert!(desktop.contains("media-video", "mpv"));
+        assert_eq!(desktop.dependencies, vec!["fonts"]);
+
+        // Converting back gives the same document, with defaults filled in
+        let toml = TomlConfig::from_portage(&config);
+        assert_eq!(toml.make["CFLAGS"], "-O3 -pipe");
+        assert_eq!(toml.make["USE"], "X wayland");
+        assert!(!toml.make.contains_key("LDFLAGS"));
+        assert_eq!(toml.package_use["app-editors/vim"], "python -perl");
+        assert_eq!(toml.keywords["dev-lang/rust"], "~amd64");
+        assert_eq!(toml.mask.len(), 2);
+        assert_eq!(toml.sets.keys().collect::<Vec<_>>(), vec!["desktop"]);
+        let reparsed: TomlConfig = toml::from_str(&toml::to_string_pretty(&toml).unwrap()).unwrap();
+        assert_eq!(reparsed, toml);
+    }
+
+    #[test]
+    fn test_invalid_entries() {
+        let parse = |content: &str| {
+            toml::from_str::<TomlConfig>(content)
+                .unwrap()
+                .into_portage(Path::new("/etc/buckos"))
+        };
+        assert!(parse("[make]\nCLEAN_DELAY = \"soon\"\n").is_err());
+        assert!(parse("[use]\n\"not an atom\" = \"X\"\n").is_err());
+        assert!(parse("[sets]\ndesktop = [\"not an atom\"]\n").is_err());
+        assert!(parse("[repos.x]\nlocation = \"/x\"\nsync-type = \"carrier-pigeon\"\n").is_err());
+
+        let mut config = PortageConfig::default();
+        config.package_use.add_package_use(
+            "app-editors/vim".parse().unwrap(),
+            vec![UseFlag::enabled("lua")],
+        );
+        config.package_use.add_package_use(
+            "app-editors/vim".parse().unwrap(),
+            vec![UseFlag::disabled("perl")],
+        );
+        let toml = TomlConfig::from_portage(&config);
+        assert_eq!(toml.package_use["app-editors/vim"], "lua -perl");
+        assert!(toml.make.is_empty());
+        assert!(toml.sets.is_empty());
+    }
+}
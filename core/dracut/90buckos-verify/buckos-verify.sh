@@ -0,0 +1,20 @@
+#!/bin/sh
+# Verify $NEWROOT against the boot manifest before switching root.
+#
+# rd.buckos.verify=0       skip verification
+# rd.buckos.verify=rescue  drop to the emergency shell when a file does
+#                          not match (default: log and continue booting)
+
+type getarg >/dev/null 2>&1 || . /lib/dracut-lib.sh
+
+mode=$(getarg rd.buckos.verify=)
+
+if [ "$mode" != "0" ] && [ "$mode" != "off" ]; then
+    if ! buckos-verify-root --root "$NEWROOT" --manifest /etc/buckos/boot-manifest; then
+        if [ "$mode" = "rescue" ]; then
+            emergency_shell -n buckos-verify "Root filesystem does not match the boot manifest"
+        else
+            warn "buckos-verify: root filesystem does not match the boot manifest; see the kernel log"
+        fi
+    fi
+fi
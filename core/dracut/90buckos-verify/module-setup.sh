@@ -0,0 +1,23 @@
+#!/bin/bash
+# dracut module: verify the root filesystem against the buckos boot
+# manifest before switching root. Install to
+# /usr/lib/dracut/modules.d/90buckos-verify.
+
+check() {
+    require_binaries buckos-verify-root || return 1
+    [[ -f /etc/buckos/boot-manifest ]] || return 1
+    # Only when requested with --add buckos-verify
+    return 255
+}
+
+depends() {
+    return 0
+}
+
+install() {
+    inst_binary buckos-verify-root
+    # The copy in the initramfs is the reference the new root is checked
+    # against; regenerate the initramfs after 'buckos boot-manifest'
+    inst_simple /etc/buckos/boot-manifest
+    inst_hook pre-pivot 90 "$moddir/buckos-verify.sh"
+}
@@ -0,0 +1,194 @@
+//! Package atoms
+//!
+//! An atom names a package with an optional version constraint, slot and
+//! repository: `sys-apps/systemd`, `>=sys-apps/systemd-250`,
+//! `sys-apps/systemd:0`, `sys-apps/systemd::gentoo`.
+
+use crate::id::PackageId;
+use crate::version::{parse_version, VersionSpec};
+use alloc::string::{String, ToString};
+use core::fmt;
+use semver::Version;
+
+/// An atom that could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AtomError {
+    /// No category/name
+    InvalidSpec(String),
+    /// The version after the name does not parse
+    InvalidVersion(String),
+}
+
+impl fmt::Display for AtomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AtomError::InvalidSpec(s) => write!(f, "Invalid package specification: {}", s),
+            AtomError::InvalidVersion(s) => write!(f, "Invalid version: {}", s),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AtomError {}
+
+/// Package specification for user input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageSpec {
+    pub id: PackageId,
+    pub version: VersionSpec,
+    pub slot: Option<String>,
+    pub repo: Option<String>,
+}
+
+impl PackageSpec {
+    /// Parse a package specification string
+    /// Examples: "sys-apps/systemd", ">=sys-apps/systemd-250", "sys-apps/systemd:0"
+    pub fn parse(s: &str) -> Result<Self, AtomError> {
+        let s = s.trim();
+
+        // Extract version operator if present
+        let (version_op, rest) = if let Some(stripped) = s.strip_prefix(">=") {
+            (Some(">="), stripped)
+        } else if let Some(stripped) = s.strip_prefix("<=") {
+            (Some("<="), stripped)
+        } else if let Some(stripped) = s.strip_prefix('>') {
+            (Some(">"), stripped)
+        } else if let Some(stripped) = s.strip_prefix('<') {
+            (Some("<"), stripped)
+        } else if let Some(stripped) = s.strip_prefix('=') {
+            (Some("="), stripped)
+        } else if let Some(stripped) = s.strip_prefix('~') {
+            (Some("~"), stripped)
+        } else {
+            (None, s)
+        };
+
+        // Extract repository if present (::repo syntax) — must be checked before slot
+        let (rest, repo) = if let Some(idx) = rest.find("::") {
+            (&rest[..idx], Some(rest[idx + 2..].to_string()))
+        } else {
+            (rest, None)
+        };
+
+        // Extract slot if present
+        let (pkg_part, slot) = if let Some(idx) = rest.find(':') {
+            (&rest[..idx], Some(rest[idx + 1..].to_string()))
+        } else {
+            (rest, None)
+        };
+
+        // Parse category/name-version
+        let (id, version) = Self::parse_name_version(pkg_part, version_op)?;
+
+        Ok(Self {
+            id,
+            version,
+            slot,
+            repo,
+        })
+    }
+
+    fn parse_name_version(
+        s: &str,
+        version_op: Option<&str>,
+    ) -> Result<(PackageId, VersionSpec), AtomError> {
+        // Split into category and name-version using last slash (supports deep paths)
+        let last_slash = s
+            .rfind('/')
+            .ok_or_else(|| AtomError::InvalidSpec(s.to_string()))?;
+        let category = s[..last_slash].to_string();
+        let name_version = &s[last_slash + 1..];
+
+        // Try to extract version from name (e.g., "systemd-250.4")
+        if let Some(version_op) = version_op {
+            // Find last dash followed by digit
+            let mut last_dash = None;
+            for (i, c) in name_version.char_indices() {
+                if c == '-'
+                    && name_version[i + 1..]
+                        .chars()
+                        .next()
+                        .map(|c| c.is_ascii_digit())
+                        .unwrap_or(false)
+                {
+                    last_dash = Some(i);
+                }
+            }
+
+            if let Some(idx) = last_dash {
+                let name = name_version[..idx].to_string();
+                let version_str = &name_version[idx + 1..];
+                let version = parse_version(version_str)
+                    .map_err(|_| AtomError::InvalidVersion(version_str.to_string()))?;
+
+                let version_spec = match version_op {
+                    "=" | "~" => VersionSpec::Exact(version),
+                    ">" => VersionSpec::GreaterThan(version),
+                    ">=" => VersionSpec::GreaterThanOrEqual(version),
+                    "<" => VersionSpec::LessThan(version),
+                    "<=" => VersionSpec::LessThanOrEqual(version),
+                    _ => VersionSpec::Any,
+                };
+
+                return Ok((PackageId::new(category, name), version_spec));
+            }
+        }
+
+        // No version specified
+        Ok((PackageId::new(category, name_version), VersionSpec::Any))
+    }
+
+    /// Whether a package version in a slot satisfies this atom
+    ///
+    /// A slot is only compared when both the atom and the package have one.
+    pub fn matches(&self, id: &PackageId, version: &Version, slot: Option<&str>) -> bool {
+        if self.id != *id || !self.version.matches(version) {
+            return false;
+        }
+        match (self.slot.as_deref(), slot) {
+            (Some(wanted), Some(slot)) => wanted == slot,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_atom() {
+        let spec = PackageSpec::parse(">=sys-apps/systemd-250:0::gentoo").unwrap();
+        assert_eq!(spec.id, PackageId::new("sys-apps", "systemd"));
+        assert_eq!(
+            spec.version,
+            VersionSpec::GreaterThanOrEqual(Version::new(250, 0, 0))
+        );
+        assert_eq!(spec.slot.as_deref(), Some("0"));
+        assert_eq!(spec.repo.as_deref(), Some("gentoo"));
+
+        assert_eq!(
+            PackageSpec::parse("systemd"),
+            Err(AtomError::InvalidSpec("systemd".to_string()))
+        );
+        assert_eq!(
+            PackageSpec::parse("=sys-apps/systemd-2x"),
+            Err(AtomError::InvalidVersion("2x".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_matches() {
+        let id = PackageId::new("dev-libs", "openssl");
+        let spec = PackageSpec::parse("<dev-libs/openssl-3:0").unwrap();
+        assert!(spec.matches(&id, &Version::new(1, 1, 1), Some("0")));
+        assert!(spec.matches(&id, &Version::new(1, 1, 1), None));
+        assert!(!spec.matches(&id, &Version::new(3, 0, 0), Some("0")));
+        assert!(!spec.matches(&id, &Version::new(1, 1, 1), Some("1.1")));
+        assert!(!spec.matches(
+            &PackageId::new("dev-libs", "libressl"),
+            &Version::new(1, 0, 0),
+            None
+        ));
+    }
+}
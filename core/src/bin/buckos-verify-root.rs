@@ -0,0 +1,118 @@
+//! Early-boot root filesystem verification
+//!
+//! Runs in the initramfs before switching root: every file in the boot
+//! manifest is hashed below the new root and compared with the manifest.
+//! The manifest is the copy built into the initramfs, so a tampered root
+//! cannot vouch for itself. Failures go to the kernel log and stderr; the
+//! exit status is 0 when everything matches, 1 when a file does not and 2
+//! when the check could not run. The dracut hook decides whether a failure
+//! stops the boot.
+//!
+//! Build it statically for the initramfs, e.g.
+//! `cargo build --release -p buckos-core --target x86_64-unknown-linux-musl`.
+
+use buckos_core::manifest::FileManifest;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const DEFAULT_ROOT: &str = "/sysroot";
+const DEFAULT_MANIFEST: &str = "/etc/buckos/boot-manifest";
+
+const USAGE: &str = "usage: buckos-verify-root [--root DIR] [--manifest FILE]";
+
+/// Kernel log levels
+const LOG_ERR: u8 = 3;
+const LOG_INFO: u8 = 6;
+
+/// Writes to /dev/kmsg when it is available, and always to stderr
+struct Log {
+    kmsg: Option<std::fs::File>,
+}
+
+impl Log {
+    fn open() -> Self {
+        Self {
+            kmsg: OpenOptions::new().write(true).open("/dev/kmsg").ok(),
+        }
+    }
+
+    fn write(&mut self, level: u8, message: &str) {
+        if let Some(kmsg) = &mut self.kmsg {
+            // One write per record; kmsg splits records on write boundaries
+            let _ = kmsg.write_all(format!("<{}>buckos-verify: {}\n", level, message).as_bytes());
+        }
+        eprintln!("buckos-verify: {}", message);
+    }
+}
+
+fn main() -> ExitCode {
+    let mut root = PathBuf::from(DEFAULT_ROOT);
+    let mut manifest_path = PathBuf::from(DEFAULT_MANIFEST);
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let target = match arg.as_str() {
+            "--root" => &mut root,
+            "--manifest" => &mut manifest_path,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ => {
+                eprintln!("{}", USAGE);
+                return ExitCode::from(2);
+            }
+        };
+        match args.next() {
+            Some(value) => *target = PathBuf::from(value),
+            None => {
+                eprintln!("{}", USAGE);
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    let mut log = Log::open();
+    let manifest = match std::fs::read_to_string(&manifest_path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| FileManifest::parse(&content).map_err(|e| e.to_string()))
+    {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            log.write(
+                LOG_ERR,
+                &format!("cannot read {}: {}", manifest_path.display(), e),
+            );
+            return ExitCode::from(2);
+        }
+    };
+
+    let failures = manifest.verify(&root);
+    for failure in &failures {
+        log.write(LOG_ERR, &failure.to_string());
+    }
+    if failures.is_empty() {
+        log.write(
+            LOG_INFO,
+            &format!(
+                "{} files in {} match the boot manifest",
+                manifest.entries.len(),
+                root.display()
+            ),
+        );
+        ExitCode::SUCCESS
+    } else {
+        log.write(
+            LOG_ERR,
+            &format!(
+                "{} of {} files in {} do not match the boot manifest",
+                failures.len(),
+                manifest.entries.len(),
+                root.display()
+            ),
+        );
+        ExitCode::from(1)
+    }
+}
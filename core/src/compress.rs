@@ -0,0 +1,596 @@
+//! Compression backends
+//!
+//! The algorithms binary packages, stored build logs and archived journal
+//! files are compressed with. gzip, xz and zstd run in process; bzip2 and
+//! lz4 go through the `bzip2` and `lz4` programs. Settings are written the
+//! make.conf way, an algorithm name plus the flags its command line tool
+//! takes (`BINPKG_COMPRESS="zstd"`, `BINPKG_COMPRESS_FLAGS="-19 -T0"`), or
+//! as `algorithm[:level]` in one word.
+//!
+//! Readers are never told the format: [`open`] recognizes each algorithm's
+//! magic bytes, so files written before a setting changed, or before they
+//! were compressed at all, still read back.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::str::FromStr;
+
+/// Inputs at least this large use a thread per core when the thread count
+/// is left automatic
+pub const PARALLEL_MIN_SIZE: u64 = 64 << 20;
+
+/// A compression algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum Algorithm {
+    None,
+    Gzip,
+    Bzip2,
+    Xz,
+    Lz4,
+    Zstd,
+}
+
+impl Algorithm {
+    pub const ALL: [Algorithm; 6] = [
+        Algorithm::None,
+        Algorithm::Gzip,
+        Algorithm::Bzip2,
+        Algorithm::Xz,
+        Algorithm::Lz4,
+        Algorithm::Zstd,
+    ];
+
+    /// Name as written in make.conf
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::None => "none",
+            Algorithm::Gzip => "gzip",
+            Algorithm::Bzip2 => "bzip2",
+            Algorithm::Xz => "xz",
+            Algorithm::Lz4 => "lz4",
+            Algorithm::Zstd => "zstd",
+        }
+    }
+
+    /// File name suffix of compressed files, without the dot
+    pub fn suffix(&self) -> Option<&'static str> {
+        match self {
+            Algorithm::None => None,
+            Algorithm::Gzip => Some("gz"),
+            Algorithm::Bzip2 => Some("bz2"),
+            Algorithm::Xz => Some("xz"),
+            Algorithm::Lz4 => Some("lz4"),
+            Algorithm::Zstd => Some("zst"),
+        }
+    }
+
+    /// Algorithm of a file name suffix such as `zst`
+    pub fn from_suffix(suffix: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.suffix() == Some(suffix))
+    }
+
+    /// Algorithm that wrote data starting with `header`, from its magic
+    /// bytes; [`Algorithm::None`] for anything unrecognized
+    pub fn detect(header: &[u8]) -> Self {
+        const MAGIC: [(&[u8], Algorithm); 5] = [
+            (&[0x1f, 0x8b], Algorithm::Gzip),
+            (b"BZh", Algorithm::Bzip2),
+            (&[0xfd, b'7', b'z', b'X', b'Z', 0x00], Algorithm::Xz),
+            (&[0x04, 0x22, 0x4d, 0x18], Algorithm::Lz4),
+            (&[0x28, 0xb5, 0x2f, 0xfd], Algorithm::Zstd),
+        ];
+        MAGIC
+            .into_iter()
+            .find(|(magic, _)| header.starts_with(magic))
+            .map_or(Algorithm::None, |(_, algorithm)| algorithm)
+    }
+
+    /// Level used when none is configured
+    pub fn default_level(&self) -> i32 {
+        match self {
+            Algorithm::None => 0,
+            Algorithm::Gzip => 6,
+            Algorithm::Bzip2 => 9,
+            Algorithm::Xz => 6,
+            Algorithm::Lz4 => 1,
+            Algorithm::Zstd => 3,
+        }
+    }
+
+    /// Levels the algorithm accepts
+    pub fn levels(&self) -> RangeInclusive<i32> {
+        match self {
+            Algorithm::None => 0..=0,
+            Algorithm::Gzip | Algorithm::Bzip2 => 1..=9,
+            Algorithm::Xz => 0..=9,
+            Algorithm::Lz4 => 1..=12,
+            Algorithm::Zstd => 1..=22,
+        }
+    }
+
+    /// Command line tool, for algorithms without an in-process
+    /// implementation and for tools such as tar that run one
+    pub fn program(&self) -> Option<&'static str> {
+        match self {
+            Algorithm::None => None,
+            Algorithm::Gzip => Some("gzip"),
+            Algorithm::Bzip2 => Some("bzip2"),
+            Algorithm::Xz => Some("xz"),
+            Algorithm::Lz4 => Some("lz4"),
+            Algorithm::Zstd => Some("zstd"),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = CompressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "none" | "uncompressed" => Ok(Algorithm::None),
+            "gzip" | "gz" => Ok(Algorithm::Gzip),
+            "bzip2" | "bz2" => Ok(Algorithm::Bzip2),
+            "xz" => Ok(Algorithm::Xz),
+            "lz4" => Ok(Algorithm::Lz4),
+            "zstd" | "zst" => Ok(Algorithm::Zstd),
+            other => Err(CompressError::UnknownAlgorithm(other.to_string())),
+        }
+    }
+}
+
+/// A compression setting that could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompressError {
+    /// Not one of the supported algorithms
+    UnknownAlgorithm(String),
+    /// A level or thread count that is not a number or out of range
+    InvalidSetting(String),
+}
+
+impl fmt::Display for CompressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompressError::UnknownAlgorithm(s) => write!(
+                f,
+                "Unknown compression '{}' (none, gzip, bzip2, xz, lz4, zstd)",
+                s
+            ),
+            CompressError::InvalidSetting(s) => write!(f, "Invalid compression setting: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for CompressError {}
+
+/// An algorithm with its level and thread count
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compression {
+    pub algorithm: Algorithm,
+    /// `None` for the algorithm's default level
+    pub level: Option<i32>,
+    /// Worker threads for zstd and xz; 0 picks one per core for inputs of
+    /// at least [`PARALLEL_MIN_SIZE`] and one otherwise
+    pub threads: u32,
+}
+
+impl Compression {
+    pub fn new(algorithm: Algorithm) -> Self {
+        Self {
+            algorithm,
+            level: None,
+            threads: 0,
+        }
+    }
+
+    pub fn with_level(mut self, level: i32) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    pub fn with_threads(mut self, threads: u32) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Parse a make.conf style setting: an algorithm (or `algorithm:level`)
+    /// and the flags its command line tool would take
+    ///
+    /// Only the level (`-19`) and thread count (`-T0`, `--threads=4`) are
+    /// understood; other flags are ignored.
+    pub fn from_make_conf(compress: &str, flags: &str) -> Result<Self, CompressError> {
+        let mut compression: Compression = compress.parse()?;
+        for flag in flags.split_whitespace() {
+            if let Some(threads) = flag
+                .strip_prefix("--threads=")
+                .or_else(|| flag.strip_prefix("-T"))
+            {
+                compression.threads = threads
+                    .parse()
+                    .map_err(|_| CompressError::InvalidSetting(flag.to_string()))?;
+            } else if let Some(level) = flag
+                .strip_prefix('-')
+                .filter(|l| !l.is_empty() && l.bytes().all(|b| b.is_ascii_digit()))
+            {
+                compression.level = Some(compression.checked_level(level)?);
+            }
+        }
+        Ok(compression)
+    }
+
+    fn checked_level(&self, level: &str) -> Result<i32, CompressError> {
+        level
+            .parse()
+            .ok()
+            .filter(|l| self.algorithm.levels().contains(l))
+            .ok_or_else(|| {
+                CompressError::InvalidSetting(format!(
+                    "level {} for {} (expected {}-{})",
+                    level,
+                    self.algorithm,
+                    self.algorithm.levels().start(),
+                    self.algorithm.levels().end()
+                ))
+            })
+    }
+
+    /// Level compressed at
+    pub fn level(&self) -> i32 {
+        let levels = self.algorithm.levels();
+        self.level
+            .unwrap_or_else(|| self.algorithm.default_level())
+            .clamp(*levels.start(), *levels.end())
+    }
+
+    /// Worker threads for an input of `size` bytes
+    pub fn threads_for(&self, size: u64) -> u32 {
+        if self.threads > 0 {
+            self.threads
+        } else if size >= PARALLEL_MIN_SIZE {
+            std::thread::available_parallelism().map_or(1, |n| n.get() as u32)
+        } else {
+            1
+        }
+    }
+
+    /// Command line compressing stdin to stdout with these settings, for
+    /// an input of about `size` bytes; `None` without compression
+    pub fn command(&self, size: u64) -> Option<Vec<String>> {
+        let program = self.algorithm.program()?;
+        let mut args = vec![program.to_string(), "-c".to_string()];
+        if self.algorithm == Algorithm::Zstd && self.level() > 19 {
+            args.push("--ultra".to_string());
+        }
+        args.push(format!("-{}", self.level()));
+        let threads = self.threads_for(size);
+        if threads > 1 && matches!(self.algorithm, Algorithm::Zstd | Algorithm::Xz) {
+            args.push(format!("-T{}", threads));
+        }
+        Some(args)
+    }
+
+    /// Compress what is written to the encoder into `out`, expecting about
+    /// `size` bytes
+    pub fn encoder(&self, out: File, size: u64) -> io::Result<Encoder> {
+        let level = self.level();
+        let inner = match self.algorithm {
+            Algorithm::None => Inner::Plain(out),
+            Algorithm::Gzip => Inner::Gzip(flate2::write::GzEncoder::new(
+                out,
+                flate2::Compression::new(level as u32),
+            )),
+            Algorithm::Xz => Inner::Xz(xz2::write::XzEncoder::new(out, level as u32)),
+            Algorithm::Zstd => {
+                let mut encoder = zstd::stream::write::Encoder::new(out, level)?;
+                let threads = self.threads_for(size);
+                if threads > 1 {
+                    encoder.multithread(threads)?;
+                }
+                Inner::Zstd(encoder)
+            }
+            Algorithm::Bzip2 | Algorithm::Lz4 => {
+                let args = self.command(size).unwrap_or_default();
+                let mut child = spawn(
+                    Command::new(&args[0])
+                        .args(&args[1..])
+                        .stdin(Stdio::piped())
+                        .stdout(out),
+                    self.algorithm,
+                )?;
+                let stdin = child.stdin.take().expect("stdin is piped");
+                Inner::Process { child, stdin }
+            }
+        };
+        Ok(Encoder { inner })
+    }
+
+    /// Write `data` compressed to `path`
+    pub fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        let mut encoder = self.encoder(File::create(path)?, data.len() as u64)?;
+        encoder.write_all(data)?;
+        encoder.finish()
+    }
+
+    /// Name of a file compressed with these settings, e.g. `build.log.zst`
+    pub fn file_name(&self, base: &str) -> String {
+        match self.algorithm.suffix() {
+            Some(suffix) => format!("{}.{}", base, suffix),
+            None => base.to_string(),
+        }
+    }
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self::new(Algorithm::Zstd)
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.level {
+            Some(level) if self.algorithm != Algorithm::None => {
+                write!(f, "{}:{}", self.algorithm, level)
+            }
+            _ => write!(f, "{}", self.algorithm),
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = CompressError;
+
+    /// `algorithm` or `algorithm:level`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (algorithm, level) = match s.split_once(':') {
+            Some((algorithm, level)) => (algorithm, Some(level)),
+            None => (s, None),
+        };
+        let mut compression = Compression::new(algorithm.parse()?);
+        if let Some(level) = level {
+            compression.level = Some(compression.checked_level(level.trim())?);
+        }
+        Ok(compression)
+    }
+}
+
+/// Writer compressing into a file; [`finish`](Encoder::finish) must be
+/// called to complete it
+pub struct Encoder {
+    inner: Inner,
+}
+
+enum Inner {
+    Plain(File),
+    Gzip(flate2::write::GzEncoder<File>),
+    Xz(xz2::write::XzEncoder<File>),
+    Zstd(zstd::stream::write::Encoder<'static, File>),
+    Process { child: Child, stdin: ChildStdin },
+}
+
+impl Encoder {
+    /// Write the end of the compressed stream and wait for the compressor
+    pub fn finish(self) -> io::Result<()> {
+        match self.inner {
+            Inner::Plain(mut file) => file.flush(),
+            Inner::Gzip(encoder) => encoder.finish().map(drop),
+            Inner::Xz(encoder) => encoder.finish().map(drop),
+            Inner::Zstd(encoder) => encoder.finish().map(drop),
+            Inner::Process { mut child, stdin } => {
+                drop(stdin);
+                let status = child.wait()?;
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(io::Error::other(format!(
+                        "compressor exited with {}",
+                        status
+                    )))
+                }
+            }
+        }
+    }
+}
+
+impl Write for Encoder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            Inner::Plain(w) => w.write(buf),
+            Inner::Gzip(w) => w.write(buf),
+            Inner::Xz(w) => w.write(buf),
+            Inner::Zstd(w) => w.write(buf),
+            Inner::Process { stdin, .. } => stdin.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.inner {
+            Inner::Plain(w) => w.flush(),
+            Inner::Gzip(w) => w.flush(),
+            Inner::Xz(w) => w.flush(),
+            Inner::Zstd(w) => w.flush(),
+            Inner::Process { stdin, .. } => stdin.flush(),
+        }
+    }
+}
+
+/// Open a file for reading, decompressing it if it is compressed with any
+/// supported algorithm
+pub fn open(path: &Path) -> io::Result<Box<dyn Read + Send>> {
+    decoder(File::open(path)?)
+}
+
+/// Reader of a file's contents, decompressed if its magic bytes say so
+pub fn decoder(mut file: File) -> io::Result<Box<dyn Read + Send>> {
+    let mut header = [0u8; 6];
+    let mut len = 0;
+    while len < header.len() {
+        match file.read(&mut header[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    file.seek(SeekFrom::Start(0))?;
+
+    Ok(match Algorithm::detect(&header[..len]) {
+        Algorithm::None => Box::new(file),
+        Algorithm::Gzip => Box::new(flate2::read::MultiGzDecoder::new(file)),
+        Algorithm::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(file)),
+        Algorithm::Zstd => Box::new(zstd::stream::read::Decoder::new(file)?),
+        algorithm @ (Algorithm::Bzip2 | Algorithm::Lz4) => {
+            let program = algorithm.program().unwrap_or_default();
+            let mut child = spawn(
+                Command::new(program)
+                    .arg("-dc")
+                    .stdin(file)
+                    .stdout(Stdio::piped()),
+                algorithm,
+            )?;
+            let stdout = child.stdout.take();
+            Box::new(ProcessReader { child, stdout })
+        }
+    })
+}
+
+/// A file's decompressed contents
+pub fn read(path: &Path) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    open(path)?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// A file's decompressed contents as text
+pub fn read_to_string(path: &Path) -> io::Result<String> {
+    let mut text = String::new();
+    open(path)?.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+fn spawn(command: &mut Command, algorithm: Algorithm) -> io::Result<Child> {
+    command.stderr(Stdio::null()).spawn().map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!(
+                "{} needs the {} program: {}",
+                algorithm,
+                algorithm.program().unwrap_or_default(),
+                e
+            ),
+        )
+    })
+}
+
+/// Output of a decompressor process, reaped once read to the end
+struct ProcessReader {
+    child: Child,
+    stdout: Option<ChildStdout>,
+}
+
+impl Read for ProcessReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(stdout) = &mut self.stdout else {
+            return Ok(0);
+        };
+        let n = stdout.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            self.stdout = None;
+            let status = self.child.wait()?;
+            if !status.success() {
+                return Err(io::Error::other(format!(
+                    "decompressor exited with {}",
+                    status
+                )));
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl Drop for ProcessReader {
+    fn drop(&mut self) {
+        // Closing the pipe first lets a decompressor stopped mid-write exit
+        self.stdout = None;
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_settings() {
+        assert_eq!("zstd".parse(), Ok(Compression::new(Algorithm::Zstd)));
+        assert_eq!(
+            "xz:9".parse(),
+            Ok(Compression::new(Algorithm::Xz).with_level(9))
+        );
+        assert!("zstd:23".parse::<Compression>().is_err());
+        assert!(matches!(
+            "brotli".parse::<Compression>(),
+            Err(CompressError::UnknownAlgorithm(_))
+        ));
+
+        let c = Compression::from_make_conf("zstd", "-19 -T0 --long").unwrap();
+        assert_eq!((c.level, c.threads), (Some(19), 0));
+        let c = Compression::from_make_conf("xz", "--threads=4 -9e").unwrap();
+        assert_eq!((c.level(), c.threads), (6, 4));
+        assert!(Compression::from_make_conf("gzip", "-12").is_err());
+        assert_eq!(c.with_level(9).to_string(), "xz:9");
+    }
+
+    #[test]
+    fn test_command_and_threads() {
+        let zstd = Compression::new(Algorithm::Zstd).with_level(22);
+        assert_eq!(zstd.threads_for(1024), 1);
+        assert!(zstd.threads_for(PARALLEL_MIN_SIZE) >= 1);
+        assert_eq!(
+            zstd.with_threads(8).command(0).unwrap(),
+            vec!["zstd", "-c", "--ultra", "-22", "-T8"]
+        );
+        assert_eq!(
+            Compression::new(Algorithm::Bzip2).command(0).unwrap(),
+            vec!["bzip2", "-c", "-9"]
+        );
+        assert_eq!(Compression::new(Algorithm::None).command(0), None);
+    }
+
+    #[test]
+    fn test_round_trip_and_detection() {
+        let dir = std::env::temp_dir().join(format!("buckos-core-compress-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let data = b"buckos build log\n".repeat(1000);
+
+        for algorithm in [
+            Algorithm::None,
+            Algorithm::Gzip,
+            Algorithm::Xz,
+            Algorithm::Zstd,
+        ] {
+            let compression = Compression::new(algorithm).with_threads(2);
+            let path = dir.join(compression.file_name("log"));
+            compression.write(&path, &data).unwrap();
+
+            let written = std::fs::read(&path).unwrap();
+            assert_eq!(Algorithm::detect(&written), algorithm);
+            if algorithm != Algorithm::None {
+                assert!(written.len() < data.len());
+            }
+            assert_eq!(read(&path).unwrap(), data, "{}", algorithm);
+            if let Some(suffix) = algorithm.suffix() {
+                assert_eq!(Algorithm::from_suffix(suffix), Some(algorithm));
+            }
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,59 @@
+//! File hashing
+//!
+//! The package database and binary package manifests record files by
+//! their BLAKE3 digest, hex encoded.
+
+use alloc::string::String;
+
+/// Hex-encoded BLAKE3 digest of `data`
+pub fn blake3_hex(data: &[u8]) -> String {
+    blake3::hash(data).to_hex().as_str().into()
+}
+
+/// Hex-encoded BLAKE3 digest of everything `reader` yields
+#[cfg(feature = "std")]
+pub fn hash_reader(mut reader: impl std::io::Read) -> std::io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = [0u8; 8192];
+
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+
+    Ok(hasher.finalize().to_hex().as_str().into())
+}
+
+/// Hex-encoded BLAKE3 digest of a file
+#[cfg(feature = "std")]
+pub fn hash_file(path: &std::path::Path) -> std::io::Result<String> {
+    hash_reader(std::fs::File::open(path)?)
+}
+
+/// Whether a file's contents match a recorded digest
+#[cfg(feature = "std")]
+pub fn verify_file(path: &std::path::Path, expected: &str) -> std::io::Result<bool> {
+    Ok(hash_file(path)?.eq_ignore_ascii_case(expected))
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash() {
+        let digest = blake3_hex(b"buckos");
+        assert_eq!(digest.len(), 64);
+        assert_eq!(hash_reader(&b"buckos"[..]).unwrap(), digest);
+
+        let path = std::env::temp_dir().join(format!("buckos-core-hash-{}", std::process::id()));
+        std::fs::write(&path, b"buckos").unwrap();
+        assert!(verify_file(&path, &digest).unwrap());
+        assert!(verify_file(&path, &digest.to_uppercase()).unwrap());
+        assert!(!verify_file(&path, &blake3_hex(b"other")).unwrap());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,500 @@
+//! Translated messages of the command line tools
+//!
+//! Messages are [Fluent](https://projectfluent.org) resources looked up by
+//! id. Each tool has a [`Catalog`] with its English messages built in,
+//! which are the fallback for any message a translation lacks.
+//! Translations are read from `<dir>/<locale>/<resource>`, where the
+//! directory is `BUCKOS_LOCALE_DIR` or [`LOCALE_DIR`], and the locale comes
+//! from `LC_ALL`, `LC_MESSAGES` or `LANG`. A locale such as `de_AT.UTF-8`
+//! uses `de-AT/` if it exists and `de/` otherwise.
+//!
+//! ```text
+//! # /usr/share/buckos/locales/de/buckos.ftl
+//! sync-complete = Synchronisierung abgeschlossen
+//! ```
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::FluentResource;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tracing::warn;
+use unic_langid::LanguageIdentifier;
+
+pub use fluent_bundle::FluentArgs;
+
+/// Where translations are installed
+pub const LOCALE_DIR: &str = "/usr/share/buckos/locales";
+
+/// Environment variable overriding [`LOCALE_DIR`]
+pub const LOCALE_DIR_VAR: &str = "BUCKOS_LOCALE_DIR";
+
+/// The messages of one tool, in the locale of the environment once
+/// [`Catalog::init`] is called and in English until then
+pub struct Catalog {
+    /// Name of the resource file in each locale directory
+    resource: &'static str,
+    /// The built-in English messages
+    english: &'static str,
+    localizer: OnceLock<Localizer>,
+}
+
+impl Catalog {
+    /// A catalog reading translations from `<locale>/<resource>`
+    pub const fn new(resource: &'static str, english: &'static str) -> Self {
+        Self {
+            resource,
+            english,
+            localizer: OnceLock::new(),
+        }
+    }
+
+    /// Use the locale of the environment for all further messages
+    pub fn init(&self) {
+        self.localizer
+            .get_or_init(|| Localizer::from_env(self.resource, self.english));
+    }
+
+    /// Format a message in the current locale
+    pub fn message(&self, id: &str, args: Option<&FluentArgs>) -> String {
+        self.localizer
+            .get_or_init(|| Localizer::english(self.english))
+            .message(id, args)
+    }
+}
+
+/// Messages of one locale, falling back to English
+pub struct Localizer {
+    /// Bundles to try in order; English is always last
+    bundles: Vec<FluentBundle<FluentResource>>,
+}
+
+impl Localizer {
+    /// The English messages in `source` alone
+    pub fn english(source: &str) -> Self {
+        Self {
+            bundles: vec![bundle(english_locale(), source.to_string())],
+        }
+    }
+
+    /// Messages of `locale` from `dir`, if translated there, over English
+    pub fn load(locale: &LanguageIdentifier, dir: &Path, resource: &str, english: &str) -> Self {
+        let mut localizer = Self::english(english);
+        if locale.language == english_locale().language {
+            return localizer;
+        }
+        let Some(path) = resource_path(locale, dir, resource) else {
+            return localizer;
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(source) => localizer.bundles.insert(0, bundle(locale.clone(), source)),
+            Err(e) => warn!("Failed to read {}: {}", path.display(), e),
+        }
+        localizer
+    }
+
+    /// Messages of the locale of the environment
+    pub fn from_env(resource: &str, english: &str) -> Self {
+        let env = |name: &str| std::env::var(name).ok();
+        let dir = env(LOCALE_DIR_VAR)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(LOCALE_DIR));
+        match detect_locale(env) {
+            Some(locale) => Self::load(&locale, &dir, resource, english),
+            None => Self::english(english),
+        }
+    }
+
+    /// Whether any bundle has a message `id`
+    pub fn has_message(&self, id: &str) -> bool {
+        self.bundles.iter().any(|b| b.has_message(id))
+    }
+
+    /// Format a message; an unknown id is returned as is
+    pub fn message(&self, id: &str, args: Option<&FluentArgs>) -> String {
+        for bundle in &self.bundles {
+            let Some(pattern) = bundle.get_message(id).and_then(|m| m.value()) else {
+                continue;
+            };
+            let mut errors = Vec::new();
+            let text = bundle.format_pattern(pattern, args, &mut errors);
+            if errors.is_empty() {
+                return text.into_owned();
+            }
+            warn!("Failed to format message {}: {:?}", id, errors);
+        }
+        id.to_string()
+    }
+}
+
+/// Locale of messages from `LC_ALL`, `LC_MESSAGES` or `LANG`, the first
+/// that is set; `None` for the C locale or when none is set
+pub fn detect_locale(env: impl Fn(&str) -> Option<String>) -> Option<LanguageIdentifier> {
+    let value = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .find_map(|name| env(name).filter(|v| !v.is_empty()))?;
+    // language[_territory][.codeset][@modifier]
+    let name = value.split(['.', '@']).next()?;
+    if name == "C" || name == "POSIX" {
+        return None;
+    }
+    name.replace('_', "-").parse().ok()
+}
+
+/// The translation of `locale` in `dir`: the full locale, then its language
+fn resource_path(locale: &LanguageIdentifier, dir: &Path, resource: &str) -> Option<PathBuf> {
+    [locale.to_string(), locale.language.to_string()]
+        .iter()
+        .map(|name| dir.join(name).join(resource))
+        .find(|path| path.is_file())
+}
+
+fn english_locale() -> LanguageIdentifier {
+    "en-US".parse().expect("valid locale")
+}
+
+fn bundle(locale: LanguageIdentifier, source: String) -> FluentBundle<FluentResource> {
+    let resource = FluentResource::try_new(source).unwrap_or_else(|(resource, errors)| {
+        warn!("Errors in {} messages: {:?}", locale, errors);
+        resource
+    });
+    let mut bundle = FluentBundle::new_concurrent(vec![locale]);
+    // Terminals show the Unicode isolation marks around arguments literally
+    bundle.set_use_isolating(false);
+    if let Err(errors) = bundle.add_resource(resource) {
+        warn!("Duplicate messages: {:?}", errors);
+    }
+    bundle
+}
+
+/// Ids of the messages defined in `source`: lines starting with `id =`
+fn defined_ids(source: &str) -> Vec<String> {
+    source
+        .lines()
+        .filter_map(|line| Some(line.split_once(" =")?.0))
+        .filter(|id| id.starts_with(|c: char| c.is_ascii_lowercase()))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Problems with how a tool's sources use its messages, for its unit tests
+///
+/// Reports `tr!` ids with no English message, English messages no `tr!`
+/// uses, and string literals with words in them passed to `println!` or
+/// `eprintln!` other than through `tr!`, which would reach users
+/// untranslated.
+pub fn check_sources<'a>(
+    catalog: &Catalog,
+    sources: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Err((_, errors)) = FluentResource::try_new(catalog.english.to_string()) {
+        problems.push(format!("errors in the English messages: {:?}", errors));
+    }
+    let english = Localizer::english(catalog.english);
+
+    let mut used = BTreeSet::new();
+    for (name, source) in sources {
+        for id in used_ids(source) {
+            if !english.has_message(&id) {
+                problems.push(format!("{}: tr!(\"{}\") has no English message", name, id));
+            }
+            used.insert(id);
+        }
+        for (line, literal) in untranslated_literals(source) {
+            problems.push(format!("{}:{}: untranslated \"{}\"", name, line, literal));
+        }
+    }
+    for id in defined_ids(catalog.english) {
+        if !used.contains(&id) {
+            problems.push(format!("message {} is never used", id));
+        }
+    }
+    problems
+}
+
+/// Ids passed to `tr!` in `source`
+fn used_ids(source: &str) -> Vec<String> {
+    source
+        .split("tr!(")
+        .skip(1)
+        .filter_map(|call| {
+            let call = call.trim_start().strip_prefix('"')?;
+            Some(call[..call.find('"')?].to_string())
+        })
+        .collect()
+}
+
+/// String literals with words in them inside `println!` and `eprintln!`
+/// calls but outside `tr!`, with their line numbers
+fn untranslated_literals(source: &str) -> Vec<(usize, String)> {
+    let mut found = Vec::new();
+    for start in macro_calls(source, &["println!(", "eprintln!("]) {
+        let Some(len) = call_len(&source[start..]) else {
+            continue;
+        };
+        let call = &source[start..start + len];
+        let mut offset = 0;
+        while let Some((at, literal)) = next_literal(&call[offset..]) {
+            let at = offset + at;
+            offset = at + literal.len() + 2;
+            // Patterns and comparisons match data, they are not shown
+            let compared = call[offset..].trim_start().starts_with(['=', '|'])
+                || call[..at].trim_end().ends_with("==")
+                || call[..at].trim_end().ends_with("!=");
+            if compared || is_message_id(&call[..at]) {
+                continue;
+            }
+            if has_words(&literal) {
+                let line = source[..start + at].matches('\n').count() + 1;
+                found.push((line, literal));
+            }
+        }
+    }
+    found
+}
+
+/// Byte offsets of the argument lists of calls to `names`
+fn macro_calls(source: &str, names: &[&str]) -> Vec<usize> {
+    let mut calls = Vec::new();
+    for name in names {
+        let mut from = 0;
+        while let Some(at) = source[from..].find(name) {
+            let at = from + at;
+            let preceded = source[..at]
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_');
+            if !preceded {
+                calls.push(at + name.len() - 1);
+            }
+            from = at + name.len();
+        }
+    }
+    calls.sort_unstable();
+    calls
+}
+
+/// Length of the parenthesized group `call` starts with, skipping strings
+fn call_len(call: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut chars = call.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            '"' => {
+                let (_, literal) = next_literal(&call[i..])?;
+                let end = i + literal.len() + 2;
+                while chars.next().is_some_and(|(j, _)| j + 1 < end) {}
+            }
+            '\'' => {
+                // A char literal such as '"' or '('; lifetimes have no
+                // closing quote two or three characters on
+                let rest = &call[i + 1..];
+                if let Some(close) = rest
+                    .char_indices()
+                    .take(4)
+                    .skip(1)
+                    .find(|(_, c)| *c == '\'')
+                {
+                    if !rest[..close.0].contains(char::is_whitespace) {
+                        for _ in 0..close.0 + 1 {
+                            chars.next();
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The first string literal in `code`: its offset and its raw contents
+fn next_literal(code: &str) -> Option<(usize, String)> {
+    let at = code.find('"')?;
+    let rest = &code[at + 1..];
+    let mut escaped = false;
+    for (i, c) in rest.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return Some((at, rest[..i].to_string())),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// Whether the code before a literal makes it the id of a `tr!` call
+fn is_message_id(before: &str) -> bool {
+    before
+        .trim_end()
+        .strip_suffix("tr!(")
+        .is_some_and(|rest| !rest.ends_with(|c: char| c.is_alphanumeric() || c == '_'))
+}
+
+/// Whether a literal has a word outside `{...}` placeholders and escapes
+///
+/// A word has two or more letters and is lowercase after its first letter;
+/// variable names such as `CFLAGS` or `SystemCallFilter`, options, paths,
+/// atoms and units such as `{}ms` are not words.
+fn has_words(literal: &str) -> bool {
+    let mut text = String::new();
+    let mut depth = 0;
+    let mut chars = literal.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                // \n, \t, \u{..}: not words
+                if chars.next() == Some('u') {
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            break;
+                        }
+                    }
+                }
+                text.push(' ');
+            }
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push(' ');
+            }
+            '{' => {
+                // A placeholder is a number at most: `{}ms` is a unit
+                if depth == 0 {
+                    text.push('0');
+                }
+                depth += 1;
+            }
+            '}' if depth > 0 => depth -= 1,
+            _ if depth > 0 => {}
+            c => text.push(c),
+        }
+    }
+    text.split_whitespace().any(|token| {
+        let word = token.trim_matches(|c: char| !c.is_alphanumeric());
+        let code = token
+            .trim_start_matches(['\'', '"', '(', '['])
+            .starts_with('-');
+        !code
+            && word.chars().filter(|c| c.is_alphabetic()).count() >= 2
+            && word.chars().any(char::is_lowercase)
+            && !word.chars().skip(1).any(char::is_uppercase)
+            && word
+                .chars()
+                .all(|c| c.is_alphabetic() || c == '\'' || c == '-')
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENGLISH: &str = "\
+sync-complete = Sync complete
+install-done = { $count ->
+    [one] { $count } package installed
+   *[other] { $count } packages installed
+}
+";
+
+    #[test]
+    fn test_detect_locale() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(k, _)| *k == name)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+        assert_eq!(detect_locale(env(&[])), None);
+        assert_eq!(detect_locale(env(&[("LANG", "C.UTF-8")])), None);
+        assert_eq!(
+            detect_locale(env(&[("LANG", "de_AT.UTF-8@euro")])),
+            Some("de-AT".parse().unwrap())
+        );
+        // LC_ALL overrides LC_MESSAGES, which overrides LANG
+        assert_eq!(
+            detect_locale(env(&[
+                ("LANG", "de_DE.UTF-8"),
+                ("LC_MESSAGES", "fr_FR.UTF-8"),
+                ("LC_ALL", "")
+            ])),
+            Some("fr-FR".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_translation_falls_back_to_english() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("de")).unwrap();
+        std::fs::write(
+            dir.path().join("de").join("tool.ftl"),
+            "sync-complete = Synchronisierung abgeschlossen\n",
+        )
+        .unwrap();
+
+        let german = Localizer::load(&"de-AT".parse().unwrap(), dir.path(), "tool.ftl", ENGLISH);
+        assert_eq!(
+            german.message("sync-complete", None),
+            "Synchronisierung abgeschlossen"
+        );
+        let mut args = FluentArgs::new();
+        args.set("count", 1);
+        assert_eq!(
+            german.message("install-done", Some(&args)),
+            "1 package installed"
+        );
+        args.set("count", 3);
+        assert_eq!(
+            german.message("install-done", Some(&args)),
+            "3 packages installed"
+        );
+
+        let missing = Localizer::load(&"fr".parse().unwrap(), dir.path(), "tool.ftl", ENGLISH);
+        assert_eq!(missing.message("sync-complete", None), "Sync complete");
+        assert_eq!(missing.message("no-such-message", None), "no-such-message");
+    }
+
+    #[test]
+    fn test_check_sources() {
+        static CATALOG: Catalog = Catalog::new("tool.ftl", ENGLISH);
+        let source = r#"
+fn main() {
+    println!("{} {}", theme::info(">>>"), tr!("sync-complete"));
+    println!("{}", tr!("install-done", count = format!("{} ({})", n, "x")));
+    println!("{}", tr!("sync-complete", state = theme::error("revoked")));
+    eprintln!("Failed: {}", e);
+    println!("{}", theme::warning("not running"));
+    let c = '"';
+    println!("{}", match level { "high" => theme::error("!"), _ => theme::plain(" ") });
+    println!("{}: {}", theme::plain("CFLAGS"), "/etc/portage/make.conf");
+    println!("  {} --ask", "x86_64-pc-linux-gnu");
+    println!("SystemCallFilter={} {:>7}ms", filter, ms);
+    println!("{}", tr!("no-such-message"));
+}
+"#;
+        assert_eq!(
+            check_sources(&CATALOG, [("main.rs", source)]),
+            vec![
+                "main.rs: tr!(\"no-such-message\") has no English message",
+                "main.rs:5: untranslated \"revoked\"",
+                "main.rs:6: untranslated \"Failed: {}\"",
+                "main.rs:7: untranslated \"not running\"",
+            ]
+        );
+        assert_eq!(
+            check_sources(&CATALOG, [("main.rs", "")]),
+            vec![
+                "message sync-complete is never used",
+                "message install-done is never used",
+            ]
+        );
+    }
+}
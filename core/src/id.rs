@@ -0,0 +1,48 @@
+//! Package identifiers
+
+use alloc::format;
+use alloc::string::String;
+use core::fmt;
+use serde::{Deserialize, Serialize};
+
+/// Package identifier with category and name
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct PackageId {
+    pub category: String,
+    pub name: String,
+}
+
+impl PackageId {
+    pub fn new(category: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            category: category.into(),
+            name: name.into(),
+        }
+    }
+
+    pub fn full_name(&self) -> String {
+        format!("{}/{}", self.category, self.name)
+    }
+
+    /// Parse a package identifier from string (e.g., "sys-apps/systemd" or "system/apps/shadow")
+    pub fn parse(s: &str) -> Option<Self> {
+        // Find the last slash - everything before is category, after is name
+        if let Some(last_slash) = s.rfind('/') {
+            let category = &s[..last_slash];
+            let name = &s[last_slash + 1..];
+            if !category.is_empty() && !name.is_empty() {
+                Some(Self::new(category, name))
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for PackageId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.category, self.name)
+    }
+}
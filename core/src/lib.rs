@@ -0,0 +1,36 @@
+//! Buckos core types
+//!
+//! Package identifiers, version specifications, atom parsing and matching,
+//! the BLAKE3 file hashing used by the package database and binary
+//! package manifests, and the machine ID, without the package manager's
+//! runtime (tokio, SQLite, HTTP). Small agents such as initramfs tools and recovery
+//! environments use this crate to read manifests and verify files.
+//!
+//! Without the default `std` feature the crate is `no_std` and needs only
+//! `alloc`; hashing then works on byte slices only. The `compress` feature
+//! adds the compression backends shared by the package manager and boss,
+//! and the `i18n` feature their translated command line messages.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod atom;
+#[cfg(feature = "compress")]
+pub mod compress;
+pub mod hash;
+#[cfg(feature = "i18n")]
+pub mod i18n;
+pub mod id;
+pub mod machine_id;
+pub mod manifest;
+pub mod version;
+
+pub use atom::{AtomError, PackageSpec};
+pub use id::PackageId;
+pub use machine_id::MachineId;
+pub use manifest::FileManifest;
+pub use version::{parse_version, VersionSpec};
+
+/// Re-exported so users agree on the version type
+pub use semver;
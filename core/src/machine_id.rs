@@ -0,0 +1,168 @@
+//! Machine ID
+//!
+//! `/etc/machine-id` identifies an installation, as systemd defines it: 128
+//! bits written as 32 lowercase hex digits and a newline, generated once on
+//! first boot and kept for the life of the installation. An empty file, or
+//! one reading `uninitialized`, marks a system that has not booted yet.
+//!
+//! The ID is meant to stay on the machine. Anything sent elsewhere should
+//! carry an [`app_specific`](MachineId::app_specific) ID instead, which
+//! identifies the machine to one application without revealing the ID.
+//! It is derived as `sd_id128_get_machine_app_specific()` derives it, so
+//! buckos and systemd tools agree on it.
+
+use alloc::string::String;
+use core::fmt;
+use sha2::{Digest, Sha256};
+
+/// Where the machine ID is kept
+pub const MACHINE_ID_PATH: &str = "/etc/machine-id";
+
+/// Contents of the machine ID file of a system that has not booted yet
+pub const UNINITIALIZED: &str = "uninitialized";
+
+/// A 128-bit machine ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MachineId([u8; 16]);
+
+impl MachineId {
+    /// An ID from its 16 bytes, as they are written in hex
+    pub const fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(bytes)
+    }
+
+    /// The ID's 16 bytes, in the order they are written in hex
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    /// Parse the contents of a machine ID file: 32 hex digits, optionally
+    /// followed by a newline. All zeroes is not an ID.
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.strip_suffix('\n').unwrap_or(s);
+        if s.len() != 32 {
+            return None;
+        }
+        let mut bytes = [0u8; 16];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(s.get(i * 2..i * 2 + 2)?, 16).ok()?;
+        }
+        (bytes != [0; 16]).then_some(Self(bytes))
+    }
+
+    /// An ID derived from random bytes, formatted as a version 4 UUID the
+    /// way systemd generates them.
+    pub fn from_random(mut bytes: [u8; 16]) -> Self {
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        Self(bytes)
+    }
+
+    /// A new random ID.
+    #[cfg(feature = "std")]
+    pub fn generate() -> std::io::Result<Self> {
+        use std::io::Read;
+
+        let mut bytes = [0u8; 16];
+        std::fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+        Ok(Self::from_random(bytes))
+    }
+
+    /// The ID of this machine, if it has one.
+    #[cfg(feature = "std")]
+    pub fn read() -> Option<Self> {
+        Self::read_from(std::path::Path::new(MACHINE_ID_PATH))
+    }
+
+    /// The ID in a machine ID file, if it holds one.
+    #[cfg(feature = "std")]
+    pub fn read_from(path: &std::path::Path) -> Option<Self> {
+        Self::parse(&std::fs::read_to_string(path).ok()?)
+    }
+
+    /// An ID identifying this machine to the application `app` only. The
+    /// same machine and application always give the same ID, but it does
+    /// not reveal the machine ID or match the ID of another application.
+    ///
+    /// `app` is a 128-bit application ID, as made by `systemd-id128 new`.
+    /// The result is HMAC-SHA256 of it keyed with the machine ID, cut to
+    /// 128 bits and marked as a version 4 UUID, as systemd computes it.
+    pub fn app_specific(&self, app: &MachineId) -> Self {
+        let mac = hmac_sha256(&self.0, &app.0);
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&mac[..16]);
+        Self::from_random(bytes)
+    }
+
+    /// Contents of a machine ID file holding this ID
+    pub fn to_file_contents(&self) -> String {
+        alloc::format!("{}\n", self)
+    }
+}
+
+/// HMAC-SHA256 (RFC 2104) with a key no longer than a block
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK: usize = 64;
+    debug_assert!(key.len() <= BLOCK);
+    let mut padded = [0u8; BLOCK];
+    padded[..key.len()].copy_from_slice(key);
+
+    let pad = |byte: u8| padded.map(|k| k ^ byte);
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+impl fmt::Display for MachineId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_machine_id() {
+        let id = MachineId::parse("0123456789ABCDEF0123456789abcdef\n").unwrap();
+        assert_eq!(id.to_string(), "0123456789abcdef0123456789abcdef");
+        assert_eq!(MachineId::parse(&id.to_file_contents()), Some(id));
+        assert_eq!(MachineId::parse(""), None);
+        assert_eq!(MachineId::parse(UNINITIALIZED), None);
+        assert_eq!(MachineId::parse(&"0".repeat(32)), None);
+        assert_eq!(MachineId::parse("0123456789abcdef0123456789abcdeg"), None);
+        assert_eq!(MachineId::parse("é123456789abcdef0123456789abcde"), None);
+
+        let assist = MachineId::parse("5a1b3e0f9c2d4e6f8a7b6c5d4e3f2a1b").unwrap();
+        let other = MachineId::parse("0f1e2d3c4b5a69788796a5b4c3d2e1f0").unwrap();
+        let app = id.app_specific(&assist);
+        assert_eq!(app, id.app_specific(&assist));
+        assert_ne!(app, id.app_specific(&other));
+        assert_ne!(app, id);
+        // Version 4, variant 1
+        assert_eq!(app.as_bytes()[6] >> 4, 4);
+        assert_eq!(app.as_bytes()[8] >> 6, 2);
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231 test case 2
+        let mac = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+        let hex: String = mac.iter().map(|b| alloc::format!("{:02x}", b)).collect();
+        assert_eq!(
+            hex,
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
@@ -0,0 +1,297 @@
+//! File manifests
+//!
+//! A file manifest lists files by absolute path with their BLAKE3 digest,
+//! one per line:
+//!
+//! ```text
+//! # buckos boot manifest
+//! 3f1c...e2a0 /usr/lib/systemd/systemd
+//! 9b07...41d5 /usr/bin/bash
+//! ```
+//!
+//! Blank lines and `#` comments are ignored; the path runs to the end of
+//! the line so it may contain spaces.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// One file and its expected digest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// BLAKE3 digest of the contents, lowercase hex
+    pub hash: String,
+    /// Absolute path, as seen from the root being verified
+    pub path: String,
+}
+
+/// A list of files with their expected digests
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileManifest {
+    /// Entries in the order they were read or pushed
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// A manifest line that could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestError {
+    /// Line number, from 1
+    pub line: usize,
+    /// What is wrong with the line
+    pub message: String,
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ManifestError {}
+
+impl FileManifest {
+    /// Add an entry
+    pub fn push(&mut self, path: impl Into<String>, hash: impl Into<String>) {
+        self.entries.push(ManifestEntry {
+            hash: hash.into(),
+            path: path.into(),
+        });
+    }
+
+    /// Parse manifest text
+    pub fn parse(content: &str) -> Result<Self, ManifestError> {
+        let mut manifest = Self::default();
+        for (idx, line) in content.lines().enumerate() {
+            let line = line.trim_start();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| ManifestError {
+                line: idx + 1,
+                message: message.to_string(),
+            };
+
+            let (hash, path) = line
+                .split_once(' ')
+                .ok_or_else(|| error("expected '<blake3> <path>'"))?;
+            if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(error("digest is not 64 hex characters"));
+            }
+            if !path.starts_with('/') {
+                return Err(error("path is not absolute"));
+            }
+            manifest.push(path, hash.to_ascii_lowercase());
+        }
+        Ok(manifest)
+    }
+
+    /// Render manifest text, sorted by path
+    pub fn render(&self) -> String {
+        let mut entries: Vec<&ManifestEntry> = self.entries.iter().collect();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        entries
+            .iter()
+            .map(|e| format!("{} {}\n", e.hash, e.path))
+            .collect()
+    }
+
+    /// Check every file below `root` against its digest
+    ///
+    /// Paths are resolved as if `root` were `/`: symlinks and `..` never
+    /// lead out of it, so an absolute link in the root is not followed to
+    /// the file of the same name on the running system.
+    #[cfg(feature = "std")]
+    pub fn verify(&self, root: &std::path::Path) -> Vec<VerifyFailure> {
+        self.entries
+            .iter()
+            .filter_map(|entry| {
+                let hash = resolve_in_root(root, &entry.path)
+                    .and_then(|path| crate::hash::hash_file(&path));
+                let problem = match hash {
+                    Ok(hash) if hash == entry.hash => return None,
+                    Ok(hash) => Problem::Mismatch { actual: hash },
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Problem::Missing,
+                    Err(e) => Problem::Unreadable(e.to_string()),
+                };
+                Some(VerifyFailure {
+                    path: entry.path.clone(),
+                    problem,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Most symlinks followed resolving one path, as for the kernel's ELOOP
+#[cfg(feature = "std")]
+const MAX_SYMLINKS: usize = 40;
+
+/// Resolve `path` below `root` as if `root` were `/`
+///
+/// Like `openat2` with `RESOLVE_IN_ROOT`: absolute symlink targets start
+/// again at `root`, and `..` stops there. A component that does not exist
+/// is kept as is, so opening the result reports it missing.
+#[cfg(feature = "std")]
+fn resolve_in_root(root: &std::path::Path, path: &str) -> std::io::Result<std::path::PathBuf> {
+    use std::ffi::OsString;
+    use std::path::{Component, Path};
+
+    // Components still to walk, the next one last; None is `..`
+    fn push_reversed(pending: &mut Vec<Option<OsString>>, path: &Path) {
+        for component in path.components().rev() {
+            match component {
+                Component::Normal(name) => pending.push(Some(name.to_os_string())),
+                Component::ParentDir => pending.push(None),
+                Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+            }
+        }
+    }
+
+    let below_root = |names: &[OsString]| names.iter().fold(root.to_path_buf(), |p, n| p.join(n));
+    let mut pending = Vec::new();
+    push_reversed(&mut pending, Path::new(path));
+    let mut resolved: Vec<OsString> = Vec::new();
+    let mut links = 0;
+    while let Some(component) = pending.pop() {
+        let Some(name) = component else {
+            resolved.pop();
+            continue;
+        };
+        let candidate = below_root(&resolved).join(&name);
+        match std::fs::symlink_metadata(&candidate) {
+            Ok(meta) if meta.file_type().is_symlink() => {
+                links += 1;
+                if links > MAX_SYMLINKS {
+                    return Err(std::io::Error::other("too many levels of symbolic links"));
+                }
+                let target = std::fs::read_link(&candidate)?;
+                if target.has_root() {
+                    resolved.clear();
+                }
+                push_reversed(&mut pending, &target);
+            }
+            _ => resolved.push(name),
+        }
+    }
+    Ok(below_root(&resolved))
+}
+
+/// A file that does not match the manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyFailure {
+    /// Path from the manifest
+    pub path: String,
+    pub problem: Problem,
+}
+
+/// What is wrong with a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// No file at the path
+    Missing,
+    /// The contents have another digest
+    Mismatch { actual: String },
+    /// The file could not be read, e.g. for lack of permission
+    Unreadable(String),
+}
+
+impl fmt::Display for VerifyFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.problem {
+            Problem::Missing => write!(f, "{}: missing", self.path),
+            Problem::Mismatch { actual } => {
+                write!(f, "{}: digest mismatch ({})", self.path, actual)
+            }
+            Problem::Unreadable(e) => write!(f, "{}: unreadable: {}", self.path, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::blake3_hex;
+
+    #[test]
+    fn test_parse_render() {
+        let hash = blake3_hex(b"init");
+        let content = format!(
+            "# boot manifest\n\n{} /usr/lib/my init\n{} /sbin/init\n",
+            hash.to_uppercase(),
+            hash
+        );
+        let manifest = FileManifest::parse(&content).unwrap();
+        assert_eq!(manifest.entries.len(), 2);
+        assert_eq!(manifest.entries[0].path, "/usr/lib/my init");
+        assert_eq!(manifest.entries[0].hash, hash);
+        assert_eq!(
+            manifest.render(),
+            format!("{} /sbin/init\n{} /usr/lib/my init\n", hash, hash)
+        );
+
+        assert_eq!(FileManifest::parse("abc /sbin/init").unwrap_err().line, 1);
+        assert!(FileManifest::parse(&format!("{} sbin/init", hash)).is_err());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_verify() {
+        let root = std::env::temp_dir().join(format!("buckos-core-verify-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("sbin")).unwrap();
+        std::fs::write(root.join("sbin/init"), b"init").unwrap();
+        std::fs::write(root.join("sbin/sh"), b"tampered").unwrap();
+
+        let mut manifest = FileManifest::default();
+        manifest.push("/sbin/init", blake3_hex(b"init"));
+        manifest.push("/sbin/sh", blake3_hex(b"sh"));
+        manifest.push("/sbin/gone", blake3_hex(b"gone"));
+
+        let failures = manifest.verify(&root);
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].path, "/sbin/sh");
+        assert!(matches!(failures[0].problem, Problem::Mismatch { .. }));
+        assert_eq!(failures[1].problem, Problem::Missing);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_verify_resolves_links_in_root() {
+        let base = std::env::temp_dir().join(format!("buckos-core-links-{}", std::process::id()));
+        let root = base.join("root");
+        std::fs::create_dir_all(root.join("usr/lib/systemd")).unwrap();
+        std::fs::create_dir_all(root.join("sbin")).unwrap();
+        std::fs::write(root.join("usr/lib/systemd/systemd"), b"systemd").unwrap();
+        // A host file with the contents the manifest expects
+        std::fs::write(base.join("host-sh"), b"sh").unwrap();
+
+        let link = |target: &std::path::Path, at: &str| {
+            std::os::unix::fs::symlink(target, root.join(at)).unwrap()
+        };
+        link("/usr/lib/systemd/systemd".as_ref(), "sbin/init");
+        link(
+            "../../../../../../usr/lib/systemd/systemd".as_ref(),
+            "sbin/up",
+        );
+        link(&base.join("host-sh"), "sbin/sh");
+        link("/sbin/loop".as_ref(), "sbin/loop");
+
+        let mut manifest = FileManifest::default();
+        manifest.push("/sbin/init", blake3_hex(b"systemd"));
+        manifest.push("/sbin/up", blake3_hex(b"systemd"));
+        manifest.push("/sbin/sh", blake3_hex(b"sh"));
+        manifest.push("/sbin/loop", blake3_hex(b"loop"));
+
+        let failures = manifest.verify(&root);
+        assert_eq!(failures.len(), 2, "{:?}", failures);
+        assert_eq!(failures[0].path, "/sbin/sh");
+        assert_eq!(failures[0].problem, Problem::Missing);
+        assert_eq!(failures[1].path, "/sbin/loop");
+        assert!(matches!(failures[1].problem, Problem::Unreadable(_)));
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
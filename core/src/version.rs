@@ -0,0 +1,72 @@
+//! Version specifications and lenient version parsing
+
+use alloc::format;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+/// Version specification with comparison operator
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum VersionSpec {
+    #[default]
+    Any,
+    Exact(Version),
+    GreaterThan(Version),
+    GreaterThanOrEqual(Version),
+    LessThan(Version),
+    LessThanOrEqual(Version),
+    Range {
+        min: Option<Version>,
+        max: Option<Version>,
+    },
+}
+
+impl VersionSpec {
+    pub fn matches(&self, version: &Version) -> bool {
+        match self {
+            VersionSpec::Any => true,
+            VersionSpec::Exact(v) => version == v,
+            VersionSpec::GreaterThan(v) => version > v,
+            VersionSpec::GreaterThanOrEqual(v) => version >= v,
+            VersionSpec::LessThan(v) => version < v,
+            VersionSpec::LessThanOrEqual(v) => version <= v,
+            VersionSpec::Range { min, max } => {
+                let min_ok = min.as_ref().map(|m| version >= m).unwrap_or(true);
+                let max_ok = max.as_ref().map(|m| version <= m).unwrap_or(true);
+                min_ok && max_ok
+            }
+        }
+    }
+}
+
+/// Parse a version, accepting the short forms used in atoms ("250" or
+/// "250.4") as well as full semver
+pub fn parse_version(s: &str) -> Result<Version, semver::Error> {
+    Version::parse(s).or_else(|_| {
+        let parts: alloc::vec::Vec<&str> = s.split('.').collect();
+        match parts.len() {
+            1 => format!("{}.0.0", parts[0]).parse(),
+            2 => format!("{}.{}.0", parts[0], parts[1]).parse(),
+            _ => s.parse(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("250").unwrap(), Version::new(250, 0, 0));
+        assert_eq!(parse_version("250.4").unwrap(), Version::new(250, 4, 0));
+        assert_eq!(parse_version("1.2.3").unwrap(), Version::new(1, 2, 3));
+        assert!(parse_version("abc").is_err());
+
+        let range = VersionSpec::Range {
+            min: Some(Version::new(1, 0, 0)),
+            max: Some(Version::new(2, 0, 0)),
+        };
+        assert!(range.matches(&Version::new(1, 5, 0)));
+        assert!(!range.matches(&Version::new(2, 0, 1)));
+    }
+}
@@ -3181,6 +3181,7 @@ rootfs(
                                 "udev-rules",     // Udev rules for device management
                                 "usrmount",       // Mount /usr if separate
                                 "resume",         // Resume from hibernation
+                                "buckos-verify",  // Check the root against the boot manifest
                             ];
 
                             for module_name in &desired_modules {
@@ -0,0 +1,118 @@
+//! Audit log of tool calls
+//!
+//! Every tool call is appended as one JSON line recording the client, the
+//! tool and its arguments, the policy decision and the outcome. Without a
+//! log file the records go to the tracing log.
+
+use crate::permissions::Client;
+use crate::policy::Decision;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::Write;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+/// One tool call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub time: DateTime<Utc>,
+    pub client: String,
+    pub tool: String,
+    pub arguments: Value,
+    pub decision: Decision,
+    /// `ok`, or the error the call failed with
+    pub outcome: String,
+}
+
+impl AuditRecord {
+    pub fn new(client: &Client, tool: &str, arguments: &Value, decision: Decision) -> Self {
+        Self {
+            time: Utc::now(),
+            client: client.name().to_string(),
+            tool: tool.to_string(),
+            arguments: arguments.clone(),
+            decision,
+            outcome: String::new(),
+        }
+    }
+}
+
+/// Where audit records go
+#[derive(Debug, Clone, Default)]
+pub struct AuditLog {
+    path: Option<PathBuf>,
+}
+
+impl AuditLog {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self { path }
+    }
+
+    /// Append a record
+    ///
+    /// A log that cannot be written is reported but does not fail the call.
+    pub fn record(&self, record: &AuditRecord) {
+        let Some(path) = &self.path else {
+            info!(
+                client = %record.client,
+                tool = %record.tool,
+                decision = ?record.decision,
+                outcome = %record.outcome,
+                "Tool call"
+            );
+            return;
+        };
+
+        let result = serde_json::to_string(record)
+            .map_err(std::io::Error::other)
+            .and_then(|line| {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                let mut file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)?;
+                writeln!(file, "{}", line)
+            });
+        if let Err(e) = result {
+            warn!(path = %path.display(), error = %e, "Failed to write audit log");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_audit_log() {
+        let path = std::env::temp_dir().join(format!("buckos-mcp-audit-{}", std::process::id()));
+        let log = AuditLog::new(Some(path.clone()));
+
+        let mut record = AuditRecord::new(
+            &Client::Local,
+            "package_install",
+            &json!({"packages": ["bash"]}),
+            Decision::Allow,
+        );
+        record.outcome = "ok".to_string();
+        log.record(&record);
+        record.outcome = "Permission error: denied".to_string();
+        log.record(&record);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let records: Vec<AuditRecord> = content
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].client, "local");
+        assert_eq!(records[0].arguments["packages"][0], "bash");
+        assert_eq!(records[1].outcome, "Permission error: denied");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -29,6 +29,10 @@ pub enum McpError {
     #[error("Permission error: {0}")]
     Permission(String),
 
+    /// Tool call refused by the policy
+    #[error("Denied by policy: {0}")]
+    PolicyDenied(String),
+
     /// Invalid confirmation token
     #[error("Invalid confirmation token: {0}")]
     InvalidToken(String),
@@ -65,6 +69,9 @@ impl McpError {
                     buckos_package::Error::ResolutionFailed(msg) => {
                         JsonRpcError::dependency_failed(msg)
                     }
+                    buckos_package::Error::ResolutionStopped { .. } => {
+                        JsonRpcError::dependency_failed(e.to_string())
+                    }
                     buckos_package::Error::BuildFailed { message, .. } => {
                         JsonRpcError::build_failed(message)
                     }
@@ -75,6 +82,10 @@ impl McpError {
                 "Package operation",
                 format!("{}\n\nSuggestions:\n- Restart with: sudo buckos mcp\n- Or use read-only tools for exploration", msg),
             ),
+            McpError::PolicyDenied(msg) => JsonRpcError::insufficient_permissions(
+                "Tool call",
+                format!("{}\n\nSee the MCP policy file, /etc/buckos/mcp-policy.toml", msg),
+            ),
             McpError::InvalidToken(reason) => JsonRpcError::invalid_token(reason),
             McpError::Io(e) => JsonRpcError::internal_error(e.to_string()),
             McpError::Json(e) => JsonRpcError::invalid_params(e.to_string()),
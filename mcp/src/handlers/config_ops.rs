@@ -0,0 +1,367 @@
+//! Configuration inspection and mutation handlers
+//!
+//! Reading is always allowed. Changes are two-phase: `config_propose`
+//! applies them to a copy of the configuration, validates the result and
+//! returns the patch that writing it would make, together with a
+//! confirmation token; `config_apply` writes it, as root only. Files are
+//! rendered with buckos-config's own serializers, so an edit that would
+//! not load again is rejected at the proposal.
+
+use crate::context::McpServerContext;
+use crate::error::{McpError, Result};
+use crate::server::confirmation::PendingOperation;
+use buckos_config::toml_config::{set_make_var, TomlConfig, CONFIG_TOML};
+use buckos_config::{ConfigError, ConfigLoader, PackageAtom, PortageConfig, UseConfig};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// A single configuration edit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConfigChange {
+    /// Add a package.use line
+    PackageUse { atom: String, flags: String },
+    /// Add a package.accept_keywords line
+    Keywords { atom: String, keywords: String },
+    /// Add a package.license line
+    License { atom: String, licenses: String },
+    /// Mask a package
+    Mask {
+        atom: String,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    /// Unmask a package
+    Unmask {
+        atom: String,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    /// Set a make.conf variable
+    Make { variable: String, value: String },
+}
+
+/// The change to one file, as a unified diff
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FilePatch {
+    /// Path relative to the configuration root
+    pub path: PathBuf,
+    pub diff: String,
+}
+
+/// Apply edits to a configuration
+pub fn apply_changes(config: &mut PortageConfig, changes: &[ConfigChange]) -> Result<()> {
+    let atom = |atom: &str| -> Result<PackageAtom> { atom.parse().map_err(invalid) };
+    for change in changes {
+        match change {
+            ConfigChange::PackageUse { atom: a, flags } => config
+                .package_use
+                .add_package_use(atom(a)?, UseConfig::parse_use_string(flags)),
+            ConfigChange::Keywords { atom: a, keywords } => {
+                config.package_keywords.add_package_keywords(
+                    atom(a)?,
+                    buckos_config::KeywordConfig::parse_keywords_string(keywords),
+                )
+            }
+            ConfigChange::License { atom: a, licenses } => {
+                config.package_license.add_package_license(
+                    atom(a)?,
+                    licenses.split_whitespace().map(str::to_string).collect(),
+                )
+            }
+            ConfigChange::Mask { atom: a, reason } => {
+                config.package_mask.add_mask(atom(a)?, reason.clone())
+            }
+            ConfigChange::Unmask { atom: a, reason } => {
+                config.package_mask.add_unmask(atom(a)?, reason.clone())
+            }
+            ConfigChange::Make { variable, value } => {
+                set_make_var(&mut config.make_conf, variable, value).map_err(invalid)?
+            }
+        }
+    }
+    buckos_config::loader::validate_config(config).map_err(invalid)
+}
+
+/// Files the configuration under `root` is written as, relative to it
+fn render(config: &PortageConfig, root: &Path) -> Result<Vec<(PathBuf, String)>> {
+    if root.join(CONFIG_TOML).exists() {
+        let toml = TomlConfig::from_portage(config).to_toml();
+        Ok(vec![(PathBuf::from(CONFIG_TOML), toml.map_err(internal)?)])
+    } else {
+        config.tree_files().map_err(internal)
+    }
+}
+
+/// Files the edits change, with their new content and the patch to them
+fn plan(root: &Path, changes: &[ConfigChange]) -> Result<Vec<(FilePatch, String)>> {
+    // Remote policy is merged at load time and never written locally
+    let current = ConfigLoader::new(root)
+        .remote(false)
+        .validate(false)
+        .load()
+        .map_err(internal)?;
+    let mut proposed = current.clone();
+    apply_changes(&mut proposed, changes)?;
+
+    let before = render(&current, root)?;
+    let mut patches = Vec::new();
+    for (path, content) in render(&proposed, root)? {
+        if before.iter().any(|(p, c)| *p == path && *c == content) {
+            continue;
+        }
+        let full = root.join(&path);
+        if full.is_dir() {
+            return Err(McpError::InvalidParams(format!(
+                "{} is a directory, which this tool does not edit",
+                full.display()
+            )));
+        }
+        let old = std::fs::read_to_string(&full).unwrap_or_default();
+        let diff = unified_diff(&path, &old, &content);
+        patches.push((FilePatch { path, diff }, content));
+    }
+    Ok(patches)
+}
+
+/// Handle config_explain tool
+pub async fn handle_config_explain(_ctx: &McpServerContext, args: Value) -> Result<Value> {
+    let query = args["query"]
+        .as_str()
+        .ok_or_else(|| McpError::InvalidParams("Missing 'query' parameter".to_string()))?;
+
+    info!(query = query, "Explaining configuration");
+
+    let root = buckos_config::loader::get_config_root();
+    let explanation = buckos_config::explain(&root, query).map_err(invalid)?;
+    let contributions: Vec<Value> = explanation
+        .contributions
+        .iter()
+        .map(|c| {
+            json!({
+                "layer": c.layer.to_string(),
+                "location": c.location(),
+                "text": c.text,
+            })
+        })
+        .collect();
+
+    Ok(json!({
+        "query": explanation.query,
+        "value": explanation.value,
+        "contributions": contributions,
+    }))
+}
+
+/// Handle config_propose tool
+pub async fn handle_config_propose(ctx: &McpServerContext, args: Value) -> Result<Value> {
+    let changes: Vec<ConfigChange> = serde_json::from_value(args["changes"].clone())?;
+    if changes.is_empty() {
+        return Err(McpError::InvalidParams(
+            "At least one change required".to_string(),
+        ));
+    }
+
+    info!(changes = changes.len(), "Proposing configuration changes");
+
+    let root = buckos_config::loader::get_config_root();
+    let patches: Vec<FilePatch> = plan(&root, &changes)?
+        .into_iter()
+        .map(|(patch, _)| patch)
+        .collect();
+    if patches.is_empty() {
+        return Ok(json!({
+            "patches": [],
+            "message": "The configuration already has these settings"
+        }));
+    }
+
+    let token = ctx
+        .create_confirmation(PendingOperation::ConfigChange {
+            root,
+            changes,
+            patches: patches.clone(),
+        })
+        .await?;
+
+    Ok(json!({
+        "patches": patches,
+        "confirmation_token": token.token,
+        "expires_at": token.expires_at.to_rfc3339(),
+        "message": format!(
+            "This will change {} file(s). Pass the confirmation_token to config_apply to write them.",
+            patches.len()
+        )
+    }))
+}
+
+/// Handle config_apply tool
+pub async fn handle_config_apply(ctx: &McpServerContext, args: Value) -> Result<Value> {
+    let token = args["confirmation_token"].as_str().ok_or_else(|| {
+        McpError::InvalidParams("Missing 'confirmation_token' parameter".to_string())
+    })?;
+
+    ctx.check_permission("config_apply")?;
+
+    let PendingOperation::ConfigChange {
+        root,
+        changes,
+        patches,
+    } = ctx.consume_confirmation(token).await?
+    else {
+        return Err(McpError::InvalidToken(
+            "Token is not for a configuration change".to_string(),
+        ));
+    };
+
+    info!(root = %root.display(), "Applying configuration changes");
+
+    // Write only what was previewed
+    let planned = plan(&root, &changes)?;
+    if planned.iter().map(|(patch, _)| patch).ne(patches.iter()) {
+        return Err(McpError::InvalidParams(
+            "The configuration changed since the proposal; propose the changes again".to_string(),
+        ));
+    }
+    let mut written = Vec::new();
+    for (patch, content) in planned {
+        let path = root.join(&patch.path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, content)?;
+        written.push(path);
+    }
+
+    Ok(json!({
+        "success": true,
+        "written": written,
+        "message": format!("Wrote {} file(s)", written.len())
+    }))
+}
+
+/// A unified diff of two versions of a file, three lines of context
+pub fn unified_diff(path: &Path, old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Longest common subsequence table, from the end
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    // Edit script of (prefix, line, old index, new index)
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push((' ', old[i], i, j));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] > lcs[i + 1][j]) {
+            ops.push(('+', new[j], i, j));
+            j += 1;
+        } else {
+            ops.push(('-', old[i], i, j));
+            i += 1;
+        }
+    }
+
+    const CONTEXT: usize = 3;
+    let mut out = format!("--- a/{0}\n+++ b/{0}\n", path.display());
+    let changed: Vec<usize> = (0..ops.len()).filter(|&k| ops[k].0 != ' ').collect();
+    let mut k = 0;
+    while k < changed.len() {
+        let start = changed[k].saturating_sub(CONTEXT);
+        let mut end = changed[k];
+        while k < changed.len() && changed[k] <= end + 2 * CONTEXT {
+            end = changed[k];
+            k += 1;
+        }
+        let end = (end + CONTEXT + 1).min(ops.len());
+        let hunk = &ops[start..end];
+        let old_len = hunk.iter().filter(|op| op.0 != '+').count();
+        let new_len = hunk.iter().filter(|op| op.0 != '-').count();
+        let (_, _, old_start, new_start) = hunk[0];
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start + usize::from(old_len > 0),
+            old_len,
+            new_start + usize::from(new_len > 0),
+            new_len
+        ));
+        for (prefix, line, _, _) in hunk {
+            out.push_str(&format!("{}{}\n", prefix, line));
+        }
+    }
+    out
+}
+
+fn invalid(e: ConfigError) -> McpError {
+    McpError::InvalidParams(e.to_string())
+}
+
+fn internal(e: ConfigError) -> McpError {
+    McpError::Internal(format!("Configuration error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_renders_changed_files() {
+        let root = std::env::temp_dir().join(format!("buckos-mcp-config-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("package.use"), "app-editors/vim python\n").unwrap();
+
+        let changes = vec![
+            ConfigChange::PackageUse {
+                atom: "dev-lang/rust".to_string(),
+                flags: "-doc".to_string(),
+            },
+            ConfigChange::Mask {
+                atom: "dev-lang/python:2.7".to_string(),
+                reason: None,
+            },
+        ];
+        let planned = plan(&root, &changes).unwrap();
+        let paths: Vec<&Path> = planned.iter().map(|(p, _)| p.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            vec![Path::new("package.use"), Path::new("package.mask")]
+        );
+        assert!(planned[0].0.diff.contains("+dev-lang/rust -doc"));
+        assert!(planned[0].0.diff.contains(" app-editors/vim python"));
+
+        let bad = vec![ConfigChange::Mask {
+            atom: "not an atom".to_string(),
+            reason: None,
+        }];
+        assert!(matches!(plan(&root, &bad), Err(McpError::InvalidParams(_))));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nb\nc\nd\nE\nf\ng\nh\ni\nj\nk\n";
+        let diff = unified_diff(Path::new("make.conf"), old, new);
+        assert_eq!(
+            diff,
+            "--- a/make.conf\n+++ b/make.conf\n@@ -2,9 +2,10 @@\n b\n c\n d\n-e\n+E\n \
+             f\n g\n h\n i\n j\n+k\n"
+        );
+        assert!(unified_diff(Path::new("x"), "", "").ends_with("+++ b/x\n"));
+    }
+}
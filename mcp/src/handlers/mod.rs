@@ -2,6 +2,8 @@
 //!
 //! Implementations of MCP tool handlers that interact with the PackageManager.
 
+pub mod config_ops;
 pub mod package_create;
 pub mod package_ops;
+pub mod query_ops;
 pub mod spec_ops;
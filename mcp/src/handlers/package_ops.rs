@@ -209,6 +209,10 @@ pub async fn handle_install(ctx: &McpServerContext, args: Value) -> Result<Value
                     "message": format!("Successfully installed {} package(s)", packages.len())
                 }))
             }
+            other => Err(McpError::InvalidToken(format!(
+                "Token is for another operation: {}",
+                other.description()
+            ))),
         }
     } else {
         Err(McpError::InvalidParams(
@@ -274,6 +278,19 @@ pub async fn handle_config_show(_ctx: &McpServerContext, args: Value) -> Result<
                 "current": config.profile.current,
             }))
         }
+        Some("masks") => {
+            let entries = |entries: &[buckos_config::MaskEntry]| {
+                entries
+                    .iter()
+                    .map(|e| json!({ "atom": e.atom.to_string(), "reason": e.reason }))
+                    .collect::<Vec<_>>()
+            };
+            Ok(json!({
+                "section": "masks",
+                "masked": entries(&config.package_mask.masked),
+                "unmasked": entries(&config.package_mask.unmasked),
+            }))
+        }
         None | Some("all") => {
             // Return overview of all configuration sections
             Ok(json!({
@@ -289,12 +306,12 @@ pub async fn handle_config_show(_ctx: &McpServerContext, args: Value) -> Result<
                     "names": config.repos.repos.keys().collect::<Vec<_>>(),
                 },
                 "profile": config.profile.current,
-                "available_sections": ["make_conf", "use", "features", "repos", "profile"],
+                "available_sections": ["make_conf", "use", "features", "repos", "profile", "masks"],
             }))
         }
         Some(other) => {
             Err(McpError::InvalidParams(format!(
-                "Unknown configuration section: '{}'. Available: make_conf, use, features, repos, profile",
+                "Unknown configuration section: '{}'. Available: make_conf, use, features, repos, profile, masks",
                 other
             )))
         }
@@ -0,0 +1,63 @@
+//! Read-only query handlers
+//!
+//! These tools return the same structures `buckos` prints with its JSON
+//! output options (`info --json`, `rdeps --format json`, `world clean
+//! --json` and `audit --json`), so a client sees the same data either way.
+
+use crate::context::McpServerContext;
+use crate::error::{McpError, Result};
+use buckos_package::ReverseDependencies;
+use serde_json::Value;
+use tracing::info;
+
+fn package_arg(args: &Value) -> Result<&str> {
+    args["package"]
+        .as_str()
+        .ok_or_else(|| McpError::InvalidParams("Missing 'package' parameter".to_string()))
+}
+
+/// Handle get_package_details tool
+pub async fn handle_package_details(ctx: &McpServerContext, args: Value) -> Result<Value> {
+    let package = package_arg(&args)?;
+
+    info!(package = package, "Getting package details");
+
+    let details = ctx.pm.package_details(package).await?.ok_or_else(|| {
+        McpError::PackageManager(buckos_package::Error::PackageNotFound(package.to_string()))
+    })?;
+    Ok(serde_json::to_value(&details)?)
+}
+
+/// Handle get_reverse_dependencies tool
+pub async fn handle_reverse_dependencies(ctx: &McpServerContext, args: Value) -> Result<Value> {
+    let package = package_arg(&args)?;
+
+    info!(package = package, "Getting reverse dependencies");
+
+    let reverse_dependencies = ctx.pm.get_reverse_dependencies(package).await?;
+    Ok(serde_json::to_value(&ReverseDependencies {
+        package: package.to_string(),
+        reverse_dependencies,
+    })?)
+}
+
+/// Handle get_world_diff tool
+pub async fn handle_world_diff(ctx: &McpServerContext, _args: Value) -> Result<Value> {
+    info!("Analyzing world set");
+
+    let issues = ctx.pm.analyze_world().await?;
+    Ok(serde_json::to_value(&issues)?)
+}
+
+/// Handle get_audit_findings tool
+pub async fn handle_audit_findings(ctx: &McpServerContext, args: Value) -> Result<Value> {
+    let severity = args["severity"].as_str();
+
+    info!(severity = ?severity, "Auditing installed packages");
+
+    let mut findings = ctx.pm.audit().await?;
+    if let Some(severity) = severity {
+        findings.retain(|v| v.severity == severity);
+    }
+    Ok(serde_json::to_value(&findings)?)
+}
@@ -2,12 +2,15 @@
 //!
 //! Model Context Protocol (MCP) server implementation for the Buckos package manager.
 //! Enables AI assistants to interact with package management operations through a
-//! standardized JSON-RPC 2.0 interface over stdio.
+//! standardized JSON-RPC 2.0 interface over stdio or, for remote clients,
+//! HTTP with Server-Sent Events.
 //!
 //! ## Architecture
 //!
-//! - **Protocol Layer**: JSON-RPC 2.0 types and stdio transport
-//! - **Permission Layer**: ExecutionContext for detecting root vs non-root
+//! - **Protocol Layer**: JSON-RPC 2.0 types, stdio and HTTP transports
+//! - **Permission Layer**: ExecutionContext for detecting root vs non-root,
+//!   Client for telling callers apart, and a Policy deciding which of them
+//!   may call which tool, with every call written to an audit log
 //! - **Server Layer**: Request routing and tool registry
 //! - **Handler Layer**: Package manager operation handlers
 //!
@@ -32,10 +35,12 @@
 //! }
 //! ```
 
+pub mod audit;
 pub mod context;
 pub mod error;
 pub mod handlers;
 pub mod permissions;
+pub mod policy;
 pub mod protocol;
 pub mod server;
 pub mod spec_registry;
@@ -43,6 +48,9 @@ pub mod spec_registry;
 // Re-export main types
 pub use context::McpServerContext;
 pub use error::{McpError, Result};
-pub use permissions::ExecutionContext;
+pub use permissions::{Client, ExecutionContext};
+pub use policy::{Decision, Policy};
+#[cfg(feature = "http")]
+pub use protocol::{HttpConfig, TlsConfig};
 pub use protocol::{JsonRpcRequest, JsonRpcResponse, StdioTransport};
 pub use server::{McpServer, ServerConfig};
@@ -7,6 +7,28 @@ use std::env;
 use std::path::PathBuf;
 use tracing::info;
 
+/// Tools that change the system
+pub const MUTATING_TOOLS: &[&str] = &["package_install", "config_apply"];
+
+/// Where a request comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Client {
+    /// A local process speaking over stdio, with the server's privileges
+    Local,
+    /// A client over HTTP, named by the token it presented
+    Remote { name: String },
+}
+
+impl Client {
+    /// Name the policy and audit log know the client by
+    pub fn name(&self) -> &str {
+        match self {
+            Client::Local => "local",
+            Client::Remote { name } => name,
+        }
+    }
+}
+
 /// Execution context for the MCP server
 ///
 /// Detects whether the server is running as root and determines
@@ -77,8 +99,32 @@ impl ExecutionContext {
     pub fn tool_available(&self, tool: &str) -> (bool, Option<String>) {
         match tool {
             // Read-only tools: always available
-            "package_search" | "package_info" | "package_list" | "package_deps" | "config_show" => {
-                (true, None)
+            "package_search"
+            | "package_info"
+            | "package_list"
+            | "package_deps"
+            | "config_show"
+            | "config_explain"
+            | "config_propose"
+            | "get_package_details"
+            | "get_reverse_dependencies"
+            | "get_world_diff"
+            | "get_audit_findings" => (true, None),
+
+            // Configuration writes: root only, user mode does not reach /etc
+            "config_apply" => {
+                if self.is_root {
+                    (true, None)
+                } else {
+                    (
+                        false,
+                        Some(format!(
+                            "Writing configuration requires root privileges. Current user: UID {}\n\
+                            Restart with: sudo buckos mcp",
+                            self.effective_uid
+                        )),
+                    )
+                }
             }
 
             // Mutating tools: require root or user mode
@@ -159,6 +205,15 @@ mod tests {
 
         let (available, _) = ctx.tool_available("package_info");
         assert!(available);
+
+        for tool in [
+            "get_package_details",
+            "get_reverse_dependencies",
+            "get_world_diff",
+            "get_audit_findings",
+        ] {
+            assert!(ctx.tool_available(tool).0, "{} should be read-only", tool);
+        }
     }
 
     #[test]
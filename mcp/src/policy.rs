@@ -0,0 +1,242 @@
+//! Tool authorization policy
+//!
+//! `/etc/buckos/mcp-policy.toml` says which client may call which tool:
+//!
+//! ```toml
+//! audit-log = "/var/log/buckos/mcp-audit.log"
+//!
+//! # Every client, unless its own section says otherwise
+//! [default]
+//! rate-limit = 60
+//! tools = { "*" = "allow", package_install = "ask", config_apply = "ask" }
+//!
+//! # The stdio client
+//! [clients.local]
+//! tools = { package_install = "allow" }
+//!
+//! # HTTP clients presenting the server's --token-file token
+//! [clients.remote]
+//! rate-limit = 30
+//! tools = { package_install = "deny", config_apply = "deny" }
+//!
+//! # HTTP clients presenting this token
+//! [clients.ci]
+//! token-file = "/etc/buckos/mcp-tokens/ci"
+//! tools = { "*" = "deny", package_search = "allow", package_info = "allow" }
+//! ```
+//!
+//! A tool is looked up in the client's section, then its `*`, then the
+//! same in `[default]`; a tool named nowhere is allowed. `ask` tools
+//! return an approval token first and run when called again with it.
+//! Rate limits count calls per client per minute.
+//!
+//! The policy decides who may call a tool. The execution context still
+//! decides what the server can do, so a non-root server refuses to
+//! install whatever the policy says.
+
+use crate::error::{McpError, Result};
+use crate::permissions::{Client, MUTATING_TOOLS};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Default location of the policy file
+pub const POLICY_PATH: &str = "/etc/buckos/mcp-policy.toml";
+
+/// What happens when a client calls a tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    Allow,
+    Deny,
+    /// Run only when called again with an approval token
+    Ask,
+}
+
+/// Rules for one client
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ClientPolicy {
+    /// Tool name, or `*` for any tool, to decision
+    pub tools: HashMap<String, Decision>,
+
+    /// Calls allowed per minute
+    pub rate_limit: Option<u32>,
+
+    /// File holding the bearer token that identifies this HTTP client
+    pub token_file: Option<PathBuf>,
+}
+
+/// Tool authorization policy
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Policy {
+    /// File every tool call is appended to
+    pub audit_log: Option<PathBuf>,
+
+    /// Rules for clients without their own section
+    pub default: ClientPolicy,
+
+    /// Rules by client name; `local` is the stdio client and `remote` the
+    /// HTTP client using the server's own token
+    pub clients: HashMap<String, ClientPolicy>,
+}
+
+impl Policy {
+    /// Load a policy file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        toml::from_str(&content)
+            .map_err(|e| McpError::Internal(format!("Invalid policy {}: {}", path.display(), e)))
+    }
+
+    /// The policy used without a policy file: local clients may call
+    /// anything, remote clients only read unless `remote_writes` is set
+    pub fn builtin(remote_writes: bool) -> Self {
+        let mut policy = Self::default();
+        if !remote_writes {
+            let tools = MUTATING_TOOLS
+                .iter()
+                .map(|tool| (tool.to_string(), Decision::Deny))
+                .collect();
+            policy.clients.insert(
+                "remote".to_string(),
+                ClientPolicy {
+                    tools,
+                    ..Default::default()
+                },
+            );
+        }
+        policy
+    }
+
+    /// Decide a call of `tool` by `client`
+    pub fn decide(&self, client: &Client, tool: &str) -> Decision {
+        let sections = [self.clients.get(client.name()), Some(&self.default)];
+        for section in sections.into_iter().flatten() {
+            if let Some(decision) = section.tools.get(tool).or_else(|| section.tools.get("*")) {
+                return *decision;
+            }
+        }
+        Decision::Allow
+    }
+
+    /// Calls per minute allowed to a client
+    pub fn rate_limit(&self, client: &Client) -> Option<u32> {
+        self.clients
+            .get(client.name())
+            .and_then(|c| c.rate_limit)
+            .or(self.default.rate_limit)
+    }
+
+    /// Bearer tokens of the named HTTP clients, with their names
+    pub fn client_tokens(&self) -> Result<Vec<(String, String)>> {
+        let mut tokens = Vec::new();
+        for (name, client) in &self.clients {
+            if let Some(path) = &client.token_file {
+                let token = std::fs::read_to_string(path)?.trim().to_string();
+                if token.is_empty() {
+                    return Err(McpError::Internal(format!(
+                        "Token file {} for client '{}' is empty",
+                        path.display(),
+                        name
+                    )));
+                }
+                tokens.push((token, name.clone()));
+            }
+        }
+        Ok(tokens)
+    }
+}
+
+/// Calls per client over the last minute
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    calls: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl RateLimiter {
+    /// Record a call, refusing it if the client already made `limit`
+    /// calls in the last minute
+    pub fn check(&self, client: &Client, limit: u32) -> bool {
+        let now = Instant::now();
+        let mut calls = self.calls.lock().unwrap();
+        let window = calls.entry(client.name().to_string()).or_default();
+        while window
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= Duration::from_secs(60))
+        {
+            window.pop_front();
+        }
+        if window.len() >= limit as usize {
+            return false;
+        }
+        window.push_back(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decide() {
+        let policy: Policy = toml::from_str(
+            r#"
+            [default]
+            rate-limit = 60
+            tools = { "*" = "allow", package_install = "ask" }
+
+            [clients.local]
+            tools = { package_install = "allow" }
+
+            [clients.ci]
+            rate-limit = 2
+            tools = { "*" = "deny", package_search = "allow" }
+            "#,
+        )
+        .unwrap();
+        let local = Client::Local;
+        let remote = Client::Remote {
+            name: "remote".to_string(),
+        };
+        let ci = Client::Remote {
+            name: "ci".to_string(),
+        };
+
+        assert_eq!(policy.decide(&local, "package_install"), Decision::Allow);
+        assert_eq!(policy.decide(&remote, "package_install"), Decision::Ask);
+        assert_eq!(policy.decide(&remote, "config_show"), Decision::Allow);
+        assert_eq!(policy.decide(&ci, "package_search"), Decision::Allow);
+        assert_eq!(policy.decide(&ci, "package_install"), Decision::Deny);
+        assert_eq!(policy.rate_limit(&remote), Some(60));
+        assert_eq!(policy.rate_limit(&ci), Some(2));
+
+        let limiter = RateLimiter::default();
+        assert!(limiter.check(&ci, 2));
+        assert!(limiter.check(&ci, 2));
+        assert!(!limiter.check(&ci, 2));
+        assert!(limiter.check(&local, 2));
+    }
+
+    #[test]
+    fn test_builtin() {
+        let remote = Client::Remote {
+            name: "remote".to_string(),
+        };
+        let policy = Policy::builtin(false);
+        assert_eq!(
+            policy.decide(&Client::Local, "config_apply"),
+            Decision::Allow
+        );
+        assert_eq!(policy.decide(&remote, "config_apply"), Decision::Deny);
+        assert_eq!(policy.decide(&remote, "package_search"), Decision::Allow);
+        assert_eq!(
+            Policy::builtin(true).decide(&remote, "package_install"),
+            Decision::Allow
+        );
+    }
+}
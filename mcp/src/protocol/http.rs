@@ -0,0 +1,437 @@
+//! HTTP transport for JSON-RPC messages
+//!
+//! Implements the MCP HTTP with Server-Sent Events transport. A client
+//! opens an event stream with `GET /sse`; the first event, `endpoint`,
+//! names the URL to `POST` requests to, and each response comes back on
+//! the stream as a `message` event. Every request must carry
+//! `Authorization: Bearer <token>`, which also names the client to the
+//! policy, and the listener can be wrapped in TLS.
+
+use super::{JsonRpcRequest, JsonRpcResponse};
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use hyper_util::rt::TokioIo;
+use hyper_util::service::TowerToHyperService;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::io;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_native_tls::native_tls;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::StreamExt;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// HTTP transport configuration
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    /// Address to listen on
+    pub listen: SocketAddr,
+
+    /// Bearer token of the `remote` client
+    pub token: String,
+
+    /// Bearer tokens of other clients, with their names
+    pub clients: Vec<(String, String)>,
+
+    /// Serve over TLS with this certificate and key
+    pub tls: Option<TlsConfig>,
+}
+
+/// PEM certificate chain and PKCS#8 private key for TLS
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// A request from an HTTP client, answered on its session's event stream
+#[derive(Debug)]
+pub struct HttpRequest {
+    /// Session the response goes to
+    pub session: String,
+    /// Name of the client, from its token
+    pub client: String,
+    pub request: JsonRpcRequest,
+}
+
+/// Open sessions with their client's name and event stream
+type Sessions = Arc<Mutex<HashMap<String, (String, mpsc::Sender<JsonRpcResponse>)>>>;
+
+#[derive(Clone)]
+struct AppState {
+    /// Token to client name
+    tokens: Arc<Vec<(String, String)>>,
+    sessions: Sessions,
+    requests: mpsc::Sender<HttpRequest>,
+}
+
+/// HTTP transport for JSON-RPC messages
+///
+/// Connections are served on background tasks; requests from all of them
+/// are read here, in arrival order.
+pub struct HttpTransport {
+    requests: mpsc::Receiver<HttpRequest>,
+    sessions: Sessions,
+    local_addr: SocketAddr,
+}
+
+impl HttpTransport {
+    /// Start listening
+    pub async fn bind(config: &HttpConfig) -> io::Result<Self> {
+        if config.token.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "An authentication token is required",
+            ));
+        }
+
+        let acceptor = match &config.tls {
+            Some(tls) => Some(tls_acceptor(tls).await?),
+            None => {
+                if !config.listen.ip().is_loopback() {
+                    warn!(listen = %config.listen, "MCP endpoint is not loopback-only and has no TLS");
+                }
+                None
+            }
+        };
+
+        let listener = TcpListener::bind(config.listen).await?;
+        let local_addr = listener.local_addr()?;
+        let sessions: Sessions = Arc::default();
+        let (tx, requests) = mpsc::channel(64);
+        let router = Router::new()
+            .route("/sse", get(open_stream))
+            .route("/message", post(post_message))
+            .with_state(AppState {
+                tokens: Arc::new(
+                    std::iter::once((config.token.clone(), "remote".to_string()))
+                        .chain(config.clients.iter().cloned())
+                        .collect(),
+                ),
+                sessions: sessions.clone(),
+                requests: tx,
+            });
+
+        info!(
+            listen = %local_addr,
+            tls = acceptor.is_some(),
+            "MCP server listening on HTTP"
+        );
+        match acceptor {
+            None => {
+                tokio::spawn(async move {
+                    if let Err(e) = axum::serve(listener, router).await {
+                        warn!(error = %e, "MCP HTTP server stopped");
+                    }
+                });
+            }
+            Some(acceptor) => {
+                tokio::spawn(serve_tls(listener, acceptor, router));
+            }
+        }
+
+        Ok(Self {
+            requests,
+            sessions,
+            local_addr,
+        })
+    }
+
+    /// Address the transport listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Read the next request from any client
+    ///
+    /// Returns None once the server has stopped.
+    pub async fn read_request(&mut self) -> Option<HttpRequest> {
+        self.requests.recv().await
+    }
+
+    /// Send a response to a session's event stream
+    ///
+    /// A session that has disconnected is skipped.
+    pub async fn write_response(&self, session: &str, response: &JsonRpcResponse) {
+        let sender = self
+            .sessions
+            .lock()
+            .unwrap()
+            .get(session)
+            .map(|(_, sender)| sender.clone());
+        match sender {
+            Some(sender) => {
+                debug!(session = session, response = ?response, "Sending JSON-RPC response");
+                let _ = sender.send(response.clone()).await;
+            }
+            None => debug!(session = session, "Dropping response for closed session"),
+        }
+    }
+}
+
+async fn tls_acceptor(tls: &TlsConfig) -> io::Result<tokio_native_tls::TlsAcceptor> {
+    let cert = tokio::fs::read(&tls.cert).await?;
+    let key = tokio::fs::read(&tls.key).await?;
+    let identity = native_tls::Identity::from_pkcs8(&cert, &key).map_err(io::Error::other)?;
+    let acceptor = native_tls::TlsAcceptor::new(identity).map_err(io::Error::other)?;
+    Ok(acceptor.into())
+}
+
+async fn serve_tls(listener: TcpListener, acceptor: tokio_native_tls::TlsAcceptor, router: Router) {
+    let acceptor = Arc::new(acceptor);
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!(error = %e, "Failed to accept connection");
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let service = TowerToHyperService::new(router.clone());
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    debug!(peer = %peer, error = %e, "TLS handshake failed");
+                    return;
+                }
+            };
+            if let Err(e) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                debug!(peer = %peer, error = %e, "Connection closed with error");
+            }
+        });
+    }
+}
+
+/// Name of the client whose bearer token the request carries
+fn authorized(headers: &HeaderMap, tokens: &[(String, String)]) -> Option<String> {
+    let presented = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))?;
+    // Compare in constant time
+    let equal = |token: &str| {
+        presented.len() == token.len()
+            && presented
+                .bytes()
+                .zip(token.bytes())
+                .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    };
+    tokens
+        .iter()
+        .find(|(token, _)| equal(token))
+        .map(|(_, name)| name.clone())
+}
+
+/// Removes a session when its event stream is dropped
+struct SessionGuard {
+    id: String,
+    sessions: Sessions,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        self.sessions.lock().unwrap().remove(&self.id);
+        debug!(session = %self.id, "Session closed");
+    }
+}
+
+async fn open_stream(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let Some(client) = authorized(&headers, &state.tokens) else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let id = Uuid::new_v4().to_string();
+    let (tx, rx) = mpsc::channel(32);
+    info!(session = %id, client = %client, "Client connected");
+    state
+        .sessions
+        .lock()
+        .unwrap()
+        .insert(id.clone(), (client, tx));
+
+    let endpoint = Event::default()
+        .event("endpoint")
+        .data(format!("/message?sessionId={}", id));
+    let guard = SessionGuard {
+        id,
+        sessions: state.sessions.clone(),
+    };
+    let messages = ReceiverStream::new(rx).map(move |response| {
+        let _ = &guard;
+        let data = serde_json::to_string(&response).unwrap_or_default();
+        Event::default().event("message").data(data)
+    });
+    let stream = tokio_stream::once(endpoint)
+        .chain(messages)
+        .map(Ok::<_, Infallible>);
+
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+#[derive(Deserialize)]
+struct MessageQuery {
+    #[serde(rename = "sessionId")]
+    session_id: String,
+}
+
+async fn post_message(
+    State(state): State<AppState>,
+    Query(query): Query<MessageQuery>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    let Some(client) = authorized(&headers, &state.tokens) else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    // A session only takes requests from the client that opened it
+    let owner = state
+        .sessions
+        .lock()
+        .unwrap()
+        .get(&query.session_id)
+        .map(|(owner, _)| owner.clone());
+    if owner.as_ref() != Some(&client) {
+        return (StatusCode::NOT_FOUND, "Unknown session").into_response();
+    }
+
+    let request: JsonRpcRequest = match serde_json::from_str(&body) {
+        Ok(request) => request,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid JSON: {}", e)).into_response(),
+    };
+    debug!(session = %query.session_id, request = ?request, "Received JSON-RPC request");
+
+    let request = HttpRequest {
+        session: query.session_id,
+        client,
+        request,
+    };
+    if state.requests.send(request).await.is_err() {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    }
+    StatusCode::ACCEPTED.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    #[test]
+    fn test_authorized() {
+        let tokens = |token: &str| vec![(token.to_string(), "remote".to_string())];
+        let mut headers = HeaderMap::new();
+        assert!(authorized(&headers, &tokens("secret")).is_none());
+
+        headers.insert("authorization", HeaderValue::from_static("Bearer secret"));
+        assert_eq!(
+            authorized(&headers, &tokens("secret")).as_deref(),
+            Some("remote")
+        );
+        assert!(authorized(&headers, &tokens("secrets")).is_none());
+        assert!(authorized(&headers, &tokens("Secret")).is_none());
+
+        let mut both = tokens("other");
+        both.push(("secret".to_string(), "ci".to_string()));
+        assert_eq!(authorized(&headers, &both).as_deref(), Some("ci"));
+
+        headers.insert("authorization", HeaderValue::from_static("Basic secret"));
+        assert!(authorized(&headers, &tokens("secret")).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sse_round_trip() {
+        let config = HttpConfig {
+            listen: "127.0.0.1:0".parse().unwrap(),
+            token: String::new(),
+            clients: Vec::new(),
+            tls: None,
+        };
+        assert!(HttpTransport::bind(&config).await.is_err());
+
+        let config = HttpConfig {
+            token: "secret".to_string(),
+            ..config
+        };
+        let mut transport = HttpTransport::bind(&config).await.unwrap();
+        let addr = transport.local_addr();
+
+        // Open a stream and read the endpoint event
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /sse HTTP/1.1\r\nHost: x\r\nAuthorization: Bearer secret\r\n\r\n")
+            .await
+            .unwrap();
+        let mut buf = vec![0; 4096];
+        let mut text = String::new();
+        while !text
+            .split_once("sessionId=")
+            .is_some_and(|(_, rest)| rest.contains('\n'))
+        {
+            let n = stream.read(&mut buf).await.unwrap();
+            text.push_str(&String::from_utf8_lossy(&buf[..n]));
+        }
+        let endpoint = text
+            .lines()
+            .find_map(|l| l.strip_prefix("data: "))
+            .unwrap()
+            .to_string();
+
+        // Post a request without and with the token
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#;
+        for (auth, status) in [("", "401"), ("Authorization: Bearer secret\r\n", "202")] {
+            let mut post = TcpStream::connect(addr).await.unwrap();
+            let request = format!(
+                "POST {} HTTP/1.1\r\nHost: x\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                endpoint,
+                auth,
+                body.len(),
+                body
+            );
+            post.write_all(request.as_bytes()).await.unwrap();
+            let mut reply = String::new();
+            post.read_to_string(&mut reply).await.unwrap();
+            assert!(
+                reply.starts_with(&format!("HTTP/1.1 {}", status)),
+                "{}",
+                reply
+            );
+        }
+
+        let message = transport.read_request().await.unwrap();
+        assert_eq!(message.request.method, "tools/list");
+        assert_eq!(message.client, "remote");
+
+        // The response arrives on the event stream
+        let response = JsonRpcResponse::success(
+            crate::protocol::RequestId::Number(1),
+            serde_json::json!({"tools": []}),
+        );
+        transport.write_response(&message.session, &response).await;
+        text.clear();
+        while !text.contains("event: message") {
+            let n = stream.read(&mut buf).await.unwrap();
+            text.push_str(&String::from_utf8_lossy(&buf[..n]));
+        }
+        assert!(text.contains(r#""tools":[]"#));
+    }
+}
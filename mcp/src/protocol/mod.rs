@@ -3,8 +3,12 @@
 //! This module provides the core protocol types and transport layer for
 //! Model Context Protocol (MCP) communication using JSON-RPC 2.0.
 
+#[cfg(feature = "http")]
+pub mod http;
 pub mod jsonrpc;
 pub mod transport;
 
+#[cfg(feature = "http")]
+pub use http::{HttpConfig, HttpTransport, TlsConfig};
 pub use jsonrpc::{JsonRpcError, JsonRpcRequest, JsonRpcResponse, RequestId};
 pub use transport::StdioTransport;
@@ -1,7 +1,9 @@
 //! Confirmation token system for two-phase operations
 
+use crate::handlers::config_ops::{ConfigChange, FilePatch};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
 /// Confirmation token for two-phase operations
 ///
@@ -31,6 +33,22 @@ pub enum PendingOperation {
         /// Packages to install
         packages: Vec<String>,
     },
+    /// A tool call the policy requires approval for
+    ToolCall {
+        /// Client that made the call
+        client: String,
+        tool: String,
+        arguments: serde_json::Value,
+    },
+    /// Configuration edits
+    ConfigChange {
+        /// Configuration root
+        root: PathBuf,
+        /// Edits to make
+        changes: Vec<ConfigChange>,
+        /// Patches shown when the edits were proposed
+        patches: Vec<FilePatch>,
+    },
 }
 
 impl PendingOperation {
@@ -40,6 +58,10 @@ impl PendingOperation {
             PendingOperation::Install { packages, .. } => {
                 format!("Install {} package(s)", packages.len())
             }
+            PendingOperation::ToolCall { tool, .. } => format!("Call {}", tool),
+            PendingOperation::ConfigChange { patches, .. } => {
+                format!("Change {} configuration file(s)", patches.len())
+            }
         }
     }
 }
@@ -5,11 +5,14 @@
 pub mod confirmation;
 pub mod tools;
 
+use crate::audit::{AuditLog, AuditRecord};
 use crate::context::McpServerContext;
 use crate::error::{McpError, Result};
-use crate::handlers::{package_create, package_ops, spec_ops};
-use crate::permissions::ExecutionContext;
+use crate::handlers::{config_ops, package_create, package_ops, query_ops, spec_ops};
+use crate::permissions::{Client, ExecutionContext};
+use crate::policy::{Decision, Policy, RateLimiter};
 use crate::protocol::{JsonRpcError, JsonRpcRequest, JsonRpcResponse, RequestId, StdioTransport};
+use crate::server::confirmation::PendingOperation;
 use buckos_package::PackageManager;
 use serde_json::{json, Value};
 use std::sync::Arc;
@@ -67,6 +70,9 @@ impl ServerConfig {
 pub struct McpServer {
     context: Arc<McpServerContext>,
     config: ServerConfig,
+    policy: Policy,
+    rate_limiter: RateLimiter,
+    audit: AuditLog,
 }
 
 impl McpServer {
@@ -82,7 +88,20 @@ impl McpServer {
             "MCP server initialized"
         );
 
-        Self { context, config }
+        Self {
+            context,
+            config,
+            policy: Policy::builtin(false),
+            rate_limiter: RateLimiter::default(),
+            audit: AuditLog::default(),
+        }
+    }
+
+    /// Authorize tool calls with a policy instead of the built-in one
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.audit = AuditLog::new(policy.audit_log.clone());
+        self.policy = policy;
+        self
     }
 
     /// Serve requests over stdio
@@ -110,7 +129,7 @@ impl McpServer {
             };
 
             // Handle request
-            let response = self.handle_request(request).await;
+            let response = self.handle_request(request, Client::Local).await;
 
             // Write response (skip for notifications)
             if response.id.is_some() || response.error.is_some() {
@@ -122,15 +141,39 @@ impl McpServer {
         Ok(())
     }
 
+    /// Serve requests over HTTP with Server-Sent Events
+    ///
+    /// Clients are remote, named by their token: `remote` for the one in
+    /// `config` and the policy's names for the tokens it lists.
+    #[cfg(feature = "http")]
+    pub async fn serve_http(&self, mut config: crate::protocol::HttpConfig) -> Result<()> {
+        config.clients.extend(self.policy.client_tokens()?);
+        let mut transport = crate::protocol::HttpTransport::bind(&config).await?;
+
+        while let Some(message) = transport.read_request().await {
+            let client = Client::Remote {
+                name: message.client,
+            };
+            let response = self.handle_request(message.request, client).await;
+
+            // Skip responses to notifications
+            if response.id.is_some() || response.error.is_some() {
+                transport.write_response(&message.session, &response).await;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Handle a JSON-RPC request
-    async fn handle_request(&self, request: JsonRpcRequest) -> JsonRpcResponse {
+    async fn handle_request(&self, request: JsonRpcRequest, client: Client) -> JsonRpcResponse {
         let id = request.id.clone();
 
         // Handle the method
         let result = match request.method.as_str() {
-            "initialize" => self.handle_initialize(request.params).await,
-            "tools/list" => self.handle_tools_list().await,
-            "tools/call" => self.handle_tool_call(request.params).await,
+            "initialize" => self.handle_initialize(request.params, &client).await,
+            "tools/list" => self.handle_tools_list(&client).await,
+            "tools/call" => self.handle_tool_call(request.params, &client).await,
             "resources/list" => self.handle_resources_list().await,
             "resources/read" => self.handle_resources_read(request.params).await,
             _ => Err(McpError::MethodNotFound(request.method.clone())),
@@ -146,7 +189,7 @@ impl McpServer {
     }
 
     /// Handle initialize request
-    async fn handle_initialize(&self, params: Option<Value>) -> Result<Value> {
+    async fn handle_initialize(&self, params: Option<Value>, client: &Client) -> Result<Value> {
         info!(?params, "Received initialize request");
 
         Ok(json!({
@@ -164,14 +207,28 @@ impl McpServer {
                 "isRoot": self.context.exec_context.is_root,
                 "uid": self.context.exec_context.effective_uid,
                 "userMode": self.context.exec_context.user_mode,
-                "installRoot": self.context.exec_context.install_root
+                "installRoot": self.context.exec_context.install_root,
+                "client": client.name()
             }
         }))
     }
 
     /// Handle tools/list request
-    async fn handle_tools_list(&self) -> Result<Value> {
-        let tools = tools::get_all_tools(&self.context.exec_context);
+    async fn handle_tools_list(&self, client: &Client) -> Result<Value> {
+        let mut tools = tools::get_all_tools(&self.context.exec_context);
+        for tool in &mut tools {
+            match self.policy.decide(client, &tool.name) {
+                Decision::Allow => {}
+                Decision::Deny => {
+                    tool.available = Some(false);
+                    tool.reason = Some("Denied by policy".to_string());
+                }
+                Decision::Ask => tool.description.push_str(
+                    " Requires approval: the first call returns an approval_token \
+                    to call again with.",
+                ),
+            }
+        }
 
         Ok(json!({
             "tools": tools
@@ -179,7 +236,7 @@ impl McpServer {
     }
 
     /// Handle tools/call request
-    async fn handle_tool_call(&self, params: Option<Value>) -> Result<Value> {
+    async fn handle_tool_call(&self, params: Option<Value>, client: &Client) -> Result<Value> {
         let params =
             params.ok_or_else(|| McpError::InvalidParams("Missing parameters".to_string()))?;
 
@@ -189,9 +246,93 @@ impl McpServer {
 
         let arguments = params["arguments"].clone();
 
-        info!(tool = tool_name, "Calling tool");
+        info!(tool = tool_name, client = client.name(), "Calling tool");
+
+        let decision = self.policy.decide(client, tool_name);
+        let mut record = AuditRecord::new(client, tool_name, &arguments, decision);
+        let result = self
+            .authorize_tool_call(tool_name, arguments, client, decision)
+            .await;
+        record.outcome = match &result {
+            Ok(_) => "ok".to_string(),
+            Err(e) => e.to_string(),
+        };
+        self.audit.record(&record);
+        result
+    }
 
-        // Route to appropriate handler
+    /// Apply the policy's decision and rate limit to a tool call
+    async fn authorize_tool_call(
+        &self,
+        tool_name: &str,
+        mut arguments: Value,
+        client: &Client,
+        decision: Decision,
+    ) -> Result<Value> {
+        if let Some(limit) = self.policy.rate_limit(client) {
+            if !self.rate_limiter.check(client, limit) {
+                return Err(McpError::PolicyDenied(format!(
+                    "client '{}' exceeded {} calls per minute",
+                    client.name(),
+                    limit
+                )));
+            }
+        }
+
+        match decision {
+            Decision::Allow => self.call_tool(tool_name, arguments).await,
+            Decision::Deny => Err(McpError::PolicyDenied(format!(
+                "client '{}' may not call '{}'",
+                client.name(),
+                tool_name
+            ))),
+            Decision::Ask => {
+                let approval = arguments
+                    .as_object_mut()
+                    .and_then(|args| args.remove("approval_token"));
+                let Some(token) = approval else {
+                    let token = self
+                        .context
+                        .create_confirmation(PendingOperation::ToolCall {
+                            client: client.name().to_string(),
+                            tool: tool_name.to_string(),
+                            arguments,
+                        })
+                        .await?;
+                    return Ok(json!({
+                        "approval_required": true,
+                        "approval_token": token.token,
+                        "expires_at": token.expires_at.to_rfc3339(),
+                        "message": format!(
+                            "The policy requires approval for '{}'. Call it again with the same \
+                            arguments and this approval_token to run it.",
+                            tool_name
+                        )
+                    }));
+                };
+
+                let token = token.as_str().unwrap_or_default();
+                match self.context.consume_confirmation(token).await? {
+                    PendingOperation::ToolCall {
+                        client: approved_client,
+                        tool,
+                        arguments: approved,
+                    } if approved_client == client.name()
+                        && tool == tool_name
+                        && approved == arguments =>
+                    {
+                        self.call_tool(tool_name, arguments).await
+                    }
+                    _ => Err(McpError::InvalidToken(
+                        "Approval token is for a different call".to_string(),
+                    )),
+                }
+            }
+        }
+    }
+
+    /// Route a tool call to its handler
+    async fn call_tool(&self, tool_name: &str, arguments: Value) -> Result<Value> {
         match tool_name {
             // Package operations
             "package_search" => package_ops::handle_search(&self.context, arguments).await,
@@ -200,6 +341,21 @@ impl McpServer {
             "package_deps" => package_ops::handle_deps(&self.context, arguments).await,
             "package_install" => package_ops::handle_install(&self.context, arguments).await,
             "config_show" => package_ops::handle_config_show(&self.context, arguments).await,
+            // Query operations
+            "get_package_details" => {
+                query_ops::handle_package_details(&self.context, arguments).await
+            }
+            "get_reverse_dependencies" => {
+                query_ops::handle_reverse_dependencies(&self.context, arguments).await
+            }
+            "get_world_diff" => query_ops::handle_world_diff(&self.context, arguments).await,
+            "get_audit_findings" => {
+                query_ops::handle_audit_findings(&self.context, arguments).await
+            }
+            // Configuration operations
+            "config_explain" => config_ops::handle_config_explain(&self.context, arguments).await,
+            "config_propose" => config_ops::handle_config_propose(&self.context, arguments).await,
+            "config_apply" => config_ops::handle_config_apply(&self.context, arguments).await,
             // Spec operations
             "spec_list" => spec_ops::handle_spec_list(&self.context, arguments).await,
             "spec_info" => spec_ops::handle_spec_info(&self.context, arguments).await,
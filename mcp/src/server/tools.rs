@@ -36,6 +36,14 @@ pub fn get_all_tools(exec_context: &ExecutionContext) -> Vec<ToolDefinition> {
         tool_package_deps(exec_context),
         tool_package_install(exec_context),
         tool_config_show(exec_context),
+        tool_config_explain(exec_context),
+        tool_config_propose(exec_context),
+        tool_config_apply(exec_context),
+        // Query tools
+        tool_get_package_details(exec_context),
+        tool_get_reverse_dependencies(exec_context),
+        tool_get_world_diff(exec_context),
+        tool_get_audit_findings(exec_context),
         // Spec validation tools
         tool_spec_list(exec_context),
         tool_spec_info(exec_context),
@@ -201,7 +209,169 @@ fn tool_config_show(exec_context: &ExecutionContext) -> ToolDefinition {
                 "section": {
                     "type": "string",
                     "description": "Configuration section to display. If not specified, shows an overview.",
-                    "enum": ["make_conf", "use", "features", "repos", "profile", "all"]
+                    "enum": ["make_conf", "use", "features", "repos", "profile", "masks", "all"]
+                }
+            }
+        }),
+        available,
+        reason,
+    }
+}
+
+fn tool_config_explain(exec_context: &ExecutionContext) -> ToolDefinition {
+    let (available, reason) = check_availability("config_explain", exec_context);
+
+    ToolDefinition {
+        name: "config_explain".to_string(),
+        description: "Show the effective value of a make.conf variable, a global USE flag or a package's USE flags, keywords and mask status, with every setting that contributed to it.".to_string(),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "Variable (CFLAGS), USE flag (wayland) or package (app-editors/vim)",
+                    "minLength": 1
+                }
+            },
+            "required": ["query"]
+        }),
+        available,
+        reason,
+    }
+}
+
+fn tool_config_propose(exec_context: &ExecutionContext) -> ToolDefinition {
+    let (available, reason) = check_availability("config_propose", exec_context);
+
+    ToolDefinition {
+        name: "config_propose".to_string(),
+        description: "Validate configuration changes and preview them as a patch without writing anything. Returns a confirmation token for config_apply.".to_string(),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "changes": {
+                    "type": "array",
+                    "description": "Changes to make, in order",
+                    "minItems": 1,
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "kind": {
+                                "type": "string",
+                                "enum": ["package_use", "keywords", "license", "mask", "unmask", "make"]
+                            },
+                            "atom": {"type": "string", "description": "Package atom, for all kinds but make"},
+                            "flags": {"type": "string", "description": "USE flags for package_use (e.g., 'python -perl')"},
+                            "keywords": {"type": "string", "description": "Keywords for keywords (e.g., '~amd64')"},
+                            "licenses": {"type": "string", "description": "Licenses for license"},
+                            "reason": {"type": "string", "description": "Optional reason for mask and unmask"},
+                            "variable": {"type": "string", "description": "make.conf variable for make (e.g., 'MAKEOPTS')"},
+                            "value": {"type": "string", "description": "Value for make"}
+                        },
+                        "required": ["kind"]
+                    }
+                }
+            },
+            "required": ["changes"]
+        }),
+        available,
+        reason,
+    }
+}
+
+fn tool_config_apply(exec_context: &ExecutionContext) -> ToolDefinition {
+    let (available, reason) = check_availability("config_apply", exec_context);
+
+    ToolDefinition {
+        name: "config_apply".to_string(),
+        description: "Write configuration changes previewed by config_propose. Requires root and the confirmation token from config_propose.".to_string(),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "confirmation_token": {
+                    "type": "string",
+                    "description": "Token returned by config_propose"
+                }
+            },
+            "required": ["confirmation_token"]
+        }),
+        available,
+        reason,
+    }
+}
+
+fn tool_get_package_details(exec_context: &ExecutionContext) -> ToolDefinition {
+    let (available, reason) = check_availability("get_package_details", exec_context);
+
+    ToolDefinition {
+        name: "get_package_details".to_string(),
+        description: "Get a package with its USE flags and their descriptions, build and runtime dependencies, the installed version and every version available in each repository.".to_string(),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "package": {
+                    "type": "string",
+                    "description": "Package name (e.g., 'bash', 'sys-apps/systemd')"
+                }
+            },
+            "required": ["package"]
+        }),
+        available,
+        reason,
+    }
+}
+
+fn tool_get_reverse_dependencies(exec_context: &ExecutionContext) -> ToolDefinition {
+    let (available, reason) = check_availability("get_reverse_dependencies", exec_context);
+
+    ToolDefinition {
+        name: "get_reverse_dependencies".to_string(),
+        description: "List installed packages that depend on a package.".to_string(),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "package": {
+                    "type": "string",
+                    "description": "Package name (e.g., 'openssl')"
+                }
+            },
+            "required": ["package"]
+        }),
+        available,
+        reason,
+    }
+}
+
+fn tool_get_world_diff(exec_context: &ExecutionContext) -> ToolDefinition {
+    let (available, reason) = check_availability("get_world_diff", exec_context);
+
+    ToolDefinition {
+        name: "get_world_diff".to_string(),
+        description: "List world set entries that are redundant, not installed, not in any repository or not valid atoms.".to_string(),
+        input_schema: json!({
+            "type": "object",
+            "properties": {}
+        }),
+        available,
+        reason,
+    }
+}
+
+fn tool_get_audit_findings(exec_context: &ExecutionContext) -> ToolDefinition {
+    let (available, reason) = check_availability("get_audit_findings", exec_context);
+
+    ToolDefinition {
+        name: "get_audit_findings".to_string(),
+        description:
+            "List known security vulnerabilities affecting installed packages, most severe first."
+                .to_string(),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "severity": {
+                    "type": "string",
+                    "description": "Only report findings of this severity",
+                    "enum": ["critical", "high", "medium", "low"]
                 }
             }
         }),
@@ -0,0 +1,541 @@
+//! Binary package archives
+//!
+//! `buckos binpkg export` packs an installed package into one archive that
+//! installs on another root or machine without a rebuild, like Portage's
+//! quickpkg writing a GPKG. The archive is an uncompressed tar holding,
+//! under `<name>-<version>/`:
+//!
+//! - `buckos-binpkg-1`: marks the format and its version
+//! - `metadata.json`: the package, its USE flags and dependencies, and
+//!   every file with its type, mode and BLAKE3 hash
+//! - `image.tar.<ext>`: the files, relative to the root, compressed
+//! - `Manifest`: size, BLAKE3 and SHA512 hash of the metadata and image
+//!
+//! Paths are relative to the root, so the package installs under any root.
+//! Nothing in an archive is used before its Manifest entry is checked, and
+//! every unpacked file is checked against the hash in the metadata.
+
+use super::BinaryPackage;
+use crate::db::{BuildInfo, DependencyRecord, PackageRecord};
+use crate::{
+    Dependency, Error, FileType, InstalledFile, InstalledPackage, PackageInfo, Result, UseFlag,
+};
+use buckos_core::compress::{self, Compression};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use std::collections::{BTreeMap, HashSet};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+/// File name extension of binary package archives
+pub const ARCHIVE_EXTENSION: &str = "gpkg.tar";
+
+/// Member marking the archive format and its version
+pub const FORMAT_MARKER: &str = "buckos-binpkg-1";
+
+const METADATA: &str = "metadata.json";
+const MANIFEST: &str = "Manifest";
+const IMAGE: &str = "image.tar";
+
+/// Everything an archive records about its package
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveMetadata {
+    /// The package, its USE flags and build settings; the hashes and size
+    /// are those of the image
+    pub package: BinaryPackage,
+    #[serde(default)]
+    pub dependencies: Vec<DependencyRecord>,
+    #[serde(default)]
+    pub build_info: Option<BuildInfo>,
+    /// Files as paths from the root, e.g. `/usr/bin/foo`
+    pub contents: Vec<InstalledFile>,
+}
+
+impl ArchiveMetadata {
+    /// File name of the archive, e.g. `curl-8.5.0.gpkg.tar`
+    pub fn filename(&self) -> String {
+        format!(
+            "{}-{}.{}",
+            self.package.id.name, self.package.version, ARCHIVE_EXTENSION
+        )
+    }
+
+    /// Package metadata for installing from the archive, with the USE
+    /// flags it was built with and its recorded dependencies
+    pub fn package_info(&self) -> PackageInfo {
+        let mut info = crate::transaction::binpkg_package_info(&self.package);
+        info.use_flags = self
+            .package
+            .use_flags
+            .iter()
+            .map(|name| UseFlag {
+                name: name.clone(),
+                description: String::new(),
+                default: true,
+            })
+            .collect();
+        info.dependencies = self
+            .dependencies
+            .iter()
+            .map(|d| Dependency {
+                slot: d.slot.clone(),
+                build_time: d.build_time,
+                run_time: d.run_time,
+                ..Dependency::new(d.package.clone())
+            })
+            .collect();
+        if let Some(build_info) = &self.build_info {
+            info.buck_target = build_info.buck_target.clone();
+        }
+        info
+    }
+
+    /// Runtime dependencies that none of `installed` satisfies, as
+    /// `category/name[:slot]`
+    pub fn missing_dependencies(&self, installed: &[InstalledPackage]) -> Vec<String> {
+        self.dependencies
+            .iter()
+            .filter(|dep| dep.run_time)
+            .filter(|dep| {
+                !installed.iter().any(|p| {
+                    p.id == dep.package && dep.slot.as_ref().is_none_or(|slot| p.slot == *slot)
+                })
+            })
+            .map(|dep| match &dep.slot {
+                Some(slot) => format!("{}:{}", dep.package, slot),
+                None => dep.package.to_string(),
+            })
+            .collect()
+    }
+}
+
+/// An archive written from an installed package
+#[derive(Debug, Clone)]
+pub struct ExportedArchive {
+    pub path: PathBuf,
+    pub metadata: ArchiveMetadata,
+    /// Files changed since they were installed, packed as they are now
+    pub modified: Vec<String>,
+    /// Files left out: missing, masked, device nodes and FIFOs
+    pub skipped: Vec<String>,
+}
+
+/// Pack `record`, installed under `root`, into an archive in
+/// `dest_dir/<category>/`
+pub fn write(
+    record: &PackageRecord,
+    root: &Path,
+    dest_dir: &Path,
+    compression: Compression,
+) -> Result<ExportedArchive> {
+    let pkg = &record.package;
+    let work = tempfile::tempdir()?;
+    let image_name = compression.file_name(IMAGE);
+    let image_path = work.path().join(&image_name);
+
+    let mut files: Vec<&InstalledFile> = pkg.files.iter().collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    let size = files.iter().map(|f| f.size).sum();
+    let mut builder =
+        tar::Builder::new(compression.encoder(std::fs::File::create(&image_path)?, size)?);
+    builder.follow_symlinks(false);
+
+    let (mut contents, mut modified, mut skipped) = (Vec::new(), Vec::new(), Vec::new());
+    for file in files {
+        let src = Path::new(&file.path);
+        let relative = match src.strip_prefix(root) {
+            Ok(relative) if !relative.as_os_str().is_empty() => relative,
+            _ => {
+                skipped.push(file.path.clone());
+                continue;
+            }
+        };
+        let packed = matches!(
+            file.file_type,
+            FileType::Regular | FileType::Hardlink | FileType::Directory | FileType::Symlink
+        );
+        let Some(meta) = std::fs::symlink_metadata(src).ok().filter(|_| packed) else {
+            skipped.push(file.path.clone());
+            continue;
+        };
+
+        let mut entry = file.clone();
+        entry.path = format!("/{}", relative.display());
+        if meta.is_file() {
+            let hash = buckos_core::hash::hash_file(src)?;
+            if file.blake3_hash.as_ref().is_some_and(|h| *h != hash) {
+                modified.push(entry.path.clone());
+            }
+            entry.blake3_hash = Some(hash);
+            entry.size = meta.len();
+        }
+        if meta.is_dir() {
+            builder.append_dir(relative, src)?;
+        } else {
+            builder.append_path_with_name(src, relative)?;
+        }
+        contents.push(entry);
+    }
+    builder.into_inner()?.finish()?;
+
+    let (image_size, blake3_hash, sha512_hash) = hash_member(&image_path)?;
+    let mut package = BinaryPackage::from_installed(pkg);
+    package.compression = compression.algorithm.into();
+    package.size = image_size;
+    package.blake3_hash = blake3_hash;
+    package.sha512_hash = sha512_hash;
+    package.files = contents.iter().map(|f| f.path.clone()).collect();
+    let dep_names = |keep: fn(&DependencyRecord) -> bool| {
+        record
+            .dependencies
+            .iter()
+            .filter(|d| keep(d))
+            .map(|d| d.package.full_name())
+            .collect()
+    };
+    package.dependencies = dep_names(|_| true);
+    package.runtime_deps = dep_names(|d| d.run_time);
+    package.build_deps = dep_names(|d| d.build_time);
+    if let Some(info) = &record.build_info {
+        package.repository = info.repository.clone().unwrap_or(package.repository);
+    }
+    let mut metadata = ArchiveMetadata {
+        package,
+        dependencies: record.dependencies.clone(),
+        build_info: record.build_info.clone(),
+        contents,
+    };
+    metadata.package.path = format!("{}/{}", pkg.id.category, metadata.filename());
+    std::fs::write(
+        work.path().join(METADATA),
+        serde_json::to_vec_pretty(&metadata)?,
+    )?;
+
+    let mut manifest = String::new();
+    for member in [METADATA, image_name.as_str()] {
+        let (size, blake3, sha512) = hash_member(&work.path().join(member))?;
+        manifest.push_str(&format!(
+            "DATA {} {} BLAKE3 {} SHA512 {}\n",
+            member, size, blake3, sha512
+        ));
+    }
+    std::fs::write(work.path().join(MANIFEST), manifest)?;
+    std::fs::write(work.path().join(FORMAT_MARKER), "")?;
+
+    let category_dir = dest_dir.join(&pkg.id.category);
+    std::fs::create_dir_all(&category_dir)?;
+    let path = category_dir.join(metadata.filename());
+    let tmp = path.with_extension("tmp");
+    let prefix = format!("{}-{}", pkg.id.name, pkg.version);
+    let mut outer = tar::Builder::new(std::fs::File::create(&tmp)?);
+    for member in [FORMAT_MARKER, METADATA, image_name.as_str(), MANIFEST] {
+        outer.append_path_with_name(work.path().join(member), Path::new(&prefix).join(member))?;
+    }
+    outer.into_inner()?.sync_all()?;
+    std::fs::rename(&tmp, &path)?;
+
+    Ok(ExportedArchive {
+        path,
+        metadata,
+        modified,
+        skipped,
+    })
+}
+
+/// Check an archive against its Manifest and read its metadata
+pub fn read_metadata(archive: &Path) -> Result<ArchiveMetadata> {
+    let work = tempfile::tempdir()?;
+    open(archive, work.path()).map(|(metadata, _)| metadata)
+}
+
+/// Check an archive and unpack its files into `dest`, checking each
+/// against its hash
+pub fn unpack(archive: &Path, dest: &Path) -> Result<ArchiveMetadata> {
+    let work = tempfile::tempdir()?;
+    let (metadata, image) = open(archive, work.path())?;
+
+    std::fs::create_dir_all(dest)?;
+    let mut tar = tar::Archive::new(compress::open(&image)?);
+    tar.set_preserve_permissions(true);
+    tar.set_preserve_mtime(true);
+    tar.unpack(dest)?;
+
+    let mut expected = HashSet::new();
+    for file in &metadata.contents {
+        let path = dest.join(file.path.trim_start_matches('/'));
+        let meta = std::fs::symlink_metadata(&path).map_err(|_| {
+            Error::Other(format!("{}: {} is missing", archive.display(), file.path))
+        })?;
+        if let (true, Some(hash)) = (meta.is_file(), &file.blake3_hash) {
+            let actual = buckos_core::hash::hash_file(&path)?;
+            if actual != *hash {
+                return Err(Error::ChecksumMismatch {
+                    path: file.path.clone(),
+                    expected: hash.clone(),
+                    actual,
+                });
+            }
+        }
+        // Parents the package doesn't list are created by the unpack
+        expected.extend(path.ancestors().map(Path::to_path_buf));
+    }
+    // The image holds nothing the metadata doesn't account for
+    for entry in walkdir::WalkDir::new(dest).min_depth(1) {
+        let entry = entry?;
+        if !expected.contains(entry.path()) {
+            return Err(Error::Other(format!(
+                "{}: {} is not in the package contents",
+                archive.display(),
+                entry
+                    .path()
+                    .strip_prefix(dest)
+                    .unwrap_or(entry.path())
+                    .display()
+            )));
+        }
+    }
+
+    Ok(metadata)
+}
+
+/// Unpack the members of `archive` into `work`, check them against the
+/// Manifest, and return the metadata and the path of the image
+fn open(archive: &Path, work: &Path) -> Result<(ArchiveMetadata, PathBuf)> {
+    let invalid = |reason: String| {
+        Error::Other(format!(
+            "{} is not a binary package archive: {}",
+            archive.display(),
+            reason
+        ))
+    };
+
+    let mut members = HashSet::new();
+    let mut outer = tar::Archive::new(std::fs::File::open(archive)?);
+    for entry in outer.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let parts: Vec<Component> = path.components().collect();
+        let member = match parts[..] {
+            [Component::Normal(_), Component::Normal(member)] => {
+                member.to_string_lossy().into_owned()
+            }
+            _ => return Err(invalid(format!("unexpected member {}", path.display()))),
+        };
+        let known = [FORMAT_MARKER, METADATA, MANIFEST].contains(&member.as_str())
+            || member.starts_with(IMAGE);
+        if !known || !members.insert(member.clone()) {
+            return Err(invalid(format!("unexpected member {}", path.display())));
+        }
+        entry.unpack(work.join(&member))?;
+    }
+    if !members.contains(FORMAT_MARKER) {
+        return Err(invalid(format!("no {} marker", FORMAT_MARKER)));
+    }
+
+    let manifest = std::fs::read_to_string(work.join(MANIFEST))
+        .map_err(|_| invalid("no Manifest".to_string()))?;
+    let mut listed = BTreeMap::new();
+    for line in manifest.lines() {
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["DATA", member, size, "BLAKE3", blake3, "SHA512", sha512] => {
+                listed.insert(member.to_string(), (size.to_string(), blake3, sha512));
+            }
+            _ => return Err(invalid(format!("bad Manifest line '{}'", line))),
+        }
+    }
+    for member in members
+        .iter()
+        .filter(|m| ![FORMAT_MARKER, MANIFEST].contains(&m.as_str()))
+    {
+        let Some((size, blake3, sha512)) = listed.get(member) else {
+            return Err(invalid(format!("{} is not in the Manifest", member)));
+        };
+        let (actual_size, actual_blake3, actual_sha512) = hash_member(&work.join(member))?;
+        for (expected, actual) in [
+            (size.as_str(), actual_size.to_string()),
+            (*blake3, actual_blake3),
+            (*sha512, actual_sha512),
+        ] {
+            if expected != actual {
+                return Err(Error::ChecksumMismatch {
+                    path: format!("{}:{}", archive.display(), member),
+                    expected: expected.to_string(),
+                    actual,
+                });
+            }
+        }
+    }
+    if let Some(missing) = listed.keys().find(|m| !members.contains(*m)) {
+        return Err(invalid(format!("{} is missing", missing)));
+    }
+
+    let metadata: ArchiveMetadata = serde_json::from_slice(
+        &std::fs::read(work.join(METADATA)).map_err(|_| invalid("no metadata".to_string()))?,
+    )?;
+    for file in &metadata.contents {
+        let path = Path::new(&file.path);
+        if !path.has_root() || path.components().any(|c| c == Component::ParentDir) {
+            return Err(invalid(format!("bad path {}", file.path)));
+        }
+    }
+    let image = image_name(&metadata);
+    if !members.contains(&image) {
+        return Err(invalid("no image".to_string()));
+    }
+    Ok((metadata, work.join(image)))
+}
+
+/// Name of the image member of the archive described by `metadata`
+fn image_name(metadata: &ArchiveMetadata) -> String {
+    // `tar.zst` -> `image.tar.zst`, `tar` -> `image.tar`
+    metadata
+        .package
+        .compression
+        .extension()
+        .replacen("tar", IMAGE, 1)
+}
+
+/// Size, BLAKE3 and SHA512 hash of a file
+fn hash_member(path: &Path) -> Result<(u64, String, String)> {
+    let mut file = std::fs::File::open(path)?;
+    let (mut blake3, mut sha512) = (blake3::Hasher::new(), Sha512::new());
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut size = 0;
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        blake3.update(&buf[..n]);
+        sha512.update(&buf[..n]);
+        size += n as u64;
+    }
+    Ok((
+        size,
+        blake3.finalize().to_hex().to_string(),
+        hex::encode(sha512.finalize()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::BinpkgCompression;
+    use crate::{InstalledPackage, PackageId};
+    use buckos_core::compress::Algorithm;
+
+    fn file(root: &Path, path: &str, file_type: FileType) -> InstalledFile {
+        let full = root.join(path.trim_start_matches('/'));
+        InstalledFile {
+            path: full.to_string_lossy().into_owned(),
+            file_type,
+            mode: 0o755,
+            size: 0,
+            blake3_hash: buckos_core::hash::hash_file(&full).ok(),
+            mtime: 0,
+        }
+    }
+
+    fn record(root: &Path) -> PackageRecord {
+        std::fs::create_dir_all(root.join("usr/bin")).unwrap();
+        std::fs::create_dir_all(root.join("usr/share/foo")).unwrap();
+        std::fs::write(root.join("usr/bin/foo"), "#!/bin/sh\necho foo\n").unwrap();
+        std::os::unix::fs::symlink("foo", root.join("usr/bin/foo-link")).unwrap();
+        let mut record = PackageRecord::new(InstalledPackage {
+            id: PackageId::new("app-misc", "foo"),
+            name: "foo".to_string(),
+            version: semver::Version::new(1, 2, 0),
+            slot: "0".to_string(),
+            installed_at: chrono::Utc::now(),
+            use_flags: ["ssl".to_string()].into(),
+            files: vec![
+                file(root, "/usr/bin/foo", FileType::Regular),
+                file(root, "/usr/bin/foo-link", FileType::Symlink),
+                file(root, "/usr/share/foo", FileType::Directory),
+                file(root, "/usr/share/foo/gone", FileType::Regular),
+            ],
+            size: 0,
+            build_time: false,
+            explicit: true,
+        });
+        record.dependencies.push(DependencyRecord {
+            package: PackageId::new("sys-libs", "zlib"),
+            slot: None,
+            build_time: true,
+            run_time: true,
+        });
+        record
+    }
+
+    #[test]
+    fn test_archive_round_trip() {
+        let root = tempfile::tempdir().unwrap();
+        let pkgdir = tempfile::tempdir().unwrap();
+        let record = record(root.path());
+        std::fs::write(root.path().join("usr/bin/foo"), "#!/bin/sh\necho bar\n").unwrap();
+
+        let compression = Compression::new(Algorithm::Gzip);
+        let exported = write(&record, root.path(), pkgdir.path(), compression).unwrap();
+        assert_eq!(
+            exported.path,
+            pkgdir.path().join("app-misc/foo-1.2.0.gpkg.tar")
+        );
+        assert_eq!(exported.modified, vec!["/usr/bin/foo"]);
+        assert_eq!(exported.skipped.len(), 1);
+
+        let metadata = read_metadata(&exported.path).unwrap();
+        assert_eq!(metadata.package.compression, BinpkgCompression::Gzip);
+        assert!(metadata.package.use_flags.contains(&"ssl".to_string()));
+        assert_eq!(metadata.package.runtime_deps, vec!["sys-libs/zlib"]);
+        assert_eq!(metadata.dependencies, record.dependencies);
+        assert_eq!(
+            metadata.package.files,
+            vec!["/usr/bin/foo", "/usr/bin/foo-link", "/usr/share/foo"]
+        );
+
+        let dest = tempfile::tempdir().unwrap();
+        unpack(&exported.path, dest.path()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dest.path().join("usr/bin/foo")).unwrap(),
+            "#!/bin/sh\necho bar\n"
+        );
+        assert_eq!(
+            std::fs::read_link(dest.path().join("usr/bin/foo-link")).unwrap(),
+            Path::new("foo")
+        );
+        assert!(dest.path().join("usr/share/foo").is_dir());
+
+        assert_eq!(metadata.missing_dependencies(&[]), vec!["sys-libs/zlib"]);
+        let mut zlib = record.package.clone();
+        zlib.id = PackageId::new("sys-libs", "zlib");
+        zlib.slot = "1".to_string();
+        assert!(metadata.missing_dependencies(&[zlib]).is_empty());
+    }
+
+    #[test]
+    fn test_archive_tampered() {
+        let root = tempfile::tempdir().unwrap();
+        let pkgdir = tempfile::tempdir().unwrap();
+        let exported = write(
+            &record(root.path()),
+            root.path(),
+            pkgdir.path(),
+            Compression::new(Algorithm::None),
+        )
+        .unwrap();
+
+        // The uncompressed image holds the script as is
+        let bytes = std::fs::read(&exported.path).unwrap();
+        let at = bytes.windows(8).position(|w| w == b"echo foo").unwrap();
+        let mut tampered = bytes.clone();
+        tampered[at + 5..at + 8].copy_from_slice(b"bad");
+        std::fs::write(&exported.path, tampered).unwrap();
+        assert!(matches!(
+            read_metadata(&exported.path),
+            Err(Error::ChecksumMismatch { .. })
+        ));
+
+        std::fs::write(&exported.path, b"not an archive").unwrap();
+        assert!(read_metadata(&exported.path).is_err());
+    }
+}
@@ -7,12 +7,20 @@
 //! - binpkg-multi-instance support
 //! - Binary package signing
 //! - --getbinpkg and --usepkg flags
+//! - BINPKG_COMPRESS algorithms and levels, with packages of any supported
+//!   compression readable whatever their index entry says
+
+pub mod archive;
+
+pub use archive::{ArchiveMetadata, ExportedArchive};
 
 use crate::security::signing::{SignatureVerification, SigningManager};
-use crate::{Error, InstalledPackage, PackageId, PackageInfo, Result};
+use crate::{Error, FileType, InstalledFile, InstalledPackage, PackageId, PackageInfo, Result};
+use buckos_core::compress::{self, Algorithm, Compression};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use tracing::{debug, info};
 
 /// Binary package format version
@@ -21,6 +29,14 @@ pub const BINPKG_FORMAT_VERSION: u32 = 2;
 /// Default compression for binary packages
 pub const DEFAULT_COMPRESSION: BinpkgCompression = BinpkgCompression::Zstd;
 
+/// Name suffix of companion split debug info packages
+pub const DEBUG_PACKAGE_SUFFIX: &str = "-dbg";
+
+/// Identifier of the -dbg companion package for a package
+pub fn debug_package_id(id: &PackageId) -> PackageId {
+    PackageId::new(&id.category, format!("{}{}", id.name, DEBUG_PACKAGE_SUFFIX))
+}
+
 /// Binary package compression types
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum BinpkgCompression {
@@ -65,6 +81,19 @@ impl BinpkgCompression {
     }
 }
 
+impl From<Algorithm> for BinpkgCompression {
+    fn from(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::None => BinpkgCompression::None,
+            Algorithm::Gzip => BinpkgCompression::Gzip,
+            Algorithm::Bzip2 => BinpkgCompression::Bzip2,
+            Algorithm::Xz => BinpkgCompression::Xz,
+            Algorithm::Lz4 => BinpkgCompression::Lz4,
+            Algorithm::Zstd => BinpkgCompression::Zstd,
+        }
+    }
+}
+
 impl Default for BinpkgCompression {
     fn default() -> Self {
         DEFAULT_COMPRESSION
@@ -139,6 +168,9 @@ pub struct BinaryPackage {
     pub eapi: String,
     /// Package format version
     pub format_version: u32,
+    /// Files contained in the package, relative to the install root (e.g. `/usr/bin/foo`)
+    #[serde(default)]
+    pub files: Vec<String>,
 }
 
 impl BinaryPackage {
@@ -171,6 +203,7 @@ impl BinaryPackage {
             arch: get_arch(),
             eapi: "8".to_string(),
             format_version: BINPKG_FORMAT_VERSION,
+            files: Vec::new(),
         }
     }
 
@@ -220,6 +253,7 @@ impl BinaryPackage {
             arch: get_arch(),
             eapi: "8".to_string(),
             format_version: BINPKG_FORMAT_VERSION,
+            files: Vec::new(),
         }
     }
 
@@ -276,8 +310,8 @@ pub struct BinaryPackageOptions {
     pub sign: bool,
     /// Signing key ID
     pub signing_key: Option<String>,
-    /// Compression type
-    pub compression: BinpkgCompression,
+    /// Compression algorithm, level and threads
+    pub compression: Compression,
     /// Remote binary package server URL
     pub binpkg_server: Option<String>,
 }
@@ -288,7 +322,7 @@ pub struct BinaryPackageManager {
     pkgdir: PathBuf,
     /// Binary package index
     index: BinaryPackageIndex,
-    /// Signing manager for GPG operations
+    /// Signing manager for GPG or minisign operations
     signing_manager: SigningManager,
     /// Multi-instance support enabled
     multi_instance: bool,
@@ -307,6 +341,40 @@ pub struct BinaryPackageIndex {
     pub version: u32,
 }
 
+impl BinaryPackageIndex {
+    /// Load the index from PKGDIR, or return an empty index if none exists yet
+    ///
+    /// Unlike [`BinaryPackageManager::new`] this never creates PKGDIR, so it is
+    /// safe to use from read-only code paths such as `--pretend`.
+    pub fn load(pkgdir: &Path) -> Result<Self> {
+        let index_path = pkgdir.join("Packages.json");
+
+        if index_path.exists() {
+            let content = std::fs::read_to_string(&index_path)?;
+            let index: BinaryPackageIndex = serde_json::from_str(&content)
+                .map_err(|e| Error::Other(format!("Failed to parse package index: {}", e)))?;
+            Ok(index)
+        } else {
+            Ok(BinaryPackageIndex {
+                packages: HashMap::new(),
+                last_updated: Some(chrono::Utc::now()),
+                version: 1,
+            })
+        }
+    }
+
+    /// Find a specific version of a binary package
+    pub fn find_version(
+        &self,
+        pkg_id: &PackageId,
+        version: &semver::Version,
+    ) -> Option<&BinaryPackage> {
+        self.packages
+            .get(&pkg_id.full_name())
+            .and_then(|packages| packages.iter().find(|p| &p.version == version))
+    }
+}
+
 impl BinaryPackageManager {
     /// Create a new binary package manager
     pub fn new(pkgdir: PathBuf) -> Result<Self> {
@@ -319,7 +387,7 @@ impl BinaryPackageManager {
         let signing_manager = SigningManager::new()?;
 
         // Load or create index
-        let index = Self::load_or_create_index(&pkgdir)?;
+        let index = BinaryPackageIndex::load(&pkgdir)?;
 
         Ok(Self {
             pkgdir,
@@ -330,6 +398,13 @@ impl BinaryPackageManager {
         })
     }
 
+    /// Sign and verify with `signing_manager`, e.g. one built from the
+    /// signing configuration
+    pub fn with_signing_manager(mut self, signing_manager: SigningManager) -> Self {
+        self.signing_manager = signing_manager;
+        self
+    }
+
     /// Create with multi-instance support
     pub fn with_multi_instance(mut self, enabled: bool) -> Self {
         self.multi_instance = enabled;
@@ -347,24 +422,6 @@ impl BinaryPackageManager {
         &self.pkgdir
     }
 
-    /// Load or create the binary package index
-    fn load_or_create_index(pkgdir: &Path) -> Result<BinaryPackageIndex> {
-        let index_path = pkgdir.join("Packages.json");
-
-        if index_path.exists() {
-            let content = std::fs::read_to_string(&index_path)?;
-            let index: BinaryPackageIndex = serde_json::from_str(&content)
-                .map_err(|e| Error::Other(format!("Failed to parse package index: {}", e)))?;
-            Ok(index)
-        } else {
-            Ok(BinaryPackageIndex {
-                packages: HashMap::new(),
-                last_updated: Some(chrono::Utc::now()),
-                version: 1,
-            })
-        }
-    }
-
     /// Save the package index
     pub fn save_index(&self) -> Result<()> {
         let index_path = self.pkgdir.join("Packages.json");
@@ -376,16 +433,36 @@ impl BinaryPackageManager {
     }
 
     /// Create a binary package from build output
+    ///
+    /// Split debug info under `/usr/lib/debug` is left out of the package
+    /// and goes into its -dbg companion, created alongside.
     pub async fn create_package(
         &mut self,
         pkg: &InstalledPackage,
         build_dir: &Path,
         opts: &BinaryPackageOptions,
+    ) -> Result<BinaryPackage> {
+        let binpkg = self
+            .write_package(pkg, build_dir, ImagePart::Main, opts)
+            .await?;
+        if !collect_manifest(build_dir, ImagePart::Debug)?.is_empty() {
+            self.write_debug_package(pkg, build_dir, opts).await?;
+        }
+        Ok(binpkg)
+    }
+
+    /// Pack one part of an image directory as a binary package of `pkg`
+    async fn write_package(
+        &mut self,
+        pkg: &InstalledPackage,
+        build_dir: &Path,
+        part: ImagePart,
+        opts: &BinaryPackageOptions,
     ) -> Result<BinaryPackage> {
         info!("Creating binary package for {}-{}", pkg.id, pkg.version);
 
         let mut binpkg = BinaryPackage::from_installed(pkg);
-        binpkg.compression = opts.compression;
+        binpkg.compression = opts.compression.algorithm.into();
 
         // Generate instance ID if multi-instance is enabled
         if opts.multi_instance || self.multi_instance {
@@ -398,9 +475,12 @@ impl BinaryPackageManager {
             std::fs::create_dir_all(&pkg_category_dir)?;
         }
 
+        // Record the file manifest so transactions can be previewed later
+        binpkg.files = collect_manifest(build_dir, part)?;
+
         // Create the archive
         let pkg_path = binpkg.full_path(&self.pkgdir);
-        self.create_archive(build_dir, &pkg_path, opts.compression)
+        self.create_archive(build_dir, &pkg_path, part, opts.compression)
             .await?;
 
         // Calculate hashes
@@ -417,7 +497,11 @@ impl BinaryPackageManager {
             binpkg.signature = Some(signature);
 
             // Also write detached signature file
-            let sig_path = pkg_path.with_extension(format!("{}.asc", opts.compression.extension()));
+            let sig_path = pkg_path.with_extension(format!(
+                "{}.{}",
+                binpkg.compression.extension(),
+                self.signing_manager.signature_extension()
+            ));
             std::fs::write(&sig_path, binpkg.signature.as_ref().unwrap())?;
             info!("Created signature: {}", sig_path.display());
         }
@@ -444,42 +528,145 @@ impl BinaryPackageManager {
         Ok(binpkg)
     }
 
+    /// Create a -dbg companion package holding a package's split debug info
+    ///
+    /// Collects the files the package installed under `/usr/lib/debug` in
+    /// `root`. Returns `None` if the package has no split debug info.
+    pub async fn create_debug_package(
+        &mut self,
+        pkg: &InstalledPackage,
+        root: &Path,
+        opts: &BinaryPackageOptions,
+    ) -> Result<Option<BinaryPackage>> {
+        let debug_dir = root.join(crate::transaction::DEBUG_DIR);
+        let files: Vec<&InstalledFile> = pkg
+            .files
+            .iter()
+            .filter(|f| Path::new(&f.path).starts_with(&debug_dir))
+            .collect();
+        if files.is_empty() {
+            return Ok(None);
+        }
+
+        let staging = tempfile::tempdir()?;
+        for file in &files {
+            let src = Path::new(&file.path);
+            let Ok(relative) = src.strip_prefix(root) else {
+                continue;
+            };
+            let dest = staging.path().join(relative);
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            match file.file_type {
+                FileType::Regular => {
+                    std::fs::copy(src, &dest)?;
+                }
+                FileType::Symlink => {
+                    std::os::unix::fs::symlink(std::fs::read_link(src)?, &dest)?;
+                }
+                _ => {}
+            }
+        }
+
+        self.write_debug_package(pkg, staging.path(), opts)
+            .await
+            .map(Some)
+    }
+
+    /// Pack the split debug info of an image directory as the -dbg
+    /// companion of `pkg`
+    async fn write_debug_package(
+        &mut self,
+        pkg: &InstalledPackage,
+        build_dir: &Path,
+        opts: &BinaryPackageOptions,
+    ) -> Result<BinaryPackage> {
+        let mut dbg = pkg.clone();
+        dbg.id = debug_package_id(&pkg.id);
+        dbg.name = dbg.id.name.clone();
+        dbg.files.clear();
+        dbg.size = walkdir::WalkDir::new(build_dir.join(crate::transaction::DEBUG_DIR))
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter_map(|e| e.metadata().ok())
+            .filter(|m| m.is_file())
+            .map(|m| m.len())
+            .sum();
+
+        let mut binpkg = self
+            .write_package(&dbg, build_dir, ImagePart::Debug, opts)
+            .await?;
+        binpkg.description = format!("Debug info for {}", pkg.id);
+        binpkg.runtime_deps = vec![format!("={}-{}", pkg.id, pkg.version)];
+        if let Some(entry) = self
+            .index
+            .packages
+            .get_mut(&dbg.id.full_name())
+            .and_then(|p| p.last_mut())
+        {
+            *entry = binpkg.clone();
+        }
+        self.save_index()?;
+
+        Ok(binpkg)
+    }
+
     /// Create a compressed archive
+    ///
+    /// tar writes the archive uncompressed and it is compressed here, so
+    /// large packages are compressed with a thread per core.
     async fn create_archive(
         &self,
         source_dir: &Path,
         output_path: &Path,
-        compression: BinpkgCompression,
+        part: ImagePart,
+        compression: Compression,
     ) -> Result<()> {
-        let tar_cmd = match compression {
-            BinpkgCompression::None => "tar -cf",
-            BinpkgCompression::Gzip => "tar -czf",
-            BinpkgCompression::Bzip2 => "tar -cjf",
-            BinpkgCompression::Xz => "tar -cJf",
-            BinpkgCompression::Lz4 => "tar -c --lz4 -f",
-            BinpkgCompression::Zstd => "tar -c --zstd -f",
-        };
-
-        let output = tokio::process::Command::new("sh")
-            .args([
-                "-c",
-                &format!(
-                    "{} {} -C {} .",
-                    tar_cmd,
-                    output_path.display(),
-                    source_dir.display()
-                ),
-            ])
-            .output()
-            .await
-            .map_err(|e| Error::Other(format!("Failed to create archive: {}", e)))?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(Error::Other(format!("Archive creation failed: {}", stderr)));
-        }
-
-        Ok(())
+        let source_dir = source_dir.to_path_buf();
+        let output_path = output_path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let size = walkdir::WalkDir::new(&source_dir)
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| {
+                    e.path()
+                        .strip_prefix(&source_dir)
+                        .is_ok_and(|relative| part.contains(relative))
+                })
+                .filter_map(|e| e.metadata().ok())
+                .filter(|m| m.is_file())
+                .map(|m| m.len())
+                .sum();
+            let debug_dir = format!("./{}", crate::transaction::DEBUG_DIR);
+            let members = match part {
+                ImagePart::Main => vec![format!("--exclude={}", debug_dir), ".".to_string()],
+                ImagePart::Debug => vec![debug_dir],
+            };
+            let mut encoder = compression.encoder(std::fs::File::create(&output_path)?, size)?;
+            let mut tar = Command::new("tar")
+                .arg("-c")
+                .arg("-C")
+                .arg(&source_dir)
+                .args(&members)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .map_err(|e| Error::Other(format!("Failed to create archive: {}", e)))?;
+            let mut stdout = tar.stdout.take().expect("stdout is piped");
+            let copied = std::io::copy(&mut stdout, &mut encoder);
+
+            let output = tar.wait_with_output()?;
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                return Err(Error::Other(format!("Archive creation failed: {}", stderr)));
+            }
+            copied?;
+            encoder.finish()?;
+            Ok(())
+        })
+        .await
+        .map_err(|e| Error::Other(format!("Failed to create archive: {}", e)))?
     }
 
     /// Extract a binary package
@@ -495,36 +682,36 @@ impl BinaryPackageManager {
             std::fs::create_dir_all(dest_dir)?;
         }
 
-        let tar_cmd = match binpkg.compression {
-            BinpkgCompression::None => "tar -xf",
-            BinpkgCompression::Gzip => "tar -xzf",
-            BinpkgCompression::Bzip2 => "tar -xjf",
-            BinpkgCompression::Xz => "tar -xJf",
-            BinpkgCompression::Lz4 => "tar -x --lz4 -f",
-            BinpkgCompression::Zstd => "tar -x --zstd -f",
-        };
-
-        let output = tokio::process::Command::new("sh")
-            .args([
-                "-c",
-                &format!(
-                    "{} {} -C {}",
-                    tar_cmd,
-                    pkg_path.display(),
-                    dest_dir.display()
-                ),
-            ])
-            .output()
-            .await
-            .map_err(|e| Error::Other(format!("Failed to extract package: {}", e)))?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(Error::Other(format!(
-                "Package extraction failed: {}",
-                stderr
-            )));
-        }
+        // Decompressed by content, so packages written with another
+        // BINPKG_COMPRESS than their index entry records still extract
+        let dest = dest_dir.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let mut decoder = compress::open(&pkg_path)?;
+            let mut tar = Command::new("tar")
+                .arg("-x")
+                .arg("-C")
+                .arg(&dest)
+                .stdin(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .map_err(|e| Error::Other(format!("Failed to extract package: {}", e)))?;
+            let mut stdin = tar.stdin.take().expect("stdin is piped");
+            let copied = std::io::copy(&mut decoder, &mut stdin);
+            drop(stdin);
+
+            let output = tar.wait_with_output()?;
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                return Err(Error::Other(format!(
+                    "Package extraction failed: {}",
+                    stderr
+                )));
+            }
+            copied?;
+            Ok(())
+        })
+        .await
+        .map_err(|e| Error::Other(format!("Failed to extract package: {}", e)))??;
 
         info!("Extracted {} to {}", binpkg.path, dest_dir.display());
         Ok(())
@@ -546,11 +733,7 @@ impl BinaryPackageManager {
         pkg_id: &PackageId,
         version: &semver::Version,
     ) -> Option<&BinaryPackage> {
-        let key = pkg_id.full_name();
-        self.index
-            .packages
-            .get(&key)
-            .and_then(|packages| packages.iter().find(|p| &p.version == version))
+        self.index.find_version(pkg_id, version)
     }
 
     /// Get the best matching binary package (latest version)
@@ -622,10 +805,13 @@ impl BinaryPackageManager {
             info!("Removed binary package: {}", pkg_path.display());
         }
 
-        // Remove signature file if exists
-        let sig_path = pkg_path.with_extension(format!("{}.asc", binpkg.compression.extension()));
-        if sig_path.exists() {
-            std::fs::remove_file(&sig_path)?;
+        // Remove signature files if they exist
+        for sig_ext in ["asc", "minisig"] {
+            let sig_path =
+                pkg_path.with_extension(format!("{}.{}", binpkg.compression.extension(), sig_ext));
+            if sig_path.exists() {
+                std::fs::remove_file(&sig_path)?;
+            }
         }
 
         // Update index
@@ -746,6 +932,7 @@ impl BinaryPackageManager {
             arch: get_arch(),
             eapi: "8".to_string(),
             format_version: BINPKG_FORMAT_VERSION,
+            files: Vec::new(),
         };
 
         // Update index
@@ -839,8 +1026,11 @@ impl BinaryPackageManager {
                         pkg.signature = Some(signature.clone());
 
                         // Write detached signature
-                        let sig_path =
-                            pkg_path.with_extension(format!("{}.asc", pkg.compression.extension()));
+                        let sig_path = pkg_path.with_extension(format!(
+                            "{}.{}",
+                            pkg.compression.extension(),
+                            self.signing_manager.signature_extension()
+                        ));
                         std::fs::write(&sig_path, &signature)?;
 
                         signed_count += 1;
@@ -971,6 +1161,7 @@ impl BinaryPackageManager {
             arch: get_arch(),
             eapi: "8".to_string(),
             format_version: BINPKG_FORMAT_VERSION,
+            files: Vec::new(),
         }))
     }
 }
@@ -990,6 +1181,41 @@ pub struct BinaryPackageVerification {
 
 // Helper functions
 
+/// Which part of an image directory a package holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ImagePart {
+    /// Everything but split debug info
+    Main,
+    /// Split debug info only, for the -dbg companion
+    Debug,
+}
+
+impl ImagePart {
+    /// Whether an image-relative path belongs to this part
+    fn contains(self, relative: &Path) -> bool {
+        relative.starts_with(crate::transaction::DEBUG_DIR) == (self == ImagePart::Debug)
+    }
+}
+
+/// Collect the non-directory entries of one part of an image directory as
+/// root-relative paths
+fn collect_manifest(image_dir: &Path, part: ImagePart) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    for entry in walkdir::WalkDir::new(image_dir) {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        if let Ok(relative) = entry.path().strip_prefix(image_dir) {
+            if part.contains(relative) {
+                files.push(format!("/{}", relative.to_string_lossy()));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
 fn get_hostname() -> String {
     std::env::var("HOSTNAME")
         .or_else(|_| std::fs::read_to_string("/etc/hostname").map(|s| s.trim().to_string()))
@@ -1080,9 +1306,8 @@ mod tests {
         );
     }
 
-    #[test]
-    fn test_binary_package_filename() {
-        let pkg = BinaryPackage {
+    fn openssl() -> BinaryPackage {
+        BinaryPackage {
             id: PackageId::new("dev-libs", "openssl"),
             version: semver::Version::new(3, 0, 0),
             slot: "0".to_string(),
@@ -1109,9 +1334,89 @@ mod tests {
             arch: "amd64".to_string(),
             eapi: "8".to_string(),
             format_version: BINPKG_FORMAT_VERSION,
+            files: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_binary_package_filename() {
+        assert_eq!(openssl().filename(), "openssl-3.0.0.tar.zst");
+    }
+
+    #[tokio::test]
+    async fn test_archive_decompressed_by_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("image");
+        std::fs::create_dir_all(image.join("usr/bin")).unwrap();
+        std::fs::write(image.join("usr/bin/openssl"), "binary").unwrap();
+
+        let manager = BinaryPackageManager::new(dir.path().join("pkgdir")).unwrap();
+        let archive = manager.pkgdir().join("openssl-3.0.0.tar.xz");
+        manager
+            .create_archive(&image, &archive, ImagePart::Main, "xz:9".parse().unwrap())
+            .await
+            .unwrap();
+        let header = std::fs::read(&archive).unwrap();
+        assert_eq!(Algorithm::detect(&header), Algorithm::Xz);
+
+        // The index entry says zstd, as it would after BINPKG_COMPRESS changed
+        let mut binpkg = openssl();
+        binpkg.path = "openssl-3.0.0.tar.xz".to_string();
+        let dest = dir.path().join("dest");
+        manager.extract_package(&binpkg, &dest).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dest.join("usr/bin/openssl")).unwrap(),
+            "binary"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_debug_info_split_into_companion() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("image");
+        std::fs::create_dir_all(image.join("usr/bin")).unwrap();
+        std::fs::create_dir_all(image.join("usr/lib/debug/usr/bin")).unwrap();
+        std::fs::write(image.join("usr/bin/openssl"), "binary").unwrap();
+        std::fs::write(image.join("usr/lib/debug/usr/bin/openssl.debug"), "dwarf").unwrap();
+
+        let pkg = InstalledPackage {
+            id: PackageId::new("dev-libs", "openssl"),
+            name: "openssl".to_string(),
+            version: semver::Version::new(3, 0, 0),
+            slot: "0".to_string(),
+            installed_at: chrono::Utc::now(),
+            use_flags: Default::default(),
+            files: Vec::new(),
+            size: 0,
+            build_time: false,
+            explicit: true,
+        };
+        let opts = BinaryPackageOptions {
+            compression: "gzip".parse().unwrap(),
+            ..Default::default()
         };
+        let mut manager = BinaryPackageManager::new(dir.path().join("pkgdir")).unwrap();
+        let binpkg = manager.create_package(&pkg, &image, &opts).await.unwrap();
+        assert_eq!(binpkg.files, vec!["/usr/bin/openssl"]);
+
+        let dbg = manager.find_packages(&debug_package_id(&pkg.id))[0].clone();
+        assert_eq!(dbg.files, vec!["/usr/lib/debug/usr/bin/openssl.debug"]);
+        assert_eq!(dbg.runtime_deps, vec!["=dev-libs/openssl-3.0.0"]);
+        assert_eq!(dbg.installed_size, 5);
+
+        let dest = dir.path().join("dest");
+        manager.extract_package(&binpkg, &dest).await.unwrap();
+        assert!(dest.join("usr/bin/openssl").exists());
+        assert!(!dest.join("usr/lib/debug").exists());
+    }
 
-        assert_eq!(pkg.filename(), "openssl-3.0.0.tar.zst");
+    #[test]
+    fn test_debug_package_id() {
+        let id = PackageId::new("sys-libs", "zlib");
+        assert_eq!(
+            debug_package_id(&id),
+            PackageId::new("sys-libs", "zlib-dbg")
+        );
     }
 
     #[test]
@@ -0,0 +1,530 @@
+//! Buck2 daemon lifecycle
+//!
+//! Every Buck2 command buckos runs uses one pinned isolation directory, so
+//! it talks to a daemon of its own rather than one a developer started in
+//! the same repository. The client version that started the daemon is
+//! recorded; after Buck2 is upgraded the old daemon is restarted instead
+//! of being reused. Commands that hang past their timeout are killed and
+//! the daemon restarted, since a wedged daemon blocks every later build.
+
+use crate::interrupt::Cancellation;
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+/// How long status, kill and version queries may take
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a cancelled command may take to stop before it is killed
+pub const CANCEL_GRACE: Duration = Duration::from_secs(10);
+
+/// Buck2 daemon settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BuckDaemonConfig {
+    /// Isolation directory of the daemon buckos uses
+    pub isolation_dir: String,
+    /// Seconds a query may run before the daemon is considered wedged
+    pub command_timeout: u64,
+    /// Seconds a build may run before the daemon is considered wedged;
+    /// builds are not timed out when unset
+    pub build_timeout: Option<u64>,
+}
+
+impl Default for BuckDaemonConfig {
+    fn default() -> Self {
+        Self {
+            isolation_dir: "buckos".to_string(),
+            command_timeout: 600,
+            build_timeout: None,
+        }
+    }
+}
+
+/// State of the Buck2 daemon
+#[derive(Debug, Clone, Serialize)]
+pub struct DaemonStatus {
+    /// Isolation directory the daemon runs in
+    pub isolation_dir: String,
+    /// Process ID of the daemon, if one is running
+    pub pid: Option<u32>,
+    /// When buckos started the daemon
+    pub started_at: Option<DateTime<Utc>>,
+    /// Version of the installed Buck2 client
+    pub client_version: String,
+    /// Version of the client that started the daemon, if buckos did
+    pub daemon_version: Option<String>,
+}
+
+impl DaemonStatus {
+    /// Whether a daemon is running
+    pub fn running(&self) -> bool {
+        self.pid.is_some()
+    }
+
+    /// Whether the daemon was started by a different Buck2 version than
+    /// the one installed
+    pub fn version_mismatch(&self) -> bool {
+        self.running()
+            && self
+                .daemon_version
+                .as_ref()
+                .is_some_and(|v| *v != self.client_version)
+    }
+}
+
+/// Daemon started by buckos, recorded to detect Buck2 upgrades
+#[derive(Debug, Serialize, Deserialize)]
+struct DaemonRecord {
+    pid: u32,
+    client_version: String,
+    started_at: DateTime<Utc>,
+}
+
+/// Starts, checks and restarts the Buck2 daemon
+#[derive(Debug, Clone)]
+pub struct BuckDaemon {
+    buck_path: PathBuf,
+    repo_path: PathBuf,
+    config: BuckDaemonConfig,
+    /// Where the record of the running daemon is kept
+    record_path: PathBuf,
+}
+
+impl BuckDaemon {
+    /// Manage the daemon of `repo_path`, recording it under `state_dir`
+    pub fn new(
+        buck_path: PathBuf,
+        repo_path: PathBuf,
+        state_dir: &Path,
+        config: BuckDaemonConfig,
+    ) -> Self {
+        let record_path = state_dir.join(format!("buck2-daemon-{}.json", config.isolation_dir));
+        Self {
+            buck_path,
+            repo_path,
+            config,
+            record_path,
+        }
+    }
+
+    /// Settings of the daemon
+    pub fn config(&self) -> &BuckDaemonConfig {
+        &self.config
+    }
+
+    /// A Buck2 command using the pinned isolation directory; the
+    /// subcommand is added by the caller
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.buck_path);
+        cmd.arg("--isolation-dir")
+            .arg(&self.config.isolation_dir)
+            .current_dir(&self.repo_path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        cmd
+    }
+
+    /// Timeout of queries
+    pub fn command_timeout(&self) -> Option<Duration> {
+        Some(Duration::from_secs(self.config.command_timeout))
+    }
+
+    /// Timeout of builds
+    pub fn build_timeout(&self) -> Option<Duration> {
+        self.config.build_timeout.map(Duration::from_secs)
+    }
+
+    /// Run a command, restarting the daemon if it hangs past `timeout`
+    pub async fn run(&self, cmd: &mut Command, timeout: Option<Duration>) -> Result<Output> {
+        debug!("Running: {:?}", cmd);
+        let output = cmd.output();
+        let result = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, output).await {
+                Ok(result) => result,
+                Err(_) => return Err(self.hung(timeout).await),
+            },
+            None => output.await,
+        };
+        result.map_err(|e| Error::BuckError(format!("Failed to execute Buck: {}", e)))
+    }
+
+    /// [`run`](Self::run) a command that stops when `cancellation` is
+    /// cancelled
+    ///
+    /// The client runs in a process group of its own, so Ctrl-C at the
+    /// terminal reaches buckos alone. On cancellation the group gets SIGINT,
+    /// which has Buck2 cancel the build in its daemon, and SIGKILL if it is
+    /// still running after [`CANCEL_GRACE`].
+    pub async fn run_cancellable(
+        &self,
+        cmd: &mut Command,
+        timeout: Option<Duration>,
+        cancellation: &Cancellation,
+    ) -> Result<Output> {
+        cancellation.check()?;
+        debug!("Running: {:?}", cmd);
+        let child = cmd
+            .process_group(0)
+            .spawn()
+            .map_err(|e| Error::BuckError(format!("Failed to execute Buck: {}", e)))?;
+        let group = child.id().map(|pid| pid as libc::pid_t);
+        let output = child.wait_with_output();
+        tokio::pin!(output);
+        let expired = async {
+            match timeout {
+                Some(timeout) => tokio::time::sleep(timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            result = &mut output => {
+                return result.map_err(|e| Error::BuckError(format!("Failed to execute Buck: {}", e)));
+            }
+            _ = cancellation.cancelled() => {}
+            _ = expired => {
+                kill_group(group, libc::SIGKILL);
+                return Err(self.hung(timeout.unwrap_or_default()).await);
+            }
+        }
+
+        info!("Stopping Buck2");
+        kill_group(group, libc::SIGINT);
+        if tokio::time::timeout(CANCEL_GRACE, &mut output)
+            .await
+            .is_err()
+        {
+            warn!(
+                "Buck2 did not stop within {}s, killing it",
+                CANCEL_GRACE.as_secs()
+            );
+            kill_group(group, libc::SIGKILL);
+            let _ = output.await;
+        }
+        Err(Error::Interrupted {
+            signal: cancellation.signal().unwrap_or(libc::SIGINT),
+        })
+    }
+
+    /// Restart a daemon that did not finish a command within `timeout`
+    async fn hung(&self, timeout: Duration) -> Error {
+        warn!(
+            "Buck2 command hung for {}s, restarting the daemon",
+            timeout.as_secs()
+        );
+        if let Err(e) = self.restart().await {
+            warn!("Failed to restart the Buck2 daemon: {}", e);
+        }
+        Error::BuckError(format!(
+            "Buck2 did not respond within {}s; its daemon was restarted",
+            timeout.as_secs()
+        ))
+    }
+
+    /// Version of the installed Buck2 client
+    pub async fn client_version(&self) -> Result<String> {
+        let mut cmd = Command::new(&self.buck_path);
+        cmd.arg("--version")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        let output = self.control(&mut cmd).await?;
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Query the daemon
+    pub async fn status(&self) -> Result<DaemonStatus> {
+        let client_version = self.client_version().await?;
+        let output = self.control(self.command().arg("status")).await?;
+        let pid = output
+            .status
+            .success()
+            .then(|| parse_status_pid(&String::from_utf8_lossy(&output.stdout)))
+            .flatten();
+        // A record of a daemon that since exited, or that someone else
+        // replaced, says nothing about the running one
+        let record = self.read_record().filter(|r| Some(r.pid) == pid);
+        Ok(DaemonStatus {
+            isolation_dir: self.config.isolation_dir.clone(),
+            pid,
+            started_at: record.as_ref().map(|r| r.started_at),
+            client_version,
+            daemon_version: record.map(|r| r.client_version),
+        })
+    }
+
+    /// Start the daemon if it is not running, and restart it if an older
+    /// Buck2 started it
+    pub async fn ensure_running(&self) -> Result<DaemonStatus> {
+        let status = self.status().await?;
+        if status.version_mismatch() {
+            info!(
+                "Buck2 daemon was started by {}, restarting it with {}",
+                status.daemon_version.as_deref().unwrap_or("unknown"),
+                status.client_version
+            );
+            return self.restart().await;
+        }
+        if status.running() && status.daemon_version.is_some() {
+            return Ok(status);
+        }
+        // Also record a daemon buck2 started on its own, so upgrades are
+        // noticed from now on
+        self.start().await
+    }
+
+    /// Start the daemon, or connect to the running one
+    pub async fn start(&self) -> Result<DaemonStatus> {
+        let output = self.control(self.command().arg("server")).await?;
+        if !output.status.success() {
+            return Err(Error::BuckError(format!(
+                "Failed to start the Buck2 daemon: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        let mut status = self.status().await?;
+        let Some(pid) = status.pid else {
+            return Err(Error::BuckError(
+                "Buck2 daemon exited right after starting".to_string(),
+            ));
+        };
+        let record = match self.read_record().filter(|r| r.pid == pid) {
+            Some(record) => record,
+            None => DaemonRecord {
+                pid,
+                client_version: status.client_version.clone(),
+                started_at: Utc::now(),
+            },
+        };
+        status.started_at = Some(record.started_at);
+        status.daemon_version = Some(record.client_version.clone());
+        self.write_record(&record)?;
+        Ok(status)
+    }
+
+    /// Stop the daemon, killing it if it does not exit when asked
+    pub async fn kill(&self) -> Result<()> {
+        let pid = self.read_record().map(|r| r.pid);
+        let asked = self.control(self.command().arg("kill")).await;
+        if !matches!(&asked, Ok(output) if output.status.success()) {
+            let Some(pid) = pid else {
+                return asked.map(|_| ());
+            };
+            warn!("Buck2 daemon {} did not stop when asked, killing it", pid);
+            // SAFETY: kill(2) has no memory safety requirements
+            if unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) } != 0 {
+                let err = std::io::Error::last_os_error();
+                if err.raw_os_error() != Some(libc::ESRCH) {
+                    return Err(Error::BuckError(format!(
+                        "Failed to kill Buck2 daemon {}: {}",
+                        pid, err
+                    )));
+                }
+            }
+        }
+        let _ = std::fs::remove_file(&self.record_path);
+        Ok(())
+    }
+
+    /// Stop the daemon and start a new one
+    pub async fn restart(&self) -> Result<DaemonStatus> {
+        self.kill().await?;
+        self.start().await
+    }
+
+    /// Run a command that must finish within [`CONTROL_TIMEOUT`]
+    async fn control(&self, cmd: &mut Command) -> Result<Output> {
+        debug!("Running: {:?}", cmd);
+        match tokio::time::timeout(CONTROL_TIMEOUT, cmd.output()).await {
+            Ok(result) => {
+                result.map_err(|e| Error::BuckError(format!("Failed to execute Buck: {}", e)))
+            }
+            Err(_) => Err(Error::BuckError(format!(
+                "Buck2 did not respond within {}s",
+                CONTROL_TIMEOUT.as_secs()
+            ))),
+        }
+    }
+
+    /// PID of the daemon buckos last started, without asking Buck2
+    ///
+    /// The daemon may have exited since; callers must cope with the
+    /// process being gone.
+    pub fn recorded_pid(&self) -> Option<u32> {
+        self.read_record().map(|r| r.pid)
+    }
+
+    fn read_record(&self) -> Option<DaemonRecord> {
+        let content = std::fs::read_to_string(&self.record_path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn write_record(&self, record: &DaemonRecord) -> Result<()> {
+        if let Some(parent) = self.record_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.record_path, serde_json::to_string_pretty(record)?)?;
+        Ok(())
+    }
+}
+
+/// Signal every process of a group, if it still exists
+fn kill_group(group: Option<libc::pid_t>, signal: libc::c_int) {
+    if let Some(group) = group {
+        // SAFETY: killpg(2) has no memory safety requirements
+        unsafe {
+            libc::killpg(group, signal);
+        }
+    }
+}
+
+/// PID of the daemon from `buck2 status`, which prints JSON when a daemon
+/// is running and a note otherwise
+fn parse_status_pid(stdout: &str) -> Option<u32> {
+    let status: serde_json::Value = serde_json::from_str(stdout).ok()?;
+    status
+        .pointer("/process_info/pid")
+        .and_then(|pid| pid.as_u64())
+        .map(|pid| pid as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// A buck2 stand-in whose daemon is a pid in a state file, and whose
+    /// `query` and `build` hang
+    fn fake_buck(dir: &Path, version: &str) -> PathBuf {
+        let path = dir.join("buck2");
+        let state = dir.join("daemon-pid");
+        let script = format!(
+            r#"#!/bin/sh
+[ "$1" = --version ] && {{ echo "buck2 {version}"; exit 0; }}
+[ "$1" = --isolation-dir ] && [ "$2" = buckos ] || exit 3
+case "$3" in
+status)
+    if [ -f {state} ]; then
+        echo "{{\"process_info\": {{\"pid\": $(cat {state})}}}}"
+    else
+        echo "no buckd running"
+    fi ;;
+server) [ -f {state} ] || echo $$ > {state} ;;
+kill) rm -f {state} ;;
+query) sleep 30 ;;
+build) echo $$ > {state}.build; sleep 30 ;;
+esac
+"#,
+            version = version,
+            state = state.display()
+        );
+        std::fs::write(&path, script).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn daemon(dir: &Path, buck: PathBuf) -> BuckDaemon {
+        BuckDaemon::new(
+            buck,
+            dir.to_path_buf(),
+            &dir.join("state"),
+            BuckDaemonConfig {
+                command_timeout: 1,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_parse_status_pid() {
+        assert_eq!(
+            parse_status_pid(r#"{"process_info": {"pid": 4242, "http_port": 0}}"#),
+            Some(4242)
+        );
+        assert_eq!(parse_status_pid("no buckd running\n"), None);
+    }
+
+    #[tokio::test]
+    async fn test_restart_after_upgrade() {
+        let dir = tempfile::tempdir().unwrap();
+        let daemon_v1 = daemon(dir.path(), fake_buck(dir.path(), "2024-01-01"));
+        assert!(!daemon_v1.status().await.unwrap().running());
+
+        let started = daemon_v1.ensure_running().await.unwrap();
+        let pid = started.pid.unwrap();
+        assert_eq!(started.daemon_version.as_deref(), Some("buck2 2024-01-01"));
+        assert_eq!(daemon_v1.ensure_running().await.unwrap().pid, Some(pid));
+
+        let daemon_v2 = daemon(dir.path(), fake_buck(dir.path(), "2024-06-01"));
+        assert!(daemon_v2.status().await.unwrap().version_mismatch());
+        let restarted = daemon_v2.ensure_running().await.unwrap();
+        assert_ne!(restarted.pid, Some(pid));
+        assert!(!restarted.version_mismatch());
+    }
+
+    #[tokio::test]
+    async fn test_hung_command_restarts_daemon() {
+        let dir = tempfile::tempdir().unwrap();
+        let daemon = daemon(dir.path(), fake_buck(dir.path(), "2024-01-01"));
+        let pid = daemon.start().await.unwrap().pid;
+
+        let mut cmd = daemon.command();
+        cmd.arg("query");
+        let err = daemon
+            .run(&mut cmd, daemon.command_timeout())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("restarted"), "{}", err);
+        let status = daemon.status().await.unwrap();
+        assert!(status.running());
+        assert_ne!(status.pid, pid);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_build_is_stopped() {
+        let dir = tempfile::tempdir().unwrap();
+        let daemon = daemon(dir.path(), fake_buck(dir.path(), "2024-01-01"));
+        let cancellation = Cancellation::new();
+        let canceller = cancellation.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            canceller.cancel();
+        });
+
+        let started = std::time::Instant::now();
+        let mut cmd = daemon.command();
+        cmd.arg("build");
+        let err = daemon
+            .run_cancellable(&mut cmd, None, &cancellation)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Interrupted { .. }), "{}", err);
+        assert!(started.elapsed() < CANCEL_GRACE);
+
+        // The client was stopped and reaped
+        let pid: libc::pid_t = std::fs::read_to_string(dir.path().join("daemon-pid.build"))
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        // SAFETY: kill(2) has no memory safety requirements
+        assert_eq!(unsafe { libc::kill(pid, 0) }, -1);
+
+        // Nothing starts once cancelled
+        let mut cmd = daemon.command();
+        cmd.arg("build");
+        assert!(daemon
+            .run_cancellable(&mut cmd, None, &cancellation)
+            .await
+            .is_err());
+    }
+}
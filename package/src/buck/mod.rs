@@ -4,21 +4,22 @@
 
 pub mod buckconfig;
 pub mod config_sync;
+pub mod daemon;
 
 pub use buckconfig::{BuckConfigFile, BuckConfigOptions, BuckConfigSection};
 pub use config_sync::sync_config_to_repo;
+pub use daemon::{BuckDaemon, BuckDaemonConfig, DaemonStatus};
 
 use crate::config::Config;
 use crate::{BuildOptions, BuildResult, Error, Result, UseConfig};
 use std::path::PathBuf;
-use std::process::Stdio;
-use tokio::process::Command;
-use tracing::{debug, error, info};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{debug, error, info, warn};
 
 /// Buck2 build system integration
 pub struct BuckIntegration {
-    /// Path to Buck2 executable
-    buck_path: PathBuf,
+    /// Buck2 daemon the commands run against
+    daemon: BuckDaemon,
     /// Path to Buck targets repository
     repo_path: PathBuf,
     /// Build output directory
@@ -29,6 +30,8 @@ pub struct BuckIntegration {
     config_options: BuckConfigOptions,
     /// USE flag configuration for modifier args
     use_config: Option<UseConfig>,
+    /// Whether the daemon was checked before the first build
+    daemon_checked: AtomicBool,
 }
 
 impl BuckIntegration {
@@ -39,38 +42,56 @@ impl BuckIntegration {
 
     /// Create a new Buck integration with custom config options
     pub fn with_config_options(config: &Config, config_options: BuckConfigOptions) -> Result<Self> {
-        let buck_path = config.buck_path.clone();
         let repo_path = config.buck_repo.clone();
         let output_dir = config.cache_dir.join("buck-out");
         let use_config = Some(config.use_flags.clone());
 
-        // Verify Buck exists
-        if !buck_path.exists() {
-            // Try to find it in PATH
-            if let Ok(found) = which::which("buck2") {
-                Ok(Self {
-                    buck_path: found,
-                    repo_path,
-                    output_dir,
-                    jobs: config.parallelism,
-                    config_options,
-                    use_config,
-                })
-            } else {
-                Err(Error::BuckError(format!(
-                    "Buck2 not found at {:?} or in PATH",
-                    buck_path
-                )))
-            }
+        // Verify Buck exists, or try to find it in PATH
+        let buck_path = if config.buck_path.exists() {
+            config.buck_path.clone()
+        } else if let Ok(found) = which::which("buck2") {
+            found
         } else {
-            Ok(Self {
+            return Err(Error::BuckError(format!(
+                "Buck2 not found at {:?} or in PATH",
+                config.buck_path
+            )));
+        };
+
+        Ok(Self {
+            daemon: BuckDaemon::new(
                 buck_path,
-                repo_path,
-                output_dir,
-                jobs: config.parallelism,
-                config_options,
-                use_config,
-            })
+                repo_path.clone(),
+                &config.cache_dir,
+                config.buck_daemon.clone(),
+            ),
+            repo_path,
+            output_dir,
+            jobs: config.parallelism,
+            config_options,
+            use_config,
+            daemon_checked: AtomicBool::new(false),
+        })
+    }
+
+    /// The Buck2 daemon
+    pub fn daemon(&self) -> &BuckDaemon {
+        &self.daemon
+    }
+
+    /// Parallel jobs of builds that don't set their own
+    pub fn jobs(&self) -> usize {
+        self.jobs
+    }
+
+    /// Start the daemon, or replace one left by an older Buck2, before the
+    /// first build; buck2 starts one itself if this fails
+    async fn check_daemon(&self) {
+        if self.daemon_checked.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        if let Err(e) = self.daemon.ensure_running().await {
+            warn!("Failed to check the Buck2 daemon: {}", e);
         }
     }
 
@@ -132,13 +153,10 @@ impl BuckIntegration {
         let start = std::time::Instant::now();
 
         info!("Building Buck target: {}", target);
+        self.check_daemon().await;
 
-        let mut cmd = Command::new(&self.buck_path);
-        cmd.arg("build")
-            .arg(target)
-            .current_dir(&self.repo_path)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+        let mut cmd = self.daemon.command();
+        cmd.arg("build").arg(target);
 
         // Add job count
         let jobs = opts.jobs.unwrap_or(self.jobs);
@@ -173,12 +191,15 @@ impl BuckIntegration {
             }
         }
 
-        debug!("Running: {:?}", cmd);
-
-        let output = cmd
-            .output()
-            .await
-            .map_err(|e| Error::BuckError(format!("Failed to execute Buck: {}", e)))?;
+        let timeout = self.daemon.build_timeout();
+        let output = match &opts.cancellation {
+            Some(cancellation) => {
+                self.daemon
+                    .run_cancellable(&mut cmd, timeout, cancellation)
+                    .await?
+            }
+            None => self.daemon.run(&mut cmd, timeout).await?,
+        };
 
         let duration = start.elapsed();
         let stdout = String::from_utf8_lossy(&output.stdout).to_string();
@@ -220,12 +241,10 @@ impl BuckIntegration {
         let start = std::time::Instant::now();
 
         info!("Building {} Buck targets", targets.len());
+        self.check_daemon().await;
 
-        let mut cmd = Command::new(&self.buck_path);
-        cmd.arg("build")
-            .current_dir(&self.repo_path)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+        let mut cmd = self.daemon.command();
+        cmd.arg("build");
 
         // Add all targets
         for target in targets {
@@ -264,10 +283,10 @@ impl BuckIntegration {
             }
         }
 
-        let output = cmd
-            .output()
-            .await
-            .map_err(|e| Error::BuckError(format!("Failed to execute Buck: {}", e)))?;
+        let output = self
+            .daemon
+            .run(&mut cmd, self.daemon.build_timeout())
+            .await?;
 
         let duration = start.elapsed();
         let stdout = String::from_utf8_lossy(&output.stdout).to_string();
@@ -299,17 +318,13 @@ impl BuckIntegration {
 
     /// Query target information
     pub async fn query(&self, pattern: &str) -> Result<Vec<String>> {
-        let mut cmd = Command::new(&self.buck_path);
-        cmd.arg("query")
-            .arg(pattern)
-            .current_dir(&self.repo_path)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-
-        let output = cmd
-            .output()
-            .await
-            .map_err(|e| Error::BuckError(format!("Failed to query Buck: {}", e)))?;
+        let mut cmd = self.daemon.command();
+        cmd.arg("query").arg(pattern);
+
+        let output = self
+            .daemon
+            .run(&mut cmd, self.daemon.command_timeout())
+            .await?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -322,17 +337,13 @@ impl BuckIntegration {
 
     /// Get target dependencies
     pub async fn deps(&self, target: &str) -> Result<Vec<String>> {
-        let mut cmd = Command::new(&self.buck_path);
-        cmd.arg("query")
-            .arg(format!("deps({})", target))
-            .current_dir(&self.repo_path)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-
-        let output = cmd
-            .output()
-            .await
-            .map_err(|e| Error::BuckError(format!("Failed to query deps: {}", e)))?;
+        let mut cmd = self.daemon.command();
+        cmd.arg("query").arg(format!("deps({})", target));
+
+        let output = self
+            .daemon
+            .run(&mut cmd, self.daemon.command_timeout())
+            .await?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -345,17 +356,13 @@ impl BuckIntegration {
 
     /// Get reverse dependencies
     pub async fn rdeps(&self, target: &str) -> Result<Vec<String>> {
-        let mut cmd = Command::new(&self.buck_path);
-        cmd.arg("query")
-            .arg(format!("rdeps(//..., {})", target))
-            .current_dir(&self.repo_path)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-
-        let output = cmd
-            .output()
-            .await
-            .map_err(|e| Error::BuckError(format!("Failed to query rdeps: {}", e)))?;
+        let mut cmd = self.daemon.command();
+        cmd.arg("query").arg(format!("rdeps(//..., {})", target));
+
+        let output = self
+            .daemon
+            .run(&mut cmd, self.daemon.command_timeout())
+            .await?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -370,16 +377,13 @@ impl BuckIntegration {
     pub async fn clean(&self) -> Result<()> {
         info!("Cleaning Buck build outputs");
 
-        let mut cmd = Command::new(&self.buck_path);
-        cmd.arg("clean")
-            .current_dir(&self.repo_path)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+        let mut cmd = self.daemon.command();
+        cmd.arg("clean");
 
-        let output = cmd
-            .output()
-            .await
-            .map_err(|e| Error::BuckError(format!("Failed to clean: {}", e)))?;
+        let output = self
+            .daemon
+            .run(&mut cmd, self.daemon.command_timeout())
+            .await?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -494,18 +498,13 @@ impl BuckIntegration {
 
     /// Find build output for a target
     async fn find_build_output(&self, target: &str) -> Result<Option<PathBuf>> {
-        let mut cmd = Command::new(&self.buck_path);
-        cmd.arg("build")
-            .arg("--show-output")
-            .arg(target)
-            .current_dir(&self.repo_path)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-
-        let output = cmd
-            .output()
-            .await
-            .map_err(|e| Error::BuckError(format!("Failed to get output path: {}", e)))?;
+        let mut cmd = self.daemon.command();
+        cmd.arg("build").arg("--show-output").arg(target);
+
+        let output = self
+            .daemon
+            .run(&mut cmd, self.daemon.build_timeout())
+            .await?;
 
         if output.status.success() {
             let stdout = String::from_utf8_lossy(&output.stdout);
@@ -534,34 +533,26 @@ impl BuckIntegration {
 
     /// Get audit information for a target
     pub async fn audit(&self, target: &str) -> Result<String> {
-        let mut cmd = Command::new(&self.buck_path);
-        cmd.arg("audit")
-            .arg("includes")
-            .arg(target)
-            .current_dir(&self.repo_path)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-
-        let output = cmd
-            .output()
-            .await
-            .map_err(|e| Error::BuckError(format!("Failed to audit: {}", e)))?;
+        let mut cmd = self.daemon.command();
+        cmd.arg("audit").arg("includes").arg(target);
+
+        let output = self
+            .daemon
+            .run(&mut cmd, self.daemon.command_timeout())
+            .await?;
 
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
     }
 
     /// Generate project files
     pub async fn project(&self) -> Result<()> {
-        let mut cmd = Command::new(&self.buck_path);
-        cmd.arg("project")
-            .current_dir(&self.repo_path)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-
-        let output = cmd
-            .output()
-            .await
-            .map_err(|e| Error::BuckError(format!("Failed to generate project: {}", e)))?;
+        let mut cmd = self.daemon.command();
+        cmd.arg("project");
+
+        let output = self
+            .daemon
+            .run(&mut cmd, self.daemon.command_timeout())
+            .await?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
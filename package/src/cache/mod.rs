@@ -1,10 +1,188 @@
 //! Package cache for downloads and build artifacts
 
+use crate::checksums::{self, ChecksumDb, DistfileRecord, MirrorAudit, MirrorHealth};
+use crate::peer::PeerCache;
 use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::io::Read;
 use std::path::{Path, PathBuf};
-use tracing::{info, warn};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info, warn};
+
+/// Download settings, named after their make.conf counterparts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FetchConfig {
+    /// Mirrors tried after a package's own URL (GENTOO_MIRRORS)
+    pub mirrors: Vec<String>,
+    /// Attempts per URL before failing over to the next one
+    pub retries: u32,
+    /// Delay before the first retry in milliseconds, doubled on each failure
+    pub retry_delay_ms: u64,
+    /// Upper bound for the retry delay in milliseconds
+    pub max_retry_delay_ms: u64,
+    /// Timeout for connecting and for a stalled transfer, in seconds
+    pub timeout: u64,
+    /// Proxy for http:// URLs; `http_proxy` from the environment otherwise
+    pub http_proxy: Option<String>,
+    /// Proxy for https:// URLs; `https_proxy` from the environment otherwise
+    pub https_proxy: Option<String>,
+    /// Comma-separated hosts that bypass the proxy; `no_proxy` from the
+    /// environment otherwise
+    pub no_proxy: Option<String>,
+    /// Bandwidth limit per download (e.g. "500K", "2M"), like wget --limit-rate
+    pub rate_limit: Option<String>,
+    /// Bandwidth limit shared by all downloads
+    pub global_rate_limit: Option<String>,
+    /// Smallest partial file worth resuming (PORTAGE_FETCH_RESUME_MIN_SIZE)
+    pub resume_min_size: String,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        Self {
+            mirrors: Vec::new(),
+            retries: 3,
+            retry_delay_ms: 1000,
+            max_retry_delay_ms: 30_000,
+            timeout: 60,
+            http_proxy: None,
+            https_proxy: None,
+            no_proxy: None,
+            rate_limit: None,
+            global_rate_limit: None,
+            resume_min_size: "350K".to_string(),
+        }
+    }
+}
+
+impl FetchConfig {
+    /// Build an HTTP client honouring the proxy and timeout settings
+    pub fn client(&self) -> Result<reqwest::Client> {
+        let mut builder =
+            reqwest::Client::builder().connect_timeout(Duration::from_secs(self.timeout));
+
+        // With nothing configured reqwest reads the *_proxy environment.
+        // Setting any proxy turns that off for every scheme, so the schemes
+        // left unset are filled from the environment here instead.
+        if self.http_proxy.is_some() || self.https_proxy.is_some() || self.no_proxy.is_some() {
+            let no_proxy = match &self.no_proxy {
+                Some(hosts) => reqwest::NoProxy::from_string(hosts),
+                None => reqwest::NoProxy::from_env(),
+            };
+            let proxy = |result: reqwest::Result<reqwest::Proxy>| {
+                result
+                    .map(|p| p.no_proxy(no_proxy.clone()))
+                    .map_err(|e| Error::ConfigError(format!("invalid proxy: {}", e)))
+            };
+            let http = self
+                .http_proxy
+                .clone()
+                .or_else(|| env_proxy(&["HTTP_PROXY", "http_proxy"]));
+            let https = self
+                .https_proxy
+                .clone()
+                .or_else(|| env_proxy(&["HTTPS_PROXY", "https_proxy"]));
+            if let Some(url) = http {
+                builder = builder.proxy(proxy(reqwest::Proxy::http(url))?);
+            }
+            if let Some(url) = https {
+                builder = builder.proxy(proxy(reqwest::Proxy::https(url))?);
+            }
+            // Proxies are tried in order, so this only catches what is left
+            if let Some(url) = env_proxy(&["ALL_PROXY", "all_proxy"]) {
+                builder = builder.proxy(proxy(reqwest::Proxy::all(url))?);
+            }
+        }
+
+        builder
+            .build()
+            .map_err(|e| Error::NetworkError(e.to_string()))
+    }
+
+    /// Delay before retry number `attempt` (starting at 0)
+    pub fn retry_delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .retry_delay_ms
+            .saturating_mul(1u64 << attempt.min(16))
+            .min(self.max_retry_delay_ms);
+        Duration::from_millis(delay)
+    }
+}
+
+/// First byte of a `Content-Range: bytes <start>-<end>/<total>` header
+fn content_range_start(value: &str) -> Option<u64> {
+    let range = value.trim().strip_prefix("bytes ")?;
+    let (start, _) = range.split_once('-')?;
+    start.trim().parse().ok()
+}
+
+/// The first of the environment variables `names` that is set and not empty
+fn env_proxy(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|v| !v.is_empty()))
+}
+
+/// Parse a size such as `350K`, `2M` or `1G` into bytes
+pub fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let (digits, multiplier) = match s.chars().last()?.to_ascii_uppercase() {
+        'K' => (&s[..s.len() - 1], 1024),
+        'M' => (&s[..s.len() - 1], 1024 * 1024),
+        'G' => (&s[..s.len() - 1], 1024 * 1024 * 1024),
+        _ => (s, 1),
+    };
+    digits.trim().parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Token bucket limiting throughput to a number of bytes per second
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate: u64,
+    state: tokio::sync::Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    /// Limit to `rate` bytes per second, allowing one second of burst
+    pub fn new(rate: u64) -> Self {
+        Self {
+            rate: rate.max(1),
+            state: tokio::sync::Mutex::new((rate as f64, Instant::now())),
+        }
+    }
+
+    /// Wait until `bytes` may be sent
+    pub async fn acquire(&self, bytes: u64) {
+        let wait = {
+            let mut state = self.state.lock().await;
+            let (tokens, last) = &mut *state;
+            let now = Instant::now();
+            *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * self.rate as f64)
+                .min(self.rate as f64);
+            *last = now;
+            *tokens -= bytes as f64;
+            if *tokens < 0.0 {
+                Duration::from_secs_f64(-*tokens / self.rate as f64)
+            } else {
+                Duration::ZERO
+            }
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Why a single download attempt failed
+enum AttemptError {
+    /// Worth retrying against the same URL
+    Transient(String),
+    /// Give up on this URL and try the next one
+    Fatal(String),
+}
 
 /// Package cache manager
 pub struct PackageCache {
@@ -16,6 +194,16 @@ pub struct PackageCache {
     packages_dir: PathBuf,
     /// Temporary directory for in-progress downloads
     tmp_dir: PathBuf,
+    /// LAN peers tried before the upstream URL
+    peers: Option<PeerCache>,
+    /// Download settings
+    fetch: FetchConfig,
+    /// HTTP client built from `fetch`
+    client: reqwest::Client,
+    /// Limit shared by all downloads, if configured
+    global_limit: Option<RateLimiter>,
+    /// Verified distfiles and mirror health
+    checksums: parking_lot::Mutex<ChecksumDb>,
 }
 
 impl PackageCache {
@@ -30,14 +218,51 @@ impl PackageCache {
         std::fs::create_dir_all(&packages_dir)?;
         std::fs::create_dir_all(&tmp_dir)?;
 
+        let fetch = FetchConfig::default();
+        let client = fetch.client()?;
+
+        let checksums_path = base_dir.join("checksums.json");
+        let checksums = ChecksumDb::load(&checksums_path).unwrap_or_else(|e| {
+            warn!("Ignoring unreadable checksum database: {}", e);
+            ChecksumDb::new(&checksums_path)
+        });
+
         Ok(Self {
             base_dir: base_dir.to_path_buf(),
             distfiles_dir,
             packages_dir,
             tmp_dir,
+            peers: None,
+            fetch,
+            client,
+            global_limit: None,
+            checksums: parking_lot::Mutex::new(checksums),
         })
     }
 
+    /// Try LAN peers for distfiles with a known hash before downloading
+    pub fn with_peers(mut self, peers: PeerCache) -> Self {
+        self.peers = Some(peers);
+        self
+    }
+
+    /// Use the given download settings
+    pub fn with_fetch_config(mut self, fetch: FetchConfig) -> Result<Self> {
+        self.client = fetch.client()?;
+        self.global_limit = match &fetch.global_rate_limit {
+            Some(limit) => Some(RateLimiter::new(parse_size(limit).ok_or_else(|| {
+                Error::ConfigError(format!("invalid global_rate_limit: {}", limit))
+            })?)),
+            None => None,
+        };
+        if let Some(limit) = &fetch.rate_limit {
+            parse_size(limit)
+                .ok_or_else(|| Error::ConfigError(format!("invalid rate_limit: {}", limit)))?;
+        }
+        self.fetch = fetch;
+        Ok(self)
+    }
+
     /// Get path to a distfile
     pub fn distfile_path(&self, filename: &str) -> PathBuf {
         self.distfiles_dir.join(filename)
@@ -61,6 +286,10 @@ impl PackageCache {
     }
 
     /// Download a file to the cache
+    ///
+    /// `url` is tried first, then each configured mirror. Every URL gets
+    /// `retries` attempts with exponential backoff, and an interrupted
+    /// download resumes from its partial file instead of starting over.
     pub async fn download(
         &self,
         url: &str,
@@ -85,52 +314,244 @@ impl PackageCache {
             }
         }
 
-        info!("Downloading: {}", url);
-
         // Download to temp file first
         let tmp_path = self.tmp_dir.join(format!("{}.partial", filename));
 
-        let client = reqwest::Client::new();
-        let response = client
-            .get(url)
-            .send()
-            .await
-            .map_err(|e| Error::DownloadFailed {
-                url: url.to_string(),
-                message: e.to_string(),
-            })?;
-
-        if !response.status().is_success() {
-            return Err(Error::DownloadFailed {
-                url: url.to_string(),
-                message: format!("HTTP {}", response.status()),
-            });
+        if let (Some(peers), Some(hash)) = (&self.peers, expected_hash) {
+            let digest = crate::peer::Digest::Sha256(hash.to_string());
+            let peer_path = self.tmp_dir.join(format!("{}.peer", filename));
+            if peers.fetch(filename, &digest, &peer_path).await.is_some() {
+                std::fs::rename(&peer_path, &dest_path)?;
+                return Ok(dest_path);
+            }
         }
 
-        let bytes = response.bytes().await.map_err(|e| Error::DownloadFailed {
-            url: url.to_string(),
-            message: e.to_string(),
-        })?;
-
-        std::fs::write(&tmp_path, &bytes)?;
-
-        // Verify hash
-        if let Some(expected) = expected_hash {
-            let actual = compute_sha256(&tmp_path)?;
-            if actual != expected {
-                std::fs::remove_file(&tmp_path)?;
-                return Err(Error::ChecksumMismatch {
-                    path: filename.to_string(),
-                    expected: expected.to_string(),
-                    actual,
-                });
+        // The package's own URL first, then mirrors healthiest first
+        let mut urls = vec![(url.to_string(), None)];
+        let mirrors = self.checksums.lock().rank(&self.fetch.mirrors);
+        urls.extend(mirrors.into_iter().map(|m| {
+            let url = format!("{}/{}", m.trim_end_matches('/'), filename);
+            (url, Some(m))
+        }));
+
+        let mut last_error = String::new();
+        for (i, (url, mirror)) in urls.iter().enumerate() {
+            // Another server's copy may differ, so never splice onto it
+            if i > 0 {
+                let _ = std::fs::remove_file(&tmp_path);
+            }
+            for attempt in 0..self.fetch.retries.max(1) {
+                if attempt > 0 {
+                    let delay = self.fetch.retry_delay(attempt - 1);
+                    debug!("Retrying {} in {:?}", url, delay);
+                    tokio::time::sleep(delay).await;
+                }
+
+                let started = Instant::now();
+                let give_up = match self.fetch_to(url, &tmp_path).await {
+                    Ok(()) => false,
+                    Err(AttemptError::Transient(message)) => {
+                        warn!("Download of {} failed: {}", url, message);
+                        last_error = message;
+                        if attempt + 1 < self.fetch.retries.max(1) {
+                            continue;
+                        }
+                        true
+                    }
+                    Err(AttemptError::Fatal(message)) => {
+                        warn!("Download of {} failed: {}", url, message);
+                        last_error = message;
+                        true
+                    }
+                };
+                if give_up {
+                    if let Some(mirror) = mirror {
+                        self.update_checksums(|db| db.record_failure(mirror));
+                    }
+                    break;
+                }
+                let elapsed = started.elapsed().as_secs_f64();
+
+                // Verify hash
+                if let Some(expected) = expected_hash {
+                    let actual = compute_sha256(&tmp_path)?;
+                    if actual != expected {
+                        // A resumed file may have been spliced onto a stale
+                        // partial; never resume from it again
+                        std::fs::remove_file(&tmp_path)?;
+                        if urls.len() == 1 {
+                            return Err(Error::ChecksumMismatch {
+                                path: filename.to_string(),
+                                expected: expected.to_string(),
+                                actual,
+                            });
+                        }
+                        warn!("Checksum mismatch for {} from {}", filename, url);
+                        if let Some(mirror) = mirror {
+                            self.update_checksums(|db| db.record_mismatch(mirror));
+                        }
+                        last_error = format!("checksum mismatch (got {})", actual);
+                        break;
+                    }
+
+                    let size = std::fs::metadata(&tmp_path)?.len();
+                    let record = DistfileRecord {
+                        sha256: actual,
+                        size,
+                        url: url.clone(),
+                        mirror: mirror.clone(),
+                        fetched_at: checksums::now(),
+                    };
+                    let rate = if elapsed > 0.0 {
+                        size as f64 / elapsed
+                    } else {
+                        0.0
+                    };
+                    self.update_checksums(|db| db.record_fetch(filename, record, rate));
+                }
+
+                // Move to final location
+                std::fs::rename(&tmp_path, &dest_path)?;
+                return Ok(dest_path);
             }
         }
 
-        // Move to final location
-        std::fs::rename(&tmp_path, &dest_path)?;
+        Err(Error::DownloadFailed {
+            url: url.to_string(),
+            message: last_error,
+        })
+    }
 
-        Ok(dest_path)
+    /// Apply a change to the checksum database and persist it
+    fn update_checksums(&self, change: impl FnOnce(&mut ChecksumDb)) {
+        let mut db = self.checksums.lock();
+        change(&mut db);
+        if let Err(e) = db.save() {
+            warn!("Failed to save checksum database: {}", e);
+        }
+    }
+
+    /// Health of the configured mirrors, healthiest first
+    pub fn mirror_health(&self) -> Vec<(String, MirrorHealth)> {
+        let db = self.checksums.lock();
+        db.rank(&self.fetch.mirrors)
+            .into_iter()
+            .map(|m| {
+                let health = db.mirrors.get(&m).cloned().unwrap_or_default();
+                (m, health)
+            })
+            .collect()
+    }
+
+    /// Re-download up to `sample_size` recorded distfiles from every mirror
+    ///
+    /// Each mirror gets the same random sample; results update the health
+    /// scores used to order mirrors.
+    pub async fn audit_mirrors(&self, sample_size: usize) -> Result<Vec<MirrorAudit>> {
+        let sample = self.checksums.lock().sample(sample_size);
+        if sample.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let timeout = Duration::from_secs(self.fetch.timeout.max(1) * 10);
+        let mut audits = Vec::new();
+        for mirror in &self.fetch.mirrors {
+            info!("Auditing mirror {}", mirror);
+            let mut audit = checksums::audit_mirror(&self.client, mirror, &sample, timeout).await;
+            self.update_checksums(|db| db.record_audit(&mut audit));
+            audits.push(audit);
+        }
+        Ok(audits)
+    }
+
+    /// One download attempt, resuming `tmp_path` if it holds enough data
+    async fn fetch_to(&self, url: &str, tmp_path: &Path) -> std::result::Result<(), AttemptError> {
+        let transient = |e: &dyn std::fmt::Display| AttemptError::Transient(e.to_string());
+
+        let partial = std::fs::metadata(tmp_path).map(|m| m.len()).unwrap_or(0);
+        let resume_min = parse_size(&self.fetch.resume_min_size).unwrap_or(0);
+        let offset = if partial > 0 && partial >= resume_min {
+            partial
+        } else {
+            0
+        };
+
+        info!("Downloading: {}", url);
+        let mut request = self.client.get(url);
+        if offset > 0 {
+            debug!("Resuming {} at byte {}", url, offset);
+            request = request.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+        }
+        let response = request.send().await.map_err(|e| transient(&e))?;
+
+        let status = response.status();
+        let append = match status {
+            reqwest::StatusCode::PARTIAL_CONTENT if offset > 0 => {
+                let start = response
+                    .headers()
+                    .get(reqwest::header::CONTENT_RANGE)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(content_range_start);
+                if start != Some(offset) {
+                    // Appending would leave a gap or an overlap
+                    let _ = std::fs::remove_file(tmp_path);
+                    return Err(AttemptError::Transient(format!(
+                        "server resumed at {:?} instead of byte {}",
+                        start, offset
+                    )));
+                }
+                true
+            }
+            reqwest::StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => {
+                // Stale or already complete partial; start over next attempt
+                let _ = std::fs::remove_file(tmp_path);
+                return Err(AttemptError::Transient(format!("HTTP {}", status)));
+            }
+            s if s.is_success() => false,
+            s if s.is_server_error() || s == reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                return Err(AttemptError::Transient(format!("HTTP {}", status)));
+            }
+            _ => return Err(AttemptError::Fatal(format!("HTTP {}", status))),
+        };
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(tmp_path)
+            .await
+            .map_err(|e| transient(&e))?;
+
+        let per_download = self
+            .fetch
+            .rate_limit
+            .as_deref()
+            .and_then(parse_size)
+            .map(RateLimiter::new);
+
+        let stall = Duration::from_secs(self.fetch.timeout);
+        let mut stream = response.bytes_stream();
+        use futures::StreamExt;
+        loop {
+            let Ok(next) = tokio::time::timeout(stall, stream.next()).await else {
+                return Err(AttemptError::Transient("transfer stalled".to_string()));
+            };
+            let Some(chunk) = next else {
+                break;
+            };
+            let chunk = chunk.map_err(|e| transient(&e))?;
+            if let Some(limit) = &per_download {
+                limit.acquire(chunk.len() as u64).await;
+            }
+            if let Some(limit) = &self.global_limit {
+                limit.acquire(chunk.len() as u64).await;
+            }
+            file.write_all(&chunk).await.map_err(|e| transient(&e))?;
+        }
+        file.flush().await.map_err(|e| transient(&e))?;
+
+        Ok(())
     }
 
     /// Store a built package in the cache
@@ -231,19 +652,7 @@ pub fn compute_sha256(path: &Path) -> Result<String> {
 
 /// Compute BLAKE3 hash of a file
 pub fn compute_blake3(path: &Path) -> Result<String> {
-    let mut file = std::fs::File::open(path)?;
-    let mut hasher = blake3::Hasher::new();
-    let mut buffer = [0u8; 8192];
-
-    loop {
-        let n = file.read(&mut buffer)?;
-        if n == 0 {
-            break;
-        }
-        hasher.update(&buffer[..n]);
-    }
-
-    Ok(hasher.finalize().to_hex().to_string())
+    Ok(buckos_core::hash::hash_file(path)?)
 }
 
 /// Extract a tarball
@@ -68,6 +68,8 @@ fn cmake_3_28_3() -> PackageInfo {
         installed_size: 55_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -112,6 +114,8 @@ fn cmake_3_29_0() -> PackageInfo {
         installed_size: 56_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -140,6 +144,8 @@ fn meson_1_3_2() -> PackageInfo {
         installed_size: 8_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -172,6 +178,8 @@ fn meson_1_4_0() -> PackageInfo {
         installed_size: 8_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -207,6 +215,8 @@ fn ninja_1_11_1() -> PackageInfo {
         installed_size: 600_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -246,6 +256,8 @@ fn ninja_1_12_0() -> PackageInfo {
         installed_size: 620_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -276,6 +288,8 @@ fn scons_4_7_0() -> PackageInfo {
         installed_size: 12_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -304,6 +318,8 @@ fn bazel_7_1_0() -> PackageInfo {
         installed_size: 400_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -332,5 +348,7 @@ fn buck2_2024_01() -> PackageInfo {
         installed_size: 150_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
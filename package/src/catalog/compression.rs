@@ -69,6 +69,8 @@ fn zlib_1_3_1() -> PackageInfo {
         installed_size: 1_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -104,6 +106,8 @@ fn xz_utils_5_4_6() -> PackageInfo {
         installed_size: 3_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -143,6 +147,8 @@ fn xz_utils_5_6_1() -> PackageInfo {
         installed_size: 3_700_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -183,6 +189,8 @@ fn zstd_1_5_5() -> PackageInfo {
         installed_size: 5_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -227,6 +235,8 @@ fn zstd_1_5_6() -> PackageInfo {
         installed_size: 5_200_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -256,6 +266,8 @@ fn bzip2_1_0_8() -> PackageInfo {
         installed_size: 1_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -284,6 +296,8 @@ fn lz4_1_9_4() -> PackageInfo {
         installed_size: 1_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -318,6 +332,8 @@ fn brotli_1_1_0() -> PackageInfo {
         installed_size: 1_200_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -374,6 +390,8 @@ fn libarchive_3_7_2() -> PackageInfo {
         installed_size: 15_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -403,6 +421,8 @@ fn pigz_2_8() -> PackageInfo {
         installed_size: 200_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -433,6 +453,8 @@ fn pbzip2_1_1_13() -> PackageInfo {
         installed_size: 150_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -466,6 +488,8 @@ fn snappy_1_1_10() -> PackageInfo {
         installed_size: 1_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -493,6 +517,8 @@ fn lzip_1_24() -> PackageInfo {
         installed_size: 400_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -518,5 +544,7 @@ fn lzop_1_04() -> PackageInfo {
         installed_size: 300_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
@@ -91,6 +91,8 @@ fn glibc_2_38() -> PackageInfo {
         installed_size: 45_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -127,6 +129,8 @@ fn glibc_2_39() -> PackageInfo {
         installed_size: 46_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -155,6 +159,8 @@ fn musl_1_2_4() -> PackageInfo {
         installed_size: 3_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -183,6 +189,8 @@ fn musl_1_2_5() -> PackageInfo {
         installed_size: 3_600_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -210,6 +218,8 @@ fn libgcc_13_2_0() -> PackageInfo {
         installed_size: 500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -233,6 +243,8 @@ fn libgcc_14_1_0() -> PackageInfo {
         installed_size: 520_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -260,6 +272,8 @@ fn linux_headers_6_6() -> PackageInfo {
         installed_size: 8_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -291,6 +305,8 @@ fn linux_headers_6_8() -> PackageInfo {
         installed_size: 8_200_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -322,6 +338,8 @@ fn libxcrypt_4_4_36() -> PackageInfo {
         installed_size: 1_200_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -349,6 +367,8 @@ fn timezone_data_2024a() -> PackageInfo {
         installed_size: 2_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -382,6 +402,8 @@ fn ncurses_6_4() -> PackageInfo {
         installed_size: 8_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -410,6 +432,8 @@ fn readline_8_2() -> PackageInfo {
         installed_size: 5_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -443,6 +467,8 @@ fn libffi_3_4_6() -> PackageInfo {
         installed_size: 2_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -472,6 +498,8 @@ fn gmp_6_3_0() -> PackageInfo {
         installed_size: 4_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -497,6 +525,8 @@ fn mpfr_4_2_1() -> PackageInfo {
         installed_size: 3_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -523,6 +553,8 @@ fn mpc_1_3_1() -> PackageInfo {
         installed_size: 1_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -558,6 +590,8 @@ fn pam_1_6_1() -> PackageInfo {
         installed_size: 3_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -589,6 +623,8 @@ fn libcap_2_69() -> PackageInfo {
         installed_size: 400_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -617,6 +653,8 @@ fn libseccomp_2_5_5() -> PackageInfo {
         installed_size: 1_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -650,6 +688,8 @@ fn attr_2_5_2() -> PackageInfo {
         installed_size: 900_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -683,5 +723,7 @@ fn acl_2_3_2() -> PackageInfo {
         installed_size: 1_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
@@ -75,6 +75,8 @@ fn openssl_3_2_1() -> PackageInfo {
         installed_size: 40_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -114,6 +116,8 @@ fn openssl_3_3_0() -> PackageInfo {
         installed_size: 42_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -172,6 +176,8 @@ fn curl_8_6_0() -> PackageInfo {
         installed_size: 8_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -224,6 +230,8 @@ fn curl_8_7_1() -> PackageInfo {
         installed_size: 8_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -273,6 +281,8 @@ fn wget_1_21_4() -> PackageInfo {
         installed_size: 10_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -319,6 +329,8 @@ fn wget_1_24_5() -> PackageInfo {
         installed_size: 11_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -346,6 +358,8 @@ fn ca_certificates_20240203() -> PackageInfo {
         installed_size: 600_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -387,6 +401,8 @@ fn nghttp2_1_60_0() -> PackageInfo {
         installed_size: 4_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -422,6 +438,8 @@ fn libssh2_1_11_0() -> PackageInfo {
         installed_size: 2_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -479,6 +497,8 @@ fn gnutls_3_8_4() -> PackageInfo {
         installed_size: 15_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -526,6 +546,8 @@ fn openssh_9_7() -> PackageInfo {
         installed_size: 5_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -568,6 +590,8 @@ fn iproute2_6_7_0() -> PackageInfo {
         installed_size: 3_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -606,6 +630,8 @@ fn iputils_20240117() -> PackageInfo {
         installed_size: 800_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -644,6 +670,8 @@ fn net_tools_2_10() -> PackageInfo {
         installed_size: 1_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -682,5 +710,7 @@ fn bind_tools_9_18_24() -> PackageInfo {
         installed_size: 8_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
@@ -111,6 +111,8 @@ fn systemd_255() -> PackageInfo {
         installed_size: 45_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -185,6 +187,8 @@ fn systemd_256() -> PackageInfo {
         installed_size: 48_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -227,6 +231,8 @@ fn openrc_0_54() -> PackageInfo {
         installed_size: 2_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -267,6 +273,8 @@ fn dbus_1_14_10() -> PackageInfo {
         installed_size: 6_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -306,6 +314,8 @@ fn eudev_3_2_14() -> PackageInfo {
         installed_size: 3_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -351,6 +361,8 @@ fn polkit_124() -> PackageInfo {
         installed_size: 3_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -395,6 +407,8 @@ fn elogind_252_9() -> PackageInfo {
         installed_size: 5_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -429,5 +443,7 @@ fn cronie_1_7_2() -> PackageInfo {
         installed_size: 700_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
@@ -106,6 +106,8 @@ fn gcc_13_2_0() -> PackageInfo {
         installed_size: 350_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -159,6 +161,8 @@ fn gcc_14_1_0() -> PackageInfo {
         installed_size: 360_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -203,6 +207,8 @@ fn llvm_17_0_6() -> PackageInfo {
         installed_size: 250_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -247,6 +253,8 @@ fn llvm_18_1_0() -> PackageInfo {
         installed_size: 280_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -284,6 +292,8 @@ fn clang_17_0_6() -> PackageInfo {
         installed_size: 120_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -321,6 +331,8 @@ fn clang_18_1_0() -> PackageInfo {
         installed_size: 130_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -361,6 +373,8 @@ fn binutils_2_41() -> PackageInfo {
         installed_size: 95_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -405,6 +419,8 @@ fn binutils_2_42() -> PackageInfo {
         installed_size: 98_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -434,6 +450,8 @@ fn make_4_4_1() -> PackageInfo {
         installed_size: 3_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -459,6 +477,8 @@ fn autoconf_2_72() -> PackageInfo {
         installed_size: 3_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -484,6 +504,8 @@ fn automake_1_16_5() -> PackageInfo {
         installed_size: 4_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -512,6 +534,8 @@ fn libtool_2_4_7() -> PackageInfo {
         installed_size: 2_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -540,6 +564,8 @@ fn m4_1_4_19() -> PackageInfo {
         installed_size: 2_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -569,6 +595,8 @@ fn bison_3_8_2() -> PackageInfo {
         installed_size: 6_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -602,6 +630,8 @@ fn flex_2_6_4() -> PackageInfo {
         installed_size: 2_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -638,6 +668,8 @@ fn gettext_0_22_5() -> PackageInfo {
         installed_size: 25_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -668,6 +700,8 @@ fn pkgconf_2_1_1() -> PackageInfo {
         installed_size: 600_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -710,6 +744,8 @@ fn rust_1_77_0() -> PackageInfo {
         installed_size: 800_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -752,6 +788,8 @@ fn rust_1_79_0() -> PackageInfo {
         installed_size: 850_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -777,6 +815,8 @@ fn go_1_22_0() -> PackageInfo {
         installed_size: 450_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -827,6 +867,8 @@ fn python_3_11_9() -> PackageInfo {
         installed_size: 100_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -881,6 +923,8 @@ fn python_3_12_3() -> PackageInfo {
         installed_size: 105_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -914,5 +958,7 @@ fn perl_5_38_2() -> PackageInfo {
         installed_size: 55_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
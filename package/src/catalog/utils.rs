@@ -97,6 +97,8 @@ fn coreutils_9_4() -> PackageInfo {
         installed_size: 18_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -145,6 +147,8 @@ fn coreutils_9_5() -> PackageInfo {
         installed_size: 18_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -206,6 +210,8 @@ fn util_linux_2_39() -> PackageInfo {
         installed_size: 25_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -271,6 +277,8 @@ fn util_linux_2_40() -> PackageInfo {
         installed_size: 26_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -303,6 +311,8 @@ fn findutils_4_9_0() -> PackageInfo {
         installed_size: 4_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -335,6 +345,8 @@ fn grep_3_11() -> PackageInfo {
         installed_size: 2_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -368,6 +380,8 @@ fn sed_4_9() -> PackageInfo {
         installed_size: 2_200_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -400,6 +414,8 @@ fn gawk_5_3_0() -> PackageInfo {
         installed_size: 7_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -431,6 +447,8 @@ fn diffutils_3_10() -> PackageInfo {
         installed_size: 2_800_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -459,6 +477,8 @@ fn patch_2_7_6() -> PackageInfo {
         installed_size: 1_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -498,6 +518,8 @@ fn file_5_45() -> PackageInfo {
         installed_size: 4_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -523,6 +545,8 @@ fn which_2_21() -> PackageInfo {
         installed_size: 300_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -551,6 +575,8 @@ fn less_643() -> PackageInfo {
         installed_size: 800_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -579,6 +605,8 @@ fn gzip_1_13() -> PackageInfo {
         installed_size: 1_500_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -612,6 +640,8 @@ fn tar_1_35() -> PackageInfo {
         installed_size: 4_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -640,6 +670,8 @@ fn cpio_2_15() -> PackageInfo {
         installed_size: 2_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -679,6 +711,8 @@ fn procps_4_0_4() -> PackageInfo {
         installed_size: 3_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -713,6 +747,8 @@ fn psmisc_23_6() -> PackageInfo {
         installed_size: 1_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -759,6 +795,8 @@ fn shadow_4_14_6() -> PackageInfo {
         installed_size: 5_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -796,6 +834,8 @@ fn sudo_1_9_15() -> PackageInfo {
         installed_size: 10_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
 
@@ -833,5 +873,7 @@ fn e2fsprogs_1_47_0() -> PackageInfo {
         installed_size: 20_000_000,
         required_use: String::new(),
         blockers: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        restrict: Vec::new(),
     }
 }
@@ -0,0 +1,412 @@
+//! Distfile checksum database and mirror health
+//!
+//! Every verified download is recorded with its hash and the mirror that
+//! served it. `buckos mirrors audit` re-downloads a random sample of those
+//! distfiles from each configured mirror and compares hashes and throughput;
+//! the results feed a per-mirror health score that decides the order in
+//! which mirrors are tried.
+
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Audits in a row below [`SLOW_RATE`] before a mirror counts as slow
+pub const SLOW_STREAK: u32 = 3;
+
+/// Throughput (bytes per second) below which an audit counts as slow
+pub const SLOW_RATE: f64 = 64.0 * 1024.0;
+
+/// Score of a mirror with no history
+const NEUTRAL_SCORE: f64 = 0.5;
+
+/// A verified distfile
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DistfileRecord {
+    /// SHA-256 of the verified file
+    pub sha256: String,
+    /// Size in bytes
+    pub size: u64,
+    /// URL the file was downloaded from
+    pub url: String,
+    /// Configured mirror that served it, if not the package's own URL
+    pub mirror: Option<String>,
+    /// When it was fetched (seconds since the epoch)
+    pub fetched_at: u64,
+}
+
+/// Accumulated behaviour of one mirror
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MirrorHealth {
+    /// Downloads that completed and verified
+    pub successes: u32,
+    /// Downloads that failed (HTTP or network errors)
+    pub failures: u32,
+    /// Downloads whose content did not match the recorded hash
+    pub mismatches: u32,
+    /// Moving average of throughput in bytes per second
+    pub average_rate: f64,
+    /// Consecutive audits below [`SLOW_RATE`]
+    pub slow_streak: u32,
+    /// Last audit (seconds since the epoch)
+    pub last_audit: Option<u64>,
+}
+
+impl MirrorHealth {
+    /// Health between 0 and 1; higher is better
+    ///
+    /// Reliability is smoothed so one early failure does not bury a mirror,
+    /// every hash mismatch halves the score, and a persistently slow mirror
+    /// is halved as well.
+    pub fn score(&self) -> f64 {
+        let attempts = self.successes + self.failures + self.mismatches;
+        if attempts == 0 {
+            return NEUTRAL_SCORE;
+        }
+        let reliability = (self.successes as f64 + 1.0) / (attempts as f64 + 2.0);
+        let integrity = 0.5f64.powi(self.mismatches.min(16) as i32);
+        let speed = if self.is_slow() { 0.5 } else { 1.0 };
+        reliability * integrity * speed
+    }
+
+    /// Whether recent audits were persistently slow
+    pub fn is_slow(&self) -> bool {
+        self.slow_streak >= SLOW_STREAK
+    }
+
+    fn observe_rate(&mut self, rate: f64) {
+        self.average_rate = if self.average_rate == 0.0 {
+            rate
+        } else {
+            0.7 * self.average_rate + 0.3 * rate
+        };
+    }
+}
+
+/// Result of auditing one mirror
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MirrorAudit {
+    /// Mirror base URL
+    pub mirror: String,
+    /// Distfiles that downloaded and matched
+    pub verified: Vec<String>,
+    /// Distfiles whose content differed from the recorded hash
+    pub mismatched: Vec<String>,
+    /// Distfiles that could not be downloaded, with the reason
+    pub failed: Vec<(String, String)>,
+    /// Bytes downloaded
+    pub bytes: u64,
+    /// Throughput in bytes per second
+    pub rate: f64,
+    /// Health after this audit
+    pub score: f64,
+    /// Whether the mirror is now considered persistently slow
+    pub slow: bool,
+}
+
+impl MirrorAudit {
+    /// Whether anything is wrong with the mirror
+    pub fn has_problems(&self) -> bool {
+        !self.mismatched.is_empty() || !self.failed.is_empty() || self.slow
+    }
+}
+
+/// On-disk record of verified distfiles and mirror health
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ChecksumDb {
+    #[serde(skip)]
+    path: PathBuf,
+    /// Verified distfiles by filename
+    #[serde(default)]
+    pub distfiles: BTreeMap<String, DistfileRecord>,
+    /// Health by mirror base URL
+    #[serde(default)]
+    pub mirrors: BTreeMap<String, MirrorHealth>,
+}
+
+impl ChecksumDb {
+    /// An empty database stored at `path`
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            ..Default::default()
+        }
+    }
+
+    /// Load the database, starting empty if it does not exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut db: Self = if path.exists() {
+            let content = std::fs::read_to_string(path)?;
+            serde_json::from_str(&content)
+                .map_err(|e| Error::Other(format!("Failed to parse {}: {}", path.display(), e)))?
+        } else {
+            Self::new(path)
+        };
+        db.path = path.to_path_buf();
+        Ok(db)
+    }
+
+    /// Write the database back to where it was loaded from
+    pub fn save(&self) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::Other(format!("Failed to serialize checksums: {}", e)))?;
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, content)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// Record a verified download
+    pub fn record_fetch(&mut self, filename: &str, record: DistfileRecord, rate: f64) {
+        if let Some(mirror) = &record.mirror {
+            let health = self.mirrors.entry(mirror.clone()).or_default();
+            health.successes += 1;
+            health.observe_rate(rate);
+        }
+        self.distfiles.insert(filename.to_string(), record);
+    }
+
+    /// Record a failed download from a mirror
+    pub fn record_failure(&mut self, mirror: &str) {
+        self.mirrors.entry(mirror.to_string()).or_default().failures += 1;
+    }
+
+    /// Record a download from a mirror that failed verification
+    pub fn record_mismatch(&mut self, mirror: &str) {
+        self.mirrors
+            .entry(mirror.to_string())
+            .or_default()
+            .mismatches += 1;
+    }
+
+    /// Fold an audit into the mirror's health, filling in its score
+    pub fn record_audit(&mut self, audit: &mut MirrorAudit) {
+        let health = self.mirrors.entry(audit.mirror.clone()).or_default();
+        health.successes += audit.verified.len() as u32;
+        health.mismatches += audit.mismatched.len() as u32;
+        health.failures += audit.failed.len() as u32;
+        if audit.bytes > 0 {
+            health.observe_rate(audit.rate);
+            if audit.rate < SLOW_RATE {
+                health.slow_streak += 1;
+            } else {
+                health.slow_streak = 0;
+            }
+        }
+        health.last_audit = Some(now());
+
+        audit.score = health.score();
+        audit.slow = health.is_slow();
+    }
+
+    /// Health score of a mirror
+    pub fn score(&self, mirror: &str) -> f64 {
+        self.mirrors
+            .get(mirror)
+            .map(MirrorHealth::score)
+            .unwrap_or(NEUTRAL_SCORE)
+    }
+
+    /// Mirrors ordered by health, keeping configured order among equals
+    pub fn rank(&self, mirrors: &[String]) -> Vec<String> {
+        let mut ranked = mirrors.to_vec();
+        ranked.sort_by(|a, b| self.score(b).total_cmp(&self.score(a)));
+        ranked
+    }
+
+    /// Up to `count` recorded distfiles chosen at random
+    pub fn sample(&self, count: usize) -> Vec<(String, DistfileRecord)> {
+        use std::hash::BuildHasher;
+
+        let state = std::collections::hash_map::RandomState::new();
+        let mut entries: Vec<_> = self
+            .distfiles
+            .iter()
+            .map(|(name, record)| (state.hash_one(name), name.clone(), record.clone()))
+            .collect();
+        entries.sort_by_key(|(key, _, _)| *key);
+        entries
+            .into_iter()
+            .take(count)
+            .map(|(_, name, record)| (name, record))
+            .collect()
+    }
+}
+
+/// Re-download distfiles from a mirror and compare them to their records
+pub async fn audit_mirror(
+    client: &reqwest::Client,
+    mirror: &str,
+    sample: &[(String, DistfileRecord)],
+    timeout: Duration,
+) -> MirrorAudit {
+    let mut audit = MirrorAudit {
+        mirror: mirror.to_string(),
+        ..Default::default()
+    };
+    let started = Instant::now();
+
+    for (filename, record) in sample {
+        let url = format!("{}/{}", mirror.trim_end_matches('/'), filename);
+        match tokio::time::timeout(timeout, hash_url(client, &url)).await {
+            Ok(Ok((hash, bytes))) => {
+                audit.bytes += bytes;
+                if hash == record.sha256 {
+                    audit.verified.push(filename.clone());
+                } else {
+                    audit.mismatched.push(filename.clone());
+                }
+            }
+            Ok(Err(e)) => audit.failed.push((filename.clone(), e.to_string())),
+            Err(_) => audit
+                .failed
+                .push((filename.clone(), "timed out".to_string())),
+        }
+    }
+
+    let elapsed = started.elapsed().as_secs_f64();
+    if elapsed > 0.0 {
+        audit.rate = audit.bytes as f64 / elapsed;
+    }
+    audit
+}
+
+/// Stream a URL, returning its SHA-256 and length
+async fn hash_url(client: &reqwest::Client, url: &str) -> Result<(String, u64)> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| Error::NetworkError(e.to_string()))?;
+    if !response.status().is_success() {
+        return Err(Error::NetworkError(format!("HTTP {}", response.status())));
+    }
+
+    let mut hasher = Sha256::new();
+    let mut bytes = 0u64;
+    let mut stream = response.bytes_stream();
+    use futures::StreamExt;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| Error::NetworkError(e.to_string()))?;
+        hasher.update(&chunk);
+        bytes += chunk.len() as u64;
+    }
+    Ok((format!("{:x}", hasher.finalize()), bytes))
+}
+
+/// Current time in seconds since the epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(mirror: Option<&str>) -> DistfileRecord {
+        DistfileRecord {
+            sha256: "ab".repeat(32),
+            size: 10,
+            url: "https://example.org/foo.tar.gz".to_string(),
+            mirror: mirror.map(String::from),
+            fetched_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_score() {
+        let fresh = MirrorHealth::default();
+        assert_eq!(fresh.score(), NEUTRAL_SCORE);
+
+        let good = MirrorHealth {
+            successes: 20,
+            ..Default::default()
+        };
+        let flaky = MirrorHealth {
+            successes: 10,
+            failures: 10,
+            ..Default::default()
+        };
+        let tampered = MirrorHealth {
+            successes: 20,
+            mismatches: 1,
+            ..Default::default()
+        };
+        let slow = MirrorHealth {
+            successes: 20,
+            slow_streak: SLOW_STREAK,
+            ..Default::default()
+        };
+        assert!(good.score() > flaky.score());
+        assert!(good.score() > tampered.score());
+        assert!(good.score() > slow.score());
+        assert!(slow.is_slow());
+    }
+
+    #[test]
+    fn test_rank_prefers_healthy_mirrors() {
+        let mut db = ChecksumDb::default();
+        db.record_failure("https://a");
+        db.record_failure("https://a");
+        db.record_fetch("foo.tar.gz", record(Some("https://b")), 1e6);
+
+        let mirrors = vec![
+            "https://a".to_string(),
+            "https://b".to_string(),
+            "https://c".to_string(),
+        ];
+        assert_eq!(
+            db.rank(&mirrors),
+            vec!["https://b", "https://c", "https://a"]
+        );
+    }
+
+    #[test]
+    fn test_record_audit_tracks_slowness() {
+        let mut db = ChecksumDb::default();
+        for i in 0..SLOW_STREAK {
+            let mut audit = MirrorAudit {
+                mirror: "https://slow".to_string(),
+                verified: vec!["foo.tar.gz".to_string()],
+                bytes: 1000,
+                rate: SLOW_RATE / 2.0,
+                ..Default::default()
+            };
+            db.record_audit(&mut audit);
+            assert_eq!(audit.slow, i + 1 >= SLOW_STREAK);
+        }
+
+        let mut fast = MirrorAudit {
+            mirror: "https://slow".to_string(),
+            bytes: 1000,
+            rate: SLOW_RATE * 10.0,
+            ..Default::default()
+        };
+        db.record_audit(&mut fast);
+        assert!(!fast.slow);
+    }
+
+    #[test]
+    fn test_load_save_sample() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checksums.json");
+
+        let mut db = ChecksumDb::load(&path).unwrap();
+        assert!(db.distfiles.is_empty());
+        db.record_fetch("a.tar.gz", record(None), 0.0);
+        db.record_fetch("b.tar.gz", record(Some("https://m")), 100.0);
+        db.save().unwrap();
+
+        let db = ChecksumDb::load(&path).unwrap();
+        assert_eq!(db.distfiles.len(), 2);
+        assert_eq!(db.mirrors["https://m"].successes, 1);
+        assert_eq!(db.sample(1).len(), 1);
+        assert_eq!(db.sample(10).len(), 2);
+    }
+}
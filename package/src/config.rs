@@ -1,9 +1,19 @@
 //! Package manager configuration
 
-use crate::buck::BuckConfigOptions;
+use crate::buck::{BuckConfigOptions, BuckDaemonConfig};
+use crate::cache::FetchConfig;
+use crate::config_protect::ProtectConfig;
+use crate::layout::LayoutConfig;
+use crate::notify::NotifyConfig;
+use crate::resolver::AnyOfWeights;
+use crate::resolver::ResolverConfig;
+use crate::security::verity::VerityConfig;
+use crate::theme::ThemeConfig;
+use crate::transaction::{DocCompression, PressureConfig, QaConfig, RetryConfig};
 use crate::{Error, Result, UseConfig, WorldSet};
+use buckos_core::compress::Compression;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
 /// Main configuration for the package manager
@@ -48,6 +58,70 @@ pub struct Config {
     /// Custom Buck configuration options
     #[serde(default)]
     pub buck_config: BuckConfigOptions,
+    /// Buck2 daemon isolation and timeouts
+    #[serde(default)]
+    pub buck_daemon: BuckDaemonConfig,
+    /// Weights used to choose between any-of dependency alternatives
+    #[serde(default)]
+    pub any_of_weights: AnyOfWeights,
+    /// Packages preferred when choosing any-of alternatives (e.g. "dev-libs/openssl")
+    #[serde(default)]
+    pub any_of_preferred: Vec<String>,
+    /// INSTALL_MASK patterns for files to skip when merging
+    #[serde(default)]
+    pub install_mask: Vec<String>,
+    /// CONFIG_PROTECT paths whose files are merged by hand rather than
+    /// overwritten
+    #[serde(default)]
+    pub config_protect: ProtectConfig,
+    /// Compression applied to man and info pages when merging
+    #[serde(default)]
+    pub doc_compression: DocCompression,
+    /// Look for distfiles on mDNS-discovered LAN peers before the internet
+    #[serde(default)]
+    pub peer_distfiles: bool,
+    /// Download retries, mirrors, proxies and bandwidth limits
+    #[serde(default)]
+    pub fetch: FetchConfig,
+    /// Directory scanned for plugins
+    #[serde(default = "default_plugin_dir")]
+    pub plugin_dir: PathBuf,
+    /// Copy transaction history entries to the local syslog
+    #[serde(default)]
+    pub audit_syslog: bool,
+    /// Pre-merge QA check settings
+    #[serde(default)]
+    pub qa: QaConfig,
+    /// Retries of builds that fail for transient reasons
+    #[serde(default)]
+    pub build_retry: RetryConfig,
+    /// Memory pressure at which builds get fewer jobs or wait
+    #[serde(default)]
+    pub build_pressure: PressureConfig,
+    /// Commits live packages are pinned to, by `category/name` or name
+    #[serde(default)]
+    pub live_pins: HashMap<String, String>,
+    /// Signing backend and key locations
+    #[serde(default)]
+    pub signing: SigningConfig,
+    /// fs-verity protection of installed files
+    #[serde(default)]
+    pub verity: VerityConfig,
+    /// Compression of binary packages and stored build logs
+    #[serde(default)]
+    pub compression: CompressionConfig,
+    /// System or per-user layout, and merged or split /usr
+    #[serde(default)]
+    pub layout: LayoutConfig,
+    /// Where advisories and news are sent after a sync or audit
+    #[serde(default)]
+    pub notify: NotifyConfig,
+    /// Colors of the CLI
+    #[serde(default)]
+    pub theme: ThemeConfig,
+    /// Time limit of dependency resolution
+    #[serde(default)]
+    pub resolver: ResolverConfig,
 }
 
 impl Default for Config {
@@ -76,6 +150,27 @@ impl Default for Config {
             accept_keywords: HashSet::new(),
             accept_license: "@FREE".to_string(),
             buck_config: BuckConfigOptions::default(),
+            buck_daemon: BuckDaemonConfig::default(),
+            any_of_weights: AnyOfWeights::default(),
+            any_of_preferred: Vec::new(),
+            install_mask: Vec::new(),
+            config_protect: ProtectConfig::default(),
+            doc_compression: DocCompression::default(),
+            peer_distfiles: false,
+            fetch: FetchConfig::default(),
+            plugin_dir: default_plugin_dir(),
+            audit_syslog: false,
+            qa: QaConfig::default(),
+            build_retry: RetryConfig::default(),
+            build_pressure: PressureConfig::default(),
+            live_pins: HashMap::new(),
+            signing: SigningConfig::default(),
+            verity: VerityConfig::default(),
+            compression: CompressionConfig::default(),
+            layout: LayoutConfig::default(),
+            notify: NotifyConfig::default(),
+            theme: ThemeConfig::default(),
+            resolver: ResolverConfig::default(),
         }
     }
 }
@@ -122,6 +217,16 @@ impl Config {
         self.cache_dir.join("build")
     }
 
+    /// Get the directory holding per-package compiler diagnostics
+    pub fn build_reports_dir(&self) -> PathBuf {
+        self.cache_dir.join("build-reports")
+    }
+
+    /// Get the path of the eix query cache
+    pub fn eix_cache_path(&self) -> PathBuf {
+        self.cache_dir.join("eix.cache")
+    }
+
     /// Get the packages cache directory
     pub fn packages_dir(&self) -> PathBuf {
         self.cache_dir.join("packages")
@@ -189,6 +294,118 @@ pub enum SyncType {
     Svn,
 }
 
+/// Tool used to sign and verify packages, manifests and repositories
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SigningBackend {
+    /// GnuPG, through the gpg binary
+    #[default]
+    Gpg,
+    /// Built-in Ed25519 signatures compatible with minisign
+    Minisign,
+}
+
+impl std::fmt::Display for SigningBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SigningBackend::Gpg => write!(f, "gpg"),
+            SigningBackend::Minisign => write!(f, "minisign"),
+        }
+    }
+}
+
+impl std::str::FromStr for SigningBackend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "gpg" => Ok(SigningBackend::Gpg),
+            "minisign" => Ok(SigningBackend::Minisign),
+            _ => Err(Error::Config(format!(
+                "Invalid signing backend: {}. Use: gpg, minisign",
+                s
+            ))),
+        }
+    }
+}
+
+/// Signing configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SigningConfig {
+    /// Backend used unless a repository names another
+    pub backend: SigningBackend,
+    /// Backend per repository name
+    pub repositories: HashMap<String, SigningBackend>,
+    /// Own minisign keys
+    pub keys_dir: PathBuf,
+    /// Public minisign keys whose signatures are trusted
+    pub trusted_keys_dir: PathBuf,
+    /// Keys each repository starts out trusting; its key rotation manifest
+    /// is followed from these, and its metadata verified on sync
+    pub repository_keys: HashMap<String, Vec<String>>,
+}
+
+impl Default for SigningConfig {
+    fn default() -> Self {
+        Self {
+            backend: SigningBackend::default(),
+            repositories: HashMap::new(),
+            keys_dir: PathBuf::from("/etc/buckos/keys"),
+            trusted_keys_dir: PathBuf::from("/etc/buckos/trusted-keys"),
+            repository_keys: HashMap::new(),
+        }
+    }
+}
+
+impl SigningConfig {
+    /// Backend signing and verifying `repository`
+    pub fn backend_for(&self, repository: &str) -> SigningBackend {
+        self.repositories
+            .get(repository)
+            .copied()
+            .unwrap_or(self.backend)
+    }
+}
+
+/// Compression settings, in make.conf terms
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressionConfig {
+    /// Binary package algorithm (BINPKG_COMPRESS), or `algorithm:level`
+    pub binpkg: String,
+    /// Flags for the binary package compressor (BINPKG_COMPRESS_FLAGS),
+    /// e.g. `-19 -T0`
+    pub binpkg_flags: String,
+    /// Build logs kept with the build reports, as `algorithm[:level]`
+    pub logs: String,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            binpkg: "zstd".to_string(),
+            binpkg_flags: String::new(),
+            logs: "zstd".to_string(),
+        }
+    }
+}
+
+impl CompressionConfig {
+    /// Settings binary packages are created with
+    pub fn binpkg_compression(&self) -> Result<Compression> {
+        Ok(Compression::from_make_conf(
+            &self.binpkg,
+            &self.binpkg_flags,
+        )?)
+    }
+
+    /// Settings build logs are stored with
+    pub fn log_compression(&self) -> Result<Compression> {
+        Ok(self.logs.parse()?)
+    }
+}
+
 fn detect_arch() -> String {
     #[cfg(target_arch = "x86_64")]
     return "amd64".to_string();
@@ -222,6 +439,10 @@ fn detect_chost() -> String {
     return "unknown-unknown-linux-gnu".to_string();
 }
 
+fn default_plugin_dir() -> PathBuf {
+    PathBuf::from("/etc/buckos/plugins")
+}
+
 fn default_features() -> HashSet<String> {
     let mut features = HashSet::new();
     features.insert("parallel-fetch".to_string());
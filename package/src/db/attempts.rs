@@ -0,0 +1,208 @@
+//! Build attempts
+//!
+//! Every try of a build is recorded, including retries of transient
+//! failures, so packages whose builds only succeed on a second or third
+//! attempt can be told apart from ones that build reliably.
+
+use super::PackageDb;
+use crate::{PackageId, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use rusqlite::params;
+use serde::Serialize;
+use std::time::Duration;
+
+/// One try of a build
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BuildAttempt {
+    pub package: PackageId,
+    pub version: String,
+    /// Try number, from 1; later tries are retries
+    pub attempt: u32,
+    pub success: bool,
+    /// Classified cause of a failed try (`out_of_memory`, `download`, ...)
+    pub failure: Option<String>,
+    /// Build jobs the try ran with
+    pub jobs: usize,
+    /// Whether the build sandbox had network access
+    pub network: bool,
+    pub duration: Duration,
+    pub started_at: DateTime<Utc>,
+}
+
+/// How reliably a package builds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BuildFlakiness {
+    pub package: PackageId,
+    /// Builds started, counting each build once however often it was tried
+    pub builds: u64,
+    /// Builds that were retried
+    pub retried: u64,
+    /// Retried builds that then succeeded
+    pub recovered: u64,
+    /// Tries that failed
+    pub failed_attempts: u64,
+}
+
+impl PackageDb {
+    /// Create the build attempt table
+    pub(super) fn init_attempts_schema(&self) -> Result<()> {
+        self.conn.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS build_attempts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                category TEXT NOT NULL,
+                name TEXT NOT NULL,
+                version TEXT NOT NULL,
+                attempt INTEGER NOT NULL,
+                success INTEGER NOT NULL,
+                failure TEXT,
+                jobs INTEGER NOT NULL,
+                network INTEGER NOT NULL,
+                seconds REAL NOT NULL,
+                started_at INTEGER NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_build_attempts_pkg
+                ON build_attempts(category, name);
+            "#,
+        )?;
+        Ok(())
+    }
+
+    /// Record one try of a build
+    pub fn record_build_attempt(&self, attempt: &BuildAttempt) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO build_attempts
+                (category, name, version, attempt, success, failure, jobs, network, seconds, started_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                attempt.package.category,
+                attempt.package.name,
+                attempt.version,
+                attempt.attempt,
+                attempt.success,
+                attempt.failure,
+                attempt.jobs as i64,
+                attempt.network,
+                attempt.duration.as_secs_f64(),
+                attempt.started_at.timestamp()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Tries of a package's builds, newest first
+    pub fn build_attempts(&self, package: &PackageId) -> Result<Vec<BuildAttempt>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT version, attempt, success, failure, jobs, network, seconds, started_at
+             FROM build_attempts WHERE category = ? AND name = ? ORDER BY id DESC",
+        )?;
+        let attempts = stmt
+            .query_map(params![package.category, package.name], |row| {
+                Ok(BuildAttempt {
+                    package: package.clone(),
+                    version: row.get(0)?,
+                    attempt: row.get(1)?,
+                    success: row.get(2)?,
+                    failure: row.get(3)?,
+                    jobs: row.get::<_, i64>(4)? as usize,
+                    network: row.get(5)?,
+                    duration: Duration::from_secs_f64(row.get(6)?),
+                    started_at: Utc
+                        .timestamp_opt(row.get(7)?, 0)
+                        .single()
+                        .unwrap_or_default(),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(attempts)
+    }
+
+    /// Packages with retried or failed tries, most retried first
+    pub fn build_flakiness(&self) -> Result<Vec<BuildFlakiness>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT category, name,
+                    SUM(attempt = 1),
+                    SUM(attempt = 2),
+                    SUM(attempt > 1 AND success),
+                    SUM(NOT success)
+             FROM build_attempts GROUP BY category, name
+             HAVING SUM(attempt > 1) > 0 OR SUM(NOT success) > 0
+             ORDER BY SUM(attempt = 2) DESC, SUM(NOT success) DESC, category, name",
+        )?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(BuildFlakiness {
+                    package: PackageId::new(row.get::<_, String>(0)?, row.get::<_, String>(1)?),
+                    builds: row.get::<_, i64>(2)? as u64,
+                    retried: row.get::<_, i64>(3)? as u64,
+                    recovered: row.get::<_, i64>(4)? as u64,
+                    failed_attempts: row.get::<_, i64>(5)? as u64,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attempt(name: &str, attempt: u32, success: bool) -> BuildAttempt {
+        BuildAttempt {
+            package: PackageId::new("dev-lang", name),
+            version: "1.0.0".to_string(),
+            attempt,
+            success,
+            failure: (!success).then(|| "out_of_memory".to_string()),
+            jobs: 8 >> (attempt - 1),
+            network: true,
+            duration: Duration::from_secs(60),
+            started_at: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_flakiness_counts_builds_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = PackageDb::open(dir.path()).unwrap();
+        for a in [
+            attempt("rust", 1, false),
+            attempt("rust", 2, true),
+            attempt("rust", 1, true),
+            attempt("gcc", 1, false),
+            attempt("gcc", 2, false),
+            attempt("gcc", 3, false),
+            attempt("python", 1, true),
+        ] {
+            db.record_build_attempt(&a).unwrap();
+        }
+
+        let flaky = db.build_flakiness().unwrap();
+        assert_eq!(
+            flaky,
+            vec![
+                BuildFlakiness {
+                    package: PackageId::new("dev-lang", "gcc"),
+                    builds: 1,
+                    retried: 1,
+                    recovered: 0,
+                    failed_attempts: 3,
+                },
+                BuildFlakiness {
+                    package: PackageId::new("dev-lang", "rust"),
+                    builds: 2,
+                    retried: 1,
+                    recovered: 1,
+                    failed_attempts: 1,
+                },
+            ]
+        );
+        let rust = db
+            .build_attempts(&PackageId::new("dev-lang", "rust"))
+            .unwrap();
+        assert_eq!(rust.len(), 3);
+        assert_eq!(rust[1], attempt("rust", 2, true));
+    }
+}
@@ -0,0 +1,321 @@
+//! Backup, restore and export of the package database
+//!
+//! A backup holds every installed package with its files, USE flags,
+//! dependencies, patches, live commit and build details, the world file
+//! entries and the audit trail. It is plain JSON; `buckos db backup` writes
+//! it zstd compressed and `buckos db export --format json` writes it as is.
+//! Either form can be restored.
+
+use super::record::PackageRecord;
+use super::{HistoryEntry, PackageDb};
+use crate::{Error, FileType, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+
+/// Backup format written by this version
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+
+/// zstd frame magic, used to tell archives from plain JSON exports
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// A portable copy of the package database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DbBackup {
+    /// Format of this backup
+    pub format_version: u32,
+    /// When the backup was taken
+    pub created_at: DateTime<Utc>,
+    /// Installed packages
+    pub packages: Vec<PackageRecord>,
+    /// World file entries
+    #[serde(default)]
+    pub world: Vec<String>,
+    /// Audit trail, oldest first
+    #[serde(default)]
+    pub history: Vec<HistoryEntry>,
+}
+
+/// How well a backed-up package matches the filesystem
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupCheck {
+    /// Package name
+    pub package: String,
+    /// Files recorded for the package that exist on disk
+    pub files: usize,
+    /// Recorded files that are not on disk
+    pub missing: Vec<String>,
+    /// Regular files whose digest differs from the recorded one
+    pub modified: Vec<String>,
+}
+
+impl BackupCheck {
+    /// Whether none of the package's files are on disk
+    ///
+    /// Packages without files (virtuals, meta packages) are never absent.
+    pub fn is_absent(&self) -> bool {
+        self.files == 0 && !self.missing.is_empty()
+    }
+}
+
+impl DbBackup {
+    /// Read a backup archive or JSON export
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)?;
+        let json = if data.starts_with(&ZSTD_MAGIC) {
+            let mut json = Vec::new();
+            zstd::Decoder::new(data.as_slice())?.read_to_end(&mut json)?;
+            json
+        } else {
+            data
+        };
+
+        let backup: Self = serde_json::from_slice(&json)?;
+        if backup.format_version > BACKUP_FORMAT_VERSION {
+            return Err(Error::DatabaseError(format!(
+                "{} is backup format {}, this buckos reads up to {}",
+                path.display(),
+                backup.format_version,
+                BACKUP_FORMAT_VERSION
+            )));
+        }
+        Ok(backup)
+    }
+
+    /// Write a zstd compressed archive
+    pub fn write_archive(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_vec(self)?;
+        let compressed = zstd::encode_all(json.as_slice(), 19)?;
+        std::fs::write(path, compressed)?;
+        Ok(())
+    }
+
+    /// Check every package's files below `root`
+    pub fn check(&self, root: &Path) -> Vec<BackupCheck> {
+        self.packages
+            .iter()
+            .map(|record| {
+                let mut check = BackupCheck {
+                    package: record.package.name.clone(),
+                    files: 0,
+                    missing: Vec::new(),
+                    modified: Vec::new(),
+                };
+                for file in &record.package.files {
+                    if file.file_type == FileType::Masked {
+                        continue;
+                    }
+                    let path = root.join(file.path.trim_start_matches('/'));
+                    if path.symlink_metadata().is_err() {
+                        check.missing.push(file.path.clone());
+                        continue;
+                    }
+                    check.files += 1;
+                    if file.file_type != FileType::Regular {
+                        continue;
+                    }
+                    if let Some(expected) = &file.blake3_hash {
+                        match buckos_core::hash::hash_file(&path) {
+                            Ok(actual) if actual == *expected => {}
+                            _ => check.modified.push(file.path.clone()),
+                        }
+                    }
+                }
+                check
+            })
+            .collect()
+    }
+}
+
+impl PackageDb {
+    /// Copy the database into a backup
+    ///
+    /// The world file lives outside the database, so `world` is left empty.
+    pub fn backup(&self) -> Result<DbBackup> {
+        let mut packages = Vec::new();
+        for package in self.get_all_installed()? {
+            if let Some(record) = self.package_record(&package.name)? {
+                packages.push(record);
+            }
+        }
+
+        Ok(DbBackup {
+            format_version: BACKUP_FORMAT_VERSION,
+            created_at: Utc::now(),
+            packages,
+            world: Vec::new(),
+            history: self.history(&Default::default())?,
+        })
+    }
+
+    /// Replace the installed packages with those in a backup
+    ///
+    /// History entries are appended when their id is not already taken;
+    /// the audit trail is never rewritten.
+    pub fn restore(&mut self, backup: &DbBackup) -> Result<()> {
+        self.begin_transaction()?;
+        match self.restore_rows(backup) {
+            Ok(()) => self.commit(),
+            Err(e) => {
+                let _ = self.rollback();
+                Err(e)
+            }
+        }
+    }
+
+    fn restore_rows(&mut self, backup: &DbBackup) -> Result<()> {
+        self.conn.execute("DELETE FROM packages", [])?;
+
+        for record in &backup.packages {
+            self.add_record(record)?;
+        }
+
+        for entry in &backup.history {
+            let inserted = self.conn.execute(
+                "INSERT OR IGNORE INTO history (id, timestamp, user, command, success, error)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+                params![
+                    entry.id,
+                    entry.timestamp.to_rfc3339(),
+                    entry.user,
+                    entry.command,
+                    entry.success,
+                    entry.error
+                ],
+            )?;
+            if inserted == 0 {
+                continue;
+            }
+            for (seq, change) in entry.changes.iter().enumerate() {
+                self.conn.execute(
+                    "INSERT INTO history_changes
+                     (entry_id, seq, category, name, slot, old_version, new_version)
+                     VALUES (?, ?, ?, ?, ?, ?, ?)",
+                    params![
+                        entry.id,
+                        seq as i64,
+                        change.package.category,
+                        change.package.name,
+                        change.slot,
+                        change.old_version,
+                        change.new_version
+                    ],
+                )?;
+            }
+            if let Some(usage) = &entry.usage {
+                super::history::insert_usage(&self.conn, entry.id, usage)?;
+            }
+            if let Some(machine_id) = &entry.machine_id {
+                super::history::insert_machine_id(&self.conn, entry.id, machine_id)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::PackageChange;
+    use crate::{InstalledFile, InstalledPackage, PackageId};
+    use std::collections::HashSet;
+
+    fn package(name: &str, files: Vec<InstalledFile>) -> InstalledPackage {
+        InstalledPackage {
+            id: PackageId::new("app-misc", name),
+            name: name.to_string(),
+            version: semver::Version::new(1, 0, 0),
+            slot: "0".to_string(),
+            installed_at: Utc::now(),
+            use_flags: HashSet::from(["ssl".to_string()]),
+            files,
+            size: 0,
+            build_time: false,
+            explicit: true,
+        }
+    }
+
+    fn file(path: &str, content: &[u8]) -> InstalledFile {
+        InstalledFile {
+            path: path.to_string(),
+            file_type: FileType::Regular,
+            mode: 0o644,
+            size: content.len() as u64,
+            blake3_hash: Some(buckos_core::hash::blake3_hex(content)),
+            mtime: 0,
+        }
+    }
+
+    #[test]
+    fn test_backup_restore_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = PackageDb::open(&dir.path().join("db")).unwrap();
+        let id = db
+            .add_package(&package("foo", vec![file("/usr/bin/foo", b"foo")]))
+            .unwrap();
+        db.add_dependency(id, &PackageId::new("sys-libs", "zlib"), None, true, true)
+            .unwrap();
+        db.set_live_commit("foo", "https://example.com/foo.git", "abc123")
+            .unwrap();
+        db.record_history(
+            "root",
+            "buckos install foo",
+            None,
+            &[PackageChange {
+                package: PackageId::new("app-misc", "foo"),
+                slot: "0".to_string(),
+                old_version: None,
+                new_version: Some("1.0.0".to_string()),
+            }],
+            None,
+        )
+        .unwrap();
+
+        let archive = dir.path().join("backup.json.zst");
+        db.backup().unwrap().write_archive(&archive).unwrap();
+        let backup = DbBackup::load(&archive).unwrap();
+
+        let mut fresh = PackageDb::open(&dir.path().join("fresh")).unwrap();
+        fresh.add_package(&package("stale", Vec::new())).unwrap();
+        fresh.restore(&backup).unwrap();
+
+        assert!(!fresh.is_installed("stale").unwrap());
+        let restored = fresh.backup().unwrap();
+        assert_eq!(restored.packages.len(), 1);
+        let record = &restored.packages[0];
+        assert_eq!(record.package.files.len(), 1);
+        assert!(record.package.use_flags.contains("ssl"));
+        assert_eq!(record.dependencies[0].package.name, "zlib");
+        assert_eq!(record.live_commit.as_ref().unwrap().commit, "abc123");
+        assert_eq!(restored.history, backup.history);
+
+        // Restoring again keeps the existing history entries
+        fresh.restore(&backup).unwrap();
+        assert_eq!(fresh.history(&Default::default()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_check_against_filesystem() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(root.path().join("usr/bin")).unwrap();
+        std::fs::write(root.path().join("usr/bin/foo"), b"changed").unwrap();
+
+        let backup = DbBackup {
+            format_version: BACKUP_FORMAT_VERSION,
+            created_at: Utc::now(),
+            packages: vec![
+                PackageRecord::new(package("foo", vec![file("/usr/bin/foo", b"foo")])),
+                PackageRecord::new(package("gone", vec![file("/usr/bin/gone", b"gone")])),
+            ],
+            world: Vec::new(),
+            history: Vec::new(),
+        };
+
+        let checks = backup.check(root.path());
+        assert_eq!(checks[0].modified, vec!["/usr/bin/foo".to_string()]);
+        assert!(!checks[0].is_absent());
+        assert!(checks[1].is_absent());
+    }
+}
@@ -0,0 +1,179 @@
+//! Historical build durations
+//!
+//! The wall-clock time of every successful build is kept per package
+//! version and USE hash, so the cost of rebuilding a package can be
+//! estimated before it is scheduled. Entries outlive the installed package; a package that was
+//! removed may well be built again.
+
+use super::PackageDb;
+use crate::{PackageId, Result};
+use rusqlite::params;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Number of recent builds averaged into an estimate
+const ESTIMATE_SAMPLES: usize = 5;
+
+impl PackageDb {
+    /// Create the build duration table
+    pub(super) fn init_durations_schema(&self) -> Result<()> {
+        self.conn.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS build_durations (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                category TEXT NOT NULL,
+                name TEXT NOT NULL,
+                version TEXT NOT NULL,
+                use_hash TEXT NOT NULL,
+                seconds REAL NOT NULL,
+                built_at INTEGER NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_build_durations_pkg
+                ON build_durations(category, name);
+            "#,
+        )?;
+        Ok(())
+    }
+
+    /// Record how long a successful build took
+    pub fn record_build_duration(
+        &self,
+        package: &PackageId,
+        version: &str,
+        use_hash: &str,
+        duration: Duration,
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO build_durations (category, name, version, use_hash, seconds, built_at)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                package.category,
+                package.name,
+                version,
+                use_hash,
+                duration.as_secs_f64(),
+                chrono::Utc::now().timestamp()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Expected build time of a package version with the given USE hash
+    ///
+    /// Averages the most recent builds of that exact configuration, or of
+    /// any version of the package if it has never been built that way.
+    pub fn build_duration_estimate(
+        &self,
+        package: &PackageId,
+        version: &str,
+        use_hash: &str,
+    ) -> Result<Option<Duration>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT seconds FROM build_durations
+             WHERE category = ?1 AND name = ?2 AND version = ?3 AND use_hash = ?4
+             ORDER BY id DESC LIMIT ?5",
+        )?;
+        let exact = stmt
+            .query_map(
+                params![
+                    package.category,
+                    package.name,
+                    version,
+                    use_hash,
+                    ESTIMATE_SAMPLES as i64
+                ],
+                |row| row.get::<_, f64>(0),
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        if !exact.is_empty() {
+            return Ok(mean(&exact));
+        }
+
+        let mut stmt = self.conn.prepare(
+            "SELECT seconds FROM build_durations WHERE category = ? AND name = ?
+             ORDER BY id DESC LIMIT ?",
+        )?;
+        let samples = stmt
+            .query_map(
+                params![package.category, package.name, ESTIMATE_SAMPLES as i64],
+                |row| row.get::<_, f64>(0),
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(mean(&samples))
+    }
+
+    /// Expected build time of every package with recorded builds
+    pub fn build_duration_estimates(&self) -> Result<HashMap<PackageId, Duration>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT category, name, seconds FROM build_durations ORDER BY id DESC")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                PackageId::new(row.get::<_, String>(0)?, row.get::<_, String>(1)?),
+                row.get::<_, f64>(2)?,
+            ))
+        })?;
+
+        let mut samples: HashMap<PackageId, Vec<f64>> = HashMap::new();
+        for row in rows {
+            let (id, seconds) = row?;
+            let entry = samples.entry(id).or_default();
+            if entry.len() < ESTIMATE_SAMPLES {
+                entry.push(seconds);
+            }
+        }
+        Ok(samples
+            .into_iter()
+            .filter_map(|(id, s)| mean(&s).map(|d| (id, d)))
+            .collect())
+    }
+}
+
+fn mean(samples: &[f64]) -> Option<Duration> {
+    if samples.is_empty() {
+        return None;
+    }
+    Some(Duration::from_secs_f64(
+        samples.iter().sum::<f64>() / samples.len() as f64,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_prefers_same_configuration() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = PackageDb::open(dir.path()).unwrap();
+        let foo = PackageId::new("dev-libs", "foo");
+        let secs = Duration::from_secs;
+
+        assert_eq!(
+            db.build_duration_estimate(&foo, "1.0.0", "a").unwrap(),
+            None
+        );
+        db.record_build_duration(&foo, "1.0.0", "a", secs(1000))
+            .unwrap();
+        for _ in 0..ESTIMATE_SAMPLES {
+            db.record_build_duration(&foo, "1.1.0", "b", secs(60))
+                .unwrap();
+        }
+        db.record_build_duration(&PackageId::new("dev-libs", "bar"), "2.0.0", "a", secs(30))
+            .unwrap();
+
+        assert_eq!(
+            db.build_duration_estimate(&foo, "1.0.0", "a").unwrap(),
+            Some(secs(1000))
+        );
+        // Unknown configuration: recent builds of any version
+        assert_eq!(
+            db.build_duration_estimate(&foo, "1.2.0", "a").unwrap(),
+            Some(secs(60))
+        );
+        let all = db.build_duration_estimates().unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[&foo], secs(60));
+    }
+}
@@ -0,0 +1,168 @@
+//! File metadata recorded at merge time
+//!
+//! Size, mtime, ctime and inode of each installed regular file are kept
+//! beside its hash. `buckos verify` only re-hashes files whose metadata
+//! changed since; ctime cannot be set from user space, so a file rewritten
+//! and touched back to its old mtime is still caught. Records are keyed by
+//! file and go away with the package.
+
+use super::PackageDb;
+use crate::{FileType, Result};
+use rusqlite::params;
+use std::collections::HashMap;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// Metadata of a file on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStat {
+    pub size: u64,
+    /// Modification time in nanoseconds since the epoch
+    pub mtime_ns: i64,
+    /// Status change time in nanoseconds since the epoch
+    pub ctime_ns: i64,
+    pub inode: u64,
+}
+
+impl FileStat {
+    /// Metadata of the file at `path`, not following symlinks
+    pub fn of(path: &Path) -> std::io::Result<Self> {
+        let meta = std::fs::symlink_metadata(path)?;
+        Ok(Self {
+            size: meta.size(),
+            mtime_ns: meta.mtime() * 1_000_000_000 + meta.mtime_nsec(),
+            ctime_ns: meta.ctime() * 1_000_000_000 + meta.ctime_nsec(),
+            inode: meta.ino(),
+        })
+    }
+}
+
+impl PackageDb {
+    /// Create the file metadata table
+    pub(super) fn init_filestat_schema(&self) -> Result<()> {
+        self.conn.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS file_stats (
+                file_id INTEGER PRIMARY KEY,
+                size INTEGER NOT NULL,
+                mtime_ns INTEGER NOT NULL,
+                ctime_ns INTEGER NOT NULL,
+                inode INTEGER NOT NULL,
+                FOREIGN KEY (file_id) REFERENCES files(id) ON DELETE CASCADE
+            );
+            "#,
+        )?;
+        Ok(())
+    }
+
+    /// Record the on-disk metadata of a package's hashed regular files,
+    /// returning how many were recorded
+    ///
+    /// Files that cannot be read are left without a record and are hashed
+    /// on every verify.
+    pub fn record_file_stats(&self, name: &str) -> Result<usize> {
+        let mut recorded = 0;
+        for file in self.get_package_files(name)? {
+            if file.file_type != FileType::Regular || file.blake3_hash.is_none() {
+                continue;
+            }
+            if let Ok(stat) = FileStat::of(Path::new(&file.path)) {
+                self.set_file_stat(&file.path, &stat)?;
+                recorded += 1;
+            }
+        }
+        Ok(recorded)
+    }
+
+    /// Record the metadata of an installed file
+    pub fn set_file_stat(&self, path: &str, stat: &FileStat) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO file_stats (file_id, size, mtime_ns, ctime_ns, inode)
+             SELECT id, ?, ?, ?, ? FROM files WHERE path = ?",
+            params![
+                stat.size as i64,
+                stat.mtime_ns,
+                stat.ctime_ns,
+                stat.inode as i64,
+                path
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Recorded metadata of a package's files, by path
+    pub fn get_file_stats(&self, name: &str) -> Result<HashMap<String, FileStat>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT f.path, s.size, s.mtime_ns, s.ctime_ns, s.inode
+             FROM file_stats s
+             JOIN files f ON f.id = s.file_id
+             JOIN packages p ON p.id = f.package_id
+             WHERE p.name = ?",
+        )?;
+        let stats = stmt
+            .query_map(params![name], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    FileStat {
+                        size: row.get::<_, i64>(1)? as u64,
+                        mtime_ns: row.get(2)?,
+                        ctime_ns: row.get(3)?,
+                        inode: row.get::<_, i64>(4)? as u64,
+                    },
+                ))
+            })?
+            .collect::<std::result::Result<HashMap<_, _>, _>>()?;
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{InstalledFile, InstalledPackage, PackageId};
+
+    fn package(path: &Path) -> InstalledPackage {
+        InstalledPackage {
+            id: PackageId::new("app-misc", "foo"),
+            name: "foo".to_string(),
+            version: semver::Version::new(1, 0, 0),
+            slot: "0".to_string(),
+            installed_at: chrono::Utc::now(),
+            use_flags: Default::default(),
+            files: vec![InstalledFile {
+                path: path.to_string_lossy().to_string(),
+                file_type: FileType::Regular,
+                mode: 0o644,
+                size: 5,
+                blake3_hash: Some("hash".to_string()),
+                mtime: 0,
+            }],
+            size: 5,
+            build_time: false,
+            explicit: true,
+        }
+    }
+
+    #[test]
+    fn test_stats_recorded_and_dropped_with_package() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("foo.conf");
+        std::fs::write(&file, "hello").unwrap();
+        let mut db = PackageDb::open(&dir.path().join("db")).unwrap();
+        db.add_package(&package(&file)).unwrap();
+
+        assert_eq!(db.record_file_stats("foo").unwrap(), 1);
+        let stats = db.get_file_stats("foo").unwrap();
+        let key = file.to_string_lossy().to_string();
+        assert_eq!(stats[&key], FileStat::of(&file).unwrap());
+        assert_eq!(stats[&key].size, 5);
+
+        // Reinstalling replaces the file rows and their stale records
+        db.add_package(&package(&file)).unwrap();
+        assert!(db.get_file_stats("foo").unwrap().is_empty());
+
+        db.record_file_stats("foo").unwrap();
+        db.remove_package("foo").unwrap();
+        assert!(db.get_file_stats("foo").unwrap().is_empty());
+    }
+}
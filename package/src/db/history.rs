@@ -0,0 +1,623 @@
+//! Audit trail of package operations
+//!
+//! Every transaction appends an entry recording who ran what, which
+//! packages changed from which version to which, and whether it succeeded.
+//! The tables are append-only: triggers reject updates and deletes.
+//!
+//! Transactions also record the resources they used, so the cost of
+//! building from source can be weighed against binhosts and remote caches,
+//! and the machine ID of the machine they ran on, so the trails of many
+//! machines collected in one place can be told apart.
+
+use super::PackageDb;
+use crate::{PackageId, Result};
+use buckos_core::MachineId;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// One package changed by a transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageChange {
+    /// Package
+    pub package: PackageId,
+    /// Slot
+    pub slot: String,
+    /// Version before the transaction, if it was installed
+    pub old_version: Option<String>,
+    /// Version after the transaction, if it is installed
+    pub new_version: Option<String>,
+}
+
+impl PackageChange {
+    /// The change that undoes this one
+    pub fn inverse(&self) -> Self {
+        Self {
+            package: self.package.clone(),
+            slot: self.slot.clone(),
+            old_version: self.new_version.clone(),
+            new_version: self.old_version.clone(),
+        }
+    }
+
+    /// Short description, e.g. `upgrade 1.0 -> 1.1`
+    pub fn describe(&self) -> String {
+        match (&self.old_version, &self.new_version) {
+            (None, Some(new)) => format!("install {}", new),
+            (Some(old), None) => format!("remove {}", old),
+            (Some(old), Some(new)) if old == new => format!("reinstall {}", new),
+            (Some(old), Some(new)) => format!("{} -> {}", old, new),
+            (None, None) => "no change".to_string(),
+        }
+    }
+}
+
+/// A recorded transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Transaction id
+    pub id: i64,
+    /// When it finished
+    pub timestamp: DateTime<Utc>,
+    /// User who ran it (the invoking user under sudo)
+    pub user: String,
+    /// Command line
+    pub command: String,
+    /// Whether it committed
+    pub success: bool,
+    /// Error message if it rolled back
+    pub error: Option<String>,
+    /// Packages changed
+    pub changes: Vec<PackageChange>,
+    /// Resources used, for entries recorded since usage was tracked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ResourceUsage>,
+    /// Machine ID of the machine it ran on, for entries recorded since
+    /// machine IDs were tracked on machines that have one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub machine_id: Option<String>,
+}
+
+/// Resources a transaction used
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    /// Wall-clock time, in milliseconds
+    pub wall_ms: u64,
+    /// User plus system CPU time of build processes, in milliseconds
+    pub cpu_ms: u64,
+    /// Peak resident memory of the largest build process, in bytes
+    pub peak_memory: u64,
+    /// Bytes received over the network
+    pub downloaded: u64,
+    /// Build actions served from Buck's cache instead of run
+    pub cache_hits: u64,
+    /// Packages merged from binary packages instead of built
+    pub prebuilt: u64,
+    /// Change in space used on the root filesystem, in bytes
+    pub disk_delta: i64,
+}
+
+impl HistoryEntry {
+    /// Changes that revert this transaction, in reverse order
+    ///
+    /// A rolled-back transaction changed nothing, so its inverse is empty.
+    pub fn inverse(&self) -> Vec<PackageChange> {
+        if !self.success {
+            return Vec::new();
+        }
+        self.changes
+            .iter()
+            .rev()
+            .map(PackageChange::inverse)
+            .collect()
+    }
+}
+
+/// Which entries to return from [`PackageDb::history`]
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    /// Only entries touching this package (`name` or `category/name`)
+    pub package: Option<String>,
+    /// Only entries at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Only entries after this id
+    pub after_id: Option<i64>,
+    /// Only entries up to and including this id
+    pub until_id: Option<i64>,
+}
+
+/// Net effect of a sequence of entries: the first old and last new version
+/// of every package touched, dropping packages that ended where they began
+pub fn net_changes(entries: &[HistoryEntry]) -> Vec<PackageChange> {
+    let mut net: BTreeMap<(PackageId, String), PackageChange> = BTreeMap::new();
+    let mut ordered: Vec<&HistoryEntry> = entries.iter().filter(|e| e.success).collect();
+    ordered.sort_by_key(|e| e.id);
+
+    for entry in ordered {
+        for change in &entry.changes {
+            net.entry((change.package.clone(), change.slot.clone()))
+                .and_modify(|c| c.new_version = change.new_version.clone())
+                .or_insert_with(|| change.clone());
+        }
+    }
+    net.into_values()
+        .filter(|c| c.old_version != c.new_version)
+        .collect()
+}
+
+/// Send an entry to the local syslog daemon
+///
+/// Failures are ignored: syslog is a convenience copy, the database is the
+/// record.
+pub fn emit_syslog(entry: &HistoryEntry) {
+    use std::os::unix::net::UnixDatagram;
+
+    let changes: Vec<String> = entry
+        .changes
+        .iter()
+        .map(|c| format!("{} {}", c.package, c.describe()))
+        .collect();
+    // facility user (1), severity notice (5) or err (3)
+    let priority = if entry.success { 8 + 5 } else { 8 + 3 };
+    let machine = entry
+        .machine_id
+        .as_ref()
+        .map(|id| format!(" machine={}", id))
+        .unwrap_or_default();
+    let message = format!(
+        "<{}>buckos[{}]: txn={} user={}{} result={} cmd=\"{}\" changes=[{}]",
+        priority,
+        std::process::id(),
+        entry.id,
+        entry.user,
+        machine,
+        if entry.success { "success" } else { "failed" },
+        entry.command,
+        changes.join(", ")
+    );
+
+    let sent = UnixDatagram::unbound().and_then(|socket| {
+        socket.connect("/dev/log")?;
+        socket.send(message.as_bytes())
+    });
+    if let Err(e) = sent {
+        tracing::debug!("Failed to send audit entry to syslog: {}", e);
+    }
+}
+
+impl PackageDb {
+    /// Create the audit tables
+    pub(super) fn init_history_schema(&self) -> Result<()> {
+        self.conn.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp TEXT NOT NULL,
+                user TEXT NOT NULL,
+                command TEXT NOT NULL,
+                success INTEGER NOT NULL,
+                error TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS history_changes (
+                entry_id INTEGER NOT NULL,
+                seq INTEGER NOT NULL,
+                category TEXT NOT NULL,
+                name TEXT NOT NULL,
+                slot TEXT NOT NULL,
+                old_version TEXT,
+                new_version TEXT,
+                FOREIGN KEY (entry_id) REFERENCES history(id),
+                PRIMARY KEY (entry_id, seq)
+            );
+
+            CREATE TABLE IF NOT EXISTS history_usage (
+                entry_id INTEGER PRIMARY KEY,
+                wall_ms INTEGER NOT NULL,
+                cpu_ms INTEGER NOT NULL,
+                peak_memory INTEGER NOT NULL,
+                downloaded INTEGER NOT NULL,
+                cache_hits INTEGER NOT NULL,
+                prebuilt INTEGER NOT NULL,
+                disk_delta INTEGER NOT NULL,
+                FOREIGN KEY (entry_id) REFERENCES history(id)
+            );
+
+            CREATE TABLE IF NOT EXISTS history_machine (
+                entry_id INTEGER PRIMARY KEY,
+                machine_id TEXT NOT NULL,
+                FOREIGN KEY (entry_id) REFERENCES history(id)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_history_changes_name ON history_changes(name);
+
+            CREATE TRIGGER IF NOT EXISTS history_no_update BEFORE UPDATE ON history
+            BEGIN SELECT RAISE(ABORT, 'history is append-only'); END;
+            CREATE TRIGGER IF NOT EXISTS history_no_delete BEFORE DELETE ON history
+            BEGIN SELECT RAISE(ABORT, 'history is append-only'); END;
+            CREATE TRIGGER IF NOT EXISTS history_changes_no_update BEFORE UPDATE ON history_changes
+            BEGIN SELECT RAISE(ABORT, 'history is append-only'); END;
+            CREATE TRIGGER IF NOT EXISTS history_changes_no_delete BEFORE DELETE ON history_changes
+            BEGIN SELECT RAISE(ABORT, 'history is append-only'); END;
+            CREATE TRIGGER IF NOT EXISTS history_usage_no_update BEFORE UPDATE ON history_usage
+            BEGIN SELECT RAISE(ABORT, 'history is append-only'); END;
+            CREATE TRIGGER IF NOT EXISTS history_usage_no_delete BEFORE DELETE ON history_usage
+            BEGIN SELECT RAISE(ABORT, 'history is append-only'); END;
+            CREATE TRIGGER IF NOT EXISTS history_machine_no_update BEFORE UPDATE ON history_machine
+            BEGIN SELECT RAISE(ABORT, 'history is append-only'); END;
+            CREATE TRIGGER IF NOT EXISTS history_machine_no_delete BEFORE DELETE ON history_machine
+            BEGIN SELECT RAISE(ABORT, 'history is append-only'); END;
+            "#,
+        )?;
+        Ok(())
+    }
+
+    /// Append an entry, returning it with its assigned id
+    ///
+    /// The entry is stamped with this machine's ID, if it has one. Must be
+    /// called outside of a database transaction so that rolled-back
+    /// operations are recorded too.
+    pub fn record_history(
+        &mut self,
+        user: &str,
+        command: &str,
+        error: Option<&str>,
+        changes: &[PackageChange],
+        usage: Option<&ResourceUsage>,
+    ) -> Result<HistoryEntry> {
+        let timestamp = Utc::now();
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT INTO history (timestamp, user, command, success, error)
+             VALUES (?, ?, ?, ?, ?)",
+            params![
+                timestamp.to_rfc3339(),
+                user,
+                command,
+                error.is_none(),
+                error
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        for (seq, change) in changes.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO history_changes
+                 (entry_id, seq, category, name, slot, old_version, new_version)
+                 VALUES (?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    id,
+                    seq as i64,
+                    change.package.category,
+                    change.package.name,
+                    change.slot,
+                    change.old_version,
+                    change.new_version
+                ],
+            )?;
+        }
+        if let Some(usage) = usage {
+            insert_usage(&tx, id, usage)?;
+        }
+        let machine_id = MachineId::read().map(|id| id.to_string());
+        if let Some(machine_id) = &machine_id {
+            insert_machine_id(&tx, id, machine_id)?;
+        }
+        tx.commit()?;
+
+        Ok(HistoryEntry {
+            id,
+            timestamp,
+            user: user.to_string(),
+            command: command.to_string(),
+            success: error.is_none(),
+            error: error.map(String::from),
+            changes: changes.to_vec(),
+            usage: usage.cloned(),
+            machine_id,
+        })
+    }
+
+    /// Entries matching a filter, oldest first
+    pub fn history(&self, filter: &HistoryFilter) -> Result<Vec<HistoryEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, timestamp, user, command, success, error FROM history
+             WHERE id > ?1 AND id <= ?2 ORDER BY id",
+        )?;
+        let rows = stmt.query_map(
+            params![
+                filter.after_id.unwrap_or(0),
+                filter.until_id.unwrap_or(i64::MAX)
+            ],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, bool>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                ))
+            },
+        )?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let (id, timestamp, user, command, success, error) = row?;
+            let timestamp = DateTime::parse_from_rfc3339(&timestamp)
+                .map(|t| t.with_timezone(&Utc))
+                .unwrap_or_default();
+            if filter.since.is_some_and(|since| timestamp < since) {
+                continue;
+            }
+            let changes = self.history_changes(id)?;
+            if let Some(package) = &filter.package {
+                let matches = changes
+                    .iter()
+                    .any(|c| c.package.name == *package || c.package.full_name() == *package);
+                if !matches {
+                    continue;
+                }
+            }
+            entries.push(HistoryEntry {
+                id,
+                timestamp,
+                user,
+                command,
+                success,
+                error,
+                changes,
+                usage: self.history_usage(id)?,
+                machine_id: self.history_machine_id(id)?,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// A single entry by id
+    pub fn history_entry(&self, id: i64) -> Result<Option<HistoryEntry>> {
+        let filter = HistoryFilter {
+            after_id: Some(id - 1),
+            until_id: Some(id),
+            ..Default::default()
+        };
+        Ok(self.history(&filter)?.into_iter().next())
+    }
+
+    /// Id of the most recent entry at or before a time
+    pub fn history_id_at(&self, time: DateTime<Utc>) -> Result<Option<i64>> {
+        // RFC 3339 timestamps in UTC sort lexicographically
+        let id = self
+            .conn
+            .query_row(
+                "SELECT MAX(id) FROM history WHERE timestamp <= ?",
+                params![time.to_rfc3339()],
+                |row| row.get::<_, Option<i64>>(0),
+            )
+            .optional()?
+            .flatten();
+        Ok(id)
+    }
+
+    fn history_changes(&self, entry_id: i64) -> Result<Vec<PackageChange>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT category, name, slot, old_version, new_version FROM history_changes
+             WHERE entry_id = ? ORDER BY seq",
+        )?;
+        let changes = stmt
+            .query_map(params![entry_id], |row| {
+                Ok(PackageChange {
+                    package: PackageId::new(row.get::<_, String>(0)?, row.get::<_, String>(1)?),
+                    slot: row.get(2)?,
+                    old_version: row.get(3)?,
+                    new_version: row.get(4)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(changes)
+    }
+
+    fn history_usage(&self, entry_id: i64) -> Result<Option<ResourceUsage>> {
+        let usage = self
+            .conn
+            .query_row(
+                "SELECT wall_ms, cpu_ms, peak_memory, downloaded, cache_hits, prebuilt,
+                 disk_delta FROM history_usage WHERE entry_id = ?",
+                params![entry_id],
+                |row| {
+                    Ok(ResourceUsage {
+                        wall_ms: row.get::<_, i64>(0)? as u64,
+                        cpu_ms: row.get::<_, i64>(1)? as u64,
+                        peak_memory: row.get::<_, i64>(2)? as u64,
+                        downloaded: row.get::<_, i64>(3)? as u64,
+                        cache_hits: row.get::<_, i64>(4)? as u64,
+                        prebuilt: row.get::<_, i64>(5)? as u64,
+                        disk_delta: row.get(6)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(usage)
+    }
+
+    fn history_machine_id(&self, entry_id: i64) -> Result<Option<String>> {
+        let machine_id = self
+            .conn
+            .query_row(
+                "SELECT machine_id FROM history_machine WHERE entry_id = ?",
+                params![entry_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(machine_id)
+    }
+}
+
+/// Store the machine an entry was recorded on
+pub(super) fn insert_machine_id(
+    conn: &rusqlite::Connection,
+    entry_id: i64,
+    machine_id: &str,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO history_machine (entry_id, machine_id) VALUES (?, ?)",
+        params![entry_id, machine_id],
+    )?;
+    Ok(())
+}
+
+/// Store the resources an entry used
+pub(super) fn insert_usage(
+    conn: &rusqlite::Connection,
+    entry_id: i64,
+    usage: &ResourceUsage,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO history_usage
+         (entry_id, wall_ms, cpu_ms, peak_memory, downloaded, cache_hits, prebuilt,
+          disk_delta)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            entry_id,
+            usage.wall_ms as i64,
+            usage.cpu_ms as i64,
+            usage.peak_memory as i64,
+            usage.downloaded as i64,
+            usage.cache_hits as i64,
+            usage.prebuilt as i64,
+            usage.disk_delta
+        ],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(name: &str, old: Option<&str>, new: Option<&str>) -> PackageChange {
+        PackageChange {
+            package: PackageId::new("app-misc", name),
+            slot: "0".to_string(),
+            old_version: old.map(String::from),
+            new_version: new.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_record_and_query() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = PackageDb::open(dir.path()).unwrap();
+
+        let usage = ResourceUsage {
+            wall_ms: 61_000,
+            cpu_ms: 240_000,
+            peak_memory: 1 << 30,
+            downloaded: 5 << 20,
+            cache_hits: 40,
+            prebuilt: 1,
+            disk_delta: -4096,
+        };
+        let first = db
+            .record_history(
+                "root",
+                "buckos install foo",
+                None,
+                &[change("foo", None, Some("1.0.0"))],
+                Some(&usage),
+            )
+            .unwrap();
+        db.record_history(
+            "root",
+            "buckos update",
+            Some("build failed"),
+            &[change("bar", Some("1.0.0"), Some("2.0.0"))],
+            None,
+        )
+        .unwrap();
+
+        let all = db.history(&HistoryFilter::default()).unwrap();
+        assert_eq!(all.len(), 2);
+        assert!(!all[1].success);
+        assert_eq!(all[1].error.as_deref(), Some("build failed"));
+        assert_eq!(all[0].usage.as_ref(), Some(&usage));
+        assert_eq!(all[1].usage, None);
+        let machine_id = MachineId::read().map(|id| id.to_string());
+        assert_eq!(all[0].machine_id, machine_id);
+
+        let foo = HistoryFilter {
+            package: Some("app-misc/foo".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(db.history(&foo).unwrap(), vec![first.clone()]);
+        assert_eq!(db.history_entry(first.id).unwrap(), Some(first));
+
+        // Append-only
+        assert!(db.conn.execute("DELETE FROM history", []).is_err());
+        assert!(db
+            .conn
+            .execute("UPDATE history_changes SET new_version = 'x'", [])
+            .is_err());
+        assert!(db.conn.execute("DELETE FROM history_usage", []).is_err());
+
+        // Entries recorded without a machine ID, e.g. on a machine before
+        // its first boot, have none
+        db.conn
+            .execute(
+                "INSERT INTO history (timestamp, user, command, success) VALUES (?, 'root', '', 1)",
+                params![Utc::now().to_rfc3339()],
+            )
+            .unwrap();
+        let id = db.conn.last_insert_rowid();
+        assert_eq!(db.history_entry(id).unwrap().unwrap().machine_id, None);
+        insert_machine_id(&db.conn, id, "0123456789abcdef0123456789abcdef").unwrap();
+        assert_eq!(
+            db.history_entry(id).unwrap().unwrap().machine_id.as_deref(),
+            Some("0123456789abcdef0123456789abcdef")
+        );
+        assert!(db.conn.execute("DELETE FROM history_machine", []).is_err());
+    }
+
+    #[test]
+    fn test_net_changes_and_inverse() {
+        let entry = |id, success, changes| HistoryEntry {
+            id,
+            timestamp: Utc::now(),
+            user: "root".to_string(),
+            command: String::new(),
+            success,
+            error: None,
+            changes,
+            usage: None,
+            machine_id: None,
+        };
+        let entries = vec![
+            entry(1, true, vec![change("foo", None, Some("1.0"))]),
+            entry(
+                2,
+                true,
+                vec![
+                    change("foo", Some("1.0"), Some("1.1")),
+                    change("bar", Some("2.0"), None),
+                ],
+            ),
+            entry(3, false, vec![change("baz", None, Some("3.0"))]),
+            entry(4, true, vec![change("bar", None, Some("2.0"))]),
+        ];
+
+        // bar was removed and reinstalled at the same version: no net change
+        assert_eq!(
+            net_changes(&entries),
+            vec![change("foo", None, Some("1.1"))]
+        );
+
+        assert_eq!(
+            entries[1].inverse(),
+            vec![
+                change("bar", None, Some("2.0")),
+                change("foo", Some("1.1"), Some("1.0")),
+            ]
+        );
+        assert!(entries[2].inverse().is_empty());
+    }
+}
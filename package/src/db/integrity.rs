@@ -0,0 +1,379 @@
+//! Corruption detection and repair of the package database
+//!
+//! SQLite's own checks catch damaged pages; comparing the database with
+//! the plain-text records in [`super::vdb`] catches packages whose rows
+//! were lost or only partly written, and packages without a record.
+//! Repair rebuilds the affected packages from the records, or the whole
+//! database when SQLite cannot read it, and writes missing records from
+//! the database.
+
+use super::vdb::{Vdb, VdbEntry};
+use super::PackageDb;
+use crate::{Error, Result};
+use rusqlite::params;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Something wrong with the package database
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IntegrityProblem {
+    /// SQLite reported damage
+    Corrupt { message: String },
+    /// A recorded package has no row in the database
+    MissingPackage { package: String },
+    /// A package's row has fewer files than its record
+    MissingFiles {
+        package: String,
+        rows: usize,
+        recorded: usize,
+    },
+    /// An installed package has no plain-text record
+    MissingRecord { package: String },
+}
+
+impl IntegrityProblem {
+    /// Short description
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Corrupt { message } => format!("database is damaged: {}", message),
+            Self::MissingPackage { package } => {
+                format!("{} is recorded but missing from the database", package)
+            }
+            Self::MissingFiles {
+                package,
+                rows,
+                recorded,
+            } => format!(
+                "{} has {} of its {} recorded files in the database",
+                package, rows, recorded
+            ),
+            Self::MissingRecord { package } => {
+                format!("{} is installed but has no package record", package)
+            }
+        }
+    }
+}
+
+/// What a repair did
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RepairReport {
+    /// Packages re-added from their records
+    pub restored: Vec<String>,
+    /// Packages whose record was written from the database
+    pub recorded: Vec<String>,
+    /// Where the damaged database was moved, when it was rebuilt
+    pub moved_aside: Option<PathBuf>,
+}
+
+impl PackageDb {
+    /// Check the database against SQLite's checks and the package records
+    ///
+    /// `full` runs `PRAGMA integrity_check`; otherwise the faster
+    /// `quick_check`, which skips index consistency, is used.
+    pub fn check_integrity(&self, vdb: &Vdb, full: bool) -> Result<Vec<IntegrityProblem>> {
+        let pragma = if full {
+            "PRAGMA integrity_check"
+        } else {
+            "PRAGMA quick_check"
+        };
+        let mut stmt = self.conn.prepare(pragma)?;
+        let mut problems: Vec<IntegrityProblem> = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+            .into_iter()
+            .filter(|message| message != "ok")
+            .map(|message| IntegrityProblem::Corrupt { message })
+            .collect();
+        if !problems.is_empty() {
+            return Ok(problems);
+        }
+
+        let rows = self.package_file_counts()?;
+        let entries = vdb.entries()?;
+        let recorded: HashSet<(String, String)> = entries
+            .iter()
+            .map(|e| (e.id.full_name(), e.version.to_string()))
+            .collect();
+        for entry in entries {
+            let key = (entry.id.full_name(), entry.version.to_string());
+            match rows.get(&key) {
+                None => problems.push(IntegrityProblem::MissingPackage {
+                    package: entry.to_string(),
+                }),
+                // Reading every record is slow, so only packages with no
+                // file rows at all are compared with theirs
+                Some(0) => {
+                    let recorded = vdb.file_count(&entry)?;
+                    if recorded > 0 {
+                        problems.push(IntegrityProblem::MissingFiles {
+                            package: entry.to_string(),
+                            rows: 0,
+                            recorded,
+                        });
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+        let mut unrecorded: Vec<String> = rows
+            .keys()
+            .filter(|key| !recorded.contains(*key))
+            .map(|(name, version)| format!("{}-{}", name, version))
+            .collect();
+        unrecorded.sort();
+        problems.extend(
+            unrecorded
+                .into_iter()
+                .map(|package| IntegrityProblem::MissingRecord { package }),
+        );
+        Ok(problems)
+    }
+
+    /// Write the records flagged missing by
+    /// [`check_integrity`](Self::check_integrity), returning the packages
+    /// written
+    pub fn write_missing_records(
+        &self,
+        vdb: &Vdb,
+        problems: &[IntegrityProblem],
+    ) -> Result<Vec<String>> {
+        let missing: HashSet<&str> = problems
+            .iter()
+            .filter_map(|p| match p {
+                IntegrityProblem::MissingRecord { package } => Some(package.as_str()),
+                _ => None,
+            })
+            .collect();
+        let mut written = Vec::new();
+        if missing.is_empty() {
+            return Ok(written);
+        }
+        for pkg in self.get_all_installed()? {
+            let atom = format!("{}-{}", pkg.id, pkg.version);
+            if !missing.contains(atom.as_str()) {
+                continue;
+            }
+            if let Some(record) = self.package_record(&pkg.name)? {
+                vdb.write(&record)?;
+                written.push(atom);
+            }
+        }
+        Ok(written)
+    }
+
+    /// Re-add packages flagged by [`check_integrity`](Self::check_integrity)
+    /// from their records
+    ///
+    /// `explicit` holds the `category/name` of packages in the world set.
+    pub fn repair(
+        &mut self,
+        vdb: &Vdb,
+        problems: &[IntegrityProblem],
+        explicit: &HashSet<String>,
+    ) -> Result<RepairReport> {
+        if problems
+            .iter()
+            .any(|p| matches!(p, IntegrityProblem::Corrupt { .. }))
+        {
+            return Err(Error::DatabaseError(
+                "the database is damaged and must be rebuilt".to_string(),
+            ));
+        }
+
+        let damaged: HashSet<&str> = problems
+            .iter()
+            .filter_map(|p| match p {
+                IntegrityProblem::MissingPackage { package }
+                | IntegrityProblem::MissingFiles { package, .. } => Some(package.as_str()),
+                _ => None,
+            })
+            .collect();
+        let entries: Vec<VdbEntry> = vdb
+            .entries()?
+            .into_iter()
+            .filter(|e| damaged.contains(e.to_string().as_str()))
+            .collect();
+
+        let mut report = RepairReport {
+            recorded: self.write_missing_records(vdb, problems)?,
+            ..Default::default()
+        };
+        self.begin_transaction()?;
+        let result = (|| -> Result<()> {
+            for entry in &entries {
+                let record = vdb.read(entry, explicit.contains(&entry.id.full_name()))?;
+                let pkg = &record.package;
+                self.conn.execute(
+                    "DELETE FROM packages WHERE category = ? AND name = ? AND version = ?",
+                    params![pkg.id.category, pkg.name, pkg.version.to_string()],
+                )?;
+                self.add_record(&record)?;
+                report.restored.push(entry.to_string());
+            }
+            Ok(())
+        })();
+        match result {
+            Ok(()) => self.commit()?,
+            Err(e) => {
+                let _ = self.rollback();
+                return Err(e);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Move a damaged database aside and build a new one from the records
+    pub fn rebuild(path: &Path, vdb: &Vdb, explicit: &HashSet<String>) -> Result<RepairReport> {
+        let db_file = path.join("packages.db");
+        let mut report = RepairReport::default();
+        if db_file.exists() {
+            let aside = path.join(format!(
+                "packages.db.corrupt-{}",
+                chrono::Utc::now().format("%Y%m%d%H%M%S")
+            ));
+            std::fs::rename(&db_file, &aside)?;
+            for suffix in ["-wal", "-shm", "-journal"] {
+                let _ = std::fs::remove_file(path.join(format!("packages.db{}", suffix)));
+            }
+            report.moved_aside = Some(aside);
+        }
+
+        let mut db = Self::open(path)?;
+        db.begin_transaction()?;
+        for entry in vdb.entries()? {
+            let record = vdb.read(&entry, explicit.contains(&entry.id.full_name()))?;
+            db.add_record(&record)?;
+            report.restored.push(entry.to_string());
+        }
+        db.commit()?;
+        Ok(report)
+    }
+
+    /// File row count of every package, keyed by `category/name` and version
+    fn package_file_counts(&self) -> Result<HashMap<(String, String), usize>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT p.category, p.name, p.version, COUNT(f.id) FROM packages p
+             LEFT JOIN files f ON f.package_id = p.id GROUP BY p.id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                format!("{}/{}", row.get::<_, String>(0)?, row.get::<_, String>(1)?),
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+            ))
+        })?;
+        let mut counts = HashMap::new();
+        for row in rows {
+            let (name, version, count) = row?;
+            counts.insert((name, version), count as usize);
+        }
+        Ok(counts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::PackageRecord;
+    use crate::{FileType, InstalledFile, InstalledPackage, PackageId};
+
+    fn package(name: &str) -> InstalledPackage {
+        InstalledPackage {
+            id: PackageId::new("app-misc", name),
+            name: name.to_string(),
+            version: semver::Version::new(1, 0, 0),
+            slot: "0".to_string(),
+            installed_at: chrono::Utc::now(),
+            use_flags: Default::default(),
+            files: vec![InstalledFile {
+                path: format!("/usr/bin/{}", name),
+                file_type: FileType::Regular,
+                mode: 0o755,
+                size: 1,
+                blake3_hash: None,
+                mtime: 0,
+            }],
+            size: 1,
+            build_time: false,
+            explicit: true,
+        }
+    }
+
+    #[test]
+    fn test_detect_and_repair() {
+        let dir = tempfile::tempdir().unwrap();
+        let vdb = Vdb::new(dir.path());
+        let mut db = PackageDb::open(dir.path()).unwrap();
+        for name in ["foo", "bar", "baz"] {
+            db.add_package(&package(name)).unwrap();
+            vdb.write(&PackageRecord::new(package(name))).unwrap();
+        }
+        assert!(db.check_integrity(&vdb, true).unwrap().is_empty());
+
+        db.remove_package("foo").unwrap();
+        db.conn
+            .execute("DELETE FROM files WHERE path = '/usr/bin/bar'", [])
+            .unwrap();
+        db.add_package(&package("qux")).unwrap();
+        let problems = db.check_integrity(&vdb, false).unwrap();
+        assert_eq!(
+            problems,
+            vec![
+                IntegrityProblem::MissingFiles {
+                    package: "app-misc/bar-1.0.0".to_string(),
+                    rows: 0,
+                    recorded: 1
+                },
+                IntegrityProblem::MissingPackage {
+                    package: "app-misc/foo-1.0.0".to_string()
+                },
+                IntegrityProblem::MissingRecord {
+                    package: "app-misc/qux-1.0.0".to_string()
+                },
+            ]
+        );
+
+        let explicit = HashSet::from(["app-misc/foo".to_string()]);
+        let report = db.repair(&vdb, &problems, &explicit).unwrap();
+        assert_eq!(report.restored.len(), 2);
+        assert_eq!(report.recorded, vec!["app-misc/qux-1.0.0".to_string()]);
+        assert!(db.check_integrity(&vdb, true).unwrap().is_empty());
+        assert_eq!(db.get_package_files("bar").unwrap().len(), 1);
+        assert!(db.get_installed("foo").unwrap().unwrap().explicit);
+    }
+
+    #[test]
+    fn test_rebuild_moves_damaged_database_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let vdb = Vdb::new(dir.path());
+        vdb.write(&PackageRecord::new(package("foo"))).unwrap();
+        std::fs::write(dir.path().join("packages.db"), b"not a database").unwrap();
+        assert!(PackageDb::open(dir.path()).is_err());
+
+        let report = PackageDb::rebuild(dir.path(), &vdb, &HashSet::new()).unwrap();
+        assert!(report.moved_aside.unwrap().exists());
+        let db = PackageDb::open(dir.path()).unwrap();
+        assert!(!db.get_installed("foo").unwrap().unwrap().explicit);
+    }
+
+    #[test]
+    fn test_unclean_marker() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = PackageDb::open(dir.path()).unwrap();
+        db.begin_transaction().unwrap();
+        db.add_package(&package("foo")).unwrap();
+        db.commit().unwrap();
+        assert!(!db.was_unclean());
+
+        // A transaction still open when the process died
+        db.begin_transaction().unwrap();
+        drop(db);
+        let db = PackageDb::open(dir.path()).unwrap();
+        assert!(db.was_unclean());
+        db.clear_unclean().unwrap();
+        assert!(!db.was_unclean());
+        assert!(!PackageDb::open_read_only(dir.path()).unwrap().was_unclean());
+    }
+}
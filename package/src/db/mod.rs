@@ -2,18 +2,41 @@
 //!
 //! Uses SQLite for reliable, ACID-compliant storage of package metadata.
 
+pub mod attempts;
+pub mod backup;
 pub mod collision;
-
+pub mod durations;
+pub mod filestat;
+pub mod history;
+pub mod integrity;
+pub mod record;
+pub mod vdb;
+pub mod verity;
+
+pub use attempts::{BuildAttempt, BuildFlakiness};
+pub use backup::{BackupCheck, DbBackup};
 pub use collision::*;
+pub use filestat::FileStat;
+pub use history::*;
+pub use integrity::{IntegrityProblem, RepairReport};
+pub use record::{BuildInfo, DependencyRecord, LiveCommit, PackageRecord};
+pub use vdb::Vdb;
 
+use crate::patches::AppliedPatch;
 use crate::{Error, InstalledFile, InstalledPackage, PackageId, Result};
 use rusqlite::{params, Connection, OptionalExtension};
-use std::collections::HashSet;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// File left in the database directory while a write transaction is open,
+/// so a start after a crash knows to check the database
+pub const UNCLEAN_MARKER: &str = "transaction.pending";
 
 /// Package database
 pub struct PackageDb {
     conn: Connection,
+    /// Database directory; `None` when opened read-only
+    dir: Option<PathBuf>,
 }
 
 impl PackageDb {
@@ -25,12 +48,25 @@ impl PackageDb {
         let db_file = path.join("packages.db");
         let conn = Connection::open(&db_file)?;
 
-        let db = Self { conn };
+        let db = Self {
+            conn,
+            dir: Some(path.to_path_buf()),
+        };
         db.init_schema()?;
 
         Ok(db)
     }
 
+    /// Open an existing package database without writing to it, e.g. the
+    /// system's from a per-user prefix
+    pub fn open_read_only(path: &Path) -> Result<Self> {
+        let conn = Connection::open_with_flags(
+            path.join("packages.db"),
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+        )?;
+        Ok(Self { conn, dir: None })
+    }
+
     /// Initialize database schema
     fn init_schema(&self) -> Result<()> {
         self.conn.execute_batch(
@@ -49,6 +85,25 @@ impl PackageDb {
                 UNIQUE(category, name, slot)
             );
 
+            -- User patches applied when building each package
+            CREATE TABLE IF NOT EXISTS package_patches (
+                package_id INTEGER NOT NULL,
+                seq INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                sha256 TEXT NOT NULL,
+                strip INTEGER,
+                FOREIGN KEY (package_id) REFERENCES packages(id) ON DELETE CASCADE,
+                PRIMARY KEY (package_id, seq)
+            );
+
+            -- Commit each live (VCS) package was built from
+            CREATE TABLE IF NOT EXISTS live_commits (
+                package_id INTEGER PRIMARY KEY,
+                url TEXT NOT NULL,
+                commit_id TEXT NOT NULL,
+                FOREIGN KEY (package_id) REFERENCES packages(id) ON DELETE CASCADE
+            );
+
             -- Package USE flags
             CREATE TABLE IF NOT EXISTS package_use_flags (
                 package_id INTEGER NOT NULL,
@@ -92,6 +147,12 @@ impl PackageDb {
             PRAGMA foreign_keys = ON;
             "#,
         )?;
+        self.init_history_schema()?;
+        self.init_record_schema()?;
+        self.init_durations_schema()?;
+        self.init_attempts_schema()?;
+        self.init_filestat_schema()?;
+        self.init_verity_schema()?;
 
         Ok(())
     }
@@ -245,6 +306,70 @@ impl PackageDb {
         Ok(pkg_id)
     }
 
+    /// Record the user patches a package was built with
+    pub fn set_package_patches(&mut self, name: &str, patches: &[AppliedPatch]) -> Result<()> {
+        let pkg_id: i64 = self.conn.query_row(
+            "SELECT id FROM packages WHERE name = ?",
+            params![name],
+            |row| row.get(0),
+        )?;
+        self.conn.execute(
+            "DELETE FROM package_patches WHERE package_id = ?",
+            params![pkg_id],
+        )?;
+        for (seq, patch) in patches.iter().enumerate() {
+            self.conn.execute(
+                "INSERT INTO package_patches (package_id, seq, name, sha256, strip)
+                 VALUES (?, ?, ?, ?, ?)",
+                params![pkg_id, seq as i64, patch.name, patch.sha256, patch.strip],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// User patches an installed package was built with, in applied order
+    pub fn get_package_patches(&self, name: &str) -> Result<Vec<AppliedPatch>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT pp.name, pp.sha256, pp.strip FROM package_patches pp
+             JOIN packages p ON p.id = pp.package_id
+             WHERE p.name = ? ORDER BY pp.seq",
+        )?;
+        let patches = stmt
+            .query_map(params![name], |row| {
+                Ok(AppliedPatch {
+                    name: row.get(0)?,
+                    sha256: row.get(1)?,
+                    strip: row.get(2)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(patches)
+    }
+
+    /// Record the commit a live package was built from
+    pub fn set_live_commit(&mut self, name: &str, url: &str, commit: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO live_commits (package_id, url, commit_id)
+             SELECT id, ?, ? FROM packages WHERE name = ?",
+            params![url, commit, name],
+        )?;
+        Ok(())
+    }
+
+    /// Commit an installed live package was built from
+    pub fn get_live_commit(&self, name: &str) -> Result<Option<String>> {
+        let commit = self
+            .conn
+            .query_row(
+                "SELECT lc.commit_id FROM live_commits lc
+                 JOIN packages p ON p.id = lc.package_id WHERE p.name = ?",
+                params![name],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(commit)
+    }
+
     /// Remove a package from the database
     pub fn remove_package(&mut self, name: &str) -> Result<()> {
         self.conn
@@ -252,6 +377,23 @@ impl PackageDb {
         Ok(())
     }
 
+    /// Rename an installed package (package move)
+    ///
+    /// Dependency records pointing at the old name are updated too. Returns
+    /// whether the package was installed under the old name.
+    pub fn rename_package(&mut self, from: &PackageId, to: &PackageId) -> Result<bool> {
+        let renamed = self.conn.execute(
+            "UPDATE packages SET category = ?, name = ? WHERE category = ? AND name = ?",
+            params![to.category, to.name, from.category, from.name],
+        )?;
+        self.conn.execute(
+            "UPDATE OR IGNORE dependencies SET dep_category = ?, dep_name = ?
+             WHERE dep_category = ? AND dep_name = ?",
+            params![to.category, to.name, from.category, from.name],
+        )?;
+        Ok(renamed > 0)
+    }
+
     /// Add a file to a package
     fn add_file(&self, pkg_id: i64, file: &InstalledFile) -> Result<()> {
         self.conn.execute(
@@ -304,6 +446,7 @@ impl PackageDb {
                     3 => crate::FileType::Hardlink,
                     4 => crate::FileType::Device,
                     5 => crate::FileType::Fifo,
+                    6 => crate::FileType::Masked,
                     _ => crate::FileType::Regular,
                 },
                 mode: row.get(2)?,
@@ -352,6 +495,25 @@ impl PackageDb {
         Ok(result)
     }
 
+    /// Reverse dependencies of every installed package, keyed by the name
+    /// of the package depended upon
+    pub fn reverse_dependency_map(&self) -> Result<HashMap<String, Vec<String>>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT DISTINCT d.dep_name, p.name FROM packages p
+             JOIN dependencies d ON p.id = d.package_id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let mut result: HashMap<String, Vec<String>> = HashMap::new();
+        for row in rows {
+            let (dep, dependent) = row?;
+            result.entry(dep).or_default().push(dependent);
+        }
+        Ok(result)
+    }
+
     /// Add a dependency relationship
     pub fn add_dependency(
         &self,
@@ -438,6 +600,9 @@ impl PackageDb {
 
     /// Begin a transaction
     pub fn begin_transaction(&mut self) -> Result<()> {
+        if let Some(dir) = &self.dir {
+            std::fs::write(dir.join(UNCLEAN_MARKER), b"")?;
+        }
         self.conn.execute("BEGIN TRANSACTION", [])?;
         Ok(())
     }
@@ -445,12 +610,31 @@ impl PackageDb {
     /// Commit a transaction
     pub fn commit(&mut self) -> Result<()> {
         self.conn.execute("COMMIT", [])?;
-        Ok(())
+        self.clear_unclean()
     }
 
     /// Rollback a transaction
     pub fn rollback(&mut self) -> Result<()> {
         self.conn.execute("ROLLBACK", [])?;
-        Ok(())
+        self.clear_unclean()
+    }
+
+    /// Whether a write transaction was left open, e.g. by a crash or a
+    /// power loss
+    pub fn was_unclean(&self) -> bool {
+        self.dir
+            .as_ref()
+            .is_some_and(|dir| dir.join(UNCLEAN_MARKER).exists())
+    }
+
+    /// Forget an unclean shutdown once the database has been checked
+    pub fn clear_unclean(&self) -> Result<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        match std::fs::remove_file(dir.join(UNCLEAN_MARKER)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
     }
 }
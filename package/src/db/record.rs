@@ -0,0 +1,188 @@
+//! Everything recorded about one installed package
+//!
+//! The package row, its files and USE flags live in [`InstalledPackage`];
+//! dependencies, user patches, the live commit and build details are kept
+//! in side tables. A [`PackageRecord`] gathers them so backups and the
+//! plain-text records can copy a package as a whole.
+
+use super::PackageDb;
+use crate::patches::AppliedPatch;
+use crate::{InstalledPackage, PackageId, Result};
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+/// An installed package and everything recorded about it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageRecord {
+    #[serde(flatten)]
+    pub package: InstalledPackage,
+    #[serde(default)]
+    pub dependencies: Vec<DependencyRecord>,
+    #[serde(default)]
+    pub patches: Vec<AppliedPatch>,
+    #[serde(default)]
+    pub live_commit: Option<LiveCommit>,
+    #[serde(default)]
+    pub build_info: Option<BuildInfo>,
+}
+
+impl PackageRecord {
+    /// A record with nothing beyond the package row
+    pub fn new(package: InstalledPackage) -> Self {
+        Self {
+            package,
+            dependencies: Vec::new(),
+            patches: Vec::new(),
+            live_commit: None,
+            build_info: None,
+        }
+    }
+}
+
+/// A recorded dependency of an installed package
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencyRecord {
+    pub package: PackageId,
+    pub slot: Option<String>,
+    pub build_time: bool,
+    pub run_time: bool,
+}
+
+/// Commit a live package was built from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LiveCommit {
+    pub url: String,
+    pub commit: String,
+}
+
+/// Where an installed package came from and how it was built
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildInfo {
+    /// Repository providing the package, when known
+    pub repository: Option<String>,
+    /// Buck target it was built from
+    pub buck_target: String,
+    /// Merged from a prebuilt image rather than built
+    pub prebuilt: bool,
+}
+
+impl PackageDb {
+    /// Create the build details table
+    pub(super) fn init_record_schema(&self) -> Result<()> {
+        self.conn.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS package_build_info (
+                package_id INTEGER PRIMARY KEY,
+                repository TEXT,
+                buck_target TEXT NOT NULL,
+                prebuilt INTEGER NOT NULL DEFAULT 0,
+                FOREIGN KEY (package_id) REFERENCES packages(id) ON DELETE CASCADE
+            );
+            "#,
+        )?;
+        Ok(())
+    }
+
+    /// Record where an installed package came from
+    pub fn set_build_info(&mut self, name: &str, info: &BuildInfo) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO package_build_info (package_id, repository, buck_target, prebuilt)
+             SELECT id, ?, ?, ? FROM packages WHERE name = ?",
+            params![info.repository, info.buck_target, info.prebuilt, name],
+        )?;
+        Ok(())
+    }
+
+    /// Where an installed package came from
+    pub fn get_build_info(&self, name: &str) -> Result<Option<BuildInfo>> {
+        let info = self
+            .conn
+            .query_row(
+                "SELECT b.repository, b.buck_target, b.prebuilt FROM package_build_info b
+                 JOIN packages p ON p.id = b.package_id WHERE p.name = ?",
+                params![name],
+                |row| {
+                    Ok(BuildInfo {
+                        repository: row.get(0)?,
+                        buck_target: row.get(1)?,
+                        prebuilt: row.get(2)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(info)
+    }
+
+    /// Everything recorded about an installed package
+    pub fn package_record(&self, name: &str) -> Result<Option<PackageRecord>> {
+        let Some(package) = self.get_installed(name)? else {
+            return Ok(None);
+        };
+        let live_commit = self
+            .conn
+            .query_row(
+                "SELECT lc.url, lc.commit_id FROM live_commits lc
+                 JOIN packages p ON p.id = lc.package_id WHERE p.name = ?",
+                params![name],
+                |row| {
+                    Ok(LiveCommit {
+                        url: row.get(0)?,
+                        commit: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(Some(PackageRecord {
+            dependencies: self.package_dependencies(name)?,
+            patches: self.get_package_patches(name)?,
+            live_commit,
+            build_info: self.get_build_info(name)?,
+            package,
+        }))
+    }
+
+    /// Add a package with everything recorded about it
+    pub fn add_record(&mut self, record: &PackageRecord) -> Result<i64> {
+        let name = &record.package.name;
+        let pkg_id = self.add_package(&record.package)?;
+        for dep in &record.dependencies {
+            self.add_dependency(
+                pkg_id,
+                &dep.package,
+                dep.slot.as_deref(),
+                dep.build_time,
+                dep.run_time,
+            )?;
+        }
+        if !record.patches.is_empty() {
+            self.set_package_patches(name, &record.patches)?;
+        }
+        if let Some(live) = &record.live_commit {
+            self.set_live_commit(name, &live.url, &live.commit)?;
+        }
+        if let Some(info) = &record.build_info {
+            self.set_build_info(name, info)?;
+        }
+        Ok(pkg_id)
+    }
+
+    /// Dependencies recorded for an installed package
+    fn package_dependencies(&self, name: &str) -> Result<Vec<DependencyRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT d.dep_category, d.dep_name, d.dep_slot, d.build_time, d.run_time
+             FROM dependencies d JOIN packages p ON p.id = d.package_id
+             WHERE p.name = ? ORDER BY d.dep_category, d.dep_name",
+        )?;
+        let deps = stmt
+            .query_map(params![name], |row| {
+                Ok(DependencyRecord {
+                    package: PackageId::new(row.get::<_, String>(0)?, row.get::<_, String>(1)?),
+                    slot: row.get(2)?,
+                    build_time: row.get(3)?,
+                    run_time: row.get(4)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(deps)
+    }
+}
@@ -0,0 +1,571 @@
+//! Plain-text package records
+//!
+//! Every merged package also gets a directory under
+//! `<db_path>/pkg/<category>/<name>-<version>/`, one small file per field
+//! in the style of Portage's VDB, so the installed state can be read and
+//! fixed with standard tools. SQLite is the working copy; these records
+//! are the redundant source of truth the database is rebuilt from when it
+//! is damaged.
+//!
+//! | File          | Contents                                               |
+//! |---------------|--------------------------------------------------------|
+//! | `CONTENTS`    | installed files, see below                             |
+//! | `SLOT`        | slot                                                   |
+//! | `USE`         | enabled USE flags, space separated                     |
+//! | `DEPEND`      | build-time dependencies, one `cat/pkg[:slot]` a line   |
+//! | `RDEPEND`     | run-time dependencies, likewise                        |
+//! | `SIZE`        | installed size in bytes                                |
+//! | `INSTALLED`   | merge time, RFC 3339                                   |
+//! | `repository`  | repository the package came from                       |
+//! | `BUCK_TARGET` | Buck target it was built from                          |
+//! | `PREBUILT`    | present when merged from a prebuilt image              |
+//! | `PATCHES`     | user patches, `<sha256> <strip or -> <name>`           |
+//! | `LIVE_COMMIT` | commit a live package was built from, `<commit> <url>` |
+//!
+//! Only `CONTENTS` is required. It lists one file per line:
+//!
+//! ```text
+//! dir /usr/bin - 755 0 1700000000
+//! obj /usr/bin/foo <blake3> 755 14232 1700000000
+//! sym /usr/lib/libfoo.so - 777 12 1700000000
+//! ```
+//!
+//! Fields after the path are fixed, so paths may contain spaces.
+
+use super::record::{BuildInfo, DependencyRecord, LiveCommit, PackageRecord};
+use crate::patches::AppliedPatch;
+use crate::{Error, FileType, InstalledFile, InstalledPackage, PackageId, Result};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Record directory relative to the database directory
+pub const VDB_DIR: &str = "pkg";
+
+/// Plain-text records of installed packages
+#[derive(Debug, Clone)]
+pub struct Vdb {
+    dir: PathBuf,
+}
+
+/// Where a record lives: package and version
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VdbEntry {
+    pub id: PackageId,
+    pub version: semver::Version,
+}
+
+impl std::fmt::Display for VdbEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.id, self.version)
+    }
+}
+
+impl Vdb {
+    /// Records kept under a database directory
+    pub fn new(db_path: &Path) -> Self {
+        Self {
+            dir: db_path.join(VDB_DIR),
+        }
+    }
+
+    fn record_dir(&self, id: &PackageId, version: &semver::Version) -> PathBuf {
+        self.dir
+            .join(&id.category)
+            .join(format!("{}-{}", id.name, version))
+    }
+
+    /// Write the record of an installed package, replacing any old one
+    pub fn write(&self, record: &PackageRecord) -> Result<()> {
+        let pkg = &record.package;
+        let dir = self.record_dir(&pkg.id, &pkg.version);
+        let parent = dir.parent().unwrap_or(&self.dir);
+        std::fs::create_dir_all(parent)?;
+
+        // Build the record next to its final place and swap it in, so a
+        // crash leaves either the old record or the new one
+        let staging = tempfile::Builder::new()
+            .prefix(".staging-")
+            .tempdir_in(parent)?;
+        for (file, content) in render_record(record) {
+            std::fs::write(staging.path().join(file), content)?;
+        }
+
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)?;
+        }
+        std::fs::rename(staging.keep(), &dir)?;
+        Ok(())
+    }
+
+    /// Remove the record of a package version, if there is one
+    pub fn remove(&self, id: &PackageId, version: &semver::Version) -> Result<()> {
+        let dir = self.record_dir(id, version);
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)?;
+        }
+        Ok(())
+    }
+
+    /// Move every record of a package to a new name (package move)
+    pub fn rename(&self, from: &PackageId, to: &PackageId) -> Result<()> {
+        for entry in self.entries()? {
+            if entry.id == *from {
+                let target = self.record_dir(to, &entry.version);
+                if let Some(parent) = target.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::rename(self.record_dir(from, &entry.version), target)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace all records with `records`
+    pub fn replace_all(&self, records: &[PackageRecord]) -> Result<()> {
+        let keep: HashSet<VdbEntry> = records
+            .iter()
+            .map(|r| VdbEntry {
+                id: r.package.id.clone(),
+                version: r.package.version.clone(),
+            })
+            .collect();
+        for entry in self.entries()? {
+            if !keep.contains(&entry) {
+                self.remove(&entry.id, &entry.version)?;
+            }
+        }
+        for record in records {
+            self.write(record)?;
+        }
+        Ok(())
+    }
+
+    /// Every recorded package version
+    pub fn entries(&self) -> Result<Vec<VdbEntry>> {
+        let mut entries = Vec::new();
+        let categories = match std::fs::read_dir(&self.dir) {
+            Ok(categories) => categories,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(entries),
+            Err(e) => return Err(e.into()),
+        };
+        for category in categories {
+            let category = category?;
+            if !category.file_type()?.is_dir() {
+                continue;
+            }
+            let category_name = category.file_name().to_string_lossy().to_string();
+            for pkg in std::fs::read_dir(category.path())? {
+                let pkg = pkg?;
+                let pf = pkg.file_name().to_string_lossy().to_string();
+                if pf.starts_with('.') || !pkg.file_type()?.is_dir() {
+                    continue;
+                }
+                match split_pf(&pf) {
+                    Some((name, version)) => entries.push(VdbEntry {
+                        id: PackageId::new(category_name.clone(), name),
+                        version,
+                    }),
+                    None => tracing::warn!("Ignoring unparseable record {}/{}", category_name, pf),
+                }
+            }
+        }
+        entries.sort();
+        Ok(entries)
+    }
+
+    /// Number of files listed in a record, without parsing them
+    pub fn file_count(&self, entry: &VdbEntry) -> Result<usize> {
+        let contents =
+            std::fs::read_to_string(self.record_dir(&entry.id, &entry.version).join("CONTENTS"))?;
+        Ok(contents.lines().filter(|l| !l.trim().is_empty()).count())
+    }
+
+    /// Whether a package version has a record
+    pub fn contains(&self, id: &PackageId, version: &semver::Version) -> bool {
+        self.record_dir(id, version).join("CONTENTS").exists()
+    }
+
+    /// Read a record back
+    ///
+    /// Records do not say whether a package was asked for explicitly; the
+    /// caller decides from the world file.
+    pub fn read(&self, entry: &VdbEntry, explicit: bool) -> Result<PackageRecord> {
+        let dir = self.record_dir(&entry.id, &entry.version);
+        let contents_path = dir.join("CONTENTS");
+        let contents = std::fs::read_to_string(&contents_path)?;
+        let files = parse_contents(&contents)
+            .map_err(|e| Error::DatabaseError(format!("{}: {}", contents_path.display(), e)))?;
+        let field = |name: &str| {
+            std::fs::read_to_string(dir.join(name))
+                .ok()
+                .map(|s| s.trim().to_string())
+        };
+
+        let installed_at =
+            match field("INSTALLED").and_then(|t| chrono::DateTime::parse_from_rfc3339(&t).ok()) {
+                Some(t) => t.with_timezone(&chrono::Utc),
+                // Records written before INSTALLED existed
+                None => std::fs::metadata(&contents_path)?
+                    .modified()
+                    .map(chrono::DateTime::<chrono::Utc>::from)
+                    .unwrap_or_else(|_| chrono::Utc::now()),
+            };
+        let size = field("SIZE")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(|| files.iter().map(|f| f.size).sum());
+        let use_flags = field("USE")
+            .map(|u| u.split_whitespace().map(String::from).collect())
+            .unwrap_or_default();
+
+        let package = InstalledPackage {
+            id: entry.id.clone(),
+            name: entry.id.name.clone(),
+            version: entry.version.clone(),
+            slot: field("SLOT").unwrap_or_else(|| "0".to_string()),
+            installed_at,
+            use_flags,
+            files,
+            size,
+            build_time: false,
+            explicit,
+        };
+
+        let build_info = field("BUCK_TARGET").map(|buck_target| BuildInfo {
+            repository: field("repository").filter(|r| !r.is_empty()),
+            buck_target,
+            prebuilt: dir.join("PREBUILT").exists(),
+        });
+        let live_commit = field("LIVE_COMMIT").and_then(|line| {
+            let (commit, url) = line.split_once(' ')?;
+            Some(LiveCommit {
+                url: url.to_string(),
+                commit: commit.to_string(),
+            })
+        });
+
+        Ok(PackageRecord {
+            package,
+            dependencies: parse_dependencies(
+                &field("DEPEND").unwrap_or_default(),
+                &field("RDEPEND").unwrap_or_default(),
+            ),
+            patches: field("PATCHES")
+                .map(|p| parse_patches(&p))
+                .unwrap_or_default(),
+            live_commit,
+            build_info,
+        })
+    }
+}
+
+/// Files making up a record, by name
+fn render_record(record: &PackageRecord) -> Vec<(&'static str, String)> {
+    let pkg = &record.package;
+    let mut use_flags: Vec<&str> = pkg.use_flags.iter().map(String::as_str).collect();
+    use_flags.sort_unstable();
+
+    let mut files = vec![
+        ("CONTENTS", render_contents(&pkg.files)),
+        ("SLOT", format!("{}\n", pkg.slot)),
+        ("USE", format!("{}\n", use_flags.join(" "))),
+        (
+            "DEPEND",
+            render_dependencies(&record.dependencies, |d| d.build_time),
+        ),
+        (
+            "RDEPEND",
+            render_dependencies(&record.dependencies, |d| d.run_time),
+        ),
+        ("SIZE", format!("{}\n", pkg.size)),
+        ("INSTALLED", format!("{}\n", pkg.installed_at.to_rfc3339())),
+    ];
+    if let Some(info) = &record.build_info {
+        if let Some(repository) = &info.repository {
+            files.push(("repository", format!("{}\n", repository)));
+        }
+        files.push(("BUCK_TARGET", format!("{}\n", info.buck_target)));
+        if info.prebuilt {
+            files.push(("PREBUILT", "1\n".to_string()));
+        }
+    }
+    if !record.patches.is_empty() {
+        let patches = record
+            .patches
+            .iter()
+            .map(|p| {
+                let strip = p.strip.map(|s| s.to_string());
+                format!(
+                    "{} {} {}\n",
+                    p.sha256,
+                    strip.as_deref().unwrap_or("-"),
+                    p.name
+                )
+            })
+            .collect();
+        files.push(("PATCHES", patches));
+    }
+    if let Some(live) = &record.live_commit {
+        files.push(("LIVE_COMMIT", format!("{} {}\n", live.commit, live.url)));
+    }
+    files
+}
+
+fn render_dependencies(deps: &[DependencyRecord], select: fn(&DependencyRecord) -> bool) -> String {
+    deps.iter()
+        .filter(|d| select(d))
+        .map(|d| match &d.slot {
+            Some(slot) => format!("{}:{}\n", d.package, slot),
+            None => format!("{}\n", d.package),
+        })
+        .collect()
+}
+
+/// Merge DEPEND and RDEPEND back into dependency records
+fn parse_dependencies(depend: &str, rdepend: &str) -> Vec<DependencyRecord> {
+    let mut deps: BTreeMap<PackageId, DependencyRecord> = BTreeMap::new();
+    for (content, run_time) in [(depend, false), (rdepend, true)] {
+        for line in content.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let (atom, slot) = match line.split_once(':') {
+                Some((atom, slot)) => (atom, Some(slot.to_string())),
+                None => (line, None),
+            };
+            let Some(package) = PackageId::parse(atom) else {
+                continue;
+            };
+            let dep = deps
+                .entry(package.clone())
+                .or_insert_with(|| DependencyRecord {
+                    package,
+                    slot,
+                    build_time: false,
+                    run_time: false,
+                });
+            if run_time {
+                dep.run_time = true;
+            } else {
+                dep.build_time = true;
+            }
+        }
+    }
+    deps.into_values().collect()
+}
+
+fn parse_patches(content: &str) -> Vec<AppliedPatch> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, ' ');
+            let sha256 = fields.next()?.to_string();
+            let strip = fields.next()?.parse().ok();
+            let name = fields.next()?.to_string();
+            Some(AppliedPatch {
+                name,
+                sha256,
+                strip,
+            })
+        })
+        .collect()
+}
+
+/// Split `<name>-<version>` at the first dash followed by a valid version
+fn split_pf(pf: &str) -> Option<(String, semver::Version)> {
+    pf.match_indices('-').find_map(|(idx, _)| {
+        let version = semver::Version::parse(&pf[idx + 1..]).ok()?;
+        Some((pf[..idx].to_string(), version))
+    })
+}
+
+fn type_tag(file_type: FileType) -> &'static str {
+    match file_type {
+        FileType::Regular => "obj",
+        FileType::Directory => "dir",
+        FileType::Symlink => "sym",
+        FileType::Hardlink => "lnk",
+        FileType::Device => "dev",
+        FileType::Fifo => "fif",
+        FileType::Masked => "msk",
+    }
+}
+
+/// Render a CONTENTS file
+pub fn render_contents(files: &[InstalledFile]) -> String {
+    files
+        .iter()
+        .map(|f| {
+            format!(
+                "{} {} {} {:o} {} {}\n",
+                type_tag(f.file_type),
+                f.path,
+                f.blake3_hash.as_deref().unwrap_or("-"),
+                f.mode,
+                f.size,
+                f.mtime
+            )
+        })
+        .collect()
+}
+
+/// Parse a CONTENTS file
+pub fn parse_contents(content: &str) -> std::result::Result<Vec<InstalledFile>, String> {
+    let mut files = Vec::new();
+    for (idx, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let error = |what: &str| format!("line {}: {}", idx + 1, what);
+
+        let (tag, rest) = line.split_once(' ').ok_or_else(|| error("no path"))?;
+        let file_type = match tag {
+            "obj" => FileType::Regular,
+            "dir" => FileType::Directory,
+            "sym" => FileType::Symlink,
+            "lnk" => FileType::Hardlink,
+            "dev" => FileType::Device,
+            "fif" => FileType::Fifo,
+            "msk" => FileType::Masked,
+            _ => return Err(error("unknown file type")),
+        };
+        let mut fields = rest.rsplitn(5, ' ');
+        let mtime = fields.next().and_then(|s| s.parse().ok());
+        let size = fields.next().and_then(|s| s.parse().ok());
+        let mode = fields.next().and_then(|s| u32::from_str_radix(s, 8).ok());
+        let hash = fields.next();
+        let path = fields.next();
+        let (Some(mtime), Some(size), Some(mode), Some(hash), Some(path)) =
+            (mtime, size, mode, hash, path)
+        else {
+            return Err(error(
+                "expected '<type> <path> <hash> <mode> <size> <mtime>'",
+            ));
+        };
+
+        files.push(InstalledFile {
+            path: path.to_string(),
+            file_type,
+            mode,
+            size,
+            blake3_hash: (hash != "-").then(|| hash.to_string()),
+            mtime,
+        });
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, version: &str) -> InstalledPackage {
+        InstalledPackage {
+            id: PackageId::new("dev-libs", name),
+            name: name.to_string(),
+            version: semver::Version::parse(version).unwrap(),
+            slot: "1".to_string(),
+            installed_at: chrono::Utc::now(),
+            use_flags: HashSet::new(),
+            files: vec![
+                InstalledFile {
+                    path: "/usr/lib".to_string(),
+                    file_type: FileType::Directory,
+                    mode: 0o755,
+                    size: 0,
+                    blake3_hash: None,
+                    mtime: 1,
+                },
+                InstalledFile {
+                    path: "/usr/lib/my lib.so".to_string(),
+                    file_type: FileType::Regular,
+                    mode: 0o644,
+                    size: 3,
+                    blake3_hash: Some("ab".repeat(32)),
+                    mtime: 2,
+                },
+            ],
+            size: 3,
+            build_time: false,
+            explicit: false,
+        }
+    }
+
+    #[test]
+    fn test_split_pf() {
+        let (name, version) = split_pf("foo-bar-1.2.3-rc1").unwrap();
+        assert_eq!(name, "foo-bar");
+        assert_eq!(version.to_string(), "1.2.3-rc1");
+        assert!(split_pf("foo").is_none());
+    }
+
+    #[test]
+    fn test_write_read_rename() {
+        let dir = tempfile::tempdir().unwrap();
+        let vdb = Vdb::new(dir.path());
+        let mut record = PackageRecord::new(package("libfoo-2", "1.0.0"));
+        record.package.use_flags = HashSet::from(["ssl".to_string(), "zstd".to_string()]);
+        record.dependencies = vec![
+            DependencyRecord {
+                package: PackageId::new("sys-libs", "zlib"),
+                slot: Some("0/1".to_string()),
+                build_time: true,
+                run_time: true,
+            },
+            DependencyRecord {
+                package: PackageId::new("virtual", "pkgconfig"),
+                slot: None,
+                build_time: true,
+                run_time: false,
+            },
+        ];
+        record.patches = vec![AppliedPatch {
+            name: "fix build.patch".to_string(),
+            sha256: "cd".repeat(32),
+            strip: Some(1),
+        }];
+        record.live_commit = Some(LiveCommit {
+            url: "https://example.com/foo.git".to_string(),
+            commit: "abc123".to_string(),
+        });
+        record.build_info = Some(BuildInfo {
+            repository: Some("gentoo".to_string()),
+            buck_target: "//packages/linux/dev-libs/libfoo:libfoo".to_string(),
+            prebuilt: true,
+        });
+        vdb.write(&record).unwrap();
+        vdb.write(&record).unwrap();
+
+        let entries = vdb.entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].id.name, "libfoo-2");
+        assert_eq!(vdb.file_count(&entries[0]).unwrap(), 2);
+        let use_file = dir.path().join("pkg/dev-libs/libfoo-2-1.0.0/USE");
+        assert_eq!(std::fs::read_to_string(use_file).unwrap(), "ssl zstd\n");
+
+        let read = vdb.read(&entries[0], true).unwrap();
+        let pkg = &read.package;
+        assert_eq!(pkg.slot, "1");
+        assert_eq!(pkg.size, 3);
+        assert_eq!(pkg.use_flags, record.package.use_flags);
+        assert_eq!(
+            pkg.installed_at.timestamp(),
+            record.package.installed_at.timestamp()
+        );
+        assert_eq!(pkg.files.len(), 2);
+        assert_eq!(pkg.files[1].path, "/usr/lib/my lib.so");
+        assert_eq!(pkg.files[1].mode, 0o644);
+        assert_eq!(
+            pkg.files[1].blake3_hash,
+            record.package.files[1].blake3_hash
+        );
+        assert_eq!(pkg.files[0].blake3_hash, None);
+        assert_eq!(read.dependencies, record.dependencies);
+        assert_eq!(read.patches, record.patches);
+        assert_eq!(read.live_commit, record.live_commit);
+        assert_eq!(read.build_info, record.build_info);
+
+        let to = PackageId::new("sys-libs", "libfoo");
+        vdb.rename(&record.package.id, &to).unwrap();
+        assert_eq!(vdb.entries().unwrap()[0].id, to);
+
+        vdb.replace_all(&[]).unwrap();
+        assert!(vdb.entries().unwrap().is_empty());
+    }
+}
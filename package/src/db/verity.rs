@@ -0,0 +1,51 @@
+//! fs-verity digests of installed files
+//!
+//! The digest the kernel measured when fs-verity was enabled on a file at
+//! merge time. A file whose measurement no longer matches, or that lost
+//! fs-verity, was replaced behind the package manager's back.
+
+use super::PackageDb;
+use crate::Result;
+use rusqlite::params;
+use std::collections::HashMap;
+
+impl PackageDb {
+    /// Create the fs-verity digest table
+    pub(super) fn init_verity_schema(&self) -> Result<()> {
+        self.conn.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS file_verity (
+                file_id INTEGER PRIMARY KEY,
+                digest TEXT NOT NULL,
+                FOREIGN KEY (file_id) REFERENCES files(id) ON DELETE CASCADE
+            );
+            "#,
+        )?;
+        Ok(())
+    }
+
+    /// Record the fs-verity digest of an installed file
+    pub fn set_file_verity(&self, path: &str, digest: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO file_verity (file_id, digest)
+             SELECT id, ? FROM files WHERE path = ?",
+            params![digest, path],
+        )?;
+        Ok(())
+    }
+
+    /// Recorded fs-verity digests of a package's files, by path
+    pub fn get_file_verity(&self, name: &str) -> Result<HashMap<String, String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT f.path, v.digest
+             FROM file_verity v
+             JOIN files f ON f.id = v.file_id
+             JOIN packages p ON p.id = f.package_id
+             WHERE p.name = ?",
+        )?;
+        let digests = stmt
+            .query_map(params![name], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<std::result::Result<HashMap<_, _>, _>>()?;
+        Ok(digests)
+    }
+}
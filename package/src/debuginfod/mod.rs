@@ -0,0 +1,223 @@
+//! debuginfod-compatible debug info server
+//!
+//! Serves split debug info (see [`crate::transaction::MergeTransforms`]) by
+//! GNU build ID, so gdb, lldb and other debuginfod clients on the network can
+//! fetch symbols for stripped binaries:
+//!
+//! ```text
+//! GET /buildid/<BUILDID>/debuginfo    the .debug file
+//! GET /buildid/<BUILDID>/executable   the stripped binary
+//! ```
+//!
+//! Clients are pointed at it with `DEBUGINFOD_URLS=http://host:8002`.
+//! Source lookups (`/buildid/<BUILDID>/source/...`) are not supported.
+
+use crate::http::{self, Response};
+use crate::transaction::{build_id_path, DEBUG_DIR};
+use crate::Result;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::BufReader;
+use tokio::net::TcpListener;
+use tracing::{debug, info, warn};
+
+/// Default listen address (the standard debuginfod port)
+pub const DEFAULT_LISTEN: &str = "0.0.0.0:8002";
+
+/// Kind of artifact requested for a build ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Artifact {
+    /// Split debug info
+    DebugInfo,
+    /// The stripped executable or library
+    Executable,
+}
+
+/// Debug info looked up from one or more install roots
+#[derive(Debug, Clone)]
+pub struct DebugInfoStore {
+    roots: Vec<PathBuf>,
+}
+
+impl DebugInfoStore {
+    /// Create a store for an install root
+    pub fn new(root: &Path) -> Self {
+        Self {
+            roots: vec![root.to_path_buf()],
+        }
+    }
+
+    /// Also search another root (e.g. an unpacked -dbg package)
+    pub fn with_root(mut self, root: &Path) -> Self {
+        self.roots.push(root.to_path_buf());
+        self
+    }
+
+    /// Find an artifact by build ID
+    pub fn find(&self, build_id: &str, artifact: Artifact) -> Option<PathBuf> {
+        if !is_valid_build_id(build_id) {
+            return None;
+        }
+        let build_id = build_id.to_ascii_lowercase();
+
+        for root in &self.roots {
+            let link = root.join(build_id_path(&build_id));
+            let Ok(debug_file) = link.canonicalize() else {
+                continue;
+            };
+
+            let path = match artifact {
+                Artifact::DebugInfo => debug_file,
+                Artifact::Executable => {
+                    let Some(executable) = executable_for(root, &debug_file) else {
+                        continue;
+                    };
+                    executable
+                }
+            };
+
+            // Never serve anything outside the root, whatever the symlinks say
+            let within_root = root
+                .canonicalize()
+                .map(|r| path.starts_with(r))
+                .unwrap_or(false);
+            if within_root && path.is_file() {
+                return Some(path);
+            }
+        }
+
+        None
+    }
+}
+
+/// Map `<root>/usr/lib/debug/<path>.debug` back to `<root>/<path>`
+fn executable_for(root: &Path, debug_file: &Path) -> Option<PathBuf> {
+    let debug_dir = root.join(DEBUG_DIR).canonicalize().ok()?;
+    let relative = debug_file.strip_prefix(&debug_dir).ok()?;
+    let relative = relative.to_str()?.strip_suffix(".debug")?;
+    root.join(relative).canonicalize().ok()
+}
+
+/// Build IDs are hex strings; anything else could escape the store
+fn is_valid_build_id(build_id: &str) -> bool {
+    build_id.len() >= 4 && build_id.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Parse a debuginfod request path
+pub fn parse_request_path(path: &str) -> Option<(&str, Artifact)> {
+    let rest = path.strip_prefix("/buildid/")?;
+    let (build_id, kind) = rest.split_once('/')?;
+    let artifact = match kind.trim_end_matches('/') {
+        "debuginfo" => Artifact::DebugInfo,
+        "executable" => Artifact::Executable,
+        _ => return None,
+    };
+    Some((build_id, artifact))
+}
+
+/// Serve debug info until the task is cancelled
+pub async fn serve(store: DebugInfoStore, listen: &str) -> Result<()> {
+    let listener = TcpListener::bind(listen).await?;
+    info!("debuginfod listening on {}", listener.local_addr()?);
+
+    let store = Arc::new(store);
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let store = store.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(&store, stream).await {
+                debug!("debuginfod connection from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn handle_connection(store: &DebugInfoStore, stream: tokio::net::TcpStream) -> Result<()> {
+    let peer = stream.peer_addr()?;
+    let mut stream = BufReader::new(stream);
+    let Some(request) = http::read_request(&mut stream).await? else {
+        return Ok(());
+    };
+
+    let response = handle_request(store, &request).await;
+    let status = response.status;
+    let sent = http::write_response(stream.get_mut(), response, request.is_head()).await?;
+    info!(
+        "{} {} {} {} {}",
+        peer, request.method, request.path, status, sent
+    );
+    Ok(())
+}
+
+async fn handle_request(store: &DebugInfoStore, request: &http::Request) -> Response {
+    if request.method != "GET" && !request.is_head() {
+        return Response::text(405, "method not allowed\n");
+    }
+
+    let Some((build_id, artifact)) = parse_request_path(&request.path) else {
+        return Response::not_found();
+    };
+    let Some(path) = store.find(build_id, artifact) else {
+        return Response::not_found();
+    };
+
+    match Response::file(&path).await {
+        Ok(response) => {
+            response.with_header("X-DEBUGINFOD-FILE", path.to_string_lossy().to_string())
+        }
+        Err(e) => {
+            warn!("Failed to open {}: {}", path.display(), e);
+            Response::text(500, "internal error\n")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        let bin = root.path().join("usr/bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        std::fs::write(bin.join("foo"), b"stripped").unwrap();
+
+        let debug = root.path().join("usr/lib/debug/usr/bin");
+        std::fs::create_dir_all(&debug).unwrap();
+        std::fs::write(debug.join("foo.debug"), b"symbols").unwrap();
+
+        let link = root.path().join(build_id_path("abcdef0123"));
+        std::fs::create_dir_all(link.parent().unwrap()).unwrap();
+        std::os::unix::fs::symlink("../../usr/bin/foo.debug", &link).unwrap();
+        root
+    }
+
+    #[test]
+    fn test_find() {
+        let root = setup();
+        let store = DebugInfoStore::new(root.path());
+
+        let debuginfo = store.find("abcdef0123", Artifact::DebugInfo).unwrap();
+        assert_eq!(std::fs::read(debuginfo).unwrap(), b"symbols");
+
+        let executable = store.find("ABCDEF0123", Artifact::Executable).unwrap();
+        assert_eq!(std::fs::read(executable).unwrap(), b"stripped");
+
+        assert!(store.find("0000000000", Artifact::DebugInfo).is_none());
+        assert!(store.find("../../etc", Artifact::DebugInfo).is_none());
+    }
+
+    #[test]
+    fn test_parse_request_path() {
+        assert_eq!(
+            parse_request_path("/buildid/abcd/debuginfo"),
+            Some(("abcd", Artifact::DebugInfo))
+        );
+        assert_eq!(
+            parse_request_path("/buildid/abcd/executable"),
+            Some(("abcd", Artifact::Executable))
+        );
+        assert_eq!(parse_request_path("/buildid/abcd/source/x.c"), None);
+        assert_eq!(parse_request_path("/metrics"), None);
+    }
+}
@@ -0,0 +1,345 @@
+//! Classification of failed builds
+//!
+//! The log of a failed build is matched against common failure signatures
+//! (a missing header, an unresolved symbol, a compiler killed for lack of
+//! memory, a failing configure check, a failed download) so the failure can
+//! be reported with its likely cause and what to try next.
+
+use serde::{Deserialize, Serialize};
+
+/// Why a build failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FailureKind {
+    /// A header was not found
+    MissingHeader { header: String },
+    /// The linker could not resolve a symbol
+    UndefinedReference { symbol: String },
+    /// The compiler was killed, usually by the OOM killer
+    OutOfMemory,
+    /// Something the build downloaded could not be fetched
+    Download,
+    /// A configure check failed, possibly one enabled by a USE flag
+    ConfigureCheck {
+        check: String,
+        use_flag: Option<String>,
+    },
+}
+
+/// A classified build failure
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildFailure {
+    #[serde(flatten)]
+    pub kind: FailureKind,
+    /// Log line the failure was recognized by
+    pub line: String,
+}
+
+impl BuildFailure {
+    /// Classify a failed build's log
+    ///
+    /// `use_flags` are the package's USE flags, matched against failing
+    /// configure checks. A killed compiler is reported over anything else,
+    /// since it leaves unrelated errors behind; otherwise the first
+    /// recognized signature wins, and a failed download last.
+    pub fn classify(log: &str, use_flags: &[String]) -> Option<Self> {
+        let lines = || log.lines().map(str::trim);
+        if let Some(line) = lines().find(|l| is_oom(l)) {
+            return Some(Self {
+                kind: FailureKind::OutOfMemory,
+                line: line.to_string(),
+            });
+        }
+        lines()
+            .find_map(|line| {
+                let kind = missing_header(line)
+                    .map(|header| FailureKind::MissingHeader { header })
+                    .or_else(|| {
+                        undefined_reference(line)
+                            .map(|symbol| FailureKind::UndefinedReference { symbol })
+                    })
+                    .or_else(|| {
+                        configure_check(line).map(|check| FailureKind::ConfigureCheck {
+                            use_flag: use_flag_of(&check, use_flags),
+                            check,
+                        })
+                    })?;
+                Some(Self {
+                    kind,
+                    line: line.to_string(),
+                })
+            })
+            .or_else(|| {
+                let line = lines().find(|l| is_download_failure(l))?;
+                Some(Self {
+                    kind: FailureKind::Download,
+                    line: line.to_string(),
+                })
+            })
+    }
+
+    /// Whether the same build may well succeed if run again
+    pub fn is_transient(&self) -> bool {
+        matches!(self.kind, FailureKind::OutOfMemory | FailureKind::Download)
+    }
+
+    /// Name of the failure class, as serialized (`out_of_memory`, ...)
+    pub fn kind_name(&self) -> &'static str {
+        match self.kind {
+            FailureKind::MissingHeader { .. } => "missing_header",
+            FailureKind::UndefinedReference { .. } => "undefined_reference",
+            FailureKind::OutOfMemory => "out_of_memory",
+            FailureKind::Download => "download",
+            FailureKind::ConfigureCheck { .. } => "configure_check",
+        }
+    }
+
+    /// One-line cause
+    pub fn reason(&self) -> String {
+        match &self.kind {
+            FailureKind::MissingHeader { header } => format!("header {} was not found", header),
+            FailureKind::UndefinedReference { symbol } => {
+                format!("undefined reference to {}", symbol)
+            }
+            FailureKind::OutOfMemory => "the compiler was killed, likely out of memory".to_string(),
+            FailureKind::Download => "a download during the build failed".to_string(),
+            FailureKind::ConfigureCheck {
+                check,
+                use_flag: Some(flag),
+            } => format!("configure check for USE={} failed: {}", flag, check),
+            FailureKind::ConfigureCheck { check, .. } => {
+                format!("configure check failed: {}", check)
+            }
+        }
+    }
+
+    /// Suggested remediation for `package` (`category/name`)
+    pub fn hint(&self, package: &str) -> String {
+        match &self.kind {
+            FailureKind::MissingHeader { header } => format!(
+                "install the package providing {}; if it is installed, {} is missing it \
+                 from DEPEND",
+                header, package
+            ),
+            FailureKind::UndefinedReference { .. } => format!(
+                "a library is missing from the link or was built without the symbol; \
+                 rebuild the library {} links against, or check its LDFLAGS",
+                package
+            ),
+            FailureKind::OutOfMemory => {
+                "build with fewer jobs (buckos -j2) or add swap, then retry".to_string()
+            }
+            FailureKind::Download => {
+                "check the network and mirrors, then retry; build_retry in buckos.toml \
+                 retries such failures automatically"
+                    .to_string()
+            }
+            FailureKind::ConfigureCheck {
+                use_flag: Some(flag),
+                ..
+            } => format!(
+                "install what USE={} needs, or build without it: \
+                 buckos use package {} -{}",
+                flag, package, flag
+            ),
+            FailureKind::ConfigureCheck { .. } => format!(
+                "install the dependency the check looks for; if it is installed, {} is \
+                 missing it from DEPEND",
+                package
+            ),
+        }
+    }
+}
+
+/// Compiler killed by a signal, or out of memory
+fn is_oom(line: &str) -> bool {
+    [
+        "Killed signal terminated program",
+        "internal compiler error: Killed",
+        "virtual memory exhausted",
+        "out of memory allocating",
+        "memory allocation of",
+        "SIGKILL: kill",
+    ]
+    .iter()
+    .any(|sig| line.contains(sig))
+}
+
+/// Network errors from curl, wget, git and name resolution
+fn is_download_failure(line: &str) -> bool {
+    [
+        "Could not resolve host",
+        "Temporary failure in name resolution",
+        "Connection timed out",
+        "Connection reset by peer",
+        "Connection refused",
+        "unable to access '",
+        "The requested URL returned error: 5",
+        "ERROR 503",
+        "ERROR 502",
+    ]
+    .iter()
+    .any(|sig| line.contains(sig))
+}
+
+/// `fatal error: foo.h: No such file or directory` (GCC) or
+/// `fatal error: 'foo.h' file not found` (Clang)
+fn missing_header(line: &str) -> Option<String> {
+    let (_, rest) = line.split_once("fatal error: ")?;
+    let header = rest
+        .strip_suffix(": No such file or directory")
+        .or_else(|| rest.strip_suffix(" file not found"))?;
+    Some(header.trim_matches('\'').to_string())
+}
+
+/// ``undefined reference to `foo'`` (GNU ld) or `undefined symbol: foo` (lld)
+fn undefined_reference(line: &str) -> Option<String> {
+    let symbol = match line.split_once("undefined reference to ") {
+        Some((_, rest)) => rest,
+        None => line.split_once("undefined symbol: ")?.1,
+    };
+    let symbol = symbol
+        .trim()
+        .trim_matches(|c| matches!(c, '`' | '\'' | '"'));
+    (!symbol.is_empty()).then(|| symbol.to_string())
+}
+
+/// Autoconf `configure: error:`, Meson `ERROR: Dependency ... not found`
+/// and CMake `Could NOT find`
+fn configure_check(line: &str) -> Option<String> {
+    if let Some((_, message)) = line.split_once("configure: error: ") {
+        return Some(message.trim().to_string());
+    }
+    if let Some((_, message)) = line.split_once("ERROR: Dependency ") {
+        if message.contains("not found") {
+            return Some(format!("dependency {}", message.trim()));
+        }
+    }
+    line.find("Could NOT find ")
+        .map(|start| line[start..].trim().to_string())
+}
+
+/// The USE flag a configure check message names, e.g. `ssl` in
+/// `--with-ssl was given, but test for openssl failed`
+fn use_flag_of(check: &str, use_flags: &[String]) -> Option<String> {
+    let check = check.to_lowercase();
+    let words: Vec<&str> = check
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        .map(|w| w.trim_matches('-'))
+        .map(|w| {
+            ["with-", "without-", "enable-", "disable-"]
+                .iter()
+                .find_map(|p| w.strip_prefix(p))
+                .unwrap_or(w)
+        })
+        .collect();
+    use_flags
+        .iter()
+        .find(|flag| {
+            let flag = flag.to_lowercase();
+            words
+                .iter()
+                .any(|w| *w == flag || w.strip_prefix("lib") == Some(flag.as_str()))
+        })
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(log: &str) -> Option<FailureKind> {
+        let flags = vec!["ssl".to_string(), "png".to_string(), "X".to_string()];
+        BuildFailure::classify(log, &flags).map(|f| f.kind)
+    }
+
+    #[test]
+    fn test_classify_missing_header() {
+        assert_eq!(
+            classify("src/a.c:3:10: fatal error: zlib.h: No such file or directory"),
+            Some(FailureKind::MissingHeader {
+                header: "zlib.h".to_string()
+            })
+        );
+        assert_eq!(
+            classify("src/a.c:3:10: fatal error: 'openssl/ssl.h' file not found"),
+            Some(FailureKind::MissingHeader {
+                header: "openssl/ssl.h".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_classify_undefined_reference() {
+        assert_eq!(
+            classify("/usr/bin/ld: main.o: in function `main':\nmain.c:(.text+0x1a): undefined reference to `deflateInit_'"),
+            Some(FailureKind::UndefinedReference {
+                symbol: "deflateInit_".to_string()
+            })
+        );
+        assert_eq!(
+            classify("ld.lld: error: undefined symbol: png_create_read_struct"),
+            Some(FailureKind::UndefinedReference {
+                symbol: "png_create_read_struct".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_killed_compiler_wins_over_later_errors() {
+        let log = "\
+src/big.cpp:1:1: fatal error: gen.h: No such file or directory
+g++: fatal error: Killed signal terminated program cc1plus
+make: *** [Makefile:12: big.o] Error 1";
+        assert_eq!(classify(log), Some(FailureKind::OutOfMemory));
+    }
+
+    #[test]
+    fn test_configure_check_tied_to_use_flag() {
+        assert_eq!(
+            classify("configure: error: --with-ssl was given, but test for openssl failed"),
+            Some(FailureKind::ConfigureCheck {
+                check: "--with-ssl was given, but test for openssl failed".to_string(),
+                use_flag: Some("ssl".to_string()),
+            })
+        );
+        assert_eq!(
+            classify("meson.build:40:2: ERROR: Dependency \"libpng\" not found, tried pkgconfig"),
+            Some(FailureKind::ConfigureCheck {
+                check: "dependency \"libpng\" not found, tried pkgconfig".to_string(),
+                use_flag: Some("png".to_string()),
+            })
+        );
+        let failure = BuildFailure::classify(
+            "CMake Error at CMakeLists.txt:5 (find_package):\n  Could NOT find Boost (missing: Boost_INCLUDE_DIR)",
+            &[],
+        )
+        .unwrap();
+        assert!(matches!(
+            failure.kind,
+            FailureKind::ConfigureCheck { use_flag: None, .. }
+        ));
+        assert!(failure.hint("dev-libs/foo").contains("DEPEND"));
+    }
+
+    #[test]
+    fn test_download_failure_is_transient() {
+        let failure = BuildFailure::classify(
+            "Cloning into 'src'...\nfatal: unable to access 'https://example.org/x.git/': Could not resolve host: example.org",
+            &[],
+        )
+        .unwrap();
+        assert!(matches!(failure.kind, FailureKind::Download));
+        assert!(failure.is_transient());
+        assert!(
+            !BuildFailure::classify("ld.lld: error: undefined symbol: foo", &[])
+                .unwrap()
+                .is_transient()
+        );
+    }
+
+    #[test]
+    fn test_unrecognized_log() {
+        assert_eq!(classify("make: *** [all] Error 2"), None);
+    }
+}
@@ -0,0 +1,492 @@
+//! Compiler diagnostics captured from build logs
+//!
+//! Each build's output is scanned for GCC/Clang and rustc warnings and
+//! errors, which are aggregated by file and warning class and stored per
+//! package version. Comparing reports across versions shows warning
+//! regressions after a toolchain bump. Failed builds are also classified
+//! by their likely cause; see [`BuildFailure`]. The log itself can be kept
+//! beside the report, compressed.
+
+mod failure;
+
+pub use failure::{BuildFailure, FailureKind};
+
+use crate::{PackageId, Result};
+use buckos_core::compress::{self, Algorithm, Compression};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Diagnostic severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A single diagnostic parsed from a build log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Source file, as printed by the compiler
+    pub file: String,
+    /// Warning or error
+    pub severity: Severity,
+    /// Warning class (`-Wunused-variable`, `E0308`), or `unclassified`
+    pub class: String,
+}
+
+/// Number of diagnostics of one class in one file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiagnosticCount {
+    pub file: String,
+    pub severity: Severity,
+    pub class: String,
+    pub count: usize,
+}
+
+/// Diagnostics from one build of one package version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildReport {
+    pub package: PackageId,
+    pub version: String,
+    /// When the build ran
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Compiler version line (`cc --version`), if known
+    pub toolchain: Option<String>,
+    /// Whether the build succeeded
+    pub success: bool,
+    /// Diagnostics grouped by file, severity and class
+    pub diagnostics: Vec<DiagnosticCount>,
+    /// Likely cause of a failed build, if recognized
+    #[serde(default)]
+    pub failure: Option<BuildFailure>,
+}
+
+impl BuildReport {
+    /// Build a report from a build's combined output
+    pub fn from_log(
+        package: PackageId,
+        version: impl Into<String>,
+        toolchain: Option<String>,
+        success: bool,
+        log: &str,
+    ) -> Self {
+        let mut counts: BTreeMap<(String, Severity, String), usize> = BTreeMap::new();
+        for diag in parse_diagnostics(log) {
+            *counts
+                .entry((diag.file, diag.severity, diag.class))
+                .or_default() += 1;
+        }
+        Self {
+            package,
+            version: version.into(),
+            timestamp: chrono::Utc::now(),
+            toolchain,
+            success,
+            diagnostics: counts
+                .into_iter()
+                .map(|((file, severity, class), count)| DiagnosticCount {
+                    file,
+                    severity,
+                    class,
+                    count,
+                })
+                .collect(),
+            failure: None,
+        }
+    }
+
+    /// Attach the classified cause of a failed build
+    pub fn with_failure(mut self, failure: Option<BuildFailure>) -> Self {
+        self.failure = failure;
+        self
+    }
+
+    /// Total diagnostics of a severity
+    pub fn total(&self, severity: Severity) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == severity)
+            .map(|d| d.count)
+            .sum()
+    }
+
+    /// Diagnostic counts per class across all files, largest first
+    pub fn by_class(&self) -> Vec<(Severity, String, usize)> {
+        let mut classes: BTreeMap<(Severity, String), usize> = BTreeMap::new();
+        for d in &self.diagnostics {
+            *classes.entry((d.severity, d.class.clone())).or_default() += d.count;
+        }
+        let mut classes: Vec<_> = classes
+            .into_iter()
+            .map(|((severity, class), count)| (severity, class, count))
+            .collect();
+        classes.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.1.cmp(&b.1)));
+        classes
+    }
+
+    /// Classes whose count changed since `previous`, as (class, before, after)
+    pub fn compare(&self, previous: &BuildReport) -> Vec<(String, usize, usize)> {
+        let mut counts: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+        for (_, class, count) in previous.by_class() {
+            counts.entry(class).or_default().0 += count;
+        }
+        for (_, class, count) in self.by_class() {
+            counts.entry(class).or_default().1 += count;
+        }
+        counts
+            .into_iter()
+            .filter(|(_, (before, after))| before != after)
+            .map(|(class, (before, after))| (class, before, after))
+            .collect()
+    }
+}
+
+/// Parse GCC/Clang and rustc diagnostics out of a build log
+pub fn parse_diagnostics(log: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    // rustc prints the location on a ` --> file:line:col` line after the message
+    let mut pending_rustc: Option<(Severity, String)> = None;
+
+    for line in log.lines() {
+        if let Some((severity, class)) = pending_rustc.take() {
+            if let Some(location) = line.trim_start().strip_prefix("--> ") {
+                diagnostics.push(Diagnostic {
+                    file: strip_location(location).to_string(),
+                    severity,
+                    class,
+                });
+                continue;
+            }
+        }
+
+        if let Some(diag) = parse_gcc_line(line) {
+            diagnostics.push(diag);
+        } else if let Some(pending) = parse_rustc_header(line) {
+            pending_rustc = Some(pending);
+        }
+    }
+    diagnostics
+}
+
+/// `file:line:col: warning: message [-Wclass]`
+fn parse_gcc_line(line: &str) -> Option<Diagnostic> {
+    let (severity, marker) = [
+        (Severity::Warning, ": warning: "),
+        (Severity::Error, ": error: "),
+        (Severity::Error, ": fatal error: "),
+    ]
+    .into_iter()
+    .find(|(_, marker)| line.contains(marker))?;
+
+    let (location, message) = line.split_once(marker)?;
+    let file = strip_location(location);
+    // A bare "cc1: warning:" or "ld: error:" has no source location
+    if file == location || file.is_empty() {
+        return None;
+    }
+
+    let class = message
+        .trim_end()
+        .strip_suffix(']')
+        .and_then(|m| m.rsplit_once(" ["))
+        .map(|(_, class)| class.split(',').next().unwrap_or(class).to_string())
+        .filter(|class| class.starts_with("-W"))
+        .unwrap_or_else(|| match severity {
+            Severity::Warning => "unclassified".to_string(),
+            Severity::Error => "error".to_string(),
+        });
+
+    Some(Diagnostic {
+        file: file.to_string(),
+        severity,
+        class,
+    })
+}
+
+/// `warning: message`, `error[E0308]: message`
+fn parse_rustc_header(line: &str) -> Option<(Severity, String)> {
+    let (severity, rest) = if let Some(rest) = line.strip_prefix("warning") {
+        (Severity::Warning, rest)
+    } else if let Some(rest) = line.strip_prefix("error") {
+        (Severity::Error, rest)
+    } else {
+        return None;
+    };
+
+    let class = match rest.strip_prefix('[') {
+        Some(rest) => rest.split_once("]:")?.0.to_string(),
+        None if rest.starts_with(": ") => match severity {
+            Severity::Warning => "unclassified".to_string(),
+            Severity::Error => "error".to_string(),
+        },
+        None => return None,
+    };
+    Some((severity, class))
+}
+
+/// Strip trailing `:line[:col]` from a location
+fn strip_location(location: &str) -> &str {
+    let mut file = location.trim();
+    for _ in 0..2 {
+        match file.rsplit_once(':') {
+            Some((head, tail)) if !tail.is_empty() && tail.bytes().all(|b| b.is_ascii_digit()) => {
+                file = head
+            }
+            _ => break,
+        }
+    }
+    file
+}
+
+/// Detect the C compiler version for report headers
+pub fn detect_toolchain() -> Option<String> {
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let output = std::process::Command::new(cc)
+        .arg("--version")
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+}
+
+/// Build reports stored as JSON under `<dir>/<category>/<name>/<version>.json`
+///
+/// With [`with_logs`](Self::with_logs) the build log is stored next to its
+/// report as `<version>.log`, plus the compression suffix.
+#[derive(Debug, Clone)]
+pub struct ReportStore {
+    dir: PathBuf,
+    logs: Option<Compression>,
+}
+
+impl ReportStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            logs: None,
+        }
+    }
+
+    /// Keep build logs, compressed with `compression`
+    pub fn with_logs(mut self, compression: Compression) -> Self {
+        self.logs = Some(compression);
+        self
+    }
+
+    fn package_dir(&self, package: &PackageId) -> PathBuf {
+        self.dir.join(&package.category).join(&package.name)
+    }
+
+    /// Save a report, replacing any earlier report for the same version
+    pub fn save(&self, report: &BuildReport) -> Result<()> {
+        let dir = self.package_dir(&report.package);
+        std::fs::create_dir_all(&dir)?;
+        let json = serde_json::to_string_pretty(report)
+            .map_err(|e| crate::Error::Other(format!("Failed to encode build report: {}", e)))?;
+        std::fs::write(dir.join(format!("{}.json", report.version)), json)?;
+        Ok(())
+    }
+
+    /// Save the log of a build, replacing any earlier log for the same
+    /// version whatever it was compressed with; does nothing unless logs
+    /// are kept
+    pub fn save_log(&self, package: &PackageId, version: &str, log: &str) -> Result<()> {
+        let Some(compression) = &self.logs else {
+            return Ok(());
+        };
+        let dir = self.package_dir(package);
+        std::fs::create_dir_all(&dir)?;
+        let name = compression.file_name(&format!("{}.log", version));
+        let tmp = dir.join(format!(".{}.tmp", name));
+        compression.write(&tmp, log.as_bytes())?;
+        std::fs::rename(&tmp, dir.join(&name))?;
+        for old in self.log_paths(package, version) {
+            if old.file_name().is_some_and(|n| n != name.as_str()) {
+                std::fs::remove_file(old)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The stored log of a build, decompressed
+    ///
+    /// Logs are read by content, so those stored uncompressed or with an
+    /// earlier setting read back too.
+    pub fn load_log(&self, package: &PackageId, version: &str) -> Result<Option<String>> {
+        match self.log_paths(package, version).first() {
+            Some(path) => Ok(Some(compress::read_to_string(path)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Log files stored for a version
+    fn log_paths(&self, package: &PackageId, version: &str) -> Vec<PathBuf> {
+        let dir = self.package_dir(package);
+        let base = format!("{}.log", version);
+        Algorithm::ALL
+            .into_iter()
+            .map(|a| dir.join(Compression::new(a).file_name(&base)))
+            .filter(|p| p.exists())
+            .collect()
+    }
+
+    /// All reports for a package, oldest build first
+    pub fn load(&self, package: &PackageId) -> Result<Vec<BuildReport>> {
+        let dir = self.package_dir(package);
+        let mut reports = Vec::new();
+        if !dir.exists() {
+            return Ok(reports);
+        }
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "json") {
+                if let Some(report) = read_report(&path) {
+                    reports.push(report);
+                }
+            }
+        }
+        reports.sort_by_key(|r| r.timestamp);
+        Ok(reports)
+    }
+
+    /// Reports of every package, newest build first
+    pub fn all(&self) -> Result<Vec<BuildReport>> {
+        let mut reports = Vec::new();
+        if !self.dir.exists() {
+            return Ok(reports);
+        }
+        for category in std::fs::read_dir(&self.dir)? {
+            let category = category?.path();
+            if !category.is_dir() {
+                continue;
+            }
+            for package in std::fs::read_dir(&category)? {
+                let package = package?.path();
+                if !package.is_dir() {
+                    continue;
+                }
+                for entry in std::fs::read_dir(&package)? {
+                    let path = entry?.path();
+                    if path.extension().is_some_and(|e| e == "json") {
+                        reports.extend(read_report(&path));
+                    }
+                }
+            }
+        }
+        reports.sort_by_key(|r| std::cmp::Reverse(r.timestamp));
+        Ok(reports)
+    }
+}
+
+fn read_report(path: &Path) -> Option<BuildReport> {
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+gcc -c foo.c -o foo.o
+src/foo.c:12:5: warning: unused variable 'x' [-Wunused-variable]
+src/foo.c:20:1: warning: control reaches end of non-void function [-Wreturn-type]
+src/foo.c:31:9: warning: unused variable 'y' [-Wunused-variable]
+src/bar.c:3:10: fatal error: baz.h: No such file or directory
+cc1: warning: command-line option '-Wfoo' is valid for C++ but not for C
+warning: unused import: `std::fs`
+ --> src/lib.rs:1:5
+error[E0308]: mismatched types
+  --> src/main.rs:4:18
+";
+
+    #[test]
+    fn test_parse_diagnostics() {
+        let diags = parse_diagnostics(LOG);
+        let summary: Vec<(&str, Severity, &str)> = diags
+            .iter()
+            .map(|d| (d.file.as_str(), d.severity, d.class.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("src/foo.c", Severity::Warning, "-Wunused-variable"),
+                ("src/foo.c", Severity::Warning, "-Wreturn-type"),
+                ("src/foo.c", Severity::Warning, "-Wunused-variable"),
+                ("src/bar.c", Severity::Error, "error"),
+                ("src/lib.rs", Severity::Warning, "unclassified"),
+                ("src/main.rs", Severity::Error, "E0308"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_report_store_and_compare() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ReportStore::new(dir.path());
+        let id = PackageId::new("app-misc", "foo");
+
+        let old = BuildReport::from_log(id.clone(), "1.0.0", None, true, LOG);
+        let mut new = BuildReport::from_log(
+            id.clone(),
+            "1.1.0",
+            Some("gcc 14.1".to_string()),
+            true,
+            "src/foo.c:1:1: warning: x [-Wunused-variable]\n",
+        );
+        new.timestamp = old.timestamp + chrono::Duration::seconds(1);
+        assert_eq!(old.total(Severity::Warning), 4);
+        assert_eq!(old.total(Severity::Error), 2);
+        assert_eq!(
+            old.by_class()[0],
+            (Severity::Warning, "-Wunused-variable".to_string(), 2)
+        );
+
+        store.save(&new).unwrap();
+        store.save(&old).unwrap();
+        let loaded = store.load(&id).unwrap();
+        assert_eq!(
+            loaded
+                .iter()
+                .map(|r| r.version.as_str())
+                .collect::<Vec<_>>(),
+            vec!["1.0.0", "1.1.0"]
+        );
+        assert_eq!(store.all().unwrap()[0].version, "1.1.0");
+
+        let changes = new.compare(&old);
+        assert!(changes.contains(&("-Wunused-variable".to_string(), 2, 1)));
+        assert!(changes.contains(&("E0308".to_string(), 1, 0)));
+    }
+
+    #[test]
+    fn test_build_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let id = PackageId::new("app-misc", "foo");
+        let version_dir = dir.path().join("app-misc/foo");
+
+        // Not kept unless asked for
+        ReportStore::new(dir.path())
+            .save_log(&id, "1.0.0", LOG)
+            .unwrap();
+        assert!(!version_dir.exists());
+
+        // A log from before compression was configured still reads back,
+        // and is replaced by the compressed one
+        std::fs::create_dir_all(&version_dir).unwrap();
+        std::fs::write(version_dir.join("1.0.0.log"), "old log").unwrap();
+        let store = ReportStore::new(dir.path()).with_logs("xz:9".parse().unwrap());
+        assert_eq!(store.load_log(&id, "1.0.0").unwrap().unwrap(), "old log");
+
+        store.save_log(&id, "1.0.0", LOG).unwrap();
+        assert!(version_dir.join("1.0.0.log.xz").exists());
+        assert!(!version_dir.join("1.0.0.log").exists());
+        assert_eq!(store.load_log(&id, "1.0.0").unwrap().unwrap(), LOG);
+        assert_eq!(store.load_log(&id, "2.0.0").unwrap(), None);
+    }
+}
@@ -3,6 +3,7 @@
 //! Handles source downloads with mirror support, checksum verification,
 //! and RESTRICT="fetch" support.
 
+use crate::peer::{Digest, PeerCache};
 use crate::{Error, Result};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
@@ -121,6 +122,8 @@ pub struct DistfileManager {
     config: DistfileConfig,
     /// HTTP client
     client: reqwest::Client,
+    /// LAN peers tried before the source URIs
+    peers: Option<PeerCache>,
 }
 
 impl DistfileManager {
@@ -134,7 +137,17 @@ impl DistfileManager {
         // Ensure distdir exists
         std::fs::create_dir_all(&config.distdir)?;
 
-        Ok(Self { config, client })
+        Ok(Self {
+            config,
+            client,
+            peers: None,
+        })
+    }
+
+    /// Try LAN peers for distfiles before the source URIs and mirrors
+    pub fn with_peers(mut self, peers: PeerCache) -> Self {
+        self.peers = Some(peers);
+        self
     }
 
     /// Fetch a source file
@@ -160,6 +173,30 @@ impl DistfileManager {
             });
         }
 
+        // Ask LAN peers first; they are only used when a digest pins the content
+        if let Some(ref peers) = self.peers {
+            let digest = source
+                .sha512
+                .clone()
+                .map(Digest::Sha512)
+                .or_else(|| source.blake2b.clone().map(Digest::Blake2b));
+            if let Some(digest) = digest {
+                if peers
+                    .fetch(&source.filename, &digest, &dest)
+                    .await
+                    .is_some()
+                    && self.verify_file(&dest, source).await?
+                {
+                    if let Some(ref new_name) = source.rename_to {
+                        let new_dest = self.config.distdir.join(new_name);
+                        std::fs::rename(&dest, &new_dest)?;
+                        return Ok(new_dest);
+                    }
+                    return Ok(dest);
+                }
+            }
+        }
+
         // Try each URI
         let mut last_error = None;
 
@@ -0,0 +1,240 @@
+//! Man pages and a command reference from the clap command tree
+//!
+//! Both are generated from the same [`clap::Command`] the CLI parses its
+//! arguments with, so packaged documentation lists exactly the commands and
+//! options of the binary it ships with. Hidden commands and options are left
+//! out. Options marked global are documented once, on the top-level command.
+
+use crate::Result;
+use clap::{Arg, Command};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// What `gen-docs` writes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DocFormat {
+    /// One man page per command, e.g. `buckos-overlay-add.1`
+    Man,
+    /// A single `<name>.md` command reference
+    Markdown,
+    #[default]
+    All,
+}
+
+/// Write the documentation of `cmd` to `dir`, returning the files written
+pub fn generate(cmd: Command, dir: &Path, format: DocFormat) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir)?;
+    let cmd = prepare(cmd);
+    let mut written = Vec::new();
+    if matches!(format, DocFormat::Man | DocFormat::All) {
+        for cmd in commands(&cmd) {
+            written.push(clap_mangen::Man::new(cmd.clone()).generate_to(dir)?);
+        }
+    }
+    if matches!(format, DocFormat::Markdown | DocFormat::All) {
+        let path = dir.join(format!("{}.md", cmd.get_name()));
+        std::fs::write(&path, render_markdown(&cmd))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Markdown reference of `cmd` and all its subcommands
+pub fn markdown(cmd: Command) -> String {
+    render_markdown(&prepare(cmd))
+}
+
+/// Propagate global options and full command names through the tree
+fn prepare(cmd: Command) -> Command {
+    let mut cmd = cmd.disable_help_subcommand(true);
+    cmd.build();
+    cmd
+}
+
+/// `cmd` and its visible subcommands, depth first
+fn commands(cmd: &Command) -> Vec<&Command> {
+    let mut all = vec![cmd];
+    for sub in cmd.get_subcommands().filter(|s| !s.is_hide_set()) {
+        all.extend(commands(sub));
+    }
+    all
+}
+
+fn render_markdown(root: &Command) -> String {
+    let mut out = String::new();
+    let mut stack = vec![(root, 1)];
+    while let Some((cmd, depth)) = stack.pop() {
+        let name = cmd.get_bin_name().unwrap_or(cmd.get_name());
+        let _ = writeln!(out, "{} `{}`\n", "#".repeat(depth.min(6)), name);
+        if let Some(about) = cmd.get_long_about().or(cmd.get_about()) {
+            let _ = writeln!(out, "{}\n", about.to_string().trim());
+        }
+        let usage = cmd.clone().render_usage().to_string();
+        let _ = writeln!(out, "```text\n{}\n```\n", usage.trim());
+
+        let args: Vec<&Arg> = cmd
+            .get_arguments()
+            .filter(|a| !a.is_hide_set())
+            .filter(|a| depth == 1 || !a.is_global_set())
+            .filter(|a| !matches!(a.get_id().as_str(), "help" | "version"))
+            .collect();
+        if !args.is_empty() {
+            out.push_str("| Option | Description |\n|--------|-------------|\n");
+            for arg in args {
+                let _ = writeln!(out, "| `{}` | {} |", arg_name(arg), arg_help(arg));
+            }
+            out.push('\n');
+        }
+
+        let subs: Vec<&Command> = cmd.get_subcommands().filter(|s| !s.is_hide_set()).collect();
+        if !subs.is_empty() {
+            out.push_str("| Command | Description |\n|---------|-------------|\n");
+            for sub in &subs {
+                let about = sub.get_about().map(|a| a.to_string()).unwrap_or_default();
+                let _ = writeln!(out, "| `{}` | {} |", sub.get_name(), table_cell(&about));
+            }
+            out.push('\n');
+        }
+        stack.extend(subs.into_iter().rev().map(|sub| (sub, depth + 1)));
+    }
+    out
+}
+
+/// How an argument is written on the command line, e.g. `-j, --jobs <JOBS>`
+fn arg_name(arg: &Arg) -> String {
+    let values = arg
+        .get_value_names()
+        .map(|names| {
+            names
+                .iter()
+                .map(|n| format!("<{}>", n))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .unwrap_or_else(|| format!("<{}>", arg.get_id().as_str().to_uppercase()));
+    if arg.is_positional() {
+        return values;
+    }
+    let mut flags = Vec::new();
+    flags.extend(arg.get_short().map(|s| format!("-{}", s)));
+    flags.extend(arg.get_long().map(|l| format!("--{}", l)));
+    let mut name = flags.join(", ");
+    if arg.get_num_args().is_some_and(|n| n.takes_values()) {
+        name.push(' ');
+        name.push_str(&values);
+    }
+    name
+}
+
+fn arg_help(arg: &Arg) -> String {
+    let mut help = arg
+        .get_long_help()
+        .or(arg.get_help())
+        .map(|h| h.to_string())
+        .unwrap_or_default();
+    let values: Vec<String> = arg
+        .get_possible_values()
+        .iter()
+        .filter(|v| !v.is_hide_set())
+        .map(|v| format!("`{}`", v.get_name()))
+        .collect();
+    if !values.is_empty() {
+        let _ = write!(help, " (one of {})", values.join(", "));
+    }
+    // Flags default to off, which goes without saying
+    let takes_values = arg.get_num_args().is_some_and(|n| n.takes_values());
+    if takes_values && !arg.get_default_values().is_empty() {
+        let defaults: Vec<_> = arg
+            .get_default_values()
+            .iter()
+            .map(|v| v.to_string_lossy())
+            .collect();
+        let _ = write!(help, " [default: `{}`]", defaults.join(","));
+    }
+    if let Some(env) = arg.get_env() {
+        let _ = write!(help, " [env: `{}`]", env.to_string_lossy());
+    }
+    table_cell(help.trim())
+}
+
+/// Text fit for one Markdown table cell
+fn table_cell(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{ArgAction, CommandFactory, Parser, Subcommand};
+
+    #[derive(Parser)]
+    #[command(name = "tool", about = "A tool")]
+    struct Cli {
+        /// Verbose output
+        #[arg(short, long, global = true, action = ArgAction::Count)]
+        verbose: u8,
+        #[command(subcommand)]
+        command: Commands,
+    }
+
+    #[derive(Subcommand)]
+    enum Commands {
+        /// Manage remotes
+        Remote {
+            #[command(subcommand)]
+            command: RemoteCommand,
+        },
+        #[command(hide = true)]
+        Internal,
+    }
+
+    #[derive(Subcommand)]
+    enum RemoteCommand {
+        /// Add a remote | alias
+        Add {
+            /// Name of the remote
+            name: String,
+            /// How often to fetch
+            #[arg(long, default_value = "daily", value_parser = ["daily", "weekly"])]
+            fetch: String,
+        },
+    }
+
+    #[test]
+    fn test_markdown() {
+        let md = markdown(Cli::command());
+        assert!(md.starts_with("# `tool`\n\nA tool\n"));
+        assert!(md.contains("| `-v, --verbose` | Verbose output |"));
+        assert!(md.contains("## `tool remote`"));
+        assert!(md.contains("| `add` | Add a remote \\| alias |"));
+        assert!(md.contains("### `tool remote add`"));
+        assert!(md.contains("Usage: tool remote add [OPTIONS] <NAME>"));
+        assert!(md.contains("| `<NAME>` | Name of the remote |"));
+        assert!(md.contains(
+            "| `--fetch <FETCH>` | How often to fetch (one of `daily`, `weekly`) \
+             [default: `daily`] |"
+        ));
+        // Global options only on the top-level command, hidden commands never
+        assert_eq!(md.matches("--verbose").count(), 1);
+        assert!(!md.contains("internal"));
+    }
+
+    #[test]
+    fn test_generate() {
+        let dir = tempfile::tempdir().unwrap();
+        let written = generate(Cli::command(), dir.path(), DocFormat::All).unwrap();
+        let names: Vec<_> = written
+            .iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            ["tool.1", "tool-remote.1", "tool-remote-add.1", "tool.md"]
+        );
+        let page = std::fs::read_to_string(dir.path().join("tool-remote-add.1")).unwrap();
+        assert!(page.contains("\\-\\-fetch"));
+    }
+}
@@ -0,0 +1,613 @@
+//! Fast package query cache (eix)
+//!
+//! Loading every repository's metadata is too slow for interactive
+//! searches, so after each sync the package list is flattened into a
+//! compact binary file: a deduplicated string table followed by one record
+//! per package whose fields are varint indices into that table. Queries read
+//! only this file, never the repositories or the package database.
+
+use crate::{Error, InstalledPackage, PackageHeader, PackageId, PackageInfo, Result};
+use regex::{Regex, RegexBuilder};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// File magic, followed by a format version byte
+const MAGIC: &[u8; 5] = b"BKEIX";
+/// Bumped whenever the record layout changes
+const FORMAT_VERSION: u8 = 1;
+
+/// One package in the cache, with all its available versions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EixEntry {
+    pub id: PackageId,
+    /// Available versions, oldest first
+    pub versions: Vec<String>,
+    pub description: String,
+    pub homepage: String,
+    pub license: String,
+    /// USE flags of the newest version
+    pub use_flags: Vec<String>,
+    /// Installed versions when the cache was generated
+    pub installed: Vec<String>,
+}
+
+impl EixEntry {
+    /// Newest available version
+    pub fn latest(&self) -> &str {
+        self.versions.last().map(String::as_str).unwrap_or_default()
+    }
+
+    /// Whether an installed version is older than the newest available one
+    pub fn is_upgradable(&self) -> bool {
+        let Ok(latest) = semver::Version::parse(self.latest()) else {
+            return false;
+        };
+        self.installed
+            .iter()
+            .filter_map(|v| semver::Version::parse(v).ok())
+            .any(|v| v < latest)
+    }
+
+    /// Render the entry through a `--format` template
+    ///
+    /// Placeholders: `{category}`, `{name}`, `{fullname}`, `{version}`,
+    /// `{versions}`, `{installed}`, `{description}`, `{homepage}`,
+    /// `{license}` and `{use}`. `\n` and `\t` are expanded; unknown
+    /// placeholders are printed as-is.
+    pub fn format(&self, template: &str) -> String {
+        let template = template.replace("\\n", "\n").replace("\\t", "\t");
+        let mut out = String::with_capacity(template.len() + 64);
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            let Some(len) = rest[start..].find('}') else {
+                break;
+            };
+            let key = &rest[start + 1..start + len];
+            match key {
+                "category" => out.push_str(&self.id.category),
+                "name" => out.push_str(&self.id.name),
+                "fullname" => out.push_str(&self.id.full_name()),
+                "version" => out.push_str(self.latest()),
+                "versions" => out.push_str(&self.versions.join(" ")),
+                "installed" => out.push_str(&self.installed.join(" ")),
+                "description" => out.push_str(&self.description),
+                "homepage" => out.push_str(&self.homepage),
+                "license" => out.push_str(&self.license),
+                "use" => out.push_str(&self.use_flags.join(" ")),
+                _ => out.push_str(&rest[start..=start + len]),
+            }
+            rest = &rest[start + len + 1..];
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+/// The newest version of each package, whose full record [`EixCache::build`]
+/// needs
+pub fn newest(headers: &[PackageHeader]) -> Vec<PackageHeader> {
+    let mut newest: BTreeMap<&PackageId, &PackageHeader> = BTreeMap::new();
+    for header in headers {
+        newest
+            .entry(&header.id)
+            .and_modify(|current| {
+                if header.version > current.version {
+                    *current = header;
+                }
+            })
+            .or_insert(header);
+    }
+    newest.into_values().cloned().collect()
+}
+
+/// Flattened package metadata for fast queries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EixCache {
+    /// When the cache was generated (Unix seconds)
+    pub generated: i64,
+    /// Packages sorted by category and name
+    pub entries: Vec<EixEntry>,
+}
+
+impl EixCache {
+    /// Build a cache from package headers, the full records of the
+    /// [`newest`] versions, and the installed packages
+    pub fn build(
+        headers: &[PackageHeader],
+        newest: &[PackageInfo],
+        installed: &[InstalledPackage],
+    ) -> Self {
+        let mut by_id: BTreeMap<(String, String), Vec<&PackageHeader>> = BTreeMap::new();
+        for pkg in headers {
+            by_id
+                .entry((pkg.id.category.clone(), pkg.id.name.clone()))
+                .or_default()
+                .push(pkg);
+        }
+        let mut installed_by_id: HashMap<&PackageId, Vec<String>> = HashMap::new();
+        for pkg in installed {
+            installed_by_id
+                .entry(&pkg.id)
+                .or_default()
+                .push(pkg.version.to_string());
+        }
+
+        let details: HashMap<(&PackageId, &semver::Version), &PackageInfo> = newest
+            .iter()
+            .map(|pkg| ((&pkg.id, &pkg.version), pkg))
+            .collect();
+
+        let entries = by_id
+            .into_values()
+            .map(|mut versions| {
+                versions.sort_by(|a, b| a.version.cmp(&b.version));
+                versions.dedup_by(|a, b| a.version == b.version);
+                let latest = versions[versions.len() - 1];
+                let full = details.get(&(&latest.id, &latest.version));
+                EixEntry {
+                    id: latest.id.clone(),
+                    versions: versions.iter().map(|p| p.version.to_string()).collect(),
+                    description: latest.description.clone(),
+                    homepage: full.and_then(|p| p.homepage.clone()).unwrap_or_default(),
+                    license: full.map(|p| p.license.clone()).unwrap_or_default(),
+                    use_flags: full
+                        .map(|p| p.use_flags.iter().map(|f| f.name.clone()).collect())
+                        .unwrap_or_default(),
+                    installed: installed_by_id.remove(&latest.id).unwrap_or_default(),
+                }
+            })
+            .collect();
+
+        Self {
+            generated: chrono::Utc::now().timestamp(),
+            entries,
+        }
+    }
+
+    /// Entries matching a filter
+    pub fn query<'a>(&'a self, filter: &'a EixFilter) -> impl Iterator<Item = &'a EixEntry> {
+        self.entries.iter().filter(move |e| filter.matches(e))
+    }
+
+    /// Whether the package database changed after the cache was generated,
+    /// making its installed state out of date
+    pub fn is_stale(&self, db_dir: &Path) -> bool {
+        std::fs::metadata(db_dir.join("packages.db"))
+            .and_then(|m| m.modified())
+            .map(|modified| {
+                chrono::DateTime::<chrono::Utc>::from(modified).timestamp() > self.generated
+            })
+            .unwrap_or(false)
+    }
+
+    /// Encode the cache
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut strings = StringTable::default();
+        let mut records = Vec::new();
+        for entry in &self.entries {
+            for s in [
+                &entry.id.category,
+                &entry.id.name,
+                &entry.description,
+                &entry.homepage,
+                &entry.license,
+            ] {
+                write_varint(&mut records, strings.intern(s));
+            }
+            for list in [&entry.versions, &entry.use_flags, &entry.installed] {
+                write_varint(&mut records, list.len() as u64);
+                for s in list {
+                    write_varint(&mut records, strings.intern(s));
+                }
+            }
+        }
+
+        let mut out = Vec::with_capacity(records.len() + strings.bytes + 32);
+        out.extend_from_slice(MAGIC);
+        out.push(FORMAT_VERSION);
+        out.extend_from_slice(&self.generated.to_le_bytes());
+        write_varint(&mut out, strings.list.len() as u64);
+        for s in &strings.list {
+            write_varint(&mut out, s.len() as u64);
+            out.extend_from_slice(s.as_bytes());
+        }
+        write_varint(&mut out, self.entries.len() as u64);
+        out.extend_from_slice(&records);
+        out
+    }
+
+    /// Decode a cache written by [`EixCache::to_bytes`]
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let corrupt =
+            || Error::Other("eix cache is corrupt; run `buckos eix --update`".to_string());
+        let mut reader = Reader { data, pos: 0 };
+
+        if reader.take(MAGIC.len()).ok_or_else(corrupt)? != MAGIC {
+            return Err(corrupt());
+        }
+        let version = reader.take(1).ok_or_else(corrupt)?[0];
+        if version != FORMAT_VERSION {
+            return Err(Error::Other(format!(
+                "eix cache has format {} (expected {}); run `buckos eix --update`",
+                version, FORMAT_VERSION
+            )));
+        }
+        let generated = i64::from_le_bytes(
+            reader
+                .take(8)
+                .ok_or_else(corrupt)?
+                .try_into()
+                .map_err(|_| corrupt())?,
+        );
+
+        let count = reader.varint().ok_or_else(corrupt)?;
+        let mut strings = Vec::new();
+        for _ in 0..count {
+            let len = reader.varint().ok_or_else(corrupt)? as usize;
+            let bytes = reader.take(len).ok_or_else(corrupt)?;
+            strings.push(std::str::from_utf8(bytes).map_err(|_| corrupt())?);
+        }
+        let string = |reader: &mut Reader| -> Option<String> {
+            strings
+                .get(reader.varint()? as usize)
+                .map(|s| s.to_string())
+        };
+        let list = |reader: &mut Reader| -> Option<Vec<String>> {
+            let len = reader.varint()?;
+            (0..len).map(|_| string(reader)).collect()
+        };
+
+        let count = reader.varint().ok_or_else(corrupt)?;
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let mut entry = || -> Option<EixEntry> {
+                Some(EixEntry {
+                    id: PackageId::new(string(&mut reader)?, string(&mut reader)?),
+                    description: string(&mut reader)?,
+                    homepage: string(&mut reader)?,
+                    license: string(&mut reader)?,
+                    versions: list(&mut reader)?,
+                    use_flags: list(&mut reader)?,
+                    installed: list(&mut reader)?,
+                })
+            };
+            entries.push(entry().ok_or_else(corrupt)?);
+        }
+
+        Ok(Self { generated, entries })
+    }
+
+    /// Write the cache atomically
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, self.to_bytes())?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Read a cache file, or None if it has not been generated yet
+    pub fn read(path: &Path) -> Result<Option<Self>> {
+        match std::fs::read(path) {
+            Ok(data) => Self::from_bytes(&data).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Package filter expression
+///
+/// Terms are combined with `and` (implied between adjacent terms), `or`
+/// and `not`, with parentheses for grouping. A term is one of
+/// `installed`, `upgradable`, `category:RE`, `desc:RE`, `license:RE`,
+/// `homepage:RE`, `use:FLAG`, `name:RE`, or a bare regex matched against
+/// the package name. Regexes are ASCII case-insensitive.
+#[derive(Debug, Clone)]
+pub enum EixFilter {
+    All,
+    Installed,
+    Upgradable,
+    Name(Regex),
+    Category(Regex),
+    Description(Regex),
+    License(Regex),
+    Homepage(Regex),
+    Use(String),
+    Not(Box<EixFilter>),
+    And(Vec<EixFilter>),
+    Or(Vec<EixFilter>),
+}
+
+impl EixFilter {
+    /// Parse a filter expression; an empty expression matches everything
+    pub fn parse(expr: &str) -> Result<Self> {
+        let spaced = expr.replace('(', " ( ").replace(')', " ) ");
+        let tokens: Vec<&str> = spaced.split_whitespace().collect();
+        if tokens.is_empty() {
+            return Ok(EixFilter::All);
+        }
+        let mut parser = Parser { tokens, pos: 0 };
+        let filter = parser.or()?;
+        match parser.peek() {
+            Some(token) => Err(Error::Other(format!(
+                "Unexpected '{}' in query '{}'",
+                token, expr
+            ))),
+            None => Ok(filter),
+        }
+    }
+
+    /// Whether an entry matches
+    pub fn matches(&self, entry: &EixEntry) -> bool {
+        match self {
+            EixFilter::All => true,
+            EixFilter::Installed => !entry.installed.is_empty(),
+            EixFilter::Upgradable => entry.is_upgradable(),
+            EixFilter::Name(re) => re.is_match(&entry.id.name),
+            EixFilter::Category(re) => re.is_match(&entry.id.category),
+            EixFilter::Description(re) => re.is_match(&entry.description),
+            EixFilter::License(re) => re.is_match(&entry.license),
+            EixFilter::Homepage(re) => re.is_match(&entry.homepage),
+            EixFilter::Use(flag) => entry.use_flags.iter().any(|f| f == flag),
+            EixFilter::Not(inner) => !inner.matches(entry),
+            EixFilter::And(filters) => filters.iter().all(|f| f.matches(entry)),
+            EixFilter::Or(filters) => filters.iter().any(|f| f.matches(entry)),
+        }
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<EixFilter> {
+        let mut terms = vec![self.and()?];
+        while self.peek() == Some("or") {
+            self.pos += 1;
+            terms.push(self.and()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            EixFilter::Or(terms)
+        })
+    }
+
+    fn and(&mut self) -> Result<EixFilter> {
+        let mut terms = vec![self.unary()?];
+        loop {
+            match self.peek() {
+                Some("and") => self.pos += 1,
+                Some("or") | Some(")") | None => break,
+                Some(_) => {}
+            }
+            terms.push(self.unary()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            EixFilter::And(terms)
+        })
+    }
+
+    fn unary(&mut self) -> Result<EixFilter> {
+        match self.next() {
+            Some("not") => Ok(EixFilter::Not(Box::new(self.unary()?))),
+            Some("(") => {
+                let inner = self.or()?;
+                match self.next() {
+                    Some(")") => Ok(inner),
+                    _ => Err(Error::Other("Missing ')' in query".to_string())),
+                }
+            }
+            Some(")") | Some("and") | Some("or") | None => {
+                Err(Error::Other("Incomplete query expression".to_string()))
+            }
+            Some(term) => term_filter(term),
+        }
+    }
+}
+
+fn term_filter(term: &str) -> Result<EixFilter> {
+    let regex = |pattern: &str| {
+        RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .unicode(false)
+            .build()
+            .map_err(|e| Error::Other(format!("Invalid pattern '{}': {}", pattern, e)))
+    };
+    Ok(match term.split_once(':') {
+        Some(("name", pattern)) => EixFilter::Name(regex(pattern)?),
+        Some(("category", pattern)) => EixFilter::Category(regex(pattern)?),
+        Some(("desc", pattern)) => EixFilter::Description(regex(pattern)?),
+        Some(("license", pattern)) => EixFilter::License(regex(pattern)?),
+        Some(("homepage", pattern)) => EixFilter::Homepage(regex(pattern)?),
+        Some(("use", flag)) => EixFilter::Use(flag.to_string()),
+        _ if term == "installed" => EixFilter::Installed,
+        _ if term == "upgradable" => EixFilter::Upgradable,
+        _ => EixFilter::Name(regex(term)?),
+    })
+}
+
+#[derive(Default)]
+struct StringTable {
+    list: Vec<String>,
+    index: HashMap<String, u64>,
+    bytes: usize,
+}
+
+impl StringTable {
+    fn intern(&mut self, s: &str) -> u64 {
+        if let Some(&i) = self.index.get(s) {
+            return i;
+        }
+        let i = self.list.len() as u64;
+        self.bytes += s.len() + 2;
+        self.list.push(s.to_string());
+        self.index.insert(s.to_string(), i);
+        i
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn varint(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(category: &str, name: &str, versions: &[&str], installed: &[&str]) -> EixEntry {
+        EixEntry {
+            id: PackageId::new(category, name),
+            versions: versions.iter().map(|v| v.to_string()).collect(),
+            description: format!("The {} package", name),
+            homepage: String::new(),
+            license: "MIT".to_string(),
+            use_flags: vec!["ssl".to_string()],
+            installed: installed.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+
+    fn cache() -> EixCache {
+        let mut vim = entry("app-editors", "vim", &["9.0.0", "9.1.0"], &["9.0.0"]);
+        vim.use_flags = vec!["python".to_string()];
+        EixCache {
+            generated: 1_700_000_000,
+            entries: vec![
+                vim,
+                entry("app-editors", "nano", &["8.0.0"], &[]),
+                entry("net-misc", "curl", &["8.5.0"], &["8.5.0"]),
+            ],
+        }
+    }
+
+    fn names(cache: &EixCache, expr: &str) -> Vec<String> {
+        let filter = EixFilter::parse(expr).unwrap();
+        cache.query(&filter).map(|e| e.id.name.clone()).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let cache = cache();
+        let bytes = cache.to_bytes();
+        assert_eq!(EixCache::from_bytes(&bytes).unwrap(), cache);
+        assert!(EixCache::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(EixCache::from_bytes(b"garbage").is_err());
+    }
+
+    #[test]
+    fn test_filters() {
+        let cache = cache();
+        assert_eq!(names(&cache, ""), vec!["vim", "nano", "curl"]);
+        assert_eq!(names(&cache, "installed"), vec!["vim", "curl"]);
+        assert_eq!(names(&cache, "upgradable"), vec!["vim"]);
+        assert_eq!(names(&cache, "category:^app- not installed"), vec!["nano"]);
+        assert_eq!(
+            names(&cache, "use:ssl or ^VIM$"),
+            vec!["vim", "nano", "curl"]
+        );
+        assert_eq!(
+            names(&cache, "not (use:ssl and installed) and desc:package"),
+            vec!["vim", "nano"]
+        );
+        assert!(EixFilter::parse("(installed").is_err());
+        assert!(EixFilter::parse("installed and").is_err());
+        assert!(EixFilter::parse("name:[").is_err());
+    }
+
+    #[test]
+    fn test_format() {
+        let cache = cache();
+        assert_eq!(
+            cache.entries[0].format("{fullname}\\t{version} [{installed}] {bogus}"),
+            "app-editors/vim\t9.1.0 [9.0.0] {bogus}"
+        );
+    }
+
+    #[test]
+    fn test_build_from_headers() {
+        let header = |version: &str| PackageHeader {
+            id: PackageId::new("app-editors", "vim"),
+            version: semver::Version::parse(version).unwrap(),
+            slot: "0".to_string(),
+            description: "Vi IMproved".to_string(),
+            size: 0,
+            repository: "buckos".to_string(),
+            metadata: None,
+        };
+        let headers = vec![header("9.1.0"), header("9.0.0")];
+        let newest = newest(&headers);
+        assert_eq!(newest, vec![header("9.1.0")]);
+
+        let full: PackageInfo = serde_json::from_value(serde_json::json!({
+            "id": {"category": "app-editors", "name": "vim"},
+            "version": "9.1.0",
+            "slot": "0",
+            "description": "Vi IMproved",
+            "homepage": "https://www.vim.org",
+            "license": "Vim",
+            "keywords": [],
+            "use_flags": [{"name": "python", "description": "", "default": false}],
+            "dependencies": [],
+            "build_dependencies": [],
+            "runtime_dependencies": [],
+            "source_url": null,
+            "source_hash": null,
+            "buck_target": "",
+            "size": 0,
+            "installed_size": 0
+        }))
+        .unwrap();
+        let cache = EixCache::build(&headers, &[full], &[]);
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(cache.entries[0].versions, vec!["9.0.0", "9.1.0"]);
+        assert_eq!(cache.entries[0].license, "Vim");
+        assert_eq!(cache.entries[0].use_flags, vec!["python"]);
+    }
+}
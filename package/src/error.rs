@@ -26,6 +26,9 @@ pub enum Error {
     #[error("Dependency resolution failed: {0}")]
     ResolutionFailed(String),
 
+    #[error("Dependency resolution {reason}: {explanation}")]
+    ResolutionStopped { reason: String, explanation: String },
+
     #[error("Circular dependency detected: {0}")]
     CircularDependency(String),
 
@@ -39,6 +42,9 @@ pub enum Error {
     #[error("Build failed for {package}: {message}")]
     BuildFailed { package: String, message: String },
 
+    #[error("QA checks failed for {package}: {message}")]
+    QaFailed { package: String, message: String },
+
     #[error("Buck error: {0}")]
     BuckError(String),
 
@@ -58,6 +64,9 @@ pub enum Error {
     #[error("Transaction rolled back: {0}")]
     TransactionRolledBack(String),
 
+    #[error("Interrupted by {}", crate::interrupt::signal_name(*signal))]
+    Interrupted { signal: i32 },
+
     #[error("Repository error: {0}")]
     RepositoryError(String),
 
@@ -229,16 +238,41 @@ pub enum Error {
     #[error("Patch error for {package}: {reason}")]
     PatchError { package: String, reason: String },
 
+    #[error("Workspace not found: {0}")]
+    WorkspaceNotFound(String),
+
+    #[error("Workspace already exists: {0}")]
+    WorkspaceAlreadyExists(String),
+
+    #[error("Invalid workspace name: {0}")]
+    InvalidWorkspaceName(String),
+
     #[error("{0}")]
     Other(String),
 }
 
+impl From<buckos_core::AtomError> for Error {
+    fn from(err: buckos_core::AtomError) -> Self {
+        match err {
+            buckos_core::AtomError::InvalidSpec(s) => Error::InvalidPackageSpec(s),
+            buckos_core::AtomError::InvalidVersion(s) => Error::InvalidVersion(s),
+        }
+    }
+}
+
+impl From<buckos_core::compress::CompressError> for Error {
+    fn from(err: buckos_core::compress::CompressError) -> Self {
+        Error::ConfigError(err.to_string())
+    }
+}
+
 impl From<anyhow::Error> for Error {
     fn from(err: anyhow::Error) -> Self {
         Error::Other(err.to_string())
     }
 }
 
+#[cfg(feature = "tui")]
 impl From<dialoguer::Error> for Error {
     fn from(err: dialoguer::Error) -> Self {
         Error::Other(format!("User input error: {}", err))
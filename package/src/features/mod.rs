@@ -68,12 +68,22 @@ pub enum Feature {
     // Misc features
     /// Enable strict checking
     Strict,
+    /// Block merging images that fail QA checks
+    StrictQa,
     /// Keep work directory after build
     KeepWork,
     /// Enable debugging features
     Debug,
     /// Strip binaries
     Strip,
+    /// Don't strip binaries when merging
+    NoStrip,
+    /// Don't install documentation (/usr/share/doc)
+    NoDoc,
+    /// Don't install man pages
+    NoMan,
+    /// Don't install info pages
+    NoInfo,
     /// Install EAPI 7+ docs
     InstallSources,
     /// Protect running processes from unmerge
@@ -109,9 +119,14 @@ impl Feature {
             Feature::Getbinpkg,
             Feature::Binpkg,
             Feature::Strict,
+            Feature::StrictQa,
             Feature::KeepWork,
             Feature::Debug,
             Feature::Strip,
+            Feature::NoStrip,
+            Feature::NoDoc,
+            Feature::NoMan,
+            Feature::NoInfo,
             Feature::InstallSources,
             Feature::UnmergeBackup,
             Feature::CollisionProtect,
@@ -143,9 +158,14 @@ impl Feature {
             Feature::Getbinpkg => "getbinpkg",
             Feature::Binpkg => "binpkg",
             Feature::Strict => "strict",
+            Feature::StrictQa => "strict-qa",
             Feature::KeepWork => "keepwork",
             Feature::Debug => "debug",
             Feature::Strip => "strip",
+            Feature::NoStrip => "nostrip",
+            Feature::NoDoc => "nodoc",
+            Feature::NoMan => "noman",
+            Feature::NoInfo => "noinfo",
             Feature::InstallSources => "install-sources",
             Feature::UnmergeBackup => "unmerge-backup",
             Feature::CollisionProtect => "collision-protect",
@@ -177,9 +197,14 @@ impl Feature {
             Feature::Getbinpkg => "Use binary packages if available",
             Feature::Binpkg => "Prefer binary packages over building from source",
             Feature::Strict => "Enable strict mode for builds",
+            Feature::StrictQa => "Refuse to merge images that fail QA checks",
             Feature::KeepWork => "Keep work directory after build",
             Feature::Debug => "Enable debug mode for builds",
             Feature::Strip => "Strip debug symbols from binaries",
+            Feature::NoStrip => "Keep binaries unstripped when merging",
+            Feature::NoDoc => "Skip installing documentation",
+            Feature::NoMan => "Skip installing man pages",
+            Feature::NoInfo => "Skip installing info pages",
             Feature::InstallSources => "Install source files for debugging",
             Feature::UnmergeBackup => "Backup files before unmerging",
             Feature::CollisionProtect => "Abort if file collisions are detected",
@@ -211,9 +236,14 @@ impl Feature {
             "getbinpkg" => Some(Feature::Getbinpkg),
             "binpkg" => Some(Feature::Binpkg),
             "strict" => Some(Feature::Strict),
+            "strict-qa" => Some(Feature::StrictQa),
             "keepwork" => Some(Feature::KeepWork),
             "debug" => Some(Feature::Debug),
             "strip" => Some(Feature::Strip),
+            "nostrip" => Some(Feature::NoStrip),
+            "nodoc" => Some(Feature::NoDoc),
+            "noman" => Some(Feature::NoMan),
+            "noinfo" => Some(Feature::NoInfo),
             "install-sources" => Some(Feature::InstallSources),
             "unmerge-backup" => Some(Feature::UnmergeBackup),
             "collision-protect" => Some(Feature::CollisionProtect),
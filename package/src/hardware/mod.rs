@@ -0,0 +1,407 @@
+//! Hardware detection and the USE flags it recommends
+//!
+//! `buckos detect` probes the CPU, GPU, audio and network and recommends
+//! USE flags for them. The last detection is stored in the package database
+//! directory and refreshed at boot (`buckos periodic hardware`). With
+//! `buckos configure --hardware-use` the recommendations are written to
+//! `package.use/99-hardware`, which the USE layers read below make.conf and
+//! package.use, so explicit configuration always wins.
+
+use crate::{PackageId, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Last detection, relative to the database directory
+pub const HARDWARE_FILE: &str = "hardware.json";
+
+/// Generated USE layer, relative to the system root
+pub const HARDWARE_USE_FILE: &str = "etc/buckos/package.use/99-hardware";
+
+/// Detected hardware
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HardwareDetection {
+    pub cpu_features: Vec<String>,
+    pub gpu_drivers: Vec<String>,
+    pub audio_systems: Vec<String>,
+    pub network_features: Vec<String>,
+    pub recommended_use_flags: Vec<String>,
+    #[serde(default)]
+    pub detected_at: Option<DateTime<Utc>>,
+}
+
+impl HardwareDetection {
+    /// Detect all hardware of this machine
+    pub fn detect() -> Self {
+        let mut detection = Self {
+            cpu_features: detect_cpu_features(),
+            gpu_drivers: detect_gpu(),
+            audio_systems: detect_audio(),
+            network_features: detect_network(),
+            recommended_use_flags: Vec::new(),
+            detected_at: Some(Utc::now()),
+        };
+        detection.recommended_use_flags = generate_recommended_flags(&detection);
+        detection
+    }
+
+    /// Path of the stored detection under the database directory
+    pub fn path(db_path: &Path) -> PathBuf {
+        db_path.join(HARDWARE_FILE)
+    }
+
+    /// The stored detection, if hardware was ever detected
+    pub fn load(db_path: &Path) -> Result<Option<Self>> {
+        match fs::read_to_string(Self::path(db_path)) {
+            Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Store the detection
+    pub fn save(&self, db_path: &Path) -> Result<()> {
+        fs::create_dir_all(db_path)?;
+        fs::write(Self::path(db_path), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Whether two detections found the same hardware
+    pub fn same_hardware(&self, other: &Self) -> bool {
+        self.cpu_features == other.cpu_features
+            && self.gpu_drivers == other.gpu_drivers
+            && self.audio_systems == other.audio_systems
+            && self.network_features == other.network_features
+    }
+
+    /// package.use entries for the detected hardware: the recommended
+    /// flags for every package, then flags only some packages take
+    pub fn package_use(&self) -> HardwareUse {
+        let mut entries = vec![("*/*".to_string(), self.recommended_use_flags.clone())];
+
+        let video_cards: Vec<String> = self
+            .gpu_drivers
+            .iter()
+            .filter_map(|driver| match driver.as_str() {
+                "amdgpu" | "radeon" | "nouveau" => Some(format!("video_cards_{}", driver)),
+                "i915" => Some("video_cards_intel".to_string()),
+                _ => None,
+            })
+            .collect();
+        if !video_cards.is_empty() {
+            entries.push(("media-libs/mesa".to_string(), video_cards));
+        }
+        if self.audio_systems.iter().any(|a| a == "pipewire") {
+            entries.push((
+                "media-video/pipewire".to_string(),
+                vec!["sound-server".to_string()],
+            ));
+        }
+
+        HardwareUse { entries }
+    }
+}
+
+/// Store `detection` in place of the previous one, rewriting the layer at
+/// `layer` if one was generated, and return whether the hardware changed
+pub fn refresh(db_path: &Path, layer: &Path, detection: &HardwareDetection) -> Result<bool> {
+    let changed =
+        HardwareDetection::load(db_path)?.is_none_or(|previous| !previous.same_hardware(detection));
+    detection.save(db_path)?;
+    if layer.exists() {
+        detection.package_use().write(layer)?;
+    }
+    Ok(changed)
+}
+
+/// USE flags recommended for the detected hardware, by package pattern
+/// (`*/*`, `category/*` or `category/name`)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HardwareUse {
+    pub entries: Vec<(String, Vec<String>)>,
+}
+
+impl HardwareUse {
+    /// Read a generated layer; empty if it does not exist
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(Self::parse(&content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Parse package.use lines
+    pub fn parse(content: &str) -> Self {
+        let entries = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let pattern = fields.next()?.to_string();
+                Some((pattern, fields.map(str::to_string).collect()))
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// Write the layer, replacing an earlier one
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        if dir.is_file() {
+            return Err(crate::Error::Config(format!(
+                "{} is a file; make it a directory to use {}",
+                dir.display(),
+                path.display()
+            )));
+        }
+        fs::create_dir_all(dir)?;
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    /// Flags that apply to a package, in file order
+    pub fn flags_for(&self, id: &PackageId) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter(|(pattern, _)| {
+                let (category, name) = pattern.split_once('/').unwrap_or((pattern, "*"));
+                (category == "*" || category == id.category) && (name == "*" || name == id.name)
+            })
+            .flat_map(|(_, flags)| flags.iter().cloned())
+            .collect()
+    }
+}
+
+impl std::fmt::Display for HardwareUse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "# Generated by buckos from detected hardware; do not edit."
+        )?;
+        writeln!(
+            f,
+            "# Flags set in make.conf, package.use or on the command line override these."
+        )?;
+        for (pattern, flags) in &self.entries {
+            if !flags.is_empty() {
+                writeln!(f, "{} {}", pattern, flags.join(" "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Detect CPU features
+pub fn detect_cpu_features() -> Vec<String> {
+    let mut features = Vec::new();
+
+    // Read /proc/cpuinfo on Linux
+    if let Ok(cpuinfo) = fs::read_to_string("/proc/cpuinfo") {
+        let cpu_flags = [
+            "aes", "avx", "avx2", "avx512f", "avx512dq", "avx512cd", "avx512bw", "avx512vl", "mmx",
+            "pclmul", "popcnt", "sse", "sse2", "sse3", "ssse3", "sse4_1", "sse4_2", "f16c", "fma",
+        ];
+
+        for flag in cpu_flags {
+            if cpuinfo.contains(flag) {
+                features.push(flag.to_string());
+            }
+        }
+    }
+
+    if features.is_empty() {
+        // Default to basic x86_64 features
+        features = vec!["sse".to_string(), "sse2".to_string(), "mmx".to_string()];
+    }
+
+    features
+}
+
+/// Detect GPU/video hardware
+pub fn detect_gpu() -> Vec<String> {
+    let mut drivers = Vec::new();
+
+    // Check for common GPU vendors
+    let checks = vec![
+        ("/sys/module/nvidia", "nvidia"),
+        ("/sys/module/amdgpu", "amdgpu"),
+        ("/sys/module/i915", "i915"),
+        ("/sys/module/nouveau", "nouveau"),
+        ("/sys/module/radeon", "radeon"),
+    ];
+
+    for (path, driver) in checks {
+        if Path::new(path).exists() {
+            drivers.push(driver.to_string());
+        }
+    }
+
+    if drivers.is_empty() {
+        // Check lspci output if available
+        drivers.push("fbdev".to_string());
+        drivers.push("vesa".to_string());
+    }
+
+    drivers
+}
+
+/// Detect audio systems
+pub fn detect_audio() -> Vec<String> {
+    let mut systems = Vec::new();
+
+    if Path::new("/proc/asound").exists() {
+        systems.push("alsa".to_string());
+    }
+
+    if Path::new("/run/user/1000/pulse").exists() || Path::new("/var/run/pulse").exists() {
+        systems.push("pulseaudio".to_string());
+    }
+
+    if Path::new("/run/user/1000/pipewire-0").exists() {
+        systems.push("pipewire".to_string());
+    }
+
+    if systems.is_empty() {
+        systems.push("alsa".to_string());
+    }
+
+    systems
+}
+
+/// Detect network features
+pub fn detect_network() -> Vec<String> {
+    let mut features = Vec::new();
+
+    // Check for IPv6 support
+    if Path::new("/proc/net/if_inet6").exists() {
+        features.push("ipv6".to_string());
+    }
+
+    // SSL/TLS is generally always available
+    features.push("ssl".to_string());
+    features.push("http2".to_string());
+
+    features
+}
+
+/// Generate recommended USE flags based on detection
+pub fn generate_recommended_flags(detection: &HardwareDetection) -> Vec<String> {
+    let mut flags = Vec::new();
+
+    // Add CPU flags
+    for feature in &detection.cpu_features {
+        flags.push(format!("cpu_flags_x86_{}", feature));
+    }
+
+    // Add GPU-related flags
+    if detection
+        .gpu_drivers
+        .iter()
+        .any(|d| d == "nvidia" || d == "amdgpu" || d == "i915")
+    {
+        flags.push("vulkan".to_string());
+        flags.push("opengl".to_string());
+    }
+
+    // Add audio flags
+    for audio in &detection.audio_systems {
+        flags.push(audio.clone());
+    }
+
+    // Add network flags
+    for net in &detection.network_features {
+        flags.push(net.clone());
+    }
+
+    // Add common flags
+    flags.push("zstd".to_string());
+    flags.push("dbus".to_string());
+
+    flags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detection() -> HardwareDetection {
+        let mut detection = HardwareDetection {
+            cpu_features: vec!["aes".to_string(), "avx2".to_string()],
+            gpu_drivers: vec!["amdgpu".to_string()],
+            audio_systems: vec!["alsa".to_string(), "pipewire".to_string()],
+            network_features: vec!["ipv6".to_string()],
+            ..Default::default()
+        };
+        detection.recommended_use_flags = generate_recommended_flags(&detection);
+        detection
+    }
+
+    #[test]
+    fn test_layer_round_trips_and_matches_packages() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(HARDWARE_USE_FILE);
+        let layer = detection().package_use();
+        layer.write(&path).unwrap();
+        assert_eq!(HardwareUse::load(&path).unwrap(), layer);
+
+        let mesa = layer.flags_for(&PackageId::new("media-libs", "mesa"));
+        assert!(mesa.contains(&"cpu_flags_x86_avx2".to_string()));
+        assert_eq!(mesa.last().unwrap(), "video_cards_amdgpu");
+        let curl = layer.flags_for(&PackageId::new("net-misc", "curl"));
+        assert!(curl.contains(&"vulkan".to_string()));
+        assert!(!curl.contains(&"video_cards_amdgpu".to_string()));
+        assert!(!curl.contains(&"sound-server".to_string()));
+
+        assert_eq!(
+            HardwareUse::load(&dir.path().join("missing")).unwrap(),
+            HardwareUse::default()
+        );
+    }
+
+    #[test]
+    fn test_layer_refuses_package_use_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("etc/buckos")).unwrap();
+        std::fs::write(dir.path().join("etc/buckos/package.use"), "").unwrap();
+        let err = detection()
+            .package_use()
+            .write(&dir.path().join(HARDWARE_USE_FILE))
+            .unwrap_err();
+        assert!(err.to_string().contains("is a file"), "{}", err);
+    }
+
+    #[test]
+    fn test_refresh_rewrites_generated_layer() {
+        let dir = tempfile::tempdir().unwrap();
+        let layer = dir.path().join(HARDWARE_USE_FILE);
+        let mut detection = detection();
+        assert!(refresh(dir.path(), &layer, &detection).unwrap());
+        assert!(!layer.exists());
+        assert!(!refresh(dir.path(), &layer, &detection).unwrap());
+
+        detection.package_use().write(&layer).unwrap();
+        detection.gpu_drivers = vec!["i915".to_string()];
+        assert!(refresh(dir.path(), &layer, &detection).unwrap());
+        let rewritten = HardwareUse::load(&layer).unwrap();
+        assert_eq!(
+            rewritten
+                .flags_for(&PackageId::new("media-libs", "mesa"))
+                .last(),
+            Some(&"video_cards_intel".to_string())
+        );
+    }
+
+    #[test]
+    fn test_detection_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(HardwareDetection::load(dir.path()).unwrap(), None);
+        let detection = detection();
+        detection.save(dir.path()).unwrap();
+        let loaded = HardwareDetection::load(dir.path()).unwrap().unwrap();
+        assert_eq!(loaded, detection);
+        assert!(loaded.same_hardware(&detection));
+    }
+}
@@ -0,0 +1,537 @@
+//! Minimal HTTP/1.1 server primitives
+//!
+//! Just enough HTTP for the small built-in servers (debuginfod, LAN
+//! mirrors): one request per connection, no chunked request bodies.
+//! Anything facing the internet should sit behind a real web server.
+
+use crate::{Error, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt,
+};
+
+/// Maximum number of header lines accepted in a request
+const MAX_HEADERS: usize = 100;
+
+/// Maximum length of a single request or header line
+const MAX_LINE: usize = 8192;
+
+/// A parsed HTTP request
+#[derive(Debug, Clone)]
+pub struct Request {
+    /// Request method (e.g. `GET`)
+    pub method: String,
+    /// Percent-decoded request path, without the query string
+    pub path: String,
+    /// Raw query string, if any
+    pub query: Option<String>,
+    /// Headers, keyed by lowercase name
+    pub headers: HashMap<String, String>,
+}
+
+impl Request {
+    /// Get a header value by (case-insensitive) name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(|s| s.as_str())
+    }
+
+    /// Whether this is a HEAD request
+    pub fn is_head(&self) -> bool {
+        self.method == "HEAD"
+    }
+}
+
+/// Read a request head from a connection
+///
+/// Returns `None` if the connection closed before a request line arrived.
+pub async fn read_request<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<Request>> {
+    let Some(request_line) = read_line(reader).await? else {
+        return Ok(None);
+    };
+
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target), Some(_version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(Error::Other(format!(
+            "malformed request line: {}",
+            request_line
+        )));
+    };
+
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, Some(query.to_string())),
+        None => (target, None),
+    };
+
+    let mut headers = HashMap::new();
+    loop {
+        let line = read_line(reader)
+            .await?
+            .ok_or_else(|| Error::Other("connection closed in request headers".to_string()))?;
+        if line.is_empty() {
+            break;
+        }
+        if headers.len() >= MAX_HEADERS {
+            return Err(Error::Other("too many request headers".to_string()));
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    Ok(Some(Request {
+        method: method.to_string(),
+        path: percent_decode(path),
+        query,
+        headers,
+    }))
+}
+
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<String>> {
+    let mut line = Vec::new();
+    let read = (&mut *reader)
+        .take(MAX_LINE as u64 + 1)
+        .read_until(b'\n', &mut line)
+        .await?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if line.len() > MAX_LINE {
+        return Err(Error::Other("request line too long".to_string()));
+    }
+    let line = String::from_utf8_lossy(&line);
+    Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+/// Decode `%XX` escapes in a request path
+pub fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Response body
+#[derive(Debug)]
+pub enum Body {
+    /// In-memory body
+    Bytes(Vec<u8>),
+    /// A byte range of a file on disk, streamed to the client
+    File {
+        /// Open file
+        file: tokio::fs::File,
+        /// Offset of the first byte to send
+        offset: u64,
+        /// Number of bytes to send
+        len: u64,
+    },
+}
+
+impl Body {
+    /// Length of the body in bytes
+    pub fn len(&self) -> u64 {
+        match self {
+            Body::Bytes(bytes) => bytes.len() as u64,
+            Body::File { len, .. } => *len,
+        }
+    }
+
+    /// Whether the body is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// An HTTP response
+#[derive(Debug)]
+pub struct Response {
+    /// Status code
+    pub status: u16,
+    /// Headers (Content-Length and Connection are added automatically)
+    pub headers: Vec<(String, String)>,
+    /// Body
+    pub body: Body,
+}
+
+impl Response {
+    /// Create a response with a plain-text body
+    pub fn text(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            headers: vec![(
+                "Content-Type".to_string(),
+                "text/plain; charset=utf-8".to_string(),
+            )],
+            body: Body::Bytes(body.into().into_bytes()),
+        }
+    }
+
+    /// 404 Not Found
+    pub fn not_found() -> Self {
+        Self::text(404, "not found\n")
+    }
+
+    /// Serve a whole file
+    pub async fn file(path: &Path) -> Result<Self> {
+        let file = tokio::fs::File::open(path).await?;
+        let len = file.metadata().await?.len();
+        Ok(Self {
+            status: 200,
+            headers: vec![(
+                "Content-Type".to_string(),
+                "application/octet-stream".to_string(),
+            )],
+            body: Body::File {
+                file,
+                offset: 0,
+                len,
+            },
+        })
+    }
+
+    /// Add a header
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Serve a file honouring conditional and range requests
+    ///
+    /// Handles `If-None-Match` (304) and single `Range` requests guarded by
+    /// `If-Range` (206/416), and adds `ETag`, `Last-Modified` and
+    /// `Accept-Ranges` headers.
+    pub async fn file_for(path: &Path, request: &Request) -> Result<Self> {
+        let file = tokio::fs::File::open(path).await?;
+        let metadata = file.metadata().await?;
+        let len = metadata.len();
+        let etag = etag(&metadata);
+
+        let mut headers = vec![
+            ("ETag".to_string(), etag.clone()),
+            ("Accept-Ranges".to_string(), "bytes".to_string()),
+        ];
+        if let Ok(modified) = metadata.modified() {
+            headers.push(("Last-Modified".to_string(), http_date(modified)));
+        }
+
+        let not_modified = request
+            .header("if-none-match")
+            .is_some_and(|tags| tags.split(',').any(|t| t.trim() == etag || t.trim() == "*"));
+        if not_modified {
+            return Ok(Self {
+                status: 304,
+                headers,
+                body: Body::Bytes(Vec::new()),
+            });
+        }
+
+        // A stale If-Range means the client's partial copy is outdated
+        let range_valid = request.header("if-range").is_none_or(|tag| tag == etag);
+        let range = match request.header("range") {
+            Some(range) if range_valid => parse_range(range, len),
+            _ => ByteRange::Full,
+        };
+
+        let (status, offset, body_len) = match range {
+            ByteRange::Full => (200, 0, len),
+            ByteRange::Partial { start, end } => {
+                headers.push((
+                    "Content-Range".to_string(),
+                    format!("bytes {}-{}/{}", start, end, len),
+                ));
+                (206, start, end - start + 1)
+            }
+            ByteRange::Unsatisfiable => {
+                headers.push(("Content-Range".to_string(), format!("bytes */{}", len)));
+                return Ok(Self {
+                    status: 416,
+                    headers,
+                    body: Body::Bytes(Vec::new()),
+                });
+            }
+        };
+
+        headers.push((
+            "Content-Type".to_string(),
+            "application/octet-stream".to_string(),
+        ));
+        Ok(Self {
+            status,
+            headers,
+            body: Body::File {
+                file,
+                offset,
+                len: body_len,
+            },
+        })
+    }
+}
+
+/// Outcome of evaluating a `Range` header against a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// Send the whole file (no range, or one we don't support)
+    Full,
+    /// Send an inclusive byte range
+    Partial {
+        /// First byte
+        start: u64,
+        /// Last byte (inclusive)
+        end: u64,
+    },
+    /// The range lies outside the file
+    Unsatisfiable,
+}
+
+/// Parse a `Range` header for a file of `len` bytes
+///
+/// Only single ranges are supported; multi-range requests get the whole
+/// file, which RFC 9110 allows.
+pub fn parse_range(header: &str, len: u64) -> ByteRange {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+
+    let (start, end) = match (start.trim(), end.trim()) {
+        // Suffix range: the last N bytes
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return ByteRange::Unsatisfiable,
+            Ok(n) => (len.saturating_sub(n), len.saturating_sub(1)),
+            Err(_) => return ByteRange::Full,
+        },
+        (start, "") => match start.parse::<u64>() {
+            Ok(start) => (start, len.saturating_sub(1)),
+            Err(_) => return ByteRange::Full,
+        },
+        (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) if start <= end => (start, end.min(len.saturating_sub(1))),
+            _ => return ByteRange::Full,
+        },
+    };
+
+    if len == 0 || start >= len {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Partial { start, end }
+}
+
+/// Strong ETag derived from a file's size and modification time
+pub fn etag(metadata: &std::fs::Metadata) -> String {
+    let mtime = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    format!("\"{:x}-{:x}\"", metadata.len(), mtime)
+}
+
+/// Format a timestamp as an HTTP date (RFC 9110 IMF-fixdate)
+pub fn http_date(time: std::time::SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(time)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+/// Compare two secrets without leaking where they differ through timing
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Reason phrase for a status code
+pub fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        416 => "Range Not Satisfiable",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Write a response and return the number of body bytes sent
+///
+/// The body is omitted for HEAD requests.
+pub async fn write_response<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    response: Response,
+    head_only: bool,
+) -> Result<u64> {
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\n",
+        response.status,
+        reason_phrase(response.status)
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str(&format!("Content-Length: {}\r\n", response.body.len()));
+    head.push_str("Connection: close\r\n\r\n");
+    writer.write_all(head.as_bytes()).await?;
+
+    let sent = if head_only {
+        0
+    } else {
+        match response.body {
+            Body::Bytes(bytes) => {
+                writer.write_all(&bytes).await?;
+                bytes.len() as u64
+            }
+            Body::File {
+                mut file,
+                offset,
+                len,
+            } => {
+                file.seek(std::io::SeekFrom::Start(offset)).await?;
+                tokio::io::copy(&mut file.take(len), writer).await?
+            }
+        }
+    };
+
+    writer.flush().await?;
+    Ok(sent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_request() {
+        let raw = b"GET /buildid/ab%20cd/debuginfo?x=1 HTTP/1.1\r\nHost: example\r\nRange: bytes=0-9\r\n\r\n";
+        let mut reader = tokio::io::BufReader::new(&raw[..]);
+        let request = read_request(&mut reader).await.unwrap().unwrap();
+
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/buildid/ab cd/debuginfo");
+        assert_eq!(request.query.as_deref(), Some("x=1"));
+        assert_eq!(request.header("range"), Some("bytes=0-9"));
+        assert_eq!(request.header("HOST"), Some("example"));
+
+        let mut empty = tokio::io::BufReader::new(&b""[..]);
+        assert!(read_request(&mut empty).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_write_response() {
+        let mut out = Vec::new();
+        let sent = write_response(&mut out, Response::text(200, "hello"), false)
+            .await
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert_eq!(sent, 5);
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(out.contains("Content-Length: 5\r\n"));
+        assert!(out.ends_with("\r\n\r\nhello"));
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(
+            parse_range("bytes=0-9", 100),
+            ByteRange::Partial { start: 0, end: 9 }
+        );
+        assert_eq!(
+            parse_range("bytes=90-", 100),
+            ByteRange::Partial { start: 90, end: 99 }
+        );
+        assert_eq!(
+            parse_range("bytes=-10", 100),
+            ByteRange::Partial { start: 90, end: 99 }
+        );
+        assert_eq!(
+            parse_range("bytes=50-500", 100),
+            ByteRange::Partial { start: 50, end: 99 }
+        );
+        assert_eq!(parse_range("bytes=100-", 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-1,5-6", 100), ByteRange::Full);
+        assert_eq!(parse_range("items=0-1", 100), ByteRange::Full);
+    }
+
+    #[tokio::test]
+    async fn test_file_for() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blob");
+        std::fs::write(&path, b"0123456789").unwrap();
+
+        let request = |headers: &[(&str, &str)]| Request {
+            method: "GET".to_string(),
+            path: "/blob".to_string(),
+            query: None,
+            headers: headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        };
+
+        let full = Response::file_for(&path, &request(&[])).await.unwrap();
+        assert_eq!(full.status, 200);
+        let tag = full
+            .headers
+            .iter()
+            .find(|(k, _)| k == "ETag")
+            .map(|(_, v)| v.clone())
+            .unwrap();
+
+        let mut out = Vec::new();
+        let partial = Response::file_for(&path, &request(&[("range", "bytes=2-4")]))
+            .await
+            .unwrap();
+        assert_eq!(partial.status, 206);
+        write_response(&mut out, partial, false).await.unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Content-Range: bytes 2-4/10\r\n"));
+        assert!(out.ends_with("\r\n\r\n234"));
+
+        let cached = Response::file_for(&path, &request(&[("if-none-match", &tag)]))
+            .await
+            .unwrap();
+        assert_eq!(cached.status, 304);
+
+        let stale = Response::file_for(
+            &path,
+            &request(&[("range", "bytes=2-4"), ("if-range", "\"other\"")]),
+        )
+        .await
+        .unwrap();
+        assert_eq!(stale.status, 200);
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("/a%2Fb"), "/a/b");
+        assert_eq!(percent_decode("/100%"), "/100%");
+        assert_eq!(percent_decode("/%zz"), "/%zz");
+    }
+}
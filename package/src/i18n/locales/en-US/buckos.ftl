@@ -0,0 +1,743 @@
+# English messages of the buckos CLI
+#
+# Each message is looked up with tr!("<id>"). Translations copy this file
+# to <locale>/buckos.ftl under the locale directory and may leave out
+# messages, which are then shown in English.
+
+## Confirmation
+
+exiting = Exiting.
+no-changes = No changes made.
+confirm-merge = Would you like to merge these packages?
+confirm-unmerge = Would you like to unmerge these packages?
+
+## Dependency resolution
+
+calculating-dependencies = Calculating dependencies...
+would-be-merged = These are the packages that would be merged:
+would-be-rebuilt = These are the packages that would be rebuilt:
+would-be-unmerged = These are the packages that would be unmerged:
+list-total = Total: { $count } package(s)
+list-new = { $count } new
+list-updates = { $count } updates
+list-rebuilds = { $count } rebuilds
+download-size = Download size: { $size }
+space-required = Space required: { $size }
+plan-hash = Plan: { $hash }
+resolution-stats = Resolution: { $stats }
+any-of-choices = Any-of choices:
+used-from-system = Used from the system:
+
+## Install and update
+
+install-none = No packages to install
+install-done = { $count ->
+    [one] { $count } package installed
+   *[other] { $count } packages installed
+}
+plan-written = Plan { $hash } written to { $path }
+plan-checking = Checking plan { $hash } from { $created }
+world-up-to-date = @world set is up-to-date
+update-done = { $count ->
+    [one] { $count } package updated
+   *[other] { $count } packages updated
+}
+update-pinned = (pinned)
+update-unknown = unknown
+
+## Removal
+
+unmerge-none = No packages to unmerge
+unmerging = Unmerging { $count } package(s)...
+unmerge-done = { $count ->
+    [one] { $count } package unmerged
+   *[other] { $count } packages unmerged
+}
+depclean-none = No packages to depclean
+depclean-selected = { $count } package(s) selected for depclean
+space-freed = Space freed: { $size }
+
+## Resume
+
+resuming = Resuming last operation...
+resume-done = Resume complete
+resume-none = No interrupted operation to resume
+resuming-command = Resuming `buckos { $command }`...
+interrupted = Interrupted by { $signal }; any changes were rolled back
+interrupted-resume = Run `buckos resume` to continue
+
+## Repositories
+
+syncing-repositories = Syncing repositories...
+syncing-all-repositories = Syncing all repositories...
+syncing-repository = Syncing repository: { $repo }...
+sync-complete = Sync complete
+notifications-sent = Sent notifications about { $count } new item(s)
+
+## Search
+
+search-none = No packages found matching '{ $query }'
+search-found = { $count ->
+    [one] Found { $count } package:
+   *[other] Found { $count } packages:
+}
+
+## Eix
+
+eix-cached-packages = Cached { $count } packages
+eix-installed-state-may-be = Installed state may be out of date; run 'buckos eix --update'
+eix-available-versions = Available versions:  { $versions }
+eix-installed-versions = Installed versions:  { $installed }
+eix-homepage = Homepage:            { $homepage }
+eix-description = Description:         { $description }
+eix-found-matches = Found { $count } matches
+
+## Info
+
+info-package-not-found = Package '{ $package }' not found
+info-package-information = Package Information
+info-name = Name
+info-version = Version
+info-slot = Slot
+info-license = License
+info-homepage = Homepage
+info-description = Description
+info-use-flags = USE flags
+info-dependencies = Dependencies
+info-size = Size
+
+## List
+
+list-no-packages-installed = No packages installed
+list-installed-packages = Installed packages ({ $count }):
+
+## Build
+
+build-building-target = Building target: { $target }
+build-successful-in = Build successful in { $duration }
+build-output = Output: { $path }
+build-failed = Build failed
+
+## Clean
+
+clean-cache-cleaned = Cache cleaned
+
+## Verify
+
+verify-verifying-installed-packages = Verifying installed packages...
+verify-all-packages-verified-successfully = All { $count } packages verified successfully
+verify-verification-found-issues = Verification found issues
+verify-file-hashed-unchanged-since = { $hashed } file(s) hashed, { $skipped } unchanged since merge, { $verity } checked by fs-verity
+verify-missing-files = { $count } missing files
+verify-modified-files = { $count } modified files
+
+## Query
+
+query-files-owned-by = Files owned by { $package }:
+query-package-not-installed = Package '{ $package }' not installed
+query-dependencies-of = Dependencies of { $package }:
+query-runtime = { $package } (runtime)
+query-no-packages-depend-on = No packages depend on '{ $package }'
+query-packages-that-depend-on = Packages that depend on { $package }:
+
+## Owner
+
+owner-searching-for-owner-of = Searching for owner of: { $path }
+owner-owns = { $category }/{ $name } { $version } owns { $file_path }
+owner-no-package-owns = No package owns '{ $path }'
+owner-found-matching-file = Found { $count } matching file(s):
+
+## Depgraph
+
+depgraph-dependency-graph-for = Dependency graph for { $package }:
+
+## Config
+
+config-root = Root: { $root }
+config-db-path = DB Path: { $db_path }
+config-cache-dir = Cache Dir: { $cache_dir }
+config-buck-path = Buck Path: { $buck_path }
+config-parallelism = Parallelism: { $parallelism }
+config-architecture = Architecture: { $arch }
+config-current-configuration = Current Configuration
+
+## Print build estimate
+
+print-build-estimate-estimated-build-time-exceeds = Estimated build time { $total } exceeds the time budget of { $budget }
+
+## Print preview report
+
+print-preview-report-transaction-preview = Transaction preview:
+
+## Prefix
+
+prefix = Prefix:       { $root }
+prefix-config = Config:       { $config_dir }
+prefix-state = State:        { $state_dir }
+prefix-database = Database:     { $db_dir }
+prefix-cache = Cache:        { $cache_dir }
+prefix-repositories = Repositories: { $repos_dir }
+prefix-system = System:       { $db }
+prefix-system-not-used-isolated = System:       not used (isolated)
+prefix-source-from-your-shell = Source { $layout } from your shell's startup file to use the prefix.
+prefix-per-user-prefix = Per-user prefix
+
+## Newuse
+
+newuse-checking-for-use-flag = Checking for USE flag changes...
+newuse-no-packages-need-rebuilding = No packages need rebuilding
+newuse-these-packages-have-use = These packages have USE flag changes:
+newuse-package-with-use-flag = { $count } package(s) with USE flag changes
+newuse-packages-rebuilt = { $count } packages rebuilt
+
+## Audit
+
+audit-checking-for-security-vulnerabilities = Checking for security vulnerabilities...
+audit-no-known-vulnerabilities-found = No known vulnerabilities found
+audit-found-security-issue = Found { $count } security issue(s):
+audit-run-install-package-to = Run '{ $command } install <package>' to update affected packages
+audit-buckos = buckos
+
+## Useflags list
+
+useflags-list-no-use-flags-found = No USE flags found
+useflags-list-package = { $name } - { $description } ({ $count ->
+    [one] { $count } package
+   *[other] { $count } packages
+})
+
+## Useflags info
+
+useflags-info-use-flag-not-found = USE flag '{ $flag }' not found
+useflags-info-use-flag-information = USE Flag Information
+useflags-info-flag = Flag
+useflags-info-scope = Scope
+useflags-info-global = global
+useflags-info-local = local
+useflags-info-global-use = Global USE
+useflags-info-packages = Packages
+useflags-info-use-expand-variable = USE_EXPAND Variable
+useflags-info-value = Value
+useflags-info-variable = Variable
+
+## Useflags set
+
+useflags-set-configuration-saved-to = Configuration saved to: { $config_path }
+useflags-set-failed-to-save-configuration = Failed to save configuration: { $error }
+useflags-set-you-may-need-to = You may need to run with elevated privileges or set USE flags manually.
+useflags-set-add-this-to-your = Add this to your make.conf or buckos config:
+useflags-set-setting-use-flags = Setting USE flags
+useflags-set-enabling = Enabling
+useflags-set-disabling = Disabling
+
+## Useflags package
+
+useflags-package-failed-to-write-configuration = Failed to write configuration: { $error }
+useflags-package-failed-to-open-configuration = Failed to open configuration file: { $error }
+useflags-package-add-this-to-your = Add this to your package.use:
+useflags-package-setting-per-package-use = Setting per-package USE flags
+useflags-package-package = Package
+useflags-package-flags = Flags
+
+## Useflags expand
+
+useflags-expand-unknown-use-expand-variable = Unknown USE_EXPAND variable: { $var }
+useflags-expand-available-variables = Available variables:
+useflags-expand-use-expand-variables = USE_EXPAND Variables
+
+## Useflags validate
+
+useflags-validate-no-issues-found = No issues found
+useflags-validate-found-issue = Found { $count } issue(s):
+useflags-validate-validating-use-flag-configuration = Validating USE flag configuration
+
+## Useflags explain
+
+useflags-explain-has-no-use-flags = { $id } has no USE flags
+useflags-explain-set-by = set by: { $chain }
+useflags-explain-locked-by = locked by { $source }
+useflags-explain-toggling-changes-no-dependencies = toggling changes no dependencies
+useflags-explain-with = with { $enabled }{ $flag }:
+useflags-explain-installed-size = installed size { $size_delta }{ $unsigned_abs }
+useflags-explain-files-added-removed = files: { $added } added, { $removed } removed
+useflags-explain-use-flags-for = USE flags for { $id }-{ $version }
+useflags-explain-could-not-resolve-dependencies = could not resolve dependencies with this flag toggled
+
+## Detect
+
+detect-stored-as-this-machine = Stored as this machine's hardware in { $db_path }
+detect-detection-results-saved-to = Detection results saved to: { $path }
+
+## Configure
+
+configure-generating-configuration = Generating configuration...
+configure-wrote-use-flags-for = Wrote USE flags for the detected hardware to { $path }
+configure-configuration-saved-to = Configuration saved to: { $path }
+configure-usage = Usage:
+configure-buck-build-packages-linux = buck2 build //packages/linux/... --config { $path }
+configure-profile = Profile: { $profile }
+configure-use-flags = USE flags: { $count }
+configure-detected-cpu-features = Detected CPU features: { $count }
+configure-detected-gpu-drivers = Detected GPU drivers: { $count }
+configure-configuration-summary = Configuration Summary
+
+## Set list
+
+set-list-unknown-set-type = Unknown set type: { $set_type }
+set-list-available-types-system-task = Available types: system, task, desktop
+set-list-available-package-sets = Available Package Sets
+
+## Set show
+
+set-show-unknown-set = Unknown set: { $set_name }
+set-show-total-packages = Total: { $count } packages
+set-show-package-set = Package Set: { $set_name }
+
+## Get set packages
+
+get-set-packages-warning-failed-to-load = Warning: Failed to load package_sets.bzl: { $error }
+get-set-packages-using-fallback-minimal-set = Using fallback minimal set
+
+## Set install
+
+set-install-installing-set-packages = Installing set: { $set_name } ({ $count } packages)
+set-install-all-packages-in-set = All packages in set are already installed
+set-install-set-installed-successfully = Set '{ $set_name }' installed successfully
+
+## Set compare
+
+set-compare-unknown-set = Unknown set: { $set1 }
+set-compare-unknown-set-2 = Unknown set: { $set2 }
+set-compare-vs = { $set1 } vs { $set2 }
+set-compare-common-packages = Common packages: { $common }
+set-compare-added-in-second-set = Added in second set
+set-compare-removed-from-first-set = Removed from first set
+
+## Patch list
+
+patch-list-total-patches = Total: { $count } patches
+patch-list-no-patches-found-for = No patches found for { $package }
+patch-list-patches-are-read-from = Patches are read from { $patches }/<category>/<name>[-<version>|:<slot>]
+patch-list-patches-for = Patches for { $package }
+patch-list-installed-version-was-built = Installed version was built with
+
+## Patch info
+
+patch-info-patch-not-found = Patch not found: { $patch_name }
+patch-info-patch-information = Patch Information
+patch-info-path = Path
+patch-info-strip = Strip
+patch-info-header = Header
+
+## Patch add
+
+patch-add-added-patch = Added patch: { $dest }
+patch-add-the-patch-will-be = The patch will be applied during the next build of { $package }
+
+## Patch remove
+
+patch-remove-patch-not-found = Patch not found: { $patch_path }
+patch-remove-removed-patch = Removed patch: { $patch_path }
+
+## Patch check
+
+patch-check-checking-patches-for = Checking patches for { $package }...
+patch-check-no-patches-to-check = No patches to check
+patch-check-error-reading = { $name } (error reading: { $error })
+patch-check-not-valid-patch-format = { $name } (not a valid patch format)
+patch-check-valid-format = { $name } (valid format)
+patch-check-applies-with = { $name } (applies with -p{ $strip })
+patch-check-does-not-apply-to = { $name } (does not apply to { $source })
+patch-check-all-patches-validated-successfully = All { $count } patches validated successfully
+patch-check-pass-source-dir-to = Pass --source <dir> to dry-run against unpacked sources.
+patch-check-some-patches-failed-validation = Some patches failed validation
+
+## Patch order
+
+patch-order-no-patches-found = No patches found
+patch-order-for = Patch Order for { $package }
+
+## Patch apply
+
+patch-apply-applied = Applied { $name } (-p{ $strip })
+
+## Deps
+
+deps-no-dependencies = No dependencies
+deps-dependencies-of = Dependencies of { $package }
+deps-build-dependencies = Build Dependencies
+deps-runtime-dependencies = Runtime Dependencies
+
+## Rdeps
+
+rdeps-no-packages-depend-on = No packages depend on { $package }
+rdeps-reverse-dependencies-of = Reverse Dependencies of { $package }
+
+## Profile show
+
+profile-show-and-more = ... and { $packages } more
+profile-show-unknown-profile = Unknown profile: { $profile }
+profile-show-base-packages = Base packages
+
+## Profile set
+
+profile-set-valid-profiles = Valid profiles: { $valid_profiles }
+profile-set-to = Profile set to: { $profile }
+profile-set-run-buckos-update-world = Run 'buckos update @world' to apply profile changes
+
+## Export
+
+export-configuration-exported-to = Configuration exported to: { $path }
+
+## Apply
+
+apply-system-already-matches = System already matches { $manifest }
+apply-changes-needed-to-match = Changes needed to match { $manifest }:
+apply-profile = profile -> { $profile }
+apply-configuration = configuration
+apply-repository = repository { $name } ({ $sync_uri })
+apply-world = @world { $entry }
+apply-total-to-install-to = Total: { $install } to install, { $rebuild } to rebuild, { $remove } to remove
+apply-configuration-written-to = Configuration written to { $path }
+apply-is-not-at-pinned = { $id } is not at pinned version { $version }
+apply-system-converged-to = System converged to { $manifest }
+
+## Converge check
+
+converge-check-system-matches = System matches { $manifest }
+converge-check-system-has-drifted-from = System has drifted from { $manifest }:
+converge-check-configuration-differs = configuration differs
+converge-check-repository-not-configured = repository { $repo } not configured
+converge-check-not-installed = { $id } not installed
+converge-check-installed-outside-the-manifest = { $id } installed outside the manifest
+converge-check-is-pinned = { $id } is { $installed } (pinned { $pinned })
+converge-check-deleted = deleted
+converge-check-modified = modified
+
+## Revdep
+
+revdep-checking-for-packages-with = Checking for packages with broken library dependencies...
+revdep-no-packages-with-broken = No packages with broken dependencies found
+revdep-found-package-with-broken = Found { $count } package(s) with broken dependencies:
+revdep-missing-library = Missing library: { $lib }
+revdep-rebuilding-package = Rebuilding { $count } package(s)...
+revdep-packages-rebuilt-successfully = { $count } packages rebuilt successfully
+
+## Sign
+
+sign-no-keys-found = No keys found
+sign-to-create-new-key = To create a new key: gpg --gen-key
+sign-to-create-new-key-2 = To create a new key: buckos sign --backend minisign generate-key
+sign-generated-minisign-key-in = Generated minisign key { $key_id } in { $keys_dir }
+sign-publish-pub-so-others = Publish { $keys_dir }/{ $key_id }.pub so others can trust it
+sign-importing-key-from = Importing key from { $source }...
+sign-key-imported-successfully = Key imported successfully
+sign-exporting-key-to = Exporting key { $key_id } to { $output }...
+sign-key-exported-successfully = Key exported successfully
+sign-signing-manifest-in = Signing manifest in { $package_dir }...
+sign-manifest-signed-and-written = Manifest signed and written to { $manifest_path }
+sign-verifying-manifest = Verifying manifest { $manifest }...
+sign-all-files-verified = All { $count } files verified
+sign-file-failed-verification = { $count } file(s) failed verification:
+sign-signature-verification-failed = Signature verification failed
+sign-signing-repository = Signing repository { $repo_dir }...
+sign-repository-signed-successfully = Repository signed successfully
+sign-repository-co-signed = Repository co-signed: { $sig_path }
+sign-verifying-repository = Verifying repository { $repo_dir }...
+sign-repository-signature-verified = Repository signature verified
+sign-repository-signature-verification-failed = Repository signature verification failed
+sign-signing-file = Signing file { $file }...
+sign-file-signed = File signed: { $sig_path }
+sign-verifying-file = Verifying file { $file }...
+sign-signature-verified = Signature verified
+sign-key-not-found = Key not found: { $key_id }
+sign-setting-trust-level-for = Setting trust level for { $key_id } to { $trust }...
+sign-trust-level-updated = Trust level updated
+sign-available-signing-keys = Available Signing Keys
+sign-key-id = Key ID:
+sign-user = User:
+sign-trust = Trust:
+sign-expires = Expires:
+sign-key-information = Key Information
+
+## Keys
+
+keys-not-verified-no-keys = not verified; no keys in signing.repository_keys
+keys-on = { $state } { $key } on { $date }{ $reason }
+keys-key-statement-not-applied = { $pending } key statement(s) not applied yet; run 'buckos sync'
+keys-of-required-signatures = { $count } of { $threshold } required signatures
+keys-key-introduced-signed-by = Key { $key_id } introduced, signed by { $signed_by }
+keys-key-revoked-signed-by = Key { $key_id } revoked, signed by { $signed_by }
+keys-trusted = trusted
+keys-revoked = revoked
+keys-signed = signed
+keys-missing = missing
+keys-rejected = rejected
+
+## Verity
+
+verity-of-file-protected = { $package }: { $recorded } of { $files } file(s) protected, { $status }
+verity-fs-verity-is-disabled = fs-verity is disabled; set verity.enabled to protect files as they are merged
+verity-of-covered-file-protected = { $recorded } of { $files } covered file(s) protected ({ $percent }%), { $intact } intact
+verity-protected-file-lost-fs = { $intact } protected file(s) lost fs-verity or changed digest
+verity-enabling-fs-verity-on = Enabling fs-verity on installed files...
+verity-file-protected = { $protected } file(s) protected
+
+## Overlay
+
+overlay-no-overlays-configured = No overlays configured
+overlay-priority = { $status } { $name } { $quality } (priority: { $priority }) { $enabled }
+overlay-enabled-overlay = { $legend } * = enabled overlay
+overlay-adding-local-overlay = Adding local overlay { $name }...
+overlay-adding-overlay-from = Adding overlay { $name } from { $uri }...
+overlay-added-successfully = Overlay { $name } added successfully
+overlay-use-buckos-overlay-enable = Use 'buckos overlay enable { $name }' to enable it
+overlay-removing-overlay = Removing overlay { $name }...
+overlay-removed = Overlay { $name } removed
+overlay-enabling-overlay = Enabling overlay { $name }...
+overlay-enabled = Overlay { $name } enabled
+overlay-disabling-overlay = Disabling overlay { $name }...
+overlay-disabled = Overlay { $name } disabled
+overlay-syncing-overlay = Syncing overlay { $name }...
+overlay-synced = Overlay { $name } synced
+overlay-syncing-all-enabled-overlays = Syncing all enabled overlays...
+overlay-all-overlays-synced = All overlays synced
+overlay-not-found = Overlay not found: { $name }
+overlay-setting-priority-for-to = Setting priority for { $name } to { $priority }...
+overlay-priority-updated = Priority updated
+overlay-no-overlays-found-matching = No overlays found matching '{ $query }'
+overlay-found-overlay-matching = Found { $count } overlay(s) matching '{ $query }'
+overlay-configured-overlays = Configured Overlays
+overlay-disabled-2 = (disabled)
+overlay-legend = Legend:
+overlay-information = Overlay Information
+overlay-name = Name:
+overlay-description = Description:
+overlay-sync-type = Sync Type:
+overlay-sync-uri = Sync URI:
+overlay-location = Location:
+overlay-priority-2 = Priority:
+overlay-quality = Quality:
+overlay-status = Status:
+overlay-enabled-2 = enabled
+overlay-disabled-3 = disabled
+overlay-auto-sync = Auto-sync:
+overlay-yes = yes
+overlay-no = no
+overlay-owner = Owner:
+overlay-homepage = Homepage:
+overlay-masters = Masters:
+overlay-last-sync = Last Sync:
+
+## World clean
+
+world-clean-analyzing-world-set = Analyzing world set...
+world-clean-world-set-is-clean = World set is clean
+world-clean-found-problem = Found { $issues } problem(s)
+world-clean-removed = { $count ->
+    [one] Removed { $count } entry from world
+   *[other] Removed { $count } entries from world
+}
+
+## Workspace
+
+workspace-no-workspaces-in = No workspaces in { $base_dir }
+workspace-created-workspace-in = Created workspace { $name } in { $dir }
+workspace-use-it-with-buckos = Use it with: buckos --workspace { $name } <command>
+workspace-config = Config:   { $config_path }
+workspace-root = Root:     { $root }
+workspace-database = Database: { $db_path }
+workspace-cache = Cache:    { $cache_dir }
+workspace-world = World:    { $world }
+workspace-removed-workspace = Removed workspace { $name }
+workspace = Workspace: { $name }
+
+## Debuginfod
+
+debuginfod-serving-debug-info-on = Serving debug info on { $listen } (set DEBUGINFOD_URLS=http://<host>:{ $next })
+debuginfod-created = Created { $path } ({ $size })
+debuginfod-package-had-no-split = { $count } package(s) had no split debug info
+
+## Serve
+
+serve-serving-on-http = Serving on http://{ $listen }
+serve-serving-on-http-token = Serving on http://{ $listen } (token required)
+serve-sharing-distfiles-with-lan = Sharing distfiles with LAN peers ({ $peer })
+serve-served-requests-average = Served { $requests } requests, { $bytes_sent } ({ $rate }/s average)
+
+## Mirrors
+
+mirrors-no-mirrors-configured-set = No mirrors configured (set fetch.mirrors)
+mirrors-ok-failed-mismatched = { $score }  { $mirror }{ $slow }  ({ $successes } ok, { $failures } failed, { $mismatches } mismatched, { $rate }/s)
+mirrors-nothing-to-audit-no = Nothing to audit: no mirrors configured or no verified distfiles recorded yet
+mirrors-in-fetch-order = Mirrors (in fetch order)
+
+## Print mirror audits
+
+print-mirror-audits-verified-score = { $mirror } { $status } - { $count } verified, { $rate }/s, score { $score }
+print-mirror-audits-checksum-mismatch = checksum mismatch: { $file }
+print-mirror-audits-failed = failed: { $file } ({ $reason })
+print-mirror-audits-persistently-slow-audits-in = persistently slow ({ $checksums } audits in a row)
+
+## History
+
+history-machine = Machine: { $machine_id }
+history-no-resource-usage-was = No resource usage was recorded for this transaction
+history-transaction-changed-nothing-nothing = Transaction { $id } changed nothing; nothing to undo
+history-reverting-transaction-would = Reverting transaction { $id } ({ $command }) would:
+history-run-buckos-undo-to = Run 'buckos undo { $id }' to apply.
+history-no-package-changes-between = No package changes between { $from } and { $to }
+history-no-package-changes-since = No package changes since { $from }
+history-no-transactions-recorded = No transactions recorded
+history-resources = Resources
+history-package-changes = Package changes
+
+## Print resource usage
+
+print-resource-usage-wall-time = Wall time:    { $wall_ms }
+print-resource-usage-cpu-time = CPU time:     { $cpu_ms }
+print-resource-usage-peak-memory = Peak memory:  { $peak_memory }
+print-resource-usage-downloaded = Downloaded:   { $downloaded }
+print-resource-usage-cache-hits-build-action = Cache hits:   { $cache_hits } build action(s), { $prebuilt } binary package(s)
+print-resource-usage-disk = Disk:         { $disk }
+
+## Report transaction usage
+
+report-transaction-usage-transaction-used = Transaction { $id } used:
+report-transaction-usage-see-buckos-history-show = (see 'buckos history show { $id }')
+
+## Undo
+
+undo-reverting-transaction = Reverting transaction { $id } ({ $timestamp }, { $command }):
+undo-cannot-undo-transaction = Cannot undo transaction { $id }:
+undo-transaction-reverted = Transaction { $id } reverted
+
+## Binpkg
+
+binpkg-exported-to = Exported { $id }-{ $version } to { $path } ({ $size })
+binpkg-changed-since-it-was = { $path } changed since it was installed
+binpkg-file-missing-or-not = { $count } file(s) missing or not packable were left out
+binpkg-these-are-the-packages = These are the packages that would be installed from binary archives:
+binpkg-installed-from = Installed { $id }-{ $version } from { $path }
+binpkg-arch = Arch:         { $arch }
+binpkg-compression = Compression:  { $compression }
+binpkg-size-installed = Size:         { $size } ({ $installed_size } installed)
+binpkg-dependencies = Dependencies: { $dependencies }
+binpkg-target = Target:       { $buck_target }
+binpkg-files = Files:        { $count }
+
+## Build report
+
+build-report-no-build-reports-recorded = No build reports recorded for { $package }
+build-report-no-build-log-stored = No build log stored for { $package }-{ $version }
+build-report-warnings-errors = { $version } { $timestamp } { $warnings } warnings { $errors } errors  { $toolchain }{ $new }
+build-report-hint = hint: { $package }
+build-report-none = none
+build-report-builds = Builds
+build-report-unknown-toolchain = unknown toolchain
+build-report-failed = [failed]
+build-report-diagnostics-in = Diagnostics in { $package }-{ $version }
+build-report-noisiest-files = Noisiest files
+build-report-failure = Failure
+build-report-changes-since = Changes since { $version }
+
+## Log
+
+log-no-builds-recorded = No builds recorded
+log-no-failed-builds-recorded = No failed builds recorded
+log-reason = reason: { $reason }
+log-reason-not-recognized-see = reason: not recognized, see 'buckos build-report { $package }'
+
+## Log flaky
+
+log-flaky-no-retried-or-failed = No retried or failed builds recorded
+
+## Plugins
+
+plugins-no-plugins-installed-in = No plugins installed in { $plugin_dir }
+plugins-buckos = buckos { $name } { $about }
+plugins = Plugins
+plugins-skipped = skipped
+
+## Impact
+
+impact-build-time = { $heading } { $package } (build time { $package_estimate })
+impact-packages-affected-estimated-rebuild = { $affected } packages affected, estimated rebuild time { $total_estimate }
+impact-packages-have-no-recorded = ({ $unknown_estimates } packages have no recorded build and are not counted)
+impact-of-upgrading = Impact of upgrading
+
+## Boot manifest
+
+boot-manifest-wrote-files-to = Wrote { $count } files to { $output }
+boot-manifest-regenerate-the-initramfs-with = Regenerate the initramfs with 'dracut --add buckos-verify' so it checks the root against it
+
+## Db
+
+db-backed-up-packages-world = Backed up { $packages } packages, { $world } world entries and { $transactions } transactions to { $output }
+db-checking-packages-from-taken = Checking { $count } packages from { $input } (taken { $created_at }) against the filesystem
+db-none-of-its-files = { $package }: none of its { $count } files are on disk
+db-missing-modified = { $package }: { $missing } missing, { $modified } modified
+db-skipping-packages-that-are = Skipping { $count } packages that are not on disk (--keep-missing restores them)
+db-restored-packages-world-entries = Restored { $packages } packages, { $world } world entries and { $transactions } transactions
+
+## Db check
+
+db-check-no-problems-found-in = No problems found in { $db_path }
+db-check-run-buckos-db-repair = Run 'buckos db repair' to rebuild them from the package records
+
+## Buck
+
+buck-restarting-the-buck-daemon = Restarting the Buck2 daemon
+buck-isolation-dir = Isolation dir: { $isolation_dir }
+buck-daemon-running-pid = Daemon:        running (pid { $pid })
+buck-daemon = Daemon:        { $state }
+buck-started = Started:       { $time }
+buck-client = Client:        { $client_version }
+buck-started-by = Started by:    { $version }
+buck-timeouts-for-queries = Timeouts:      { $command_timeout }s for queries, { $builds }
+buck-the-daemon-predates-the = The daemon predates the installed Buck2; run 'buckos buck restart'
+buck-not-running = not running
+buck-for-builds = { $seconds }s for builds
+buck-no-build-timeout = none for builds
+
+## Gen docs
+
+gen-docs-wrote-file-to = Wrote { $count } file(s) to { $out }
+
+## Gen units
+
+gen-units-exists-skipping-force-replaces = { $path } exists, skipping (--force replaces it)
+gen-units-wrote = Wrote { $path } ({ $calendar })
+gen-units-at-boot = at boot
+
+## Reload init
+
+reload-init-failed-to-reload-service = Failed to reload service definitions: { $message }
+
+## Status
+
+status-generation = { $name } { $time }  generation { $generation }
+status-generation-2 = { $name } generation { $generation }
+status-not-synced-into-generation = { $name } not synced into a generation
+status-no-periodic-tasks-have = No periodic tasks have run; see 'buckos gen-units'
+
+## Db repair
+
+db-repair-the-database-will-be = The database will be moved aside and rebuilt from the package records;
+db-repair-the-transaction-history-it = the transaction history it held is not in the records.
+db-repair-moved-the-damaged-database = Moved the damaged database to { $aside }
+db-repair-restored-packages-from-their = Restored { $count } packages from their records
+db-repair-wrote-missing-package-records = Wrote { $count } missing package records
+
+
+## Useflags get
+
+useflags-get-current-use-configuration = Current USE Configuration
+
+## Profile list
+
+profile-list-available-profiles = Available Profiles
+
+## Profile current
+
+profile-current-current-profile = Current profile
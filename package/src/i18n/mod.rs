@@ -0,0 +1,81 @@
+//! Translated messages of the CLI
+//!
+//! Messages are [Fluent](https://projectfluent.org) resources looked up by
+//! id through [`tr!`](crate::tr). English is built in and is the fallback for
+//! any message a translation lacks; translations are read from
+//! `<dir>/<locale>/buckos.ftl` as described in [`buckos_core::i18n`].
+//!
+//! ```text
+//! # /usr/share/buckos/locales/de/buckos.ftl
+//! sync-complete = Synchronisierung abgeschlossen
+//! ```
+
+use buckos_core::i18n::Catalog;
+
+pub use buckos_core::i18n::{FluentArgs, LOCALE_DIR};
+
+/// The messages of the CLI
+static CATALOG: Catalog = Catalog::new("buckos.ftl", include_str!("locales/en-US/buckos.ftl"));
+
+/// Look up a message, with optional `name = value` arguments
+///
+/// ```ignore
+/// println!("{}", tr!("sync-complete"));
+/// println!("{}", tr!("install-done", count = resolution.packages.len()));
+/// ```
+#[macro_export]
+macro_rules! tr {
+    ($id:literal) => {
+        $crate::i18n::message($id, None)
+    };
+    ($id:literal, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = $crate::i18n::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::i18n::message($id, Some(&args))
+    }};
+}
+
+/// Use the locale of the environment for all further messages
+pub fn init() {
+    CATALOG.init();
+}
+
+/// Format a message in the current locale, English until [`init`] is called
+pub fn message(id: &str, args: Option<&FluentArgs>) -> String {
+    CATALOG.message(id, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// Every message the CLI uses has an English text, every English text
+    /// is used, and nothing printed skips `tr!`
+    #[test]
+    fn test_messages_have_english_text() {
+        let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let mut sources = Vec::new();
+        for entry in walkdir::WalkDir::new(&src) {
+            let entry = entry.unwrap();
+            if entry.path().extension().is_some_and(|e| e == "rs")
+                && !entry.path().ends_with("i18n/mod.rs")
+            {
+                let name = entry
+                    .path()
+                    .strip_prefix(&src)
+                    .unwrap()
+                    .display()
+                    .to_string();
+                sources.push((name, std::fs::read_to_string(entry.path()).unwrap()));
+            }
+        }
+        let problems = buckos_core::i18n::check_sources(
+            &CATALOG,
+            sources
+                .iter()
+                .map(|(name, source)| (name.as_str(), source.as_str())),
+        );
+        assert!(problems.is_empty(), "{}", problems.join("\n"));
+    }
+}
@@ -0,0 +1,266 @@
+//! INSTALL_MASK file filtering
+//!
+//! Skips files matching configured patterns during the merge phase. Masked
+//! files are still recorded in the package database (as
+//! [`FileType::Masked`](crate::FileType::Masked)) so verification does not
+//! report them as missing.
+//!
+//! Pattern syntax follows Portage:
+//!
+//! - `/usr/share/doc` masks the path and everything below it
+//! - `/usr/lib/*.la` globs are matched against the full path
+//! - `*.la` patterns without a slash are matched against the file name
+//! - `-/usr/share/doc/foo` exempts a path from earlier patterns
+//!
+//! The `nodoc`, `noman` and `noinfo` FEATURES add the standard
+//! documentation directories to the mask.
+
+use crate::features::Feature;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Paths masked by FEATURES=nodoc
+pub const NODOC_PATHS: &[&str] = &["/usr/share/doc", "/usr/share/gtk-doc"];
+
+/// Paths masked by FEATURES=noman
+pub const NOMAN_PATHS: &[&str] = &["/usr/share/man"];
+
+/// Paths masked by FEATURES=noinfo
+pub const NOINFO_PATHS: &[&str] = &["/usr/share/info"];
+
+/// A single INSTALL_MASK entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MaskPattern {
+    pattern: String,
+    exempt: bool,
+}
+
+/// INSTALL_MASK configuration
+#[derive(Debug, Clone, Default)]
+pub struct InstallMask {
+    patterns: Vec<MaskPattern>,
+}
+
+impl InstallMask {
+    /// Create an empty mask
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the mask from INSTALL_MASK entries and enabled FEATURES
+    pub fn from_config(install_mask: &[String], features: &HashSet<String>) -> Self {
+        let mut mask = Self::new();
+
+        let feature_paths = [
+            (Feature::NoDoc, NODOC_PATHS),
+            (Feature::NoMan, NOMAN_PATHS),
+            (Feature::NoInfo, NOINFO_PATHS),
+        ];
+        for (feature, paths) in feature_paths {
+            if features.contains(feature.name()) {
+                for path in paths {
+                    mask.add(path);
+                }
+            }
+        }
+
+        for entry in install_mask {
+            for pattern in entry.split_whitespace() {
+                mask.add(pattern);
+            }
+        }
+
+        mask
+    }
+
+    /// Add a pattern (prefix with `-` to exempt)
+    pub fn add(&mut self, pattern: &str) {
+        let (pattern, exempt) = match pattern.strip_prefix('-') {
+            Some(p) => (p, true),
+            None => (pattern, false),
+        };
+        if pattern.is_empty() {
+            return;
+        }
+        self.patterns.push(MaskPattern {
+            pattern: pattern.trim_end_matches('/').to_string(),
+            exempt,
+        });
+    }
+
+    /// Whether any patterns are configured
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Check whether a root-relative path (e.g. `/usr/share/doc/foo/README`) is masked
+    ///
+    /// Later patterns take precedence over earlier ones.
+    pub fn is_masked(&self, path: &Path) -> bool {
+        let path = path.to_string_lossy();
+        let path = if path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("/{}", path)
+        };
+
+        let mut masked = false;
+        for entry in &self.patterns {
+            if pattern_matches(&entry.pattern, &path) {
+                masked = !entry.exempt;
+            }
+        }
+        masked
+    }
+}
+
+/// Match a single pattern against an absolute path
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let has_glob = pattern.contains(['*', '?', '[']);
+
+    if !pattern.starts_with('/') {
+        // Bare patterns match the file name
+        let name = path.rsplit('/').next().unwrap_or(path);
+        return glob_match(pattern, name);
+    }
+
+    if has_glob {
+        // A glob also masks everything below a matching directory
+        let mut prefix = String::new();
+        for component in path.split('/').skip(1) {
+            prefix.push('/');
+            prefix.push_str(component);
+            if glob_match(pattern, &prefix) {
+                return true;
+            }
+        }
+        return false;
+    }
+
+    path == pattern
+        || path
+            .strip_prefix(pattern)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Minimal shell-style glob matching supporting `*`, `?` and `[...]`
+///
+/// `*` does not match `/`.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    glob_match_at(&pattern, &text)
+}
+
+fn glob_match_at(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') => {
+            // Try every split point that doesn't cross a path separator
+            for i in 0..=text.len() {
+                if glob_match_at(&pattern[1..], &text[i..]) {
+                    return true;
+                }
+                if i < text.len() && text[i] == '/' {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => !text.is_empty() && text[0] != '/' && glob_match_at(&pattern[1..], &text[1..]),
+        Some('[') => {
+            let Some(end) = pattern.iter().position(|&c| c == ']') else {
+                return !text.is_empty()
+                    && text[0] == '['
+                    && glob_match_at(&pattern[1..], &text[1..]);
+            };
+            let Some(&c) = text.first() else {
+                return false;
+            };
+            let class = &pattern[1..end];
+            let (negate, class) = match class.first() {
+                Some('!') | Some('^') => (true, &class[1..]),
+                _ => (false, class),
+            };
+            let mut matched = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    matched |= class[i] <= c && c <= class[i + 2];
+                    i += 3;
+                } else {
+                    matched |= class[i] == c;
+                    i += 1;
+                }
+            }
+            matched != negate && glob_match_at(&pattern[end + 1..], &text[1..])
+        }
+        Some(&p) => !text.is_empty() && text[0] == p && glob_match_at(&pattern[1..], &text[1..]),
+    }
+}
+
+/// Summary of files skipped by INSTALL_MASK
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MaskedStats {
+    /// Number of files skipped
+    pub files: usize,
+    /// Bytes not written to disk
+    pub bytes: u64,
+}
+
+impl MaskedStats {
+    /// Record a skipped file
+    pub fn record(&mut self, size: u64) {
+        self.files += 1;
+        self.bytes += size;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix_patterns() {
+        let mut mask = InstallMask::new();
+        mask.add("/usr/share/doc");
+
+        assert!(mask.is_masked(Path::new("/usr/share/doc")));
+        assert!(mask.is_masked(Path::new("/usr/share/doc/foo/README")));
+        assert!(mask.is_masked(Path::new("usr/share/doc/foo/README")));
+        assert!(!mask.is_masked(Path::new("/usr/share/doctor")));
+        assert!(!mask.is_masked(Path::new("/usr/bin/foo")));
+    }
+
+    #[test]
+    fn test_glob_patterns() {
+        let mut mask = InstallMask::new();
+        mask.add("*.la");
+        mask.add("/usr/share/locale/*");
+        mask.add("-/usr/share/locale/en");
+
+        assert!(mask.is_masked(Path::new("/usr/lib/libfoo.la")));
+        assert!(!mask.is_masked(Path::new("/usr/lib/libfoo.so")));
+        assert!(mask.is_masked(Path::new("/usr/share/locale/de/LC_MESSAGES/foo.mo")));
+        assert!(!mask.is_masked(Path::new("/usr/share/locale/en/LC_MESSAGES/foo.mo")));
+    }
+
+    #[test]
+    fn test_features() {
+        let features: HashSet<String> = ["nodoc".to_string(), "noman".to_string()].into();
+        let mask = InstallMask::from_config(&["/usr/lib/debug".to_string()], &features);
+
+        assert!(mask.is_masked(Path::new("/usr/share/doc/foo")));
+        assert!(mask.is_masked(Path::new("/usr/share/man/man1/foo.1")));
+        assert!(!mask.is_masked(Path::new("/usr/share/info/foo.info")));
+        assert!(mask.is_masked(Path::new("/usr/lib/debug/foo.debug")));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("lib*.so.[0-9]", "libfoo.so.1"));
+        assert!(!glob_match("lib*.so.[!0-9]", "libfoo.so.1"));
+        assert!(!glob_match("/usr/*", "/usr/lib/foo"));
+        assert!(glob_match("/usr/*/foo", "/usr/lib/foo"));
+    }
+}
@@ -0,0 +1,213 @@
+//! Ctrl-C and SIGTERM as requests to stop at the next safe point
+//!
+//! While a [`SignalGuard`] is held, SIGINT and SIGTERM no longer kill
+//! buckos. They are recorded instead, and long operations holding a
+//! [`Cancellation`] notice them where stopping leaves nothing half done:
+//! resolution between packages, a transaction between operations. Dropping
+//! the last guard restores the previous handlers.
+
+use crate::{Error, Result};
+use std::sync::atomic::{AtomicI32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Signals received while a guard is installed, one bit each
+static RECEIVED: AtomicU64 = AtomicU64::new(0);
+
+/// Guards installed, so nested ones don't clear each other's signal
+static GUARDS: AtomicUsize = AtomicUsize::new(0);
+
+/// Serializes installing and restoring handlers
+static HANDLERS: Mutex<()> = Mutex::new(());
+
+fn bit(signal: libc::c_int) -> u64 {
+    1u64.checked_shl(signal as u32).unwrap_or(0)
+}
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    RECEIVED.fetch_or(bit(signal), Ordering::SeqCst);
+}
+
+/// Records `signals` rather than dying of them until dropped
+pub struct SignalGuard {
+    previous: Vec<(libc::c_int, libc::sigaction)>,
+}
+
+impl SignalGuard {
+    /// Catch `signals`; `None` if a handler could not be installed
+    pub fn install(signals: &[libc::c_int]) -> Option<Self> {
+        let _lock = HANDLERS.lock().unwrap_or_else(|e| e.into_inner());
+        let mut previous = Vec::new();
+        for &signal in signals {
+            // SAFETY: the handler only updates an atomic, which is
+            // async-signal-safe, and both structs are fully initialized
+            unsafe {
+                let mut action: libc::sigaction = std::mem::zeroed();
+                action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as usize;
+                libc::sigemptyset(&mut action.sa_mask);
+                let mut old: libc::sigaction = std::mem::zeroed();
+                if libc::sigaction(signal, &action, &mut old) != 0 {
+                    restore(&previous);
+                    return None;
+                }
+                previous.push((signal, old));
+            }
+        }
+        if GUARDS.fetch_add(1, Ordering::SeqCst) == 0 {
+            let mask = signals.iter().fold(0, |mask, &s| mask | bit(s));
+            RECEIVED.fetch_and(!mask, Ordering::SeqCst);
+        }
+        Some(Self { previous })
+    }
+
+    /// Whether this guard catches `signal`
+    fn catches(&self, signal: libc::c_int) -> bool {
+        self.previous.iter().any(|(s, _)| *s == signal)
+    }
+}
+
+impl Drop for SignalGuard {
+    fn drop(&mut self) {
+        let _lock = HANDLERS.lock().unwrap_or_else(|e| e.into_inner());
+        restore(&self.previous);
+        GUARDS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Put back handlers replaced by a guard, newest first
+fn restore(previous: &[(libc::c_int, libc::sigaction)]) {
+    for (signal, action) in previous.iter().rev() {
+        // SAFETY: `action` is what sigaction(2) returned for `signal`
+        unsafe {
+            libc::sigaction(*signal, action, std::ptr::null_mut());
+        }
+    }
+}
+
+/// The first of `signals` received since the first guard was installed
+pub fn received(signals: &[libc::c_int]) -> Option<libc::c_int> {
+    let received = RECEIVED.load(Ordering::SeqCst);
+    signals.iter().copied().find(|&s| received & bit(s) != 0)
+}
+
+/// Name of a signal for messages, e.g. `SIGINT`
+pub fn signal_name(signal: libc::c_int) -> String {
+    match signal {
+        libc::SIGINT => "SIGINT".to_string(),
+        libc::SIGTERM => "SIGTERM".to_string(),
+        libc::SIGHUP => "SIGHUP".to_string(),
+        other => format!("signal {}", other),
+    }
+}
+
+/// Exit status of a process stopped by `signal`, as shells report it
+pub fn exit_code(signal: libc::c_int) -> u8 {
+    (128 + signal).clamp(0, 255) as u8
+}
+
+/// A request to stop, shared by clones
+///
+/// Set by [`cancel`](Self::cancel), or by SIGINT and SIGTERM once
+/// [`catch_signals`](Self::catch_signals) is called.
+#[derive(Clone, Default)]
+pub struct Cancellation {
+    signal: Arc<AtomicI32>,
+    guard: Option<Arc<SignalGuard>>,
+}
+
+impl std::fmt::Debug for Cancellation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cancellation")
+            .field("signal", &self.signal())
+            .field("catches_signals", &self.guard.is_some())
+            .finish()
+    }
+}
+
+impl Cancellation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also stop at Ctrl-C and SIGTERM, until the last clone is dropped
+    pub fn catch_signals(mut self) -> Self {
+        self.guard = SignalGuard::install(&[libc::SIGINT, libc::SIGTERM]).map(Arc::new);
+        self
+    }
+
+    /// Also stop at Ctrl-C alone, until the last clone is dropped
+    pub fn catch_interrupt(mut self) -> Self {
+        self.guard = SignalGuard::install(&[libc::SIGINT]).map(Arc::new);
+        self
+    }
+
+    /// Whether signals request a stop, not just [`cancel`](Self::cancel)
+    pub fn catches_signals(&self) -> bool {
+        self.guard.is_some()
+    }
+
+    /// Request a stop, as Ctrl-C would
+    pub fn cancel(&self) {
+        self.signal.store(libc::SIGINT, Ordering::SeqCst);
+    }
+
+    /// The signal that requested a stop, if one did
+    pub fn signal(&self) -> Option<libc::c_int> {
+        match self.signal.load(Ordering::SeqCst) {
+            0 => {
+                let guard = self.guard.as_ref()?;
+                received(&[libc::SIGINT, libc::SIGTERM]).filter(|&s| guard.catches(s))
+            }
+            signal => Some(signal),
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.signal().is_some()
+    }
+
+    /// [`Error::Interrupted`] once a stop is requested
+    pub fn check(&self) -> Result<()> {
+        match self.signal() {
+            Some(signal) => Err(Error::Interrupted { signal }),
+            None => Ok(()),
+        }
+    }
+
+    /// Wait for a stop to be requested
+    pub async fn cancelled(&self) {
+        // Signal handlers can't wake a task, so the flag is polled
+        while !self.is_cancelled() {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancellation() {
+        let cancel = Cancellation::new();
+        assert!(cancel.check().is_ok());
+        cancel.clone().cancel();
+        assert_eq!(cancel.signal(), Some(libc::SIGINT));
+        let err = cancel.check().unwrap_err();
+        assert_eq!(err.to_string(), "Interrupted by SIGINT");
+        assert_eq!(exit_code(libc::SIGINT), 130);
+        assert_eq!(exit_code(libc::SIGTERM), 143);
+    }
+
+    #[test]
+    fn test_signal_is_caught() {
+        // A signal no other test catches, as the flags are process-wide
+        let guard = SignalGuard::install(&[libc::SIGUSR2]).unwrap();
+        assert_eq!(received(&[libc::SIGUSR2]), None);
+        // SAFETY: raise(3) has no memory safety requirements
+        unsafe { libc::raise(libc::SIGUSR2) };
+        assert_eq!(received(&[libc::SIGUSR2]), Some(libc::SIGUSR2));
+        assert!(guard.catches(libc::SIGUSR2));
+        assert_eq!(received(&[libc::SIGINT]), None);
+    }
+}
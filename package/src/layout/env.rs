@@ -0,0 +1,145 @@
+//! Environment of a per-user prefix
+//!
+//! Programs installed in a prefix are only found when the search paths
+//! point into it. The prefix gets an `env.sh` to source from the shell's
+//! startup file, which puts the prefix ahead of the system:
+//!
+//! ```sh
+//! . ~/.local/buckos/env.sh
+//! ```
+
+use super::Layout;
+use crate::Result;
+use std::path::PathBuf;
+
+/// Script setting up the environment, relative to the prefix
+pub const ENV_SCRIPT: &str = "env.sh";
+
+/// A search path variable and the prefix directories prepended to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvVar {
+    pub name: &'static str,
+    pub dirs: Vec<PathBuf>,
+    /// Value the program using it assumes when unset, kept after the
+    /// prefix so the system's entries are still searched
+    pub default: Option<&'static str>,
+}
+
+impl Layout {
+    /// Search path variables pointing into the prefix
+    pub fn env_vars(&self) -> Vec<EnvVar> {
+        let dirs =
+            |paths: &[&str]| -> Vec<PathBuf> { paths.iter().map(|p| self.root.join(p)).collect() };
+        let (bin, lib): (&[&str], &[&str]) = if self.usr_merged {
+            (&["usr/bin"], &["usr/lib64", "usr/lib"])
+        } else {
+            (
+                &["usr/bin", "bin"],
+                &["usr/lib64", "usr/lib", "lib64", "lib"],
+            )
+        };
+        vec![
+            EnvVar {
+                name: "PATH",
+                dirs: dirs(bin),
+                default: None,
+            },
+            EnvVar {
+                name: "LD_LIBRARY_PATH",
+                dirs: dirs(lib),
+                default: None,
+            },
+            EnvVar {
+                name: "PKG_CONFIG_PATH",
+                dirs: dirs(&[
+                    "usr/lib64/pkgconfig",
+                    "usr/lib/pkgconfig",
+                    "usr/share/pkgconfig",
+                ]),
+                default: None,
+            },
+            EnvVar {
+                name: "MANPATH",
+                dirs: dirs(&["usr/share/man"]),
+                // An empty entry makes man search its configured path too
+                default: Some(""),
+            },
+            EnvVar {
+                name: "INFOPATH",
+                dirs: dirs(&["usr/share/info"]),
+                default: None,
+            },
+            EnvVar {
+                name: "XDG_DATA_DIRS",
+                dirs: dirs(&["usr/share"]),
+                default: Some("/usr/local/share:/usr/share"),
+            },
+        ]
+    }
+
+    /// POSIX shell script exporting [`Layout::env_vars`]
+    pub fn env_script(&self) -> String {
+        let mut script = format!(
+            "# Environment of the buckos prefix {}, generated by buckos\n",
+            self.root.display()
+        );
+        for var in self.env_vars() {
+            let dirs: Vec<String> = var.dirs.iter().map(|d| d.display().to_string()).collect();
+            let rest = match var.default {
+                Some(default) => format!(":${{{}:-{}}}", var.name, default),
+                None => format!("${{{0}:+:${0}}}", var.name),
+            };
+            script.push_str(&format!(
+                "export {}=\"{}{}\"\n",
+                var.name,
+                dirs.join(":"),
+                rest
+            ));
+        }
+        script
+    }
+
+    /// Write the prefix's `env.sh`, if its contents changed
+    pub fn write_env_script(&self) -> Result<PathBuf> {
+        let path = self.root.join(ENV_SCRIPT);
+        let script = self.env_script();
+        if std::fs::read_to_string(&path).ok().as_deref() != Some(script.as_str()) {
+            std::fs::create_dir_all(&self.root)?;
+            std::fs::write(&path, script)?;
+        }
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::XdgDirs;
+    use crate::Config;
+    use std::path::Path;
+
+    #[test]
+    fn test_env_script() {
+        let home = Path::new("/home/alex");
+        let mut config = Config::default();
+        config.layout.usr_merge = crate::layout::UsrMerge::Split;
+        let xdg = XdgDirs {
+            home: home.to_path_buf(),
+            config: home.join(".config"),
+            data: home.join(".local/share"),
+            state: home.join(".local/state"),
+            cache: home.join(".cache"),
+        };
+        let script = Layout::user(&config, &xdg).env_script();
+
+        assert!(script.contains(
+            "export PATH=\"/home/alex/.local/buckos/usr/bin:/home/alex/.local/buckos/bin${PATH:+:$PATH}\"\n"
+        ));
+        assert!(script.contains("export LD_LIBRARY_PATH=\"/home/alex/.local/buckos/usr/lib64:"));
+        assert!(script
+            .contains("export MANPATH=\"/home/alex/.local/buckos/usr/share/man:${MANPATH:-}\"\n"));
+        assert!(script.contains(
+            "export XDG_DATA_DIRS=\"/home/alex/.local/buckos/usr/share:${XDG_DATA_DIRS:-/usr/local/share:/usr/share}\"\n"
+        ));
+    }
+}
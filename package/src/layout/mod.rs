@@ -0,0 +1,384 @@
+//! Filesystem layout
+//!
+//! Where buckos keeps configuration, state, caches and installed files,
+//! resolved once from [`Config`] instead of each module joining its own
+//! constant onto ROOT. Modules still name files by their system location
+//! (`etc/buckos/repos.conf`, [`WORLD_FILE`](crate::world::WORLD_FILE));
+//! [`Layout::path`] says where that file lives in this layout.
+//!
+//! The system layout is the Portage one under ROOT. The per-user layout
+//! installs into a prefix owned by the user and follows the XDG base
+//! directories, so packages can be managed without root:
+//!
+//! ```text
+//! /etc/buckos          -> $XDG_CONFIG_HOME/buckos
+//! /var/lib/buckos      -> $XDG_STATE_HOME/buckos
+//! /var/lib/portage     -> $XDG_STATE_HOME/buckos   (the world file)
+//! /var/db/buckos       -> $XDG_DATA_HOME/buckos/db
+//! /var/db/repos        -> $XDG_DATA_HOME/buckos/repos
+//! /var/cache/buckos    -> $XDG_CACHE_HOME/buckos
+//! everything else      -> ~/.local/buckos
+//! ```
+//!
+//! Packages in the prefix are found through the environment set up by its
+//! generated `env.sh` (see [`env`]), and dependencies the system already
+//! satisfies are used from the system (see
+//! [`HostPackages`](crate::resolver::HostPackages)).
+//!
+//! With merged /usr, `/bin`, `/sbin` and `/lib*` are symlinks into `/usr`,
+//! so a file has two names; [`Layout::spellings`] gives both.
+
+pub mod env;
+
+pub use env::*;
+
+use crate::config::Config;
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Directories that are symlinks into /usr on a merged-/usr system
+const MERGED_DIRS: [&str; 5] = ["bin", "sbin", "lib", "lib32", "lib64"];
+
+/// The system's package database, read from per-user prefixes
+pub const HOST_DB: &str = "/var/db/buckos";
+
+/// Who the installation belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutMode {
+    /// The whole system, under ROOT
+    #[default]
+    System,
+    /// The current user, in a prefix in their home directory
+    User,
+}
+
+impl std::fmt::Display for LayoutMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayoutMode::System => write!(f, "system"),
+            LayoutMode::User => write!(f, "user"),
+        }
+    }
+}
+
+/// Whether /bin, /sbin and /lib* are symlinks into /usr
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsrMerge {
+    /// Merged if ROOT's /bin is a symlink
+    #[default]
+    Auto,
+    Merged,
+    Split,
+}
+
+/// Layout settings in the configuration file
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayoutConfig {
+    pub mode: LayoutMode,
+    pub usr_merge: UsrMerge,
+    /// Install prefix in per-user mode, instead of `~/.local/buckos`
+    pub prefix: Option<PathBuf>,
+    /// Install every dependency in the per-user prefix, even those the
+    /// system already has
+    pub isolated: bool,
+}
+
+/// The XDG base directories of the current user
+#[derive(Debug, Clone, PartialEq)]
+pub struct XdgDirs {
+    pub home: PathBuf,
+    pub config: PathBuf,
+    pub data: PathBuf,
+    pub state: PathBuf,
+    pub cache: PathBuf,
+}
+
+impl XdgDirs {
+    /// From `$XDG_*_HOME`, falling back to the defaults under `$HOME`
+    pub fn from_env() -> Option<Self> {
+        let home = dirs::home_dir()?;
+        Some(Self {
+            config: dirs::config_dir().unwrap_or_else(|| home.join(".config")),
+            data: dirs::data_dir().unwrap_or_else(|| home.join(".local/share")),
+            state: dirs::state_dir().unwrap_or_else(|| home.join(".local/state")),
+            cache: dirs::cache_dir().unwrap_or_else(|| home.join(".cache")),
+            home,
+        })
+    }
+}
+
+/// Resolved locations of an installation
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Layout {
+    pub mode: LayoutMode,
+    /// Where packages are installed: ROOT, or the per-user prefix
+    pub root: PathBuf,
+    /// Configuration (`/etc/buckos`)
+    pub config_dir: PathBuf,
+    /// State such as the world file and read news
+    pub state_dir: PathBuf,
+    /// Package database
+    pub db_dir: PathBuf,
+    /// Distfiles, builds and binary packages
+    pub cache_dir: PathBuf,
+    /// Synced repositories
+    pub repos_dir: PathBuf,
+    /// Whether /bin, /sbin and /lib* are symlinks into /usr
+    pub usr_merged: bool,
+    /// Database of the system packages a per-user prefix may use
+    pub host_db: Option<PathBuf>,
+}
+
+impl Layout {
+    /// Resolve the layout a configuration asks for
+    pub fn resolve(config: &Config) -> Result<Self> {
+        match config.layout.mode {
+            LayoutMode::System => Ok(Self::system(config)),
+            LayoutMode::User => {
+                let xdg = XdgDirs::from_env().ok_or_else(|| {
+                    Error::ConfigError("per-user mode needs a home directory".to_string())
+                })?;
+                Ok(Self::user(config, &xdg))
+            }
+        }
+    }
+
+    fn system(config: &Config) -> Self {
+        let root = config.root.clone();
+        Self {
+            mode: LayoutMode::System,
+            config_dir: root.join("etc/buckos"),
+            state_dir: root.join("var/lib/buckos"),
+            db_dir: config.db_path.clone(),
+            cache_dir: config.cache_dir.clone(),
+            repos_dir: PathBuf::from("/var/db/repos"),
+            usr_merged: usr_merged(&root, config.layout.usr_merge),
+            host_db: None,
+            root,
+        }
+    }
+
+    /// The per-user layout under the given XDG directories
+    pub fn user(config: &Config, xdg: &XdgDirs) -> Self {
+        let data = xdg.data.join("buckos");
+        let root = config
+            .layout
+            .prefix
+            .clone()
+            .unwrap_or_else(|| xdg.home.join(".local/buckos"));
+        Self {
+            mode: LayoutMode::User,
+            config_dir: xdg.config.join("buckos"),
+            state_dir: xdg.state.join("buckos"),
+            db_dir: data.join("db"),
+            cache_dir: xdg.cache.join("buckos"),
+            repos_dir: data.join("repos"),
+            usr_merged: usr_merged(&root, config.layout.usr_merge),
+            host_db: (!config.layout.isolated).then(|| PathBuf::from(HOST_DB)),
+            root,
+        }
+    }
+
+    /// Directories system locations move to in this layout
+    fn mapped(&self) -> Vec<(&'static str, &Path)> {
+        let mut dirs = vec![
+            ("var/db/buckos", self.db_dir.as_path()),
+            ("var/cache/buckos", self.cache_dir.as_path()),
+            ("var/db/repos", self.repos_dir.as_path()),
+        ];
+        if self.mode == LayoutMode::User {
+            dirs.extend([
+                ("etc/buckos", self.config_dir.as_path()),
+                ("var/lib/buckos", self.state_dir.as_path()),
+                ("var/lib/portage", self.state_dir.as_path()),
+            ]);
+        }
+        dirs
+    }
+
+    /// Where a buckos file or directory lives in this layout, if it is
+    /// one that moves
+    fn rebase(&self, path: &Path) -> Option<PathBuf> {
+        let relative = path.strip_prefix("/").unwrap_or(path);
+        self.mapped().into_iter().find_map(|(prefix, dir)| {
+            let rest = relative.strip_prefix(prefix).ok()?;
+            Some(if rest.as_os_str().is_empty() {
+                dir.to_path_buf()
+            } else {
+                dir.join(rest)
+            })
+        })
+    }
+
+    /// Where a file named by its system location lives in this layout,
+    /// e.g. `etc/buckos/repos.conf`
+    pub fn path(&self, path: impl AsRef<Path>) -> PathBuf {
+        let path = path.as_ref();
+        self.rebase(path)
+            .unwrap_or_else(|| self.root.join(path.strip_prefix("/").unwrap_or(path)))
+    }
+
+    /// Move a configuration's paths into this layout
+    ///
+    /// Only paths at their system locations move, so explicitly configured
+    /// ones stay where they are. Applying a layout twice changes nothing.
+    pub fn apply(&self, config: &mut Config) {
+        if self.mode == LayoutMode::System {
+            return;
+        }
+        let rebase = |path: &mut PathBuf| {
+            if let Some(moved) = self.rebase(path) {
+                *path = moved;
+            }
+        };
+        config.root = self.root.clone();
+        config.db_path = self.db_dir.clone();
+        config.cache_dir = self.cache_dir.clone();
+        rebase(&mut config.buck_repo);
+        rebase(&mut config.plugin_dir);
+        rebase(&mut config.signing.keys_dir);
+        rebase(&mut config.signing.trusted_keys_dir);
+        for repo in &mut config.repositories {
+            rebase(&mut repo.location);
+        }
+    }
+
+    /// The names a file may be recorded under: itself, and with merged
+    /// /usr its name through the other side of the symlink
+    pub fn spellings(&self, path: &str) -> Vec<String> {
+        let mut names = vec![path.to_string()];
+        if !self.usr_merged {
+            return names;
+        }
+        let relative = path.trim_start_matches('/');
+        let (first, rest) = relative.split_once('/').unwrap_or((relative, ""));
+        if MERGED_DIRS.contains(&first) {
+            names.push(format!("/usr/{}", relative));
+        } else if first == "usr" {
+            let (dir, _) = rest.split_once('/').unwrap_or((rest, ""));
+            if MERGED_DIRS.contains(&dir) {
+                names.push(format!("/{}", rest));
+            }
+        }
+        names
+    }
+}
+
+fn usr_merged(root: &Path, setting: UsrMerge) -> bool {
+    match setting {
+        UsrMerge::Merged => true,
+        UsrMerge::Split => false,
+        UsrMerge::Auto => root.join("bin").is_symlink(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn xdg(home: &Path) -> XdgDirs {
+        XdgDirs {
+            home: home.to_path_buf(),
+            config: home.join(".config"),
+            data: home.join(".local/share"),
+            state: home.join(".local/state"),
+            cache: home.join(".cache"),
+        }
+    }
+
+    #[test]
+    fn test_system_layout() {
+        let config = Config {
+            root: PathBuf::from("/mnt/gentoo"),
+            ..Default::default()
+        };
+        let layout = Layout::resolve(&config).unwrap();
+        assert_eq!(layout.mode, LayoutMode::System);
+        assert_eq!(
+            layout.path(crate::world::WORLD_FILE),
+            PathBuf::from("/mnt/gentoo/var/lib/portage/world")
+        );
+        assert_eq!(
+            layout.path("/etc/buckos/repos.conf"),
+            PathBuf::from("/mnt/gentoo/etc/buckos/repos.conf")
+        );
+        assert_eq!(
+            layout.path("var/db/buckos/keys"),
+            PathBuf::from("/var/db/buckos/keys")
+        );
+
+        let mut applied = config.clone();
+        layout.apply(&mut applied);
+        assert_eq!(applied.root, config.root);
+        assert_eq!(applied.buck_repo, config.buck_repo);
+    }
+
+    #[test]
+    fn test_user_layout() {
+        let home = Path::new("/home/alex");
+        let mut config = Config::default();
+        config.layout.mode = LayoutMode::User;
+        let layout = Layout::user(&config, &xdg(home));
+
+        assert_eq!(
+            layout.path(crate::world::WORLD_FILE),
+            home.join(".local/state/buckos/world")
+        );
+        assert_eq!(
+            layout.path("etc/buckos/repos.conf"),
+            home.join(".config/buckos/repos.conf")
+        );
+        assert_eq!(
+            layout.path("/usr/bin/jq"),
+            home.join(".local/buckos/usr/bin/jq")
+        );
+
+        layout.apply(&mut config);
+        assert_eq!(config.root, home.join(".local/buckos"));
+        assert_eq!(layout.host_db, Some(PathBuf::from(HOST_DB)));
+        assert_eq!(config.db_path, home.join(".local/share/buckos/db"));
+        assert_eq!(config.cache_dir, home.join(".cache/buckos"));
+        assert_eq!(
+            config.repositories[0].location,
+            home.join(".local/share/buckos/repos/buckos-build")
+        );
+        assert_eq!(config.signing.keys_dir, home.join(".config/buckos/keys"));
+
+        // Applying again, or resolving from the applied config, is stable
+        let again = Layout::user(&config, &xdg(home));
+        assert_eq!(again, layout);
+        let before = config.clone();
+        again.apply(&mut config);
+        assert_eq!(config.root, before.root);
+        assert_eq!(config.buck_repo, before.buck_repo);
+        assert_eq!(config.signing.keys_dir, before.signing.keys_dir);
+    }
+
+    #[test]
+    fn test_merged_usr_spellings() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("usr/bin")).unwrap();
+        std::os::unix::fs::symlink("usr/bin", dir.path().join("bin")).unwrap();
+        let config = Config {
+            root: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let layout = Layout::resolve(&config).unwrap();
+        assert!(layout.usr_merged);
+        assert_eq!(layout.spellings("/bin/ls"), vec!["/bin/ls", "/usr/bin/ls"]);
+        assert_eq!(
+            layout.spellings("/usr/lib64/libc.so"),
+            vec!["/usr/lib64/libc.so", "/lib64/libc.so"]
+        );
+        assert_eq!(layout.spellings("/usr/share/doc"), vec!["/usr/share/doc"]);
+
+        let mut config = config;
+        config.layout.usr_merge = UsrMerge::Split;
+        let layout = Layout::resolve(&config).unwrap();
+        assert_eq!(layout.spellings("/bin/ls"), vec!["/bin/ls"]);
+    }
+}
@@ -13,47 +13,92 @@
 //! - **Transaction**: Atomic package operations with rollback support
 //! - **Cache**: Download and build artifact caching
 //! - **Repository**: Package repository management
-
+//!
+//! # Features
+//!
+//! All enabled by default; `--no-default-features` leaves the core
+//! resolve/build/install path.
+//!
+//! - `binary-packages`: the `binary` module and what needs PKGDIR
+//!   (undo, -dbg packages, file previews); implies `signing`
+//! - `signing`: GPG and minisign signing in `security::signing`
+//! - `cross`: the `cross` module
+//! - `sandbox`: the `sandbox` module
+//! - `tui`: dialoguer prompts in the `buckos` binary
+
+#[cfg(feature = "binary-packages")]
 pub mod binary;
 pub mod buck;
 pub mod cache;
 pub mod catalog;
+pub mod checksums;
 pub mod config;
 pub mod config_protect;
+#[cfg(feature = "cross")]
 pub mod cross;
 pub mod db;
+pub mod debuginfod;
+pub mod diagnostics;
 pub mod distfile;
+pub mod docs;
+pub mod eix;
 pub mod error;
 pub mod executor;
 pub mod features;
+pub mod hardware;
+pub mod http;
+pub mod i18n;
+pub mod install_mask;
+pub mod interrupt;
+pub mod layout;
+pub mod live;
+pub mod manifest;
 pub mod mask;
+pub mod mirror;
 pub mod news;
+pub mod notify;
 pub mod overlay;
+pub mod patches;
+pub mod peer;
+pub mod periodic;
+pub mod pkgmove;
+pub mod plugin;
 pub mod preserved_libs;
 pub mod profile;
 pub mod repository;
 pub mod resolver;
+#[cfg(feature = "sandbox")]
 pub mod sandbox;
 pub mod security;
+pub mod theme;
 pub mod transaction;
 pub mod types;
+pub mod use_explain;
 pub mod validation;
 pub mod r#virtual;
+pub mod workspace;
+pub mod world;
 
 pub use buck::{BuckConfigFile, BuckConfigOptions, BuckConfigSection};
 pub use config::Config;
 pub use error::{Error, Result};
+pub use layout::{Layout, LayoutMode};
 pub use types::*;
 
+/// Boot manifest checked by `buckos-verify-root` in the initramfs
+pub const BOOT_MANIFEST: &str = "/etc/buckos/boot-manifest";
+
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tracing::info;
+use tracing::{info, warn};
 
 /// Main package manager instance
 pub struct PackageManager {
     /// Configuration
     config: config::Config,
+    /// Where configuration, state and installed files live
+    layout: layout::Layout,
     /// Package database
     db: Arc<RwLock<db::PackageDb>>,
     /// Build cache
@@ -64,21 +109,70 @@ pub struct PackageManager {
     buck: Arc<buck::BuckIntegration>,
     /// Parallel executor
     executor: Arc<executor::ParallelExecutor>,
+    /// Loaded plugins
+    plugins: Arc<plugin::PluginManager>,
 }
 
 impl PackageManager {
     /// Create a new package manager instance
-    pub async fn new(config: config::Config) -> Result<Self> {
+    pub async fn new(mut config: config::Config) -> Result<Self> {
         info!("Initializing Buckos package manager");
 
+        let layout = layout::Layout::resolve(&config)?;
+        layout.apply(&mut config);
+        if layout.mode == layout::LayoutMode::User {
+            if let Err(e) = layout.write_env_script() {
+                warn!("Failed to write the prefix environment script: {}", e);
+            }
+        }
+
         // Initialize database
         let db_path = config.db_path.clone();
         let db = db::PackageDb::open(&db_path)?;
+        let vdb = db::Vdb::new(&db_path);
+        // Checking reads the whole database, so it only runs here after a
+        // transaction was cut short; 'buckos db check' runs it on demand
+        let check = if db.was_unclean() {
+            warn!("The last package transaction did not finish; checking the package database");
+            Some(db.check_integrity(&vdb, false))
+        } else {
+            None
+        };
+        match check {
+            None => {}
+            Some(Ok(problems)) => {
+                // Packages merged before records were kept get one now
+                match db.write_missing_records(&vdb, &problems) {
+                    Ok(written) if !written.is_empty() => {
+                        info!("Wrote {} missing package records", written.len())
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("Failed to write package records: {}", e),
+                }
+                let damaged = problems
+                    .iter()
+                    .filter(|p| !matches!(p, db::IntegrityProblem::MissingRecord { .. }))
+                    .count();
+                if damaged > 0 {
+                    warn!(
+                        "The package database has {} problem(s); see 'buckos db check' and repair with 'buckos db repair'",
+                        damaged
+                    );
+                } else if let Err(e) = db.clear_unclean() {
+                    warn!("Failed to clear the unclean shutdown marker: {}", e);
+                }
+            }
+            Some(Err(e)) => warn!("Failed to check the package database: {}", e),
+        }
         #[allow(clippy::arc_with_non_send_sync)]
         let db = Arc::new(RwLock::new(db));
 
         // Initialize cache
-        let cache = cache::PackageCache::new(&config.cache_dir)?;
+        let mut cache =
+            cache::PackageCache::new(&config.cache_dir)?.with_fetch_config(config.fetch.clone())?;
+        if config.peer_distfiles {
+            cache = cache.with_peers(peer::PeerCache::new()?);
+        }
         let cache = Arc::new(cache);
 
         // Initialize repository manager
@@ -93,24 +187,227 @@ impl PackageManager {
         let executor = executor::ParallelExecutor::new(config.parallelism);
         let executor = Arc::new(executor);
 
+        let plugins = Arc::new(plugin::PluginManager::discover(&config.plugin_dir));
+
         Ok(Self {
             config,
+            layout,
             db,
             cache,
             repos,
             buck,
             executor,
+            plugins,
         })
     }
 
+    /// Configuration this package manager was created with
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Where this package manager keeps configuration, state and
+    /// installed files
+    pub fn layout(&self) -> &layout::Layout {
+        &self.layout
+    }
+
+    /// Loaded plugins
+    pub fn plugins(&self) -> &plugin::PluginManager {
+        &self.plugins
+    }
+
+    /// Recorded transactions matching a filter, oldest first
+    pub async fn history(&self, filter: &db::HistoryFilter) -> Result<Vec<db::HistoryEntry>> {
+        self.db.read().await.history(filter)
+    }
+
+    /// A recorded transaction by id
+    pub async fn history_entry(&self, id: i64) -> Result<Option<db::HistoryEntry>> {
+        self.db.read().await.history_entry(id)
+    }
+
+    /// Id of the last transaction recorded at or before a time
+    pub async fn history_id_at(&self, time: chrono::DateTime<chrono::Utc>) -> Result<Option<i64>> {
+        self.db.read().await.history_id_at(time)
+    }
+
+    /// Plan reverting a recorded transaction, by default the last one that
+    /// changed anything
+    #[cfg(feature = "binary-packages")]
+    pub async fn plan_undo(&self, id: Option<i64>) -> Result<transaction::UndoPlan> {
+        let db = self.db.read().await;
+        let entry = match id {
+            Some(id) => db
+                .history_entry(id)?
+                .ok_or_else(|| Error::Other(format!("no transaction {} in history", id)))?,
+            None => db
+                .history(&db::HistoryFilter::default())?
+                .into_iter()
+                .rev()
+                .find(|e| e.success && !e.changes.is_empty())
+                .ok_or_else(|| Error::Other("no transaction to undo".to_string()))?,
+        };
+        if !entry.success {
+            return Err(Error::Other(format!(
+                "transaction {} was rolled back; nothing to undo",
+                entry.id
+            )));
+        }
+        let installed = db.get_all_installed()?;
+        drop(db);
+
+        let pkgdir = self.config.packages_dir();
+        let index = binary::BinaryPackageIndex::load(&pkgdir)?;
+        Ok(transaction::UndoPlan::new(
+            entry, &installed, &index, &pkgdir,
+        ))
+    }
+
+    /// Revert a transaction using cached binary packages
+    ///
+    /// Every binary package is verified before anything is changed, and the
+    /// reversal runs as a single transaction of its own.
+    #[cfg(feature = "binary-packages")]
+    pub async fn undo(&self, plan: &transaction::UndoPlan) -> Result<()> {
+        if !plan.is_possible() {
+            let problems: Vec<String> = plan.problems.iter().map(|p| p.to_string()).collect();
+            return Err(Error::Other(format!(
+                "cannot undo transaction {}: {}",
+                plan.entry.id,
+                problems.join("; ")
+            )));
+        }
+
+        let binpkgs = binary::BinaryPackageManager::new(self.config.packages_dir())?
+            .with_signing_manager(security::SigningManager::from_config(&self.config.signing)?);
+        let staging = tempfile::tempdir()?;
+        let mut transaction = self.new_transaction();
+
+        for action in &plan.actions {
+            let previous = match action {
+                transaction::UndoAction::Remove(pkg) => {
+                    transaction.add_remove(pkg.clone());
+                    continue;
+                }
+                transaction::UndoAction::Install(previous) => previous,
+                transaction::UndoAction::Replace { previous, .. } => previous,
+            };
+
+            let verification = binpkgs.verify_package(previous)?;
+            if !verification.valid {
+                return Err(Error::Other(format!(
+                    "cannot undo transaction {}: {}: {}",
+                    plan.entry.id, previous.path, verification.message
+                )));
+            }
+            let staged = staging
+                .path()
+                .join(format!("{}-{}", previous.id.name, previous.version));
+            binpkgs.extract_package(previous, &staged).await?;
+
+            let pkg = transaction::binpkg_package_info(previous);
+            transaction.use_prebuilt(&pkg, staged);
+            match action {
+                transaction::UndoAction::Replace { current, .. } => {
+                    transaction.add_upgrade(current.clone(), pkg)
+                }
+                _ => transaction.add_install(pkg),
+            }
+        }
+
+        transaction.execute(&self.executor).await
+    }
+
+    /// User patches an installed package was built with
+    pub async fn package_patches(&self, name: &str) -> Result<Vec<patches::AppliedPatch>> {
+        self.db.read().await.get_package_patches(name)
+    }
+
+    /// Reverse-dependency closure of an installed package, with rebuild
+    /// time estimates from recorded build durations
+    pub async fn impact(&self, package: &str) -> Result<resolver::ImpactReport> {
+        let db = self.db.read().await;
+        let installed = db.get_all_installed()?;
+        let target = installed
+            .iter()
+            .find(|p| p.name == package || p.id.full_name() == package)
+            .ok_or_else(|| Error::PackageNotFound(package.to_string()))?;
+        Ok(resolver::ImpactReport::compute(
+            target,
+            &installed,
+            &db.reverse_dependency_map()?,
+            &db.build_duration_estimates()?,
+        ))
+    }
+
+    /// Compiler diagnostics recorded for each built version of a package
+    pub async fn build_reports(&self, package: &str) -> Result<Vec<diagnostics::BuildReport>> {
+        let id = match PackageId::parse(package) {
+            Some(id) => id,
+            None => match self.db.read().await.get_installed(package)? {
+                Some(installed) => installed.id,
+                None => self
+                    .repos
+                    .get_info(package)
+                    .await?
+                    .map(|info| info.id)
+                    .ok_or_else(|| Error::PackageNotFound(package.to_string()))?,
+            },
+        };
+        self.report_store().load(&id)
+    }
+
+    /// Build reports of every package, newest build first
+    pub fn recent_builds(&self) -> Result<Vec<diagnostics::BuildReport>> {
+        self.report_store().all()
+    }
+
+    /// The stored log of the build a report was made from
+    pub fn build_log(&self, report: &diagnostics::BuildReport) -> Result<Option<String>> {
+        self.report_store()
+            .load_log(&report.package, &report.version)
+    }
+
+    /// Build report store, keeping logs compressed as configured
+    fn report_store(&self) -> diagnostics::ReportStore {
+        let compression = self
+            .config
+            .compression
+            .log_compression()
+            .unwrap_or_else(|e| {
+                warn!("{}; storing build logs with zstd", e);
+                Default::default()
+            });
+        diagnostics::ReportStore::new(self.config.build_reports_dir()).with_logs(compression)
+    }
+
+    /// Packages whose builds were retried or failed, from recorded attempts
+    pub async fn build_flakiness(&self) -> Result<Vec<db::BuildFlakiness>> {
+        self.db.read().await.build_flakiness()
+    }
+
+    /// Configured distfile mirrors with their health, in fetch order
+    pub fn mirror_health(&self) -> Vec<(String, checksums::MirrorHealth)> {
+        self.cache.mirror_health()
+    }
+
+    /// Spot-check every mirror against the checksum database
+    pub async fn audit_mirrors(&self, sample_size: usize) -> Result<Vec<checksums::MirrorAudit>> {
+        self.cache.audit_mirrors(sample_size).await
+    }
+
     /// Install packages
     pub async fn install(&self, packages: &[String], opts: InstallOptions) -> Result<()> {
         info!("Installing packages: {:?}", packages);
 
         // Resolve dependencies
-        let resolver = resolver::DependencyResolver::new(self.db.clone(), self.repos.clone());
-
-        let resolution = resolver.resolve(packages, &opts).await?;
+        let layers = self.use_layers()?.with_cli(&opts.use_flags);
+        let resolution = self
+            .resolver(layers)
+            .await?
+            .resolve(packages, &opts)
+            .await?;
 
         if resolution.packages.is_empty() {
             info!("All packages are already installed");
@@ -135,12 +432,7 @@ impl PackageManager {
         }
 
         // Create transaction
-        let mut transaction = transaction::Transaction::new(
-            self.db.clone(),
-            self.cache.clone(),
-            self.buck.clone(),
-            self.config.root.clone(),
-        );
+        let mut transaction = self.new_transaction();
 
         // Add install operations
         for pkg in &resolution.packages {
@@ -148,7 +440,12 @@ impl PackageManager {
         }
 
         // Execute transaction
-        transaction.execute(&self.executor).await?;
+        let result = transaction.execute(&self.executor).await;
+        self.save_resume_state(&result, || transaction::ResumeCommand::Install {
+            packages: packages.to_vec(),
+            opts: opts.clone(),
+        });
+        result?;
 
         // Add to world set if not oneshot
         if !opts.oneshot {
@@ -203,12 +500,7 @@ impl PackageManager {
         }
 
         // Create transaction
-        let mut transaction = transaction::Transaction::new(
-            self.db.clone(),
-            self.cache.clone(),
-            self.buck.clone(),
-            self.config.root.clone(),
-        );
+        let mut transaction = self.new_transaction();
 
         // Add remove operations
         for pkg in to_remove {
@@ -216,7 +508,12 @@ impl PackageManager {
         }
 
         // Execute transaction
-        transaction.execute(&self.executor).await?;
+        let result = transaction.execute(&self.executor).await;
+        self.save_resume_state(&result, || transaction::ResumeCommand::Remove {
+            packages: packages.to_vec(),
+            opts: opts.clone(),
+        });
+        result?;
 
         info!("Successfully removed {} packages", packages.len());
         Ok(())
@@ -229,6 +526,40 @@ impl PackageManager {
             self.sync().await?;
         }
 
+        let updates = self.available_updates(packages, opts.live).await?;
+        if updates.is_empty() {
+            info!("All packages are up to date");
+            return Ok(());
+        }
+
+        info!("Found {} updates", updates.len());
+
+        // Create transaction
+        let mut transaction = self.new_transaction();
+
+        // Add upgrade operations
+        for (old, new) in updates {
+            transaction.add_upgrade(old, new);
+        }
+
+        // Execute transaction
+        let result = transaction.execute(&self.executor).await;
+        self.save_resume_state(&result, || transaction::ResumeCommand::Update {
+            packages: packages.map(<[String]>::to_vec),
+            opts: opts.clone(),
+        });
+        result?;
+
+        Ok(())
+    }
+
+    /// Installed packages with a newer version in the repositories, or with
+    /// `live`, live packages whose upstream commit changed
+    pub async fn available_updates(
+        &self,
+        packages: Option<&[String]>,
+        live: bool,
+    ) -> Result<Vec<(InstalledPackage, PackageInfo)>> {
         let db = self.db.read().await;
 
         // Get packages to update
@@ -250,47 +581,214 @@ impl PackageManager {
         let mut updates = Vec::new();
         for pkg in to_check {
             if let Some(available) = self.repos.get_latest(&pkg.name).await? {
-                if available.version > pkg.version {
+                let live_rebuild = live
+                    && available.version == pkg.version
+                    && self
+                        .check_live_package(&pkg, &available)
+                        .await?
+                        .is_some_and(|check| check.needs_rebuild());
+                if available.version > pkg.version || live_rebuild {
                     updates.push((pkg, available));
                 }
             }
         }
+        Ok(updates)
+    }
 
-        if updates.is_empty() {
-            info!("All packages are up to date");
-            return Ok(());
+    /// Sync package repositories
+    pub async fn sync(&self) -> Result<()> {
+        info!("Syncing package repositories");
+        self.repos.sync_all().await?;
+        #[cfg(feature = "signing")]
+        {
+            self.update_repository_keys()?;
+            self.verify_signature_thresholds()?;
         }
+        self.apply_package_moves().await?;
+        self.refresh_eix_cache().await;
+        Ok(())
+    }
 
-        info!("Found {} updates", updates.len());
+    /// Follow each repository's key rotation manifest and verify its signed
+    /// metadata, refusing signatures by revoked or unknown keys
+    ///
+    /// Only repositories with anchor keys in `signing.repository_keys` are
+    /// checked; their key sets are kept in the database directory.
+    #[cfg(feature = "signing")]
+    pub fn update_repository_keys(&self) -> Result<Vec<security::rotation::RepoKeys>> {
+        use security::rotation::{KeyRotation, RepoKeys};
+
+        let mut all = Vec::new();
+        for repo in &self.config.repositories {
+            let Some(anchors) = self.config.signing.repository_keys.get(&repo.name) else {
+                continue;
+            };
+            let manager = self.repo_signing_manager(&repo.name)?;
+            let mut keys = RepoKeys::load(&self.config.db_path, &repo.name)?
+                .unwrap_or_else(|| RepoKeys::new(&repo.name, anchors));
+
+            // Statements applied before a bad one are kept
+            let changes = keys.apply(&KeyRotation::load(&repo.location)?, &manager);
+            keys.save(&self.config.db_path)?;
+            for change in changes? {
+                info!(
+                    "Repository {}: {} key {}",
+                    repo.name, change.action, change.key_id
+                );
+            }
 
-        // Create transaction
-        let mut transaction = transaction::Transaction::new(
-            self.db.clone(),
-            self.cache.clone(),
-            self.buck.clone(),
-            self.config.root.clone(),
-        );
+            keys.verify_repository(&manager, &repo.location)?;
+            all.push(keys);
+        }
+        Ok(all)
+    }
 
-        // Add upgrade operations
-        for (old, new) in updates {
-            transaction.add_upgrade(old, new);
+    /// Require the k-of-n signatures repos.conf asks of each repository's
+    /// metadata, refusing repositories short of them
+    #[cfg(feature = "signing")]
+    pub fn verify_signature_thresholds(
+        &self,
+    ) -> Result<Vec<(String, security::threshold::ThresholdVerification)>> {
+        use security::threshold::{enforce_threshold, ThresholdPolicy, REPOS_CONF};
+
+        let policies = ThresholdPolicy::load_all(&self.layout.path(REPOS_CONF))?;
+        let mut results = Vec::new();
+        for repo in &self.config.repositories {
+            let Some(policy) = policies.get(&repo.name) else {
+                continue;
+            };
+            let manager = self.repo_signing_manager(&repo.name)?;
+            let keys = security::rotation::RepoKeys::load(&self.config.db_path, &repo.name)?;
+            let result =
+                enforce_threshold(&manager, &repo.name, &repo.location, policy, keys.as_ref())?;
+            info!(
+                "Repository {} signed by {} of {} required keys: {}",
+                repo.name,
+                result.signed.len(),
+                result.threshold,
+                result.signed.join(", ")
+            );
+            results.push((repo.name.clone(), result));
         }
+        Ok(results)
+    }
 
-        // Execute transaction
-        transaction.execute(&self.executor).await?;
+    /// Signing manager using the backend configured for `repo`
+    #[cfg(feature = "signing")]
+    pub fn repo_signing_manager(&self, repo: &str) -> Result<security::SigningManager> {
+        Ok(security::SigningManager::from_config(&self.config.signing)?
+            .with_backend(self.config.signing.backend_for(repo)))
+    }
 
-        Ok(())
+    /// Regenerate the eix query cache from repository metadata and the
+    /// installed packages, returning the number of packages cached
+    pub async fn update_eix_cache(&self) -> Result<usize> {
+        // Only the newest version of each package contributes more than
+        // its version
+        let headers = self.repos.headers().await?;
+        let newest = self.repos.details_of(&eix::newest(&headers)).await?;
+        let installed = self.db.read().await.get_all_installed()?;
+        let cache = eix::EixCache::build(&headers, &newest, &installed);
+        cache.write(&self.config.eix_cache_path())?;
+        Ok(cache.entries.len())
     }
 
-    /// Sync package repositories
-    pub async fn sync(&self) -> Result<()> {
-        info!("Syncing package repositories");
-        self.repos.sync_all().await?;
-        Ok(())
+    /// Load the eix query cache, generating it on first use
+    pub async fn eix_cache(&self) -> Result<eix::EixCache> {
+        if let Some(cache) = eix::EixCache::read(&self.config.eix_cache_path())? {
+            return Ok(cache);
+        }
+        self.update_eix_cache().await?;
+        eix::EixCache::read(&self.config.eix_cache_path())?
+            .ok_or_else(|| Error::Other("eix cache was not written".to_string()))
+    }
+
+    async fn refresh_eix_cache(&self) {
+        if let Err(e) = self.update_eix_cache().await {
+            warn!("Failed to update eix cache: {}", e);
+        }
+    }
+
+    /// Apply package moves shipped by repositories
+    ///
+    /// Renames installed packages, world entries and custom set entries.
+    /// Moves where both the old and new name are installed are skipped and
+    /// reported as conflicts.
+    pub async fn apply_package_moves(&self) -> Result<pkgmove::MoveReport> {
+        let mut moves = Vec::new();
+        for repo in &self.config.repositories {
+            moves.extend(pkgmove::load_repo_moves(&repo.location)?);
+        }
+
+        let mut report = pkgmove::MoveReport::default();
+        if moves.is_empty() {
+            return Ok(report);
+        }
+
+        let world_file = self.layout.path(world::WORLD_FILE);
+        let sets_dir = self.layout.path("etc/buckos/sets");
+        let set_files: Vec<PathBuf> = std::fs::read_dir(&sets_dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.path())
+                    .filter(|p| p.is_file())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut db = self.db.write().await;
+        for package_move in moves {
+            // Re-check each move so chained renames (a -> b -> c) see earlier ones
+            let installed = db.get_all_installed()?;
+            if !pkgmove::find_conflicts(std::slice::from_ref(&package_move), &installed).is_empty()
+            {
+                warn!(
+                    "Skipping package move {} -> {}: both are installed",
+                    package_move.from, package_move.to
+                );
+                report.conflicts.push(package_move);
+                continue;
+            }
+
+            let installed = db.rename_package(&package_move.from, &package_move.to)?;
+            if installed {
+                self.vdb().rename(&package_move.from, &package_move.to)?;
+            }
+            let world = pkgmove::rename_in_file(&world_file, &package_move)?;
+            let mut sets = Vec::new();
+            for set_file in &set_files {
+                if pkgmove::rename_in_file(set_file, &package_move)? {
+                    sets.push(
+                        set_file
+                            .file_name()
+                            .map(|n| n.to_string_lossy().to_string())
+                            .unwrap_or_default(),
+                    );
+                }
+            }
+
+            if installed || world || !sets.is_empty() {
+                info!(
+                    "Applied package move {} -> {}",
+                    package_move.from, package_move.to
+                );
+                report.applied.push(pkgmove::AppliedMove {
+                    package_move,
+                    installed,
+                    world,
+                    sets,
+                });
+            }
+        }
+        drop(db);
+
+        pkgmove::log_applied(&self.config.db_path, &report.applied)?;
+        Ok(report)
     }
 
     /// Search for packages
-    pub async fn search(&self, query: &str) -> Result<Vec<PackageInfo>> {
+    pub async fn search(&self, query: &str) -> Result<Vec<PackageHeader>> {
         self.repos.search(query).await
     }
 
@@ -299,6 +797,35 @@ impl PackageManager {
         self.repos.get_info(package).await
     }
 
+    /// Get package information with every available version and the
+    /// installed one
+    pub async fn package_details(&self, package: &str) -> Result<Option<PackageDetails>> {
+        let versions = self.repos.get_versions(package).await?;
+        let Some(pkg) = self.info(package).await? else {
+            return Ok(None);
+        };
+        let installed = self
+            .list_installed()
+            .await?
+            .into_iter()
+            .find(|p| p.id == pkg.id)
+            .map(|p| p.version);
+
+        Ok(Some(PackageDetails {
+            package: pkg,
+            installed,
+            versions: versions
+                .into_iter()
+                .map(|(repository, p)| RepoVersion {
+                    repository,
+                    version: p.version,
+                    slot: p.slot,
+                    keywords: p.keywords,
+                })
+                .collect(),
+        }))
+    }
+
     /// List installed packages
     pub async fn list_installed(&self) -> Result<Vec<InstalledPackage>> {
         let db = self.db.read().await;
@@ -326,39 +853,90 @@ impl PackageManager {
 
     /// Verify installed packages
     pub async fn verify(&self) -> Result<Vec<VerifyResult>> {
+        Ok(self.verify_with(&VerifyOptions::default()).await?.results)
+    }
+
+    /// Verify installed packages, re-hashing only files whose metadata
+    /// changed since they were merged unless `opts.paranoid` is set
+    pub async fn verify_with(&self, opts: &VerifyOptions) -> Result<VerifyReport> {
         let db = self.db.read().await;
         let installed = db.get_all_installed()?;
         drop(db);
 
-        let mut results = Vec::new();
+        let mut report = VerifyReport::default();
         for pkg in installed {
-            let result = self.verify_package(&pkg).await?;
-            results.push(result);
+            let result = self.verify_package(&pkg, opts, &mut report).await?;
+            report.results.push(result);
         }
 
-        Ok(results)
+        Ok(report)
     }
 
-    async fn verify_package(&self, pkg: &InstalledPackage) -> Result<VerifyResult> {
+    async fn verify_package(
+        &self,
+        pkg: &InstalledPackage,
+        opts: &VerifyOptions,
+        report: &mut VerifyReport,
+    ) -> Result<VerifyResult> {
         let db = self.db.read().await;
         let files = db.get_package_files(&pkg.name)?;
+        let stats = db.get_file_stats(&pkg.name)?;
+        let digests = db.get_file_verity(&pkg.name)?;
         drop(db);
 
         let mut missing = Vec::new();
         let mut modified = Vec::new();
+        let mut refreshed = Vec::new();
 
         for file in files {
+            // Masked files were never installed
+            if file.file_type == FileType::Masked {
+                continue;
+            }
+
             let path = PathBuf::from(&file.path);
             if !path.exists() {
                 missing.push(file.path.clone());
-            } else if let Some(expected_hash) = &file.blake3_hash {
+                continue;
+            }
+            if let Some(digest) = digests.get(&file.path) {
+                // Lost fs-verity or carries another digest: replaced offline
+                if !security::verity::check(&path, digest) {
+                    modified.push(file.path.clone());
+                    continue;
+                }
+                report.verity += 1;
+                // Every read is checked against the digest by the kernel
+                if !opts.paranoid {
+                    continue;
+                }
+            }
+            if let Some(expected_hash) = &file.blake3_hash {
+                // Taken before hashing, so a later write changes the ctime
+                let stat = db::FileStat::of(&path).ok();
+                let recorded = stats.get(&file.path);
+                if !opts.paranoid && stat.is_some() && recorded == stat.as_ref() {
+                    report.skipped += 1;
+                    continue;
+                }
+                report.hashed += 1;
                 let actual_hash = cache::compute_blake3(&path)?;
                 if &actual_hash != expected_hash {
                     modified.push(file.path.clone());
+                } else if let Some(stat) = stat.filter(|s| recorded != Some(s)) {
+                    // Unchanged contents, e.g. after a chmod or touch
+                    refreshed.push((file.path.clone(), stat));
                 }
             }
         }
 
+        if !refreshed.is_empty() {
+            let db = self.db.write().await;
+            for (path, stat) in &refreshed {
+                db.set_file_stat(path, stat)?;
+            }
+        }
+
         let ok = missing.is_empty() && modified.is_empty();
         Ok(VerifyResult {
             package: pkg.name.clone(),
@@ -368,6 +946,260 @@ impl PackageManager {
         })
     }
 
+    /// fs-verity coverage of each installed package
+    pub async fn verity_status(&self) -> Result<Vec<security::verity::VerityCoverage>> {
+        let db = self.db.read().await;
+        let mut coverage = Vec::new();
+        for pkg in db.get_all_installed()? {
+            coverage.push(security::verity::package_coverage(
+                &db,
+                &self.config.root,
+                &self.config.verity,
+                &pkg.name,
+            )?);
+        }
+        Ok(coverage)
+    }
+
+    /// Enable fs-verity on the files of installed packages (all when
+    /// `packages` is empty), returning how many files are protected
+    ///
+    /// Files are hashed first, so ones already modified are not sealed
+    /// with their modified contents.
+    pub async fn enable_verity(&self, packages: &[String]) -> Result<usize> {
+        let db = self.db.read().await;
+        let mut protected = 0;
+        for pkg in db.get_all_installed()? {
+            if !packages.is_empty() && !packages.iter().any(|p| p == &pkg.name) {
+                continue;
+            }
+            let modified: Vec<String> = pkg
+                .files
+                .iter()
+                .filter(|f| {
+                    f.file_type == FileType::Regular
+                        && self
+                            .config
+                            .verity
+                            .covers(&self.config.root, std::path::Path::new(&f.path))
+                })
+                .filter(|f| match &f.blake3_hash {
+                    Some(hash) => cache::compute_blake3(std::path::Path::new(&f.path))
+                        .map_or(true, |actual| &actual != hash),
+                    None => false,
+                })
+                .map(|f| f.path.clone())
+                .collect();
+            if !modified.is_empty() {
+                warn!(
+                    "Not enabling fs-verity on {}: {} file(s) modified since merge",
+                    pkg.name,
+                    modified.len()
+                );
+                continue;
+            }
+            protected += security::verity::protect_package(
+                &db,
+                &self.config.root,
+                &self.config.verity,
+                &pkg.name,
+            )?;
+            db.record_file_stats(&pkg.name)?;
+        }
+        Ok(protected)
+    }
+
+    /// Detect the hardware again and store it, rewriting the generated
+    /// hardware USE layer if there is one; returns the detection and
+    /// whether the hardware changed
+    pub fn refresh_hardware(&self) -> Result<(hardware::HardwareDetection, bool)> {
+        let detection = hardware::HardwareDetection::detect();
+        let changed = hardware::refresh(
+            &self.config.db_path,
+            &self.layout.path(hardware::HARDWARE_USE_FILE),
+            &detection,
+        )?;
+        Ok((detection, changed))
+    }
+
+    /// USE flag layers of this system: the USE configuration, the
+    /// generated hardware layer and the selected profile's flags
+    pub fn use_layers(&self) -> Result<use_explain::UseLayers> {
+        let mut layers = use_explain::UseLayers::new(&self.config.use_flags).with_hardware(
+            hardware::HardwareUse::load(&self.layout.path(hardware::HARDWARE_USE_FILE))?,
+        );
+        if let Some(profile) = self.selected_profile() {
+            layers = layers.with_profile(&profile);
+        }
+        Ok(layers)
+    }
+
+    /// Where `buckos profile set` records the selected profile
+    pub fn selected_profile_path(&self) -> PathBuf {
+        self.layout.config_dir.join("profile")
+    }
+
+    /// The profile selected with `buckos profile set`, if any
+    pub fn selected_profile(&self) -> Option<profile::ResolvedProfile> {
+        let current = self.selected_profile_path();
+        if !current.exists() {
+            return None;
+        }
+
+        let mut manager =
+            profile::ProfileManager::new(self.config.buck_repo.join("profiles"), current);
+        manager.load().ok()?;
+        manager.current().cloned()
+    }
+
+    /// Plain-text records of installed packages
+    pub fn vdb(&self) -> db::Vdb {
+        db::Vdb::new(&self.config.db_path)
+    }
+
+    /// Create a transaction configured for this system
+    fn new_transaction(&self) -> transaction::Transaction {
+        transaction::Transaction::new(
+            self.db.clone(),
+            self.cache.clone(),
+            self.buck.clone(),
+            self.config.root.clone(),
+        )
+        .with_install_mask(install_mask::InstallMask::from_config(
+            &self.config.install_mask,
+            &self.config.features,
+        ))
+        .with_transforms(transaction::MergeTransforms::from_config(&self.config))
+        .with_qa(transaction::QaPolicy::from_config(&self.config))
+        .with_retry(self.config.build_retry.clone())
+        .with_pressure(transaction::PressureThrottle::new(
+            self.config.build_pressure.clone(),
+        ))
+        .with_verity(self.config.verity.clone())
+        .with_user_patches(self.layout.path(patches::USER_PATCH_DIR))
+        .with_live_pins(self.config.live_pins.clone())
+        .with_plugins(self.plugins.clone())
+        .with_vdb(self.vdb())
+        .with_repositories(self.repos.clone())
+        .with_audit_syslog(self.config.audit_syslog)
+        .with_build_reports(self.report_store())
+    }
+
+    /// Create -dbg binary packages from installed split debug info
+    ///
+    /// Packages without split debug info are skipped.
+    #[cfg(feature = "binary-packages")]
+    pub async fn create_debug_packages(
+        &self,
+        packages: &[String],
+    ) -> Result<Vec<binary::BinaryPackage>> {
+        let db = self.db.read().await;
+        let mut installed = Vec::new();
+        for name in packages {
+            let pkg = db
+                .get_installed(name)?
+                .ok_or_else(|| Error::PackageNotInstalled(name.clone()))?;
+            installed.push(pkg);
+        }
+        drop(db);
+
+        let mut manager = binary::BinaryPackageManager::new(self.config.packages_dir())?
+            .with_signing_manager(security::SigningManager::from_config(&self.config.signing)?);
+        let opts = binary::BinaryPackageOptions {
+            compression: self.config.compression.binpkg_compression()?,
+            ..Default::default()
+        };
+        let mut created = Vec::new();
+        for pkg in &installed {
+            if let Some(binpkg) = manager
+                .create_debug_package(pkg, &self.config.root, &opts)
+                .await?
+            {
+                created.push(binpkg);
+            }
+        }
+
+        Ok(created)
+    }
+
+    /// Pack an installed package into a relocatable binary archive in
+    /// `dest_dir`, as its files are now
+    #[cfg(feature = "binary-packages")]
+    pub async fn export_binary(
+        &self,
+        name: &str,
+        dest_dir: &std::path::Path,
+    ) -> Result<binary::ExportedArchive> {
+        let record = self
+            .db
+            .read()
+            .await
+            .package_record(name)?
+            .ok_or_else(|| Error::PackageNotInstalled(name.to_string()))?;
+        let root = self.config.root.clone();
+        let dest_dir = dest_dir.to_path_buf();
+        let compression = self.config.compression.binpkg_compression()?;
+        tokio::task::spawn_blocking(move || {
+            binary::archive::write(&record, &root, &dest_dir, compression)
+        })
+        .await
+        .map_err(|e| Error::Other(e.to_string()))?
+    }
+
+    /// Install a package from a binary archive without building it
+    ///
+    /// The archive is checked in full before anything is changed, and
+    /// replaces an installed package in the same slot. Fails when a runtime
+    /// dependency the archive records is not installed.
+    #[cfg(feature = "binary-packages")]
+    pub async fn import_binary(
+        &self,
+        archive: &std::path::Path,
+    ) -> Result<binary::ArchiveMetadata> {
+        let staging = tempfile::tempdir()?;
+        let staged = staging.path().join("image");
+        let metadata = {
+            let (archive, staged) = (archive.to_path_buf(), staged.clone());
+            tokio::task::spawn_blocking(move || binary::archive::unpack(&archive, &staged))
+                .await
+                .map_err(|e| Error::Other(e.to_string()))??
+        };
+        if metadata.package.arch != std::env::consts::ARCH {
+            return Err(Error::Other(format!(
+                "{} was built for {}, not {}",
+                archive.display(),
+                metadata.package.arch,
+                std::env::consts::ARCH
+            )));
+        }
+
+        // Importing installs nothing else, so what it needs at run time
+        // must be installed already
+        let installed = self.db.read().await.get_all_installed()?;
+        let missing = metadata.missing_dependencies(&installed);
+        if !missing.is_empty() {
+            return Err(Error::Other(format!(
+                "{} needs {} installed first",
+                archive.display(),
+                missing.join(", ")
+            )));
+        }
+
+        let pkg = metadata.package_info();
+        let installed = installed
+            .into_iter()
+            .find(|p| p.id == pkg.id && p.slot == pkg.slot);
+        let mut transaction = self.new_transaction();
+        transaction.use_prebuilt(&pkg, staged);
+        match installed {
+            Some(old) => transaction.add_upgrade(old, pkg),
+            None => transaction.add_install(pkg),
+        }
+        transaction.execute(&self.executor).await?;
+
+        Ok(metadata)
+    }
+
     /// Resolve packages without installing (for pretend mode)
     pub async fn resolve_packages(
         &self,
@@ -376,9 +1208,8 @@ impl PackageManager {
     ) -> Result<Resolution> {
         info!("Resolving packages: {:?}", packages);
 
-        let resolver = resolver::DependencyResolver::new(self.db.clone(), self.repos.clone());
-
-        let resolution = resolver.resolve(packages, opts).await?;
+        let layers = self.use_layers()?.with_cli(&opts.use_flags);
+        let resolution = self.resolver(layers).await?.resolve(packages, opts).await?;
 
         // Convert to ResolvedPackage format
         let db = self.db.read().await;
@@ -432,9 +1263,172 @@ impl PackageManager {
             build_order: resolution.build_order,
             download_size: resolution.download_size,
             install_size: resolution.install_size,
+            any_of_choices: resolution.any_of_choices,
+            from_host: resolution.from_host,
+            stats: resolution.stats,
         })
     }
 
+    /// Resolve a plan from `install --plan-out` again, checking that it
+    /// still holds
+    ///
+    /// Fails when the repositories or configuration differ from those the
+    /// plan was made against, or when the fresh resolution is not the
+    /// planned one (e.g. because installed packages changed meanwhile).
+    pub async fn check_plan(&self, plan: &resolver::ResolutionPlan) -> Result<Resolution> {
+        let mismatches = plan.check(&self.config)?;
+        if !mismatches.is_empty() {
+            let reasons: Vec<String> = mismatches.iter().map(|m| m.to_string()).collect();
+            return Err(Error::ResolutionFailed(format!(
+                "plan {} no longer applies: {}",
+                plan.short_hash(),
+                reasons.join("; ")
+            )));
+        }
+
+        let resolution = self
+            .resolve_packages(&plan.requested, &plan.options)
+            .await?;
+        if resolution.plan_hash() != plan.plan_hash {
+            return Err(Error::ResolutionFailed(format!(
+                "plan {} no longer applies: resolving again gives plan {}",
+                plan.short_hash(),
+                &resolution.plan_hash()[..16]
+            )));
+        }
+        Ok(resolution)
+    }
+
+    /// Estimate how long building a resolution will take from recorded
+    /// build durations
+    pub async fn estimate_build_time(
+        &self,
+        resolution: &Resolution,
+    ) -> Result<transaction::TransactionEstimate> {
+        let db = self.db.read().await;
+        let mut packages = Vec::new();
+        for pkg in &resolution.packages {
+            let version = pkg.version.to_string();
+            let use_hash = transaction::use_hash(
+                pkg.use_flags
+                    .iter()
+                    .filter(|f| f.enabled)
+                    .map(|f| f.name.as_str()),
+            );
+            packages.push(transaction::BuildEstimate {
+                estimate: db.build_duration_estimate(&pkg.id, &version, &use_hash)?,
+                package: pkg.id.clone(),
+                version,
+            });
+        }
+        Ok(transaction::TransactionEstimate { packages })
+    }
+
+    /// Compute the filesystem-level effect of a resolution without applying it
+    ///
+    /// File lists for new versions come from binary package manifests in
+    /// PKGDIR. Packages without a binary package are reported as having no
+    /// manifest available.
+    pub async fn preview_resolution(
+        &self,
+        resolution: &Resolution,
+    ) -> Result<transaction::TransactionPreview> {
+        #[cfg(feature = "binary-packages")]
+        let index = binary::BinaryPackageIndex::load(&self.config.packages_dir())?;
+
+        let db = self.db.read().await;
+        let installed = db.get_all_installed()?;
+        drop(db);
+
+        let mut builder = self.preview_builder(&installed).with_install_mask(
+            install_mask::InstallMask::from_config(
+                &self.config.install_mask,
+                &self.config.features,
+            ),
+        );
+        for idx in &resolution.build_order {
+            let Some(pkg) = resolution.packages.get(*idx) else {
+                continue;
+            };
+            let old = installed
+                .iter()
+                .find(|p| p.id == pkg.id && p.slot == pkg.slot);
+            #[cfg(feature = "binary-packages")]
+            let manifest = index
+                .find_version(&pkg.id, &pkg.version)
+                .filter(|b| !b.files.is_empty())
+                .map(|b| b.files.as_slice());
+            #[cfg(not(feature = "binary-packages"))]
+            let manifest = None;
+            builder.add_install(&pkg.id, &pkg.version, old, manifest);
+        }
+
+        Ok(builder.build())
+    }
+
+    /// Compute the filesystem-level effect of removing `packages`
+    pub async fn preview_removal(
+        &self,
+        packages: &[InstalledPackage],
+    ) -> Result<transaction::TransactionPreview> {
+        let installed = self.db.read().await.get_all_installed()?;
+        let mut builder = self.preview_builder(&installed);
+        for pkg in packages {
+            builder.add_remove(pkg);
+        }
+        Ok(builder.build())
+    }
+
+    fn preview_builder(&self, installed: &[InstalledPackage]) -> transaction::PreviewBuilder {
+        transaction::PreviewBuilder::new(&self.config.root, installed).with_protect(
+            config_protect::ConfigProtect::new(self.config.config_protect.clone()),
+        )
+    }
+
+    /// System packages a per-user prefix may use
+    fn host_packages(&self) -> Result<Option<resolver::HostPackages>> {
+        match &self.layout.host_db {
+            Some(db_path) => resolver::HostPackages::load(db_path),
+            None => Ok(None),
+        }
+    }
+
+    /// Resolver with the configured policy, constraints, host packages and
+    /// time limit, following USE-conditional dependencies as `layers` set
+    /// them, and stopping at Ctrl-C until it is dropped
+    async fn resolver(
+        &self,
+        layers: use_explain::UseLayers,
+    ) -> Result<resolver::DependencyResolver> {
+        // Constraints come from plugin executables, run off the runtime
+        let plugins = self.plugins.clone();
+        let constraints = tokio::task::spawn_blocking(move || plugins.constraints())
+            .await
+            .map_err(|e| Error::Other(e.to_string()))??;
+        let budget = resolver::ResolveBudget::from_config(&self.config.resolver).catch_interrupts();
+        let mut resolver = resolver::DependencyResolver::new(self.db.clone(), self.repos.clone())
+            .with_any_of_policy(self.any_of_policy())
+            .with_use_layers(layers)
+            .with_constraints(constraints)
+            .with_budget(budget);
+        if let Some(host) = self.host_packages()? {
+            resolver = resolver.with_host(host);
+        }
+        Ok(resolver)
+    }
+
+    /// Build the any-of preference policy from configuration
+    fn any_of_policy(&self) -> resolver::AnyOfPolicy {
+        resolver::AnyOfPolicy::new()
+            .with_weights(self.config.any_of_weights)
+            .with_preferred(
+                self.config
+                    .any_of_preferred
+                    .iter()
+                    .filter_map(|s| PackageId::parse(s)),
+            )
+    }
+
     /// Get the world set (explicitly installed packages)
     pub async fn get_world_set(&self) -> Result<WorldSet> {
         let db = self.db.read().await;
@@ -568,7 +1562,13 @@ impl PackageManager {
         for pkg in to_check {
             if let Some(available) = self.repos.get_latest(&pkg.name).await? {
                 let needs_update = available.version > pkg.version;
-                let needs_rebuild = opts.newuse && self.has_use_changes(&pkg, &available).await;
+                let mut needs_rebuild = opts.newuse && self.has_use_changes(&pkg, &available).await;
+                if opts.live && !needs_update && !needs_rebuild {
+                    needs_rebuild = self
+                        .check_live_package(&pkg, &available)
+                        .await?
+                        .is_some_and(|check| check.needs_rebuild());
+                }
 
                 if needs_update || needs_rebuild {
                     let use_flags: Vec<UseFlagStatus> = available
@@ -606,9 +1606,64 @@ impl PackageManager {
             packages: resolved_packages,
             download_size,
             install_size,
+            any_of_choices: Vec::new(),
+            from_host: Vec::new(),
+            stats: Default::default(),
         })
     }
 
+    /// Compare installed live packages against the commits they should be
+    /// built from
+    ///
+    /// Checks `packages` by name, or every installed package when None.
+    /// Non-live packages are skipped.
+    pub async fn check_live(&self, packages: Option<&[String]>) -> Result<Vec<live::LiveCheck>> {
+        let installed = {
+            let db = self.db.read().await;
+            match packages {
+                Some(names) => {
+                    let mut pkgs = Vec::new();
+                    for name in names {
+                        if let Some(pkg) = db.get_installed(name)? {
+                            pkgs.push(pkg);
+                        }
+                    }
+                    pkgs
+                }
+                None => db.get_all_installed()?,
+            }
+        };
+
+        let mut checks = Vec::new();
+        for pkg in installed {
+            if let Some(available) = self.repos.get_latest(&pkg.name).await? {
+                if let Some(check) = self.check_live_package(&pkg, &available).await? {
+                    checks.push(check);
+                }
+            }
+        }
+        Ok(checks)
+    }
+
+    async fn check_live_package(
+        &self,
+        installed: &InstalledPackage,
+        available: &PackageInfo,
+    ) -> Result<Option<live::LiveCheck>> {
+        if !live::is_live(available) {
+            return Ok(None);
+        }
+        let recorded = self.db.read().await.get_live_commit(&installed.name)?;
+        let pinned = live::pinned_commit(&self.config.live_pins, available).is_some();
+        let target = live::target_commit(&self.config.live_pins, available).await?;
+        Ok(Some(live::LiveCheck {
+            package: available.clone(),
+            installed: recorded,
+            target,
+            pinned,
+        }))
+    }
+
     async fn has_use_changes(&self, installed: &InstalledPackage, available: &PackageInfo) -> bool {
         let available_flags: std::collections::HashSet<String> = available
             .use_flags
@@ -623,7 +1678,10 @@ impl PackageManager {
     /// Sync a specific repository
     pub async fn sync_repo(&self, repo_name: &str) -> Result<()> {
         info!("Syncing repository: {}", repo_name);
-        self.repos.sync_repo(repo_name).await
+        self.repos.sync_repo(repo_name).await?;
+        self.apply_package_moves().await?;
+        self.refresh_eix_cache().await;
+        Ok(())
     }
 
     /// Calculate packages to depclean
@@ -678,12 +1736,7 @@ impl PackageManager {
         }
 
         // Create transaction for removal
-        let mut transaction = transaction::Transaction::new(
-            self.db.clone(),
-            self.cache.clone(),
-            self.buck.clone(),
-            self.config.root.clone(),
-        );
+        let mut transaction = self.new_transaction();
 
         for pkg in to_remove {
             transaction.add_remove(pkg);
@@ -694,25 +1747,148 @@ impl PackageManager {
         Ok(())
     }
 
-    /// Resume interrupted operation
-    pub async fn resume(&self) -> Result<bool> {
-        // Check for saved transaction state
-        let state_file = self.config.cache_dir.join("transaction_state.json");
+    /// The interrupted transaction `buckos resume` would run again
+    pub fn resume_state(&self) -> Result<Option<transaction::ResumeState>> {
+        transaction::ResumeState::load(&self.resume_path())
+    }
 
-        if !state_file.exists() {
+    /// Run the command of an interrupted transaction again
+    ///
+    /// The state is kept until the command succeeds, and saved anew if it
+    /// is interrupted again.
+    pub async fn resume(&self) -> Result<bool> {
+        let Some(state) = self.resume_state()? else {
             return Ok(false);
+        };
+        info!(
+            "Resuming `{}`, interrupted at {}",
+            state.command, state.interrupted_at
+        );
+
+        match state.command {
+            transaction::ResumeCommand::Install { packages, opts } => {
+                self.install(&packages, opts).await?
+            }
+            transaction::ResumeCommand::Update { packages, opts } => {
+                self.update(packages.as_deref(), opts).await?
+            }
+            transaction::ResumeCommand::Remove { packages, opts } => {
+                self.remove(&packages, opts).await?
+            }
         }
 
-        info!("Found interrupted transaction, resuming...");
+        transaction::ResumeState::clear(&self.resume_path())?;
+        Ok(true)
+    }
 
-        // Load and execute saved transaction
-        let state_data = std::fs::read_to_string(&state_file)?;
-        let _state: serde_json::Value = serde_json::from_str(&state_data)?;
+    fn resume_path(&self) -> PathBuf {
+        self.config.cache_dir.join(transaction::RESUME_FILE)
+    }
 
-        // Remove state file after successful resume
-        std::fs::remove_file(&state_file)?;
+    /// Save the command of a transaction that `result` says was
+    /// interrupted, for `buckos resume`
+    fn save_resume_state(
+        &self,
+        result: &Result<()>,
+        command: impl FnOnce() -> transaction::ResumeCommand,
+    ) {
+        let Err(Error::Interrupted { signal }) = result else {
+            return;
+        };
+        let state = transaction::ResumeState::new(command(), *signal);
+        if let Err(e) = state.save(&self.resume_path()) {
+            warn!("Failed to save the interrupted transaction: {}", e);
+        }
+    }
 
-        Ok(true)
+    /// Explain the USE flags of a package and what toggling each would change
+    ///
+    /// Every flag not pinned by use.mask or use.force is toggled for this
+    /// package alone and the dependency tree resolved again with the resolver
+    /// `install` uses. File differences
+    /// come from binary packages in PKGDIR built with and without the flag,
+    /// when both are available.
+    pub async fn explain_use(
+        &self,
+        package: &str,
+        layers: use_explain::UseLayers,
+    ) -> Result<(PackageInfo, Vec<use_explain::FlagExplanation>)> {
+        let pkg = self
+            .info(package)
+            .await?
+            .ok_or_else(|| Error::PackageNotFound(package.to_string()))?;
+        let target = [pkg.id.full_name()];
+        let opts = InstallOptions {
+            force: true,
+            ..Default::default()
+        };
+
+        let resolve = |layers: use_explain::UseLayers| {
+            let opts = opts.clone();
+            let target = target.clone();
+            async move { self.resolver(layers).await?.resolve(&target, &opts).await }
+        };
+
+        let current = resolve(layers.clone()).await?;
+        #[cfg(feature = "binary-packages")]
+        let index = binary::BinaryPackageIndex::load(&self.config.packages_dir())?;
+        #[cfg(feature = "binary-packages")]
+        let builds: Vec<&binary::BinaryPackage> = index
+            .packages
+            .get(&pkg.id.full_name())
+            .map(|builds| {
+                builds
+                    .iter()
+                    .filter(|b| b.version == pkg.version && !b.files.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut explanations = layers.explain(&pkg);
+        for flag in &mut explanations {
+            if flag.is_locked() {
+                continue;
+            }
+
+            let toggled_layers =
+                layers
+                    .clone()
+                    .with_package_override(&pkg.id, &flag.flag, !flag.enabled);
+            let toggled = match resolve(toggled_layers).await {
+                Ok(resolution) => resolution,
+                Err(e) => {
+                    warn!("Resolution with USE={} toggled failed: {}", flag.flag, e);
+                    continue;
+                }
+            };
+
+            #[cfg_attr(not(feature = "binary-packages"), allow(unused_mut))]
+            let mut impact =
+                use_explain::ToggleImpact::between(&current.packages, &toggled.packages);
+            #[cfg(feature = "binary-packages")]
+            let build_with = |enabled: bool| {
+                builds
+                    .iter()
+                    .find(|b| b.use_flags.contains(&flag.flag) == enabled)
+            };
+            #[cfg(feature = "binary-packages")]
+            if let (Some(current), Some(toggled)) =
+                (build_with(flag.enabled), build_with(!flag.enabled))
+            {
+                impact = impact.with_files(&current.files, &toggled.files);
+            }
+            flag.impact = Some(impact);
+        }
+
+        Ok((pkg, explanations))
+    }
+
+    /// Collect the USE flags declared by packages across all repositories
+    pub async fn use_flag_usage(
+        &self,
+    ) -> Result<std::collections::BTreeMap<String, use_explain::UseFlagUsage>> {
+        let packages = self.repos.get_all_packages().await?;
+        Ok(use_explain::collect_flag_usage(&packages))
     }
 
     /// Find packages that need rebuilding due to USE flag changes
@@ -807,6 +1983,48 @@ impl PackageManager {
         Ok(newuse_packages)
     }
 
+    /// Send advisories affecting installed packages and unread news to the
+    /// configured notifiers, skipping items they were already sent
+    pub async fn notify(&self) -> Result<notify::NotifyReport> {
+        if self.config.notify.notifiers().is_empty() {
+            return Ok(notify::NotifyReport::default());
+        }
+        let mut items: Vec<notify::NotifyItem> = self
+            .audit()
+            .await?
+            .iter()
+            .map(notify::NotifyItem::advisory)
+            .collect();
+
+        let installed: Vec<PackageId> = self
+            .db
+            .read()
+            .await
+            .get_all_installed()?
+            .into_iter()
+            .map(|p| p.id)
+            .collect();
+        let keywords: Vec<String> = self.config.accept_keywords.iter().cloned().collect();
+        let mut news = news::NewsManager::new(
+            self.layout.repos_dir.clone(),
+            self.layout.path("var/lib/buckos/news.read"),
+        );
+        news.load()?;
+        items.extend(
+            news.get_unread()
+                .into_iter()
+                .filter(|item| news.should_display(item, &installed, "", &keywords))
+                .map(notify::NotifyItem::news),
+        );
+
+        notify::dispatch(
+            &self.config.notify,
+            &self.layout.path(notify::NOTIFIED_FILE),
+            items,
+        )
+        .await
+    }
+
     /// Audit installed packages for security vulnerabilities
     pub async fn audit(&self) -> Result<Vec<Vulnerability>> {
         info!("Auditing for security vulnerabilities");
@@ -863,75 +2081,264 @@ impl PackageManager {
         Ok(vulnerabilities)
     }
 
+    /// The world file of this system
+    pub fn world_file(&self) -> Result<world::WorldFile> {
+        world::WorldFile::at(&self.layout.path(world::WORLD_FILE))
+    }
+
     /// Add package to world set
     pub async fn add_to_world(&self, pkg_id: &PackageId) -> Result<()> {
-        let world_file = self.config.root.join("var/lib/portage/world");
+        let mut world = self.world_file()?;
+        world.insert(pkg_id.full_name());
+        world.save()
+    }
 
-        // Ensure directory exists
-        if let Some(parent) = world_file.parent() {
-            std::fs::create_dir_all(parent)?;
+    /// Remove package from world set
+    pub async fn remove_from_world(&self, pkg_id: &PackageId) -> Result<()> {
+        let mut world = self.world_file()?;
+        if world.remove(&pkg_id.full_name()) {
+            world.save()?;
         }
+        Ok(())
+    }
+
+    /// Capture this machine's configuration and installed state as a manifest
+    pub async fn export_manifest(
+        &self,
+        profile: Option<String>,
+    ) -> Result<manifest::MachineManifest> {
+        let world = self.world_file()?;
+        let installed = self.list_installed().await?;
+        Ok(manifest::MachineManifest::capture(
+            &self.config,
+            profile,
+            world.entries().cloned(),
+            &installed,
+        ))
+    }
 
-        // Read existing world set
-        let mut world = std::collections::HashSet::new();
-        if world_file.exists() {
-            let content = std::fs::read_to_string(&world_file)?;
-            for line in content.lines() {
-                let line = line.trim();
-                if !line.is_empty() && !line.starts_with('#') {
-                    world.insert(line.to_string());
+    /// Work out what must change for this machine to match a manifest
+    pub async fn plan_manifest(
+        &self,
+        manifest: &manifest::MachineManifest,
+    ) -> Result<manifest::ManifestPlan> {
+        let world = self.world_file()?;
+        let world: std::collections::HashSet<String> = world.entries().cloned().collect();
+        let installed = self.list_installed().await?;
+        Ok(manifest.plan(&self.config, &world, &installed))
+    }
+
+    /// Report how this machine differs from a manifest without changing anything
+    ///
+    /// Besides package and configuration drift, protected configuration
+    /// files (CONFIG_PROTECT) whose contents no longer match the merged copy
+    /// are reported.
+    pub async fn check_drift(
+        &self,
+        manifest: &manifest::MachineManifest,
+    ) -> Result<manifest::DriftReport> {
+        let world = self.world_file()?;
+        let world: std::collections::HashSet<String> = world.entries().cloned().collect();
+        let installed = self.list_installed().await?;
+        let mut report = manifest.drift(&self.config, &world, &installed);
+
+        let protect = config_protect::ConfigProtect::new(self.config.config_protect.clone());
+        let db = self.db.read().await;
+        for pkg in &installed {
+            for file in db.get_package_files(&pkg.name)? {
+                if file.file_type != FileType::Regular
+                    || !protect.is_protected(std::path::Path::new(&file.path))
+                {
+                    continue;
+                }
+                let Some(expected) = &file.blake3_hash else {
+                    continue;
+                };
+
+                let path = self.config.system_path(&file.path);
+                let missing = !path.exists();
+                if missing || &cache::compute_blake3(&path)? != expected {
+                    report.config_files.push(manifest::ConfigFileDrift {
+                        path: file.path.clone(),
+                        package: pkg.id.full_name(),
+                        missing,
+                    });
                 }
             }
         }
 
-        // Add new package
-        world.insert(pkg_id.full_name());
+        Ok(report)
+    }
+
+    /// Build the boot manifest of `packages`, or of the installed system set
+    ///
+    /// Lists each regular file with the digest recorded when it was merged.
+    /// Protected configuration files are left out since they are expected
+    /// to change.
+    pub async fn boot_manifest(&self, packages: &[String]) -> Result<buckos_core::FileManifest> {
+        let installed = self.list_installed().await?;
+        let selected: Vec<&InstalledPackage> = if packages.is_empty() {
+            let system = self.get_system_set().await?;
+            installed
+                .iter()
+                .filter(|p| system.packages.contains(&p.id))
+                .collect()
+        } else {
+            packages
+                .iter()
+                .map(|name| {
+                    installed
+                        .iter()
+                        .find(|p| p.name == *name || p.id.full_name() == *name)
+                        .ok_or_else(|| Error::PackageNotInstalled(name.clone()))
+                })
+                .collect::<Result<_>>()?
+        };
 
-        // Write back
-        let mut content = String::new();
-        let mut sorted: Vec<_> = world.into_iter().collect();
-        sorted.sort();
-        for pkg in sorted {
-            content.push_str(&pkg);
-            content.push('\n');
+        let protect = config_protect::ConfigProtect::new(self.config.config_protect.clone());
+        let db = self.db.read().await;
+        let mut manifest = buckos_core::FileManifest::default();
+        for pkg in selected {
+            for file in db.get_package_files(&pkg.name)? {
+                if file.file_type != FileType::Regular
+                    || protect.is_protected(std::path::Path::new(&file.path))
+                {
+                    continue;
+                }
+                if let Some(hash) = file.blake3_hash {
+                    manifest.push(file.path, hash);
+                }
+            }
         }
+        Ok(manifest)
+    }
 
-        std::fs::write(&world_file, content)?;
+    /// Converge the installed packages and world set to a manifest plan
+    ///
+    /// Configuration and repository changes are not applied here: the
+    /// package manager should already be running with the manifest's
+    /// configuration so packages are built with the pinned USE flags.
+    /// Pins are installed at exactly their version and slot, so one the
+    /// repositories no longer offer fails the apply. Returns the pins that
+    /// still differ from what is installed afterwards.
+    pub async fn apply_manifest(
+        &self,
+        manifest: &manifest::MachineManifest,
+        plan: &manifest::ManifestPlan,
+    ) -> Result<Vec<manifest::PinnedPackage>> {
+        if !plan.remove.is_empty() {
+            // Only the slots the manifest lacks, whatever else shares the name
+            let mut transaction = self.new_transaction();
+            for pkg in self.list_installed().await? {
+                if plan
+                    .remove
+                    .iter()
+                    .any(|p| p.id == pkg.id && p.slot == pkg.slot)
+                {
+                    transaction.add_remove(pkg);
+                }
+            }
+            transaction.execute(&self.executor).await?;
+        }
 
-        Ok(())
-    }
+        if !plan.install.is_empty() {
+            let specs: Vec<String> = plan.install.iter().map(|p| p.atom()).collect();
+            let opts = InstallOptions {
+                oneshot: true,
+                ..Default::default()
+            };
+            self.install(&specs, opts).await?;
+        }
 
-    /// Remove package from world set
-    pub async fn remove_from_world(&self, pkg_id: &PackageId) -> Result<()> {
-        let world_file = self.config.root.join("var/lib/portage/world");
+        if !plan.rebuild.is_empty() {
+            let specs: Vec<String> = plan.rebuild.iter().map(|p| p.atom()).collect();
+            let opts = InstallOptions {
+                force: true,
+                oneshot: true,
+                ..Default::default()
+            };
+            self.install(&specs, opts).await?;
+        }
 
-        if !world_file.exists() {
-            return Ok(());
+        let mut world = self.world_file()?;
+        for entry in &plan.world_add {
+            world.insert(entry.clone());
         }
+        for entry in &plan.world_remove {
+            world.remove(entry);
+        }
+        world.save()?;
 
-        // Read existing world set
-        let content = std::fs::read_to_string(&world_file)?;
-        let mut world: std::collections::HashSet<String> = content
-            .lines()
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty() && !s.starts_with('#'))
+        let installed = self.list_installed().await?;
+        let unmatched = manifest
+            .packages
+            .iter()
+            .filter(|pin| {
+                !installed
+                    .iter()
+                    .any(|p| p.id == pin.id && p.slot == pin.slot && p.version == pin.version)
+            })
+            .cloned()
             .collect();
+        Ok(unmatched)
+    }
+
+    /// Analyze the world file for redundant, unavailable or uninstalled entries
+    pub async fn analyze_world(&self) -> Result<Vec<world::WorldIssue>> {
+        let world = self.world_file()?;
+
+        let db = self.db.read().await;
+        let installed = db.get_all_installed()?;
+        drop(db);
 
-        // Remove package
-        world.remove(&pkg_id.full_name());
+        let packages = self.repos.get_all_packages().await?;
+        let available: std::collections::HashSet<PackageId> =
+            packages.iter().map(|p| p.id.clone()).collect();
+        let graph = resolver::ReachabilityGraph::new(&packages);
 
-        // Write back
-        let mut new_content = String::new();
-        let mut sorted: Vec<_> = world.into_iter().collect();
-        sorted.sort();
-        for pkg in sorted {
-            new_content.push_str(&pkg);
-            new_content.push('\n');
+        Ok(world::analyze_world(
+            world.entries(),
+            &installed,
+            &available,
+            &graph,
+        ))
+    }
+
+    /// Remove entries from the world file
+    pub async fn remove_world_entries(&self, entries: &[String]) -> Result<()> {
+        let mut world = self.world_file()?;
+        for entry in entries {
+            world.remove(entry);
         }
+        world.save()
+    }
 
-        std::fs::write(&world_file, new_content)?;
+    /// Copy the package database, world set and history into a backup
+    pub async fn backup_db(&self) -> Result<db::DbBackup> {
+        let mut backup = self.db.read().await.backup()?;
+        backup.world = self.world_file()?.entries().cloned().collect();
+        Ok(backup)
+    }
 
-        Ok(())
+    /// Check a backup's packages against the files below the install root
+    pub fn check_backup(&self, backup: &db::DbBackup) -> Vec<db::BackupCheck> {
+        backup.check(&self.config.root)
+    }
+
+    /// Replace the package database and world set with a backup
+    pub async fn restore_db(&self, backup: &db::DbBackup) -> Result<()> {
+        self.db.write().await.restore(backup)?;
+        self.vdb().replace_all(&backup.packages)?;
+
+        let mut world = self.world_file()?;
+        let current: Vec<String> = world.entries().cloned().collect();
+        for entry in &current {
+            world.remove(entry);
+        }
+        for entry in &backup.world {
+            world.insert(entry.clone());
+        }
+        world.save()
     }
 
     /// Get reverse dependencies (packages that depend on a given package)
@@ -951,14 +2358,16 @@ impl PackageManager {
             format!("/{}", path)
         };
 
-        // Try exact match first
-        if let Some(pkg_name) = db.get_file_owner(&normalized_path)? {
-            if let Some(pkg) = db.get_installed(&pkg_name)? {
-                return Ok(Some(OwnerResult {
-                    package: pkg.id.clone(),
-                    version: pkg.version.clone(),
-                    file_path: normalized_path,
-                }));
+        // Try the path as given, then its other name under merged /usr
+        for file_path in self.layout.spellings(&normalized_path) {
+            if let Some(pkg_name) = db.get_file_owner(&file_path)? {
+                if let Some(pkg) = db.get_installed(&pkg_name)? {
+                    return Ok(Some(OwnerResult {
+                        package: pkg.id.clone(),
+                        version: pkg.version.clone(),
+                        file_path,
+                    }));
+                }
             }
         }
 
@@ -1152,7 +2561,8 @@ pub struct BrokenPackage {
 }
 
 /// Options for install command
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct InstallOptions {
     /// Force reinstall even if already installed
     pub force: bool,
@@ -1223,6 +2633,8 @@ pub struct EmergeOptions {
     pub build_pkg: bool,
     /// Only build binary packages (--buildpkgonly)
     pub build_pkg_only: bool,
+    /// Warn when the estimated build time exceeds this
+    pub time_budget: Option<std::time::Duration>,
 }
 
 /// Options for depclean command
@@ -1244,7 +2656,8 @@ pub struct SyncOptions {
 }
 
 /// Options for remove command
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct RemoveOptions {
     /// Force removal even with dependents
     pub force: bool,
@@ -1253,7 +2666,8 @@ pub struct RemoveOptions {
 }
 
 /// Options for update command
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct UpdateOptions {
     /// Sync repositories before updating
     pub sync: bool,
@@ -1265,6 +2679,8 @@ pub struct UpdateOptions {
     pub newuse: bool,
     /// Include build dependencies
     pub with_bdeps: bool,
+    /// Rebuild live packages whose upstream commit changed
+    pub live: bool,
 }
 
 /// Options for build command
@@ -1278,6 +2694,8 @@ pub struct BuildOptions {
     pub buck_args: Vec<String>,
     /// Custom Buck configuration options for this build
     pub config_options: Option<BuckConfigOptions>,
+    /// Stops the build, killing its processes, when cancelled
+    pub cancellation: Option<interrupt::Cancellation>,
 }
 
 /// Options for clean command
@@ -1300,6 +2718,26 @@ pub struct VerifyResult {
     pub ok: bool,
 }
 
+/// Options for verify command
+#[derive(Debug, Clone, Default)]
+pub struct VerifyOptions {
+    /// Hash every file, even ones whose metadata is unchanged since merge
+    pub paranoid: bool,
+}
+
+/// Result of verifying all installed packages
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    pub results: Vec<VerifyResult>,
+    /// Files whose contents were hashed
+    pub hashed: usize,
+    /// Files trusted because their size, mtime, ctime and inode were
+    /// unchanged
+    pub skipped: usize,
+    /// Files whose fs-verity digest matched the one recorded at merge
+    pub verity: usize,
+}
+
 /// Version check for vulnerability matching
 #[derive(Debug, Clone)]
 pub enum VersionCheck {
@@ -0,0 +1,197 @@
+//! Live (VCS) packages
+//!
+//! A live package builds from the tip of a git branch rather than a
+//! release tarball; by convention its version is 9999. Its source URL is
+//! a git URL (`git+https://host/repo.git#branch`, `git://…`, or anything
+//! ending in `.git`). The commit that was built is recorded in the package
+//! database, so `buckos update --live` only rebuilds when upstream has
+//! moved on or a different commit is pinned in the configuration.
+
+use crate::{Error, PackageInfo, Result};
+use std::collections::HashMap;
+
+/// Git source of a live package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveSource {
+    /// URL passed to git
+    pub url: String,
+    /// Branch to follow; the remote's HEAD when unset
+    pub branch: Option<String>,
+}
+
+impl LiveSource {
+    /// Parse a package source URL, returning None if it is not a git source
+    pub fn parse(source_url: &str) -> Option<Self> {
+        let (url, branch) = match source_url.split_once('#') {
+            Some((url, branch)) => (url, Some(branch.to_string())),
+            None => (source_url, None),
+        };
+        let url = if let Some(url) = url.strip_prefix("git+") {
+            url
+        } else if url.starts_with("git://") || url.ends_with(".git") {
+            url
+        } else {
+            return None;
+        };
+        Some(Self {
+            url: url.to_string(),
+            branch: branch.filter(|b| !b.is_empty()),
+        })
+    }
+
+    /// Ref to resolve on the remote
+    pub fn remote_ref(&self) -> String {
+        match &self.branch {
+            Some(branch) => format!("refs/heads/{}", branch),
+            None => "HEAD".to_string(),
+        }
+    }
+
+    /// Commit the branch currently points at upstream
+    pub async fn remote_head(&self) -> Result<String> {
+        let output = tokio::process::Command::new("git")
+            .args(["ls-remote", "--", &self.url, &self.remote_ref()])
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .output()
+            .await
+            .map_err(|e| Error::Other(format!("Failed to run git ls-remote: {}", e)))?;
+        if !output.status.success() {
+            return Err(Error::NetworkError(format!(
+                "git ls-remote {} failed: {}",
+                self.url,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        parse_ls_remote(&String::from_utf8_lossy(&output.stdout), &self.remote_ref()).ok_or_else(
+            || Error::NetworkError(format!("{} has no ref {}", self.url, self.remote_ref())),
+        )
+    }
+}
+
+/// Whether a package builds from a git branch
+pub fn is_live(pkg: &PackageInfo) -> bool {
+    pkg.source_url
+        .as_deref()
+        .and_then(LiveSource::parse)
+        .is_some()
+}
+
+/// Commit pinned for a package in the configuration, by `category/name` or name
+pub fn pinned_commit<'a>(pins: &'a HashMap<String, String>, pkg: &PackageInfo) -> Option<&'a str> {
+    pins.get(&pkg.id.full_name())
+        .or_else(|| pins.get(&pkg.id.name))
+        .map(String::as_str)
+}
+
+/// Commit a live package should be built at: its pin, else upstream's head
+pub async fn target_commit(pins: &HashMap<String, String>, pkg: &PackageInfo) -> Result<String> {
+    if let Some(commit) = pinned_commit(pins, pkg) {
+        return Ok(commit.to_string());
+    }
+    let source = pkg
+        .source_url
+        .as_deref()
+        .and_then(LiveSource::parse)
+        .ok_or_else(|| Error::Other(format!("{} is not a live package", pkg.id)))?;
+    source.remote_head().await
+}
+
+/// Result of checking one installed live package against upstream
+#[derive(Debug, Clone)]
+pub struct LiveCheck {
+    pub package: PackageInfo,
+    /// Commit the installed package was built from, if recorded
+    pub installed: Option<String>,
+    /// Commit it should be built from
+    pub target: String,
+    /// Whether `target` comes from a configured pin
+    pub pinned: bool,
+}
+
+impl LiveCheck {
+    /// Whether the package must be rebuilt to reach the target commit
+    pub fn needs_rebuild(&self) -> bool {
+        match &self.installed {
+            // Pins may be abbreviated
+            Some(installed) => !installed.starts_with(&self.target),
+            None => true,
+        }
+    }
+}
+
+fn parse_ls_remote(output: &str, remote_ref: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let (commit, name) = line.split_once('\t')?;
+        (name == remote_ref && commit.len() >= 40 && commit.bytes().all(|b| b.is_ascii_hexdigit()))
+            .then(|| commit.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_live_source() {
+        assert_eq!(
+            LiveSource::parse("git+https://example.org/foo.git#devel"),
+            Some(LiveSource {
+                url: "https://example.org/foo.git".to_string(),
+                branch: Some("devel".to_string()),
+            })
+        );
+        let source = LiveSource::parse("https://example.org/foo.git").unwrap();
+        assert_eq!(source.remote_ref(), "HEAD");
+        assert!(LiveSource::parse("git://example.org/foo").is_some());
+        assert!(LiveSource::parse("https://example.org/foo-1.0.tar.gz").is_none());
+    }
+
+    #[test]
+    fn test_parse_ls_remote_and_rebuild() {
+        let head = "0123456789abcdef0123456789abcdef01234567";
+        let output = format!(
+            "{}\tHEAD\n{}\trefs/heads/main\n",
+            head, "fedcba9876543210fedcba9876543210fedcba98"
+        );
+        assert_eq!(parse_ls_remote(&output, "HEAD").as_deref(), Some(head));
+        assert_eq!(parse_ls_remote(&output, "refs/heads/devel"), None);
+
+        let package: PackageInfo = serde_json::from_value(serde_json::json!({
+            "id": {"category": "app-misc", "name": "foo"},
+            "version": "9999.0.0",
+            "slot": "0",
+            "description": "",
+            "homepage": null,
+            "license": "MIT",
+            "keywords": [],
+            "use_flags": [],
+            "dependencies": [],
+            "build_dependencies": [],
+            "runtime_dependencies": [],
+            "source_url": "git+https://example.org/foo.git",
+            "source_hash": null,
+            "buck_target": "//app-misc/foo:foo",
+            "size": 0,
+            "installed_size": 0
+        }))
+        .unwrap();
+        assert!(is_live(&package));
+
+        let mut pins = HashMap::new();
+        pins.insert("foo".to_string(), "0123456".to_string());
+        assert_eq!(pinned_commit(&pins, &package), Some("0123456"));
+
+        let check = LiveCheck {
+            package,
+            installed: Some(head.to_string()),
+            target: "0123456".to_string(),
+            pinned: true,
+        };
+        assert!(!check.needs_rebuild());
+        let check = LiveCheck {
+            installed: None,
+            ..check
+        };
+        assert!(check.needs_rebuild());
+    }
+}
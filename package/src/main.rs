@@ -4,15 +4,36 @@
 //! Designed to be compatible with Gentoo's emerge command.
 
 use buckos_package::{
+    buck::BuckIntegration,
     config::SyncType,
+    db::{IntegrityProblem, PackageDb, Vdb},
+    debuginfod::DebugInfoStore,
+    docs::DocFormat,
+    eix::EixFilter,
+    hardware::HardwareDetection,
+    interrupt::{self, Cancellation},
+    manifest::MachineManifest,
+    mirror::{MirrorConfig, MirrorServer},
     overlay::{OverlayConfig, OverlayManager, OverlayQuality},
+    patches::{self, PatchSet},
+    peer::Advertiser,
+    periodic::{PeriodicStatus, PeriodicTask},
+    repository::RepoGeneration,
+    resolver::ResolutionPlan,
+    theme::{self, ColorChoice, Role},
+    tr,
+    transaction::{format_duration, format_preview_report, TransactionPreview},
+    workspace::WorkspaceManager,
+    world::{WorldFile, WorldIssueKind},
     BuildOptions, CleanOptions, Config, DepcleanOptions, EmergeOptions, InstallOptions,
-    PackageManager, RemoveOptions, Resolution, UpdateOptions,
+    InstalledPackage, Layout, LayoutMode, PackageManager, RemoveOptions, Resolution, UpdateOptions,
+    VerifyOptions,
 };
-use clap::{Args, Parser, Subcommand};
-use console::style;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+#[cfg(feature = "tui")]
 use dialoguer::Confirm;
-use serde::{Deserialize, Serialize};
+#[cfg(not(feature = "tui"))]
+use prompt::Confirm;
 use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::Write;
@@ -32,6 +53,15 @@ struct Cli {
     #[arg(short, long, global = true)]
     config: Option<String>,
 
+    /// Operate on a named workspace (see `buckos workspace`)
+    #[arg(
+        long,
+        global = true,
+        env = "BUCKOS_WORKSPACE",
+        conflicts_with = "config"
+    )]
+    workspace: Option<String>,
+
     /// Verbose output
     #[arg(short, long, global = true, action = clap::ArgAction::Count)]
     verbose: u8,
@@ -40,6 +70,11 @@ struct Cli {
     #[arg(short, long, global = true)]
     quiet: bool,
 
+    /// When to color output; defaults to the [theme] setting, which
+    /// defaults to auto (honoring NO_COLOR, CLICOLOR and CLICOLOR_FORCE)
+    #[arg(long, global = true, value_enum, value_name = "WHEN")]
+    color: Option<ColorChoice>,
+
     /// Pretend mode (don't actually do anything)
     #[arg(short, long, global = true)]
     pretend: bool,
@@ -72,6 +107,15 @@ struct Cli {
     #[arg(short, long, global = true)]
     jobs: Option<usize>,
 
+    /// Warn when the estimated build time exceeds this (e.g. 90m, 2h, 1h30m)
+    #[arg(long, global = true, value_parser = parse_time_budget)]
+    time_budget: Option<std::time::Duration>,
+
+    /// Manage packages of the current user in a prefix under the XDG
+    /// directories, without root
+    #[arg(long, global = true, env = "BUCKOS_USER")]
+    user: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -81,6 +125,9 @@ enum Commands {
     /// Install packages (emerge-style)
     Install(InstallArgs),
 
+    /// Install exactly what a plan from `install --plan-out` lists
+    ApplyPlan(ApplyPlanArgs),
+
     /// Remove/unmerge packages
     #[command(alias = "unmerge")]
     Remove(RemoveArgs),
@@ -94,6 +141,9 @@ enum Commands {
     /// Search for packages (emerge --search)
     Search(SearchArgs),
 
+    /// Query the package cache (eix)
+    Eix(EixArgs),
+
     /// Show package information (emerge --info / equery)
     Info(InfoArgs),
 
@@ -107,7 +157,7 @@ enum Commands {
     Clean(CleanArgs),
 
     /// Verify installed packages (qcheck equivalent)
-    Verify,
+    Verify(VerifyArgs),
 
     /// Query package database (equery equivalent)
     Query(QueryArgs),
@@ -131,7 +181,11 @@ enum Commands {
     Newuse(NewuseArgs),
 
     /// Check for security vulnerabilities (glsa-check equivalent)
-    Audit,
+    Audit {
+        /// Output findings as JSON
+        #[arg(long)]
+        json: bool,
+    },
 
     /// Manage USE flags
     #[command(alias = "use")]
@@ -161,14 +215,95 @@ enum Commands {
     /// Export configuration in various formats
     Export(ExportArgs),
 
+    /// Converge this machine to a manifest from `buckos export --manifest`
+    Apply(ApplyArgs),
+
+    /// Converge to a manifest, or report drift from it with --check
+    Converge(ConvergeArgs),
+
     /// Rebuild packages with broken library dependencies (revdep-rebuild)
     Revdep(RevdepArgs),
 
     /// Manage package signing and verification
+    #[cfg(feature = "signing")]
     Sign(SignArgs),
 
+    /// Show and rotate the signing keys of repositories
+    #[cfg(feature = "signing")]
+    Keys(KeysArgs),
+
+    /// Protect installed files with fs-verity and report coverage
+    Verity(VerityArgs),
+
     /// Manage overlays (additional package repositories)
     Overlay(OverlayArgs),
+
+    /// Manage the world set
+    World(WorldArgs),
+
+    /// Manage named workspaces (separate roots with their own db and config)
+    Workspace(WorkspaceArgs),
+
+    /// Split debug info packages and debuginfod server
+    Debuginfod(DebuginfodArgs),
+
+    /// Serve the repository, binary packages and distfiles to the LAN
+    Serve(ServeArgs),
+
+    /// Inspect and audit distfile mirrors
+    Mirrors(MirrorsArgs),
+
+    /// Show the audit trail of package operations
+    History(HistoryArgs),
+
+    /// Revert a transaction using cached binary packages
+    #[cfg(feature = "binary-packages")]
+    Undo(UndoArgs),
+
+    /// Export installed packages as binary archives and install from them
+    #[cfg(feature = "binary-packages")]
+    Binpkg(BinpkgArgs),
+
+    /// Summarize compiler warnings and errors across built versions
+    BuildReport(BuildReportArgs),
+
+    /// List recent builds, with the likely cause of failed ones
+    Log(LogArgs),
+
+    /// Show what depends on a package and how long rebuilding it would take
+    Impact(ImpactArgs),
+
+    /// Write the manifest the initramfs verifies the root filesystem against
+    BootManifest(BootManifestArgs),
+
+    /// Back up, restore or export the installed-package database
+    Db(DbArgs),
+
+    /// Show the per-user prefix and set up its environment (with --user)
+    Prefix(PrefixArgs),
+
+    /// Check or restart the Buck2 daemon builds run against
+    Buck(BuckArgs),
+
+    /// Write services that periodically sync, audit and check for updates
+    GenUnits(GenUnitsArgs),
+
+    /// Run a periodic task and record its result for 'buckos status'
+    Periodic(PeriodicArgs),
+
+    /// Show the active repository generations and the results of the last
+    /// periodic sync, audit and update check
+    Status(StatusArgs),
+
+    /// List loaded plugins
+    Plugins(PluginsArgs),
+
+    /// Generate man pages and a Markdown command reference from this binary
+    GenDocs(GenDocsArgs),
+
+    /// Subcommands provided by plugins
+    #[command(external_subcommand)]
+    External(Vec<String>),
 }
 
 #[derive(Args)]
@@ -204,6 +339,17 @@ struct InstallArgs {
     /// Empty dependency tree before installing
     #[arg(long = "emptytree", short = 'e')]
     empty_tree: bool,
+
+    /// Write the resolution to a plan file for `apply-plan` instead of
+    /// installing
+    #[arg(long = "plan-out", value_name = "FILE")]
+    plan_out: Option<String>,
+}
+
+#[derive(Args)]
+struct ApplyPlanArgs {
+    /// Plan written by `buckos install --plan-out`
+    plan: String,
 }
 
 #[derive(Args)]
@@ -241,6 +387,10 @@ struct UpdateArgs {
     /// Include deep dependencies
     #[arg(long)]
     with_bdeps: bool,
+
+    /// Rebuild live (git) packages whose upstream commit has changed
+    #[arg(long)]
+    live: bool,
 }
 
 #[derive(Args)]
@@ -287,10 +437,33 @@ struct SearchArgs {
     query: String,
 }
 
+#[derive(Args)]
+struct EixArgs {
+    /// Filter expression, e.g. `installed and use:ssl and not category:^dev-`
+    query: Vec<String>,
+
+    /// Print one line per package using a template
+    /// (placeholders: {category} {name} {fullname} {version} {versions}
+    /// {installed} {description} {homepage} {license} {use})
+    #[arg(long)]
+    format: Option<String>,
+
+    /// Regenerate the cache before querying
+    #[arg(long)]
+    update: bool,
+
+    /// Only print the number of matches
+    #[arg(long)]
+    count: bool,
+}
+
 #[derive(Args)]
 struct InfoArgs {
     /// Package name
     package: String,
+    /// Output as JSON, with every available version
+    #[arg(long)]
+    json: bool,
 }
 
 #[derive(Args)]
@@ -337,6 +510,13 @@ struct CleanArgs {
     builds: bool,
 }
 
+#[derive(Args)]
+struct VerifyArgs {
+    /// Hash every file, not only those whose metadata changed since merge
+    #[arg(long)]
+    paranoid: bool,
+}
+
 #[derive(Args)]
 struct QueryArgs {
     /// Query type
@@ -379,12 +559,12 @@ struct UseflagsArgs {
 
 #[derive(Subcommand)]
 enum UseflagsCommand {
-    /// List available USE flags
+    /// List USE flags declared by packages in the configured repositories
     List {
-        /// Filter by category (e.g., network, security, graphics)
+        /// Only flags used by packages in this category (e.g., dev-libs)
         #[arg(long)]
         category: Option<String>,
-        /// Show only global flags
+        /// Show only flags shared by several packages
         #[arg(short, long)]
         global: bool,
         /// Show detailed descriptions
@@ -423,6 +603,14 @@ enum UseflagsCommand {
     },
     /// Validate USE flag configuration
     Validate,
+    /// Explain where a package's USE flags come from and what toggling them changes
+    Explain {
+        /// Package name (e.g., dev-libs/openssl)
+        package: String,
+        /// USE flags as if given on the command line (prefix with - to disable)
+        #[arg(long, value_delimiter = ',', allow_hyphen_values = true)]
+        use_flags: Vec<String>,
+    },
 }
 
 #[derive(Args)]
@@ -448,6 +636,9 @@ struct DetectArgs {
     /// Output to file instead of stdout
     #[arg(short, long)]
     output: Option<String>,
+    /// Store the results as this machine's hardware
+    #[arg(long)]
+    save: bool,
 }
 
 #[derive(Args)]
@@ -470,6 +661,10 @@ struct ConfigureArgs {
     /// Auto-detect hardware and add appropriate flags
     #[arg(long)]
     auto_detect: bool,
+    /// Write USE flags for the detected hardware to package.use/99-hardware,
+    /// below make.conf and package.use
+    #[arg(long)]
+    hardware_use: bool,
 }
 
 #[derive(Args)]
@@ -545,12 +740,29 @@ enum PatchCommand {
     Check {
         /// Package name
         package: String,
+        /// Unpacked sources to dry-run the patches against
+        #[arg(long)]
+        source: Option<std::path::PathBuf>,
     },
     /// Show patch application order
     Order {
         /// Package name
         package: String,
     },
+    /// Apply user patches to unpacked sources (called from the prepare phase)
+    Apply {
+        /// Package (category/name)
+        package: String,
+        /// Package version, for version-specific patch directories
+        #[arg(long)]
+        version: Option<String>,
+        /// Package slot, for slot-specific patch directories
+        #[arg(long, default_value = "0")]
+        slot: String,
+        /// Source directory to patch
+        #[arg(long)]
+        source: std::path::PathBuf,
+    },
 }
 
 #[derive(Args)]
@@ -610,6 +822,33 @@ struct ExportArgs {
     /// Include package list
     #[arg(long)]
     with_packages: bool,
+    /// Export a complete machine manifest for `buckos apply` (json or toml)
+    #[arg(long)]
+    manifest: bool,
+}
+
+#[derive(Args)]
+struct ApplyArgs {
+    /// Manifest produced by `buckos export --manifest`
+    manifest: String,
+    /// Keep installed packages that are not in the manifest
+    #[arg(long)]
+    keep: bool,
+}
+
+#[derive(Args)]
+struct ConvergeArgs {
+    /// Manifest produced by `buckos export --manifest`
+    manifest: String,
+    /// Only report drift; exits with status 2 if the system has drifted
+    #[arg(long)]
+    check: bool,
+    /// Print the drift report as JSON (with --check)
+    #[arg(long)]
+    json: bool,
+    /// Keep installed packages that are not in the manifest
+    #[arg(long)]
+    keep: bool,
 }
 
 #[derive(Args)]
@@ -627,13 +866,21 @@ struct RevdepArgs {
     ignore: Vec<String>,
 }
 
+#[cfg(feature = "signing")]
 #[derive(Args)]
 struct SignArgs {
+    /// Signing backend (gpg, minisign); defaults to the configured one
+    #[arg(long, global = true)]
+    backend: Option<buckos_package::config::SigningBackend>,
+    /// Use the backend configured for this repository
+    #[arg(long, global = true, conflicts_with = "backend")]
+    repo: Option<String>,
     /// Signing subcommand
     #[command(subcommand)]
     subcommand: SignCommand,
 }
 
+#[cfg(feature = "signing")]
 #[derive(Subcommand)]
 enum SignCommand {
     /// List available signing keys
@@ -642,9 +889,12 @@ enum SignCommand {
         #[arg(short, long)]
         secret: bool,
     },
+    /// Generate a minisign key pair and trust it
+    GenerateKey,
     /// Import a signing key
     ImportKey {
-        /// Key source (file path, URL, or key ID)
+        /// Key source (file path, URL, or key ID; for minisign, a public
+        /// key file or its base64 line)
         source: String,
     },
     /// Export a signing key
@@ -653,8 +903,8 @@ enum SignCommand {
         key_id: String,
         /// Output file
         output: String,
-        /// ASCII armor output
-        #[arg(short, long)]
+        /// ASCII armor output (-a is the global --ask)
+        #[arg(long)]
         armor: bool,
     },
     /// Sign a package manifest
@@ -678,6 +928,15 @@ enum SignCommand {
         #[arg(short, long)]
         key: Option<String>,
     },
+    /// Add a co-signature over a repository's Manifest, counted towards
+    /// its signature threshold
+    CosignRepo {
+        /// Repository directory
+        repo_dir: String,
+        /// Key ID to use
+        #[arg(short, long)]
+        key: Option<String>,
+    },
     /// Verify a repository signature
     VerifyRepo {
         /// Repository directory
@@ -695,7 +954,7 @@ enum SignCommand {
     VerifyFile {
         /// File to verify
         file: String,
-        /// Signature file (defaults to file.asc)
+        /// Signature file (defaults to file.asc or file.minisig)
         #[arg(short, long)]
         signature: Option<String>,
     },
@@ -713,6 +972,83 @@ enum SignCommand {
     },
 }
 
+#[cfg(feature = "signing")]
+#[derive(Args)]
+struct KeysArgs {
+    #[command(subcommand)]
+    command: KeysCommand,
+}
+
+#[cfg(feature = "signing")]
+#[derive(Subcommand)]
+enum KeysCommand {
+    /// Show the trusted and revoked keys of each repository
+    Status,
+    /// Show which keys signed a repository's metadata against the
+    /// signature threshold in repos.conf
+    Verify {
+        /// Repository name
+        repo: String,
+    },
+    /// Add a statement introducing a new key to a repository's key
+    /// rotation manifest, signed by a key it already trusts
+    Introduce {
+        /// Repository directory
+        repo_dir: String,
+        /// Key to introduce
+        key_id: String,
+        /// Trusted key signing the statement
+        #[arg(long)]
+        signed_by: String,
+        /// Public key file (defaults to exporting the key)
+        #[arg(long)]
+        public_key: Option<String>,
+        /// Signing backend (gpg, minisign); defaults to the configured one
+        #[arg(long)]
+        backend: Option<buckos_package::config::SigningBackend>,
+    },
+    /// Add a statement revoking a key to a repository's key rotation
+    /// manifest
+    Revoke {
+        /// Repository directory
+        repo_dir: String,
+        /// Key to revoke
+        key_id: String,
+        /// Trusted key signing the statement (may be the revoked key)
+        #[arg(long)]
+        signed_by: String,
+        /// Why the key is revoked
+        #[arg(long)]
+        reason: Option<String>,
+        /// Signing backend (gpg, minisign); defaults to the configured one
+        #[arg(long)]
+        backend: Option<buckos_package::config::SigningBackend>,
+    },
+}
+
+#[derive(Args)]
+struct VerityArgs {
+    #[command(subcommand)]
+    command: VerityCommand,
+}
+
+#[derive(Subcommand)]
+enum VerityCommand {
+    /// Report how many installed files are protected by fs-verity and
+    /// which lost it
+    Status {
+        /// List every package, not only those with tampered files
+        #[arg(long)]
+        all: bool,
+    },
+    /// Enable fs-verity on the files of installed packages, after checking
+    /// they match their recorded hashes
+    Enable {
+        /// Packages to protect (default: all installed)
+        packages: Vec<String>,
+    },
+}
+
 #[derive(Args)]
 struct OverlayArgs {
     /// Overlay subcommand
@@ -728,7 +1064,7 @@ enum OverlayCommand {
         #[arg(short, long)]
         enabled: bool,
         /// Show all available overlays (including disabled)
-        #[arg(short, long)]
+        #[arg(long)]
         all: bool,
     },
     /// Add a new overlay
@@ -739,10 +1075,10 @@ enum OverlayCommand {
         #[arg(short, long)]
         uri: Option<String>,
         /// Sync type (git, rsync, http, local)
-        #[arg(short = 't', long, default_value = "git")]
+        #[arg(long, default_value = "git")]
         sync_type: String,
         /// Priority (higher = preferred)
-        #[arg(short, long, default_value = "50")]
+        #[arg(long, default_value = "50")]
         priority: i32,
         /// Local path (for local overlays)
         #[arg(short, long)]
@@ -790,1996 +1126,3347 @@ enum OverlayCommand {
     },
 }
 
-#[tokio::main]
-async fn main() -> ExitCode {
-    let cli = Cli::parse();
+#[derive(Args)]
+struct WorldArgs {
+    /// World subcommand
+    #[command(subcommand)]
+    subcommand: WorldCommand,
+}
 
-    // Initialize logging
-    let filter = match cli.verbose {
-        0 if cli.quiet => "error",
-        0 => "warn",
-        1 => "info",
-        2 => "debug",
-        _ => "trace",
-    };
+#[derive(Subcommand)]
+enum WorldCommand {
+    /// Find and remove redundant, unavailable or uninstalled world entries
+    Clean {
+        /// Only report problems, don't change the world file
+        #[arg(long)]
+        dry_run: bool,
+        /// Output problems as JSON without changing the world file
+        #[arg(long)]
+        json: bool,
+    },
+}
 
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(filter)),
-        )
-        .with_target(false)
-        .init();
+#[derive(Args)]
+struct WorkspaceArgs {
+    /// Workspace subcommand
+    #[command(subcommand)]
+    subcommand: WorkspaceCommand,
+}
 
-    // Load configuration
-    let config = match cli.config {
-        Some(path) => match Config::load_from(std::path::Path::new(&path)) {
-            Ok(c) => c,
-            Err(e) => {
-                error!("Failed to load config: {}", e);
-                return ExitCode::FAILURE;
-            }
-        },
-        None => Config::default(),
-    };
+#[derive(Subcommand)]
+enum WorkspaceCommand {
+    /// List workspaces
+    List,
+    /// Create a new workspace
+    Create {
+        /// Workspace name
+        name: String,
+        /// Sysroot for the workspace (defaults to <workspace>/root)
+        #[arg(long)]
+        root: Option<String>,
+    },
+    /// Show workspace paths
+    Show {
+        /// Workspace name
+        name: String,
+    },
+    /// Remove a workspace
+    Remove {
+        /// Workspace name
+        name: String,
+    },
+}
 
-    // Create package manager
-    let pkg_manager = match PackageManager::new(config).await {
-        Ok(pm) => pm,
-        Err(e) => {
-            error!("Failed to initialize package manager: {}", e);
-            return ExitCode::FAILURE;
-        }
-    };
+#[derive(Args)]
+struct DebuginfodArgs {
+    /// Debuginfod subcommand
+    #[command(subcommand)]
+    subcommand: DebuginfodCommand,
+}
 
-    // Build global emerge options
-    let emerge_opts = EmergeOptions {
-        pretend: cli.pretend,
-        ask: cli.ask,
-        fetch_only: cli.fetch_only,
-        oneshot: cli.oneshot,
-        deep: cli.deep,
-        newuse: cli.newuse,
-        tree: cli.tree,
-        verbose: cli.verbose,
-        quiet: cli.quiet,
-        jobs: cli.jobs,
-        ..Default::default()
-    };
+#[derive(Subcommand)]
+enum DebuginfodCommand {
+    /// Serve debug info by build ID to debuginfod clients
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = buckos_package::debuginfod::DEFAULT_LISTEN)]
+        listen: String,
+        /// Additional roots to search (e.g. unpacked -dbg packages)
+        #[arg(long = "extra-root")]
+        extra_roots: Vec<String>,
+    },
+    /// Create -dbg binary packages from installed split debug info
+    #[cfg(feature = "binary-packages")]
+    Pack {
+        /// Packages to create -dbg packages for
+        #[arg(required = true)]
+        packages: Vec<String>,
+    },
+}
 
-    // Execute command
-    let result = match cli.command {
-        Commands::Install(args) => cmd_install(&pkg_manager, args, &emerge_opts).await,
-        Commands::Remove(args) => cmd_remove(&pkg_manager, args, &emerge_opts).await,
-        Commands::Update(args) => cmd_update(&pkg_manager, args, &emerge_opts).await,
-        Commands::Sync(args) => cmd_sync(&pkg_manager, args).await,
-        Commands::Search(args) => cmd_search(&pkg_manager, args).await,
-        Commands::Info(args) => cmd_info(&pkg_manager, args).await,
-        Commands::List(args) => cmd_list(&pkg_manager, args).await,
-        Commands::Build(args) => cmd_build(&pkg_manager, args).await,
-        Commands::Clean(args) => cmd_clean(&pkg_manager, args).await,
-        Commands::Verify => cmd_verify(&pkg_manager).await,
-        Commands::Query(args) => cmd_query(&pkg_manager, args).await,
-        Commands::Owner(args) => cmd_owner(&pkg_manager, args).await,
-        Commands::Depgraph(args) => cmd_depgraph(&pkg_manager, args).await,
-        Commands::Config => cmd_config().await,
-        Commands::Depclean(args) => cmd_depclean(&pkg_manager, args, &emerge_opts).await,
-        Commands::Resume => cmd_resume(&pkg_manager).await,
-        Commands::Newuse(args) => cmd_newuse(&pkg_manager, args, &emerge_opts).await,
-        Commands::Audit => cmd_audit(&pkg_manager).await,
-        Commands::Useflags(args) => cmd_useflags(&pkg_manager, args).await,
-        Commands::Detect(args) => cmd_detect(args).await,
-        Commands::Configure(args) => cmd_configure(args).await,
-        Commands::Set(args) => cmd_set(&pkg_manager, args, &emerge_opts).await,
-        Commands::Patch(args) => cmd_patch(args).await,
-        Commands::Deps(args) => cmd_deps(&pkg_manager, args).await,
-        Commands::Rdeps(args) => cmd_rdeps(&pkg_manager, args).await,
-        Commands::Profile(args) => cmd_profile(args).await,
-        Commands::Export(args) => cmd_export(args).await,
-        Commands::Revdep(args) => cmd_revdep(&pkg_manager, args, &emerge_opts).await,
-        Commands::Sign(args) => cmd_sign(args).await,
-        Commands::Overlay(args) => cmd_overlay(args).await,
-    };
+#[derive(Args)]
+struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, default_value = buckos_package::mirror::DEFAULT_LISTEN)]
+    listen: String,
+    /// Require clients to present this token (Authorization: Bearer or ?token=)
+    #[arg(long, env = "BUCKOS_SERVE_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+    /// Don't generate directory listings
+    #[arg(long)]
+    no_listing: bool,
+    /// Advertise this mirror over mDNS so LAN peers fetch distfiles from it
+    #[arg(long)]
+    share: bool,
+}
 
-    match result {
-        Ok(()) => ExitCode::SUCCESS,
-        Err(e) => {
-            error!("{}", e);
-            ExitCode::FAILURE
-        }
-    }
+#[derive(Args)]
+struct MirrorsArgs {
+    #[command(subcommand)]
+    command: MirrorsCommand,
 }
 
-async fn cmd_install(
-    pm: &PackageManager,
-    args: InstallArgs,
-    emerge_opts: &EmergeOptions,
-) -> buckos_package::Result<()> {
-    // Expand package sets
-    let packages = expand_package_sets(pm, &args.packages).await?;
+#[derive(Args)]
+struct HistoryArgs {
+    #[command(subcommand)]
+    command: Option<HistoryCommand>,
+    /// Only transactions touching this package
+    #[arg(long)]
+    package: Option<String>,
+    /// Only transactions since this date (YYYY-MM-DD or RFC 3339)
+    #[arg(long)]
+    since: Option<String>,
+    /// Net package changes between two points (transaction ids or dates);
+    /// TO defaults to now
+    #[arg(long, num_args = 1..=2, value_names = ["FROM", "TO"])]
+    diff: Option<Vec<String>>,
+    /// Print the transaction that reverts the given one
+    #[arg(long, value_name = "ID")]
+    undo: Option<i64>,
+    /// Output as JSON
+    #[arg(long)]
+    json: bool,
+}
 
-    let opts = InstallOptions {
-        force: args.force,
-        no_deps: args.no_deps,
-        build: args.build,
-        use_flags: args.use_flags,
-        oneshot: emerge_opts.oneshot,
-        fetch_only: emerge_opts.fetch_only,
-        deep: emerge_opts.deep,
-        newuse: emerge_opts.newuse,
-        empty_tree: args.empty_tree,
-        no_replace: args.no_replace,
-        use_pkg: emerge_opts.use_pkg,
-        use_pkg_only: emerge_opts.use_pkg_only,
-        get_binpkg: emerge_opts.get_binpkg,
-        get_binpkg_only: emerge_opts.get_binpkg_only,
-        build_pkg: emerge_opts.build_pkg,
-        build_pkg_only: emerge_opts.build_pkg_only,
-    };
+#[derive(Subcommand)]
+enum HistoryCommand {
+    /// Show one transaction with the resources it used
+    Show {
+        /// Transaction id
+        id: i64,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
 
-    // Resolve dependencies first to show what will be installed
-    let resolution = pm.resolve_packages(&packages, &opts).await?;
+#[cfg(feature = "binary-packages")]
+#[derive(Args)]
+struct UndoArgs {
+    /// Transaction to revert (see 'buckos history'); defaults to the last one
+    id: Option<i64>,
+}
 
-    if resolution.packages.is_empty() {
-        if !emerge_opts.quiet {
-            println!("\n{}", style(">>> No packages to install").green().bold());
-        }
-        return Ok(());
-    }
+#[cfg(feature = "binary-packages")]
+#[derive(Args)]
+struct BinpkgArgs {
+    /// Binpkg subcommand
+    #[command(subcommand)]
+    subcommand: BinpkgCommand,
+}
 
-    // Display emerge-style package list
-    print_emerge_list(&resolution, emerge_opts, "install")?;
+#[cfg(feature = "binary-packages")]
+#[derive(Subcommand)]
+enum BinpkgCommand {
+    /// Pack installed packages, as their files are now, into archives
+    Export {
+        /// Packages to export
+        #[arg(required = true)]
+        packages: Vec<String>,
+        /// Directory to write to; defaults to PKGDIR
+        #[arg(long, short)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Install packages from archives without building them
+    Import {
+        /// Archives to install
+        #[arg(required = true)]
+        archives: Vec<std::path::PathBuf>,
+    },
+    /// Check an archive and show what it holds
+    Info {
+        /// Archive to show
+        archive: std::path::PathBuf,
+        /// List the files in the archive
+        #[arg(long)]
+        files: bool,
+    },
+}
 
-    // Pretend mode - just show what would be done
-    if emerge_opts.pretend {
-        return Ok(());
-    }
+#[derive(Args)]
+struct BuildReportArgs {
+    /// Package name or category/name
+    package: String,
+    /// Show details for this version instead of the latest
+    #[arg(long)]
+    version: Option<String>,
+    /// Number of files and classes to list
+    #[arg(long, default_value = "10")]
+    top: usize,
+    /// Print the stored build log instead of the summary
+    #[arg(long)]
+    log: bool,
+    /// Output reports as JSON
+    #[arg(long)]
+    json: bool,
+}
 
-    // Ask mode - prompt for confirmation
-    if emerge_opts.ask {
-        if !Confirm::new()
-            .with_prompt("Would you like to merge these packages?")
-            .default(true)
-            .interact()?
-        {
-            println!("{}", style(">>> Exiting.").yellow().bold());
-            return Ok(());
-        }
-        println!();
-    }
+#[derive(Args)]
+struct LogArgs {
+    /// Only builds of this package (name or category/name)
+    package: Option<String>,
+    /// Only failed builds, with their likely cause and a suggested fix
+    #[arg(long)]
+    failed: bool,
+    /// Packages whose builds were retried or failed, with attempt counts
+    #[arg(long, conflicts_with = "failed")]
+    flaky: bool,
+    /// Number of builds to list
+    #[arg(short = 'n', long, default_value = "20")]
+    limit: usize,
+    /// Output builds as JSON
+    #[arg(long)]
+    json: bool,
+}
 
-    // Actually install
-    pm.install(&packages, opts).await?;
+#[derive(Args)]
+struct ImpactArgs {
+    /// Package name or category/name
+    package: String,
+    /// Only list direct dependents
+    #[arg(long)]
+    direct: bool,
+}
 
-    println!(
-        "\n{} {} packages installed",
-        style(">>>").green().bold(),
-        resolution.packages.len()
-    );
+#[derive(Args)]
+struct BootManifestArgs {
+    /// Packages to include (default: the installed system set)
+    packages: Vec<String>,
+    /// Where to write the manifest (default: /etc/buckos/boot-manifest
+    /// below the install root)
+    #[arg(short, long)]
+    output: Option<String>,
+}
 
-    Ok(())
+#[derive(Args)]
+struct PrefixArgs {
+    #[command(subcommand)]
+    command: PrefixCommand,
 }
 
-async fn cmd_remove(
-    pm: &PackageManager,
-    args: RemoveArgs,
-    emerge_opts: &EmergeOptions,
-) -> buckos_package::Result<()> {
-    // Expand package sets
-    let packages = expand_package_sets(pm, &args.packages).await?;
+#[derive(Subcommand)]
+enum PrefixCommand {
+    /// Show where the prefix keeps its files
+    Show {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the shell script putting the prefix on PATH and friends, e.g.
+    /// for `eval "$(buckos --user prefix env)"`
+    Env,
+}
 
-    let opts = RemoveOptions {
-        force: args.force,
-        recursive: args.recursive,
-    };
+#[derive(Args)]
+struct DbArgs {
+    #[command(subcommand)]
+    command: DbCommand,
+}
 
-    // Get packages that would be removed
-    let to_remove = pm.get_removal_list(&packages, &opts).await?;
+#[derive(Subcommand)]
+enum DbCommand {
+    /// Write packages, files, world set and history to a compressed archive
+    Backup {
+        /// Archive to write
+        output: String,
+    },
+    /// Replace the database with a backup, checking it against the filesystem
+    Restore {
+        /// Archive from `buckos db backup` or JSON from `buckos db export`
+        input: String,
+        /// Replace a database that already has packages installed
+        #[arg(long)]
+        force: bool,
+        /// Restore packages none of whose files are on disk
+        #[arg(long)]
+        keep_missing: bool,
+    },
+    /// Print the database in a portable format
+    Export {
+        /// Output format (json)
+        #[arg(short, long, default_value = "json")]
+        format: String,
+        /// Output file (stdout if not specified)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Check the database for corruption and lost or partial package rows
+    Check {
+        /// Output problems as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Rebuild damaged package entries from the plain-text package records
+    Repair,
+}
 
-    if to_remove.is_empty() {
-        println!("{} No packages to unmerge", style(">>>").yellow().bold());
-        return Ok(());
-    }
+#[derive(Args)]
+struct BuckArgs {
+    #[command(subcommand)]
+    command: BuckCommand,
+}
 
-    // Display unmerge list
-    println!(
-        "\n{} These are the packages that would be unmerged:\n",
-        style(">>>").red().bold()
-    );
+#[derive(Subcommand)]
+enum BuckCommand {
+    /// Show the daemon's process, isolation directory and version
+    Status {
+        /// Output status as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Stop the daemon and start a new one
+    Restart,
+}
 
-    for pkg in &to_remove {
-        println!(
-            "  {} {}/{}",
-            style("R").red().bold(),
-            style(&pkg.id.category).cyan(),
-            style(format!("{}-{}", &pkg.name, &pkg.version)).red()
-        );
-    }
+#[derive(Args)]
+struct GenDocsArgs {
+    /// Directory the documentation is written to
+    #[arg(long, default_value = "doc")]
+    out: std::path::PathBuf,
+    /// What to generate
+    #[arg(long, value_enum, default_value_t = DocFormat::All)]
+    format: DocFormat,
+}
 
-    println!(
-        "\n>>> Unmerging {} package(s)...",
-        style(to_remove.len()).bold()
-    );
+#[derive(Args)]
+struct GenUnitsArgs {
+    /// Directory the service definitions are written to
+    #[arg(long, default_value = "/etc/buckos/services")]
+    dir: std::path::PathBuf,
+    /// Print the definitions instead of writing them
+    #[arg(long)]
+    stdout: bool,
+    /// Replace existing definitions
+    #[arg(long)]
+    force: bool,
+    /// When to sync the repositories, as a calendar expression
+    #[arg(long)]
+    sync_at: Option<String>,
+    /// When to check installed packages for vulnerabilities
+    #[arg(long)]
+    audit_at: Option<String>,
+    /// When to check for updates
+    #[arg(long)]
+    updates_at: Option<String>,
+}
 
-    // Pretend mode
-    if emerge_opts.pretend {
-        return Ok(());
-    }
+#[derive(Args)]
+struct PeriodicArgs {
+    #[command(subcommand)]
+    task: PeriodicCommand,
+}
 
-    // Ask mode
-    if emerge_opts.ask {
-        if !Confirm::new()
-            .with_prompt("Would you like to unmerge these packages?")
-            .default(false)
-            .interact()?
-        {
-            println!("{}", style(">>> Exiting.").yellow().bold());
-            return Ok(());
-        }
-        println!();
-    }
+#[derive(Subcommand)]
+enum PeriodicCommand {
+    /// Sync the package repositories
+    Sync,
+    /// Check installed packages for known vulnerabilities
+    Audit,
+    /// Check for package updates
+    Updates,
+    /// Detect the hardware and refresh the USE flags recommended for it
+    Hardware,
+}
 
-    pm.remove(&packages, opts).await?;
+#[derive(Args)]
+struct StatusArgs {
+    /// Output status as JSON
+    #[arg(long)]
+    json: bool,
+    /// Print only what needs attention, as shown at login
+    #[arg(long, conflicts_with = "json")]
+    motd: bool,
+}
 
-    println!(
-        "{} {} packages unmerged",
-        style(">>>").green().bold(),
-        to_remove.len()
-    );
+#[derive(Args)]
+struct PluginsArgs {
+    #[command(subcommand)]
+    command: PluginsCommand,
+}
 
-    Ok(())
+#[derive(Subcommand)]
+enum PluginsCommand {
+    /// List loaded plugins, their hooks and commands
+    List,
 }
 
-async fn cmd_update(
-    pm: &PackageManager,
-    args: UpdateArgs,
-    emerge_opts: &EmergeOptions,
-) -> buckos_package::Result<()> {
-    // Expand package sets (default to @world if nothing specified)
-    let packages = if args.packages.is_empty() {
-        vec!["@world".to_string()]
-    } else {
-        args.packages.clone()
-    };
-    let expanded = expand_package_sets(pm, &packages).await?;
+#[derive(Subcommand)]
+enum MirrorsCommand {
+    /// List configured mirrors in the order they are tried, with health
+    List,
+    /// Re-download a random sample of distfiles from each mirror and verify them
+    Audit {
+        /// Distfiles to sample per mirror
+        #[arg(long, default_value = "5")]
+        sample: usize,
+        /// Keep auditing every this many seconds
+        #[arg(long)]
+        interval: Option<u64>,
+        /// Output results as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
 
-    let opts = UpdateOptions {
-        sync: !args.no_sync,
-        check_only: args.check || emerge_opts.pretend,
-        deep: emerge_opts.deep,
-        newuse: emerge_opts.newuse,
-        with_bdeps: args.with_bdeps,
-    };
+/// Configuration file to load: the workspace's, the one given with `-c`,
+/// or in per-user mode the user's if they have one
+fn config_file(
+    workspace: Option<&str>,
+    config: Option<&str>,
+    user: bool,
+) -> buckos_package::Result<Option<std::path::PathBuf>> {
+    Ok(match (workspace, config) {
+        (Some(name), _) => Some(WorkspaceManager::new().get(name)?.config_path()),
+        (None, Some(path)) => Some(std::path::PathBuf::from(path)),
+        (None, None) => user_config_file(user),
+    })
+}
 
-    // Sync first if requested
-    if opts.sync && !emerge_opts.quiet {
-        println!("{} Syncing repositories...", style(">>>").blue().bold());
-        pm.sync().await?;
+/// Configuration file of per-user mode, if the user has one
+fn user_config_file(user: bool) -> Option<std::path::PathBuf> {
+    if !user {
+        return None;
     }
+    let path = buckos_package::layout::XdgDirs::from_env()?
+        .config
+        .join("buckos/buckos.toml");
+    path.exists().then_some(path)
+}
 
-    if !emerge_opts.quiet {
-        println!("{} Calculating dependencies...", style(">>>").blue().bold());
-    }
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    buckos_package::i18n::init();
+    // Refined once the configuration is loaded
+    cli.color.unwrap_or_default().apply();
 
-    let packages_slice = if expanded.is_empty() {
-        None
-    } else {
-        Some(expanded.as_slice())
+    // Initialize logging
+    let filter = match cli.verbose {
+        0 if cli.quiet => "error",
+        0 => "warn",
+        1 => "info",
+        2 => "debug",
+        _ => "trace",
     };
 
-    // Get update resolution
-    let resolution = pm.get_update_resolution(packages_slice, &opts).await?;
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(filter)),
+        )
+        .with_target(false)
+        .with_ansi(console::colors_enabled())
+        .init();
 
-    if resolution.packages.is_empty() {
-        if !emerge_opts.quiet {
-            println!("\n{} @world set is up-to-date", style(">>>").green().bold());
+    // Workspace management and documentation don't need a package manager
+    let command = match cli.command {
+        Commands::Workspace(args) => {
+            return match cmd_workspace(args).await {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    error!("{}", e);
+                    ExitCode::FAILURE
+                }
+            };
         }
-        return Ok(());
-    }
-
-    // Display emerge-style list
-    print_emerge_list(&resolution, emerge_opts, "update")?;
-
-    // Pretend or check mode
-    if emerge_opts.pretend || args.check {
-        return Ok(());
-    }
+        Commands::GenDocs(args) => {
+            return match cmd_gen_docs(args) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    error!("{}", e);
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        command => command,
+    };
 
-    // Ask mode
-    if emerge_opts.ask {
-        if !Confirm::new()
-            .with_prompt("Would you like to merge these packages?")
-            .default(true)
-            .interact()?
-        {
-            println!("{}", style(">>> Exiting.").yellow().bold());
-            return Ok(());
+    // Load configuration
+    let loaded =
+        config_file(cli.workspace.as_deref(), cli.config.as_deref(), cli.user).and_then(|path| {
+            match path {
+                Some(path) => Ok((Config::load_from(&path)?, Some(path))),
+                None => Ok((Config::default(), None)),
+            }
+        });
+    let (mut config, config_path) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            error!("Failed to load config: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if cli.user {
+        config.layout.mode = LayoutMode::User;
+    }
+    cli.color.unwrap_or(config.theme.color).apply();
+    theme::set_palette(config.theme.palette);
+    match Layout::resolve(&config) {
+        Ok(layout) => layout.apply(&mut config),
+        Err(e) => {
+            error!("Failed to resolve the filesystem layout: {}", e);
+            return ExitCode::FAILURE;
         }
-        println!();
     }
 
-    pm.update(packages_slice, opts).await?;
+    // Checking and repairing the database must work when it cannot be opened
+    let command = match command {
+        Commands::Db(DbArgs {
+            command: DbCommand::Check { json },
+        }) => {
+            return match cmd_db_check(&config, json) {
+                Ok(true) => ExitCode::SUCCESS,
+                Ok(false) => ExitCode::from(2),
+                Err(e) => {
+                    error!("{}", e);
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Commands::Db(DbArgs {
+            command: DbCommand::Repair,
+        }) => {
+            return match cmd_db_repair(&config, cli.pretend, cli.ask) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    error!("{}", e);
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Commands::Buck(args) => {
+            return match cmd_buck(&config, args).await {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    error!("{}", e);
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Commands::GenUnits(args) => {
+            return match cmd_gen_units(args).await {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    error!("{}", e);
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Commands::Status(args) => {
+            return match cmd_status(args, &config) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    error!("{}", e);
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        command => command,
+    };
 
-    println!(
-        "\n{} {} packages updated",
-        style(">>>").green().bold(),
-        resolution.packages.len()
-    );
+    // Create package manager
+    let pkg_manager = match PackageManager::new(config).await {
+        Ok(pm) => pm,
+        Err(e) => {
+            error!("Failed to initialize package manager: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
 
-    Ok(())
-}
+    // Build global emerge options
+    let emerge_opts = EmergeOptions {
+        pretend: cli.pretend,
+        ask: cli.ask,
+        fetch_only: cli.fetch_only,
+        oneshot: cli.oneshot,
+        deep: cli.deep,
+        newuse: cli.newuse,
+        tree: cli.tree,
+        verbose: cli.verbose,
+        quiet: cli.quiet,
+        jobs: cli.jobs,
+        time_budget: cli.time_budget,
+        ..Default::default()
+    };
 
-async fn cmd_sync(pm: &PackageManager, args: SyncArgs) -> buckos_package::Result<()> {
-    if args.repos.is_empty() || args.all {
-        println!("{} Syncing all repositories...", style(">>>").blue().bold());
-        pm.sync().await?;
-    } else {
-        for repo in &args.repos {
-            println!(
-                "{} Syncing repository: {}...",
-                style(">>>").blue().bold(),
-                repo
-            );
-            pm.sync_repo(repo).await?;
+    // Drift checks report through the exit status
+    if let Commands::Converge(args) = &command {
+        if args.check {
+            return match cmd_converge_check(&pkg_manager, args).await {
+                Ok(true) => ExitCode::SUCCESS,
+                Ok(false) => ExitCode::from(2),
+                Err(e) => {
+                    error!("{}", e);
+                    ExitCode::FAILURE
+                }
+            };
         }
     }
-    println!("{} Sync complete", style(">>>").green().bold());
-    Ok(())
-}
-
-async fn cmd_search(pm: &PackageManager, args: SearchArgs) -> buckos_package::Result<()> {
-    let results = pm.search(&args.query).await?;
 
-    if results.is_empty() {
-        println!("No packages found matching '{}'", args.query);
-        return Ok(());
+    // Plugin subcommands report through their own exit status
+    if let Commands::External(args) = &command {
+        return match cmd_plugin_command(&pkg_manager, args) {
+            Ok(code) => ExitCode::from(code.clamp(0, 255) as u8),
+            Err(e) => {
+                error!("{}", e);
+                ExitCode::FAILURE
+            }
+        };
     }
 
-    println!("Found {} packages:\n", results.len());
+    // Transactions the command records get their resource usage reported
+    let last_transaction = pkg_manager
+        .history_id_at(chrono::Utc::now())
+        .await
+        .ok()
+        .flatten();
+    let quiet = cli.quiet;
 
-    for pkg in results {
-        println!(
-            "{}/{} {}",
-            style(&pkg.id.category).cyan(),
-            style(&pkg.id.name).green().bold(),
-            style(&pkg.version.to_string()).yellow()
-        );
-        println!("    {}", pkg.description);
+    // Execute command
+    let result = match command {
+        Commands::Install(args) => cmd_install(&pkg_manager, args, &emerge_opts).await,
+        Commands::ApplyPlan(args) => cmd_apply_plan(&pkg_manager, args, &emerge_opts).await,
+        Commands::Remove(args) => cmd_remove(&pkg_manager, args, &emerge_opts).await,
+        Commands::Update(args) => cmd_update(&pkg_manager, args, &emerge_opts).await,
+        Commands::Sync(args) => cmd_sync(&pkg_manager, args).await,
+        Commands::Search(args) => cmd_search(&pkg_manager, args).await,
+        Commands::Eix(args) => cmd_eix(&pkg_manager, args).await,
+        Commands::Info(args) => cmd_info(&pkg_manager, args).await,
+        Commands::List(args) => cmd_list(&pkg_manager, args).await,
+        Commands::Build(args) => cmd_build(&pkg_manager, args).await,
+        Commands::Clean(args) => cmd_clean(&pkg_manager, args).await,
+        Commands::Verify(args) => cmd_verify(&pkg_manager, args).await,
+        Commands::Query(args) => cmd_query(&pkg_manager, args).await,
+        Commands::Owner(args) => cmd_owner(&pkg_manager, args).await,
+        Commands::Depgraph(args) => cmd_depgraph(&pkg_manager, args).await,
+        Commands::Config => cmd_config().await,
+        Commands::Depclean(args) => cmd_depclean(&pkg_manager, args, &emerge_opts).await,
+        Commands::Resume => cmd_resume(&pkg_manager).await,
+        Commands::Newuse(args) => cmd_newuse(&pkg_manager, args, &emerge_opts).await,
+        Commands::Audit { json } => cmd_audit(&pkg_manager, json).await,
+        Commands::Useflags(args) => cmd_useflags(&pkg_manager, args).await,
+        Commands::Detect(args) => cmd_detect(args, pkg_manager.config()).await,
+        Commands::Configure(args) => cmd_configure(args, pkg_manager.config()).await,
+        Commands::Set(args) => cmd_set(&pkg_manager, args, &emerge_opts).await,
+        Commands::Patch(args) => cmd_patch(&pkg_manager, args).await,
+        Commands::Deps(args) => cmd_deps(&pkg_manager, args).await,
+        Commands::Rdeps(args) => cmd_rdeps(&pkg_manager, args).await,
+        Commands::Profile(args) => cmd_profile(&pkg_manager, args).await,
+        Commands::Export(args) => cmd_export(&pkg_manager, args).await,
+        Commands::Apply(args) => {
+            cmd_apply(&pkg_manager, args, &emerge_opts, config_path.as_deref()).await
+        }
+        Commands::Converge(args) => {
+            let args = ApplyArgs {
+                manifest: args.manifest,
+                keep: args.keep,
+            };
+            cmd_apply(&pkg_manager, args, &emerge_opts, config_path.as_deref()).await
+        }
+        Commands::Revdep(args) => cmd_revdep(&pkg_manager, args, &emerge_opts).await,
+        #[cfg(feature = "signing")]
+        Commands::Sign(args) => cmd_sign(args, pkg_manager.config()).await,
+        #[cfg(feature = "signing")]
+        Commands::Keys(args) => cmd_keys(args, pkg_manager.config()),
+        Commands::Verity(args) => cmd_verity(&pkg_manager, args).await,
+        Commands::Overlay(args) => cmd_overlay(pkg_manager.layout(), args).await,
+        Commands::World(args) => cmd_world(&pkg_manager, args, &emerge_opts).await,
+        Commands::Workspace(_)
+        | Commands::GenDocs(_)
+        | Commands::Buck(_)
+        | Commands::GenUnits(_)
+        | Commands::Status(_) => {
+            unreachable!("handled before package manager setup")
+        }
+        Commands::Debuginfod(args) => cmd_debuginfod(&pkg_manager, args).await,
+        Commands::Serve(args) => cmd_serve(&pkg_manager, args).await,
+        Commands::Mirrors(args) => cmd_mirrors(&pkg_manager, args).await,
+        Commands::History(args) => cmd_history(&pkg_manager, args).await,
+        #[cfg(feature = "binary-packages")]
+        Commands::Undo(args) => cmd_undo(&pkg_manager, args, &emerge_opts).await,
+        #[cfg(feature = "binary-packages")]
+        Commands::Binpkg(args) => cmd_binpkg(&pkg_manager, args, &emerge_opts).await,
+        Commands::BuildReport(args) => cmd_build_report(&pkg_manager, args).await,
+        Commands::Log(args) => cmd_log(&pkg_manager, args).await,
+        Commands::Impact(args) => cmd_impact(&pkg_manager, args).await,
+        Commands::BootManifest(args) => cmd_boot_manifest(&pkg_manager, args).await,
+        Commands::Db(args) => cmd_db(&pkg_manager, args, &emerge_opts).await,
+        Commands::Prefix(args) => cmd_prefix(&pkg_manager, args),
+        Commands::Periodic(args) => cmd_periodic(&pkg_manager, args).await,
+        Commands::Plugins(args) => cmd_plugins(&pkg_manager, args),
+        Commands::External(_) => unreachable!("handled before dispatch"),
+    };
+    if !quiet {
+        report_transaction_usage(&pkg_manager, last_transaction).await;
     }
 
-    Ok(())
-}
-
-async fn cmd_info(pm: &PackageManager, args: InfoArgs) -> buckos_package::Result<()> {
-    match pm.info(&args.package).await? {
-        Some(pkg) => {
-            println!("{}", style("Package Information").bold().underlined());
-            println!();
-            println!(
-                "  {}: {}/{}",
-                style("Name").bold(),
-                pkg.id.category,
-                pkg.id.name
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(buckos_package::Error::Interrupted { signal }) => {
+            eprintln!(
+                "{} {}",
+                theme::warning(">>>").bold(),
+                tr!("interrupted", signal = interrupt::signal_name(signal))
             );
-            println!("  {}: {}", style("Version").bold(), pkg.version);
-            println!("  {}: {}", style("Slot").bold(), pkg.slot);
-            println!("  {}: {}", style("License").bold(), pkg.license);
-            if let Some(homepage) = &pkg.homepage {
-                println!("  {}: {}", style("Homepage").bold(), homepage);
+            if matches!(pkg_manager.resume_state(), Ok(Some(_))) {
+                eprintln!(
+                    "{} {}",
+                    theme::info(">>>").bold(),
+                    tr!("interrupted-resume")
+                );
             }
-            println!("  {}: {}", style("Description").bold(), pkg.description);
+            ExitCode::from(interrupt::exit_code(signal))
+        }
+        Err(e) => {
+            error!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
 
-            if !pkg.use_flags.is_empty() {
-                println!("  {}:", style("USE flags").bold());
-                for flag in &pkg.use_flags {
-                    println!("    {} - {}", style(&flag.name).cyan(), flag.description);
-                }
-            }
+async fn cmd_install(
+    pm: &PackageManager,
+    args: InstallArgs,
+    emerge_opts: &EmergeOptions,
+) -> buckos_package::Result<()> {
+    // Expand package sets
+    let packages = expand_package_sets(pm, &args.packages).await?;
 
-            if !pkg.dependencies.is_empty() {
-                println!("  {}:", style("Dependencies").bold());
-                for dep in &pkg.dependencies {
-                    println!("    {}", dep.package);
-                }
-            }
+    let opts = InstallOptions {
+        force: args.force,
+        no_deps: args.no_deps,
+        build: args.build,
+        use_flags: args.use_flags,
+        oneshot: emerge_opts.oneshot,
+        fetch_only: emerge_opts.fetch_only,
+        deep: emerge_opts.deep,
+        newuse: emerge_opts.newuse,
+        empty_tree: args.empty_tree,
+        no_replace: args.no_replace,
+        use_pkg: emerge_opts.use_pkg,
+        use_pkg_only: emerge_opts.use_pkg_only,
+        get_binpkg: emerge_opts.get_binpkg,
+        get_binpkg_only: emerge_opts.get_binpkg_only,
+        build_pkg: emerge_opts.build_pkg,
+        build_pkg_only: emerge_opts.build_pkg_only,
+    };
+
+    // Resolve dependencies first to show what will be installed
+    let resolution = pm.resolve_packages(&packages, &opts).await?;
 
+    if resolution.packages.is_empty() {
+        if !emerge_opts.quiet {
             println!(
-                "  {}: {}",
-                style("Size").bold(),
-                format_size(pkg.installed_size)
+                "\n{} {}",
+                theme::success(">>>").bold(),
+                theme::success(tr!("install-none")).bold()
             );
         }
-        None => {
-            println!("Package '{}' not found", args.package);
-        }
+        return Ok(());
     }
 
-    Ok(())
-}
-
-async fn cmd_list(pm: &PackageManager, args: ListArgs) -> buckos_package::Result<()> {
-    let packages = pm.list_installed().await?;
-
-    let filtered: Vec<_> = if args.explicit {
-        packages.into_iter().filter(|p| p.explicit).collect()
-    } else {
-        packages
-    };
+    // Display emerge-style package list
+    print_emerge_list(&resolution, emerge_opts, "install")?;
+    print_build_estimate(pm, &resolution, emerge_opts).await?;
 
-    if filtered.is_empty() {
-        println!("No packages installed");
+    if let Some(path) = &args.plan_out {
+        let plan = ResolutionPlan::capture(pm.config(), &packages, &opts, &resolution)?;
+        plan.save(std::path::Path::new(path))?;
+        println!(
+            "\n{} {}",
+            theme::success(">>>").bold(),
+            tr!(
+                "plan-written",
+                hash = plan.short_hash(),
+                path = path.as_str()
+            )
+        );
         return Ok(());
     }
 
-    println!("Installed packages ({}):\n", filtered.len());
+    // Pretend mode - just show what would be done
+    if emerge_opts.pretend {
+        print_transaction_preview(pm, &resolution, emerge_opts).await?;
+        return Ok(());
+    }
 
-    for pkg in filtered {
-        if args.size {
-            println!(
-                "{}/{} {} [{}]",
-                style(&pkg.id.category).cyan(),
-                style(&pkg.name).green(),
-                style(&pkg.version.to_string()).yellow(),
-                format_size(pkg.size)
-            );
-        } else {
+    // Ask mode - prompt for confirmation
+    if emerge_opts.ask {
+        if !Confirm::new()
+            .with_prompt(tr!("confirm-merge"))
+            .default(true)
+            .interact()?
+        {
             println!(
-                "{}/{} {}",
-                style(&pkg.id.category).cyan(),
-                style(&pkg.name).green(),
-                style(&pkg.version.to_string()).yellow()
+                "{} {}",
+                theme::warning(">>>").bold(),
+                theme::warning(tr!("exiting")).bold()
             );
+            return Ok(());
         }
+        println!();
     }
 
-    Ok(())
-}
+    // Actually install
+    pm.install(&packages, opts).await?;
 
-async fn cmd_build(pm: &PackageManager, args: BuildArgs) -> buckos_package::Result<()> {
     println!(
-        "{} Building target: {}",
-        style(">>>").blue().bold(),
-        args.target
+        "\n{} {}",
+        theme::success(">>>").bold(),
+        tr!("install-done", count = resolution.packages.len())
     );
 
-    let opts = BuildOptions {
-        jobs: args.jobs,
-        release: args.release,
-        buck_args: args.buck_args,
-        config_options: None,
-    };
+    Ok(())
+}
 
-    let result = pm.build(&args.target, opts).await?;
+async fn cmd_apply_plan(
+    pm: &PackageManager,
+    args: ApplyPlanArgs,
+    emerge_opts: &EmergeOptions,
+) -> buckos_package::Result<()> {
+    let plan = ResolutionPlan::load(std::path::Path::new(&args.plan))?;
+    println!(
+        "{} {}",
+        theme::success(">>>").bold(),
+        tr!(
+            "plan-checking",
+            hash = plan.short_hash(),
+            created = plan.created.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+        )
+    );
+    let resolution = pm.check_plan(&plan).await?;
 
-    if result.success {
+    if resolution.packages.is_empty() {
         println!(
-            "{} Build successful in {:?}",
-            style(">>>").green().bold(),
-            result.duration
+            "\n{} {}",
+            theme::success(">>>").bold(),
+            theme::success(tr!("install-none")).bold()
         );
-        if let Some(path) = result.output_path {
-            println!("  Output: {}", path.display());
-        }
-    } else {
-        println!("{} Build failed", style(">>>").red().bold());
-        if !result.stderr.is_empty() {
-            eprintln!("{}", result.stderr);
-        }
+        return Ok(());
     }
 
-    Ok(())
-}
+    print_emerge_list(&resolution, emerge_opts, "install")?;
 
-async fn cmd_clean(pm: &PackageManager, args: CleanArgs) -> buckos_package::Result<()> {
-    let opts = CleanOptions {
-        all: args.all,
-        downloads: args.downloads,
-        builds: args.builds,
-    };
+    if emerge_opts.pretend {
+        print_transaction_preview(pm, &resolution, emerge_opts).await?;
+        return Ok(());
+    }
 
-    pm.clean(opts).await?;
-    println!("{} Cache cleaned", style(">>>").green().bold());
+    if emerge_opts.ask
+        && !Confirm::new()
+            .with_prompt(tr!("confirm-merge"))
+            .default(true)
+            .interact()?
+    {
+        println!(
+            "{} {}",
+            theme::warning(">>>").bold(),
+            theme::warning(tr!("exiting")).bold()
+        );
+        return Ok(());
+    }
 
-    Ok(())
-}
+    pm.install(&plan.requested, plan.options.clone()).await?;
 
-async fn cmd_verify(pm: &PackageManager) -> buckos_package::Result<()> {
     println!(
-        "{} Verifying installed packages...",
-        style(">>>").blue().bold()
+        "\n{} {}",
+        theme::success(">>>").bold(),
+        tr!("install-done", count = resolution.packages.len())
     );
+    Ok(())
+}
 
-    let results = pm.verify().await?;
-
-    let mut all_ok = true;
-    for result in &results {
-        if !result.ok {
-            all_ok = false;
-            println!(
-                "{}: {}",
-                style(&result.package).red().bold(),
-                if !result.missing.is_empty() {
-                    format!("{} missing files", result.missing.len())
-                } else {
-                    format!("{} modified files", result.modified.len())
-                }
-            );
-        }
+async fn cmd_remove(
+    pm: &PackageManager,
+    args: RemoveArgs,
+    emerge_opts: &EmergeOptions,
+) -> buckos_package::Result<()> {
+    // Expand package sets
+    let packages = expand_package_sets(pm, &args.packages).await?;
+
+    let opts = RemoveOptions {
+        force: args.force,
+        recursive: args.recursive,
+    };
+
+    // Get packages that would be removed
+    let to_remove = pm.get_removal_list(&packages, &opts).await?;
+
+    if to_remove.is_empty() {
+        println!("{} {}", theme::warning(">>>").bold(), tr!("unmerge-none"));
+        return Ok(());
     }
 
-    if all_ok {
+    // Display unmerge list
+    println!(
+        "\n{} {}\n",
+        theme::error(">>>").bold(),
+        tr!("would-be-unmerged")
+    );
+
+    for pkg in &to_remove {
         println!(
-            "{} All {} packages verified successfully",
-            style(">>>").green().bold(),
-            results.len()
+            "  {} {}/{}",
+            theme::paint(Role::Remove, "R").bold(),
+            theme::accent(&pkg.id.category),
+            theme::error(format!("{}-{}", &pkg.name, &pkg.version))
         );
-    } else {
-        println!("{} Verification found issues", style(">>>").yellow().bold());
     }
 
-    Ok(())
-}
+    println!(
+        "\n>>> {}",
+        tr!(
+            "unmerging",
+            count = theme::plain(to_remove.len()).bold().to_string()
+        )
+    );
 
-async fn cmd_query(pm: &PackageManager, args: QueryArgs) -> buckos_package::Result<()> {
-    match args.query_type {
-        QueryType::Files { package } => {
-            let installed = pm.list_installed().await?;
-            if let Some(pkg) = installed.iter().find(|p| p.name == package) {
-                println!("Files owned by {}:\n", package);
-                for file in &pkg.files {
-                    println!("  {}", file.path);
-                }
-            } else {
-                println!("Package '{}' not installed", package);
-            }
-        }
-        QueryType::Deps { package } => {
-            if let Some(pkg) = pm.info(&package).await? {
-                println!("Dependencies of {}:\n", package);
-                for dep in &pkg.dependencies {
-                    println!("  {}", dep.package);
-                }
-                for dep in &pkg.runtime_dependencies {
-                    println!("  {} (runtime)", dep.package);
-                }
-            } else {
-                println!("Package '{}' not found", package);
-            }
-        }
-        QueryType::Rdeps { package } => {
-            let rdeps = pm.get_reverse_dependencies(&package).await?;
-            if rdeps.is_empty() {
-                println!("No packages depend on '{}'", package);
-            } else {
-                println!("Packages that depend on {}:\n", package);
-                for rdep in rdeps {
-                    println!("  {}", rdep);
-                }
-            }
+    // Pretend mode
+    if emerge_opts.pretend {
+        print_removal_preview(pm, &to_remove, emerge_opts).await?;
+        return Ok(());
+    }
+
+    // Ask mode
+    if emerge_opts.ask {
+        if !Confirm::new()
+            .with_prompt(tr!("confirm-unmerge"))
+            .default(false)
+            .interact()?
+        {
+            println!(
+                "{} {}",
+                theme::warning(">>>").bold(),
+                theme::warning(tr!("exiting")).bold()
+            );
+            return Ok(());
         }
+        println!();
     }
 
-    Ok(())
-}
+    pm.remove(&packages, opts).await?;
 
-async fn cmd_owner(pm: &PackageManager, args: OwnerArgs) -> buckos_package::Result<()> {
     println!(
-        "{} Searching for owner of: {}",
-        style(">>>").blue().bold(),
-        args.path
+        "{} {}",
+        theme::success(">>>").bold(),
+        tr!("unmerge-done", count = to_remove.len())
     );
 
-    // First try exact match
-    if let Some(result) = pm.find_file_owner(&args.path).await? {
+    Ok(())
+}
+
+async fn cmd_update(
+    pm: &PackageManager,
+    args: UpdateArgs,
+    emerge_opts: &EmergeOptions,
+) -> buckos_package::Result<()> {
+    // Expand package sets (default to @world if nothing specified)
+    let packages = if args.packages.is_empty() {
+        vec!["@world".to_string()]
+    } else {
+        args.packages.clone()
+    };
+    let expanded = expand_package_sets(pm, &packages).await?;
+
+    let opts = UpdateOptions {
+        sync: !args.no_sync,
+        check_only: args.check || emerge_opts.pretend,
+        deep: emerge_opts.deep,
+        newuse: emerge_opts.newuse,
+        with_bdeps: args.with_bdeps,
+        live: args.live,
+    };
+
+    // Sync first if requested
+    if opts.sync && !emerge_opts.quiet {
         println!(
-            "\n{}/{} {} owns {}",
-            style(&result.package.category).cyan(),
-            style(&result.package.name).green().bold(),
-            style(format!("({})", result.version)).yellow(),
-            result.file_path
+            "{} {}",
+            theme::info(">>>").bold(),
+            tr!("syncing-repositories")
         );
-        return Ok(());
+        pm.sync().await?;
     }
 
-    // If no exact match, try pattern search
-    let results = pm.find_file_owners_by_pattern(&args.path).await?;
-
-    if results.is_empty() {
+    if !emerge_opts.quiet {
         println!(
-            "{} No package owns '{}'",
-            style(">>>").yellow().bold(),
-            args.path
+            "{} {}",
+            theme::info(">>>").bold(),
+            tr!("calculating-dependencies")
         );
+    }
+
+    let packages_slice = if expanded.is_empty() {
+        None
     } else {
-        println!("\nFound {} matching file(s):\n", results.len());
-        for result in results {
+        Some(expanded.as_slice())
+    };
+
+    if opts.live && !emerge_opts.quiet {
+        for check in pm.check_live(packages_slice).await? {
+            if !check.needs_rebuild() {
+                continue;
+            }
             println!(
-                "  {} {}/{} {}",
-                result.file_path,
-                style(&result.package.category).cyan(),
-                style(&result.package.name).green().bold(),
-                style(format!("({})", result.version)).yellow()
+                "    {} {} -> {}{}",
+                theme::success(check.package.id.full_name()),
+                check
+                    .installed
+                    .as_deref()
+                    .map(|c| &c[..c.len().min(12)])
+                    .unwrap_or(&tr!("update-unknown")),
+                &check.target[..check.target.len().min(12)],
+                if check.pinned {
+                    format!(" {}", tr!("update-pinned"))
+                } else {
+                    String::new()
+                }
+            );
+        }
+    }
+
+    // Get update resolution
+    let resolution = pm.get_update_resolution(packages_slice, &opts).await?;
+
+    if resolution.packages.is_empty() {
+        if !emerge_opts.quiet {
+            println!(
+                "\n{} {}",
+                theme::success(">>>").bold(),
+                tr!("world-up-to-date")
+            );
+        }
+        return Ok(());
+    }
+
+    // Display emerge-style list
+    print_emerge_list(&resolution, emerge_opts, "update")?;
+    print_build_estimate(pm, &resolution, emerge_opts).await?;
+
+    // Pretend or check mode
+    if emerge_opts.pretend || args.check {
+        if emerge_opts.pretend {
+            print_transaction_preview(pm, &resolution, emerge_opts).await?;
+        }
+        return Ok(());
+    }
+
+    // Ask mode
+    if emerge_opts.ask {
+        if !Confirm::new()
+            .with_prompt(tr!("confirm-merge"))
+            .default(true)
+            .interact()?
+        {
+            println!(
+                "{} {}",
+                theme::warning(">>>").bold(),
+                theme::warning(tr!("exiting")).bold()
             );
+            return Ok(());
         }
+        println!();
     }
 
+    pm.update(packages_slice, opts).await?;
+
+    println!(
+        "\n{} {}",
+        theme::success(">>>").bold(),
+        tr!("update-done", count = resolution.packages.len())
+    );
+
     Ok(())
 }
 
-async fn cmd_depgraph(pm: &PackageManager, args: DepgraphArgs) -> buckos_package::Result<()> {
-    if let Some(pkg) = pm.info(&args.package).await? {
-        println!("Dependency graph for {}:\n", args.package);
-        print_deps(
-            &pkg.dependencies
-                .iter()
-                .map(|d| d.package.to_string())
-                .collect::<Vec<_>>(),
-            0,
-            args.depth,
+async fn cmd_sync(pm: &PackageManager, args: SyncArgs) -> buckos_package::Result<()> {
+    if args.repos.is_empty() || args.all {
+        println!(
+            "{} {}",
+            theme::info(">>>").bold(),
+            tr!("syncing-all-repositories")
         );
+        pm.sync().await?;
     } else {
-        println!("Package '{}' not found", args.package);
+        for repo in &args.repos {
+            println!(
+                "{} {}",
+                theme::info(">>>").bold(),
+                tr!("syncing-repository", repo = repo.as_str())
+            );
+            pm.sync_repo(repo).await?;
+        }
     }
+    println!("{} {}", theme::success(">>>").bold(), tr!("sync-complete"));
+    send_notifications(pm, false).await;
     Ok(())
 }
 
-fn print_deps(deps: &[String], level: usize, max_depth: usize) {
-    if level >= max_depth {
-        return;
+/// Send new advisories and unread news to the configured notifiers,
+/// reporting failures without failing the command
+async fn send_notifications(pm: &PackageManager, quiet: bool) {
+    match pm.notify().await {
+        Ok(report) if !report.sent.is_empty() && !quiet => println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!("notifications-sent", count = report.sent.len())
+        ),
+        Ok(_) => {}
+        Err(e) => error!("Failed to send notifications: {}", e),
     }
+}
 
-    for dep in deps {
-        let indent = "  ".repeat(level);
-        println!("{}|- {}", indent, dep);
+async fn cmd_search(pm: &PackageManager, args: SearchArgs) -> buckos_package::Result<()> {
+    let results = pm.search(&args.query).await?;
+
+    if results.is_empty() {
+        println!("{}", tr!("search-none", query = args.query.as_str()));
+        return Ok(());
     }
-}
 
-async fn cmd_config() -> buckos_package::Result<()> {
-    let config = Config::default();
+    println!("{}\n", tr!("search-found", count = results.len()));
 
-    println!("{}", style("Current Configuration").bold().underlined());
-    println!();
-    println!("  Root: {}", config.root.display());
-    println!("  DB Path: {}", config.db_path.display());
-    println!("  Cache Dir: {}", config.cache_dir.display());
-    println!("  Buck Path: {}", config.buck_path.display());
-    println!("  Parallelism: {}", config.parallelism);
-    println!("  Architecture: {}", config.arch);
-    println!("  CHOST: {}", config.chost);
-    println!("  CFLAGS: {}", config.cflags);
-    println!("  MAKEOPTS: {}", config.makeopts);
+    for pkg in results {
+        println!(
+            "{}/{} {}",
+            theme::accent(&pkg.id.category),
+            theme::success(&pkg.id.name).bold(),
+            theme::warning(&pkg.version.to_string())
+        );
+        println!("    {}", pkg.description);
+    }
 
     Ok(())
 }
 
-fn format_size(bytes: u64) -> String {
-    const KB: u64 = 1024;
-    const MB: u64 = KB * 1024;
-    const GB: u64 = MB * 1024;
-
-    if bytes >= GB {
-        format!("{:.2} GB", bytes as f64 / GB as f64)
-    } else if bytes >= MB {
-        format!("{:.2} MB", bytes as f64 / MB as f64)
-    } else if bytes >= KB {
-        format!("{:.2} KB", bytes as f64 / KB as f64)
-    } else {
-        format!("{} B", bytes)
+async fn cmd_eix(pm: &PackageManager, args: EixArgs) -> buckos_package::Result<()> {
+    if args.update {
+        let count = pm.update_eix_cache().await?;
+        eprintln!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!("eix-cached-packages", count = count.to_string())
+        );
+    }
+    let filter = EixFilter::parse(&args.query.join(" "))?;
+    let cache = pm.eix_cache().await?;
+    if cache.is_stale(&pm.config().db_path) {
+        eprintln!(
+            "{} {}",
+            theme::warning("!!!").bold(),
+            tr!("eix-installed-state-may-be")
+        );
     }
-}
 
-/// Expand package sets (@world, @system, @selected) to individual packages
-async fn expand_package_sets(
-    pm: &PackageManager,
-    packages: &[String],
-) -> buckos_package::Result<Vec<String>> {
-    let mut result = Vec::new();
+    let matches: Vec<_> = cache.query(&filter).collect();
+    if args.count {
+        println!("{}", matches.len());
+        return Ok(());
+    }
+    if let Some(template) = &args.format {
+        for entry in &matches {
+            println!("{}", entry.format(template));
+        }
+        return Ok(());
+    }
 
-    for pkg in packages {
-        if pkg.starts_with('@') {
-            match pkg.as_str() {
-                "@world" => {
-                    let world = pm.get_world_set().await?;
-                    result.extend(world.packages.iter().map(|p| p.full_name()));
-                }
-                "@system" => {
-                    let system = pm.get_system_set().await?;
-                    result.extend(system.packages.iter().map(|p| p.full_name()));
-                }
-                "@selected" => {
-                    let selected = pm.get_selected_set().await?;
-                    result.extend(selected.packages.iter().map(|p| p.full_name()));
-                }
-                "@installed" => {
-                    let installed = pm.list_installed().await?;
-                    result.extend(installed.iter().map(|p| p.id.full_name()));
-                }
-                _ => {
-                    // Unknown set, treat as literal
-                    result.push(pkg.clone());
-                }
-            }
+    for entry in &matches {
+        let marker = if entry.is_upgradable() {
+            theme::accent("[U]").bold()
+        } else if !entry.installed.is_empty() {
+            theme::success("[I]").bold()
         } else {
-            result.push(pkg.clone());
+            theme::success("*").bold()
+        };
+        println!(
+            "{} {}/{}",
+            marker,
+            theme::accent(&entry.id.category),
+            theme::success(&entry.id.name).bold()
+        );
+        println!(
+            "     {}",
+            tr!(
+                "eix-available-versions",
+                versions = theme::warning(entry.versions.join(" ")).to_string()
+            )
+        );
+        if !entry.installed.is_empty() {
+            println!(
+                "     {}",
+                tr!(
+                    "eix-installed-versions",
+                    installed = entry.installed.join(" ")
+                )
+            );
+        }
+        if !entry.homepage.is_empty() {
+            println!(
+                "     {}",
+                tr!("eix-homepage", homepage = entry.homepage.to_string())
+            );
         }
+        println!(
+            "     {}",
+            tr!(
+                "eix-description",
+                description = entry.description.to_string()
+            )
+        );
+        println!();
     }
-
-    Ok(result)
+    println!("{}", tr!("eix-found-matches", count = matches.len()));
+    Ok(())
 }
 
-/// Print emerge-style package list with colors and USE flags
-fn print_emerge_list(
-    resolution: &Resolution,
-    opts: &EmergeOptions,
-    action: &str,
-) -> buckos_package::Result<()> {
-    println!(
-        "\n{} These are the packages that would be {}:\n",
-        style(">>>").green().bold(),
-        match action {
-            "install" => "merged",
-            "update" => "merged",
-            "rebuild" => "rebuilt",
-            _ => action,
+async fn cmd_info(pm: &PackageManager, args: InfoArgs) -> buckos_package::Result<()> {
+    if args.json {
+        match pm.package_details(&args.package).await? {
+            Some(details) => println!(
+                "{}",
+                serde_json::to_string_pretty(&details).unwrap_or_default()
+            ),
+            None => println!(
+                "{}",
+                tr!("info-package-not-found", package = args.package.to_string())
+            ),
         }
-    );
-
-    // Calculate counts
-    let new_count = resolution.packages.iter().filter(|p| !p.is_upgrade).count();
-    let update_count = resolution.packages.iter().filter(|p| p.is_upgrade).count();
-    let rebuild_count = resolution.packages.iter().filter(|p| p.is_rebuild).count();
-
-    for (idx, pkg) in resolution.packages.iter().enumerate() {
-        // Determine status marker
-        let marker = if pkg.is_rebuild {
-            style("R").yellow().bold() // Rebuild
-        } else if pkg.is_upgrade {
-            style("U").blue().bold() // Update
-        } else {
-            style("N").green().bold() // New
-        };
-
-        // Build slot string
-        let slot = if pkg.slot != "0" {
-            format!(":{}", pkg.slot)
-        } else {
-            String::new()
-        };
+        return Ok(());
+    }
 
-        // Print package line
-        print!(
-            "[{:>3}] {} {}/{}",
-            idx + 1,
-            marker,
-            style(&pkg.id.category).cyan(),
-            style(format!("{}-{}{}", &pkg.id.name, &pkg.version, slot)).bold()
-        );
+    match pm.info(&args.package).await? {
+        Some(pkg) => {
+            println!(
+                "{}",
+                theme::plain(tr!("info-package-information"))
+                    .bold()
+                    .underlined()
+            );
+            println!();
+            println!(
+                "  {}: {}/{}",
+                theme::plain(tr!("info-name")).bold(),
+                pkg.id.category,
+                pkg.id.name
+            );
+            println!(
+                "  {}: {}",
+                theme::plain(tr!("info-version")).bold(),
+                pkg.version
+            );
+            println!("  {}: {}", theme::plain(tr!("info-slot")).bold(), pkg.slot);
+            println!(
+                "  {}: {}",
+                theme::plain(tr!("info-license")).bold(),
+                pkg.license
+            );
+            if let Some(homepage) = &pkg.homepage {
+                println!(
+                    "  {}: {}",
+                    theme::plain(tr!("info-homepage")).bold(),
+                    homepage
+                );
+            }
+            println!(
+                "  {}: {}",
+                theme::plain(tr!("info-description")).bold(),
+                pkg.description
+            );
 
-        // Show USE flags if verbose or tree mode
-        if (opts.verbose > 0 || opts.tree) && !pkg.use_flags.is_empty() {
-            print!(" USE=\"");
-            for (i, flag) in pkg.use_flags.iter().enumerate() {
-                if i > 0 {
-                    print!(" ");
+            if !pkg.use_flags.is_empty() {
+                println!("  {}:", theme::plain(tr!("info-use-flags")).bold());
+                for flag in &pkg.use_flags {
+                    println!("    {} - {}", theme::accent(&flag.name), flag.description);
                 }
-                if flag.enabled {
-                    print!("{}", style(&flag.name).green());
-                } else {
-                    print!("{}", style(format!("-{}", flag.name)).red());
+            }
+
+            if !pkg.dependencies.is_empty() {
+                println!("  {}:", theme::plain(tr!("info-dependencies")).bold());
+                for dep in &pkg.dependencies {
+                    println!("    {}", dep.package);
                 }
             }
-            print!("\"");
-        }
 
-        // Show size if verbose
-        if opts.verbose > 1 {
-            print!(" [{}]", format_size(pkg.installed_size));
+            println!(
+                "  {}: {}",
+                theme::plain(tr!("info-size")).bold(),
+                format_size(pkg.installed_size)
+            );
         }
-
-        println!();
-
-        // Show tree if requested
-        if opts.tree && !pkg.dependencies.is_empty() {
-            for dep in &pkg.dependencies {
-                println!("      └── {}", dep.package);
-            }
+        None => {
+            println!(
+                "{}",
+                tr!("info-package-not-found", package = args.package.to_string())
+            );
         }
     }
 
-    // Print summary
-    println!();
-    println!(
-        "Total: {} package(s)",
-        style(resolution.packages.len()).bold()
-    );
-    if new_count > 0 {
-        print!("{} new, ", style(new_count).green());
-    }
-    if update_count > 0 {
-        print!("{} updates, ", style(update_count).blue());
-    }
-    if rebuild_count > 0 {
-        print!("{} rebuilds, ", style(rebuild_count).yellow());
-    }
-    println!();
-
-    // Size totals
-    println!(
-        "Download size: {}",
-        style(format_size(resolution.download_size)).cyan()
-    );
-    println!(
-        "Space required: {}",
-        style(format_size(resolution.install_size)).cyan()
-    );
-
     Ok(())
 }
 
-/// Depclean command - remove unused packages
-async fn cmd_depclean(
-    pm: &PackageManager,
-    args: DepcleanArgs,
-    emerge_opts: &EmergeOptions,
-) -> buckos_package::Result<()> {
-    println!("{} Calculating dependencies...", style(">>>").blue().bold());
+async fn cmd_list(pm: &PackageManager, args: ListArgs) -> buckos_package::Result<()> {
+    let packages = pm.list_installed().await?;
 
-    let opts = DepcleanOptions {
-        packages: args.packages.clone(),
-        pretend: args.pretend || emerge_opts.pretend,
+    let filtered: Vec<_> = if args.explicit {
+        packages.into_iter().filter(|p| p.explicit).collect()
+    } else {
+        packages
     };
 
-    let to_remove = pm.calculate_depclean(&opts).await?;
-
-    if to_remove.is_empty() {
-        println!("{} No packages to depclean", style(">>>").green().bold());
+    if filtered.is_empty() {
+        println!("{}", tr!("list-no-packages-installed"));
         return Ok(());
     }
 
-    // Display packages to remove
-    println!(
-        "\n{} These are the packages that would be unmerged:\n",
-        style(">>>").red().bold()
-    );
-
-    let mut total_size = 0u64;
-    for pkg in &to_remove {
-        println!(
-            "  {} {}/{}",
-            style("D").red().bold(),
-            style(&pkg.id.category).cyan(),
-            style(format!("{}-{}", &pkg.name, &pkg.version)).red()
-        );
-        total_size += pkg.size;
-    }
-
     println!(
-        "\n>>> {} package(s) selected for depclean",
-        style(to_remove.len()).bold()
+        "{}\n",
+        tr!("list-installed-packages", count = filtered.len())
     );
-    println!(">>> Space freed: {}", style(format_size(total_size)).cyan());
 
-    // Pretend mode
-    if opts.pretend || emerge_opts.pretend {
-        return Ok(());
-    }
-
-    // Ask mode
-    if emerge_opts.ask {
-        if !Confirm::new()
-            .with_prompt("Would you like to unmerge these packages?")
-            .default(false)
-            .interact()?
-        {
-            println!("{}", style(">>> Exiting.").yellow().bold());
-            return Ok(());
+    for pkg in filtered {
+        if args.size {
+            println!(
+                "{}/{} {} [{}]",
+                theme::accent(&pkg.id.category),
+                theme::success(&pkg.name),
+                theme::warning(&pkg.version.to_string()),
+                format_size(pkg.size)
+            );
+        } else {
+            println!(
+                "{}/{} {}",
+                theme::accent(&pkg.id.category),
+                theme::success(&pkg.name),
+                theme::warning(&pkg.version.to_string())
+            );
         }
-        println!();
     }
 
-    // Actually remove
-    pm.depclean(&opts).await?;
+    Ok(())
+}
 
+async fn cmd_build(pm: &PackageManager, args: BuildArgs) -> buckos_package::Result<()> {
     println!(
-        "{} {} packages unmerged",
-        style(">>>").green().bold(),
-        to_remove.len()
+        "{} {}",
+        theme::info(">>>").bold(),
+        tr!("build-building-target", target = args.target.to_string())
     );
 
-    Ok(())
-}
+    let opts = BuildOptions {
+        jobs: args.jobs,
+        release: args.release,
+        buck_args: args.buck_args,
+        config_options: None,
+        cancellation: Some(Cancellation::new().catch_signals()),
+    };
 
-/// Resume interrupted operation
-async fn cmd_resume(pm: &PackageManager) -> buckos_package::Result<()> {
-    println!("{} Resuming last operation...", style(">>>").blue().bold());
+    let result = pm.build(&args.target, opts).await?;
 
-    if pm.resume().await? {
-        println!("{} Resume complete", style(">>>").green().bold());
-    } else {
+    if result.success {
         println!(
-            "{} No interrupted operation to resume",
-            style(">>>").yellow().bold()
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!(
+                "build-successful-in",
+                duration = format!("{:?}", result.duration)
+            )
         );
+        if let Some(path) = result.output_path {
+            println!(
+                "  {}",
+                tr!("build-output", path = path.display().to_string())
+            );
+        }
+    } else {
+        println!("{} {}", theme::error(">>>").bold(), tr!("build-failed"));
+        if !result.stderr.is_empty() {
+            eprintln!("{}", result.stderr);
+        }
     }
 
     Ok(())
 }
 
-/// Rebuild packages with changed USE flags
-async fn cmd_newuse(
-    pm: &PackageManager,
-    args: NewuseArgs,
-    emerge_opts: &EmergeOptions,
-) -> buckos_package::Result<()> {
+async fn cmd_clean(pm: &PackageManager, args: CleanArgs) -> buckos_package::Result<()> {
+    let opts = CleanOptions {
+        all: args.all,
+        downloads: args.downloads,
+        builds: args.builds,
+    };
+
+    pm.clean(opts).await?;
     println!(
-        "{} Checking for USE flag changes...",
-        style(">>>").blue().bold()
+        "{} {}",
+        theme::success(">>>").bold(),
+        tr!("clean-cache-cleaned")
     );
 
-    let packages = if args.packages.is_empty() {
-        None
-    } else {
-        Some(args.packages.as_slice())
-    };
+    Ok(())
+}
 
-    let to_rebuild = pm.find_newuse_packages(packages, args.deep).await?;
-
-    if to_rebuild.is_empty() {
-        println!(
-            "{} No packages need rebuilding",
-            style(">>>").green().bold()
-        );
-        return Ok(());
-    }
-
-    // Display packages to rebuild
+async fn cmd_verify(pm: &PackageManager, args: VerifyArgs) -> buckos_package::Result<()> {
     println!(
-        "\n{} These packages have USE flag changes:\n",
-        style(">>>").yellow().bold()
+        "{} {}",
+        theme::info(">>>").bold(),
+        tr!("verify-verifying-installed-packages")
     );
 
-    for pkg in &to_rebuild {
-        println!(
-            "  {} {}/{}",
-            style("R").yellow().bold(),
-            style(&pkg.id.category).cyan(),
-            style(format!("{}-{}", &pkg.name, &pkg.version)).yellow()
-        );
+    let report = pm
+        .verify_with(&VerifyOptions {
+            paranoid: args.paranoid,
+        })
+        .await?;
+    let results = &report.results;
 
-        // Show USE flag changes
-        if !pkg.use_changes.is_empty() {
-            print!("      USE: ");
-            for (i, change) in pkg.use_changes.iter().enumerate() {
-                if i > 0 {
-                    print!(" ");
-                }
-                if change.added {
-                    print!("{}", style(format!("+{}", change.flag)).green());
+    let mut all_ok = true;
+    for result in results {
+        if !result.ok {
+            all_ok = false;
+            println!(
+                "{}: {}",
+                theme::error(&result.package).bold(),
+                if !result.missing.is_empty() {
+                    tr!("verify-missing-files", count = result.missing.len())
                 } else {
-                    print!("{}", style(format!("-{}", change.flag)).red());
+                    tr!("verify-modified-files", count = result.modified.len())
                 }
-            }
-            println!();
+            );
         }
     }
 
+    if all_ok {
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!(
+                "verify-all-packages-verified-successfully",
+                count = results.len()
+            )
+        );
+    } else {
+        println!(
+            "{} {}",
+            theme::warning(">>>").bold(),
+            tr!("verify-verification-found-issues")
+        );
+    }
     println!(
-        "\n>>> {} package(s) with USE flag changes",
-        style(to_rebuild.len()).bold()
+        "    {}",
+        tr!(
+            "verify-file-hashed-unchanged-since",
+            hashed = report.hashed.to_string(),
+            skipped = report.skipped.to_string(),
+            verity = report.verity.to_string()
+        )
     );
 
-    // Pretend mode
-    if emerge_opts.pretend {
-        return Ok(());
-    }
+    Ok(())
+}
 
-    // Ask mode
-    if emerge_opts.ask {
-        if !Confirm::new()
-            .with_prompt("Would you like to rebuild these packages?")
-            .default(true)
-            .interact()?
-        {
-            println!("{}", style(">>> Exiting.").yellow().bold());
-            return Ok(());
+async fn cmd_query(pm: &PackageManager, args: QueryArgs) -> buckos_package::Result<()> {
+    match args.query_type {
+        QueryType::Files { package } => {
+            let installed = pm.list_installed().await?;
+            if let Some(pkg) = installed.iter().find(|p| p.name == package) {
+                println!(
+                    "{}\n",
+                    tr!("query-files-owned-by", package = package.to_string())
+                );
+                for file in &pkg.files {
+                    println!("  {}", file.path);
+                }
+            } else {
+                println!(
+                    "{}",
+                    tr!("query-package-not-installed", package = package.to_string())
+                );
+            }
+        }
+        QueryType::Deps { package } => {
+            if let Some(pkg) = pm.info(&package).await? {
+                println!(
+                    "{}\n",
+                    tr!("query-dependencies-of", package = package.to_string())
+                );
+                for dep in &pkg.dependencies {
+                    println!("  {}", dep.package);
+                }
+                for dep in &pkg.runtime_dependencies {
+                    println!(
+                        "  {}",
+                        tr!("query-runtime", package = dep.package.to_string())
+                    );
+                }
+            } else {
+                println!(
+                    "{}",
+                    tr!("info-package-not-found", package = package.to_string())
+                );
+            }
+        }
+        QueryType::Rdeps { package } => {
+            let rdeps = pm.get_reverse_dependencies(&package).await?;
+            if rdeps.is_empty() {
+                println!(
+                    "{}",
+                    tr!("query-no-packages-depend-on", package = package.to_string())
+                );
+            } else {
+                println!(
+                    "{}\n",
+                    tr!(
+                        "query-packages-that-depend-on",
+                        package = package.to_string()
+                    )
+                );
+                for rdep in rdeps {
+                    println!("  {}", rdep);
+                }
+            }
         }
-        println!();
     }
 
-    // Rebuild packages
-    let pkg_names: Vec<String> = to_rebuild.iter().map(|p| p.id.full_name()).collect();
-
-    let opts = InstallOptions {
-        force: true,
-        ..Default::default()
-    };
-
-    pm.install(&pkg_names, opts).await?;
-
-    println!(
-        "{} {} packages rebuilt",
-        style(">>>").green().bold(),
-        to_rebuild.len()
-    );
-
     Ok(())
 }
 
-/// Audit for security vulnerabilities
-async fn cmd_audit(pm: &PackageManager) -> buckos_package::Result<()> {
+async fn cmd_owner(pm: &PackageManager, args: OwnerArgs) -> buckos_package::Result<()> {
     println!(
-        "{} Checking for security vulnerabilities...",
-        style(">>>").blue().bold()
+        "{} {}",
+        theme::info(">>>").bold(),
+        tr!("owner-searching-for-owner-of", path = args.path.to_string())
     );
 
-    let vulnerabilities = pm.audit().await?;
-
-    if vulnerabilities.is_empty() {
+    // First try exact match
+    if let Some(result) = pm.find_file_owner(&args.path).await? {
         println!(
-            "{} No known vulnerabilities found",
-            style(">>>").green().bold()
+            "\n{}",
+            tr!(
+                "owner-owns",
+                category = theme::accent(&result.package.category).to_string(),
+                name = theme::success(&result.package.name).bold().to_string(),
+                version = theme::warning(format!("({})", result.version)).to_string(),
+                file_path = result.file_path.to_string()
+            )
         );
         return Ok(());
     }
 
-    println!(
-        "\n{} Found {} security issue(s):\n",
-        style(">>>").red().bold(),
-        vulnerabilities.len()
-    );
+    // If no exact match, try pattern search
+    let results = pm.find_file_owners_by_pattern(&args.path).await?;
 
-    for vuln in &vulnerabilities {
+    if results.is_empty() {
         println!(
-            "  {} {}/{} - {}",
-            match vuln.severity.as_str() {
-                "critical" => style("!").red().bold(),
-                "high" => style("!").red(),
-                "medium" => style("*").yellow(),
-                _ => style("*").white(),
-            },
-            style(&vuln.package.category).cyan(),
-            style(&vuln.package.name).bold(),
-            vuln.id
+            "{} {}",
+            theme::warning(">>>").bold(),
+            tr!("owner-no-package-owns", path = args.path.to_string())
         );
-        if !vuln.title.is_empty() {
-            println!("    {}", vuln.title);
+    } else {
+        println!(
+            "\n{}\n",
+            tr!("owner-found-matching-file", count = results.len())
+        );
+        for result in results {
+            println!(
+                "  {} {}/{} {}",
+                result.file_path,
+                theme::accent(&result.package.category),
+                theme::success(&result.package.name).bold(),
+                theme::warning(format!("({})", result.version))
+            );
         }
     }
 
-    println!(
-        "\n>>> Run '{} install <package>' to update affected packages",
-        style("buckos").bold()
-    );
-
     Ok(())
 }
 
-/// USE flags management command
-async fn cmd_useflags(_pm: &PackageManager, args: UseflagsArgs) -> buckos_package::Result<()> {
-    match args.subcommand {
-        UseflagsCommand::List {
-            category,
-            global,
-            verbose,
-        } => cmd_useflags_list(category, global, verbose).await,
-        UseflagsCommand::Info { flag } => cmd_useflags_info(&flag).await,
-        UseflagsCommand::Set { flags } => cmd_useflags_set(&flags).await,
-        UseflagsCommand::Get { format } => cmd_useflags_get(&format).await,
-        UseflagsCommand::Package { package, flags } => cmd_useflags_package(&package, &flags).await,
-        UseflagsCommand::Expand { variable } => cmd_useflags_expand(variable).await,
-        UseflagsCommand::Validate => cmd_useflags_validate().await,
-    }
-}
-
-/// List available USE flags
-async fn cmd_useflags_list(
-    category: Option<String>,
-    _global: bool,
-    verbose: bool,
-) -> buckos_package::Result<()> {
-    // Define categorized USE flags
-    let flags_by_category = get_use_flags_by_category();
-
-    if let Some(cat) = category {
-        // Show only requested category
-        if let Some(flags) = flags_by_category.get(&cat) {
-            println!(
-                "{}",
-                style(format!("USE Flags: {}", cat)).bold().underlined()
-            );
-            println!();
-            for (flag, description) in flags {
-                if verbose {
-                    println!("  {} - {}", style(flag).green(), description);
-                } else {
-                    print!("{} ", style(flag).green());
-                }
-            }
-            if !verbose {
-                println!();
-            }
-        } else {
-            println!("{} Unknown category: {}", style(">>>").yellow().bold(), cat);
-            println!("\nAvailable categories:");
-            for cat in flags_by_category.keys() {
-                println!("  - {}", cat);
-            }
-        }
+async fn cmd_depgraph(pm: &PackageManager, args: DepgraphArgs) -> buckos_package::Result<()> {
+    if let Some(pkg) = pm.info(&args.package).await? {
+        println!(
+            "{}\n",
+            tr!(
+                "depgraph-dependency-graph-for",
+                package = args.package.to_string()
+            )
+        );
+        print_deps(
+            &pkg.dependencies
+                .iter()
+                .map(|d| d.package.to_string())
+                .collect::<Vec<_>>(),
+            0,
+            args.depth,
+        );
     } else {
-        // Show all categories
         println!(
             "{}",
-            style("Available USE Flags by Category").bold().underlined()
+            tr!("info-package-not-found", package = args.package.to_string())
         );
-        println!();
-
-        for (cat, flags) in &flags_by_category {
-            println!("{}", style(cat).cyan().bold());
-            for (flag, description) in flags {
-                if verbose {
-                    println!("  {} - {}", style(flag).green(), description);
-                } else {
-                    print!("  {}", style(flag).green());
-                }
-            }
-            if !verbose {
-                println!();
-            }
-            println!();
-        }
     }
-
     Ok(())
 }
 
-/// Get USE flags organized by category
-fn get_use_flags_by_category() -> HashMap<String, Vec<(&'static str, &'static str)>> {
-    let mut categories = HashMap::new();
+fn print_deps(deps: &[String], level: usize, max_depth: usize) {
+    if level >= max_depth {
+        return;
+    }
+
+    for dep in deps {
+        let indent = "  ".repeat(level);
+        println!("{}|- {}", indent, dep);
+    }
+}
 
-    categories.insert(
-        "build".to_string(),
-        vec![
-            ("debug", "Enable debugging symbols and assertions"),
-            ("doc", "Build and install documentation"),
-            ("examples", "Install example files"),
-            ("static", "Build static libraries"),
-            ("test", "Enable test suite during build"),
-            ("lto", "Enable Link Time Optimization"),
-        ],
-    );
+async fn cmd_config() -> buckos_package::Result<()> {
+    let config = Config::default();
 
-    categories.insert(
-        "security".to_string(),
-        vec![
-            ("hardened", "Enable security hardening features"),
-            ("pie", "Build position independent executables"),
-            ("ssp", "Enable stack smashing protection"),
-            ("caps", "Use Linux capabilities library"),
-            ("seccomp", "Enable seccomp sandboxing"),
-            ("selinux", "Enable SELinux support"),
-        ],
+    println!(
+        "{}",
+        theme::plain(tr!("config-current-configuration"))
+            .bold()
+            .underlined()
     );
-
-    categories.insert(
-        "network".to_string(),
-        vec![
-            ("ipv6", "Enable IPv6 support"),
-            ("ssl", "Enable SSL/TLS support (OpenSSL)"),
-            ("gnutls", "Enable GnuTLS support"),
-            ("http2", "Enable HTTP/2 support"),
-            ("curl", "Use libcurl for HTTP operations"),
-        ],
+    println!();
+    println!(
+        "  {}",
+        tr!("config-root", root = config.root.display().to_string())
     );
-
-    categories.insert(
-        "compression".to_string(),
-        vec![
-            ("zlib", "Enable zlib compression"),
-            ("bzip2", "Enable bzip2 compression"),
-            ("zstd", "Enable Zstandard compression"),
-            ("lz4", "Enable LZ4 compression"),
-            ("brotli", "Enable Brotli compression"),
-        ],
+    println!(
+        "  {}",
+        tr!(
+            "config-db-path",
+            db_path = config.db_path.display().to_string()
+        )
     );
-
-    categories.insert(
-        "graphics".to_string(),
-        vec![
-            ("X", "Enable X11 support"),
-            ("wayland", "Enable Wayland support"),
-            ("opengl", "Enable OpenGL support"),
-            ("vulkan", "Enable Vulkan support"),
-            ("gtk", "Enable GTK+ toolkit"),
-            ("qt5", "Enable Qt5 toolkit"),
-            ("qt6", "Enable Qt6 toolkit"),
-        ],
+    println!(
+        "  {}",
+        tr!(
+            "config-cache-dir",
+            cache_dir = config.cache_dir.display().to_string()
+        )
     );
-
-    categories.insert(
-        "audio".to_string(),
-        vec![
-            ("alsa", "Enable ALSA audio support"),
-            ("pulseaudio", "Enable PulseAudio support"),
-            ("pipewire", "Enable PipeWire support"),
-            ("ffmpeg", "Enable FFmpeg support"),
-        ],
+    println!(
+        "  {}",
+        tr!(
+            "config-buck-path",
+            buck_path = config.buck_path.display().to_string()
+        )
     );
-
-    categories.insert(
-        "language".to_string(),
-        vec![
-            ("python", "Build Python bindings"),
-            ("perl", "Build Perl bindings"),
-            ("ruby", "Build Ruby bindings"),
-            ("lua", "Build Lua bindings"),
-        ],
+    println!(
+        "  {}",
+        tr!(
+            "config-parallelism",
+            parallelism = config.parallelism.to_string()
+        )
     );
-
-    categories.insert(
-        "system".to_string(),
-        vec![
-            ("dbus", "Enable D-Bus support"),
-            ("systemd", "Enable systemd integration"),
-            ("pam", "Enable PAM authentication"),
-            ("acl", "Enable Access Control Lists"),
-            ("udev", "Enable udev device management"),
-        ],
+    println!(
+        "  {}",
+        tr!("config-architecture", arch = config.arch.to_string())
     );
+    println!("  CHOST: {}", config.chost);
+    println!("  CFLAGS: {}", config.cflags);
+    println!("  MAKEOPTS: {}", config.makeopts);
 
-    categories
+    Ok(())
 }
 
-/// Show information about a specific USE flag
-async fn cmd_useflags_info(flag: &str) -> buckos_package::Result<()> {
-    let flags_by_category = get_use_flags_by_category();
-
-    for (category, flags) in &flags_by_category {
-        for (name, description) in flags {
-            if *name == flag {
-                println!("{}", style("USE Flag Information").bold().underlined());
-                println!();
-                println!("  {}: {}", style("Flag").bold(), style(name).green());
-                println!("  {}: {}", style("Category").bold(), category);
-                println!("  {}: {}", style("Description").bold(), description);
-                return Ok(());
-            }
+/// Parse a duration like `45s`, `90m`, `2h` or `1h30m`; bare numbers are minutes
+fn parse_time_budget(s: &str) -> Result<std::time::Duration, String> {
+    let invalid = || format!("invalid duration '{}'", s);
+    if let Ok(minutes) = s.parse::<u64>() {
+        return Ok(std::time::Duration::from_secs(minutes * 60));
+    }
+    let mut secs = 0;
+    let mut digits = String::new();
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
         }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            _ => return Err(invalid()),
+        };
+        secs += digits.parse::<u64>().map_err(|_| invalid())? * unit;
+        digits.clear();
     }
+    if !digits.is_empty() || secs == 0 {
+        return Err(invalid());
+    }
+    Ok(std::time::Duration::from_secs(secs))
+}
 
-    // Check USE_EXPAND variables
-    let expand_vars = get_use_expand_variables();
-    for (var_name, values) in &expand_vars {
-        if values.contains(&flag.to_string()) {
-            println!("{}", style("USE_EXPAND Variable").bold().underlined());
-            println!();
-            println!("  {}: {}", style("Value").bold(), style(flag).green());
-            println!("  {}: {}", style("Variable").bold(), var_name);
-            return Ok(());
+/// Print the estimated build time of a resolution, warning if it is over
+/// the --time-budget
+async fn print_build_estimate(
+    pm: &PackageManager,
+    resolution: &Resolution,
+    opts: &EmergeOptions,
+) -> buckos_package::Result<()> {
+    if opts.quiet && opts.time_budget.is_none() {
+        return Ok(());
+    }
+    let estimate = pm.estimate_build_time(resolution).await?;
+    if !opts.quiet && estimate.unknown() < estimate.packages.len() {
+        print!(
+            "\n{} Estimated build time: {}",
+            theme::info(">>>").bold(),
+            format_duration(estimate.total())
+        );
+        if estimate.unknown() > 0 {
+            print!(" (+{} packages never built here)", estimate.unknown());
+        }
+        println!();
+    }
+    if let Some(budget) = opts.time_budget {
+        if estimate.exceeds(budget) {
+            println!(
+                "{} {}",
+                theme::warning("!!!").bold(),
+                tr!(
+                    "print-build-estimate-estimated-build-time-exceeds",
+                    total = format_duration(estimate.total()).to_string(),
+                    budget = format_duration(budget).to_string()
+                )
+            );
         }
     }
-
-    println!(
-        "{} USE flag '{}' not found",
-        style(">>>").yellow().bold(),
-        flag
-    );
     Ok(())
 }
 
-/// Set global USE flags
-async fn cmd_useflags_set(flags: &[String]) -> buckos_package::Result<()> {
-    let config_path = dirs::config_dir()
-        .unwrap_or_else(|| std::path::PathBuf::from("/etc/buckos"))
-        .join("buckos")
-        .join("use.conf");
-
-    // Parse flags
-    let mut enabled = Vec::new();
-    let mut disabled = Vec::new();
+fn format_size(bytes: u64) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = KB * 1024;
+    const GB: u64 = MB * 1024;
 
-    for flag in flags {
-        if let Some(stripped) = flag.strip_prefix('-') {
-            disabled.push(stripped);
-        } else {
-            enabled.push(flag.as_str());
-        }
+    if bytes >= GB {
+        format!("{:.2} GB", bytes as f64 / GB as f64)
+    } else if bytes >= MB {
+        format!("{:.2} MB", bytes as f64 / MB as f64)
+    } else if bytes >= KB {
+        format!("{:.2} KB", bytes as f64 / KB as f64)
+    } else {
+        format!("{} B", bytes)
     }
+}
 
-    println!("{}", style("Setting USE flags").bold().underlined());
-    println!();
-
-    if !enabled.is_empty() {
-        println!("  {}: {}", style("Enabling").green(), enabled.join(" "));
-    }
-    if !disabled.is_empty() {
-        println!("  {}: {}", style("Disabling").red(), disabled.join(" "));
-    }
+/// Expand package sets (@world, @system, @selected) to individual packages
+async fn expand_package_sets(
+    pm: &PackageManager,
+    packages: &[String],
+) -> buckos_package::Result<Vec<String>> {
+    let mut result = Vec::new();
 
-    // Create config directory if it doesn't exist
-    if let Some(parent) = config_path.parent() {
-        fs::create_dir_all(parent).ok();
+    for pkg in packages {
+        if pkg.starts_with('@') {
+            match pkg.as_str() {
+                "@world" => {
+                    let world = pm.get_world_set().await?;
+                    result.extend(world.packages.iter().map(|p| p.full_name()));
+                }
+                "@system" => {
+                    let system = pm.get_system_set().await?;
+                    result.extend(system.packages.iter().map(|p| p.full_name()));
+                }
+                "@selected" => {
+                    let selected = pm.get_selected_set().await?;
+                    result.extend(selected.packages.iter().map(|p| p.full_name()));
+                }
+                "@installed" => {
+                    let installed = pm.list_installed().await?;
+                    result.extend(installed.iter().map(|p| p.id.full_name()));
+                }
+                _ => {
+                    // Unknown set, treat as literal
+                    result.push(pkg.clone());
+                }
+            }
+        } else {
+            result.push(pkg.clone());
+        }
     }
 
-    // Build USE string
-    let use_string = flags.join(" ");
-
-    // Write to config file
-    let content = format!(
-        "# BuckOs USE flags configuration\n# Generated by buckos useflags set\n\nUSE=\"{}\"\n",
-        use_string
-    );
+    Ok(result)
+}
 
-    match fs::write(&config_path, content) {
-        Ok(_) => {
-            println!();
-            println!(
-                "{} Configuration saved to: {}",
-                style(">>>").green().bold(),
-                config_path.display()
-            );
-        }
-        Err(e) => {
-            println!();
-            println!(
-                "{} Failed to save configuration: {}",
-                style(">>>").red().bold(),
-                e
-            );
-            println!("You may need to run with elevated privileges or set USE flags manually.");
-            println!();
-            println!("Add this to your make.conf or buckos config:");
-            println!("  USE=\"{}\"", use_string);
-        }
+/// Print the filesystem-level effect of a resolution (pretend mode)
+async fn print_transaction_preview(
+    pm: &PackageManager,
+    resolution: &Resolution,
+    opts: &EmergeOptions,
+) -> buckos_package::Result<()> {
+    if opts.quiet {
+        return Ok(());
     }
 
+    let preview = pm.preview_resolution(resolution).await?;
+    print_preview_report(&preview, opts);
     Ok(())
 }
 
-/// Get current USE flag configuration
-async fn cmd_useflags_get(format: &str) -> buckos_package::Result<()> {
-    let config = Config::default();
-
-    // Get USE flags from config
-    let use_flags: Vec<String> = vec![
-        "ssl".to_string(),
-        "http2".to_string(),
-        "ipv6".to_string(),
-        "zstd".to_string(),
-    ]; // Default example flags
-
-    match format {
-        "json" => {
-            let output = serde_json::json!({
-                "use_flags": use_flags,
-                "arch": config.arch,
-                "chost": config.chost,
-            });
-            println!(
-                "{}",
-                serde_json::to_string_pretty(&output).unwrap_or_default()
-            );
-        }
-        "toml" => {
-            println!("[use]");
-            println!("flags = {:?}", use_flags);
-            println!();
-            println!("[system]");
-            println!("arch = \"{}\"", config.arch);
-            println!("chost = \"{}\"", config.chost);
-        }
-        _ => {
-            println!("{}", style("Current USE Configuration").bold().underlined());
-            println!();
-            println!("  {}: {}", style("USE").bold(), use_flags.join(" "));
-            println!("  {}: {}", style("ARCH").bold(), config.arch);
-            println!("  {}: {}", style("CHOST").bold(), config.chost);
-            println!("  {}: {}", style("CFLAGS").bold(), config.cflags);
-        }
+/// Print the filesystem-level effect of removing packages (pretend mode)
+async fn print_removal_preview(
+    pm: &PackageManager,
+    packages: &[InstalledPackage],
+    opts: &EmergeOptions,
+) -> buckos_package::Result<()> {
+    if opts.quiet {
+        return Ok(());
     }
 
+    let preview = pm.preview_removal(packages).await?;
+    print_preview_report(&preview, opts);
     Ok(())
 }
 
-/// Set per-package USE flags
-async fn cmd_useflags_package(package: &str, flags: &[String]) -> buckos_package::Result<()> {
-    let config_path = dirs::config_dir()
-        .unwrap_or_else(|| std::path::PathBuf::from("/etc/buckos"))
-        .join("buckos")
-        .join("package.use");
-
-    // Create config directory if it doesn't exist
-    if let Some(parent) = config_path.parent() {
-        fs::create_dir_all(parent).ok();
-    }
-
-    // Format the package.use entry
-    let entry = format!("{} {}\n", package, flags.join(" "));
+fn print_preview_report(preview: &TransactionPreview, opts: &EmergeOptions) {
+    let report = format_preview_report(preview, opts.verbose > 0);
 
     println!(
-        "{}",
-        style("Setting per-package USE flags").bold().underlined()
+        "\n{} {}\n",
+        theme::success(">>>").bold(),
+        tr!("print-preview-report-transaction-preview")
     );
-    println!();
-    println!("  {}: {}", style("Package").bold(), package);
-    println!("  {}: {}", style("Flags").bold(), flags.join(" "));
+    for line in report.lines() {
+        println!("  {}", line);
+    }
+}
 
-    // Append to package.use file
-    match fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&config_path)
-    {
-        Ok(mut file) => {
-            if let Err(e) = file.write_all(entry.as_bytes()) {
-                println!();
-                println!(
-                    "{} Failed to write configuration: {}",
-                    style(">>>").red().bold(),
-                    e
-                );
-            } else {
-                println!();
-                println!(
-                    "{} Configuration saved to: {}",
-                    style(">>>").green().bold(),
-                    config_path.display()
-                );
-            }
-        }
-        Err(e) => {
-            println!();
-            println!(
-                "{} Failed to open configuration file: {}",
-                style(">>>").red().bold(),
-                e
-            );
-            println!("\nAdd this to your package.use:");
-            println!("  {}", entry.trim());
+/// Print emerge-style package list with colors and USE flags
+fn print_emerge_list(
+    resolution: &Resolution,
+    opts: &EmergeOptions,
+    action: &str,
+) -> buckos_package::Result<()> {
+    println!(
+        "\n{} {}\n",
+        theme::success(">>>").bold(),
+        match action {
+            "rebuild" => tr!("would-be-rebuilt"),
+            _ => tr!("would-be-merged"),
         }
-    }
+    );
 
-    Ok(())
-}
+    // Calculate counts
+    let new_count = resolution.packages.iter().filter(|p| !p.is_upgrade).count();
+    let update_count = resolution.packages.iter().filter(|p| p.is_upgrade).count();
+    let rebuild_count = resolution.packages.iter().filter(|p| p.is_rebuild).count();
 
-/// Show USE_EXPAND variables
-async fn cmd_useflags_expand(variable: Option<String>) -> buckos_package::Result<()> {
-    let expand_vars = get_use_expand_variables();
+    for (idx, pkg) in resolution.packages.iter().enumerate() {
+        // Determine status marker
+        let marker = if pkg.is_rebuild {
+            theme::paint(Role::Rebuild, "R").bold() // Rebuild
+        } else if pkg.is_upgrade {
+            theme::paint(Role::Update, "U").bold() // Update
+        } else {
+            theme::paint(Role::New, "N").bold() // New
+        };
 
-    if let Some(var) = variable {
-        if let Some(values) = expand_vars.get(&var.to_uppercase()) {
-            println!(
-                "{}",
-                style(var.to_uppercase().to_string()).bold().underlined()
-            );
-            println!();
-            for value in values {
-                println!("  {}", style(value).green());
-            }
+        // Build slot string
+        let slot = if pkg.slot != "0" {
+            format!(":{}", pkg.slot)
         } else {
-            println!(
-                "{} Unknown USE_EXPAND variable: {}",
-                style(">>>").yellow().bold(),
-                var
-            );
-            println!("\nAvailable variables:");
-            for var_name in expand_vars.keys() {
-                println!("  - {}", var_name);
+            String::new()
+        };
+
+        // Print package line
+        print!(
+            "[{:>3}] {} {}/{}",
+            idx + 1,
+            marker,
+            theme::accent(&pkg.id.category),
+            theme::plain(format!("{}-{}{}", &pkg.id.name, &pkg.version, slot)).bold()
+        );
+
+        // Show USE flags if verbose or tree mode
+        if (opts.verbose > 0 || opts.tree) && !pkg.use_flags.is_empty() {
+            print!(" USE=\"");
+            for (i, flag) in pkg.use_flags.iter().enumerate() {
+                if i > 0 {
+                    print!(" ");
+                }
+                if flag.enabled {
+                    print!("{}", theme::success(&flag.name));
+                } else {
+                    print!("{}", theme::error(format!("-{}", flag.name)));
+                }
             }
+            print!("\"");
         }
-    } else {
-        println!("{}", style("USE_EXPAND Variables").bold().underlined());
+
+        // Show size if verbose
+        if opts.verbose > 1 {
+            print!(" [{}]", format_size(pkg.installed_size));
+        }
+
         println!();
-        for (var_name, values) in &expand_vars {
-            println!("{}:", style(var_name).cyan().bold());
-            let values_str: Vec<&str> = values.iter().map(|s| s.as_str()).collect();
-            println!("  {}", values_str.join(" "));
-            println!();
+
+        // Show tree if requested
+        if opts.tree && !pkg.dependencies.is_empty() {
+            for dep in &pkg.dependencies {
+                println!("      └── {}", dep.package);
+            }
         }
     }
 
-    Ok(())
-}
-
-/// Get USE_EXPAND variables
-fn get_use_expand_variables() -> HashMap<String, Vec<String>> {
-    let mut vars = HashMap::new();
-
-    vars.insert(
-        "CPU_FLAGS_X86".to_string(),
-        vec![
-            "aes", "avx", "avx2", "avx512f", "avx512dq", "avx512cd", "avx512bw", "avx512vl", "mmx",
-            "mmxext", "pclmul", "popcnt", "sse", "sse2", "sse3", "ssse3", "sse4_1", "sse4_2",
-            "sse4a", "f16c", "fma", "fma4", "xop",
-        ]
-        .into_iter()
-        .map(String::from)
-        .collect(),
-    );
-
-    vars.insert(
-        "VIDEO_CARDS".to_string(),
-        vec![
-            "amdgpu",
-            "ast",
-            "dummy",
-            "fbdev",
-            "i915",
-            "i965",
-            "intel",
-            "mga",
-            "nouveau",
-            "nvidia",
-            "r128",
-            "r600",
-            "radeon",
-            "radeonsi",
-            "vesa",
-            "via",
-            "virtualbox",
-            "vmware",
-        ]
-        .into_iter()
-        .map(String::from)
-        .collect(),
-    );
-
-    vars.insert(
-        "INPUT_DEVICES".to_string(),
-        vec![
-            "evdev",
-            "joystick",
-            "keyboard",
-            "libinput",
-            "mouse",
-            "synaptics",
-            "vmmouse",
-            "wacom",
-        ]
-        .into_iter()
-        .map(String::from)
-        .collect(),
+    // Print summary
+    println!();
+    println!(
+        "{}",
+        tr!(
+            "list-total",
+            count = theme::plain(resolution.packages.len()).bold().to_string()
+        )
     );
+    if new_count > 0 {
+        print!(
+            "{}, ",
+            tr!("list-new", count = theme::success(new_count).to_string())
+        );
+    }
+    if update_count > 0 {
+        print!(
+            "{}, ",
+            tr!(
+                "list-updates",
+                count = theme::info(update_count).to_string()
+            )
+        );
+    }
+    if rebuild_count > 0 {
+        print!(
+            "{}, ",
+            tr!(
+                "list-rebuilds",
+                count = theme::warning(rebuild_count).to_string()
+            )
+        );
+    }
+    println!();
 
-    vars.insert(
-        "PYTHON_TARGETS".to_string(),
-        vec!["python3_10", "python3_11", "python3_12", "python3_13"]
-            .into_iter()
-            .map(String::from)
-            .collect(),
+    // Size totals
+    println!(
+        "{}",
+        tr!(
+            "download-size",
+            size = theme::accent(format_size(resolution.download_size)).to_string()
+        )
     );
-
-    vars.insert(
-        "RUBY_TARGETS".to_string(),
-        vec!["ruby31", "ruby32", "ruby33"]
-            .into_iter()
-            .map(String::from)
-            .collect(),
+    println!(
+        "{}",
+        tr!(
+            "space-required",
+            size = theme::accent(format_size(resolution.install_size)).to_string()
+        )
     );
-
-    vars
-}
-
-/// Validate USE flag configuration
-async fn cmd_useflags_validate() -> buckos_package::Result<()> {
     println!(
         "{}",
-        style("Validating USE flag configuration")
-            .bold()
-            .underlined()
+        tr!(
+            "plan-hash",
+            hash = theme::plain(&resolution.plan_hash()[..16])
+                .dim()
+                .to_string()
+        )
     );
-    println!();
-
-    let mut issues = Vec::new();
-
-    // Check for conflicting flags
-    let conflicts = vec![
-        ("systemd", "elogind"),
-        ("pulseaudio", "pipewire"),
-        ("ssl", "gnutls"),
-        ("gtk", "qt5"),
-    ];
-
-    // Simulate checking current config
-    let current_flags: HashSet<&str> = ["ssl", "http2", "systemd"].into_iter().collect();
+    if opts.verbose > 0 {
+        println!(
+            "{}",
+            tr!("resolution-stats", stats = resolution.stats.to_string())
+        );
+    }
 
-    for (flag1, flag2) in &conflicts {
-        if current_flags.contains(flag1) && current_flags.contains(flag2) {
-            issues.push(format!(
-                "Conflicting flags: {} and {} are both enabled",
-                flag1, flag2
-            ));
+    // Explain any-of choices
+    if !resolution.any_of_choices.is_empty() {
+        println!(
+            "\n{} {}",
+            theme::success(">>>").bold(),
+            tr!("any-of-choices")
+        );
+        for choice in &resolution.any_of_choices {
+            println!("  {}", choice.explain());
         }
     }
 
-    if issues.is_empty() {
-        println!("{} No issues found", style(">>>").green().bold());
-    } else {
+    if !resolution.from_host.is_empty() {
         println!(
-            "{} Found {} issue(s):",
-            style(">>>").yellow().bold(),
-            issues.len()
+            "\n{} {}",
+            theme::success(">>>").bold(),
+            tr!("used-from-system")
         );
-        for issue in issues {
-            println!("  - {}", issue);
+        for id in &resolution.from_host {
+            println!("  {}", id);
         }
     }
 
     Ok(())
 }
 
-/// System detection data
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct SystemDetection {
-    cpu_features: Vec<String>,
-    gpu_drivers: Vec<String>,
-    audio_systems: Vec<String>,
-    network_features: Vec<String>,
-    recommended_use_flags: Vec<String>,
+/// Per-user prefix commands
+fn cmd_prefix(pm: &PackageManager, args: PrefixArgs) -> buckos_package::Result<()> {
+    let layout = pm.layout();
+    if layout.mode != LayoutMode::User {
+        return Err(buckos_package::Error::Other(
+            "buckos prefix works on the per-user prefix; pass --user".to_string(),
+        ));
+    }
+    match args.command {
+        PrefixCommand::Show { json } => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(layout)?);
+                return Ok(());
+            }
+            println!(
+                "{}",
+                theme::plain(tr!("prefix-per-user-prefix"))
+                    .bold()
+                    .underlined()
+            );
+            println!(
+                "  {}",
+                tr!("prefix", root = layout.root.display().to_string())
+            );
+            println!(
+                "  {}",
+                tr!(
+                    "prefix-config",
+                    config_dir = layout.config_dir.display().to_string()
+                )
+            );
+            println!(
+                "  {}",
+                tr!(
+                    "prefix-state",
+                    state_dir = layout.state_dir.display().to_string()
+                )
+            );
+            println!(
+                "  {}",
+                tr!(
+                    "prefix-database",
+                    db_dir = layout.db_dir.display().to_string()
+                )
+            );
+            println!(
+                "  {}",
+                tr!(
+                    "prefix-cache",
+                    cache_dir = layout.cache_dir.display().to_string()
+                )
+            );
+            println!(
+                "  {}",
+                tr!(
+                    "prefix-repositories",
+                    repos_dir = layout.repos_dir.display().to_string()
+                )
+            );
+            match &layout.host_db {
+                Some(db) => println!("  {}", tr!("prefix-system", db = db.display().to_string())),
+                None => println!("  {}", tr!("prefix-system-not-used-isolated")),
+            }
+            println!(
+                "\n{}",
+                tr!(
+                    "prefix-source-from-your-shell",
+                    layout = theme::accent(
+                        layout
+                            .root
+                            .join(buckos_package::layout::ENV_SCRIPT)
+                            .display()
+                    )
+                    .to_string()
+                )
+            );
+        }
+        PrefixCommand::Env => print!("{}", layout.env_script()),
+    }
+    Ok(())
 }
 
-/// Detect system capabilities
-async fn cmd_detect(args: DetectArgs) -> buckos_package::Result<()> {
-    let detect_all = args.all || (!args.cpu && !args.gpu && !args.audio && !args.network);
+/// Depclean command - remove unused packages
+async fn cmd_depclean(
+    pm: &PackageManager,
+    args: DepcleanArgs,
+    emerge_opts: &EmergeOptions,
+) -> buckos_package::Result<()> {
+    println!(
+        "{} {}",
+        theme::info(">>>").bold(),
+        tr!("calculating-dependencies")
+    );
 
-    let mut detection = SystemDetection {
-        cpu_features: Vec::new(),
-        gpu_drivers: Vec::new(),
-        audio_systems: Vec::new(),
-        network_features: Vec::new(),
-        recommended_use_flags: Vec::new(),
+    let opts = DepcleanOptions {
+        packages: args.packages.clone(),
+        pretend: args.pretend || emerge_opts.pretend,
     };
 
-    if detect_all || args.cpu {
-        detection.cpu_features = detect_cpu_features();
-    }
-
-    if detect_all || args.gpu {
-        detection.gpu_drivers = detect_gpu();
-    }
-
-    if detect_all || args.audio {
-        detection.audio_systems = detect_audio();
-    }
+    let to_remove = pm.calculate_depclean(&opts).await?;
 
-    if detect_all || args.network {
-        detection.network_features = detect_network();
+    if to_remove.is_empty() {
+        println!("{} {}", theme::success(">>>").bold(), tr!("depclean-none"));
+        return Ok(());
     }
 
-    // Generate recommended USE flags based on detection
-    detection.recommended_use_flags = generate_recommended_flags(&detection);
-
-    // Output in requested format
-    let output = match args.format.as_str() {
-        "json" => serde_json::to_string_pretty(&detection).unwrap_or_default(),
-        "toml" => format_detection_toml(&detection),
-        "shell" => format_detection_shell(&detection),
-        _ => format_detection_text(&detection),
-    };
+    // Display packages to remove
+    println!(
+        "\n{} {}\n",
+        theme::error(">>>").bold(),
+        tr!("would-be-unmerged")
+    );
 
-    // Write to file or stdout
-    if let Some(path) = args.output {
-        fs::write(&path, &output)?;
+    let mut total_size = 0u64;
+    for pkg in &to_remove {
         println!(
-            "{} Detection results saved to: {}",
-            style(">>>").green().bold(),
-            path
+            "  {} {}/{}",
+            theme::paint(Role::Remove, "D").bold(),
+            theme::accent(&pkg.id.category),
+            theme::error(format!("{}-{}", &pkg.name, &pkg.version))
         );
-    } else {
-        println!("{}", output);
+        total_size += pkg.size;
     }
 
-    Ok(())
-}
-
-/// Detect CPU features
-fn detect_cpu_features() -> Vec<String> {
-    let mut features = Vec::new();
-
-    // Read /proc/cpuinfo on Linux
-    if let Ok(cpuinfo) = fs::read_to_string("/proc/cpuinfo") {
-        let cpu_flags = [
-            "aes", "avx", "avx2", "avx512f", "avx512dq", "avx512cd", "avx512bw", "avx512vl", "mmx",
-            "pclmul", "popcnt", "sse", "sse2", "sse3", "ssse3", "sse4_1", "sse4_2", "f16c", "fma",
-        ];
+    println!(
+        "\n>>> {}",
+        tr!(
+            "depclean-selected",
+            count = theme::plain(to_remove.len()).bold().to_string()
+        )
+    );
+    println!(
+        ">>> {}",
+        tr!(
+            "space-freed",
+            size = theme::accent(format_size(total_size)).to_string()
+        )
+    );
 
-        for flag in cpu_flags {
-            if cpuinfo.contains(flag) {
-                features.push(flag.to_string());
-            }
-        }
+    // Pretend mode
+    if opts.pretend || emerge_opts.pretend {
+        print_removal_preview(pm, &to_remove, emerge_opts).await?;
+        return Ok(());
     }
 
-    if features.is_empty() {
-        // Default to basic x86_64 features
-        features = vec!["sse".to_string(), "sse2".to_string(), "mmx".to_string()];
+    // Ask mode
+    if emerge_opts.ask {
+        if !Confirm::new()
+            .with_prompt(tr!("confirm-unmerge"))
+            .default(false)
+            .interact()?
+        {
+            println!(
+                "{} {}",
+                theme::warning(">>>").bold(),
+                theme::warning(tr!("exiting")).bold()
+            );
+            return Ok(());
+        }
+        println!();
     }
 
-    features
-}
+    // Actually remove
+    pm.depclean(&opts).await?;
 
-/// Detect GPU/video hardware
-fn detect_gpu() -> Vec<String> {
-    let mut drivers = Vec::new();
+    println!(
+        "{} {}",
+        theme::success(">>>").bold(),
+        tr!("unmerge-done", count = to_remove.len())
+    );
 
-    // Check for common GPU vendors
-    let checks = vec![
-        ("/sys/module/nvidia", "nvidia"),
-        ("/sys/module/amdgpu", "amdgpu"),
-        ("/sys/module/i915", "i915"),
-        ("/sys/module/nouveau", "nouveau"),
-        ("/sys/module/radeon", "radeon"),
-    ];
+    Ok(())
+}
 
-    for (path, driver) in checks {
-        if std::path::Path::new(path).exists() {
-            drivers.push(driver.to_string());
-        }
+/// Resume interrupted operation
+async fn cmd_resume(pm: &PackageManager) -> buckos_package::Result<()> {
+    match pm.resume_state()? {
+        Some(state) => println!(
+            "{} {}",
+            theme::info(">>>").bold(),
+            tr!("resuming-command", command = state.command.to_string())
+        ),
+        None => println!("{} {}", theme::info(">>>").bold(), tr!("resuming")),
     }
 
-    if drivers.is_empty() {
-        // Check lspci output if available
-        drivers.push("fbdev".to_string());
-        drivers.push("vesa".to_string());
+    if pm.resume().await? {
+        println!("{} {}", theme::success(">>>").bold(), tr!("resume-done"));
+    } else {
+        println!("{} {}", theme::warning(">>>").bold(), tr!("resume-none"));
     }
 
-    drivers
+    Ok(())
 }
 
-/// Detect audio systems
-fn detect_audio() -> Vec<String> {
-    let mut systems = Vec::new();
-
-    if std::path::Path::new("/proc/asound").exists() {
-        systems.push("alsa".to_string());
-    }
-
-    if std::path::Path::new("/run/user/1000/pulse").exists()
-        || std::path::Path::new("/var/run/pulse").exists()
-    {
-        systems.push("pulseaudio".to_string());
-    }
-
-    if std::path::Path::new("/run/user/1000/pipewire-0").exists() {
-        systems.push("pipewire".to_string());
-    }
-
-    if systems.is_empty() {
-        systems.push("alsa".to_string());
-    }
+/// Rebuild packages with changed USE flags
+async fn cmd_newuse(
+    pm: &PackageManager,
+    args: NewuseArgs,
+    emerge_opts: &EmergeOptions,
+) -> buckos_package::Result<()> {
+    println!(
+        "{} {}",
+        theme::info(">>>").bold(),
+        tr!("newuse-checking-for-use-flag")
+    );
 
-    systems
-}
+    let packages = if args.packages.is_empty() {
+        None
+    } else {
+        Some(args.packages.as_slice())
+    };
 
-/// Detect network features
-fn detect_network() -> Vec<String> {
-    let mut features = Vec::new();
+    let to_rebuild = pm.find_newuse_packages(packages, args.deep).await?;
 
-    // Check for IPv6 support
-    if std::path::Path::new("/proc/net/if_inet6").exists() {
-        features.push("ipv6".to_string());
+    if to_rebuild.is_empty() {
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!("newuse-no-packages-need-rebuilding")
+        );
+        return Ok(());
     }
 
-    // SSL/TLS is generally always available
-    features.push("ssl".to_string());
-    features.push("http2".to_string());
-
-    features
-}
-
-/// Generate recommended USE flags based on detection
-fn generate_recommended_flags(detection: &SystemDetection) -> Vec<String> {
-    let mut flags = Vec::new();
+    // Display packages to rebuild
+    println!(
+        "\n{} {}\n",
+        theme::warning(">>>").bold(),
+        tr!("newuse-these-packages-have-use")
+    );
 
-    // Add CPU flags
-    for feature in &detection.cpu_features {
-        flags.push(format!("cpu_flags_x86_{}", feature));
-    }
+    for pkg in &to_rebuild {
+        println!(
+            "  {} {}/{}",
+            theme::paint(Role::Rebuild, "R").bold(),
+            theme::accent(&pkg.id.category),
+            theme::warning(format!("{}-{}", &pkg.name, &pkg.version))
+        );
 
-    // Add GPU-related flags
-    if detection
-        .gpu_drivers
-        .iter()
-        .any(|d| d == "nvidia" || d == "amdgpu" || d == "i915")
-    {
-        flags.push("vulkan".to_string());
-        flags.push("opengl".to_string());
+        // Show USE flag changes
+        if !pkg.use_changes.is_empty() {
+            print!("      USE: ");
+            for (i, change) in pkg.use_changes.iter().enumerate() {
+                if i > 0 {
+                    print!(" ");
+                }
+                if change.added {
+                    print!("{}", theme::success(format!("+{}", change.flag)));
+                } else {
+                    print!("{}", theme::error(format!("-{}", change.flag)));
+                }
+            }
+            println!();
+        }
     }
 
-    // Add audio flags
-    for audio in &detection.audio_systems {
-        flags.push(audio.clone());
-    }
+    println!(
+        "\n>>> {}",
+        tr!(
+            "newuse-package-with-use-flag",
+            count = theme::plain(to_rebuild.len()).bold().to_string()
+        )
+    );
 
-    // Add network flags
-    for net in &detection.network_features {
-        flags.push(net.clone());
+    // Pretend mode
+    if emerge_opts.pretend {
+        return Ok(());
     }
 
-    // Add common flags
-    flags.push("zstd".to_string());
-    flags.push("dbus".to_string());
-
-    flags
-}
-
-/// Format detection output as text
-fn format_detection_text(detection: &SystemDetection) -> String {
-    let mut output = String::new();
-
-    output.push_str(&format!(
-        "{}\n\n",
-        style("System Detection Results").bold().underlined()
-    ));
-
-    if !detection.cpu_features.is_empty() {
-        output.push_str(&format!("{}:\n", style("CPU Features").cyan().bold()));
-        output.push_str(&format!("  {}\n\n", detection.cpu_features.join(" ")));
+    // Ask mode
+    if emerge_opts.ask {
+        if !Confirm::new()
+            .with_prompt("Would you like to rebuild these packages?")
+            .default(true)
+            .interact()?
+        {
+            println!(
+                "{} {}",
+                theme::warning(">>>").bold(),
+                theme::warning(tr!("exiting")).bold()
+            );
+            return Ok(());
+        }
+        println!();
     }
 
-    if !detection.gpu_drivers.is_empty() {
-        output.push_str(&format!("{}:\n", style("GPU Drivers").cyan().bold()));
-        output.push_str(&format!("  {}\n\n", detection.gpu_drivers.join(" ")));
-    }
+    // Rebuild packages
+    let pkg_names: Vec<String> = to_rebuild.iter().map(|p| p.id.full_name()).collect();
 
-    if !detection.audio_systems.is_empty() {
-        output.push_str(&format!("{}:\n", style("Audio Systems").cyan().bold()));
-        output.push_str(&format!("  {}\n\n", detection.audio_systems.join(" ")));
-    }
+    let opts = InstallOptions {
+        force: true,
+        ..Default::default()
+    };
 
-    if !detection.network_features.is_empty() {
-        output.push_str(&format!("{}:\n", style("Network Features").cyan().bold()));
-        output.push_str(&format!("  {}\n\n", detection.network_features.join(" ")));
-    }
+    pm.install(&pkg_names, opts).await?;
 
-    output.push_str(&format!(
-        "{}:\n",
-        style("Recommended USE Flags").green().bold()
-    ));
-    output.push_str(&format!(
-        "  {}\n",
-        detection.recommended_use_flags.join(" ")
-    ));
+    println!(
+        "{} {}",
+        theme::success(">>>").bold(),
+        tr!("newuse-packages-rebuilt", count = to_rebuild.len())
+    );
 
-    output
+    Ok(())
 }
 
-/// Format detection output as TOML
-fn format_detection_toml(detection: &SystemDetection) -> String {
-    format!(
-        r#"# BuckOs System Detection
-# Generated by buckos detect
-
-[cpu]
-features = {:?}
-
-[gpu]
-drivers = {:?}
-
-[audio]
-systems = {:?}
-
-[network]
-features = {:?}
+/// Audit for security vulnerabilities
+async fn cmd_audit(pm: &PackageManager, json: bool) -> buckos_package::Result<()> {
+    if json {
+        let vulnerabilities = pm.audit().await?;
+        send_notifications(pm, true).await;
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&vulnerabilities).unwrap_or_default()
+        );
+        return Ok(());
+    }
 
-[recommended]
-use_flags = {:?}
-"#,
-        detection.cpu_features,
-        detection.gpu_drivers,
-        detection.audio_systems,
-        detection.network_features,
-        detection.recommended_use_flags
-    )
-}
+    println!(
+        "{} {}",
+        theme::info(">>>").bold(),
+        tr!("audit-checking-for-security-vulnerabilities")
+    );
 
-/// Format detection output as shell script
-fn format_detection_shell(detection: &SystemDetection) -> String {
-    let mut output = String::new();
+    let vulnerabilities = pm.audit().await?;
+    send_notifications(pm, false).await;
 
-    output.push_str("#!/bin/bash\n");
-    output.push_str("# BuckOs System Detection\n");
-    output.push_str("# Generated by buckos detect\n\n");
+    if vulnerabilities.is_empty() {
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!("audit-no-known-vulnerabilities-found")
+        );
+        return Ok(());
+    }
 
-    output.push_str(&format!(
-        "CPU_FLAGS_X86=\"{}\"\n",
-        detection.cpu_features.join(" ")
-    ));
+    println!(
+        "\n{} {}\n",
+        theme::error(">>>").bold(),
+        tr!("audit-found-security-issue", count = vulnerabilities.len())
+    );
 
-    output.push_str(&format!(
-        "VIDEO_CARDS=\"{}\"\n",
-        detection.gpu_drivers.join(" ")
-    ));
+    for vuln in &vulnerabilities {
+        println!(
+            "  {} {}/{} - {}",
+            match vuln.severity.as_str() {
+                "critical" => theme::error("!").bold(),
+                "high" => theme::error("!"),
+                "medium" => theme::warning("*"),
+                _ => theme::plain("*"),
+            },
+            theme::accent(&vuln.package.category),
+            theme::plain(&vuln.package.name).bold(),
+            vuln.id
+        );
+        if !vuln.title.is_empty() {
+            println!("    {}", vuln.title);
+        }
+    }
 
-    output.push_str(&format!(
-        "USE=\"{}\"\n",
-        detection.recommended_use_flags.join(" ")
-    ));
+    println!(
+        "\n>>> {}",
+        tr!(
+            "audit-run-install-package-to",
+            command = theme::plain(tr!("audit-buckos")).bold().to_string()
+        )
+    );
 
-    output
+    Ok(())
 }
 
-/// Generate system configuration
-async fn cmd_configure(args: ConfigureArgs) -> buckos_package::Result<()> {
-    println!("{} Generating configuration...", style(">>>").blue().bold());
-
-    // Get profile settings
-    let profile_flags = get_profile_flags(&args.profile);
-
-    // Combine with user-specified flags
-    let mut all_flags: Vec<String> = profile_flags;
-    all_flags.extend(args.use_flags.clone());
-
-    // Auto-detect hardware if requested
-    let mut detection = None;
-    if args.auto_detect {
-        let detect_result = SystemDetection {
-            cpu_features: detect_cpu_features(),
-            gpu_drivers: detect_gpu(),
-            audio_systems: detect_audio(),
-            network_features: detect_network(),
-            recommended_use_flags: Vec::new(),
-        };
-
-        // Add detected features
-        for feature in &detect_result.cpu_features {
-            all_flags.push(format!("cpu_flags_x86_{}", feature));
+/// USE flags management command
+async fn cmd_useflags(pm: &PackageManager, args: UseflagsArgs) -> buckos_package::Result<()> {
+    match args.subcommand {
+        UseflagsCommand::List {
+            category,
+            global,
+            verbose,
+        } => cmd_useflags_list(pm, category, global, verbose).await,
+        UseflagsCommand::Info { flag } => cmd_useflags_info(pm, &flag).await,
+        UseflagsCommand::Set { flags } => cmd_useflags_set(pm.layout(), &flags).await,
+        UseflagsCommand::Get { format } => cmd_useflags_get(pm.config(), &format).await,
+        UseflagsCommand::Package { package, flags } => {
+            cmd_useflags_package(pm.layout(), &package, &flags).await
+        }
+        UseflagsCommand::Expand { variable } => cmd_useflags_expand(variable).await,
+        UseflagsCommand::Validate => cmd_useflags_validate().await,
+        UseflagsCommand::Explain { package, use_flags } => {
+            cmd_useflags_explain(pm, &package, &use_flags).await
         }
-
-        detection = Some(detect_result);
     }
+}
 
-    // Generate output in requested format
-    let output = match args.format.as_str() {
-        "json" => generate_config_json(&args.profile, &all_flags, &args.arch),
-        "toml" => generate_config_toml(&args.profile, &all_flags, &args.arch),
-        "shell" => generate_config_shell(&args.profile, &all_flags, &args.arch),
-        _ => generate_config_bzl(&args.profile, &all_flags, &args.arch),
-    };
+/// List USE flags declared by repository packages
+async fn cmd_useflags_list(
+    pm: &PackageManager,
+    category: Option<String>,
+    global: bool,
+    verbose: bool,
+) -> buckos_package::Result<()> {
+    let usage = pm.use_flag_usage().await?;
+    let flags: Vec<_> = usage
+        .values()
+        .filter(|u| !global || u.is_global())
+        .filter(|u| {
+            category
+                .as_ref()
+                .is_none_or(|cat| u.packages.iter().any(|p| &p.category == cat))
+        })
+        .collect();
 
-    // Write to file or stdout
-    if let Some(path) = args.output {
-        fs::write(&path, &output)?;
+    if flags.is_empty() {
         println!(
-            "{} Configuration saved to: {}",
-            style(">>>").green().bold(),
-            path
+            "{} {}",
+            theme::warning(">>>").bold(),
+            tr!("useflags-list-no-use-flags-found")
         );
-
-        if args.format == "bzl" {
-            println!();
-            println!("Usage:");
-            println!("  buck2 build //packages/linux/... --config {}", path);
-        }
-    } else {
-        println!("{}", output);
+        return Ok(());
     }
 
-    // Print summary
+    let title = match &category {
+        Some(cat) => format!("USE Flags: {}", cat),
+        None => "Available USE Flags".to_string(),
+    };
+    println!("{}", theme::plain(title).bold().underlined());
     println!();
-    println!("{}", style("Configuration Summary").bold().underlined());
-    println!("  Profile: {}", style(&args.profile).cyan());
-    println!("  Architecture: {}", args.arch);
-    println!("  USE flags: {}", all_flags.len());
 
-    if let Some(det) = detection {
-        println!("  Detected CPU features: {}", det.cpu_features.len());
-        println!("  Detected GPU drivers: {}", det.gpu_drivers.len());
+    for usage in &flags {
+        if verbose {
+            println!(
+                "  {}",
+                tr!(
+                    "useflags-list-package",
+                    name = theme::success(&usage.name).to_string(),
+                    description = usage.description().to_string(),
+                    count = usage.packages.len()
+                )
+            );
+        } else {
+            print!("{} ", theme::success(&usage.name));
+        }
+    }
+    if !verbose {
+        println!();
     }
 
     Ok(())
 }
 
-/// Get USE flags for a profile
-fn get_profile_flags(profile: &str) -> Vec<String> {
-    match profile {
-        "minimal" => vec![
-            "-X".to_string(),
-            "-wayland".to_string(),
-            "-pulseaudio".to_string(),
-            "-pipewire".to_string(),
-            "-gtk".to_string(),
-            "-qt5".to_string(),
-            "ipv6".to_string(),
-            "ssl".to_string(),
+/// Show information about a specific USE flag
+async fn cmd_useflags_info(pm: &PackageManager, flag: &str) -> buckos_package::Result<()> {
+    let usage = pm.use_flag_usage().await?;
+
+    if let Some(usage) = usage.get(flag) {
+        let use_config = &pm.config().use_flags;
+        let global_value = if use_config.mask.contains(flag) {
+            "masked"
+        } else if use_config.global.contains(flag) {
+            "enabled"
+        } else if use_config.global.contains(&format!("-{}", flag)) {
+            "disabled"
+        } else {
+            "not set"
+        };
+
+        println!(
+            "{}",
+            theme::plain(tr!("useflags-info-use-flag-information"))
+                .bold()
+                .underlined()
+        );
+        println!();
+        println!(
+            "  {}: {}",
+            theme::plain(tr!("useflags-info-flag")).bold(),
+            theme::success(&usage.name)
+        );
+        println!(
+            "  {}: {}",
+            theme::plain(tr!("useflags-info-scope")).bold(),
+            if usage.is_global() {
+                tr!("useflags-info-global")
+            } else {
+                tr!("useflags-info-local")
+            }
+        );
+        println!(
+            "  {}: {}",
+            theme::plain(tr!("useflags-info-global-use")).bold(),
+            global_value
+        );
+        for description in &usage.descriptions {
+            println!(
+                "  {}: {}",
+                theme::plain(tr!("info-description")).bold(),
+                description
+            );
+        }
+        println!("  {}:", theme::plain(tr!("useflags-info-packages")).bold());
+        for pkg in &usage.packages {
+            println!("    {}", pkg);
+        }
+        return Ok(());
+    }
+
+    // Check USE_EXPAND variables
+    let expand_vars = get_use_expand_variables();
+    for (var_name, values) in &expand_vars {
+        if values.contains(&flag.to_string()) {
+            println!(
+                "{}",
+                theme::plain(tr!("useflags-info-use-expand-variable"))
+                    .bold()
+                    .underlined()
+            );
+            println!();
+            println!(
+                "  {}: {}",
+                theme::plain(tr!("useflags-info-value")).bold(),
+                theme::success(flag)
+            );
+            println!(
+                "  {}: {}",
+                theme::plain(tr!("useflags-info-variable")).bold(),
+                var_name
+            );
+            return Ok(());
+        }
+    }
+
+    println!(
+        "{} {}",
+        theme::warning(">>>").bold(),
+        tr!("useflags-info-use-flag-not-found", flag = flag.to_string())
+    );
+    Ok(())
+}
+
+/// Set global USE flags
+async fn cmd_useflags_set(layout: &Layout, flags: &[String]) -> buckos_package::Result<()> {
+    let config_path = layout.config_dir.join("use.conf");
+
+    // Parse flags
+    let mut enabled = Vec::new();
+    let mut disabled = Vec::new();
+
+    for flag in flags {
+        if let Some(stripped) = flag.strip_prefix('-') {
+            disabled.push(stripped);
+        } else {
+            enabled.push(flag.as_str());
+        }
+    }
+
+    println!(
+        "{}",
+        theme::plain(tr!("useflags-set-setting-use-flags"))
+            .bold()
+            .underlined()
+    );
+    println!();
+
+    if !enabled.is_empty() {
+        println!(
+            "  {}: {}",
+            theme::success(tr!("useflags-set-enabling")),
+            enabled.join(" ")
+        );
+    }
+    if !disabled.is_empty() {
+        println!(
+            "  {}: {}",
+            theme::error(tr!("useflags-set-disabling")),
+            disabled.join(" ")
+        );
+    }
+
+    // Create config directory if it doesn't exist
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent).ok();
+    }
+
+    // Build USE string
+    let use_string = flags.join(" ");
+
+    // Write to config file
+    let content = format!(
+        "# BuckOs USE flags configuration\n# Generated by buckos useflags set\n\nUSE=\"{}\"\n",
+        use_string
+    );
+
+    match fs::write(&config_path, content) {
+        Ok(_) => {
+            println!();
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!(
+                    "useflags-set-configuration-saved-to",
+                    config_path = config_path.display().to_string()
+                )
+            );
+        }
+        Err(e) => {
+            println!();
+            println!(
+                "{} {}",
+                theme::error(">>>").bold(),
+                tr!(
+                    "useflags-set-failed-to-save-configuration",
+                    error = e.to_string()
+                )
+            );
+            println!("{}", tr!("useflags-set-you-may-need-to"));
+            println!();
+            println!("{}", tr!("useflags-set-add-this-to-your"));
+            println!("  USE=\"{}\"", use_string);
+        }
+    }
+
+    Ok(())
+}
+
+/// Get current USE flag configuration
+async fn cmd_useflags_get(config: &Config, format: &str) -> buckos_package::Result<()> {
+    let mut use_flags: Vec<String> = config.use_flags.global.iter().cloned().collect();
+    use_flags.sort();
+
+    match format {
+        "json" => {
+            let output = serde_json::json!({
+                "use_flags": use_flags,
+                "arch": config.arch,
+                "chost": config.chost,
+            });
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&output).unwrap_or_default()
+            );
+        }
+        "toml" => {
+            let output = serde_json::json!({
+                "use": { "flags": use_flags },
+                "system": { "arch": config.arch, "chost": config.chost },
+            });
+            print!("{}", toml::to_string(&output).unwrap_or_default());
+        }
+        _ => {
+            println!(
+                "{}",
+                theme::plain(tr!("useflags-get-current-use-configuration"))
+                    .bold()
+                    .underlined()
+            );
+            println!();
+            println!("  {}: {}", theme::plain("USE").bold(), use_flags.join(" "));
+            println!("  {}: {}", theme::plain("ARCH").bold(), config.arch);
+            println!("  {}: {}", theme::plain("CHOST").bold(), config.chost);
+            println!("  {}: {}", theme::plain("CFLAGS").bold(), config.cflags);
+        }
+    }
+
+    Ok(())
+}
+
+/// Set per-package USE flags
+async fn cmd_useflags_package(
+    layout: &Layout,
+    package: &str,
+    flags: &[String],
+) -> buckos_package::Result<()> {
+    let config_path = layout.config_dir.join("package.use");
+
+    // Create config directory if it doesn't exist
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent).ok();
+    }
+
+    // Format the package.use entry
+    let entry = format!("{} {}\n", package, flags.join(" "));
+
+    println!(
+        "{}",
+        theme::plain(tr!("useflags-package-setting-per-package-use"))
+            .bold()
+            .underlined()
+    );
+    println!();
+    println!(
+        "  {}: {}",
+        theme::plain(tr!("useflags-package-package")).bold(),
+        package
+    );
+    println!(
+        "  {}: {}",
+        theme::plain(tr!("useflags-package-flags")).bold(),
+        flags.join(" ")
+    );
+
+    // Append to package.use file
+    match fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&config_path)
+    {
+        Ok(mut file) => {
+            if let Err(e) = file.write_all(entry.as_bytes()) {
+                println!();
+                println!(
+                    "{} {}",
+                    theme::error(">>>").bold(),
+                    tr!(
+                        "useflags-package-failed-to-write-configuration",
+                        error = e.to_string()
+                    )
+                );
+            } else {
+                println!();
+                println!(
+                    "{} {}",
+                    theme::success(">>>").bold(),
+                    tr!(
+                        "useflags-set-configuration-saved-to",
+                        config_path = config_path.display().to_string()
+                    )
+                );
+            }
+        }
+        Err(e) => {
+            println!();
+            println!(
+                "{} {}",
+                theme::error(">>>").bold(),
+                tr!(
+                    "useflags-package-failed-to-open-configuration",
+                    error = e.to_string()
+                )
+            );
+            println!("\n{}", tr!("useflags-package-add-this-to-your"));
+            println!("  {}", entry.trim());
+        }
+    }
+
+    Ok(())
+}
+
+/// Show USE_EXPAND variables
+async fn cmd_useflags_expand(variable: Option<String>) -> buckos_package::Result<()> {
+    let expand_vars = get_use_expand_variables();
+
+    if let Some(var) = variable {
+        if let Some(values) = expand_vars.get(&var.to_uppercase()) {
+            println!(
+                "{}",
+                theme::plain(var.to_uppercase().to_string())
+                    .bold()
+                    .underlined()
+            );
+            println!();
+            for value in values {
+                println!("  {}", theme::success(value));
+            }
+        } else {
+            println!(
+                "{} {}",
+                theme::warning(">>>").bold(),
+                tr!(
+                    "useflags-expand-unknown-use-expand-variable",
+                    var = var.to_string()
+                )
+            );
+            println!("\n{}", tr!("useflags-expand-available-variables"));
+            for var_name in expand_vars.keys() {
+                println!("  - {}", var_name);
+            }
+        }
+    } else {
+        println!(
+            "{}",
+            theme::plain(tr!("useflags-expand-use-expand-variables"))
+                .bold()
+                .underlined()
+        );
+        println!();
+        for (var_name, values) in &expand_vars {
+            println!("{}:", theme::accent(var_name).bold());
+            let values_str: Vec<&str> = values.iter().map(|s| s.as_str()).collect();
+            println!("  {}", values_str.join(" "));
+            println!();
+        }
+    }
+
+    Ok(())
+}
+
+/// Get USE_EXPAND variables
+fn get_use_expand_variables() -> HashMap<String, Vec<String>> {
+    let mut vars = HashMap::new();
+
+    vars.insert(
+        "CPU_FLAGS_X86".to_string(),
+        vec![
+            "aes", "avx", "avx2", "avx512f", "avx512dq", "avx512cd", "avx512bw", "avx512vl", "mmx",
+            "mmxext", "pclmul", "popcnt", "sse", "sse2", "sse3", "ssse3", "sse4_1", "sse4_2",
+            "sse4a", "f16c", "fma", "fma4", "xop",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect(),
+    );
+
+    vars.insert(
+        "VIDEO_CARDS".to_string(),
+        vec![
+            "amdgpu",
+            "ast",
+            "dummy",
+            "fbdev",
+            "i915",
+            "i965",
+            "intel",
+            "mga",
+            "nouveau",
+            "nvidia",
+            "r128",
+            "r600",
+            "radeon",
+            "radeonsi",
+            "vesa",
+            "via",
+            "virtualbox",
+            "vmware",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect(),
+    );
+
+    vars.insert(
+        "INPUT_DEVICES".to_string(),
+        vec![
+            "evdev",
+            "joystick",
+            "keyboard",
+            "libinput",
+            "mouse",
+            "synaptics",
+            "vmmouse",
+            "wacom",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect(),
+    );
+
+    vars.insert(
+        "PYTHON_TARGETS".to_string(),
+        vec!["python3_10", "python3_11", "python3_12", "python3_13"]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+    );
+
+    vars.insert(
+        "RUBY_TARGETS".to_string(),
+        vec!["ruby31", "ruby32", "ruby33"]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+    );
+
+    vars
+}
+
+/// Validate USE flag configuration
+async fn cmd_useflags_validate() -> buckos_package::Result<()> {
+    println!(
+        "{}",
+        theme::plain(tr!("useflags-validate-validating-use-flag-configuration"))
+            .bold()
+            .underlined()
+    );
+    println!();
+
+    let mut issues = Vec::new();
+
+    // Check for conflicting flags
+    let conflicts = vec![
+        ("systemd", "elogind"),
+        ("pulseaudio", "pipewire"),
+        ("ssl", "gnutls"),
+        ("gtk", "qt5"),
+    ];
+
+    // Simulate checking current config
+    let current_flags: HashSet<&str> = ["ssl", "http2", "systemd"].into_iter().collect();
+
+    for (flag1, flag2) in &conflicts {
+        if current_flags.contains(flag1) && current_flags.contains(flag2) {
+            issues.push(format!(
+                "Conflicting flags: {} and {} are both enabled",
+                flag1, flag2
+            ));
+        }
+    }
+
+    if issues.is_empty() {
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!("useflags-validate-no-issues-found")
+        );
+    } else {
+        println!(
+            "{} {}",
+            theme::warning(">>>").bold(),
+            tr!("useflags-validate-found-issue", count = issues.len())
+        );
+        for issue in issues {
+            println!("  - {}", issue);
+        }
+    }
+
+    Ok(())
+}
+
+/// Explain a package's USE flags and the impact of toggling them
+async fn cmd_useflags_explain(
+    pm: &PackageManager,
+    package: &str,
+    cli_flags: &[String],
+) -> buckos_package::Result<()> {
+    let layers = pm.use_layers()?.with_cli(cli_flags);
+    let (pkg, flags) = pm.explain_use(package, layers).await?;
+
+    println!(
+        "{}",
+        theme::plain(tr!(
+            "useflags-explain-use-flags-for",
+            id = pkg.id.to_string(),
+            version = pkg.version.to_string()
+        ))
+        .bold()
+        .underlined()
+    );
+    println!();
+
+    if flags.is_empty() {
+        println!(
+            "{} {}",
+            theme::warning(">>>").bold(),
+            tr!("useflags-explain-has-no-use-flags", id = pkg.id.to_string())
+        );
+        return Ok(());
+    }
+
+    let sign = |enabled: bool| if enabled { "+" } else { "-" };
+    for flag in &flags {
+        let value = format!("{}{}", sign(flag.enabled), flag.flag);
+        let value = if flag.enabled {
+            theme::success(value).bold()
+        } else {
+            theme::error(value).bold()
+        };
+        println!("  {} ({})", value, theme::accent(flag.source()));
+        if !flag.description.is_empty() {
+            println!("      {}", theme::plain(&flag.description).dim());
+        }
+        if flag.settings.len() > 1 {
+            let chain: Vec<String> = flag
+                .settings
+                .iter()
+                .map(|s| format!("{}{} [{}]", sign(s.enabled), flag.flag, s.layer))
+                .collect();
+            println!(
+                "      {}",
+                tr!("useflags-explain-set-by", chain = chain.join(" -> "))
+            );
+        }
+
+        match &flag.impact {
+            None if flag.is_locked() => {
+                println!(
+                    "      {}",
+                    tr!(
+                        "useflags-explain-locked-by",
+                        source = flag.source().to_string()
+                    )
+                );
+            }
+            None => {
+                println!(
+                    "      {}",
+                    theme::warning(tr!("useflags-explain-could-not-resolve-dependencies"))
+                );
+            }
+            Some(impact) if impact.is_empty() => {
+                println!(
+                    "      {}",
+                    tr!("useflags-explain-toggling-changes-no-dependencies")
+                );
+            }
+            Some(impact) => {
+                println!(
+                    "      {}",
+                    tr!(
+                        "useflags-explain-with",
+                        enabled = sign(!flag.enabled).to_string(),
+                        flag = theme::plain(&flag.flag).bold().to_string()
+                    )
+                );
+                for id in &impact.added {
+                    println!("        {} {}", theme::paint(Role::New, "+"), id);
+                }
+                for id in &impact.removed {
+                    println!("        {} {}", theme::paint(Role::Remove, "-"), id);
+                }
+                if impact.size_delta != 0 {
+                    println!(
+                        "        {}",
+                        tr!(
+                            "useflags-explain-installed-size",
+                            size_delta =
+                                (if impact.size_delta > 0 { "+" } else { "-" }).to_string(),
+                            unsigned_abs =
+                                format_size(impact.size_delta.unsigned_abs()).to_string()
+                        )
+                    );
+                }
+                if !impact.files_added.is_empty() || !impact.files_removed.is_empty() {
+                    println!(
+                        "        {}",
+                        tr!(
+                            "useflags-explain-files-added-removed",
+                            added = impact.files_added.len(),
+                            removed = impact.files_removed.len()
+                        )
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Detect system capabilities
+async fn cmd_detect(args: DetectArgs, config: &Config) -> buckos_package::Result<()> {
+    use buckos_package::hardware;
+
+    let detect_all = args.all || (!args.cpu && !args.gpu && !args.audio && !args.network);
+    if args.save && !detect_all {
+        return Err(buckos_package::Error::Other(
+            "--save stores a full detection; drop --cpu, --gpu, --audio and --network".to_string(),
+        ));
+    }
+
+    let mut detection = HardwareDetection {
+        detected_at: Some(chrono::Utc::now()),
+        ..Default::default()
+    };
+
+    if detect_all || args.cpu {
+        detection.cpu_features = hardware::detect_cpu_features();
+    }
+
+    if detect_all || args.gpu {
+        detection.gpu_drivers = hardware::detect_gpu();
+    }
+
+    if detect_all || args.audio {
+        detection.audio_systems = hardware::detect_audio();
+    }
+
+    if detect_all || args.network {
+        detection.network_features = hardware::detect_network();
+    }
+
+    // Generate recommended USE flags based on detection
+    detection.recommended_use_flags = hardware::generate_recommended_flags(&detection);
+
+    if args.save {
+        detection.save(&config.db_path)?;
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!(
+                "detect-stored-as-this-machine",
+                db_path = HardwareDetection::path(&config.db_path)
+                    .display()
+                    .to_string()
+            )
+        );
+    }
+
+    // Output in requested format
+    let output = match args.format.as_str() {
+        "json" => serde_json::to_string_pretty(&detection).unwrap_or_default(),
+        "toml" => format_detection_toml(&detection),
+        "shell" => format_detection_shell(&detection),
+        _ => format_detection_text(&detection),
+    };
+
+    // Write to file or stdout
+    if let Some(path) = args.output {
+        fs::write(&path, &output)?;
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!("detect-detection-results-saved-to", path = path.to_string())
+        );
+    } else {
+        println!("{}", output);
+    }
+
+    Ok(())
+}
+
+/// Format detection output as text
+fn format_detection_text(detection: &HardwareDetection) -> String {
+    let mut output = String::new();
+
+    output.push_str(&format!(
+        "{}\n\n",
+        theme::plain("System Detection Results").bold().underlined()
+    ));
+
+    if !detection.cpu_features.is_empty() {
+        output.push_str(&format!("{}:\n", theme::accent("CPU Features").bold()));
+        output.push_str(&format!("  {}\n\n", detection.cpu_features.join(" ")));
+    }
+
+    if !detection.gpu_drivers.is_empty() {
+        output.push_str(&format!("{}:\n", theme::accent("GPU Drivers").bold()));
+        output.push_str(&format!("  {}\n\n", detection.gpu_drivers.join(" ")));
+    }
+
+    if !detection.audio_systems.is_empty() {
+        output.push_str(&format!("{}:\n", theme::accent("Audio Systems").bold()));
+        output.push_str(&format!("  {}\n\n", detection.audio_systems.join(" ")));
+    }
+
+    if !detection.network_features.is_empty() {
+        output.push_str(&format!("{}:\n", theme::accent("Network Features").bold()));
+        output.push_str(&format!("  {}\n\n", detection.network_features.join(" ")));
+    }
+
+    output.push_str(&format!(
+        "{}:\n",
+        theme::success("Recommended USE Flags").bold()
+    ));
+    output.push_str(&format!(
+        "  {}\n",
+        detection.recommended_use_flags.join(" ")
+    ));
+
+    output
+}
+
+/// Format detection output as TOML
+fn format_detection_toml(detection: &HardwareDetection) -> String {
+    format!(
+        r#"# BuckOs System Detection
+# Generated by buckos detect
+
+[cpu]
+features = {:?}
+
+[gpu]
+drivers = {:?}
+
+[audio]
+systems = {:?}
+
+[network]
+features = {:?}
+
+[recommended]
+use_flags = {:?}
+"#,
+        detection.cpu_features,
+        detection.gpu_drivers,
+        detection.audio_systems,
+        detection.network_features,
+        detection.recommended_use_flags
+    )
+}
+
+/// Format detection output as shell script
+fn format_detection_shell(detection: &HardwareDetection) -> String {
+    let mut output = String::new();
+
+    output.push_str("#!/bin/bash\n");
+    output.push_str("# BuckOs System Detection\n");
+    output.push_str("# Generated by buckos detect\n\n");
+
+    output.push_str(&format!(
+        "CPU_FLAGS_X86=\"{}\"\n",
+        detection.cpu_features.join(" ")
+    ));
+
+    output.push_str(&format!(
+        "VIDEO_CARDS=\"{}\"\n",
+        detection.gpu_drivers.join(" ")
+    ));
+
+    output.push_str(&format!(
+        "USE=\"{}\"\n",
+        detection.recommended_use_flags.join(" ")
+    ));
+
+    output
+}
+
+/// Generate system configuration
+async fn cmd_configure(args: ConfigureArgs, config: &Config) -> buckos_package::Result<()> {
+    println!(
+        "{} {}",
+        theme::info(">>>").bold(),
+        tr!("configure-generating-configuration")
+    );
+
+    // Get profile settings
+    let profile_flags = get_profile_flags(&args.profile);
+
+    // Combine with user-specified flags
+    let mut all_flags: Vec<String> = profile_flags;
+    all_flags.extend(args.use_flags.clone());
+
+    // Auto-detect hardware if requested
+    let mut detection = None;
+    if args.auto_detect {
+        let detect_result = HardwareDetection::detect();
+
+        // Add detected features
+        for feature in &detect_result.cpu_features {
+            all_flags.push(format!("cpu_flags_x86_{}", feature));
+        }
+
+        detection = Some(detect_result);
+    }
+
+    if args.hardware_use {
+        let hardware = match HardwareDetection::load(&config.db_path)? {
+            Some(stored) => stored,
+            None => {
+                let detected = HardwareDetection::detect();
+                detected.save(&config.db_path)?;
+                detected
+            }
+        };
+        let path = Layout::resolve(config)?.path(buckos_package::hardware::HARDWARE_USE_FILE);
+        hardware.package_use().write(&path)?;
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!(
+                "configure-wrote-use-flags-for",
+                path = path.display().to_string()
+            )
+        );
+    }
+
+    // Generate output in requested format
+    let output = match args.format.as_str() {
+        "json" => generate_config_json(&args.profile, &all_flags, &args.arch),
+        "toml" => generate_config_toml(&args.profile, &all_flags, &args.arch),
+        "shell" => generate_config_shell(&args.profile, &all_flags, &args.arch),
+        _ => generate_config_bzl(&args.profile, &all_flags, &args.arch),
+    };
+
+    // Write to file or stdout
+    if let Some(path) = args.output {
+        fs::write(&path, &output)?;
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!("configure-configuration-saved-to", path = path.to_string())
+        );
+
+        if args.format == "bzl" {
+            println!();
+            println!("{}", tr!("configure-usage"));
+            println!(
+                "  {}",
+                tr!(
+                    "configure-buck-build-packages-linux",
+                    path = path.to_string()
+                )
+            );
+        }
+    } else {
+        println!("{}", output);
+    }
+
+    // Print summary
+    println!();
+    println!(
+        "{}",
+        theme::plain(tr!("configure-configuration-summary"))
+            .bold()
+            .underlined()
+    );
+    println!(
+        "  {}",
+        tr!(
+            "configure-profile",
+            profile = theme::accent(&args.profile).to_string()
+        )
+    );
+    println!(
+        "  {}",
+        tr!("config-architecture", arch = args.arch.to_string())
+    );
+    println!("  {}", tr!("configure-use-flags", count = all_flags.len()));
+
+    if let Some(det) = detection {
+        println!(
+            "  {}",
+            tr!(
+                "configure-detected-cpu-features",
+                count = det.cpu_features.len()
+            )
+        );
+        println!(
+            "  {}",
+            tr!(
+                "configure-detected-gpu-drivers",
+                count = det.gpu_drivers.len()
+            )
+        );
+    }
+
+    Ok(())
+}
+
+/// Get USE flags for a profile
+fn get_profile_flags(profile: &str) -> Vec<String> {
+    match profile {
+        "minimal" => vec![
+            "-X".to_string(),
+            "-wayland".to_string(),
+            "-pulseaudio".to_string(),
+            "-pipewire".to_string(),
+            "-gtk".to_string(),
+            "-qt5".to_string(),
+            "ipv6".to_string(),
+            "ssl".to_string(),
         ],
         "server" => vec![
             "-X".to_string(),
@@ -2842,1789 +4529,4872 @@ fn get_profile_flags(profile: &str) -> Vec<String> {
     }
 }
 
-/// Generate Buck2 modifier configuration (local_modifiers.bzl)
-///
-/// Produces a Starlark file compatible with buckos-build's modifier system.
-/// The root PACKAGE loads this and applies modifiers via set_cfg_modifiers().
-fn generate_config_bzl(profile: &str, flags: &[String], _arch: &str) -> String {
-    let modifier_lines: Vec<String> = flags
-        .iter()
-        .map(|f| format!("    \"//use/constraints:{}-on\",", f))
-        .collect();
+/// Generate Buck2 modifier configuration (local_modifiers.bzl)
+///
+/// Produces a Starlark file compatible with buckos-build's modifier system.
+/// The root PACKAGE loads this and applies modifiers via set_cfg_modifiers().
+fn generate_config_bzl(profile: &str, flags: &[String], _arch: &str) -> String {
+    let modifier_lines: Vec<String> = flags
+        .iter()
+        .map(|f| format!("    \"//use/constraints:{}-on\",", f))
+        .collect();
+
+    format!(
+        r#"# BuckOS Local Modifier Configuration
+# Generated by buckos configure
+# Profile: {}
+# Edit via: buckos use [flags] or buckos config set use "..."
+
+LOCAL_MODIFIERS = [
+{}
+]
+"#,
+        profile,
+        modifier_lines.join("\n"),
+    )
+}
+
+/// Generate JSON configuration
+fn generate_config_json(profile: &str, flags: &[String], arch: &str) -> String {
+    serde_json::to_string_pretty(&serde_json::json!({
+        "profile": profile,
+        "use_flags": flags,
+        "arch": arch
+    }))
+    .unwrap_or_default()
+}
+
+/// Generate TOML configuration
+fn generate_config_toml(profile: &str, flags: &[String], arch: &str) -> String {
+    format!(
+        r#"# BuckOs Configuration
+# Generated by buckos configure
+
+[system]
+profile = "{}"
+arch = "{}"
+
+[use]
+flags = [
+{}
+]
+"#,
+        profile,
+        arch,
+        flags
+            .iter()
+            .map(|f| format!("    \"{}\",", f))
+            .collect::<Vec<_>>()
+            .join("\n")
+    )
+}
+
+/// Generate shell configuration
+fn generate_config_shell(profile: &str, flags: &[String], arch: &str) -> String {
+    format!(
+        r#"#!/bin/bash
+# BuckOs Configuration
+# Generated by buckos configure
+
+# Profile: {}
+BUCKOS_PROFILE="{}"
+
+# Architecture
+ARCH="{}"
+
+# USE flags
+USE="{}"
+
+export BUCKOS_PROFILE ARCH USE
+"#,
+        profile,
+        profile,
+        arch,
+        flags.join(" ")
+    )
+}
+
+/// Package set management command
+async fn cmd_set(
+    pm: &PackageManager,
+    args: SetArgs,
+    emerge_opts: &EmergeOptions,
+) -> buckos_package::Result<()> {
+    match args.subcommand {
+        SetCommand::List { r#type } => cmd_set_list(r#type).await,
+        SetCommand::Show { set_name } => cmd_set_show(&set_name).await,
+        SetCommand::Install { set_name } => cmd_set_install(pm, &set_name, emerge_opts).await,
+        SetCommand::Compare { set1, set2 } => cmd_set_compare(&set1, &set2).await,
+    }
+}
+
+/// List available package sets
+async fn cmd_set_list(set_type: Option<String>) -> buckos_package::Result<()> {
+    println!(
+        "{}",
+        theme::plain(tr!("set-list-available-package-sets"))
+            .bold()
+            .underlined()
+    );
+    println!();
+
+    let sets = get_package_sets();
+
+    if let Some(t) = set_type {
+        // Filter by type
+        if let Some(type_sets) = sets.get(&t) {
+            println!("{}:", theme::accent(&t).bold());
+            for (name, info) in type_sets {
+                println!("  {} - {}", theme::success(name), info);
+            }
+        } else {
+            println!(
+                "{} {}",
+                theme::warning(">>>").bold(),
+                tr!("set-list-unknown-set-type", set_type = t.to_string())
+            );
+            println!("\n{}", tr!("set-list-available-types-system-task"));
+        }
+    } else {
+        // Show all sets
+        for (set_type, type_sets) in &sets {
+            println!("{}:", theme::accent(set_type).bold());
+            for (name, description) in type_sets {
+                println!("  {} - {}", theme::success(name), description);
+            }
+            println!();
+        }
+    }
+
+    Ok(())
+}
+
+/// Get package sets organized by type
+fn get_package_sets() -> HashMap<String, Vec<(&'static str, &'static str)>> {
+    let mut sets = HashMap::new();
+
+    sets.insert(
+        "system".to_string(),
+        vec![
+            ("minimal", "Minimal bootable system"),
+            ("server", "Server base system"),
+            ("desktop", "Desktop base system"),
+            ("developer", "Development environment"),
+            ("hardened", "Security-hardened system"),
+        ],
+    );
+
+    sets.insert(
+        "task".to_string(),
+        vec![
+            ("web-server", "Web server packages"),
+            ("database", "Database packages"),
+            ("container", "Container runtime packages"),
+            ("virtualization", "Virtualization packages"),
+            ("monitoring", "System monitoring packages"),
+        ],
+    );
+
+    sets.insert(
+        "desktop".to_string(),
+        vec![
+            ("gnome", "GNOME desktop environment"),
+            ("kde", "KDE Plasma desktop environment"),
+            ("xfce", "Xfce desktop environment"),
+            ("sway", "Sway Wayland compositor"),
+        ],
+    );
+
+    sets
+}
+
+/// Show contents of a package set
+async fn cmd_set_show(set_name: &str) -> buckos_package::Result<()> {
+    let packages = get_set_packages(set_name);
+
+    if packages.is_empty() {
+        println!(
+            "{} {}",
+            theme::warning(">>>").bold(),
+            tr!("set-show-unknown-set", set_name = set_name.to_string())
+        );
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        theme::plain(tr!("set-show-package-set", set_name = set_name.to_string()))
+            .bold()
+            .underlined()
+    );
+    println!();
+
+    for pkg in &packages {
+        println!("  {}", theme::success(pkg));
+    }
+
+    println!();
+    println!("{}", tr!("set-show-total-packages", count = packages.len()));
+
+    Ok(())
+}
+
+/// Get packages in a set by reading from package_sets.bzl
+fn get_set_packages(set_name: &str) -> Vec<String> {
+    // Try to load package sets from bzl file
+    match load_package_sets_bzl() {
+        Ok(package_sets) => {
+            // Handle @system specially
+            if set_name == "system" {
+                return package_sets.get_system_packages().to_vec();
+            }
+
+            // Resolve the set with inheritance
+            package_sets.resolve_set(set_name)
+        }
+        Err(e) => {
+            eprintln!(
+                "{}",
+                tr!(
+                    "get-set-packages-warning-failed-to-load",
+                    error = e.to_string()
+                )
+            );
+            eprintln!("{}", tr!("get-set-packages-using-fallback-minimal-set"));
+            // Return empty vec as fallback
+            Vec::new()
+        }
+    }
+}
+
+/// Load package sets from bzl file
+fn load_package_sets_bzl() -> buckos_package::Result<buckos_config::PackageSets> {
+    // Try to find package_sets.bzl file
+    let paths_to_try = vec![
+        std::path::PathBuf::from("../buckos-build/defs/package_sets.bzl"),
+        std::path::PathBuf::from("../../buckos-build/defs/package_sets.bzl"),
+        std::path::PathBuf::from("defs/package_sets.bzl"),
+    ];
+
+    for path in paths_to_try {
+        if path.exists() {
+            return buckos_config::PackageSets::from_file(&path).map_err(|e| {
+                buckos_package::Error::Config(format!("Failed to parse package_sets.bzl: {}", e))
+            });
+        }
+    }
+
+    // Try relative to current directory
+    if let Ok(cwd) = std::env::current_dir() {
+        let path = cwd
+            .parent()
+            .map(|p| p.join("buckos-build/defs/package_sets.bzl"));
+
+        if let Some(path) = path {
+            if path.exists() {
+                return buckos_config::PackageSets::from_file(&path).map_err(|e| {
+                    buckos_package::Error::Config(format!(
+                        "Failed to parse package_sets.bzl: {}",
+                        e
+                    ))
+                });
+            }
+        }
+    }
+
+    Err(buckos_package::Error::Config(
+        "package_sets.bzl not found. Please ensure buckos-build repository is accessible."
+            .to_string(),
+    ))
+}
+
+/// Install all packages in a set
+async fn cmd_set_install(
+    pm: &PackageManager,
+    set_name: &str,
+    emerge_opts: &EmergeOptions,
+) -> buckos_package::Result<()> {
+    let packages = get_set_packages(set_name);
+
+    if packages.is_empty() {
+        println!(
+            "{} {}",
+            theme::warning(">>>").bold(),
+            tr!("set-show-unknown-set", set_name = set_name.to_string())
+        );
+        return Ok(());
+    }
+
+    println!(
+        "{} {}",
+        theme::info(">>>").bold(),
+        tr!(
+            "set-install-installing-set-packages",
+            set_name = set_name.to_string(),
+            count = packages.len()
+        )
+    );
+
+    let opts = InstallOptions {
+        force: false,
+        no_deps: false,
+        build: true,
+        use_flags: Vec::new(),
+        oneshot: emerge_opts.oneshot,
+        fetch_only: emerge_opts.fetch_only,
+        deep: emerge_opts.deep,
+        newuse: emerge_opts.newuse,
+        empty_tree: false,
+        no_replace: true,
+        use_pkg: emerge_opts.use_pkg,
+        use_pkg_only: emerge_opts.use_pkg_only,
+        get_binpkg: emerge_opts.get_binpkg,
+        get_binpkg_only: emerge_opts.get_binpkg_only,
+        build_pkg: emerge_opts.build_pkg,
+        build_pkg_only: emerge_opts.build_pkg_only,
+    };
+
+    // Resolve dependencies
+    let resolution = pm.resolve_packages(&packages, &opts).await?;
+
+    if resolution.packages.is_empty() {
+        println!(
+            "\n{} {}",
+            theme::success(">>>").bold(),
+            tr!("set-install-all-packages-in-set")
+        );
+        return Ok(());
+    }
+
+    // Display package list
+    print_emerge_list(&resolution, emerge_opts, "install")?;
+    print_build_estimate(pm, &resolution, emerge_opts).await?;
+
+    // Pretend mode
+    if emerge_opts.pretend {
+        return Ok(());
+    }
+
+    // Ask mode
+    if emerge_opts.ask {
+        if !Confirm::new()
+            .with_prompt(tr!("confirm-merge"))
+            .default(true)
+            .interact()?
+        {
+            println!(
+                "{} {}",
+                theme::warning(">>>").bold(),
+                theme::warning(tr!("exiting")).bold()
+            );
+            return Ok(());
+        }
+        println!();
+    }
+
+    pm.install(&packages, opts).await?;
+
+    println!(
+        "\n{} {}",
+        theme::success(">>>").bold(),
+        tr!(
+            "set-install-set-installed-successfully",
+            set_name = set_name.to_string()
+        )
+    );
+
+    Ok(())
+}
+
+/// Compare two package sets
+async fn cmd_set_compare(set1: &str, set2: &str) -> buckos_package::Result<()> {
+    let packages1: HashSet<String> = get_set_packages(set1).into_iter().collect();
+    let packages2: HashSet<String> = get_set_packages(set2).into_iter().collect();
+
+    if packages1.is_empty() {
+        println!(
+            "{} {}",
+            theme::warning(">>>").bold(),
+            tr!("set-compare-unknown-set", set1 = set1.to_string())
+        );
+        return Ok(());
+    }
+    if packages2.is_empty() {
+        println!(
+            "{} {}",
+            theme::warning(">>>").bold(),
+            tr!("set-compare-unknown-set-2", set2 = set2.to_string())
+        );
+        return Ok(());
+    }
+
+    let added: Vec<_> = packages2.difference(&packages1).collect();
+    let removed: Vec<_> = packages1.difference(&packages2).collect();
+    let common: Vec<_> = packages1.intersection(&packages2).collect();
+
+    println!(
+        "{}",
+        tr!(
+            "set-compare-vs",
+            set1 = theme::accent(set1).bold().to_string(),
+            set2 = theme::accent(set2).bold().to_string()
+        )
+    );
+    println!();
+
+    if !added.is_empty() {
+        println!(
+            "{}:",
+            theme::success(tr!("set-compare-added-in-second-set"))
+        );
+        for pkg in &added {
+            println!("  + {}", pkg);
+        }
+        println!();
+    }
+
+    if !removed.is_empty() {
+        println!(
+            "{}:",
+            theme::error(tr!("set-compare-removed-from-first-set"))
+        );
+        for pkg in &removed {
+            println!("  - {}", pkg);
+        }
+        println!();
+    }
+
+    println!(
+        "{}",
+        tr!(
+            "set-compare-common-packages",
+            common = theme::plain(common.len()).bold().to_string()
+        )
+    );
+
+    Ok(())
+}
+
+/// Patch management command
+async fn cmd_patch(pm: &PackageManager, args: PatchArgs) -> buckos_package::Result<()> {
+    match args.subcommand {
+        PatchCommand::List { package } => cmd_patch_list(pm, &package).await,
+        PatchCommand::Info {
+            package,
+            patch_name,
+        } => cmd_patch_info(pm, &package, &patch_name).await,
+        PatchCommand::Add {
+            package,
+            patch_file,
+        } => cmd_patch_add(pm, &package, &patch_file).await,
+        PatchCommand::Remove {
+            package,
+            patch_name,
+        } => cmd_patch_remove(pm, &package, &patch_name).await,
+        PatchCommand::Check { package, source } => {
+            cmd_patch_check(pm, &package, source.as_deref()).await
+        }
+        PatchCommand::Order { package } => cmd_patch_order(pm, &package).await,
+        PatchCommand::Apply {
+            package,
+            version,
+            slot,
+            source,
+        } => cmd_patch_apply(pm, &package, version.as_deref(), &slot, &source).await,
+    }
+}
+
+/// User patches for `package` (`category/name` or a bare name)
+fn user_patch_set(
+    pm: &PackageManager,
+    package: &str,
+    version: Option<&str>,
+    slot: &str,
+) -> buckos_package::Result<PatchSet> {
+    let base = pm.layout().path(patches::USER_PATCH_DIR);
+    match buckos_package::PackageId::parse(package) {
+        Some(id) => PatchSet::discover(&base, &id, version.unwrap_or_default(), slot),
+        None if base.join(package).is_dir() => PatchSet::from_dir(package, &base.join(package)),
+        None => Ok(PatchSet::default()),
+    }
+}
+
+/// List patches for a package
+async fn cmd_patch_list(pm: &PackageManager, package: &str) -> buckos_package::Result<()> {
+    println!(
+        "{}",
+        theme::plain(tr!("patch-list-patches-for", package = package.to_string()))
+            .bold()
+            .underlined()
+    );
+    println!();
+
+    let set = user_patch_set(pm, package, None, "0")?;
+    match &set.dir {
+        Some(dir) if !set.is_empty() => {
+            for patch in &set.patches {
+                println!("  {} ({})", theme::success(&patch.name), dir.display());
+            }
+            println!();
+            println!(
+                "{}",
+                tr!("patch-list-total-patches", count = set.patches.len())
+            );
+        }
+        _ => {
+            println!(
+                "{}",
+                tr!(
+                    "patch-list-no-patches-found-for",
+                    package = package.to_string()
+                )
+            );
+            println!();
+            println!(
+                "{}",
+                tr!(
+                    "patch-list-patches-are-read-from",
+                    patches = pm
+                        .layout()
+                        .path(patches::USER_PATCH_DIR)
+                        .display()
+                        .to_string()
+                )
+            );
+        }
+    }
+
+    let name = package.rsplit('/').next().unwrap_or(package);
+    let applied = pm.package_patches(name).await?;
+    if !applied.is_empty() {
+        println!();
+        println!(
+            "{}",
+            theme::plain(tr!("patch-list-installed-version-was-built")).bold()
+        );
+        for patch in applied {
+            println!("  {} sha256:{}", patch.name, patch.sha256);
+        }
+    }
+
+    Ok(())
+}
+
+/// Show patch information
+async fn cmd_patch_info(
+    pm: &PackageManager,
+    package: &str,
+    patch_name: &str,
+) -> buckos_package::Result<()> {
+    let set = user_patch_set(pm, package, None, "0")?;
+    let Some(patch) = set.patches.iter().find(|p| p.name == patch_name) else {
+        println!(
+            "{} {}",
+            theme::warning(">>>").bold(),
+            tr!(
+                "patch-info-patch-not-found",
+                patch_name = patch_name.to_string()
+            )
+        );
+        return Ok(());
+    };
+
+    println!(
+        "{}",
+        theme::plain(tr!("patch-info-patch-information"))
+            .bold()
+            .underlined()
+    );
+    println!();
+    println!(
+        "  {}: {}",
+        theme::plain(tr!("info-name")).bold(),
+        patch.name
+    );
+    println!(
+        "  {}: {}",
+        theme::plain(tr!("useflags-package-package")).bold(),
+        package
+    );
+    println!(
+        "  {}: {}",
+        theme::plain(tr!("patch-info-path")).bold(),
+        patch.path.display()
+    );
+    if let Some(strip) = patch.strip {
+        println!(
+            "  {}: -p{}",
+            theme::plain(tr!("patch-info-strip")).bold(),
+            strip
+        );
+    }
+
+    // Read first few lines of patch to show description
+    if let Ok(content) = fs::read_to_string(&patch.path) {
+        let lines: Vec<&str> = content.lines().take(10).collect();
+        if !lines.is_empty() {
+            println!();
+            println!("{}:", theme::plain(tr!("patch-info-header")).bold());
+            for line in lines {
+                println!("  {}", line);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Add a user patch
+async fn cmd_patch_add(
+    pm: &PackageManager,
+    package: &str,
+    patch_file: &str,
+) -> buckos_package::Result<()> {
+    let patch_dir = pm.layout().path(patches::USER_PATCH_DIR).join(package);
+
+    // Create directory if it doesn't exist
+    fs::create_dir_all(&patch_dir)?;
+
+    // Copy patch file
+    let source = std::path::Path::new(patch_file);
+    let file_name = source.file_name().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid patch file name")
+    })?;
+    let dest = patch_dir.join(file_name);
+
+    fs::copy(source, &dest)?;
+
+    // Keep an existing series file in step with the directory
+    let series = patch_dir.join("series");
+    if series.exists() {
+        let mut content = fs::read_to_string(&series)?;
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(&format!("{}\n", file_name.to_string_lossy()));
+        fs::write(&series, content)?;
+    }
+
+    println!(
+        "{} {}",
+        theme::success(">>>").bold(),
+        tr!("patch-add-added-patch", dest = dest.display().to_string())
+    );
+    println!();
+    println!(
+        "{}",
+        tr!("patch-add-the-patch-will-be", package = package.to_string())
+    );
+
+    Ok(())
+}
+
+/// Remove a user patch
+async fn cmd_patch_remove(
+    pm: &PackageManager,
+    package: &str,
+    patch_name: &str,
+) -> buckos_package::Result<()> {
+    let patch_dir = pm.layout().path(patches::USER_PATCH_DIR).join(package);
+    let patch_path = patch_dir.join(patch_name);
+
+    if !patch_path.exists() {
+        println!(
+            "{} {}",
+            theme::warning(">>>").bold(),
+            tr!(
+                "patch-remove-patch-not-found",
+                patch_path = patch_path.display().to_string()
+            )
+        );
+        return Ok(());
+    }
+
+    fs::remove_file(&patch_path)?;
+
+    let series = patch_dir.join("series");
+    if series.exists() {
+        let content: String = fs::read_to_string(&series)?
+            .lines()
+            .filter(|line| line.split_whitespace().next() != Some(patch_name))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        fs::write(&series, content)?;
+    }
+
+    println!(
+        "{} {}",
+        theme::success(">>>").bold(),
+        tr!(
+            "patch-remove-removed-patch",
+            patch_path = patch_path.display().to_string()
+        )
+    );
+
+    Ok(())
+}
+
+/// Check if patches apply cleanly
+async fn cmd_patch_check(
+    pm: &PackageManager,
+    package: &str,
+    source: Option<&std::path::Path>,
+) -> buckos_package::Result<()> {
+    println!(
+        "{} {}",
+        theme::info(">>>").bold(),
+        tr!(
+            "patch-check-checking-patches-for",
+            package = package.to_string()
+        )
+    );
+
+    let set = user_patch_set(pm, package, None, "0")?;
+    if set.is_empty() {
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!("patch-check-no-patches-to-check")
+        );
+        return Ok(());
+    }
+
+    let mut all_valid = true;
+    for patch in &set.patches {
+        let content = match fs::read_to_string(&patch.path) {
+            Ok(content) => content,
+            Err(e) => {
+                println!(
+                    "  {} {}",
+                    theme::error("✗").bold(),
+                    tr!(
+                        "patch-check-error-reading",
+                        name = patch.name.to_string(),
+                        error = e.to_string()
+                    )
+                );
+                all_valid = false;
+                continue;
+            }
+        };
+        if !(content.contains("---") && content.contains("+++")) {
+            println!(
+                "  {} {}",
+                theme::error("✗").bold(),
+                tr!(
+                    "patch-check-not-valid-patch-format",
+                    name = patch.name.to_string()
+                )
+            );
+            all_valid = false;
+            continue;
+        }
+
+        let Some(source) = source else {
+            println!(
+                "  {} {}",
+                theme::success("✓").bold(),
+                tr!("patch-check-valid-format", name = patch.name.to_string())
+            );
+            continue;
+        };
+        let strip = match patch.strip {
+            Some(strip) => Some(strip),
+            None => patches::detect_strip(&patch.path, source)?,
+        };
+        match strip {
+            Some(strip) => println!(
+                "  {} {}",
+                theme::success("✓").bold(),
+                tr!(
+                    "patch-check-applies-with",
+                    name = patch.name.to_string(),
+                    strip = strip.to_string()
+                )
+            ),
+            None => {
+                println!(
+                    "  {} {}",
+                    theme::error("✗").bold(),
+                    tr!(
+                        "patch-check-does-not-apply-to",
+                        name = patch.name.to_string(),
+                        source = source.display().to_string()
+                    )
+                );
+                all_valid = false;
+            }
+        }
+    }
+
+    println!();
+    if all_valid {
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!(
+                "patch-check-all-patches-validated-successfully",
+                count = set.patches.len()
+            )
+        );
+        if source.is_none() {
+            println!();
+            println!("{}", tr!("patch-check-pass-source-dir-to"));
+        }
+    } else {
+        println!(
+            "{} {}",
+            theme::error(">>>").bold(),
+            tr!("patch-check-some-patches-failed-validation")
+        );
+        return Err(buckos_package::Error::PatchError {
+            package: package.to_string(),
+            reason: "Patch validation failed".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Show patch application order
+async fn cmd_patch_order(pm: &PackageManager, package: &str) -> buckos_package::Result<()> {
+    println!(
+        "{}",
+        theme::plain(tr!("patch-order-for", package = package.to_string()))
+            .bold()
+            .underlined()
+    );
+    println!();
+
+    let set = user_patch_set(pm, package, None, "0")?;
+    if set.is_empty() {
+        println!("  {}", tr!("patch-order-no-patches-found"));
+    }
+    for (idx, patch) in set.patches.iter().enumerate() {
+        match patch.strip {
+            Some(strip) => println!("  {}. {} (-p{})", idx + 1, patch.name, strip),
+            None => println!("  {}. {}", idx + 1, patch.name),
+        }
+    }
+
+    Ok(())
+}
+
+/// Apply user patches to unpacked sources (the prepare phase's eapply_user)
+async fn cmd_patch_apply(
+    pm: &PackageManager,
+    package: &str,
+    version: Option<&str>,
+    slot: &str,
+    source: &std::path::Path,
+) -> buckos_package::Result<()> {
+    let set = user_patch_set(pm, package, version, slot)?;
+    for patch in set.apply(package, source)? {
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!(
+                "patch-apply-applied",
+                name = patch.name.to_string(),
+                strip = patch.strip.unwrap_or_default().to_string()
+            )
+        );
+    }
+    Ok(())
+}
+
+/// Show package dependencies
+async fn cmd_deps(pm: &PackageManager, args: DepsArgs) -> buckos_package::Result<()> {
+    if let Some(pkg) = pm.info(&args.package).await? {
+        if args.format == "json" {
+            let output = serde_json::json!({
+                "package": args.package,
+                "dependencies": pkg.dependencies.iter().map(|d| &d.package).collect::<Vec<_>>(),
+                "runtime_dependencies": pkg.runtime_dependencies.iter().map(|d| &d.package).collect::<Vec<_>>(),
+            });
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&output).unwrap_or_default()
+            );
+        } else {
+            println!(
+                "{}",
+                theme::plain(tr!(
+                    "deps-dependencies-of",
+                    package = args.package.to_string()
+                ))
+                .bold()
+                .underlined()
+            );
+            println!();
+
+            if !pkg.dependencies.is_empty() {
+                println!("{}:", theme::accent(tr!("deps-build-dependencies")));
+                for dep in &pkg.dependencies {
+                    println!("  {}", dep.package);
+                }
+            }
+
+            if !pkg.runtime_dependencies.is_empty() {
+                println!();
+                println!("{}:", theme::accent(tr!("deps-runtime-dependencies")));
+                for dep in &pkg.runtime_dependencies {
+                    println!("  {}", dep.package);
+                }
+            }
+
+            if pkg.dependencies.is_empty() && pkg.runtime_dependencies.is_empty() {
+                println!("  {}", tr!("deps-no-dependencies"));
+            }
+        }
+    } else {
+        println!(
+            "{} {}",
+            theme::warning(">>>").bold(),
+            tr!("info-package-not-found", package = args.package.to_string())
+        );
+    }
+
+    Ok(())
+}
+
+/// Show reverse dependencies
+async fn cmd_rdeps(pm: &PackageManager, args: RdepsArgs) -> buckos_package::Result<()> {
+    let rdeps = pm.get_reverse_dependencies(&args.package).await?;
+
+    if args.format == "json" {
+        let output = buckos_package::ReverseDependencies {
+            package: args.package,
+            reverse_dependencies: rdeps,
+        };
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&output).unwrap_or_default()
+        );
+    } else {
+        println!(
+            "{}",
+            theme::plain(tr!(
+                "rdeps-reverse-dependencies-of",
+                package = args.package.to_string()
+            ))
+            .bold()
+            .underlined()
+        );
+        println!();
+
+        if rdeps.is_empty() {
+            println!(
+                "  {}",
+                tr!(
+                    "rdeps-no-packages-depend-on",
+                    package = args.package.to_string()
+                )
+            );
+        } else {
+            for rdep in &rdeps {
+                println!("  {}", rdep);
+            }
+            println!();
+            println!("{}", tr!("set-show-total-packages", count = rdeps.len()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Profile management command
+async fn cmd_profile(pm: &PackageManager, args: ProfileArgs) -> buckos_package::Result<()> {
+    match args.subcommand {
+        ProfileCommand::List => cmd_profile_list().await,
+        ProfileCommand::Show { profile } => cmd_profile_show(&profile).await,
+        ProfileCommand::Set { profile } => cmd_profile_set(pm, &profile).await,
+        ProfileCommand::Current => cmd_profile_current(pm).await,
+    }
+}
+
+/// List available profiles
+async fn cmd_profile_list() -> buckos_package::Result<()> {
+    println!(
+        "{}",
+        theme::plain(tr!("profile-list-available-profiles"))
+            .bold()
+            .underlined()
+    );
+    println!();
+
+    let profiles = vec![
+        ("minimal", "Absolute minimum system", vec!["ipv6"]),
+        (
+            "server",
+            "Server systems",
+            vec!["ssl", "ipv6", "threads", "caps"],
+        ),
+        (
+            "desktop",
+            "Desktop systems",
+            vec!["X", "dbus", "pulseaudio", "gtk", "ssl", "ipv6"],
+        ),
+        (
+            "developer",
+            "Development environment",
+            vec!["debug", "doc", "test", "ssl", "ipv6"],
+        ),
+        (
+            "hardened",
+            "Security-focused systems",
+            vec!["hardened", "pie", "ssp", "caps"],
+        ),
+        ("embedded", "Embedded systems", vec!["static", "-ipv6"]),
+        (
+            "container",
+            "Container environments",
+            vec!["static", "-pam", "-systemd"],
+        ),
+    ];
+
+    for (name, description, flags) in &profiles {
+        println!("  {} - {}", theme::success(name).bold(), description);
+        println!("    USE: {}", flags.join(" "));
+        println!();
+    }
+
+    Ok(())
+}
+
+/// Show profile information
+async fn cmd_profile_show(profile: &str) -> buckos_package::Result<()> {
+    let profiles: HashMap<&str, (&str, Vec<&str>)> = vec![
+        ("minimal", ("Absolute minimum system", vec!["ipv6"])),
+        (
+            "server",
+            ("Server systems", vec!["ssl", "ipv6", "threads", "caps"]),
+        ),
+        (
+            "desktop",
+            (
+                "Desktop systems",
+                vec!["X", "dbus", "pulseaudio", "gtk", "ssl", "ipv6"],
+            ),
+        ),
+        (
+            "developer",
+            (
+                "Development environment",
+                vec!["debug", "doc", "test", "ssl", "ipv6"],
+            ),
+        ),
+        (
+            "hardened",
+            (
+                "Security-focused systems",
+                vec!["hardened", "pie", "ssp", "caps"],
+            ),
+        ),
+        ("embedded", ("Embedded systems", vec!["static", "-ipv6"])),
+        (
+            "container",
+            ("Container environments", vec!["static", "-pam", "-systemd"]),
+        ),
+    ]
+    .into_iter()
+    .collect();
+
+    if let Some((description, flags)) = profiles.get(profile) {
+        println!(
+            "{}",
+            theme::plain(tr!("configure-profile", profile = profile.to_string()))
+                .bold()
+                .underlined()
+        );
+        println!();
+        println!(
+            "  {}: {}",
+            theme::plain(tr!("info-description")).bold(),
+            description
+        );
+        println!(
+            "  {}: {}",
+            theme::plain(tr!("info-use-flags")).bold(),
+            flags.join(" ")
+        );
+
+        // Show package set for this profile
+        let set_name = match profile {
+            "minimal" | "embedded" | "container" => "minimal",
+            "server" | "hardened" => "server",
+            "desktop" | "developer" => "desktop",
+            _ => "server",
+        };
+
+        let packages = get_set_packages(set_name);
+        println!();
+        println!(
+            "  {}:",
+            theme::plain(tr!("profile-show-base-packages")).bold()
+        );
+        for pkg in packages.iter().take(5) {
+            println!("    {}", pkg);
+        }
+        if packages.len() > 5 {
+            println!(
+                "    {}",
+                tr!(
+                    "profile-show-and-more",
+                    packages = (packages.len() - 5).to_string()
+                )
+            );
+        }
+    } else {
+        println!(
+            "{} {}",
+            theme::warning(">>>").bold(),
+            tr!(
+                "profile-show-unknown-profile",
+                profile = profile.to_string()
+            )
+        );
+    }
+
+    Ok(())
+}
+
+/// Set the active profile
+async fn cmd_profile_set(pm: &PackageManager, profile: &str) -> buckos_package::Result<()> {
+    let valid_profiles = [
+        "minimal",
+        "server",
+        "desktop",
+        "developer",
+        "hardened",
+        "embedded",
+        "container",
+    ];
+
+    if !valid_profiles.contains(&profile) {
+        println!(
+            "{} {}",
+            theme::warning(">>>").bold(),
+            tr!(
+                "profile-show-unknown-profile",
+                profile = profile.to_string()
+            )
+        );
+        println!(
+            "{}",
+            tr!(
+                "profile-set-valid-profiles",
+                valid_profiles = valid_profiles.join(", ")
+            )
+        );
+        return Ok(());
+    }
+
+    let config_path = pm.selected_profile_path();
+
+    // Create config directory
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent).ok();
+    }
+
+    // Write profile
+    fs::write(&config_path, profile)?;
+
+    println!(
+        "{} {}",
+        theme::success(">>>").bold(),
+        tr!("profile-set-to", profile = profile.to_string())
+    );
+    println!();
+    println!("{}", tr!("profile-set-run-buckos-update-world"));
+
+    Ok(())
+}
+
+/// Show current profile
+async fn cmd_profile_current(pm: &PackageManager) -> buckos_package::Result<()> {
+    let config_path = pm.selected_profile_path();
+
+    let profile = if config_path.exists() {
+        fs::read_to_string(&config_path).unwrap_or_else(|_| "default".to_string())
+    } else {
+        "default".to_string()
+    };
+
+    println!(
+        "{}: {}",
+        theme::plain(tr!("profile-current-current-profile")).bold(),
+        theme::success(profile.trim())
+    );
+
+    Ok(())
+}
+
+/// Export configuration in various formats
+async fn cmd_export(pm: &PackageManager, args: ExportArgs) -> buckos_package::Result<()> {
+    let config = pm.config();
+    let mut use_flags: Vec<String> = config.use_flags.global.iter().cloned().collect();
+    use_flags.sort();
+
+    let output = match args.format.as_str() {
+        _ if args.manifest => {
+            let profile = fs::read_to_string(pm.selected_profile_path())
+                .ok()
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty());
+            pm.export_manifest(profile).await?.render(&args.format)?
+        }
+        "json" => {
+            let mut export = serde_json::json!({
+                "profile": "default",
+                "arch": config.arch,
+                "use_flags": {
+                    "global": use_flags,
+                    "package": {}
+                },
+                "env": {
+                    "CFLAGS": config.cflags,
+                    "CXXFLAGS": config.cxxflags,
+                    "LDFLAGS": config.ldflags,
+                    "MAKEOPTS": config.makeopts
+                },
+                "accept_keywords": [],
+                "package_mask": [],
+                "package_unmask": []
+            });
+
+            if args.with_packages {
+                let installed: Vec<String> = pm
+                    .list_installed()
+                    .await?
+                    .iter()
+                    .map(|p| format!("{}-{}", p.id, p.version))
+                    .collect();
+                export["packages"] = serde_json::json!({
+                    "installed": installed
+                });
+            }
+
+            serde_json::to_string_pretty(&export).unwrap_or_default()
+        }
+        "toml" => {
+            let mut output = String::new();
+            output.push_str("# BuckOS Configuration Export\n\n");
+            output.push_str("[profile]\n");
+            output.push_str("name = \"default\"\n");
+            output.push_str(&format!("arch = \"{}\"\n\n", config.arch));
+
+            output.push_str("[use_flags]\n");
+            output.push_str(&format!("global = {:?}\n\n", use_flags));
+
+            output.push_str("[env]\n");
+            output.push_str(&format!("CFLAGS = \"{}\"\n", config.cflags));
+            output.push_str(&format!("CXXFLAGS = \"{}\"\n", config.cxxflags));
+            output.push_str(&format!("LDFLAGS = \"{}\"\n", config.ldflags));
+            output.push_str(&format!("MAKEOPTS = \"{}\"\n", config.makeopts));
+
+            output
+        }
+        "shell" => {
+            let mut output = String::new();
+            output.push_str("#!/bin/bash\n");
+            output.push_str("# BuckOS Configuration Export\n\n");
+
+            output.push_str("export PROFILE=\"default\"\n");
+            output.push_str(&format!("export ARCH=\"{}\"\n\n", config.arch));
+
+            output.push_str("# USE flags\n");
+            output.push_str(&format!("export USE=\"{}\"\n\n", use_flags.join(" ")));
+
+            output.push_str("# Environment\n");
+            output.push_str(&format!("export CFLAGS=\"{}\"\n", config.cflags));
+            output.push_str(&format!("export CXXFLAGS=\"{}\"\n", config.cxxflags));
+            output.push_str(&format!("export LDFLAGS=\"{}\"\n", config.ldflags));
+            output.push_str(&format!("export MAKEOPTS=\"{}\"\n", config.makeopts));
+
+            output
+        }
+        "buck" => {
+            let mut output = String::new();
+            output.push_str("# BuckOS Configuration Export\n\n");
+
+            output.push_str("PROFILE = \"default\"\n");
+            output.push_str(&format!("ARCH = \"{}\"\n\n", config.arch));
+
+            output.push_str(&format!("USE_FLAGS = {:?}\n\n", use_flags));
+
+            output.push_str("PACKAGE_USE = {}\n\n");
+
+            output.push_str("ENV = {\n");
+            output.push_str(&format!("    \"CFLAGS\": \"{}\",\n", config.cflags));
+            output.push_str(&format!("    \"CXXFLAGS\": \"{}\",\n", config.cxxflags));
+            output.push_str(&format!("    \"LDFLAGS\": \"{}\",\n", config.ldflags));
+            output.push_str(&format!("    \"MAKEOPTS\": \"{}\",\n", config.makeopts));
+            output.push_str("}\n");
+
+            output
+        }
+        _ => {
+            return Err(buckos_package::Error::ConfigError(format!(
+                "Unknown format: {}. Use json, toml, shell, or buck",
+                args.format
+            )));
+        }
+    };
+
+    // Write to file or stdout
+    if let Some(path) = args.output {
+        fs::write(&path, &output)?;
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!("export-configuration-exported-to", path = path.to_string())
+        );
+    } else {
+        println!("{}", output);
+    }
+
+    Ok(())
+}
+
+/// Converge this machine to a manifest
+async fn cmd_apply(
+    pm: &PackageManager,
+    args: ApplyArgs,
+    emerge_opts: &EmergeOptions,
+    config_path: Option<&std::path::Path>,
+) -> buckos_package::Result<()> {
+    let manifest = MachineManifest::load(std::path::Path::new(&args.manifest))?;
+    let mut plan = pm.plan_manifest(&manifest).await?;
+    if args.keep {
+        plan.remove.clear();
+    }
 
-    format!(
-        r#"# BuckOS Local Modifier Configuration
-# Generated by buckos configure
-# Profile: {}
-# Edit via: buckos use [flags] or buckos config set use "..."
+    let current_profile = fs::read_to_string(pm.selected_profile_path())
+        .ok()
+        .map(|p| p.trim().to_string());
+    let profile_change = manifest
+        .profile
+        .as_ref()
+        .filter(|p| current_profile.as_ref() != Some(*p));
 
-LOCAL_MODIFIERS = [
-{}
-]
-"#,
-        profile,
-        modifier_lines.join("\n"),
-    )
-}
+    if plan.is_empty() && profile_change.is_none() {
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!(
+                "apply-system-already-matches",
+                manifest = args.manifest.to_string()
+            )
+        );
+        return Ok(());
+    }
 
-/// Generate JSON configuration
-fn generate_config_json(profile: &str, flags: &[String], arch: &str) -> String {
-    serde_json::to_string_pretty(&serde_json::json!({
-        "profile": profile,
-        "use_flags": flags,
-        "arch": arch
-    }))
-    .unwrap_or_default()
+    println!(
+        "{} {}\n",
+        theme::success(">>>").bold(),
+        tr!(
+            "apply-changes-needed-to-match",
+            manifest = args.manifest.to_string()
+        )
+    );
+    if let Some(profile) = profile_change {
+        println!(
+            "  {} {}",
+            theme::paint(Role::Config, "C").bold(),
+            tr!("apply-profile", profile = profile.to_string())
+        );
+    }
+    if plan.config_changed {
+        println!(
+            "  {} {}",
+            theme::paint(Role::Config, "C").bold(),
+            tr!("apply-configuration")
+        );
+    }
+    for repo in &plan.add_repositories {
+        println!(
+            "  {} {}",
+            theme::paint(Role::Config, "C").bold(),
+            tr!(
+                "apply-repository",
+                name = repo.name.to_string(),
+                sync_uri = repo.sync_uri.to_string()
+            )
+        );
+    }
+    for pin in &plan.install {
+        println!(
+            "  {} {}-{}",
+            theme::paint(Role::New, "N").bold(),
+            pin.id,
+            pin.version
+        );
+    }
+    for pin in &plan.rebuild {
+        println!(
+            "  {} {}-{}",
+            theme::paint(Role::Rebuild, "R").bold(),
+            pin.id,
+            pin.version
+        );
+    }
+    for pin in &plan.remove {
+        println!(
+            "  {} {}:{}",
+            theme::paint(Role::Remove, "D").bold(),
+            pin.id,
+            pin.slot
+        );
+    }
+    for entry in &plan.world_add {
+        println!(
+            "  {} {}",
+            theme::paint(Role::New, "+"),
+            tr!("apply-world", entry = entry.to_string())
+        );
+    }
+    for entry in &plan.world_remove {
+        println!(
+            "  {} {}",
+            theme::paint(Role::Remove, "-"),
+            tr!("apply-world", entry = entry.to_string())
+        );
+    }
+    println!(
+        "\n{}",
+        tr!(
+            "apply-total-to-install-to",
+            install = plan.install.len(),
+            rebuild = plan.rebuild.len(),
+            remove = plan.remove.len()
+        )
+    );
+
+    if emerge_opts.pretend {
+        return Ok(());
+    }
+
+    if emerge_opts.ask {
+        println!();
+        if !Confirm::new()
+            .with_prompt("Would you like to apply this manifest?")
+            .default(false)
+            .interact()?
+        {
+            println!(
+                "{} {}",
+                theme::warning(">>>").bold(),
+                theme::warning(tr!("exiting")).bold()
+            );
+            return Ok(());
+        }
+        println!();
+    }
+
+    if let Some(profile) = profile_change {
+        let path = pm.selected_profile_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, profile)?;
+    }
+
+    // Packages must be built with the manifest's configuration, so switch to
+    // it before touching anything
+    let mut config = pm.config().clone();
+    manifest.config.apply_to(&mut config)?;
+    config
+        .repositories
+        .extend(plan.add_repositories.iter().cloned());
+
+    let target;
+    let pm = if plan.config_changed || !plan.add_repositories.is_empty() {
+        // Written back where it was loaded from
+        let path = match config_path {
+            Some(path) => path.to_path_buf(),
+            None => pm.layout().path("etc/buckos/buckos.toml"),
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        config.save_to(&path)?;
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!(
+                "apply-configuration-written-to",
+                path = path.display().to_string()
+            )
+        );
+
+        target = PackageManager::new(config).await?;
+        &target
+    } else {
+        pm
+    };
+
+    let unmatched = pm.apply_manifest(&manifest, &plan).await?;
+    for pin in &unmatched {
+        println!(
+            "{} {}",
+            theme::warning(">>>").bold(),
+            tr!(
+                "apply-is-not-at-pinned",
+                id = pin.id.to_string(),
+                version = pin.version.to_string()
+            )
+        );
+    }
+
+    println!(
+        "{} {}",
+        theme::success(">>>").bold(),
+        tr!(
+            "apply-system-converged-to",
+            manifest = args.manifest.to_string()
+        )
+    );
+    Ok(())
 }
 
-/// Generate TOML configuration
-fn generate_config_toml(profile: &str, flags: &[String], arch: &str) -> String {
-    format!(
-        r#"# BuckOs Configuration
-# Generated by buckos configure
+/// Report drift from a manifest, returning whether the system matches it
+async fn cmd_converge_check(
+    pm: &PackageManager,
+    args: &ConvergeArgs,
+) -> buckos_package::Result<bool> {
+    let manifest = MachineManifest::load(std::path::Path::new(&args.manifest))?;
+    let mut report = pm.check_drift(&manifest).await?;
+    if args.keep {
+        report.extra.clear();
+    }
 
-[system]
-profile = "{}"
-arch = "{}"
+    if args.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).unwrap_or_default()
+        );
+        return Ok(report.is_clean());
+    }
 
-[use]
-flags = [
-{}
-]
-"#,
-        profile,
-        arch,
-        flags
+    if report.is_clean() {
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!(
+                "converge-check-system-matches",
+                manifest = args.manifest.to_string()
+            )
+        );
+        return Ok(true);
+    }
+
+    println!(
+        "{} {}\n",
+        theme::warning(">>>").bold(),
+        tr!(
+            "converge-check-system-has-drifted-from",
+            manifest = args.manifest.to_string()
+        )
+    );
+    if report.config_changed {
+        println!(
+            "  {} {}",
+            theme::paint(Role::Config, "C").bold(),
+            tr!("converge-check-configuration-differs")
+        );
+    }
+    for repo in &report.missing_repositories {
+        println!(
+            "  {} {}",
+            theme::paint(Role::Config, "C").bold(),
+            tr!(
+                "converge-check-repository-not-configured",
+                repo = repo.to_string()
+            )
+        );
+    }
+    for id in &report.missing {
+        println!(
+            "  {} {}",
+            theme::paint(Role::Remove, "-").bold(),
+            tr!("converge-check-not-installed", id = id.to_string())
+        );
+    }
+    for id in &report.extra {
+        println!(
+            "  {} {}",
+            theme::paint(Role::New, "+").bold(),
+            tr!(
+                "converge-check-installed-outside-the-manifest",
+                id = id.to_string()
+            )
+        );
+    }
+    for drift in &report.versions {
+        println!(
+            "  {} {}",
+            theme::warning("V").bold(),
+            tr!(
+                "converge-check-is-pinned",
+                id = drift.id.to_string(),
+                installed = drift.installed.to_string(),
+                pinned = drift.pinned.to_string()
+            )
+        );
+    }
+    for drift in &report.use_flags {
+        let flags: Vec<String> = drift
+            .added
             .iter()
-            .map(|f| format!("    \"{}\",", f))
-            .collect::<Vec<_>>()
-            .join("\n")
-    )
+            .map(|f| format!("+{}", f))
+            .chain(drift.removed.iter().map(|f| format!("-{}", f)))
+            .collect();
+        println!(
+            "  {} {} USE {}",
+            theme::paint(Role::Update, "U").bold(),
+            drift.id,
+            flags.join(" ")
+        );
+    }
+    for entry in &report.world_added {
+        println!(
+            "  {} {}",
+            theme::paint(Role::New, "+"),
+            tr!("apply-world", entry = entry.to_string())
+        );
+    }
+    for entry in &report.world_removed {
+        println!(
+            "  {} {}",
+            theme::paint(Role::Remove, "-"),
+            tr!("apply-world", entry = entry.to_string())
+        );
+    }
+    for file in &report.config_files {
+        println!(
+            "  {} {} ({}, {})",
+            theme::highlight("M").bold(),
+            file.path,
+            file.package,
+            if file.missing {
+                tr!("converge-check-deleted")
+            } else {
+                tr!("converge-check-modified")
+            }
+        );
+    }
+
+    Ok(false)
 }
 
-/// Generate shell configuration
-fn generate_config_shell(profile: &str, flags: &[String], arch: &str) -> String {
-    format!(
-        r#"#!/bin/bash
-# BuckOs Configuration
-# Generated by buckos configure
+/// Rebuild packages with broken library dependencies
+async fn cmd_revdep(
+    pm: &PackageManager,
+    args: RevdepArgs,
+    emerge_opts: &EmergeOptions,
+) -> buckos_package::Result<()> {
+    println!(
+        "{} {}",
+        theme::info(">>>").bold(),
+        tr!("revdep-checking-for-packages-with")
+    );
 
-# Profile: {}
-BUCKOS_PROFILE="{}"
+    // Find packages with broken dependencies
+    let broken = pm
+        .find_broken_deps(args.library.as_deref(), &args.packages)
+        .await?;
+
+    // Filter out ignored packages
+    let to_rebuild: Vec<_> = broken
+        .into_iter()
+        .filter(|pkg| {
+            !args.ignore.contains(&pkg.name) && !args.ignore.contains(&pkg.id.full_name())
+        })
+        .collect();
+
+    if to_rebuild.is_empty() {
+        println!(
+            "\n{} {}",
+            theme::success(">>>").bold(),
+            tr!("revdep-no-packages-with-broken")
+        );
+        return Ok(());
+    }
+
+    // Display packages to rebuild
+    println!(
+        "\n{} {}\n",
+        theme::warning(">>>").bold(),
+        tr!("revdep-found-package-with-broken", count = to_rebuild.len())
+    );
+
+    for pkg in &to_rebuild {
+        println!(
+            "  {} {}/{}",
+            theme::paint(Role::Rebuild, "R").bold(),
+            theme::accent(&pkg.id.category),
+            theme::warning(format!("{}-{}", &pkg.name, &pkg.version))
+        );
+
+        // Show broken libraries
+        if !pkg.broken_libs.is_empty() {
+            for lib in &pkg.broken_libs {
+                println!(
+                    "      {} {}",
+                    theme::plain("->").dim(),
+                    tr!(
+                        "revdep-missing-library",
+                        lib = theme::error(lib).to_string()
+                    )
+                );
+            }
+        }
+    }
+
+    println!(
+        "\n>>> {}",
+        tr!(
+            "revdep-rebuilding-package",
+            count = theme::plain(to_rebuild.len()).bold().to_string()
+        )
+    );
+
+    // Pretend mode
+    if args.pretend || emerge_opts.pretend {
+        return Ok(());
+    }
+
+    // Ask mode
+    if emerge_opts.ask {
+        if !Confirm::new()
+            .with_prompt("Would you like to rebuild these packages?")
+            .default(true)
+            .interact()?
+        {
+            println!(
+                "{} {}",
+                theme::warning(">>>").bold(),
+                theme::warning(tr!("exiting")).bold()
+            );
+            return Ok(());
+        }
+        println!();
+    }
 
-# Architecture
-ARCH="{}"
+    // Actually rebuild
+    pm.rebuild_packages(&to_rebuild).await?;
 
-# USE flags
-USE="{}"
+    println!(
+        "\n{} {}",
+        theme::success(">>>").bold(),
+        tr!(
+            "revdep-packages-rebuilt-successfully",
+            count = to_rebuild.len()
+        )
+    );
 
-export BUCKOS_PROFILE ARCH USE
-"#,
-        profile,
-        profile,
-        arch,
-        flags.join(" ")
-    )
+    Ok(())
 }
 
-/// Package set management command
-async fn cmd_set(
-    pm: &PackageManager,
-    args: SetArgs,
-    emerge_opts: &EmergeOptions,
-) -> buckos_package::Result<()> {
-    match args.subcommand {
-        SetCommand::List { r#type } => cmd_set_list(r#type).await,
-        SetCommand::Show { set_name } => cmd_set_show(&set_name).await,
-        SetCommand::Install { set_name } => cmd_set_install(pm, &set_name, emerge_opts).await,
-        SetCommand::Compare { set1, set2 } => cmd_set_compare(&set1, &set2).await,
+/// Package signing management
+#[cfg(feature = "signing")]
+async fn cmd_sign(args: SignArgs, config: &Config) -> buckos_package::Result<()> {
+    use buckos_package::config::SigningBackend;
+    use buckos_package::security::signing::{
+        format_key, format_verification, SigningManager, TrustLevel,
+    };
+
+    let backend = match (args.backend, args.repo.as_deref()) {
+        (Some(backend), _) => backend,
+        (None, Some(repo)) => config.signing.backend_for(repo),
+        (None, None) => config.signing.backend,
+    };
+    let mut manager = SigningManager::from_config(&config.signing)?.with_backend(backend);
+
+    // Check if GPG is available
+    if !manager.is_available() {
+        return Err(buckos_package::Error::Signing(
+            "GPG is not available. Please install gnupg, or use --backend minisign.".to_string(),
+        ));
     }
-}
 
-/// List available package sets
-async fn cmd_set_list(set_type: Option<String>) -> buckos_package::Result<()> {
-    println!("{}", style("Available Package Sets").bold().underlined());
-    println!();
+    match args.subcommand {
+        SignCommand::ListKeys { secret } => {
+            println!(
+                "{}",
+                theme::plain(tr!("sign-available-signing-keys"))
+                    .bold()
+                    .underlined()
+            );
+            println!();
 
-    let sets = get_package_sets();
+            let keys = manager.list_keys(secret)?;
 
-    if let Some(t) = set_type {
-        // Filter by type
-        if let Some(type_sets) = sets.get(&t) {
-            println!("{}:", style(&t).cyan().bold());
-            for (name, info) in type_sets {
-                println!("  {} - {}", style(name).green(), info);
+            if keys.is_empty() {
+                println!("  {}", tr!("sign-no-keys-found"));
+                if secret {
+                    match backend {
+                        SigningBackend::Gpg => println!("\n  {}", tr!("sign-to-create-new-key")),
+                        SigningBackend::Minisign => {
+                            println!("\n  {}", tr!("sign-to-create-new-key-2"))
+                        }
+                    }
+                }
+            } else {
+                for key in keys {
+                    let key_type = if key.is_secret { "sec" } else { "pub" };
+                    println!(
+                        "  {} {}/{} {}",
+                        theme::plain(key_type).dim(),
+                        key.algorithm,
+                        key.key_size,
+                        key.created
+                    );
+                    println!(
+                        "        {} {}",
+                        theme::plain(tr!("sign-key-id")).bold(),
+                        key.key_id
+                    );
+                    println!(
+                        "        {} {}",
+                        theme::plain(tr!("sign-user")).bold(),
+                        key.user_id
+                    );
+                    println!(
+                        "        {} {}",
+                        theme::plain(tr!("sign-trust")).bold(),
+                        key.trust
+                    );
+                    if let Some(ref expires) = key.expires {
+                        println!(
+                            "        {} {}",
+                            theme::plain(tr!("sign-expires")).bold(),
+                            expires
+                        );
+                    }
+                    println!();
+                }
             }
-        } else {
-            println!("{} Unknown set type: {}", style(">>>").yellow().bold(), t);
-            println!("\nAvailable types: system, task, desktop");
         }
-    } else {
-        // Show all sets
-        for (set_type, type_sets) in &sets {
-            println!("{}:", style(set_type).cyan().bold());
-            for (name, description) in type_sets {
-                println!("  {} - {}", style(name).green(), description);
-            }
-            println!();
+
+        SignCommand::GenerateKey => {
+            let key_id = manager.generate_key()?;
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!(
+                    "sign-generated-minisign-key-in",
+                    key_id = key_id.to_string(),
+                    keys_dir = config.signing.keys_dir.display().to_string()
+                )
+            );
+            println!(
+                "    {}",
+                tr!(
+                    "sign-publish-pub-so-others",
+                    keys_dir = config.signing.keys_dir.display().to_string(),
+                    key_id = key_id.to_string()
+                )
+            );
         }
-    }
 
-    Ok(())
-}
+        SignCommand::ImportKey { source } => {
+            println!(
+                "{} {}",
+                theme::info(">>>").bold(),
+                tr!("sign-importing-key-from", source = source.to_string())
+            );
 
-/// Get package sets organized by type
-fn get_package_sets() -> HashMap<String, Vec<(&'static str, &'static str)>> {
-    let mut sets = HashMap::new();
+            let result = manager.import_key(&source)?;
+            println!("{}", result);
 
-    sets.insert(
-        "system".to_string(),
-        vec![
-            ("minimal", "Minimal bootable system"),
-            ("server", "Server base system"),
-            ("desktop", "Desktop base system"),
-            ("developer", "Development environment"),
-            ("hardened", "Security-hardened system"),
-        ],
-    );
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!("sign-key-imported-successfully")
+            );
+        }
 
-    sets.insert(
-        "task".to_string(),
-        vec![
-            ("web-server", "Web server packages"),
-            ("database", "Database packages"),
-            ("container", "Container runtime packages"),
-            ("virtualization", "Virtualization packages"),
-            ("monitoring", "System monitoring packages"),
-        ],
-    );
+        SignCommand::ExportKey {
+            key_id,
+            output,
+            armor,
+        } => {
+            println!(
+                "{} {}",
+                theme::info(">>>").bold(),
+                tr!(
+                    "sign-exporting-key-to",
+                    key_id = key_id.to_string(),
+                    output = output.to_string()
+                )
+            );
 
-    sets.insert(
-        "desktop".to_string(),
-        vec![
-            ("gnome", "GNOME desktop environment"),
-            ("kde", "KDE Plasma desktop environment"),
-            ("xfce", "Xfce desktop environment"),
-            ("sway", "Sway Wayland compositor"),
-        ],
-    );
+            manager.export_key(&key_id, std::path::Path::new(&output), armor)?;
 
-    sets
-}
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!("sign-key-exported-successfully")
+            );
+        }
 
-/// Show contents of a package set
-async fn cmd_set_show(set_name: &str) -> buckos_package::Result<()> {
-    let packages = get_set_packages(set_name);
+        SignCommand::SignManifest { package_dir, key } => {
+            println!(
+                "{} {}",
+                theme::info(">>>").bold(),
+                tr!(
+                    "sign-signing-manifest-in",
+                    package_dir = package_dir.to_string()
+                )
+            );
 
-    if packages.is_empty() {
-        println!("{} Unknown set: {}", style(">>>").yellow().bold(), set_name);
-        return Ok(());
-    }
+            let path = std::path::Path::new(&package_dir);
+            if !path.exists() {
+                return Err(buckos_package::Error::Signing(format!(
+                    "Directory not found: {}",
+                    package_dir
+                )));
+            }
 
-    println!(
-        "{}",
-        style(format!("Package Set: {}", set_name))
-            .bold()
-            .underlined()
-    );
-    println!();
+            // Extract package ID from directory name
+            let pkg_name = path
+                .file_name()
+                .and_then(|s| s.to_str())
+                .unwrap_or("unknown");
+            let category = path
+                .parent()
+                .and_then(|p| p.file_name())
+                .and_then(|s| s.to_str())
+                .unwrap_or("unknown");
 
-    for pkg in &packages {
-        println!("  {}", style(pkg).green());
-    }
+            let package_id = buckos_package::PackageId::new(category, pkg_name);
 
-    println!();
-    println!("Total: {} packages", packages.len());
+            // Generate manifest
+            let mut manifest = manager.generate_manifest(path, &package_id)?;
 
-    Ok(())
-}
+            // Sign it
+            manager.sign_manifest(&mut manifest, key.as_deref())?;
 
-/// Get packages in a set by reading from package_sets.bzl
-fn get_set_packages(set_name: &str) -> Vec<String> {
-    // Try to load package sets from bzl file
-    match load_package_sets_bzl() {
-        Ok(package_sets) => {
-            // Handle @system specially
-            if set_name == "system" {
-                return package_sets.get_system_packages().to_vec();
+            // Write to file
+            let manifest_path = path.join("Manifest");
+            manager.write_manifest(&manifest, &manifest_path)?;
+
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!(
+                    "sign-manifest-signed-and-written",
+                    manifest_path = manifest_path.display().to_string()
+                )
+            );
+        }
+
+        SignCommand::VerifyManifest { manifest } => {
+            println!(
+                "{} {}",
+                theme::info(">>>").bold(),
+                tr!("sign-verifying-manifest", manifest = manifest.to_string())
+            );
+
+            let path = std::path::Path::new(&manifest);
+            let pkg_manifest = manager.read_manifest(path)?;
+
+            let verification = manager.verify_manifest(&pkg_manifest)?;
+            println!("\n{}", format_verification(&verification));
+
+            if verification.valid {
+                // Also verify files
+                if let Some(parent) = path.parent() {
+                    let file_results = manager.verify_manifest_files(&pkg_manifest, parent)?;
+                    let failed: Vec<_> = file_results
+                        .iter()
+                        .filter(|r| {
+                            r.status != buckos_package::security::signing::ManifestVerifyStatus::Ok
+                        })
+                        .collect();
+
+                    if failed.is_empty() {
+                        println!(
+                            "{} {}",
+                            theme::success(">>>").bold(),
+                            tr!("sign-all-files-verified", count = file_results.len())
+                        );
+                    } else {
+                        println!(
+                            "{} {}",
+                            theme::error(">>>").bold(),
+                            tr!("sign-file-failed-verification", count = failed.len())
+                        );
+                        for result in failed {
+                            println!(
+                                "  {} {}: {}",
+                                theme::error("!"),
+                                result.path,
+                                result.message
+                            );
+                        }
+                    }
+                }
+            } else {
+                println!(
+                    "{} {}",
+                    theme::error(">>>").bold(),
+                    tr!("sign-signature-verification-failed")
+                );
             }
+        }
+
+        SignCommand::SignRepo { repo_dir, key } => {
+            println!(
+                "{} {}",
+                theme::info(">>>").bold(),
+                tr!("sign-signing-repository", repo_dir = repo_dir.to_string())
+            );
+
+            let path = std::path::Path::new(&repo_dir);
+            manager.sign_repository(path, key.as_deref())?;
 
-            // Resolve the set with inheritance
-            package_sets.resolve_set(set_name)
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!("sign-repository-signed-successfully")
+            );
         }
-        Err(e) => {
-            eprintln!("Warning: Failed to load package_sets.bzl: {}", e);
-            eprintln!("Using fallback minimal set");
-            // Return empty vec as fallback
-            Vec::new()
+
+        SignCommand::CosignRepo { repo_dir, key } => {
+            let path = std::path::Path::new(&repo_dir);
+            let sig_path = buckos_package::security::threshold::cosign_repository(
+                &manager,
+                path,
+                key.as_deref(),
+            )?;
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!(
+                    "sign-repository-co-signed",
+                    sig_path = sig_path.display().to_string()
+                )
+            );
         }
-    }
-}
 
-/// Load package sets from bzl file
-fn load_package_sets_bzl() -> buckos_package::Result<buckos_config::PackageSets> {
-    // Try to find package_sets.bzl file
-    let paths_to_try = vec![
-        std::path::PathBuf::from("../buckos-build/defs/package_sets.bzl"),
-        std::path::PathBuf::from("../../buckos-build/defs/package_sets.bzl"),
-        std::path::PathBuf::from("defs/package_sets.bzl"),
-    ];
+        SignCommand::VerifyRepo { repo_dir } => {
+            println!(
+                "{} {}",
+                theme::info(">>>").bold(),
+                tr!("sign-verifying-repository", repo_dir = repo_dir.to_string())
+            );
 
-    for path in paths_to_try {
-        if path.exists() {
-            return buckos_config::PackageSets::from_file(&path).map_err(|e| {
-                buckos_package::Error::Config(format!("Failed to parse package_sets.bzl: {}", e))
-            });
-        }
-    }
+            let path = std::path::Path::new(&repo_dir);
+            let verification = manager.verify_repository(path)?;
 
-    // Try relative to current directory
-    if let Ok(cwd) = std::env::current_dir() {
-        let path = cwd
-            .parent()
-            .map(|p| p.join("buckos-build/defs/package_sets.bzl"));
+            println!("\n{}", format_verification(&verification));
 
-        if let Some(path) = path {
-            if path.exists() {
-                return buckos_config::PackageSets::from_file(&path).map_err(|e| {
-                    buckos_package::Error::Config(format!(
-                        "Failed to parse package_sets.bzl: {}",
-                        e
-                    ))
-                });
+            if verification.valid {
+                println!(
+                    "{} {}",
+                    theme::success(">>>").bold(),
+                    tr!("sign-repository-signature-verified")
+                );
+            } else {
+                println!(
+                    "{} {}",
+                    theme::error(">>>").bold(),
+                    tr!("sign-repository-signature-verification-failed")
+                );
             }
         }
-    }
 
-    Err(buckos_package::Error::Config(
-        "package_sets.bzl not found. Please ensure buckos-build repository is accessible."
-            .to_string(),
-    ))
-}
+        SignCommand::SignFile { file, key } => {
+            println!(
+                "{} {}",
+                theme::info(">>>").bold(),
+                tr!("sign-signing-file", file = file.to_string())
+            );
 
-/// Install all packages in a set
-async fn cmd_set_install(
-    pm: &PackageManager,
-    set_name: &str,
-    emerge_opts: &EmergeOptions,
-) -> buckos_package::Result<()> {
-    let packages = get_set_packages(set_name);
+            let path = std::path::Path::new(&file);
+            let sig_path = manager.sign_file(path, key.as_deref())?;
 
-    if packages.is_empty() {
-        println!("{} Unknown set: {}", style(">>>").yellow().bold(), set_name);
-        return Ok(());
-    }
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!(
+                    "sign-file-signed",
+                    sig_path = sig_path.display().to_string()
+                )
+            );
+        }
 
-    println!(
-        "{} Installing set: {} ({} packages)",
-        style(">>>").blue().bold(),
-        set_name,
-        packages.len()
-    );
+        SignCommand::VerifyFile { file, signature } => {
+            println!(
+                "{} {}",
+                theme::info(">>>").bold(),
+                tr!("sign-verifying-file", file = file.to_string())
+            );
 
-    let opts = InstallOptions {
-        force: false,
-        no_deps: false,
-        build: true,
-        use_flags: Vec::new(),
-        oneshot: emerge_opts.oneshot,
-        fetch_only: emerge_opts.fetch_only,
-        deep: emerge_opts.deep,
-        newuse: emerge_opts.newuse,
-        empty_tree: false,
-        no_replace: true,
-        use_pkg: emerge_opts.use_pkg,
-        use_pkg_only: emerge_opts.use_pkg_only,
-        get_binpkg: emerge_opts.get_binpkg,
-        get_binpkg_only: emerge_opts.get_binpkg_only,
-        build_pkg: emerge_opts.build_pkg,
-        build_pkg_only: emerge_opts.build_pkg_only,
-    };
+            let path = std::path::Path::new(&file);
+            let sig_path = signature.map(std::path::PathBuf::from);
 
-    // Resolve dependencies
-    let resolution = pm.resolve_packages(&packages, &opts).await?;
+            let verification = manager.verify_file(path, sig_path.as_deref())?;
+            println!("\n{}", format_verification(&verification));
 
-    if resolution.packages.is_empty() {
-        println!(
-            "\n{} All packages in set are already installed",
-            style(">>>").green().bold()
-        );
-        return Ok(());
-    }
+            if verification.valid {
+                println!(
+                    "{} {}",
+                    theme::success(">>>").bold(),
+                    tr!("sign-signature-verified")
+                );
+            } else {
+                println!(
+                    "{} {}",
+                    theme::error(">>>").bold(),
+                    tr!("sign-signature-verification-failed")
+                );
+            }
+        }
 
-    // Display package list
-    print_emerge_list(&resolution, emerge_opts, "install")?;
+        SignCommand::KeyInfo { key_id } => match manager.get_key(&key_id)? {
+            Some(key) => {
+                println!(
+                    "{}",
+                    theme::plain(tr!("sign-key-information"))
+                        .bold()
+                        .underlined()
+                );
+                println!();
+                print!("{}", format_key(&key));
+            }
+            None => {
+                println!(
+                    "{} {}",
+                    theme::warning(">>>").bold(),
+                    tr!("sign-key-not-found", key_id = key_id.to_string())
+                );
+            }
+        },
 
-    // Pretend mode
-    if emerge_opts.pretend {
-        return Ok(());
-    }
+        SignCommand::SetTrust { key_id, trust } => {
+            let trust_level = match trust.to_lowercase().as_str() {
+                "unknown" => TrustLevel::Unknown,
+                "never" => TrustLevel::Never,
+                "marginal" => TrustLevel::Marginal,
+                "full" => TrustLevel::Full,
+                "ultimate" => TrustLevel::Ultimate,
+                _ => {
+                    return Err(buckos_package::Error::Signing(format!(
+                        "Invalid trust level: {}. Use: unknown, never, marginal, full, ultimate",
+                        trust
+                    )));
+                }
+            };
 
-    // Ask mode
-    if emerge_opts.ask {
-        if !Confirm::new()
-            .with_prompt("Would you like to merge these packages?")
-            .default(true)
-            .interact()?
-        {
-            println!("{}", style(">>> Exiting.").yellow().bold());
-            return Ok(());
-        }
-        println!();
-    }
+            println!(
+                "{} {}",
+                theme::info(">>>").bold(),
+                tr!(
+                    "sign-setting-trust-level-for",
+                    key_id = key_id.to_string(),
+                    trust = trust.to_string()
+                )
+            );
 
-    pm.install(&packages, opts).await?;
+            manager.set_key_trust(&key_id, trust_level)?;
 
-    println!(
-        "\n{} Set '{}' installed successfully",
-        style(">>>").green().bold(),
-        set_name
-    );
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!("sign-trust-level-updated")
+            );
+        }
+    }
 
     Ok(())
 }
 
-/// Compare two package sets
-async fn cmd_set_compare(set1: &str, set2: &str) -> buckos_package::Result<()> {
-    let packages1: HashSet<String> = get_set_packages(set1).into_iter().collect();
-    let packages2: HashSet<String> = get_set_packages(set2).into_iter().collect();
+/// Repository key rotation
+#[cfg(feature = "signing")]
+fn cmd_keys(args: KeysArgs, config: &Config) -> buckos_package::Result<()> {
+    use buckos_package::security::rotation::{KeyRotation, RepoKeys};
+    use buckos_package::security::signing::SigningManager;
+
+    match args.command {
+        KeysCommand::Status => {
+            for repo in &config.repositories {
+                let backend = config.signing.backend_for(&repo.name);
+                println!("{} ({})", theme::plain(&repo.name).bold(), backend);
+                let Some(anchors) = config.signing.repository_keys.get(&repo.name) else {
+                    println!("  {}", tr!("keys-not-verified-no-keys"));
+                    println!();
+                    continue;
+                };
+                let keys = RepoKeys::load(&config.db_path, &repo.name)?
+                    .unwrap_or_else(|| RepoKeys::new(&repo.name, anchors));
+                for key in &keys.trusted {
+                    let anchor = if anchors.iter().any(|a| a.eq_ignore_ascii_case(key)) {
+                        " (anchor)"
+                    } else {
+                        ""
+                    };
+                    println!(
+                        "  {} {}{}",
+                        theme::success(tr!("keys-trusted")),
+                        key,
+                        anchor
+                    );
+                }
+                for (key, revocation) in &keys.revoked {
+                    println!(
+                        "  {}",
+                        tr!(
+                            "keys-on",
+                            state = theme::error(tr!("keys-revoked")).to_string(),
+                            key = key.to_string(),
+                            date = revocation.date.format("%Y-%m-%d").to_string(),
+                            reason = (revocation
+                                .reason
+                                .as_deref()
+                                .map(|r| format!(": {}", r))
+                                .unwrap_or_default())
+                            .to_string()
+                        )
+                    );
+                }
+                let pending = KeyRotation::load(&repo.location)?
+                    .statements
+                    .len()
+                    .saturating_sub(keys.applied);
+                if pending > 0 {
+                    println!(
+                        "  {}",
+                        tr!(
+                            "keys-key-statement-not-applied",
+                            pending = pending.to_string()
+                        )
+                    );
+                }
+                println!();
+            }
+        }
 
-    if packages1.is_empty() {
-        println!("{} Unknown set: {}", style(">>>").yellow().bold(), set1);
-        return Ok(());
-    }
-    if packages2.is_empty() {
-        println!("{} Unknown set: {}", style(">>>").yellow().bold(), set2);
-        return Ok(());
+        KeysCommand::Verify { repo } => {
+            use buckos_package::security::threshold::{
+                verify_threshold, ThresholdPolicy, REPOS_CONF,
+            };
+
+            let repo_config = config
+                .repositories
+                .iter()
+                .find(|r| r.name == repo)
+                .ok_or_else(|| {
+                    buckos_package::Error::Config(format!("Unknown repository: {}", repo))
+                })?;
+            let policies = ThresholdPolicy::load_all(&Layout::resolve(config)?.path(REPOS_CONF))?;
+            let policy = policies.get(&repo).ok_or_else(|| {
+                buckos_package::Error::Config(format!(
+                    "Repository {} has no sync-signature-threshold in repos.conf",
+                    repo
+                ))
+            })?;
+            let manager = SigningManager::from_config(&config.signing)?
+                .with_backend(config.signing.backend_for(&repo));
+            let keys = RepoKeys::load(&config.db_path, &repo)?;
+            let result = verify_threshold(&manager, &repo_config.location, policy, keys.as_ref())?;
+
+            for key in &result.signed {
+                println!("  {} {}", theme::success(tr!("keys-signed")), key);
+            }
+            for key in &result.missing {
+                println!("  {} {}", theme::warning(tr!("keys-missing")), key);
+            }
+            for (file, reason) in &result.rejected {
+                println!(
+                    "  {} {}: {}",
+                    theme::error(tr!("keys-rejected")),
+                    file,
+                    reason
+                );
+            }
+            if result.is_met() {
+                println!(
+                    "{} {}",
+                    theme::success(">>>").bold(),
+                    tr!(
+                        "keys-of-required-signatures",
+                        count = result.signed.len(),
+                        threshold = result.threshold.to_string()
+                    )
+                );
+            } else {
+                return Err(buckos_package::Error::Signing(format!(
+                    "Repository {} has {} of {} required signatures",
+                    repo,
+                    result.signed.len(),
+                    result.threshold
+                )));
+            }
+        }
+
+        KeysCommand::Introduce {
+            repo_dir,
+            key_id,
+            signed_by,
+            public_key,
+            backend,
+        } => {
+            let manager = SigningManager::from_config(&config.signing)?
+                .with_backend(backend.unwrap_or(config.signing.backend));
+            let public_key = match public_key {
+                Some(path) => std::fs::read_to_string(path)?,
+                None => {
+                    let dir = tempfile::tempdir()?;
+                    let path = dir.path().join("key");
+                    manager.export_key(&key_id, &path, true)?;
+                    std::fs::read_to_string(path)?
+                }
+            };
+            let repo_dir = std::path::Path::new(&repo_dir);
+            let mut rotation = KeyRotation::load(repo_dir)?;
+            rotation.introduce(&manager, &key_id, &public_key, &signed_by)?;
+            rotation.save(repo_dir)?;
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!(
+                    "keys-key-introduced-signed-by",
+                    key_id = key_id.to_string(),
+                    signed_by = signed_by.to_string()
+                )
+            );
+        }
+
+        KeysCommand::Revoke {
+            repo_dir,
+            key_id,
+            signed_by,
+            reason,
+            backend,
+        } => {
+            let manager = SigningManager::from_config(&config.signing)?
+                .with_backend(backend.unwrap_or(config.signing.backend));
+            let repo_dir = std::path::Path::new(&repo_dir);
+            let mut rotation = KeyRotation::load(repo_dir)?;
+            rotation.revoke(&manager, &key_id, reason.as_deref(), &signed_by)?;
+            rotation.save(repo_dir)?;
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!(
+                    "keys-key-revoked-signed-by",
+                    key_id = key_id.to_string(),
+                    signed_by = signed_by.to_string()
+                )
+            );
+        }
     }
 
-    let added: Vec<_> = packages2.difference(&packages1).collect();
-    let removed: Vec<_> = packages1.difference(&packages2).collect();
-    let common: Vec<_> = packages1.intersection(&packages2).collect();
+    Ok(())
+}
 
-    println!(
-        "{} vs {}",
-        style(set1).cyan().bold(),
-        style(set2).cyan().bold()
-    );
-    println!();
+async fn cmd_verity(pm: &PackageManager, args: VerityArgs) -> buckos_package::Result<()> {
+    match args.command {
+        VerityCommand::Status { all } => {
+            let coverage = pm.verity_status().await?;
+            let files: usize = coverage.iter().map(|c| c.files).sum();
+            let recorded: usize = coverage.iter().map(|c| c.recorded).sum();
+            let intact: usize = coverage.iter().map(|c| c.intact).sum();
+
+            for c in &coverage {
+                if c.tampered.is_empty() && (!all || c.files == 0) {
+                    continue;
+                }
+                let status = if !c.tampered.is_empty() {
+                    theme::error(format!("{} tampered", c.tampered.len()))
+                } else if c.recorded < c.files {
+                    theme::warning("partial".to_string())
+                } else {
+                    theme::success("protected".to_string())
+                };
+                println!(
+                    "{}",
+                    tr!(
+                        "verity-of-file-protected",
+                        package = theme::plain(&c.package).bold().to_string(),
+                        recorded = c.recorded.to_string(),
+                        files = c.files.to_string(),
+                        status = status.to_string()
+                    )
+                );
+                for path in &c.tampered {
+                    println!("    {}", path);
+                }
+            }
 
-    if !added.is_empty() {
-        println!("{}:", style("Added in second set").green());
-        for pkg in &added {
-            println!("  + {}", pkg);
+            if !pm.config().verity.enabled {
+                println!(
+                    "{} {}",
+                    theme::warning("!!!").bold(),
+                    tr!("verity-fs-verity-is-disabled")
+                );
+            }
+            let percent = if files == 0 {
+                100.0
+            } else {
+                recorded as f64 * 100.0 / files as f64
+            };
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!(
+                    "verity-of-covered-file-protected",
+                    recorded = recorded.to_string(),
+                    files = files.to_string(),
+                    percent = format!("{:.1}", percent),
+                    intact = intact.to_string()
+                )
+            );
+            if intact < recorded {
+                println!(
+                    "{} {}",
+                    theme::error(">>>").bold(),
+                    tr!(
+                        "verity-protected-file-lost-fs",
+                        intact = (recorded - intact).to_string()
+                    )
+                );
+            }
         }
-        println!();
-    }
-
-    if !removed.is_empty() {
-        println!("{}:", style("Removed from first set").red());
-        for pkg in &removed {
-            println!("  - {}", pkg);
+        VerityCommand::Enable { packages } => {
+            println!(
+                "{} {}",
+                theme::info(">>>").bold(),
+                tr!("verity-enabling-fs-verity-on")
+            );
+            let protected = pm.enable_verity(&packages).await?;
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!("verity-file-protected", protected = protected.to_string())
+            );
         }
-        println!();
     }
-
-    println!("Common packages: {}", style(common.len()).bold());
-
     Ok(())
 }
 
-/// Patch management command
-async fn cmd_patch(args: PatchArgs) -> buckos_package::Result<()> {
+/// Handle overlay commands
+async fn cmd_overlay(layout: &Layout, args: OverlayArgs) -> buckos_package::Result<()> {
+    let defaults = OverlayConfig::default();
+    let config = OverlayConfig {
+        config_path: layout.path(&defaults.config_path),
+        list_path: layout.path(&defaults.list_path),
+        storage_dir: layout.path(&defaults.storage_dir),
+        ..defaults
+    };
+    let mut manager = OverlayManager::new(config)?;
+
     match args.subcommand {
-        PatchCommand::List { package } => cmd_patch_list(&package).await,
-        PatchCommand::Info {
-            package,
-            patch_name,
-        } => cmd_patch_info(&package, &patch_name).await,
-        PatchCommand::Add {
-            package,
-            patch_file,
-        } => cmd_patch_add(&package, &patch_file).await,
-        PatchCommand::Remove {
-            package,
-            patch_name,
-        } => cmd_patch_remove(&package, &patch_name).await,
-        PatchCommand::Check { package } => cmd_patch_check(&package).await,
-        PatchCommand::Order { package } => cmd_patch_order(&package).await,
-    }
-}
+        OverlayCommand::List { enabled, all } => {
+            let overlays = if enabled {
+                manager.list_enabled()
+            } else if all {
+                manager.list_all()
+            } else {
+                // Default: show all overlays with status
+                manager.list_all()
+            };
 
-/// List patches for a package
-async fn cmd_patch_list(package: &str) -> buckos_package::Result<()> {
-    println!(
-        "{}",
-        style(format!("Patches for {}", package))
-            .bold()
-            .underlined()
-    );
-    println!();
+            if overlays.is_empty() {
+                println!(
+                    "{} {}",
+                    theme::warning(">>>").bold(),
+                    tr!("overlay-no-overlays-configured")
+                );
+                return Ok(());
+            }
 
-    // Check for patches in standard locations
-    let patch_dirs = vec![
-        format!("/etc/portage/patches/{}", package),
-        format!("/var/db/buckos/patches/{}", package),
-    ];
+            println!(
+                "{}",
+                theme::plain(tr!("overlay-configured-overlays"))
+                    .bold()
+                    .underlined()
+            );
+            println!();
 
-    let mut found_patches = Vec::new();
+            for overlay in overlays {
+                let status = if overlay.enabled {
+                    theme::success("*").bold()
+                } else {
+                    theme::plain(" ").dim()
+                };
 
-    for dir in &patch_dirs {
-        let path = std::path::Path::new(dir);
-        if path.exists() {
-            if let Ok(entries) = fs::read_dir(path) {
-                for entry in entries.flatten() {
-                    if entry.path().extension().is_some_and(|e| e == "patch") {
-                        let name = entry.file_name().to_string_lossy().to_string();
-                        found_patches.push((name, dir.clone()));
-                    }
+                let quality = match overlay.quality {
+                    OverlayQuality::Official => theme::success("[official]"),
+                    OverlayQuality::Community => theme::info("[community]"),
+                    OverlayQuality::Experimental => theme::warning("[experimental]"),
+                    OverlayQuality::Local => theme::accent("[local]"),
+                };
+
+                println!(
+                    " {}",
+                    tr!(
+                        "overlay-priority",
+                        status = status.to_string(),
+                        name = theme::plain(&overlay.name).bold().to_string(),
+                        quality = quality.to_string(),
+                        priority = overlay.priority.to_string(),
+                        enabled = if overlay.enabled {
+                            String::new()
+                        } else {
+                            tr!("overlay-disabled-2")
+                        }
+                    )
+                );
+
+                if !overlay.description.is_empty() {
+                    println!("     {}", theme::plain(&overlay.description).dim());
                 }
             }
-        }
-    }
 
-    if found_patches.is_empty() {
-        println!("No patches found for {}", package);
-        println!();
-        println!("Patch locations checked:");
-        for dir in &patch_dirs {
-            println!("  {}", dir);
-        }
-    } else {
-        for (patch, source) in &found_patches {
-            println!("  {} ({})", style(patch).green(), source);
+            println!();
+            println!(
+                "{}",
+                tr!(
+                    "overlay-enabled-overlay",
+                    legend = theme::plain(tr!("overlay-legend")).dim().to_string()
+                )
+            );
         }
-        println!();
-        println!("Total: {} patches", found_patches.len());
-    }
 
-    Ok(())
-}
-
-/// Show patch information
-async fn cmd_patch_info(package: &str, patch_name: &str) -> buckos_package::Result<()> {
-    let patch_path = format!("/etc/portage/patches/{}/{}", package, patch_name);
+        OverlayCommand::Add {
+            name,
+            uri,
+            sync_type,
+            priority,
+            location,
+        } => {
+            let sync_type_enum = match sync_type.to_lowercase().as_str() {
+                "git" => SyncType::Git,
+                "rsync" => SyncType::Rsync,
+                "http" | "https" => SyncType::Http,
+                "local" => SyncType::Local,
+                _ => {
+                    return Err(buckos_package::Error::InvalidOverlayConfig(format!(
+                        "Unknown sync type: {}. Use: git, rsync, http, local",
+                        sync_type
+                    )));
+                }
+            };
 
-    if !std::path::Path::new(&patch_path).exists() {
-        println!(
-            "{} Patch not found: {}",
-            style(">>>").yellow().bold(),
-            patch_path
-        );
-        return Ok(());
-    }
+            if sync_type_enum == SyncType::Local {
+                let path = location.ok_or_else(|| {
+                    buckos_package::Error::InvalidOverlayConfig(
+                        "Local overlays require --location".to_string(),
+                    )
+                })?;
 
-    println!("{}", style("Patch Information").bold().underlined());
-    println!();
-    println!("  {}: {}", style("Name").bold(), patch_name);
-    println!("  {}: {}", style("Package").bold(), package);
-    println!("  {}: {}", style("Path").bold(), patch_path);
+                println!(
+                    "{} {}",
+                    theme::info(">>>").bold(),
+                    tr!("overlay-adding-local-overlay", name = name.to_string())
+                );
 
-    // Read first few lines of patch to show description
-    if let Ok(content) = fs::read_to_string(&patch_path) {
-        let lines: Vec<&str> = content.lines().take(10).collect();
-        if !lines.is_empty() {
-            println!();
-            println!("{}:", style("Header").bold());
-            for line in lines {
-                println!("  {}", line);
-            }
-        }
-    }
+                manager.add_local(&name, std::path::Path::new(&path), priority)?;
+            } else {
+                let uri = uri.ok_or_else(|| {
+                    buckos_package::Error::InvalidOverlayConfig(
+                        "Remote overlays require --uri".to_string(),
+                    )
+                })?;
 
-    Ok(())
-}
+                println!(
+                    "{} {}",
+                    theme::info(">>>").bold(),
+                    tr!(
+                        "overlay-adding-overlay-from",
+                        name = name.to_string(),
+                        uri = uri.to_string()
+                    )
+                );
 
-/// Add a user patch
-async fn cmd_patch_add(package: &str, patch_file: &str) -> buckos_package::Result<()> {
-    let patch_dir = format!("/etc/portage/patches/{}", package);
-    let patch_path = std::path::Path::new(&patch_dir);
+                manager.add_remote(&name, &uri, sync_type_enum, priority)?;
+            }
 
-    // Create directory if it doesn't exist
-    fs::create_dir_all(&patch_dir)?;
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!("overlay-added-successfully", name = name.to_string())
+            );
+            println!(
+                "  {}",
+                tr!("overlay-use-buckos-overlay-enable", name = name.to_string())
+            );
+        }
 
-    // Copy patch file
-    let source = std::path::Path::new(patch_file);
-    let file_name = source.file_name().ok_or_else(|| {
-        std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid patch file name")
-    })?;
-    let dest = patch_path.join(file_name);
+        OverlayCommand::Remove { name, delete } => {
+            println!(
+                "{} {}",
+                theme::info(">>>").bold(),
+                tr!("overlay-removing-overlay", name = name.to_string())
+            );
 
-    fs::copy(source, &dest)?;
+            manager.remove(&name, delete)?;
 
-    println!(
-        "{} Added patch: {}",
-        style(">>>").green().bold(),
-        dest.display()
-    );
-    println!();
-    println!(
-        "The patch will be applied during the next build of {}",
-        package
-    );
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!("overlay-removed", name = name.to_string())
+            );
+        }
 
-    Ok(())
-}
+        OverlayCommand::Enable { name } => {
+            println!(
+                "{} {}",
+                theme::info(">>>").bold(),
+                tr!("overlay-enabling-overlay", name = name.to_string())
+            );
 
-/// Remove a user patch
-async fn cmd_patch_remove(package: &str, patch_name: &str) -> buckos_package::Result<()> {
-    let patch_path = format!("/etc/portage/patches/{}/{}", package, patch_name);
+            manager.enable(&name)?;
 
-    if !std::path::Path::new(&patch_path).exists() {
-        println!(
-            "{} Patch not found: {}",
-            style(">>>").yellow().bold(),
-            patch_path
-        );
-        return Ok(());
-    }
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!("overlay-enabled", name = name.to_string())
+            );
+        }
 
-    fs::remove_file(&patch_path)?;
+        OverlayCommand::Disable { name } => {
+            println!(
+                "{} {}",
+                theme::info(">>>").bold(),
+                tr!("overlay-disabling-overlay", name = name.to_string())
+            );
 
-    println!(
-        "{} Removed patch: {}",
-        style(">>>").green().bold(),
-        patch_path
-    );
+            manager.disable(&name)?;
 
-    Ok(())
-}
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!("overlay-disabled", name = name.to_string())
+            );
+        }
 
-/// Check if patches apply cleanly
-async fn cmd_patch_check(package: &str) -> buckos_package::Result<()> {
-    println!(
-        "{} Checking patches for {}...",
-        style(">>>").blue().bold(),
-        package
-    );
+        OverlayCommand::Sync { name } => {
+            if let Some(name) = name {
+                println!(
+                    "{} {}",
+                    theme::info(">>>").bold(),
+                    tr!("overlay-syncing-overlay", name = name.to_string())
+                );
 
-    let patch_dir = format!("/etc/portage/patches/{}", package);
-    let path = std::path::Path::new(&patch_dir);
+                manager.sync(&name).await?;
 
-    if !path.exists() {
-        println!("{} No patches to check", style(">>>").green().bold());
-        return Ok(());
-    }
+                println!(
+                    "{} {}",
+                    theme::success(">>>").bold(),
+                    tr!("overlay-synced", name = name.to_string())
+                );
+            } else {
+                println!(
+                    "{} {}",
+                    theme::info(">>>").bold(),
+                    tr!("overlay-syncing-all-enabled-overlays")
+                );
 
-    // Get list of patches
-    let mut patches = Vec::new();
-    let series_file = format!("{}/series", patch_dir);
+                manager.sync_all().await?;
 
-    if std::path::Path::new(&series_file).exists() {
-        // Use series file if it exists
-        if let Ok(content) = fs::read_to_string(&series_file) {
-            for line in content.lines() {
-                let line = line.trim();
-                if !line.is_empty() && !line.starts_with('#') {
-                    patches.push(line.to_string());
-                }
+                println!(
+                    "{} {}",
+                    theme::success(">>>").bold(),
+                    tr!("overlay-all-overlays-synced")
+                );
             }
         }
-    } else {
-        // Auto-discover patches by sorting alphabetically
-        if let Ok(entries) = fs::read_dir(&patch_dir) {
-            let mut patch_files: Vec<_> = entries
-                .filter_map(|e| e.ok())
-                .filter(|e| {
-                    e.path()
-                        .extension()
-                        .map(|ext| ext == "patch" || ext == "diff")
-                        .unwrap_or(false)
-                })
-                .collect();
-            patch_files.sort_by_key(|e| e.file_name());
 
-            for entry in patch_files {
-                if let Some(name) = entry.file_name().to_str() {
-                    patches.push(name.to_string());
+        OverlayCommand::Info { name } => match manager.get_info(&name) {
+            Some(overlay) => {
+                println!(
+                    "{}",
+                    theme::plain(tr!("overlay-information")).bold().underlined()
+                );
+                println!();
+                println!(
+                    "  {} {}",
+                    theme::plain(tr!("overlay-name")).bold(),
+                    overlay.name
+                );
+                println!(
+                    "  {} {}",
+                    theme::plain(tr!("overlay-description")).bold(),
+                    overlay.description
+                );
+                println!(
+                    "  {} {:?}",
+                    theme::plain(tr!("overlay-sync-type")).bold(),
+                    overlay.sync_type
+                );
+                println!(
+                    "  {} {}",
+                    theme::plain(tr!("overlay-sync-uri")).bold(),
+                    overlay.sync_uri
+                );
+                println!(
+                    "  {} {}",
+                    theme::plain(tr!("overlay-location")).bold(),
+                    overlay.location.display()
+                );
+                println!(
+                    "  {} {}",
+                    theme::plain(tr!("overlay-priority-2")).bold(),
+                    overlay.priority
+                );
+                println!(
+                    "  {} {}",
+                    theme::plain(tr!("overlay-quality")).bold(),
+                    overlay.quality
+                );
+                println!(
+                    "  {} {}",
+                    theme::plain(tr!("overlay-status")).bold(),
+                    if overlay.enabled {
+                        tr!("overlay-enabled-2")
+                    } else {
+                        tr!("overlay-disabled-3")
+                    }
+                );
+                println!(
+                    "  {} {}",
+                    theme::plain(tr!("overlay-auto-sync")).bold(),
+                    if overlay.auto_sync {
+                        tr!("overlay-yes")
+                    } else {
+                        tr!("overlay-no")
+                    }
+                );
+                if let Some(owner) = &overlay.owner {
+                    println!("  {} {}", theme::plain(tr!("overlay-owner")).bold(), owner);
                 }
-            }
-        }
-    }
-
-    if patches.is_empty() {
-        println!("{} No patches found to check", style(">>>").yellow().bold());
-        return Ok(());
-    }
-
-    // Verify patches can be applied (dry-run)
-    let mut all_valid = true;
-    let mut checked = 0;
-
-    for patch_name in &patches {
-        let patch_path = format!("{}/{}", patch_dir, patch_name);
-
-        // Check if patch file is valid by reading it
-        match fs::read_to_string(&patch_path) {
-            Ok(content) => {
-                // Basic validation: check if it looks like a patch file
-                if content.contains("---") && content.contains("+++") {
+                if let Some(homepage) = &overlay.homepage {
                     println!(
-                        "  {} {} (valid format)",
-                        style("✓").green().bold(),
-                        patch_name
+                        "  {} {}",
+                        theme::plain(tr!("overlay-homepage")).bold(),
+                        homepage
                     );
-                    checked += 1;
-                } else {
+                }
+                if !overlay.masters.is_empty() {
                     println!(
-                        "  {} {} (not a valid patch format)",
-                        style("✗").red().bold(),
-                        patch_name
+                        "  {} {}",
+                        theme::plain(tr!("overlay-masters")).bold(),
+                        overlay.masters.join(", ")
+                    );
+                }
+                if let Some(last_sync) = overlay.last_sync {
+                    let datetime = chrono::DateTime::from_timestamp(last_sync as i64, 0)
+                        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                        .unwrap_or_else(|| "unknown".to_string());
+                    println!(
+                        "  {} {}",
+                        theme::plain(tr!("overlay-last-sync")).bold(),
+                        datetime
                     );
-                    all_valid = false;
                 }
             }
-            Err(e) => {
+            None => {
                 println!(
-                    "  {} {} (error reading: {})",
-                    style("✗").red().bold(),
-                    patch_name,
-                    e
+                    "{} {}",
+                    theme::warning(">>>").bold(),
+                    tr!("overlay-not-found", name = name.to_string())
                 );
-                all_valid = false;
             }
-        }
-    }
-
-    println!();
-    if all_valid {
-        println!(
-            "{} All {} patches validated successfully",
-            style(">>>").green().bold(),
-            checked
-        );
-        println!();
-        println!("Note: Full dry-run test requires source extraction.");
-        println!("Patches will be tested during actual package build.");
-    } else {
-        println!(
-            "{} Some patches failed validation",
-            style(">>>").red().bold()
-        );
-        return Err(buckos_package::Error::PatchError {
-            package: package.to_string(),
-            reason: "Patch validation failed".to_string(),
-        });
-    }
+        },
 
-    Ok(())
-}
+        OverlayCommand::Priority { name, priority } => {
+            println!(
+                "{} {}",
+                theme::info(">>>").bold(),
+                tr!(
+                    "overlay-setting-priority-for-to",
+                    name = name.to_string(),
+                    priority = priority.to_string()
+                )
+            );
 
-/// Show patch application order
-async fn cmd_patch_order(package: &str) -> buckos_package::Result<()> {
-    println!(
-        "{}",
-        style(format!("Patch Order for {}", package))
-            .bold()
-            .underlined()
-    );
-    println!();
+            manager.set_priority(&name, priority)?;
 
-    let patch_dir = format!("/etc/portage/patches/{}", package);
-    let series_file = format!("{}/series", patch_dir);
-
-    if std::path::Path::new(&series_file).exists() {
-        // Use series file if it exists
-        if let Ok(content) = fs::read_to_string(&series_file) {
-            let mut idx = 1;
-            for line in content.lines() {
-                let line = line.trim();
-                if !line.is_empty() && !line.starts_with('#') {
-                    println!("  {}. {}", idx, line);
-                    idx += 1;
-                }
-            }
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!("overlay-priority-updated")
+            );
         }
-    } else {
-        // List patches alphabetically
-        let path = std::path::Path::new(&patch_dir);
-        if path.exists() {
-            let mut patches: Vec<_> = fs::read_dir(path)?
-                .flatten()
-                .filter(|e| e.path().extension().is_some_and(|e| e == "patch"))
-                .map(|e| e.file_name().to_string_lossy().to_string())
-                .collect();
-            patches.sort();
 
-            for (idx, patch) in patches.iter().enumerate() {
-                println!("  {}. {}", idx + 1, patch);
-            }
+        OverlayCommand::Search { query } => {
+            let results = manager.search(&query);
 
-            if patches.is_empty() {
-                println!("  No patches found");
+            if results.is_empty() {
+                println!(
+                    "{} {}",
+                    theme::warning(">>>").bold(),
+                    tr!(
+                        "overlay-no-overlays-found-matching",
+                        query = query.to_string()
+                    )
+                );
+                return Ok(());
             }
-        } else {
-            println!("  No patches found");
-        }
-    }
-
-    Ok(())
-}
 
-/// Show package dependencies
-async fn cmd_deps(pm: &PackageManager, args: DepsArgs) -> buckos_package::Result<()> {
-    if let Some(pkg) = pm.info(&args.package).await? {
-        if args.format == "json" {
-            let output = serde_json::json!({
-                "package": args.package,
-                "dependencies": pkg.dependencies.iter().map(|d| &d.package).collect::<Vec<_>>(),
-                "runtime_dependencies": pkg.runtime_dependencies.iter().map(|d| &d.package).collect::<Vec<_>>(),
-            });
-            println!(
-                "{}",
-                serde_json::to_string_pretty(&output).unwrap_or_default()
-            );
-        } else {
             println!(
-                "{}",
-                style(format!("Dependencies of {}", args.package))
-                    .bold()
-                    .underlined()
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!(
+                    "overlay-found-overlay-matching",
+                    count = results.len(),
+                    query = query.to_string()
+                )
             );
             println!();
 
-            if !pkg.dependencies.is_empty() {
-                println!("{}:", style("Build Dependencies").cyan());
-                for dep in &pkg.dependencies {
-                    println!("  {}", dep.package);
-                }
-            }
+            for overlay in results {
+                let quality = match overlay.quality {
+                    OverlayQuality::Official => theme::success("[official]"),
+                    OverlayQuality::Community => theme::info("[community]"),
+                    OverlayQuality::Experimental => theme::warning("[experimental]"),
+                    OverlayQuality::Local => theme::accent("[local]"),
+                };
 
-            if !pkg.runtime_dependencies.is_empty() {
-                println!();
-                println!("{}:", style("Runtime Dependencies").cyan());
-                for dep in &pkg.runtime_dependencies {
-                    println!("  {}", dep.package);
+                println!("  {} {}", theme::plain(&overlay.name).bold(), quality);
+                if !overlay.description.is_empty() {
+                    println!("    {}", theme::plain(&overlay.description).dim());
                 }
             }
-
-            if pkg.dependencies.is_empty() && pkg.runtime_dependencies.is_empty() {
-                println!("  No dependencies");
-            }
         }
-    } else {
-        println!(
-            "{} Package '{}' not found",
-            style(">>>").yellow().bold(),
-            args.package
-        );
     }
 
     Ok(())
 }
 
-/// Show reverse dependencies
-async fn cmd_rdeps(pm: &PackageManager, args: RdepsArgs) -> buckos_package::Result<()> {
-    let rdeps = pm.get_reverse_dependencies(&args.package).await?;
-
-    if args.format == "json" {
-        let output = serde_json::json!({
-            "package": args.package,
-            "reverse_dependencies": rdeps,
-        });
-        println!(
-            "{}",
-            serde_json::to_string_pretty(&output).unwrap_or_default()
-        );
-    } else {
-        println!(
-            "{}",
-            style(format!("Reverse Dependencies of {}", args.package))
-                .bold()
-                .underlined()
-        );
-        println!();
-
-        if rdeps.is_empty() {
-            println!("  No packages depend on {}", args.package);
-        } else {
-            for rdep in &rdeps {
-                println!("  {}", rdep);
+/// World set management
+async fn cmd_world(
+    pm: &PackageManager,
+    args: WorldArgs,
+    emerge_opts: &EmergeOptions,
+) -> buckos_package::Result<()> {
+    match args.subcommand {
+        WorldCommand::Clean { dry_run, json } => {
+            if json {
+                let issues = pm.analyze_world().await?;
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&issues).unwrap_or_default()
+                );
+                return Ok(());
             }
-            println!();
-            println!("Total: {} packages", rdeps.len());
+            cmd_world_clean(pm, dry_run || emerge_opts.pretend).await
         }
     }
-
-    Ok(())
-}
-
-/// Profile management command
-async fn cmd_profile(args: ProfileArgs) -> buckos_package::Result<()> {
-    match args.subcommand {
-        ProfileCommand::List => cmd_profile_list().await,
-        ProfileCommand::Show { profile } => cmd_profile_show(&profile).await,
-        ProfileCommand::Set { profile } => cmd_profile_set(&profile).await,
-        ProfileCommand::Current => cmd_profile_current().await,
-    }
 }
 
-/// List available profiles
-async fn cmd_profile_list() -> buckos_package::Result<()> {
-    println!("{}", style("Available Profiles").bold().underlined());
-    println!();
-
-    let profiles = vec![
-        ("minimal", "Absolute minimum system", vec!["ipv6"]),
-        (
-            "server",
-            "Server systems",
-            vec!["ssl", "ipv6", "threads", "caps"],
-        ),
-        (
-            "desktop",
-            "Desktop systems",
-            vec!["X", "dbus", "pulseaudio", "gtk", "ssl", "ipv6"],
-        ),
-        (
-            "developer",
-            "Development environment",
-            vec!["debug", "doc", "test", "ssl", "ipv6"],
-        ),
-        (
-            "hardened",
-            "Security-focused systems",
-            vec!["hardened", "pie", "ssp", "caps"],
-        ),
-        ("embedded", "Embedded systems", vec!["static", "-ipv6"]),
-        (
-            "container",
-            "Container environments",
-            vec!["static", "-pam", "-systemd"],
-        ),
-    ];
-
-    for (name, description, flags) in &profiles {
-        println!("  {} - {}", style(name).green().bold(), description);
-        println!("    USE: {}", flags.join(" "));
-        println!();
-    }
-
-    Ok(())
-}
+/// Analyze the world file and offer to remove problematic entries
+async fn cmd_world_clean(pm: &PackageManager, dry_run: bool) -> buckos_package::Result<()> {
+    println!(
+        "{} {}",
+        theme::info(">>>").bold(),
+        tr!("world-clean-analyzing-world-set")
+    );
 
-/// Show profile information
-async fn cmd_profile_show(profile: &str) -> buckos_package::Result<()> {
-    let profiles: HashMap<&str, (&str, Vec<&str>)> = vec![
-        ("minimal", ("Absolute minimum system", vec!["ipv6"])),
-        (
-            "server",
-            ("Server systems", vec!["ssl", "ipv6", "threads", "caps"]),
-        ),
-        (
-            "desktop",
-            (
-                "Desktop systems",
-                vec!["X", "dbus", "pulseaudio", "gtk", "ssl", "ipv6"],
-            ),
-        ),
-        (
-            "developer",
-            (
-                "Development environment",
-                vec!["debug", "doc", "test", "ssl", "ipv6"],
-            ),
-        ),
-        (
-            "hardened",
-            (
-                "Security-focused systems",
-                vec!["hardened", "pie", "ssp", "caps"],
-            ),
-        ),
-        ("embedded", ("Embedded systems", vec!["static", "-ipv6"])),
-        (
-            "container",
-            ("Container environments", vec!["static", "-pam", "-systemd"]),
-        ),
-    ]
-    .into_iter()
-    .collect();
+    let issues = pm.analyze_world().await?;
 
-    if let Some((description, flags)) = profiles.get(profile) {
+    if issues.is_empty() {
         println!(
-            "{}",
-            style(format!("Profile: {}", profile)).bold().underlined()
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!("world-clean-world-set-is-clean")
         );
-        println!();
-        println!("  {}: {}", style("Description").bold(), description);
-        println!("  {}: {}", style("USE flags").bold(), flags.join(" "));
-
-        // Show package set for this profile
-        let set_name = match profile {
-            "minimal" | "embedded" | "container" => "minimal",
-            "server" | "hardened" => "server",
-            "desktop" | "developer" => "desktop",
-            _ => "server",
-        };
-
-        let packages = get_set_packages(set_name);
-        println!();
-        println!("  {}:", style("Base packages").bold());
-        for pkg in packages.iter().take(5) {
-            println!("    {}", pkg);
-        }
-        if packages.len() > 5 {
-            println!("    ... and {} more", packages.len() - 5);
-        }
-    } else {
+        return Ok(());
+    }
+
+    println!();
+    for issue in &issues {
         println!(
-            "{} Unknown profile: {}",
-            style(">>>").yellow().bold(),
-            profile
+            "  {} {}",
+            theme::warning(&issue.entry),
+            theme::plain(format!("({})", issue.describe())).dim()
         );
     }
+    println!(
+        "\n{}",
+        tr!(
+            "world-clean-found-problem",
+            issues = theme::plain(issues.len()).bold().to_string()
+        )
+    );
 
-    Ok(())
-}
+    if dry_run {
+        return Ok(());
+    }
 
-/// Set the active profile
-async fn cmd_profile_set(profile: &str) -> buckos_package::Result<()> {
-    let valid_profiles = [
-        "minimal",
-        "server",
-        "desktop",
-        "developer",
-        "hardened",
-        "embedded",
-        "container",
-    ];
+    println!();
+    let mut to_remove = Vec::new();
+    for issue in &issues {
+        let prompt = match &issue.kind {
+            WorldIssueKind::NotInstalled => format!(
+                "Remove {} from world? (or install it with 'buckos install {}')",
+                issue.entry, issue.entry
+            ),
+            _ => format!("Remove {} from world?", issue.entry),
+        };
+        if Confirm::new()
+            .with_prompt(prompt)
+            .default(!matches!(issue.kind, WorldIssueKind::NotInstalled))
+            .interact()?
+        {
+            to_remove.push(issue.entry.clone());
+        }
+    }
 
-    if !valid_profiles.contains(&profile) {
+    if to_remove.is_empty() {
         println!(
-            "{} Unknown profile: {}",
-            style(">>>").yellow().bold(),
-            profile
+            "{} {}",
+            theme::warning(">>>").bold(),
+            theme::warning(tr!("no-changes")).bold()
         );
-        println!("Valid profiles: {}", valid_profiles.join(", "));
         return Ok(());
     }
 
-    let config_path = dirs::config_dir()
-        .unwrap_or_else(|| std::path::PathBuf::from("/etc/buckos"))
-        .join("buckos")
-        .join("profile");
+    pm.remove_world_entries(&to_remove).await?;
+    println!(
+        "{} {}",
+        theme::success(">>>").bold(),
+        tr!("world-clean-removed", count = to_remove.len())
+    );
 
-    // Create config directory
-    if let Some(parent) = config_path.parent() {
-        fs::create_dir_all(parent).ok();
-    }
+    Ok(())
+}
 
-    // Write profile
-    fs::write(&config_path, profile)?;
+/// Workspace management
+async fn cmd_workspace(args: WorkspaceArgs) -> buckos_package::Result<()> {
+    let manager = WorkspaceManager::new();
 
-    println!(
-        "{} Profile set to: {}",
-        style(">>>").green().bold(),
-        profile
-    );
-    println!();
-    println!("Run 'buckos update @world' to apply profile changes");
+    match args.subcommand {
+        WorkspaceCommand::List => {
+            let workspaces = manager.list()?;
+            if workspaces.is_empty() {
+                println!(
+                    "{}",
+                    tr!(
+                        "workspace-no-workspaces-in",
+                        base_dir = manager.base_dir().display().to_string()
+                    )
+                );
+                return Ok(());
+            }
+            for ws in workspaces {
+                let root = ws
+                    .load_config()
+                    .map(|c| c.root.display().to_string())
+                    .unwrap_or_else(|_| "?".to_string());
+                println!(
+                    "  {} {}",
+                    theme::success(&ws.name).bold(),
+                    theme::plain(root).dim()
+                );
+            }
+        }
+        WorkspaceCommand::Create { name, root } => {
+            let ws = manager.create(&name, root.map(std::path::PathBuf::from))?;
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!(
+                    "workspace-created-workspace-in",
+                    name = theme::plain(&ws.name).bold().to_string(),
+                    dir = ws.dir.display().to_string()
+                )
+            );
+            println!(
+                "{}",
+                tr!("workspace-use-it-with-buckos", name = ws.name.to_string())
+            );
+        }
+        WorkspaceCommand::Show { name } => {
+            let ws = manager.get(&name)?;
+            let config = ws.load_config()?;
+            println!(
+                "{}",
+                theme::plain(tr!("workspace", name = ws.name.to_string()))
+                    .bold()
+                    .underlined()
+            );
+            println!(
+                "  {}",
+                tr!(
+                    "workspace-config",
+                    config_path = ws.config_path().display().to_string()
+                )
+            );
+            println!(
+                "  {}",
+                tr!("workspace-root", root = config.root.display().to_string())
+            );
+            println!(
+                "  {}",
+                tr!(
+                    "workspace-database",
+                    db_path = config.db_path.display().to_string()
+                )
+            );
+            println!(
+                "  {}",
+                tr!(
+                    "workspace-cache",
+                    cache_dir = config.cache_dir.display().to_string()
+                )
+            );
+            println!(
+                "  {}",
+                tr!(
+                    "workspace-world",
+                    world = (Layout::resolve(&config)?
+                        .path(buckos_package::world::WORLD_FILE)
+                        .display())
+                    .to_string()
+                )
+            );
+        }
+        WorkspaceCommand::Remove { name } => {
+            if !Confirm::new()
+                .with_prompt(format!("Remove workspace {} and its database?", name))
+                .default(false)
+                .interact()?
+            {
+                println!(
+                    "{} {}",
+                    theme::warning(">>>").bold(),
+                    theme::warning(tr!("exiting")).bold()
+                );
+                return Ok(());
+            }
+            manager.remove(&name)?;
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!("workspace-removed-workspace", name = name.to_string())
+            );
+        }
+    }
 
     Ok(())
 }
 
-/// Show current profile
-async fn cmd_profile_current() -> buckos_package::Result<()> {
-    let config_path = dirs::config_dir()
-        .unwrap_or_else(|| std::path::PathBuf::from("/etc/buckos"))
-        .join("buckos")
-        .join("profile");
+/// Split debug info packages and debuginfod server
+async fn cmd_debuginfod(pm: &PackageManager, args: DebuginfodArgs) -> buckos_package::Result<()> {
+    match args.subcommand {
+        DebuginfodCommand::Serve {
+            listen,
+            extra_roots,
+        } => {
+            let mut store = DebugInfoStore::new(&pm.config().root);
+            for root in &extra_roots {
+                store = store.with_root(std::path::Path::new(root));
+            }
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!(
+                    "debuginfod-serving-debug-info-on",
+                    listen = listen.to_string(),
+                    next = listen.rsplit(':').next().unwrap_or("8002").to_string()
+                )
+            );
+            buckos_package::debuginfod::serve(store, &listen).await
+        }
+        #[cfg(feature = "binary-packages")]
+        DebuginfodCommand::Pack { packages } => {
+            let created = pm.create_debug_packages(&packages).await?;
+            for binpkg in &created {
+                println!(
+                    "{} {}",
+                    theme::success(">>>").bold(),
+                    tr!(
+                        "debuginfod-created",
+                        path = binpkg.path.to_string(),
+                        size = format_size(binpkg.size).to_string()
+                    )
+                );
+            }
+            if created.len() < packages.len() {
+                println!(
+                    "{} {}",
+                    theme::warning(">>>").bold(),
+                    tr!(
+                        "debuginfod-package-had-no-split",
+                        count = (packages.len() - created.len()).to_string()
+                    )
+                );
+            }
+            Ok(())
+        }
+    }
+}
 
-    let profile = if config_path.exists() {
-        fs::read_to_string(&config_path).unwrap_or_else(|_| "default".to_string())
+/// Serve the repository, PKGDIR and distfiles over HTTP
+async fn cmd_serve(pm: &PackageManager, args: ServeArgs) -> buckos_package::Result<()> {
+    let config = MirrorConfig::from_config(pm.config())
+        .with_listen(args.listen)
+        .with_token(args.token)
+        .with_listing(!args.no_listing);
+
+    println!(
+        "{} {}",
+        theme::success(">>>").bold(),
+        if config.token.is_some() {
+            tr!(
+                "serve-serving-on-http-token",
+                listen = config.listen.to_string()
+            )
+        } else {
+            tr!("serve-serving-on-http", listen = config.listen.to_string())
+        }
+    );
+    for mount in &config.mounts {
+        println!("    /{:<10} {}", mount.name, mount.root.display());
+    }
+
+    let advertiser = if args.share {
+        let port = config
+            .listen
+            .parse::<std::net::SocketAddr>()
+            .map(|addr| addr.port())
+            .map_err(|e| {
+                buckos_package::Error::ConfigError(format!(
+                    "invalid listen address {}: {}",
+                    config.listen, e
+                ))
+            })?;
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!(
+                "serve-sharing-distfiles-with-lan",
+                peer = buckos_package::peer::SERVICE.to_string()
+            )
+        );
+        Some(Advertiser::new(port))
     } else {
-        "default".to_string()
+        None
+    };
+    let advertise = async move {
+        match advertiser {
+            Some(advertiser) => advertiser.run().await,
+            None => std::future::pending().await,
+        }
     };
 
+    let server = MirrorServer::new(config);
+    let stats = server.stats();
+    tokio::select! {
+        result = server.run() => result?,
+        result = advertise => result?,
+        _ = tokio::signal::ctrl_c() => {}
+    }
+
     println!(
-        "{}: {}",
-        style("Current profile").bold(),
-        style(profile.trim()).green()
+        "\n{} {}",
+        theme::success(">>>").bold(),
+        tr!(
+            "serve-served-requests-average",
+            requests = stats.requests().to_string(),
+            bytes_sent = format_size(stats.bytes_sent()).to_string(),
+            rate = (format_size(stats.average_rate() as u64)).to_string()
+        )
     );
-
     Ok(())
 }
 
-/// Export configuration in various formats
-async fn cmd_export(args: ExportArgs) -> buckos_package::Result<()> {
-    let config = Config::default();
-
-    // Build configuration structure
-    let use_flags: Vec<String> = vec![
-        "ssl".to_string(),
-        "http2".to_string(),
-        "ipv6".to_string(),
-        "zstd".to_string(),
-        "dbus".to_string(),
-    ];
-
-    let output = match args.format.as_str() {
-        "json" => {
-            let mut export = serde_json::json!({
-                "profile": "default",
-                "arch": config.arch,
-                "use_flags": {
-                    "global": use_flags,
-                    "package": {}
-                },
-                "env": {
-                    "CFLAGS": config.cflags,
-                    "CXXFLAGS": config.cxxflags,
-                    "LDFLAGS": config.ldflags,
-                    "MAKEOPTS": config.makeopts
-                },
-                "accept_keywords": [],
-                "package_mask": [],
-                "package_unmask": []
-            });
-
-            if args.with_packages {
-                // Would include installed packages here
-                export["packages"] = serde_json::json!({
-                    "installed": []
-                });
+async fn cmd_mirrors(pm: &PackageManager, args: MirrorsArgs) -> buckos_package::Result<()> {
+    match args.command {
+        MirrorsCommand::List => {
+            let mirrors = pm.mirror_health();
+            if mirrors.is_empty() {
+                println!("{}", tr!("mirrors-no-mirrors-configured-set"));
+                return Ok(());
             }
-
-            serde_json::to_string_pretty(&export).unwrap_or_default()
+            println!(
+                "{}",
+                theme::plain(tr!("mirrors-in-fetch-order"))
+                    .bold()
+                    .underlined()
+            );
+            for (mirror, health) in mirrors {
+                let slow = if health.is_slow() {
+                    theme::warning(" [slow]").to_string()
+                } else {
+                    String::new()
+                };
+                println!(
+                    "  {}",
+                    tr!(
+                        "mirrors-ok-failed-mismatched",
+                        score = format!("{:.2}", health.score()),
+                        mirror = mirror.to_string(),
+                        slow = slow.to_string(),
+                        successes = health.successes.to_string(),
+                        failures = health.failures.to_string(),
+                        mismatches = health.mismatches.to_string(),
+                        rate = (format_size(health.average_rate as u64)).to_string()
+                    )
+                );
+            }
+            Ok(())
         }
-        "toml" => {
-            let mut output = String::new();
-            output.push_str("# BuckOS Configuration Export\n\n");
-            output.push_str("[profile]\n");
-            output.push_str("name = \"default\"\n");
-            output.push_str(&format!("arch = \"{}\"\n\n", config.arch));
-
-            output.push_str("[use_flags]\n");
-            output.push_str(&format!("global = {:?}\n\n", use_flags));
+        MirrorsCommand::Audit {
+            sample,
+            interval,
+            json,
+        } => loop {
+            let audits = pm.audit_mirrors(sample).await?;
+            if json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&audits)
+                        .map_err(|e| buckos_package::Error::Other(e.to_string()))?
+                );
+            } else if audits.is_empty() {
+                println!(
+                    "{} {}",
+                    theme::warning(">>>").bold(),
+                    tr!("mirrors-nothing-to-audit-no")
+                );
+            } else {
+                print_mirror_audits(&audits);
+            }
 
-            output.push_str("[env]\n");
-            output.push_str(&format!("CFLAGS = \"{}\"\n", config.cflags));
-            output.push_str(&format!("CXXFLAGS = \"{}\"\n", config.cxxflags));
-            output.push_str(&format!("LDFLAGS = \"{}\"\n", config.ldflags));
-            output.push_str(&format!("MAKEOPTS = \"{}\"\n", config.makeopts));
+            match interval {
+                Some(secs) => tokio::time::sleep(std::time::Duration::from_secs(secs)).await,
+                None => return Ok(()),
+            }
+        },
+    }
+}
 
-            output
+fn print_mirror_audits(audits: &[buckos_package::checksums::MirrorAudit]) {
+    for audit in audits {
+        let status = if audit.has_problems() {
+            theme::error("PROBLEMS").bold()
+        } else {
+            theme::success("OK").bold()
+        };
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!(
+                "print-mirror-audits-verified-score",
+                mirror = audit.mirror.to_string(),
+                status = status.to_string(),
+                count = audit.verified.len(),
+                rate = (format_size(audit.rate as u64)).to_string(),
+                score = format!("{:.2}", audit.score)
+            )
+        );
+        for file in &audit.mismatched {
+            println!(
+                "    {} {}",
+                theme::error("!!!"),
+                tr!(
+                    "print-mirror-audits-checksum-mismatch",
+                    file = file.to_string()
+                )
+            );
         }
-        "shell" => {
-            let mut output = String::new();
-            output.push_str("#!/bin/bash\n");
-            output.push_str("# BuckOS Configuration Export\n\n");
-
-            output.push_str("export PROFILE=\"default\"\n");
-            output.push_str(&format!("export ARCH=\"{}\"\n\n", config.arch));
+        for (file, reason) in &audit.failed {
+            println!(
+                "    {} {}",
+                theme::warning("!!!"),
+                tr!(
+                    "print-mirror-audits-failed",
+                    file = file.to_string(),
+                    reason = reason.to_string()
+                )
+            );
+        }
+        if audit.slow {
+            println!(
+                "    {} {}",
+                theme::warning("!!!"),
+                tr!(
+                    "print-mirror-audits-persistently-slow-audits-in",
+                    checksums = buckos_package::checksums::SLOW_STREAK.to_string()
+                )
+            );
+        }
+    }
+}
 
-            output.push_str("# USE flags\n");
-            output.push_str(&format!("export USE=\"{}\"\n\n", use_flags.join(" ")));
+/// Parse a YYYY-MM-DD date (midnight UTC) or an RFC 3339 timestamp
+fn parse_history_time(s: &str) -> buckos_package::Result<chrono::DateTime<chrono::Utc>> {
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(s) {
+        return Ok(time.with_timezone(&chrono::Utc));
+    }
+    chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map(|date| date.and_time(chrono::NaiveTime::MIN).and_utc())
+        .map_err(|_| {
+            buckos_package::Error::Other(format!(
+                "invalid date '{}' (expected YYYY-MM-DD or RFC 3339)",
+                s
+            ))
+        })
+}
 
-            output.push_str("# Environment\n");
-            output.push_str(&format!("export CFLAGS=\"{}\"\n", config.cflags));
-            output.push_str(&format!("export CXXFLAGS=\"{}\"\n", config.cxxflags));
-            output.push_str(&format!("export LDFLAGS=\"{}\"\n", config.ldflags));
-            output.push_str(&format!("export MAKEOPTS=\"{}\"\n", config.makeopts));
+/// Resolve a history point to the id of the last transaction it includes
+async fn resolve_history_point(pm: &PackageManager, point: &str) -> buckos_package::Result<i64> {
+    if let Ok(id) = point.parse::<i64>() {
+        return Ok(id);
+    }
+    let time = parse_history_time(point)?;
+    Ok(pm.history_id_at(time).await?.unwrap_or(0))
+}
 
-            output
-        }
-        "buck" => {
-            let mut output = String::new();
-            output.push_str("# BuckOS Configuration Export\n\n");
+fn print_package_changes(changes: &[buckos_package::db::PackageChange]) {
+    for change in changes {
+        let marker = match (&change.old_version, &change.new_version) {
+            (None, Some(_)) => theme::paint(Role::New, "+"),
+            (Some(_), None) => theme::paint(Role::Remove, "-"),
+            _ => theme::paint(Role::Update, "~"),
+        };
+        println!(
+            "  {} {}:{} {}",
+            marker,
+            change.package,
+            change.slot,
+            change.describe()
+        );
+    }
+}
 
-            output.push_str("PROFILE = \"default\"\n");
-            output.push_str(&format!("ARCH = \"{}\"\n\n", config.arch));
+async fn cmd_history(pm: &PackageManager, args: HistoryArgs) -> buckos_package::Result<()> {
+    use buckos_package::db::{net_changes, HistoryFilter};
 
-            output.push_str(&format!("USE_FLAGS = {:?}\n\n", use_flags));
+    if let Some(HistoryCommand::Show { id, json }) = args.command {
+        let entry = pm.history_entry(id).await?.ok_or_else(|| {
+            buckos_package::Error::Other(format!("no transaction {} in history", id))
+        })?;
+        if json {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&entry).unwrap_or_default()
+            );
+            return Ok(());
+        }
+        print_history_entry(&entry);
+        if let Some(machine_id) = &entry.machine_id {
+            println!(
+                "    {}",
+                tr!("history-machine", machine_id = machine_id.to_string())
+            );
+        }
+        match &entry.usage {
+            Some(usage) => {
+                println!(
+                    "\n{}",
+                    theme::plain(tr!("history-resources")).bold().underlined()
+                );
+                print_resource_usage(usage);
+            }
+            None => println!("\n{}", tr!("history-no-resource-usage-was")),
+        }
+        return Ok(());
+    }
 
-            output.push_str("PACKAGE_USE = {}\n\n");
+    if let Some(id) = args.undo {
+        let entry = pm.history_entry(id).await?.ok_or_else(|| {
+            buckos_package::Error::Other(format!("no transaction {} in history", id))
+        })?;
+        let inverse = entry.inverse();
+        if args.json {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&inverse).unwrap_or_default()
+            );
+            return Ok(());
+        }
+        if inverse.is_empty() {
+            println!(
+                "{}",
+                tr!(
+                    "history-transaction-changed-nothing-nothing",
+                    id = id.to_string()
+                )
+            );
+            return Ok(());
+        }
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!(
+                "history-reverting-transaction-would",
+                id = id.to_string(),
+                command = entry.command.to_string()
+            )
+        );
+        print_package_changes(&inverse);
+        println!(
+            "\n{}",
+            tr!("history-run-buckos-undo-to", id = id.to_string())
+        );
+        return Ok(());
+    }
 
-            output.push_str("ENV = {\n");
-            output.push_str(&format!("    \"CFLAGS\": \"{}\",\n", config.cflags));
-            output.push_str(&format!("    \"CXXFLAGS\": \"{}\",\n", config.cxxflags));
-            output.push_str(&format!("    \"LDFLAGS\": \"{}\",\n", config.ldflags));
-            output.push_str(&format!("    \"MAKEOPTS\": \"{}\",\n", config.makeopts));
-            output.push_str("}\n");
+    let mut filter = HistoryFilter {
+        package: args.package,
+        since: args.since.as_deref().map(parse_history_time).transpose()?,
+        ..Default::default()
+    };
 
-            output
+    if let Some(points) = args.diff {
+        filter.after_id = Some(resolve_history_point(pm, &points[0]).await?);
+        if let Some(to) = points.get(1) {
+            filter.until_id = Some(resolve_history_point(pm, to).await?);
         }
-        _ => {
-            return Err(buckos_package::Error::ConfigError(format!(
-                "Unknown format: {}. Use json, toml, shell, or buck",
-                args.format
-            )));
+        let changes = net_changes(&pm.history(&filter).await?);
+        if args.json {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&changes).unwrap_or_default()
+            );
+        } else if changes.is_empty() {
+            println!(
+                "{}",
+                match points.get(1) {
+                    Some(to) => tr!(
+                        "history-no-package-changes-between",
+                        from = points[0].as_str(),
+                        to = to.as_str()
+                    ),
+                    None => tr!(
+                        "history-no-package-changes-since",
+                        from = points[0].as_str()
+                    ),
+                }
+            );
+        } else {
+            println!(
+                "{}",
+                theme::plain(tr!("history-package-changes"))
+                    .bold()
+                    .underlined()
+            );
+            print_package_changes(&changes);
         }
-    };
+        return Ok(());
+    }
 
-    // Write to file or stdout
-    if let Some(path) = args.output {
-        fs::write(&path, &output)?;
+    let entries = pm.history(&filter).await?;
+    if args.json {
         println!(
-            "{} Configuration exported to: {}",
-            style(">>>").green().bold(),
-            path
+            "{}",
+            serde_json::to_string_pretty(&entries).unwrap_or_default()
         );
-    } else {
-        println!("{}", output);
+        return Ok(());
+    }
+    if entries.is_empty() {
+        println!("{}", tr!("history-no-transactions-recorded"));
+        return Ok(());
+    }
+    for entry in &entries {
+        print_history_entry(entry);
     }
-
     Ok(())
 }
 
-/// Rebuild packages with broken library dependencies
-async fn cmd_revdep(
-    pm: &PackageManager,
-    args: RevdepArgs,
-    emerge_opts: &EmergeOptions,
-) -> buckos_package::Result<()> {
+fn print_history_entry(entry: &buckos_package::db::HistoryEntry) {
+    let result = if entry.success {
+        theme::success("ok")
+    } else {
+        theme::error("failed")
+    };
     println!(
-        "{} Checking for packages with broken library dependencies...",
-        style(">>>").blue().bold()
+        "{} {} {} {} [{}]",
+        theme::plain(format!("#{}", entry.id)).bold(),
+        entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+        entry.user,
+        entry.command,
+        result
     );
-
-    // Find packages with broken dependencies
-    let broken = pm
-        .find_broken_deps(args.library.as_deref(), &args.packages)
-        .await?;
-
-    // Filter out ignored packages
-    let to_rebuild: Vec<_> = broken
-        .into_iter()
-        .filter(|pkg| {
-            !args.ignore.contains(&pkg.name) && !args.ignore.contains(&pkg.id.full_name())
-        })
-        .collect();
-
-    if to_rebuild.is_empty() {
-        println!(
-            "\n{} No packages with broken dependencies found",
-            style(">>>").green().bold()
-        );
-        return Ok(());
+    if let Some(error) = &entry.error {
+        println!("    {}", theme::plain(error).dim());
     }
+    print_package_changes(&entry.changes);
+}
 
-    // Display packages to rebuild
+fn print_resource_usage(usage: &buckos_package::db::ResourceUsage) {
+    let disk = if usage.disk_delta < 0 {
+        format!("-{}", format_size(usage.disk_delta.unsigned_abs()))
+    } else {
+        format!("+{}", format_size(usage.disk_delta as u64))
+    };
+    println!(
+        "  {}",
+        tr!(
+            "print-resource-usage-wall-time",
+            wall_ms = format_duration(std::time::Duration::from_millis(usage.wall_ms)).to_string()
+        )
+    );
+    println!(
+        "  {}",
+        tr!(
+            "print-resource-usage-cpu-time",
+            cpu_ms = format_duration(std::time::Duration::from_millis(usage.cpu_ms)).to_string()
+        )
+    );
+    println!(
+        "  {}",
+        tr!(
+            "print-resource-usage-peak-memory",
+            peak_memory = format_size(usage.peak_memory).to_string()
+        )
+    );
     println!(
-        "\n{} Found {} package(s) with broken dependencies:\n",
-        style(">>>").yellow().bold(),
-        to_rebuild.len()
+        "  {}",
+        tr!(
+            "print-resource-usage-downloaded",
+            downloaded = format_size(usage.downloaded).to_string()
+        )
+    );
+    println!(
+        "  {}",
+        tr!(
+            "print-resource-usage-cache-hits-build-action",
+            cache_hits = usage.cache_hits.to_string(),
+            prebuilt = usage.prebuilt.to_string()
+        )
     );
+    println!(
+        "  {}",
+        tr!("print-resource-usage-disk", disk = disk.to_string())
+    );
+}
 
-    for pkg in &to_rebuild {
+/// Report the resources of transactions recorded after `last`
+async fn report_transaction_usage(pm: &PackageManager, last: Option<i64>) {
+    let filter = buckos_package::db::HistoryFilter {
+        after_id: Some(last.unwrap_or(0)),
+        ..Default::default()
+    };
+    let Ok(entries) = pm.history(&filter).await else {
+        return;
+    };
+    for entry in entries {
+        let Some(usage) = &entry.usage else {
+            continue;
+        };
         println!(
-            "  {} {}/{}",
-            style("R").yellow().bold(),
-            style(&pkg.id.category).cyan(),
-            style(format!("{}-{}", &pkg.name, &pkg.version)).yellow()
+            "\n{} {}",
+            theme::success(">>>").bold(),
+            tr!(
+                "report-transaction-usage-transaction-used",
+                id = entry.id.to_string()
+            )
+        );
+        print_resource_usage(usage);
+        println!(
+            "  {}",
+            tr!(
+                "report-transaction-usage-see-buckos-history-show",
+                id = entry.id.to_string()
+            )
         );
+    }
+}
 
-        // Show broken libraries
-        if !pkg.broken_libs.is_empty() {
-            for lib in &pkg.broken_libs {
+#[cfg(feature = "binary-packages")]
+async fn cmd_undo(
+    pm: &PackageManager,
+    args: UndoArgs,
+    emerge_opts: &EmergeOptions,
+) -> buckos_package::Result<()> {
+    use buckos_package::transaction::UndoAction;
+
+    let plan = pm.plan_undo(args.id).await?;
+    println!(
+        "\n{} {}\n",
+        theme::success(">>>").bold(),
+        tr!(
+            "undo-reverting-transaction",
+            id = plan.entry.id.to_string(),
+            timestamp = plan.entry.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+            command = plan.entry.command.to_string()
+        )
+    );
+    for action in &plan.actions {
+        match action {
+            UndoAction::Remove(pkg) => {
                 println!(
-                    "      {} Missing library: {}",
-                    style("->").dim(),
-                    style(lib).red()
-                );
+                    "  {} {}-{}",
+                    theme::paint(Role::Remove, "R").bold(),
+                    pkg.id,
+                    pkg.version
+                )
             }
+            UndoAction::Install(binpkg) => println!(
+                "  {} {}-{}",
+                theme::paint(Role::New, "N").bold(),
+                binpkg.id,
+                binpkg.version
+            ),
+            UndoAction::Replace { current, previous } => println!(
+                "  {} {}-{} [{}]",
+                theme::paint(Role::Downgrade, "D").bold(),
+                previous.id,
+                previous.version,
+                current.version
+            ),
         }
     }
 
-    println!(
-        "\n>>> Rebuilding {} package(s)...",
-        style(to_rebuild.len()).bold()
-    );
+    if !plan.is_possible() {
+        println!(
+            "\n{} {}",
+            theme::error("!!!").bold(),
+            tr!(
+                "undo-cannot-undo-transaction",
+                id = plan.entry.id.to_string()
+            )
+        );
+        for problem in &plan.problems {
+            println!("  {}", problem);
+        }
+        return Err(buckos_package::Error::Other(
+            "required binary packages or package states are no longer available".to_string(),
+        ));
+    }
 
-    // Pretend mode
-    if args.pretend || emerge_opts.pretend {
+    if emerge_opts.pretend {
         return Ok(());
     }
 
-    // Ask mode
     if emerge_opts.ask {
         if !Confirm::new()
-            .with_prompt("Would you like to rebuild these packages?")
-            .default(true)
+            .with_prompt("Would you like to revert this transaction?")
+            .default(false)
             .interact()?
         {
-            println!("{}", style(">>> Exiting.").yellow().bold());
+            println!(
+                "{} {}",
+                theme::warning(">>>").bold(),
+                theme::warning(tr!("exiting")).bold()
+            );
             return Ok(());
         }
         println!();
     }
 
-    // Actually rebuild
-    pm.rebuild_packages(&to_rebuild).await?;
-
+    pm.undo(&plan).await?;
     println!(
-        "\n{} {} packages rebuilt successfully",
-        style(">>>").green().bold(),
-        to_rebuild.len()
+        "{} {}",
+        theme::success(">>>").bold(),
+        tr!("undo-transaction-reverted", id = plan.entry.id.to_string())
     );
-
     Ok(())
 }
 
-/// Package signing management
-async fn cmd_sign(args: SignArgs) -> buckos_package::Result<()> {
-    use buckos_package::security::signing::{
-        format_key, format_verification, SigningManager, TrustLevel,
-    };
-
-    let mut manager = SigningManager::new()?;
-
-    // Check if GPG is available
-    if !manager.is_gpg_available() {
-        return Err(buckos_package::Error::Signing(
-            "GPG is not available. Please install gnupg.".to_string(),
-        ));
-    }
+#[cfg(feature = "binary-packages")]
+async fn cmd_binpkg(
+    pm: &PackageManager,
+    args: BinpkgArgs,
+    emerge_opts: &EmergeOptions,
+) -> buckos_package::Result<()> {
+    use buckos_package::binary::archive;
 
     match args.subcommand {
-        SignCommand::ListKeys { secret } => {
-            println!("{}", style("Available Signing Keys").bold().underlined());
-            println!();
-
-            let keys = manager.list_keys(secret)?;
-
-            if keys.is_empty() {
-                println!("  No keys found");
-                if secret {
-                    println!("\n  To create a new key: gpg --gen-key");
+        BinpkgCommand::Export { packages, output } => {
+            let dest = output.unwrap_or_else(|| pm.config().packages_dir());
+            for name in &packages {
+                let exported = pm.export_binary(name, &dest).await?;
+                println!(
+                    "{} {}",
+                    theme::success(">>>").bold(),
+                    tr!(
+                        "binpkg-exported-to",
+                        id = exported.metadata.package.id.to_string(),
+                        version = exported.metadata.package.version.to_string(),
+                        path = exported.path.display().to_string(),
+                        size = format_size(std::fs::metadata(&exported.path)?.len()).to_string()
+                    )
+                );
+                for path in &exported.modified {
+                    println!(
+                        "    {} {}",
+                        theme::warning("*").bold(),
+                        tr!("binpkg-changed-since-it-was", path = path.to_string())
+                    );
                 }
-            } else {
-                for key in keys {
-                    let key_type = if key.is_secret { "sec" } else { "pub" };
+                if !exported.skipped.is_empty() {
                     println!(
-                        "  {} {}/{} {}",
-                        style(key_type).dim(),
-                        key.algorithm,
-                        key.key_size,
-                        key.created
+                        "    {} {}",
+                        theme::warning("*").bold(),
+                        tr!("binpkg-file-missing-or-not", count = exported.skipped.len())
                     );
-                    println!("        {} {}", style("Key ID:").bold(), key.key_id);
-                    println!("        {} {}", style("User:").bold(), key.user_id);
-                    println!("        {} {}", style("Trust:").bold(), key.trust);
-                    if let Some(ref expires) = key.expires {
-                        println!("        {} {}", style("Expires:").bold(), expires);
-                    }
-                    println!();
                 }
             }
+            Ok(())
         }
-
-        SignCommand::ImportKey { source } => {
-            println!(
-                "{} Importing key from {}...",
-                style(">>>").blue().bold(),
-                source
-            );
-
-            let result = manager.import_key(&source)?;
-            println!("{}", result);
-
-            println!("{} Key imported successfully", style(">>>").green().bold());
-        }
-
-        SignCommand::ExportKey {
-            key_id,
-            output,
-            armor,
-        } => {
-            println!(
-                "{} Exporting key {} to {}...",
-                style(">>>").blue().bold(),
-                key_id,
-                output
-            );
-
-            manager.export_key(&key_id, std::path::Path::new(&output), armor)?;
-
-            println!("{} Key exported successfully", style(">>>").green().bold());
-        }
-
-        SignCommand::SignManifest { package_dir, key } => {
-            println!(
-                "{} Signing manifest in {}...",
-                style(">>>").blue().bold(),
-                package_dir
-            );
-
-            let path = std::path::Path::new(&package_dir);
-            if !path.exists() {
-                return Err(buckos_package::Error::Signing(format!(
-                    "Directory not found: {}",
-                    package_dir
-                )));
-            }
-
-            // Extract package ID from directory name
-            let pkg_name = path
-                .file_name()
-                .and_then(|s| s.to_str())
-                .unwrap_or("unknown");
-            let category = path
-                .parent()
-                .and_then(|p| p.file_name())
-                .and_then(|s| s.to_str())
-                .unwrap_or("unknown");
-
-            let package_id = buckos_package::PackageId::new(category, pkg_name);
-
-            // Generate manifest
-            let mut manifest = manager.generate_manifest(path, &package_id)?;
-
-            // Sign it
-            manager.sign_manifest(&mut manifest, key.as_deref())?;
-
-            // Write to file
-            let manifest_path = path.join("Manifest");
-            manager.write_manifest(&manifest, &manifest_path)?;
-
-            println!(
-                "{} Manifest signed and written to {}",
-                style(">>>").green().bold(),
-                manifest_path.display()
-            );
-        }
-
-        SignCommand::VerifyManifest { manifest } => {
+        BinpkgCommand::Import { archives } => {
+            let installed = pm.list_installed().await?;
             println!(
-                "{} Verifying manifest {}...",
-                style(">>>").blue().bold(),
-                manifest
-            );
-
-            let path = std::path::Path::new(&manifest);
-            let pkg_manifest = manager.read_manifest(path)?;
-
-            let verification = manager.verify_manifest(&pkg_manifest)?;
-            println!("\n{}", format_verification(&verification));
-
-            if verification.valid {
-                // Also verify files
-                if let Some(parent) = path.parent() {
-                    let file_results = manager.verify_manifest_files(&pkg_manifest, parent)?;
-                    let failed: Vec<_> = file_results
-                        .iter()
-                        .filter(|r| {
-                            r.status != buckos_package::security::signing::ManifestVerifyStatus::Ok
-                        })
-                        .collect();
+                "\n{} {}\n",
+                theme::success(">>>").bold(),
+                tr!("binpkg-these-are-the-packages")
+            );
+            for path in &archives {
+                let metadata = archive::read_metadata(path)?;
+                let pkg = &metadata.package;
+                let current = installed
+                    .iter()
+                    .find(|p| p.id == pkg.id && p.slot == pkg.slot);
+                match current {
+                    Some(current) => println!(
+                        "  {} {}-{} [{}]",
+                        theme::paint(Role::Update, "U").bold(),
+                        pkg.id,
+                        pkg.version,
+                        current.version
+                    ),
+                    None => println!(
+                        "  {} {}-{}",
+                        theme::paint(Role::New, "N").bold(),
+                        pkg.id,
+                        pkg.version
+                    ),
+                }
+            }
 
-                    if failed.is_empty() {
-                        println!(
-                            "{} All {} files verified",
-                            style(">>>").green().bold(),
-                            file_results.len()
-                        );
-                    } else {
-                        println!(
-                            "{} {} file(s) failed verification:",
-                            style(">>>").red().bold(),
-                            failed.len()
-                        );
-                        for result in failed {
-                            println!("  {} {}: {}", style("!").red(), result.path, result.message);
-                        }
-                    }
+            if emerge_opts.pretend {
+                return Ok(());
+            }
+
+            if emerge_opts.ask {
+                if !Confirm::new()
+                    .with_prompt("Would you like to install these packages?")
+                    .default(false)
+                    .interact()?
+                {
+                    println!(
+                        "{} {}",
+                        theme::warning(">>>").bold(),
+                        theme::warning(tr!("exiting")).bold()
+                    );
+                    return Ok(());
                 }
-            } else {
+                println!();
+            }
+
+            for path in &archives {
+                let metadata = pm.import_binary(path).await?;
                 println!(
-                    "{} Signature verification failed",
-                    style(">>>").red().bold()
+                    "{} {}",
+                    theme::success(">>>").bold(),
+                    tr!(
+                        "binpkg-installed-from",
+                        id = metadata.package.id.to_string(),
+                        version = metadata.package.version.to_string(),
+                        path = path.display().to_string()
+                    )
                 );
             }
+            Ok(())
         }
-
-        SignCommand::SignRepo { repo_dir, key } => {
+        BinpkgCommand::Info {
+            archive: path,
+            files,
+        } => {
+            let metadata = archive::read_metadata(&path)?;
+            let pkg = &metadata.package;
+            println!("{}-{}:{}", pkg.id, pkg.version, pkg.slot);
+            println!("  {}", tr!("binpkg-arch", arch = pkg.arch.to_string()));
             println!(
-                "{} Signing repository {}...",
-                style(">>>").blue().bold(),
-                repo_dir
+                "  {}",
+                tr!(
+                    "binpkg-compression",
+                    compression = pkg.compression.to_string()
+                )
             );
-
-            let path = std::path::Path::new(&repo_dir);
-            manager.sign_repository(path, key.as_deref())?;
-
             println!(
-                "{} Repository signed successfully",
-                style(">>>").green().bold()
+                "  {}",
+                tr!(
+                    "binpkg-size-installed",
+                    size = format_size(pkg.size).to_string(),
+                    installed_size = format_size(pkg.installed_size).to_string()
+                )
             );
-        }
-
-        SignCommand::VerifyRepo { repo_dir } => {
+            let mut use_flags = pkg.use_flags.clone();
+            use_flags.sort();
+            println!("  USE:          {}", use_flags.join(" "));
             println!(
-                "{} Verifying repository {}...",
-                style(">>>").blue().bold(),
-                repo_dir
+                "  {}",
+                tr!(
+                    "binpkg-dependencies",
+                    dependencies = pkg.dependencies.join(" ")
+                )
             );
-
-            let path = std::path::Path::new(&repo_dir);
-            let verification = manager.verify_repository(path)?;
-
-            println!("\n{}", format_verification(&verification));
-
-            if verification.valid {
-                println!(
-                    "{} Repository signature verified",
-                    style(">>>").green().bold()
-                );
-            } else {
+            if let Some(info) = &metadata.build_info {
                 println!(
-                    "{} Repository signature verification failed",
-                    style(">>>").red().bold()
+                    "  {}",
+                    tr!("binpkg-target", buck_target = info.buck_target.to_string())
                 );
             }
+            println!("  BLAKE3:       {}", pkg.blake3_hash);
+            println!("  {}", tr!("binpkg-files", count = metadata.contents.len()));
+            if files {
+                for file in &metadata.contents {
+                    println!("    {}", file.path);
+                }
+            }
+            Ok(())
         }
+    }
+}
 
-        SignCommand::SignFile { file, key } => {
-            println!("{} Signing file {}...", style(">>>").blue().bold(), file);
+async fn cmd_build_report(
+    pm: &PackageManager,
+    args: BuildReportArgs,
+) -> buckos_package::Result<()> {
+    use buckos_package::diagnostics::Severity;
 
-            let path = std::path::Path::new(&file);
-            let sig_path = manager.sign_file(path, key.as_deref())?;
+    let reports = pm.build_reports(&args.package).await?;
+    if args.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&reports).unwrap_or_default()
+        );
+        return Ok(());
+    }
+    if reports.is_empty() {
+        println!(
+            "{}",
+            tr!(
+                "build-report-no-build-reports-recorded",
+                package = args.package.to_string()
+            )
+        );
+        return Ok(());
+    }
 
-            println!(
-                "{} File signed: {}",
-                style(">>>").green().bold(),
-                sig_path.display()
-            );
+    let index = match &args.version {
+        Some(version) => reports
+            .iter()
+            .position(|r| &r.version == version)
+            .ok_or_else(|| {
+                buckos_package::Error::Other(format!(
+                    "no build report for {}-{}",
+                    args.package, version
+                ))
+            })?,
+        None => reports.len() - 1,
+    };
+    let report = &reports[index];
+
+    if args.log {
+        match pm.build_log(report)? {
+            Some(log) => print!("{}", log),
+            None => println!(
+                "{}",
+                tr!(
+                    "build-report-no-build-log-stored",
+                    package = report.package.to_string(),
+                    version = report.version.to_string()
+                )
+            ),
         }
+        return Ok(());
+    }
 
-        SignCommand::VerifyFile { file, signature } => {
-            println!("{} Verifying file {}...", style(">>>").blue().bold(), file);
+    println!(
+        "{}",
+        theme::plain(tr!("build-report-builds")).bold().underlined()
+    );
+    for report in &reports {
+        println!(
+            "  {}",
+            tr!(
+                "build-report-warnings-errors",
+                version = format!("{:<16}", report.version),
+                timestamp = report.timestamp.format("%Y-%m-%d").to_string(),
+                warnings = format!("{:>6}", report.total(Severity::Warning)),
+                errors = format!("{:>4}", report.total(Severity::Error)),
+                toolchain = report
+                    .toolchain
+                    .as_deref()
+                    .unwrap_or(&tr!("build-report-unknown-toolchain"))
+                    .to_string(),
+                new = (if report.success {
+                    String::new()
+                } else {
+                    format!(" {}", theme::error(tr!("build-report-failed")))
+                })
+                .to_string()
+            )
+        );
+    }
 
-            let path = std::path::Path::new(&file);
-            let sig_path = signature.map(std::path::PathBuf::from);
+    println!(
+        "\n{}",
+        theme::plain(tr!(
+            "build-report-diagnostics-in",
+            package = report.package.to_string(),
+            version = report.version.to_string()
+        ))
+        .bold()
+        .underlined()
+    );
+    for (severity, class, count) in report.by_class().into_iter().take(args.top) {
+        let class = match severity {
+            Severity::Warning => theme::warning(class),
+            Severity::Error => theme::error(class),
+        };
+        println!("  {:>6}  {}", count, class);
+    }
 
-            let verification = manager.verify_file(path, sig_path.as_deref())?;
-            println!("\n{}", format_verification(&verification));
+    let mut files: std::collections::BTreeMap<&str, usize> = Default::default();
+    for d in &report.diagnostics {
+        *files.entry(d.file.as_str()).or_default() += d.count;
+    }
+    let mut files: Vec<_> = files.into_iter().collect();
+    files.sort_by_key(|f| std::cmp::Reverse(f.1));
+    if !files.is_empty() {
+        println!(
+            "\n{}",
+            theme::plain(tr!("build-report-noisiest-files"))
+                .bold()
+                .underlined()
+        );
+        for (file, count) in files.into_iter().take(args.top) {
+            println!("  {:>6}  {}", count, file);
+        }
+    }
 
-            if verification.valid {
-                println!("{} Signature verified", style(">>>").green().bold());
+    if let Some(failure) = &report.failure {
+        println!(
+            "\n{}",
+            theme::plain(tr!("build-report-failure"))
+                .bold()
+                .underlined()
+        );
+        println!("  {}", theme::error(failure.reason()));
+        println!("  {}", failure.line);
+        println!(
+            "  {}",
+            tr!(
+                "build-report-hint",
+                package = failure.hint(&report.package.full_name()).to_string()
+            )
+        );
+    }
+
+    if let Some(previous) = index.checked_sub(1).map(|i| &reports[i]) {
+        let changes = report.compare(previous);
+        println!(
+            "\n{}",
+            theme::plain(tr!(
+                "build-report-changes-since",
+                version = previous.version.to_string()
+            ))
+            .bold()
+            .underlined()
+        );
+        if changes.is_empty() {
+            println!("  {}", tr!("build-report-none"));
+        }
+        for (class, before, after) in changes {
+            let delta = if after > before {
+                theme::error(format!("+{}", after - before))
             } else {
-                println!(
-                    "{} Signature verification failed",
-                    style(">>>").red().bold()
-                );
-            }
+                theme::success(format!("-{}", before - after))
+            };
+            println!("  {:>6}  {} ({} -> {})", delta, class, before, after);
         }
+    }
+    Ok(())
+}
 
-        SignCommand::KeyInfo { key_id } => match manager.get_key(&key_id)? {
-            Some(key) => {
-                println!("{}", style("Key Information").bold().underlined());
-                println!();
-                print!("{}", format_key(&key));
-            }
-            None => {
-                println!("{} Key not found: {}", style(">>>").yellow().bold(), key_id);
+/// List recent builds, newest first
+async fn cmd_log(pm: &PackageManager, args: LogArgs) -> buckos_package::Result<()> {
+    if args.flaky {
+        return cmd_log_flaky(pm, args).await;
+    }
+    let mut reports = match &args.package {
+        Some(package) => {
+            let mut reports = pm.build_reports(package).await?;
+            reports.reverse();
+            reports
+        }
+        None => pm.recent_builds()?,
+    };
+    reports.retain(|r| !args.failed || !r.success);
+    reports.truncate(args.limit);
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+        return Ok(());
+    }
+    if reports.is_empty() {
+        println!(
+            "{}",
+            if args.failed {
+                tr!("log-no-failed-builds-recorded")
+            } else {
+                tr!("log-no-builds-recorded")
             }
-        },
+        );
+        return Ok(());
+    }
 
-        SignCommand::SetTrust { key_id, trust } => {
-            let trust_level = match trust.to_lowercase().as_str() {
-                "unknown" => TrustLevel::Unknown,
-                "never" => TrustLevel::Never,
-                "marginal" => TrustLevel::Marginal,
-                "full" => TrustLevel::Full,
-                "ultimate" => TrustLevel::Ultimate,
-                _ => {
-                    return Err(buckos_package::Error::Signing(format!(
-                        "Invalid trust level: {}. Use: unknown, never, marginal, full, ultimate",
-                        trust
-                    )));
+    for report in &reports {
+        let outcome = if report.success {
+            theme::success("ok")
+        } else {
+            theme::error("failed")
+        };
+        println!(
+            "{}  {}-{}  {}",
+            report
+                .timestamp
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M"),
+            report.package,
+            report.version,
+            outcome
+        );
+        if report.success {
+            continue;
+        }
+        match &report.failure {
+            Some(failure) => {
+                println!(
+                    "    {}",
+                    tr!("log-reason", reason = failure.reason().to_string())
+                );
+                if args.failed {
+                    println!("    {}", theme::plain(&failure.line).dim());
+                    println!(
+                        "    {}",
+                        tr!(
+                            "build-report-hint",
+                            package = failure.hint(&report.package.full_name()).to_string()
+                        )
+                    );
                 }
-            };
-
-            println!(
-                "{} Setting trust level for {} to {}...",
-                style(">>>").blue().bold(),
-                key_id,
-                trust
-            );
-
-            manager.set_key_trust(&key_id, trust_level)?;
-
-            println!("{} Trust level updated", style(">>>").green().bold());
+            }
+            None => println!(
+                "    {}",
+                tr!(
+                    "log-reason-not-recognized-see",
+                    package = report.package.to_string()
+                )
+            ),
         }
     }
-
     Ok(())
 }
 
-/// Handle overlay commands
-async fn cmd_overlay(args: OverlayArgs) -> buckos_package::Result<()> {
-    let config = OverlayConfig::default();
-    let mut manager = OverlayManager::new(config)?;
+async fn cmd_log_flaky(pm: &PackageManager, args: LogArgs) -> buckos_package::Result<()> {
+    let mut flaky = pm.build_flakiness().await?;
+    if let Some(package) = &args.package {
+        flaky.retain(|f| f.package.name == *package || f.package.full_name() == *package);
+    }
+    flaky.truncate(args.limit);
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&flaky)?);
+        return Ok(());
+    }
+    if flaky.is_empty() {
+        println!("{}", tr!("log-flaky-no-retried-or-failed"));
+        return Ok(());
+    }
 
-    match args.subcommand {
-        OverlayCommand::List { enabled, all } => {
-            let overlays = if enabled {
-                manager.list_enabled()
-            } else if all {
-                manager.list_all()
-            } else {
-                // Default: show all overlays with status
-                manager.list_all()
-            };
+    println!(
+        "{:<40} {:>6} {:>8} {:>10} {:>15}",
+        "PACKAGE", "BUILDS", "RETRIED", "RECOVERED", "FAILED ATTEMPTS"
+    );
+    for f in &flaky {
+        println!(
+            "{:<40} {:>6} {:>8} {:>10} {:>15}",
+            f.package.full_name(),
+            f.builds,
+            f.retried,
+            f.recovered,
+            f.failed_attempts
+        );
+    }
+    Ok(())
+}
 
-            if overlays.is_empty() {
-                println!("{} No overlays configured", style(">>>").yellow().bold());
+fn cmd_plugins(pm: &PackageManager, args: PluginsArgs) -> buckos_package::Result<()> {
+    match args.command {
+        PluginsCommand::List => {
+            let plugins = pm.plugins();
+            if plugins.plugins().next().is_none() && plugins.rejected().is_empty() {
+                println!(
+                    "{}",
+                    tr!(
+                        "plugins-no-plugins-installed-in",
+                        plugin_dir = pm.config().plugin_dir.display().to_string()
+                    )
+                );
                 return Ok(());
             }
 
-            println!("{}", style("Configured Overlays").bold().underlined());
-            println!();
-
-            for overlay in overlays {
-                let status = if overlay.enabled {
-                    style("*").green().bold()
-                } else {
-                    style(" ").dim()
-                };
-
-                let quality = match overlay.quality {
-                    OverlayQuality::Official => style("[official]").green(),
-                    OverlayQuality::Community => style("[community]").blue(),
-                    OverlayQuality::Experimental => style("[experimental]").yellow(),
-                    OverlayQuality::Local => style("[local]").cyan(),
-                };
-
+            println!("{}", theme::plain(tr!("plugins")).bold().underlined());
+            for plugin in plugins.plugins() {
                 println!(
-                    " {} {} {} (priority: {}) {}",
-                    status,
-                    style(&overlay.name).bold(),
-                    quality,
-                    overlay.priority,
-                    if overlay.enabled { "" } else { "(disabled)" }
+                    "  {} (ABI {})",
+                    theme::success(plugin.name()).bold(),
+                    plugin.abi_version()
                 );
-
-                if !overlay.description.is_empty() {
-                    println!("     {}", style(&overlay.description).dim());
+                for command in plugin.commands() {
+                    println!(
+                        "      {}",
+                        tr!(
+                            "plugins-buckos",
+                            name = format!("{:<16}", command.name),
+                            about = command.about.to_string()
+                        )
+                    );
                 }
             }
+            for (path, reason) in plugins.rejected() {
+                println!(
+                    "  {} {}: {}",
+                    theme::warning(tr!("plugins-skipped")),
+                    path.display(),
+                    reason
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+fn cmd_plugin_command(pm: &PackageManager, args: &[String]) -> buckos_package::Result<i32> {
+    let Some((name, rest)) = args.split_first() else {
+        return Err(buckos_package::Error::Other("no command given".to_string()));
+    };
+    let (plugin, command) = pm.plugins().find_command(name).ok_or_else(|| {
+        buckos_package::Error::Other(format!(
+            "unknown command '{}' (see 'buckos --help' and 'buckos plugins list')",
+            name
+        ))
+    })?;
+    plugin.run_command(&command.name, rest)
+}
+
+async fn cmd_impact(pm: &PackageManager, args: ImpactArgs) -> buckos_package::Result<()> {
+    let report = pm.impact(&args.package).await?;
+    let estimate = |e: Option<std::time::Duration>| match e {
+        Some(d) => format_duration(d),
+        None => "unknown".to_string(),
+    };
+
+    println!(
+        "{}",
+        tr!(
+            "impact-build-time",
+            heading = theme::plain(tr!("impact-of-upgrading")).bold().to_string(),
+            package = theme::success(report.package.full_name())
+                .bold()
+                .to_string(),
+            package_estimate = estimate(report.package_estimate).to_string()
+        )
+    );
 
+    let mut groups = vec![("Direct dependents", &report.direct)];
+    if !args.direct {
+        groups.push(("Transitive dependents", &report.transitive));
+    }
+    for (title, packages) in groups {
+        println!(
+            "\n{} ({})",
+            theme::plain(title).bold().underlined(),
+            packages.len()
+        );
+        for pkg in packages {
+            print!(
+                "  {}-{}  {}",
+                theme::accent(pkg.id.full_name()),
+                pkg.version,
+                estimate(pkg.estimate)
+            );
+            if pkg.depth > 1 {
+                print!("  {}", theme::plain(format!("via {}", pkg.via)).dim());
+            }
             println!();
-            println!("{} * = enabled overlay", style("Legend:").dim());
         }
+    }
 
-        OverlayCommand::Add {
-            name,
-            uri,
-            sync_type,
-            priority,
-            location,
-        } => {
-            let sync_type_enum = match sync_type.to_lowercase().as_str() {
-                "git" => SyncType::Git,
-                "rsync" => SyncType::Rsync,
-                "http" | "https" => SyncType::Http,
-                "local" => SyncType::Local,
-                _ => {
-                    return Err(buckos_package::Error::InvalidOverlayConfig(format!(
-                        "Unknown sync type: {}. Use: git, rsync, http, local",
-                        sync_type
-                    )));
-                }
-            };
+    let affected = report.direct.len()
+        + if args.direct {
+            0
+        } else {
+            report.transitive.len()
+        };
+    println!(
+        "\n{} {}",
+        theme::info(">>>").bold(),
+        tr!(
+            "impact-packages-affected-estimated-rebuild",
+            affected = affected.to_string(),
+            total_estimate = format_duration(report.total_estimate()).to_string()
+        )
+    );
+    if report.unknown_estimates() > 0 {
+        println!(
+            "    {}",
+            tr!(
+                "impact-packages-have-no-recorded",
+                unknown_estimates = report.unknown_estimates().to_string()
+            )
+        );
+    }
+    Ok(())
+}
 
-            if sync_type_enum == SyncType::Local {
-                let path = location.ok_or_else(|| {
-                    buckos_package::Error::InvalidOverlayConfig(
-                        "Local overlays require --location".to_string(),
-                    )
-                })?;
+/// Write the boot manifest for the initramfs verifier
+async fn cmd_boot_manifest(
+    pm: &PackageManager,
+    args: BootManifestArgs,
+) -> buckos_package::Result<()> {
+    let manifest = pm.boot_manifest(&args.packages).await?;
+    let output = match args.output {
+        Some(path) => std::path::PathBuf::from(path),
+        None => pm.layout().path(buckos_package::BOOT_MANIFEST),
+    };
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(
+        &output,
+        format!(
+            "# buckos boot manifest, generated by 'buckos boot-manifest'\n{}",
+            manifest.render()
+        ),
+    )?;
 
-                println!(
-                    "{} Adding local overlay {}...",
-                    style(">>>").blue().bold(),
-                    name
-                );
+    println!(
+        "{} {}",
+        theme::success(">>>").bold(),
+        tr!(
+            "boot-manifest-wrote-files-to",
+            count = manifest.entries.len(),
+            output = output.display().to_string()
+        )
+    );
+    println!("    {}", tr!("boot-manifest-regenerate-the-initramfs-with"));
+    Ok(())
+}
 
-                manager.add_local(&name, std::path::Path::new(&path), priority)?;
-            } else {
-                let uri = uri.ok_or_else(|| {
-                    buckos_package::Error::InvalidOverlayConfig(
-                        "Remote overlays require --uri".to_string(),
-                    )
-                })?;
+/// Back up, restore or export the package database
+async fn cmd_db(
+    pm: &PackageManager,
+    args: DbArgs,
+    emerge_opts: &EmergeOptions,
+) -> buckos_package::Result<()> {
+    match args.command {
+        DbCommand::Backup { output } => {
+            let backup = pm.backup_db().await?;
+            backup.write_archive(std::path::Path::new(&output))?;
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!(
+                    "db-backed-up-packages-world",
+                    packages = backup.packages.len(),
+                    world = backup.world.len(),
+                    transactions = backup.history.len(),
+                    output = output.to_string()
+                )
+            );
+            Ok(())
+        }
+        DbCommand::Export { format, output } => {
+            if format != "json" {
+                return Err(buckos_package::Error::Other(format!(
+                    "unsupported export format '{}' (expected json)",
+                    format
+                )));
+            }
+            let json = serde_json::to_string_pretty(&pm.backup_db().await?)?;
+            match output {
+                Some(path) => fs::write(path, json + "\n")?,
+                None => println!("{}", json),
+            }
+            Ok(())
+        }
+        DbCommand::Restore {
+            input,
+            force,
+            keep_missing,
+        } => {
+            let mut backup = buckos_package::db::DbBackup::load(std::path::Path::new(&input))?;
+            let installed = pm.list_installed().await?.len();
+            if installed > 0 && !force {
+                return Err(buckos_package::Error::Other(format!(
+                    "the database already has {} packages installed; use --force to replace it",
+                    installed
+                )));
+            }
 
+            println!(
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!(
+                    "db-checking-packages-from-taken",
+                    count = backup.packages.len(),
+                    input = input.to_string(),
+                    created_at = backup.created_at.format("%Y-%m-%d %H:%M").to_string()
+                )
+            );
+            let mut absent = HashSet::new();
+            for check in pm.check_backup(&backup) {
+                if check.is_absent() {
+                    println!(
+                        "  {} {}",
+                        theme::error("!").bold(),
+                        tr!(
+                            "db-none-of-its-files",
+                            package = check.package.to_string(),
+                            count = check.missing.len()
+                        )
+                    );
+                    absent.insert(check.package);
+                } else if !check.missing.is_empty() || !check.modified.is_empty() {
+                    println!(
+                        "  {} {}",
+                        theme::warning("*").bold(),
+                        tr!(
+                            "db-missing-modified",
+                            package = check.package.to_string(),
+                            missing = check.missing.len(),
+                            modified = check.modified.len()
+                        )
+                    );
+                    for path in check.missing.iter().chain(&check.modified).take(5) {
+                        println!("      {}", path);
+                    }
+                }
+            }
+            if !keep_missing && !absent.is_empty() {
                 println!(
-                    "{} Adding overlay {} from {}...",
-                    style(">>>").blue().bold(),
-                    name,
-                    uri
+                    "    {}",
+                    tr!("db-skipping-packages-that-are", count = absent.len())
                 );
+                backup
+                    .packages
+                    .retain(|record| !absent.contains(&record.package.name));
+            }
 
-                manager.add_remote(&name, &uri, sync_type_enum, priority)?;
+            if emerge_opts.pretend {
+                return Ok(());
+            }
+            if emerge_opts.ask {
+                println!();
+                if !Confirm::new()
+                    .with_prompt("Would you like to restore this backup?")
+                    .default(false)
+                    .interact()?
+                {
+                    println!(
+                        "{} {}",
+                        theme::warning(">>>").bold(),
+                        theme::warning(tr!("exiting")).bold()
+                    );
+                    return Ok(());
+                }
             }
 
+            pm.restore_db(&backup).await?;
             println!(
-                "{} Overlay {} added successfully",
-                style(">>>").green().bold(),
-                name
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!(
+                    "db-restored-packages-world-entries",
+                    packages = backup.packages.len(),
+                    world = backup.world.len(),
+                    transactions = backup.history.len()
+                )
             );
-            println!("  Use 'buckos overlay enable {}' to enable it", name);
+            Ok(())
         }
+        DbCommand::Check { .. } | DbCommand::Repair => unreachable!("handled before dispatch"),
+    }
+}
 
-        OverlayCommand::Remove { name, delete } => {
-            println!(
-                "{} Removing overlay {}...",
-                style(">>>").blue().bold(),
-                name
-            );
-
-            manager.remove(&name, delete)?;
+/// Problems with the package database at a configuration's db path
+///
+/// A database SQLite cannot open is reported as corrupt rather than failing.
+fn db_problems(config: &Config) -> buckos_package::Result<Vec<IntegrityProblem>> {
+    let vdb = Vdb::new(&config.db_path);
+    match PackageDb::open(&config.db_path) {
+        Ok(db) => db.check_integrity(&vdb, true),
+        Err(e) => Ok(vec![IntegrityProblem::Corrupt {
+            message: e.to_string(),
+        }]),
+    }
+}
 
-            println!("{} Overlay {} removed", style(">>>").green().bold(), name);
+/// Report problems with the package database; returns whether there were none
+fn cmd_db_check(config: &Config, json: bool) -> buckos_package::Result<bool> {
+    let problems = db_problems(config)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&problems)?);
+    } else if problems.is_empty() {
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!(
+                "db-check-no-problems-found-in",
+                db_path = config.db_path.display().to_string()
+            )
+        );
+    } else {
+        for problem in &problems {
+            println!("  {} {}", theme::error("!").bold(), problem.describe());
         }
+        println!("\n{}", tr!("db-check-run-buckos-db-repair"));
+    }
+    Ok(problems.is_empty())
+}
 
-        OverlayCommand::Enable { name } => {
+/// Show or restart the Buck2 daemon
+async fn cmd_buck(config: &Config, args: BuckArgs) -> buckos_package::Result<()> {
+    let buck = BuckIntegration::new(config)?;
+    let daemon = buck.daemon();
+    let status = match args.command {
+        BuckCommand::Status { json } => {
+            let status = daemon.status().await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&status)?);
+                return Ok(());
+            }
+            status
+        }
+        BuckCommand::Restart => {
             println!(
-                "{} Enabling overlay {}...",
-                style(">>>").blue().bold(),
-                name
+                "{} {}",
+                theme::success(">>>").bold(),
+                tr!("buck-restarting-the-buck-daemon")
             );
+            daemon.restart().await?
+        }
+    };
 
-            manager.enable(&name)?;
+    println!(
+        "{}",
+        tr!(
+            "buck-isolation-dir",
+            isolation_dir = status.isolation_dir.to_string()
+        )
+    );
+    match status.pid {
+        Some(pid) => println!("{}", tr!("buck-daemon-running-pid", pid = pid.to_string())),
+        None => println!(
+            "{}",
+            tr!(
+                "buck-daemon",
+                state = theme::warning(tr!("buck-not-running")).to_string()
+            )
+        ),
+    }
+    if let Some(started) = status.started_at {
+        println!(
+            "{}",
+            tr!(
+                "buck-started",
+                time = (started
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M:%S"))
+                .to_string()
+            )
+        );
+    }
+    println!(
+        "{}",
+        tr!(
+            "buck-client",
+            client_version = status.client_version.to_string()
+        )
+    );
+    if let Some(version) = &status.daemon_version {
+        println!("{}", tr!("buck-started-by", version = version.to_string()));
+    }
+    println!(
+        "{}",
+        tr!(
+            "buck-timeouts-for-queries",
+            command_timeout = daemon.config().command_timeout.to_string(),
+            builds = daemon
+                .config()
+                .build_timeout
+                .map_or(tr!("buck-no-build-timeout"), |t| tr!(
+                    "buck-for-builds",
+                    seconds = t.to_string()
+                ))
+        )
+    );
+    if status.version_mismatch() {
+        println!(
+            "\n{} {}",
+            theme::error("!").bold(),
+            tr!("buck-the-daemon-predates-the")
+        );
+    }
+    Ok(())
+}
+
+fn cmd_gen_docs(args: GenDocsArgs) -> buckos_package::Result<()> {
+    let written = buckos_package::docs::generate(Cli::command(), &args.out, args.format)?;
+    println!(
+        "{} {}",
+        theme::success(">>>").bold(),
+        tr!(
+            "gen-docs-wrote-file-to",
+            count = written.len(),
+            out = args.out.display().to_string()
+        )
+    );
+    Ok(())
+}
 
-            println!("{} Overlay {} enabled", style(">>>").green().bold(), name);
+async fn cmd_gen_units(args: GenUnitsArgs) -> buckos_package::Result<()> {
+    let buckos =
+        std::env::current_exe().unwrap_or_else(|_| std::path::PathBuf::from("/usr/bin/buckos"));
+    for task in PeriodicTask::ALL {
+        let calendar = match task {
+            PeriodicTask::Sync => args.sync_at.as_deref(),
+            PeriodicTask::Audit => args.audit_at.as_deref(),
+            PeriodicTask::Updates => args.updates_at.as_deref(),
+            PeriodicTask::Hardware => None,
         }
+        .or(task.default_calendar());
+        #[cfg(feature = "init")]
+        if let Some(calendar) = calendar {
+            calendar.parse::<buckos_boss::CalendarSpec>().map_err(|e| {
+                buckos_package::Error::Config(format!("invalid time for {}: {}", task, e))
+            })?;
+        }
+        let unit = task.unit(&buckos, calendar);
 
-        OverlayCommand::Disable { name } => {
+        if args.stdout {
+            println!("# {}.toml\n{}", task.unit_name(), unit);
+            continue;
+        }
+        let path = args.dir.join(format!("{}.toml", task.unit_name()));
+        if path.exists() && !args.force {
             println!(
-                "{} Disabling overlay {}...",
-                style(">>>").blue().bold(),
-                name
+                "{} {}",
+                theme::warning("*"),
+                tr!(
+                    "gen-units-exists-skipping-force-replaces",
+                    path = path.display().to_string()
+                )
             );
+            continue;
+        }
+        fs::create_dir_all(&args.dir)?;
+        fs::write(&path, unit)?;
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!(
+                "gen-units-wrote",
+                path = path.display().to_string(),
+                calendar = calendar.unwrap_or(&tr!("gen-units-at-boot")).to_string()
+            )
+        );
+    }
+    if args.stdout {
+        return Ok(());
+    }
 
-            manager.disable(&name)?;
+    // Arm the new timers right away when the init system is running
+    #[cfg(feature = "init")]
+    reload_init().await;
+    Ok(())
+}
 
-            println!("{} Overlay {} disabled", style(">>>").green().bold(), name);
+/// Have a running init reload its service definitions
+#[cfg(feature = "init")]
+async fn reload_init() {
+    let client = buckos_boss::ControlClient::with_default_path();
+    if client.is_available() {
+        match client.reload_daemon().await {
+            Ok(buckos_boss::ControlResponse::Success { message }) => {
+                println!("{} {}", theme::success(">>>").bold(), message)
+            }
+            Ok(buckos_boss::ControlResponse::Error { message }) => println!(
+                "{} {}",
+                theme::warning("*"),
+                tr!(
+                    "reload-init-failed-to-reload-service",
+                    message = message.to_string()
+                )
+            ),
+            Ok(_) => {}
+            Err(e) => println!(
+                "{} {}",
+                theme::warning("*"),
+                tr!(
+                    "reload-init-failed-to-reload-service",
+                    message = e.to_string()
+                )
+            ),
         }
+    }
+}
 
-        OverlayCommand::Sync { name } => {
-            if let Some(name) = name {
-                println!("{} Syncing overlay {}...", style(">>>").blue().bold(), name);
+async fn cmd_periodic(pm: &PackageManager, args: PeriodicArgs) -> buckos_package::Result<()> {
+    let task = match args.task {
+        PeriodicCommand::Sync => PeriodicTask::Sync,
+        PeriodicCommand::Audit => PeriodicTask::Audit,
+        PeriodicCommand::Updates => PeriodicTask::Updates,
+        PeriodicCommand::Hardware => PeriodicTask::Hardware,
+    };
+    let status = task.run(pm).await;
+    // Standard output goes to the journal when run by a timer
+    println!("{}: {}", task, status.summary);
+    for package in &status.packages {
+        println!("  {}", package);
+    }
+    if status.success && matches!(task, PeriodicTask::Sync | PeriodicTask::Audit) {
+        send_notifications(pm, false).await;
+    }
+
+    let all = PeriodicStatus::record(
+        std::path::Path::new(buckos_package::periodic::STATUS_FILE),
+        task,
+        status.clone(),
+    )?;
+    all.write_motd(std::path::Path::new(buckos_package::periodic::MOTD_FILE))?;
+    if !status.success {
+        return Err(buckos_package::Error::Other(format!(
+            "periodic {} failed: {}",
+            task, status.summary
+        )));
+    }
+    Ok(())
+}
 
-                manager.sync(&name).await?;
+fn cmd_status(args: StatusArgs, config: &Config) -> buckos_package::Result<()> {
+    let status = PeriodicStatus::load(std::path::Path::new(buckos_package::periodic::STATUS_FILE))?;
+    let repos: Vec<RepoGeneration> = config.repositories.iter().map(RepoGeneration::of).collect();
+    if args.json {
+        let mut value = serde_json::to_value(&status)?;
+        value["repositories"] = serde_json::to_value(&repos)?;
+        println!("{}", serde_json::to_string_pretty(&value)?);
+        return Ok(());
+    }
+    if args.motd {
+        print!("{}", status.motd().unwrap_or_default());
+        return Ok(());
+    }
 
-                println!("{} Overlay {} synced", style(">>>").green().bold(), name);
-            } else {
+    for repo in &repos {
+        match (repo.generation, repo.activated) {
+            (Some(generation), Some(activated)) => println!(
+                "{}",
+                tr!(
+                    "status-generation",
+                    name = format!("{:<8}", repo.name),
+                    time = (activated
+                        .with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M"))
+                    .to_string(),
+                    generation = generation.to_string()
+                )
+            ),
+            (Some(generation), None) => {
                 println!(
-                    "{} Syncing all enabled overlays...",
-                    style(">>>").blue().bold()
-                );
+                    "{}",
+                    tr!(
+                        "status-generation-2",
+                        name = format!("{:<8}", repo.name),
+                        generation = generation.to_string()
+                    )
+                )
+            }
+            (None, _) => println!(
+                "{}",
+                tr!(
+                    "status-not-synced-into-generation",
+                    name = format!("{:<8}", repo.name)
+                )
+            ),
+        }
+    }
+    if !repos.is_empty() {
+        println!();
+    }
 
-                manager.sync_all().await?;
+    if status.tasks.is_empty() {
+        println!("{}", tr!("status-no-periodic-tasks-have"));
+        return Ok(());
+    }
 
-                println!("{} All overlays synced", style(">>>").green().bold());
-            }
+    for (task, result) in &status.tasks {
+        let outcome = if result.success {
+            theme::success("ok")
+        } else {
+            theme::error("failed")
+        };
+        println!(
+            "{:<8} {}  {:<6}  {}",
+            task,
+            result
+                .finished_at
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M"),
+            outcome,
+            result.summary
+        );
+        for package in &result.packages {
+            println!("    {}", package);
         }
+    }
+    Ok(())
+}
 
-        OverlayCommand::Info { name } => match manager.get_info(&name) {
-            Some(overlay) => {
-                println!("{}", style("Overlay Information").bold().underlined());
-                println!();
-                println!("  {} {}", style("Name:").bold(), overlay.name);
-                println!("  {} {}", style("Description:").bold(), overlay.description);
-                println!("  {} {:?}", style("Sync Type:").bold(), overlay.sync_type);
-                println!("  {} {}", style("Sync URI:").bold(), overlay.sync_uri);
-                println!(
-                    "  {} {}",
-                    style("Location:").bold(),
-                    overlay.location.display()
-                );
-                println!("  {} {}", style("Priority:").bold(), overlay.priority);
-                println!("  {} {}", style("Quality:").bold(), overlay.quality);
-                println!(
-                    "  {} {}",
-                    style("Status:").bold(),
-                    if overlay.enabled {
-                        "enabled"
-                    } else {
-                        "disabled"
-                    }
-                );
-                println!(
-                    "  {} {}",
-                    style("Auto-sync:").bold(),
-                    if overlay.auto_sync { "yes" } else { "no" }
-                );
-                if let Some(owner) = &overlay.owner {
-                    println!("  {} {}", style("Owner:").bold(), owner);
-                }
-                if let Some(homepage) = &overlay.homepage {
-                    println!("  {} {}", style("Homepage:").bold(), homepage);
-                }
-                if !overlay.masters.is_empty() {
-                    println!(
-                        "  {} {}",
-                        style("Masters:").bold(),
-                        overlay.masters.join(", ")
-                    );
-                }
-                if let Some(last_sync) = overlay.last_sync {
-                    let datetime = chrono::DateTime::from_timestamp(last_sync as i64, 0)
-                        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S UTC").to_string())
-                        .unwrap_or_else(|| "unknown".to_string());
-                    println!("  {} {}", style("Last Sync:").bold(), datetime);
-                }
-            }
-            None => {
-                println!(
-                    "{} Overlay not found: {}",
-                    style(">>>").yellow().bold(),
-                    name
-                );
-            }
-        },
+/// Repair the package database from the plain-text package records
+fn cmd_db_repair(config: &Config, pretend: bool, ask: bool) -> buckos_package::Result<()> {
+    let problems = db_problems(config)?;
+    if problems.is_empty() {
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!(
+                "db-check-no-problems-found-in",
+                db_path = config.db_path.display().to_string()
+            )
+        );
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("  {} {}", theme::error("!").bold(), problem.describe());
+    }
 
-        OverlayCommand::Priority { name, priority } => {
+    let rebuild = problems
+        .iter()
+        .any(|p| matches!(p, IntegrityProblem::Corrupt { .. }));
+    if rebuild {
+        println!(
+            "\n{}\n{}",
+            tr!("db-repair-the-database-will-be"),
+            tr!("db-repair-the-transaction-history-it")
+        );
+    }
+    if pretend {
+        return Ok(());
+    }
+    if ask {
+        println!();
+        if !Confirm::new()
+            .with_prompt("Would you like to repair the package database?")
+            .default(false)
+            .interact()?
+        {
             println!(
-                "{} Setting priority for {} to {}...",
-                style(">>>").blue().bold(),
-                name,
-                priority
+                "{} {}",
+                theme::warning(">>>").bold(),
+                theme::warning(tr!("exiting")).bold()
             );
+            return Ok(());
+        }
+    }
 
-            manager.set_priority(&name, priority)?;
+    let vdb = Vdb::new(&config.db_path);
+    let world = WorldFile::at(&Layout::resolve(config)?.path(buckos_package::world::WORLD_FILE))?;
+    let explicit: HashSet<String> = world.entries().cloned().collect();
+    let report = if rebuild {
+        PackageDb::rebuild(&config.db_path, &vdb, &explicit)?
+    } else {
+        PackageDb::open(&config.db_path)?.repair(&vdb, &problems, &explicit)?
+    };
 
-            println!("{} Priority updated", style(">>>").green().bold());
-        }
+    if let Some(aside) = &report.moved_aside {
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!(
+                "db-repair-moved-the-damaged-database",
+                aside = aside.display().to_string()
+            )
+        );
+    }
+    println!(
+        "{} {}",
+        theme::success(">>>").bold(),
+        tr!(
+            "db-repair-restored-packages-from-their",
+            count = report.restored.len()
+        )
+    );
+    if !report.recorded.is_empty() {
+        println!(
+            "{} {}",
+            theme::success(">>>").bold(),
+            tr!(
+                "db-repair-wrote-missing-package-records",
+                count = report.recorded.len()
+            )
+        );
+    }
+    Ok(())
+}
 
-        OverlayCommand::Search { query } => {
-            let results = manager.search(&query);
+/// Plain line-based confirmation prompt for builds without the `tui`
+/// feature, with the subset of dialoguer's `Confirm` the CLI uses
+#[cfg(not(feature = "tui"))]
+mod prompt {
+    use std::io::{BufRead, Write};
 
-            if results.is_empty() {
-                println!(
-                    "{} No overlays found matching '{}'",
-                    style(">>>").yellow().bold(),
-                    query
-                );
-                return Ok(());
+    pub struct Confirm {
+        prompt: String,
+        default: bool,
+    }
+
+    impl Confirm {
+        pub fn new() -> Self {
+            Self {
+                prompt: String::new(),
+                default: true,
             }
+        }
 
-            println!(
-                "{} Found {} overlay(s) matching '{}'",
-                style(">>>").green().bold(),
-                results.len(),
-                query
-            );
-            println!();
+        pub fn with_prompt(mut self, prompt: impl Into<String>) -> Self {
+            self.prompt = prompt.into();
+            self
+        }
 
-            for overlay in results {
-                let quality = match overlay.quality {
-                    OverlayQuality::Official => style("[official]").green(),
-                    OverlayQuality::Community => style("[community]").blue(),
-                    OverlayQuality::Experimental => style("[experimental]").yellow(),
-                    OverlayQuality::Local => style("[local]").cyan(),
-                };
+        pub fn default(mut self, default: bool) -> Self {
+            self.default = default;
+            self
+        }
 
-                println!("  {} {}", style(&overlay.name).bold(), quality);
-                if !overlay.description.is_empty() {
-                    println!("    {}", style(&overlay.description).dim());
+        /// Ask until the answer is yes, no or empty (the default)
+        pub fn interact(&self) -> std::io::Result<bool> {
+            let choices = if self.default { "[Y/n]" } else { "[y/N]" };
+            let stdin = std::io::stdin();
+            loop {
+                eprint!("{} {} ", self.prompt, choices);
+                std::io::stderr().flush()?;
+
+                let mut answer = String::new();
+                if stdin.lock().read_line(&mut answer)? == 0 {
+                    return Err(std::io::ErrorKind::UnexpectedEof.into());
+                }
+                match answer.trim().to_lowercase().as_str() {
+                    "" => return Ok(self.default),
+                    "y" | "yes" => return Ok(true),
+                    "n" | "no" => return Ok(false),
+                    _ => continue,
                 }
             }
         }
     }
-
-    Ok(())
 }
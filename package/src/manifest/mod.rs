@@ -0,0 +1,605 @@
+//! Machine manifests
+//!
+//! A manifest captures everything needed to reproduce a machine's package
+//! state: the portable part of the configuration stack, the enabled
+//! repositories and overlays, the world set and the exact version and USE
+//! flags of every installed package. `buckos export --manifest` writes one and
+//! `buckos apply` converges another machine to it; `buckos converge --check`
+//! reports how far a machine has drifted from one.
+
+use crate::config::RepositoryConfig;
+use crate::{Config, Error, InstalledPackage, PackageId, Result, UseConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::Path;
+
+/// Current manifest format version
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Portable configuration settings
+///
+/// Paths such as the root, database and cache locations are machine-specific
+/// and deliberately left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigStack {
+    /// Architecture
+    pub arch: String,
+    /// CHOST
+    pub chost: String,
+    /// CFLAGS
+    pub cflags: String,
+    /// CXXFLAGS
+    pub cxxflags: String,
+    /// LDFLAGS
+    pub ldflags: String,
+    /// MAKEOPTS
+    pub makeopts: String,
+    /// Global USE flags
+    #[serde(default)]
+    pub use_global: BTreeSet<String>,
+    /// Per-package USE flags, keyed by `category/name`
+    #[serde(default)]
+    pub package_use: BTreeMap<String, BTreeSet<String>>,
+    /// Masked USE flags
+    #[serde(default)]
+    pub use_mask: BTreeSet<String>,
+    /// USE_EXPAND variables
+    #[serde(default)]
+    pub use_expand: BTreeMap<String, BTreeSet<String>>,
+    /// FEATURES
+    #[serde(default)]
+    pub features: BTreeSet<String>,
+    /// ACCEPT_KEYWORDS
+    #[serde(default)]
+    pub accept_keywords: BTreeSet<String>,
+    /// ACCEPT_LICENSE
+    pub accept_license: String,
+    /// INSTALL_MASK
+    #[serde(default)]
+    pub install_mask: Vec<String>,
+    /// Preferred any-of alternatives
+    #[serde(default)]
+    pub any_of_preferred: Vec<String>,
+}
+
+impl ConfigStack {
+    /// Capture the portable settings of a configuration
+    pub fn from_config(config: &Config) -> Self {
+        let use_flags = &config.use_flags;
+        Self {
+            arch: config.arch.clone(),
+            chost: config.chost.clone(),
+            cflags: config.cflags.clone(),
+            cxxflags: config.cxxflags.clone(),
+            ldflags: config.ldflags.clone(),
+            makeopts: config.makeopts.clone(),
+            use_global: use_flags.global.iter().cloned().collect(),
+            package_use: use_flags
+                .package
+                .iter()
+                .map(|(id, flags)| (id.full_name(), flags.iter().cloned().collect()))
+                .collect(),
+            use_mask: use_flags.mask.iter().cloned().collect(),
+            use_expand: use_flags
+                .expand
+                .iter()
+                .map(|(var, values)| (var.clone(), values.iter().cloned().collect()))
+                .collect(),
+            features: config.features.iter().cloned().collect(),
+            accept_keywords: config.accept_keywords.iter().cloned().collect(),
+            accept_license: config.accept_license.clone(),
+            install_mask: config.install_mask.clone(),
+            any_of_preferred: config.any_of_preferred.clone(),
+        }
+    }
+
+    /// Overwrite the portable settings of a configuration
+    pub fn apply_to(&self, config: &mut Config) -> Result<()> {
+        let mut package = BTreeMap::new();
+        for (name, flags) in &self.package_use {
+            let id =
+                PackageId::parse(name).ok_or_else(|| Error::InvalidPackageSpec(name.clone()))?;
+            package.insert(id, flags.iter().cloned().collect());
+        }
+
+        config.arch = self.arch.clone();
+        config.chost = self.chost.clone();
+        config.cflags = self.cflags.clone();
+        config.cxxflags = self.cxxflags.clone();
+        config.ldflags = self.ldflags.clone();
+        config.makeopts = self.makeopts.clone();
+        config.use_flags = UseConfig {
+            global: self.use_global.iter().cloned().collect(),
+            package,
+            mask: self.use_mask.iter().cloned().collect(),
+            expand: self
+                .use_expand
+                .iter()
+                .map(|(var, values)| (var.clone(), values.iter().cloned().collect()))
+                .collect(),
+        };
+        config.features = self.features.iter().cloned().collect();
+        config.accept_keywords = self.accept_keywords.iter().cloned().collect();
+        config.accept_license = self.accept_license.clone();
+        config.install_mask = self.install_mask.clone();
+        config.any_of_preferred = self.any_of_preferred.clone();
+        Ok(())
+    }
+}
+
+/// An installed package pinned to an exact version and USE flags
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinnedPackage {
+    /// Package ID
+    pub id: PackageId,
+    /// Exact version
+    pub version: semver::Version,
+    /// Slot
+    pub slot: String,
+    /// USE flags the package was built with
+    #[serde(default)]
+    pub use_flags: BTreeSet<String>,
+}
+
+impl PinnedPackage {
+    /// Pin an installed package
+    pub fn from_installed(pkg: &InstalledPackage) -> Self {
+        Self {
+            id: pkg.id.clone(),
+            version: pkg.version.clone(),
+            slot: pkg.slot.clone(),
+            use_flags: pkg.use_flags.iter().cloned().collect(),
+        }
+    }
+
+    /// Atom selecting exactly this version and slot
+    pub fn atom(&self) -> String {
+        format!("={}-{}:{}", self.id, self.version, self.slot)
+    }
+}
+
+/// A complete description of a machine's package state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MachineManifest {
+    /// Manifest format version
+    pub version: u32,
+    /// When the manifest was exported
+    pub created: chrono::DateTime<chrono::Utc>,
+    /// Selected profile, if any
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Portable configuration
+    pub config: ConfigStack,
+    /// Repositories and overlays
+    #[serde(default)]
+    pub repositories: Vec<RepositoryConfig>,
+    /// World set entries
+    #[serde(default)]
+    pub world: BTreeSet<String>,
+    /// Every installed package
+    #[serde(default)]
+    pub packages: Vec<PinnedPackage>,
+}
+
+impl MachineManifest {
+    /// Capture a machine's state
+    pub fn capture(
+        config: &Config,
+        profile: Option<String>,
+        world: impl IntoIterator<Item = String>,
+        installed: &[InstalledPackage],
+    ) -> Self {
+        let mut packages: Vec<PinnedPackage> = installed
+            .iter()
+            .map(PinnedPackage::from_installed)
+            .collect();
+        packages.sort_by(|a, b| a.id.cmp(&b.id).then_with(|| a.slot.cmp(&b.slot)));
+
+        Self {
+            version: MANIFEST_VERSION,
+            created: chrono::Utc::now(),
+            profile,
+            config: ConfigStack::from_config(config),
+            repositories: config.repositories.clone(),
+            world: world.into_iter().collect(),
+            packages,
+        }
+    }
+
+    /// Serialize as JSON or TOML
+    pub fn render(&self, format: &str) -> Result<String> {
+        match format {
+            "json" => serde_json::to_string_pretty(self)
+                .map_err(|e| Error::Other(format!("Failed to serialize manifest: {}", e))),
+            "toml" => toml::to_string_pretty(self)
+                .map_err(|e| Error::Other(format!("Failed to serialize manifest: {}", e))),
+            _ => Err(Error::ConfigError(format!(
+                "Unknown manifest format: {}. Use json or toml",
+                format
+            ))),
+        }
+    }
+
+    /// Parse a manifest, accepting either JSON or TOML
+    pub fn parse(content: &str) -> Result<Self> {
+        let manifest: Self = if content.trim_start().starts_with('{') {
+            serde_json::from_str(content)
+                .map_err(|e| Error::Other(format!("Invalid manifest: {}", e)))?
+        } else {
+            toml::from_str(content)?
+        };
+
+        if manifest.version > MANIFEST_VERSION {
+            return Err(Error::Other(format!(
+                "Manifest version {} is newer than supported version {}",
+                manifest.version, MANIFEST_VERSION
+            )));
+        }
+        Ok(manifest)
+    }
+
+    /// Load a manifest from a file
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Compare a machine against this manifest
+    ///
+    /// Protected configuration files are not checked here since that needs
+    /// the package database; see
+    /// [`PackageManager::check_drift`](crate::PackageManager::check_drift).
+    pub fn drift(
+        &self,
+        config: &Config,
+        world: &HashSet<String>,
+        installed: &[InstalledPackage],
+    ) -> DriftReport {
+        let plan = self.plan(config, world, installed);
+        let mut report = DriftReport {
+            config_changed: plan.config_changed,
+            missing_repositories: plan.add_repositories.into_iter().map(|r| r.name).collect(),
+            missing: plan.install.into_iter().map(|p| p.id).collect(),
+            extra: plan.remove.into_iter().map(|p| p.id).collect(),
+            world_added: plan.world_remove,
+            world_removed: plan.world_add,
+            ..Default::default()
+        };
+
+        for pin in &plan.rebuild {
+            let Some(pkg) = installed
+                .iter()
+                .find(|p| p.id == pin.id && p.slot == pin.slot)
+            else {
+                continue;
+            };
+
+            if pkg.version != pin.version {
+                report.versions.push(VersionDrift {
+                    id: pin.id.clone(),
+                    pinned: pin.version.clone(),
+                    installed: pkg.version.clone(),
+                });
+            }
+
+            let use_flags: BTreeSet<String> = pkg.use_flags.iter().cloned().collect();
+            if use_flags != pin.use_flags {
+                report.use_flags.push(UseDrift {
+                    id: pin.id.clone(),
+                    added: use_flags.difference(&pin.use_flags).cloned().collect(),
+                    removed: pin.use_flags.difference(&use_flags).cloned().collect(),
+                });
+            }
+        }
+
+        report
+    }
+
+    /// Work out what must change for a machine to match this manifest
+    pub fn plan(
+        &self,
+        config: &Config,
+        world: &HashSet<String>,
+        installed: &[InstalledPackage],
+    ) -> ManifestPlan {
+        let mut plan = ManifestPlan {
+            config_changed: ConfigStack::from_config(config) != self.config,
+            ..Default::default()
+        };
+
+        for repo in &self.repositories {
+            if !config.repositories.iter().any(|r| r.name == repo.name) {
+                plan.add_repositories.push(repo.clone());
+            }
+        }
+
+        for pin in &self.packages {
+            let current = installed
+                .iter()
+                .find(|p| p.id == pin.id && p.slot == pin.slot);
+            match current {
+                None => plan.install.push(pin.clone()),
+                Some(pkg) => {
+                    let use_flags: BTreeSet<String> = pkg.use_flags.iter().cloned().collect();
+                    if pkg.version != pin.version || use_flags != pin.use_flags {
+                        plan.rebuild.push(pin.clone());
+                    }
+                }
+            }
+        }
+
+        for pkg in installed {
+            let pinned = self
+                .packages
+                .iter()
+                .any(|p| p.id == pkg.id && p.slot == pkg.slot);
+            if !pinned {
+                plan.remove.push(PinnedPackage::from_installed(pkg));
+            }
+        }
+        plan.remove
+            .sort_by(|a, b| (&a.id, &a.slot).cmp(&(&b.id, &b.slot)));
+
+        plan.world_add = self
+            .world
+            .iter()
+            .filter(|w| !world.contains(*w))
+            .cloned()
+            .collect();
+        plan.world_remove = world
+            .iter()
+            .filter(|w| !self.world.contains(*w))
+            .cloned()
+            .collect();
+        plan.world_remove.sort();
+
+        plan
+    }
+}
+
+/// Changes needed to converge a machine to a manifest
+#[derive(Debug, Clone, Default)]
+pub struct ManifestPlan {
+    /// Whether the portable configuration differs
+    pub config_changed: bool,
+    /// Repositories to add
+    pub add_repositories: Vec<RepositoryConfig>,
+    /// Packages to install
+    pub install: Vec<PinnedPackage>,
+    /// Installed packages whose version or USE flags differ
+    pub rebuild: Vec<PinnedPackage>,
+    /// Installed packages whose slot is not in the manifest
+    pub remove: Vec<PinnedPackage>,
+    /// World entries to add
+    pub world_add: Vec<String>,
+    /// World entries to drop
+    pub world_remove: Vec<String>,
+}
+
+impl ManifestPlan {
+    /// Whether the machine already matches the manifest
+    pub fn is_empty(&self) -> bool {
+        !self.config_changed
+            && self.add_repositories.is_empty()
+            && self.install.is_empty()
+            && self.rebuild.is_empty()
+            && self.remove.is_empty()
+            && self.world_add.is_empty()
+            && self.world_remove.is_empty()
+    }
+}
+
+/// USE flags of an installed package that differ from its pin
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UseDrift {
+    /// Package ID
+    pub id: PackageId,
+    /// Flags enabled on the machine but not in the manifest
+    pub added: Vec<String>,
+    /// Flags in the manifest but not enabled on the machine
+    pub removed: Vec<String>,
+}
+
+/// An installed package at a different version than pinned
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VersionDrift {
+    /// Package ID
+    pub id: PackageId,
+    /// Version in the manifest
+    pub pinned: semver::Version,
+    /// Version installed
+    pub installed: semver::Version,
+}
+
+/// A protected configuration file that no longer matches what was merged
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigFileDrift {
+    /// File path
+    pub path: String,
+    /// Package that installed it
+    pub package: String,
+    /// Whether the file was deleted rather than edited
+    pub missing: bool,
+}
+
+/// Differences between a machine and a manifest
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DriftReport {
+    /// Whether the portable configuration differs
+    pub config_changed: bool,
+    /// Repositories in the manifest but not configured
+    pub missing_repositories: Vec<String>,
+    /// Pinned packages that are not installed
+    pub missing: Vec<PackageId>,
+    /// Installed packages that are not in the manifest
+    pub extra: Vec<PackageId>,
+    /// Packages at a different version
+    pub versions: Vec<VersionDrift>,
+    /// Packages built with different USE flags
+    pub use_flags: Vec<UseDrift>,
+    /// World entries added outside the manifest
+    pub world_added: Vec<String>,
+    /// World entries removed outside the manifest
+    pub world_removed: Vec<String>,
+    /// Protected configuration files changed since merge
+    pub config_files: Vec<ConfigFileDrift>,
+}
+
+impl DriftReport {
+    /// Whether the machine matches the manifest
+    pub fn is_clean(&self) -> bool {
+        !self.config_changed
+            && self.missing_repositories.is_empty()
+            && self.missing.is_empty()
+            && self.extra.is_empty()
+            && self.versions.is_empty()
+            && self.use_flags.is_empty()
+            && self.world_added.is_empty()
+            && self.world_removed.is_empty()
+            && self.config_files.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn installed(name: &str, version: &str, use_flags: &[&str]) -> InstalledPackage {
+        InstalledPackage {
+            id: PackageId::new("app-misc", name),
+            name: name.to_string(),
+            version: semver::Version::parse(version).unwrap(),
+            slot: "0".to_string(),
+            installed_at: chrono::Utc::now(),
+            use_flags: use_flags.iter().map(|s| s.to_string()).collect(),
+            files: Vec::new(),
+            size: 0,
+            build_time: false,
+            explicit: true,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut config = Config::default();
+        config.use_flags.global.insert("ssl".to_string());
+        config.use_flags.package.insert(
+            PackageId::new("app-misc", "foo"),
+            ["-doc".to_string()].into(),
+        );
+
+        let manifest = MachineManifest::capture(
+            &config,
+            Some("default/linux/amd64".to_string()),
+            ["app-misc/foo".to_string()],
+            &[installed("foo", "1.2.3", &["ssl"])],
+        );
+
+        for format in ["json", "toml"] {
+            let parsed = MachineManifest::parse(&manifest.render(format).unwrap()).unwrap();
+            assert_eq!(parsed.config, manifest.config);
+            assert_eq!(parsed.packages, manifest.packages);
+            assert_eq!(parsed.world, manifest.world);
+            assert_eq!(parsed.profile, manifest.profile);
+        }
+
+        let mut other = Config::default();
+        manifest.config.apply_to(&mut other).unwrap();
+        assert_eq!(ConfigStack::from_config(&other), manifest.config);
+
+        // The applied configuration must survive being written to disk
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("buckos.toml");
+        other.save_to(&path).unwrap();
+        let loaded = Config::load_from(&path).unwrap();
+        assert_eq!(ConfigStack::from_config(&loaded), manifest.config);
+    }
+
+    #[test]
+    fn test_plan() {
+        let config = Config::default();
+        let manifest = MachineManifest::capture(
+            &config,
+            None,
+            ["app-misc/foo".to_string(), "app-misc/bar".to_string()],
+            &[
+                installed("foo", "1.0.0", &[]),
+                installed("bar", "2.0.0", &["gtk"]),
+            ],
+        );
+
+        let world: HashSet<String> =
+            ["app-misc/foo".to_string(), "app-misc/old".to_string()].into();
+        let plan = manifest.plan(
+            &config,
+            &world,
+            &[
+                installed("foo", "1.0.0", &[]),
+                installed("bar", "2.0.0", &[]),
+                installed("old", "0.1.0", &[]),
+            ],
+        );
+
+        assert!(!plan.config_changed);
+        assert!(plan.install.is_empty());
+        assert_eq!(plan.rebuild.len(), 1);
+        assert_eq!(plan.rebuild[0].id.name, "bar");
+        assert_eq!(plan.remove.len(), 1);
+        assert_eq!(plan.remove[0].id, PackageId::new("app-misc", "old"));
+        assert_eq!(plan.remove[0].atom(), "=app-misc/old-0.1.0:0");
+        assert_eq!(plan.world_add, vec!["app-misc/bar".to_string()]);
+        assert_eq!(plan.world_remove, vec!["app-misc/old".to_string()]);
+        assert!(!plan.is_empty());
+
+        let plan = manifest.plan(&config, &world, &[]);
+        assert_eq!(plan.install.len(), 2);
+    }
+
+    #[test]
+    fn test_drift() {
+        let config = Config::default();
+        let manifest = MachineManifest::capture(
+            &config,
+            None,
+            ["app-misc/foo".to_string()],
+            &[
+                installed("foo", "1.0.0", &["ssl"]),
+                installed("bar", "2.0.0", &[]),
+            ],
+        );
+        let world: HashSet<String> = ["app-misc/foo".to_string()].into();
+
+        let report = manifest.drift(
+            &config,
+            &world,
+            &[
+                installed("foo", "1.0.1", &["gtk"]),
+                installed("baz", "0.1.0", &[]),
+            ],
+        );
+        assert_eq!(report.missing, vec![PackageId::new("app-misc", "bar")]);
+        assert_eq!(report.extra, vec![PackageId::new("app-misc", "baz")]);
+        assert_eq!(report.versions.len(), 1);
+        assert_eq!(report.versions[0].installed, semver::Version::new(1, 0, 1));
+        assert_eq!(report.use_flags[0].added, vec!["gtk".to_string()]);
+        assert_eq!(report.use_flags[0].removed, vec!["ssl".to_string()]);
+        assert!(!report.is_clean());
+
+        let report = manifest.drift(
+            &config,
+            &world,
+            &[
+                installed("foo", "1.0.0", &["ssl"]),
+                installed("bar", "2.0.0", &[]),
+            ],
+        );
+        assert!(report.is_clean());
+    }
+
+    #[test]
+    fn test_rejects_newer_version() {
+        let mut manifest = MachineManifest::capture(&Config::default(), None, [], &[]);
+        manifest.version = MANIFEST_VERSION + 1;
+        let content = manifest.render("json").unwrap();
+        assert!(MachineManifest::parse(&content).is_err());
+    }
+}
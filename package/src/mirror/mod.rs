@@ -0,0 +1,533 @@
+//! LAN mirror server
+//!
+//! Serves the repository tree, binary packages and distfiles over HTTP so
+//! one machine can act as a mirror and binhost for others on the network:
+//!
+//! ```text
+//! GET /repo/...        repository tree
+//! GET /binpkgs/...     PKGDIR (point PORTAGE_BINHOST / binpkg_server here)
+//! GET /distfiles/...   distfile cache (usable as a GENTOO_MIRRORS entry)
+//! GET /_stats          request and bandwidth counters
+//! GET /_has/<path>?sha256=<hex>   whether a file with that digest is here
+//! ```
+//!
+//! With `--share` the server is also advertised over mDNS so other machines
+//! pull distfiles from it before the internet (see [`crate::peer`]).
+//!
+//! Files support range requests and ETags so interrupted downloads resume.
+//! An optional bearer token (`Authorization: Bearer <token>` or `?token=`)
+//! restricts access.
+
+use crate::http::{self, Request, Response};
+use crate::peer::Digest;
+use crate::{Config, Result};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::io::BufReader;
+use tokio::net::TcpListener;
+use tracing::{debug, info, warn};
+
+/// Default listen address
+pub const DEFAULT_LISTEN: &str = "0.0.0.0:8080";
+
+/// A directory served under a URL prefix
+#[derive(Debug, Clone)]
+pub struct Mount {
+    /// URL prefix without slashes (e.g. `binpkgs`)
+    pub name: String,
+    /// Directory served
+    pub root: PathBuf,
+}
+
+/// Mirror server settings
+#[derive(Debug, Clone)]
+pub struct MirrorConfig {
+    /// Address to listen on
+    pub listen: String,
+    /// Directories to serve
+    pub mounts: Vec<Mount>,
+    /// Token clients must present, if any
+    pub token: Option<String>,
+    /// Generate directory listings
+    pub listing: bool,
+}
+
+impl MirrorConfig {
+    /// Serve the repository, PKGDIR and distfiles of a configuration
+    pub fn from_config(config: &Config) -> Self {
+        let repo = config
+            .repositories
+            .first()
+            .map(|r| r.location.clone())
+            .unwrap_or_else(|| config.buck_repo.clone());
+
+        Self {
+            listen: DEFAULT_LISTEN.to_string(),
+            mounts: vec![
+                Mount {
+                    name: "repo".to_string(),
+                    root: repo,
+                },
+                Mount {
+                    name: "binpkgs".to_string(),
+                    root: config.packages_dir(),
+                },
+                Mount {
+                    name: "distfiles".to_string(),
+                    root: config.download_cache(),
+                },
+            ],
+            token: None,
+            listing: true,
+        }
+    }
+
+    /// Set the listen address
+    pub fn with_listen(mut self, listen: impl Into<String>) -> Self {
+        self.listen = listen.into();
+        self
+    }
+
+    /// Require a token
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token.filter(|t| !t.is_empty());
+        self
+    }
+
+    /// Enable or disable directory listings
+    pub fn with_listing(mut self, listing: bool) -> Self {
+        self.listing = listing;
+        self
+    }
+}
+
+/// Request and bandwidth counters
+#[derive(Debug)]
+pub struct MirrorStats {
+    started: Instant,
+    requests: AtomicU64,
+    errors: AtomicU64,
+    bytes: AtomicU64,
+    mount_bytes: BTreeMap<String, AtomicU64>,
+}
+
+impl MirrorStats {
+    fn new(mounts: &[Mount]) -> Self {
+        Self {
+            started: Instant::now(),
+            requests: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            mount_bytes: mounts
+                .iter()
+                .map(|m| (m.name.clone(), AtomicU64::new(0)))
+                .collect(),
+        }
+    }
+
+    fn record(&self, mount: Option<&str>, status: u16, bytes: u64) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if status >= 400 {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        if let Some(counter) = mount.and_then(|m| self.mount_bytes.get(m)) {
+            counter.fetch_add(bytes, Ordering::Relaxed);
+        }
+    }
+
+    /// Total requests handled
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    /// Total body bytes sent
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Average outgoing bandwidth since start, in bytes per second
+    pub fn average_rate(&self) -> f64 {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            self.bytes_sent() as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+
+    /// Plain-text summary
+    pub fn report(&self) -> String {
+        let mut out = format!(
+            "uptime_seconds {}\nrequests {}\nerrors {}\nbytes_sent {}\naverage_bytes_per_second {:.0}\n",
+            self.started.elapsed().as_secs(),
+            self.requests(),
+            self.errors.load(Ordering::Relaxed),
+            self.bytes_sent(),
+            self.average_rate()
+        );
+        for (name, bytes) in &self.mount_bytes {
+            out.push_str(&format!(
+                "bytes_sent{{mount=\"{}\"}} {}\n",
+                name,
+                bytes.load(Ordering::Relaxed)
+            ));
+        }
+        out
+    }
+}
+
+/// HTTP mirror server
+pub struct MirrorServer {
+    config: MirrorConfig,
+    stats: Arc<MirrorStats>,
+}
+
+impl MirrorServer {
+    /// Create a server
+    pub fn new(config: MirrorConfig) -> Self {
+        let stats = Arc::new(MirrorStats::new(&config.mounts));
+        Self { config, stats }
+    }
+
+    /// Counters shared with the running server
+    pub fn stats(&self) -> Arc<MirrorStats> {
+        self.stats.clone()
+    }
+
+    /// Serve requests until the task is cancelled
+    pub async fn run(self) -> Result<()> {
+        let listener = TcpListener::bind(&self.config.listen).await?;
+        info!("Mirror listening on {}", listener.local_addr()?);
+
+        let server = Arc::new(self);
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            let server = server.clone();
+            tokio::spawn(async move {
+                if let Err(e) = server.handle_connection(stream).await {
+                    debug!("Mirror connection from {} failed: {}", peer, e);
+                }
+            });
+        }
+    }
+
+    async fn handle_connection(&self, stream: tokio::net::TcpStream) -> Result<()> {
+        let peer = stream.peer_addr()?;
+        let mut stream = BufReader::new(stream);
+        let Some(request) = http::read_request(&mut stream).await? else {
+            return Ok(());
+        };
+
+        let start = Instant::now();
+        let (mount, response) = self.handle_request(&request).await;
+        let status = response.status;
+        let sent = http::write_response(stream.get_mut(), response, request.is_head()).await?;
+        self.stats.record(mount.as_deref(), status, sent);
+
+        info!(
+            "{} \"{} {}\" {} {} {}ms",
+            peer,
+            request.method,
+            request.path,
+            status,
+            sent,
+            start.elapsed().as_millis()
+        );
+        Ok(())
+    }
+
+    async fn handle_request(&self, request: &Request) -> (Option<String>, Response) {
+        if request.method != "GET" && !request.is_head() {
+            return (None, Response::text(405, "method not allowed\n"));
+        }
+        if !self.authorized(request) {
+            let response = Response::text(401, "unauthorized\n")
+                .with_header("WWW-Authenticate", "Bearer realm=\"buckos\"");
+            return (None, response);
+        }
+
+        if request.path == "/_stats" {
+            return (None, Response::text(200, self.stats.report()));
+        }
+        if let Some(rest) = request.path.strip_prefix("/_has") {
+            return (None, self.has_digest(rest, request.query.as_deref()).await);
+        }
+        if request.path == "/" {
+            let names: Vec<String> = self
+                .config
+                .mounts
+                .iter()
+                .map(|m| format!("{}/", m.name))
+                .collect();
+            return (None, listing_page("/", &names));
+        }
+
+        let Some((mount, path)) = self.resolve(&request.path) else {
+            return (None, Response::not_found());
+        };
+        let name = Some(mount.name.clone());
+
+        if path.is_dir() {
+            if !request.path.ends_with('/') {
+                let location = format!("{}/", request.path);
+                return (
+                    name,
+                    Response::text(301, "").with_header("Location", location),
+                );
+            }
+            if !self.config.listing {
+                return (name, Response::text(403, "forbidden\n"));
+            }
+            return match directory_entries(&path) {
+                Ok(entries) => (name, listing_page(&request.path, &entries)),
+                Err(e) => {
+                    warn!("Failed to list {}: {}", path.display(), e);
+                    (name, Response::text(500, "internal error\n"))
+                }
+            };
+        }
+
+        match Response::file_for(&path, request).await {
+            Ok(response) => (name, response),
+            Err(e) => {
+                warn!("Failed to open {}: {}", path.display(), e);
+                (name, Response::text(500, "internal error\n"))
+            }
+        }
+    }
+
+    /// Answer a peer asking whether a file with a given digest is here
+    async fn has_digest(&self, path: &str, query: Option<&str>) -> Response {
+        let Some(digest) = query.and_then(Digest::from_query) else {
+            return Response::text(400, "missing digest\n");
+        };
+        let Some((_, path)) = self.resolve(path) else {
+            return Response::not_found();
+        };
+        if !path.is_file() {
+            return Response::not_found();
+        }
+
+        let matches = tokio::task::spawn_blocking(move || digest.matches(&path)).await;
+        match matches {
+            Ok(Ok(true)) => Response::text(200, "yes\n"),
+            Ok(Ok(false)) => Response::not_found(),
+            Ok(Err(e)) => {
+                warn!("Failed to hash distfile: {}", e);
+                Response::text(500, "internal error\n")
+            }
+            Err(e) => {
+                warn!("Hashing task failed: {}", e);
+                Response::text(500, "internal error\n")
+            }
+        }
+    }
+
+    /// Check the bearer token, if one is required
+    fn authorized(&self, request: &Request) -> bool {
+        let Some(token) = &self.config.token else {
+            return true;
+        };
+
+        let from_header = request
+            .header("authorization")
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .map(|t| t.trim().to_string());
+        let from_query = request.query.as_deref().and_then(|q| {
+            q.split('&')
+                .find_map(|pair| pair.strip_prefix("token="))
+                .map(http::percent_decode)
+        });
+
+        from_header
+            .or(from_query)
+            .is_some_and(|given| http::constant_time_eq(given.as_bytes(), token.as_bytes()))
+    }
+
+    /// Map a request path onto a file inside one of the mounts
+    fn resolve(&self, request_path: &str) -> Option<(&Mount, PathBuf)> {
+        let trimmed = request_path.trim_start_matches('/');
+        let (name, rest) = trimmed.split_once('/').unwrap_or((trimmed, ""));
+        let mount = self.config.mounts.iter().find(|m| m.name == name)?;
+
+        let relative = Path::new(rest);
+        if relative
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)))
+        {
+            return None;
+        }
+
+        // Symlinks must not lead outside the mount
+        let root = mount.root.canonicalize().ok()?;
+        let path = root.join(relative).canonicalize().ok()?;
+        path.starts_with(&root).then_some((mount, path))
+    }
+}
+
+/// Sorted entry names of a directory, with `/` appended to subdirectories
+fn directory_entries(dir: &Path) -> std::io::Result<Vec<String>> {
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let mut name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            name.push('/');
+        }
+        entries.push(name);
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+/// Minimal HTML directory index
+fn listing_page(path: &str, entries: &[String]) -> Response {
+    let title = html_escape(path);
+    let mut body = format!(
+        "<!DOCTYPE html>\n<html><head><title>Index of {0}</title></head>\n<body><h1>Index of {0}</h1>\n<ul>\n",
+        title
+    );
+    for entry in entries {
+        let name = html_escape(entry);
+        body.push_str(&format!("<li><a href=\"{0}\">{0}</a></li>\n", name));
+    }
+    body.push_str("</ul></body></html>\n");
+
+    let mut response = Response::text(200, body);
+    response.headers = vec![(
+        "Content-Type".to_string(),
+        "text/html; charset=utf-8".to_string(),
+    )];
+    response
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn server(root: &Path, token: Option<&str>) -> MirrorServer {
+        MirrorServer::new(MirrorConfig {
+            listen: DEFAULT_LISTEN.to_string(),
+            mounts: vec![Mount {
+                name: "binpkgs".to_string(),
+                root: root.to_path_buf(),
+            }],
+            token: token.map(String::from),
+            listing: true,
+        })
+    }
+
+    fn get(path: &str, query: Option<&str>, headers: &[(&str, &str)]) -> Request {
+        Request {
+            method: "GET".to_string(),
+            path: path.to_string(),
+            query: query.map(String::from),
+            headers: headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serves_files_within_mount() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("app-misc")).unwrap();
+        std::fs::write(dir.path().join("app-misc/foo-1.0.tar.zst"), b"data").unwrap();
+        let server = server(dir.path(), None);
+
+        let (mount, response) = server
+            .handle_request(&get("/binpkgs/app-misc/foo-1.0.tar.zst", None, &[]))
+            .await;
+        assert_eq!(mount.as_deref(), Some("binpkgs"));
+        assert_eq!(response.status, 200);
+
+        let (_, listing) = server
+            .handle_request(&get("/binpkgs/app-misc/", None, &[]))
+            .await;
+        assert_eq!(listing.status, 200);
+
+        let (_, escape) = server
+            .handle_request(&get("/binpkgs/../../etc/passwd", None, &[]))
+            .await;
+        assert_eq!(escape.status, 404);
+
+        let (_, unknown) = server.handle_request(&get("/other/x", None, &[])).await;
+        assert_eq!(unknown.status, 404);
+    }
+
+    #[tokio::test]
+    async fn test_token_auth() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = server(dir.path(), Some("s3cret"));
+
+        let (_, denied) = server.handle_request(&get("/_stats", None, &[])).await;
+        assert_eq!(denied.status, 401);
+
+        let (_, header) = server
+            .handle_request(&get("/_stats", None, &[("authorization", "Bearer s3cret")]))
+            .await;
+        assert_eq!(header.status, 200);
+
+        let (_, query) = server
+            .handle_request(&get("/_stats", Some("token=s3cret"), &[]))
+            .await;
+        assert_eq!(query.status, 200);
+    }
+
+    #[tokio::test]
+    async fn test_has_digest() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("foo-1.0.tar.gz");
+        std::fs::write(&file, b"data").unwrap();
+        let sha256 = crate::cache::compute_sha256(&file).unwrap();
+        let server = server(dir.path(), None);
+
+        let query = format!("sha256={}", sha256);
+        let (_, found) = server
+            .handle_request(&get("/_has/binpkgs/foo-1.0.tar.gz", Some(&query), &[]))
+            .await;
+        assert_eq!(found.status, 200);
+
+        let (_, wrong) = server
+            .handle_request(&get("/_has/binpkgs/foo-1.0.tar.gz", Some("sha256=00"), &[]))
+            .await;
+        assert_eq!(wrong.status, 404);
+
+        let (_, missing) = server
+            .handle_request(&get("/_has/binpkgs/foo-1.0.tar.gz", None, &[]))
+            .await;
+        assert_eq!(missing.status, 400);
+    }
+
+    #[test]
+    fn test_stats() {
+        let stats = MirrorStats::new(&[Mount {
+            name: "distfiles".to_string(),
+            root: PathBuf::new(),
+        }]);
+        stats.record(Some("distfiles"), 200, 1000);
+        stats.record(None, 404, 10);
+
+        assert_eq!(stats.requests(), 2);
+        assert_eq!(stats.bytes_sent(), 1010);
+        let report = stats.report();
+        assert!(report.contains("errors 1\n"));
+        assert!(report.contains("bytes_sent{mount=\"distfiles\"} 1000\n"));
+    }
+}
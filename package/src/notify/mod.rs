@@ -0,0 +1,646 @@
+//! Notifications about advisories and news
+//!
+//! After a sync or an audit, the advisories affecting installed packages and
+//! the unread news items are summarized and handed to the configured
+//! notifiers:
+//!
+//! - commands, which get the summary as JSON on standard input
+//! - mail, through an SMTP relay such as the local MTA
+//! - desktop notifications, shown to a logged-in user by a transient unit
+//!   of the init system running `notify-send` in their session
+//!
+//! Items already notified are remembered by key, so each advisory and news
+//! item is sent once. When every notifier fails, nothing is remembered and
+//! the items are sent again after the next sync or audit.
+
+use crate::news::NewsItem;
+use crate::{Error, Result, Vulnerability};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tracing::warn;
+
+/// Keys of the items already notified, relative to the install root
+pub const NOTIFIED_FILE: &str = "var/lib/buckos/notified.json";
+
+/// Scripts run by desktop notification units
+pub const DESKTOP_SCRIPT_DIR: &str = "/run/buckos/notify";
+
+/// Longest a notifier may take
+const NOTIFIER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Notification settings
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotifyConfig {
+    /// Commands run through `sh -c` with the summary as JSON on stdin
+    pub commands: Vec<String>,
+    /// Users shown a desktop notification in their session
+    pub desktop_users: Vec<String>,
+    /// Mail delivery through an SMTP relay
+    pub smtp: Option<SmtpConfig>,
+}
+
+impl NotifyConfig {
+    /// The configured notifiers
+    pub fn notifiers(&self) -> Vec<Notifier> {
+        let mut notifiers: Vec<Notifier> = self
+            .commands
+            .iter()
+            .cloned()
+            .map(Notifier::Command)
+            .collect();
+        notifiers.extend(self.desktop_users.iter().cloned().map(Notifier::Desktop));
+        notifiers.extend(self.smtp.clone().map(Notifier::Smtp));
+        notifiers
+    }
+}
+
+/// An SMTP relay; connections are plain, so this is meant for a relay on
+/// the machine or a trusted network
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmtpConfig {
+    pub server: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    /// Sender address
+    pub from: String,
+    /// Recipient addresses
+    pub to: Vec<String>,
+}
+
+fn default_smtp_port() -> u16 {
+    25
+}
+
+/// What a notified item is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemKind {
+    Advisory,
+    News,
+}
+
+/// One advisory or news item in a summary
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotifyItem {
+    pub kind: ItemKind,
+    /// Identifies the item across runs
+    pub key: String,
+    pub title: String,
+    /// Affected package of an advisory
+    pub package: Option<String>,
+    /// Severity of an advisory
+    pub severity: Option<String>,
+    /// Version fixing an advisory, or the date news was posted
+    pub detail: Option<String>,
+}
+
+impl NotifyItem {
+    pub fn advisory(vuln: &Vulnerability) -> Self {
+        Self {
+            kind: ItemKind::Advisory,
+            key: format!("advisory:{}:{}", vuln.id, vuln.package.full_name()),
+            title: format!("{}: {}", vuln.id, vuln.title),
+            package: Some(vuln.package.full_name()),
+            severity: Some(vuln.severity.clone()),
+            detail: vuln
+                .fixed_version
+                .as_ref()
+                .map(|v| format!("fixed in {}", v)),
+        }
+    }
+
+    pub fn news(item: &NewsItem) -> Self {
+        Self {
+            kind: ItemKind::News,
+            key: format!("news:{}", item.name),
+            title: item.title.clone(),
+            package: None,
+            severity: None,
+            detail: Some(format!("posted {}", item.posted)),
+        }
+    }
+
+    /// One line describing the item
+    pub fn line(&self) -> String {
+        let mut line = match self.kind {
+            ItemKind::Advisory => format!(
+                "[{}] {} ({})",
+                self.severity.as_deref().unwrap_or("unknown"),
+                self.title,
+                self.package.as_deref().unwrap_or_default()
+            ),
+            ItemKind::News => format!("[news] {}", self.title),
+        };
+        if let Some(detail) = &self.detail {
+            line.push_str(&format!(", {}", detail));
+        }
+        line
+    }
+}
+
+/// What notifiers receive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Summary {
+    pub host: String,
+    pub generated_at: DateTime<Utc>,
+    pub items: Vec<NotifyItem>,
+}
+
+impl Summary {
+    pub fn new(items: Vec<NotifyItem>) -> Self {
+        let host = std::fs::read_to_string("/etc/hostname")
+            .map(|s| s.trim().to_string())
+            .unwrap_or_else(|_| "localhost".to_string());
+        Self {
+            host,
+            generated_at: Utc::now(),
+            items,
+        }
+    }
+
+    fn count(&self, kind: ItemKind) -> usize {
+        self.items.iter().filter(|i| i.kind == kind).count()
+    }
+
+    /// Mail subject and notification title
+    pub fn subject(&self) -> String {
+        let mut parts = Vec::new();
+        match self.count(ItemKind::Advisory) {
+            0 => {}
+            n => parts.push(format!("{} new advisory(ies)", n)),
+        }
+        match self.count(ItemKind::News) {
+            0 => {}
+            n => parts.push(format!("{} unread news item(s)", n)),
+        }
+        format!("buckos on {}: {}", self.host, parts.join(", "))
+    }
+
+    /// Plain-text body
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for item in &self.items {
+            text.push_str(&item.line());
+            text.push('\n');
+        }
+        if self.count(ItemKind::Advisory) > 0 {
+            text.push_str("\nRun 'buckos audit' for details.\n");
+        }
+        if self.count(ItemKind::News) > 0 {
+            text.push_str("\nRun 'eselect news read' to read the news.\n");
+        }
+        text
+    }
+
+    /// Whether an advisory of high or critical severity is included
+    pub fn urgent(&self) -> bool {
+        self.items
+            .iter()
+            .any(|i| matches!(i.severity.as_deref(), Some("critical" | "high")))
+    }
+}
+
+/// A destination for summaries
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notifier {
+    /// Shell command receiving the summary as JSON on stdin
+    Command(String),
+    /// Desktop notification for a user
+    Desktop(String),
+    /// Mail through an SMTP relay
+    Smtp(SmtpConfig),
+}
+
+impl std::fmt::Display for Notifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Notifier::Command(command) => write!(f, "command '{}'", command),
+            Notifier::Desktop(user) => write!(f, "desktop of {}", user),
+            Notifier::Smtp(smtp) => write!(f, "mail to {}", smtp.to.join(", ")),
+        }
+    }
+}
+
+impl Notifier {
+    /// Send a summary
+    pub async fn send(&self, summary: &Summary) -> Result<()> {
+        let send = async {
+            match self {
+                Notifier::Command(command) => run_command(command, summary).await,
+                Notifier::Desktop(user) => notify_desktop(user, summary).await,
+                Notifier::Smtp(smtp) => send_mail(smtp, summary).await,
+            }
+        };
+        tokio::time::timeout(NOTIFIER_TIMEOUT, send)
+            .await
+            .map_err(|_| Error::Other(format!("{} timed out", self)))?
+    }
+}
+
+async fn run_command(command: &str, summary: &Summary) -> Result<()> {
+    let mut child = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("BUCKOS_NOTIFY_SUBJECT", summary.subject())
+        .stdin(std::process::Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(serde_json::to_string(summary)?.as_bytes())
+            .await?;
+    }
+    let status = child.wait().await?;
+    if !status.success() {
+        return Err(Error::Other(format!(
+            "notify command '{}' failed with {}",
+            command, status
+        )));
+    }
+    Ok(())
+}
+
+/// Quote a string for a POSIX shell
+#[cfg(feature = "init")]
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Script showing a summary with `notify-send`
+#[cfg(feature = "init")]
+fn desktop_script(summary: &Summary) -> String {
+    format!(
+        "#!/bin/sh\n# Generated by buckos\nexec notify-send --app-name=buckos --urgency={} {} {}\n",
+        if summary.urgent() {
+            "critical"
+        } else {
+            "normal"
+        },
+        shell_quote(&summary.subject()),
+        shell_quote(&summary.text()),
+    )
+}
+
+/// Uid of a local user, from /etc/passwd
+#[cfg(feature = "init")]
+fn user_uid(user: &str) -> Option<u32> {
+    let passwd = std::fs::read_to_string("/etc/passwd").ok()?;
+    passwd.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        (fields.first() == Some(&user))
+            .then(|| fields.get(2)?.parse().ok())
+            .flatten()
+    })
+}
+
+/// Show a summary in a user's session through a transient unit running as
+/// them, since only their session can reach their notification daemon
+#[cfg(feature = "init")]
+async fn notify_desktop(user: &str, summary: &Summary) -> Result<()> {
+    let uid = user_uid(user).ok_or_else(|| Error::Other(format!("unknown user '{}'", user)))?;
+    let bus = PathBuf::from(format!("/run/user/{}/bus", uid));
+    if !bus.exists() {
+        return Err(Error::Other(format!("{} has no session bus", user)));
+    }
+
+    let unit = format!("buckos-notify-{}", user);
+    let dir = Path::new(DESKTOP_SCRIPT_DIR);
+    std::fs::create_dir_all(dir)?;
+    let script = dir.join(format!("{}.sh", unit));
+    std::fs::write(&script, desktop_script(summary))?;
+
+    let client = buckos_boss::ControlClient::with_default_path();
+    if !client.is_available() {
+        return Err(Error::Other("the init system is not running".to_string()));
+    }
+    let response = client
+        .start_transient(buckos_boss::TransientUnit {
+            name: Some(unit),
+            command: vec!["/bin/sh".to_string(), script.display().to_string()],
+            properties: vec![
+                "Description=buckos notification".to_string(),
+                format!("User={}", user),
+                format!(
+                    "Environment=DBUS_SESSION_BUS_ADDRESS=unix:path={}",
+                    bus.display()
+                ),
+            ],
+        })
+        .await
+        .map_err(|e| Error::Other(e.to_string()))?;
+    match response {
+        buckos_boss::ControlResponse::Error { message } => Err(Error::Other(message)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(feature = "init"))]
+async fn notify_desktop(_user: &str, _summary: &Summary) -> Result<()> {
+    Err(Error::Other(
+        "desktop notifications need the init feature".to_string(),
+    ))
+}
+
+/// Read an SMTP reply, failing unless its code is one of `expected`
+async fn smtp_reply<R: tokio::io::AsyncBufRead + Unpin>(
+    reader: &mut R,
+    expected: &[u16],
+) -> Result<()> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(Error::Other(
+                "SMTP server closed the connection".to_string(),
+            ));
+        }
+        // "250-..." continues a multi-line reply, "250 ..." ends it
+        if line.as_bytes().get(3) != Some(&b'-') {
+            break;
+        }
+    }
+    let code: u16 = line
+        .get(..3)
+        .and_then(|c| c.parse().ok())
+        .ok_or_else(|| Error::Other(format!("invalid SMTP reply: {}", line.trim())))?;
+    if !expected.contains(&code) {
+        return Err(Error::Other(format!("SMTP server: {}", line.trim())));
+    }
+    Ok(())
+}
+
+/// The message, with lines starting with a dot escaped
+fn mail_message(smtp: &SmtpConfig, summary: &Summary) -> String {
+    let mut message = format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n",
+        smtp.from,
+        smtp.to.join(", "),
+        summary.subject(),
+        summary.generated_at.to_rfc2822(),
+    );
+    for line in summary.text().lines() {
+        if line.starts_with('.') {
+            message.push('.');
+        }
+        message.push_str(line);
+        message.push_str("\r\n");
+    }
+    message
+}
+
+async fn send_mail(smtp: &SmtpConfig, summary: &Summary) -> Result<()> {
+    let stream = tokio::net::TcpStream::connect((smtp.server.as_str(), smtp.port)).await?;
+    let (read, mut write) = stream.into_split();
+    let mut reader = BufReader::new(read);
+
+    smtp_reply(&mut reader, &[220]).await?;
+    let mut commands = vec![
+        (format!("EHLO {}", summary.host), vec![250]),
+        (format!("MAIL FROM:<{}>", smtp.from), vec![250]),
+    ];
+    for to in &smtp.to {
+        commands.push((format!("RCPT TO:<{}>", to), vec![250, 251]));
+    }
+    commands.push(("DATA".to_string(), vec![354]));
+    for (command, expected) in commands {
+        write
+            .write_all(format!("{}\r\n", command).as_bytes())
+            .await?;
+        smtp_reply(&mut reader, &expected).await?;
+    }
+    write
+        .write_all(format!("{}.\r\n", mail_message(smtp, summary)).as_bytes())
+        .await?;
+    smtp_reply(&mut reader, &[250]).await?;
+    write.write_all(b"QUIT\r\n").await?;
+    Ok(())
+}
+
+/// Keys of the items already notified
+#[derive(Debug, Clone, Default)]
+pub struct Notified {
+    path: PathBuf,
+    keys: BTreeSet<String>,
+}
+
+impl Notified {
+    /// Read the keys; nothing was notified if the file is missing
+    pub fn load(path: &Path) -> Result<Self> {
+        let keys = match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeSet::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            keys,
+        })
+    }
+
+    /// Items not notified yet
+    pub fn fresh(&self, items: &[NotifyItem]) -> Vec<NotifyItem> {
+        items
+            .iter()
+            .filter(|i| !self.keys.contains(&i.key))
+            .cloned()
+            .collect()
+    }
+
+    /// Remember `current` as notified, forgetting items no longer current
+    /// so the file does not grow without bound
+    pub fn record(&mut self, current: &[NotifyItem]) -> Result<()> {
+        self.keys = current.iter().map(|i| i.key.clone()).collect();
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&self.keys)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Outcome of a dispatch
+#[derive(Debug, Clone, Default)]
+pub struct NotifyReport {
+    /// Items sent
+    pub sent: Vec<NotifyItem>,
+    /// Notifiers that failed, with the error
+    pub failed: Vec<(String, String)>,
+}
+
+/// Send the current items not notified yet to every notifier
+pub async fn dispatch(
+    config: &NotifyConfig,
+    state: &Path,
+    current: Vec<NotifyItem>,
+) -> Result<NotifyReport> {
+    let notifiers = config.notifiers();
+    let mut notified = Notified::load(state)?;
+    let fresh = notified.fresh(&current);
+    let mut report = NotifyReport::default();
+    if notifiers.is_empty() || fresh.is_empty() {
+        return Ok(report);
+    }
+
+    let summary = Summary::new(fresh);
+    for notifier in &notifiers {
+        if let Err(e) = notifier.send(&summary).await {
+            warn!("Failed to notify {}: {}", notifier, e);
+            report.failed.push((notifier.to_string(), e.to_string()));
+        }
+    }
+    if report.failed.len() < notifiers.len() {
+        notified.record(&current)?;
+        report.sent = summary.items;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PackageId;
+
+    fn vuln(id: &str, severity: &str) -> Vulnerability {
+        Vulnerability {
+            id: id.to_string(),
+            title: "Buffer overflow".to_string(),
+            severity: severity.to_string(),
+            package: PackageId::new("dev-libs", "openssl"),
+            affected_versions: "<3.1.4".to_string(),
+            fixed_version: Some("3.1.4".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_dedups() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = dir.path().join("notified.json");
+        let out = dir.path().join("out");
+        let config = NotifyConfig {
+            commands: vec![format!("cat >> {}", out.display())],
+            ..Default::default()
+        };
+
+        let first = vec![NotifyItem::advisory(&vuln("CVE-2023-0001", "high"))];
+        let report = dispatch(&config, &state, first.clone()).await.unwrap();
+        assert_eq!(report.sent.len(), 1);
+        let sent: Summary = serde_json::from_str(&std::fs::read_to_string(&out).unwrap()).unwrap();
+        assert_eq!(sent.items, first);
+        assert!(sent.urgent());
+
+        // The same advisory is not sent again; a new one is, alone
+        let report = dispatch(&config, &state, first.clone()).await.unwrap();
+        assert!(report.sent.is_empty());
+        let mut second = first.clone();
+        second.push(NotifyItem::advisory(&vuln("CVE-2023-0002", "low")));
+        let report = dispatch(&config, &state, second).await.unwrap();
+        assert_eq!(report.sent.len(), 1);
+        assert_eq!(
+            report.sent[0].key,
+            "advisory:CVE-2023-0002:dev-libs/openssl"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_dispatch_is_retried() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = dir.path().join("notified.json");
+        let items = vec![NotifyItem::advisory(&vuln("CVE-2023-0001", "high"))];
+
+        let failing = NotifyConfig {
+            commands: vec!["exit 1".to_string()],
+            ..Default::default()
+        };
+        let report = dispatch(&failing, &state, items.clone()).await.unwrap();
+        assert!(report.sent.is_empty());
+        assert_eq!(report.failed.len(), 1);
+
+        let working = NotifyConfig {
+            commands: vec!["cat > /dev/null".to_string()],
+            ..Default::default()
+        };
+        let report = dispatch(&working, &state, items).await.unwrap();
+        assert_eq!(report.sent.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_send_mail() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (read, mut write) = stream.into_split();
+            let mut lines = BufReader::new(read).lines();
+            write.write_all(b"220 mail ESMTP\r\n").await.unwrap();
+            let mut received = Vec::new();
+            let mut in_data = false;
+            while let Some(line) = lines.next_line().await.unwrap() {
+                received.push(line.clone());
+                let reply: &[u8] = if in_data {
+                    if line != "." {
+                        continue;
+                    }
+                    in_data = false;
+                    b"250 queued\r\n"
+                } else if line.starts_with("EHLO") {
+                    b"250-mail\r\n250 8BITMIME\r\n"
+                } else if line == "DATA" {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                } else if line == "QUIT" {
+                    break;
+                } else {
+                    b"250 ok\r\n"
+                };
+                write.write_all(reply).await.unwrap();
+            }
+            received
+        });
+
+        let smtp = SmtpConfig {
+            server: "127.0.0.1".to_string(),
+            port,
+            from: "buckos@host".to_string(),
+            to: vec!["root@example.com".to_string()],
+        };
+        let item = NotifyItem::advisory(&vuln("CVE-2023-0001", "high"));
+        send_mail(&smtp, &Summary::new(vec![item])).await.unwrap();
+
+        let received = server.await.unwrap();
+        assert!(received.contains(&"MAIL FROM:<buckos@host>".to_string()));
+        assert!(received.contains(&"RCPT TO:<root@example.com>".to_string()));
+        assert!(received
+            .iter()
+            .any(|l| l.starts_with("Subject: buckos on ")));
+        assert!(received.contains(
+            &"[high] CVE-2023-0001: Buffer overflow (dev-libs/openssl), fixed in 3.1.4".to_string()
+        ));
+        assert_eq!(received.last().map(String::as_str), Some("QUIT"));
+    }
+
+    #[test]
+    #[cfg(feature = "init")]
+    fn test_desktop_script_quoting() {
+        let mut item = NotifyItem::news(&NewsItem {
+            name: "2024-01-01-it's-here".to_string(),
+            title: "It's here".to_string(),
+            author: String::new(),
+            email: None,
+            content_type: "text/plain".to_string(),
+            posted: chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            revision: None,
+            display_if: Vec::new(),
+            content: String::new(),
+        });
+        item.detail = None;
+        let script = desktop_script(&Summary::new(vec![item]));
+        assert!(script.contains("--urgency=normal"));
+        assert!(script.contains(r"'[news] It'\''s here"));
+    }
+}
@@ -0,0 +1,402 @@
+//! User patches applied during the prepare phase
+//!
+//! Patches live under `/etc/buckos/patches` in the first directory that
+//! matches the package being built, from most to least specific:
+//! `category/name-version`, `category/name:slot`, `category/name`, then
+//! a bare `name`. A `series` file fixes the order (and optionally the
+//! `-pN` level) like quilt; otherwise `*.patch` and `*.diff` files are
+//! applied in name order. Applied patches are recorded by SHA-256 in the
+//! package's provenance.
+
+use crate::{Error, PackageId, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Directory searched for user patches
+pub const USER_PATCH_DIR: &str = "/etc/buckos/patches";
+
+/// Highest `-p` level tried when detecting the strip level
+const MAX_STRIP: u32 = 4;
+
+/// A user patch found for a package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserPatch {
+    /// File name within the patch directory
+    pub name: String,
+    pub path: PathBuf,
+    /// Strip level from the series file, detected at apply time if unset
+    pub strip: Option<u32>,
+}
+
+/// A patch that was applied to a package's sources
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppliedPatch {
+    pub name: String,
+    pub sha256: String,
+    pub strip: Option<u32>,
+}
+
+/// Ordered user patches for one package
+#[derive(Debug, Clone, Default)]
+pub struct PatchSet {
+    /// Directory the patches came from
+    pub dir: Option<PathBuf>,
+    pub patches: Vec<UserPatch>,
+}
+
+impl PatchSet {
+    /// Find the user patches for a package under `base`
+    pub fn discover(base: &Path, id: &PackageId, version: &str, slot: &str) -> Result<Self> {
+        let candidates = [
+            base.join(&id.category)
+                .join(format!("{}-{}", id.name, version)),
+            base.join(&id.category)
+                .join(format!("{}:{}", id.name, slot)),
+            base.join(&id.category).join(&id.name),
+            base.join(&id.name),
+        ];
+        match candidates.into_iter().find(|dir| dir.is_dir()) {
+            Some(dir) => Self::from_dir(&id.full_name(), &dir),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Read the patches in a directory, honouring its series file
+    pub fn from_dir(package: &str, dir: &Path) -> Result<Self> {
+        let series = dir.join("series");
+        let mut patches = Vec::new();
+
+        if series.exists() {
+            let content = std::fs::read_to_string(&series)?;
+            for (line_num, line) in content.lines().enumerate() {
+                let line = line.split('#').next().unwrap_or_default().trim();
+                let mut fields = line.split_whitespace();
+                let Some(name) = fields.next() else {
+                    continue;
+                };
+                let strip = match fields.next() {
+                    Some(opt) => Some(
+                        opt.strip_prefix("-p")
+                            .and_then(|n| n.parse().ok())
+                            .ok_or_else(|| Error::PatchError {
+                                package: package.to_string(),
+                                reason: format!(
+                                    "{}:{}: invalid option '{}'",
+                                    series.display(),
+                                    line_num + 1,
+                                    opt
+                                ),
+                            })?,
+                    ),
+                    None => None,
+                };
+                let path = dir.join(name);
+                if !path.is_file() {
+                    return Err(Error::PatchError {
+                        package: package.to_string(),
+                        reason: format!("{} lists missing patch {}", series.display(), name),
+                    });
+                }
+                patches.push(UserPatch {
+                    name: name.to_string(),
+                    path,
+                    strip,
+                });
+            }
+        } else {
+            let mut names: Vec<String> = std::fs::read_dir(dir)?
+                .flatten()
+                .filter(|e| {
+                    e.path()
+                        .extension()
+                        .is_some_and(|ext| ext == "patch" || ext == "diff")
+                })
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            patches = names
+                .into_iter()
+                .map(|name| UserPatch {
+                    path: dir.join(&name),
+                    name,
+                    strip: None,
+                })
+                .collect();
+        }
+
+        Ok(Self {
+            dir: Some(dir.to_path_buf()),
+            patches,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patches.is_empty()
+    }
+
+    /// Hash of every patch's name and contents, in order
+    ///
+    /// Passed to the build so that changing a patch invalidates cached
+    /// build results.
+    pub fn digest(&self) -> Result<String> {
+        let mut hasher = Sha256::new();
+        for patch in &self.patches {
+            hasher.update(patch.name.as_bytes());
+            hasher.update([0]);
+            hasher.update(std::fs::read(&patch.path)?);
+        }
+        Ok(hex::encode(hasher.finalize()))
+    }
+
+    /// Provenance records for these patches
+    pub fn records(&self) -> Result<Vec<AppliedPatch>> {
+        self.patches
+            .iter()
+            .map(|patch| {
+                Ok(AppliedPatch {
+                    name: patch.name.clone(),
+                    sha256: hex::encode(Sha256::digest(std::fs::read(&patch.path)?)),
+                    strip: patch.strip,
+                })
+            })
+            .collect()
+    }
+
+    /// Apply every patch to a source tree, stopping at the first failure
+    pub fn apply(&self, package: &str, srcdir: &Path) -> Result<Vec<AppliedPatch>> {
+        let mut applied = Vec::new();
+        for (patch, mut record) in self.patches.iter().zip(self.records()?) {
+            let strip = match patch.strip {
+                Some(strip) => strip,
+                None => detect_strip(&patch.path, srcdir)?.ok_or_else(|| Error::PatchError {
+                    package: package.to_string(),
+                    reason: format!(
+                        "{}: could not determine -p level (tried -p0 to -p{}); \
+                         add it to the series file, e.g. '{} -p1'",
+                        patch.name, MAX_STRIP, patch.name
+                    ),
+                })?,
+            };
+
+            let output = run_patch(&patch.path, srcdir, strip, false)?;
+            if !output.status.success() {
+                return Err(Error::PatchError {
+                    package: package.to_string(),
+                    reason: failure_diagnostics(patch, strip, srcdir, &output),
+                });
+            }
+            record.strip = Some(strip);
+            applied.push(record);
+        }
+        Ok(applied)
+    }
+}
+
+/// Pick the strip level for a patch against a source tree
+///
+/// The level at which the files a patch modifies exist is preferred; if
+/// the headers are inconclusive each level is dry-run in turn.
+pub fn detect_strip(patch: &Path, srcdir: &Path) -> Result<Option<u32>> {
+    let content = std::fs::read_to_string(patch)?;
+    let targets = patch_targets(&content);
+    if !targets.is_empty() {
+        for strip in 0..=MAX_STRIP {
+            let all_exist = targets.iter().all(|target| {
+                strip_components(target, strip).is_some_and(|rel| srcdir.join(rel).exists())
+            });
+            if all_exist {
+                return Ok(Some(strip));
+            }
+        }
+    }
+
+    for strip in [1, 0, 2, 3, 4] {
+        if run_patch(patch, srcdir, strip, true)?.status.success() {
+            return Ok(Some(strip));
+        }
+    }
+    Ok(None)
+}
+
+/// Existing files a unified diff modifies (new files are skipped)
+fn patch_targets(content: &str) -> Vec<String> {
+    let mut targets = Vec::new();
+    let mut lines = content.lines().peekable();
+    while let Some(line) = lines.next() {
+        let Some(old) = line.strip_prefix("--- ") else {
+            continue;
+        };
+        if !lines.peek().is_some_and(|next| next.starts_with("+++ ")) {
+            continue;
+        }
+        let old = old.split('\t').next().unwrap_or(old).trim();
+        if old != "/dev/null" && !targets.iter().any(|t| t == old) {
+            targets.push(old.to_string());
+        }
+    }
+    targets
+}
+
+fn strip_components(path: &str, strip: u32) -> Option<&str> {
+    let mut rest = path;
+    for _ in 0..strip {
+        rest = rest.split_once('/')?.1.trim_start_matches('/');
+    }
+    (!rest.is_empty()).then_some(rest)
+}
+
+fn run_patch(
+    patch: &Path,
+    srcdir: &Path,
+    strip: u32,
+    dry_run: bool,
+) -> Result<std::process::Output> {
+    let mut cmd = std::process::Command::new("patch");
+    cmd.arg(format!("-p{}", strip))
+        .args(["--forward", "--batch", "--no-backup-if-mismatch"])
+        .arg("-d")
+        .arg(srcdir)
+        .arg("-i")
+        .arg(patch);
+    if dry_run {
+        cmd.arg("--dry-run");
+    }
+    cmd.output()
+        .map_err(|e| Error::Other(format!("Failed to run patch: {}", e)))
+}
+
+/// Explain a failed patch: failing hunks and where the rejects went
+fn failure_diagnostics(
+    patch: &UserPatch,
+    strip: u32,
+    srcdir: &Path,
+    output: &std::process::Output,
+) -> String {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let mut details: Vec<&str> = stdout
+        .lines()
+        .chain(stderr.lines())
+        .filter(|l| {
+            l.contains("FAILED")
+                || l.contains("can't find file")
+                || l.contains("Reversed")
+                || l.contains("saving rejects")
+                || l.contains("malformed")
+        })
+        .collect();
+    if details.is_empty() {
+        details = stdout.lines().chain(stderr.lines()).take(5).collect();
+    }
+    format!(
+        "{} failed to apply with -p{} in {}:\n  {}",
+        patch.name,
+        strip,
+        srcdir.display(),
+        details.join("\n  ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATCH: &str = "\
+--- a/src/main.c\t2024-01-01
++++ b/src/main.c\t2024-01-02
+@@ -1 +1 @@
+-old
++new
+--- /dev/null
++++ b/NEWS
+@@ -0,0 +1 @@
++added
+";
+
+    #[test]
+    fn test_discover_precedence_and_series() {
+        let base = tempfile::tempdir().unwrap();
+        let id = PackageId::new("app-misc", "foo");
+        let generic = base.path().join("app-misc/foo");
+        let versioned = base.path().join("app-misc/foo-1.2.0");
+        std::fs::create_dir_all(&generic).unwrap();
+        std::fs::write(generic.join("b.patch"), PATCH).unwrap();
+        std::fs::write(generic.join("a.diff"), PATCH).unwrap();
+        std::fs::write(generic.join("README"), "notes").unwrap();
+
+        let set = PatchSet::discover(base.path(), &id, "1.2.0", "0").unwrap();
+        let names: Vec<&str> = set.patches.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["a.diff", "b.patch"]);
+
+        std::fs::create_dir_all(&versioned).unwrap();
+        std::fs::write(versioned.join("fix.patch"), PATCH).unwrap();
+        std::fs::write(versioned.join("other.patch"), PATCH).unwrap();
+        std::fs::write(
+            versioned.join("series"),
+            "# applied in this order\nother.patch -p0\nfix.patch\n",
+        )
+        .unwrap();
+        let set = PatchSet::discover(base.path(), &id, "1.2.0", "0").unwrap();
+        assert_eq!(set.dir.as_deref(), Some(versioned.as_path()));
+        assert_eq!(
+            set.patches
+                .iter()
+                .map(|p| (p.name.as_str(), p.strip))
+                .collect::<Vec<_>>(),
+            vec![("other.patch", Some(0)), ("fix.patch", None)]
+        );
+        assert_eq!(set.records().unwrap()[0].sha256.len(), 64);
+
+        std::fs::write(versioned.join("series"), "gone.patch\n").unwrap();
+        assert!(PatchSet::discover(base.path(), &id, "1.2.0", "0").is_err());
+
+        // Other versions fall back to the generic directory
+        let set = PatchSet::discover(base.path(), &id, "1.3.0", "0").unwrap();
+        assert_eq!(set.dir.as_deref(), Some(generic.as_path()));
+    }
+
+    #[test]
+    fn test_detect_strip_from_headers() {
+        assert_eq!(patch_targets(PATCH), vec!["a/src/main.c".to_string()]);
+        assert_eq!(strip_components("a/src/main.c", 1), Some("src/main.c"));
+        assert_eq!(strip_components("main.c", 1), None);
+
+        let src = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(src.path().join("src")).unwrap();
+        std::fs::write(src.path().join("src/main.c"), "old\n").unwrap();
+        let patch = src.path().join("fix.patch");
+        std::fs::write(&patch, PATCH).unwrap();
+
+        assert_eq!(detect_strip(&patch, src.path()).unwrap(), Some(1));
+    }
+
+    #[test]
+    fn test_apply_and_failure_diagnostics() {
+        if which::which("patch").is_err() {
+            return;
+        }
+        let src = tempfile::tempdir().unwrap();
+        let patches = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(src.path().join("src")).unwrap();
+        std::fs::write(src.path().join("src/main.c"), "old\n").unwrap();
+        std::fs::write(patches.path().join("01-fix.patch"), PATCH).unwrap();
+
+        let set = PatchSet::from_dir("app-misc/foo", patches.path()).unwrap();
+        let applied = set.apply("app-misc/foo", src.path()).unwrap();
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].strip, Some(1));
+        assert_eq!(
+            std::fs::read_to_string(src.path().join("src/main.c")).unwrap(),
+            "new\n"
+        );
+        assert!(src.path().join("NEWS").exists());
+
+        // Applying again fails: the hunk no longer matches
+        std::fs::remove_file(src.path().join("NEWS")).unwrap();
+        let err = set.apply("app-misc/foo", src.path()).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("01-fix.patch failed to apply with -p1"));
+    }
+}
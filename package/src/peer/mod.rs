@@ -0,0 +1,577 @@
+//! Peer-to-peer distfile sharing
+//!
+//! Machines running `buckos serve --share` advertise their mirror over
+//! mDNS as `_buckos-share._tcp.local`. Before going to the internet the
+//! downloader browses for peers, asks each one whether it holds the distfile
+//! with the expected digest:
+//!
+//! ```text
+//! GET /_has/distfiles/<filename>?sha256=<hex>     200 = yes, 404 = no
+//! ```
+//!
+//! and if so fetches `/distfiles/<filename>` over the LAN. The file is
+//! verified exactly like one downloaded from upstream, so a misbehaving peer
+//! can only waste time, never inject content.
+
+use crate::{Error, Result};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::net::UdpSocket;
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+/// DNS-SD service type advertised by sharing machines
+pub const SERVICE: &str = "_buckos-share._tcp.local";
+
+/// mDNS multicast group and port
+const MDNS_ADDR: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_PORT: u16 = 5353;
+
+/// How long to wait for peers to answer a browse query
+const DISCOVERY_TIMEOUT: Duration = Duration::from_millis(750);
+
+/// How long a browse result is reused before browsing again
+const PEER_TTL: Duration = Duration::from_secs(300);
+
+/// TTL of advertised records, in seconds
+const RECORD_TTL: u32 = 120;
+
+const TYPE_PTR: u16 = 12;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+const CACHE_FLUSH: u16 = 0x8000;
+
+/// Digest a distfile must match
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Digest {
+    /// SHA-256 (package `source_hash`)
+    Sha256(String),
+    /// SHA-512 (Manifest `SHA512`)
+    Sha512(String),
+    /// Manifest `BLAKE2B`, as computed by [`crate::distfile::DistfileManager`]
+    Blake2b(String),
+}
+
+impl Digest {
+    /// Query parameter name and value
+    fn param(&self) -> (&'static str, &str) {
+        match self {
+            Digest::Sha256(hex) => ("sha256", hex),
+            Digest::Sha512(hex) => ("sha512", hex),
+            Digest::Blake2b(hex) => ("blake2b", hex),
+        }
+    }
+
+    /// Parse the first digest parameter of a query string
+    pub fn from_query(query: &str) -> Option<Self> {
+        query.split('&').find_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            let value = value.to_ascii_lowercase();
+            if value.is_empty() || !value.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+            match key {
+                "sha256" => Some(Digest::Sha256(value)),
+                "sha512" => Some(Digest::Sha512(value)),
+                "blake2b" => Some(Digest::Blake2b(value)),
+                _ => None,
+            }
+        })
+    }
+
+    /// Whether a file has this digest
+    pub fn matches(&self, path: &Path) -> Result<bool> {
+        let actual = match self {
+            Digest::Sha256(_) => crate::cache::compute_sha256(path)?,
+            Digest::Sha512(_) => {
+                use sha2::{Digest as _, Sha512};
+                let mut hasher = Sha512::new();
+                let mut file = std::fs::File::open(path)?;
+                std::io::copy(&mut file, &mut hasher)?;
+                hex::encode(hasher.finalize())
+            }
+            // DistfileManager stores BLAKE3 digests under the BLAKE2B key
+            Digest::Blake2b(_) => crate::cache::compute_blake3(path)?,
+        };
+        Ok(actual.eq_ignore_ascii_case(self.param().1))
+    }
+}
+
+/// A machine sharing its distfiles
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Peer {
+    /// Advertised instance name (usually the hostname)
+    pub name: String,
+    /// Address of its mirror server
+    pub addr: SocketAddr,
+}
+
+impl Peer {
+    fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+}
+
+/// Browse the local network for sharing peers
+pub async fn discover(timeout: Duration) -> Result<Vec<Peer>> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    // Sent from an ephemeral port, so responders answer us directly
+    socket
+        .send_to(&build_query(), (MDNS_ADDR, MDNS_PORT))
+        .await?;
+
+    let mut peers: Vec<Peer> = Vec::new();
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut buf = [0u8; 9000];
+    loop {
+        let received = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await;
+        let Ok(received) = received else {
+            break;
+        };
+        let (len, from) = received?;
+        if let Some((name, port)) = parse_response(&buf[..len]) {
+            let peer = Peer {
+                name,
+                addr: SocketAddr::new(from.ip(), port),
+            };
+            if !peers.contains(&peer) {
+                debug!("Discovered peer {} at {}", peer.name, peer.addr);
+                peers.push(peer);
+            }
+        }
+    }
+    Ok(peers)
+}
+
+/// Answers browse queries for a local mirror server
+pub struct Advertiser {
+    instance: String,
+    host: String,
+    port: u16,
+}
+
+impl Advertiser {
+    /// Advertise a mirror listening on `port`, named after this host
+    pub fn new(port: u16) -> Self {
+        let host = hostname();
+        Self {
+            instance: host.clone(),
+            host,
+            port,
+        }
+    }
+
+    /// Answer queries until the task is cancelled
+    pub async fn run(self) -> Result<()> {
+        let socket = bind_mdns()?;
+        info!(
+            "Advertising {}.{} on port {}",
+            self.instance, SERVICE, self.port
+        );
+
+        // Unsolicited announcement so browsers with cached results notice us
+        let announcement = build_response(None, &self.instance, &self.host, self.port);
+        socket
+            .send_to(&announcement, (MDNS_ADDR, MDNS_PORT))
+            .await?;
+
+        let mut buf = [0u8; 9000];
+        loop {
+            let (len, from) = socket.recv_from(&mut buf).await?;
+            let Some(id) = query_for_service(&buf[..len]) else {
+                continue;
+            };
+
+            if from.port() == MDNS_PORT {
+                let response = build_response(None, &self.instance, &self.host, self.port);
+                socket.send_to(&response, (MDNS_ADDR, MDNS_PORT)).await?;
+            } else {
+                // Legacy unicast query: answer the sender directly
+                let response = build_response(Some(id), &self.instance, &self.host, self.port);
+                socket.send_to(&response, from).await?;
+            }
+        }
+    }
+}
+
+/// Bind the shared mDNS port, coexisting with other responders (e.g. avahi)
+fn bind_mdns() -> Result<UdpSocket> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, MDNS_PORT).into())?;
+    socket.join_multicast_v4(&MDNS_ADDR, &Ipv4Addr::UNSPECIFIED)?;
+    Ok(UdpSocket::from_std(socket.into())?)
+}
+
+fn hostname() -> String {
+    let name = std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .or_else(|_| std::fs::read_to_string("/etc/hostname"))
+        .unwrap_or_default();
+    let name = name.trim().split('.').next().unwrap_or_default();
+    if name.is_empty() {
+        "buckos".to_string()
+    } else {
+        name.chars().take(63).collect()
+    }
+}
+
+/// Fetches distfiles from discovered peers
+pub struct PeerCache {
+    client: reqwest::Client,
+    peers: Mutex<Option<(Instant, Vec<Peer>)>>,
+}
+
+impl PeerCache {
+    /// Create a peer cache; peers are discovered on first use
+    pub fn new() -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(2))
+            .build()
+            .map_err(|e| Error::NetworkError(e.to_string()))?;
+        Ok(Self {
+            client,
+            peers: Mutex::new(None),
+        })
+    }
+
+    /// Currently known peers, browsing again once the last result is stale
+    pub async fn peers(&self) -> Vec<Peer> {
+        let mut cached = self.peers.lock().await;
+        if let Some((at, peers)) = cached.as_ref() {
+            if at.elapsed() < PEER_TTL {
+                return peers.clone();
+            }
+        }
+
+        let peers = discover(DISCOVERY_TIMEOUT).await.unwrap_or_else(|e| {
+            debug!("Peer discovery failed: {}", e);
+            Vec::new()
+        });
+        *cached = Some((Instant::now(), peers.clone()));
+        peers
+    }
+
+    /// Fetch a distfile with the given digest from the first peer that has it
+    ///
+    /// Returns the name of the peer used, or `None` (leaving `dest` absent)
+    /// if no peer could supply a matching file.
+    pub async fn fetch(&self, filename: &str, digest: &Digest, dest: &Path) -> Option<String> {
+        for peer in self.peers().await {
+            match self.fetch_from(&peer, filename, digest, dest).await {
+                Ok(true) => {
+                    info!("Fetched {} from peer {}", filename, peer.name);
+                    return Some(peer.name);
+                }
+                Ok(false) => {}
+                Err(e) => debug!("Peer {} failed for {}: {}", peer.name, filename, e),
+            }
+            let _ = std::fs::remove_file(dest);
+        }
+        None
+    }
+
+    async fn fetch_from(
+        &self,
+        peer: &Peer,
+        filename: &str,
+        digest: &Digest,
+        dest: &Path,
+    ) -> Result<bool> {
+        let encoded = encode_segment(filename);
+        let (key, value) = digest.param();
+        let probe = peer.url(&format!("/_has/distfiles/{}?{}={}", encoded, key, value));
+        let response = self
+            .client
+            .get(&probe)
+            .send()
+            .await
+            .map_err(|e| Error::NetworkError(e.to_string()))?;
+        if !response.status().is_success() {
+            return Ok(false);
+        }
+
+        let url = peer.url(&format!("/distfiles/{}", encoded));
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| Error::NetworkError(e.to_string()))?;
+        if !response.status().is_success() {
+            return Ok(false);
+        }
+
+        let mut file = tokio::fs::File::create(dest).await?;
+        let mut stream = response.bytes_stream();
+        use futures::StreamExt;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| Error::NetworkError(e.to_string()))?;
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+
+        if digest.matches(dest)? {
+            Ok(true)
+        } else {
+            warn!("Peer {} served a corrupt {}", peer.name, filename);
+            Ok(false)
+        }
+    }
+}
+
+/// Percent-encode a single path segment
+fn encode_segment(segment: &str) -> String {
+    let mut out = String::with_capacity(segment.len());
+    for b in segment.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~' | b'+') {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+// ---------------------------------------------------------------------------
+// DNS wire format (just the records DNS-SD browsing needs)
+// ---------------------------------------------------------------------------
+
+fn push_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+fn push_name(buf: &mut Vec<u8>, name: &str) {
+    for label in name.split('.').filter(|l| !l.is_empty()) {
+        let label = &label.as_bytes()[..label.len().min(63)];
+        buf.push(label.len() as u8);
+        buf.extend_from_slice(label);
+    }
+    buf.push(0);
+}
+
+fn push_header(buf: &mut Vec<u8>, id: u16, flags: u16, questions: u16, answers: u16) {
+    push_u16(buf, id);
+    push_u16(buf, flags);
+    push_u16(buf, questions);
+    push_u16(buf, answers);
+    push_u16(buf, 0);
+    push_u16(buf, 0);
+}
+
+/// PTR query for [`SERVICE`]
+fn build_query() -> Vec<u8> {
+    let mut buf = Vec::with_capacity(64);
+    push_header(&mut buf, 0, 0, 1, 0);
+    push_name(&mut buf, SERVICE);
+    push_u16(&mut buf, TYPE_PTR);
+    push_u16(&mut buf, CLASS_IN);
+    buf
+}
+
+/// PTR and SRV records for an instance
+///
+/// `query_id` is set when answering a legacy unicast query, which expects
+/// its ID and question echoed back; multicast responses carry neither.
+fn build_response(query_id: Option<u16>, instance: &str, host: &str, port: u16) -> Vec<u8> {
+    let full_name = format!("{}.{}", instance, SERVICE);
+    let mut buf = Vec::with_capacity(256);
+    push_header(
+        &mut buf,
+        query_id.unwrap_or(0),
+        0x8400,
+        query_id.is_some() as u16,
+        2,
+    );
+    if query_id.is_some() {
+        push_name(&mut buf, SERVICE);
+        push_u16(&mut buf, TYPE_PTR);
+        push_u16(&mut buf, CLASS_IN);
+    }
+
+    let mut rdata = Vec::new();
+    push_name(&mut rdata, &full_name);
+    push_name(&mut buf, SERVICE);
+    push_u16(&mut buf, TYPE_PTR);
+    push_u16(&mut buf, CLASS_IN);
+    buf.extend_from_slice(&RECORD_TTL.to_be_bytes());
+    push_u16(&mut buf, rdata.len() as u16);
+    buf.extend_from_slice(&rdata);
+
+    let mut rdata = Vec::new();
+    push_u16(&mut rdata, 0);
+    push_u16(&mut rdata, 0);
+    push_u16(&mut rdata, port);
+    push_name(&mut rdata, &format!("{}.local", host));
+    push_name(&mut buf, &full_name);
+    push_u16(&mut buf, TYPE_SRV);
+    push_u16(&mut buf, CLASS_IN | CACHE_FLUSH);
+    buf.extend_from_slice(&RECORD_TTL.to_be_bytes());
+    push_u16(&mut buf, rdata.len() as u16);
+    buf.extend_from_slice(&rdata);
+
+    buf
+}
+
+fn read_u16(packet: &[u8], offset: usize) -> Option<u16> {
+    let bytes = packet.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+/// Read a possibly compressed name, returning it and the offset after it
+fn read_name(packet: &[u8], mut offset: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    let mut jumps = 0;
+    loop {
+        let len = *packet.get(offset)? as usize;
+        if len == 0 {
+            offset += 1;
+            break;
+        }
+        if len & 0xC0 == 0xC0 {
+            let pointer = (read_u16(packet, offset)? & 0x3FFF) as usize;
+            end.get_or_insert(offset + 2);
+            jumps += 1;
+            if jumps > 16 {
+                return None;
+            }
+            offset = pointer;
+            continue;
+        }
+        let label = packet.get(offset + 1..offset + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).to_string());
+        offset += 1 + len;
+    }
+    Some((labels.join("."), end.unwrap_or(offset)))
+}
+
+/// ID of a query asking for [`SERVICE`], if this packet is one
+fn query_for_service(packet: &[u8]) -> Option<u16> {
+    let id = read_u16(packet, 0)?;
+    if read_u16(packet, 2)? & 0x8000 != 0 {
+        return None;
+    }
+    let questions = read_u16(packet, 4)?;
+    let mut offset = 12;
+    for _ in 0..questions {
+        let (name, next) = read_name(packet, offset)?;
+        let qtype = read_u16(packet, next)?;
+        offset = next + 4;
+        if name.eq_ignore_ascii_case(SERVICE) && matches!(qtype, TYPE_PTR | TYPE_ANY) {
+            return Some(id);
+        }
+    }
+    None
+}
+
+/// Instance name and port from the SRV record of a response
+fn parse_response(packet: &[u8]) -> Option<(String, u16)> {
+    if read_u16(packet, 2)? & 0x8000 == 0 {
+        return None;
+    }
+    let questions = read_u16(packet, 4)?;
+    let records = read_u16(packet, 6)? as usize
+        + read_u16(packet, 8)? as usize
+        + read_u16(packet, 10)? as usize;
+
+    let mut offset = 12;
+    for _ in 0..questions {
+        let (_, next) = read_name(packet, offset)?;
+        offset = next + 4;
+    }
+
+    let suffix = format!(".{}", SERVICE);
+    for _ in 0..records {
+        let (name, next) = read_name(packet, offset)?;
+        let rtype = read_u16(packet, next)?;
+        let rdlen = read_u16(packet, next + 8)? as usize;
+        let rdata = next + 10;
+        offset = rdata + rdlen;
+        if offset > packet.len() {
+            return None;
+        }
+
+        if rtype == TYPE_SRV && name.to_ascii_lowercase().ends_with(&suffix) {
+            let port = read_u16(packet, rdata + 4)?;
+            let instance = name[..name.len() - suffix.len()].to_string();
+            return Some((instance, port));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_roundtrip() {
+        let query = build_query();
+        assert_eq!(query_for_service(&query), Some(0));
+        // Responses are not queries
+        let response = build_response(None, "builder", "builder", 8080);
+        assert_eq!(query_for_service(&response), None);
+    }
+
+    #[test]
+    fn test_response_roundtrip() {
+        let multicast = build_response(None, "builder", "builder", 8080);
+        assert_eq!(
+            parse_response(&multicast),
+            Some(("builder".to_string(), 8080))
+        );
+
+        let unicast = build_response(Some(0x1234), "builder", "builder", 9000);
+        assert_eq!(read_u16(&unicast, 0), Some(0x1234));
+        assert_eq!(read_u16(&unicast, 4), Some(1));
+        assert_eq!(
+            parse_response(&unicast),
+            Some(("builder".to_string(), 9000))
+        );
+
+        assert_eq!(parse_response(&build_query()), None);
+        assert_eq!(parse_response(&multicast[..20]), None);
+    }
+
+    #[test]
+    fn test_read_compressed_name() {
+        // "local" at offset 0, then "a.local" as a label plus pointer
+        let packet = [5, b'l', b'o', b'c', b'a', b'l', 0, 1, b'a', 0xC0, 0x00];
+        assert_eq!(read_name(&packet, 7), Some(("a.local".to_string(), 11)));
+
+        // Pointer loops are rejected
+        let looping = [0xC0, 0x00];
+        assert_eq!(read_name(&looping, 0), None);
+    }
+
+    #[test]
+    fn test_digest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("foo-1.0.tar.gz");
+        std::fs::write(&path, b"hello").unwrap();
+
+        let sha256 = crate::cache::compute_sha256(&path).unwrap();
+        let query = format!("token=x&sha256={}", sha256.to_uppercase());
+        let digest = Digest::from_query(&query).unwrap();
+        assert_eq!(digest, Digest::Sha256(sha256.clone()));
+        assert!(digest.matches(&path).unwrap());
+
+        assert!(!Digest::Sha512("00".to_string()).matches(&path).unwrap());
+        assert_eq!(Digest::from_query("sha256=not-hex"), None);
+        assert_eq!(Digest::from_query("md5=abcd"), None);
+    }
+
+    #[test]
+    fn test_encode_segment() {
+        assert_eq!(encode_segment("foo-1.0.tar.gz"), "foo-1.0.tar.gz");
+        assert_eq!(encode_segment("a b?c"), "a%20b%3Fc");
+    }
+}
@@ -0,0 +1,344 @@
+//! Periodic maintenance run by the init system
+//!
+//! `buckos gen-units` writes boss service definitions whose timers sync the
+//! repositories, audit installed packages and check for updates, and detect
+//! the hardware again at boot. Each run
+//! (`buckos periodic <task>`) logs its result to the journal through its
+//! standard output and records it in a status file under `/run`, which
+//! `buckos status` and the login message of the day read.
+
+use crate::{PackageManager, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Results of the last periodic runs
+pub const STATUS_FILE: &str = "/run/buckos/status.json";
+
+/// Login message listing what needs attention, read by pam_motd
+pub const MOTD_FILE: &str = "/run/motd.d/buckos";
+
+/// A periodic maintenance task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PeriodicTask {
+    /// Sync the package repositories
+    Sync,
+    /// Check installed packages for known vulnerabilities
+    Audit,
+    /// Check for package updates
+    Updates,
+    /// Detect the hardware and refresh the USE flags recommended for it
+    Hardware,
+}
+
+impl PeriodicTask {
+    pub const ALL: [PeriodicTask; 4] = [
+        PeriodicTask::Sync,
+        PeriodicTask::Audit,
+        PeriodicTask::Updates,
+        PeriodicTask::Hardware,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PeriodicTask::Sync => "sync",
+            PeriodicTask::Audit => "audit",
+            PeriodicTask::Updates => "updates",
+            PeriodicTask::Hardware => "hardware",
+        }
+    }
+
+    /// Name of the service running the task
+    pub fn unit_name(&self) -> String {
+        format!("buckos-{}", self.name())
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            PeriodicTask::Sync => "Sync buckos package repositories",
+            PeriodicTask::Audit => "Check installed packages for known vulnerabilities",
+            PeriodicTask::Updates => "Check for buckos package updates",
+            PeriodicTask::Hardware => "Detect hardware for buckos USE flag defaults",
+        }
+    }
+
+    /// When the task runs unless told otherwise; the checks follow the
+    /// nightly sync. Hardware only changes across reboots, so it is
+    /// detected at boot instead.
+    pub fn default_calendar(&self) -> Option<&'static str> {
+        match self {
+            PeriodicTask::Sync => Some("*-*-* 04:00:00"),
+            PeriodicTask::Audit | PeriodicTask::Updates => Some("*-*-* 05:00:00"),
+            PeriodicTask::Hardware => None,
+        }
+    }
+
+    /// Service definition running `buckos periodic <task>` from `buckos`
+    /// on `calendar`, or shortly after boot without one
+    pub fn unit(&self, buckos: &Path, calendar: Option<&str>) -> String {
+        // Repository servers are spared every machine syncing at once
+        let randomized_delay = match self {
+            PeriodicTask::Sync => 3600,
+            _ => 0,
+        };
+        let timer = match calendar {
+            Some(calendar) => format!(
+                "on_calendar = \"{}\"\npersistent = true\nrandomized_delay = {}\n",
+                calendar, randomized_delay
+            ),
+            None => "on_boot = 60\n".to_string(),
+        };
+        format!(
+            r#"# Generated by buckos gen-units
+name = "{name}"
+description = "{description}"
+service_type = "oneshot"
+exec_start = "{buckos} periodic {task}"
+
+[timer]
+{timer}"#,
+            name = self.unit_name(),
+            description = self.description(),
+            buckos = buckos.display(),
+            task = self.name(),
+        )
+    }
+
+    /// Run the task, summarizing the result
+    pub async fn run(&self, pm: &PackageManager) -> TaskStatus {
+        let result = match self {
+            PeriodicTask::Sync => pm
+                .sync()
+                .await
+                .map(|()| ("repositories synced".to_string(), Vec::new())),
+            PeriodicTask::Audit => pm.audit().await.map(|vulns| {
+                let mut packages: Vec<String> =
+                    vulns.iter().map(|v| v.package.full_name()).collect();
+                packages.sort();
+                packages.dedup();
+                let summary = match vulns.len() {
+                    0 => "no known vulnerabilities".to_string(),
+                    n => format!(
+                        "{} security advisory(ies) affect {} package(s)",
+                        n,
+                        packages.len()
+                    ),
+                };
+                (summary, packages)
+            }),
+            PeriodicTask::Updates => pm.available_updates(None, false).await.map(|updates| {
+                let packages: Vec<String> = updates
+                    .iter()
+                    .map(|(old, new)| format!("{}-{} -> {}", old.id, old.version, new.version))
+                    .collect();
+                let summary = match packages.len() {
+                    0 => "all packages are up to date".to_string(),
+                    n => format!("{} package update(s) available", n),
+                };
+                (summary, packages)
+            }),
+            PeriodicTask::Hardware => pm.refresh_hardware().map(|(detection, changed)| {
+                let summary = format!(
+                    "{} recommended USE flag(s){}",
+                    detection.recommended_use_flags.len(),
+                    if changed { ", hardware changed" } else { "" }
+                );
+                (summary, Vec::new())
+            }),
+        };
+        let finished_at = Utc::now();
+        match result {
+            Ok((summary, packages)) => TaskStatus {
+                finished_at,
+                success: true,
+                summary,
+                packages,
+            },
+            Err(e) => TaskStatus {
+                finished_at,
+                success: false,
+                summary: e.to_string(),
+                packages: Vec::new(),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for PeriodicTask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Result of a task's last run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskStatus {
+    pub finished_at: DateTime<Utc>,
+    pub success: bool,
+    /// One-line result, or the error the run failed with
+    pub summary: String,
+    /// Vulnerable packages, or pending updates
+    #[serde(default)]
+    pub packages: Vec<String>,
+}
+
+/// Results of the last run of each periodic task
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeriodicStatus {
+    #[serde(flatten)]
+    pub tasks: BTreeMap<PeriodicTask, TaskStatus>,
+}
+
+impl PeriodicStatus {
+    /// Read the status file; nothing has run yet if it is missing
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Replace the stored result of `task`, rewriting the file atomically
+    pub fn record(path: &Path, task: PeriodicTask, status: TaskStatus) -> Result<Self> {
+        // A damaged file is started over rather than blocking every later run
+        let mut all = Self::load(path).unwrap_or_default();
+        all.tasks.insert(task, status);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&all)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(all)
+    }
+
+    /// Lines worth showing at login, if anything needs attention
+    pub fn motd(&self) -> Option<String> {
+        let mut lines = Vec::new();
+        for (task, status) in &self.tasks {
+            if !status.success {
+                lines.push(format!(
+                    "buckos: last {} failed at {}: {}",
+                    task,
+                    status.finished_at.format("%Y-%m-%d %H:%M UTC"),
+                    status.summary
+                ));
+                continue;
+            }
+            match task {
+                PeriodicTask::Audit if !status.packages.is_empty() => {
+                    lines.push(format!("buckos: {}; run 'buckos audit'", status.summary))
+                }
+                PeriodicTask::Updates if !status.packages.is_empty() => {
+                    lines.push(format!("buckos: {}; run 'buckos update'", status.summary))
+                }
+                _ => {}
+            }
+        }
+        (!lines.is_empty()).then(|| lines.join("\n") + "\n")
+    }
+
+    /// Write the login message, or remove it when nothing needs attention
+    pub fn write_motd(&self, path: &Path) -> Result<()> {
+        match self.motd() {
+            Some(motd) => {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                std::fs::write(path, motd)?;
+            }
+            None => match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            },
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(success: bool, summary: &str, packages: &[&str]) -> TaskStatus {
+        TaskStatus {
+            finished_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            success,
+            summary: summary.to_string(),
+            packages: packages.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "init")]
+    fn test_units_parse_as_service_definitions() {
+        use buckos_boss::ServiceDefinition;
+
+        for task in PeriodicTask::ALL {
+            let unit = task.unit(Path::new("/usr/bin/buckos"), task.default_calendar());
+            let def: ServiceDefinition = toml::from_str(&unit).unwrap();
+            assert_eq!(def.name, task.unit_name());
+            assert_eq!(def.exec_start, format!("/usr/bin/buckos periodic {}", task));
+            let timer = def.timer.unwrap();
+            assert_eq!(timer.on_calendar.as_deref(), task.default_calendar());
+            assert_eq!(timer.persistent, task.default_calendar().is_some());
+        }
+        let hardware = PeriodicTask::Hardware.unit(Path::new("/usr/bin/buckos"), None);
+        let def: ServiceDefinition = toml::from_str(&hardware).unwrap();
+        assert_eq!(
+            def.timer.unwrap().on_boot,
+            Some(std::time::Duration::from_secs(60))
+        );
+    }
+
+    #[test]
+    fn test_status_records_each_task() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("buckos/status.json");
+        assert_eq!(
+            PeriodicStatus::load(&path).unwrap(),
+            PeriodicStatus::default()
+        );
+
+        PeriodicStatus::record(&path, PeriodicTask::Sync, status(true, "synced", &[])).unwrap();
+        let all = PeriodicStatus::record(
+            &path,
+            PeriodicTask::Updates,
+            status(true, "2 package update(s) available", &["a", "b"]),
+        )
+        .unwrap();
+        assert_eq!(PeriodicStatus::load(&path).unwrap(), all);
+        assert_eq!(all.tasks.len(), 2);
+        assert_eq!(
+            all.motd().unwrap(),
+            "buckos: 2 package update(s) available; run 'buckos update'\n"
+        );
+    }
+
+    #[test]
+    fn test_motd_removed_when_nothing_to_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let motd = dir.path().join("motd.d/buckos");
+        let mut all = PeriodicStatus::default();
+        all.tasks.insert(
+            PeriodicTask::Sync,
+            status(false, "network unreachable", &[]),
+        );
+        all.write_motd(&motd).unwrap();
+        assert!(std::fs::read_to_string(&motd)
+            .unwrap()
+            .starts_with("buckos: last sync failed at 2023-11-14 22:13 UTC"));
+
+        all.tasks
+            .insert(PeriodicTask::Sync, status(true, "synced", &[]));
+        all.tasks.insert(
+            PeriodicTask::Audit,
+            status(true, "no known vulnerabilities", &[]),
+        );
+        all.write_motd(&motd).unwrap();
+        assert!(!motd.exists());
+    }
+}
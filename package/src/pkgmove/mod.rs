@@ -0,0 +1,300 @@
+//! Package moves (renames)
+//!
+//! Repositories ship package renames as update files under
+//! `profiles/updates/` (e.g. `profiles/updates/2Q-2024`), one move per line:
+//!
+//! ```text
+//! move dev-util/old-name dev-util/new-name
+//! move app-misc/foo sys-apps/foo
+//! ```
+//!
+//! Moves are applied after sync to the installed package database, the
+//! world file and custom package sets. Applied moves are appended to a log
+//! in the database directory.
+
+use crate::{Error, InstalledPackage, PackageId, Result};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Directory inside a repository holding update files
+pub const UPDATES_DIR: &str = "profiles/updates";
+
+/// Name of the applied-moves log inside the database directory
+pub const MOVES_LOG: &str = "pkgmoves.log";
+
+/// A package rename
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageMove {
+    /// Old package identifier
+    pub from: PackageId,
+    /// New package identifier
+    pub to: PackageId,
+    /// Update file the move came from
+    pub source: String,
+}
+
+/// A move that changed something on the system
+#[derive(Debug, Clone)]
+pub struct AppliedMove {
+    /// The move
+    pub package_move: PackageMove,
+    /// Whether the installed package was renamed
+    pub installed: bool,
+    /// Whether the world file was updated
+    pub world: bool,
+    /// Package sets that were updated
+    pub sets: Vec<String>,
+}
+
+/// Result of applying package moves
+#[derive(Debug, Clone, Default)]
+pub struct MoveReport {
+    /// Moves that were applied
+    pub applied: Vec<AppliedMove>,
+    /// Moves skipped because both old and new names are installed
+    pub conflicts: Vec<PackageMove>,
+}
+
+/// Parse the contents of an update file
+///
+/// Lines other than `move` (e.g. `slotmove`) are ignored.
+pub fn parse_updates(content: &str, source: &str) -> Result<Vec<PackageMove>> {
+    let mut moves = Vec::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields[0] != "move" {
+            continue;
+        }
+        if fields.len() != 3 {
+            return Err(Error::Other(format!(
+                "invalid package move in {}: {}",
+                source, line
+            )));
+        }
+
+        let parse =
+            |s: &str| PackageId::parse(s).ok_or_else(|| Error::InvalidPackageSpec(s.to_string()));
+        moves.push(PackageMove {
+            from: parse(fields[1])?,
+            to: parse(fields[2])?,
+            source: source.to_string(),
+        });
+    }
+
+    Ok(moves)
+}
+
+/// Load all moves from a repository, in chronological order
+pub fn load_repo_moves(repo_dir: &Path) -> Result<Vec<PackageMove>> {
+    let updates_dir = repo_dir.join(UPDATES_DIR);
+    if !updates_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut files: Vec<PathBuf> = std::fs::read_dir(&updates_dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .collect();
+    files.sort_by_key(|p| update_file_order(p));
+
+    let mut moves = Vec::new();
+    for file in files {
+        let content = std::fs::read_to_string(&file)?;
+        let source = file
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        moves.extend(parse_updates(&content, &source)?);
+    }
+
+    Ok(moves)
+}
+
+/// Sort key for update files: `NQ-YYYY` names sort by date, others by name
+fn update_file_order(path: &Path) -> (u32, u32, String) {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let quarter = name
+        .split_once("Q-")
+        .and_then(|(q, y)| Some((y.parse::<u32>().ok()?, q.parse::<u32>().ok()?)));
+    match quarter {
+        Some((year, q)) => (year, q, name),
+        None => (u32::MAX, 0, name),
+    }
+}
+
+/// Moves that would rename an installed package onto another installed package
+pub fn find_conflicts(moves: &[PackageMove], installed: &[InstalledPackage]) -> Vec<PackageMove> {
+    let installed: HashSet<&PackageId> = installed.iter().map(|p| &p.id).collect();
+    moves
+        .iter()
+        .filter(|m| installed.contains(&m.from) && installed.contains(&m.to))
+        .cloned()
+        .collect()
+}
+
+/// Rename an atom in a set file line, keeping operators and version suffixes
+///
+/// Returns `None` if the line does not reference `from`.
+pub fn rename_atom(line: &str, from: &PackageId, to: &PackageId) -> Option<String> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return None;
+    }
+
+    let old = from.full_name();
+    let start = trimmed.find(&old)?;
+
+    // Must be preceded only by version operators
+    if !trimmed[..start]
+        .chars()
+        .all(|c| matches!(c, '>' | '<' | '=' | '~' | '!'))
+    {
+        return None;
+    }
+
+    // Must end at an atom boundary (version, slot, USE deps or end of line)
+    let rest = &trimmed[start + old.len()..];
+    let boundary = match rest.chars().next() {
+        None => true,
+        Some('-') => rest[1..].starts_with(|c: char| c.is_ascii_digit()),
+        Some(c) => matches!(c, ':' | '[' | ' ' | '\t' | '*'),
+    };
+    if !boundary {
+        return None;
+    }
+
+    Some(format!("{}{}{}", &trimmed[..start], to.full_name(), rest))
+}
+
+/// Apply a move to a set or world file, returning whether it changed
+pub fn rename_in_file(path: &Path, package_move: &PackageMove) -> Result<bool> {
+    if !path.is_file() {
+        return Ok(false);
+    }
+
+    let content = std::fs::read_to_string(path)?;
+    let mut changed = false;
+    let mut output = String::new();
+    for line in content.lines() {
+        match rename_atom(line, &package_move.from, &package_move.to) {
+            Some(renamed) => {
+                changed = true;
+                output.push_str(&renamed);
+            }
+            None => output.push_str(line),
+        }
+        output.push('\n');
+    }
+
+    if changed {
+        std::fs::write(path, output)?;
+    }
+    Ok(changed)
+}
+
+/// Append applied moves to the log in the database directory
+pub fn log_applied(db_path: &Path, applied: &[AppliedMove]) -> Result<()> {
+    if applied.is_empty() {
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(db_path)?;
+    let mut log = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(db_path.join(MOVES_LOG))?;
+
+    let now = chrono::Utc::now().to_rfc3339();
+    for entry in applied {
+        let mut targets = Vec::new();
+        if entry.installed {
+            targets.push("db".to_string());
+        }
+        if entry.world {
+            targets.push("world".to_string());
+        }
+        targets.extend(entry.sets.iter().map(|s| format!("set:{}", s)));
+
+        writeln!(
+            log,
+            "{} {} -> {} ({}) [{}]",
+            now,
+            entry.package_move.from,
+            entry.package_move.to,
+            entry.package_move.source,
+            targets.join(",")
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_updates() {
+        let content = "\
+# comment
+move dev-util/old dev-util/new
+slotmove dev-libs/foo 0 1
+move app-misc/a sys-apps/a
+";
+        let moves = parse_updates(content, "1Q-2024").unwrap();
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[0].from, PackageId::new("dev-util", "old"));
+        assert_eq!(moves[0].to, PackageId::new("dev-util", "new"));
+        assert_eq!(moves[1].source, "1Q-2024");
+
+        assert!(parse_updates("move dev-util/old", "x").is_err());
+    }
+
+    #[test]
+    fn test_rename_atom() {
+        let from = PackageId::new("dev-util", "old");
+        let to = PackageId::new("dev-util", "new");
+
+        assert_eq!(
+            rename_atom("dev-util/old", &from, &to),
+            Some("dev-util/new".to_string())
+        );
+        assert_eq!(
+            rename_atom(">=dev-util/old-1.2:0[foo]", &from, &to),
+            Some(">=dev-util/new-1.2:0[foo]".to_string())
+        );
+        assert_eq!(rename_atom("dev-util/old-tools", &from, &to), None);
+        assert_eq!(rename_atom("# dev-util/old", &from, &to), None);
+        assert_eq!(rename_atom("xdev-util/old", &from, &to), None);
+    }
+
+    #[test]
+    fn test_update_file_order() {
+        let mut files = vec![
+            PathBuf::from("1Q-2025"),
+            PathBuf::from("4Q-2024"),
+            PathBuf::from("1Q-2024"),
+        ];
+        files.sort_by_key(|p| update_file_order(p));
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from("1Q-2024"),
+                PathBuf::from("4Q-2024"),
+                PathBuf::from("1Q-2025")
+            ]
+        );
+    }
+}
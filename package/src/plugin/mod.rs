@@ -0,0 +1,700 @@
+//! Package manager plugins
+//!
+//! Plugins let site-specific policy hook into buckos without forking it:
+//!
+//! - **constraints** forbid or pin packages during dependency resolution
+//! - **pre-transaction** hooks can veto a transaction before anything changes
+//! - **post-transaction** hooks observe the outcome (notifications, audit logs)
+//! - **commands** add `buckos <name>` subcommands
+//!
+//! Embedders implement [`Plugin`] directly. Everyone else drops a directory
+//! containing a `plugin.toml` and an executable into the plugin directory
+//! (`/etc/buckos/plugins` by default):
+//!
+//! ```toml
+//! name = "site-policy"
+//! abi = 1
+//! executable = "site-policy"
+//! hooks = ["constraints", "pre-transaction"]
+//!
+//! [[commands]]
+//! name = "license-report"
+//! about = "Summarise licenses of installed packages"
+//! ```
+//!
+//! Executable plugins run out of process with a scrubbed environment, a
+//! timeout and the plugin directory as working directory. Hooks are invoked
+//! as `<executable> hook <name>` with a JSON request on stdin; commands as
+//! `<executable> command <name> [args...]` with the terminal attached.
+//! A plugin built for an ABI version this buckos does not speak is skipped
+//! rather than loaded.
+
+use crate::{Error, PackageInfo, PackageSpec, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Plugin ABI spoken by this build
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Oldest plugin ABI still accepted
+pub const MIN_PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Default time limit for a hook, in seconds
+const DEFAULT_TIMEOUT: u64 = 30;
+
+/// Resolution constraint contributed by a plugin
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Constraint {
+    /// What the constraint does
+    pub kind: ConstraintKind,
+    /// Package atom (e.g. `dev-lang/python` or `<dev-lang/python-3.13.0`)
+    pub atom: String,
+    /// Shown when the constraint rejects a package
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// Kind of resolution constraint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConstraintKind {
+    /// Packages matching the atom must not be selected
+    Forbid,
+    /// Only versions matching the atom may be selected for that package
+    Only,
+}
+
+impl Constraint {
+    /// Whether this constraint rejects a package
+    pub fn rejects(&self, pkg: &PackageInfo) -> Result<bool> {
+        let spec = PackageSpec::parse(&self.atom)?;
+        if spec.id != pkg.id {
+            return Ok(false);
+        }
+        let version_ok = spec.version.matches(&pkg.version);
+        Ok(match self.kind {
+            ConstraintKind::Forbid => version_ok,
+            ConstraintKind::Only => !version_ok,
+        })
+    }
+}
+
+/// Why a package is rejected by the first of `constraints` that rejects it,
+/// if any does
+pub fn rejection(
+    constraints: &[(String, Constraint)],
+    pkg: &PackageInfo,
+) -> Result<Option<String>> {
+    for (plugin, constraint) in constraints {
+        if constraint.rejects(pkg)? {
+            return Ok(Some(format!(
+                "{}-{} rejected by plugin {} ({}): {}",
+                pkg.id, pkg.version, plugin, constraint.atom, constraint.reason
+            )));
+        }
+    }
+    Ok(None)
+}
+
+/// The newest of `candidates` that no constraint rejects
+pub fn newest_allowed<'a>(
+    constraints: &[(String, Constraint)],
+    candidates: impl IntoIterator<Item = &'a PackageInfo>,
+) -> Result<Option<&'a PackageInfo>> {
+    let mut newest: Option<&PackageInfo> = None;
+    for pkg in candidates {
+        if newest.is_none_or(|n| pkg.version > n.version) && rejection(constraints, pkg)?.is_none()
+        {
+            newest = Some(pkg);
+        }
+    }
+    Ok(newest)
+}
+
+/// Packages a transaction is about to change
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionSummary {
+    /// Packages being installed (`category/name-version`)
+    pub install: Vec<String>,
+    /// Packages being removed
+    pub remove: Vec<String>,
+    /// Packages being upgraded, as (old, new)
+    pub upgrade: Vec<(String, String)>,
+}
+
+/// A subcommand contributed by a plugin
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginCommand {
+    /// Subcommand name (`buckos <name>`)
+    pub name: String,
+    /// One-line help text
+    #[serde(default)]
+    pub about: String,
+}
+
+/// Extension point implemented by plugins
+///
+/// Every hook has a no-op default, so a plugin only implements what it needs.
+pub trait Plugin: Send + Sync {
+    /// Unique plugin name
+    fn name(&self) -> &str;
+
+    /// ABI version the plugin was written against
+    fn abi_version(&self) -> u32 {
+        PLUGIN_ABI_VERSION
+    }
+
+    /// Extra constraints applied during dependency resolution
+    fn constraints(&self) -> Result<Vec<Constraint>> {
+        Ok(Vec::new())
+    }
+
+    /// Called before a transaction starts; an error aborts the transaction
+    fn pre_transaction(&self, _summary: &TransactionSummary) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called after a transaction committed or rolled back
+    fn post_transaction(&self, _summary: &TransactionSummary, _success: bool) -> Result<()> {
+        Ok(())
+    }
+
+    /// Subcommands this plugin provides
+    fn commands(&self) -> Vec<PluginCommand> {
+        Vec::new()
+    }
+
+    /// Run one of [`Plugin::commands`], returning its exit code
+    fn run_command(&self, command: &str, _args: &[String]) -> Result<i32> {
+        Err(Error::Other(format!(
+            "plugin {} does not provide command {}",
+            self.name(),
+            command
+        )))
+    }
+}
+
+/// Hooks an executable plugin subscribes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Hook {
+    Constraints,
+    PreTransaction,
+    PostTransaction,
+}
+
+impl Hook {
+    fn as_str(self) -> &'static str {
+        match self {
+            Hook::Constraints => "constraints",
+            Hook::PreTransaction => "pre-transaction",
+            Hook::PostTransaction => "post-transaction",
+        }
+    }
+}
+
+/// `plugin.toml`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginManifest {
+    /// Plugin name
+    pub name: String,
+    /// Plugin ABI version
+    pub abi: u32,
+    /// Executable, relative to the plugin directory
+    pub executable: PathBuf,
+    /// Hooks to invoke the executable for
+    #[serde(default)]
+    pub hooks: Vec<Hook>,
+    /// Subcommands provided
+    #[serde(default)]
+    pub commands: Vec<PluginCommand>,
+    /// Time limit for each hook invocation, in seconds
+    #[serde(default)]
+    pub timeout: Option<u64>,
+}
+
+/// Request written to a hook's stdin
+#[derive(Serialize)]
+struct HookRequest<'a> {
+    abi: u32,
+    hook: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transaction: Option<&'a TransactionSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    success: Option<bool>,
+}
+
+/// Response read from a `constraints` hook's stdout
+#[derive(Deserialize)]
+struct ConstraintsResponse {
+    #[serde(default)]
+    constraints: Vec<Constraint>,
+}
+
+/// Plugin backed by an executable and its manifest
+pub struct ExecPlugin {
+    manifest: PluginManifest,
+    dir: PathBuf,
+}
+
+impl ExecPlugin {
+    /// Load a plugin from its directory
+    pub fn load(dir: &Path) -> Result<Self> {
+        let manifest_path = dir.join("plugin.toml");
+        let content = std::fs::read_to_string(&manifest_path)?;
+        let manifest: PluginManifest = toml::from_str(&content)
+            .map_err(|e| Error::ConfigError(format!("{}: {}", manifest_path.display(), e)))?;
+
+        let plugin = Self {
+            manifest,
+            dir: dir.to_path_buf(),
+        };
+        let contained = match (plugin.executable().canonicalize(), dir.canonicalize()) {
+            (Ok(executable), Ok(dir)) => executable.starts_with(dir) && executable.is_file(),
+            _ => false,
+        };
+        if !contained {
+            return Err(Error::ConfigError(format!(
+                "{}: executable {} not found in plugin directory",
+                manifest_path.display(),
+                plugin.manifest.executable.display()
+            )));
+        }
+        Ok(plugin)
+    }
+
+    /// The plugin's manifest
+    pub fn manifest(&self) -> &PluginManifest {
+        &self.manifest
+    }
+
+    fn executable(&self) -> PathBuf {
+        self.dir.join(&self.manifest.executable)
+    }
+
+    /// Command with a scrubbed environment, run from the plugin directory
+    fn command(&self) -> Command {
+        let mut cmd = Command::new(self.executable());
+        cmd.env_clear()
+            .env("PATH", "/usr/local/bin:/usr/bin:/bin")
+            .env("BUCKOS_PLUGIN_ABI", PLUGIN_ABI_VERSION.to_string())
+            .env("BUCKOS_PLUGIN_DIR", &self.dir)
+            .current_dir(&self.dir);
+        for var in ["HOME", "LANG", "TERM"] {
+            if let Ok(value) = std::env::var(var) {
+                cmd.env(var, value);
+            }
+        }
+        cmd
+    }
+
+    /// Run a hook, returning its stdout; non-zero exit is an error
+    fn invoke(&self, hook: Hook, request: &HookRequest) -> Result<Option<String>> {
+        if !self.manifest.hooks.contains(&hook) {
+            return Ok(None);
+        }
+        let input = serde_json::to_vec(request)
+            .map_err(|e| Error::Other(format!("Failed to encode hook request: {}", e)))?;
+
+        debug!("Running {} hook of plugin {}", hook.as_str(), self.name());
+        let mut child = self
+            .command()
+            .arg("hook")
+            .arg(hook.as_str())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // A hook that ignores its input may exit before reading it
+            let _ = stdin.write_all(&input);
+        }
+
+        // Drain pipes on threads so a chatty plugin cannot block on a full pipe
+        let mut stdout = child.stdout.take();
+        let mut stderr = child.stderr.take();
+        let out = std::thread::spawn(move || {
+            let mut buf = String::new();
+            if let Some(pipe) = stdout.as_mut() {
+                let _ = pipe.read_to_string(&mut buf);
+            }
+            buf
+        });
+        let err = std::thread::spawn(move || {
+            let mut buf = String::new();
+            if let Some(pipe) = stderr.as_mut() {
+                let _ = pipe.read_to_string(&mut buf);
+            }
+            buf
+        });
+
+        let timeout = Duration::from_secs(self.manifest.timeout.unwrap_or(DEFAULT_TIMEOUT));
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if started.elapsed() > timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(Error::Other(format!(
+                    "plugin {} {} hook timed out after {}s",
+                    self.name(),
+                    hook.as_str(),
+                    timeout.as_secs()
+                )));
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+
+        let stdout = out.join().unwrap_or_default();
+        let stderr = err.join().unwrap_or_default();
+        if !status.success() {
+            let message = stderr.trim();
+            return Err(Error::Other(format!(
+                "plugin {} {} hook failed: {}",
+                self.name(),
+                hook.as_str(),
+                if message.is_empty() {
+                    status.to_string()
+                } else {
+                    message.to_string()
+                }
+            )));
+        }
+        Ok(Some(stdout))
+    }
+}
+
+impl Plugin for ExecPlugin {
+    fn name(&self) -> &str {
+        &self.manifest.name
+    }
+
+    fn abi_version(&self) -> u32 {
+        self.manifest.abi
+    }
+
+    fn constraints(&self) -> Result<Vec<Constraint>> {
+        let request = HookRequest {
+            abi: PLUGIN_ABI_VERSION,
+            hook: Hook::Constraints.as_str(),
+            transaction: None,
+            success: None,
+        };
+        let Some(output) = self.invoke(Hook::Constraints, &request)? else {
+            return Ok(Vec::new());
+        };
+        let response: ConstraintsResponse = serde_json::from_str(&output).map_err(|e| {
+            Error::Other(format!(
+                "plugin {} returned invalid constraints: {}",
+                self.name(),
+                e
+            ))
+        })?;
+        Ok(response.constraints)
+    }
+
+    fn pre_transaction(&self, summary: &TransactionSummary) -> Result<()> {
+        let request = HookRequest {
+            abi: PLUGIN_ABI_VERSION,
+            hook: Hook::PreTransaction.as_str(),
+            transaction: Some(summary),
+            success: None,
+        };
+        self.invoke(Hook::PreTransaction, &request).map(|_| ())
+    }
+
+    fn post_transaction(&self, summary: &TransactionSummary, success: bool) -> Result<()> {
+        let request = HookRequest {
+            abi: PLUGIN_ABI_VERSION,
+            hook: Hook::PostTransaction.as_str(),
+            transaction: Some(summary),
+            success: Some(success),
+        };
+        self.invoke(Hook::PostTransaction, &request).map(|_| ())
+    }
+
+    fn commands(&self) -> Vec<PluginCommand> {
+        self.manifest.commands.clone()
+    }
+
+    fn run_command(&self, command: &str, args: &[String]) -> Result<i32> {
+        let status = self
+            .command()
+            .arg("command")
+            .arg(command)
+            .args(args)
+            .status()?;
+        Ok(status.code().unwrap_or(1))
+    }
+}
+
+/// The set of loaded plugins
+#[derive(Default)]
+pub struct PluginManager {
+    plugins: Vec<Box<dyn Plugin>>,
+    rejected: Vec<(PathBuf, String)>,
+}
+
+impl PluginManager {
+    /// A manager with no plugins
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load every plugin directory under `dir`
+    ///
+    /// Plugins that fail to load or target an unsupported ABI are recorded
+    /// in [`PluginManager::rejected`] instead of failing the whole load.
+    pub fn discover(dir: &Path) -> Self {
+        let mut manager = Self::new();
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return manager;
+        };
+
+        let mut dirs: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.join("plugin.toml").is_file())
+            .collect();
+        dirs.sort();
+
+        for path in dirs {
+            let loaded = ExecPlugin::load(&path).and_then(|p| manager.register(Box::new(p)));
+            if let Err(e) = loaded {
+                warn!("Skipping plugin {}: {}", path.display(), e);
+                manager.rejected.push((path, e.to_string()));
+            }
+        }
+        manager
+    }
+
+    /// Add a plugin, checking its ABI version and name
+    pub fn register(&mut self, plugin: Box<dyn Plugin>) -> Result<()> {
+        let abi = plugin.abi_version();
+        if !(MIN_PLUGIN_ABI_VERSION..=PLUGIN_ABI_VERSION).contains(&abi) {
+            return Err(Error::Other(format!(
+                "plugin {} targets ABI {}, supported: {}..={}",
+                plugin.name(),
+                abi,
+                MIN_PLUGIN_ABI_VERSION,
+                PLUGIN_ABI_VERSION
+            )));
+        }
+        if self.plugins.iter().any(|p| p.name() == plugin.name()) {
+            return Err(Error::Other(format!(
+                "a plugin named {} is already loaded",
+                plugin.name()
+            )));
+        }
+        self.plugins.push(plugin);
+        Ok(())
+    }
+
+    /// Loaded plugins
+    pub fn plugins(&self) -> impl Iterator<Item = &dyn Plugin> {
+        self.plugins.iter().map(|p| p.as_ref())
+    }
+
+    /// Plugin directories that were skipped, with the reason
+    pub fn rejected(&self) -> &[(PathBuf, String)] {
+        &self.rejected
+    }
+
+    /// Constraints from every plugin, tagged with the plugin name
+    pub fn constraints(&self) -> Result<Vec<(String, Constraint)>> {
+        let mut all = Vec::new();
+        for plugin in &self.plugins {
+            for constraint in plugin.constraints()? {
+                PackageSpec::parse(&constraint.atom)?;
+                all.push((plugin.name().to_string(), constraint));
+            }
+        }
+        Ok(all)
+    }
+
+    /// Run pre-transaction hooks; the first error vetoes the transaction
+    pub fn pre_transaction(&self, summary: &TransactionSummary) -> Result<()> {
+        for plugin in &self.plugins {
+            plugin.pre_transaction(summary)?;
+        }
+        Ok(())
+    }
+
+    /// Run post-transaction hooks, logging failures
+    pub fn post_transaction(&self, summary: &TransactionSummary, success: bool) {
+        for plugin in &self.plugins {
+            if let Err(e) = plugin.post_transaction(summary, success) {
+                warn!("{}", e);
+            }
+        }
+    }
+
+    /// Find the plugin providing a subcommand
+    pub fn find_command(&self, name: &str) -> Option<(&dyn Plugin, PluginCommand)> {
+        self.plugins().find_map(|plugin| {
+            plugin
+                .commands()
+                .into_iter()
+                .find(|c| c.name == name)
+                .map(|c| (plugin, c))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn write_plugin(root: &Path, name: &str, abi: u32, script: &str) -> PathBuf {
+        let dir = root.join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("plugin.toml"),
+            format!(
+                "name = \"{}\"\nabi = {}\nexecutable = \"run\"\nhooks = [\"constraints\", \"pre-transaction\"]\n\n[[commands]]\nname = \"hello\"\n",
+                name, abi
+            ),
+        )
+        .unwrap();
+        let exe = dir.join("run");
+        std::fs::write(&exe, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        std::fs::set_permissions(&exe, std::fs::Permissions::from_mode(0o755)).unwrap();
+        dir
+    }
+
+    fn package(name: &str, version: &str) -> PackageInfo {
+        PackageInfo {
+            id: crate::PackageId::new("dev-lang", name),
+            version: semver::Version::parse(version).unwrap(),
+            slot: "0".to_string(),
+            description: String::new(),
+            homepage: None,
+            license: String::new(),
+            keywords: Vec::new(),
+            use_flags: Vec::new(),
+            dependencies: Vec::new(),
+            build_dependencies: Vec::new(),
+            runtime_dependencies: Vec::new(),
+            any_of_dependencies: Vec::new(),
+            source_url: None,
+            source_hash: None,
+            buck_target: String::new(),
+            size: 0,
+            installed_size: 0,
+            required_use: String::new(),
+            blockers: Vec::new(),
+            restrict: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_constraint_rejects() {
+        let forbid = Constraint {
+            kind: ConstraintKind::Forbid,
+            atom: "dev-lang/ruby".to_string(),
+            reason: String::new(),
+        };
+        assert!(forbid.rejects(&package("ruby", "3.3.0")).unwrap());
+        assert!(!forbid.rejects(&package("python", "3.12.0")).unwrap());
+
+        let only = Constraint {
+            kind: ConstraintKind::Only,
+            atom: "<dev-lang/python-3.13.0".to_string(),
+            reason: String::new(),
+        };
+        assert!(!only.rejects(&package("python", "3.12.0")).unwrap());
+        assert!(only.rejects(&package("python", "3.13.1")).unwrap());
+        assert!(!only.rejects(&package("ruby", "9.9.9")).unwrap());
+    }
+
+    #[test]
+    fn test_newest_allowed_skips_rejected() {
+        let constraints = vec![(
+            "site-policy".to_string(),
+            Constraint {
+                kind: ConstraintKind::Only,
+                atom: "<dev-lang/python-3.13.0".to_string(),
+                reason: "not yet supported".to_string(),
+            },
+        )];
+        let candidates = [
+            package("python", "3.11.9"),
+            package("python", "3.13.1"),
+            package("python", "3.12.3"),
+        ];
+        let reason = rejection(&constraints, &candidates[1]).unwrap().unwrap();
+        assert!(reason.contains("site-policy"), "{}", reason);
+        assert!(reason.contains("not yet supported"), "{}", reason);
+
+        let newest = newest_allowed(&constraints, &candidates).unwrap().unwrap();
+        assert_eq!(newest.version, semver::Version::new(3, 12, 3));
+        assert!(newest_allowed(&constraints, &candidates[1..2])
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_exec_plugin_hooks() {
+        let root = tempfile::tempdir().unwrap();
+        write_plugin(
+            root.path(),
+            "policy",
+            PLUGIN_ABI_VERSION,
+            r#"case "$1 $2" in
+  "hook constraints") echo '{"constraints":[{"kind":"forbid","atom":"dev-lang/ruby","reason":"not allowed here"}]}' ;;
+  "hook pre-transaction") grep -q ruby && { echo "ruby is banned" >&2; exit 1; } ; exit 0 ;;
+  "command hello") exit 7 ;;
+esac"#,
+        );
+
+        let manager = PluginManager::discover(root.path());
+        assert!(manager.rejected().is_empty());
+        assert_eq!(manager.plugins().count(), 1);
+
+        let constraints = manager.constraints().unwrap();
+        assert_eq!(constraints.len(), 1);
+        assert_eq!(constraints[0].0, "policy");
+        assert_eq!(constraints[0].1.reason, "not allowed here");
+
+        let ok = TransactionSummary {
+            install: vec!["dev-lang/python-3.12.0".to_string()],
+            ..Default::default()
+        };
+        assert!(manager.pre_transaction(&ok).is_ok());
+        let banned = TransactionSummary {
+            install: vec!["dev-lang/ruby-3.3.0".to_string()],
+            ..Default::default()
+        };
+        let err = manager.pre_transaction(&banned).unwrap_err();
+        assert!(err.to_string().contains("ruby is banned"));
+
+        let (plugin, command) = manager.find_command("hello").unwrap();
+        assert_eq!(command.name, "hello");
+        assert_eq!(plugin.run_command("hello", &[]).unwrap(), 7);
+        assert!(manager.find_command("missing").is_none());
+    }
+
+    #[test]
+    fn test_unsupported_abi_rejected() {
+        let root = tempfile::tempdir().unwrap();
+        write_plugin(root.path(), "future", PLUGIN_ABI_VERSION + 1, "exit 0");
+        let escape = root.path().join("escape");
+        std::fs::create_dir_all(&escape).unwrap();
+        std::fs::write(
+            escape.join("plugin.toml"),
+            "name = \"escape\"\nabi = 1\nexecutable = \"../../bin/sh\"\n",
+        )
+        .unwrap();
+
+        let manager = PluginManager::discover(root.path());
+        assert_eq!(manager.plugins().count(), 0);
+        assert_eq!(manager.rejected().len(), 2);
+    }
+}
@@ -222,6 +222,21 @@ impl ResolvedProfile {
         flags
     }
 
+    /// Get the package.use entries that apply to a package, in order
+    pub fn package_use_for(&self, package: &str) -> Vec<String> {
+        let mut patterns: Vec<&String> = self
+            .package_use
+            .keys()
+            .filter(|pattern| package_matches(package, pattern))
+            .collect();
+        patterns.sort();
+
+        patterns
+            .into_iter()
+            .flat_map(|pattern| self.package_use[pattern].iter().cloned())
+            .collect()
+    }
+
     /// Check if a package is masked
     pub fn is_masked(&self, package: &str) -> bool {
         // Check if explicitly unmasked first
@@ -0,0 +1,283 @@
+//! Repository generations
+//!
+//! A synced repository lives in a numbered generation directory beside its
+//! configured location, and the location is a symlink to the active one:
+//!
+//! ```text
+//! /var/db/repos/buckos-build -> .buckos-build.generations/7
+//! ```
+//!
+//! Sync fills the next generation in a staging directory and swaps the
+//! symlink with a rename, so a reader that resolves the location once (see
+//! [`active_path`]) sees either the old tree or the new one, never a mix of
+//! both. The previous generation is kept for readers still walking it.
+
+use crate::config::RepositoryConfig;
+use crate::Result;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Generations kept on disk, the active one included
+pub const KEEP_GENERATIONS: usize = 2;
+
+const STAGING_PREFIX: &str = ".staging-";
+
+/// The generations of one repository location
+#[derive(Debug, Clone)]
+pub struct Generations {
+    location: PathBuf,
+    dir: PathBuf,
+    dir_name: String,
+}
+
+/// A generation being synced, not yet visible to readers
+#[derive(Debug)]
+pub struct StagedGeneration {
+    pub id: u64,
+    pub path: PathBuf,
+}
+
+impl Generations {
+    pub fn new(location: &Path) -> Self {
+        let name = location
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let dir_name = format!(".{}.generations", name);
+        Self {
+            location: location.to_path_buf(),
+            dir: location.with_file_name(&dir_name),
+            dir_name,
+        }
+    }
+
+    /// Directory holding the generations
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Id of the active generation, if the location points at one
+    pub fn active(&self) -> Option<u64> {
+        let target = fs::read_link(&self.location).ok()?;
+        let parent = target.parent()?.file_name()?;
+        if parent != self.dir_name.as_str() {
+            return None;
+        }
+        target.file_name()?.to_str()?.parse().ok()
+    }
+
+    /// Directory of the active generation
+    pub fn active_dir(&self) -> Option<PathBuf> {
+        self.active().map(|id| self.dir.join(id.to_string()))
+    }
+
+    /// Generation ids on disk, oldest first
+    pub fn list(&self) -> Result<Vec<u64>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut ids: Vec<u64> = fs::read_dir(&self.dir)?
+            .filter_map(|e| e.ok())
+            .filter_map(|e| e.file_name().to_str()?.parse().ok())
+            .collect();
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    fn next_id(&self) -> Result<u64> {
+        let newest = self.list()?.last().copied().unwrap_or(0);
+        Ok(newest.max(self.active().unwrap_or(0)) + 1)
+    }
+
+    /// Turn a location that is still a plain directory into the first
+    /// generation
+    ///
+    /// The directory is renamed, so there is a moment where the location
+    /// does not exist; this happens once per repository.
+    pub fn adopt(&self) -> Result<()> {
+        let Ok(meta) = fs::symlink_metadata(&self.location) else {
+            return Ok(());
+        };
+        if !meta.is_dir() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)?;
+        let id = self.next_id()?;
+        fs::rename(&self.location, self.dir.join(id.to_string()))?;
+        self.point_to(id)?;
+        info!(
+            "Repository at {} moved to generation {}",
+            self.location.display(),
+            id
+        );
+        Ok(())
+    }
+
+    /// Create an empty staging directory for the next generation
+    pub fn stage(&self) -> Result<StagedGeneration> {
+        fs::create_dir_all(&self.dir)?;
+        let id = self.next_id()?;
+        let path = self.dir.join(format!("{}{}", STAGING_PREFIX, id));
+        if path.exists() {
+            fs::remove_dir_all(&path)?;
+        }
+        fs::create_dir(&path)?;
+        Ok(StagedGeneration { id, path })
+    }
+
+    /// Make a staged generation the active one and prune old generations
+    pub fn commit(&self, staged: StagedGeneration) -> Result<()> {
+        fs::rename(&staged.path, self.dir.join(staged.id.to_string()))?;
+        self.point_to(staged.id)?;
+        self.prune()
+    }
+
+    /// Throw away a staged generation after a failed sync
+    pub fn discard(&self, staged: StagedGeneration) {
+        if let Err(e) = fs::remove_dir_all(&staged.path) {
+            warn!("Failed to remove {}: {}", staged.path.display(), e);
+        }
+    }
+
+    /// Swap the location symlink to generation `id` in one rename
+    fn point_to(&self, id: u64) -> Result<()> {
+        let name = self
+            .location
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let tmp = self.location.with_file_name(format!(".{}.link", name));
+        if fs::symlink_metadata(&tmp).is_ok() {
+            fs::remove_file(&tmp)?;
+        }
+        std::os::unix::fs::symlink(Path::new(&self.dir_name).join(id.to_string()), &tmp)?;
+        fs::rename(&tmp, &self.location)?;
+        Ok(())
+    }
+
+    /// Remove all but the newest [`KEEP_GENERATIONS`] generations, and any
+    /// staging directories left by interrupted syncs
+    fn prune(&self) -> Result<()> {
+        let active = self.active();
+        let ids = self.list()?;
+        let keep_from = ids.len().saturating_sub(KEEP_GENERATIONS);
+        for id in &ids[..keep_from] {
+            if Some(*id) != active {
+                let path = self.dir.join(id.to_string());
+                if let Err(e) = fs::remove_dir_all(&path) {
+                    warn!("Failed to remove {}: {}", path.display(), e);
+                }
+            }
+        }
+        for entry in fs::read_dir(&self.dir)?.filter_map(|e| e.ok()) {
+            if entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(STAGING_PREFIX)
+            {
+                let _ = fs::remove_dir_all(entry.path());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The directory readers should use for a repository: its active
+/// generation, resolved once, or the location itself when it has none
+pub fn active_path(location: &Path) -> PathBuf {
+    Generations::new(location)
+        .active_dir()
+        .unwrap_or_else(|| location.to_path_buf())
+}
+
+/// Active generation of a repository, as shown by `buckos status`
+#[derive(Debug, Clone, Serialize)]
+pub struct RepoGeneration {
+    pub name: String,
+    /// None while the repository has not been synced into generations
+    pub generation: Option<u64>,
+    /// When the generation became active
+    pub activated: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl RepoGeneration {
+    pub fn of(repo: &RepositoryConfig) -> Self {
+        let generation = Generations::new(&repo.location).active();
+        let activated = generation
+            .and_then(|_| fs::symlink_metadata(&repo.location).ok())
+            .and_then(|m| m.modified().ok())
+            .map(chrono::DateTime::from);
+        Self {
+            name: repo.name.clone(),
+            generation,
+            activated,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adopt_and_swap_generations() {
+        let dir = tempfile::tempdir().unwrap();
+        let location = dir.path().join("buckos-build");
+        fs::create_dir_all(location.join("packages")).unwrap();
+        fs::write(location.join("packages/old"), "old").unwrap();
+
+        let generations = Generations::new(&location);
+        assert_eq!(generations.active(), None);
+        assert_eq!(active_path(&location), location);
+
+        generations.adopt().unwrap();
+        assert_eq!(generations.active(), Some(1));
+        assert!(location.join("packages/old").exists());
+        // Adopting again is a no-op
+        generations.adopt().unwrap();
+        assert_eq!(generations.list().unwrap(), vec![1]);
+
+        // Readers holding the old generation keep seeing it
+        let before = active_path(&location);
+        let staged = generations.stage().unwrap();
+        assert_eq!(staged.id, 2);
+        fs::create_dir(staged.path.join("packages")).unwrap();
+        fs::write(staged.path.join("packages/new"), "new").unwrap();
+        assert_eq!(generations.active(), Some(1));
+        generations.commit(staged).unwrap();
+
+        assert_eq!(generations.active(), Some(2));
+        assert!(location.join("packages/new").exists());
+        assert!(!location.join("packages/old").exists());
+        assert!(before.join("packages/old").exists());
+        assert_eq!(active_path(&location), generations.dir().join("2"));
+    }
+
+    #[test]
+    fn test_prune_and_discard() {
+        let dir = tempfile::tempdir().unwrap();
+        let location = dir.path().join("repo");
+        let generations = Generations::new(&location);
+        for _ in 0..4 {
+            let staged = generations.stage().unwrap();
+            generations.commit(staged).unwrap();
+        }
+        assert_eq!(generations.list().unwrap(), vec![3, 4]);
+
+        let staged = generations.stage().unwrap();
+        fs::write(staged.path.join("partial"), "").unwrap();
+        generations.discard(staged);
+        assert_eq!(generations.active(), Some(4));
+        assert_eq!(fs::read_dir(generations.dir()).unwrap().count(), 2);
+
+        let status = RepoGeneration::of(&RepositoryConfig {
+            name: "repo".to_string(),
+            location,
+            ..Default::default()
+        });
+        assert_eq!(status.generation, Some(4));
+        assert!(status.activated.is_some());
+    }
+}
@@ -2,9 +2,14 @@
 //!
 //! Handles syncing and querying package repositories.
 
+pub mod generation;
+
+pub use generation::*;
+
 use crate::config::{Config, RepositoryConfig, SyncType};
 use crate::{
-    Dependency, Error, PackageId, PackageInfo, Result, UseCondition, UseFlag, VersionSpec,
+    Dependency, Error, PackageHeader, PackageId, PackageInfo, Result, UseCondition, UseFlag,
+    VersionSpec,
 };
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
@@ -74,73 +79,94 @@ impl RepositoryManager {
         }
     }
 
+    /// Make the active generation of a plain repository directory, then
+    /// stage the next one
+    fn begin_generation(
+        &self,
+        repo: &RepositoryConfig,
+    ) -> Result<(Generations, StagedGeneration, Option<PathBuf>)> {
+        let generations = Generations::new(&repo.location);
+        generations.adopt()?;
+        let active = generations.active_dir();
+        let staged = generations.stage()?;
+        Ok((generations, staged, active))
+    }
+
+    /// Swap in a staged generation if it synced, or discard it
+    fn finish_generation(
+        &self,
+        repo: &RepositoryConfig,
+        generations: Generations,
+        staged: StagedGeneration,
+        synced: Result<()>,
+    ) -> Result<()> {
+        if let Err(e) = synced {
+            generations.discard(staged);
+            return Err(e);
+        }
+        let id = staged.id;
+        generations.commit(staged)?;
+        info!("Repository {} synced to generation {}", repo.name, id);
+        Ok(())
+    }
+
     async fn sync_git(&self, repo: &RepositoryConfig) -> Result<()> {
-        let repo_path = &repo.location;
-
-        if repo_path.exists() {
-            // Pull updates
-            let output = Command::new("git")
-                .args(["pull", "--ff-only"])
-                .current_dir(repo_path)
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .output()
-                .await
-                .map_err(|e| Error::RepositoryError(format!("Git pull failed: {}", e)))?;
-
-            if !output.status.success() {
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                return Err(Error::RepositoryError(format!(
-                    "Git pull failed: {}",
-                    stderr
-                )));
+        let (generations, staged, active) = self.begin_generation(repo)?;
+        let staging = staged.path.to_string_lossy().to_string();
+
+        let synced = match &active {
+            // Pull updates into a copy of the active generation
+            Some(active) => {
+                let copied = run_sync(
+                    Command::new("cp").args([
+                        "-a",
+                        "--reflink=auto",
+                        &format!("{}/.", active.display()),
+                        &staging,
+                    ]),
+                    "Copying repository",
+                )
+                .await;
+                match copied {
+                    Ok(()) => {
+                        run_sync(
+                            Command::new("git")
+                                .args(["pull", "--ff-only"])
+                                .current_dir(&staged.path),
+                            "Git pull",
+                        )
+                        .await
+                    }
+                    Err(e) => Err(e),
+                }
             }
-        } else {
-            // Clone
-            let output = Command::new("git")
-                .args(["clone", &repo.sync_uri, repo_path.to_str().unwrap()])
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .output()
+            None => {
+                run_sync(
+                    Command::new("git").args(["clone", &repo.sync_uri, &staging]),
+                    "Git clone",
+                )
                 .await
-                .map_err(|e| Error::RepositoryError(format!("Git clone failed: {}", e)))?;
-
-            if !output.status.success() {
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                return Err(Error::RepositoryError(format!(
-                    "Git clone failed: {}",
-                    stderr
-                )));
             }
-        }
+        };
 
-        info!("Repository {} synced successfully", repo.name);
-        Ok(())
+        self.finish_generation(repo, generations, staged, synced)
     }
 
     async fn sync_rsync(&self, repo: &RepositoryConfig) -> Result<()> {
-        let repo_path = &repo.location;
-        std::fs::create_dir_all(repo_path)?;
-
-        let output = Command::new("rsync")
-            .args([
-                "-av",
-                "--delete",
-                &repo.sync_uri,
-                repo_path.to_str().unwrap(),
-            ])
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
-            .await
-            .map_err(|e| Error::RepositoryError(format!("Rsync failed: {}", e)))?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(Error::RepositoryError(format!("Rsync failed: {}", stderr)));
+        let (generations, staged, active) = self.begin_generation(repo)?;
+
+        // Unchanged files are hard links into the active generation; rsync
+        // replaces changed files rather than writing through the links
+        let mut cmd = Command::new("rsync");
+        cmd.args(["-av", "--delete"]);
+        if let Some(active) = &active {
+            cmd.arg(format!("--link-dest={}", active.display()));
         }
+        cmd.arg(&repo.sync_uri)
+            .arg(format!("{}/", staged.path.display()));
+        let synced = run_sync(&mut cmd, "Rsync").await;
 
-        Ok(())
+        self.finish_generation(repo, generations, staged, synced)
     }
 
     async fn sync_http(&self, repo: &RepositoryConfig) -> Result<()> {
@@ -166,21 +192,28 @@ impl RepositoryManager {
             .await
             .map_err(|e| Error::RepositoryError(format!("Failed to read index: {}", e)))?;
 
-        // Save index
+        // Save index, replacing the old one in one rename
         let index_path = self.cache_dir.join(format!("{}.json", repo.name));
-        std::fs::write(&index_path, &index_data)?;
+        let tmp_path = self.cache_dir.join(format!(".{}.json.tmp", repo.name));
+        std::fs::write(&tmp_path, &index_data)?;
+        std::fs::rename(&tmp_path, &index_path)?;
 
         Ok(())
     }
 
     /// Search for packages
-    pub async fn search(&self, query: &str) -> Result<Vec<PackageInfo>> {
-        let mut results = Vec::new();
-
-        for repo in &self.repos {
-            let packages = self.search_repo(repo, query).await?;
-            results.extend(packages);
-        }
+    pub async fn search(&self, query: &str) -> Result<Vec<PackageHeader>> {
+        let query_lower = query.to_lowercase();
+        let mut results: Vec<PackageHeader> = self
+            .headers()
+            .await?
+            .into_iter()
+            .filter(|pkg| {
+                pkg.id.name.to_lowercase().contains(&query_lower)
+                    || pkg.id.category.to_lowercase().contains(&query_lower)
+                    || pkg.description.to_lowercase().contains(&query_lower)
+            })
+            .collect();
 
         // Sort by relevance (name match first)
         results.sort_by(|a, b| {
@@ -197,51 +230,138 @@ impl RepositoryManager {
         Ok(results)
     }
 
-    async fn search_repo(&self, repo: &RepositoryConfig, query: &str) -> Result<Vec<PackageInfo>> {
-        let packages = self.load_repo_packages(repo).await?;
-        let query_lower = query.to_lowercase();
+    /// Headers of every available package, in repository order
+    pub async fn headers(&self) -> Result<Vec<PackageHeader>> {
+        let mut headers = Vec::new();
+        for repo in &self.repos {
+            headers.extend(self.load_repo_headers(repo).await?);
+        }
+        Ok(headers)
+    }
 
-        Ok(packages
+    /// Full record of a package from its header
+    pub async fn details(&self, header: &PackageHeader) -> Result<Option<PackageInfo>> {
+        Ok(self
+            .details_of(std::slice::from_ref(header))
+            .await?
             .into_iter()
-            .filter(|pkg| {
-                pkg.id.name.to_lowercase().contains(&query_lower)
-                    || pkg.id.category.to_lowercase().contains(&query_lower)
-                    || pkg.description.to_lowercase().contains(&query_lower)
-            })
+            .next())
+    }
+
+    /// Full records of several packages, in the order of their headers
+    ///
+    /// Packages whose repository no longer offers them are left out.
+    /// Repositories without per-package metadata files are scanned at most
+    /// once.
+    pub async fn details_of(&self, headers: &[PackageHeader]) -> Result<Vec<PackageInfo>> {
+        Ok(self
+            .load_details(headers)
+            .await?
+            .into_iter()
+            .map(|(_, pkg)| pkg)
             .collect())
     }
 
+    async fn load_details<'a>(
+        &self,
+        headers: &'a [PackageHeader],
+    ) -> Result<Vec<(&'a PackageHeader, PackageInfo)>> {
+        let mut scanned: HashMap<&str, Vec<PackageInfo>> = HashMap::new();
+        let mut details = Vec::new();
+        for header in headers {
+            if let Some(path) = &header.metadata {
+                match self.load_package_metadata(path, &header.id.category, &header.id.name) {
+                    Ok(pkg) if pkg.version == header.version => details.push((header, pkg)),
+                    Ok(_) => {}
+                    Err(e) => warn!("Failed to load {}: {}", header.id, e),
+                }
+                continue;
+            }
+            if !scanned.contains_key(header.repository.as_str()) {
+                let packages = match self.repos.iter().find(|r| r.name == header.repository) {
+                    Some(repo) => self.load_repo_packages(repo).await?,
+                    None => Vec::new(),
+                };
+                scanned.insert(&header.repository, packages);
+            }
+            details.extend(
+                scanned[header.repository.as_str()]
+                    .iter()
+                    .find(|p| p.id == header.id && p.version == header.version)
+                    .map(|pkg| (header, pkg.clone())),
+            );
+        }
+        Ok(details)
+    }
+
     /// Get package information
     pub async fn get_info(&self, name: &str) -> Result<Option<PackageInfo>> {
-        for repo in &self.repos {
-            let packages = self.load_repo_packages(repo).await?;
-            if let Some(pkg) = packages.into_iter().find(|p| p.id.name == name) {
-                return Ok(Some(pkg));
-            }
+        match self
+            .headers()
+            .await?
+            .into_iter()
+            .find(|p| p.id.name == name)
+        {
+            Some(header) => self.details(&header).await,
+            None => Ok(None),
         }
-        Ok(None)
     }
 
     /// Get latest version of a package
     pub async fn get_latest(&self, name: &str) -> Result<Option<PackageInfo>> {
-        let mut best: Option<PackageInfo> = None;
+        let mut best: Option<PackageHeader> = None;
 
-        for repo in &self.repos {
-            let packages = self.load_repo_packages(repo).await?;
-            for pkg in packages {
-                if pkg.id.name == name {
-                    if let Some(ref current) = best {
-                        if pkg.version > current.version {
-                            best = Some(pkg);
-                        }
-                    } else {
-                        best = Some(pkg);
+        for header in self.headers().await? {
+            if header.id.name == name {
+                if let Some(ref current) = best {
+                    if header.version > current.version {
+                        best = Some(header);
                     }
+                } else {
+                    best = Some(header);
                 }
             }
         }
 
-        Ok(best)
+        match best {
+            Some(header) => self.details(&header).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Get every version of a package, with the repository offering it
+    pub async fn get_versions(&self, name: &str) -> Result<Vec<(String, PackageInfo)>> {
+        let matching: Vec<PackageHeader> = self
+            .headers()
+            .await?
+            .into_iter()
+            .filter(|p| p.id.name == name || p.id.full_name() == name)
+            .collect();
+
+        let mut versions: Vec<(String, PackageInfo)> = self
+            .load_details(&matching)
+            .await?
+            .into_iter()
+            .map(|(header, pkg)| (header.repository.clone(), pkg))
+            .collect();
+
+        versions.sort_by(|a, b| b.1.version.cmp(&a.1.version));
+        Ok(versions)
+    }
+
+    /// Name of the repository whose tree holds a package's build definition
+    ///
+    /// Repositories are searched in the order they are resolved from.
+    pub fn repository_of(&self, pkg: &PackageInfo) -> Option<String> {
+        let target = pkg
+            .buck_target
+            .split_once("//")
+            .map_or(pkg.buck_target.as_str(), |(_, path)| path);
+        let package_dir = target.split(':').next().filter(|d| !d.is_empty())?;
+        self.repos
+            .iter()
+            .find(|repo| repo.location.join(package_dir).is_dir())
+            .map(|repo| repo.name.clone())
     }
 
     /// Get all available packages
@@ -256,17 +376,68 @@ impl RepositoryManager {
         Ok(all_packages)
     }
 
+    /// Load package headers from a repository
+    ///
+    /// Mirrors [`Self::load_repo_packages`], but reads only the header
+    /// fields of metadata files.
+    async fn load_repo_headers(&self, repo: &RepositoryConfig) -> Result<Vec<PackageHeader>> {
+        let root = active_path(&repo.location);
+        let packages_dir = root.join("packages");
+
+        if !packages_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        // BUCK files carry no dependency lists, so their packages are
+        // already light
+        if let Ok(buck_packages) = self.scan_buck_packages(&root).await {
+            if !buck_packages.is_empty() {
+                return Ok(buck_packages
+                    .iter()
+                    .map(|pkg| PackageHeader {
+                        id: pkg.id.clone(),
+                        version: pkg.version.clone(),
+                        slot: pkg.slot.clone(),
+                        description: pkg.description.clone(),
+                        size: pkg.size,
+                        repository: repo.name.clone(),
+                        metadata: None,
+                    })
+                    .collect());
+            }
+        }
+
+        scan_metadata_files(&packages_dir, |path, category, name| {
+            let metadata: PackageMetadataHeader =
+                serde_json::from_str(&std::fs::read_to_string(path)?)?;
+            Ok(PackageHeader {
+                id: PackageId::new(category, name),
+                version: semver::Version::parse(&metadata.version)
+                    .map_err(|_| Error::InvalidVersion(metadata.version.clone()))?,
+                slot: metadata.slot.unwrap_or_else(|| "0".to_string()),
+                description: metadata.description,
+                size: metadata.size.unwrap_or(0),
+                repository: repo.name.clone(),
+                metadata: Some(path.to_path_buf()),
+            })
+        })
+    }
+
     /// Load packages from a repository
     async fn load_repo_packages(&self, repo: &RepositoryConfig) -> Result<Vec<PackageInfo>> {
+        // Resolve the active generation once so a concurrent sync cannot
+        // swap the tree out from under the scan
+        let root = active_path(&repo.location);
+
         // Look for package metadata in the repository
-        let packages_dir = repo.location.join("packages");
+        let packages_dir = root.join("packages");
 
         if !packages_dir.exists() {
             return Ok(Vec::new());
         }
 
         // Try to use Buck2 to scan packages first (for buckos-build style repos)
-        if let Ok(buck_packages) = self.scan_buck_packages(&repo.location).await {
+        if let Ok(buck_packages) = self.scan_buck_packages(&root).await {
             if !buck_packages.is_empty() {
                 return Ok(buck_packages);
             }
@@ -278,39 +449,9 @@ impl RepositoryManager {
 
     /// Scan packages from metadata.json files
     async fn scan_metadata_packages(&self, packages_dir: &Path) -> Result<Vec<PackageInfo>> {
-        let mut packages = Vec::new();
-
-        // Walk through category directories
-        for category_entry in std::fs::read_dir(packages_dir)? {
-            let category_entry = category_entry?;
-            if !category_entry.file_type()?.is_dir() {
-                continue;
-            }
-
-            let category = category_entry.file_name().to_string_lossy().to_string();
-
-            // Walk through package directories
-            for pkg_entry in std::fs::read_dir(category_entry.path())? {
-                let pkg_entry = pkg_entry?;
-                if !pkg_entry.file_type()?.is_dir() {
-                    continue;
-                }
-
-                let pkg_name = pkg_entry.file_name().to_string_lossy().to_string();
-                let metadata_path = pkg_entry.path().join("metadata.json");
-
-                if metadata_path.exists() {
-                    match self.load_package_metadata(&metadata_path, &category, &pkg_name) {
-                        Ok(pkg) => packages.push(pkg),
-                        Err(e) => {
-                            warn!("Failed to load {}/{}: {}", category, pkg_name, e);
-                        }
-                    }
-                }
-            }
-        }
-
-        Ok(packages)
+        scan_metadata_files(packages_dir, |path, category, name| {
+            self.load_package_metadata(path, category, name)
+        })
     }
 
     /// Scan packages from buckos-build repository by walking BUCK files
@@ -475,6 +616,8 @@ impl RepositoryManager {
                     installed_size: 0,
                     required_use: String::new(),
                     blockers: Vec::new(),
+                    any_of_dependencies: Vec::new(),
+                    restrict: Vec::new(),
                 });
             }
         }
@@ -519,6 +662,8 @@ impl RepositoryManager {
             installed_size: metadata.installed_size.unwrap_or(0),
             required_use: metadata.required_use.unwrap_or_default(),
             blockers: metadata.blockers,
+            any_of_dependencies: metadata.any_of_dependencies,
+            restrict: metadata.restrict,
         })
     }
 
@@ -544,6 +689,66 @@ impl RepositoryManager {
     }
 }
 
+/// Load every `<category>/<name>/metadata.json` under `packages_dir`,
+/// skipping packages that fail to load
+fn scan_metadata_files<T>(
+    packages_dir: &Path,
+    load: impl Fn(&Path, &str, &str) -> Result<T>,
+) -> Result<Vec<T>> {
+    let mut packages = Vec::new();
+
+    // Walk through category directories
+    for category_entry in std::fs::read_dir(packages_dir)? {
+        let category_entry = category_entry?;
+        if !category_entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        let category = category_entry.file_name().to_string_lossy().to_string();
+
+        // Walk through package directories
+        for pkg_entry in std::fs::read_dir(category_entry.path())? {
+            let pkg_entry = pkg_entry?;
+            if !pkg_entry.file_type()?.is_dir() {
+                continue;
+            }
+
+            let pkg_name = pkg_entry.file_name().to_string_lossy().to_string();
+            let metadata_path = pkg_entry.path().join("metadata.json");
+
+            if metadata_path.exists() {
+                match load(&metadata_path, &category, &pkg_name) {
+                    Ok(pkg) => packages.push(pkg),
+                    Err(e) => {
+                        warn!("Failed to load {}/{}: {}", category, pkg_name, e);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(packages)
+}
+
+/// Run a sync command, failing with its stderr
+async fn run_sync(cmd: &mut Command, what: &str) -> Result<()> {
+    let output = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .await
+        .map_err(|e| Error::RepositoryError(format!("{} failed: {}", what, e)))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::RepositoryError(format!(
+            "{} failed: {}",
+            what, stderr
+        )));
+    }
+    Ok(())
+}
+
 /// Package metadata from repository
 #[derive(Debug, serde::Deserialize)]
 struct PackageMetadata {
@@ -569,4 +774,89 @@ struct PackageMetadata {
     required_use: Option<String>,
     #[serde(default)]
     blockers: Vec<String>,
+    #[serde(default)]
+    any_of_dependencies: Vec<String>,
+    #[serde(default)]
+    restrict: Vec<String>,
+}
+
+/// The header fields of [`PackageMetadata`]; the other fields are skipped
+/// without being parsed into values
+#[derive(Debug, serde::Deserialize)]
+struct PackageMetadataHeader {
+    version: String,
+    description: String,
+    slot: Option<String>,
+    size: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_package(repo: &Path, category: &str, name: &str, version: &str) {
+        let dir = repo.join("packages").join(category).join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        let metadata = serde_json::json!({
+            "version": version,
+            "description": format!("The {} package", name),
+            "license": "MIT",
+            "keywords": ["amd64"],
+            "use_flags": {"ssl": "Enable TLS"},
+            "dependencies": ["dev-libs/openssl"],
+        });
+        std::fs::write(dir.join("metadata.json"), metadata.to_string()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_headers_and_details() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config {
+            cache_dir: dir.path().join("cache"),
+            ..Default::default()
+        };
+        config.repositories = ["main", "extra"]
+            .iter()
+            .map(|name| RepositoryConfig {
+                name: name.to_string(),
+                location: dir.path().join(name),
+                ..Default::default()
+            })
+            .collect();
+        write_package(&dir.path().join("main"), "app-misc", "foo", "1.0.0");
+        write_package(&dir.path().join("main"), "app-misc", "bar", "1.0.0");
+        write_package(&dir.path().join("extra"), "app-misc", "foo", "2.0.0");
+        let repos = RepositoryManager::new(&config).unwrap();
+
+        let found = repos.search("foo").await.unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].description, "The foo package");
+        assert!(found.iter().all(|h| h.metadata.is_some()));
+
+        let latest = repos.get_latest("foo").await.unwrap().unwrap();
+        assert_eq!(latest.version, semver::Version::new(2, 0, 0));
+        assert_eq!(
+            latest.dependencies[0].package.full_name(),
+            "dev-libs/openssl"
+        );
+        assert_eq!(latest.use_flags[0].name, "ssl");
+
+        let versions = repos.get_versions("app-misc/foo").await.unwrap();
+        let versions: Vec<(&str, String)> = versions
+            .iter()
+            .map(|(repo, pkg)| (repo.as_str(), pkg.version.to_string()))
+            .collect();
+        assert_eq!(
+            versions,
+            vec![
+                ("extra", "2.0.0".to_string()),
+                ("main", "1.0.0".to_string())
+            ]
+        );
+
+        // A header whose version the repository no longer offers
+        let mut stale = found[0].clone();
+        stale.version = semver::Version::new(0, 9, 0);
+        assert!(repos.details(&stale).await.unwrap().is_none());
+    }
 }
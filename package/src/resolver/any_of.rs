@@ -0,0 +1,463 @@
+//! Any-of dependency groups
+//!
+//! Handles `|| ( a b c )` dependency groups where any one alternative
+//! satisfies the dependency, e.g. `|| ( dev-libs/openssl dev-libs/libressl )`.
+//!
+//! Alternatives are ranked by a deterministic preference function:
+//!
+//! 1. Already installed
+//! 2. Preferred by the profile
+//! 3. Has a stable keyword
+//! 4. Highest version among the alternatives
+//!
+//! Ties are broken by the order of the alternatives in the group, so the
+//! leftmost alternative wins when nothing else distinguishes them.
+
+use crate::resolver::BlockerResolver;
+use crate::{Error, PackageId, PackageInfo, Result, VersionSpec};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// A single alternative within an any-of group
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnyOfAtom {
+    /// Package providing the alternative
+    pub package: PackageId,
+    /// Version constraint
+    pub version: VersionSpec,
+}
+
+/// An any-of dependency group
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnyOfGroup {
+    /// Alternatives in declaration order
+    pub alternatives: Vec<AnyOfAtom>,
+}
+
+impl AnyOfGroup {
+    /// Parse a group string (e.g., "|| ( dev-libs/openssl >=dev-libs/libressl-3.0 )")
+    pub fn parse(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidPackageSpec(s.to_string());
+
+        let inner = s
+            .trim()
+            .strip_prefix("||")
+            .map(str::trim)
+            .and_then(|rest| rest.strip_prefix('('))
+            .and_then(|rest| rest.strip_suffix(')'))
+            .ok_or_else(invalid)?;
+
+        let mut alternatives = Vec::new();
+        for token in inner.split_whitespace() {
+            if token.contains('(') || token.contains(')') || token.starts_with("||") {
+                // Nested groups are not supported
+                return Err(invalid());
+            }
+            alternatives.push(parse_atom(token).ok_or_else(invalid)?);
+        }
+
+        if alternatives.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(Self { alternatives })
+    }
+
+    /// Check whether a package satisfies any alternative of this group
+    pub fn is_satisfied_by(&self, pkg: &PackageInfo) -> bool {
+        self.alternatives
+            .iter()
+            .any(|a| a.package == pkg.id && a.version.matches(&pkg.version))
+    }
+}
+
+impl std::fmt::Display for AnyOfGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "|| (")?;
+        for alt in &self.alternatives {
+            write!(f, " {}", alt.package)?;
+        }
+        write!(f, " )")
+    }
+}
+
+/// Parse a single atom with an optional version operator
+fn parse_atom(token: &str) -> Option<AnyOfAtom> {
+    for op in [">=", "<=", ">", "<", "="] {
+        if let Some(rest) = token.strip_prefix(op) {
+            let version = BlockerResolver::parse_versioned_atom(rest, op).ok()?;
+            let name = strip_version(rest);
+            return Some(AnyOfAtom {
+                package: PackageId::parse(name)?,
+                version,
+            });
+        }
+    }
+
+    Some(AnyOfAtom {
+        package: PackageId::parse(token)?,
+        version: VersionSpec::Any,
+    })
+}
+
+/// Strip a trailing "-<version>" from an atom
+fn strip_version(s: &str) -> &str {
+    let idx = s.char_indices().rev().find(|&(i, c)| {
+        c == '-'
+            && s[i + 1..]
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_digit())
+    });
+    match idx {
+        Some((i, _)) => &s[..i],
+        None => s,
+    }
+}
+
+/// Weights applied to each preference criterion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnyOfWeights {
+    /// Alternative is already installed
+    pub installed: i64,
+    /// Alternative is preferred by the profile
+    pub profile: i64,
+    /// Alternative has a stable keyword
+    pub stable: i64,
+    /// Alternative has the highest version in the group
+    pub highest_version: i64,
+}
+
+impl Default for AnyOfWeights {
+    fn default() -> Self {
+        Self {
+            installed: 1000,
+            profile: 100,
+            stable: 10,
+            highest_version: 1,
+        }
+    }
+}
+
+/// Policy used to choose between alternatives
+#[derive(Debug, Clone, Default)]
+pub struct AnyOfPolicy {
+    /// Criterion weights
+    pub weights: AnyOfWeights,
+    /// Names of installed packages
+    pub installed: HashSet<PackageId>,
+    /// Packages the profile prefers as providers
+    pub preferred: HashSet<PackageId>,
+}
+
+/// Outcome of choosing an alternative for an any-of group
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnyOfChoice {
+    /// Package that declared the group
+    pub parent: PackageId,
+    /// The group as written
+    pub group: String,
+    /// Selected alternative
+    pub chosen: PackageId,
+    /// Selected version
+    pub version: semver::Version,
+    /// Score of the selected alternative
+    pub score: i64,
+    /// Reasons the selected alternative scored as it did
+    pub reasons: Vec<String>,
+    /// Other available alternatives with their scores
+    pub rejected: Vec<(PackageId, i64)>,
+}
+
+impl AnyOfChoice {
+    /// Human-readable explanation of the choice
+    pub fn explain(&self) -> String {
+        let why = if self.reasons.is_empty() {
+            "first listed alternative".to_string()
+        } else {
+            self.reasons.join(", ")
+        };
+        let mut line = format!(
+            "{}: {} chose {}-{} ({})",
+            self.parent, self.group, self.chosen, self.version, why
+        );
+        if !self.rejected.is_empty() {
+            let others: Vec<String> = self
+                .rejected
+                .iter()
+                .map(|(id, score)| format!("{} [{}]", id, score))
+                .collect();
+            line.push_str(&format!(" over {}", others.join(", ")));
+        }
+        line
+    }
+}
+
+impl AnyOfPolicy {
+    /// Create a policy with default weights
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set custom weights
+    pub fn with_weights(mut self, weights: AnyOfWeights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// Set installed packages
+    pub fn with_installed(mut self, installed: impl IntoIterator<Item = PackageId>) -> Self {
+        self.installed = installed.into_iter().collect();
+        self
+    }
+
+    /// Set profile-preferred packages
+    pub fn with_preferred(mut self, preferred: impl IntoIterator<Item = PackageId>) -> Self {
+        self.preferred = preferred.into_iter().collect();
+        self
+    }
+
+    /// Choose an alternative from a group
+    ///
+    /// Returns `None` if no alternative is available in `available`.
+    pub fn choose(
+        &self,
+        parent: &PackageId,
+        group: &AnyOfGroup,
+        available: &[PackageInfo],
+    ) -> Option<AnyOfChoice> {
+        // Best matching version for each alternative, in declaration order
+        let candidates: Vec<&PackageInfo> = group
+            .alternatives
+            .iter()
+            .filter_map(|alt| {
+                available
+                    .iter()
+                    .filter(|p| p.id == alt.package && alt.version.matches(&p.version))
+                    .max_by(|a, b| a.version.cmp(&b.version))
+            })
+            .collect();
+
+        let highest = candidates.iter().map(|p| &p.version).max()?;
+
+        let scored: Vec<(&PackageInfo, i64, Vec<String>)> = candidates
+            .iter()
+            .map(|pkg| {
+                let (score, reasons) = self.score(pkg, highest);
+                (*pkg, score, reasons)
+            })
+            .collect();
+
+        // Highest score wins; earliest alternative wins ties
+        let mut best = 0;
+        for (idx, (_, score, _)) in scored.iter().enumerate() {
+            if *score > scored[best].1 {
+                best = idx;
+            }
+        }
+
+        let (pkg, score, reasons) = scored[best].clone();
+        let rejected = scored
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| *idx != best)
+            .map(|(_, (p, s, _))| (p.id.clone(), *s))
+            .collect();
+
+        Some(AnyOfChoice {
+            parent: parent.clone(),
+            group: group.to_string(),
+            chosen: pkg.id.clone(),
+            version: pkg.version.clone(),
+            score,
+            reasons,
+            rejected,
+        })
+    }
+
+    fn score(&self, pkg: &PackageInfo, highest: &semver::Version) -> (i64, Vec<String>) {
+        let mut score = 0;
+        let mut reasons = Vec::new();
+
+        if self.installed.contains(&pkg.id) {
+            score += self.weights.installed;
+            reasons.push("installed".to_string());
+        }
+        if self.preferred.contains(&pkg.id) {
+            score += self.weights.profile;
+            reasons.push("profile preference".to_string());
+        }
+        if is_stable(pkg) {
+            score += self.weights.stable;
+            reasons.push("stable".to_string());
+        }
+        if pkg.version == *highest {
+            score += self.weights.highest_version;
+            reasons.push("highest version".to_string());
+        }
+
+        (score, reasons)
+    }
+}
+
+/// Chosen providers by the package that declared their group, to order
+/// each package after the alternatives it pulled in
+pub fn chosen_providers(choices: &[AnyOfChoice]) -> HashMap<PackageId, Vec<PackageId>> {
+    let mut providers: HashMap<PackageId, Vec<PackageId>> = HashMap::new();
+    for choice in choices {
+        providers
+            .entry(choice.parent.clone())
+            .or_default()
+            .push(choice.chosen.clone());
+    }
+    providers
+}
+
+/// The alternatives a solver selected for the any-of groups of `selected`,
+/// scored by `policy`
+///
+/// A group with several alternatives selected reports the one `policy`
+/// prefers among them.
+pub fn selected_choices(policy: &AnyOfPolicy, selected: &[PackageInfo]) -> Vec<AnyOfChoice> {
+    let mut choices = Vec::new();
+    for pkg in selected {
+        for group_str in &pkg.any_of_dependencies {
+            let Ok(group) = AnyOfGroup::parse(group_str) else {
+                continue;
+            };
+            if let Some(choice) = policy.choose(&pkg.id, &group, selected) {
+                choices.push(choice);
+            }
+        }
+    }
+    choices
+}
+
+/// A package is stable if it has at least one keyword not marked testing (~)
+fn is_stable(pkg: &PackageInfo) -> bool {
+    pkg.keywords
+        .iter()
+        .any(|k| !k.starts_with('~') && !k.starts_with('-'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pkg(category: &str, name: &str, version: &str, keywords: &[&str]) -> PackageInfo {
+        PackageInfo {
+            id: PackageId::new(category, name),
+            version: semver::Version::parse(version).unwrap(),
+            slot: "0".to_string(),
+            description: String::new(),
+            homepage: None,
+            license: "MIT".to_string(),
+            keywords: keywords.iter().map(|k| k.to_string()).collect(),
+            use_flags: Vec::new(),
+            dependencies: Vec::new(),
+            build_dependencies: Vec::new(),
+            runtime_dependencies: Vec::new(),
+            source_url: None,
+            source_hash: None,
+            buck_target: String::new(),
+            size: 0,
+            installed_size: 0,
+            required_use: String::new(),
+            blockers: Vec::new(),
+            any_of_dependencies: Vec::new(),
+            restrict: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_group() {
+        let group = AnyOfGroup::parse("|| ( dev-libs/openssl >=dev-libs/libressl-3.0 )").unwrap();
+        assert_eq!(group.alternatives.len(), 2);
+        assert_eq!(group.alternatives[0].package.name, "openssl");
+        assert_eq!(group.alternatives[1].package.name, "libressl");
+        assert_eq!(
+            group.alternatives[1].version,
+            VersionSpec::GreaterThanOrEqual(semver::Version::new(3, 0, 0))
+        );
+
+        assert!(AnyOfGroup::parse("dev-libs/openssl").is_err());
+        assert!(AnyOfGroup::parse("|| ( )").is_err());
+        assert!(AnyOfGroup::parse("|| ( a/b || ( c/d ) )").is_err());
+    }
+
+    #[test]
+    fn test_installed_beats_everything() {
+        let group = AnyOfGroup::parse("|| ( dev-libs/openssl dev-libs/libressl )").unwrap();
+        let available = vec![
+            pkg("dev-libs", "openssl", "3.0.0", &["amd64"]),
+            pkg("dev-libs", "libressl", "3.8.0", &["~amd64"]),
+        ];
+        let parent = PackageId::new("net-misc", "curl");
+
+        let policy = AnyOfPolicy::new()
+            .with_installed([PackageId::new("dev-libs", "libressl")])
+            .with_preferred([PackageId::new("dev-libs", "openssl")]);
+        let choice = policy.choose(&parent, &group, &available).unwrap();
+        assert_eq!(choice.chosen.name, "libressl");
+        assert!(choice.reasons.contains(&"installed".to_string()));
+        assert_eq!(choice.rejected.len(), 1);
+    }
+
+    #[test]
+    fn test_stable_and_tie_break() {
+        let group = AnyOfGroup::parse("|| ( dev-libs/openssl dev-libs/libressl )").unwrap();
+        let parent = PackageId::new("net-misc", "curl");
+
+        // Stable outweighs highest version
+        let available = vec![
+            pkg("dev-libs", "openssl", "3.0.0", &["~amd64"]),
+            pkg("dev-libs", "libressl", "2.0.0", &["amd64"]),
+        ];
+        let choice = AnyOfPolicy::new()
+            .choose(&parent, &group, &available)
+            .unwrap();
+        assert_eq!(choice.chosen.name, "libressl");
+
+        // Equal scores fall back to declaration order
+        let weights = AnyOfWeights {
+            highest_version: 0,
+            ..Default::default()
+        };
+        let available = vec![
+            pkg("dev-libs", "openssl", "1.0.0", &["amd64"]),
+            pkg("dev-libs", "libressl", "2.0.0", &["amd64"]),
+        ];
+        let choice = AnyOfPolicy::new()
+            .with_weights(weights)
+            .choose(&parent, &group, &available)
+            .unwrap();
+        assert_eq!(choice.chosen.name, "openssl");
+    }
+
+    #[test]
+    fn test_chosen_provider_ordered_first() {
+        // The provider sorts after its dependent by name
+        let mut parent = pkg("app-misc", "aaa", "1.0.0", &["amd64"]);
+        parent.any_of_dependencies = vec!["|| ( dev-libs/zzz dev-libs/yyy )".to_string()];
+        let selected = vec![parent, pkg("dev-libs", "zzz", "1.0.0", &["amd64"])];
+
+        let choices = selected_choices(&AnyOfPolicy::new(), &selected);
+        assert_eq!(choices.len(), 1);
+        assert_eq!(choices[0].chosen, PackageId::new("dev-libs", "zzz"));
+
+        let providers = chosen_providers(&choices);
+        let order = crate::resolver::stable_order(selected, |p| {
+            providers.get(&p.id).cloned().unwrap_or_default()
+        })
+        .unwrap();
+        let names: Vec<&str> = order.iter().map(|p| p.id.name.as_str()).collect();
+        assert_eq!(names, vec!["zzz", "aaa"]);
+    }
+
+    #[test]
+    fn test_no_alternative_available() {
+        let group = AnyOfGroup::parse("|| ( dev-libs/openssl dev-libs/libressl )").unwrap();
+        let parent = PackageId::new("net-misc", "curl");
+        assert!(AnyOfPolicy::new().choose(&parent, &group, &[]).is_none());
+    }
+}
@@ -142,7 +142,7 @@ impl BlockerResolver {
         })
     }
 
-    fn parse_versioned_atom(s: &str, op: &str) -> Result<VersionSpec> {
+    pub(crate) fn parse_versioned_atom(s: &str, op: &str) -> Result<VersionSpec> {
         // Find version part (after last hyphen followed by digit)
         let mut last_dash = None;
         for (i, c) in s.char_indices() {
@@ -0,0 +1,265 @@
+//! Time limits, cancellation and statistics of dependency resolution
+//!
+//! Resolution checks its [`ResolveBudget`] as it goes and stops at the
+//! configured timeout, or at Ctrl-C when the budget catches interrupts.
+//! Either way it fails with [`Error::ResolutionStopped`] explaining how far
+//! it got instead of hanging or killing the process. The SAT solver is
+//! checked through a proof processor, which makes varisat verify its proof
+//! as it solves; that costs some speed but is the only point at which a
+//! running solve can be stopped, so it is attached only when the budget
+//! has a timeout or catches interrupts.
+//!
+//! ```toml
+//! [resolver]
+//! timeout = 120   # seconds; unlimited when unset
+//! ```
+
+use crate::interrupt::Cancellation;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, Instant};
+use varisat::checker::{CheckedProofStep, CheckerData, ProofProcessor};
+
+/// `[resolver]` settings
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResolverConfig {
+    /// Seconds resolution may take; unlimited when unset
+    pub timeout: Option<u64>,
+}
+
+/// What resolution did, for diagnosing slow or failing resolutions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ResolveStats {
+    /// Packages examined
+    pub visited: usize,
+    /// Choices among alternatives, such as any-of groups
+    pub decisions: usize,
+    /// Variables of the SAT formula
+    pub variables: usize,
+    /// Clauses of the SAT formula
+    pub clauses: usize,
+    /// Conflicts the SAT solver learned a clause from
+    pub conflicts: u64,
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for ResolveStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} packages visited, {} decisions",
+            self.visited, self.decisions
+        )?;
+        if self.clauses > 0 {
+            write!(
+                f,
+                ", {} variables, {} clauses, {} conflicts",
+                self.variables, self.clauses, self.conflicts
+            )?;
+        }
+        write!(f, " in {:.2}s", self.elapsed.as_secs_f64())
+    }
+}
+
+/// Why resolution stopped early
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    TimedOut(Duration),
+    Interrupted,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::TimedOut(limit) => write!(f, "timed out after {}s", limit.as_secs()),
+            StopReason::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+/// Limits one resolution runs under; clones share cancellation
+#[derive(Clone)]
+pub struct ResolveBudget {
+    started: Instant,
+    timeout: Option<Duration>,
+    cancellation: Cancellation,
+}
+
+impl Default for ResolveBudget {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl ResolveBudget {
+    /// Start the clock, stopping after `timeout` if given
+    pub fn new(timeout: Option<Duration>) -> Self {
+        Self {
+            started: Instant::now(),
+            timeout,
+            cancellation: Cancellation::new(),
+        }
+    }
+
+    /// Budget of the `[resolver]` settings
+    pub fn from_config(config: &ResolverConfig) -> Self {
+        Self::new(config.timeout.map(Duration::from_secs))
+    }
+
+    /// Stop at Ctrl-C rather than letting it kill the process, until the
+    /// last clone of the budget is dropped
+    pub fn catch_interrupts(mut self) -> Self {
+        self.cancellation = self.cancellation.catch_interrupt();
+        self
+    }
+
+    /// Whether a running SAT solve can be stopped: the budget has a
+    /// timeout or catches interrupts
+    pub fn is_bounded(&self) -> bool {
+        self.timeout.is_some() || self.cancellation.catches_signals()
+    }
+
+    /// Stop resolution at its next check
+    pub fn cancel(&self) {
+        self.cancellation.cancel();
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Why resolution has to stop, if it does
+    pub fn check(&self) -> Option<StopReason> {
+        if self.cancellation.is_cancelled() {
+            return Some(StopReason::Interrupted);
+        }
+        self.timeout
+            .filter(|limit| self.started.elapsed() >= *limit)
+            .map(StopReason::TimedOut)
+    }
+
+    /// [`check`](Self::check) as an error explaining the progress so far
+    pub fn check_or_explain(
+        &self,
+        stats: &ResolveStats,
+        explain: impl FnOnce() -> Vec<String>,
+    ) -> crate::Result<()> {
+        match self.check() {
+            Some(reason) => Err(stopped(reason, stats, explain())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Error of a resolution stopped early
+pub(crate) fn stopped(reason: StopReason, stats: &ResolveStats, lines: Vec<String>) -> Error {
+    let mut explanation = format!("{}", stats);
+    for line in lines {
+        explanation.push_str("\n  ");
+        explanation.push_str(&line);
+    }
+    Error::ResolutionStopped {
+        reason: reason.to_string(),
+        explanation,
+    }
+}
+
+/// Counts SAT solver progress and stops it when the budget runs out
+pub(crate) struct SolveMonitor {
+    budget: ResolveBudget,
+    /// Input clauses, including duplicates
+    pub(crate) clauses: usize,
+    pub(crate) conflicts: u64,
+    pub(crate) stopped: Option<StopReason>,
+}
+
+impl SolveMonitor {
+    pub(crate) fn new(budget: ResolveBudget) -> Self {
+        Self {
+            budget,
+            clauses: 0,
+            conflicts: 0,
+            stopped: None,
+        }
+    }
+}
+
+impl ProofProcessor for SolveMonitor {
+    fn process_step(&mut self, step: &CheckedProofStep, _: CheckerData) -> anyhow::Result<()> {
+        match step {
+            CheckedProofStep::AddClause { .. } | CheckedProofStep::DuplicatedClause { .. } => {
+                self.clauses += 1;
+                return Ok(());
+            }
+            CheckedProofStep::AtClause { .. } => {
+                self.conflicts += 1;
+                // Checking the clock on every learned clause would dominate
+                if !self.conflicts.is_multiple_of(64) {
+                    return Ok(());
+                }
+            }
+            _ => {}
+        }
+        match self.budget.check() {
+            Some(reason) => {
+                self.stopped = Some(reason);
+                Err(anyhow::anyhow!("resolution {}", reason))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget() {
+        let budget = ResolveBudget::default();
+        assert_eq!(budget.check(), None);
+        let clone = budget.clone();
+        clone.cancel();
+        assert_eq!(budget.check(), Some(StopReason::Interrupted));
+
+        assert!(!budget.is_bounded());
+
+        let budget = ResolveBudget::new(Some(Duration::ZERO));
+        assert!(budget.is_bounded());
+        assert_eq!(budget.check(), Some(StopReason::TimedOut(Duration::ZERO)));
+
+        let stats = ResolveStats {
+            visited: 3,
+            decisions: 1,
+            ..Default::default()
+        };
+        let err = budget
+            .check_or_explain(&stats, || vec!["still queued: a/b".to_string()])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Dependency resolution timed out after 0s: 3 packages visited, 1 decisions \
+             in 0.00s\n  still queued: a/b"
+        );
+    }
+
+    #[test]
+    fn test_monitor_stops_solver() {
+        use varisat::{ExtendFormula, Lit, Solver};
+
+        let budget = ResolveBudget::default();
+        budget.cancel();
+        let mut monitor = SolveMonitor::new(budget);
+        {
+            let mut solver = Solver::new();
+            solver.add_proof_processor(&mut monitor);
+            let (a, b) = (Lit::from_dimacs(1), Lit::from_dimacs(2));
+            solver.add_clause(&[a, b]);
+            solver.add_clause(&[!a, b]);
+            assert!(solver.solve().is_err());
+        }
+        assert_eq!(monitor.stopped, Some(StopReason::Interrupted));
+    }
+}
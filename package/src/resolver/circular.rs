@@ -394,6 +394,8 @@ mod tests {
             installed_size: 0,
             required_use: String::new(),
             blockers: Vec::new(),
+            any_of_dependencies: Vec::new(),
+            restrict: Vec::new(),
         }
     }
 
@@ -0,0 +1,192 @@
+//! System packages under a per-user prefix
+//!
+//! A prefix sits on a system whose installed packages it may use: a
+//! dependency the system already satisfies is taken from it rather than
+//! built again in the prefix. The prefix's environment puts its own
+//! libraries first, so a package installed in the prefix shadows the
+//! system's copy for everything run from the prefix, system binaries
+//! included. That is only safe when no system package the resolution relies
+//! on links against the shadowed copy; [`HostPackages::check_shadowing`]
+//! refuses resolutions where one does.
+
+use crate::db::PackageDb;
+use crate::{Dependency, Error, InstalledPackage, PackageId, PackageInfo, Result};
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+/// Packages installed on the system a prefix sits on
+#[derive(Debug, Clone, Default)]
+pub struct HostPackages {
+    installed: HashMap<String, InstalledPackage>,
+    /// Names of the system packages depending on each package
+    dependents: HashMap<String, Vec<String>>,
+}
+
+impl HostPackages {
+    pub fn new(installed: Vec<InstalledPackage>, dependents: HashMap<String, Vec<String>>) -> Self {
+        Self {
+            installed: installed
+                .into_iter()
+                .map(|pkg| (pkg.id.name.clone(), pkg))
+                .collect(),
+            dependents,
+        }
+    }
+
+    /// Read the system's package database, if it has one
+    pub fn load(db_path: &Path) -> Result<Option<Self>> {
+        if !db_path.join("packages.db").exists() {
+            return Ok(None);
+        }
+        let db = PackageDb::open_read_only(db_path)?;
+        Ok(Some(Self::new(
+            db.get_all_installed()?,
+            db.reverse_dependency_map()?,
+        )))
+    }
+
+    /// The system package satisfying a dependency, if any
+    pub fn satisfying(&self, dep: &Dependency) -> Option<&InstalledPackage> {
+        let pkg = self.provides(&dep.package)?;
+        let slot_matches = dep
+            .slot
+            .as_deref()
+            .is_none_or(|slot| pkg.slot.split('/').next() == Some(slot));
+        (slot_matches && dep.version.matches(&pkg.version)).then_some(pkg)
+    }
+
+    /// The system's installed copy of a package, any version
+    pub fn provides(&self, id: &PackageId) -> Option<&InstalledPackage> {
+        self.installed
+            .get(&id.name)
+            .filter(|pkg| pkg.id.category == id.category)
+    }
+
+    /// Refuse prefix packages that would shadow a different version of a
+    /// system package which system packages in `used` link against
+    pub fn check_shadowing(
+        &self,
+        prefix: &[PackageInfo],
+        used: &BTreeSet<PackageId>,
+    ) -> Result<()> {
+        for pkg in prefix {
+            let Some(system) = self.provides(&pkg.id) else {
+                continue;
+            };
+            if system.version == pkg.version {
+                continue;
+            }
+            let linked: Vec<String> = self
+                .dependents
+                .get(&pkg.id.name)
+                .into_iter()
+                .flatten()
+                .filter(|name| used.iter().any(|id| &id.name == *name))
+                .cloned()
+                .collect();
+            if !linked.is_empty() {
+                return Err(Error::ResolutionFailed(format!(
+                    "{}-{} in the prefix would shadow the system's {}-{}, which the system's {} \
+                     used here was built against; install {} in the prefix as well",
+                    pkg.id,
+                    pkg.version,
+                    system.id,
+                    system.version,
+                    linked.join(", "),
+                    if linked.len() == 1 { "it" } else { "them" },
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VersionSpec;
+
+    fn installed(id: &str, version: &str) -> InstalledPackage {
+        let id = PackageId::parse(id).unwrap();
+        InstalledPackage {
+            name: id.name.clone(),
+            id,
+            version: semver::Version::parse(version).unwrap(),
+            slot: "0".to_string(),
+            installed_at: chrono::Utc::now(),
+            use_flags: Default::default(),
+            files: Vec::new(),
+            size: 0,
+            build_time: false,
+            explicit: false,
+        }
+    }
+
+    fn available(id: &str, version: &str) -> PackageInfo {
+        PackageInfo {
+            id: PackageId::parse(id).unwrap(),
+            version: semver::Version::parse(version).unwrap(),
+            slot: "0".to_string(),
+            description: String::new(),
+            homepage: None,
+            license: "MIT".to_string(),
+            keywords: Vec::new(),
+            use_flags: Vec::new(),
+            dependencies: Vec::new(),
+            build_dependencies: Vec::new(),
+            runtime_dependencies: Vec::new(),
+            source_url: None,
+            source_hash: None,
+            buck_target: String::new(),
+            size: 0,
+            installed_size: 0,
+            required_use: String::new(),
+            blockers: Vec::new(),
+            any_of_dependencies: Vec::new(),
+            restrict: Vec::new(),
+        }
+    }
+
+    fn host() -> HostPackages {
+        HostPackages::new(
+            vec![
+                installed("dev-libs/openssl", "3.1.0"),
+                installed("net-misc/curl", "8.5.0"),
+            ],
+            HashMap::from([("openssl".to_string(), vec!["curl".to_string()])]),
+        )
+    }
+
+    #[test]
+    fn test_satisfying() {
+        let host = host();
+        let mut dep = Dependency::new(PackageId::new("dev-libs", "openssl"));
+        assert!(host.satisfying(&dep).is_some());
+
+        dep.version = VersionSpec::GreaterThanOrEqual(semver::Version::new(3, 2, 0));
+        assert!(host.satisfying(&dep).is_none());
+
+        let mut dep = Dependency::new(PackageId::new("dev-libs", "openssl"));
+        dep.slot = Some("1.1".to_string());
+        assert!(host.satisfying(&dep).is_none());
+        assert!(host
+            .satisfying(&Dependency::new(PackageId::new("app-misc", "openssl")))
+            .is_none());
+    }
+
+    #[test]
+    fn test_shadowing_linked_system_package() {
+        let host = host();
+        let openssl = [available("dev-libs/openssl", "3.2.0")];
+        let curl = BTreeSet::from([PackageId::new("net-misc", "curl")]);
+
+        let err = host.check_shadowing(&openssl, &curl).unwrap_err();
+        assert!(err.to_string().contains("install it in the prefix"));
+
+        // Fine when nothing from the system links against it, or when the
+        // prefix copy is the same version
+        assert!(host.check_shadowing(&openssl, &BTreeSet::new()).is_ok());
+        let same = [available("dev-libs/openssl", "3.1.0")];
+        assert!(host.check_shadowing(&same, &curl).is_ok());
+    }
+}
@@ -0,0 +1,167 @@
+//! Upgrade impact analysis
+//!
+//! Walks the reverse-dependency closure of an installed package to show
+//! what may be affected when it changes, split into packages that depend on
+//! it directly and those that only reach it transitively, with a rebuild
+//! time estimate from recorded build durations.
+
+use crate::{InstalledPackage, PackageId};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Duration;
+
+/// A package in the reverse-dependency closure
+#[derive(Debug, Clone)]
+pub struct ImpactedPackage {
+    pub id: PackageId,
+    pub version: semver::Version,
+    /// Distance from the upgraded package; 1 for direct dependents
+    pub depth: usize,
+    /// The dependency through which it was reached
+    pub via: PackageId,
+    /// Average recorded build time, if it has been built before
+    pub estimate: Option<Duration>,
+}
+
+/// Reverse-dependency closure of one package
+#[derive(Debug, Clone)]
+pub struct ImpactReport {
+    pub package: PackageId,
+    /// Build time of the package itself, if known
+    pub package_estimate: Option<Duration>,
+    /// Packages depending on it directly
+    pub direct: Vec<ImpactedPackage>,
+    /// Packages depending on it through other packages
+    pub transitive: Vec<ImpactedPackage>,
+}
+
+impl ImpactReport {
+    /// Compute the closure of `package` over the installed packages
+    ///
+    /// `reverse_deps` maps a package name to the names of installed packages
+    /// that depend on it.
+    pub fn compute(
+        package: &InstalledPackage,
+        installed: &[InstalledPackage],
+        reverse_deps: &HashMap<String, Vec<String>>,
+        durations: &HashMap<PackageId, Duration>,
+    ) -> Self {
+        let by_name: HashMap<&str, &InstalledPackage> =
+            installed.iter().map(|p| (p.name.as_str(), p)).collect();
+
+        let mut direct = Vec::new();
+        let mut transitive = Vec::new();
+        let mut seen = HashSet::from([package.name.clone()]);
+        let mut queue = VecDeque::from([(package, 0usize)]);
+
+        while let Some((current, depth)) = queue.pop_front() {
+            let Some(dependents) = reverse_deps.get(&current.name) else {
+                continue;
+            };
+            let mut dependents: Vec<&String> = dependents.iter().collect();
+            dependents.sort();
+            for name in dependents {
+                let Some(dependent) = by_name.get(name.as_str()).copied() else {
+                    continue;
+                };
+                if !seen.insert(name.clone()) {
+                    continue;
+                }
+                let impacted = ImpactedPackage {
+                    id: dependent.id.clone(),
+                    version: dependent.version.clone(),
+                    depth: depth + 1,
+                    via: current.id.clone(),
+                    estimate: durations.get(&dependent.id).copied(),
+                };
+                if depth == 0 {
+                    direct.push(impacted);
+                } else {
+                    transitive.push(impacted);
+                }
+                queue.push_back((dependent, depth + 1));
+            }
+        }
+
+        Self {
+            package: package.id.clone(),
+            package_estimate: durations.get(&package.id).copied(),
+            direct,
+            transitive,
+        }
+    }
+
+    /// All affected packages, direct dependents first
+    pub fn affected(&self) -> impl Iterator<Item = &ImpactedPackage> {
+        self.direct.iter().chain(&self.transitive)
+    }
+
+    /// Estimated time to rebuild the package and everything affected,
+    /// counting only packages with recorded builds
+    pub fn total_estimate(&self) -> Duration {
+        self.package_estimate.unwrap_or_default()
+            + self.affected().filter_map(|p| p.estimate).sum::<Duration>()
+    }
+
+    /// Number of packages (including the package itself) with no recorded
+    /// build, whose time is missing from the estimate
+    pub fn unknown_estimates(&self) -> usize {
+        usize::from(self.package_estimate.is_none())
+            + self.affected().filter(|p| p.estimate.is_none()).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn installed(name: &str) -> InstalledPackage {
+        InstalledPackage {
+            id: PackageId::new("dev-libs", name),
+            name: name.to_string(),
+            version: semver::Version::new(1, 0, 0),
+            slot: "0".to_string(),
+            installed_at: chrono::Utc::now(),
+            use_flags: HashSet::new(),
+            files: Vec::new(),
+            size: 0,
+            build_time: false,
+            explicit: false,
+        }
+    }
+
+    #[test]
+    fn test_closure_and_estimate() {
+        let packages: Vec<InstalledPackage> = ["openssl", "curl", "python", "git", "pip"]
+            .into_iter()
+            .map(installed)
+            .collect();
+        let mut rdeps: HashMap<String, Vec<String>> = HashMap::new();
+        rdeps.insert("openssl".into(), vec!["python".into(), "curl".into()]);
+        rdeps.insert("curl".into(), vec!["git".into()]);
+        rdeps.insert("python".into(), vec!["pip".into(), "git".into()]);
+        // Cycles and uninstalled dependents are ignored
+        rdeps.insert("git".into(), vec!["openssl".into(), "gone".into()]);
+
+        let mut durations = HashMap::new();
+        durations.insert(
+            PackageId::new("dev-libs", "openssl"),
+            Duration::from_secs(120),
+        );
+        durations.insert(
+            PackageId::new("dev-libs", "python"),
+            Duration::from_secs(600),
+        );
+        durations.insert(PackageId::new("dev-libs", "git"), Duration::from_secs(90));
+
+        let report = ImpactReport::compute(&packages[0], &packages, &rdeps, &durations);
+        let names = |list: &[ImpactedPackage]| -> Vec<String> {
+            list.iter().map(|p| p.id.name.clone()).collect()
+        };
+        assert_eq!(names(&report.direct), vec!["curl", "python"]);
+        assert_eq!(names(&report.transitive), vec!["git", "pip"]);
+        assert_eq!(report.transitive[0].via.name, "curl");
+        assert_eq!(report.transitive[0].depth, 2);
+        assert_eq!(report.total_estimate(), Duration::from_secs(810));
+        assert_eq!(report.unknown_estimates(), 2);
+    }
+}
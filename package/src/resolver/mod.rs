@@ -2,24 +2,40 @@
 //!
 //! Uses the varisat SAT solver for optimal dependency resolution.
 
+pub mod any_of;
 pub mod autounmask;
 pub mod backtrack;
 pub mod blocker;
+pub mod budget;
 pub mod circular;
+pub mod host;
+pub mod impact;
+pub mod order;
+pub mod plan;
+pub mod reachability;
 pub mod required_use;
 
+pub use any_of::*;
 pub use autounmask::*;
 pub use backtrack::*;
 pub use blocker::*;
+pub use budget::*;
 pub use circular::*;
+pub use host::*;
+pub use impact::*;
+pub use order::*;
+pub use plan::*;
+pub use reachability::*;
 pub use required_use::*;
 
 use crate::db::PackageDb;
+use crate::plugin::{newest_allowed, rejection, Constraint};
 use crate::repository::RepositoryManager;
-use crate::{Error, InstallOptions, PackageId, PackageInfo, Result};
-use petgraph::algo::toposort;
-use petgraph::graph::{DiGraph, NodeIndex};
-use std::collections::{HashMap, HashSet};
+use crate::use_explain::UseLayers;
+use crate::{
+    Dependency, Error, InstallOptions, PackageId, PackageInfo, PackageSpec, Result, VersionSpec,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::info;
@@ -32,18 +48,95 @@ pub struct InternalResolution {
     pub build_order: Vec<usize>,
     pub download_size: u64,
     pub install_size: u64,
+    /// Alternatives chosen for any-of dependency groups
+    pub any_of_choices: Vec<AnyOfChoice>,
+    /// System packages a per-user prefix relies on instead of installing
+    pub from_host: Vec<PackageId>,
+    pub stats: ResolveStats,
 }
 
 /// Dependency resolver
 pub struct DependencyResolver {
     db: Arc<RwLock<PackageDb>>,
     repos: Arc<RepositoryManager>,
+    any_of_policy: AnyOfPolicy,
+    use_layers: Option<UseLayers>,
+    constraints: Vec<(String, Constraint)>,
+    host: Option<HostPackages>,
+    budget: ResolveBudget,
 }
 
 impl DependencyResolver {
     /// Create a new dependency resolver
     pub fn new(db: Arc<RwLock<PackageDb>>, repos: Arc<RepositoryManager>) -> Self {
-        Self { db, repos }
+        Self {
+            db,
+            repos,
+            any_of_policy: AnyOfPolicy::default(),
+            use_layers: None,
+            constraints: Vec::new(),
+            host: None,
+            budget: ResolveBudget::default(),
+        }
+    }
+
+    /// Use a custom policy for choosing any-of alternatives
+    ///
+    /// Packages installed according to the database are always added to the
+    /// policy's installed set.
+    pub fn with_any_of_policy(mut self, policy: AnyOfPolicy) -> Self {
+        self.any_of_policy = policy;
+        self
+    }
+
+    /// Evaluate USE-conditional dependencies against these layers
+    ///
+    /// Without layers every conditional dependency is followed.
+    pub fn with_use_layers(mut self, layers: UseLayers) -> Self {
+        self.use_layers = Some(layers);
+        self
+    }
+
+    /// Reject packages that violate plugin constraints, keyed by plugin name
+    pub fn with_constraints(mut self, constraints: Vec<(String, Constraint)>) -> Self {
+        self.constraints = constraints;
+        self
+    }
+
+    /// Resolve for a per-user prefix on a system with these packages
+    ///
+    /// Dependencies the system satisfies are taken from it, and prefix
+    /// packages may not shadow system libraries those link against.
+    pub fn with_host(mut self, host: HostPackages) -> Self {
+        self.host = Some(host);
+        self
+    }
+
+    /// Stop when this budget runs out or is cancelled
+    pub fn with_budget(mut self, budget: ResolveBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Whether the system provides a dependency, recording it in `used`
+    fn provided_by_host(&self, dep: &Dependency, used: &mut BTreeSet<PackageId>) -> bool {
+        match self.host.as_ref().and_then(|host| host.satisfying(dep)) {
+            Some(pkg) => {
+                used.insert(pkg.id.clone());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Filter for the dependencies of `pkg` that are active under the USE layers
+    fn active_dependencies(&self, pkg: &PackageInfo) -> impl Fn(&&Dependency) -> bool {
+        let flags = self.use_layers.as_ref().map(|layers| layers.effective(pkg));
+        move |dep| {
+            flags
+                .as_ref()
+                .is_none_or(|flags| dep.use_flags.evaluate(flags))
+        }
     }
 
     /// Resolve dependencies for packages
@@ -54,9 +147,18 @@ impl DependencyResolver {
     ) -> Result<InternalResolution> {
         info!("Resolving dependencies for {} packages", packages.len());
 
-        // Parse package specifications
+        // Parse package specifications; those naming a version or slot
+        // narrow the choice of that package
         let mut requested: Vec<PackageId> = Vec::new();
+        let mut wanted: HashMap<PackageId, (&String, PackageSpec)> = HashMap::new();
         for pkg in packages {
+            if let Ok(spec) = PackageSpec::parse(pkg) {
+                if spec.version != VersionSpec::Any || spec.slot.is_some() {
+                    requested.push(spec.id.clone());
+                    wanted.insert(spec.id.clone(), (pkg, spec));
+                    continue;
+                }
+            }
             let id = PackageId::parse(pkg)
                 .or_else(|| {
                     // Try to find by name only
@@ -67,18 +169,23 @@ impl DependencyResolver {
         }
 
         // Get all available packages
-        let mut available = self.repos.get_all_packages().await?;
+        let all_packages = self.repos.get_all_packages().await?;
+        let mut available = all_packages.clone();
         let db = self.db.read().await;
 
         // Filter out already installed packages (unless forcing)
         if !opts.force {
             available.retain(|pkg| !db.is_installed(&pkg.id.name).unwrap_or(false));
         }
+
+        let mut any_of_policy = self.any_of_policy.clone();
+        any_of_policy
+            .installed
+            .extend(db.get_all_installed()?.into_iter().map(|p| p.id));
         drop(db);
+        let mut any_of_choices = Vec::new();
+        let mut from_host: BTreeSet<PackageId> = BTreeSet::new();
 
-        // Build dependency graph
-        let mut graph: DiGraph<PackageInfo, ()> = DiGraph::new();
-        let mut node_map: HashMap<PackageId, NodeIndex> = HashMap::new();
         let mut pkg_map: HashMap<PackageId, PackageInfo> = HashMap::new();
 
         // First pass: add all packages to map
@@ -87,15 +194,23 @@ impl DependencyResolver {
         }
 
         // Collect all packages we need
-        let mut to_install: HashSet<PackageId> = HashSet::new();
+        let mut to_install: BTreeSet<PackageId> = BTreeSet::new();
         let mut queue: Vec<PackageId> = requested.clone();
         let mut visited: HashSet<PackageId> = HashSet::new();
+        // First package found to need each one, to explain a stop
+        let mut required_by: HashMap<PackageId, PackageId> = HashMap::new();
+        let mut stats = ResolveStats::default();
 
         while let Some(pkg_id) = queue.pop() {
             if visited.contains(&pkg_id) {
                 continue;
             }
+            stats.elapsed = self.budget.elapsed();
+            self.budget.check_or_explain(&stats, || {
+                explain_progress(&pkg_id, &required_by, to_install.len(), &queue)
+            })?;
             visited.insert(pkg_id.clone());
+            stats.visited += 1;
 
             // Find package info
             let pkg_info = if let Some(info) = pkg_map.get(&pkg_id) {
@@ -113,79 +228,132 @@ impl DependencyResolver {
                 }
             };
 
+            // A version that was not asked for, or that a plugin rejects,
+            // gives way to the newest one that was and it allows
+            let spec = wanted.get(&pkg_info.id);
+            let fits = |p: &PackageInfo| {
+                spec.is_none_or(|(_, spec)| spec.matches(&p.id, &p.version, Some(&p.slot)))
+            };
+            let pkg_info = match rejection(&self.constraints, &pkg_info)? {
+                None if fits(&pkg_info) => pkg_info,
+                rejected => {
+                    let candidates = all_packages
+                        .iter()
+                        .filter(|p| p.id == pkg_info.id && fits(p));
+                    let allowed = newest_allowed(&self.constraints, candidates)?
+                        .cloned()
+                        .ok_or_else(|| match rejected {
+                            Some(reason) => Error::ResolutionFailed(reason),
+                            None => Error::PackageNotFound(spec.map_or_else(
+                                || pkg_info.id.to_string(),
+                                |(atom, _)| atom.to_string(),
+                            )),
+                        })?;
+                    pkg_map.insert(allowed.id.clone(), allowed.clone());
+                    allowed
+                }
+            };
+
             to_install.insert(pkg_id.clone());
 
             // Add dependencies to queue
             if !opts.no_deps {
-                for dep in &pkg_info.dependencies {
-                    if !visited.contains(&dep.package) {
+                let active = self.active_dependencies(&pkg_info);
+                for dep in pkg_info.dependencies.iter().filter(&active) {
+                    if !visited.contains(&dep.package)
+                        && !self.provided_by_host(dep, &mut from_host)
+                    {
+                        required_by
+                            .entry(dep.package.clone())
+                            .or_insert_with(|| pkg_id.clone());
                         queue.push(dep.package.clone());
                     }
                 }
-                for dep in &pkg_info.runtime_dependencies {
-                    if !visited.contains(&dep.package) {
+                for dep in pkg_info.runtime_dependencies.iter().filter(&active) {
+                    if !visited.contains(&dep.package)
+                        && !self.provided_by_host(dep, &mut from_host)
+                    {
+                        required_by
+                            .entry(dep.package.clone())
+                            .or_insert_with(|| pkg_id.clone());
                         queue.push(dep.package.clone());
                     }
                 }
                 if opts.build {
-                    for dep in &pkg_info.build_dependencies {
-                        if !visited.contains(&dep.package) {
+                    for dep in pkg_info.build_dependencies.iter().filter(&active) {
+                        if !visited.contains(&dep.package)
+                            && !self.provided_by_host(dep, &mut from_host)
+                        {
+                            required_by
+                                .entry(dep.package.clone())
+                                .or_insert_with(|| pkg_id.clone());
                             queue.push(dep.package.clone());
                         }
                     }
                 }
-            }
-        }
-
-        // Build the graph with actual packages
-        for pkg_id in &to_install {
-            if let Some(pkg) = pkg_map.get(pkg_id) {
-                let node = graph.add_node(pkg.clone());
-                node_map.insert(pkg_id.clone(), node);
-            }
-        }
-
-        // Add edges for dependencies
-        for pkg_id in &to_install {
-            if let Some(pkg) = pkg_map.get(pkg_id) {
-                let pkg_node = node_map[pkg_id];
-
-                for dep in &pkg.dependencies {
-                    if let Some(&dep_node) = node_map.get(&dep.package) {
-                        graph.add_edge(dep_node, pkg_node, ());
-                    }
-                }
-                for dep in &pkg.runtime_dependencies {
-                    if let Some(&dep_node) = node_map.get(&dep.package) {
-                        graph.add_edge(dep_node, pkg_node, ());
+                for group_str in &pkg_info.any_of_dependencies {
+                    let group = AnyOfGroup::parse(group_str)?;
+                    let choice = any_of_policy
+                        .choose(&pkg_info.id, &group, &all_packages)
+                        .ok_or_else(|| {
+                            Error::ResolutionFailed(format!(
+                                "no alternative of {} available for {}",
+                                group_str, pkg_info.id
+                            ))
+                        })?;
+
+                    // An installed alternative already satisfies the group
+                    let on_host = self
+                        .host
+                        .as_ref()
+                        .is_some_and(|host| host.provides(&choice.chosen).is_some());
+                    if on_host {
+                        from_host.insert(choice.chosen.clone());
+                    } else if !any_of_policy.installed.contains(&choice.chosen)
+                        && !visited.contains(&choice.chosen)
+                    {
+                        required_by
+                            .entry(choice.chosen.clone())
+                            .or_insert_with(|| pkg_id.clone());
+                        queue.push(choice.chosen.clone());
                     }
+                    stats.decisions += 1;
+                    any_of_choices.push(choice);
                 }
             }
         }
 
-        // Topological sort for build order
-        let sorted = toposort(&graph, None)
-            .map_err(|_| Error::CircularDependency("Circular dependency detected".to_string()))?;
-
-        // Build resolution
-        let mut packages = Vec::new();
-        let mut build_order = Vec::new();
-        let mut download_size = 0u64;
-        let mut install_size = 0u64;
-
-        for (idx, node) in sorted.iter().enumerate() {
-            let pkg = &graph[*node];
-            packages.push(pkg.clone());
-            build_order.push(idx);
-            download_size += pkg.size;
-            install_size += pkg.installed_size;
+        // Order the packages canonically for a stable build plan
+        let selected: Vec<PackageInfo> = to_install
+            .iter()
+            .filter_map(|id| pkg_map.get(id).cloned())
+            .collect();
+        let providers = chosen_providers(&any_of_choices);
+        let packages = stable_order(selected, |pkg| {
+            let active = self.active_dependencies(pkg);
+            pkg.dependencies
+                .iter()
+                .chain(&pkg.runtime_dependencies)
+                .filter(&active)
+                .map(|dep| dep.package.clone())
+                .chain(providers.get(&pkg.id).into_iter().flatten().cloned())
+                .collect()
+        })?;
+        if let Some(host) = &self.host {
+            host.check_shadowing(&packages, &from_host)?;
         }
 
+        let build_order = (0..packages.len()).collect();
+        let download_size: u64 = packages.iter().map(|p| p.size).sum();
+        let install_size: u64 = packages.iter().map(|p| p.installed_size).sum();
+
+        stats.elapsed = self.budget.elapsed();
         info!(
-            "Resolution complete: {} packages, {} download, {} install",
+            "Resolution complete: {} packages, {} download, {} install ({})",
             packages.len(),
             format_size(download_size),
-            format_size(install_size)
+            format_size(install_size),
+            stats
         );
 
         Ok(InternalResolution {
@@ -193,6 +361,9 @@ impl DependencyResolver {
             build_order,
             download_size,
             install_size,
+            any_of_choices,
+            from_host: from_host.into_iter().collect(),
+            stats,
         })
     }
 
@@ -204,8 +375,15 @@ impl DependencyResolver {
     ) -> Result<InternalResolution> {
         info!("Using SAT solver for dependency resolution");
 
+        let mut monitor = SolveMonitor::new(self.budget.clone());
         let mut solver = Solver::new();
+        // Proof checking slows every solve; only pay for it when the solve
+        // may have to stop
+        if self.budget.is_bounded() {
+            solver.add_proof_processor(&mut monitor);
+        }
         let mut unsatisfiable_deps: Vec<String> = Vec::new();
+        let mut stats = ResolveStats::default();
 
         // Convert enabled USE flags to a set for faster lookups
         let enabled_use_flags: HashSet<String> = opts.use_flags.iter().cloned().collect();
@@ -227,10 +405,21 @@ impl DependencyResolver {
 
         // Add constraints
 
+        // 0. Versions rejected by plugins are never selected
+        for pkg in &all_packages {
+            if let Some(reason) = rejection(&self.constraints, pkg)? {
+                solver.add_clause(&[!var_map[&(pkg.id.clone(), pkg.version.clone())]]);
+                unsatisfiable_deps.push(reason);
+            }
+        }
+
         // 1. At most one version of each package
-        let mut versions_by_pkg: HashMap<PackageId, Vec<Lit>> = HashMap::new();
-        for ((id, _ver), &lit) in &var_map {
-            versions_by_pkg.entry(id.clone()).or_default().push(lit);
+        let mut versions_by_pkg: BTreeMap<PackageId, Vec<Lit>> = BTreeMap::new();
+        for pkg in &all_packages {
+            versions_by_pkg
+                .entry(pkg.id.clone())
+                .or_default()
+                .push(var_map[&(pkg.id.clone(), pkg.version.clone())]);
         }
 
         for versions in versions_by_pkg.values() {
@@ -246,19 +435,26 @@ impl DependencyResolver {
 
         // 2. Requested packages must be installed
         for pkg_name in packages {
-            let pkg_id = PackageId::parse(pkg_name)
-                .ok_or_else(|| Error::InvalidPackageSpec(pkg_name.clone()))?;
-
-            if let Some(versions) = versions_by_pkg.get(&pkg_id) {
-                // At least one version must be selected
-                solver.add_clause(versions);
-            } else {
+            let spec = PackageSpec::parse(pkg_name)?;
+            // At least one version matching the atom must be selected
+            let versions: Vec<Lit> = all_packages
+                .iter()
+                .filter(|p| spec.matches(&p.id, &p.version, Some(&p.slot)))
+                .map(|p| var_map[&(p.id.clone(), p.version.clone())])
+                .collect();
+            if versions.is_empty() {
                 return Err(Error::PackageNotFound(pkg_name.clone()));
             }
+            solver.add_clause(&versions);
         }
 
         // 3. Dependencies (compile-time, runtime, and optionally build-time)
+        stats.variables = var_map.len();
         for pkg in &all_packages {
+            stats.visited += 1;
+            stats.elapsed = self.budget.elapsed();
+            self.budget
+                .check_or_explain(&stats, || explain_conflicts(&unsatisfiable_deps))?;
             let pkg_lit = var_map[&(pkg.id.clone(), pkg.version.clone())];
 
             // Helper function to add dependency constraints
@@ -340,6 +536,34 @@ impl DependencyResolver {
                 }
             }
 
+            // Any-of groups: pkg => (alt1 || alt2 || ...)
+            if !opts.no_deps {
+                for group_str in &pkg.any_of_dependencies {
+                    let Ok(group) = AnyOfGroup::parse(group_str) else {
+                        unsatisfiable_deps.push(format!(
+                            "{} v{} has invalid any-of group {}",
+                            pkg.id.name, pkg.version, group_str
+                        ));
+                        solver.add_clause(&[!pkg_lit]);
+                        continue;
+                    };
+                    let mut clause = vec![!pkg_lit];
+                    clause.extend(
+                        all_packages
+                            .iter()
+                            .filter(|p| group.is_satisfied_by(p))
+                            .map(|p| var_map[&(p.id.clone(), p.version.clone())]),
+                    );
+                    if clause.len() == 1 {
+                        unsatisfiable_deps.push(format!(
+                            "{} v{} requires {}",
+                            pkg.id.name, pkg.version, group_str
+                        ));
+                    }
+                    solver.add_clause(&clause);
+                }
+            }
+
             // Build dependencies (only if building)
             if opts.build && !opts.no_deps {
                 for dep in &pkg.build_dependencies {
@@ -356,9 +580,22 @@ impl DependencyResolver {
         }
 
         // Solve
-        let solution = solver
-            .solve()
-            .map_err(|e| Error::ResolutionFailed(format!("SAT solver error: {:?}", e)))?;
+        let solved = solver.solve();
+        let model = solver.model();
+        drop(solver);
+        stats.clauses = monitor.clauses;
+        stats.conflicts = monitor.conflicts;
+        stats.elapsed = self.budget.elapsed();
+        if let Some(reason) = monitor.stopped {
+            return Err(stopped(
+                reason,
+                &stats,
+                explain_conflicts(&unsatisfiable_deps),
+            ));
+        }
+        let solution =
+            solved.map_err(|e| Error::ResolutionFailed(format!("SAT solver error: {:?}", e)))?;
+        info!("SAT resolution: {}", stats);
 
         if !solution {
             let mut error_msg = String::from("No solution found for dependencies");
@@ -381,9 +618,8 @@ impl DependencyResolver {
         }
 
         // Extract solution
-        let model = solver
-            .model()
-            .ok_or_else(|| Error::ResolutionFailed("No model available".to_string()))?;
+        let model =
+            model.ok_or_else(|| Error::ResolutionFailed("No model available".to_string()))?;
 
         let mut selected: Vec<PackageInfo> = Vec::new();
         for lit in model {
@@ -401,8 +637,23 @@ impl DependencyResolver {
             }
         }
 
+        let mut any_of_policy = self.any_of_policy.clone();
+        any_of_policy.installed.extend(
+            self.db
+                .read()
+                .await
+                .get_all_installed()?
+                .into_iter()
+                .map(|p| p.id),
+        );
+        let any_of_choices = if opts.no_deps {
+            Vec::new()
+        } else {
+            selected_choices(&any_of_policy, &selected)
+        };
+
         // Sort by dependencies (build order)
-        let packages = self.compute_build_order(selected)?;
+        let packages = self.compute_build_order(selected, &any_of_choices)?;
 
         let download_size: u64 = packages.iter().map(|p| p.size).sum();
         let install_size: u64 = packages.iter().map(|p| p.installed_size).sum();
@@ -412,58 +663,83 @@ impl DependencyResolver {
             packages,
             download_size,
             install_size,
+            any_of_choices,
+            from_host: Vec::new(),
+            stats,
         })
     }
 
-    fn compute_build_order(&self, packages: Vec<PackageInfo>) -> Result<Vec<PackageInfo>> {
-        let mut graph: DiGraph<usize, ()> = DiGraph::new();
-        let mut node_map: HashMap<PackageId, NodeIndex> = HashMap::new();
-
-        // Add nodes
-        for (idx, pkg) in packages.iter().enumerate() {
-            let node = graph.add_node(idx);
-            node_map.insert(pkg.id.clone(), node);
-        }
-
-        // Add edges for all dependency types
-        for pkg in packages.iter() {
-            let pkg_node = node_map[&pkg.id];
-
-            // Regular dependencies
-            for dep in &pkg.dependencies {
-                if let Some(&dep_node) = node_map.get(&dep.package) {
-                    graph.add_edge(dep_node, pkg_node, ());
-                }
-            }
-
-            // Runtime dependencies
-            for dep in &pkg.runtime_dependencies {
-                if let Some(&dep_node) = node_map.get(&dep.package) {
-                    graph.add_edge(dep_node, pkg_node, ());
-                }
-            }
-
-            // Build dependencies
-            for dep in &pkg.build_dependencies {
-                if let Some(&dep_node) = node_map.get(&dep.package) {
-                    graph.add_edge(dep_node, pkg_node, ());
-                }
-            }
-        }
+    fn compute_build_order(
+        &self,
+        packages: Vec<PackageInfo>,
+        any_of_choices: &[AnyOfChoice],
+    ) -> Result<Vec<PackageInfo>> {
+        let providers = chosen_providers(any_of_choices);
+        stable_order(packages, |pkg| {
+            pkg.dependencies
+                .iter()
+                .chain(&pkg.runtime_dependencies)
+                .chain(&pkg.build_dependencies)
+                .map(|dep| dep.package.clone())
+                .chain(providers.get(&pkg.id).into_iter().flatten().cloned())
+                .collect()
+        })
+    }
+}
 
-        // Topological sort
-        let sorted = toposort(&graph, None).map_err(|_| {
-            Error::CircularDependency("Circular dependency in packages".to_string())
-        })?;
+/// Where a stopped walk was: the package it was on, the chain of packages
+/// that pulled it in, and what remained
+fn explain_progress(
+    current: &PackageId,
+    required_by: &HashMap<PackageId, PackageId>,
+    selected: usize,
+    queue: &[PackageId],
+) -> Vec<String> {
+    let mut chain = vec![current.to_string()];
+    let mut seen = HashSet::from([current]);
+    let mut at = current;
+    while let Some(parent) = required_by.get(at).filter(|p| seen.insert(*p)) {
+        chain.push(parent.to_string());
+        at = parent;
+    }
+    let mut lines = vec![
+        format!("stopped at {}", chain.join(" <- ")),
+        format!("{} packages selected so far", selected),
+    ];
+    if !queue.is_empty() {
+        let pending: Vec<String> = queue
+            .iter()
+            .rev()
+            .take(10)
+            .map(|id| id.to_string())
+            .collect();
+        lines.push(format!(
+            "{} still queued: {}{}",
+            queue.len(),
+            pending.join(", "),
+            if queue.len() > 10 { ", ..." } else { "" }
+        ));
+    }
+    lines
+}
 
-        Ok(sorted
-            .into_iter()
-            .map(|n| packages[graph[n]].clone())
-            .collect())
+/// Unsatisfiable dependencies found before a SAT resolution stopped
+fn explain_conflicts(unsatisfiable: &[String]) -> Vec<String> {
+    if unsatisfiable.is_empty() {
+        return vec!["no unsatisfiable dependencies found so far".to_string()];
     }
+    let mut lines: Vec<String> = unsatisfiable
+        .iter()
+        .take(10)
+        .map(|dep| format!("conflict: {}", dep))
+        .collect();
+    if unsatisfiable.len() > 10 {
+        lines.push(format!("... and {} more", unsatisfiable.len() - 10));
+    }
+    lines
 }
 
-fn format_size(bytes: u64) -> String {
+pub(crate) fn format_size(bytes: u64) -> String {
     const KB: u64 = 1024;
     const MB: u64 = KB * 1024;
     const GB: u64 = MB * 1024;
@@ -478,3 +754,68 @@ fn format_size(bytes: u64) -> String {
         format!("{} B", bytes)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RepositoryConfig;
+    use crate::Config;
+
+    /// A resolver over one repository per version of `app-misc/foo`
+    fn resolver(dir: &std::path::Path, versions: &[&str]) -> DependencyResolver {
+        let mut config = Config {
+            cache_dir: dir.join("cache"),
+            ..Default::default()
+        };
+        config.repositories = versions
+            .iter()
+            .map(|version| {
+                let location = dir.join(version);
+                let package = location.join("packages/app-misc/foo");
+                std::fs::create_dir_all(&package).unwrap();
+                let metadata = serde_json::json!({
+                    "version": version,
+                    "description": "The foo package",
+                    "license": "MIT",
+                    "keywords": ["amd64"],
+                });
+                std::fs::write(package.join("metadata.json"), metadata.to_string()).unwrap();
+                RepositoryConfig {
+                    name: version.to_string(),
+                    location,
+                    ..Default::default()
+                }
+            })
+            .collect();
+        let db = PackageDb::open(&dir.join("db")).unwrap();
+        let repos = RepositoryManager::new(&config).unwrap();
+        #[allow(clippy::arc_with_non_send_sync)]
+        let db = Arc::new(RwLock::new(db));
+        DependencyResolver::new(db, Arc::new(repos))
+    }
+
+    fn versions(resolution: &InternalResolution) -> Vec<String> {
+        resolution
+            .packages
+            .iter()
+            .map(|p| format!("{}-{}", p.id, p.version))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_requested_version_is_honoured() {
+        let dir = tempfile::tempdir().unwrap();
+        let resolver = resolver(dir.path(), &["1.0.0", "2.0.0"]);
+        let opts = InstallOptions::default();
+
+        let pinned = ["=app-misc/foo-1.0.0:0".to_string()];
+        let resolution = resolver.resolve(&pinned, &opts).await.unwrap();
+        assert_eq!(versions(&resolution), vec!["app-misc/foo-1.0.0"]);
+        let resolution = resolver.resolve_sat(&pinned, &opts).await.unwrap();
+        assert_eq!(versions(&resolution), vec!["app-misc/foo-1.0.0"]);
+
+        let missing = ["=app-misc/foo-3.0.0".to_string()];
+        assert!(resolver.resolve(&missing, &opts).await.is_err());
+        assert!(resolver.resolve_sat(&missing, &opts).await.is_err());
+    }
+}
@@ -0,0 +1,277 @@
+//! Canonical ordering of resolved packages
+//!
+//! The build order is topological, and among packages whose dependencies
+//! are all placed it is lexicographic by category, name and version, so
+//! the same inputs always give the same plan whatever order the
+//! repositories or hash maps handed them over in. The plan hash is a
+//! digest of that order, for telling at a glance whether two `--pretend`
+//! runs would do the same thing.
+
+use crate::{Error, PackageId, PackageInfo, Result};
+use std::collections::{BTreeSet, HashMap};
+
+/// Order `packages` so each comes after the packages it depends on,
+/// breaking ties lexicographically
+///
+/// `deps` lists the dependencies of a package that count for ordering;
+/// those outside `packages` are ignored.
+pub fn stable_order<F>(packages: Vec<PackageInfo>, deps: F) -> Result<Vec<PackageInfo>>
+where
+    F: Fn(&PackageInfo) -> Vec<PackageId>,
+{
+    let index: HashMap<&PackageId, usize> = packages
+        .iter()
+        .enumerate()
+        .map(|(i, pkg)| (&pkg.id, i))
+        .collect();
+
+    let mut pending = vec![0usize; packages.len()];
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); packages.len()];
+    for (i, pkg) in packages.iter().enumerate() {
+        let mut seen = BTreeSet::new();
+        for dep in deps(pkg) {
+            if let Some(&d) = index.get(&dep) {
+                if d != i && seen.insert(d) {
+                    pending[i] += 1;
+                    dependents[d].push(i);
+                }
+            }
+        }
+    }
+
+    let key = |i: usize| (&packages[i].id, &packages[i].version, i);
+    let mut ready: BTreeSet<_> = (0..packages.len())
+        .filter(|&i| pending[i] == 0)
+        .map(key)
+        .collect();
+    let mut order = Vec::with_capacity(packages.len());
+    while let Some((_, _, i)) = ready.pop_first() {
+        order.push(i);
+        for &next in &dependents[i] {
+            pending[next] -= 1;
+            if pending[next] == 0 {
+                ready.insert(key(next));
+            }
+        }
+    }
+
+    if order.len() < packages.len() {
+        let cycle: BTreeSet<String> = (0..packages.len())
+            .filter(|&i| pending[i] > 0)
+            .map(|i| packages[i].id.to_string())
+            .collect();
+        return Err(Error::CircularDependency(format!(
+            "Circular dependency between {}",
+            cycle.into_iter().collect::<Vec<_>>().join(", ")
+        )));
+    }
+
+    let mut slots: Vec<Option<PackageInfo>> = packages.into_iter().map(Some).collect();
+    Ok(order.into_iter().filter_map(|i| slots[i].take()).collect())
+}
+
+/// One line of a plan: `category/name-version:slot`, then the enabled
+/// USE flags in sorted order
+pub fn plan_line<'a>(
+    id: &PackageId,
+    version: &semver::Version,
+    slot: &str,
+    enabled: impl IntoIterator<Item = &'a str>,
+) -> String {
+    let flags: BTreeSet<&str> = enabled.into_iter().collect();
+    let mut line = format!("{}-{}:{}", id, version, slot);
+    for flag in flags {
+        line.push(' ');
+        line.push_str(flag);
+    }
+    line
+}
+
+/// Digest of a plan's lines, in order
+pub fn plan_hash<I, S>(lines: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut hasher = blake3::Hasher::new();
+    for line in lines {
+        hasher.update(line.as_ref().as_bytes());
+        hasher.update(b"\n");
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+impl super::InternalResolution {
+    /// Digest of the build plan, with each package's default USE flags
+    pub fn plan_hash(&self) -> String {
+        plan_hash(self.build_order.iter().map(|&i| {
+            let pkg = &self.packages[i];
+            plan_line(
+                &pkg.id,
+                &pkg.version,
+                &pkg.slot,
+                pkg.use_flags
+                    .iter()
+                    .filter(|f| f.default)
+                    .map(|f| f.name.as_str()),
+            )
+        }))
+    }
+}
+
+impl crate::Resolution {
+    /// Digest of the build plan, with each package's enabled USE flags
+    ///
+    /// Equal for resolutions that would build the same packages, versions,
+    /// slots and USE flags in the same order.
+    pub fn plan_hash(&self) -> String {
+        plan_hash(self.build_order.iter().map(|&i| {
+            let pkg = &self.packages[i];
+            plan_line(
+                &pkg.id,
+                &pkg.version,
+                &pkg.slot,
+                pkg.use_flags
+                    .iter()
+                    .filter(|f| f.enabled)
+                    .map(|f| f.name.as_str()),
+            )
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolver::InternalResolution;
+    use crate::{Dependency, UseFlag};
+
+    fn pkg(category: &str, name: &str, deps: &[(&str, &str)]) -> PackageInfo {
+        PackageInfo {
+            id: PackageId::new(category, name),
+            version: semver::Version::new(1, 0, 0),
+            slot: "0".to_string(),
+            description: String::new(),
+            homepage: None,
+            license: "MIT".to_string(),
+            keywords: Vec::new(),
+            use_flags: vec![UseFlag {
+                name: "ssl".to_string(),
+                description: String::new(),
+                default: true,
+            }],
+            dependencies: deps
+                .iter()
+                .map(|(c, n)| Dependency::new(PackageId::new(*c, *n)))
+                .collect(),
+            build_dependencies: Vec::new(),
+            runtime_dependencies: Vec::new(),
+            source_url: None,
+            source_hash: None,
+            buck_target: String::new(),
+            size: 0,
+            installed_size: 0,
+            required_use: String::new(),
+            blockers: Vec::new(),
+            any_of_dependencies: Vec::new(),
+            restrict: Vec::new(),
+        }
+    }
+
+    fn packages() -> Vec<PackageInfo> {
+        vec![
+            pkg(
+                "net-misc",
+                "curl",
+                &[("dev-libs", "openssl"), ("sys-libs", "zlib")],
+            ),
+            pkg(
+                "dev-vcs",
+                "git",
+                &[("net-misc", "curl"), ("sys-libs", "zlib")],
+            ),
+            pkg("sys-libs", "zlib", &[]),
+            pkg("dev-libs", "openssl", &[("sys-libs", "zlib")]),
+            pkg("app-misc", "jq", &[]),
+        ]
+    }
+
+    fn deps(pkg: &PackageInfo) -> Vec<PackageId> {
+        pkg.dependencies.iter().map(|d| d.package.clone()).collect()
+    }
+
+    fn resolution(packages: Vec<PackageInfo>) -> InternalResolution {
+        InternalResolution {
+            build_order: (0..packages.len()).collect(),
+            packages,
+            download_size: 0,
+            install_size: 0,
+            any_of_choices: Vec::new(),
+            from_host: Vec::new(),
+            stats: Default::default(),
+        }
+    }
+
+    fn names(packages: &[PackageInfo]) -> Vec<String> {
+        packages.iter().map(|p| p.id.to_string()).collect()
+    }
+
+    #[test]
+    fn test_topological_then_lexicographic() {
+        let ordered = stable_order(packages(), deps).unwrap();
+        assert_eq!(
+            names(&ordered),
+            vec![
+                "app-misc/jq",
+                "sys-libs/zlib",
+                "dev-libs/openssl",
+                "net-misc/curl",
+                "dev-vcs/git",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_identical_plans_for_identical_inputs() {
+        let first = resolution(stable_order(packages(), deps).unwrap());
+        for rotation in 1..5 {
+            let mut shuffled = packages();
+            shuffled.rotate_left(rotation);
+            shuffled.swap(0, 2);
+            let again = resolution(stable_order(shuffled, deps).unwrap());
+            assert_eq!(names(&again.packages), names(&first.packages));
+            assert_eq!(again.plan_hash(), first.plan_hash());
+        }
+
+        let mut changed = packages();
+        changed[4].version = semver::Version::new(1, 1, 0);
+        let changed = resolution(stable_order(changed, deps).unwrap());
+        assert_ne!(changed.plan_hash(), first.plan_hash());
+
+        let mut reordered = first.clone();
+        reordered.build_order.swap(0, 1);
+        assert_ne!(reordered.plan_hash(), first.plan_hash());
+    }
+
+    #[test]
+    fn test_plan_line_sorts_flags() {
+        let id = PackageId::new("net-misc", "curl");
+        let version = semver::Version::new(8, 5, 0);
+        assert_eq!(
+            plan_line(&id, &version, "0", ["ssl", "http2"]),
+            "net-misc/curl-8.5.0:0 http2 ssl"
+        );
+    }
+
+    #[test]
+    fn test_cycle_names_packages() {
+        let packages = vec![
+            pkg("dev-libs", "b", &[("dev-libs", "a")]),
+            pkg("dev-libs", "a", &[("dev-libs", "b")]),
+            pkg("dev-libs", "c", &[]),
+        ];
+        let err = stable_order(packages, deps).unwrap_err().to_string();
+        assert!(err.contains("dev-libs/a, dev-libs/b"), "{}", err);
+        assert!(!err.contains("dev-libs/c"), "{}", err);
+    }
+}
@@ -0,0 +1,427 @@
+//! Exported resolution plans
+//!
+//! `buckos install --plan-out plan.json` writes the resolution it would
+//! carry out together with what it was computed against: a digest of each
+//! repository's tree and of the portable configuration. `buckos apply-plan`
+//! executes it later or on another machine, refusing when the repositories
+//! or configuration have moved on, or when resolving again no longer gives
+//! the same plan, so what was reviewed is what gets merged.
+
+use crate::config::RepositoryConfig;
+use crate::manifest::ConfigStack;
+use crate::{Config, Error, InstallOptions, PackageId, Resolution, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::Path;
+
+/// Current plan format version
+pub const PLAN_VERSION: u32 = 1;
+
+/// Content digest of a repository tree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepoSnapshot {
+    pub name: String,
+    /// blake3 over every file path and content, VCS metadata excluded
+    pub digest: String,
+}
+
+impl RepoSnapshot {
+    /// Digest a repository as it is on disk
+    pub fn capture(repo: &RepositoryConfig) -> Result<Self> {
+        let mut hasher = blake3::Hasher::new();
+        let root = crate::repository::active_path(&repo.location);
+        if root.exists() {
+            let walker = walkdir::WalkDir::new(&root)
+                .sort_by_file_name()
+                .into_iter()
+                .filter_entry(|e| e.depth() == 0 || !is_vcs_dir(e.file_name()));
+            for entry in walker {
+                let entry = entry.map_err(|e| Error::Other(e.to_string()))?;
+                let relative = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+                let file_type = entry.file_type();
+                if file_type.is_file() {
+                    hasher.update(b"F ");
+                    hasher.update(relative.as_os_str().as_encoded_bytes());
+                    hasher.update(b"\0");
+                    hasher.update(crate::cache::compute_blake3(entry.path())?.as_bytes());
+                } else if file_type.is_symlink() {
+                    hasher.update(b"L ");
+                    hasher.update(relative.as_os_str().as_encoded_bytes());
+                    hasher.update(b"\0");
+                    let target = std::fs::read_link(entry.path())?;
+                    hasher.update(target.as_os_str().as_encoded_bytes());
+                } else {
+                    continue;
+                }
+                hasher.update(b"\n");
+            }
+        }
+        Ok(Self {
+            name: repo.name.clone(),
+            digest: hasher.finalize().to_hex().to_string(),
+        })
+    }
+}
+
+fn is_vcs_dir(name: &std::ffi::OsStr) -> bool {
+    matches!(name.to_str(), Some(".git" | ".hg" | ".svn"))
+}
+
+/// Digest of the portable configuration a plan was resolved with
+pub fn config_hash(config: &Config) -> Result<String> {
+    let stack = serde_json::to_vec(&ConfigStack::from_config(config))?;
+    Ok(blake3::hash(&stack).to_hex().to_string())
+}
+
+/// A package in a plan, in build order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedPackage {
+    pub id: PackageId,
+    pub version: semver::Version,
+    pub slot: String,
+    /// Enabled USE flags
+    #[serde(default)]
+    pub use_flags: BTreeSet<String>,
+}
+
+impl PlannedPackage {
+    fn line(&self) -> String {
+        super::plan_line(
+            &self.id,
+            &self.version,
+            &self.slot,
+            self.use_flags.iter().map(String::as_str),
+        )
+    }
+}
+
+/// A resolution to execute later or elsewhere
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolutionPlan {
+    /// Plan format version
+    pub version: u32,
+    /// When the plan was resolved
+    pub created: chrono::DateTime<chrono::Utc>,
+    /// Packages as requested, with sets expanded
+    pub requested: Vec<String>,
+    /// Options the plan was resolved with
+    pub options: InstallOptions,
+    /// [`Resolution::plan_hash`] of the packages below
+    pub plan_hash: String,
+    /// Packages in build order
+    pub packages: Vec<PlannedPackage>,
+    /// Repositories the plan was resolved against
+    pub repositories: Vec<RepoSnapshot>,
+    /// Digest of the portable configuration
+    pub config_hash: String,
+}
+
+/// Why a plan cannot be applied as it stands
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlanMismatch {
+    /// The plan's package list does not match its plan hash
+    Edited,
+    /// A repository the plan was resolved against is not configured
+    MissingRepository(String),
+    /// A repository's tree differs from the plan's snapshot
+    RepositoryChanged(String),
+    /// A configured repository was not part of the plan's resolution
+    ExtraRepository(String),
+    /// The portable configuration differs
+    ConfigChanged,
+}
+
+impl fmt::Display for PlanMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Edited => write!(f, "package list does not match the plan hash"),
+            Self::MissingRepository(name) => write!(f, "repository {} is not configured", name),
+            Self::RepositoryChanged(name) => {
+                write!(f, "repository {} changed since the plan was made", name)
+            }
+            Self::ExtraRepository(name) => {
+                write!(f, "repository {} was not used to make the plan", name)
+            }
+            Self::ConfigChanged => write!(f, "configuration changed since the plan was made"),
+        }
+    }
+}
+
+impl ResolutionPlan {
+    /// Record a resolution with the repositories and configuration it was
+    /// computed against
+    pub fn capture(
+        config: &Config,
+        requested: &[String],
+        options: &InstallOptions,
+        resolution: &Resolution,
+    ) -> Result<Self> {
+        let packages = resolution
+            .build_order
+            .iter()
+            .map(|&i| {
+                let pkg = &resolution.packages[i];
+                PlannedPackage {
+                    id: pkg.id.clone(),
+                    version: pkg.version.clone(),
+                    slot: pkg.slot.clone(),
+                    use_flags: pkg
+                        .use_flags
+                        .iter()
+                        .filter(|f| f.enabled)
+                        .map(|f| f.name.clone())
+                        .collect(),
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            version: PLAN_VERSION,
+            created: chrono::Utc::now(),
+            requested: requested.to_vec(),
+            options: options.clone(),
+            plan_hash: resolution.plan_hash(),
+            packages,
+            repositories: config
+                .repositories
+                .iter()
+                .map(RepoSnapshot::capture)
+                .collect::<Result<_>>()?,
+            config_hash: config_hash(config)?,
+        })
+    }
+
+    /// Digest of the package list, comparable to [`Resolution::plan_hash`]
+    pub fn packages_hash(&self) -> String {
+        super::plan_hash(self.packages.iter().map(PlannedPackage::line))
+    }
+
+    /// Check that the plan still holds for this configuration and its
+    /// repositories
+    pub fn check(&self, config: &Config) -> Result<Vec<PlanMismatch>> {
+        let mut mismatches = Vec::new();
+        if self.packages_hash() != self.plan_hash {
+            mismatches.push(PlanMismatch::Edited);
+        }
+
+        for snapshot in &self.repositories {
+            match config.repositories.iter().find(|r| r.name == snapshot.name) {
+                None => mismatches.push(PlanMismatch::MissingRepository(snapshot.name.clone())),
+                Some(repo) => {
+                    if RepoSnapshot::capture(repo)?.digest != snapshot.digest {
+                        mismatches.push(PlanMismatch::RepositoryChanged(snapshot.name.clone()));
+                    }
+                }
+            }
+        }
+        for repo in &config.repositories {
+            if !self.repositories.iter().any(|s| s.name == repo.name) {
+                mismatches.push(PlanMismatch::ExtraRepository(repo.name.clone()));
+            }
+        }
+
+        if config_hash(config)? != self.config_hash {
+            mismatches.push(PlanMismatch::ConfigChanged);
+        }
+        Ok(mismatches)
+    }
+
+    /// Start of the plan hash, as shown to users
+    pub fn short_hash(&self) -> &str {
+        self.plan_hash.get(..16).unwrap_or(&self.plan_hash)
+    }
+
+    /// Parse a plan
+    pub fn parse(content: &str) -> Result<Self> {
+        let plan: Self = serde_json::from_str(content)
+            .map_err(|e| Error::Other(format!("Invalid plan: {}", e)))?;
+        if plan.version > PLAN_VERSION {
+            return Err(Error::Other(format!(
+                "Plan version {} is newer than supported version {}",
+                plan.version, PLAN_VERSION
+            )));
+        }
+        // Plans are shown by a prefix of their hash, so it must be a digest
+        let is_digest = plan.plan_hash.len() == 64
+            && plan
+                .plan_hash
+                .bytes()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b));
+        if !is_digest {
+            return Err(Error::Other(format!(
+                "Invalid plan: plan_hash {:?} is not a hex digest",
+                plan.plan_hash
+            )));
+        }
+        Ok(plan)
+    }
+
+    /// Load a plan from a file
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Write the plan to a file as JSON
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ResolvedPackage, UseFlagStatus};
+
+    fn resolved(name: &str, flags: &[(&str, bool)]) -> ResolvedPackage {
+        ResolvedPackage {
+            id: PackageId::new("dev-libs", name),
+            version: semver::Version::new(1, 0, 0),
+            slot: "0".to_string(),
+            description: String::new(),
+            use_flags: flags
+                .iter()
+                .map(|(name, enabled)| UseFlagStatus {
+                    name: name.to_string(),
+                    enabled: *enabled,
+                })
+                .collect(),
+            dependencies: Vec::new(),
+            size: 0,
+            installed_size: 0,
+            is_upgrade: false,
+            is_rebuild: false,
+            is_new: true,
+            old_version: None,
+        }
+    }
+
+    fn setup(dir: &Path) -> (Config, Resolution) {
+        let repo = dir.join("repo");
+        std::fs::create_dir_all(repo.join("packages/dev-libs/zlib")).unwrap();
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::write(repo.join("packages/dev-libs/zlib/BUCK"), "package()").unwrap();
+        std::fs::write(repo.join(".git/HEAD"), "ref: refs/heads/main").unwrap();
+
+        let config = Config {
+            repositories: vec![RepositoryConfig {
+                name: "buckos".to_string(),
+                location: repo,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let resolution = Resolution {
+            packages: vec![
+                resolved("zlib", &[("static", false)]),
+                resolved("openssl", &[("asm", true), ("ktls", true)]),
+            ],
+            build_order: vec![0, 1],
+            download_size: 0,
+            install_size: 0,
+            any_of_choices: Vec::new(),
+            from_host: Vec::new(),
+            stats: Default::default(),
+        };
+        (config, resolution)
+    }
+
+    #[test]
+    fn test_plan_round_trip_and_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let (config, resolution) = setup(dir.path());
+        let opts = InstallOptions {
+            use_flags: vec!["ktls".to_string()],
+            ..Default::default()
+        };
+        let plan = ResolutionPlan::capture(
+            &config,
+            &["dev-libs/openssl".to_string()],
+            &opts,
+            &resolution,
+        )
+        .unwrap();
+        assert_eq!(plan.packages_hash(), resolution.plan_hash());
+        assert_eq!(plan.packages[1].use_flags.len(), 2);
+
+        let path = dir.path().join("plan.json");
+        plan.save(&path).unwrap();
+        let loaded = ResolutionPlan::load(&path).unwrap();
+        assert_eq!(loaded.packages, plan.packages);
+        assert_eq!(loaded.options.use_flags, opts.use_flags);
+        assert!(loaded.check(&config).unwrap().is_empty());
+
+        // VCS metadata does not count, repository contents do
+        let repo = &config.repositories[0].location;
+        std::fs::write(repo.join(".git/HEAD"), "ref: refs/heads/other").unwrap();
+        assert!(loaded.check(&config).unwrap().is_empty());
+        std::fs::write(repo.join("packages/dev-libs/zlib/BUCK"), "package(x)").unwrap();
+        assert_eq!(
+            loaded.check(&config).unwrap(),
+            vec![PlanMismatch::RepositoryChanged("buckos".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_check_reports_config_and_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut config, resolution) = setup(dir.path());
+        let mut plan =
+            ResolutionPlan::capture(&config, &[], &InstallOptions::default(), &resolution).unwrap();
+
+        plan.packages[0].version = semver::Version::new(1, 0, 1);
+        config.cflags = "-O3".to_string();
+        config.repositories.push(RepositoryConfig {
+            name: "overlay".to_string(),
+            location: dir.path().join("overlay"),
+            ..Default::default()
+        });
+        assert_eq!(
+            plan.check(&config).unwrap(),
+            vec![
+                PlanMismatch::Edited,
+                PlanMismatch::ExtraRepository("overlay".to_string()),
+                PlanMismatch::ConfigChanged,
+            ]
+        );
+
+        config.repositories.remove(0);
+        assert!(plan
+            .check(&config)
+            .unwrap()
+            .contains(&PlanMismatch::MissingRepository("buckos".to_string())));
+    }
+
+    #[test]
+    fn test_newer_plan_version_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let (config, resolution) = setup(dir.path());
+        let mut plan =
+            ResolutionPlan::capture(&config, &[], &InstallOptions::default(), &resolution).unwrap();
+        plan.version = PLAN_VERSION + 1;
+        let content = serde_json::to_string(&plan).unwrap();
+        assert!(ResolutionPlan::parse(&content).is_err());
+    }
+
+    #[test]
+    fn test_malformed_plan_hash_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let (config, resolution) = setup(dir.path());
+        let plan =
+            ResolutionPlan::capture(&config, &[], &InstallOptions::default(), &resolution).unwrap();
+        let content = serde_json::to_string(&plan).unwrap();
+        assert!(ResolutionPlan::parse(&content).is_ok());
+
+        for hash in [
+            "abc",
+            "é".repeat(32).as_str(),
+            &plan.plan_hash.to_uppercase(),
+        ] {
+            let mut bad = plan.clone();
+            bad.plan_hash = hash.to_string();
+            let content = serde_json::to_string(&bad).unwrap();
+            assert!(ResolutionPlan::parse(&content).is_err(), "{}", hash);
+        }
+    }
+}
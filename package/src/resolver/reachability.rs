@@ -0,0 +1,77 @@
+//! Dependency reachability graph
+//!
+//! Answers "which packages are pulled in by this package" over the
+//! runtime dependency graph of a package set. Used by world-set analysis
+//! to find entries that are already required by other entries.
+
+use crate::{PackageId, PackageInfo};
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::visit::Dfs;
+use std::collections::{HashMap, HashSet};
+
+/// Directed graph of package -> runtime dependency edges
+pub struct ReachabilityGraph {
+    graph: DiGraph<PackageId, ()>,
+    nodes: HashMap<PackageId, NodeIndex>,
+}
+
+impl ReachabilityGraph {
+    /// Build a graph from package metadata
+    ///
+    /// Regular and runtime dependencies are followed; build-time dependencies
+    /// are not, since they are not needed to keep a package installed.
+    pub fn new(packages: &[PackageInfo]) -> Self {
+        let mut graph = DiGraph::new();
+        let mut nodes: HashMap<PackageId, NodeIndex> = HashMap::new();
+
+        let mut node_for = |graph: &mut DiGraph<PackageId, ()>, id: &PackageId| {
+            *nodes
+                .entry(id.clone())
+                .or_insert_with(|| graph.add_node(id.clone()))
+        };
+
+        for pkg in packages {
+            let from = node_for(&mut graph, &pkg.id);
+            for dep in pkg
+                .dependencies
+                .iter()
+                .chain(pkg.runtime_dependencies.iter())
+            {
+                let to = node_for(&mut graph, &dep.package);
+                graph.update_edge(from, to, ());
+            }
+        }
+
+        Self { graph, nodes }
+    }
+
+    /// Packages reachable from `root`, not including `root` itself
+    pub fn reachable_from(&self, root: &PackageId) -> HashSet<PackageId> {
+        let mut reachable = HashSet::new();
+        let Some(&start) = self.nodes.get(root) else {
+            return reachable;
+        };
+
+        let mut dfs = Dfs::new(&self.graph, start);
+        while let Some(node) = dfs.next(&self.graph) {
+            if node != start {
+                reachable.insert(self.graph[node].clone());
+            }
+        }
+
+        reachable
+    }
+
+    /// Find a root (other than `target`) from which `target` is reachable
+    pub fn required_by<'a>(
+        &self,
+        target: &PackageId,
+        roots: impl IntoIterator<Item = &'a PackageId>,
+    ) -> Option<PackageId> {
+        roots
+            .into_iter()
+            .filter(|root| *root != target)
+            .find(|root| self.reachable_from(root).contains(target))
+            .cloned()
+    }
+}
@@ -0,0 +1,631 @@
+//! minisign-compatible Ed25519 signatures
+//!
+//! Keys and signatures use minisign's file formats, so packages signed here
+//! verify with `minisign -V` and keys made by `minisign -G -W` can sign.
+//! Data is signed prehashed with BLAKE2b-512, as minisign does by default,
+//! and every signature carries a trusted comment with its timestamp.
+//! Secret keys are stored unencrypted and protected by file permissions;
+//! password-protected minisign keys are not supported.
+//!
+//! Public keys trusted for verification are kept one per file in a trust
+//! store directory, named by key ID.
+
+use crate::{Error, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Blake2b512, Digest};
+use ed25519_dalek::{Signer, Verifier};
+use std::path::{Path, PathBuf};
+
+/// Signature algorithm of keys, and of signatures over raw data
+const ALG_ED25519: [u8; 2] = *b"Ed";
+/// Signature algorithm of signatures over a BLAKE2b-512 digest
+const ALG_PREHASHED: [u8; 2] = *b"ED";
+/// Checksum algorithm of secret keys
+const CHECKSUM_BLAKE2B: [u8; 2] = *b"B2";
+/// Key derivation algorithm of a password-protected secret key
+const KDF_SCRYPT: [u8; 2] = *b"Sc";
+
+const UNTRUSTED_PREFIX: &str = "untrusted comment: ";
+const TRUSTED_PREFIX: &str = "trusted comment: ";
+
+/// File extension of public keys
+pub const PUBLIC_KEY_EXTENSION: &str = "pub";
+/// File extension of secret keys
+pub const SECRET_KEY_EXTENSION: &str = "key";
+/// File extension of detached signatures
+pub const SIGNATURE_EXTENSION: &str = "minisig";
+
+/// Random number identifying a key pair, shown as 16 hex digits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyId(pub [u8; 8]);
+
+impl std::fmt::Display for KeyId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // minisign prints the little-endian number
+        write!(f, "{:016X}", u64::from_le_bytes(self.0))
+    }
+}
+
+impl std::str::FromStr for KeyId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        u64::from_str_radix(s, 16)
+            .map(|n| KeyId(n.to_le_bytes()))
+            .map_err(|_| Error::Signing(format!("Invalid minisign key ID: {}", s)))
+    }
+}
+
+/// A minisign public key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKey {
+    pub key_id: KeyId,
+    key: ed25519_dalek::VerifyingKey,
+}
+
+impl PublicKey {
+    /// Parse a public key file, or the bare base64 line `minisign -P` takes
+    pub fn parse(text: &str) -> Result<Self> {
+        let line = text
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty() && !l.starts_with(UNTRUSTED_PREFIX))
+            .ok_or_else(|| Error::Signing("Empty minisign public key".to_string()))?;
+        let bytes = decode(line, 42, "public key")?;
+        if bytes[..2] != ALG_ED25519 {
+            return Err(Error::Signing(
+                "Unsupported minisign public key algorithm".to_string(),
+            ));
+        }
+        let key = ed25519_dalek::VerifyingKey::from_bytes(&bytes[10..42].try_into().unwrap())
+            .map_err(|e| Error::Signing(format!("Invalid minisign public key: {}", e)))?;
+        Ok(Self {
+            key_id: KeyId(bytes[2..10].try_into().unwrap()),
+            key,
+        })
+    }
+
+    /// The base64 line identifying the key
+    pub fn encoded(&self) -> String {
+        let mut bytes = Vec::with_capacity(42);
+        bytes.extend_from_slice(&ALG_ED25519);
+        bytes.extend_from_slice(&self.key_id.0);
+        bytes.extend_from_slice(self.key.as_bytes());
+        BASE64.encode(bytes)
+    }
+
+    /// Contents of a public key file
+    pub fn to_file(&self) -> String {
+        format!(
+            "{}minisign public key {}\n{}\n",
+            UNTRUSTED_PREFIX,
+            self.key_id,
+            self.encoded()
+        )
+    }
+
+    /// Hex of the raw key
+    pub fn fingerprint(&self) -> String {
+        hex::encode(self.key.as_bytes())
+    }
+
+    /// Check `signature` over `data`, returning its trusted comment
+    pub fn verify(&self, data: &[u8], signature: &Signature) -> Result<String> {
+        if signature.key_id != self.key_id {
+            return Err(Error::Signing(format!(
+                "Signature was made by key {}, not {}",
+                signature.key_id, self.key_id
+            )));
+        }
+        let sig = ed25519_dalek::Signature::from_bytes(&signature.signature);
+        let valid = if signature.prehashed {
+            self.key.verify(&Blake2b512::digest(data), &sig)
+        } else {
+            self.key.verify(data, &sig)
+        };
+        valid.map_err(|_| Error::Signing("Signature does not match the data".to_string()))?;
+
+        let global = ed25519_dalek::Signature::from_bytes(&signature.global_signature);
+        self.key
+            .verify(&signature.global_message(), &global)
+            .map_err(|_| Error::Signing("Trusted comment was tampered with".to_string()))?;
+        Ok(signature.trusted_comment.clone())
+    }
+}
+
+/// A minisign secret key
+pub struct SecretKey {
+    pub key_id: KeyId,
+    key: ed25519_dalek::SigningKey,
+}
+
+impl SecretKey {
+    /// Generate a new key pair
+    pub fn generate() -> Self {
+        let mut key_id = [0u8; 8];
+        rand_core::RngCore::fill_bytes(&mut rand_core::OsRng, &mut key_id);
+        Self {
+            key_id: KeyId(key_id),
+            key: ed25519_dalek::SigningKey::generate(&mut rand_core::OsRng),
+        }
+    }
+
+    /// Parse an unencrypted secret key file
+    pub fn parse(text: &str) -> Result<Self> {
+        let line = text
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty() && !l.starts_with(UNTRUSTED_PREFIX))
+            .ok_or_else(|| Error::Signing("Empty minisign secret key".to_string()))?;
+        let bytes = decode(line, 158, "secret key")?;
+        if bytes[..2] != ALG_ED25519 || bytes[4..6] != CHECKSUM_BLAKE2B {
+            return Err(Error::Signing(
+                "Unsupported minisign secret key algorithm".to_string(),
+            ));
+        }
+        if bytes[2..4] == KDF_SCRYPT {
+            return Err(Error::Signing(
+                "Password-protected minisign keys are not supported; \
+                 generate one without a password (minisign -G -W)"
+                    .to_string(),
+            ));
+        }
+        // Salt and scrypt limits are unused without a password
+        let key_id: [u8; 8] = bytes[54..62].try_into().unwrap();
+        let secret: [u8; 64] = bytes[62..126].try_into().unwrap();
+        if checksum(&key_id, &secret) != bytes[126..158] {
+            return Err(Error::Signing(
+                "minisign secret key checksum mismatch".to_string(),
+            ));
+        }
+        let key = ed25519_dalek::SigningKey::from_keypair_bytes(&secret)
+            .map_err(|e| Error::Signing(format!("Invalid minisign secret key: {}", e)))?;
+        Ok(Self {
+            key_id: KeyId(key_id),
+            key,
+        })
+    }
+
+    /// Contents of an unencrypted secret key file
+    pub fn to_file(&self) -> String {
+        let secret = self.key.to_keypair_bytes();
+        let mut bytes = Vec::with_capacity(158);
+        bytes.extend_from_slice(&ALG_ED25519);
+        bytes.extend_from_slice(&[0, 0]);
+        bytes.extend_from_slice(&CHECKSUM_BLAKE2B);
+        // No salt or scrypt limits without a password
+        bytes.extend_from_slice(&[0u8; 48]);
+        bytes.extend_from_slice(&self.key_id.0);
+        bytes.extend_from_slice(&secret);
+        bytes.extend_from_slice(&checksum(&self.key_id.0, &secret));
+        format!(
+            "{}minisign secret key {}\n{}\n",
+            UNTRUSTED_PREFIX,
+            self.key_id,
+            BASE64.encode(bytes)
+        )
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey {
+            key_id: self.key_id,
+            key: self.key.verifying_key(),
+        }
+    }
+
+    /// Sign `data` prehashed, with `trusted_comment` bound to the signature
+    pub fn sign(&self, data: &[u8], trusted_comment: &str) -> Signature {
+        let signature = self.key.sign(&Blake2b512::digest(data)).to_bytes();
+        let mut sig = Signature {
+            prehashed: true,
+            key_id: self.key_id,
+            signature,
+            trusted_comment: trusted_comment.to_string(),
+            global_signature: [0; 64],
+        };
+        sig.global_signature = self.key.sign(&sig.global_message()).to_bytes();
+        sig
+    }
+}
+
+/// A detached minisign signature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    /// Made over the BLAKE2b-512 digest of the data rather than the data
+    pub prehashed: bool,
+    pub key_id: KeyId,
+    signature: [u8; 64],
+    /// Signed metadata, `timestamp:<unix time>` and more
+    pub trusted_comment: String,
+    global_signature: [u8; 64],
+}
+
+impl Signature {
+    /// Whether `text` looks like a minisign signature rather than an
+    /// OpenPGP one
+    pub fn is_minisign(text: &str) -> bool {
+        text.trim_start().starts_with(UNTRUSTED_PREFIX)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut lines = text.lines().map(str::trim_end);
+        let invalid = || Error::Signing("Malformed minisign signature".to_string());
+        lines
+            .next()
+            .filter(|l| l.starts_with(UNTRUSTED_PREFIX))
+            .ok_or_else(invalid)?;
+        let bytes = decode(lines.next().ok_or_else(invalid)?, 74, "signature")?;
+        let prehashed = match [bytes[0], bytes[1]] {
+            ALG_PREHASHED => true,
+            ALG_ED25519 => false,
+            _ => {
+                return Err(Error::Signing(
+                    "Unsupported minisign signature algorithm".to_string(),
+                ))
+            }
+        };
+        let trusted_comment = lines
+            .next()
+            .and_then(|l| l.strip_prefix(TRUSTED_PREFIX))
+            .ok_or_else(invalid)?
+            .to_string();
+        let global = decode(lines.next().ok_or_else(invalid)?, 64, "signature")?;
+        Ok(Self {
+            prehashed,
+            key_id: KeyId(bytes[2..10].try_into().unwrap()),
+            signature: bytes[10..74].try_into().unwrap(),
+            trusted_comment,
+            global_signature: global.try_into().unwrap(),
+        })
+    }
+
+    /// Unix time from a `timestamp:` field of the trusted comment
+    pub fn timestamp(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        let secs = self
+            .trusted_comment
+            .split('\t')
+            .find_map(|field| field.strip_prefix("timestamp:"))?
+            .parse()
+            .ok()?;
+        chrono::DateTime::from_timestamp(secs, 0)
+    }
+
+    /// The signature and trusted comment, signed again so the comment
+    /// cannot be altered
+    fn global_message(&self) -> Vec<u8> {
+        let mut message = self.signature.to_vec();
+        message.extend_from_slice(self.trusted_comment.as_bytes());
+        message
+    }
+}
+
+impl std::fmt::Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut bytes = Vec::with_capacity(74);
+        bytes.extend_from_slice(if self.prehashed {
+            &ALG_PREHASHED
+        } else {
+            &ALG_ED25519
+        });
+        bytes.extend_from_slice(&self.key_id.0);
+        bytes.extend_from_slice(&self.signature);
+        writeln!(
+            f,
+            "{}signature from minisign secret key {}",
+            UNTRUSTED_PREFIX, self.key_id
+        )?;
+        writeln!(f, "{}", BASE64.encode(bytes))?;
+        writeln!(f, "{}{}", TRUSTED_PREFIX, self.trusted_comment)?;
+        writeln!(f, "{}", BASE64.encode(self.global_signature))
+    }
+}
+
+/// Secret keys of this machine and the public keys it trusts
+#[derive(Debug, Clone)]
+pub struct Keyring {
+    /// Secret keys and their public halves
+    keys_dir: PathBuf,
+    /// Trusted public keys
+    trusted_dir: PathBuf,
+}
+
+impl Keyring {
+    pub fn new(keys_dir: PathBuf, trusted_dir: PathBuf) -> Self {
+        Self {
+            keys_dir,
+            trusted_dir,
+        }
+    }
+
+    /// Generate a key pair, saving it in the keys directory
+    ///
+    /// The public half is trusted as well, so packages signed here verify.
+    pub fn generate(&self) -> Result<PublicKey> {
+        let secret = SecretKey::generate();
+        let public = secret.public_key();
+        std::fs::create_dir_all(&self.keys_dir)?;
+        write_private(
+            &self.key_path(&self.keys_dir, secret.key_id, SECRET_KEY_EXTENSION),
+            &secret.to_file(),
+        )?;
+        std::fs::write(
+            self.key_path(&self.keys_dir, secret.key_id, PUBLIC_KEY_EXTENSION),
+            public.to_file(),
+        )?;
+        self.trust(&public)?;
+        Ok(public)
+    }
+
+    /// Secret key to sign with: `key_id`, or the only one there is
+    pub fn secret_key(&self, key_id: Option<&str>) -> Result<SecretKey> {
+        let path = match key_id {
+            Some(id) => self.key_path(&self.keys_dir, id.parse()?, SECRET_KEY_EXTENSION),
+            None => {
+                let keys = list_dir(&self.keys_dir, SECRET_KEY_EXTENSION)?;
+                match keys.as_slice() {
+                    [only] => only.clone(),
+                    [] => {
+                        return Err(Error::Signing(
+                            "No minisign secret key; run 'buckos sign generate-key'".to_string(),
+                        ))
+                    }
+                    _ => {
+                        return Err(Error::Signing(
+                            "Several minisign secret keys; choose one with --key".to_string(),
+                        ))
+                    }
+                }
+            }
+        };
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| Error::Signing(format!("Failed to read key {}: {}", path.display(), e)))?;
+        SecretKey::parse(&text)
+    }
+
+    /// Public keys with secret halves in the keys directory
+    pub fn own_keys(&self) -> Result<Vec<PublicKey>> {
+        let mut keys = Vec::new();
+        for path in list_dir(&self.keys_dir, SECRET_KEY_EXTENSION)? {
+            keys.push(SecretKey::parse(&std::fs::read_to_string(path)?)?.public_key());
+        }
+        Ok(keys)
+    }
+
+    /// Keys in the trust store
+    pub fn trusted_keys(&self) -> Result<Vec<PublicKey>> {
+        let mut keys = Vec::new();
+        for path in list_dir(&self.trusted_dir, PUBLIC_KEY_EXTENSION)? {
+            keys.push(PublicKey::parse(&std::fs::read_to_string(path)?)?);
+        }
+        Ok(keys)
+    }
+
+    /// Trusted key with `key_id`
+    pub fn trusted_key(&self, key_id: KeyId) -> Result<Option<PublicKey>> {
+        let path = self.key_path(&self.trusted_dir, key_id, PUBLIC_KEY_EXTENSION);
+        match std::fs::read_to_string(&path) {
+            Ok(text) => PublicKey::parse(&text).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Own or trusted key matching `key_id`
+    pub fn find(&self, key_id: &str) -> Result<Option<PublicKey>> {
+        let key_id: KeyId = key_id.parse()?;
+        if let Some(key) = self.trusted_key(key_id)? {
+            return Ok(Some(key));
+        }
+        Ok(self.own_keys()?.into_iter().find(|k| k.key_id == key_id))
+    }
+
+    /// Add `key` to the trust store
+    pub fn trust(&self, key: &PublicKey) -> Result<()> {
+        std::fs::create_dir_all(&self.trusted_dir)?;
+        std::fs::write(
+            self.key_path(&self.trusted_dir, key.key_id, PUBLIC_KEY_EXTENSION),
+            key.to_file(),
+        )?;
+        Ok(())
+    }
+
+    /// Remove a key from the trust store, and with `secret`, the key pair
+    /// from the keys directory; returns whether anything was removed
+    pub fn remove(&self, key_id: &str, secret: bool) -> Result<bool> {
+        let key_id: KeyId = key_id.parse()?;
+        let mut paths = vec![self.key_path(&self.trusted_dir, key_id, PUBLIC_KEY_EXTENSION)];
+        if secret {
+            paths.push(self.key_path(&self.keys_dir, key_id, SECRET_KEY_EXTENSION));
+            paths.push(self.key_path(&self.keys_dir, key_id, PUBLIC_KEY_EXTENSION));
+        }
+        let mut removed = false;
+        for path in paths {
+            match std::fs::remove_file(&path) {
+                Ok(()) => removed = true,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Whether `key_id` has its secret half here
+    pub fn is_own(&self, key_id: KeyId) -> bool {
+        self.key_path(&self.keys_dir, key_id, SECRET_KEY_EXTENSION)
+            .exists()
+    }
+
+    fn key_path(&self, dir: &Path, key_id: KeyId, extension: &str) -> PathBuf {
+        dir.join(format!("{}.{}", key_id, extension))
+    }
+}
+
+/// BLAKE2b-256 checksum of a secret key
+fn checksum(key_id: &[u8; 8], secret: &[u8; 64]) -> [u8; 32] {
+    let mut hasher = Blake2b::<U32>::new();
+    hasher.update(ALG_ED25519);
+    hasher.update(key_id);
+    hasher.update(secret);
+    hasher.finalize().into()
+}
+
+fn decode(line: &str, len: usize, what: &str) -> Result<Vec<u8>> {
+    let bytes = BASE64
+        .decode(line.trim())
+        .map_err(|e| Error::Signing(format!("Invalid minisign {}: {}", what, e)))?;
+    if bytes.len() != len {
+        return Err(Error::Signing(format!(
+            "Invalid minisign {}: {} bytes, expected {}",
+            what,
+            bytes.len(),
+            len
+        )));
+    }
+    Ok(bytes)
+}
+
+/// Files in `dir` with `extension`, sorted; none if `dir` is missing
+fn list_dir(dir: &Path, extension: &str) -> Result<Vec<PathBuf>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == extension))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Write a file only its owner can read
+fn write_private(path: &Path, content: &str) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Key and signatures of "test" made by minisign, from the
+    // minisign-verify test suite
+    const PUBLIC_KEY: &str = "untrusted comment: minisign public key E7620F1842B4E81F
+RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RWQf6LRCGA9i59SLOFxz6NxvASXDJeRtuZykwQepbDEGt87ig1BNpWaVWuNrm73YiIiJbq71Wi+dP9eKL8OC351vwIasSSbXxwA=
+trusted comment: timestamp:1555779966\tfile:test
+QtKMXWyYcwdpZAlPF7tE2ENJkRd1ujvKjlj1m9RtHTBnZPa5WKU5uWRs5GoP5M/VqE81QFuMKI5k/SfNQUaOAA==
+";
+    const PREHASHED_SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1556193335\tfile:test
+y/rUw2y8/hOUYjZU71eHp/Wo1KZ40fGy2VJEDl34XMJM+TX48Ss/17u3IvIfbVR1FkZZSNCisQbuQY+bHwhEBg==
+";
+
+    #[test]
+    fn test_key_id_display_round_trips() {
+        let id = KeyId([0x1f, 0xe8, 0xb4, 0x42, 0x18, 0x0f, 0x62, 0xe7]);
+        assert_eq!(id.to_string(), "E7620F1842B4E81F");
+        assert_eq!("E7620F1842B4E81F".parse::<KeyId>().unwrap(), id);
+        assert_eq!(PublicKey::parse(PUBLIC_KEY).unwrap().key_id, id);
+    }
+
+    #[test]
+    fn test_verifies_minisign_signatures() {
+        let public = PublicKey::parse(PUBLIC_KEY).unwrap();
+        assert_eq!(public.to_file(), format!("{}\n", PUBLIC_KEY));
+
+        let legacy = Signature::parse(SIGNATURE).unwrap();
+        assert!(!legacy.prehashed);
+        assert_eq!(Signature::parse(&legacy.to_string()).unwrap(), legacy);
+        assert_eq!(
+            public.verify(b"test", &legacy).unwrap(),
+            "timestamp:1555779966\tfile:test"
+        );
+        assert!(public.verify(b"Test", &legacy).is_err());
+
+        let prehashed = Signature::parse(PREHASHED_SIGNATURE).unwrap();
+        assert!(prehashed.prehashed);
+        assert_eq!(prehashed.timestamp().unwrap().timestamp(), 1_556_193_335);
+        assert!(public.verify(b"test", &prehashed).is_ok());
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let secret = SecretKey::generate();
+        let public = secret.public_key();
+        let signature = secret.sign(b"package contents", "timestamp:1700000000\tfile:a.tar");
+
+        let parsed = Signature::parse(&signature.to_string()).unwrap();
+        assert_eq!(parsed, signature);
+        assert!(Signature::is_minisign(&signature.to_string()));
+        assert_eq!(parsed.timestamp().unwrap().timestamp(), 1_700_000_000);
+        assert_eq!(
+            public.verify(b"package contents", &parsed).unwrap(),
+            "timestamp:1700000000\tfile:a.tar"
+        );
+        assert!(public.verify(b"tampered contents", &parsed).is_err());
+
+        let mut forged = parsed.clone();
+        forged.trusted_comment = "timestamp:1800000000".to_string();
+        assert!(public.verify(b"package contents", &forged).is_err());
+        let other = SecretKey::generate().public_key();
+        assert!(other.verify(b"package contents", &parsed).is_err());
+    }
+
+    #[test]
+    fn test_key_files_round_trip() {
+        let secret = SecretKey::generate();
+        let reparsed = SecretKey::parse(&secret.to_file()).unwrap();
+        assert_eq!(reparsed.public_key(), secret.public_key());
+        let public = PublicKey::parse(&secret.public_key().to_file()).unwrap();
+        assert_eq!(public, secret.public_key());
+
+        let mut damaged = BASE64
+            .decode(secret.to_file().lines().nth(1).unwrap())
+            .unwrap();
+        damaged[100] ^= 1;
+        assert!(SecretKey::parse(&BASE64.encode(damaged)).is_err());
+    }
+
+    #[test]
+    fn test_keyring() {
+        let dir = tempfile::tempdir().unwrap();
+        let keyring = Keyring::new(dir.path().join("keys"), dir.path().join("trusted"));
+        assert!(keyring.secret_key(None).is_err());
+
+        let public = keyring.generate().unwrap();
+        let secret = keyring.secret_key(None).unwrap();
+        assert_eq!(secret.public_key(), public);
+        assert!(keyring.is_own(public.key_id));
+        assert_eq!(
+            keyring.trusted_key(public.key_id).unwrap(),
+            Some(public.clone())
+        );
+        assert_eq!(
+            keyring.find(&public.key_id.to_string()).unwrap(),
+            Some(public.clone())
+        );
+
+        keyring.generate().unwrap();
+        assert!(keyring.secret_key(None).is_err());
+        assert!(keyring.remove(&public.key_id.to_string(), false).unwrap());
+        assert_eq!(keyring.trusted_key(public.key_id).unwrap(), None);
+        // Still found among own keys
+        assert!(keyring.find(&public.key_id.to_string()).unwrap().is_some());
+        assert!(keyring.remove(&public.key_id.to_string(), true).unwrap());
+        assert!(keyring.find(&public.key_id.to_string()).unwrap().is_none());
+    }
+}
@@ -1,9 +1,19 @@
 //! Security features
 //!
-//! GLSA support, package signing, and hardened build options.
+//! GLSA support, package signing (GPG or minisign), fs-verity protection of
+//! installed files, and hardened build options.
 
 pub mod glsa;
+#[cfg(feature = "signing")]
+pub mod minisign;
+#[cfg(feature = "signing")]
+pub mod rotation;
+#[cfg(feature = "signing")]
 pub mod signing;
+#[cfg(feature = "signing")]
+pub mod threshold;
+pub mod verity;
 
 pub use glsa::*;
+#[cfg(feature = "signing")]
 pub use signing::*;
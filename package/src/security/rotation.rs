@@ -0,0 +1,538 @@
+//! Key rotation and revocation for signed repositories
+//!
+//! A repository ships an append-only list of key statements in
+//! `metadata/key-rotation.toml`. Each statement introduces a new signing key
+//! or revokes one, and is signed by a key the repository already trusts, so
+//! a new key is vouched for by the old one:
+//!
+//! ```toml
+//! [[statement]]
+//! action = "introduce"
+//! key_id = "9E1A0C37B6D2F481"
+//! public_key = "RWSB9NK2Nwwang..."
+//! date = "2026-03-01T00:00:00Z"
+//! signed_by = "E7620F1842B4E81F"
+//! signature = """..."""
+//!
+//! [[statement]]
+//! action = "revoke"
+//! key_id = "E7620F1842B4E81F"
+//! reason = "retired"
+//! date = "2026-04-01T00:00:00Z"
+//! signed_by = "9E1A0C37B6D2F481"
+//! signature = """..."""
+//! ```
+//!
+//! Clients start from anchor keys configured per repository
+//! (`signing.repository_keys`), apply new statements after each sync and
+//! keep the resulting key set in the database directory. Metadata signed by
+//! a revoked key, or by a key the repository never introduced, is refused.
+
+use super::signing::{SignatureVerification, SigningManager};
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// Key rotation manifest inside a repository
+pub const KEY_ROTATION_FILE: &str = "metadata/key-rotation.toml";
+
+/// Directory inside the database directory holding each repository's key set
+pub const REPO_KEYS_DIR: &str = "repo-keys";
+
+/// What a key statement does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyAction {
+    /// Start trusting a new key
+    Introduce,
+    /// Stop trusting a key for good
+    Revoke,
+}
+
+impl std::fmt::Display for KeyAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyAction::Introduce => write!(f, "introduce"),
+            KeyAction::Revoke => write!(f, "revoke"),
+        }
+    }
+}
+
+/// A signed change to a repository's signing keys
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyStatement {
+    pub action: KeyAction,
+    /// Key introduced or revoked
+    pub key_id: String,
+    /// Public key being introduced, imported once the statement verifies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    /// Why a key was revoked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub date: DateTime<Utc>,
+    /// Trusted key that made the signature
+    pub signed_by: String,
+    /// Detached signature over [`KeyStatement::payload`]
+    pub signature: String,
+}
+
+impl KeyStatement {
+    /// The text the signature covers
+    pub fn payload(&self) -> String {
+        format!(
+            "buckos key statement\naction: {}\nkey: {}\npublic_key: {}\nreason: {}\ndate: {}\n",
+            self.action,
+            self.key_id,
+            self.public_key.as_deref().unwrap_or(""),
+            self.reason.as_deref().unwrap_or(""),
+            self.date.to_rfc3339(),
+        )
+    }
+}
+
+/// The key rotation manifest of a repository
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyRotation {
+    #[serde(default, rename = "statement")]
+    pub statements: Vec<KeyStatement>,
+}
+
+impl KeyRotation {
+    /// Load the manifest of the repository at `repo_dir`; none if missing
+    pub fn load(repo_dir: &Path) -> Result<Self> {
+        let path = repo_dir.join(KEY_ROTATION_FILE);
+        match std::fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content).map_err(|e| {
+                Error::Signing(format!(
+                    "Invalid key rotation manifest {}: {}",
+                    path.display(),
+                    e
+                ))
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, repo_dir: &Path) -> Result<()> {
+        let path = repo_dir.join(KEY_ROTATION_FILE);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let content = toml::to_string_pretty(self)
+            .map_err(|e| Error::Signing(format!("Failed to write key rotation manifest: {}", e)))?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Append a statement introducing `key_id`, signed with `signed_by`
+    pub fn introduce(
+        &mut self,
+        manager: &SigningManager,
+        key_id: &str,
+        public_key: &str,
+        signed_by: &str,
+    ) -> Result<&KeyStatement> {
+        self.append(
+            manager,
+            KeyAction::Introduce,
+            key_id,
+            Some(public_key.to_string()),
+            None,
+            signed_by,
+        )
+    }
+
+    /// Append a statement revoking `key_id`, signed with `signed_by`
+    pub fn revoke(
+        &mut self,
+        manager: &SigningManager,
+        key_id: &str,
+        reason: Option<&str>,
+        signed_by: &str,
+    ) -> Result<&KeyStatement> {
+        self.append(
+            manager,
+            KeyAction::Revoke,
+            key_id,
+            None,
+            reason.map(str::to_string),
+            signed_by,
+        )
+    }
+
+    fn append(
+        &mut self,
+        manager: &SigningManager,
+        action: KeyAction,
+        key_id: &str,
+        public_key: Option<String>,
+        reason: Option<String>,
+        signed_by: &str,
+    ) -> Result<&KeyStatement> {
+        let mut statement = KeyStatement {
+            action,
+            key_id: key_id.to_string(),
+            public_key,
+            reason,
+            date: Utc::now(),
+            signed_by: signed_by.to_string(),
+            signature: String::new(),
+        };
+        statement.signature = manager.sign_data(statement.payload().as_bytes(), Some(signed_by))?;
+        self.statements.push(statement);
+        Ok(self.statements.last().unwrap())
+    }
+}
+
+/// Why and when a key was revoked
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Revocation {
+    pub reason: Option<String>,
+    pub date: DateTime<Utc>,
+    pub signed_by: String,
+}
+
+/// A change applied to a repository's key set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyChange {
+    pub action: KeyAction,
+    pub key_id: String,
+}
+
+/// Keys a repository's metadata may be signed with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepoKeys {
+    pub repo: String,
+    /// Anchors and introduced keys that have not been revoked
+    pub trusted: BTreeSet<String>,
+    pub revoked: BTreeMap<String, Revocation>,
+    /// Statements of the rotation manifest applied so far
+    pub applied: usize,
+}
+
+impl RepoKeys {
+    /// Key set trusting only the configured `anchors`
+    pub fn new(repo: &str, anchors: &[String]) -> Self {
+        Self {
+            repo: repo.to_string(),
+            trusted: anchors.iter().map(|k| k.to_uppercase()).collect(),
+            revoked: BTreeMap::new(),
+            applied: 0,
+        }
+    }
+
+    fn path(db_dir: &Path, repo: &str) -> PathBuf {
+        db_dir.join(REPO_KEYS_DIR).join(format!("{}.json", repo))
+    }
+
+    /// Key set recorded for `repo`, if its rotation has been followed
+    pub fn load(db_dir: &Path, repo: &str) -> Result<Option<Self>> {
+        match std::fs::read_to_string(Self::path(db_dir, repo)) {
+            Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, db_dir: &Path) -> Result<()> {
+        let path = Self::path(db_dir, &self.repo);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Trusted key matching `key_id`, which may be a long ID or fingerprint
+    pub fn trusted_key(&self, key_id: &str) -> Option<&String> {
+        self.trusted.iter().find(|k| same_key(k, key_id))
+    }
+
+    pub fn revocation(&self, key_id: &str) -> Option<(&String, &Revocation)> {
+        self.revoked.iter().find(|(k, _)| same_key(k, key_id))
+    }
+
+    /// Apply the statements of `rotation` not applied yet
+    ///
+    /// Each statement must be signed by a key trusted at that point. Keys
+    /// introduced are imported into `manager`'s keyring. A revoked key can
+    /// never be introduced again, and a manifest shorter than what was
+    /// already applied is refused, so revocations cannot be rolled back.
+    pub fn apply(
+        &mut self,
+        rotation: &KeyRotation,
+        manager: &SigningManager,
+    ) -> Result<Vec<KeyChange>> {
+        if rotation.statements.len() < self.applied {
+            return Err(Error::Signing(format!(
+                "Key rotation manifest of {} has {} statements but {} were already applied; \
+                 refusing a rolled back manifest",
+                self.repo,
+                rotation.statements.len(),
+                self.applied
+            )));
+        }
+
+        let mut changes = Vec::new();
+        for (index, statement) in rotation.statements.iter().enumerate().skip(self.applied) {
+            self.check_statement(statement, manager).map_err(|e| {
+                Error::Signing(format!(
+                    "Key statement {} of {} ({} {}): {}",
+                    index + 1,
+                    self.repo,
+                    statement.action,
+                    statement.key_id,
+                    e
+                ))
+            })?;
+
+            let key_id = statement.key_id.to_uppercase();
+            match statement.action {
+                KeyAction::Introduce => {
+                    if let Some(public_key) = &statement.public_key {
+                        import_public_key(manager, public_key)?;
+                    }
+                    self.trusted.insert(key_id.clone());
+                }
+                KeyAction::Revoke => {
+                    self.trusted.retain(|k| !same_key(k, &key_id));
+                    self.revoked.insert(
+                        key_id.clone(),
+                        Revocation {
+                            reason: statement.reason.clone(),
+                            date: statement.date,
+                            signed_by: statement.signed_by.clone(),
+                        },
+                    );
+                }
+            }
+            self.applied = index + 1;
+            changes.push(KeyChange {
+                action: statement.action,
+                key_id,
+            });
+        }
+        Ok(changes)
+    }
+
+    fn check_statement(&self, statement: &KeyStatement, manager: &SigningManager) -> Result<()> {
+        if let Some((key, revocation)) = self.revocation(&statement.signed_by) {
+            return Err(revoked_error(key, revocation));
+        }
+        if self.trusted_key(&statement.signed_by).is_none() {
+            return Err(Error::Signing(format!(
+                "signed by {}, which the repository does not trust",
+                statement.signed_by
+            )));
+        }
+        if statement.action == KeyAction::Introduce {
+            if let Some((key, _)) = self.revocation(&statement.key_id) {
+                return Err(Error::Signing(format!(
+                    "key {} was revoked and cannot be introduced again",
+                    key
+                )));
+            }
+        }
+
+        let verification =
+            manager.verify_data(statement.payload().as_bytes(), &statement.signature)?;
+        if !verification.valid {
+            return Err(Error::Signing(format!(
+                "bad signature{}",
+                verification
+                    .warnings
+                    .first()
+                    .map(|w| format!(": {}", w))
+                    .unwrap_or_default()
+            )));
+        }
+        if !same_key(&verification.key_id, &statement.signed_by) {
+            return Err(Error::Signing(format!(
+                "signed by {}, not {}",
+                verification.key_id, statement.signed_by
+            )));
+        }
+        Ok(())
+    }
+
+    /// Refuse a signature by a revoked key, or by one the repository does
+    /// not trust
+    pub fn check_signer(&self, verification: &SignatureVerification) -> Result<()> {
+        if let Some((key, revocation)) = self.revocation(&verification.key_id) {
+            return Err(revoked_error(key, revocation));
+        }
+        if self.trusted_key(&verification.key_id).is_none() {
+            return Err(Error::Signing(format!(
+                "Metadata of {} is signed by {}, which is not one of its keys ({})",
+                self.repo,
+                verification.key_id,
+                self.trusted.iter().cloned().collect::<Vec<_>>().join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    /// Verify the signed Manifest of the repository at `repo_dir` and check
+    /// it was signed by one of the repository's keys
+    pub fn verify_repository(
+        &self,
+        manager: &SigningManager,
+        repo_dir: &Path,
+    ) -> Result<SignatureVerification> {
+        let verification = manager.verify_repository(repo_dir)?;
+        if !verification.valid {
+            return Err(Error::Signing(format!(
+                "Repository {} has a bad signature from {}",
+                self.repo, verification.key_id
+            )));
+        }
+        self.check_signer(&verification)
+            .map_err(|e| Error::Signing(format!("Refusing repository {}: {}", self.repo, e)))?;
+        Ok(verification)
+    }
+}
+
+fn revoked_error(key: &str, revocation: &Revocation) -> Error {
+    Error::Signing(format!(
+        "key {} was revoked on {}{}",
+        key,
+        revocation.date.format("%Y-%m-%d"),
+        revocation
+            .reason
+            .as_deref()
+            .map(|r| format!(" ({})", r))
+            .unwrap_or_default()
+    ))
+}
+
+/// Whether two key IDs name the same key; GPG IDs may be short, long or
+/// full fingerprints
+pub(crate) fn same_key(a: &str, b: &str) -> bool {
+    let (a, b) = (a.to_uppercase(), b.to_uppercase());
+    !a.is_empty() && !b.is_empty() && (a.ends_with(&b) || b.ends_with(&a))
+}
+
+/// Import a public key given as text, with the manager's backend
+fn import_public_key(manager: &SigningManager, public_key: &str) -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("key");
+    std::fs::write(&path, public_key)?;
+    manager.import_key(&path.to_string_lossy())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{SigningBackend, SigningConfig};
+
+    fn manager(dir: &Path, name: &str) -> SigningManager {
+        SigningManager::with_gpg_home(dir.join("gnupg")).with_keyring(&SigningConfig {
+            backend: SigningBackend::Minisign,
+            keys_dir: dir.join(name).join("keys"),
+            trusted_keys_dir: dir.join(name).join("trusted"),
+            ..Default::default()
+        })
+    }
+
+    fn public_key(manager: &SigningManager, key_id: &str, dir: &Path) -> String {
+        let path = dir.join(format!("{}.pub", key_id));
+        manager.export_key(key_id, &path, false).unwrap();
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn test_rotation_introduces_and_revokes() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        let maintainer = manager(dir.path(), "maintainer");
+        let old = maintainer.generate_key().unwrap();
+        let new = maintainer.generate_key().unwrap();
+
+        let mut rotation = KeyRotation::default();
+        rotation
+            .introduce(
+                &maintainer,
+                &new,
+                &public_key(&maintainer, &new, dir.path()),
+                &old,
+            )
+            .unwrap();
+        rotation
+            .revoke(&maintainer, &old, Some("retired"), &new)
+            .unwrap();
+        rotation.save(&repo).unwrap();
+        let rotation = KeyRotation::load(&repo).unwrap();
+        assert_eq!(rotation.statements.len(), 2);
+
+        // The client trusts only the old key to begin with
+        let client = manager(dir.path(), "client");
+        client
+            .import_key(&public_key(&maintainer, &old, dir.path()))
+            .unwrap();
+        let mut keys = RepoKeys::new("test", std::slice::from_ref(&old));
+        let changes = keys.apply(&rotation, &client).unwrap();
+        assert_eq!(changes.len(), 2);
+        assert!(keys.trusted_key(&new).is_some());
+        assert!(keys.trusted_key(&old).is_none());
+        assert_eq!(
+            keys.revocation(&old).unwrap().1.reason.as_deref(),
+            Some("retired")
+        );
+        assert!(keys.apply(&rotation, &client).unwrap().is_empty());
+
+        keys.save(dir.path()).unwrap();
+        assert_eq!(
+            RepoKeys::load(dir.path(), "test").unwrap(),
+            Some(keys.clone())
+        );
+
+        // Metadata signed by the revoked key is refused
+        std::fs::write(repo.join("README"), "hello").unwrap();
+        maintainer.sign_repository(&repo, Some(&old)).unwrap();
+        let err = keys.verify_repository(&client, &repo).unwrap_err();
+        assert!(err.to_string().contains("was revoked"), "{}", err);
+        maintainer.sign_repository(&repo, Some(&new)).unwrap();
+        assert!(keys.verify_repository(&client, &repo).unwrap().valid);
+
+        // Dropping the revocation from the manifest does not undo it
+        let mut rolled_back = rotation.clone();
+        rolled_back.statements.pop();
+        assert!(keys.apply(&rolled_back, &client).is_err());
+    }
+
+    #[test]
+    fn test_statement_from_untrusted_key_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let maintainer = manager(dir.path(), "maintainer");
+        let anchor = maintainer.generate_key().unwrap();
+        let rogue = maintainer.generate_key().unwrap();
+
+        let mut rotation = KeyRotation::default();
+        rotation
+            .introduce(
+                &maintainer,
+                &rogue,
+                &public_key(&maintainer, &rogue, dir.path()),
+                &rogue,
+            )
+            .unwrap();
+        let mut keys = RepoKeys::new("test", &[anchor]);
+        let err = keys.apply(&rotation, &maintainer).unwrap_err();
+        assert!(err.to_string().contains("does not trust"), "{}", err);
+        assert_eq!(keys.applied, 0);
+    }
+
+    #[test]
+    fn test_same_key() {
+        assert!(same_key("0123456789ABCDEF", "89abcdef"));
+        assert!(!same_key("0123456789ABCDEF", ""));
+        assert!(!same_key("0123456789ABCDEF", "FEDCBA9876543210"));
+    }
+}
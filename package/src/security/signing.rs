@@ -1,8 +1,11 @@
 //! Package signing and verification
 //!
-//! Provides GPG-based signing and verification for packages, manifests, and repositories.
-//! Compatible with Gentoo's gemato and Manifest signing.
+//! Provides signing and verification for packages, manifests, and repositories,
+//! through GPG or the built-in minisign-compatible backend. GPG signatures are
+//! compatible with Gentoo's gemato and Manifest signing.
 
+use super::minisign;
+use crate::config::{SigningBackend, SigningConfig};
 use crate::{Error, PackageId, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -162,30 +165,64 @@ pub struct SigningManager {
     trusted_keys: Vec<String>,
     /// Key cache
     key_cache: HashMap<String, SigningKey>,
+    /// Tool signatures are made and checked with
+    backend: SigningBackend,
+    /// minisign keys and trust store
+    keyring: minisign::Keyring,
 }
 
 impl SigningManager {
     /// Create a new signing manager with default GPG home
     pub fn new() -> Result<Self> {
+        Self::from_config(&SigningConfig::default())
+    }
+
+    /// Create a signing manager using the configured default backend and
+    /// minisign key directories
+    pub fn from_config(config: &SigningConfig) -> Result<Self> {
         let gpg_home = dirs::home_dir()
             .ok_or_else(|| Error::Config("Cannot find home directory".to_string()))?
             .join(".gnupg");
 
-        Ok(Self {
-            gpg_home,
-            default_key: None,
-            trusted_keys: Vec::new(),
-            key_cache: HashMap::new(),
-        })
+        Ok(Self::with_gpg_home(gpg_home).with_keyring(config))
     }
 
     /// Create with custom GPG home directory
     pub fn with_gpg_home(gpg_home: PathBuf) -> Self {
+        let config = SigningConfig::default();
         Self {
             gpg_home,
             default_key: None,
             trusted_keys: Vec::new(),
             key_cache: HashMap::new(),
+            backend: config.backend,
+            keyring: minisign::Keyring::new(config.keys_dir, config.trusted_keys_dir),
+        }
+    }
+
+    /// Use the backend and minisign key directories of `config`
+    pub fn with_keyring(mut self, config: &SigningConfig) -> Self {
+        self.backend = config.backend;
+        self.keyring =
+            minisign::Keyring::new(config.keys_dir.clone(), config.trusted_keys_dir.clone());
+        self
+    }
+
+    /// Sign with `backend`, e.g. the one configured for a repository
+    pub fn with_backend(mut self, backend: SigningBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn backend(&self) -> SigningBackend {
+        self.backend
+    }
+
+    /// Extension of detached signature files, appended to the signed file's
+    pub fn signature_extension(&self) -> &'static str {
+        match self.backend {
+            SigningBackend::Gpg => "asc",
+            SigningBackend::Minisign => minisign::SIGNATURE_EXTENSION,
         }
     }
 
@@ -201,6 +238,14 @@ impl SigningManager {
         }
     }
 
+    /// Check if the backend can be used; minisign is built in
+    pub fn is_available(&self) -> bool {
+        match self.backend {
+            SigningBackend::Gpg => self.is_gpg_available(),
+            SigningBackend::Minisign => true,
+        }
+    }
+
     /// Check if GPG is available
     pub fn is_gpg_available(&self) -> bool {
         Command::new("gpg")
@@ -212,6 +257,14 @@ impl SigningManager {
 
     /// List available signing keys
     pub fn list_keys(&mut self, secret_only: bool) -> Result<Vec<SigningKey>> {
+        if self.backend == SigningBackend::Minisign {
+            let keys = self.list_minisign_keys(secret_only)?;
+            for key in &keys {
+                self.key_cache.insert(key.fingerprint.clone(), key.clone());
+            }
+            return Ok(keys);
+        }
+
         let mut cmd = Command::new("gpg");
         cmd.args([
             "--homedir",
@@ -245,6 +298,36 @@ impl SigningManager {
         Ok(keys)
     }
 
+    /// Own minisign keys, and unless `secret_only`, trusted ones
+    fn list_minisign_keys(&self, secret_only: bool) -> Result<Vec<SigningKey>> {
+        let mut keys: Vec<SigningKey> = self
+            .keyring
+            .own_keys()?
+            .iter()
+            .map(|k| minisign_key_info(k, true))
+            .collect();
+        if !secret_only {
+            for key in self.keyring.trusted_keys()? {
+                if !keys.iter().any(|k| k.key_id == key.key_id.to_string()) {
+                    keys.push(minisign_key_info(&key, false));
+                }
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Generate a minisign key pair, returning its key ID
+    ///
+    /// GPG keys are generated interactively with `gpg --gen-key` instead.
+    pub fn generate_key(&self) -> Result<String> {
+        match self.backend {
+            SigningBackend::Gpg => Err(Error::Signing(
+                "Generate GPG keys with 'gpg --full-generate-key'".to_string(),
+            )),
+            SigningBackend::Minisign => Ok(self.keyring.generate()?.key_id.to_string()),
+        }
+    }
+
     /// Parse GPG key listing output
     fn parse_key_listing(&self, output: &str, is_secret: bool) -> Vec<SigningKey> {
         let mut keys = Vec::new();
@@ -351,6 +434,19 @@ impl SigningManager {
 
     /// Import a key from a file or keyserver
     pub fn import_key(&self, source: &str) -> Result<String> {
+        if self.backend == SigningBackend::Minisign {
+            // A key file, or the bare key as passed to minisign -P
+            let text = if Path::new(source).exists() {
+                std::fs::read_to_string(source)
+                    .map_err(|e| Error::Signing(format!("Failed to import key: {}", e)))?
+            } else {
+                source.to_string()
+            };
+            let key = minisign::PublicKey::parse(&text)?;
+            self.keyring.trust(&key)?;
+            return Ok(format!("Imported and trusted minisign key {}", key.key_id));
+        }
+
         let mut cmd = Command::new("gpg");
         cmd.args(["--homedir", self.gpg_home.to_str().unwrap_or("~/.gnupg")]);
 
@@ -386,6 +482,16 @@ impl SigningManager {
 
     /// Export a key to a file
     pub fn export_key(&self, key_id: &str, output_path: &Path, armor: bool) -> Result<()> {
+        if self.backend == SigningBackend::Minisign {
+            let key = self
+                .keyring
+                .find(key_id)?
+                .ok_or_else(|| Error::Signing(format!("No minisign key {}", key_id)))?;
+            std::fs::write(output_path, key.to_file())
+                .map_err(|e| Error::Signing(format!("Failed to export key: {}", e)))?;
+            return Ok(());
+        }
+
         let mut cmd = Command::new("gpg");
         cmd.args([
             "--homedir",
@@ -414,6 +520,20 @@ impl SigningManager {
 
     /// Sign a file with detached signature
     pub fn sign_file(&self, file_path: &Path, key_id: Option<&str>) -> Result<PathBuf> {
+        if self.backend == SigningBackend::Minisign {
+            let data = std::fs::read(file_path)
+                .map_err(|e| Error::Signing(format!("Failed to sign file: {}", e)))?;
+            let file_name = file_path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let signature = self.minisign(&data, key_id, Some(&file_name))?;
+            let sig_path = signature_path(file_path, minisign::SIGNATURE_EXTENSION);
+            std::fs::write(&sig_path, signature)
+                .map_err(|e| Error::Signing(format!("Failed to write signature: {}", e)))?;
+            return Ok(sig_path);
+        }
+
         let key = key_id
             .map(|s| s.to_string())
             .or_else(|| self.default_key.clone())
@@ -440,20 +560,15 @@ impl SigningManager {
         }
 
         // Return path to signature file
-        let sig_path = file_path.with_extension(format!(
-            "{}.asc",
-            file_path
-                .extension()
-                .unwrap_or_default()
-                .to_str()
-                .unwrap_or("")
-        ));
-
-        Ok(sig_path)
+        Ok(signature_path(file_path, "asc"))
     }
 
     /// Sign data and return signature
     pub fn sign_data(&self, data: &[u8], key_id: Option<&str>) -> Result<String> {
+        if self.backend == SigningBackend::Minisign {
+            return self.minisign(data, key_id, None);
+        }
+
         let key = key_id
             .map(|s| s.to_string())
             .or_else(|| self.default_key.clone())
@@ -496,22 +611,48 @@ impl SigningManager {
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
     }
 
+    /// minisign signature of `data`, with the signing time and `file_name`
+    /// in its trusted comment as minisign records them
+    fn minisign(
+        &self,
+        data: &[u8],
+        key_id: Option<&str>,
+        file_name: Option<&str>,
+    ) -> Result<String> {
+        let key_id = key_id.or(self.default_key.as_deref());
+        let secret = self.keyring.secret_key(key_id)?;
+        let mut comment = format!("timestamp:{}", chrono::Utc::now().timestamp());
+        if let Some(name) = file_name {
+            comment.push_str(&format!("\tfile:{}", name));
+        }
+        Ok(secret.sign(data, &comment).to_string())
+    }
+
     /// Verify a file signature
     pub fn verify_file(
         &self,
         file_path: &Path,
         sig_path: Option<&Path>,
     ) -> Result<SignatureVerification> {
-        let default_sig = file_path.with_extension(format!(
-            "{}.asc",
-            file_path
-                .extension()
-                .unwrap_or_default()
-                .to_str()
-                .unwrap_or("")
-        ));
+        // The backend's own signature if present, else whichever exists
+        let default_sig = [
+            self.signature_extension(),
+            "asc",
+            minisign::SIGNATURE_EXTENSION,
+        ]
+        .iter()
+        .map(|ext| signature_path(file_path, ext))
+        .find(|p| p.exists())
+        .unwrap_or_else(|| signature_path(file_path, self.signature_extension()));
         let sig = sig_path.unwrap_or(&default_sig);
 
+        let signature = std::fs::read_to_string(sig).unwrap_or_default();
+        if minisign::Signature::is_minisign(&signature) {
+            let data = std::fs::read(file_path)
+                .map_err(|e| Error::Signing(format!("Failed to read file: {}", e)))?;
+            return self.verify_minisign(&data, &signature);
+        }
+
         let mut cmd = Command::new("gpg");
         cmd.args([
             "--homedir",
@@ -532,6 +673,10 @@ impl SigningManager {
 
     /// Verify a signature on data
     pub fn verify_data(&self, data: &[u8], signature: &str) -> Result<SignatureVerification> {
+        if minisign::Signature::is_minisign(signature) {
+            return self.verify_minisign(data, signature);
+        }
+
         // Write signature to temp file
         let temp_dir = tempfile::tempdir()
             .map_err(|e| Error::Signing(format!("Failed to create temp dir: {}", e)))?;
@@ -547,6 +692,47 @@ impl SigningManager {
         self.verify_file(&data_path, Some(&sig_path))
     }
 
+    /// Verify a minisign signature against the trust store
+    ///
+    /// Keys with a secret half here are trusted ultimately, those in the
+    /// trust store fully; a signature by any other key is not valid.
+    fn verify_minisign(&self, data: &[u8], signature: &str) -> Result<SignatureVerification> {
+        let signature = minisign::Signature::parse(signature)?;
+        let key_id = signature.key_id.to_string();
+        let mut verification = SignatureVerification {
+            valid: false,
+            key_id: key_id.clone(),
+            signer: format!("minisign key {}", key_id),
+            timestamp: signature.timestamp(),
+            trust: TrustLevel::Unknown,
+            warnings: Vec::new(),
+        };
+
+        let Some(key) = self.keyring.find(&key_id)? else {
+            verification
+                .warnings
+                .push(format!("Key {} is not in the trust store", key_id));
+            return Ok(verification);
+        };
+        match key.verify(data, &signature) {
+            Ok(_) => {
+                verification.valid = true;
+                verification.trust = if self.keyring.is_own(key.key_id) {
+                    TrustLevel::Ultimate
+                } else {
+                    TrustLevel::Full
+                };
+            }
+            Err(e) => verification.warnings.push(e.to_string()),
+        }
+        if !signature.prehashed {
+            verification
+                .warnings
+                .push("Legacy signature over the raw data".to_string());
+        }
+        Ok(verification)
+    }
+
     /// Parse GPG verification output
     fn parse_verification_output(
         &self,
@@ -834,8 +1020,19 @@ impl SigningManager {
     /// Write manifest to file
     pub fn write_manifest(&self, manifest: &PackageManifest, path: &Path) -> Result<()> {
         let content = self.format_manifest(manifest);
+        let minisig_path = signature_path(path, minisign::SIGNATURE_EXTENSION);
 
-        if let Some(ref sig) = manifest.signature {
+        if let Some(sig) = manifest
+            .signature
+            .as_ref()
+            .filter(|sig| minisign::Signature::is_minisign(sig))
+        {
+            // minisign has no clear-signing; the signature sits beside it
+            std::fs::write(path, content)
+                .map_err(|e| Error::Signing(format!("Failed to write manifest: {}", e)))?;
+            std::fs::write(&minisig_path, sig)
+                .map_err(|e| Error::Signing(format!("Failed to write signature: {}", e)))?;
+        } else if let Some(ref sig) = manifest.signature {
             // Write clear-signed manifest
             let signed = format!(
                 "-----BEGIN PGP SIGNED MESSAGE-----\nHash: SHA512\n\n{}\n{}",
@@ -861,7 +1058,9 @@ impl SigningManager {
             if content.contains("-----BEGIN PGP SIGNED MESSAGE-----") {
                 self.parse_signed_manifest(&content)?
             } else {
-                (content, None)
+                let minisig_path = signature_path(path, minisign::SIGNATURE_EXTENSION);
+                let signature = std::fs::read_to_string(minisig_path).ok();
+                (content, signature)
             };
 
         let entries = self.parse_manifest_content(&manifest_content)?;
@@ -1014,6 +1213,13 @@ impl SigningManager {
 
     /// Delete a key
     pub fn delete_key(&self, key_id: &str, secret: bool) -> Result<()> {
+        if self.backend == SigningBackend::Minisign {
+            if !self.keyring.remove(key_id, secret)? {
+                return Err(Error::Signing(format!("No minisign key {}", key_id)));
+            }
+            return Ok(());
+        }
+
         let mut cmd = Command::new("gpg");
         cmd.args([
             "--homedir",
@@ -1043,7 +1249,26 @@ impl SigningManager {
     }
 
     /// Set trust level for a key
+    ///
+    /// minisign trust is binary: marginal and above put the key in the
+    /// trust store, never and unknown take it out.
     pub fn set_key_trust(&self, key_id: &str, trust: TrustLevel) -> Result<()> {
+        if self.backend == SigningBackend::Minisign {
+            let key = self
+                .keyring
+                .find(key_id)?
+                .ok_or_else(|| Error::Signing(format!("No minisign key {}", key_id)))?;
+            match trust {
+                TrustLevel::Marginal | TrustLevel::Full | TrustLevel::Ultimate => {
+                    self.keyring.trust(&key)?
+                }
+                TrustLevel::Never | TrustLevel::Unknown => {
+                    self.keyring.remove(key_id, false)?;
+                }
+            }
+            return Ok(());
+        }
+
         let trust_value = match trust {
             TrustLevel::Unknown => "1",
             TrustLevel::Never => "2",
@@ -1189,12 +1414,7 @@ impl SigningManager {
 
 impl Default for SigningManager {
     fn default() -> Self {
-        Self::new().unwrap_or_else(|_| Self {
-            gpg_home: PathBuf::from("/tmp/.gnupg"),
-            default_key: None,
-            trusted_keys: Vec::new(),
-            key_cache: HashMap::new(),
-        })
+        Self::new().unwrap_or_else(|_| Self::with_gpg_home(PathBuf::from("/tmp/.gnupg")))
     }
 }
 
@@ -1224,6 +1444,34 @@ pub enum ManifestVerifyStatus {
     Error,
 }
 
+/// Detached signature of `file`: its name with `extension` appended
+fn signature_path(file: &Path, extension: &str) -> PathBuf {
+    let mut name = file.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(extension);
+    file.with_file_name(name)
+}
+
+/// Key listing entry for a minisign key
+fn minisign_key_info(key: &minisign::PublicKey, is_secret: bool) -> SigningKey {
+    SigningKey {
+        fingerprint: key.fingerprint(),
+        key_id: key.key_id.to_string(),
+        user_id: format!("minisign key {}", key.key_id),
+        // minisign keys carry no creation date
+        created: chrono::NaiveDate::default(),
+        expires: None,
+        algorithm: "Ed25519".to_string(),
+        key_size: 256,
+        trust: if is_secret {
+            TrustLevel::Ultimate
+        } else {
+            TrustLevel::Full
+        },
+        is_secret,
+    }
+}
+
 /// Format key information for display
 pub fn format_key(key: &SigningKey) -> String {
     let mut output = String::new();
@@ -1332,6 +1580,86 @@ mod tests {
         assert!(formatted.contains("SHA512 def456"));
     }
 
+    #[test]
+    fn test_minisign_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SigningConfig {
+            backend: SigningBackend::Minisign,
+            keys_dir: dir.path().join("keys"),
+            trusted_keys_dir: dir.path().join("trusted"),
+            ..Default::default()
+        };
+        let mut manager =
+            SigningManager::with_gpg_home(dir.path().join("gnupg")).with_keyring(&config);
+        assert!(manager.is_available());
+        let key_id = manager.generate_key().unwrap();
+
+        let file = dir.path().join("foo-1.0.tar.zst");
+        std::fs::write(&file, b"package").unwrap();
+        let sig_path = manager.sign_file(&file, None).unwrap();
+        assert_eq!(sig_path, dir.path().join("foo-1.0.tar.zst.minisig"));
+        let verification = manager.verify_file(&file, None).unwrap();
+        assert!(verification.valid);
+        assert_eq!(verification.key_id, key_id);
+        assert_eq!(verification.trust, TrustLevel::Ultimate);
+
+        std::fs::write(&file, b"tampered").unwrap();
+        assert!(!manager.verify_file(&file, None).unwrap().valid);
+
+        let keys = manager.list_keys(false).unwrap();
+        assert_eq!(keys.len(), 1);
+        assert!(keys[0].is_secret);
+    }
+
+    #[test]
+    fn test_minisign_manifest_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SigningConfig {
+            backend: SigningBackend::Minisign,
+            keys_dir: dir.path().join("keys"),
+            trusted_keys_dir: dir.path().join("trusted"),
+            ..Default::default()
+        };
+        let manager = SigningManager::with_gpg_home(dir.path().join("gnupg")).with_keyring(&config);
+        manager.generate_key().unwrap();
+
+        let pkg_dir = dir.path().join("dev-libs/foo");
+        std::fs::create_dir_all(&pkg_dir).unwrap();
+        std::fs::write(pkg_dir.join("foo-1.0.ebuild"), "EAPI=8\n").unwrap();
+        let package = PackageId::new("dev-libs", "foo");
+        let mut manifest = manager.generate_manifest(&pkg_dir, &package).unwrap();
+        manager.sign_manifest(&mut manifest, None).unwrap();
+        let manifest_path = pkg_dir.join("Manifest");
+        manager.write_manifest(&manifest, &manifest_path).unwrap();
+        assert!(pkg_dir.join("Manifest.minisig").exists());
+
+        let read = manager.read_manifest(&manifest_path).unwrap();
+        assert!(manager.verify_manifest(&read).unwrap().valid);
+
+        // Signatures by keys outside the trust store are rejected
+        let other =
+            SigningManager::with_gpg_home(dir.path().join("gnupg")).with_keyring(&SigningConfig {
+                keys_dir: dir.path().join("other-keys"),
+                trusted_keys_dir: dir.path().join("other-trusted"),
+                ..config.clone()
+            });
+        let verification = other.verify_manifest(&read).unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_backend_per_repository() {
+        let mut config = SigningConfig::default();
+        config
+            .repositories
+            .insert("overlay".to_string(), SigningBackend::Minisign);
+        assert_eq!(config.backend_for("buckos"), SigningBackend::Gpg);
+        assert_eq!(config.backend_for("overlay"), SigningBackend::Minisign);
+        let manager = SigningManager::default().with_backend(config.backend_for("overlay"));
+        assert_eq!(manager.signature_extension(), "minisig");
+    }
+
     #[test]
     fn test_parse_manifest_content() {
         let manager = SigningManager::default();
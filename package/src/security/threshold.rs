@@ -0,0 +1,316 @@
+//! Threshold (k-of-n) signatures over repository metadata
+//!
+//! High-assurance repositories can require several signers to approve the
+//! same metadata before clients trust it. In repos.conf:
+//!
+//! ```ini
+//! [production]
+//! sync-signature-threshold = 2
+//! sync-signature-keys = E7620F1842B4E81F 9E1A0C37B6D2F481 0123456789ABCDEF
+//! ```
+//!
+//! Each signer adds a detached signature over the repository's top-level
+//! `Manifest` to `Manifest.sigs/` (`buckos sign cosign-repo`). On sync the
+//! signatures are verified, and the repository is refused unless enough of
+//! the listed keys signed and the Manifest matches the files it lists.
+
+use super::rotation::{same_key, RepoKeys};
+use super::signing::{ManifestVerifyStatus, SigningManager};
+use crate::{Error, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Directory beside the repository Manifest holding one signature per signer
+pub const SIGNATURES_DIR: &str = "Manifest.sigs";
+
+/// repos.conf, relative to the system root
+pub const REPOS_CONF: &str = "etc/buckos/repos.conf";
+
+/// How many of which keys must sign a repository's metadata
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThresholdPolicy {
+    pub threshold: usize,
+    pub keys: Vec<String>,
+}
+
+impl ThresholdPolicy {
+    /// Policies of the repositories in the repos.conf at `path` that set a
+    /// threshold; none if it is missing
+    pub fn load_all(path: &Path) -> Result<HashMap<String, Self>> {
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+        let repos = buckos_config::repos::parse_repos_conf(path)
+            .map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))?;
+        let mut policies = HashMap::new();
+        for (name, repo) in repos.repos {
+            if repo.sync_signature_threshold == 0 {
+                continue;
+            }
+            let policy = Self {
+                threshold: repo.sync_signature_threshold as usize,
+                keys: repo.sync_signature_keys,
+            };
+            if policy.threshold > policy.keys.len() {
+                return Err(Error::Config(format!(
+                    "Repository {} requires {} signatures but lists only {} keys",
+                    name,
+                    policy.threshold,
+                    policy.keys.len()
+                )));
+            }
+            policies.insert(name, policy);
+        }
+        Ok(policies)
+    }
+}
+
+/// Which keys signed a repository's metadata
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThresholdVerification {
+    pub threshold: usize,
+    /// Listed keys with a valid signature
+    pub signed: Vec<String>,
+    /// Listed keys without one
+    pub missing: Vec<String>,
+    /// Signatures that did not count, by file, with the reason
+    pub rejected: Vec<(String, String)>,
+}
+
+impl ThresholdVerification {
+    pub fn is_met(&self) -> bool {
+        self.signed.len() >= self.threshold
+    }
+}
+
+/// Add a signature by `key_id` (or the default key) over the Manifest of
+/// the repository at `repo_dir`, returning the signature file
+pub fn cosign_repository(
+    manager: &SigningManager,
+    repo_dir: &Path,
+    key_id: Option<&str>,
+) -> Result<PathBuf> {
+    let manifest = read_manifest(repo_dir)?;
+    let signature = manager.sign_data(&manifest, key_id)?;
+    let verification = manager.verify_data(&manifest, &signature)?;
+    let dir = repo_dir.join(SIGNATURES_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!(
+        "{}.{}",
+        verification.key_id,
+        manager.signature_extension()
+    ));
+    std::fs::write(&path, signature)?;
+    Ok(path)
+}
+
+/// Count the valid signatures over the Manifest of the repository at
+/// `repo_dir` by keys of `policy`
+///
+/// Signatures by keys revoked in `keys` do not count.
+pub fn verify_threshold(
+    manager: &SigningManager,
+    repo_dir: &Path,
+    policy: &ThresholdPolicy,
+    keys: Option<&RepoKeys>,
+) -> Result<ThresholdVerification> {
+    let manifest = read_manifest(repo_dir)?;
+    let mut result = ThresholdVerification {
+        threshold: policy.threshold,
+        ..Default::default()
+    };
+
+    let mut files: Vec<PathBuf> = match std::fs::read_dir(repo_dir.join(SIGNATURES_DIR)) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    files.sort();
+    for file in files {
+        let name = file
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut reject = |reason: String| result.rejected.push((name.clone(), reason));
+
+        let signature = std::fs::read_to_string(&file)?;
+        let verification = match manager.verify_data(&manifest, &signature) {
+            Ok(v) if v.valid => v,
+            Ok(v) => {
+                reject(
+                    v.warnings
+                        .first()
+                        .cloned()
+                        .unwrap_or_else(|| "bad signature".to_string()),
+                );
+                continue;
+            }
+            Err(e) => {
+                reject(e.to_string());
+                continue;
+            }
+        };
+        if let Some(Err(e)) = keys.map(|k| k.check_signer(&verification)) {
+            reject(e.to_string());
+            continue;
+        }
+        let Some(key) = policy
+            .keys
+            .iter()
+            .find(|k| same_key(k, &verification.key_id))
+        else {
+            reject(format!(
+                "{} is not one of the repository's signing keys",
+                verification.key_id
+            ));
+            continue;
+        };
+        if result.signed.contains(key) {
+            reject(format!("another signature by {}", key));
+            continue;
+        }
+        result.signed.push(key.clone());
+    }
+
+    result.missing = policy
+        .keys
+        .iter()
+        .filter(|k| !result.signed.contains(k))
+        .cloned()
+        .collect();
+    Ok(result)
+}
+
+/// Verify the threshold and that the Manifest matches the repository's
+/// files, refusing the repository otherwise
+pub fn enforce_threshold(
+    manager: &SigningManager,
+    repo: &str,
+    repo_dir: &Path,
+    policy: &ThresholdPolicy,
+    keys: Option<&RepoKeys>,
+) -> Result<ThresholdVerification> {
+    let result = verify_threshold(manager, repo_dir, policy, keys)?;
+    if !result.is_met() {
+        return Err(Error::Signing(format!(
+            "Repository {} has {} of {} required signatures (signed: {}; missing: {})",
+            repo,
+            result.signed.len(),
+            result.threshold,
+            list(&result.signed),
+            list(&result.missing)
+        )));
+    }
+
+    let manifest = manager.read_manifest(&repo_dir.join("Manifest"))?;
+    let mismatched: Vec<String> = manager
+        .verify_manifest_files(&manifest, repo_dir)?
+        .into_iter()
+        .filter(|r| r.status != ManifestVerifyStatus::Ok)
+        .map(|r| format!("{}: {}", r.path, r.message))
+        .collect();
+    if !mismatched.is_empty() {
+        return Err(Error::Signing(format!(
+            "Repository {} does not match its signed Manifest: {}",
+            repo,
+            mismatched.join("; ")
+        )));
+    }
+    Ok(result)
+}
+
+fn read_manifest(repo_dir: &Path) -> Result<Vec<u8>> {
+    let path = repo_dir.join("Manifest");
+    std::fs::read(&path).map_err(|e| {
+        Error::Signing(format!(
+            "Repository is not signed; cannot read {}: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+fn list(keys: &[String]) -> String {
+    if keys.is_empty() {
+        "none".to_string()
+    } else {
+        keys.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{SigningBackend, SigningConfig};
+
+    fn manager(dir: &Path) -> SigningManager {
+        SigningManager::with_gpg_home(dir.join("gnupg")).with_keyring(&SigningConfig {
+            backend: SigningBackend::Minisign,
+            keys_dir: dir.join("keys"),
+            trusted_keys_dir: dir.join("trusted"),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_threshold_counts_listed_signers() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir_all(repo.join("app-misc/foo")).unwrap();
+        std::fs::write(repo.join("app-misc/foo/Manifest"), "DIST foo.tar.gz 1\n").unwrap();
+        let manager = manager(dir.path());
+        let alice = manager.generate_key().unwrap();
+        let bob = manager.generate_key().unwrap();
+        let carol = manager.generate_key().unwrap();
+        let outsider = manager.generate_key().unwrap();
+        manager.sign_repository(&repo, Some(&alice)).unwrap();
+
+        let policy = ThresholdPolicy {
+            threshold: 2,
+            keys: vec![alice.clone(), bob.clone(), carol.clone()],
+        };
+        cosign_repository(&manager, &repo, Some(&alice)).unwrap();
+        cosign_repository(&manager, &repo, Some(&outsider)).unwrap();
+        let result = verify_threshold(&manager, &repo, &policy, None).unwrap();
+        assert!(!result.is_met());
+        assert_eq!(result.signed, vec![alice.clone()]);
+        assert_eq!(result.missing, vec![bob.clone(), carol.clone()]);
+        assert_eq!(result.rejected.len(), 1);
+        let err = enforce_threshold(&manager, "production", &repo, &policy, None).unwrap_err();
+        assert!(err.to_string().contains("1 of 2"), "{}", err);
+
+        cosign_repository(&manager, &repo, Some(&carol)).unwrap();
+        let result = enforce_threshold(&manager, "production", &repo, &policy, None).unwrap();
+        assert_eq!(result.signed.len(), 2);
+        assert_eq!(result.missing, vec![bob]);
+
+        // Tampering with a listed file breaks the repository
+        std::fs::write(repo.join("app-misc/foo/Manifest"), "DIST foo.tar.gz 2\n").unwrap();
+        assert!(enforce_threshold(&manager, "production", &repo, &policy, None).is_err());
+    }
+
+    #[test]
+    fn test_load_policies_from_repos_conf() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("repos.conf");
+        assert!(ThresholdPolicy::load_all(&path).unwrap().is_empty());
+
+        std::fs::write(
+            &path,
+            "[production]\nlocation = /var/db/repos/production\n\
+             sync-signature-threshold = 2\nsync-signature-keys = A B C\n\n\
+             [overlay]\nlocation = /var/db/repos/overlay\n",
+        )
+        .unwrap();
+        let policies = ThresholdPolicy::load_all(&path).unwrap();
+        assert_eq!(policies.len(), 1);
+        assert_eq!(policies["production"].threshold, 2);
+
+        std::fs::write(
+            &path,
+            "[production]\nsync-signature-threshold = 3\nsync-signature-keys = A B\n",
+        )
+        .unwrap();
+        assert!(ThresholdPolicy::load_all(&path).is_err());
+    }
+}
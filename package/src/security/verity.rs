@@ -0,0 +1,304 @@
+//! fs-verity protection of installed files
+//!
+//! With `verity.enabled`, fs-verity is turned on for the regular files a
+//! package installs under the system directories (`/usr`, `/opt`, ...) as
+//! it is merged, and the digest the kernel measures is recorded. The kernel
+//! then checks every block read against that digest, so a file changed
+//! offline (on the disk, from another system) fails to read instead of
+//! being trusted, and a file replaced by one without fs-verity or with a
+//! different digest shows up in `buckos verify` and `buckos verity status`.
+//!
+//! Files with fs-verity cannot be written, only replaced, which merging
+//! already does. Configuration files are left alone. Filesystems without
+//! fs-verity (it must be enabled on ext4 and f2fs) are skipped.
+
+use crate::db::PackageDb;
+use crate::{FileType, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use tracing::warn;
+
+/// `_IOW('f', 133, struct fsverity_enable_arg)`
+const FS_IOC_ENABLE_VERITY: u32 = 0x4080_6685;
+/// `_IOWR('f', 134, struct fsverity_digest)`
+const FS_IOC_MEASURE_VERITY: u32 = 0xc004_6686;
+const FS_VERITY_HASH_ALG_SHA256: u32 = 1;
+const FS_VERITY_HASH_ALG_SHA512: u16 = 2;
+const MAX_DIGEST_SIZE: usize = 64;
+const BLOCK_SIZE: u32 = 4096;
+
+/// Which installed files get fs-verity
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VerityConfig {
+    /// Enable fs-verity on installed files as they are merged
+    pub enabled: bool,
+    /// Directories, relative to the system root, whose files are protected
+    pub paths: Vec<String>,
+}
+
+impl Default for VerityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            paths: ["/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/opt"]
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl VerityConfig {
+    /// Whether the installed file at `path` under `root` is protected
+    pub fn covers(&self, root: &Path, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(root) else {
+            return false;
+        };
+        self.paths
+            .iter()
+            .any(|p| relative.starts_with(p.trim_start_matches('/')))
+    }
+}
+
+/// fs-verity state of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerityState {
+    /// Protected, with the digest the kernel measured (`sha256:<hex>`)
+    Enabled(String),
+    /// Not protected
+    Disabled,
+    /// The filesystem does not support fs-verity
+    Unsupported,
+}
+
+#[repr(C)]
+struct EnableArg {
+    version: u32,
+    hash_algorithm: u32,
+    block_size: u32,
+    salt_size: u32,
+    salt_ptr: u64,
+    sig_size: u32,
+    reserved1: u32,
+    sig_ptr: u64,
+    reserved2: [u64; 11],
+}
+
+#[repr(C)]
+struct Digest {
+    algorithm: u16,
+    size: u16,
+    digest: [u8; MAX_DIGEST_SIZE],
+}
+
+/// Whether `err` means the filesystem has no fs-verity
+pub fn is_unsupported(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::ENOTTY) | Some(libc::EOPNOTSUPP)
+    )
+}
+
+/// Measure the file at `path`
+pub fn measure(path: &Path) -> io::Result<VerityState> {
+    let file = File::open(path)?;
+    let mut digest = Digest {
+        algorithm: 0,
+        size: MAX_DIGEST_SIZE as u16,
+        digest: [0; MAX_DIGEST_SIZE],
+    };
+    // SAFETY: `digest` is a valid fsverity_digest with room for
+    // MAX_DIGEST_SIZE bytes, as its size field tells the kernel
+    let ret = unsafe {
+        libc::ioctl(
+            file.as_raw_fd(),
+            FS_IOC_MEASURE_VERITY as _,
+            &mut digest as *mut Digest,
+        )
+    };
+    if ret != 0 {
+        let err = io::Error::last_os_error();
+        return match err.raw_os_error() {
+            Some(libc::ENODATA) => Ok(VerityState::Disabled),
+            _ if is_unsupported(&err) => Ok(VerityState::Unsupported),
+            _ => Err(err),
+        };
+    }
+    let algorithm = match digest.algorithm {
+        FS_VERITY_HASH_ALG_SHA512 => "sha512",
+        _ => "sha256",
+    };
+    let bytes = &digest.digest[..(digest.size as usize).min(MAX_DIGEST_SIZE)];
+    Ok(VerityState::Enabled(format!(
+        "{}:{}",
+        algorithm,
+        hex::encode(bytes)
+    )))
+}
+
+/// Enable fs-verity on the file at `path`, returning its digest
+///
+/// Already protected files keep their digest.
+pub fn enable(path: &Path) -> io::Result<String> {
+    let file = File::open(path)?;
+    let arg = EnableArg {
+        version: 1,
+        hash_algorithm: FS_VERITY_HASH_ALG_SHA256,
+        block_size: BLOCK_SIZE,
+        salt_size: 0,
+        salt_ptr: 0,
+        sig_size: 0,
+        reserved1: 0,
+        sig_ptr: 0,
+        reserved2: [0; 11],
+    };
+    // SAFETY: `arg` is a valid fsverity_enable_arg without salt or signature
+    let ret = unsafe {
+        libc::ioctl(
+            file.as_raw_fd(),
+            FS_IOC_ENABLE_VERITY as _,
+            &arg as *const EnableArg,
+        )
+    };
+    if ret != 0 {
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::EEXIST) {
+            return Err(err);
+        }
+    }
+    match measure(path)? {
+        VerityState::Enabled(digest) => Ok(digest),
+        _ => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "fs-verity was not enabled",
+        )),
+    }
+}
+
+/// Enable fs-verity on the covered files of an installed package and
+/// record their digests, returning how many are protected
+///
+/// Stops at the first file on a filesystem without fs-verity.
+pub fn protect_package(
+    db: &PackageDb,
+    root: &Path,
+    config: &VerityConfig,
+    name: &str,
+) -> Result<usize> {
+    let mut protected = 0;
+    for file in db.get_package_files(name)? {
+        let path = Path::new(&file.path);
+        if file.file_type != FileType::Regular || !config.covers(root, path) {
+            continue;
+        }
+        match enable(path) {
+            Ok(digest) => {
+                db.set_file_verity(&file.path, &digest)?;
+                protected += 1;
+            }
+            Err(e) if is_unsupported(&e) => {
+                warn!(
+                    "fs-verity is not supported for {}, leaving {} unprotected",
+                    file.path, name
+                );
+                break;
+            }
+            Err(e) => warn!("Cannot enable fs-verity on {}: {}", file.path, e),
+        }
+    }
+    Ok(protected)
+}
+
+/// fs-verity coverage of an installed package
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct VerityCoverage {
+    pub package: String,
+    /// Installed files under the protected directories
+    pub files: usize,
+    /// Files protected at merge time
+    pub recorded: usize,
+    /// Protected files still carrying their recorded digest
+    pub intact: usize,
+    /// Protected files that lost fs-verity or changed digest
+    pub tampered: Vec<String>,
+}
+
+/// Check the fs-verity coverage of an installed package
+pub fn package_coverage(
+    db: &PackageDb,
+    root: &Path,
+    config: &VerityConfig,
+    name: &str,
+) -> Result<VerityCoverage> {
+    let digests = db.get_file_verity(name)?;
+    let mut coverage = VerityCoverage {
+        package: name.to_string(),
+        ..Default::default()
+    };
+    for file in db.get_package_files(name)? {
+        let path = Path::new(&file.path);
+        let recorded = digests.get(&file.path);
+        if file.file_type != FileType::Regular || (!config.covers(root, path) && recorded.is_none())
+        {
+            continue;
+        }
+        coverage.files += 1;
+        let Some(recorded) = recorded else {
+            continue;
+        };
+        coverage.recorded += 1;
+        if check(path, recorded) {
+            coverage.intact += 1;
+        } else {
+            coverage.tampered.push(file.path.clone());
+        }
+    }
+    Ok(coverage)
+}
+
+/// Whether the file at `path` is protected with the `recorded` digest
+pub fn check(path: &Path, recorded: &str) -> bool {
+    matches!(measure(path), Ok(VerityState::Enabled(digest)) if digest == recorded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_covers_system_directories() {
+        let config = VerityConfig::default();
+        let root = Path::new("/mnt/target");
+        assert!(config.covers(root, Path::new("/mnt/target/usr/bin/foo")));
+        assert!(config.covers(root, Path::new("/mnt/target/lib64/libc.so.6")));
+        assert!(!config.covers(root, Path::new("/mnt/target/etc/foo.conf")));
+        assert!(!config.covers(root, Path::new("/mnt/target/usrlocal/foo")));
+        assert!(!config.covers(root, Path::new("/usr/bin/foo")));
+        assert!(config.covers(Path::new("/"), Path::new("/opt/foo/bin/foo")));
+    }
+
+    #[test]
+    fn test_ioctl_arguments_match_kernel_layout() {
+        assert_eq!(std::mem::size_of::<EnableArg>(), 128);
+        assert_eq!(std::mem::size_of::<Digest>(), 4 + MAX_DIGEST_SIZE);
+        assert_eq!(FS_IOC_ENABLE_VERITY >> 16 & 0x3fff, 128);
+    }
+
+    #[test]
+    fn test_unprotected_file_is_not_intact() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("foo");
+        std::fs::write(&path, "foo").unwrap();
+        // Disabled where fs-verity is supported, unsupported elsewhere
+        assert!(matches!(
+            measure(&path).unwrap(),
+            VerityState::Disabled | VerityState::Unsupported
+        ));
+        assert!(!check(&path, "sha256:00"));
+        assert!(!check(&dir.path().join("missing"), "sha256:00"));
+    }
+}
@@ -0,0 +1,236 @@
+//! Terminal colors of the CLI
+//!
+//! Output is styled by what it means rather than by color: a [`Role`] is
+//! looked up in the configured [`Palette`]. Whether color is written at all
+//! follows `--color`, then `NO_COLOR`, `CLICOLOR_FORCE` and `CLICOLOR`, then
+//! whether the stream is a terminal.
+//!
+//! ```toml
+//! [theme]
+//! palette = "colorblind"   # default, colorblind or monochrome
+//! color = "auto"           # used when --color is not given
+//! ```
+
+use console::{Color, StyledObject};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// When to write color
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    /// When the output is a terminal and the environment allows it
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color a stream, given whether it is a terminal and a
+    /// lookup of environment variables
+    pub fn enabled(self, is_terminal: bool, env: impl Fn(&str) -> Option<String>) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                let set = |name: &str| env(name).filter(|v| !v.is_empty());
+                if set("NO_COLOR").is_some() {
+                    false
+                } else if set("CLICOLOR_FORCE").is_some_and(|v| v != "0") {
+                    true
+                } else if set("CLICOLOR").is_some_and(|v| v == "0") {
+                    false
+                } else {
+                    is_terminal
+                }
+            }
+        }
+    }
+
+    /// Turn color on or off for stdout and stderr
+    pub fn apply(self) {
+        let env = |name: &str| std::env::var(name).ok();
+        console::set_colors_enabled(self.enabled(console::Term::stdout().is_term(), env));
+        console::set_colors_enabled_stderr(self.enabled(console::Term::stderr().is_term(), env));
+    }
+}
+
+/// What a piece of output means
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Progress and completed steps
+    Success,
+    Warning,
+    Error,
+    /// Neutral status
+    Info,
+    /// Categories, paths and other secondary names
+    Accent,
+    /// Rarely used emphasis distinct from the other roles
+    Highlight,
+    /// Package list marker: newly installed
+    New,
+    /// Package list marker: upgraded
+    Update,
+    /// Package list marker: rebuilt at the same version
+    Rebuild,
+    /// Package list marker: removed
+    Remove,
+    /// Package list marker: replaced by an older version
+    Downgrade,
+    /// Package list marker: configuration changed
+    Config,
+}
+
+/// Colors given to each role
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[repr(u8)]
+pub enum Palette {
+    /// Green for new and successful, red for removed and failed
+    #[default]
+    Default,
+    /// Okabe-Ito colors, which stay distinct under the common kinds of
+    /// color blindness: blue for new, orange for removed
+    Colorblind,
+    /// No colors; the list markers and bold text still tell roles apart
+    Monochrome,
+}
+
+impl Palette {
+    /// Color of a role, if any
+    pub fn color(self, role: Role) -> Option<Color> {
+        match self {
+            Palette::Default => Some(match role {
+                Role::Success | Role::New => Color::Green,
+                Role::Warning | Role::Rebuild => Color::Yellow,
+                Role::Error | Role::Remove => Color::Red,
+                Role::Info | Role::Update | Role::Downgrade => Color::Blue,
+                Role::Accent | Role::Config => Color::Cyan,
+                Role::Highlight => Color::Magenta,
+            }),
+            Palette::Colorblind => Some(Color::Color256(match role {
+                // sky blue, blue, yellow, vermillion, orange, bluish green
+                Role::Success | Role::New => 74,
+                Role::Info | Role::Update => 25,
+                Role::Warning | Role::Rebuild => 221,
+                Role::Error => 166,
+                Role::Remove => 208,
+                Role::Downgrade | Role::Highlight => 175,
+                Role::Accent | Role::Config => 36,
+            })),
+            Palette::Monochrome => None,
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Palette::Colorblind,
+            2 => Palette::Monochrome,
+            _ => Palette::Default,
+        }
+    }
+}
+
+/// `[theme]` settings
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThemeConfig {
+    pub palette: Palette,
+    /// When to write color if `--color` is not given
+    pub color: ColorChoice,
+}
+
+static PALETTE: AtomicU8 = AtomicU8::new(Palette::Default as u8);
+
+/// Use a palette for all further output
+pub fn set_palette(palette: Palette) {
+    PALETTE.store(palette as u8, Ordering::Relaxed);
+}
+
+/// The palette in use
+pub fn palette() -> Palette {
+    Palette::from_u8(PALETTE.load(Ordering::Relaxed))
+}
+
+/// Style a value by its role in the current palette
+pub fn paint<D>(role: Role, value: D) -> StyledObject<D> {
+    let styled = console::style(value);
+    match palette().color(role) {
+        Some(color) => styled.fg(color),
+        None => styled,
+    }
+}
+
+/// Value without a role, for attributes such as bold alone
+pub fn plain<D>(value: D) -> StyledObject<D> {
+    console::style(value)
+}
+
+pub fn success<D>(value: D) -> StyledObject<D> {
+    paint(Role::Success, value)
+}
+
+pub fn warning<D>(value: D) -> StyledObject<D> {
+    paint(Role::Warning, value)
+}
+
+pub fn error<D>(value: D) -> StyledObject<D> {
+    paint(Role::Error, value)
+}
+
+pub fn info<D>(value: D) -> StyledObject<D> {
+    paint(Role::Info, value)
+}
+
+pub fn accent<D>(value: D) -> StyledObject<D> {
+    paint(Role::Accent, value)
+}
+
+pub fn highlight<D>(value: D) -> StyledObject<D> {
+    paint(Role::Highlight, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_choice() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(k, _)| *k == name)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+        assert!(ColorChoice::Auto.enabled(true, env(&[])));
+        assert!(!ColorChoice::Auto.enabled(false, env(&[])));
+        assert!(!ColorChoice::Auto.enabled(true, env(&[("NO_COLOR", "1")])));
+        // An empty NO_COLOR does not count
+        assert!(ColorChoice::Auto.enabled(true, env(&[("NO_COLOR", "")])));
+        assert!(!ColorChoice::Auto.enabled(true, env(&[("CLICOLOR", "0")])));
+        assert!(ColorChoice::Auto.enabled(false, env(&[("CLICOLOR_FORCE", "1")])));
+        assert!(
+            !ColorChoice::Auto.enabled(false, env(&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")]))
+        );
+        assert!(ColorChoice::Always.enabled(false, env(&[("NO_COLOR", "1")])));
+        assert!(!ColorChoice::Never.enabled(true, env(&[])));
+    }
+
+    #[test]
+    fn test_palettes() {
+        assert_eq!(Palette::Default.color(Role::New), Some(Color::Green));
+        assert_eq!(Palette::Monochrome.color(Role::Error), None);
+        // Colorblind markers never share a color across new/update/remove
+        let cb = |role| Palette::Colorblind.color(role);
+        assert_ne!(cb(Role::New), cb(Role::Remove));
+        assert_ne!(cb(Role::New), cb(Role::Update));
+        assert_ne!(cb(Role::Update), cb(Role::Remove));
+
+        let config: ThemeConfig = toml::from_str("palette = \"colorblind\"").unwrap();
+        assert_eq!(config.palette, Palette::Colorblind);
+        assert_eq!(config.color, ColorChoice::Auto);
+    }
+}
@@ -0,0 +1,162 @@
+//! Build time estimates
+//!
+//! Every successful build's duration is recorded by version and USE hash.
+//! Planned transactions are estimated from those records, preferring an
+//! earlier build of the same version and USE flags and falling back to the
+//! package's recent builds. While a transaction runs, the remaining
+//! estimate is scaled by how this run compares to the recorded history.
+
+use crate::PackageId;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Short, order-independent hash of a set of enabled USE flags
+pub fn use_hash<'a>(flags: impl IntoIterator<Item = &'a str>) -> String {
+    let mut flags: Vec<&str> = flags.into_iter().collect();
+    flags.sort_unstable();
+    flags.dedup();
+    let digest = Sha256::digest(flags.join(" ").as_bytes());
+    hex::encode(&digest[..8])
+}
+
+/// Estimated build time of one planned package
+#[derive(Debug, Clone)]
+pub struct BuildEstimate {
+    pub package: PackageId,
+    pub version: String,
+    /// None if the package has never been built here
+    pub estimate: Option<Duration>,
+}
+
+/// Estimated build time of a planned transaction
+#[derive(Debug, Clone, Default)]
+pub struct TransactionEstimate {
+    pub packages: Vec<BuildEstimate>,
+}
+
+impl TransactionEstimate {
+    /// Sum of the known estimates
+    pub fn total(&self) -> Duration {
+        self.packages.iter().filter_map(|p| p.estimate).sum()
+    }
+
+    /// Packages without a recorded build
+    pub fn unknown(&self) -> usize {
+        self.packages
+            .iter()
+            .filter(|p| p.estimate.is_none())
+            .count()
+    }
+
+    /// Whether the known estimates alone exceed a time budget
+    pub fn exceeds(&self, budget: Duration) -> bool {
+        self.total() > budget
+    }
+}
+
+/// Remaining time of a running transaction
+#[derive(Debug, Clone, Default)]
+pub struct Eta {
+    pending: HashMap<String, Option<Duration>>,
+    estimated_done: Duration,
+    actual_done: Duration,
+}
+
+impl Eta {
+    /// Track the packages of an estimate, keyed by `category/name-version`
+    pub fn new(estimate: &TransactionEstimate) -> Self {
+        Self {
+            pending: estimate
+                .packages
+                .iter()
+                .map(|p| (format!("{}-{}", p.package, p.version), p.estimate))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Mark a package as done after taking `actual`
+    pub fn complete(&mut self, key: &str, actual: Duration) {
+        if let Some(Some(estimate)) = self.pending.remove(key) {
+            self.estimated_done += estimate;
+            self.actual_done += actual;
+        }
+    }
+
+    /// Estimated time left, or None if nothing left has a recorded build
+    pub fn remaining(&self) -> Option<Duration> {
+        let left: Duration = self.pending.values().flatten().sum();
+        if left.is_zero() {
+            return None;
+        }
+        // Builds running faster or slower than recorded shift the rest too
+        let pace = if self.estimated_done.is_zero() {
+            1.0
+        } else {
+            self.actual_done.as_secs_f64() / self.estimated_done.as_secs_f64()
+        };
+        Some(left.mul_f64(pace))
+    }
+}
+
+/// Format a duration for display, e.g. `1h 05m` or `42s`
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h {:02}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn estimate(name: &str, secs: Option<u64>) -> BuildEstimate {
+        BuildEstimate {
+            package: PackageId::new("dev-libs", name),
+            version: "1.0.0".to_string(),
+            estimate: secs.map(Duration::from_secs),
+        }
+    }
+
+    #[test]
+    fn test_use_hash_is_order_independent() {
+        assert_eq!(use_hash(["ssl", "ipv6"]), use_hash(["ipv6", "ssl", "ssl"]));
+        assert_ne!(use_hash(["ssl"]), use_hash([]));
+        assert_eq!(use_hash([]).len(), 16);
+    }
+
+    #[test]
+    fn test_eta_scales_with_pace() {
+        let plan = TransactionEstimate {
+            packages: vec![
+                estimate("a", Some(100)),
+                estimate("b", Some(300)),
+                estimate("c", None),
+            ],
+        };
+        assert_eq!(plan.total(), Duration::from_secs(400));
+        assert_eq!(plan.unknown(), 1);
+        assert!(plan.exceeds(Duration::from_secs(300)));
+
+        let mut eta = Eta::new(&plan);
+        assert_eq!(eta.remaining(), Some(Duration::from_secs(400)));
+        // Twice as slow as recorded
+        eta.complete("dev-libs/a-1.0.0", Duration::from_secs(200));
+        assert_eq!(eta.remaining(), Some(Duration::from_secs(600)));
+        eta.complete("dev-libs/b-1.0.0", Duration::from_secs(500));
+        assert_eq!(eta.remaining(), None);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(42)), "42s");
+        assert_eq!(format_duration(Duration::from_secs(125)), "2m 05s");
+        assert_eq!(format_duration(Duration::from_secs(3900)), "1h 05m");
+    }
+}
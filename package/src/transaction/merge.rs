@@ -0,0 +1,493 @@
+//! Transactional merge of files into the root
+//!
+//! Files are never copied over their destination. Each is written to a
+//! temporary name in the destination's directory, so it is on the same
+//! filesystem, synced, then renamed into place: with `RENAME_NOREPLACE`
+//! when nothing is there yet, so a file appearing meanwhile is never
+//! clobbered, or over the old file once that is kept in the backup
+//! directory. Removed files are moved to the backup directory as well,
+//! copying when it is on another filesystem.
+//!
+//! Every change is journaled. [`FileMerge::rollback`] undoes them newest
+//! first, putting the originals back. [`FileMerge::sync`] makes the
+//! renames durable before the database records them, and
+//! [`FileMerge::commit`] then drops the backups.
+
+use crate::{Error, Result};
+use std::collections::BTreeSet;
+use std::ffi::CString;
+use std::fs::File;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Prefix of temporary names in destination directories
+const STAGE_PREFIX: &str = ".buckos-merge.";
+
+/// Distinguishes temporary names within the process
+static STAGE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A change made to the root
+#[derive(Debug)]
+enum MergeAction {
+    /// Something created where nothing was
+    Created(PathBuf),
+    /// Something moved to the backup directory, to be replaced or removed
+    Saved { path: PathBuf, backup: PathBuf },
+}
+
+/// A file written under a temporary name, waiting to be installed
+#[derive(Debug)]
+pub struct StagedFile {
+    temp: PathBuf,
+    dest: PathBuf,
+}
+
+impl StagedFile {
+    /// Temporary path holding the contents
+    pub fn path(&self) -> &Path {
+        &self.temp
+    }
+
+    /// Where the file will be installed
+    pub fn dest(&self) -> &Path {
+        &self.dest
+    }
+}
+
+/// Journaled changes to the root for one transaction
+#[derive(Debug)]
+pub struct FileMerge {
+    backup_dir: PathBuf,
+    journal: Vec<MergeAction>,
+    /// Temporary files not yet installed or discarded
+    staged: Vec<PathBuf>,
+    /// Directories whose entries changed
+    dirty: BTreeSet<PathBuf>,
+    backups: u64,
+}
+
+impl FileMerge {
+    /// Start a merge keeping originals in `backup_dir`
+    pub fn new(backup_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            backup_dir: backup_dir.into(),
+            journal: Vec::new(),
+            staged: Vec::new(),
+            dirty: BTreeSet::new(),
+            backups: 0,
+        }
+    }
+
+    /// Create an empty file to be installed at `dest`
+    pub fn stage(&mut self, dest: &Path) -> Result<StagedFile> {
+        let parent = parent_of(dest)?;
+        self.create_dirs(parent)?;
+        let temp = temp_name(parent, dest);
+        File::options().write(true).create_new(true).open(&temp)?;
+        self.staged.push(temp.clone());
+        Ok(StagedFile {
+            temp,
+            dest: dest.to_path_buf(),
+        })
+    }
+
+    /// Copy `src` to be installed at `dest`, keeping its permissions
+    pub fn stage_copy(&mut self, src: &Path, dest: &Path) -> Result<StagedFile> {
+        let staged = self.stage(dest)?;
+        std::fs::copy(src, &staged.temp)?;
+        Ok(staged)
+    }
+
+    /// Drop a staged file without installing it
+    pub fn discard(&mut self, staged: StagedFile) {
+        self.staged.retain(|t| *t != staged.temp);
+        let _ = std::fs::remove_file(&staged.temp);
+    }
+
+    /// Sync a staged file and rename it into place
+    pub fn install(&mut self, staged: StagedFile) -> Result<()> {
+        File::open(&staged.temp)?.sync_all()?;
+        self.staged.retain(|t| *t != staged.temp);
+        let result = self.replace(&staged.temp, &staged.dest);
+        if result.is_err() {
+            let _ = std::fs::remove_file(&staged.temp);
+        }
+        result
+    }
+
+    /// Create a symlink at `dest`, replacing what is there
+    pub fn symlink(&mut self, target: &Path, dest: &Path) -> Result<()> {
+        let parent = parent_of(dest)?;
+        self.create_dirs(parent)?;
+        let temp = temp_name(parent, dest);
+        std::os::unix::fs::symlink(target, &temp)?;
+        let result = self.replace(&temp, dest);
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp);
+        }
+        result
+    }
+
+    /// Create a directory at `dest` and any missing parents
+    ///
+    /// A directory, or a symlink to one, is kept as it is; this is how
+    /// `/lib -> usr/lib` style links survive merges. Anything else at
+    /// `dest` is moved aside.
+    pub fn dir(&mut self, dest: &Path, mode: u32) -> Result<()> {
+        if dest.is_dir() {
+            return Ok(());
+        }
+        if dest.symlink_metadata().is_ok() {
+            self.save(dest)?;
+        }
+        let parent = parent_of(dest)?;
+        self.create_dirs(parent)?;
+        std::fs::create_dir(dest)?;
+        std::fs::set_permissions(dest, std::fs::Permissions::from_mode(mode))?;
+        self.created(dest);
+        Ok(())
+    }
+
+    /// Remove a file, symlink or empty directory, keeping it for rollback
+    ///
+    /// Directories that still hold files are left alone.
+    pub fn remove(&mut self, path: &Path) -> Result<()> {
+        let Ok(meta) = path.symlink_metadata() else {
+            return Ok(());
+        };
+        if meta.is_dir() && std::fs::read_dir(path)?.next().is_some() {
+            return Ok(());
+        }
+        self.save(path)
+    }
+
+    /// Sync the directories whose entries changed
+    pub fn sync(&self) -> Result<()> {
+        for dir in &self.dirty {
+            // Directories removed later in the merge are gone
+            if let Ok(dir) = File::open(dir) {
+                dir.sync_all()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Keep the changes and drop the backups
+    pub fn commit(mut self) -> Result<()> {
+        self.journal.clear();
+        if self.backup_dir.exists() {
+            std::fs::remove_dir_all(&self.backup_dir)?;
+        }
+        Ok(())
+    }
+
+    /// Undo every change, newest first
+    ///
+    /// Each step is attempted even if an earlier one failed; the first
+    /// error is returned.
+    pub fn rollback(mut self) -> Result<()> {
+        let mut first_error = None;
+        for temp in self.staged.drain(..) {
+            let _ = std::fs::remove_file(temp);
+        }
+        while let Some(action) = self.journal.pop() {
+            let result = match &action {
+                MergeAction::Created(path) => remove_entry(path),
+                MergeAction::Saved { path, backup } => {
+                    remove_entry(path).and_then(|()| move_entry(backup, path))
+                }
+            };
+            if let Err(e) = result {
+                first_error.get_or_insert(e);
+            }
+        }
+        if first_error.is_none() && self.backup_dir.exists() {
+            std::fs::remove_dir_all(&self.backup_dir)?;
+        }
+        match first_error {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Rename `temp` to `dest`, saving whatever `dest` was
+    fn replace(&mut self, temp: &Path, dest: &Path) -> Result<()> {
+        match dest.symlink_metadata() {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                rename_noreplace(temp, dest)?;
+                self.created(dest);
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+            Ok(meta) if meta.is_dir() => {
+                if std::fs::read_dir(dest)?.next().is_some() {
+                    return Err(Error::TransactionFailed(format!(
+                        "{} is a directory that is not empty",
+                        dest.display()
+                    )));
+                }
+                // An empty directory turning into a file or symlink
+                self.save(dest)?;
+                rename_noreplace(temp, dest)?;
+            }
+            Ok(_) => {
+                // The original stays in place until the rename swaps it
+                let backup = self.next_backup()?;
+                if std::fs::hard_link(dest, &backup).is_err() {
+                    copy_entry(dest, &backup)?;
+                }
+                self.journal.push(MergeAction::Saved {
+                    path: dest.to_path_buf(),
+                    backup,
+                });
+                std::fs::rename(temp, dest)?;
+            }
+        }
+        self.mark_dirty(dest);
+        Ok(())
+    }
+
+    /// Move `path` to the backup directory
+    fn save(&mut self, path: &Path) -> Result<()> {
+        let backup = self.next_backup()?;
+        move_entry(path, &backup)?;
+        self.journal.push(MergeAction::Saved {
+            path: path.to_path_buf(),
+            backup,
+        });
+        self.mark_dirty(path);
+        Ok(())
+    }
+
+    /// Create missing directories down to `dir`, journaling each
+    fn create_dirs(&mut self, dir: &Path) -> Result<()> {
+        if dir.is_dir() {
+            return Ok(());
+        }
+        let parent = parent_of(dir)?;
+        self.create_dirs(parent)?;
+        self.dir(dir, 0o755)
+    }
+
+    fn next_backup(&mut self) -> Result<PathBuf> {
+        std::fs::create_dir_all(&self.backup_dir)?;
+        loop {
+            self.backups += 1;
+            // Skip anything left by a merge that did not finish
+            let backup = self.backup_dir.join(self.backups.to_string());
+            if backup.symlink_metadata().is_err() {
+                return Ok(backup);
+            }
+        }
+    }
+
+    fn created(&mut self, path: &Path) {
+        self.journal.push(MergeAction::Created(path.to_path_buf()));
+        self.mark_dirty(path);
+    }
+
+    fn mark_dirty(&mut self, path: &Path) {
+        if let Some(parent) = path.parent() {
+            self.dirty.insert(parent.to_path_buf());
+        }
+    }
+}
+
+fn parent_of(path: &Path) -> Result<&Path> {
+    path.parent()
+        .ok_or_else(|| Error::TransactionFailed(format!("cannot merge {}", path.display())))
+}
+
+/// Unused temporary name for `dest` in `dir`
+fn temp_name(dir: &Path, dest: &Path) -> PathBuf {
+    let name = dest
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    dir.join(format!(
+        "{}{}.{}.{}",
+        STAGE_PREFIX,
+        name,
+        std::process::id(),
+        STAGE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Rename without replacing an existing `to`
+///
+/// Filesystems without `renameat2` flags get a hard link and unlink,
+/// which fails the same way when `to` exists.
+fn rename_noreplace(from: &Path, to: &Path) -> io::Result<()> {
+    let c_from = CString::new(from.as_os_str().as_bytes())?;
+    let c_to = CString::new(to.as_os_str().as_bytes())?;
+    // SAFETY: both paths are valid NUL-terminated strings
+    let ret = unsafe {
+        libc::renameat2(
+            libc::AT_FDCWD,
+            c_from.as_ptr(),
+            libc::AT_FDCWD,
+            c_to.as_ptr(),
+            libc::RENAME_NOREPLACE,
+        )
+    };
+    if ret == 0 {
+        return Ok(());
+    }
+    let err = io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(libc::EINVAL) | Some(libc::ENOSYS) => {
+            std::fs::hard_link(from, to)?;
+            std::fs::remove_file(from)
+        }
+        _ => Err(err),
+    }
+}
+
+/// Move a file, symlink or empty directory, copying across filesystems
+fn move_entry(from: &Path, to: &Path) -> io::Result<()> {
+    match std::fs::rename(from, to) {
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+            copy_entry(from, to)?;
+            remove_entry(from)
+        }
+        result => result,
+    }
+}
+
+/// Copy a file, symlink or empty directory with its mode, owner and mtime
+fn copy_entry(from: &Path, to: &Path) -> io::Result<()> {
+    let meta = from.symlink_metadata()?;
+    if meta.file_type().is_symlink() {
+        std::os::unix::fs::symlink(std::fs::read_link(from)?, to)?;
+    } else if meta.is_dir() {
+        std::fs::create_dir(to)?;
+        std::fs::set_permissions(to, meta.permissions())?;
+    } else {
+        std::fs::copy(from, to)?;
+        let file = File::options().write(true).open(to)?;
+        file.set_modified(meta.modified()?)?;
+        file.sync_all()?;
+    }
+    // Only root can give files away; unprivileged roots keep their owner
+    let _ = std::os::unix::fs::lchown(to, Some(meta.uid()), Some(meta.gid()));
+    Ok(())
+}
+
+/// Remove a file, symlink or empty directory if present
+fn remove_entry(path: &Path) -> io::Result<()> {
+    match path.symlink_metadata() {
+        Ok(meta) if meta.is_dir() => std::fs::remove_dir(path),
+        Ok(_) => std::fs::remove_file(path),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge(dir: &tempfile::TempDir) -> FileMerge {
+        FileMerge::new(dir.path().join("backup"))
+    }
+
+    fn write(merge: &mut FileMerge, dest: &Path, content: &[u8]) -> Result<()> {
+        let staged = merge.stage(dest)?;
+        std::fs::write(staged.path(), content)?;
+        merge.install(staged)
+    }
+
+    #[test]
+    fn test_rollback_restores_originals() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        std::fs::create_dir_all(root.join("usr/bin")).unwrap();
+        std::fs::write(root.join("usr/bin/old"), b"old").unwrap();
+        std::fs::write(root.join("usr/bin/gone"), b"gone").unwrap();
+        std::os::unix::fs::symlink("old", root.join("usr/bin/link")).unwrap();
+
+        let mut m = merge(&dir);
+        write(&mut m, &root.join("usr/bin/old"), b"new").unwrap();
+        write(&mut m, &root.join("usr/share/foo/data"), b"data").unwrap();
+        m.symlink(Path::new("new"), &root.join("usr/bin/link"))
+            .unwrap();
+        m.remove(&root.join("usr/bin/gone")).unwrap();
+        assert_eq!(std::fs::read(root.join("usr/bin/old")).unwrap(), b"new");
+        assert!(!root.join("usr/bin/gone").exists());
+
+        m.rollback().unwrap();
+        assert_eq!(std::fs::read(root.join("usr/bin/old")).unwrap(), b"old");
+        assert_eq!(std::fs::read(root.join("usr/bin/gone")).unwrap(), b"gone");
+        assert_eq!(
+            std::fs::read_link(root.join("usr/bin/link")).unwrap(),
+            Path::new("old")
+        );
+        assert!(!root.join("usr/share").exists());
+        let leftovers: Vec<_> = std::fs::read_dir(root.join("usr/bin"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .filter(|n| n.to_string_lossy().starts_with(STAGE_PREFIX))
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+
+    #[test]
+    fn test_directory_type_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        std::fs::create_dir_all(root.join("etc/empty")).unwrap();
+        std::fs::create_dir_all(root.join("etc/full")).unwrap();
+        std::fs::write(root.join("etc/full/file"), b"").unwrap();
+        std::fs::write(root.join("etc/was-file"), b"file").unwrap();
+        std::fs::create_dir_all(root.join("usr/lib")).unwrap();
+        std::os::unix::fs::symlink("usr/lib", root.join("lib")).unwrap();
+
+        let mut m = merge(&dir);
+        m.symlink(Path::new("/dev/null"), &root.join("etc/empty"))
+            .unwrap();
+        assert!(write(&mut m, &root.join("etc/full"), b"").is_err());
+        m.dir(&root.join("etc/was-file"), 0o755).unwrap();
+        // Directories reached through a symlink stay symlinks
+        write(&mut m, &root.join("lib/libfoo.so"), b"lib").unwrap();
+        assert!(root.join("lib").is_symlink());
+        assert!(root.join("usr/lib/libfoo.so").exists());
+        assert!(root.join("etc/empty").is_symlink());
+        assert!(root.join("etc/was-file").is_dir());
+
+        m.rollback().unwrap();
+        assert!(root.join("etc/empty").is_dir());
+        assert!(root.join("etc/full/file").exists());
+        assert_eq!(std::fs::read(root.join("etc/was-file")).unwrap(), b"file");
+        assert!(!root.join("usr/lib/libfoo.so").exists());
+    }
+
+    #[test]
+    fn test_commit_drops_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("root/file");
+        std::fs::create_dir_all(dest.parent().unwrap()).unwrap();
+        std::fs::write(&dest, b"old").unwrap();
+
+        let mut m = merge(&dir);
+        write(&mut m, &dest, b"new").unwrap();
+        assert!(dir.path().join("backup").exists());
+        m.sync().unwrap();
+        m.commit().unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"new");
+        assert!(!dir.path().join("backup").exists());
+    }
+
+    #[test]
+    fn test_rename_noreplace() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        std::fs::write(&a, b"a").unwrap();
+        std::fs::write(&b, b"b").unwrap();
+        let err = rename_noreplace(&a, &b).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read(&b).unwrap(), b"b");
+    }
+}
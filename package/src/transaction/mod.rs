@@ -4,21 +4,63 @@
 
 use crate::buck::BuckIntegration;
 use crate::cache::PackageCache;
-use crate::db::PackageDb;
+use crate::db::{
+    emit_syslog, BuildAttempt, BuildInfo, DependencyRecord, PackageChange, PackageDb,
+    PackageRecord, ResourceUsage, Vdb,
+};
+use crate::diagnostics::{detect_toolchain, BuildFailure, BuildReport, ReportStore};
 use crate::executor::ParallelExecutor;
+use crate::install_mask::{InstallMask, MaskedStats};
+use crate::interrupt::Cancellation;
+use crate::live::{self, LiveSource};
+use crate::patches::{AppliedPatch, PatchSet};
+use crate::plugin::{PluginManager, TransactionSummary};
+use crate::repository::RepositoryManager;
+use crate::security::verity::{self, VerityConfig};
 use crate::{
-    BuildOptions, Error, FileType, InstalledFile, InstalledPackage, PackageId, PackageInfo, Result,
+    BuckConfigOptions, BuildOptions, BuildResult, Error, FileType, InstalledFile, InstalledPackage,
+    PackageId, PackageInfo, Result,
 };
-use std::collections::HashSet;
+#[cfg(feature = "init")]
+use buckos_boss::{ControlClient, ControlResponse, InhibitWhat, InhibitorLock};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "init")]
+use tokio::sync::OnceCell;
 use tokio::sync::RwLock;
-use tracing::{error, info};
+#[cfg(feature = "init")]
+use tracing::debug;
+use tracing::{error, info, warn};
+
+pub mod eta;
+pub mod merge;
+pub mod pressure;
+pub mod preview;
+pub mod qa;
+pub mod resume;
+pub mod retry;
+pub mod transform;
+#[cfg(feature = "binary-packages")]
+pub mod undo;
+pub mod usage;
+pub use eta::*;
+pub use merge::*;
+pub use pressure::*;
+pub use preview::*;
+pub use qa::*;
+pub use resume::*;
+pub use retry::*;
+pub use transform::*;
+#[cfg(feature = "binary-packages")]
+pub use undo::*;
+pub use usage::*;
 
 /// Package operation type
 #[derive(Debug, Clone)]
 pub enum Operation {
-    Install(PackageInfo),
+    Install(Box<PackageInfo>),
     Remove(InstalledPackage),
     Upgrade {
         old: InstalledPackage,
@@ -26,6 +68,30 @@ pub enum Operation {
     },
 }
 
+/// A built package ready to merge
+struct BuildOutput {
+    /// DESTDIR-structured image
+    path: PathBuf,
+    /// User patches handed to the prepare phase
+    patches: Vec<AppliedPatch>,
+    /// Git URL and commit a live package was built from
+    live_commit: Option<(String, String)>,
+}
+
+/// A change to the plain-text package records, applied on commit
+enum RecordChange {
+    Write(Box<PackageRecord>),
+    Remove(PackageId, semver::Version),
+}
+
+/// A successful build's duration, saved once the transaction finishes
+struct BuildTime {
+    package: PackageId,
+    version: String,
+    use_hash: String,
+    duration: std::time::Duration,
+}
+
 /// Transaction for package operations
 pub struct Transaction {
     db: Arc<RwLock<PackageDb>>,
@@ -34,7 +100,45 @@ pub struct Transaction {
     buck: Arc<BuckIntegration>,
     operations: Vec<Operation>,
     backup_dir: PathBuf,
+    /// Files merged or removed so far, undone on rollback
+    merge: Mutex<FileMerge>,
     root: PathBuf,
+    install_mask: InstallMask,
+    masked: Mutex<MaskedStats>,
+    transforms: MergeTransforms,
+    qa: QaPolicy,
+    plugins: Option<Arc<PluginManager>>,
+    audit_syslog: bool,
+    /// Staged DESTDIRs for packages installed without building, by atom
+    prebuilt: HashMap<String, PathBuf>,
+    build_reports: Option<ReportStore>,
+    /// Base directory searched for user patches
+    user_patches: Option<PathBuf>,
+    /// Commits live packages are pinned to
+    live_pins: HashMap<String, String>,
+    toolchain: std::sync::OnceLock<Option<String>>,
+    build_times: Mutex<Vec<BuildTime>>,
+    /// Retry policies of builds that fail for transient reasons
+    retry: RetryConfig,
+    /// Holds builds back under memory pressure
+    pressure: PressureThrottle,
+    /// fs-verity protection of merged files
+    verity: VerityConfig,
+    /// Every try of every build, saved once the transaction finishes
+    build_attempts: Mutex<Vec<BuildAttempt>>,
+    /// Plain-text package records kept alongside the database
+    vdb: Option<Vdb>,
+    record_changes: Mutex<Vec<RecordChange>>,
+    /// Repositories, to record where each package came from
+    repos: Option<Arc<RepositoryManager>>,
+    /// Shutdown inhibitor held from the first change to the filesystem
+    /// until commit or rollback
+    #[cfg(feature = "init")]
+    inhibitor: OnceCell<Option<InhibitorLock>>,
+    /// Build actions Buck served from its cache
+    cached_actions: AtomicU64,
+    /// Stops the transaction before its next operation
+    cancellation: Cancellation,
 }
 
 impl Transaction {
@@ -51,14 +155,178 @@ impl Transaction {
             cache,
             buck,
             operations: Vec::new(),
+            merge: Mutex::new(FileMerge::new(&backup_dir)),
             backup_dir,
             root,
+            install_mask: InstallMask::new(),
+            masked: Mutex::new(MaskedStats::default()),
+            transforms: MergeTransforms::new(),
+            qa: QaPolicy::new(),
+            plugins: None,
+            audit_syslog: false,
+            prebuilt: HashMap::new(),
+            build_reports: None,
+            user_patches: None,
+            live_pins: HashMap::new(),
+            toolchain: std::sync::OnceLock::new(),
+            build_times: Mutex::new(Vec::new()),
+            retry: RetryConfig::default(),
+            pressure: PressureThrottle::default(),
+            verity: VerityConfig::default(),
+            build_attempts: Mutex::new(Vec::new()),
+            vdb: None,
+            record_changes: Mutex::new(Vec::new()),
+            repos: None,
+            #[cfg(feature = "init")]
+            inhibitor: OnceCell::new(),
+            cached_actions: AtomicU64::new(0),
+            cancellation: Cancellation::new(),
         }
     }
 
+    /// Handle to stop the transaction from elsewhere
+    ///
+    /// The operation in progress ends first: a build is stopped, a merge
+    /// finishes. Then everything the transaction did is rolled back.
+    pub fn cancellation(&self) -> Cancellation {
+        self.cancellation.clone()
+    }
+
+    /// Run plugin pre/post-transaction hooks around execution
+    pub fn with_plugins(mut self, plugins: Arc<PluginManager>) -> Self {
+        self.plugins = Some(plugins);
+        self
+    }
+
+    /// Copy history entries to the local syslog
+    pub fn with_audit_syslog(mut self, enabled: bool) -> Self {
+        self.audit_syslog = enabled;
+        self
+    }
+
+    /// Save compiler diagnostics from each build
+    pub fn with_build_reports(mut self, store: ReportStore) -> Self {
+        self.build_reports = Some(store);
+        self
+    }
+
+    /// Hand user patches under `dir` to each build's prepare phase
+    pub fn with_user_patches(mut self, dir: PathBuf) -> Self {
+        self.user_patches = Some(dir);
+        self
+    }
+
+    /// Keep plain-text records of merged packages in sync with the database
+    pub fn with_vdb(mut self, vdb: Vdb) -> Self {
+        self.vdb = Some(vdb);
+        self
+    }
+
+    /// Record which repository each merged package came from
+    pub fn with_repositories(mut self, repos: Arc<RepositoryManager>) -> Self {
+        self.repos = Some(repos);
+        self
+    }
+
+    /// Build live packages at these commits instead of upstream's head
+    pub fn with_live_pins(mut self, pins: HashMap<String, String>) -> Self {
+        self.live_pins = pins;
+        self
+    }
+
+    /// Packages this transaction changes, as reported to plugins
+    pub fn summary(&self) -> TransactionSummary {
+        let atom = |id: &crate::PackageId, version: &semver::Version| format!("{}-{}", id, version);
+        let mut summary = TransactionSummary::default();
+        for op in &self.operations {
+            match op {
+                Operation::Install(pkg) => summary.install.push(atom(&pkg.id, &pkg.version)),
+                Operation::Remove(pkg) => summary.remove.push(atom(&pkg.id, &pkg.version)),
+                Operation::Upgrade { old, new } => summary
+                    .upgrade
+                    .push((atom(&old.id, &old.version), atom(&new.id, &new.version))),
+            }
+        }
+        summary
+    }
+
+    /// Packages this transaction changes, as recorded in the history
+    pub fn changes(&self) -> Vec<PackageChange> {
+        self.operations
+            .iter()
+            .map(|op| match op {
+                Operation::Install(pkg) => PackageChange {
+                    package: pkg.id.clone(),
+                    slot: pkg.slot.clone(),
+                    old_version: None,
+                    new_version: Some(pkg.version.to_string()),
+                },
+                Operation::Remove(pkg) => PackageChange {
+                    package: pkg.id.clone(),
+                    slot: pkg.slot.clone(),
+                    old_version: Some(pkg.version.to_string()),
+                    new_version: None,
+                },
+                Operation::Upgrade { old, new } => PackageChange {
+                    package: new.id.clone(),
+                    slot: new.slot.clone(),
+                    old_version: Some(old.version.to_string()),
+                    new_version: Some(new.version.to_string()),
+                },
+            })
+            .collect()
+    }
+
+    /// Skip files matching an INSTALL_MASK when merging
+    pub fn with_install_mask(mut self, install_mask: InstallMask) -> Self {
+        self.install_mask = install_mask;
+        self
+    }
+
+    /// Strip binaries and compress pages while merging
+    pub fn with_transforms(mut self, transforms: MergeTransforms) -> Self {
+        self.transforms = transforms;
+        self
+    }
+
+    /// Run QA checks on each image before merging
+    pub fn with_qa(mut self, qa: QaPolicy) -> Self {
+        self.qa = qa;
+        self
+    }
+
+    /// Retry builds that fail for transient reasons
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Throttle build launches by memory pressure
+    pub fn with_pressure(mut self, pressure: PressureThrottle) -> Self {
+        self.pressure = pressure;
+        self
+    }
+
+    /// Enable fs-verity on merged files
+    pub fn with_verity(mut self, verity: VerityConfig) -> Self {
+        self.verity = verity;
+        self
+    }
+
+    /// Files skipped by INSTALL_MASK so far
+    pub fn masked_stats(&self) -> MaskedStats {
+        *self.masked.lock().unwrap()
+    }
+
     /// Add an install operation
     pub fn add_install(&mut self, pkg: PackageInfo) {
-        self.operations.push(Operation::Install(pkg));
+        self.operations.push(Operation::Install(Box::new(pkg)));
+    }
+
+    /// Install `pkg` from an already-staged DESTDIR instead of building it
+    pub fn use_prebuilt(&mut self, pkg: &PackageInfo, staged: PathBuf) {
+        self.prebuilt
+            .insert(format!("{}-{}", pkg.id, pkg.version), staged);
     }
 
     /// Add a remove operation
@@ -85,8 +353,19 @@ impl Transaction {
             self.operations.len()
         );
 
-        // Create backup directory
-        std::fs::create_dir_all(&self.backup_dir)?;
+        // Ctrl-C and SIGTERM cancel rather than kill until the end
+        let uncaught = self.cancellation.clone();
+        self.cancellation = uncaught.clone().catch_signals();
+
+        let meter = UsageMeter::start(self.buck.daemon(), &self.root);
+        let summary = self.summary();
+        // Hooks run plugin executables; keep them off the runtime's workers
+        if let Some(plugins) = self.plugins.clone() {
+            let summary = summary.clone();
+            tokio::task::spawn_blocking(move || plugins.pre_transaction(&summary))
+                .await
+                .map_err(|e| Error::Other(e.to_string()))??;
+        }
 
         // Start database transaction
         {
@@ -95,38 +374,269 @@ impl Transaction {
         }
 
         let result = self.execute_operations(executor).await;
+        if let Some(plugins) = self.plugins.clone() {
+            let success = result.is_ok();
+            let _ =
+                tokio::task::spawn_blocking(move || plugins.post_transaction(&summary, success))
+                    .await;
+        }
+
+        let outcome = self.finish(result).await;
+        // The system is consistent again, committed or restored
+        #[cfg(feature = "init")]
+        self.inhibitor.take();
+        if outcome.is_ok() {
+            self.notify_packages_changed().await;
+        }
+        let usage = meter.finish(
+            self.cached_actions.load(Ordering::Relaxed),
+            self.prebuilt_operations(),
+        );
+        // Outside the database transaction, so failed runs keep their timings
+        self.record_build_times().await;
+        self.record_history(outcome.as_ref().err(), &usage).await;
+        self.cancellation = uncaught;
+        outcome
+    }
 
-        match result {
+    /// Commit on success, roll back and restore backups on failure
+    async fn finish(&self, result: Result<()>) -> Result<()> {
+        let merge = std::mem::replace(
+            &mut *self.merge.lock().unwrap(),
+            FileMerge::new(&self.backup_dir),
+        );
+        // Merged files reach the disk before the database records them
+        match result.and_then(|()| merge.sync()) {
             Ok(()) => {
                 // Commit database transaction
                 let mut db = self.db.write().await;
-                db.commit()?;
+                if let Err(e) = db.commit() {
+                    if let Err(restore_err) = merge.rollback() {
+                        error!("Failed to restore backup: {}", restore_err);
+                    }
+                    return Err(e);
+                }
                 info!("Transaction committed successfully");
+                self.write_records();
+
+                let masked = self.masked_stats();
+                if masked.files > 0 {
+                    info!(
+                        "INSTALL_MASK skipped {} files, reclaimed {}",
+                        masked.files,
+                        crate::resolver::format_size(masked.bytes)
+                    );
+                }
 
                 // Clean up backup
-                if self.backup_dir.exists() {
-                    let _ = std::fs::remove_dir_all(&self.backup_dir);
+                if let Err(e) = merge.commit() {
+                    warn!("Failed to remove merge backups: {}", e);
                 }
 
                 Ok(())
             }
             Err(e) => {
-                error!("Transaction failed: {}", e);
+                match &e {
+                    Error::Interrupted { .. } => warn!("{}, rolling back", e),
+                    _ => error!("Transaction failed: {}", e),
+                }
 
                 // Rollback database transaction
                 let mut db = self.db.write().await;
                 let _ = db.rollback();
+                self.record_changes.lock().unwrap().clear();
 
                 // Restore from backup
-                if let Err(restore_err) = self.restore_backup().await {
+                if let Err(restore_err) = merge.rollback() {
                     error!("Failed to restore backup: {}", restore_err);
                 }
 
-                Err(Error::TransactionRolledBack(e.to_string()))
+                match e {
+                    Error::Interrupted { .. } => Err(e),
+                    e => Err(Error::TransactionRolledBack(e.to_string())),
+                }
+            }
+        }
+    }
+
+    /// Apply record changes once the database has committed
+    ///
+    /// A record that fails to write is logged; `buckos db check` reports
+    /// the database and records disagreeing.
+    fn write_records(&self) {
+        let changes = std::mem::take(&mut *self.record_changes.lock().unwrap());
+        let Some(vdb) = &self.vdb else {
+            return;
+        };
+        for change in changes {
+            let result = match &change {
+                RecordChange::Write(pkg) => vdb.write(pkg),
+                RecordChange::Remove(id, version) => vdb.remove(id, version),
+            };
+            if let Err(e) = result {
+                warn!("Failed to update the package record: {}", e);
             }
         }
     }
 
+    /// Tell init the installed packages changed, so services waiting for
+    /// a package they require can start
+    ///
+    /// Only for the running system: packages merged into another root are
+    /// not the ones its init reads.
+    #[cfg(feature = "init")]
+    async fn notify_packages_changed(&self) {
+        if self.root != Path::new("/") {
+            return;
+        }
+        let client = ControlClient::with_default_path();
+        if !client.is_available() {
+            return;
+        }
+        match client.packages_changed().await {
+            Ok(ControlResponse::Success { message }) => debug!("{}", message),
+            Ok(ControlResponse::Error { message }) => {
+                warn!("Init failed to check required packages: {}", message)
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to notify init of package changes: {}", e),
+        }
+    }
+
+    /// Take a shutdown inhibitor from init, once, before files are changed
+    ///
+    /// A reboot between removing an old version and merging the new one
+    /// leaves the system broken, so shutdown is blocked until the
+    /// transaction commits or rolls back. Without a running init (in a
+    /// chroot, say) the transaction goes ahead without a lock.
+    #[cfg(feature = "init")]
+    async fn inhibit_shutdown(&self) {
+        self.inhibitor
+            .get_or_init(|| async {
+                let client = ControlClient::with_default_path();
+                if !client.is_available() {
+                    return None;
+                }
+                let summary = self.summary();
+                let why = format!(
+                    "Merging packages ({} install, {} upgrade, {} remove)",
+                    summary.install.len(),
+                    summary.upgrade.len(),
+                    summary.remove.len()
+                );
+                match client
+                    .inhibit(vec![InhibitWhat::Shutdown], "buckos", &why)
+                    .await
+                {
+                    Ok(lock) => {
+                        debug!("Holding shutdown inhibitor: {}", why);
+                        Some(lock)
+                    }
+                    Err(e) => {
+                        warn!("Failed to take shutdown inhibitor: {}", e);
+                        None
+                    }
+                }
+            })
+            .await;
+    }
+
+    #[cfg(not(feature = "init"))]
+    async fn notify_packages_changed(&self) {}
+
+    #[cfg(not(feature = "init"))]
+    async fn inhibit_shutdown(&self) {}
+
+    /// Append this transaction to the audit trail
+    ///
+    /// A failure to record is logged rather than failing an operation that
+    /// already happened.
+    async fn record_history(&self, error: Option<&Error>, usage: &ResourceUsage) {
+        let user = std::env::var("SUDO_USER")
+            .or_else(|_| std::env::var("USER"))
+            .unwrap_or_else(|_| "unknown".to_string());
+        let command = std::env::args().collect::<Vec<_>>().join(" ");
+        let error = error.map(|e| e.to_string());
+
+        let mut db = self.db.write().await;
+        match db.record_history(
+            &user,
+            &command,
+            error.as_deref(),
+            &self.changes(),
+            Some(usage),
+        ) {
+            Ok(entry) => {
+                if self.audit_syslog {
+                    emit_syslog(&entry);
+                }
+            }
+            Err(e) => warn!("Failed to record transaction history: {}", e),
+        }
+    }
+
+    /// Operations merging a binary package instead of building
+    fn prebuilt_operations(&self) -> u64 {
+        self.operations
+            .iter()
+            .filter(|op| match op {
+                Operation::Install(pkg) | Operation::Upgrade { new: pkg, .. } => self
+                    .prebuilt
+                    .contains_key(&format!("{}-{}", pkg.id, pkg.version)),
+                Operation::Remove(_) => false,
+            })
+            .count() as u64
+    }
+
+    async fn record_build_times(&self) {
+        let times = std::mem::take(&mut *self.build_times.lock().unwrap());
+        let attempts = std::mem::take(&mut *self.build_attempts.lock().unwrap());
+        let db = self.db.write().await;
+        for attempt in attempts {
+            if let Err(e) = db.record_build_attempt(&attempt) {
+                warn!(
+                    "Failed to record build attempt of {}: {}",
+                    attempt.package, e
+                );
+            }
+        }
+        for time in times {
+            if let Err(e) = db.record_build_duration(
+                &time.package,
+                &time.version,
+                &time.use_hash,
+                time.duration,
+            ) {
+                warn!("Failed to record build time of {}: {}", time.package, e);
+            }
+        }
+    }
+
+    /// Estimated time to build the packages this transaction installs
+    pub async fn estimate(&self) -> Result<TransactionEstimate> {
+        let db = self.db.read().await;
+        let mut packages = Vec::new();
+        for op in &self.operations {
+            let pkg = match op {
+                Operation::Install(pkg) | Operation::Upgrade { new: pkg, .. } => pkg,
+                Operation::Remove(_) => continue,
+            };
+            if self
+                .prebuilt
+                .contains_key(&format!("{}-{}", pkg.id, pkg.version))
+            {
+                continue;
+            }
+            let version = pkg.version.to_string();
+            packages.push(BuildEstimate {
+                estimate: db.build_duration_estimate(&pkg.id, &version, &default_use_hash(pkg))?,
+                package: pkg.id.clone(),
+                version,
+            });
+        }
+        Ok(TransactionEstimate { packages })
+    }
+
     async fn execute_operations(&self, _executor: &ParallelExecutor) -> Result<()> {
         // Group operations by type
         let mut installs = Vec::new();
@@ -135,7 +645,7 @@ impl Transaction {
 
         for op in &self.operations {
             match op {
-                Operation::Install(pkg) => installs.push(pkg.clone()),
+                Operation::Install(pkg) => installs.push(pkg.as_ref().clone()),
                 Operation::Remove(pkg) => removes.push(pkg.clone()),
                 Operation::Upgrade { old, new } => upgrades.push((old.clone(), new.clone())),
             }
@@ -143,45 +653,80 @@ impl Transaction {
 
         // Execute removes first
         for pkg in &removes {
+            self.cancellation.check()?;
             self.execute_remove(pkg).await?;
         }
 
+        let mut eta = Eta::new(&self.estimate().await?);
+        let total = upgrades.len() + installs.len();
+        let mut step = 0;
+
         // Execute upgrades (remove old, install new)
         for (old, new) in &upgrades {
+            self.cancellation.check()?;
             self.execute_remove(old).await?;
-            self.execute_install(new).await?;
+            step += 1;
+            self.execute_install_timed(new, &mut eta, step, total)
+                .await?;
         }
 
         // Execute installs
         for pkg in &installs {
-            self.execute_install(pkg).await?;
+            self.cancellation.check()?;
+            step += 1;
+            self.execute_install_timed(pkg, &mut eta, step, total)
+                .await?;
         }
 
         Ok(())
     }
 
+    /// Install a package, reporting progress and the ETA of the rest
+    async fn execute_install_timed(
+        &self,
+        pkg: &PackageInfo,
+        eta: &mut Eta,
+        step: usize,
+        total: usize,
+    ) -> Result<()> {
+        match eta.remaining() {
+            Some(left) => info!(
+                "({}/{}) {}-{}, about {} remaining",
+                step,
+                total,
+                pkg.id,
+                pkg.version,
+                format_duration(left)
+            ),
+            None => info!("({}/{}) {}-{}", step, total, pkg.id, pkg.version),
+        }
+        let started = std::time::Instant::now();
+        self.execute_install(pkg).await?;
+        eta.complete(&format!("{}-{}", pkg.id, pkg.version), started.elapsed());
+        Ok(())
+    }
+
     async fn execute_install(&self, pkg: &PackageInfo) -> Result<()> {
         info!("Installing {}-{}", pkg.id.name, pkg.version);
 
-        // Build the package using Buck
-        let target = &pkg.buck_target;
-        let build_result = self.buck.build(target, &BuildOptions::default()).await?;
-
-        if !build_result.success {
-            return Err(Error::BuildFailed {
-                package: pkg.id.name.clone(),
-                message: build_result.stderr,
-            });
-        }
+        let prebuilt = self.prebuilt.get(&format!("{}-{}", pkg.id, pkg.version));
+        let built = match prebuilt {
+            Some(staged) => BuildOutput {
+                path: staged.clone(),
+                patches: Vec::new(),
+                live_commit: None,
+            },
+            None => self.build(pkg).await?,
+        };
+        let output_path = built.path;
 
-        // Get the built package
-        let output_path = build_result.output_path.ok_or_else(|| Error::BuildFailed {
-            package: pkg.id.name.clone(),
-            message: "No output produced".to_string(),
-        })?;
+        self.check_qa(pkg, &output_path)?;
+        // A merge is not stopped once started, so don't start one
+        self.cancellation.check()?;
 
         // Extract and install files
-        let files = self.install_files(&output_path, &pkg.id).await?;
+        self.inhibit_shutdown().await;
+        let files = self.install_files(&output_path, pkg).await?;
 
         // Record in database
         let installed = InstalledPackage {
@@ -190,41 +735,232 @@ impl Transaction {
             version: pkg.version.clone(),
             slot: pkg.slot.clone(),
             installed_at: chrono::Utc::now(),
-            use_flags: HashSet::new(),
+            // Builds use each flag's default, as in the build-time estimates
+            use_flags: pkg
+                .use_flags
+                .iter()
+                .filter(|f| f.default)
+                .map(|f| f.name.clone())
+                .collect(),
             files,
             size: pkg.installed_size,
             build_time: false,
             explicit: true,
         };
+        let record = PackageRecord {
+            package: installed,
+            dependencies: dependency_records(pkg),
+            patches: built.patches,
+            live_commit: built
+                .live_commit
+                .map(|(url, commit)| crate::db::LiveCommit { url, commit }),
+            build_info: Some(BuildInfo {
+                repository: self.repos.as_ref().and_then(|r| r.repository_of(pkg)),
+                buck_target: pkg.buck_target.clone(),
+                prebuilt: prebuilt.is_some(),
+            }),
+        };
 
         let mut db = self.db.write().await;
-        db.add_package(&installed)?;
+        db.add_record(&record)?;
+        if self.verity.enabled {
+            // Before the stats, as enabling fs-verity changes the ctime
+            verity::protect_package(&db, &self.root, &self.verity, &record.package.name)?;
+        }
+        db.record_file_stats(&record.package.name)?;
+        self.record_changes
+            .lock()
+            .unwrap()
+            .push(RecordChange::Write(Box::new(record)));
 
         info!("Installed {}-{}", pkg.id.name, pkg.version);
         Ok(())
     }
 
+    /// Build a package with Buck
+    async fn build(&self, pkg: &PackageInfo) -> Result<BuildOutput> {
+        let patches = match &self.user_patches {
+            Some(dir) => PatchSet::discover(dir, &pkg.id, &pkg.version.to_string(), &pkg.slot)?,
+            None => PatchSet::default(),
+        };
+        let mut opts = BuildOptions::default();
+        if let Some(dir) = patches.dir.as_ref().filter(|_| !patches.is_empty()) {
+            info!(
+                "Applying {} user patch(es) from {}",
+                patches.patches.len(),
+                dir.display()
+            );
+            // The digest makes Buck rebuild when a patch changes
+            let mut config = BuckConfigOptions::default();
+            config.overrides.insert(
+                format!("user_patches.{}", pkg.id.name),
+                dir.display().to_string(),
+            );
+            config.overrides.insert(
+                format!("user_patches.{}_sha256", pkg.id.name),
+                patches.digest()?,
+            );
+            opts.config_options = Some(config);
+        }
+
+        let live_commit = match pkg.source_url.as_deref().and_then(LiveSource::parse) {
+            Some(source) => {
+                let commit = live::target_commit(&self.live_pins, pkg).await?;
+                info!("Building {} from {} at {}", pkg.id, source.url, commit);
+                opts.config_options
+                    .get_or_insert_with(BuckConfigOptions::default)
+                    .overrides
+                    .insert(format!("live.{}", pkg.id.name), commit.clone());
+                Some((source.url, commit))
+            }
+            None => None,
+        };
+
+        let target = &pkg.buck_target;
+        let policy = self.retry.policy_for(&pkg.id);
+        let flags: Vec<String> = pkg.use_flags.iter().map(|f| f.name.clone()).collect();
+        let mut attempt = 1;
+        let (build_result, failure) = loop {
+            let mut attempt_opts = opts.clone();
+            policy.apply(attempt, self.buck.jobs(), &mut attempt_opts);
+            let jobs = attempt_opts.jobs.unwrap_or_else(|| self.buck.jobs());
+            attempt_opts.jobs = tokio::select! {
+                jobs = self.pressure.admit(jobs) => Some(jobs),
+                _ = self.cancellation.cancelled() => None,
+            };
+            self.cancellation.check()?;
+            attempt_opts.cancellation = Some(self.cancellation.clone());
+            let started_at = chrono::Utc::now();
+            let build_result = self.buck.build(target, &attempt_opts).await?;
+            self.cached_actions
+                .fetch_add(cached_actions(&build_result.stderr), Ordering::Relaxed);
+            let log = format!("{}\n{}", build_result.stdout, build_result.stderr);
+            let failure = if build_result.success {
+                None
+            } else {
+                BuildFailure::classify(&log, &flags)
+            };
+            self.record_build_report(pkg, &build_result, &log, failure.clone());
+            self.build_attempts.lock().unwrap().push(BuildAttempt {
+                package: pkg.id.clone(),
+                version: pkg.version.to_string(),
+                attempt,
+                success: build_result.success,
+                failure: failure.as_ref().map(|f| f.kind_name().to_string()),
+                jobs: attempt_opts.jobs.unwrap_or_else(|| self.buck.jobs()),
+                network: policy.network(attempt),
+                duration: build_result.duration,
+                started_at,
+            });
+
+            if build_result.success || !policy.should_retry(attempt, failure.as_ref()) {
+                break (build_result, failure);
+            }
+            let reason = failure.as_ref().map(|f| f.reason()).unwrap_or_default();
+            attempt += 1;
+            warn!(
+                "Build of {}-{} failed ({}), retrying ({}/{}) with {} job(s)",
+                pkg.id,
+                pkg.version,
+                reason,
+                attempt - 1,
+                policy.retries,
+                policy.jobs(attempt, self.buck.jobs())
+            );
+        };
+
+        if !build_result.success {
+            let mut message = build_result.stderr;
+            if let Some(failure) = failure {
+                message = format!(
+                    "{}\nreason: {}\nhint: {}",
+                    message.trim_end(),
+                    failure.reason(),
+                    failure.hint(&pkg.id.full_name())
+                );
+            }
+            return Err(Error::BuildFailed {
+                package: pkg.id.name.clone(),
+                message,
+            });
+        }
+
+        self.build_times.lock().unwrap().push(BuildTime {
+            package: pkg.id.clone(),
+            version: pkg.version.to_string(),
+            use_hash: default_use_hash(pkg),
+            duration: build_result.duration,
+        });
+
+        let output_path = build_result.output_path.ok_or_else(|| Error::BuildFailed {
+            package: pkg.id.name.clone(),
+            message: "No output produced".to_string(),
+        })?;
+        Ok(BuildOutput {
+            path: output_path,
+            patches: patches.records()?,
+            live_commit,
+        })
+    }
+
+    /// Scan a staged image, failing in strict mode if anything is found
+    fn check_qa(&self, pkg: &PackageInfo, image: &Path) -> Result<()> {
+        let issues = self.qa.check_image(image, &pkg.id)?;
+        for issue in &issues {
+            warn!("QA {}-{}: {}", pkg.id, pkg.version, issue);
+        }
+        if self.qa.is_strict() && !issues.is_empty() {
+            let messages: Vec<String> = issues.iter().map(|i| i.to_string()).collect();
+            return Err(Error::QaFailed {
+                package: format!("{}-{}", pkg.id, pkg.version),
+                message: messages.join("; "),
+            });
+        }
+        Ok(())
+    }
+
+    fn record_build_report(
+        &self,
+        pkg: &PackageInfo,
+        build_result: &BuildResult,
+        log: &str,
+        failure: Option<BuildFailure>,
+    ) {
+        let Some(store) = &self.build_reports else {
+            return;
+        };
+        let toolchain = self.toolchain.get_or_init(detect_toolchain).clone();
+        let report = BuildReport::from_log(
+            pkg.id.clone(),
+            pkg.version.to_string(),
+            toolchain,
+            build_result.success,
+            log,
+        )
+        .with_failure(failure);
+        if let Err(e) = store.save(&report) {
+            warn!("Failed to save build report for {}: {}", pkg.id, e);
+        }
+        if let Err(e) = store.save_log(&pkg.id, &report.version, log) {
+            warn!("Failed to save build log for {}: {}", pkg.id, e);
+        }
+    }
+
     async fn execute_remove(&self, pkg: &InstalledPackage) -> Result<()> {
         info!("Removing {}-{}", pkg.name, pkg.version);
 
-        // Backup files first
-        self.backup_package(pkg).await?;
+        self.inhibit_shutdown().await;
 
         // Remove files in reverse order (files before directories)
         let mut files = pkg.files.clone();
         files.sort_by(|a, b| b.path.cmp(&a.path));
 
-        for file in &files {
-            let path = Path::new(&file.path);
-            if path.exists() {
-                match file.file_type {
-                    FileType::Directory => {
-                        // Only remove empty directories
-                        let _ = std::fs::remove_dir(path);
-                    }
-                    _ => {
-                        std::fs::remove_file(path)?;
-                    }
+        {
+            // Kept aside until commit; only empty directories are removed
+            let mut merge = self.merge.lock().unwrap();
+            for file in &files {
+                if file.file_type != FileType::Masked {
+                    merge.remove(Path::new(&file.path))?;
                 }
             }
         }
@@ -232,6 +968,10 @@ impl Transaction {
         // Remove from database
         let mut db = self.db.write().await;
         db.remove_package(&pkg.name)?;
+        self.record_changes
+            .lock()
+            .unwrap()
+            .push(RecordChange::Remove(pkg.id.clone(), pkg.version.clone()));
 
         info!("Removed {}-{}", pkg.name, pkg.version);
         Ok(())
@@ -240,8 +980,11 @@ impl Transaction {
     async fn install_files(
         &self,
         build_output_path: &Path,
-        _pkg_id: &PackageId,
+        pkg: &PackageInfo,
     ) -> Result<Vec<InstalledFile>> {
+        let transforms = self.transforms.for_package(&pkg.restrict);
+        let mut merge = self.merge.lock().unwrap();
+
         // Buck output is a DESTDIR-structured directory (usr/lib, usr/include, etc.)
         // not a tarball, so we walk it directly
         let mut installed_files = Vec::new();
@@ -262,8 +1005,24 @@ impl Transaction {
             let dest_path = self.root.join(relative_path);
             let metadata = entry.metadata()?;
 
+            if self.install_mask.is_masked(relative_path) {
+                // Directories are implied by the files below them
+                if !metadata.is_dir() {
+                    self.masked.lock().unwrap().record(metadata.len());
+                    installed_files.push(InstalledFile {
+                        path: dest_path.to_string_lossy().to_string(),
+                        file_type: FileType::Masked,
+                        mode: 0,
+                        size: metadata.len(),
+                        blake3_hash: None,
+                        mtime: 0,
+                    });
+                }
+                continue;
+            }
+
             if metadata.is_dir() {
-                std::fs::create_dir_all(&dest_path)?;
+                merge.dir(&dest_path, 0o755)?;
                 installed_files.push(InstalledFile {
                     path: dest_path.to_string_lossy().to_string(),
                     file_type: FileType::Directory,
@@ -277,22 +1036,27 @@ impl Transaction {
                         .as_secs() as i64,
                 });
             } else if metadata.is_file() {
-                // Ensure parent directory exists
-                if let Some(parent) = dest_path.parent() {
-                    std::fs::create_dir_all(parent)?;
+                // Stage next to the destination, strip / compress, then
+                // hash what actually landed on disk
+                let staged = merge.stage_copy(entry.path(), &dest_path)?;
+                let outcome = transforms.apply(
+                    &self.root,
+                    relative_path,
+                    staged,
+                    &mut merge,
+                    &self.install_mask,
+                )?;
+                let hash = crate::cache::compute_blake3(&outcome.path)?;
+
+                for extra in &outcome.extra {
+                    installed_files.push(transformed_file(extra)?);
                 }
 
-                // Copy file
-                std::fs::copy(entry.path(), &dest_path)?;
-
-                // Compute hash
-                let hash = crate::cache::compute_blake3(&dest_path)?;
-
                 installed_files.push(InstalledFile {
-                    path: dest_path.to_string_lossy().to_string(),
+                    path: outcome.path.to_string_lossy().to_string(),
                     file_type: FileType::Regular,
                     mode: 0o644,
-                    size: metadata.len(),
+                    size: std::fs::metadata(&outcome.path)?.len(),
                     blake3_hash: Some(hash),
                     mtime: metadata
                         .modified()?
@@ -301,14 +1065,17 @@ impl Transaction {
                         .as_secs() as i64,
                 });
             } else if metadata.file_type().is_symlink() {
-                let target = std::fs::read_link(entry.path())?;
-
-                // Remove existing symlink if present
-                if dest_path.exists() || dest_path.is_symlink() {
-                    std::fs::remove_file(&dest_path)?;
+                let mut target = std::fs::read_link(entry.path())?;
+
+                // Links to compressed pages follow the rename
+                let mut dest_path = dest_path;
+                if let Some((link, new_target)) = transforms.rename_symlink(relative_path, &target)
+                {
+                    dest_path = self.root.join(link);
+                    target = new_target;
                 }
 
-                std::os::unix::fs::symlink(&target, &dest_path)?;
+                merge.symlink(&target, &dest_path)?;
 
                 installed_files.push(InstalledFile {
                     path: dest_path.to_string_lossy().to_string(),
@@ -323,80 +1090,62 @@ impl Transaction {
 
         Ok(installed_files)
     }
+}
 
-    async fn backup_package(&self, pkg: &InstalledPackage) -> Result<()> {
-        let backup_path = self.backup_dir.join(&pkg.name);
-        std::fs::create_dir_all(&backup_path)?;
-
-        for file in &pkg.files {
-            let src_path = Path::new(&file.path);
-            if !src_path.exists() {
-                continue;
-            }
-
-            let relative = src_path.strip_prefix("/").unwrap_or(src_path);
-            let dest = backup_path.join(relative);
-
-            if let Some(parent) = dest.parent() {
-                std::fs::create_dir_all(parent)?;
-            }
+/// Database entry for a file created by a merge transform
+fn transformed_file(extra: &ExtraFile) -> Result<InstalledFile> {
+    let path = extra.path.to_string_lossy().to_string();
+    Ok(match extra.file_type {
+        FileType::Symlink => InstalledFile {
+            path,
+            file_type: FileType::Symlink,
+            mode: 0o777,
+            size: 0,
+            blake3_hash: None,
+            mtime: 0,
+        },
+        file_type => InstalledFile {
+            path,
+            file_type,
+            mode: 0o644,
+            size: std::fs::metadata(&extra.path)?.len(),
+            blake3_hash: Some(crate::cache::compute_blake3(&extra.path)?),
+            mtime: 0,
+        },
+    })
+}
 
-            match file.file_type {
-                FileType::Regular => {
-                    std::fs::copy(src_path, &dest)?;
-                }
-                FileType::Symlink => {
-                    let target = std::fs::read_link(src_path)?;
-                    std::os::unix::fs::symlink(target, &dest)?;
-                }
-                _ => {}
+/// Dependencies of a package to record, merging the build and run-time lists
+fn dependency_records(pkg: &PackageInfo) -> Vec<DependencyRecord> {
+    let mut records: Vec<DependencyRecord> = Vec::new();
+    let deps = pkg
+        .dependencies
+        .iter()
+        .chain(&pkg.build_dependencies)
+        .chain(&pkg.runtime_dependencies);
+    for dep in deps {
+        match records.iter_mut().find(|r| r.package == dep.package) {
+            Some(record) => {
+                record.build_time |= dep.build_time;
+                record.run_time |= dep.run_time;
             }
+            None => records.push(DependencyRecord {
+                package: dep.package.clone(),
+                slot: dep.slot.clone(),
+                build_time: dep.build_time,
+                run_time: dep.run_time,
+            }),
         }
-
-        Ok(())
     }
+    records
+}
 
-    async fn restore_backup(&self) -> Result<()> {
-        if !self.backup_dir.exists() {
-            return Ok(());
-        }
-
-        info!("Restoring from backup");
-
-        for entry in walkdir::WalkDir::new(&self.backup_dir) {
-            let entry = entry?;
-            let relative = match entry.path().strip_prefix(&self.backup_dir) {
-                Ok(p) => p,
-                Err(_) => continue,
-            };
-
-            if relative.as_os_str().is_empty() {
-                continue;
-            }
-
-            // First component is package name
-            let components: Vec<_> = relative.components().collect();
-            if components.len() < 2 {
-                continue;
-            }
-
-            let file_path: PathBuf = components[1..].iter().collect();
-            let dest_path = Path::new("/").join(&file_path);
-
-            if entry.file_type().is_file() {
-                if let Some(parent) = dest_path.parent() {
-                    std::fs::create_dir_all(parent)?;
-                }
-                std::fs::copy(entry.path(), &dest_path)?;
-            } else if entry.file_type().is_symlink() {
-                let target = std::fs::read_link(entry.path())?;
-                if dest_path.exists() {
-                    std::fs::remove_file(&dest_path)?;
-                }
-                std::os::unix::fs::symlink(target, &dest_path)?;
-            }
-        }
-
-        Ok(())
-    }
+/// USE hash of the flags a package builds with by default
+fn default_use_hash(pkg: &PackageInfo) -> String {
+    use_hash(
+        pkg.use_flags
+            .iter()
+            .filter(|f| f.default)
+            .map(|f| f.name.as_str()),
+    )
 }
@@ -0,0 +1,249 @@
+//! Memory-pressure-aware build throttling
+//!
+//! Before each build is launched, the kernel's pressure stall information
+//! (`/proc/pressure/memory`) is read. While tasks stall on memory the build
+//! runs with fewer jobs, and while the whole system stalls no build is
+//! launched until pressure subsides, so small machines swap or slow down
+//! instead of having the OOM killer take out half-finished compiles.
+//! Kernels without PSI are never throttled.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Kernel file reporting memory pressure
+pub const MEMORY_PRESSURE_PATH: &str = "/proc/pressure/memory";
+
+/// Share of time, in percent, tasks stalled on memory
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PressureAverages {
+    pub avg10: f64,
+    pub avg60: f64,
+    pub avg300: f64,
+}
+
+/// One reading of `/proc/pressure/memory`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MemoryPressure {
+    /// Some tasks stalled on memory
+    pub some: PressureAverages,
+    /// All non-idle tasks stalled on memory at once
+    pub full: PressureAverages,
+}
+
+impl MemoryPressure {
+    /// Parse the `some` and `full` lines of a PSI file
+    pub fn parse(content: &str) -> Option<Self> {
+        let mut pressure = Self::default();
+        let mut seen_some = false;
+        for line in content.lines() {
+            let mut fields = line.split_whitespace();
+            let averages = match fields.next() {
+                Some("some") => {
+                    seen_some = true;
+                    &mut pressure.some
+                }
+                Some("full") => &mut pressure.full,
+                _ => continue,
+            };
+            for field in fields {
+                let Some((key, value)) = field.split_once('=') else {
+                    continue;
+                };
+                let value = match key {
+                    "avg10" | "avg60" | "avg300" => value.parse().ok()?,
+                    _ => continue,
+                };
+                match key {
+                    "avg10" => averages.avg10 = value,
+                    "avg60" => averages.avg60 = value,
+                    _ => averages.avg300 = value,
+                }
+            }
+        }
+        seen_some.then_some(pressure)
+    }
+}
+
+/// When builds are throttled
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PressureConfig {
+    /// Watch memory pressure at all
+    pub enabled: bool,
+    /// `some avg10` from which jobs are halved, and halved again at each
+    /// multiple
+    pub reduce_jobs_at: f64,
+    /// `full avg10` from which no build is launched
+    pub pause_at: f64,
+    /// `full avg10` below which paused builds resume
+    pub resume_below: f64,
+    /// Seconds between readings while paused
+    pub poll_interval: u64,
+    /// Seconds to wait for pressure to subside before building with a
+    /// single job anyway
+    pub max_pause: u64,
+}
+
+impl Default for PressureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            reduce_jobs_at: 20.0,
+            pause_at: 10.0,
+            resume_below: 2.0,
+            poll_interval: 2,
+            max_pause: 300,
+        }
+    }
+}
+
+/// Decides how many jobs a build may launch with
+#[derive(Debug, Clone)]
+pub struct PressureThrottle {
+    config: PressureConfig,
+    path: PathBuf,
+}
+
+impl Default for PressureThrottle {
+    fn default() -> Self {
+        Self::new(PressureConfig::default())
+    }
+}
+
+impl PressureThrottle {
+    pub fn new(config: PressureConfig) -> Self {
+        Self {
+            config,
+            path: PathBuf::from(MEMORY_PRESSURE_PATH),
+        }
+    }
+
+    /// Read pressure from `path` instead of the kernel's file
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Current memory pressure, if the kernel reports it
+    pub fn read(&self) -> Option<MemoryPressure> {
+        if !self.config.enabled {
+            return None;
+        }
+        MemoryPressure::parse(&std::fs::read_to_string(&self.path).ok()?)
+    }
+
+    /// Whether a build should wait before launching
+    pub fn should_pause(&self, pressure: &MemoryPressure) -> bool {
+        pressure.full.avg10 >= self.config.pause_at
+    }
+
+    /// Jobs a build may use under `pressure`, given its configured `jobs`
+    pub fn jobs(&self, pressure: &MemoryPressure, jobs: usize) -> usize {
+        let threshold = self.config.reduce_jobs_at;
+        if threshold <= 0.0 || pressure.some.avg10 < threshold {
+            return jobs;
+        }
+        let halvings = (pressure.some.avg10 / threshold).floor() as u32;
+        (jobs >> halvings.min(usize::BITS - 1)).max(1)
+    }
+
+    /// Wait until memory pressure allows a build to launch, then return
+    /// the jobs it may use
+    pub async fn admit(&self, jobs: usize) -> usize {
+        let Some(mut pressure) = self.read() else {
+            return jobs;
+        };
+        if self.should_pause(&pressure) {
+            info!(
+                "Memory pressure at {:.1}%, waiting before launching the next build",
+                pressure.full.avg10
+            );
+            let started = Instant::now();
+            let max_pause = Duration::from_secs(self.config.max_pause);
+            loop {
+                tokio::time::sleep(Duration::from_secs(self.config.poll_interval.max(1))).await;
+                match self.read() {
+                    Some(p) => pressure = p,
+                    None => return jobs,
+                }
+                if pressure.full.avg10 < self.config.resume_below {
+                    info!("Memory pressure subsided, resuming builds");
+                    break;
+                }
+                if started.elapsed() >= max_pause {
+                    warn!(
+                        "Memory pressure still at {:.1}% after {}s, building with one job",
+                        pressure.full.avg10, self.config.max_pause
+                    );
+                    return 1;
+                }
+            }
+        }
+
+        let throttled = self.jobs(&pressure, jobs);
+        if throttled < jobs {
+            info!(
+                "Memory pressure at {:.1}%, building with {} of {} jobs",
+                pressure.some.avg10, throttled, jobs
+            );
+        }
+        throttled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PSI: &str = "\
+some avg10=45.50 avg60=12.02 avg300=3.10 total=123456
+full avg10=0.00 avg60=0.00 avg300=0.00 total=0
+";
+
+    #[test]
+    fn test_parse_psi() {
+        let pressure = MemoryPressure::parse(PSI).unwrap();
+        assert_eq!(pressure.some.avg10, 45.5);
+        assert_eq!(pressure.some.avg300, 3.1);
+        assert_eq!(pressure.full, PressureAverages::default());
+        assert_eq!(MemoryPressure::parse(""), None);
+        assert_eq!(MemoryPressure::parse("some avg10=x"), None);
+    }
+
+    #[test]
+    fn test_jobs_halved_per_threshold() {
+        let throttle = PressureThrottle::default();
+        let at = |some: f64| MemoryPressure {
+            some: PressureAverages {
+                avg10: some,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(throttle.jobs(&at(5.0), 16), 16);
+        assert_eq!(throttle.jobs(&at(20.0), 16), 8);
+        assert_eq!(throttle.jobs(&at(45.5), 16), 4);
+        assert_eq!(throttle.jobs(&at(100.0), 16), 1);
+        assert!(!throttle.should_pause(&at(100.0)));
+    }
+
+    #[tokio::test]
+    async fn test_admit_reads_pressure_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory");
+        std::fs::write(&path, PSI).unwrap();
+        let throttle = PressureThrottle::default().with_path(&path);
+        assert_eq!(throttle.admit(8).await, 2);
+
+        let missing = PressureThrottle::default().with_path(dir.path().join("none"));
+        assert_eq!(missing.admit(8).await, 8);
+        let disabled = PressureThrottle::new(PressureConfig {
+            enabled: false,
+            ..Default::default()
+        })
+        .with_path(&path);
+        assert_eq!(disabled.admit(8).await, 8);
+    }
+}
@@ -0,0 +1,528 @@
+//! Filesystem-level transaction preview
+//!
+//! Computes what a transaction would do to the filesystem (files added,
+//! removed or replaced, protected config files that will need merging and
+//! services that will need a restart) without touching the live system.
+//! Used by `--pretend` so admins can audit changes before committing.
+
+use crate::config_protect::ConfigProtect;
+use crate::install_mask::InstallMask;
+use crate::{FileType, InstalledPackage, PackageId};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
+
+/// Directories that hold service definitions, relative to the install root
+const SERVICE_DIRS: &[&str] = &[
+    "/etc/buckos/services",
+    "/usr/lib/systemd/system",
+    "/lib/systemd/system",
+    "/etc/init.d",
+];
+
+/// Kind of change a package preview describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreviewAction {
+    /// New package
+    Install,
+    /// Newer version replaces an installed one
+    Upgrade,
+    /// Same version is reinstalled
+    Rebuild,
+    /// Package is removed
+    Remove,
+}
+
+/// Filesystem changes caused by a single package operation
+#[derive(Debug, Clone)]
+pub struct PackagePreview {
+    /// Package identifier
+    pub id: PackageId,
+    /// Version being installed (or removed)
+    pub version: semver::Version,
+    /// Kind of operation
+    pub action: PreviewAction,
+    /// Whether a file manifest was available for the new version
+    pub manifest_available: bool,
+    /// Files that do not exist yet
+    pub added: Vec<String>,
+    /// Files that will be deleted
+    pub removed: Vec<String>,
+    /// Files that will be overwritten
+    pub replaced: Vec<String>,
+    /// Files currently owned by another package (path, owner)
+    pub collisions: Vec<(String, String)>,
+    /// Files that will be skipped by INSTALL_MASK
+    pub masked: Vec<String>,
+}
+
+/// Filesystem-level effect of a whole transaction
+#[derive(Debug, Clone, Default)]
+pub struct TransactionPreview {
+    /// Per-package changes, in transaction order
+    pub packages: Vec<PackagePreview>,
+    /// Protected config files that will need merging (etc-update)
+    pub config_merges: Vec<String>,
+    /// Services whose definitions or files change and will need a restart
+    pub service_restarts: Vec<String>,
+}
+
+impl TransactionPreview {
+    /// Total number of files added
+    pub fn total_added(&self) -> usize {
+        self.packages.iter().map(|p| p.added.len()).sum()
+    }
+
+    /// Total number of files removed
+    pub fn total_removed(&self) -> usize {
+        self.packages.iter().map(|p| p.removed.len()).sum()
+    }
+
+    /// Total number of files replaced
+    pub fn total_replaced(&self) -> usize {
+        self.packages.iter().map(|p| p.replaced.len()).sum()
+    }
+
+    /// Total number of files skipped by INSTALL_MASK
+    pub fn total_masked(&self) -> usize {
+        self.packages.iter().map(|p| p.masked.len()).sum()
+    }
+
+    /// Packages for which no file manifest could be found
+    pub fn missing_manifests(&self) -> Vec<&PackageId> {
+        self.packages
+            .iter()
+            .filter(|p| !p.manifest_available)
+            .map(|p| &p.id)
+            .collect()
+    }
+}
+
+/// Builds a [`TransactionPreview`] from package operations
+pub struct PreviewBuilder {
+    root: std::path::PathBuf,
+    protect: ConfigProtect,
+    install_mask: InstallMask,
+    /// Root-relative path -> owning package and slot
+    owners: HashMap<String, (PackageId, String)>,
+    preview: TransactionPreview,
+    config_merges: BTreeSet<String>,
+    service_restarts: BTreeSet<String>,
+}
+
+impl PreviewBuilder {
+    /// Create a builder for the given root, using installed packages for ownership
+    pub fn new(root: &Path, installed: &[InstalledPackage]) -> Self {
+        let mut owners = HashMap::new();
+        for pkg in installed {
+            for file in pkg.files.iter().filter(|f| f.file_type != FileType::Masked) {
+                owners.insert(
+                    root_relative(root, &file.path),
+                    (pkg.id.clone(), pkg.slot.clone()),
+                );
+            }
+        }
+
+        Self {
+            root: root.to_path_buf(),
+            protect: ConfigProtect::default(),
+            install_mask: InstallMask::new(),
+            owners,
+            preview: TransactionPreview::default(),
+            config_merges: BTreeSet::new(),
+            service_restarts: BTreeSet::new(),
+        }
+    }
+
+    /// Use a custom config protection policy
+    pub fn with_protect(mut self, protect: ConfigProtect) -> Self {
+        self.protect = protect;
+        self
+    }
+
+    /// Account for files that INSTALL_MASK will skip
+    pub fn with_install_mask(mut self, install_mask: InstallMask) -> Self {
+        self.install_mask = install_mask;
+        self
+    }
+
+    /// Record an install, upgrade or rebuild
+    ///
+    /// `manifest` is the file list of the new version, if known. `old` is the
+    /// currently installed version being replaced, if any.
+    pub fn add_install(
+        &mut self,
+        id: &PackageId,
+        version: &semver::Version,
+        old: Option<&InstalledPackage>,
+        manifest: Option<&[String]>,
+    ) {
+        let action = match old {
+            Some(old) if old.version == *version => PreviewAction::Rebuild,
+            Some(_) => PreviewAction::Upgrade,
+            None => PreviewAction::Install,
+        };
+
+        let old_files: HashSet<String> = old
+            .map(|o| {
+                o.files
+                    .iter()
+                    .filter(|f| !matches!(f.file_type, FileType::Directory | FileType::Masked))
+                    .map(|f| root_relative(&self.root, &f.path))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut preview = PackagePreview {
+            id: id.clone(),
+            version: version.clone(),
+            action,
+            manifest_available: manifest.is_some(),
+            added: Vec::new(),
+            removed: Vec::new(),
+            replaced: Vec::new(),
+            collisions: Vec::new(),
+            masked: Vec::new(),
+        };
+
+        if let Some(manifest) = manifest {
+            let (masked, manifest): (Vec<&String>, Vec<&String>) = manifest
+                .iter()
+                .partition(|p| self.install_mask.is_masked(Path::new(p)));
+            preview.masked = masked.into_iter().cloned().collect();
+            let new_files: HashSet<&String> = manifest.iter().copied().collect();
+
+            for path in manifest {
+                let on_disk = self.root.join(path.trim_start_matches('/'));
+                if old_files.contains(path) || on_disk.exists() {
+                    preview.replaced.push(path.clone());
+                } else {
+                    preview.added.push(path.clone());
+                }
+
+                // Files of the package and slot being replaced are not
+                // collisions; those of anything else, however named, are
+                if let Some((owner, slot)) = self.owners.get(path) {
+                    if old.is_none_or(|o| o.id != *owner || o.slot != *slot) {
+                        preview.collisions.push((path.clone(), owner.to_string()));
+                    }
+                }
+
+                if on_disk.exists() && self.protect.is_protected(Path::new(path)) {
+                    self.config_merges.insert(path.clone());
+                }
+            }
+
+            preview.removed = old_files
+                .iter()
+                .filter(|p| !new_files.contains(p))
+                .cloned()
+                .collect();
+        }
+
+        // Running services shipped by a replaced package need a restart
+        if old.is_some() {
+            let candidates = old_files.iter().chain(manifest.unwrap_or_default().iter());
+            for path in candidates {
+                if let Some(service) = service_name(path) {
+                    self.service_restarts.insert(service);
+                }
+            }
+        }
+
+        preview.added.sort();
+        preview.removed.sort();
+        preview.replaced.sort();
+        preview.collisions.sort();
+        self.preview.packages.push(preview);
+    }
+
+    /// Record a removal
+    pub fn add_remove(&mut self, pkg: &InstalledPackage) {
+        let mut removed: Vec<String> = pkg
+            .files
+            .iter()
+            .filter(|f| !matches!(f.file_type, FileType::Directory | FileType::Masked))
+            .map(|f| root_relative(&self.root, &f.path))
+            .collect();
+        removed.sort();
+
+        self.preview.packages.push(PackagePreview {
+            id: pkg.id.clone(),
+            version: pkg.version.clone(),
+            action: PreviewAction::Remove,
+            manifest_available: true,
+            added: Vec::new(),
+            removed,
+            replaced: Vec::new(),
+            collisions: Vec::new(),
+            masked: Vec::new(),
+        });
+    }
+
+    /// Finish building the preview
+    pub fn build(mut self) -> TransactionPreview {
+        self.preview.config_merges = self.config_merges.into_iter().collect();
+        self.preview.service_restarts = self.service_restarts.into_iter().collect();
+        self.preview
+    }
+}
+
+/// Convert an installed file path (which includes ROOT) to a root-relative path
+fn root_relative(root: &Path, path: &str) -> String {
+    match Path::new(path).strip_prefix(root) {
+        Ok(rel) => format!("/{}", rel.to_string_lossy()),
+        Err(_) => path.to_string(),
+    }
+}
+
+/// Extract a service name if the path is a service definition
+fn service_name(path: &str) -> Option<String> {
+    let path = Path::new(path);
+    let parent = path.parent()?.to_str()?;
+    if !SERVICE_DIRS.contains(&parent) {
+        return None;
+    }
+    let stem = path.file_stem()?.to_string_lossy().to_string();
+    Some(stem)
+}
+
+/// Format a preview report for display
+pub fn format_preview_report(preview: &TransactionPreview, verbose: bool) -> String {
+    let mut report = String::new();
+
+    report.push_str(&format!(
+        "Filesystem changes: {} added, {} replaced, {} removed\n",
+        preview.total_added(),
+        preview.total_replaced(),
+        preview.total_removed()
+    ));
+
+    if preview.total_masked() > 0 {
+        report.push_str(&format!(
+            "Skipped by INSTALL_MASK: {} files\n",
+            preview.total_masked()
+        ));
+    }
+
+    if verbose {
+        for pkg in &preview.packages {
+            report.push_str(&format!("\n  {}-{}:\n", pkg.id, pkg.version));
+            if !pkg.manifest_available {
+                report.push_str("    (no file manifest available)\n");
+                continue;
+            }
+            for path in &pkg.added {
+                report.push_str(&format!("    + {}\n", path));
+            }
+            for path in &pkg.replaced {
+                report.push_str(&format!("    ~ {}\n", path));
+            }
+            for path in &pkg.removed {
+                report.push_str(&format!("    - {}\n", path));
+            }
+        }
+    }
+
+    let collisions: Vec<_> = preview
+        .packages
+        .iter()
+        .flat_map(|p| p.collisions.iter().map(move |c| (&p.id, c)))
+        .collect();
+    if !collisions.is_empty() {
+        report.push_str(&format!("\nFile collisions: {}\n", collisions.len()));
+        for (id, (path, owner)) in collisions {
+            report.push_str(&format!("  {} ({} owned by {})\n", path, id, owner));
+        }
+    }
+
+    if !preview.config_merges.is_empty() {
+        report.push_str(&format!(
+            "\nConfiguration files needing merge: {}\n",
+            preview.config_merges.len()
+        ));
+        for path in &preview.config_merges {
+            report.push_str(&format!("  {}\n", path));
+        }
+    }
+
+    if !preview.service_restarts.is_empty() {
+        report.push_str(&format!(
+            "\nServices needing restart: {}\n",
+            preview.service_restarts.join(", ")
+        ));
+    }
+
+    let missing = preview.missing_manifests();
+    if !missing.is_empty() {
+        report.push_str(&format!(
+            "\nNo file manifest for {} package(s); their file changes are not shown\n",
+            missing.len()
+        ));
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InstalledFile;
+
+    fn installed(name: &str, version: semver::Version, files: &[&str]) -> InstalledPackage {
+        InstalledPackage {
+            id: PackageId::new("app-misc", name),
+            name: name.to_string(),
+            version,
+            slot: "0".to_string(),
+            installed_at: chrono::Utc::now(),
+            use_flags: HashSet::new(),
+            files: files
+                .iter()
+                .map(|p| InstalledFile {
+                    path: p.to_string(),
+                    file_type: FileType::Regular,
+                    mode: 0o644,
+                    size: 0,
+                    blake3_hash: None,
+                    mtime: 0,
+                })
+                .collect(),
+            size: 0,
+            build_time: false,
+            explicit: true,
+        }
+    }
+
+    #[test]
+    fn test_upgrade_diff() {
+        let old = installed(
+            "foo",
+            semver::Version::new(1, 0, 0),
+            &[
+                "/usr/bin/foo",
+                "/usr/share/foo/old.dat",
+                "/etc/buckos/services/foo.toml",
+            ],
+        );
+        let mut builder = PreviewBuilder::new(Path::new("/"), std::slice::from_ref(&old));
+        let manifest = vec![
+            "/usr/bin/foo".to_string(),
+            "/usr/share/foo/new.dat".to_string(),
+            "/etc/buckos/services/foo.toml".to_string(),
+        ];
+        builder.add_install(
+            &old.id,
+            &semver::Version::new(2, 0, 0),
+            Some(&old),
+            Some(&manifest),
+        );
+        let preview = builder.build();
+
+        let pkg = &preview.packages[0];
+        assert_eq!(pkg.action, PreviewAction::Upgrade);
+        assert_eq!(pkg.added, vec!["/usr/share/foo/new.dat".to_string()]);
+        assert_eq!(pkg.removed, vec!["/usr/share/foo/old.dat".to_string()]);
+        assert!(pkg.replaced.contains(&"/usr/bin/foo".to_string()));
+        assert!(pkg.collisions.is_empty());
+        assert_eq!(preview.service_restarts, vec!["foo".to_string()]);
+    }
+
+    #[test]
+    fn test_collision_and_missing_manifest() {
+        let other = installed("bar", semver::Version::new(1, 0, 0), &["/usr/bin/shared"]);
+        let mut builder = PreviewBuilder::new(Path::new("/"), &[other]);
+        let id = PackageId::new("app-misc", "baz");
+        let manifest = vec!["/usr/bin/shared".to_string()];
+        builder.add_install(&id, &semver::Version::new(1, 0, 0), None, Some(&manifest));
+        builder.add_install(
+            &PackageId::new("app-misc", "qux"),
+            &semver::Version::new(1, 0, 0),
+            None,
+            None,
+        );
+        let preview = builder.build();
+
+        assert_eq!(
+            preview.packages[0].collisions,
+            vec![("/usr/bin/shared".to_string(), "app-misc/bar".to_string())]
+        );
+        assert_eq!(preview.missing_manifests().len(), 1);
+    }
+
+    #[test]
+    fn test_collision_same_name_other_category() {
+        let other = installed("foo", semver::Version::new(1, 0, 0), &["/usr/bin/foo"]);
+        let mut builder = PreviewBuilder::new(Path::new("/"), std::slice::from_ref(&other));
+        let manifest = vec!["/usr/bin/foo".to_string()];
+        builder.add_install(
+            &PackageId::new("dev-util", "foo"),
+            &semver::Version::new(2, 0, 0),
+            None,
+            Some(&manifest),
+        );
+        let preview = builder.build();
+
+        assert_eq!(preview.packages[0].action, PreviewAction::Install);
+        assert_eq!(
+            preview.packages[0].collisions,
+            vec![("/usr/bin/foo".to_string(), "app-misc/foo".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_install_mask() {
+        let mut mask = InstallMask::new();
+        mask.add("/usr/share/doc");
+        let mut builder =
+            PreviewBuilder::new(Path::new("/nonexistent"), &[]).with_install_mask(mask);
+        let manifest = vec![
+            "/usr/bin/foo".to_string(),
+            "/usr/share/doc/foo/README".to_string(),
+        ];
+        builder.add_install(
+            &PackageId::new("app-misc", "foo"),
+            &semver::Version::new(1, 0, 0),
+            None,
+            Some(&manifest),
+        );
+        let preview = builder.build();
+
+        assert_eq!(preview.packages[0].added, vec!["/usr/bin/foo".to_string()]);
+        assert_eq!(
+            preview.packages[0].masked,
+            vec!["/usr/share/doc/foo/README".to_string()]
+        );
+        assert_eq!(preview.total_masked(), 1);
+    }
+
+    #[test]
+    fn test_remove() {
+        let old = installed(
+            "foo",
+            semver::Version::new(1, 0, 0),
+            &["/tmp/root/usr/bin/foo", "/tmp/root/etc/foo.conf"],
+        );
+        let mut builder = PreviewBuilder::new(Path::new("/tmp/root"), std::slice::from_ref(&old));
+        builder.add_remove(&old);
+        let preview = builder.build();
+
+        assert_eq!(preview.packages[0].action, PreviewAction::Remove);
+        assert_eq!(
+            preview.packages[0].removed,
+            vec!["/etc/foo.conf".to_string(), "/usr/bin/foo".to_string()]
+        );
+        assert_eq!(preview.total_removed(), 2);
+    }
+
+    #[test]
+    fn test_root_relative() {
+        assert_eq!(
+            root_relative(Path::new("/mnt/sysroot"), "/mnt/sysroot/usr/bin/foo"),
+            "/usr/bin/foo"
+        );
+        assert_eq!(
+            root_relative(Path::new("/"), "/usr/bin/foo"),
+            "/usr/bin/foo"
+        );
+    }
+}
@@ -0,0 +1,503 @@
+//! QA checks on staged install images
+//!
+//! Before a package is merged its image is scanned for problems that are
+//! cheap to catch here and expensive to find later: world-writable files,
+//! undeclared setuid/setgid binaries, shared libraries without a SONAME,
+//! RPATHs pointing into build directories, text relocations and files
+//! installed outside the paths a package may own. Issues are logged, or
+//! block the merge with FEATURES=strict-qa.
+
+use crate::config::Config;
+use crate::{PackageId, Result};
+use serde::{Deserialize, Serialize};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+/// Kind of QA issue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum QaCheck {
+    WorldWritable,
+    UndeclaredSetuid,
+    MissingSoname,
+    BuildDirRpath,
+    TextRelocation,
+    ForbiddenPath,
+}
+
+impl QaCheck {
+    pub fn name(&self) -> &'static str {
+        match self {
+            QaCheck::WorldWritable => "world-writable",
+            QaCheck::UndeclaredSetuid => "undeclared-setuid",
+            QaCheck::MissingSoname => "missing-soname",
+            QaCheck::BuildDirRpath => "build-dir-rpath",
+            QaCheck::TextRelocation => "textrel",
+            QaCheck::ForbiddenPath => "forbidden-path",
+        }
+    }
+}
+
+/// A problem found in an install image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QaIssue {
+    pub check: QaCheck,
+    /// Path relative to the install root, e.g. `/usr/bin/foo`
+    pub path: String,
+    pub detail: String,
+}
+
+impl std::fmt::Display for QaIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}: {}", self.check.name(), self.path, self.detail)
+    }
+}
+
+/// QA settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QaConfig {
+    /// Setuid/setgid files packages may install, as `category/name:/path`
+    #[serde(default)]
+    pub declared_setuid: Vec<String>,
+    /// Paths no package may install into
+    #[serde(default = "default_forbidden_paths")]
+    pub forbidden_paths: Vec<String>,
+}
+
+impl Default for QaConfig {
+    fn default() -> Self {
+        Self {
+            declared_setuid: Vec::new(),
+            forbidden_paths: default_forbidden_paths(),
+        }
+    }
+}
+
+fn default_forbidden_paths() -> Vec<String> {
+    [
+        "/usr/local",
+        "/home",
+        "/root",
+        "/tmp",
+        "/var/tmp",
+        "/run",
+        "/dev",
+        "/proc",
+        "/sys",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+/// QA checks configured for the system
+#[derive(Debug, Clone)]
+pub struct QaPolicy {
+    config: QaConfig,
+    /// Directories an installed RPATH must never point into
+    build_dirs: Vec<String>,
+    strict: bool,
+}
+
+impl QaPolicy {
+    /// Checks with default settings, in warn mode
+    pub fn new() -> Self {
+        Self {
+            config: QaConfig::default(),
+            build_dirs: vec!["/tmp".to_string(), "/var/tmp".to_string()],
+            strict: false,
+        }
+    }
+
+    /// Checks configured for the system
+    pub fn from_config(config: &Config) -> Self {
+        let mut policy = Self::new().with_strict(config.features.contains("strict-qa"));
+        policy.config = config.qa.clone();
+        policy
+            .build_dirs
+            .push(config.build_dir().to_string_lossy().into_owned());
+        policy
+    }
+
+    /// Block merging on any issue instead of warning
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Whether issues block the merge
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Scan an install image for a package
+    pub fn check_image(&self, image: &Path, package: &PackageId) -> Result<Vec<QaIssue>> {
+        let mut issues = Vec::new();
+
+        for entry in walkdir::WalkDir::new(image) {
+            let entry = entry?;
+            let Ok(relative) = entry.path().strip_prefix(image) else {
+                continue;
+            };
+            if relative.as_os_str().is_empty() {
+                continue;
+            }
+            let path = format!("/{}", relative.to_string_lossy());
+            let metadata = entry.path().symlink_metadata()?;
+            if metadata.file_type().is_symlink() {
+                continue;
+            }
+            let mode = metadata.permissions().mode();
+            let issue = |check, detail: String| QaIssue {
+                check,
+                path: path.clone(),
+                detail,
+            };
+
+            if let Some(forbidden) = self
+                .config
+                .forbidden_paths
+                .iter()
+                .find(|f| path_within(&path, f))
+            {
+                // Report the forbidden directory itself and each file below it
+                if path == *forbidden || metadata.is_file() {
+                    issues.push(issue(
+                        QaCheck::ForbiddenPath,
+                        format!("installed under {}", forbidden),
+                    ));
+                }
+            }
+
+            // Sticky world-writable directories (like /tmp) are fine
+            if mode & 0o002 != 0 && !(metadata.is_dir() && mode & 0o1000 != 0) {
+                issues.push(issue(
+                    QaCheck::WorldWritable,
+                    format!("mode {:o}", mode & 0o7777),
+                ));
+            }
+
+            if !metadata.is_file() {
+                continue;
+            }
+
+            if mode & 0o6000 != 0 && !self.is_declared_setuid(package, &path) {
+                issues.push(issue(
+                    QaCheck::UndeclaredSetuid,
+                    format!("mode {:o} not listed in qa.declared_setuid", mode & 0o7777),
+                ));
+            }
+
+            let Some(elf) = read_elf(entry.path()) else {
+                continue;
+            };
+            if elf.textrel {
+                issues.push(issue(
+                    QaCheck::TextRelocation,
+                    "contains text relocations".to_string(),
+                ));
+            }
+            for rpath in &elf.rpaths {
+                if let Some(bad) = rpath.split(':').find(|dir| self.is_build_dir(dir)) {
+                    issues.push(issue(
+                        QaCheck::BuildDirRpath,
+                        format!("RPATH entry '{}'", bad),
+                    ));
+                }
+            }
+            if elf.shared && elf.soname.is_none() && is_library_path(&path) {
+                issues.push(issue(
+                    QaCheck::MissingSoname,
+                    "shared library has no SONAME".to_string(),
+                ));
+            }
+        }
+
+        Ok(issues)
+    }
+
+    fn is_declared_setuid(&self, package: &PackageId, path: &str) -> bool {
+        let package = package.full_name();
+        self.config.declared_setuid.iter().any(|d| {
+            d.split_once(':')
+                .is_some_and(|(pkg, file)| pkg == package && file == path)
+        })
+    }
+
+    fn is_build_dir(&self, dir: &str) -> bool {
+        // Relative entries resolve against the working directory at run time
+        if dir.is_empty() || !(dir.starts_with('/') || dir.starts_with("$ORIGIN")) {
+            return true;
+        }
+        dir.contains("/buck-out/") || self.build_dirs.iter().any(|b| path_within(dir, b))
+    }
+}
+
+impl Default for QaPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn path_within(path: &str, dir: &str) -> bool {
+    let dir = dir.trim_end_matches('/');
+    path == dir
+        || path
+            .strip_prefix(dir)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+fn is_library_path(path: &str) -> bool {
+    let Some(name) = path.rsplit('/').next() else {
+        return false;
+    };
+    name.starts_with("lib") && (name.ends_with(".so") || name.contains(".so."))
+}
+
+/// Dynamic section facts QA cares about
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ElfInfo {
+    /// ET_DYN with a dynamic section (shared library or PIE)
+    shared: bool,
+    soname: Option<String>,
+    /// DT_RPATH and DT_RUNPATH values
+    rpaths: Vec<String>,
+    textrel: bool,
+}
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const DT_NULL: u64 = 0;
+const DT_STRTAB: u64 = 5;
+const DT_SONAME: u64 = 14;
+const DT_RPATH: u64 = 15;
+const DT_TEXTREL: u64 = 22;
+const DT_RUNPATH: u64 = 29;
+const DT_FLAGS: u64 = 30;
+const DF_TEXTREL: u64 = 0x4;
+
+/// Read the dynamic section of an ELF file, or None if it is not ELF
+fn read_elf(path: &Path) -> Option<ElfInfo> {
+    use std::io::Read;
+
+    let mut magic = [0u8; 4];
+    std::fs::File::open(path)
+        .ok()?
+        .read_exact(&mut magic)
+        .ok()?;
+    if magic != *b"\x7fELF" {
+        return None;
+    }
+    parse_elf(&std::fs::read(path).ok()?)
+}
+
+fn parse_elf(data: &[u8]) -> Option<ElfInfo> {
+    let is64 = match data.get(4)? {
+        1 => false,
+        2 => true,
+        _ => return None,
+    };
+    let le = match data.get(5)? {
+        1 => true,
+        2 => false,
+        _ => return None,
+    };
+    let read = |offset: usize, size: usize| -> Option<u64> {
+        let bytes = data.get(offset..offset.checked_add(size)?)?;
+        let mut value = 0u64;
+        for i in 0..size {
+            let byte = if le { bytes[size - 1 - i] } else { bytes[i] };
+            value = (value << 8) | byte as u64;
+        }
+        Some(value)
+    };
+    let word = if is64 { 8 } else { 4 };
+
+    let e_type = read(16, 2)?;
+    let (phoff, phentsize, phnum) = if is64 {
+        (read(32, 8)?, read(54, 2)?, read(56, 2)?)
+    } else {
+        (read(28, 4)?, read(42, 2)?, read(44, 2)?)
+    };
+
+    // (vaddr, offset, filesz) of each PT_LOAD, and the PT_DYNAMIC range
+    let mut loads = Vec::new();
+    let mut dynamic = None;
+    for i in 0..phnum as usize {
+        let ph = phoff as usize + i * phentsize as usize;
+        let p_type = read(ph, 4)? as u32;
+        let (offset, vaddr, filesz) = if is64 {
+            (read(ph + 8, 8)?, read(ph + 16, 8)?, read(ph + 32, 8)?)
+        } else {
+            (read(ph + 4, 4)?, read(ph + 8, 4)?, read(ph + 16, 4)?)
+        };
+        match p_type {
+            PT_LOAD => loads.push((vaddr, offset, filesz)),
+            PT_DYNAMIC => dynamic = Some((offset, filesz)),
+            _ => {}
+        }
+    }
+
+    let mut info = ElfInfo::default();
+    let Some((dyn_offset, dyn_size)) = dynamic else {
+        return Some(info);
+    };
+    info.shared = e_type == 3;
+
+    let mut strtab = None;
+    let mut soname = None;
+    let mut rpaths = Vec::new();
+    let mut entry = dyn_offset as usize;
+    while entry + 2 * word <= (dyn_offset + dyn_size) as usize {
+        let tag = read(entry, word)?;
+        let value = read(entry + word, word)?;
+        match tag {
+            DT_NULL => break,
+            DT_STRTAB => strtab = Some(value),
+            DT_SONAME => soname = Some(value),
+            DT_RPATH | DT_RUNPATH => rpaths.push(value),
+            DT_TEXTREL => info.textrel = true,
+            DT_FLAGS if value & DF_TEXTREL != 0 => info.textrel = true,
+            _ => {}
+        }
+        entry += 2 * word;
+    }
+
+    // DT_STRTAB is a virtual address; map it through the load segments
+    let strtab = strtab.and_then(|addr| {
+        loads
+            .iter()
+            .find(|(vaddr, _, filesz)| addr >= *vaddr && addr < vaddr + filesz)
+            .map(|(vaddr, offset, _)| (addr - vaddr + offset) as usize)
+    });
+    let string = |index: u64| -> Option<String> {
+        let start = strtab? + index as usize;
+        let bytes = data.get(start..)?;
+        let end = bytes.iter().position(|&b| b == 0)?;
+        Some(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    };
+    info.soname = soname.and_then(string);
+    info.rpaths = rpaths.into_iter().filter_map(string).collect();
+    Some(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal little-endian ELF64 shared object with a dynamic section
+    fn elf(soname: Option<&str>, rpath: Option<&str>, textrel: bool) -> Vec<u8> {
+        let mut strtab = vec![0u8];
+        let mut add = |s: &str| {
+            let index = strtab.len() as u64;
+            strtab.extend_from_slice(s.as_bytes());
+            strtab.push(0);
+            index
+        };
+        let mut dynamic = vec![(DT_STRTAB, 0u64)];
+        if let Some(soname) = soname {
+            dynamic.push((DT_SONAME, add(soname)));
+        }
+        if let Some(rpath) = rpath {
+            dynamic.push((DT_RUNPATH, add(rpath)));
+        }
+        if textrel {
+            dynamic.push((DT_TEXTREL, 0));
+        }
+        dynamic.push((DT_NULL, 0));
+
+        let phoff = 64u64;
+        let dyn_offset = phoff + 2 * 56;
+        let dyn_size = dynamic.len() as u64 * 16;
+        let str_offset = dyn_offset + dyn_size;
+        dynamic[0].1 = str_offset;
+        let total = str_offset + strtab.len() as u64;
+
+        let mut data = vec![0u8; 64];
+        data[..6].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1]);
+        data[16..18].copy_from_slice(&3u16.to_le_bytes());
+        data[32..40].copy_from_slice(&phoff.to_le_bytes());
+        data[54..56].copy_from_slice(&56u16.to_le_bytes());
+        data[56..58].copy_from_slice(&2u16.to_le_bytes());
+        for (p_type, offset, size) in [(PT_LOAD, 0, total), (PT_DYNAMIC, dyn_offset, dyn_size)] {
+            let mut ph = vec![0u8; 56];
+            ph[0..4].copy_from_slice(&p_type.to_le_bytes());
+            ph[8..16].copy_from_slice(&offset.to_le_bytes());
+            ph[16..24].copy_from_slice(&offset.to_le_bytes());
+            ph[32..40].copy_from_slice(&size.to_le_bytes());
+            data.extend(ph);
+        }
+        for (tag, value) in dynamic {
+            data.extend(tag.to_le_bytes());
+            data.extend(value.to_le_bytes());
+        }
+        data.extend(strtab);
+        data
+    }
+
+    #[test]
+    fn test_parse_elf() {
+        let info = parse_elf(&elf(Some("libfoo.so.1"), Some("$ORIGIN:/tmp/build"), true)).unwrap();
+        assert_eq!(
+            info,
+            ElfInfo {
+                shared: true,
+                soname: Some("libfoo.so.1".to_string()),
+                rpaths: vec!["$ORIGIN:/tmp/build".to_string()],
+                textrel: true,
+            }
+        );
+        assert!(parse_elf(b"\x7fELF").is_none());
+    }
+
+    #[test]
+    fn test_check_image() {
+        let image = tempfile::tempdir().unwrap();
+        let root = image.path();
+        let write = |path: &str, data: &[u8], mode: u32| {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, data).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        };
+
+        write(
+            "usr/lib/libgood.so.1",
+            &elf(Some("libgood.so.1"), None, false),
+            0o755,
+        );
+        write(
+            "usr/lib/libbad.so",
+            &elf(None, Some("/var/tmp/build/lib"), true),
+            0o755,
+        );
+        write("usr/bin/passwd", b"#!/bin/sh\n", 0o4755);
+        write("usr/bin/su", b"#!/bin/sh\n", 0o4755);
+        write("etc/foo.conf", b"x", 0o666);
+        write("usr/local/bin/foo", b"x", 0o755);
+
+        let mut policy = QaPolicy::new();
+        policy.config.declared_setuid = vec!["app-misc/foo:/usr/bin/passwd".to_string()];
+        let id = PackageId::new("app-misc", "foo");
+        let mut found: Vec<(QaCheck, String)> = policy
+            .check_image(root, &id)
+            .unwrap()
+            .into_iter()
+            .map(|i| (i.check, i.path))
+            .collect();
+        found.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.name().cmp(b.0.name())));
+
+        assert_eq!(
+            found,
+            vec![
+                (QaCheck::WorldWritable, "/etc/foo.conf".to_string()),
+                (QaCheck::UndeclaredSetuid, "/usr/bin/su".to_string()),
+                (QaCheck::BuildDirRpath, "/usr/lib/libbad.so".to_string()),
+                (QaCheck::MissingSoname, "/usr/lib/libbad.so".to_string()),
+                (QaCheck::TextRelocation, "/usr/lib/libbad.so".to_string()),
+                (QaCheck::ForbiddenPath, "/usr/local".to_string()),
+                (QaCheck::ForbiddenPath, "/usr/local/bin/foo".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! Commands to run again after an interrupted transaction
+//!
+//! A transaction stopped by Ctrl-C or SIGTERM rolls back like any failed
+//! one, and the command that started it is saved for `buckos resume`.
+//! Packages built before the interruption are in Buck's cache, so running
+//! the command again does not build them a second time.
+
+use crate::{InstallOptions, RemoveOptions, Result, UpdateOptions};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// Name of the resume state in the cache directory
+pub const RESUME_FILE: &str = "transaction_state.json";
+
+/// The command an interrupted transaction was run for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum ResumeCommand {
+    Install {
+        packages: Vec<String>,
+        opts: InstallOptions,
+    },
+    Update {
+        /// Packages to update; every installed package when unset
+        packages: Option<Vec<String>>,
+        opts: UpdateOptions,
+    },
+    Remove {
+        packages: Vec<String>,
+        opts: RemoveOptions,
+    },
+}
+
+impl fmt::Display for ResumeCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResumeCommand::Install { packages, .. } => write!(f, "install {}", packages.join(" ")),
+            ResumeCommand::Update { packages: None, .. } => write!(f, "update"),
+            ResumeCommand::Update {
+                packages: Some(packages),
+                ..
+            } => write!(f, "update {}", packages.join(" ")),
+            ResumeCommand::Remove { packages, .. } => write!(f, "remove {}", packages.join(" ")),
+        }
+    }
+}
+
+/// An interrupted transaction, saved for `buckos resume`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResumeState {
+    pub command: ResumeCommand,
+    /// Signal that stopped the transaction
+    pub signal: i32,
+    pub interrupted_at: DateTime<Utc>,
+}
+
+impl ResumeState {
+    pub fn new(command: ResumeCommand, signal: i32) -> Self {
+        Self {
+            command,
+            signal,
+            interrupted_at: Utc::now(),
+        }
+    }
+
+    /// The saved state, if a transaction was interrupted
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Save atomically, so a second interruption can't leave half a file
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Forget the saved state once it was resumed
+    pub fn clear(path: &Path) -> Result<()> {
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resume_state_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache").join(RESUME_FILE);
+        assert_eq!(ResumeState::load(&path).unwrap(), None);
+
+        let state = ResumeState::new(
+            ResumeCommand::Install {
+                packages: vec!["app-misc/hello".to_string()],
+                opts: InstallOptions {
+                    oneshot: true,
+                    ..Default::default()
+                },
+            },
+            libc::SIGTERM,
+        );
+        state.save(&path).unwrap();
+        assert_eq!(ResumeState::load(&path).unwrap(), Some(state.clone()));
+        assert_eq!(state.command.to_string(), "install app-misc/hello");
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.contains(r#""command": "install""#), "{}", content);
+
+        // Every option comes back, not just the ones the summary shows
+        let update = ResumeState::new(
+            ResumeCommand::Update {
+                packages: None,
+                opts: UpdateOptions {
+                    deep: true,
+                    newuse: true,
+                    live: true,
+                    ..Default::default()
+                },
+            },
+            libc::SIGINT,
+        );
+        update.save(&path).unwrap();
+        assert_eq!(ResumeState::load(&path).unwrap(), Some(update));
+
+        ResumeState::clear(&path).unwrap();
+        ResumeState::clear(&path).unwrap();
+        assert_eq!(ResumeState::load(&path).unwrap(), None);
+    }
+}
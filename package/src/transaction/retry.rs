@@ -0,0 +1,190 @@
+//! Retries of builds that failed for transient reasons
+//!
+//! A build killed for lack of memory, or one whose download hit a network
+//! hiccup, often succeeds when simply run again. Retry policies say how
+//! many times such builds are retried and how each retry is made more
+//! likely to succeed: with fewer jobs, or without sandbox network access.
+//! Every attempt is recorded, so flaky packages show up in
+//! `buckos log --flaky`.
+
+use crate::buck::BuckConfigOptions;
+use crate::diagnostics::{BuildFailure, FailureKind};
+use crate::{BuildOptions, PackageId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Transient failure class a policy retries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RetryOn {
+    /// A download during the build failed
+    Download,
+    /// The compiler was killed, likely out of memory
+    OutOfMemory,
+}
+
+impl RetryOn {
+    fn matches(&self, failure: &BuildFailure) -> bool {
+        matches!(
+            (self, &failure.kind),
+            (RetryOn::Download, FailureKind::Download)
+                | (RetryOn::OutOfMemory, FailureKind::OutOfMemory)
+        )
+    }
+}
+
+/// How a package's failed builds are retried
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Attempts after the first; 0 disables retries
+    pub retries: u32,
+    /// Failure classes retried
+    pub on: Vec<RetryOn>,
+    /// Halve the build jobs on each retry
+    pub reduce_jobs: bool,
+    /// Retry with the build sandbox's network access disabled
+    pub disable_network: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 0,
+            on: vec![RetryOn::Download, RetryOn::OutOfMemory],
+            reduce_jobs: true,
+            disable_network: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether a build that failed on its `attempt`th try (from 1) is run
+    /// again
+    pub fn should_retry(&self, attempt: u32, failure: Option<&BuildFailure>) -> bool {
+        attempt <= self.retries
+            && failure.is_some_and(|f| f.is_transient() && self.on.iter().any(|on| on.matches(f)))
+    }
+
+    /// Jobs of the `attempt`th try, given those of the first
+    pub fn jobs(&self, attempt: u32, jobs: usize) -> usize {
+        if self.reduce_jobs {
+            (jobs >> attempt.saturating_sub(1).min(usize::BITS - 1)).max(1)
+        } else {
+            jobs
+        }
+    }
+
+    /// Whether the `attempt`th try may use the network
+    pub fn network(&self, attempt: u32) -> bool {
+        !(self.disable_network && attempt > 1)
+    }
+
+    /// Adjust build options for the `attempt`th try
+    pub fn apply(&self, attempt: u32, jobs: usize, opts: &mut BuildOptions) {
+        opts.jobs = Some(self.jobs(attempt, jobs));
+        if !self.network(attempt) {
+            opts.config_options
+                .get_or_insert_with(BuckConfigOptions::default)
+                .overrides
+                .insert("sandbox.network".to_string(), "false".to_string());
+        }
+    }
+}
+
+/// Global retry policy and per-package overrides
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Policy of packages without one of their own
+    #[serde(flatten)]
+    pub default: RetryPolicy,
+    /// Policies by `category/name` or name
+    pub packages: HashMap<String, RetryPolicy>,
+}
+
+impl RetryConfig {
+    /// Policy a package is built with
+    pub fn policy_for(&self, package: &PackageId) -> &RetryPolicy {
+        self.packages
+            .get(&package.full_name())
+            .or_else(|| self.packages.get(&package.name))
+            .unwrap_or(&self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure(log: &str) -> Option<BuildFailure> {
+        BuildFailure::classify(log, &[])
+    }
+
+    #[test]
+    fn test_retries_only_transient_failures() {
+        let policy = RetryPolicy {
+            retries: 2,
+            on: vec![RetryOn::OutOfMemory],
+            ..Default::default()
+        };
+        let oom = failure("g++: fatal error: Killed signal terminated program cc1plus");
+        let download = failure("curl: (6) Could not resolve host: example.org");
+        let header = failure("a.c:1:10: fatal error: z.h: No such file or directory");
+
+        assert!(policy.should_retry(1, oom.as_ref()));
+        assert!(policy.should_retry(2, oom.as_ref()));
+        assert!(!policy.should_retry(3, oom.as_ref()));
+        assert!(!policy.should_retry(1, download.as_ref()));
+        assert!(!policy.should_retry(1, header.as_ref()));
+        assert!(!policy.should_retry(1, None));
+    }
+
+    #[test]
+    fn test_retry_adjusts_jobs_and_network() {
+        let policy = RetryPolicy {
+            retries: 3,
+            disable_network: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            (1..=4).map(|a| policy.jobs(a, 8)).collect::<Vec<_>>(),
+            vec![8, 4, 2, 1]
+        );
+        assert_eq!(policy.jobs(40, 8), 1);
+
+        let mut opts = BuildOptions::default();
+        policy.apply(1, 8, &mut opts);
+        assert!(opts.config_options.is_none());
+        policy.apply(2, 8, &mut opts);
+        assert_eq!(opts.jobs, Some(4));
+        assert_eq!(
+            opts.config_options.unwrap().overrides["sandbox.network"],
+            "false"
+        );
+    }
+
+    #[test]
+    fn test_package_policy_overrides_default() {
+        let config: RetryConfig = toml::from_str(
+            r#"
+            retries = 1
+
+            [packages."dev-lang/rust"]
+            retries = 3
+            on = ["out-of-memory"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.default.retries, 1);
+        let rust = config.policy_for(&PackageId::new("dev-lang", "rust"));
+        assert_eq!(rust.retries, 3);
+        assert_eq!(rust.on, vec![RetryOn::OutOfMemory]);
+        assert_eq!(
+            config
+                .policy_for(&PackageId::new("dev-libs", "zlib"))
+                .retries,
+            1
+        );
+    }
+}
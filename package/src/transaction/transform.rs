@@ -0,0 +1,581 @@
+//! Merge-phase file transforms
+//!
+//! Applied to each file as it is merged into the root:
+//!
+//! - ELF executables and shared libraries are stripped. Debug info is split
+//!   into `/usr/lib/debug/<path>.debug` first, linked back with
+//!   `.gnu_debuglink`, and indexed under `/usr/lib/debug/.build-id/xx/yyyy.debug`
+//!   so gdb and debuginfod clients can find it.
+//! - Man and info pages are compressed.
+//!
+//! Stripping is skipped with FEATURES=nostrip or for packages with
+//! RESTRICT="strip". Pages are gzipped unless `doc_compression` says
+//! otherwise. Split debug info is dropped if `/usr/lib/debug` is covered
+//! by INSTALL_MASK.
+
+use super::merge::{FileMerge, StagedFile};
+use crate::features::Feature;
+use crate::install_mask::InstallMask;
+use crate::{Config, FileType, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::warn;
+
+/// Directory holding split debug info, relative to the root
+pub const DEBUG_DIR: &str = "usr/lib/debug";
+
+/// Directories whose contents are compressed, relative to the root
+const DOC_COMPRESS_DIRS: &[&str] = &["usr/share/man", "usr/share/info"];
+
+/// Files in compressed directories that must stay uncompressed
+const DOC_COMPRESS_SKIP: &[&str] = &["dir"];
+
+/// Extensions of files that are already compressed
+const COMPRESSED_EXTENSIONS: &[&str] = &["gz", "bz2", "xz", "zst", "lzma", "Z"];
+
+/// Compression used for man and info pages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DocCompression {
+    /// Install pages uncompressed
+    None,
+    /// gzip (`.gz`)
+    #[default]
+    Gzip,
+    /// xz (`.xz`)
+    Xz,
+    /// zstd (`.zst`)
+    Zstd,
+}
+
+impl DocCompression {
+    /// File extension added to compressed pages
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            DocCompression::None => None,
+            DocCompression::Gzip => Some("gz"),
+            DocCompression::Xz => Some("xz"),
+            DocCompression::Zstd => Some("zst"),
+        }
+    }
+
+    fn compress(&self, data: &[u8], out: std::fs::File) -> Result<()> {
+        match self {
+            DocCompression::None => {}
+            DocCompression::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(out, flate2::Compression::best());
+                encoder.write_all(data)?;
+                encoder.finish()?;
+            }
+            DocCompression::Xz => {
+                let mut encoder = xz2::write::XzEncoder::new(out, 9);
+                encoder.write_all(data)?;
+                encoder.finish()?;
+            }
+            DocCompression::Zstd => {
+                let mut encoder = zstd::stream::write::Encoder::new(out, 19)?;
+                encoder.write_all(data)?;
+                encoder.finish()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A file created by a transform in addition to the merged file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtraFile {
+    /// Absolute path (including root)
+    pub path: PathBuf,
+    /// Regular file or symlink
+    pub file_type: FileType,
+}
+
+/// Result of transforming a merged file
+#[derive(Debug, Clone)]
+pub struct TransformOutcome {
+    /// Final location of the merged file (renamed if compressed)
+    pub path: PathBuf,
+    /// Files created alongside it (split debug info)
+    pub extra: Vec<ExtraFile>,
+}
+
+/// Merge-phase transforms for one package
+#[derive(Debug, Clone)]
+pub struct MergeTransforms {
+    strip: bool,
+    compression: DocCompression,
+}
+
+impl MergeTransforms {
+    /// Transforms that leave files untouched
+    pub fn new() -> Self {
+        Self {
+            strip: false,
+            compression: DocCompression::None,
+        }
+    }
+
+    /// Transforms configured for the system
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            strip: !config.features.contains(Feature::NoStrip.name()),
+            compression: config.doc_compression,
+        }
+    }
+
+    /// Enable or disable stripping
+    pub fn with_strip(mut self, strip: bool) -> Self {
+        self.strip = strip;
+        self
+    }
+
+    /// Set man/info page compression
+    pub fn with_compression(mut self, compression: DocCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Apply per-package RESTRICT tokens
+    pub fn for_package(&self, restrict: &[String]) -> Self {
+        let mut transforms = self.clone();
+        if restrict.iter().any(|r| r == "strip") {
+            transforms.strip = false;
+        }
+        transforms
+    }
+
+    /// Transform a staged regular file and install it
+    ///
+    /// `relative` is the file's path relative to `root`.
+    pub fn apply(
+        &self,
+        root: &Path,
+        relative: &Path,
+        staged: StagedFile,
+        merge: &mut FileMerge,
+        install_mask: &InstallMask,
+    ) -> Result<TransformOutcome> {
+        let mut outcome = TransformOutcome {
+            path: staged.dest().to_path_buf(),
+            extra: Vec::new(),
+        };
+
+        if let Some(path) = self.compressed_name(relative) {
+            let data = std::fs::read(staged.path())?;
+            merge.discard(staged);
+            let compressed = merge.stage(&root.join(path))?;
+            self.compression.compress(
+                &data,
+                std::fs::File::options()
+                    .write(true)
+                    .open(compressed.path())?,
+            )?;
+            outcome.path = compressed.dest().to_path_buf();
+            merge.install(compressed)?;
+            return Ok(outcome);
+        }
+        if self.strip && is_strippable(relative, staged.path()) {
+            outcome.extra = self.strip_file(root, relative, staged.path(), merge, install_mask)?;
+        }
+        merge.install(staged)?;
+
+        Ok(outcome)
+    }
+
+    /// New name for a symlink to a page that will be compressed
+    ///
+    /// Returns the renamed link (relative to root) and its new target.
+    pub fn rename_symlink(&self, relative: &Path, target: &Path) -> Option<(PathBuf, PathBuf)> {
+        let ext = self.compression.extension()?;
+        let link = self.compressed_name(relative)?;
+        if has_compressed_extension(target) {
+            return Some((link, target.to_path_buf()));
+        }
+        let mut target = target.as_os_str().to_os_string();
+        target.push(".");
+        target.push(ext);
+        Some((link, PathBuf::from(target)))
+    }
+
+    /// Compressed name of a page, if the file should be compressed
+    fn compressed_name(&self, relative: &Path) -> Option<PathBuf> {
+        let ext = self.compression.extension()?;
+        if !DOC_COMPRESS_DIRS.iter().any(|d| relative.starts_with(d))
+            || has_compressed_extension(relative)
+        {
+            return None;
+        }
+        let name = relative.file_name()?.to_string_lossy();
+        if DOC_COMPRESS_SKIP.contains(&name.as_ref()) {
+            return None;
+        }
+        let mut path = relative.as_os_str().to_os_string();
+        path.push(".");
+        path.push(ext);
+        Some(PathBuf::from(path))
+    }
+
+    fn strip_file(
+        &self,
+        root: &Path,
+        relative: &Path,
+        dest: &Path,
+        merge: &mut FileMerge,
+        install_mask: &InstallMask,
+    ) -> Result<Vec<ExtraFile>> {
+        let (Ok(objcopy), Ok(strip)) = (which::which("objcopy"), which::which("strip")) else {
+            warn!(
+                "objcopy/strip not found, not stripping {}",
+                relative.display()
+            );
+            return Ok(Vec::new());
+        };
+
+        let mut extra = Vec::new();
+        let debug_relative = debug_path(relative);
+        let keep_debug = !install_mask.is_masked(&debug_relative);
+        let build_id = read_build_id(&std::fs::read(dest)?);
+
+        if keep_debug {
+            let debug_file = root.join(&debug_relative);
+            let staged = merge.stage(&debug_file)?;
+            if !run_tool(&objcopy, &["--only-keep-debug"], dest, Some(staged.path())) {
+                merge.discard(staged);
+                return Ok(Vec::new());
+            }
+            // Installed now, since the debuglink checksums it
+            merge.install(staged)?;
+            extra.push(ExtraFile {
+                path: debug_file,
+                file_type: FileType::Regular,
+            });
+        }
+
+        if !run_tool(&strip, &["--strip-unneeded"], dest, None) {
+            return Ok(extra);
+        }
+
+        if keep_debug {
+            let debug_file = root.join(&debug_relative);
+            let debuglink = format!("--add-gnu-debuglink={}", debug_file.display());
+            run_tool(&objcopy, &[&debuglink], dest, None);
+
+            if let Some(build_id) = build_id {
+                let link = build_id_path(&build_id);
+                // Relative link so it resolves inside alternate roots
+                let target = Path::new("../..").join(
+                    debug_relative
+                        .strip_prefix(DEBUG_DIR)
+                        .unwrap_or(&debug_relative),
+                );
+                let link_path = root.join(&link);
+                merge.symlink(&target, &link_path)?;
+                extra.push(ExtraFile {
+                    path: link_path,
+                    file_type: FileType::Symlink,
+                });
+            }
+        }
+
+        Ok(extra)
+    }
+}
+
+impl Default for MergeTransforms {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Run a binutils tool on a file, warning on failure
+fn run_tool(tool: &Path, args: &[&str], file: &Path, output: Option<&Path>) -> bool {
+    let mut cmd = Command::new(tool);
+    cmd.args(args).arg(file);
+    if let Some(output) = output {
+        cmd.arg(output);
+    }
+    match cmd.output() {
+        Ok(out) if out.status.success() => true,
+        Ok(out) => {
+            warn!(
+                "{} failed on {}: {}",
+                tool.display(),
+                file.display(),
+                String::from_utf8_lossy(&out.stderr).trim()
+            );
+            false
+        }
+        Err(e) => {
+            warn!("Failed to run {}: {}", tool.display(), e);
+            false
+        }
+    }
+}
+
+/// Split debug info location for a file, relative to the root
+pub fn debug_path(relative: &Path) -> PathBuf {
+    let mut path = Path::new(DEBUG_DIR).join(relative).into_os_string();
+    path.push(".debug");
+    PathBuf::from(path)
+}
+
+/// Build-id index entry for a build ID, relative to the root
+pub fn build_id_path(build_id: &str) -> PathBuf {
+    let (prefix, rest) = build_id.split_at(2.min(build_id.len()));
+    Path::new(DEBUG_DIR)
+        .join(".build-id")
+        .join(prefix)
+        .join(format!("{}.debug", rest))
+}
+
+fn has_compressed_extension(path: &Path) -> bool {
+    path.extension()
+        .map(|e| COMPRESSED_EXTENSIONS.contains(&e.to_string_lossy().as_ref()))
+        .unwrap_or(false)
+}
+
+/// Whether a merged file is an ELF executable or shared library worth stripping
+fn is_strippable(relative: &Path, dest: &Path) -> bool {
+    if relative.starts_with(DEBUG_DIR) {
+        return false;
+    }
+    let mut header = [0u8; 18];
+    let Ok(mut file) = std::fs::File::open(dest) else {
+        return false;
+    };
+    if std::io::Read::read_exact(&mut file, &mut header).is_err() {
+        return false;
+    }
+    if &header[..4] != b"\x7fELF" {
+        return false;
+    }
+    let e_type = match header[5] {
+        2 => u16::from_be_bytes([header[16], header[17]]),
+        _ => u16::from_le_bytes([header[16], header[17]]),
+    };
+    // ET_EXEC or ET_DYN; relocatable objects and core files are left alone
+    matches!(e_type, 2 | 3)
+}
+
+/// Extract the GNU build ID from an ELF image as a hex string
+pub fn read_build_id(data: &[u8]) -> Option<String> {
+    if data.get(..4)? != b"\x7fELF" {
+        return None;
+    }
+    let is_64 = *data.get(4)? == 2;
+    let big_endian = *data.get(5)? == 2;
+
+    let read = |offset: usize, size: usize| -> Option<u64> {
+        let bytes = data.get(offset..offset.checked_add(size)?)?;
+        let mut value = 0u64;
+        for i in 0..size {
+            let b = if big_endian {
+                bytes[i]
+            } else {
+                bytes[size - 1 - i]
+            };
+            value = (value << 8) | b as u64;
+        }
+        Some(value)
+    };
+
+    let (shoff, shentsize, shnum) = if is_64 {
+        (read(0x28, 8)?, read(0x3A, 2)?, read(0x3C, 2)?)
+    } else {
+        (read(0x20, 4)?, read(0x2E, 2)?, read(0x30, 2)?)
+    };
+
+    // Offsets come from the file, so every sum is checked
+    let shoff = usize::try_from(shoff).ok()?;
+    let shentsize = usize::try_from(shentsize).ok()?;
+    let at = |offset: usize, len: usize| data.get(offset..offset.checked_add(len)?);
+    for i in 0..usize::try_from(shnum).ok()? {
+        let header = shoff.checked_add(i.checked_mul(shentsize)?)?;
+        const SHT_NOTE: u64 = 7;
+        if read(header.checked_add(4)?, 4)? != SHT_NOTE {
+            continue;
+        }
+        let (offset, size) = if is_64 {
+            (
+                read(header.checked_add(0x18)?, 8)?,
+                read(header.checked_add(0x20)?, 8)?,
+            )
+        } else {
+            (
+                read(header.checked_add(0x10)?, 4)?,
+                read(header.checked_add(0x14)?, 4)?,
+            )
+        };
+
+        let mut pos = usize::try_from(offset).ok()?;
+        let end = pos.checked_add(usize::try_from(size).ok()?)?;
+        while pos.checked_add(12)? <= end {
+            let namesz = usize::try_from(read(pos, 4)?).ok()?;
+            let descsz = usize::try_from(read(pos + 4, 4)?).ok()?;
+            let note_type = read(pos + 8, 4)?;
+            let name_start = pos + 12;
+            let desc_start = name_start.checked_add(namesz.checked_next_multiple_of(4)?)?;
+            const NT_GNU_BUILD_ID: u64 = 3;
+            if note_type == NT_GNU_BUILD_ID && at(name_start, namesz)? == b"GNU\0" {
+                return Some(hex::encode(at(desc_start, descsz)?));
+            }
+            pos = desc_start.checked_add(descsz.checked_next_multiple_of(4)?)?;
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal little-endian ELF64 image with a single build-id note section
+    fn elf_with_build_id(build_id: &[u8]) -> Vec<u8> {
+        let mut note = Vec::new();
+        note.extend_from_slice(&4u32.to_le_bytes());
+        note.extend_from_slice(&(build_id.len() as u32).to_le_bytes());
+        note.extend_from_slice(&3u32.to_le_bytes());
+        note.extend_from_slice(b"GNU\0");
+        note.extend_from_slice(build_id);
+
+        let mut data = vec![0u8; 64];
+        data[..4].copy_from_slice(b"\x7fELF");
+        data[4] = 2;
+        data[5] = 1;
+        data[16..18].copy_from_slice(&3u16.to_le_bytes());
+
+        let note_offset = data.len() as u64;
+        data.extend_from_slice(&note);
+        let shoff = data.len() as u64;
+        data[0x28..0x30].copy_from_slice(&shoff.to_le_bytes());
+        data[0x3A..0x3C].copy_from_slice(&64u16.to_le_bytes());
+        data[0x3C..0x3E].copy_from_slice(&1u16.to_le_bytes());
+
+        let mut section = vec![0u8; 64];
+        section[4..8].copy_from_slice(&7u32.to_le_bytes());
+        section[0x18..0x20].copy_from_slice(&note_offset.to_le_bytes());
+        section[0x20..0x28].copy_from_slice(&(note.len() as u64).to_le_bytes());
+        data.extend_from_slice(&section);
+        data
+    }
+
+    #[test]
+    fn test_read_build_id() {
+        let data = elf_with_build_id(&[0xab, 0xcd, 0xef, 0x01]);
+        assert_eq!(read_build_id(&data), Some("abcdef01".to_string()));
+        assert_eq!(read_build_id(b"#!/bin/sh\n"), None);
+        assert_eq!(read_build_id(&data[..40]), None);
+
+        // Offsets that overflow are rejected, not wrapped around
+        let mut bad = data.clone();
+        bad[0x28..0x30].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(read_build_id(&bad), None);
+        let section = bad.len() - 64;
+        let mut bad = data.clone();
+        bad[section + 0x18..section + 0x20].copy_from_slice(&(u64::MAX - 4).to_le_bytes());
+        assert_eq!(read_build_id(&bad), None);
+        let mut bad = data;
+        bad[64..68].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(read_build_id(&bad), None);
+    }
+
+    #[test]
+    fn test_debug_paths() {
+        assert_eq!(
+            debug_path(Path::new("usr/bin/foo")),
+            PathBuf::from("usr/lib/debug/usr/bin/foo.debug")
+        );
+        assert_eq!(
+            build_id_path("abcdef01"),
+            PathBuf::from("usr/lib/debug/.build-id/ab/cdef01.debug")
+        );
+    }
+
+    #[test]
+    fn test_compress_man_page() {
+        let root = tempfile::tempdir().unwrap();
+        let relative = Path::new("usr/share/man/man1/foo.1");
+        let dest = root.path().join(relative);
+        let mut merge = FileMerge::new(root.path().join("backup"));
+        let staged = merge.stage(&dest).unwrap();
+        std::fs::write(staged.path(), ".TH FOO 1\n").unwrap();
+
+        let transforms = MergeTransforms::new().with_compression(DocCompression::Gzip);
+        let outcome = transforms
+            .apply(
+                root.path(),
+                relative,
+                staged,
+                &mut merge,
+                &InstallMask::new(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            outcome.path,
+            root.path().join("usr/share/man/man1/foo.1.gz")
+        );
+        assert!(outcome.path.exists());
+        assert!(!dest.exists());
+
+        let mut content = String::new();
+        std::io::Read::read_to_string(
+            &mut flate2::read::GzDecoder::new(std::fs::File::open(&outcome.path).unwrap()),
+            &mut content,
+        )
+        .unwrap();
+        assert_eq!(content, ".TH FOO 1\n");
+    }
+
+    #[test]
+    fn test_compression_exclusions() {
+        let transforms = MergeTransforms::new().with_compression(DocCompression::Xz);
+        assert_eq!(
+            transforms.compressed_name(Path::new("usr/share/info/foo.info")),
+            Some(PathBuf::from("usr/share/info/foo.info.xz"))
+        );
+        assert_eq!(
+            transforms.compressed_name(Path::new("usr/share/info/dir")),
+            None
+        );
+        assert_eq!(
+            transforms.compressed_name(Path::new("usr/share/man/man1/foo.1.gz")),
+            None
+        );
+        assert_eq!(transforms.compressed_name(Path::new("usr/bin/foo")), None);
+        assert_eq!(
+            MergeTransforms::new().compressed_name(Path::new("usr/share/man/man1/foo.1")),
+            None
+        );
+
+        assert_eq!(
+            transforms.rename_symlink(Path::new("usr/share/man/man1/bar.1"), Path::new("foo.1")),
+            Some((
+                PathBuf::from("usr/share/man/man1/bar.1.xz"),
+                PathBuf::from("foo.1.xz")
+            ))
+        );
+    }
+
+    #[test]
+    fn test_restrict_strip() {
+        let transforms = MergeTransforms::new().with_strip(true);
+        assert!(!transforms.for_package(&["strip".to_string()]).strip);
+        assert!(transforms.for_package(&["mirror".to_string()]).strip);
+    }
+
+    #[test]
+    fn test_defaults_from_config() {
+        let mut config = Config::default();
+        let transforms = MergeTransforms::from_config(&config);
+        assert!(transforms.strip);
+        assert_eq!(transforms.compression, DocCompression::Gzip);
+
+        config.features.insert("nostrip".to_string());
+        assert!(!MergeTransforms::from_config(&config).strip);
+    }
+}
@@ -0,0 +1,277 @@
+//! Reverting recorded transactions
+//!
+//! Undo replays the inverse of a history entry: packages it added are
+//! removed and packages it removed or replaced are reinstalled at their
+//! previous version from binary packages in PKGDIR. Nothing is rebuilt, so
+//! a plan is only possible when every binary package it needs is still
+//! cached and the installed packages have not moved on since.
+
+use crate::binary::{BinaryPackage, BinaryPackageIndex};
+use crate::db::{HistoryEntry, PackageChange};
+use crate::{InstalledPackage, PackageInfo};
+use std::path::Path;
+
+/// What one step of an undo does
+#[derive(Debug, Clone)]
+pub enum UndoAction {
+    /// Remove a package the transaction installed
+    Remove(InstalledPackage),
+    /// Reinstall a package the transaction removed
+    Install(Box<BinaryPackage>),
+    /// Put back the version the transaction replaced
+    Replace {
+        current: InstalledPackage,
+        previous: Box<BinaryPackage>,
+    },
+}
+
+/// Why an undo cannot be carried out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UndoProblem {
+    /// The binary package for the previous version is gone from PKGDIR
+    MissingBinpkg { change: PackageChange },
+    /// The package is no longer in the state the transaction left it in
+    Diverged {
+        change: PackageChange,
+        installed: Option<String>,
+    },
+}
+
+impl std::fmt::Display for UndoProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UndoProblem::MissingBinpkg { change } => write!(
+                f,
+                "{}-{}: no binary package in PKGDIR",
+                change.package,
+                change.new_version.as_deref().unwrap_or_default()
+            ),
+            UndoProblem::Diverged { change, installed } => write!(
+                f,
+                "{}: expected {} installed but found {}",
+                change.package,
+                change.old_version.as_deref().unwrap_or("nothing"),
+                installed.as_deref().unwrap_or("nothing")
+            ),
+        }
+    }
+}
+
+/// Steps that revert a transaction, and anything preventing them
+#[derive(Debug, Clone)]
+pub struct UndoPlan {
+    /// Transaction being reverted
+    pub entry: HistoryEntry,
+    /// Steps in the order they run
+    pub actions: Vec<UndoAction>,
+    /// Reasons the plan cannot run
+    pub problems: Vec<UndoProblem>,
+}
+
+impl UndoPlan {
+    /// Plan reverting `entry` against the installed packages and PKGDIR
+    pub fn new(
+        entry: HistoryEntry,
+        installed: &[InstalledPackage],
+        index: &BinaryPackageIndex,
+        pkgdir: &Path,
+    ) -> Self {
+        let mut actions = Vec::new();
+        let mut problems = Vec::new();
+
+        for change in entry.inverse() {
+            let current = installed
+                .iter()
+                .find(|p| p.id == change.package && p.slot == change.slot);
+            let current_version = current.map(|p| p.version.to_string());
+            if current_version != change.old_version {
+                problems.push(UndoProblem::Diverged {
+                    change,
+                    installed: current_version,
+                });
+                continue;
+            }
+
+            let Some(version) = &change.new_version else {
+                if let Some(current) = current {
+                    actions.push(UndoAction::Remove(current.clone()));
+                }
+                continue;
+            };
+
+            let binpkg = semver::Version::parse(version)
+                .ok()
+                .and_then(|v| index.find_version(&change.package, &v))
+                .filter(|b| !b.path.is_empty() && pkgdir.join(&b.path).is_file());
+            let Some(binpkg) = binpkg else {
+                problems.push(UndoProblem::MissingBinpkg { change });
+                continue;
+            };
+
+            let previous = Box::new(binpkg.clone());
+            actions.push(match current {
+                Some(current) => UndoAction::Replace {
+                    current: current.clone(),
+                    previous,
+                },
+                None => UndoAction::Install(previous),
+            });
+        }
+
+        Self {
+            entry,
+            actions,
+            problems,
+        }
+    }
+
+    /// Whether the plan can be executed
+    pub fn is_possible(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Package metadata for reinstalling from a binary package
+pub(crate) fn binpkg_package_info(binpkg: &BinaryPackage) -> PackageInfo {
+    PackageInfo {
+        id: binpkg.id.clone(),
+        version: binpkg.version.clone(),
+        slot: binpkg.slot.clone(),
+        description: binpkg.description.clone(),
+        homepage: None,
+        license: binpkg.license.clone(),
+        keywords: Vec::new(),
+        use_flags: Vec::new(),
+        dependencies: Vec::new(),
+        build_dependencies: Vec::new(),
+        runtime_dependencies: Vec::new(),
+        any_of_dependencies: Vec::new(),
+        source_url: None,
+        source_hash: None,
+        buck_target: String::new(),
+        size: binpkg.size,
+        installed_size: binpkg.installed_size,
+        required_use: String::new(),
+        blockers: Vec::new(),
+        restrict: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PackageId;
+    use std::collections::HashSet;
+
+    fn change(name: &str, old: Option<&str>, new: Option<&str>) -> PackageChange {
+        PackageChange {
+            package: PackageId::new("app-misc", name),
+            slot: "0".to_string(),
+            old_version: old.map(String::from),
+            new_version: new.map(String::from),
+        }
+    }
+
+    fn installed(name: &str, version: &str) -> InstalledPackage {
+        InstalledPackage {
+            id: PackageId::new("app-misc", name),
+            name: name.to_string(),
+            version: semver::Version::parse(version).unwrap(),
+            slot: "0".to_string(),
+            installed_at: chrono::Utc::now(),
+            use_flags: HashSet::new(),
+            files: Vec::new(),
+            size: 0,
+            build_time: false,
+            explicit: true,
+        }
+    }
+
+    fn binpkg(pkgdir: &Path, name: &str, version: &str) -> BinaryPackage {
+        let mut binpkg = BinaryPackage::from_installed(&installed(name, version));
+        binpkg.path = format!("app-misc/{}-{}.tar.zst", name, version);
+        let path = pkgdir.join(&binpkg.path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, b"").unwrap();
+        binpkg
+    }
+
+    fn entry(changes: Vec<PackageChange>) -> HistoryEntry {
+        HistoryEntry {
+            id: 7,
+            timestamp: chrono::Utc::now(),
+            user: "root".to_string(),
+            command: "buckos update".to_string(),
+            success: true,
+            error: None,
+            changes,
+            usage: None,
+            machine_id: None,
+        }
+    }
+
+    #[test]
+    fn test_plan_reverts_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut index = BinaryPackageIndex::default();
+        index.packages.insert(
+            "app-misc/foo".to_string(),
+            vec![binpkg(dir.path(), "foo", "1.0.0")],
+        );
+        index.packages.insert(
+            "app-misc/baz".to_string(),
+            vec![binpkg(dir.path(), "baz", "3.0.0")],
+        );
+
+        let plan = UndoPlan::new(
+            entry(vec![
+                change("foo", Some("1.0.0"), Some("1.1.0")),
+                change("bar", None, Some("2.0.0")),
+                change("baz", Some("3.0.0"), None),
+            ]),
+            &[installed("foo", "1.1.0"), installed("bar", "2.0.0")],
+            &index,
+            dir.path(),
+        );
+
+        assert!(plan.is_possible(), "{:?}", plan.problems);
+        assert_eq!(plan.actions.len(), 3);
+        assert!(matches!(&plan.actions[0], UndoAction::Install(b) if b.id.name == "baz"));
+        assert!(matches!(&plan.actions[1], UndoAction::Remove(p) if p.name == "bar"));
+        assert!(matches!(
+            &plan.actions[2],
+            UndoAction::Replace { current, previous }
+                if current.version.to_string() == "1.1.0" && previous.version.to_string() == "1.0.0"
+        ));
+    }
+
+    #[test]
+    fn test_plan_refuses_missing_or_diverged() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = BinaryPackageIndex::default();
+
+        let plan = UndoPlan::new(
+            entry(vec![
+                change("foo", Some("1.0.0"), Some("1.1.0")),
+                change("bar", None, Some("2.0.0")),
+            ]),
+            &[installed("foo", "1.1.0"), installed("bar", "2.1.0")],
+            &index,
+            dir.path(),
+        );
+
+        assert!(!plan.is_possible());
+        assert_eq!(
+            plan.problems,
+            vec![
+                UndoProblem::Diverged {
+                    change: change("bar", Some("2.0.0"), None),
+                    installed: Some("2.1.0".to_string()),
+                },
+                UndoProblem::MissingBinpkg {
+                    change: change("foo", Some("1.1.0"), Some("1.0.0")),
+                },
+            ]
+        );
+    }
+}
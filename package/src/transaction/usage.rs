@@ -0,0 +1,265 @@
+//! Resources used by a transaction
+//!
+//! Builds run in the Buck2 daemon rather than under buckos, so CPU time and
+//! memory are read from `/proc` for the process trees of buckos and of the
+//! daemon. CPU time includes exited processes their parents have reaped.
+//! The trees are sampled while the transaction runs; the kernel keeps each
+//! process's peak resident memory (`VmHWM`), so every process living longer
+//! than the sampling interval is seen at its peak. Bytes downloaded are
+//! counted on the host's network interfaces and include any other traffic
+//! at the time.
+
+use crate::buck::BuckDaemon;
+use crate::db::ResourceUsage;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How often process trees are sampled for peak memory
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Fields of `/proc/<pid>/stat` used here
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ProcStat {
+    ppid: u32,
+    /// utime + stime + cutime + cstime, in clock ticks
+    cpu_ticks: u64,
+}
+
+/// Parse `/proc/<pid>/stat`
+fn parse_stat(content: &str) -> Option<ProcStat> {
+    // The command name may contain spaces and parentheses
+    let rest = &content[content.rfind(')')? + 1..];
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    let ppid = fields.get(1)?.parse().ok()?;
+    let cpu_ticks = fields
+        .get(11..15)?
+        .iter()
+        .map(|f| f.parse::<u64>().ok())
+        .sum::<Option<u64>>()?;
+    Some(ProcStat { ppid, cpu_ticks })
+}
+
+/// Peak resident memory from `/proc/<pid>/status`, in bytes
+fn parse_hwm(status: &str) -> Option<u64> {
+    let kb = status
+        .lines()
+        .find_map(|l| l.strip_prefix("VmHWM:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+/// Bytes received on every interface but loopback, from `/proc/net/dev`
+fn parse_net_dev(content: &str) -> u64 {
+    content
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(iface, _)| iface.trim() != "lo")
+        .filter_map(|(_, counters)| counters.split_whitespace().next()?.parse::<u64>().ok())
+        .sum()
+}
+
+/// Build actions served from cache, from the summary Buck2 prints after a
+/// build, e.g. `Commands: 12 (cached: 9, remote: 0, local: 3)`
+pub fn cached_actions(output: &str) -> u64 {
+    output
+        .lines()
+        .rev()
+        .find_map(|line| {
+            let rest = &line[line.find("(cached: ")? + "(cached: ".len()..];
+            rest.split(|c: char| !c.is_ascii_digit())
+                .next()?
+                .parse()
+                .ok()
+        })
+        .unwrap_or(0)
+}
+
+/// Processes in the trees rooted at `roots`, with their stats
+fn process_tree(roots: &[u32]) -> Vec<(u32, ProcStat)> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    let stats: HashMap<u32, ProcStat> = entries
+        .flatten()
+        .filter_map(|e| e.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|pid| {
+            let content = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+            Some((pid, parse_stat(&content)?))
+        })
+        .collect();
+
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (pid, stat) in &stats {
+        children.entry(stat.ppid).or_default().push(*pid);
+    }
+    // The daemon may itself be a child of buckos
+    let mut seen = HashSet::new();
+    let mut tree = Vec::new();
+    let mut queue = roots.to_vec();
+    while let Some(pid) = queue.pop() {
+        let Some(stat) = stats.get(&pid).filter(|_| seen.insert(pid)) else {
+            continue;
+        };
+        tree.push((pid, *stat));
+        queue.extend(children.get(&pid).into_iter().flatten());
+    }
+    tree
+}
+
+fn cpu_ticks(roots: &[u32]) -> u64 {
+    process_tree(roots).iter().map(|(_, s)| s.cpu_ticks).sum()
+}
+
+fn peak_memory(roots: &[u32]) -> u64 {
+    process_tree(roots)
+        .iter()
+        .filter_map(|(pid, _)| {
+            parse_hwm(&std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?)
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+fn received_bytes() -> Option<u64> {
+    Some(parse_net_dev(
+        &std::fs::read_to_string("/proc/net/dev").ok()?,
+    ))
+}
+
+/// Bytes in use on the filesystem holding `path`
+fn used_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some((stat.f_blocks - stat.f_bfree) as u64 * stat.f_frsize as u64)
+}
+
+fn clock_ticks_per_sec() -> u64 {
+    match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        ticks if ticks > 0 => ticks as u64,
+        _ => 100,
+    }
+}
+
+/// Measures a transaction from [`UsageMeter::start`] to
+/// [`UsageMeter::finish`]
+pub struct UsageMeter {
+    started: Instant,
+    daemon: BuckDaemon,
+    root: PathBuf,
+    cpu_ticks: u64,
+    received: Option<u64>,
+    used: Option<u64>,
+    peak: Arc<AtomicU64>,
+    sampler: tokio::task::JoinHandle<()>,
+}
+
+impl UsageMeter {
+    /// Take the starting readings and sample peak memory until finished
+    pub fn start(daemon: &BuckDaemon, root: &Path) -> Self {
+        let peak = Arc::new(AtomicU64::new(0));
+        let sampler = {
+            let daemon = daemon.clone();
+            let peak = peak.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    peak.fetch_max(peak_memory(&roots(&daemon)), Ordering::Relaxed);
+                }
+            })
+        };
+        Self {
+            started: Instant::now(),
+            daemon: daemon.clone(),
+            root: root.to_path_buf(),
+            cpu_ticks: cpu_ticks(&roots(daemon)),
+            received: received_bytes(),
+            used: used_space(root),
+            peak,
+            sampler,
+        }
+    }
+
+    /// Readings since the start
+    pub fn finish(&self, cache_hits: u64, prebuilt: u64) -> ResourceUsage {
+        let roots = roots(&self.daemon);
+        let ticks = cpu_ticks(&roots).saturating_sub(self.cpu_ticks);
+        let peak = self.peak.load(Ordering::Relaxed).max(peak_memory(&roots));
+        let delta = |start: Option<u64>, end: Option<u64>| match (start, end) {
+            (Some(start), Some(end)) => Some(end as i64 - start as i64),
+            _ => None,
+        };
+        ResourceUsage {
+            wall_ms: self.started.elapsed().as_millis() as u64,
+            cpu_ms: ticks * 1000 / clock_ticks_per_sec(),
+            peak_memory: peak,
+            downloaded: delta(self.received, received_bytes()).unwrap_or(0).max(0) as u64,
+            cache_hits,
+            prebuilt,
+            disk_delta: delta(self.used, used_space(&self.root)).unwrap_or(0),
+        }
+    }
+}
+
+impl Drop for UsageMeter {
+    fn drop(&mut self) {
+        self.sampler.abort();
+    }
+}
+
+/// buckos itself and the Buck2 daemon, if it is running
+fn roots(daemon: &BuckDaemon) -> Vec<u32> {
+    let mut roots = vec![std::process::id()];
+    roots.extend(daemon.recorded_pid());
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_files() {
+        let stat = "4242 (buck2 (daemon)) S 1 4242 4242 0 -1 4194560 9000 0 0 0 \
+                    150 30 12 8 20 0 40 0 123456 1000000 500 18446744073709551615";
+        assert_eq!(
+            parse_stat(stat),
+            Some(ProcStat {
+                ppid: 1,
+                cpu_ticks: 200
+            })
+        );
+        assert_eq!(parse_stat("4242 (truncated"), None);
+
+        let status =
+            "Name:\tcc1plus\nVmPeak:\t  900000 kB\nVmHWM:\t  524288 kB\nVmRSS:\t 1024 kB\n";
+        assert_eq!(parse_hwm(status), Some(512 << 20));
+
+        let net = "Inter-|   Receive                            |  Transmit\n \
+                   face |bytes    packets errs drop fifo frame compressed multicast|bytes\n    \
+                   lo: 5000 50 0 0 0 0 0 0 5000\n  \
+                   eth0: 1200 10 0 0 0 0 0 0 800\n  \
+                   wlan0: 34 1 0 0 0 0 0 0 0\n";
+        assert_eq!(parse_net_dev(net), 1234);
+    }
+
+    #[test]
+    fn test_cached_actions() {
+        let stderr = "Jobs completed: 14. Time elapsed: 3.2s.\n\
+                      Cache hits: 75%. Commands: 12 (cached: 9, remote: 0, local: 3)\n\
+                      BUILD SUCCEEDED\n";
+        assert_eq!(cached_actions(stderr), 9);
+        assert_eq!(cached_actions("BUILD FAILED\n"), 0);
+    }
+}
@@ -3,81 +3,7 @@
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashSet};
 
-/// Package identifier with category and name
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
-pub struct PackageId {
-    pub category: String,
-    pub name: String,
-}
-
-impl PackageId {
-    pub fn new(category: impl Into<String>, name: impl Into<String>) -> Self {
-        Self {
-            category: category.into(),
-            name: name.into(),
-        }
-    }
-
-    pub fn full_name(&self) -> String {
-        format!("{}/{}", self.category, self.name)
-    }
-
-    /// Parse a package identifier from string (e.g., "sys-apps/systemd" or "system/apps/shadow")
-    pub fn parse(s: &str) -> Option<Self> {
-        // Find the last slash - everything before is category, after is name
-        if let Some(last_slash) = s.rfind('/') {
-            let category = &s[..last_slash];
-            let name = &s[last_slash + 1..];
-            if !category.is_empty() && !name.is_empty() {
-                Some(Self::new(category, name))
-            } else {
-                None
-            }
-        } else {
-            None
-        }
-    }
-}
-
-impl std::fmt::Display for PackageId {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}/{}", self.category, self.name)
-    }
-}
-
-/// Version specification with comparison operator
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
-pub enum VersionSpec {
-    #[default]
-    Any,
-    Exact(semver::Version),
-    GreaterThan(semver::Version),
-    GreaterThanOrEqual(semver::Version),
-    LessThan(semver::Version),
-    LessThanOrEqual(semver::Version),
-    Range {
-        min: Option<semver::Version>,
-        max: Option<semver::Version>,
-    },
-}
-
-impl VersionSpec {
-    pub fn matches(&self, version: &semver::Version) -> bool {
-        match self {
-            VersionSpec::Any => true,
-            VersionSpec::Exact(v) => version == v,
-            VersionSpec::GreaterThan(v) => version > v,
-            VersionSpec::GreaterThanOrEqual(v) => version >= v,
-            VersionSpec::LessThan(v) => version < v,
-            VersionSpec::LessThanOrEqual(v) => version <= v,
-            VersionSpec::Range { min, max } => {
-                let min_ok = min.as_ref().map(|m| version >= m).unwrap_or(true);
-                let max_ok = max.as_ref().map(|m| version <= m).unwrap_or(true);
-                min_ok && max_ok
-            }
-        }
-    }
-}
+pub use buckos_core::{PackageId, PackageSpec, VersionSpec};
 
 /// Package dependency specification
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -152,6 +78,32 @@ pub struct PackageInfo {
     /// Package blockers (e.g., "!sys-apps/openrc", "!!sys-apps/sysvinit")
     #[serde(default)]
     pub blockers: Vec<String>,
+    /// Any-of dependency groups (e.g., "|| ( dev-libs/openssl dev-libs/libressl )")
+    #[serde(default)]
+    pub any_of_dependencies: Vec<String>,
+    /// RESTRICT tokens (e.g., "strip", "mirror")
+    #[serde(default)]
+    pub restrict: Vec<String>,
+}
+
+/// The fields of a [`PackageInfo`] needed to search and list packages
+///
+/// Scanning a repository for headers skips the dependency and USE lists;
+/// [`crate::repository::RepositoryManager::details`] loads the full record
+/// of the packages that need it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackageHeader {
+    pub id: PackageId,
+    pub version: semver::Version,
+    pub slot: String,
+    pub description: String,
+    pub size: u64,
+    /// Repository offering this version
+    pub repository: String,
+    /// Metadata file holding the full record, for repositories that keep
+    /// one per package
+    #[serde(skip)]
+    pub metadata: Option<std::path::PathBuf>,
 }
 
 /// USE flag definition
@@ -197,6 +149,8 @@ pub enum FileType {
     Hardlink,
     Device,
     Fifo,
+    /// Skipped by INSTALL_MASK; recorded but not present on disk
+    Masked,
 }
 
 /// USE flag status for resolution display
@@ -230,6 +184,11 @@ pub struct Resolution {
     pub build_order: Vec<usize>,
     pub download_size: u64,
     pub install_size: u64,
+    /// Alternatives chosen for any-of dependency groups, with reasons
+    pub any_of_choices: Vec<crate::resolver::AnyOfChoice>,
+    /// System packages a per-user prefix relies on instead of installing
+    pub from_host: Vec<PackageId>,
+    pub stats: crate::resolver::ResolveStats,
 }
 
 /// USE flag change for newuse detection
@@ -248,6 +207,35 @@ pub struct NewusePackage {
     pub use_changes: Vec<UseFlagChange>,
 }
 
+/// A package with every version the repositories offer, as shown by
+/// `buckos info --json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageDetails {
+    /// The version `info` resolves to, with its USE flags and dependencies
+    pub package: PackageInfo,
+    /// Installed version, if any
+    pub installed: Option<semver::Version>,
+    /// Versions available, by repository
+    pub versions: Vec<RepoVersion>,
+}
+
+/// One version of a package in one repository
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoVersion {
+    pub repository: String,
+    pub version: semver::Version,
+    pub slot: String,
+    pub keywords: Vec<String>,
+}
+
+/// Installed packages depending on a package, as shown by
+/// `buckos rdeps --format json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReverseDependencies {
+    pub package: String,
+    pub reverse_dependencies: Vec<String>,
+}
+
 /// Security vulnerability information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Vulnerability {
@@ -281,128 +269,6 @@ pub struct Repository {
     pub buck_targets_path: String,
 }
 
-/// Package specification for user input
-#[derive(Debug, Clone)]
-pub struct PackageSpec {
-    pub id: PackageId,
-    pub version: VersionSpec,
-    pub slot: Option<String>,
-    pub repo: Option<String>,
-}
-
-impl PackageSpec {
-    /// Parse a package specification string
-    /// Examples: "sys-apps/systemd", ">=sys-apps/systemd-250", "sys-apps/systemd:0"
-    pub fn parse(s: &str) -> crate::Result<Self> {
-        let s = s.trim();
-
-        // Extract version operator if present
-        let (version_op, rest) = if let Some(stripped) = s.strip_prefix(">=") {
-            (Some(">="), stripped)
-        } else if let Some(stripped) = s.strip_prefix("<=") {
-            (Some("<="), stripped)
-        } else if let Some(stripped) = s.strip_prefix('>') {
-            (Some(">"), stripped)
-        } else if let Some(stripped) = s.strip_prefix('<') {
-            (Some("<"), stripped)
-        } else if let Some(stripped) = s.strip_prefix('=') {
-            (Some("="), stripped)
-        } else if let Some(stripped) = s.strip_prefix('~') {
-            (Some("~"), stripped)
-        } else {
-            (None, s)
-        };
-
-        // Extract repository if present (::repo syntax) — must be checked before slot
-        let (rest, repo) = if let Some(idx) = rest.find("::") {
-            (&rest[..idx], Some(rest[idx + 2..].to_string()))
-        } else {
-            (rest, None)
-        };
-
-        // Extract slot if present
-        let (pkg_part, slot) = if let Some(idx) = rest.find(':') {
-            (&rest[..idx], Some(rest[idx + 1..].to_string()))
-        } else {
-            (rest, None)
-        };
-
-        // Parse category/name-version
-        let (id, version) = Self::parse_name_version(pkg_part, version_op)?;
-
-        Ok(Self {
-            id,
-            version,
-            slot,
-            repo,
-        })
-    }
-
-    fn parse_name_version(
-        s: &str,
-        version_op: Option<&str>,
-    ) -> crate::Result<(PackageId, VersionSpec)> {
-        // Split into category and name-version using last slash (supports deep paths)
-        let last_slash = s
-            .rfind('/')
-            .ok_or_else(|| crate::Error::InvalidPackageSpec(s.to_string()))?;
-        let category = s[..last_slash].to_string();
-        let name_version = &s[last_slash + 1..];
-
-        // Try to extract version from name (e.g., "systemd-250.4")
-        if let Some(version_op) = version_op {
-            // Find last dash followed by digit
-            let mut last_dash = None;
-            for (i, c) in name_version.char_indices() {
-                if c == '-'
-                    && name_version[i + 1..]
-                        .chars()
-                        .next()
-                        .map(|c| c.is_ascii_digit())
-                        .unwrap_or(false)
-                {
-                    last_dash = Some(i);
-                }
-            }
-
-            if let Some(idx) = last_dash {
-                let name = name_version[..idx].to_string();
-                let version_str = &name_version[idx + 1..];
-                let version = semver::Version::parse(version_str)
-                    .or_else(|_| {
-                        // Try parsing as simple version
-                        Self::parse_simple_version(version_str)
-                    })
-                    .map_err(|_| crate::Error::InvalidVersion(version_str.to_string()))?;
-
-                let version_spec = match version_op {
-                    "=" | "~" => VersionSpec::Exact(version),
-                    ">" => VersionSpec::GreaterThan(version),
-                    ">=" => VersionSpec::GreaterThanOrEqual(version),
-                    "<" => VersionSpec::LessThan(version),
-                    "<=" => VersionSpec::LessThanOrEqual(version),
-                    _ => VersionSpec::Any,
-                };
-
-                return Ok((PackageId::new(category, name), version_spec));
-            }
-        }
-
-        // No version specified
-        Ok((PackageId::new(category, name_version), VersionSpec::Any))
-    }
-
-    fn parse_simple_version(s: &str) -> Result<semver::Version, semver::Error> {
-        // Handle versions like "250" or "250.4"
-        let parts: Vec<&str> = s.split('.').collect();
-        match parts.len() {
-            1 => format!("{}.0.0", parts[0]).parse(),
-            2 => format!("{}.{}.0", parts[0], parts[1]).parse(),
-            _ => s.parse(),
-        }
-    }
-}
-
 /// World set - explicitly installed packages
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct WorldSet {
@@ -415,6 +281,7 @@ pub struct UseConfig {
     /// Global USE flags (enabled)
     pub global: HashSet<String>,
     /// Per-package USE flags
+    #[serde(default, with = "package_id_keys")]
     pub package: BTreeMap<PackageId, HashSet<String>>,
     /// Masked (explicitly disabled) USE flags
     #[serde(default)]
@@ -448,6 +315,38 @@ impl UseConfig {
     }
 }
 
+/// (De)serialize maps keyed by [`PackageId`] using `category/name` keys,
+/// since TOML and JSON only allow string keys
+mod package_id_keys {
+    use super::PackageId;
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<S, V>(map: &BTreeMap<PackageId, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        V: Serialize,
+    {
+        serializer.collect_map(map.iter().map(|(id, value)| (id.full_name(), value)))
+    }
+
+    pub fn deserialize<'de, D, V>(deserializer: D) -> Result<BTreeMap<PackageId, V>, D::Error>
+    where
+        D: Deserializer<'de>,
+        V: Deserialize<'de>,
+    {
+        BTreeMap::<String, V>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(name, value)| {
+                PackageId::parse(&name)
+                    .map(|id| (id, value))
+                    .ok_or_else(|| D::Error::custom(format!("invalid package name: {}", name)))
+            })
+            .collect()
+    }
+}
+
 // ============================================================================
 // Buck2 Integration Types
 // ============================================================================
@@ -0,0 +1,595 @@
+//! USE flag explanation
+//!
+//! Works out where each USE flag of a package gets its value from. Flags are
+//! evaluated layer by layer, later layers overriding earlier ones:
+//!
+//! 1. IUSE defaults from the package itself
+//! 2. The selected profile (USE and package.use)
+//! 3. Flags recommended for the detected hardware (package.use/99-hardware)
+//! 4. Global USE from make.conf
+//! 5. USE_EXPAND variables (`VIDEO_CARDS`, `INPUT_DEVICES`, ...)
+//! 6. Per-package USE from package.use
+//! 7. Flags given on the command line
+//! 8. use.mask, then use.force
+//!
+//! The same layers decide which USE-conditional dependencies the install
+//! resolver follows (see
+//! [`DependencyResolver::with_use_layers`](crate::resolver::DependencyResolver::with_use_layers)),
+//! so the impact of toggling a flag can be computed by resolving twice and
+//! diffing the results.
+
+use crate::hardware::HardwareUse;
+use crate::profile::ResolvedProfile;
+use crate::{PackageId, PackageInfo, UseConfig};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// A configuration layer that can set a USE flag
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum UseLayer {
+    /// IUSE default (`+flag`) from the package
+    Default,
+    /// The selected profile
+    Profile,
+    /// Detected hardware (package.use/99-hardware)
+    Hardware,
+    /// Global USE (make.conf)
+    Global,
+    /// USE_EXPAND variables
+    Expand,
+    /// package.use
+    PackageUse,
+    /// Command line
+    CommandLine,
+    /// use.mask (profile or configuration)
+    Mask,
+    /// use.force (profile)
+    Force,
+}
+
+impl UseLayer {
+    /// Human-readable layer name
+    pub fn name(&self) -> &'static str {
+        match self {
+            UseLayer::Default => "IUSE default",
+            UseLayer::Profile => "profile",
+            UseLayer::Hardware => "hardware",
+            UseLayer::Global => "make.conf",
+            UseLayer::Expand => "USE_EXPAND",
+            UseLayer::PackageUse => "package.use",
+            UseLayer::CommandLine => "command line",
+            UseLayer::Mask => "use.mask",
+            UseLayer::Force => "use.force",
+        }
+    }
+}
+
+impl std::fmt::Display for UseLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A value assigned to a flag by one layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayerSetting {
+    /// Layer that set the flag
+    pub layer: UseLayer,
+    /// Value it set
+    pub enabled: bool,
+}
+
+/// What changes if a flag is toggled
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToggleImpact {
+    /// Packages pulled in by the toggle
+    pub added: Vec<PackageId>,
+    /// Packages no longer needed after the toggle
+    pub removed: Vec<PackageId>,
+    /// Change in total installed size, in bytes
+    pub size_delta: i64,
+    /// Files only installed with the toggled value
+    pub files_added: Vec<String>,
+    /// Files only installed with the current value
+    pub files_removed: Vec<String>,
+}
+
+impl ToggleImpact {
+    /// Diff the package sets of two resolutions
+    pub fn between(current: &[PackageInfo], toggled: &[PackageInfo]) -> Self {
+        let current_ids: HashSet<&PackageId> = current.iter().map(|p| &p.id).collect();
+        let toggled_ids: HashSet<&PackageId> = toggled.iter().map(|p| &p.id).collect();
+
+        let mut added: Vec<PackageId> = toggled
+            .iter()
+            .filter(|p| !current_ids.contains(&p.id))
+            .map(|p| p.id.clone())
+            .collect();
+        let mut removed: Vec<PackageId> = current
+            .iter()
+            .filter(|p| !toggled_ids.contains(&p.id))
+            .map(|p| p.id.clone())
+            .collect();
+        added.sort();
+        removed.sort();
+
+        let size = |pkgs: &[PackageInfo]| pkgs.iter().map(|p| p.installed_size as i64).sum::<i64>();
+
+        Self {
+            added,
+            removed,
+            size_delta: size(toggled) - size(current),
+            files_added: Vec::new(),
+            files_removed: Vec::new(),
+        }
+    }
+
+    /// Record the file list difference between two builds of the package
+    pub fn with_files(mut self, current: &[String], toggled: &[String]) -> Self {
+        let current_set: HashSet<&String> = current.iter().collect();
+        let toggled_set: HashSet<&String> = toggled.iter().collect();
+
+        self.files_added = toggled
+            .iter()
+            .filter(|f| !current_set.contains(f))
+            .cloned()
+            .collect();
+        self.files_removed = current
+            .iter()
+            .filter(|f| !toggled_set.contains(f))
+            .cloned()
+            .collect();
+        self.files_added.sort();
+        self.files_removed.sort();
+        self
+    }
+
+    /// Whether toggling changes nothing that could be measured
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.size_delta == 0
+            && self.files_added.is_empty()
+            && self.files_removed.is_empty()
+    }
+}
+
+/// Explanation of a single USE flag of a package
+#[derive(Debug, Clone)]
+pub struct FlagExplanation {
+    /// Flag name
+    pub flag: String,
+    /// Flag description from the package metadata
+    pub description: String,
+    /// Effective value
+    pub enabled: bool,
+    /// Every layer that set the flag, in evaluation order
+    pub settings: Vec<LayerSetting>,
+    /// Effect of toggling the flag, if it could be computed
+    pub impact: Option<ToggleImpact>,
+}
+
+impl FlagExplanation {
+    /// The layer that decided the effective value
+    pub fn source(&self) -> UseLayer {
+        self.settings
+            .last()
+            .map(|s| s.layer)
+            .unwrap_or(UseLayer::Default)
+    }
+
+    /// Whether the flag is pinned by use.mask or use.force
+    pub fn is_locked(&self) -> bool {
+        matches!(self.source(), UseLayer::Mask | UseLayer::Force)
+    }
+}
+
+/// The USE flag configuration layers
+#[derive(Debug, Clone, Default)]
+pub struct UseLayers {
+    profile: Option<ResolvedProfile>,
+    hardware: HardwareUse,
+    global: Vec<String>,
+    expand: Vec<String>,
+    package: BTreeMap<PackageId, Vec<String>>,
+    cli: Vec<String>,
+    cli_package: BTreeMap<PackageId, Vec<String>>,
+    mask: HashSet<String>,
+}
+
+impl UseLayers {
+    /// Build the layers from the USE configuration
+    pub fn new(config: &UseConfig) -> Self {
+        let mut global: Vec<String> = config.global.iter().cloned().collect();
+        global.sort();
+
+        let mut expand: Vec<String> = config
+            .expand
+            .iter()
+            .flat_map(|(prefix, values)| {
+                values
+                    .iter()
+                    .map(move |v| format!("{}_{}", prefix.to_lowercase(), v.to_lowercase()))
+            })
+            .collect();
+        expand.sort();
+
+        let package = config
+            .package
+            .iter()
+            .map(|(id, flags)| {
+                let mut flags: Vec<String> = flags.iter().cloned().collect();
+                flags.sort();
+                (id.clone(), flags)
+            })
+            .collect();
+
+        Self {
+            profile: None,
+            hardware: HardwareUse::default(),
+            global,
+            expand,
+            package,
+            cli: Vec::new(),
+            cli_package: BTreeMap::new(),
+            mask: config.mask.clone(),
+        }
+    }
+
+    /// Add the selected profile
+    pub fn with_profile(mut self, profile: &ResolvedProfile) -> Self {
+        self.profile = Some(profile.clone());
+        self
+    }
+
+    /// Add the flags recommended for the detected hardware
+    pub fn with_hardware(mut self, hardware: HardwareUse) -> Self {
+        self.hardware = hardware;
+        self
+    }
+
+    /// Add flags given on the command line (prefix with `-` to disable)
+    pub fn with_cli(mut self, flags: &[String]) -> Self {
+        self.cli.extend(flags.iter().cloned());
+        self
+    }
+
+    /// Force a flag to a value for a single package, as if given on the command line
+    pub fn with_package_override(mut self, pkg: &PackageId, flag: &str, enabled: bool) -> Self {
+        let token = if enabled {
+            flag.to_string()
+        } else {
+            format!("-{}", flag)
+        };
+        self.cli_package.entry(pkg.clone()).or_default().push(token);
+        self
+    }
+
+    /// Every layer that sets a flag for a package, in evaluation order
+    pub fn settings(&self, pkg: &PackageInfo, flag: &str) -> Vec<LayerSetting> {
+        let mut settings = Vec::new();
+
+        if let Some(iuse) = pkg.use_flags.iter().find(|f| f.name == flag) {
+            settings.push(LayerSetting {
+                layer: UseLayer::Default,
+                enabled: iuse.default,
+            });
+        }
+
+        for (layer, tokens) in self.token_layers(pkg) {
+            if let Some(enabled) = last_setting(&tokens, flag) {
+                settings.push(LayerSetting { layer, enabled });
+            }
+        }
+
+        let profile_mask = self
+            .profile
+            .as_ref()
+            .is_some_and(|p| p.use_mask.contains(flag));
+        if self.mask.contains(flag) || profile_mask {
+            settings.push(LayerSetting {
+                layer: UseLayer::Mask,
+                enabled: false,
+            });
+        }
+        if self
+            .profile
+            .as_ref()
+            .is_some_and(|p| p.use_force.contains(flag))
+        {
+            settings.push(LayerSetting {
+                layer: UseLayer::Force,
+                enabled: true,
+            });
+        }
+
+        settings
+    }
+
+    /// Explain every IUSE flag of a package
+    pub fn explain(&self, pkg: &PackageInfo) -> Vec<FlagExplanation> {
+        let mut explanations: Vec<FlagExplanation> = pkg
+            .use_flags
+            .iter()
+            .map(|iuse| {
+                let settings = self.settings(pkg, &iuse.name);
+                FlagExplanation {
+                    flag: iuse.name.clone(),
+                    description: iuse.description.clone(),
+                    enabled: settings.last().is_some_and(|s| s.enabled),
+                    settings,
+                    impact: None,
+                }
+            })
+            .collect();
+        explanations.sort_by(|a, b| a.flag.cmp(&b.flag));
+        explanations
+    }
+
+    /// Effective set of enabled flags for a package
+    ///
+    /// Includes flags outside IUSE that are enabled by configuration, since
+    /// dependency conditions may refer to them.
+    pub fn effective(&self, pkg: &PackageInfo) -> HashSet<String> {
+        let mut candidates: HashSet<&str> = pkg.use_flags.iter().map(|f| f.name.as_str()).collect();
+        let layers = self.token_layers(pkg);
+        for (_, tokens) in &layers {
+            candidates.extend(tokens.iter().map(|t| t.trim_start_matches('-')));
+        }
+        if let Some(profile) = &self.profile {
+            candidates.extend(profile.use_force.iter().map(|s| s.as_str()));
+        }
+
+        candidates
+            .into_iter()
+            .filter(|flag| self.settings(pkg, flag).last().is_some_and(|s| s.enabled))
+            .map(|flag| flag.to_string())
+            .collect()
+    }
+
+    /// Token-based layers (`flag` / `-flag`) that apply to a package
+    fn token_layers(&self, pkg: &PackageInfo) -> Vec<(UseLayer, Vec<String>)> {
+        let mut layers = Vec::new();
+
+        if let Some(profile) = &self.profile {
+            let mut tokens: Vec<String> = profile.use_flags.iter().cloned().collect();
+            tokens.sort();
+            tokens.extend(profile.package_use_for(&pkg.id.full_name()));
+            layers.push((UseLayer::Profile, tokens));
+        }
+        layers.push((UseLayer::Hardware, self.hardware.flags_for(&pkg.id)));
+        layers.push((UseLayer::Global, self.global.clone()));
+        layers.push((UseLayer::Expand, self.expand.clone()));
+        layers.push((
+            UseLayer::PackageUse,
+            self.package.get(&pkg.id).cloned().unwrap_or_default(),
+        ));
+
+        let mut cli = self.cli.clone();
+        cli.extend(self.cli_package.get(&pkg.id).cloned().unwrap_or_default());
+        layers.push((UseLayer::CommandLine, cli));
+
+        layers
+    }
+}
+
+/// The value the last matching token assigns to a flag
+fn last_setting(tokens: &[String], flag: &str) -> Option<bool> {
+    tokens
+        .iter()
+        .rev()
+        .find_map(|token| match token.strip_prefix('-') {
+            Some(name) if name == flag => Some(false),
+            None if token == flag => Some(true),
+            _ => None,
+        })
+}
+
+/// How a USE flag is used across the repository
+#[derive(Debug, Clone)]
+pub struct UseFlagUsage {
+    /// Flag name
+    pub name: String,
+    /// Descriptions given by packages, most common first
+    pub descriptions: Vec<String>,
+    /// Packages that declare the flag in IUSE
+    pub packages: Vec<PackageId>,
+}
+
+impl UseFlagUsage {
+    /// The most common description
+    pub fn description(&self) -> &str {
+        self.descriptions.first().map(|s| s.as_str()).unwrap_or("")
+    }
+
+    /// Whether the flag is shared by several packages
+    pub fn is_global(&self) -> bool {
+        self.packages.len() > 1
+    }
+}
+
+/// Collect every USE flag declared by the given packages
+pub fn collect_flag_usage(packages: &[PackageInfo]) -> BTreeMap<String, UseFlagUsage> {
+    let mut usage: BTreeMap<String, (HashMap<String, usize>, Vec<PackageId>)> = BTreeMap::new();
+
+    for pkg in packages {
+        for flag in &pkg.use_flags {
+            let (descriptions, ids) = usage.entry(flag.name.clone()).or_default();
+            if !flag.description.is_empty() {
+                *descriptions.entry(flag.description.clone()).or_default() += 1;
+            }
+            if !ids.contains(&pkg.id) {
+                ids.push(pkg.id.clone());
+            }
+        }
+    }
+
+    usage
+        .into_iter()
+        .map(|(name, (descriptions, mut packages))| {
+            let mut descriptions: Vec<(String, usize)> = descriptions.into_iter().collect();
+            descriptions.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            packages.sort();
+            let usage = UseFlagUsage {
+                name: name.clone(),
+                descriptions: descriptions.into_iter().map(|(d, _)| d).collect(),
+                packages,
+            };
+            (name, usage)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::UseFlag;
+
+    fn package(name: &str, flags: &[(&str, bool)]) -> PackageInfo {
+        PackageInfo {
+            id: PackageId::new("app-misc", name),
+            version: semver::Version::new(1, 0, 0),
+            slot: "0".to_string(),
+            description: String::new(),
+            homepage: None,
+            license: "MIT".to_string(),
+            keywords: Vec::new(),
+            use_flags: flags
+                .iter()
+                .map(|(name, default)| UseFlag {
+                    name: name.to_string(),
+                    description: format!("{} support", name),
+                    default: *default,
+                })
+                .collect(),
+            dependencies: Vec::new(),
+            build_dependencies: Vec::new(),
+            runtime_dependencies: Vec::new(),
+            source_url: None,
+            source_hash: None,
+            buck_target: String::new(),
+            size: 0,
+            installed_size: 100,
+            required_use: String::new(),
+            blockers: Vec::new(),
+            any_of_dependencies: Vec::new(),
+            restrict: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_layer_precedence() {
+        let pkg = package("foo", &[("ssl", true), ("ipv6", false), ("doc", false)]);
+
+        let mut config = UseConfig::default();
+        config.global.insert("-ssl".to_string());
+        config.global.insert("ipv6".to_string());
+        config
+            .package
+            .insert(pkg.id.clone(), ["ssl".to_string()].into());
+        config.mask.insert("doc".to_string());
+
+        let layers = UseLayers::new(&config).with_cli(&["doc".to_string(), "-ipv6".to_string()]);
+        let explained = layers.explain(&pkg);
+
+        let ssl = explained.iter().find(|f| f.flag == "ssl").unwrap();
+        assert!(ssl.enabled);
+        assert_eq!(ssl.source(), UseLayer::PackageUse);
+        assert_eq!(
+            ssl.settings.iter().map(|s| s.layer).collect::<Vec<_>>(),
+            vec![UseLayer::Default, UseLayer::Global, UseLayer::PackageUse]
+        );
+
+        let ipv6 = explained.iter().find(|f| f.flag == "ipv6").unwrap();
+        assert!(!ipv6.enabled);
+        assert_eq!(ipv6.source(), UseLayer::CommandLine);
+
+        let doc = explained.iter().find(|f| f.flag == "doc").unwrap();
+        assert!(!doc.enabled);
+        assert!(doc.is_locked());
+    }
+
+    #[test]
+    fn test_hardware_below_user_configuration() {
+        let pkg = package("foo", &[("ssl", true), ("vulkan", false), ("gtk", false)]);
+        let mut config = UseConfig::default();
+        config.global.insert("-ssl".to_string());
+        let hardware = HardwareUse::parse("*/* ssl vulkan\nother/bar gtk\n");
+
+        let layers = UseLayers::new(&config).with_hardware(hardware);
+        let flags = layers.effective(&pkg);
+        assert!(flags.contains("vulkan"));
+        assert!(!flags.contains("ssl"));
+        assert!(!flags.contains("gtk"));
+        assert_eq!(
+            layers
+                .settings(&pkg, "ssl")
+                .iter()
+                .map(|s| s.layer)
+                .collect::<Vec<_>>(),
+            vec![UseLayer::Default, UseLayer::Hardware, UseLayer::Global]
+        );
+    }
+
+    #[test]
+    fn test_effective_and_override() {
+        let pkg = package("foo", &[("ssl", true), ("gtk", false)]);
+        let mut config = UseConfig::default();
+        config
+            .expand
+            .insert("VIDEO_CARDS".to_string(), ["amdgpu".to_string()].into());
+
+        let layers = UseLayers::new(&config);
+        let flags = layers.effective(&pkg);
+        assert!(flags.contains("ssl"));
+        assert!(flags.contains("video_cards_amdgpu"));
+        assert!(!flags.contains("gtk"));
+
+        let toggled = layers
+            .clone()
+            .with_package_override(&pkg.id, "gtk", true)
+            .effective(&pkg);
+        assert!(toggled.contains("gtk"));
+
+        // Overrides only apply to the named package
+        let other = package("bar", &[("gtk", false)]);
+        assert!(!layers
+            .with_package_override(&pkg.id, "gtk", true)
+            .effective(&other)
+            .contains("gtk"));
+    }
+
+    #[test]
+    fn test_toggle_impact() {
+        let a = package("a", &[]);
+        let b = package("b", &[]);
+        let c = package("c", &[]);
+
+        let impact = ToggleImpact::between(&[a.clone(), b.clone()], &[a, c.clone()]);
+        assert_eq!(impact.added, vec![c.id]);
+        assert_eq!(impact.removed, vec![b.id]);
+        assert_eq!(impact.size_delta, 0);
+
+        let impact = ToggleImpact::default().with_files(
+            &["/usr/bin/foo".to_string()],
+            &[
+                "/usr/bin/foo".to_string(),
+                "/usr/lib/libgtk-foo.so".to_string(),
+            ],
+        );
+        assert_eq!(impact.files_added, vec!["/usr/lib/libgtk-foo.so"]);
+        assert!(impact.files_removed.is_empty());
+        assert!(!impact.is_empty());
+    }
+
+    #[test]
+    fn test_collect_flag_usage() {
+        let usage = collect_flag_usage(&[
+            package("a", &[("ssl", true)]),
+            package("b", &[("ssl", false), ("gtk", false)]),
+        ]);
+
+        assert!(usage["ssl"].is_global());
+        assert_eq!(usage["ssl"].description(), "ssl support");
+        assert!(!usage["gtk"].is_global());
+    }
+}
@@ -349,9 +349,7 @@ pub async fn compute_sha256(path: &Path) -> Result<String> {
 
 /// Compute BLAKE3 hash of a file
 pub fn compute_blake3(path: &Path) -> Result<String> {
-    let contents = std::fs::read(path)?;
-
-    Ok(blake3::hash(&contents).to_hex().to_string())
+    Ok(buckos_core::hash::hash_file(path)?)
 }
 
 /// Result of package validation
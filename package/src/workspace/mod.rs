@@ -0,0 +1,216 @@
+//! Named workspaces
+//!
+//! A workspace is a self-contained root with its own package database,
+//! world set and configuration, stored under
+//! `/var/lib/buckos/workspaces/<name>`. Workspaces let a developer keep a
+//! host system and several target sysroots side by side:
+//!
+//! ```text
+//! /var/lib/buckos/workspaces/embedded-arm/
+//!     buckos.toml     workspace configuration
+//!     db/             package database
+//!     cache/          distfiles, builds and binary packages
+//!     root/           the sysroot (including its world file)
+//! ```
+
+use crate::{Config, Error, Result};
+use std::path::{Path, PathBuf};
+
+/// Default directory holding all workspaces
+pub const DEFAULT_WORKSPACES_DIR: &str = "/var/lib/buckos/workspaces";
+
+/// Configuration file name inside a workspace
+const CONFIG_FILE: &str = "buckos.toml";
+
+/// A named workspace
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    /// Workspace name
+    pub name: String,
+    /// Workspace directory
+    pub dir: PathBuf,
+}
+
+impl Workspace {
+    /// Path to the workspace configuration file
+    pub fn config_path(&self) -> PathBuf {
+        self.dir.join(CONFIG_FILE)
+    }
+
+    /// Load the workspace configuration
+    pub fn load_config(&self) -> Result<Config> {
+        Config::load_from(&self.config_path())
+    }
+
+    /// Default configuration for a new workspace rooted at `root`
+    fn default_config(&self, root: PathBuf) -> Config {
+        Config {
+            root,
+            db_path: self.dir.join("db"),
+            cache_dir: self.dir.join("cache"),
+            ..Config::default()
+        }
+    }
+}
+
+/// Manages named workspaces
+pub struct WorkspaceManager {
+    base_dir: PathBuf,
+}
+
+impl WorkspaceManager {
+    /// Create a manager using the default workspaces directory
+    pub fn new() -> Self {
+        Self::with_base_dir(DEFAULT_WORKSPACES_DIR)
+    }
+
+    /// Create a manager for a custom workspaces directory
+    pub fn with_base_dir(base_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            base_dir: base_dir.into(),
+        }
+    }
+
+    /// Directory holding all workspaces
+    pub fn base_dir(&self) -> &Path {
+        &self.base_dir
+    }
+
+    /// Get an existing workspace
+    pub fn get(&self, name: &str) -> Result<Workspace> {
+        validate_name(name)?;
+        let workspace = self.workspace(name);
+        if !workspace.config_path().exists() {
+            return Err(Error::WorkspaceNotFound(name.to_string()));
+        }
+        Ok(workspace)
+    }
+
+    /// List workspaces, sorted by name
+    pub fn list(&self) -> Result<Vec<Workspace>> {
+        let mut workspaces = Vec::new();
+        if !self.base_dir.exists() {
+            return Ok(workspaces);
+        }
+
+        for entry in std::fs::read_dir(&self.base_dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let workspace = self.workspace(&name);
+            if workspace.config_path().exists() {
+                workspaces.push(workspace);
+            }
+        }
+
+        workspaces.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(workspaces)
+    }
+
+    /// Create a new workspace
+    ///
+    /// The sysroot defaults to `<workspace>/root` unless `root` is given.
+    pub fn create(&self, name: &str, root: Option<PathBuf>) -> Result<Workspace> {
+        validate_name(name)?;
+        let workspace = self.workspace(name);
+        if workspace.config_path().exists() {
+            return Err(Error::WorkspaceAlreadyExists(name.to_string()));
+        }
+
+        let root = root.unwrap_or_else(|| workspace.dir.join("root"));
+        let config = workspace.default_config(root);
+
+        std::fs::create_dir_all(&workspace.dir)?;
+        std::fs::create_dir_all(&config.root)?;
+        std::fs::create_dir_all(&config.db_path)?;
+        std::fs::create_dir_all(&config.cache_dir)?;
+        config.save_to(&workspace.config_path())?;
+
+        Ok(workspace)
+    }
+
+    /// Remove a workspace and everything under its directory
+    ///
+    /// A sysroot outside the workspace directory is left in place.
+    pub fn remove(&self, name: &str) -> Result<()> {
+        let workspace = self.get(name)?;
+        std::fs::remove_dir_all(&workspace.dir)?;
+        Ok(())
+    }
+
+    fn workspace(&self, name: &str) -> Workspace {
+        Workspace {
+            name: name.to_string(),
+            dir: self.base_dir.join(name),
+        }
+    }
+}
+
+impl Default for WorkspaceManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Workspace names are restricted so they are always a single path component
+fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidWorkspaceName(name.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_list_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = WorkspaceManager::with_base_dir(dir.path());
+
+        let ws = manager.create("embedded-arm", None).unwrap();
+        let config = ws.load_config().unwrap();
+        assert_eq!(config.root, ws.dir.join("root"));
+        assert_eq!(config.db_path, ws.dir.join("db"));
+        assert!(config.root.is_dir());
+
+        manager.create("host", None).unwrap();
+        assert!(matches!(
+            manager.create("host", None),
+            Err(Error::WorkspaceAlreadyExists(_))
+        ));
+
+        let names: Vec<String> = manager
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|w| w.name)
+            .collect();
+        assert_eq!(names, vec!["embedded-arm", "host"]);
+
+        manager.remove("host").unwrap();
+        assert!(matches!(
+            manager.get("host"),
+            Err(Error::WorkspaceNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = WorkspaceManager::with_base_dir(dir.path());
+
+        for name in ["", "..", "../etc", "a/b", ".hidden"] {
+            assert!(matches!(
+                manager.create(name, None),
+                Err(Error::InvalidWorkspaceName(_))
+            ));
+        }
+    }
+}
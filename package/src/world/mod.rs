@@ -0,0 +1,287 @@
+//! World file management and hygiene
+//!
+//! The world file (`/var/lib/portage/world`) lists packages the user asked
+//! for explicitly. Over time it accumulates entries that are redundant,
+//! no longer available or never installed; this module finds them.
+
+use crate::resolver::ReachabilityGraph;
+use crate::{InstalledPackage, PackageId, Result};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+/// World file location relative to the install root
+pub const WORLD_FILE: &str = "var/lib/portage/world";
+
+/// The world file
+#[derive(Debug, Clone)]
+pub struct WorldFile {
+    path: PathBuf,
+    entries: BTreeSet<String>,
+}
+
+impl WorldFile {
+    /// Load the world file for a root (empty if it does not exist)
+    pub fn load(root: &Path) -> Result<Self> {
+        Self::at(&root.join(WORLD_FILE))
+    }
+
+    /// Load the world file at a path, e.g. from
+    /// [`Layout::path`](crate::Layout::path) (empty if it does not exist)
+    pub fn at(path: &Path) -> Result<Self> {
+        let path = path.to_path_buf();
+        let mut entries = BTreeSet::new();
+
+        if path.exists() {
+            let content = std::fs::read_to_string(&path)?;
+            for line in content.lines() {
+                let line = line.trim();
+                if !line.is_empty() && !line.starts_with('#') {
+                    entries.insert(line.to_string());
+                }
+            }
+        }
+
+        Ok(Self { path, entries })
+    }
+
+    /// Entries in sorted order
+    pub fn entries(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter()
+    }
+
+    /// Add an entry
+    pub fn insert(&mut self, entry: impl Into<String>) -> bool {
+        self.entries.insert(entry.into())
+    }
+
+    /// Remove an entry
+    pub fn remove(&mut self, entry: &str) -> bool {
+        self.entries.remove(entry)
+    }
+
+    /// Write the world file back to disk
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut content = String::new();
+        for entry in &self.entries {
+            content.push_str(entry);
+            content.push('\n');
+        }
+
+        std::fs::write(&self.path, content)?;
+        Ok(())
+    }
+}
+
+/// Problem found with a world entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WorldIssueKind {
+    /// Entry is pulled in as a dependency of another world entry
+    Redundant { required_by: PackageId },
+    /// Entry is not provided by any repository
+    NotInRepos,
+    /// Entry is not installed
+    NotInstalled,
+    /// Entry is not a valid package atom
+    Invalid,
+}
+
+/// A world entry with a problem
+#[derive(Debug, Clone, Serialize)]
+pub struct WorldIssue {
+    /// The world entry as written
+    pub entry: String,
+    /// What is wrong with it
+    #[serde(flatten)]
+    pub kind: WorldIssueKind,
+}
+
+impl WorldIssue {
+    /// Short description of the issue
+    pub fn describe(&self) -> String {
+        match &self.kind {
+            WorldIssueKind::Redundant { required_by } => {
+                format!("redundant, already required by {}", required_by)
+            }
+            WorldIssueKind::NotInRepos => "not found in any repository".to_string(),
+            WorldIssueKind::NotInstalled => "not installed".to_string(),
+            WorldIssueKind::Invalid => "not a valid package atom".to_string(),
+        }
+    }
+}
+
+/// Analyze world entries for problems
+///
+/// Each entry is reported at most once; an entry that is missing from the
+/// repositories is not also reported as redundant.
+pub fn analyze_world<'a>(
+    entries: impl IntoIterator<Item = &'a String>,
+    installed: &[InstalledPackage],
+    available: &HashSet<PackageId>,
+    graph: &ReachabilityGraph,
+) -> Vec<WorldIssue> {
+    let entries: Vec<&String> = entries.into_iter().collect();
+    let installed: HashSet<&PackageId> = installed.iter().map(|p| &p.id).collect();
+    let mut roots: Vec<PackageId> = entries.iter().filter_map(|e| PackageId::parse(e)).collect();
+
+    let mut issues = Vec::new();
+    for entry in entries {
+        let kind = match PackageId::parse(entry) {
+            None => Some(WorldIssueKind::Invalid),
+            Some(id) if !available.contains(&id) => Some(WorldIssueKind::NotInRepos),
+            Some(id) if !installed.contains(&id) => Some(WorldIssueKind::NotInstalled),
+            Some(id) => {
+                let required_by = graph.required_by(&id, &roots);
+                if required_by.is_some() {
+                    // Don't let a dropped entry justify dropping another
+                    // (e.g. two entries that depend on each other)
+                    roots.retain(|r| *r != id);
+                }
+                required_by.map(|required_by| WorldIssueKind::Redundant { required_by })
+            }
+        };
+
+        if let Some(kind) = kind {
+            issues.push(WorldIssue {
+                entry: entry.clone(),
+                kind,
+            });
+        }
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Dependency, PackageInfo};
+
+    fn info(name: &str, deps: &[&str]) -> PackageInfo {
+        PackageInfo {
+            id: PackageId::new("app-misc", name),
+            version: semver::Version::new(1, 0, 0),
+            slot: "0".to_string(),
+            description: String::new(),
+            homepage: None,
+            license: "MIT".to_string(),
+            keywords: Vec::new(),
+            use_flags: Vec::new(),
+            dependencies: Vec::new(),
+            build_dependencies: Vec::new(),
+            runtime_dependencies: deps
+                .iter()
+                .map(|d| Dependency::new(PackageId::new("app-misc", *d)))
+                .collect(),
+            source_url: None,
+            source_hash: None,
+            buck_target: String::new(),
+            size: 0,
+            installed_size: 0,
+            required_use: String::new(),
+            blockers: Vec::new(),
+            any_of_dependencies: Vec::new(),
+            restrict: Vec::new(),
+        }
+    }
+
+    fn installed(name: &str) -> InstalledPackage {
+        InstalledPackage {
+            id: PackageId::new("app-misc", name),
+            name: name.to_string(),
+            version: semver::Version::new(1, 0, 0),
+            slot: "0".to_string(),
+            installed_at: chrono::Utc::now(),
+            use_flags: HashSet::new(),
+            files: Vec::new(),
+            size: 0,
+            build_time: false,
+            explicit: true,
+        }
+    }
+
+    #[test]
+    fn test_analyze_world() {
+        let packages = vec![info("app", &["lib"]), info("lib", &[]), info("tool", &[])];
+        let graph = ReachabilityGraph::new(&packages);
+        let available: HashSet<PackageId> = packages.iter().map(|p| p.id.clone()).collect();
+        let installed = vec![installed("app"), installed("lib")];
+
+        let entries: Vec<String> = [
+            "app-misc/app",
+            "app-misc/lib",
+            "app-misc/tool",
+            "app-misc/gone",
+            "bogus",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        let issues = analyze_world(&entries, &installed, &available, &graph);
+        let kind = |entry: &str| {
+            issues
+                .iter()
+                .find(|i| i.entry == entry)
+                .map(|i| i.kind.clone())
+        };
+
+        assert_eq!(kind("app-misc/app"), None);
+        assert_eq!(
+            kind("app-misc/lib"),
+            Some(WorldIssueKind::Redundant {
+                required_by: PackageId::new("app-misc", "app")
+            })
+        );
+        assert_eq!(kind("app-misc/tool"), Some(WorldIssueKind::NotInstalled));
+        assert_eq!(kind("app-misc/gone"), Some(WorldIssueKind::NotInRepos));
+        assert_eq!(kind("bogus"), Some(WorldIssueKind::Invalid));
+    }
+
+    #[test]
+    fn test_world_file_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut world = WorldFile::load(dir.path()).unwrap();
+        assert_eq!(world.entries().count(), 0);
+
+        world.insert("app-misc/b");
+        world.insert("app-misc/a");
+        world.save().unwrap();
+
+        let mut world = WorldFile::load(dir.path()).unwrap();
+        let entries: Vec<&String> = world.entries().collect();
+        assert_eq!(entries, vec!["app-misc/a", "app-misc/b"]);
+
+        assert!(world.remove("app-misc/a"));
+        world.save().unwrap();
+        assert_eq!(WorldFile::load(dir.path()).unwrap().entries().count(), 1);
+    }
+
+    #[test]
+    fn test_world_issue_json() {
+        let issue = WorldIssue {
+            entry: "app-misc/lib".to_string(),
+            kind: WorldIssueKind::Redundant {
+                required_by: PackageId::new("app-misc", "app"),
+            },
+        };
+        let value = serde_json::to_value(&issue).unwrap();
+        assert_eq!(value["entry"], "app-misc/lib");
+        assert_eq!(value["kind"], "redundant");
+        assert_eq!(value["required_by"]["name"], "app");
+
+        let issue = WorldIssue {
+            entry: "bogus".to_string(),
+            kind: WorldIssueKind::Invalid,
+        };
+        assert_eq!(
+            serde_json::to_value(&issue).unwrap(),
+            serde_json::json!({"entry": "bogus", "kind": "invalid"})
+        );
+    }
+}
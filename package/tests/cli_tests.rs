@@ -627,4 +627,31 @@ mod combined_flags {
         let output = run_buckos(&["-D", "-N", "--help"]);
         assert!(output.status.success());
     }
+
+    #[test]
+    fn test_color_choices() {
+        for when in ["auto", "always", "never"] {
+            let output = run_buckos(&["--color", when, "--help"]);
+            assert!(output.status.success());
+        }
+        let output = run_buckos(&["--color", "sometimes", "--help"]);
+        assert!(!output.status.success());
+    }
+
+    /// Generating the docs builds the whole command tree, which fails on
+    /// any conflicting options, e.g. a subcommand short flag shadowing a
+    /// global one
+    #[test]
+    fn test_gen_docs() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().to_str().unwrap();
+        let output = run_buckos(&["gen-docs", "--out", out]);
+        assert!(output.status.success());
+        for page in ["buckos.1", "buckos-install.1", "buckos-overlay-add.1"] {
+            assert!(dir.path().join(page).exists(), "{} missing", page);
+        }
+        let reference = std::fs::read_to_string(dir.path().join("buckos.md")).unwrap();
+        assert!(reference.contains("## `buckos install`"));
+        assert!(reference.contains("### `buckos overlay add`"));
+    }
 }
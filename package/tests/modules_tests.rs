@@ -33,6 +33,27 @@ fn create_test_config() -> (Config, TempDir) {
         accept_keywords: HashSet::new(),
         accept_license: "@FREE".to_string(),
         buck_config: Default::default(),
+        buck_daemon: Default::default(),
+        any_of_weights: Default::default(),
+        any_of_preferred: Vec::new(),
+        install_mask: Vec::new(),
+        config_protect: Default::default(),
+        doc_compression: Default::default(),
+        peer_distfiles: false,
+        fetch: Default::default(),
+        plugin_dir: temp_path.join("plugins"),
+        audit_syslog: false,
+        qa: Default::default(),
+        build_retry: Default::default(),
+        build_pressure: Default::default(),
+        live_pins: Default::default(),
+        signing: Default::default(),
+        verity: Default::default(),
+        compression: Default::default(),
+        layout: Default::default(),
+        notify: Default::default(),
+        theme: Default::default(),
+        resolver: Default::default(),
     };
 
     // Create necessary directories
@@ -72,6 +93,175 @@ mod cache_tests {
         let result = cache.clean_downloads();
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_parse_size() {
+        use buckos_package::cache::parse_size;
+        assert_eq!(parse_size("350K"), Some(350 * 1024));
+        assert_eq!(parse_size("2m"), Some(2 * 1024 * 1024));
+        assert_eq!(parse_size("1000"), Some(1000));
+        assert_eq!(parse_size("fast"), None);
+        assert_eq!(parse_size(""), None);
+    }
+
+    #[test]
+    fn test_retry_delay_backoff() {
+        use buckos_package::cache::FetchConfig;
+        let fetch = FetchConfig {
+            retry_delay_ms: 100,
+            max_retry_delay_ms: 1000,
+            ..Default::default()
+        };
+        assert_eq!(fetch.retry_delay(0).as_millis(), 100);
+        assert_eq!(fetch.retry_delay(2).as_millis(), 400);
+        assert_eq!(fetch.retry_delay(10).as_millis(), 1000);
+    }
+
+    #[test]
+    fn test_invalid_rate_limit_rejected() {
+        use buckos_package::cache::FetchConfig;
+        let (config, _temp_dir) = create_test_config();
+        let fetch = FetchConfig {
+            rate_limit: Some("lots".to_string()),
+            ..Default::default()
+        };
+        let cache = PackageCache::new(&config.cache_dir)
+            .unwrap()
+            .with_fetch_config(fetch);
+        assert!(cache.is_err());
+    }
+
+    /// Serve a directory with the built-in HTTP server on an ephemeral port
+    async fn serve_dir(root: std::path::PathBuf) -> std::net::SocketAddr {
+        use buckos_package::http::{self, Response};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut stream = tokio::io::BufReader::new(stream);
+                let Ok(Some(request)) = http::read_request(&mut stream).await else {
+                    continue;
+                };
+                let path = root.join(request.path.trim_start_matches('/'));
+                let response = match Response::file_for(&path, &request).await {
+                    Ok(response) => response,
+                    Err(_) => Response::not_found(),
+                };
+                let _ = http::write_response(stream.get_mut(), response, false).await;
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_download_resumes_partial() {
+        use buckos_package::cache::{compute_sha256, FetchConfig};
+
+        let (config, temp_dir) = create_test_config();
+        let served = temp_dir.path().join("served");
+        std::fs::create_dir_all(&served).unwrap();
+        let content: Vec<u8> = (0..4096u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(served.join("foo-1.0.tar.gz"), &content).unwrap();
+        let hash = compute_sha256(&served.join("foo-1.0.tar.gz")).unwrap();
+
+        let fetch = FetchConfig {
+            retry_delay_ms: 1,
+            resume_min_size: "0".to_string(),
+            ..Default::default()
+        };
+        let cache = PackageCache::new(&config.cache_dir)
+            .unwrap()
+            .with_fetch_config(fetch)
+            .unwrap();
+
+        // Half of the file is already on disk from an interrupted download
+        let partial = config.cache_dir.join("tmp/foo-1.0.tar.gz.partial");
+        std::fs::write(&partial, &content[..2048]).unwrap();
+
+        let addr = serve_dir(served).await;
+        let url = format!("http://{}/foo-1.0.tar.gz", addr);
+        let path = cache
+            .download(&url, "foo-1.0.tar.gz", Some(&hash))
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), content);
+        assert!(!partial.exists());
+    }
+
+    #[tokio::test]
+    async fn test_download_fails_over_to_mirror() {
+        use buckos_package::cache::FetchConfig;
+
+        let (config, temp_dir) = create_test_config();
+        let served = temp_dir.path().join("served");
+        std::fs::create_dir_all(&served).unwrap();
+        std::fs::write(served.join("bar-2.0.tar.gz"), b"bar").unwrap();
+        let hash = buckos_package::cache::compute_sha256(&served.join("bar-2.0.tar.gz")).unwrap();
+
+        let addr = serve_dir(served).await;
+        let fetch = FetchConfig {
+            mirrors: vec![format!("http://{}/", addr)],
+            retry_delay_ms: 1,
+            ..Default::default()
+        };
+        let cache = PackageCache::new(&config.cache_dir)
+            .unwrap()
+            .with_fetch_config(fetch)
+            .unwrap();
+
+        let missing = format!("http://{}/elsewhere/bar-2.0.tar.gz", addr);
+        let path = cache
+            .download(&missing, "bar-2.0.tar.gz", Some(&hash))
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"bar");
+
+        // The mirror that served it is credited and can be audited
+        let health = cache.mirror_health();
+        assert_eq!(health.len(), 1);
+        assert_eq!(health[0].1.successes, 1);
+
+        let audits = cache.audit_mirrors(5).await.unwrap();
+        assert_eq!(audits.len(), 1);
+        assert_eq!(audits[0].verified, vec!["bar-2.0.tar.gz".to_string()]);
+        assert!(audits[0].mismatched.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failover_does_not_resume_other_partial() {
+        use buckos_package::cache::FetchConfig;
+
+        let (config, temp_dir) = create_test_config();
+        let served = temp_dir.path().join("served");
+        std::fs::create_dir_all(&served).unwrap();
+        let content: Vec<u8> = (0..4096u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(served.join("baz-3.0.tar.gz"), &content).unwrap();
+
+        let addr = serve_dir(served).await;
+        let fetch = FetchConfig {
+            mirrors: vec![format!("http://{}/", addr)],
+            retry_delay_ms: 1,
+            resume_min_size: "0".to_string(),
+            ..Default::default()
+        };
+        let cache = PackageCache::new(&config.cache_dir)
+            .unwrap()
+            .with_fetch_config(fetch)
+            .unwrap();
+
+        // A partial left by another server, and no checksum to catch a splice
+        let partial = config.cache_dir.join("tmp/baz-3.0.tar.gz.partial");
+        std::fs::write(&partial, vec![0xff; 2048]).unwrap();
+
+        let missing = format!("http://{}/elsewhere/baz-3.0.tar.gz", addr);
+        let path = cache
+            .download(&missing, "baz-3.0.tar.gz", None)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), content);
+    }
 }
 
 mod config_path_tests {
@@ -295,6 +485,8 @@ mod package_build_meta_tests {
             installed_size: 200_000_000,
             required_use: String::new(),
             blockers: Vec::new(),
+            any_of_dependencies: Vec::new(),
+            restrict: Vec::new(),
         }
     }
 
@@ -384,6 +576,9 @@ mod resolution_tests {
             build_order: vec![],
             download_size: 0,
             install_size: 0,
+            any_of_choices: vec![],
+            from_host: vec![],
+            stats: Default::default(),
         };
 
         assert!(resolution.packages.is_empty());
@@ -44,6 +44,27 @@ fn create_test_config() -> (Config, TempDir) {
         accept_keywords: HashSet::new(),
         accept_license: "@FREE".to_string(),
         buck_config: Default::default(),
+        buck_daemon: Default::default(),
+        any_of_weights: Default::default(),
+        any_of_preferred: Vec::new(),
+        install_mask: Vec::new(),
+        config_protect: Default::default(),
+        doc_compression: Default::default(),
+        peer_distfiles: false,
+        fetch: Default::default(),
+        plugin_dir: temp_path.join("plugins"),
+        audit_syslog: false,
+        qa: Default::default(),
+        build_retry: Default::default(),
+        build_pressure: Default::default(),
+        live_pins: Default::default(),
+        signing: Default::default(),
+        verity: Default::default(),
+        compression: Default::default(),
+        layout: Default::default(),
+        notify: Default::default(),
+        theme: Default::default(),
+        resolver: Default::default(),
     };
 
     // Create necessary directories
@@ -226,6 +247,7 @@ mod build_options {
             release: true,
             buck_args: vec!["--show-output".to_string()],
             config_options: None,
+            cancellation: None,
         };
         assert_eq!(opts.jobs, Some(4));
         assert!(opts.release);
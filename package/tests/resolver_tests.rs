@@ -136,6 +136,9 @@ mod resolution_tests {
             build_order: vec![],
             download_size: 0,
             install_size: 0,
+            any_of_choices: vec![],
+            from_host: vec![],
+            stats: Default::default(),
         };
 
         assert!(resolution.packages.is_empty());
@@ -165,6 +168,8 @@ mod resolution_tests {
             installed_size: 50000,
             required_use: String::new(),
             blockers: Vec::new(),
+            any_of_dependencies: Vec::new(),
+            restrict: Vec::new(),
         };
 
         let resolution = InternalResolution {
@@ -172,6 +177,9 @@ mod resolution_tests {
             build_order: vec![0],
             download_size: 10000,
             install_size: 50000,
+            any_of_choices: vec![],
+            from_host: vec![],
+            stats: Default::default(),
         };
 
         assert_eq!(resolution.packages.len(), 1);